@@ -4,4 +4,6 @@ export interface AuthProviderTemplate {
     id: string;
     name: string;
     updated: number;
+    logo_url?: string;
+    available: boolean;
 }
@@ -1,6 +1,8 @@
 import type { CodeChallengeMethod } from '$api/types/authorize.ts';
 
-export type AuthProviderType = 'auto' | 'custom' | 'github' | 'google' | 'oidc';
+export type AuthProviderType = 'apple' | 'auto' | 'custom' | 'github' | 'google' | 'oidc';
+
+export type ProviderSyncPolicy = 'always' | 'on_empty' | 'never';
 
 export interface ProviderRequest {
     /// Validation: PATTERN_CLIENT_NAME
@@ -14,19 +16,32 @@ export interface ProviderRequest {
     authorization_endpoint: string;
     /// Validation: PATTERN_URI
     token_endpoint: string;
-    /// Validation: PATTERN_URI
+    /// Validation: PATTERN_URI, can only be empty for `apple`
     userinfo_endpoint: string;
+    /// Validation: PATTERN_URI
+    end_session_endpoint?: string;
 
     use_pkce: boolean;
     client_secret_basic: boolean;
     client_secret_post: boolean;
     auto_onboarding: boolean;
     auto_link: boolean;
+    auto_upstream_logout?: boolean;
 
     /// Validation: PATTERN_URI
     client_id: string;
     /// Validation: max 256
     client_secret?: string;
+    /// Validation: max 16384, must be given together with `client_key_pem`
+    client_cert_pem?: string;
+    /// Validation: max 16384, the current key is kept, if only `client_cert_pem` is given
+    client_key_pem?: string;
+    /// Validation: PATTERN_ALNUM, max 32, mandatory for `apple`
+    apple_team_id?: string;
+    /// Validation: PATTERN_ALNUM, max 32, mandatory for `apple`
+    apple_key_id?: string;
+    /// Validation: max 4096, the current key is kept during an update, if not given
+    apple_private_key?: string;
     /// Validation: PATTERN_SCOPE_SPACE
     scope: string;
 
@@ -38,6 +53,36 @@ export interface ProviderRequest {
     mfa_claim_path?: string;
     /// Validation: PATTERN_URI
     mfa_claim_value?: string;
+
+    /// Validation: max 256, valid JsonPath
+    claim_path_email?: string;
+    /// Validation: max 256, valid JsonPath
+    claim_path_given_name?: string;
+    /// Validation: max 256, valid JsonPath
+    claim_path_family_name?: string;
+    /// Validation: max 256, valid JsonPath
+    claim_path_groups?: string;
+    /// Validation: max 1024, comma-separated domains
+    restrict_hd?: string;
+    /// Validation: max 256, PATTERN_URI with exactly one `{tid}`
+    issuer_template?: string;
+    /// Validation: max 1024, comma-separated `[a-zA-Z0-9-]` tenant IDs
+    allowed_tenants?: string;
+    sync_policy?: ProviderSyncPolicy;
+    /// Validation: max 1024, comma-separated domains
+    allowed_email_domains?: string;
+    /// Validation: max 1024, comma-separated domains
+    denied_email_domains?: string;
+    use_userinfo?: boolean;
+    auto_refresh?: boolean;
+    upstream_refresh?: boolean;
+    require_email_verified?: boolean;
+    sort_order?: number;
+
+    /// Validation: max 16 entries, keys `[a-zA-Z0-9-_.]{1,64}`, values max 256
+    extra_auth_params?: Record<string, string>;
+    /// Validation: `60 <= callback_timeout_secs <= 3600`
+    callback_timeout_secs?: number;
 }
 
 export interface ProviderCallbackRequest {
@@ -100,18 +145,42 @@ export interface ProviderResponse {
     authorization_endpoint: string;
     token_endpoint: string;
     userinfo_endpoint: string;
+    end_session_endpoint?: string;
     client_id: string;
     client_secret?: string;
+    client_cert_pem?: string;
+    client_key_set: boolean;
+    apple_team_id?: string;
+    apple_key_id?: string;
+    apple_private_key_set: boolean;
     scope: string;
     admin_claim_path?: string;
     admin_claim_value?: string;
     mfa_claim_path?: string;
     mfa_claim_value?: string;
+    claim_path_email?: string;
+    claim_path_given_name?: string;
+    claim_path_family_name?: string;
+    claim_path_groups?: string;
+    restrict_hd?: string;
+    issuer_template?: string;
+    allowed_tenants?: string;
+    sync_policy: ProviderSyncPolicy;
+    allowed_email_domains?: string;
+    denied_email_domains?: string;
+    use_userinfo?: boolean;
+    auto_refresh: boolean;
+    upstream_refresh: boolean;
+    require_email_verified: boolean;
+    sort_order: number;
     use_pkce: boolean;
     client_secret_basic: boolean;
     client_secret_post: boolean;
     auto_onboarding: boolean;
     auto_link: boolean;
+    auto_upstream_logout: boolean;
+    extra_auth_params?: Record<string, string>;
+    callback_timeout_secs?: number;
 }
 
 export interface ProviderLinkedUserResponse {
@@ -124,6 +193,7 @@ export interface ProviderLookupResponse {
     authorization_endpoint: string;
     token_endpoint: string;
     userinfo_endpoint: string;
+    end_session_endpoint?: string;
     scope: string;
     use_pkce: boolean;
     client_secret_basic: boolean;
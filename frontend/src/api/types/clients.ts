@@ -9,6 +9,8 @@ export type AuthFlow =
     | 'refresh_token'
     | 'urn:ietf:params:oauth:grant-type:device_code';
 export type CodeChallengeMethod = 'plain' | 'S256';
+export type ClientType = 'web' | 'native';
+export type ResponseType = 'code' | 'none';
 
 export interface NewClientRequest {
     /// Validation: PATTERN_CLIENT_ID
@@ -20,6 +22,8 @@ export interface NewClientRequest {
     redirect_uris: string[];
     /// Validation: PATTERN_URI
     post_logout_redirect_uris?: string[];
+    /// `native` clients must be public and always use PKCE with S256
+    client_type?: ClientType;
 }
 
 export interface ScimClientRequestResponse {
@@ -32,6 +36,57 @@ export interface ScimClientRequestResponse {
     group_sync_prefix?: string;
 }
 
+export interface ClaimsWebhookRequest {
+    /// Must be `https://` outside of `dev_mode`
+    /// Validation: PATTERN_URI
+    uri: string;
+    /// Write-only, keeps the current secret if not given.
+    /// Validation: `32 <= secret.length <= 128`
+    secret?: string;
+    /// Validation: `100 <= timeout_ms <= 5000`
+    timeout_ms: number;
+    /// Validation: `0 <= cache_ttl <= 900`
+    cache_ttl: number;
+    /// Validation: `^[a-zA-Z0-9-_.:/]{2,128}$`
+    allowed_claims: string[];
+    fail_open: boolean;
+}
+
+export interface ClaimsWebhookResponse {
+    uri: string;
+    timeout_ms: number;
+    cache_ttl: number;
+    allowed_claims: string[];
+    fail_open: boolean;
+}
+
+export type ClaimTarget = 'id_token' | 'access_token' | 'userinfo';
+
+export type ClaimGroupsFormat = 'flat' | 'path';
+
+export interface ClientClaimsEmission {
+    /// Validation: `^[a-zA-Z0-9-_.:/]{2,128}$`
+    roles_claim?: string;
+    /// Validation: `^[a-zA-Z0-9-_.:/]{2,128}$`
+    groups_claim?: string;
+    groups_format?: ClaimGroupsFormat;
+    roles_in_groups?: boolean;
+    roles_targets?: ClaimTarget[];
+    groups_targets?: ClaimTarget[];
+    /// Validation: `1 <= groups_max <= 65535`
+    groups_max?: number;
+    groups_overflow?: boolean;
+}
+
+export type UserAgentBinding = 'off' | 'family' | 'family_major';
+
+export type IpBinding = 'off' | 'wide' | 'narrow';
+
+export interface ClientRefreshTokenBinding {
+    user_agent?: UserAgentBinding;
+    ip?: IpBinding;
+}
+
 export interface UpdateClientRequest {
     /// Validation: PATTERN_CLIENT_NAME
     name?: string;
@@ -74,7 +129,20 @@ export interface UpdateClientRequest {
     /// Audiences always added to this client's tokens, independent of any request.
     /// Validation: PATTERN_URI
     default_aud?: string[];
+    /// Users need at least one of these roles or one of the `allowed_groups`.
+    /// Empty for both means unrestricted.
+    /// Validation: PATTERN_ROLE_SCOPE
+    allowed_roles?: string[];
+    /// Validation: PATTERN_GROUP
+    allowed_groups?: string[];
+    /// Missing keeps the current value, must not be empty.
+    response_types?: ResponseType[];
+    claims_emission?: ClientClaimsEmission;
+    /// Only allowed for public clients
+    refresh_token_binding?: ClientRefreshTokenBinding;
+    client_type?: ClientType;
     scim?: ScimClientRequestResponse;
+    claims_webhook?: ClaimsWebhookRequest;
 }
 
 export interface ClientSecretRequest {
@@ -107,7 +175,14 @@ export interface ClientResponse {
     claims_at_root: boolean;
     allowed_resources?: string[];
     default_aud?: string[];
+    allowed_roles?: string[];
+    allowed_groups?: string[];
+    response_types: ResponseType[];
+    claims_emission?: ClientClaimsEmission;
+    refresh_token_binding?: ClientRefreshTokenBinding;
+    client_type: ClientType;
     scim?: ScimClientRequestResponse;
+    claims_webhook?: ClaimsWebhookResponse;
 }
 
 export interface ClientSecretResponse {
@@ -1,4 +1,5 @@
-export type Language = 'en' | 'de' | 'fr' | 'ko' | 'nb' | 'nl' | 'ru' | 'uk' | 'zh';
+// Custom languages loaded by the backend are added as their lowercase BCP 47 tag.
+export type Language = 'en' | 'de' | 'fr' | 'ko' | 'nb' | 'nl' | 'ru' | 'uk' | 'zh' | (string & {});
 
 export interface I18nConfigResponse {
     common: Language[];
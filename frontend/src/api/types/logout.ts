@@ -2,4 +2,10 @@ export interface LogoutParams {
     post_logout_redirect_uri?: string | null;
     id_token_hint?: string | null;
     state?: string | null;
+    upstream_logout?: boolean;
+}
+
+export interface UpstreamLogoutTpl {
+    provider_name: string;
+    auto: boolean;
 }
@@ -102,6 +102,14 @@ export type UserAccountTypeResponse =
     | 'federated_passkey'
     | 'federated_password';
 
+export interface UserClientResponse {
+    id: string;
+    name?: string;
+    client_uri?: string;
+    /// Append as `?updated=` to `/auth/v1/clients/{id}/logo`
+    logo_updated?: number;
+}
+
 export interface UserResponseSimple {
     id: string;
     email: string;
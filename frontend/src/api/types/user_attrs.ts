@@ -13,9 +13,12 @@ export interface UserAttrConfigRequest {
     /// Validation: PATTERN_ATTR_DESC
     desc?: string;
     default_value?: JsonValue;
-    /// Currently ignored - will be implemented in a future version
+    /// Values synced from an upstream claim must match this type.
     typ?: UserAttrConfigTyp;
     user_editable?: boolean;
+    /// If set, the value is synced from this top-level upstream claim during each login via an
+    /// upstream auth provider. A differing claim value overwrites manual edits.
+    auto_populate_from_claim?: string;
 }
 
 export interface UserAttrConfigValueResponse {
@@ -25,6 +28,7 @@ export interface UserAttrConfigValueResponse {
     /// Currently ignored - will be implemented in a future version
     typ?: UserAttrConfigTyp;
     user_editable?: boolean;
+    auto_populate_from_claim?: string;
 }
 
 export interface UserAttrConfigResponse {
@@ -34,6 +38,9 @@ export interface UserAttrConfigResponse {
 export interface UserAttrValueResponse {
     key: string;
     value: JsonValue;
+    /// Unix timestamp of the last sync from an upstream claim. Not set if the value has been set
+    /// manually.
+    last_synced_from_claim_at?: number;
 }
 
 export interface UserAttrValuesResponse {
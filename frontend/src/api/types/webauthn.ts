@@ -5,6 +5,8 @@ export interface PasskeyResponse {
     /// Unix timestamp in seconds
     last_used: number;
     user_verified?: boolean;
+    /// Unix timestamp in seconds
+    counter_anomaly?: number;
 }
 
 export interface WebauthnDeleteRequest {
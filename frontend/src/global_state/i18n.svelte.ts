@@ -9,6 +9,8 @@ import { I18nRu } from '../i18n/common/ru';
 import { I18nNl } from '../i18n/common/nl';
 import { I18nUk } from '../i18n/common/uk';
 import { I18nZh } from '../i18n/common/zh';
+import { TPL_I18N_OVERRIDES } from '$utils/constants';
+import { fetchGet } from '$api/fetch';
 
 // This hack makes typescript happy and is fine as long as
 // we `initI18n()` as the very first thing in +layout
@@ -44,5 +46,76 @@ const i18nMap: Record<string, I18n> = {
 
 export function initI18n() {
     let lang = useLang();
-    _i18n = i18nMap[lang] ?? I18nEn;
+    _i18n = applyOverrides(i18nMap[lang] ?? I18nEn);
+}
+
+/**
+ * `true` if the current language is not built-in and must be loaded with `initI18nCustom()`.
+ */
+export function isI18nCustom(): boolean {
+    return !i18nMap[useLang()];
+}
+
+/**
+ * Loads the translation for a custom language from the backend. Each missing key falls back to
+ * the built-in English value, and admin defined overrides are still applied on top.
+ */
+export async function initI18nCustom() {
+    let lang = useLang();
+    let res = await fetchGet<Record<string, string>>(
+        `/auth/v1/i18n/languages/${lang}`,
+        'json',
+        'noRedirect',
+    );
+    if (res.error) {
+        console.error('Cannot load the custom language', lang, res.error);
+        return;
+    }
+
+    let i18n = applyPaths(structuredClone(I18nEn), res.body ?? {});
+    i18n.lang = lang;
+    _i18n = applyOverrides(i18n);
+}
+
+/**
+ * Merges admin defined translation overrides over the built-in values. The backend renders them
+ * into the page as `{ "section.key": "value" }` for the current language. Missing overrides
+ * simply keep the built-in value.
+ */
+function applyOverrides(i18n: I18n): I18n {
+    if (typeof document === 'undefined') {
+        return i18n;
+    }
+    let tpl = document.getElementById(TPL_I18N_OVERRIDES);
+    if (!tpl?.innerHTML) {
+        return i18n;
+    }
+
+    let overrides: Record<string, string>;
+    try {
+        overrides = JSON.parse(tpl.innerHTML);
+    } catch (e) {
+        console.error('Cannot parse i18n overrides', e);
+        return i18n;
+    }
+
+    return applyPaths(structuredClone(i18n), overrides);
+}
+
+/**
+ * Sets each `{ "section.key": "value" }` on the given `i18n`. Unknown paths are ignored.
+ */
+function applyPaths(res: I18n, values: Record<string, string>): I18n {
+    for (let [path, value] of Object.entries(values)) {
+        let keys = path.split('.');
+        let last = keys.pop();
+        let target: any = res;
+        for (let key of keys) {
+            target = target?.[key];
+        }
+        if (last && target && typeof target === 'object') {
+            target[last] = value;
+        }
+    }
+    return res;
 }
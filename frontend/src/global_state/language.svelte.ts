@@ -2,7 +2,9 @@ let _lang = $state('');
 
 export function initLang() {
     if (typeof document !== 'undefined') {
-        _lang = document.documentElement.lang.slice(0, 2).toLowerCase();
+        let lang = document.documentElement.lang.toLowerCase();
+        // built-in languages always use their short version, custom ones the full tag
+        _lang = lang === 'zhhans' ? 'zh' : lang;
     } else {
         _lang = 'en';
     }
@@ -17,7 +17,9 @@ export let I18nAdminDe: I18nAdmin = {
         limitedValidity: 'Begrenzte Gültigkeit',
     },
     attrs: {
+        autoPopulateFromClaim: 'Von Upstream Claim synchronisieren',
         delete1: 'Soll dieses Attribut wirklich gelöscht werden?',
+        deleteValues: '{{ count }} Benutzer haben einen Wert für dieses Attribut, welcher ebenfalls gelöscht wird.',
         defaultValue: 'Standard Wert',
         desc: 'Beschreibung',
         makeEditable: 'Editierbar machen',
@@ -40,9 +42,12 @@ export let I18nAdminDe: I18nAdmin = {
         size: 'Größe',
     },
     clients: {
+        allowedGroups: 'Erlaubte Gruppen',
         allowedResources: 'Erlaubte Ressourcen',
+        allowedRoles: 'Erlaubte Rollen',
         defaultAud: 'Standard-Audiences',
         descAllowedResources: `Optionale RFC 8707 Resource Indicators, die dieser Client anfordern darf. Eine leere Liste lehnt jeden 'resource'-Parameter mit 'invalid_target' ab.`,
+        descAllowedRolesGroups: `Wenn Rollen oder Gruppen gesetzt sind, können nur Benutzer mit mindestens einer davon diesen Client nutzen. Beide leer lassen, um alle Benutzer zuzulassen.`,
         descDefaultAud: `Audiences, die immer zu den Tokens dieses Clients hinzugefügt werden, unabhängig von einem 'resource'-Parameter.`,
         backchannelLogout:
             'Sollte dieser client {{ OIDC_BCL }} unterstützen, kann die URI hier angegeben werden.',
@@ -87,11 +92,14 @@ export let I18nAdminDe: I18nAdmin = {
             die keine korrekte Challenge bereit stellen.`,
         descUri: `Es können beliebig viele Redirect URIs angegeben werden. Am Ende einer Jeden wird 
             optional <code>*</code> als Wildcard akzeptiert.`,
+        descResponseTypes: `Nur die ausgewählten Response Types werden am Authorize Endpunkt akzeptiert. 'none' prüft nur eine bestehende Session, ohne einen Code auszustellen.`,
         errConfidentialPKCE: `Der Client muss entweder vertraulich sein oder mindestens eine PKCE
             Challenge aktiviert haben.`,
+        errResponseTypes: `Mindestens ein Response Type muss ausgewählt sein.`,
         forceMfa: 'MFA Erzwingen',
         groupLoginPrefix: 'Login Gruppen Prefix',
         name: 'Client Name',
+        responseTypes: 'Response Types',
         scim: {
             baseUri: `Die SCIM base URI muss jene sein, von der Sub-Routen wie 
                 <code>{base_uri}/Users/{id}</base_uri></code> korrekt abgeleitet werden können.`,
@@ -411,6 +419,16 @@ export let I18nAdminDe: I18nAdmin = {
     providers: {
         config: {
             allowInsecureTls: 'Erlaube unsicheres TLS',
+            autoUpstreamLogout: 'Upstream-Logout automatisch',
+            autoRefresh: 'Metadaten automatisch aktualisieren',
+            upstreamRefresh: 'Benutzer über Upstream Refresh Tokens synchronisieren',
+            upstreamRefreshDesc: `Speichert das Refresh Token vom Upstream beim Login und nutzt es regelmäßig, um den Benutzer zu synchronisieren. Widerruft der Upstream es, wird der Benutzer abgemeldet. Die meisten Provider stellen Refresh Tokens nur mit dem offline_access Scope aus.`,
+            mtlsCert: 'mTLS Client-Zertifikat (PEM)',
+            mtlsKey: 'mTLS Private Key (PEM)',
+            mtlsKeySet: 'Ein Key ist gesetzt - leer lassen, um ihn zu behalten',
+            appleKeyId: 'Apple Key ID',
+            appleKey: 'Apple Private Key (.p8)',
+            appleTeamId: 'Apple Team ID',
             autoLink: 'Auto-Link Benutzer',
             autoLinkDesc1: `Wenn Auto-Link Benutzer aktiviert ist, wird beim Login über diesen Provider automatisch
                 ein eventuell existierender, nicht-verlinkter Benutzer mit diesem Provider verbunden.`,
@@ -421,8 +439,8 @@ export let I18nAdminDe: I18nAdmin = {
             custRootCa: 'Eigenes Root CA PEM',
             descAuthMethod: `Die Authentication Method, welche für den <code>/token</code> Endpunkt genutzt werden soll.
                 Die meisten Provider sollten mit <code>basic</code> funktionieren, manche jedoch nur mit 
-                <code>post</code>. In seltenen Fällen müssen beide Optionen aktiviert werden, auch wenn es gegen das
-                RFC verstößt.`,
+                <code>post</code>. Es kann immer nur eine Methode genutzt werden. Ohne beide ist der Client public und
+                nutzt nur PKCE.`,
             descClientId: 'Client ID, vom Auth Provider vorgegeben.',
             descClientName: 'Client Name der auf der Rauthy Login Form angezeigt werden soll.',
             descClientSecret: `Client Secret, vom Auth Provider vorgegeben.
@@ -445,7 +463,31 @@ export let I18nAdminDe: I18nAdmin = {
             lookup: 'Prüfen',
             pathAdminClaim: 'Admin Claim Pfad',
             pathMfaClaim: 'MFA Claim Pfad',
+            pathEmailClaim: 'E-Mail Claim Pfad',
+            pathGivenNameClaim: 'Vorname Claim Pfad',
+            pathFamilyNameClaim: 'Nachname Claim Pfad',
+            pathGroupsClaim: 'Gruppen Claim Pfad',
             rootPemCert: 'Root PEM Zertifikat',
+            mapClaims: `Auth Provider, welche nicht die Standard-Claims nutzen, können über eigene Pfade gemappt werden. Ist ein Pfad leer oder wird nicht gefunden, wird der Standard-Claim genutzt.`,
+            restrictHd: 'Erlaubte Google Workspace Domains',
+            restrictHdDesc: `Nur Google Workspace Accounts aus diesen Domains akzeptieren. Mehrere Domains können kommagetrennt angegeben werden.`,
+            sortOrder: 'Reihenfolge auf der Login Seite',
+            issuerTemplate: 'Issuer Template',
+            allowedTenants: 'Erlaubte Tenant IDs',
+            tenantsDesc: `Für Multi-Tenant Provider wie Azure, deren ID Tokens den Tenant im Issuer enthalten. {tid} wird vor der Validierung des Issuers durch den tid Claim ersetzt. Mehrere Tenant IDs können kommagetrennt angegeben werden.`,
+            syncAlways: 'Immer',
+            syncDesc: `Legt fest, wie E-Mail, Namen und Benutzerwerte bestehender Benutzer beim Login aus den Upstream Claims aktualisiert werden. Neue Benutzer erhalten immer alle Werte.`,
+            syncNever: 'Nie',
+            syncOnEmpty: 'Nur leere Werte',
+            emailDomainsAllowed: 'Erlaubte E-Mail Domains',
+            emailDomainsDenied: 'Verbotene E-Mail Domains',
+            emailDomainsDesc: `Logins über diesen Provider anhand der E-Mail Domain einschränken. Sind erlaubte Domains angegeben, werden alle anderen abgelehnt. Verbotene Domains werden immer abgelehnt, auch für bereits existierende Benutzer. Domains müssen exakt übereinstimmen und mehrere können kommagetrennt angegeben werden.`,
+            requireEmailVerified: 'Verifizierte E-Mail erforderlich',
+            requireEmailVerifiedDesc: `Logins ablehnen, solange der Provider nicht <code>email_verified: true</code> sendet. Ansonsten erhalten neue Benutzer mit einer unverifizierten Adresse eine Bestätigungs E-Mail.`,
+            userinfoDesc: `Provider mit knappen ID Tokens benötigen eventuell den Userinfo Endpunkt, um Claims wie die E-Mail zu erhalten. Standardmäßig wird er nur abgefragt, wenn die E-Mail im ID Token fehlt. Claims aus dem ID Token haben immer Vorrang.`,
+            userinfoAuto: 'Userinfo: bei Bedarf',
+            userinfoAlways: 'Userinfo: immer',
+            userinfoNever: 'Userinfo: nie',
             mapMfa: `Sollte der Auth Provider in ID Claim bereit stellen, welches anzeigt, ob eine Art 2FA oder MFA
                 beim Login verwandt wurde, so kann Rauthy diesen Werten extrahieren und entsprechend weitergeben.`,
             mapUser: `Es kann beim Login automatisch ein Nutzer mit der Rauthy Admin Rolle verlinkt werden, in
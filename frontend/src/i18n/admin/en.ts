@@ -16,7 +16,9 @@ export let I18nAdminEn: I18nAdmin = {
         limitedValidity: 'Limited Validity',
     },
     attrs: {
+        autoPopulateFromClaim: 'Sync from upstream claim',
         delete1: 'Are you sure you want to delete this attribute?',
+        deleteValues: '{{ count }} users have a value for this attribute, which will be deleted as well.',
         defaultValue: 'Default Value',
         desc: 'Description',
         makeEditable: 'Make Editable',
@@ -37,9 +39,12 @@ export let I18nAdminEn: I18nAdmin = {
         size: 'Size',
     },
     clients: {
+        allowedGroups: 'Allowed Groups',
         allowedResources: 'Allowed Resources',
+        allowedRoles: 'Allowed Roles',
         defaultAud: 'Default Audiences',
         descAllowedResources: `Optional RFC 8707 resource indicators this client may request. An empty list rejects any 'resource' request parameter with 'invalid_target'.`,
+        descAllowedRolesGroups: `If any roles or groups are set, only users with at least one of them can use this client. Leave both empty to allow all users.`,
         descDefaultAud: `Audiences that are always added to this client's tokens, independent of any 'resource' request parameter.`,
         backchannelLogout: 'If this client supports {{ OIDC_BCL }}, you can provide the URI here.',
         branding: {
@@ -81,11 +86,14 @@ export let I18nAdminEn: I18nAdmin = {
             rejects login request that do not contain a valid challenge.`,
         descUri: `You can provide as many redirect URIs as you like. At the end of each, you can use 
             <code>*</code> as a Wildcard.`,
+        descResponseTypes: `Only the selected response types are accepted at the authorize endpoint. 'none' only checks for an existing session without issuing a code.`,
         errConfidentialPKCE: `The client must either be confidential or have at least one PKCE
             challenge activated.`,
+        errResponseTypes: `At least one response type must be selected.`,
         forceMfa: 'Force MFA',
         groupLoginPrefix: 'Login Group Prefix',
         name: 'Client Name',
+        responseTypes: 'Response Types',
         scim: {
             baseUri: `The SCIM base URI is the one from which the sub routes like 
                 <code>{base_uri}/Users/{id}</base_uri></code> can be derived correctly.`,
@@ -394,6 +402,16 @@ export let I18nAdminEn: I18nAdmin = {
     providers: {
         config: {
             allowInsecureTls: 'Allow insecure TLS',
+            autoUpstreamLogout: 'Automatic Upstream Logout',
+            autoRefresh: 'Refresh Metadata automatically',
+            upstreamRefresh: 'Sync users via upstream refresh tokens',
+            upstreamRefreshDesc: `Stores the refresh token from the upstream during login and uses it periodically to sync the user. If the upstream revokes it, the user is logged out. Most providers only issue refresh tokens with the offline_access scope.`,
+            mtlsCert: 'mTLS Client Certificate (PEM)',
+            mtlsKey: 'mTLS Private Key (PEM)',
+            mtlsKeySet: 'A key is set - leave empty to keep it',
+            appleKeyId: 'Apple Key ID',
+            appleKey: 'Apple Private Key (.p8)',
+            appleTeamId: 'Apple Team ID',
             autoLink: 'Auto-Link User',
             autoLinkDesc1: `If Auto-Link User is activated, the login via this provider will automatically link a
                 possibly existing, non-linked user to this provider.`,
@@ -404,7 +422,7 @@ export let I18nAdminEn: I18nAdmin = {
             custRootCa: 'Custom Root CA PEM',
             descAuthMethod: `The authentication method to use on the <code>/token</code> endpoint.<br>
                 Most providers should work with <code>basic</code>, some only with <code>post</code>.
-                In rare situations, you need both, while it can lead to errors with others.`,
+                Only a single method can be used. Without any, the client is public and relies on PKCE only.`,
             descClientId: 'Client ID given by the auth provider.',
             descClientName: 'Client name that should be shown on the Rauthy login page.',
             descClientSecret: `Client Secret given by the auth provider.
@@ -426,7 +444,31 @@ export let I18nAdminEn: I18nAdmin = {
             lookup: 'Lookup',
             pathAdminClaim: 'Admin Claim Path',
             pathMfaClaim: 'MFA Claim Path',
+            pathEmailClaim: 'E-Mail Claim Path',
+            pathGivenNameClaim: 'Given Name Claim Path',
+            pathFamilyNameClaim: 'Family Name Claim Path',
+            pathGroupsClaim: 'Groups Claim Path',
             rootPemCert: 'Root PEM Certificate',
+            mapClaims: `Providers which do not use the standard claims can be mapped with custom paths. Each value falls back to the standard claim, if the path is empty or not found.`,
+            restrictHd: 'Allowed Google Workspace Domains',
+            restrictHdDesc: `Only accept Google Workspace accounts from these domains. Multiple domains can be given comma-separated.`,
+            sortOrder: 'Order on the Login Page',
+            issuerTemplate: 'Issuer Template',
+            allowedTenants: 'Allowed Tenant IDs',
+            tenantsDesc: `For multi-tenant providers like Azure, whose ID tokens contain the tenant in the issuer. {tid} is replaced with the tid claim before the issuer is validated. Multiple tenant IDs can be given comma-separated.`,
+            syncAlways: 'Always',
+            syncDesc: `Defines how the E-Mail, names and user values of existing users are updated from the upstream claims during login. New users always receive all values.`,
+            syncNever: 'Never',
+            syncOnEmpty: 'Only empty values',
+            emailDomainsAllowed: 'Allowed E-Mail Domains',
+            emailDomainsDenied: 'Denied E-Mail Domains',
+            emailDomainsDesc: `Restrict logins via this provider by the E-Mail domain. If allowed domains are given, all others are rejected. Denied domains are always rejected, even for already existing users. Domains only match exactly and multiple ones can be given comma-separated.`,
+            requireEmailVerified: 'Require a verified E-Mail',
+            requireEmailVerifiedDesc: `Reject logins unless the provider sends <code>email_verified: true</code>. Otherwise, new users with an unverified address receive a confirmation E-Mail.`,
+            userinfoDesc: `Providers with sparse ID tokens may need the userinfo endpoint to get claims like the E-Mail. By default, it is only requested when the E-Mail is missing in the ID token. Claims from the ID token always take precedence.`,
+            userinfoAuto: 'Userinfo: when needed',
+            userinfoAlways: 'Userinfo: always',
+            userinfoNever: 'Userinfo: never',
             mapMfa: `If your provider issues a claim indicating that the user has used at least 2FA during
                 login, you can specify the mfa claim path.`,
             mapUser: `You can map a user to be a Rauthy admin depending on an upstream ID claim.`,
@@ -18,7 +18,9 @@ export let I18nAdminFr: I18nAdmin = {
         limitedValidity: 'Validité limitée',
     },
     attrs: {
+        autoPopulateFromClaim: 'Sync from upstream claim',
         delete1: 'Êtes-vous sûr de vouloir supprimer cet attribut ?',
+        deleteValues: '{{ count }} utilisateurs ont une valeur pour cet attribut, qui sera également supprimée.',
         defaultValue: 'Valeur par défaut',
         desc: 'Description',
         makeEditable: 'Rendre modifiable',
@@ -41,9 +43,12 @@ export let I18nAdminFr: I18nAdmin = {
         size: 'Taille',
     },
     clients: {
+        allowedGroups: 'Groupes autorisés',
         allowedResources: 'Ressources autorisées',
+        allowedRoles: 'Rôles autorisés',
         defaultAud: 'Audiences par défaut',
         descAllowedResources: `Indicateurs de ressources RFC 8707 optionnels que ce client peut demander. Une liste vide rejette tout paramètre 'resource' avec 'invalid_target'.`,
+        descAllowedRolesGroups: `Si des rôles ou groupes sont définis, seuls les utilisateurs ayant au moins l'un d'eux peuvent utiliser ce client. Laissez les deux vides pour autoriser tous les utilisateurs.`,
         descDefaultAud: `Audiences toujours ajoutées aux jetons de ce client, indépendamment de tout paramètre 'resource'.`,
         backchannelLogout:
             'Si ce client prend en charge {{ OIDC_BCL }}, vous pouvez fournir l’URI ici.',
@@ -88,10 +93,13 @@ export let I18nAdminFr: I18nAdmin = {
             de connexion ne contenant pas de défi valide.`,
         descUri: `Vous pouvez fournir autant d'URI de redirection que vous le souhaitez. À la fin de chacune, vous
             pouvez utiliser <code>*</code> comme caractère générique.`,
+        descResponseTypes: `Seuls les types de réponse sélectionnés sont acceptés par le point de terminaison d'autorisation. 'none' vérifie uniquement une session existante sans émettre de code.`,
         errConfidentialPKCE: `Le client doit être confidentiel ou avoir au moins un défi PKCE activé.`,
+        errResponseTypes: `Au moins un type de réponse doit être sélectionné.`,
         forceMfa: 'Forcer l’authentification multifacteur',
         groupLoginPrefix: 'Préfixe du groupe de connexion',
         name: 'Nom du client',
+        responseTypes: 'Types de réponse',
         scim: {
             baseUri: `L'URI de base SCIM est celle à partir de laquelle les sous-routes comme
                 <code>{base_uri}/Users/{id}</base_uri></code> peuvent être correctement dérivées.`,
@@ -419,6 +427,16 @@ export let I18nAdminFr: I18nAdmin = {
     providers: {
         config: {
             allowInsecureTls: 'Autoriser TLS non sécurisé',
+            autoUpstreamLogout: 'Déconnexion automatique du fournisseur',
+            autoRefresh: 'Actualiser les métadonnées automatiquement',
+            upstreamRefresh: 'Synchroniser les utilisateurs via les refresh tokens en amont',
+            upstreamRefreshDesc: `Stocke le refresh token du fournisseur lors de la connexion et l'utilise périodiquement pour synchroniser l'utilisateur. Si le fournisseur le révoque, l'utilisateur est déconnecté. La plupart des fournisseurs n'émettent des refresh tokens qu'avec le scope offline_access.`,
+            mtlsCert: 'Certificat client mTLS (PEM)',
+            mtlsKey: 'Clé privée mTLS (PEM)',
+            mtlsKeySet: 'Une clé est définie - laisser vide pour la conserver',
+            appleKeyId: 'Apple Key ID',
+            appleKey: 'Clé privée Apple (.p8)',
+            appleTeamId: 'Apple Team ID',
             autoLink: `Lien automatique avec l'utilisateur`,
             autoLinkDesc1: `Si l'option Lien automatique avec l'utilisateur est activée,
                 la connexion via ce fournisseur liera automatiquement un utilisateur existant,
@@ -431,7 +449,7 @@ export let I18nAdminFr: I18nAdmin = {
             descAuthMethod: `Méthode d'authentification à utiliser sur le point de terminaison <code>/token</code>.<br>
                 La plupart des fournisseurs fonctionnent avec <code>basic</code>,
                 certains uniquement avec <code>post</code>.
-                Dans de rares cas, les deux sont nécessaires, tandis que d'autres peuvent entraîner des erreurs.`,
+                Une seule méthode peut être utilisée. Sans aucune, le client est public et repose uniquement sur PKCE.`,
             descClientId: `ID client fourni par le fournisseur d'authentification.`,
             descClientName: 'Nom du client à afficher sur la page de connexion Rauthy.',
             descClientSecret: `Secret client fourni par le fournisseur d'authentification.
@@ -454,7 +472,31 @@ export let I18nAdminFr: I18nAdmin = {
             lookup: 'Chercher',
             pathAdminClaim: `Chemin de revendication d'administration`,
             pathMfaClaim: 'Chemin de revendication MFA',
+            pathEmailClaim: 'Chemin de revendication E-Mail',
+            pathGivenNameClaim: 'Chemin de revendication prénom',
+            pathFamilyNameClaim: 'Chemin de revendication nom',
+            pathGroupsClaim: 'Chemin de revendication groupes',
             rootPemCert: 'Certificat PEM racine',
+            mapClaims: `Les fournisseurs qui n'utilisent pas les revendications standard peuvent être mappés avec des chemins personnalisés. Si un chemin est vide ou introuvable, la revendication standard est utilisée.`,
+            restrictHd: 'Domaines Google Workspace autorisés',
+            restrictHdDesc: `N'accepter que les comptes Google Workspace de ces domaines. Plusieurs domaines peuvent être séparés par des virgules.`,
+            sortOrder: 'Ordre sur la page de connexion',
+            issuerTemplate: 'Modèle d\'émetteur',
+            allowedTenants: 'IDs de tenant autorisés',
+            tenantsDesc: `Pour les fournisseurs multi-tenant comme Azure, dont les ID tokens contiennent le tenant dans l'émetteur. {tid} est remplacé par le claim tid avant la validation de l'émetteur. Plusieurs IDs peuvent être séparés par des virgules.`,
+            syncAlways: 'Toujours',
+            syncDesc: `Définit comment l'e-mail, les noms et les valeurs des utilisateurs existants sont mis à jour à partir des claims en amont lors de la connexion. Les nouveaux utilisateurs reçoivent toujours toutes les valeurs.`,
+            syncNever: 'Jamais',
+            syncOnEmpty: 'Valeurs vides uniquement',
+            emailDomainsAllowed: 'Domaines e-mail autorisés',
+            emailDomainsDenied: 'Domaines e-mail refusés',
+            emailDomainsDesc: `Restreindre les connexions via ce fournisseur selon le domaine de l'e-mail. Si des domaines autorisés sont indiqués, tous les autres sont refusés. Les domaines refusés le sont toujours, même pour les utilisateurs existants. Les domaines doivent correspondre exactement et peuvent être séparés par des virgules.`,
+            requireEmailVerified: 'Exiger un e-mail vérifié',
+            requireEmailVerifiedDesc: `Refuser les connexions tant que le fournisseur n'envoie pas <code>email_verified: true</code>. Sinon, les nouveaux utilisateurs avec une adresse non vérifiée reçoivent un e-mail de confirmation.`,
+            userinfoDesc: `Les fournisseurs avec des ID tokens incomplets peuvent nécessiter l'endpoint userinfo pour obtenir des claims comme l'e-mail. Par défaut, il n'est appelé que si l'e-mail manque dans l'ID token. Les claims de l'ID token sont toujours prioritaires.`,
+            userinfoAuto: 'Userinfo : si nécessaire',
+            userinfoAlways: 'Userinfo : toujours',
+            userinfoNever: 'Userinfo : jamais',
             mapMfa: `Si votre fournisseur émet une attestation indiquant que l'utilisateur a utilisé au moins
                 l'authentification à deux facteurs (2FA) lors de la connexion,
                 vous pouvez spécifier le chemin d'accès à l'attestation multifacteur (MFA).`,
@@ -19,7 +19,9 @@ export interface I18nAdmin {
         limitedValidity: string;
     };
     attrs: {
+        autoPopulateFromClaim: string;
         delete1: string;
+        deleteValues: string;
         defaultValue: string;
         desc: string;
         makeEditable: string;
@@ -56,9 +58,12 @@ export interface I18nAdmin {
         delete1: string;
         descAuthCode: string;
         descClientUri: string;
+        allowedGroups: string;
         allowedResources: string;
+        allowedRoles: string;
         defaultAud: string;
         descAllowedResources: string;
+        descAllowedRolesGroups: string;
         descDefaultAud: string;
         descGroupPrefix: string;
         descName: string;
@@ -67,10 +72,13 @@ export interface I18nAdmin {
         descPKCEEnforce: string;
         // inserted as html
         descUri: string;
+        descResponseTypes: string;
         errConfidentialPKCE: string;
+        errResponseTypes: string;
         forceMfa: string;
         groupLoginPrefix: string;
         name: string;
+        responseTypes: string;
         scim: {
             // inserted as html
             baseUri: string;
@@ -335,6 +343,16 @@ export interface I18nAdmin {
     providers: {
         config: {
             allowInsecureTls: string;
+            autoUpstreamLogout: string;
+            autoRefresh: string;
+            upstreamRefresh: string;
+            upstreamRefreshDesc: string;
+            mtlsCert: string;
+            mtlsKey: string;
+            mtlsKeySet: string;
+            appleKeyId: string;
+            appleKey: string;
+            appleTeamId: string;
             autoLink: string;
             autoLinkDesc1: string;
             autoLinkDesc2: string;
@@ -364,7 +382,31 @@ export interface I18nAdmin {
             lookup: string;
             pathAdminClaim: string;
             pathMfaClaim: string;
+            pathEmailClaim: string;
+            pathGivenNameClaim: string;
+            pathFamilyNameClaim: string;
+            pathGroupsClaim: string;
             rootPemCert: string;
+            mapClaims: string;
+            restrictHd: string;
+            restrictHdDesc: string;
+            sortOrder: string;
+            issuerTemplate: string;
+            allowedTenants: string;
+            tenantsDesc: string;
+            syncAlways: string;
+            syncDesc: string;
+            syncNever: string;
+            syncOnEmpty: string;
+            emailDomainsAllowed: string;
+            emailDomainsDenied: string;
+            emailDomainsDesc: string;
+            requireEmailVerified: string;
+            requireEmailVerifiedDesc: string;
+            userinfoDesc: string;
+            userinfoAuto: string;
+            userinfoAlways: string;
+            userinfoNever: string;
             mapMfa: string;
             mapUser: string;
             valueAdminClaim: string;
@@ -15,7 +15,9 @@ export let I18nAdminKo: I18nAdmin = {
         limitedValidity: '만료일',
     },
     attrs: {
+        autoPopulateFromClaim: 'Sync from upstream claim',
         delete1: '이 속성을 삭제하시겠습니까?',
+        deleteValues: '{{ count }}명의 사용자가 이 속성에 값을 가지고 있으며, 해당 값도 함께 삭제됩니다.',
         defaultValue: 'Default Value',
         desc: '설명',
         makeEditable: 'Make Editable',
@@ -36,9 +38,12 @@ export let I18nAdminKo: I18nAdmin = {
         size: 'Size',
     },
     clients: {
+        allowedGroups: '허용된 그룹',
         allowedResources: '허용된 리소스',
+        allowedRoles: '허용된 역할',
         defaultAud: '기본 대상(Audience)',
         descAllowedResources: `이 클라이언트가 요청할 수 있는 선택적 RFC 8707 리소스 인디케이터입니다. 목록이 비어 있으면 모든 'resource' 요청 파라미터를 'invalid_target'으로 거부합니다.`,
+        descAllowedRolesGroups: `역할이나 그룹이 설정되어 있으면 그중 하나 이상을 가진 사용자만 이 클라이언트를 사용할 수 있습니다. 모든 사용자를 허용하려면 둘 다 비워 두세요.`,
         descDefaultAud: `'resource' 요청 파라미터와 무관하게 이 클라이언트의 토큰에 항상 추가되는 대상(audience)입니다.`,
         backchannelLogout: 'If this client supports {{ OIDC_BCL }}, you can provide the URI here.',
         branding: {
@@ -79,10 +84,13 @@ export let I18nAdminKo: I18nAdmin = {
             않은 로그인 요청은 거부됩니다.`,
         descUri: `원하는 만큼 리디렉션 URI를 제공할 수 있습니다. 각각의 끝에 <code>*</code> 를
             와일드카드로 사용할 수 있습니다.`,
+        descResponseTypes: `선택한 응답 유형만 authorize 엔드포인트에서 허용됩니다. 'none'은 코드를 발급하지 않고 기존 세션만 확인합니다.`,
         errConfidentialPKCE: `클라이언트는 기밀 또는 PKCE 챌린지 중 하나 이상 활성화되어야 합니다.`,
+        errResponseTypes: `응답 유형을 하나 이상 선택해야 합니다.`,
         forceMfa: '강제 MFA',
         groupLoginPrefix: 'Login Group Prefix',
         name: '클라이언트 이름',
+        responseTypes: '응답 유형',
         scim: {
             baseUri: `The SCIM base URI is the one from which the sub routes like 
                 <code>{base_uri}/Users/{id}</base_uri></code> can be derived correctly.`,
@@ -382,6 +390,16 @@ export let I18nAdminKo: I18nAdmin = {
     providers: {
         config: {
             allowInsecureTls: '안전하지 않은 TLS 허용',
+            autoUpstreamLogout: 'Automatic Upstream Logout',
+            autoRefresh: 'Refresh Metadata automatically',
+            upstreamRefresh: 'Sync users via upstream refresh tokens',
+            upstreamRefreshDesc: `Stores the refresh token from the upstream during login and uses it periodically to sync the user. If the upstream revokes it, the user is logged out. Most providers only issue refresh tokens with the offline_access scope.`,
+            mtlsCert: 'mTLS Client Certificate (PEM)',
+            mtlsKey: 'mTLS Private Key (PEM)',
+            mtlsKeySet: 'A key is set - leave empty to keep it',
+            appleKeyId: 'Apple Key ID',
+            appleKey: 'Apple Private Key (.p8)',
+            appleTeamId: 'Apple Team ID',
             autoLink: 'Auto-Link User',
             autoLinkDesc1: `If Auto-Link User is activated, the login via this provider will automatically link a
                 possibly existing, non-linked user to this provider.`,
@@ -392,8 +410,8 @@ export let I18nAdminKo: I18nAdmin = {
             custRootCa: '사용자 지정 Root CA PEM 사용',
             descAuthMethod: `<code>/token</code> 엔드포인트에서 사용할 인증 방법입니다.<br>
                 대부분의 인증 공급자는 <code>basic</code> 으로 작업해야 하며, 일부 인증 공급자는
-                <code>post</code> 만 사용해야 합니다. 드문 경우지만 두 가지를 모두 사용해야 하는 경우도
-                있습니다. 인증 공급자의 가이드대로 설정하시면 됩니다.`,
+                <code>post</code> 만 사용해야 합니다. 한 가지 방법만 사용할 수 있습니다. 둘 다 선택하지 않으면
+                클라이언트는 공개 클라이언트가 되며 PKCE만 사용합니다.`,
             descClientId: '인증 공급자가 제공한 클라이언트 ID',
             descClientName: '로그인 페이지에 표시될 클라이언트 이름',
             descClientSecret: `인증 공급자가 제공한 클라이언트 Secret입니다. 최소한 클라이언트
@@ -416,7 +434,31 @@ export let I18nAdminKo: I18nAdmin = {
             lookup: '조회',
             pathAdminClaim: '관리자 Claim 경로',
             pathMfaClaim: 'MFA Claim 경로',
+            pathEmailClaim: '이메일 Claim 경로',
+            pathGivenNameClaim: '이름 Claim 경로',
+            pathFamilyNameClaim: '성 Claim 경로',
+            pathGroupsClaim: '그룹 Claim 경로',
             rootPemCert: 'Root CA의 PEM 인증서',
+            mapClaims: `표준 Claim을 사용하지 않는 공급자는 사용자 지정 경로로 매핑할 수 있습니다. 경로가 비어 있거나 찾을 수 없으면 표준 Claim이 사용됩니다.`,
+            restrictHd: '허용된 Google Workspace 도메인',
+            restrictHdDesc: `이 도메인의 Google Workspace 계정만 허용합니다. 여러 도메인은 쉼표로 구분할 수 있습니다.`,
+            sortOrder: 'Order on the Login Page',
+            issuerTemplate: 'Issuer Template',
+            allowedTenants: 'Allowed Tenant IDs',
+            tenantsDesc: `For multi-tenant providers like Azure, whose ID tokens contain the tenant in the issuer. {tid} is replaced with the tid claim before the issuer is validated. Multiple tenant IDs can be given comma-separated.`,
+            syncAlways: 'Always',
+            syncDesc: `Defines how the E-Mail, names and user values of existing users are updated from the upstream claims during login. New users always receive all values.`,
+            syncNever: 'Never',
+            syncOnEmpty: 'Only empty values',
+            emailDomainsAllowed: '허용된 이메일 도메인',
+            emailDomainsDenied: '거부된 이메일 도메인',
+            emailDomainsDesc: `이메일 도메인으로 이 공급자를 통한 로그인을 제한합니다. 허용된 도메인이 지정되면 다른 모든 도메인은 거부됩니다. 거부된 도메인은 기존 사용자에게도 항상 거부됩니다. 도메인은 정확히 일치해야 하며 여러 도메인은 쉼표로 구분할 수 있습니다.`,
+            requireEmailVerified: 'Require a verified E-Mail',
+            requireEmailVerifiedDesc: `Reject logins unless the provider sends <code>email_verified: true</code>. Otherwise, new users with an unverified address receive a confirmation E-Mail.`,
+            userinfoDesc: `ID 토큰에 정보가 부족한 공급자는 이메일과 같은 클레임을 얻기 위해 userinfo 엔드포인트가 필요할 수 있습니다. 기본적으로 ID 토큰에 이메일이 없을 때만 요청됩니다. ID 토큰의 클레임이 항상 우선합니다.`,
+            userinfoAuto: 'Userinfo: 필요할 때',
+            userinfoAlways: 'Userinfo: 항상',
+            userinfoNever: 'Userinfo: 사용 안 함',
             mapMfa: `공급자에서 사용자가 로그인하는 동안 2FA 이상을 사용했음을 나타내는 Claim을 발행하는 경우,
                 MFA Claim 경로를 지정할 수 있습니다.`,
             mapUser: `업스트림 ID Claim 에 따라 사용자를 Rauthy 관리자로 매핑할 수 있습니다.`,
@@ -16,7 +16,9 @@ export let I18nAdminNb: I18nAdmin = {
         limitedValidity: 'Begrenset gyldighet',
     },
     attrs: {
+        autoPopulateFromClaim: 'Sync from upstream claim',
         delete1: 'Skal dette attributtet slettes?',
+        deleteValues: '{{ count }} brukere har en verdi for dette attributtet, som også vil bli slettet.',
         defaultValue: 'Standardverdi',
         desc: 'Beskrivelse',
         makeEditable: 'Gjør redigerbart',
@@ -39,9 +41,12 @@ export let I18nAdminNb: I18nAdmin = {
         size: 'Størrelse',
     },
     clients: {
+        allowedGroups: 'Tillatte grupper',
         allowedResources: 'Tillatte ressurser',
+        allowedRoles: 'Tillatte roller',
         defaultAud: 'Standard-mottakere (aud)',
         descAllowedResources: `Valgfrie RFC 8707 ressursindikatorer denne klienten kan be om. En tom liste avviser enhver 'resource'-parameter med 'invalid_target'.`,
+        descAllowedRolesGroups: `Hvis roller eller grupper er satt, kan bare brukere med minst én av dem bruke denne klienten. La begge stå tomme for å tillate alle brukere.`,
         descDefaultAud: `Mottakere (aud) som alltid legges til i denne klientens tokens, uavhengig av en 'resource'-parameter.`,
         backchannelLogout: 'Hvis denne klienten støtter {{ OIDC_BCL }}, kan URIen angis her.',
         branding: {
@@ -84,11 +89,14 @@ export let I18nAdminNb: I18nAdmin = {
             som ikke gir en gyldig Challenge.`,
         descUri: `Det kan angis et vilkårlig antall Redirect URIs. På slutten av hver kan
             valgfritt <code>*</code> aksepteres som en jokertegn.`,
+        descResponseTypes: `Bare de valgte svartypene godtas av authorize-endepunktet. 'none' sjekker bare en eksisterende økt uten å utstede en kode.`,
         errConfidentialPKCE: `Klienten må enten være følsom eller ha minst én PKCE
             Challenge aktivert.`,
+        errResponseTypes: `Minst én svartype må være valgt.`,
         forceMfa: 'Tving MFA',
         groupLoginPrefix: 'Gruppepåloggingsprefiks',
         name: 'Klientnavn',
+        responseTypes: 'Svartyper',
         scim: {
             baseUri: `SCIM Base URI'en må være den som underordnede ruter som
                 <code>{base_uri}/Users/{id}</code> kan avledes korrekt fra.`,
@@ -379,12 +387,22 @@ export let I18nAdminNb: I18nAdmin = {
     providers: {
         config: {
             allowInsecureTls: 'Tillat usikker TLS',
+            autoUpstreamLogout: 'Automatisk utlogging hos leverandør',
+            autoRefresh: 'Oppdater metadata automatisk',
+            upstreamRefresh: 'Synkroniser brukere via upstream refresh tokens',
+            upstreamRefreshDesc: `Lagrer refresh tokenet fra upstream ved innlogging og bruker det jevnlig for å synkronisere brukeren. Hvis upstream trekker det tilbake, logges brukeren ut. De fleste leverandører utsteder kun refresh tokens med offline_access-scopet.`,
+            mtlsCert: 'mTLS-klientsertifikat (PEM)',
+            mtlsKey: 'mTLS privat nøkkel (PEM)',
+            mtlsKeySet: 'En nøkkel er satt - la feltet stå tomt for å beholde den',
+            appleKeyId: 'Apple Key ID',
+            appleKey: 'Apple privat nøkkel (.p8)',
+            appleTeamId: 'Apple Team ID',
             autoLink: 'Auto-link bruker',
             autoLinkDesc1: `Hvis auto-link bruker er aktivert, vil en eventuell eksisterende, ikke-koblet bruker automatisk kobles til denne leverandøren ved innlogging.`,
             autoLinkDesc2: `ADVARSEL: Dette kan være svært farlig og føre til kontoovertakelse hvis leverandøren ikke utfører fullstendig e-postverifisering og lar en fremmed adresse bli registrert for en bruker! MÅ ALDRI brukes i slike tilfeller!`,
             clientName: 'Klientnavn',
             custRootCa: 'Egen Root CA PEM',
-            descAuthMethod: `Autentiseringsmetoden som skal brukes på <code>/token</code>-endepunktet.<br>De fleste leverandører bør fungere med <code>basic</code>, noen kun med <code>post</code>. I sjeldne tilfeller må begge aktiveres, selv om det kan føre til feil med andre leverandører.`,
+            descAuthMethod: `Autentiseringsmetoden som skal brukes på <code>/token</code>-endepunktet.<br>De fleste leverandører bør fungere med <code>basic</code>, noen kun med <code>post</code>. Kun én metode kan brukes. Uten noen er klienten offentlig og bruker kun PKCE.`,
             descClientId: 'Klient-ID gitt av leverandøren.',
             descClientName: 'Klientnavn som skal vises på Rauthy-innloggingssiden.',
             descClientSecret: `Klienthemmelighet gitt av leverandøren. Minst én hemmelighet eller PKCE må være aktivert.`,
@@ -403,7 +421,31 @@ export let I18nAdminNb: I18nAdmin = {
             lookup: 'Søk',
             pathAdminClaim: 'Sti til admin-claim',
             pathMfaClaim: 'Sti til MFA-claim',
+            pathEmailClaim: 'Sti til e-post-claim',
+            pathGivenNameClaim: 'Sti til fornavn-claim',
+            pathFamilyNameClaim: 'Sti til etternavn-claim',
+            pathGroupsClaim: 'Sti til gruppe-claim',
             rootPemCert: 'Root PEM-sertifikat',
+            mapClaims: `Leverandører som ikke bruker standard-claims kan mappes med egne stier. Hvis en sti er tom eller ikke finnes, brukes standard-claimen.`,
+            restrictHd: 'Tillatte Google Workspace-domener',
+            restrictHdDesc: `Godta kun Google Workspace-kontoer fra disse domenene. Flere domener kan angis kommaseparert.`,
+            sortOrder: 'Rekkefølge på innloggingssiden',
+            issuerTemplate: 'Issuer-mal',
+            allowedTenants: 'Tillatte tenant-ID-er',
+            tenantsDesc: `For multi-tenant-leverandører som Azure, der ID-tokens inneholder tenanten i issuer. {tid} erstattes med tid-claimet før issuer valideres. Flere tenant-ID-er kan angis kommaseparert.`,
+            syncAlways: 'Alltid',
+            syncDesc: `Bestemmer hvordan e-post, navn og brukerverdier for eksisterende brukere oppdateres fra upstream-claims ved innlogging. Nye brukere får alltid alle verdier.`,
+            syncNever: 'Aldri',
+            syncOnEmpty: 'Kun tomme verdier',
+            emailDomainsAllowed: 'Tillatte e-postdomener',
+            emailDomainsDenied: 'Avviste e-postdomener',
+            emailDomainsDesc: `Begrens innlogginger via denne leverandøren etter e-postdomenet. Hvis tillatte domener er angitt, avvises alle andre. Avviste domener avvises alltid, også for eksisterende brukere. Domener må samsvare nøyaktig, og flere kan angis kommaseparert.`,
+            requireEmailVerified: 'Krev verifisert e-post',
+            requireEmailVerifiedDesc: `Avvis innlogginger med mindre leverandøren sender <code>email_verified: true</code>. Ellers mottar nye brukere med en uverifisert adresse en bekreftelses-e-post.`,
+            userinfoDesc: `Leverandører med sparsomme ID-tokens kan trenge userinfo-endepunktet for å få claims som e-posten. Som standard spørres det bare når e-posten mangler i ID-tokenet. Claims fra ID-tokenet har alltid forrang.`,
+            userinfoAuto: 'Userinfo: ved behov',
+            userinfoAlways: 'Userinfo: alltid',
+            userinfoNever: 'Userinfo: aldri',
             mapMfa: `Hvis leverandøren gir en claim som indikerer at brukeren har brukt minst 2FA ved innlogging, kan du oppgi stien til MFA-claimen her.`,
             mapUser: `Du kan mappe en bruker til å være Rauthy-admin basert på en upstream ID-claim.`,
             valueAdminClaim: 'Verdi for admin-claim',
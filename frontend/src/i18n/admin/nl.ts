@@ -17,7 +17,9 @@ export let I18nAdminNl: I18nAdmin = {
         limitedValidity: 'Beperkte geldigheid',
     },
     attrs: {
+        autoPopulateFromClaim: 'Sync from upstream claim',
         delete1: 'Weet u zeker dat u dit attribuut wilt verwijderen?',
+        deleteValues: '{{ count }} gebruikers hebben een waarde voor dit attribuut, die ook verwijderd wordt.',
         defaultValue: 'Standaardwaarde',
         desc: 'Beschrijving',
         makeEditable: 'Bewerkbaar maken',
@@ -40,9 +42,12 @@ export let I18nAdminNl: I18nAdmin = {
         size: 'Grootte',
     },
     clients: {
+        allowedGroups: 'Toegestane groepen',
         allowedResources: 'Toegestane resources',
+        allowedRoles: 'Toegestane rollen',
         defaultAud: 'Standaard audiences',
         descAllowedResources: `Optionele RFC 8707 resource-indicatoren die deze client mag opvragen. Een lege lijst weigert elke 'resource'-parameter met 'invalid_target'.`,
+        descAllowedRolesGroups: `Als er rollen of groepen zijn ingesteld, kunnen alleen gebruikers met minstens één daarvan deze client gebruiken. Laat beide leeg om alle gebruikers toe te staan.`,
         descDefaultAud: `Audiences die altijd aan de tokens van deze client worden toegevoegd, onafhankelijk van een 'resource'-parameter.`,
         backchannelLogout:
             'Als deze client {{ OIDC_BCL }} ondersteunt, kunt u de URI hier opgeven.',
@@ -85,11 +90,14 @@ export let I18nAdminNl: I18nAdmin = {
             weigert loginverzoeken die geen geldige uitdaging bevatten.`,
         descUri: `U kunt zoveel omleidings-URI's opgeven als u wilt. Aan het einde van elk kunt u
             <code>*</code> gebruiken als jokerteken.`,
+        descResponseTypes: `Alleen de geselecteerde response types worden geaccepteerd door het authorize-endpoint. 'none' controleert alleen een bestaande sessie zonder een code uit te geven.`,
         errConfidentialPKCE: `De client moet vertrouwelijk zijn of minimaal één PKCE-uitdaging
             geactiveerd hebben.`,
+        errResponseTypes: `Er moet minstens één response type geselecteerd zijn.`,
         forceMfa: 'MFA verplichten',
         groupLoginPrefix: 'Login-groepsprefix',
         name: 'Clientnaam',
+        responseTypes: 'Response Types',
         scim: {
             baseUri: `De SCIM-basis-URI is die waarvan de subroutes zoals
                 <code>{base_uri}/Users/{id}</base_uri></code> correct kunnen worden afgeleid.`,
@@ -408,6 +416,16 @@ export let I18nAdminNl: I18nAdmin = {
     providers: {
         config: {
             allowInsecureTls: 'Onveilige TLS toestaan',
+            autoUpstreamLogout: 'Automatisch uitloggen bij provider',
+            autoRefresh: 'Metadata automatisch vernieuwen',
+            upstreamRefresh: 'Gebruikers synchroniseren via upstream refresh tokens',
+            upstreamRefreshDesc: `Slaat het refresh token van de upstream op bij het inloggen en gebruikt het periodiek om de gebruiker te synchroniseren. Als de upstream het intrekt, wordt de gebruiker uitgelogd. De meeste providers geven alleen refresh tokens uit met de offline_access scope.`,
+            mtlsCert: 'mTLS-clientcertificaat (PEM)',
+            mtlsKey: 'mTLS privésleutel (PEM)',
+            mtlsKeySet: 'Er is een sleutel ingesteld - laat leeg om deze te behouden',
+            appleKeyId: 'Apple Key ID',
+            appleKey: 'Apple privésleutel (.p8)',
+            appleTeamId: 'Apple Team ID',
             autoLink: 'Gebruiker automatisch koppelen',
             autoLinkDesc1: `Als Gebruiker automatisch koppelen is geactiveerd, wordt bij inloggen via deze provider
                 automatisch een mogelijk bestaande, niet-gekoppelde gebruiker aan deze provider gekoppeld.`,
@@ -418,7 +436,7 @@ export let I18nAdminNl: I18nAdmin = {
             custRootCa: 'Aangepaste root CA PEM',
             descAuthMethod: `De authenticatiemethode voor het <code>/token</code>-eindpunt.<br>
                 De meeste providers werken met <code>basic</code>, sommige alleen met <code>post</code>.
-                In zeldzame situaties heeft u beide nodig, terwijl het bij anderen tot fouten kan leiden.`,
+                Er kan slechts één methode worden gebruikt. Zonder methode is de client publiek en gebruikt alleen PKCE.`,
             descClientId: 'Client-ID opgegeven door de auth-provider.',
             descClientName: 'Clientnaam die op de Rauthy-inlogpagina moet worden getoond.',
             descClientSecret: `Clientgeheim opgegeven door de auth-provider.
@@ -441,7 +459,31 @@ export let I18nAdminNl: I18nAdmin = {
             lookup: 'Opzoeken',
             pathAdminClaim: 'Beheerdersclaimpad',
             pathMfaClaim: 'MFA-claimpad',
+            pathEmailClaim: 'E-mail-claimpad',
+            pathGivenNameClaim: 'Voornaam-claimpad',
+            pathFamilyNameClaim: 'Achternaam-claimpad',
+            pathGroupsClaim: 'Groepen-claimpad',
             rootPemCert: 'Root PEM-certificaat',
+            mapClaims: `Providers die niet de standaard claims gebruiken, kunnen met eigen paden worden gemapt. Als een pad leeg is of niet wordt gevonden, wordt de standaard claim gebruikt.`,
+            restrictHd: 'Toegestane Google Workspace-domeinen',
+            restrictHdDesc: `Alleen Google Workspace-accounts van deze domeinen accepteren. Meerdere domeinen kunnen kommagescheiden worden opgegeven.`,
+            sortOrder: 'Volgorde op de inlogpagina',
+            issuerTemplate: 'Issuer-sjabloon',
+            allowedTenants: `Toegestane tenant-ID's`,
+            tenantsDesc: `Voor multi-tenant providers zoals Azure, waarvan de ID-tokens de tenant in de issuer bevatten. {tid} wordt vervangen door de tid-claim voordat de issuer wordt gevalideerd. Meerdere tenant-ID's kunnen kommagescheiden worden opgegeven.`,
+            syncAlways: 'Altijd',
+            syncDesc: `Bepaalt hoe e-mail, namen en gebruikerswaarden van bestaande gebruikers bij het inloggen worden bijgewerkt vanuit de upstream claims. Nieuwe gebruikers krijgen altijd alle waarden.`,
+            syncNever: 'Nooit',
+            syncOnEmpty: 'Alleen lege waarden',
+            emailDomainsAllowed: 'Toegestane e-maildomeinen',
+            emailDomainsDenied: 'Geweigerde e-maildomeinen',
+            emailDomainsDesc: `Beperk logins via deze provider op basis van het e-maildomein. Als toegestane domeinen zijn opgegeven, worden alle andere geweigerd. Geweigerde domeinen worden altijd geweigerd, ook voor bestaande gebruikers. Domeinen moeten exact overeenkomen en meerdere kunnen kommagescheiden worden opgegeven.`,
+            requireEmailVerified: 'Geverifieerd e-mailadres vereisen',
+            requireEmailVerifiedDesc: `Weiger logins tenzij de provider <code>email_verified: true</code> stuurt. Anders ontvangen nieuwe gebruikers met een niet-geverifieerd adres een bevestigingsmail.`,
+            userinfoDesc: `Providers met beperkte ID tokens hebben mogelijk het userinfo endpoint nodig om claims zoals het e-mailadres te krijgen. Standaard wordt het alleen opgevraagd als het e-mailadres in het ID token ontbreekt. Claims uit het ID token hebben altijd voorrang.`,
+            userinfoAuto: 'Userinfo: indien nodig',
+            userinfoAlways: 'Userinfo: altijd',
+            userinfoNever: 'Userinfo: nooit',
             mapMfa: `Als uw provider een claim uitgeeft die aangeeft dat de gebruiker minimaal 2FA heeft gebruikt
                 tijdens inloggen, kunt u het MFA-claimpad opgeven.`,
             mapUser: `U kunt een gebruiker toewijzen als Rauthy-beheerder op basis van een upstream ID-claim.`,
@@ -17,7 +17,9 @@ export let I18nAdminRu: I18nAdmin = {
         limitedValidity: 'Ограниченный срок действия',
     },
     attrs: {
+        autoPopulateFromClaim: 'Sync from upstream claim',
         delete1: 'Вы уверены, что хотите удалить этот атрибут?',
+        deleteValues: '{{ count }} пользователей имеют значение для этого атрибута, которое также будет удалено.',
         defaultValue: 'Значение по умолчанию',
         desc: 'Описание',
         makeEditable: 'Сделать редактируемым',
@@ -40,9 +42,12 @@ export let I18nAdminRu: I18nAdmin = {
         size: 'Размер',
     },
     clients: {
+        allowedGroups: 'Разрешённые группы',
         allowedResources: 'Разрешённые ресурсы',
+        allowedRoles: 'Разрешённые роли',
         defaultAud: 'Аудитории по умолчанию',
         descAllowedResources: `Необязательные индикаторы ресурсов RFC 8707, которые может запрашивать этот клиент. Пустой список отклоняет любой параметр 'resource' с ошибкой 'invalid_target'.`,
+        descAllowedRolesGroups: `Если заданы роли или группы, этим клиентом могут пользоваться только пользователи, имеющие хотя бы одну из них. Оставьте оба поля пустыми, чтобы разрешить всем пользователям.`,
         descDefaultAud: `Аудитории, которые всегда добавляются в токены этого клиента, независимо от параметра 'resource'.`,
         backchannelLogout:
             'Если этот клиент поддерживает {{ OIDC_BCL }}, вы можете указать URI здесь.',
@@ -85,11 +90,14 @@ export let I18nAdminRu: I18nAdmin = {
             отклонять запросы на вход, не содержащие допустимого вызова.`,
         descUri: `Вы можете указать любое количество URI перенаправления. В конце каждого можно использовать
             <code>*</code> в качестве подстановочного символа.`,
+        descResponseTypes: `Конечная точка authorize принимает только выбранные типы ответа. 'none' только проверяет существующую сессию, не выдавая код.`,
         errConfidentialPKCE: `Клиент должен быть либо конфиденциальным, либо иметь активированную хотя бы одну
             проверку PKCE.`,
+        errResponseTypes: `Необходимо выбрать хотя бы один тип ответа.`,
         forceMfa: 'Принудительная MFA',
         groupLoginPrefix: 'Префикс группы для входа',
         name: 'Имя клиента',
+        responseTypes: 'Типы ответа',
         scim: {
             baseUri: `Базовый URI SCIM — это URI, от которого могут быть правильно получены подмаршруты, такие как
                 <code>{base_uri}/Users/{id}</base_uri></code>.`,
@@ -400,6 +408,16 @@ export let I18nAdminRu: I18nAdmin = {
     providers: {
         config: {
             allowInsecureTls: 'Разрешить небезопасный TLS',
+            autoUpstreamLogout: 'Автоматический выход у провайдера',
+            autoRefresh: 'Автоматически обновлять метаданные',
+            upstreamRefresh: 'Синхронизировать пользователей через refresh токены провайдера',
+            upstreamRefreshDesc: `Сохраняет refresh токен провайдера при входе и периодически использует его для синхронизации пользователя. Если провайдер отзывает его, пользователь выходит из системы. Большинство провайдеров выдают refresh токены только со scope offline_access.`,
+            mtlsCert: 'Клиентский сертификат mTLS (PEM)',
+            mtlsKey: 'Закрытый ключ mTLS (PEM)',
+            mtlsKeySet: 'Ключ задан - оставьте пустым, чтобы сохранить его',
+            appleKeyId: 'Apple Key ID',
+            appleKey: 'Закрытый ключ Apple (.p8)',
+            appleTeamId: 'Apple Team ID',
             autoLink: 'Автопривязка пользователя',
             autoLinkDesc1: `Если автопривязка пользователя активирована, вход через этого провайдера автоматически привяжет
                 возможно существующего, непривязанного пользователя к этому провайдеру.`,
@@ -410,7 +428,7 @@ export let I18nAdminRu: I18nAdmin = {
             custRootCa: 'Пользовательский корневой CA PEM',
             descAuthMethod: `Метод аутентификации для использования на эндпоинте <code>/token</code>.<br>
                 Большинство провайдеров работают с <code>basic</code>, некоторые только с <code>post</code>.
-                В редких случаях нужны оба, в то время как у других это может вызвать ошибки.`,
+                Можно использовать только один метод. Без метода клиент является публичным и использует только PKCE.`,
             descClientId: 'ID клиента, предоставленный провайдером аутентификации.',
             descClientName: 'Имя клиента, которое должно отображаться на странице входа Rauthy.',
             descClientSecret: `Секрет клиента, предоставленный провайдером аутентификации.
@@ -434,7 +452,31 @@ export let I18nAdminRu: I18nAdmin = {
             lookup: 'Поиск',
             pathAdminClaim: 'Путь к утверждению администратора',
             pathMfaClaim: 'Путь к утверждению MFA',
+            pathEmailClaim: 'Путь к утверждению E-Mail',
+            pathGivenNameClaim: 'Путь к утверждению имени',
+            pathFamilyNameClaim: 'Путь к утверждению фамилии',
+            pathGroupsClaim: 'Путь к утверждению групп',
             rootPemCert: 'Корневой PEM-сертификат',
+            mapClaims: `Провайдеры, не использующие стандартные утверждения, могут быть сопоставлены с помощью собственных путей. Если путь пуст или не найден, используется стандартное утверждение.`,
+            restrictHd: 'Разрешённые домены Google Workspace',
+            restrictHdDesc: `Принимать только учётные записи Google Workspace из этих доменов. Несколько доменов можно указать через запятую.`,
+            sortOrder: 'Порядок на странице входа',
+            issuerTemplate: 'Шаблон издателя',
+            allowedTenants: 'Разрешённые ID тенантов',
+            tenantsDesc: `Для мультитенантных провайдеров, таких как Azure, у которых ID токены содержат тенант в издателе. {tid} заменяется claim tid перед проверкой издателя. Несколько ID можно указать через запятую.`,
+            syncAlways: 'Всегда',
+            syncDesc: `Определяет, как E-Mail, имена и значения существующих пользователей обновляются из claims провайдера при входе. Новые пользователи всегда получают все значения.`,
+            syncNever: 'Никогда',
+            syncOnEmpty: 'Только пустые значения',
+            emailDomainsAllowed: 'Разрешённые домены E-Mail',
+            emailDomainsDenied: 'Запрещённые домены E-Mail',
+            emailDomainsDesc: `Ограничить входы через этого провайдера по домену E-Mail. Если указаны разрешённые домены, все остальные отклоняются. Запрещённые домены отклоняются всегда, даже для существующих пользователей. Домены должны совпадать точно, несколько можно указать через запятую.`,
+            requireEmailVerified: 'Требовать подтверждённый E-Mail',
+            requireEmailVerifiedDesc: `Отклонять входы, если провайдер не отправляет <code>email_verified: true</code>. Иначе новые пользователи с неподтверждённым адресом получают письмо для подтверждения.`,
+            userinfoDesc: `Провайдерам с неполными ID токенами может понадобиться эндпоинт userinfo, чтобы получить claims, например E-Mail. По умолчанию он запрашивается, только если E-Mail отсутствует в ID токене. Claims из ID токена всегда имеют приоритет.`,
+            userinfoAuto: 'Userinfo: при необходимости',
+            userinfoAlways: 'Userinfo: всегда',
+            userinfoNever: 'Userinfo: никогда',
             mapMfa: `Если ваш провайдер выдаёт утверждение, указывающее, что пользователь использовал как минимум 2FA при
                 входе, вы можете указать путь к утверждению MFA.`,
             mapUser: `Вы можете назначить пользователю роль администратора Rauthy на основе утверждения вышестоящего ID.`,
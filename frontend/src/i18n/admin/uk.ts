@@ -17,7 +17,9 @@ export let I18nAdminUk: I18nAdmin = {
         limitedValidity: 'Обмежений термін дії',
     },
     attrs: {
+        autoPopulateFromClaim: 'Sync from upstream claim',
         delete1: 'Дійсно видалити цей атрибут?',
+        deleteValues: '{{ count }} користувачів мають значення для цього атрибута, яке також буде видалено.',
         defaultValue: 'Значення за замовчуванням',
         desc: 'Опис',
         makeEditable: 'Дозволити редагування',
@@ -39,9 +41,12 @@ export let I18nAdminUk: I18nAdmin = {
         size: 'Розмір',
     },
     clients: {
+        allowedGroups: 'Дозволені групи',
         allowedResources: 'Дозволені ресурси',
+        allowedRoles: 'Дозволені ролі',
         defaultAud: 'Аудиторії за замовчуванням',
         descAllowedResources: `Необов'язкові індикатори ресурсів RFC 8707, які може запитувати цей клієнт. Порожній список відхиляє будь-який параметр 'resource' з помилкою 'invalid_target'.`,
+        descAllowedRolesGroups: `Якщо задано ролі або групи, цим клієнтом можуть користуватися лише користувачі, що мають хоча б одну з них. Залиште обидва поля порожніми, щоб дозволити всім користувачам.`,
         descDefaultAud: `Аудиторії, які завжди додаються до токенів цього клієнта, незалежно від параметра 'resource'.`,
         backchannelLogout: 'Якщо цей клієнт підтримує {{ OIDC_BCL }}, ви можете вказати URI тут.',
         branding: {
@@ -83,11 +88,14 @@ export let I18nAdminUk: I18nAdmin = {
             відхилятиме запити, що не містять дійсного виклику.`,
         descUri: `Ви можете вказати скільки завгодно URI для перенаправлення. В кінці кожного можна використати
             <code>*</code> як шаблон.`,
+        descResponseTypes: `Кінцева точка authorize приймає лише вибрані типи відповіді. 'none' лише перевіряє наявну сесію, не видаючи код.`,
         errConfidentialPKCE: `Клієнт повинен бути або конфіденційним, або мати активованим принаймні один
             метод PKCE.`,
+        errResponseTypes: `Потрібно вибрати принаймні один тип відповіді.`,
         forceMfa: 'Вимагати MFA',
         groupLoginPrefix: 'Префікс групи для входу',
         name: 'Назва клієнта',
+        responseTypes: 'Типи відповіді',
         scim: {
             baseUri: `Базовий URI для SCIM — це той, з якого можна коректно вивести під-маршрути,
                 наприклад <code>{base_uri}/Users/{id}</base_uri></code>.`,
@@ -403,6 +411,16 @@ export let I18nAdminUk: I18nAdmin = {
     providers: {
         config: {
             allowInsecureTls: 'Дозволити небезпечний TLS',
+            autoUpstreamLogout: 'Автоматичний вихід у провайдера',
+            autoRefresh: 'Автоматично оновлювати метадані',
+            upstreamRefresh: 'Синхронізувати користувачів через refresh токени провайдера',
+            upstreamRefreshDesc: `Зберігає refresh токен провайдера під час входу та періодично використовує його для синхронізації користувача. Якщо провайдер відкликає його, користувач виходить із системи. Більшість провайдерів видають refresh токени лише зі scope offline_access.`,
+            mtlsCert: 'Клієнтський сертифікат mTLS (PEM)',
+            mtlsKey: 'Закритий ключ mTLS (PEM)',
+            mtlsKeySet: 'Ключ задано - залиште порожнім, щоб зберегти його',
+            appleKeyId: 'Apple Key ID',
+            appleKey: 'Закритий ключ Apple (.p8)',
+            appleTeamId: 'Apple Team ID',
             autoLink: "Автоматична прив'язка",
             autoLinkDesc1: `Якщо активовано "Авто-прив'язку", вхід через цього провайдера автоматично прив'яже
                 можливо існуючого, не прив'язаного користувача до цього провайдера.`,
@@ -413,7 +431,7 @@ export let I18nAdminUk: I18nAdmin = {
             custRootCa: 'Власний кореневий CA (PEM)',
             descAuthMethod: `Метод автентифікації для ендпоінту <code>/token</code>.<br>
                 Більшість провайдерів повинні працювати з <code>basic</code>, деякі — лише з <code>post</code>.
-                У рідкісних ситуаціях вам можуть знадобитися обидва, хоча це може призвести до помилок з іншими.`,
+                Можна використовувати лише один метод. Без методу клієнт є публічним і використовує лише PKCE.`,
             descClientId: 'ID клієнта, виданий провайдером.',
             descClientName: 'Назва клієнта, що буде показана на сторінці входу Rauthy.',
             descClientSecret: `Секрет клієнта, виданий провайдером.
@@ -436,7 +454,31 @@ export let I18nAdminUk: I18nAdmin = {
             lookup: 'Пошук',
             pathAdminClaim: 'Шлях до Admin Claim',
             pathMfaClaim: 'Шлях до MFA Claim',
+            pathEmailClaim: 'Шлях до E-Mail Claim',
+            pathGivenNameClaim: 'Шлях до Claim імені',
+            pathFamilyNameClaim: 'Шлях до Claim прізвища',
+            pathGroupsClaim: 'Шлях до Claim груп',
             rootPemCert: 'Кореневий сертифікат (PEM)',
+            mapClaims: `Провайдери, які не використовують стандартні claims, можна зіставити за допомогою власних шляхів. Якщо шлях порожній або не знайдений, використовується стандартний claim.`,
+            restrictHd: 'Дозволені домени Google Workspace',
+            restrictHdDesc: `Приймати лише облікові записи Google Workspace з цих доменів. Кілька доменів можна вказати через кому.`,
+            sortOrder: 'Порядок на сторінці входу',
+            issuerTemplate: 'Шаблон видавця',
+            allowedTenants: 'Дозволені ID тенантів',
+            tenantsDesc: `Для мультитенантних провайдерів, таких як Azure, у яких ID токени містять тенант у видавці. {tid} замінюється claim tid перед перевіркою видавця. Кілька ID можна вказати через кому.`,
+            syncAlways: 'Завжди',
+            syncDesc: `Визначає, як E-Mail, імена та значення наявних користувачів оновлюються з claims провайдера під час входу. Нові користувачі завжди отримують усі значення.`,
+            syncNever: 'Ніколи',
+            syncOnEmpty: 'Лише порожні значення',
+            emailDomainsAllowed: 'Дозволені домени E-Mail',
+            emailDomainsDenied: 'Заборонені домени E-Mail',
+            emailDomainsDesc: `Обмежити входи через цього провайдера за доменом E-Mail. Якщо вказано дозволені домени, усі інші відхиляються. Заборонені домени відхиляються завжди, навіть для наявних користувачів. Домени мають збігатися точно, кілька можна вказати через кому.`,
+            requireEmailVerified: 'Вимагати підтверджений E-Mail',
+            requireEmailVerifiedDesc: `Відхиляти входи, якщо провайдер не надсилає <code>email_verified: true</code>. Інакше нові користувачі з непідтвердженою адресою отримують лист для підтвердження.`,
+            userinfoDesc: `Провайдерам з неповними ID токенами може знадобитися ендпоінт userinfo, щоб отримати claims, наприклад E-Mail. За замовчуванням він запитується, лише якщо E-Mail відсутній в ID токені. Claims з ID токена завжди мають пріоритет.`,
+            userinfoAuto: 'Userinfo: за потреби',
+            userinfoAlways: 'Userinfo: завжди',
+            userinfoNever: 'Userinfo: ніколи',
             mapMfa: `Якщо ваш провайдер видає claim, що вказує на те, що користувач використовував принаймні
                 2FA під час входу, ви можете вказати шлях до mfa claim.`,
             mapUser: `Ви можете призначити користувача адміном Rauthy на основі клейму (claim) з ID-токена зовнішнього провайдера.`,
@@ -16,7 +16,9 @@ export let I18nAdminZh: I18nAdmin = {
         limitedValidity: '有限有效期',
     },
     attrs: {
+        autoPopulateFromClaim: 'Sync from upstream claim',
         delete1: '您确定要删除此属性吗？',
+        deleteValues: '{{ count }} 个用户拥有此属性的值，这些值也将被删除。',
         defaultValue: '默认值',
         desc: '描述',
         makeEditable: '设为可编辑',
@@ -37,9 +39,12 @@ export let I18nAdminZh: I18nAdmin = {
         size: '大小',
     },
     clients: {
+        allowedGroups: '允许的组',
         allowedResources: '允许的资源',
+        allowedRoles: '允许的角色',
         defaultAud: '默认受众 (aud)',
         descAllowedResources: `此客户端可以请求的可选 RFC 8707 资源指示符。空列表将以 'invalid_target' 拒绝任何 'resource' 请求参数。`,
+        descAllowedRolesGroups: `如果设置了角色或组，只有至少拥有其中之一的用户才能使用此客户端。两者都留空则允许所有用户。`,
         descDefaultAud: `无论是否提供 'resource' 请求参数，始终添加到此客户端令牌中的受众 (aud)。`,
         backchannelLogout: '如果此客户端支持{{ OIDC_BCL }}，您可以在此处提供URI。',
         branding: {
@@ -80,11 +85,14 @@ export let I18nAdminZh: I18nAdmin = {
             并拒绝不包含有效挑战的登录请求。`,
         descUri: `您可以提供任意数量的重定向URI。在每个URI末尾，
             您可以使用<code>*</code>作为通配符。`,
+        descResponseTypes: `授权端点只接受所选的响应类型。'none' 仅检查现有会话，不颁发授权码。`,
         errConfidentialPKCE: `客户端必须是机密客户端或至少激活一个PKCE
             挑战。`,
+        errResponseTypes: `必须至少选择一种响应类型。`,
         forceMfa: '强制MFA',
         groupLoginPrefix: '登录组前缀',
         name: '客户端名称',
+        responseTypes: '响应类型',
         scim: {
             baseUri: `SCIM基础URI是从中可以正确派生子路由的URI，
                 如<code>{base_uri}/Users/{id}</base_uri></code>。`,
@@ -378,6 +386,16 @@ export let I18nAdminZh: I18nAdmin = {
     providers: {
         config: {
             allowInsecureTls: '允许不安全的TLS',
+            autoUpstreamLogout: '自动注销上游提供商',
+            autoRefresh: '自动刷新元数据',
+            upstreamRefresh: '通过上游刷新令牌同步用户',
+            upstreamRefreshDesc: `在登录时保存上游的刷新令牌，并定期使用它同步用户。如果上游撤销了该令牌，用户将被注销。大多数提供商仅在使用 offline_access 范围时才会签发刷新令牌。`,
+            mtlsCert: 'mTLS 客户端证书 (PEM)',
+            mtlsKey: 'mTLS 私钥 (PEM)',
+            mtlsKeySet: '已设置密钥 - 留空以保留',
+            appleKeyId: 'Apple Key ID',
+            appleKey: 'Apple 私钥 (.p8)',
+            appleTeamId: 'Apple Team ID',
             autoLink: '自动链接用户',
             autoLinkDesc1: `如果激活自动链接用户，通过此提供商登录将自动将可能存在的
                 未链接用户与此提供商链接。`,
@@ -388,7 +406,7 @@ export let I18nAdminZh: I18nAdmin = {
             custRootCa: '自定义根CA PEM',
             descAuthMethod: `在<code>/token</code>端点使用的身份验证方法。<br>
                 大多数提供商应能与<code>basic</code>配合工作，有些只能使用<code>post</code>。
-                在极少数情况下，您需要两者，但这可能导致与其他提供商出现错误。`,
+                只能使用一种方法。如果都不选择，客户端为公共客户端，仅依赖 PKCE。`,
             descClientId: '提供商提供的客户端ID。',
             descClientName: '应在Rauthy登录页面显示的客户端名称。',
             descClientSecret: `提供商提供的客户端密钥。
@@ -410,7 +428,31 @@ export let I18nAdminZh: I18nAdmin = {
             lookup: '查找',
             pathAdminClaim: '管理员声明路径',
             pathMfaClaim: 'MFA声明路径',
+            pathEmailClaim: '电子邮件声明路径',
+            pathGivenNameClaim: '名字声明路径',
+            pathFamilyNameClaim: '姓氏声明路径',
+            pathGroupsClaim: '群组声明路径',
             rootPemCert: '根PEM证书',
+            mapClaims: `不使用标准声明的提供商可以通过自定义路径进行映射。如果路径为空或未找到，则使用标准声明。`,
+            restrictHd: '允许的 Google Workspace 域',
+            restrictHdDesc: `仅接受来自这些域的 Google Workspace 账户。多个域可以用逗号分隔。`,
+            sortOrder: '登录页面上的顺序',
+            issuerTemplate: 'Issuer 模板',
+            allowedTenants: '允许的租户 ID',
+            tenantsDesc: `适用于 Azure 等多租户提供商，其 ID 令牌的 issuer 中包含租户。在验证 issuer 之前，{tid} 会被替换为 tid 声明。多个租户 ID 可以用逗号分隔。`,
+            syncAlways: '始终',
+            syncDesc: `定义登录时如何根据上游声明更新现有用户的邮箱、姓名和用户值。新用户始终会获得所有值。`,
+            syncNever: '从不',
+            syncOnEmpty: '仅空值',
+            emailDomainsAllowed: '允许的电子邮件域',
+            emailDomainsDenied: '禁止的电子邮件域',
+            emailDomainsDesc: `按电子邮件域限制通过此提供商的登录。如果指定了允许的域，则拒绝所有其他域。禁止的域始终被拒绝，即使是已存在的用户。域必须完全匹配，多个域可以用逗号分隔。`,
+            requireEmailVerified: '要求已验证的电子邮件',
+            requireEmailVerifiedDesc: `除非提供商发送 <code>email_verified: true</code>，否则拒绝登录。否则，地址未验证的新用户会收到一封确认电子邮件。`,
+            userinfoDesc: `ID 令牌信息不全的提供商可能需要 userinfo 端点来获取电子邮件等声明。默认情况下，仅当 ID 令牌中缺少电子邮件时才会请求。ID 令牌中的声明始终优先。`,
+            userinfoAuto: 'Userinfo：需要时',
+            userinfoAlways: 'Userinfo：始终',
+            userinfoNever: 'Userinfo：从不',
             mapMfa: `如果您的提供商在登录期间发出表明用户至少使用了2FA的声明，
                 您可以指定MFA声明路径。`,
             mapUser: `您可以根据上游ID声明将用户映射为Rauthy管理员。`,
@@ -192,6 +192,7 @@ export const I18nDe: I18n = {
         passwordResetDesc: `Bitte E-Mail Adresse angeben, um einen Password Reset Link anzufordern. 
             Sollte die Adresse in der Datenbank existieren, wird and diese ein Link verschickt.`,
         passwordResetSuccess: 'Anfrage erhalten. Dieses Fenster kann nun geschlossen werden.',
+        providerUnavailable: 'vorübergehend nicht verfügbar',
         requestExpires: 'Anfrage läuft ab',
         requestExpired: 'Anfrage ist abgelaufen',
         signUp: 'Benutzer Registrierung',
@@ -231,6 +232,7 @@ export const I18nDe: I18n = {
         logout: 'Logout',
         confirmMsg: 'Sind Sie sicher, dass Sie sich ausloggen und die Session beenden möchten?',
         cancel: 'Abbrechen',
+        upstreamLogout: 'Auch beim Login-Provider abmelden',
     },
     mfa: {
         p1: `Wenn Sie mehrere Systeme parallel nutzen möchten, wie z.B. Windows und Android, 
@@ -252,6 +254,8 @@ export const I18nDe: I18n = {
         registerdKeys: 'Registrierte Keys',
         passkeyName: 'Passkey Name',
         passkeyNameErr: '2 - 32 Buchstaben, keine Sonderzeichen',
+        counterAnomaly: 'Möglicherweise geklont seit',
+        counterAnomalyClear: 'Warnung entfernen',
         passwordInvalid: 'Ungültiges Password',
         test: 'Test',
         testError: 'Fehler beim Starten des Tests',
@@ -185,6 +185,7 @@ export const I18nEn: I18n = {
         passwordResetDesc: `Please provide your E-Mail to request a password reset link. If your 
             address exists in out database, you will receive a link via E-Mail.`,
         passwordResetSuccess: 'Request received. You can close this window now.',
+        providerUnavailable: 'temporarily unavailable',
         requestExpires: 'Request expires',
         requestExpired: 'Request has expired',
         signUp: 'User Registration',
@@ -224,6 +225,7 @@ export const I18nEn: I18n = {
         logout: 'Logout',
         confirmMsg: 'Do you really want to logout and end your session?',
         cancel: 'Cancel',
+        upstreamLogout: 'Logout at the login provider as well',
     },
     mfa: {
         p1: `If you plan on using your MFA key with multiple systems like Windows and Android, 
@@ -246,6 +248,8 @@ export const I18nEn: I18n = {
         registerdKeys: 'Registered Keys',
         passkeyName: 'Passkey Name',
         passkeyNameErr: '2 - 32 non-special characters',
+        counterAnomaly: 'Possibly cloned since',
+        counterAnomalyClear: 'Clear Warning',
         passwordInvalid: 'Password Invalid',
         test: 'Test',
         testError: 'Error starting the Test',
@@ -188,6 +188,7 @@ export const I18nFr: I18n = {
         passwordResetDesc: `Please provide your E-Mail to request a password reset link. If your 
             address exists in out database, you will receive a link via E-Mail.`,
         passwordResetSuccess: 'Request received. You can close this window now.',
+        providerUnavailable: 'temporairement indisponible',
         requestExpires: 'Request expires',
         requestExpired: 'Request has expired',
         signUp: 'User Registration',
@@ -227,6 +228,7 @@ export const I18nFr: I18n = {
         logout: 'Se déconnecter',
         confirmMsg: 'Voulez-vous vraiment vous déconnecter et mettre fin à votre session ?',
         cancel: 'Annuler',
+        upstreamLogout: 'Se déconnecter également du fournisseur de connexion',
     },
     mfa: {
         p1: `Si vous prévoyez d’utiliser votre clé MFA avec plusieurs systèmes comme Windows et Android,
@@ -249,6 +251,8 @@ export const I18nFr: I18n = {
         registerdKeys: 'Clés enregistrées',
         passkeyName: `Nom de la clé d'accès`,
         passkeyNameErr: '2 à 32 caractères non spéciaux',
+        counterAnomaly: 'Possiblement clonée depuis',
+        counterAnomalyClear: 'Effacer l’avertissement',
         passwordInvalid: 'Mot de passe invalide',
         test: 'Test',
         testError: 'Erreur lors du démarrage du test',
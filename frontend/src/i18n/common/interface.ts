@@ -82,6 +82,8 @@ export interface I18n {
         navMfa: string;
         navLogout: string;
         navBackToAdmin: string;
+        /** Only exists with an admin defined translation override */
+        notice?: string;
         other: string;
         pam: {
             addSshKey: string;
@@ -142,6 +144,8 @@ export interface I18n {
         mfaAck: string;
         navigateToAccount: string;
         needsUserUpdate: string;
+        /** Only exists with an admin defined translation override */
+        notice?: string;
         orLoginWith: string;
         password: string;
         passwordExpired: string;
@@ -151,6 +155,7 @@ export interface I18n {
         passwordResetDesc: string;
         passwordResetSuccess: string;
         expectingPasskey: string;
+        providerUnavailable: string;
         requestExpires: string;
         requestExpired: string;
         signUp: string;
@@ -188,6 +193,7 @@ export interface I18n {
         logout: string;
         confirmMsg: string;
         cancel: string;
+        upstreamLogout: string;
     };
     mfa: {
         p1: string;
@@ -206,6 +212,8 @@ export interface I18n {
         registerdKeys: string;
         passkeyName: string;
         passkeyNameErr: string;
+        counterAnomaly: string;
+        counterAnomalyClear: string;
         passwordInvalid: string;
         test: string;
         testError: string;
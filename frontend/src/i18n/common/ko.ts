@@ -180,6 +180,7 @@ export const I18nKo: I18n = {
         passwordResetDesc: `Please provide your E-Mail to request a password reset link. If your 
             address exists in out database, you will receive a link via E-Mail.`,
         passwordResetSuccess: 'Request received. You can close this window now.',
+        providerUnavailable: '일시적으로 사용할 수 없음',
         requestExpires: '만료일',
         requestExpired: '요청이 만료되었습니다.',
         signUp: '사용자 가입',
@@ -219,6 +220,7 @@ export const I18nKo: I18n = {
         logout: '로그아웃',
         confirmMsg: '로그아웃하고 세션을 종료하겠습니까?',
         cancel: '취소',
+        upstreamLogout: '로그인 제공자에서도 로그아웃',
     },
     mfa: {
         p1: '윈도우와 안드로이드 등 다양한 시스템에서 MFA를 사용하려면, 안드로이드에서 키를 등록하여야 합니다.',
@@ -238,6 +240,8 @@ export const I18nKo: I18n = {
         registerdKeys: '등록된 키',
         passkeyName: '패스키 이름',
         passkeyNameErr: '특수문자를 제외한 2자에서 32자이어야 합니다.',
+        counterAnomaly: '복제 의심 시점',
+        counterAnomalyClear: '경고 지우기',
         passwordInvalid: 'Password Invalid',
         test: '테스트',
         testError: '테스트 시작 중 오류 발생',
@@ -184,6 +184,7 @@ export const I18nNb: I18n = {
         passwordResetDesc: `Vennligst oppgi e-postadressen for å be om en tilbakestillingslenke for 
             passord. Hvis adressen finnes i databasen, vil en lenke bli sendt dit.`,
         passwordResetSuccess: 'Forespørsel mottatt. Dette vinduet kan nå lukkes.',
+        providerUnavailable: 'midlertidig utilgjengelig',
         requestExpires: 'Forespørselen utløper',
         requestExpired: 'Forespørselen er utløpt',
         signUp: 'Brukerregistrering',
@@ -221,6 +222,7 @@ export const I18nNb: I18n = {
         logout: 'Logg ut',
         confirmMsg: 'Er du sikker på at du vil logge ut og avslutte økten?',
         cancel: 'Avbryt',
+        upstreamLogout: 'Logg også ut hos innloggingsleverandøren',
     },
     mfa: {
         p1: `Hvis du ønsker å bruke flere systemer parallelt, som f.eks. Windows og Android, bør du 
@@ -243,6 +245,8 @@ export const I18nNb: I18n = {
         registerdKeys: 'Registrerte nøkler',
         passkeyName: 'Passkey-navn',
         passkeyNameErr: '2 - 32 bokstaver, ingen spesialtegn',
+        counterAnomaly: 'Muligens klonet siden',
+        counterAnomalyClear: 'Fjern advarsel',
         passwordInvalid: 'Ugyldig passord',
         test: 'Test',
         testError: 'Feil ved oppstart av testen',
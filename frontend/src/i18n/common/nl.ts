@@ -187,6 +187,7 @@ export const I18nNl: I18n = {
         passwordResetDesc: `Geef uw e-mailadres op om een wachtwoordresetlink aan te vragen. Als uw
             adres in onze database bestaat, ontvangt u een link via e-mail.`,
         passwordResetSuccess: 'Verzoek ontvangen. U kunt dit venster nu sluiten.',
+        providerUnavailable: 'tijdelijk niet beschikbaar',
         requestExpires: 'Verzoek vervalt',
         requestExpired: 'Verzoek is verlopen',
         signUp: 'Gebruikersregistratie',
@@ -226,6 +227,7 @@ export const I18nNl: I18n = {
         logout: 'Uitloggen',
         confirmMsg: 'Weet u zeker dat u wilt uitloggen en uw sessie wilt beëindigen?',
         cancel: 'Annuleren',
+        upstreamLogout: 'Ook uitloggen bij de loginprovider',
     },
     mfa: {
         p1: `Als u van plan bent uw MFA-sleutel te gebruiken met meerdere systemen zoals Windows en
@@ -248,6 +250,8 @@ export const I18nNl: I18n = {
         registerdKeys: 'Geregistreerde sleutels',
         passkeyName: 'Passkey naam',
         passkeyNameErr: '2 - 32 tekens zonder speciale tekens',
+        counterAnomaly: 'Mogelijk gekloond sinds',
+        counterAnomalyClear: 'Waarschuwing wissen',
         passwordInvalid: 'Ongeldig wachtwoord',
         test: 'Test',
         testError: 'Fout bij het starten van de test',
@@ -187,6 +187,7 @@ export const I18nRu: I18n = {
         passwordResetDesc: `Пожалуйста, укажите вашу эл. почту для запроса ссылки на сброс пароля. Если ваш
             адрес существует в нашей базе данных, вы получите ссылку по электронной почте.`,
         passwordResetSuccess: 'Запрос получен. Теперь вы можете закрыть это окно.',
+        providerUnavailable: 'временно недоступен',
         requestExpires: 'Запрос истекает',
         requestExpired: 'Запрос истёк',
         signUp: 'Регистрация пользователя',
@@ -224,6 +225,7 @@ export const I18nRu: I18n = {
         logout: 'Выход',
         confirmMsg: 'Вы действительно хотите выйти и завершить сеанс?',
         cancel: 'Отмена',
+        upstreamLogout: 'Также выйти у провайдера входа',
     },
     mfa: {
         p1: `Если вы планируете использовать свой МФА-ключ с несколькими системами, такими как Windows и Android,
@@ -246,6 +248,8 @@ export const I18nRu: I18n = {
         registerdKeys: 'Зарегистрированные ключи',
         passkeyName: 'Название ключа доступа',
         passkeyNameErr: '2 - 32 обычных символа',
+        counterAnomaly: 'Возможно клонирован с',
+        counterAnomalyClear: 'Сбросить предупреждение',
         passwordInvalid: 'Неверный пароль',
         test: 'Тест',
         testError: 'Ошибка при запуске теста',
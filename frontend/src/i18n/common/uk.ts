@@ -191,6 +191,7 @@ export const I18nUk: I18n = {
         passwordResetDesc: `Будь ласка, вкажіть ваш E-Mail, щоб запросити посилання для скидання
             пароля.\nЯкщо ваша адреса є в нашій базі, ви отримаєте посилання на E-Mail.`,
         passwordResetSuccess: 'Запит отримано. Можете закрити це вікно.',
+        providerUnavailable: 'тимчасово недоступний',
         requestExpires: 'Запит закінчується',
         requestExpired: 'Термін дії запиту минув',
         signUp: 'Реєстрація',
@@ -230,6 +231,7 @@ export const I18nUk: I18n = {
         logout: 'Вийти',
         confirmMsg: 'Ви дійсно хочете вийти і завершити сесію?',
         cancel: 'Скасувати',
+        upstreamLogout: 'Також вийти у провайдера входу',
     },
     mfa: {
         p1: `Якщо ви плануєте використовувати свій MFA-ключ з різними системами, як-от Windows та
@@ -251,6 +253,8 @@ export const I18nUk: I18n = {
         registerdKeys: 'Зареєстровані ключі',
         passkeyName: 'Назва ключа доступу',
         passkeyNameErr: '2 - 32 символи, без спецсимволів',
+        counterAnomaly: 'Можливо клоновано з',
+        counterAnomalyClear: 'Скинути попередження',
         passwordInvalid: 'Неправильний пароль',
         test: 'Тест',
         testError: 'Помилка під час запуску тесту',
@@ -172,6 +172,7 @@ export const I18nZh: I18n = {
         passwordRequired: '密码必填。',
         passwordResetDesc: `请提供您的电子邮件以请求密码重置链接。如果您的地址存在于我们的数据库中，您将通过电子邮件收到一个链接。`,
         passwordResetSuccess: '请求已接收。您现在可以关闭此窗口。',
+        providerUnavailable: '暂时不可用',
         requestExpires: '请求过期于',
         requestExpired: '请求已过期',
         signUp: '用户注册',
@@ -211,6 +212,7 @@ export const I18nZh: I18n = {
         logout: '退出登录',
         confirmMsg: '您确定要退出登录并结束会话吗？',
         cancel: '取消',
+        upstreamLogout: '同时从登录提供商退出',
     },
     mfa: {
         p1: `如果您计划在多个系统上使用您的MFA密钥，例如Windows和Android，您应该在Android上进行注册。`,
@@ -229,6 +231,8 @@ export const I18nZh: I18n = {
         registerdKeys: '已注册的密钥',
         passkeyName: '通行密钥名称',
         passkeyNameErr: '2-32个非特殊字符',
+        counterAnomaly: '疑似被复制，时间',
+        counterAnomalyClear: '清除警告',
         passwordInvalid: '密码无效',
         test: '测试',
         testError: '开始测试时出现错误',
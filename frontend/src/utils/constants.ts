@@ -53,9 +53,11 @@ export const TPL_EMAIL_NEW = 'tpl_email_new';
 // export const TPL_ERROR_DETAILS = 'tpl_error_details';
 export const TPL_ERROR_TEXT = 'tpl_error_text';
 export const TPL_DEVICE_USER_CODE_LENGTH = 'tpl_device_user_code_length';
+export const TPL_I18N_OVERRIDES = 'tpl_i18n_overrides';
 export const TPL_IS_REG_OPEN = 'tpl_is_reg_open';
 export const TPL_LOGIN_ACTION = 'tpl_login_action';
 export const TPL_PASSWORD_RESET = 'tpl_password_reset';
 export const TPL_STATUS_CODE = 'tpl_status_code';
 export const TPL_RESTRICTED_EMAIL_DOMAIN = 'tpl_restricted_email_domain';
+export const TPL_UPSTREAM_LOGOUT = 'tpl_upstream_logout';
 export const TPL_USER_VALUES_CONFIG = 'tpl_user_values_config';
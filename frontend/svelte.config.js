@@ -27,13 +27,13 @@ const config = {
             $utils: 'src/utils',
             $webauthn: 'src/webauthn',
         },
+        // Only the `script-src` with the hashes for the inline scripts can be built here.
+        // All other directives come from the `Content-Security-Policy` header, which can be
+        // extended via the config. Browsers enforce both policies, so any other directive in
+        // here would block the configured sources again.
         csp: {
             directives: {
-                'default-src': ['none'],
-                'connect-src': ['self'],
                 'script-src': ['self', 'wasm-unsafe-eval'],
-                'style-src': ['self', 'unsafe-inline'],
-                'img-src': ['self'],
             },
         },
         env: {
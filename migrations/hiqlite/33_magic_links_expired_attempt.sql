@@ -0,0 +1,2 @@
+ALTER TABLE magic_links
+    ADD expired_attempt INTEGER;
@@ -0,0 +1,20 @@
+CREATE TABLE auth_provider_group_mappings
+(
+    id             TEXT NOT NULL
+        CONSTRAINT auth_provider_group_mappings_pk
+            PRIMARY KEY,
+    provider_id    TEXT NOT NULL
+        CONSTRAINT auth_provider_group_mappings_auth_providers_id_fk
+            REFERENCES auth_providers
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    claim_key      TEXT NOT NULL,
+    upstream_group TEXT NOT NULL,
+    group_id       TEXT NOT NULL
+        CONSTRAINT auth_provider_group_mappings_groups_id_fk
+            REFERENCES groups
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    sync_mode      TEXT NOT NULL
+) STRICT;
+
+CREATE UNIQUE INDEX auth_provider_group_mappings_unique_idx
+    ON auth_provider_group_mappings (provider_id, claim_key, upstream_group, group_id);
@@ -0,0 +1,11 @@
+CREATE TABLE user_notification_prefs
+(
+    user_id  TEXT    NOT NULL
+        CONSTRAINT user_notification_prefs_users_id_fk
+            REFERENCES users
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    category TEXT    NOT NULL,
+    enabled  INTEGER NOT NULL,
+    CONSTRAINT user_notification_prefs_pk
+        PRIMARY KEY (user_id, category)
+) STRICT;
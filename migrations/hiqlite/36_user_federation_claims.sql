@@ -0,0 +1,16 @@
+CREATE TABLE user_federation_claims
+(
+    user_id     TEXT    NOT NULL
+        CONSTRAINT user_federation_claims_pk
+            PRIMARY KEY
+        CONSTRAINT user_federation_claims_users_id_fk
+            REFERENCES users
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    provider_id TEXT    NOT NULL
+        CONSTRAINT user_federation_claims_auth_providers_id_fk
+            REFERENCES auth_providers
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    claims      BLOB    NOT NULL,
+    updated     INTEGER NOT NULL,
+    history     TEXT    NOT NULL
+) STRICT;
@@ -0,0 +1,12 @@
+CREATE TABLE clients_jwks
+(
+    client_id TEXT    NOT NULL
+        CONSTRAINT clients_jwks_pk
+            PRIMARY KEY
+        CONSTRAINT clients_jwks_clients_id_fk
+            REFERENCES clients
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    jwks      TEXT,
+    jwks_uri  TEXT,
+    updated   INTEGER NOT NULL
+) STRICT;
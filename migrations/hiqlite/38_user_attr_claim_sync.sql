@@ -0,0 +1,5 @@
+ALTER TABLE user_attr_config
+    ADD auto_populate_from_claim TEXT;
+
+ALTER TABLE user_attr_values
+    ADD last_synced_from_claim_at INTEGER;
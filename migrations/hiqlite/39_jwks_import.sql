@@ -0,0 +1,13 @@
+ALTER TABLE jwks
+    ADD inactive INTEGER DEFAULT false NOT NULL;
+
+CREATE TABLE jwks_hsm_refs
+(
+    kid        TEXT    NOT NULL
+        CONSTRAINT jwks_hsm_refs_pk
+            PRIMARY KEY,
+    created_at INTEGER NOT NULL,
+    signature  TEXT    NOT NULL,
+    slot       INTEGER NOT NULL,
+    key_id     TEXT    NOT NULL
+) STRICT;
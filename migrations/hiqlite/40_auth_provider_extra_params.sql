@@ -0,0 +1,2 @@
+ALTER TABLE auth_providers
+    ADD extra_auth_params BLOB;
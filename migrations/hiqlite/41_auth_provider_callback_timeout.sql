@@ -0,0 +1,2 @@
+ALTER TABLE auth_providers
+    ADD callback_timeout_secs INTEGER;
@@ -0,0 +1,5 @@
+ALTER TABLE users
+    ADD email_hash TEXT;
+
+CREATE UNIQUE INDEX users_email_hash_uindex
+    ON users (email_hash);
@@ -0,0 +1,5 @@
+ALTER TABLE auth_providers
+    ADD end_session_endpoint TEXT;
+
+ALTER TABLE auth_providers
+    ADD auto_upstream_logout INTEGER DEFAULT 0 NOT NULL;
@@ -0,0 +1,5 @@
+ALTER TABLE clients
+    ADD refresh_token_binding BLOB;
+
+ALTER TABLE refresh_tokens
+    ADD fingerprint TEXT;
@@ -0,0 +1,2 @@
+ALTER TABLE clients
+    ADD client_type TEXT DEFAULT 'web' NOT NULL;
@@ -0,0 +1,4 @@
+ALTER TABLE clients
+    ADD allowed_roles TEXT;
+ALTER TABLE clients
+    ADD allowed_groups TEXT;
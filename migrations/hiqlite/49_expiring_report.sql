@@ -0,0 +1,2 @@
+CREATE INDEX users_user_expires_index
+    ON users (user_expires);
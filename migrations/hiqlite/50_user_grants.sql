@@ -0,0 +1,11 @@
+ALTER TABLE refresh_tokens
+    ADD client_id TEXT;
+
+ALTER TABLE refresh_tokens
+    ADD granted INTEGER;
+
+ALTER TABLE refresh_tokens
+    ADD last_used INTEGER;
+
+CREATE INDEX refresh_tokens_user_id_client_id_index
+    ON refresh_tokens (user_id, client_id);
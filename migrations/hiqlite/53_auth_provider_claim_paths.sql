@@ -0,0 +1,11 @@
+ALTER TABLE auth_providers
+    ADD claim_path_email TEXT;
+
+ALTER TABLE auth_providers
+    ADD claim_path_given_name TEXT;
+
+ALTER TABLE auth_providers
+    ADD claim_path_family_name TEXT;
+
+ALTER TABLE auth_providers
+    ADD claim_path_groups TEXT;
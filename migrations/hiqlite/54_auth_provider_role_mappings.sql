@@ -0,0 +1,33 @@
+CREATE TABLE auth_provider_role_mappings
+(
+    id          TEXT NOT NULL
+        CONSTRAINT auth_provider_role_mappings_pk
+            PRIMARY KEY,
+    provider_id TEXT NOT NULL
+        CONSTRAINT auth_provider_role_mappings_auth_providers_id_fk
+            REFERENCES auth_providers
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    claim_path  TEXT NOT NULL,
+    claim_value TEXT NOT NULL,
+    role_id     TEXT NOT NULL
+        CONSTRAINT auth_provider_role_mappings_roles_id_fk
+            REFERENCES roles
+            ON UPDATE CASCADE ON DELETE CASCADE
+) STRICT;
+
+CREATE UNIQUE INDEX auth_provider_role_mappings_unique_idx
+    ON auth_provider_role_mappings (provider_id, claim_path, claim_value, role_id);
+
+CREATE TABLE user_mapped_roles
+(
+    user_id TEXT NOT NULL
+        CONSTRAINT user_mapped_roles_users_id_fk
+            REFERENCES users
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    role_id TEXT NOT NULL
+        CONSTRAINT user_mapped_roles_roles_id_fk
+            REFERENCES roles
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    CONSTRAINT user_mapped_roles_pk
+        PRIMARY KEY (user_id, role_id)
+) STRICT;
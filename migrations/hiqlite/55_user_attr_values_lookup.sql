@@ -0,0 +1,2 @@
+CREATE INDEX user_attr_values_key_value_index
+    ON user_attr_values (key, value);
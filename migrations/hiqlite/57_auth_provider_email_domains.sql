@@ -0,0 +1,5 @@
+ALTER TABLE auth_providers
+    ADD allowed_email_domains TEXT;
+
+ALTER TABLE auth_providers
+    ADD denied_email_domains TEXT;
@@ -0,0 +1,7 @@
+ALTER TABLE auth_providers
+    ADD auto_refresh INTEGER DEFAULT 1 NOT NULL;
+
+-- custom providers are configured manually and GitHub has no openid-configuration
+UPDATE auth_providers
+SET auto_refresh = 0
+WHERE typ IN ('custom', 'github');
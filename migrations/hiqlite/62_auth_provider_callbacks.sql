@@ -0,0 +1,25 @@
+CREATE TABLE auth_provider_callbacks
+(
+    callback_id               TEXT    NOT NULL
+        CONSTRAINT auth_provider_callbacks_pk
+            PRIMARY KEY,
+    xsrf_token                TEXT    NOT NULL,
+    typ                       TEXT    NOT NULL,
+    req_client_id             TEXT    NOT NULL,
+    req_scopes                TEXT,
+    req_redirect_uri          TEXT    NOT NULL,
+    req_state                 TEXT,
+    req_nonce                 TEXT,
+    req_code_challenge        TEXT,
+    req_code_challenge_method TEXT,
+    provider_id               TEXT    NOT NULL
+        CONSTRAINT auth_provider_callbacks_auth_providers_id_fk
+            REFERENCES auth_providers
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    pkce_challenge            TEXT    NOT NULL,
+    nonce                     TEXT    NOT NULL,
+    expires_at                INTEGER NOT NULL
+) STRICT;
+
+CREATE INDEX auth_provider_callbacks_expires_at_index
+    ON auth_provider_callbacks (expires_at);
@@ -0,0 +1,11 @@
+ALTER TABLE auth_providers
+    ADD apple_team_id TEXT;
+
+ALTER TABLE auth_providers
+    ADD apple_key_id TEXT;
+
+ALTER TABLE auth_providers
+    ADD apple_private_key BLOB;
+
+ALTER TABLE auth_provider_callbacks
+    ADD upstream_user TEXT;
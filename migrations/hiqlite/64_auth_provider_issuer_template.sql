@@ -0,0 +1,5 @@
+ALTER TABLE auth_providers
+    ADD issuer_template TEXT;
+
+ALTER TABLE auth_providers
+    ADD allowed_tenants TEXT;
@@ -0,0 +1,2 @@
+ALTER TABLE auth_providers
+    ADD sync_policy TEXT DEFAULT 'always' NOT NULL;
@@ -0,0 +1,21 @@
+ALTER TABLE auth_providers
+    ADD upstream_refresh INTEGER DEFAULT 0 NOT NULL;
+
+CREATE TABLE auth_provider_refresh_tokens
+(
+    user_id       TEXT    NOT NULL
+        CONSTRAINT auth_provider_refresh_tokens_pk
+            PRIMARY KEY
+        CONSTRAINT auth_provider_refresh_tokens_users_id_fk
+            REFERENCES users
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    provider_id   TEXT    NOT NULL
+        CONSTRAINT auth_provider_refresh_tokens_auth_providers_id_fk
+            REFERENCES auth_providers
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    refresh_token BLOB    NOT NULL,
+    refreshed     INTEGER NOT NULL
+) STRICT;
+
+CREATE INDEX auth_provider_refresh_tokens_provider_id_refreshed_index
+    ON auth_provider_refresh_tokens (provider_id, refreshed);
@@ -0,0 +1,2 @@
+ALTER TABLE auth_providers
+    ADD require_email_verified INTEGER DEFAULT 0 NOT NULL;
@@ -0,0 +1,2 @@
+ALTER TABLE auth_providers
+    ADD sort_order INTEGER DEFAULT 0 NOT NULL;
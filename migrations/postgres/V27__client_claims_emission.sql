@@ -0,0 +1,2 @@
+ALTER TABLE clients
+    ADD claims_emission BYTEA;
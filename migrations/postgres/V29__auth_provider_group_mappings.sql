@@ -0,0 +1,20 @@
+CREATE TABLE auth_provider_group_mappings
+(
+    id             VARCHAR NOT NULL
+        CONSTRAINT auth_provider_group_mappings_pk
+            PRIMARY KEY,
+    provider_id    VARCHAR NOT NULL
+        CONSTRAINT auth_provider_group_mappings_auth_providers_id_fk
+            REFERENCES auth_providers
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    claim_key      VARCHAR NOT NULL,
+    upstream_group VARCHAR NOT NULL,
+    group_id       VARCHAR NOT NULL
+        CONSTRAINT auth_provider_group_mappings_groups_id_fk
+            REFERENCES groups
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    sync_mode      VARCHAR NOT NULL
+);
+
+CREATE UNIQUE INDEX auth_provider_group_mappings_unique_idx
+    ON auth_provider_group_mappings (provider_id, claim_key, upstream_group, group_id);
@@ -0,0 +1,11 @@
+CREATE TABLE user_notification_prefs
+(
+    user_id  VARCHAR NOT NULL
+        CONSTRAINT user_notification_prefs_users_id_fk
+            REFERENCES users
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    category VARCHAR NOT NULL,
+    enabled  BOOLEAN NOT NULL,
+    CONSTRAINT user_notification_prefs_pk
+        PRIMARY KEY (user_id, category)
+);
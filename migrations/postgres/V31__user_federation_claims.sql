@@ -0,0 +1,16 @@
+CREATE TABLE user_federation_claims
+(
+    user_id     VARCHAR NOT NULL
+        CONSTRAINT user_federation_claims_pk
+            PRIMARY KEY
+        CONSTRAINT user_federation_claims_users_id_fk
+            REFERENCES users
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    provider_id VARCHAR NOT NULL
+        CONSTRAINT user_federation_claims_auth_providers_id_fk
+            REFERENCES auth_providers
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    claims      BYTEA   NOT NULL,
+    updated     BIGINT  NOT NULL,
+    history     VARCHAR NOT NULL
+);
@@ -0,0 +1,12 @@
+CREATE TABLE clients_jwks
+(
+    client_id VARCHAR NOT NULL
+        CONSTRAINT clients_jwks_pk
+            PRIMARY KEY
+        CONSTRAINT clients_jwks_clients_id_fk
+            REFERENCES clients
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    jwks      VARCHAR,
+    jwks_uri  VARCHAR,
+    updated   BIGINT  NOT NULL
+);
@@ -0,0 +1,13 @@
+ALTER TABLE jwks
+    ADD inactive BOOL DEFAULT false NOT NULL;
+
+CREATE TABLE jwks_hsm_refs
+(
+    kid        VARCHAR NOT NULL
+        CONSTRAINT jwks_hsm_refs_pk
+            PRIMARY KEY,
+    created_at BIGINT  NOT NULL,
+    signature  VARCHAR NOT NULL,
+    slot       BIGINT  NOT NULL,
+    key_id     VARCHAR NOT NULL
+);
@@ -0,0 +1,5 @@
+ALTER TABLE auth_providers
+    ADD end_session_endpoint VARCHAR;
+
+ALTER TABLE auth_providers
+    ADD auto_upstream_logout BOOLEAN DEFAULT false NOT NULL;
@@ -0,0 +1,15 @@
+CREATE TABLE clients_claims_webhook
+(
+    client_id      VARCHAR NOT NULL
+        CONSTRAINT clients_claims_webhook_pk
+            PRIMARY KEY
+        CONSTRAINT clients_claims_webhook_clients_id_fk
+            REFERENCES clients
+            ON DELETE CASCADE ON UPDATE CASCADE,
+    uri            VARCHAR NOT NULL,
+    secret         BYTEA   NOT NULL,
+    timeout_ms     INTEGER NOT NULL,
+    cache_ttl      INTEGER NOT NULL,
+    allowed_claims VARCHAR NOT NULL,
+    fail_open      BOOLEAN NOT NULL
+);
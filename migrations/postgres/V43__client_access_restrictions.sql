@@ -0,0 +1,4 @@
+ALTER TABLE clients
+    ADD allowed_roles VARCHAR;
+ALTER TABLE clients
+    ADD allowed_groups VARCHAR;
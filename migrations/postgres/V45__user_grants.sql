@@ -0,0 +1,11 @@
+ALTER TABLE refresh_tokens
+    ADD client_id VARCHAR;
+
+ALTER TABLE refresh_tokens
+    ADD granted BIGINT;
+
+ALTER TABLE refresh_tokens
+    ADD last_used BIGINT;
+
+CREATE INDEX refresh_tokens_user_id_client_id_index
+    ON refresh_tokens (user_id, client_id);
@@ -0,0 +1,2 @@
+ALTER TABLE passkeys
+    ADD counter_anomaly BIGINT;
@@ -0,0 +1,2 @@
+ALTER TABLE clients
+    ADD response_types VARCHAR DEFAULT 'code,none' NOT NULL;
@@ -0,0 +1,11 @@
+ALTER TABLE auth_providers
+    ADD claim_path_email VARCHAR;
+
+ALTER TABLE auth_providers
+    ADD claim_path_given_name VARCHAR;
+
+ALTER TABLE auth_providers
+    ADD claim_path_family_name VARCHAR;
+
+ALTER TABLE auth_providers
+    ADD claim_path_groups VARCHAR;
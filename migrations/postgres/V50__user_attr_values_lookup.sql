@@ -0,0 +1,6 @@
+CREATE INDEX user_attr_values_key_index
+    ON user_attr_values (key);
+
+-- A btree index would fail for values bigger than ~2.7kB, which a JSON value may easily be.
+CREATE INDEX user_attr_values_value_index
+    ON user_attr_values USING hash (value);
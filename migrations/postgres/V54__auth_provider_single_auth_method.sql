@@ -0,0 +1,5 @@
+-- only a single auth method is used at the upstream token endpoint, basic is the spec default
+UPDATE auth_providers
+SET client_secret_post = false
+WHERE client_secret_basic = true
+  AND client_secret_post = true;
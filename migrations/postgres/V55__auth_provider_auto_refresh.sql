@@ -0,0 +1,7 @@
+ALTER TABLE auth_providers
+    ADD auto_refresh BOOLEAN DEFAULT true NOT NULL;
+
+-- custom providers are configured manually and GitHub has no openid-configuration
+UPDATE auth_providers
+SET auto_refresh = false
+WHERE typ IN ('custom', 'github');
@@ -0,0 +1,5 @@
+ALTER TABLE auth_providers
+    ADD client_cert_pem BYTEA;
+
+ALTER TABLE auth_providers
+    ADD client_key_pem BYTEA;
@@ -0,0 +1,25 @@
+CREATE TABLE auth_provider_callbacks
+(
+    callback_id               VARCHAR NOT NULL
+        CONSTRAINT auth_provider_callbacks_pk
+            PRIMARY KEY,
+    xsrf_token                VARCHAR NOT NULL,
+    typ                       VARCHAR NOT NULL,
+    req_client_id             VARCHAR NOT NULL,
+    req_scopes                VARCHAR,
+    req_redirect_uri          VARCHAR NOT NULL,
+    req_state                 VARCHAR,
+    req_nonce                 VARCHAR,
+    req_code_challenge        VARCHAR,
+    req_code_challenge_method VARCHAR,
+    provider_id               VARCHAR NOT NULL
+        CONSTRAINT auth_provider_callbacks_auth_providers_id_fk
+            REFERENCES auth_providers
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    pkce_challenge            VARCHAR NOT NULL,
+    nonce                     VARCHAR NOT NULL,
+    expires_at                BIGINT  NOT NULL
+);
+
+CREATE INDEX auth_provider_callbacks_expires_at_index
+    ON auth_provider_callbacks (expires_at);
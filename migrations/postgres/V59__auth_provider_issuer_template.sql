@@ -0,0 +1,5 @@
+ALTER TABLE auth_providers
+    ADD issuer_template VARCHAR;
+
+ALTER TABLE auth_providers
+    ADD allowed_tenants VARCHAR;
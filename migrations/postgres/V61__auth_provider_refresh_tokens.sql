@@ -0,0 +1,21 @@
+ALTER TABLE auth_providers
+    ADD upstream_refresh BOOLEAN DEFAULT false NOT NULL;
+
+CREATE TABLE auth_provider_refresh_tokens
+(
+    user_id       VARCHAR NOT NULL
+        CONSTRAINT auth_provider_refresh_tokens_pk
+            PRIMARY KEY
+        CONSTRAINT auth_provider_refresh_tokens_users_id_fk
+            REFERENCES users
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    provider_id   VARCHAR NOT NULL
+        CONSTRAINT auth_provider_refresh_tokens_auth_providers_id_fk
+            REFERENCES auth_providers
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    refresh_token BYTEA   NOT NULL,
+    refreshed     BIGINT  NOT NULL
+);
+
+CREATE INDEX auth_provider_refresh_tokens_provider_id_refreshed_index
+    ON auth_provider_refresh_tokens (provider_id, refreshed);
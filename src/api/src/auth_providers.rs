@@ -1,28 +1,50 @@
 use crate::{ReqPrincipal, content_len_limit, map_auth_step};
-use actix_web::http::header::{CACHE_CONTROL, CONTENT_TYPE, LOCATION};
-use actix_web::web::{Json, Query};
+use actix_web::guard::GuardContext;
+use actix_web::http::header::{CACHE_CONTROL, CONTENT_TYPE, ContentType, LOCATION};
+use actix_web::web::{Form, Json, Query};
 use actix_web::{HttpRequest, HttpResponse, delete, get, post, put, web};
 use actix_web_lab::__reexports::futures_util::StreamExt;
 use rauthy_api_types::auth_providers::{
-    ProviderCallbackRequest, ProviderLinkedUserResponse, ProviderLoginRequest,
-    ProviderLookupRequest, ProviderRequest,
+    ProviderCallbackFormRequest, ProviderCallbackRequest, ProviderDeleteParams, ProviderExport,
+    ProviderGroupMappingRequest, ProviderGroupMappingResponse, ProviderImportResponse,
+    ProviderLinkedUserResponse, ProviderLoginRequest, ProviderLookupRequest, ProviderRequest,
+    ProviderRoleMappingRequest, ProviderRoleMappingResponse, ProviderSecretRequest,
+    ProviderSecretResponse, ProviderTestCallbackRequest, ProviderTestCallbackResponse,
+    ProviderTestResponse,
 };
 use rauthy_api_types::auth_providers::{ProviderLookupResponse, ProviderResponse};
 use rauthy_api_types::generic::LogoParams;
 use rauthy_api_types::users::{UserResponse, WebauthnLoginResponse};
-use rauthy_common::constants::{HEADER_JSON, PROVIDER_ATPROTO};
+use rauthy_common::constants::{
+    COOKIE_UPSTREAM_CALLBACK, HEADER_EXPORT_PASSPHRASE, HEADER_JSON, PROVIDER_ATPROTO,
+    RAUTHY_ADMIN_ROLE,
+};
+use rauthy_common::utils::real_ip;
+use rauthy_data::api_cookie::ApiCookie;
 use rauthy_data::entity::api_keys::{AccessGroup, AccessRights};
+use rauthy_data::entity::auth_provider_group_mappings::AuthProviderGroupMapping;
+use rauthy_data::entity::auth_provider_role_mappings::AuthProviderRoleMapping;
 use rauthy_data::entity::auth_providers::{
-    AuthProvider, AuthProviderLinkCookie, AuthProviderTemplate,
+    AuthProvider, AuthProviderCallback, AuthProviderIdClaims, AuthProviderLinkCookie,
+    AuthProviderResumeCookie, AuthProviderTemplate,
 };
 use rauthy_data::entity::logos::{Logo, LogoType};
 use rauthy_data::entity::pow::PowEntity;
+use rauthy_data::entity::principal::Principal;
+use rauthy_data::entity::roles::Role;
 use rauthy_data::entity::theme::ThemeCssFull;
 use rauthy_data::entity::users::User;
+use rauthy_data::events::event::Event;
 use rauthy_data::html::HtmlCached;
+use rauthy_data::html::flow_error::{
+    ErrorFlow, flow_error_response, flow_error_response_with_retry,
+};
+use rauthy_data::metrics;
+use rauthy_data::rauthy_config::RauthyConfig;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
+use rauthy_service::oidc::validation;
 use spow::pow::Pow;
-use tracing::debug;
+use tracing::{debug, warn};
 use validator::Validate;
 
 /// GET all upstream auth providers
@@ -54,6 +76,8 @@ pub async fn post_providers(principal: ReqPrincipal) -> Result<HttpResponse, Err
 
 /// POST create a new upstream auth provider
 ///
+/// `admin_claim_path` and `admin_claim_value` can only be set with an admin session.
+///
 /// **Permissions**
 /// - `rauthy_admin`
 #[utoipa::path(
@@ -75,6 +99,10 @@ pub async fn post_provider(
     principal
         .validate_api_key_or_admin_session(AccessGroup::AuthProviders, AccessRights::Create)?;
     payload.validate()?;
+    validate_admin_claim_change(
+        &principal,
+        payload.admin_claim_path.is_some() || payload.admin_claim_value.is_some(),
+    )?;
 
     if payload.issuer == PROVIDER_ATPROTO {
         return Err(ErrorResponse::new(
@@ -94,9 +122,95 @@ pub async fn post_provider(
     Ok(HttpResponse::Ok().json(ProviderResponse::try_from(provider)?))
 }
 
+/// GET export all upstream auth providers as JSON
+///
+/// ATProto is never exported. The secrets are only included, if a passphrase with at least 16
+/// characters is given in the `x-rauthy-export-passphrase` header. They are encrypted with a key
+/// derived from it, and the same passphrase is needed for the import.
+///
+/// **Permissions**
+/// - `rauthy_admin`
+#[utoipa::path(
+    get,
+    path = "/providers/export",
+    tag = "providers",
+    responses(
+        (status = 200, description = "OK", body = ProviderExport),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/providers/export")]
+pub async fn get_providers_export(
+    req: HttpRequest,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::AuthProviders, AccessRights::Read)?;
+
+    let export = AuthProvider::export(export_passphrase(&req)).await?;
+    Ok(HttpResponse::Ok()
+        .insert_header((CACHE_CONTROL, "no-store"))
+        .json(export))
+}
+
+/// POST import upstream auth providers from an export
+///
+/// Each provider is upserted by its `issuer` and `client_id`. Every entry goes through the same
+/// validation as a new provider, and the result is reported for each of them, so a single
+/// invalid entry does not fail the whole import. Without exported secrets, the current ones of
+/// existing providers are kept. Encrypted secrets need the passphrase from the export in the
+/// `x-rauthy-export-passphrase` header.
+///
+/// `admin_claim_path` and `admin_claim_value` can only be imported with an admin session.
+///
+/// **Permissions**
+/// - `rauthy_admin`
+#[utoipa::path(
+    post,
+    path = "/providers/import",
+    tag = "providers",
+    request_body = ProviderExport,
+    responses(
+        (status = 200, description = "OK", body = ProviderImportResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[post("/providers/import")]
+pub async fn post_providers_import(
+    req: HttpRequest,
+    Json(payload): Json<ProviderExport>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal
+        .validate_api_key_or_admin_session(AccessGroup::AuthProviders, AccessRights::Create)?;
+    principal
+        .validate_api_key_or_admin_session(AccessGroup::AuthProviders, AccessRights::Update)?;
+    validate_admin_claim_change(
+        &principal,
+        payload.providers.iter().any(|entry| {
+            entry.provider.admin_claim_path.is_some() || entry.provider.admin_claim_value.is_some()
+        }),
+    )?;
+
+    let res = AuthProvider::import(payload, export_passphrase(&req)).await?;
+    Ok(HttpResponse::Ok().json(res))
+}
+
+fn export_passphrase(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get(HEADER_EXPORT_PASSPHRASE)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from)
+}
+
 /// POST possible upstream auth provider config lookup
 ///
 /// This will try to autoconfigure and build and upstream auth provider by the given issuer URL.
+/// GitHub does not support the metadata lookup. For the issuer `github.com`, its fixed config
+/// is returned instead.
 ///
 /// **Permissions**
 /// - `rauthy_admin`
@@ -146,31 +260,120 @@ pub async fn post_provider_lookup(
 )]
 #[post("/providers/login")]
 pub async fn post_provider_login(
+    req: HttpRequest,
     Json(payload): Json<ProviderLoginRequest>,
     principal: ReqPrincipal,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_session_auth_or_init()?;
     payload.validate()?;
+    validation::validate_auth_param_limits(
+        &req,
+        &payload.client_id,
+        &payload.redirect_uri,
+        payload.state.as_deref(),
+        payload.nonce.as_deref(),
+    )?;
 
     let challenge = Pow::validate(&payload.pow)?;
     PowEntity::check_prevent_reuse(challenge.to_string()).await?;
 
-    let (cookie, xsrf_token, location) =
+    let (cookie, resume_cookie, xsrf_token, location) =
         rauthy_service::oidc::auth_providers::login_start::login_start(payload).await?;
 
-    Ok(HttpResponse::Accepted()
-        .insert_header((LOCATION, location))
-        .cookie(cookie)
-        .body(xsrf_token))
+    let mut res = HttpResponse::Accepted();
+    res.insert_header((LOCATION, location)).cookie(cookie);
+    if let Some(resume_cookie) = resume_cookie {
+        res.cookie(resume_cookie);
+    }
+    Ok(res.body(xsrf_token))
 }
 
 #[get("/providers/callback")]
 pub async fn get_provider_callback_html(req: HttpRequest) -> Result<HttpResponse, ErrorResponse> {
+    // The callback cookie has the same lifetime as the cached callback. If either one is gone,
+    // the upstream login took too long, and the user should not only find out after the UI
+    // has sent the `POST`.
+    let exists = match ApiCookie::from_req(&req, COOKIE_UPSTREAM_CALLBACK) {
+        Some(callback_id) => AuthProviderCallback::exists(callback_id).await?,
+        None => false,
+    };
+    if !exists {
+        let err = ErrorResponse::new(
+            ErrorResponseType::NotFound,
+            "Callback Code not found - timeout reached?",
+        );
+
+        // The resume cookie outlives the callback, which makes it possible to retry the login
+        // with the original parameters.
+        if let Some(resume) = AuthProviderResumeCookie::from_req(&req) {
+            warn!(
+                provider_id = resume.provider_id,
+                "Upstream login callback timed out - the provider timeout may be too short"
+            );
+            metrics::AUTH_CALLBACKS_EXPIRED
+                .with_label_values(&[&resume.provider_id])
+                .inc();
+            let retry_uri = resume.retry_uri();
+            return Ok(flow_error_response_with_retry(
+                &req,
+                err,
+                ErrorFlow::ProviderCallback,
+                &retry_uri,
+            )
+            .await);
+        }
+
+        return Ok(flow_error_response(&req, err, ErrorFlow::ProviderCallback).await);
+    }
+
     HtmlCached::AuthProviderCallback
         .handle(req, ThemeCssFull::find_theme_ts_rauthy().await?, true)
         .await
 }
 
+fn is_form_post(ctx: &GuardContext) -> bool {
+    ctx.header::<ContentType>()
+        .is_some_and(|ct| ct.0.essence_str() == "application/x-www-form-urlencoded")
+}
+
+/// Callback for upstream auth providers with `response_mode=form_post`, like Apple
+///
+/// The cross-site `POST` does not include the callback cookie. The optional `user` is stored with
+/// the callback, and the browser is redirected to the `GET` callback, which continues the login
+/// as usual. This endpoint shares the path with the JSON callback and is not part of the
+/// OpenAPI docs.
+#[post("/providers/callback", guard = "is_form_post")]
+pub async fn post_provider_callback_form(
+    Form(payload): Form<ProviderCallbackFormRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    payload.validate()?;
+
+    if let Some(user) = payload.user {
+        AuthProviderCallback::set_upstream_user(payload.state.clone(), user).await?;
+    }
+
+    let mut params = vec![("state", payload.state.as_str())];
+    if let Some(code) = &payload.code {
+        params.push(("code", code));
+    }
+    if let Some(error) = &payload.error {
+        params.push(("error", error));
+    }
+    let query = serde_urlencoded::to_string(params).map_err(|err| {
+        ErrorResponse::new(
+            ErrorResponseType::Internal,
+            format!("Cannot build the callback query: {err}"),
+        )
+    })?;
+
+    Ok(HttpResponse::SeeOther()
+        .insert_header((
+            LOCATION,
+            format!("{}?{query}", RauthyConfig::get().provider_callback_uri),
+        ))
+        .finish())
+}
+
 /// Callback for an upstream auth provider login
 ///
 /// **Permissions**
@@ -235,6 +438,7 @@ pub async fn post_provider_callback_handle(
 /// This will always unlink the currently logged-in user from its registered
 /// upstream auth provider. The user account must have been set up with at least
 /// a password or a passkey. Otherwise, this endpoint will return an error.
+/// Each unlink is logged as an Event.
 #[utoipa::path(
     delete,
     path = "/providers/link",
@@ -245,11 +449,31 @@ pub async fn post_provider_callback_handle(
     ),
 )]
 #[delete("/providers/link")]
-pub async fn delete_provider_link(principal: ReqPrincipal) -> Result<HttpResponse, ErrorResponse> {
+pub async fn delete_provider_link(
+    req: HttpRequest,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_session_auth()?;
 
     let user_id = principal.user_id()?.to_string();
+    provider_unlink(&req, user_id).await
+}
+
+/// Unlinks the given user from its upstream provider. The caller must make sure, that the user
+/// belongs to the current session.
+pub(crate) async fn provider_unlink(
+    req: &HttpRequest,
+    user_id: String,
+) -> Result<HttpResponse, ErrorResponse> {
     let user = User::provider_unlink(user_id).await?;
+
+    Event::user_provider_link(
+        format!("`{}` unlinked from its upstream auth provider", user.email),
+        real_ip(req)?,
+    )
+    .send()
+    .await?;
+
     Ok(HttpResponse::Ok().json(user.into_response(None)))
 }
 
@@ -275,6 +499,8 @@ pub async fn get_providers_minimal() -> Result<HttpResponse, ErrorResponse> {
 
 /// PUT update an upstream auth provider
 ///
+/// `admin_claim_path` and `admin_claim_value` can only be changed with an admin session.
+///
 /// **Permissions**
 /// - `rauthy_admin`
 #[utoipa::path(
@@ -297,6 +523,14 @@ pub async fn put_provider(
         .validate_api_key_or_admin_session(AccessGroup::AuthProviders, AccessRights::Update)?;
     payload.validate()?;
 
+    let id = id.into_inner();
+    let provider = AuthProvider::find(&id).await?;
+    validate_admin_claim_change(
+        &principal,
+        payload.admin_claim_path != provider.admin_claim_path
+            || payload.admin_claim_value != provider.admin_claim_value,
+    )?;
+
     if !payload.use_pkce && payload.client_secret.is_none() {
         return Err(ErrorResponse::new(
             ErrorResponseType::BadRequest,
@@ -312,31 +546,229 @@ pub async fn put_provider(
         ));
     }
 
-    AuthProvider::update(id.into_inner(), payload).await?;
+    AuthProvider::update(id, payload).await?;
     Ok(HttpResponse::Ok().finish())
 }
 
+/// The `admin_claim_path` and `admin_claim_value`, as well as role mappings to `rauthy_admin`,
+/// grant the `rauthy_admin` role during an upstream login. Changing them is only allowed with an
+/// admin session and never via API key.
+fn validate_admin_claim_change(principal: &Principal, changed: bool) -> Result<(), ErrorResponse> {
+    if !changed {
+        return Ok(());
+    }
+    if principal.api_key.is_some() {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::Forbidden,
+            "Upstream mappings to `rauthy_admin` cannot be changed via API key",
+        ));
+    }
+    principal.validate_admin_session()
+}
+
+#[inline]
+async fn is_admin_role(role_id: &str) -> Result<bool, ErrorResponse> {
+    Ok(Role::find(role_id).await?.name == RAUTHY_ADMIN_ROLE)
+}
+
+/// POST rotate the client secret for an upstream auth provider
+///
+/// The new secret is checked against the upstream `token_endpoint` first. It will only be saved,
+/// if the upstream did not reject it, or if `force` is set. The probe result is returned either
+/// way, with a `406` if the secret has not been saved. Each rotation is logged as an Event.
+///
+/// **Permissions**
+/// - `rauthy_admin`
+#[utoipa::path(
+    post,
+    path = "/providers/{id}/secret",
+    tag = "providers",
+    request_body = ProviderSecretRequest,
+    responses(
+        (status = 200, description = "Ok", body = ProviderSecretResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+        (
+            status = 406,
+            description = "NotAcceptable - the upstream rejected the secret",
+            body = ProviderSecretResponse
+        ),
+    ),
+)]
+#[post("/providers/{id}/secret")]
+pub async fn post_provider_secret(
+    req: HttpRequest,
+    id: web::Path<String>,
+    Json(payload): Json<ProviderSecretRequest>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal
+        .validate_api_key_or_admin_session(AccessGroup::AuthProviders, AccessRights::Update)?;
+    payload.validate()?;
+
+    let id = id.into_inner();
+    if id == PROVIDER_ATPROTO {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "ATProto does not use a client secret",
+        ));
+    }
+
+    let probe = AuthProvider::rotate_secret(&id, &payload.client_secret, payload.force).await?;
+    let persisted = probe.allows_persist(payload.force);
+
+    if persisted {
+        let triggered_by = if let Some(api_key) = &principal.api_key {
+            format!("API Key `{}`", api_key.name)
+        } else {
+            format!("user `{}`", principal.user_id()?)
+        };
+        Event::provider_secret_rotated(
+            format!(
+                "provider `{id}` by {triggered_by} (probe: {:?}{})",
+                probe.result,
+                if payload.force { ", forced" } else { "" }
+            ),
+            real_ip(&req)?,
+        )
+        .send()
+        .await?;
+    }
+
+    let res = probe.into_response(persisted);
+    if persisted {
+        Ok(HttpResponse::Ok().json(res))
+    } else {
+        Ok(HttpResponse::NotAcceptable().json(res))
+    }
+}
+
+/// POST test the claim mapping of an upstream auth provider
+///
+/// Runs the user lookup and all claim mappings of a login for the given ID token without
+/// persisting anything. The signature of the `mock_id_token` is NOT validated, which makes it
+/// possible to test a provider config with hand-crafted claims. Each test is logged as an Event.
+///
+/// **Permissions**
+/// - `rauthy_admin`
+#[utoipa::path(
+    post,
+    path = "/providers/{id}/test_callback",
+    tag = "providers",
+    request_body = ProviderTestCallbackRequest,
+    responses(
+        (status = 200, description = "Ok", body = ProviderTestCallbackResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[post("/providers/{id}/test_callback")]
+pub async fn post_provider_test_callback(
+    req: HttpRequest,
+    id: web::Path<String>,
+    Json(payload): Json<ProviderTestCallbackRequest>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal
+        .validate_api_key_or_admin_session(AccessGroup::AuthProviders, AccessRights::Update)?;
+    payload.validate()?;
+
+    let id = id.into_inner();
+    if id == PROVIDER_ATPROTO {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "ATProto does not use ID tokens",
+        ));
+    }
+    let provider = AuthProvider::find(&id).await?;
+
+    let bytes = AuthProviderIdClaims::self_as_bytes_from_token(&payload.mock_id_token)?;
+    let claims = AuthProviderIdClaims::try_from(bytes.as_slice())?;
+    let res = claims
+        .dry_run_update_user(&provider, payload.simulate_new_user)
+        .await?;
+
+    let triggered_by = if let Some(api_key) = &principal.api_key {
+        format!("API Key `{}`", api_key.name)
+    } else {
+        format!("user `{}`", principal.user_id()?)
+    };
+    Event::provider_test_callback(
+        format!("provider `{}` by {triggered_by}", provider.name),
+        real_ip(&req)?,
+    )
+    .send()
+    .await?;
+
+    Ok(HttpResponse::Ok().json(res))
+}
+
+/// POST test the connectivity to an upstream auth provider
+///
+/// Fetches the openid-configuration and, if a client secret is set, sends a `client_credentials`
+/// grant to the `token_endpoint`. Each step reports its own result and timing, which makes it
+/// possible to tell apart TLS, connection and credential errors. Nothing is persisted and the
+/// client secret is never part of the response.
+///
+/// **Permissions**
+/// - `rauthy_admin`
+#[utoipa::path(
+    post,
+    path = "/providers/{id}/test",
+    tag = "providers",
+    responses(
+        (status = 200, description = "Ok", body = ProviderTestResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[post("/providers/{id}/test")]
+pub async fn post_provider_test(
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal
+        .validate_api_key_or_admin_session(AccessGroup::AuthProviders, AccessRights::Update)?;
+
+    let id = id.into_inner();
+    if id == PROVIDER_ATPROTO {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "ATProto has no single upstream to test",
+        ));
+    }
+
+    let res = AuthProvider::find(&id).await?.test_connectivity().await?;
+    Ok(HttpResponse::Ok().json(res))
+}
+
 /// DELETE update an upstream auth provider
 ///
+/// If any users are still linked to this provider, the request will be rejected, unless
+/// `force=true` is given. Check `/providers/{id}/delete_safe` upfront.
+///
 /// **Permissions**
 /// - `rauthy_admin`
 #[utoipa::path(
     delete,
     path = "/providers/{id}",
     tag = "providers",
+    params(ProviderDeleteParams),
     responses(
         (status = 404, description = "NotFound", body = ErrorResponse),
+        (status = 406, description = "NotAcceptable - linked users to this provider", body = ErrorResponse),
     ),
 )]
 #[delete("/providers/{id}")]
 pub async fn delete_provider(
     id: web::Path<String>,
     principal: ReqPrincipal,
+    Query(params): Query<ProviderDeleteParams>,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal
         .validate_api_key_or_admin_session(AccessGroup::AuthProviders, AccessRights::Delete)?;
 
-    AuthProvider::delete(&id.into_inner()).await?;
+    AuthProvider::delete(&id.into_inner(), params.force == Some(true)).await?;
     Ok(HttpResponse::Ok().finish())
 }
 
@@ -374,6 +806,259 @@ pub async fn get_provider_delete_safe(
     }
 }
 
+/// GET all upstream group mappings for an auth provider
+///
+/// **Permissions**
+/// - `rauthy_admin`
+#[utoipa::path(
+    get,
+    path = "/providers/{id}/group_mappings",
+    tag = "providers",
+    responses(
+        (status = 200, description = "Ok", body = [ProviderGroupMappingResponse]),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/providers/{id}/group_mappings")]
+pub async fn get_provider_group_mappings(
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::AuthProviders, AccessRights::Read)?;
+
+    let mappings = AuthProviderGroupMapping::find_for_provider(&id.into_inner())
+        .await?
+        .into_iter()
+        .map(ProviderGroupMappingResponse::from)
+        .collect::<Vec<_>>();
+    Ok(HttpResponse::Ok().json(mappings))
+}
+
+/// POST create a new upstream group mapping for an auth provider
+///
+/// During each login via this provider, the `claim_key` from the ID token will be checked for
+/// the `upstream_group`. With `sync_mode: add_only`, the user will only be added to the Rauthy
+/// group. With `sync_mode: sync`, it will also be removed, if the upstream group is missing.
+///
+/// **Permissions**
+/// - `rauthy_admin`
+#[utoipa::path(
+    post,
+    path = "/providers/{id}/group_mappings",
+    tag = "providers",
+    request_body = ProviderGroupMappingRequest,
+    responses(
+        (status = 200, description = "Ok", body = ProviderGroupMappingResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[post("/providers/{id}/group_mappings")]
+pub async fn post_provider_group_mapping(
+    id: web::Path<String>,
+    Json(payload): Json<ProviderGroupMappingRequest>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal
+        .validate_api_key_or_admin_session(AccessGroup::AuthProviders, AccessRights::Create)?;
+    payload.validate()?;
+
+    let mapping = AuthProviderGroupMapping::create(id.into_inner(), payload).await?;
+    Ok(HttpResponse::Ok().json(ProviderGroupMappingResponse::from(mapping)))
+}
+
+/// PUT update an upstream group mapping for an auth provider
+///
+/// **Permissions**
+/// - `rauthy_admin`
+#[utoipa::path(
+    put,
+    path = "/providers/{id}/group_mappings/{mapping_id}",
+    tag = "providers",
+    request_body = ProviderGroupMappingRequest,
+    responses(
+        (status = 200, description = "Ok", body = ProviderGroupMappingResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[put("/providers/{id}/group_mappings/{mapping_id}")]
+pub async fn put_provider_group_mapping(
+    path: web::Path<(String, String)>,
+    Json(payload): Json<ProviderGroupMappingRequest>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal
+        .validate_api_key_or_admin_session(AccessGroup::AuthProviders, AccessRights::Update)?;
+    payload.validate()?;
+
+    let (id, mapping_id) = path.into_inner();
+    let mapping = AuthProviderGroupMapping::update(&id, &mapping_id, payload).await?;
+    Ok(HttpResponse::Ok().json(ProviderGroupMappingResponse::from(mapping)))
+}
+
+/// DELETE an upstream group mapping for an auth provider
+///
+/// Already assigned Rauthy groups will not be removed from any user.
+///
+/// **Permissions**
+/// - `rauthy_admin`
+#[utoipa::path(
+    delete,
+    path = "/providers/{id}/group_mappings/{mapping_id}",
+    tag = "providers",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[delete("/providers/{id}/group_mappings/{mapping_id}")]
+pub async fn delete_provider_group_mapping(
+    path: web::Path<(String, String)>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal
+        .validate_api_key_or_admin_session(AccessGroup::AuthProviders, AccessRights::Delete)?;
+
+    let (id, mapping_id) = path.into_inner();
+    AuthProviderGroupMapping::delete(&id, &mapping_id).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// GET all upstream role mappings for an auth provider
+///
+/// **Permissions**
+/// - `rauthy_admin`
+#[utoipa::path(
+    get,
+    path = "/providers/{id}/role_mappings",
+    tag = "providers",
+    responses(
+        (status = 200, description = "Ok", body = [ProviderRoleMappingResponse]),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/providers/{id}/role_mappings")]
+pub async fn get_provider_role_mappings(
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::AuthProviders, AccessRights::Read)?;
+
+    let mappings = AuthProviderRoleMapping::find_for_provider(&id.into_inner())
+        .await?
+        .into_iter()
+        .map(ProviderRoleMappingResponse::from)
+        .collect::<Vec<_>>();
+    Ok(HttpResponse::Ok().json(mappings))
+}
+
+/// POST create a new upstream role mapping for an auth provider
+///
+/// During each login via this provider, the JsonPath `claim_path` will be queried on the ID token
+/// claims. If any result matches the `claim_value`, the user will get the Rauthy role. Roles
+/// added this way will be removed again, as soon as the claim or the mapping is gone. Roles,
+/// which have been assigned manually, are never touched.
+///
+/// Mappings to `rauthy_admin` can only be created with an admin session.
+///
+/// **Permissions**
+/// - `rauthy_admin`
+#[utoipa::path(
+    post,
+    path = "/providers/{id}/role_mappings",
+    tag = "providers",
+    request_body = ProviderRoleMappingRequest,
+    responses(
+        (status = 200, description = "Ok", body = ProviderRoleMappingResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[post("/providers/{id}/role_mappings")]
+pub async fn post_provider_role_mapping(
+    id: web::Path<String>,
+    Json(payload): Json<ProviderRoleMappingRequest>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal
+        .validate_api_key_or_admin_session(AccessGroup::AuthProviders, AccessRights::Create)?;
+    payload.validate()?;
+    validate_admin_claim_change(&principal, is_admin_role(&payload.role_id).await?)?;
+
+    let mapping = AuthProviderRoleMapping::create(id.into_inner(), payload).await?;
+    Ok(HttpResponse::Ok().json(ProviderRoleMappingResponse::from(mapping)))
+}
+
+/// PUT update an upstream role mapping for an auth provider
+///
+/// Mappings from or to `rauthy_admin` can only be updated with an admin session.
+///
+/// **Permissions**
+/// - `rauthy_admin`
+#[utoipa::path(
+    put,
+    path = "/providers/{id}/role_mappings/{mapping_id}",
+    tag = "providers",
+    request_body = ProviderRoleMappingRequest,
+    responses(
+        (status = 200, description = "Ok", body = ProviderRoleMappingResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[put("/providers/{id}/role_mappings/{mapping_id}")]
+pub async fn put_provider_role_mapping(
+    path: web::Path<(String, String)>,
+    Json(payload): Json<ProviderRoleMappingRequest>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal
+        .validate_api_key_or_admin_session(AccessGroup::AuthProviders, AccessRights::Update)?;
+    payload.validate()?;
+
+    let (id, mapping_id) = path.into_inner();
+    let current = AuthProviderRoleMapping::find(&id, &mapping_id).await?;
+    // the claim of an existing admin mapping must not be changed either
+    let admin_changed =
+        is_admin_role(&current.role_id).await? || is_admin_role(&payload.role_id).await?;
+    validate_admin_claim_change(&principal, admin_changed)?;
+
+    let mapping = AuthProviderRoleMapping::update(&id, &mapping_id, payload).await?;
+    Ok(HttpResponse::Ok().json(ProviderRoleMappingResponse::from(mapping)))
+}
+
+/// DELETE an upstream role mapping for an auth provider
+///
+/// Roles, which have been added by this mapping, will be removed during the next login of each
+/// user.
+///
+/// **Permissions**
+/// - `rauthy_admin`
+#[utoipa::path(
+    delete,
+    path = "/providers/{id}/role_mappings/{mapping_id}",
+    tag = "providers",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[delete("/providers/{id}/role_mappings/{mapping_id}")]
+pub async fn delete_provider_role_mapping(
+    path: web::Path<(String, String)>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal
+        .validate_api_key_or_admin_session(AccessGroup::AuthProviders, AccessRights::Delete)?;
+
+    let (id, mapping_id) = path.into_inner();
+    AuthProviderRoleMapping::delete(&id, &mapping_id).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
 /// GET the uploaded image an auth provider
 #[utoipa::path(
     get,
@@ -519,14 +1204,35 @@ pub async fn delete_provider_img(
 )]
 #[post("/providers/{id}/link")]
 pub async fn post_provider_link(
+    req: HttpRequest,
     provider_id: web::Path<String>,
     principal: ReqPrincipal,
     Json(payload): Json<ProviderLoginRequest>,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_session_auth()?;
-    payload.validate()?;
 
     let user_id = principal.user_id()?.to_string();
+    provider_link_start(&req, user_id, provider_id.into_inner(), payload).await
+}
+
+/// Starts the upstream login for linking the given user to `provider_id`. The caller must make
+/// sure, that the user belongs to the current session. The link itself is bound to this session
+/// and only created with the callback in `login_finish()`.
+pub(crate) async fn provider_link_start(
+    req: &HttpRequest,
+    user_id: String,
+    provider_id: String,
+    payload: ProviderLoginRequest,
+) -> Result<HttpResponse, ErrorResponse> {
+    payload.validate()?;
+    validation::validate_auth_param_limits(
+        req,
+        &payload.client_id,
+        &payload.redirect_uri,
+        payload.state.as_deref(),
+        payload.nonce.as_deref(),
+    )?;
+
     let user = User::find(user_id).await?;
 
     // make sure the user is currently un-linked
@@ -539,18 +1245,21 @@ pub async fn post_provider_link(
 
     // set an encrypted cookie with the provider_id + user_id / email
     let link_cookie = AuthProviderLinkCookie {
-        provider_id: provider_id.into_inner(),
+        provider_id,
         user_id: user.id,
         user_email: user.email,
     };
 
     // directly redirect to the provider login page
-    let (login_cookie, xsrf_token, location) =
+    let (login_cookie, resume_cookie, xsrf_token, location) =
         rauthy_service::oidc::auth_providers::login_start::login_start(payload).await?;
 
-    Ok(HttpResponse::Accepted()
-        .insert_header((LOCATION, location))
+    let mut res = HttpResponse::Accepted();
+    res.insert_header((LOCATION, location))
         .cookie(login_cookie)
-        .cookie(link_cookie.build_cookie()?)
-        .body(xsrf_token))
+        .cookie(link_cookie.build_cookie()?);
+    if let Some(resume_cookie) = resume_cookie {
+        res.cookie(resume_cookie);
+    }
+    Ok(res.body(xsrf_token))
 }
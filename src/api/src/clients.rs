@@ -7,15 +7,18 @@ use actix_web::web::{Json, Query};
 use actix_web::{HttpRequest, HttpResponse, delete, get, post, put, web};
 use actix_web_lab::__reexports::futures_util::StreamExt;
 use rauthy_api_types::clients::{
-    ClientResponse, ClientSecretRequest, ClientSecretResponse, DynamicClientRequest,
-    DynamicClientResponse, NewClientRequest, UpdateClientRequest,
+    ClientJwksRequest, ClientJwksResponse, ClientResponse, ClientSecretRequest,
+    ClientSecretResponse, DynamicClientRequest, DynamicClientResponse, NewClientRequest,
+    UpdateClientRequest,
 };
 use rauthy_api_types::forward_auth::{ForwardAuthCallbackParams, ForwardAuthParams};
 use rauthy_api_types::generic::LogoParams;
-use rauthy_common::utils::real_ip_from_req;
+use rauthy_common::utils::{real_ip, secure_compare};
 use rauthy_data::entity::api_keys::{AccessGroup, AccessRights};
 use rauthy_data::entity::clients::Client;
+use rauthy_data::entity::clients_claims_webhook::ClientClaimsWebhook;
 use rauthy_data::entity::clients_dyn::ClientDyn;
+use rauthy_data::entity::clients_jwks::ClientJwks;
 use rauthy_data::entity::clients_scim::ClientScim;
 use rauthy_data::entity::failed_backchannel_logout::FailedBackchannelLogout;
 use rauthy_data::entity::logos::{Logo, LogoType};
@@ -56,7 +59,7 @@ pub async fn get_clients(principal: ReqPrincipal) -> Result<HttpResponse, ErrorR
     let mut res = Vec::new();
     clients
         .into_iter()
-        .for_each(|c| res.push(c.into_response(None)));
+        .for_each(|c| res.push(c.into_response(None, None)));
 
     Ok(HttpResponse::Ok().json(res))
 }
@@ -86,8 +89,9 @@ pub async fn get_client_by_id(
 
     let client = Client::find(path.into_inner()).await?;
     let scim = ClientScim::find_opt(client.id.clone()).await?;
+    let claims_webhook = ClientClaimsWebhook::find_opt(&client.id).await?;
 
-    Ok(HttpResponse::Ok().json(client.into_response(scim)))
+    Ok(HttpResponse::Ok().json(client.into_response(scim, claims_webhook)))
 }
 
 /// Returns the secret in cleartext for a given client by its *id*.
@@ -149,7 +153,7 @@ pub async fn post_clients(
     // The `NewClientRequest` does not allow setting up SCIM immediately - no need to trigger here
 
     let client = Client::create(payload).await?;
-    Ok(HttpResponse::Ok().json(client.into_response(None)))
+    Ok(HttpResponse::Ok().json(client.into_response(None, None)))
 }
 
 /// OIDC Dynamic Client Registration (if enabled)
@@ -178,7 +182,7 @@ pub async fn post_clients_dyn(
 
     if let Some(token) = &RauthyConfig::get().vars.dynamic_clients.reg_token {
         let bearer = helpers::get_bearer_token_from_header(req.headers())?;
-        if token != &bearer {
+        if !secure_compare(token, &bearer) {
             return Ok(HttpResponse::Unauthorized()
                 .insert_header((
                     WWW_AUTHENTICATE,
@@ -188,7 +192,7 @@ pub async fn post_clients_dyn(
                 .finish());
         }
     } else {
-        let ip = real_ip_from_req(&req)?;
+        let ip = real_ip(&req)?;
         ClientDyn::rate_limit_ip(ip).await?;
     }
 
@@ -299,18 +303,18 @@ pub async fn put_clients(
     payload.validate()?;
 
     let client_id = path.into_inner();
-    let (client, scim) = client::update_client(client_id, payload).await?;
+    let (client, scim, claims_webhook) = client::update_client(client_id, payload).await?;
     debug!("scim: {:?}", scim);
 
     let resp = if let Some((scim, needs_sync)) = scim {
-        let resp = client.into_response(Some(scim.clone()));
+        let resp = client.into_response(Some(scim.clone()), claims_webhook);
         debug!("scim needs sync: {:?}", needs_sync);
         if needs_sync {
             scim.sync_full().await?;
         }
         resp
     } else {
-        client.into_response(None)
+        client.into_response(None, claims_webhook)
     };
 
     Ok(HttpResponse::Ok().json(resp))
@@ -457,6 +461,139 @@ pub async fn delete_client_logo(
     Ok(HttpResponse::Ok().finish())
 }
 
+/// Returns the JWKS config for this client
+///
+/// The `keys` contain the currently usable signing keys. For a `jwks_uri`, these are the keys
+/// from the last successful fetch, if they are still cached.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/clients/{id}/jwks",
+    tag = "clients",
+    responses(
+        (status = 200, description = "Ok", body = ClientJwksResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[get("/clients/{id}/jwks")]
+pub async fn get_client_jwks(
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Clients, AccessRights::Read)?;
+
+    let jwks = find_client_jwks(id.as_str()).await?;
+    let keys = jwks.keys_cached().await?;
+
+    Ok(HttpResponse::Ok().json(jwks.into_response(keys)))
+}
+
+/// Sets the JWKS for this client
+///
+/// The JWKS is used to validate inbound signed requests from this client. It can either be given
+/// inline, or as a `jwks_uri`, which will be fetched and cached on demand. Only signing keys with
+/// a supported `kty` and `alg` will be used.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    put,
+    path = "/clients/{id}/jwks",
+    tag = "clients",
+    request_body = ClientJwksRequest,
+    responses(
+        (status = 200, description = "Ok", body = ClientJwksResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[put("/clients/{id}/jwks")]
+pub async fn put_client_jwks(
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+    Json(payload): Json<ClientJwksRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Clients, AccessRights::Update)?;
+    payload.validate()?;
+
+    let jwks = ClientJwks::upsert(id.into_inner(), payload).await?;
+    let keys = jwks.keys_cached().await?;
+
+    Ok(HttpResponse::Ok().json(jwks.into_response(keys)))
+}
+
+/// Deletes the JWKS for this client
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    delete,
+    path = "/clients/{id}/jwks",
+    tag = "clients",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[delete("/clients/{id}/jwks")]
+pub async fn delete_client_jwks(
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Clients, AccessRights::Delete)?;
+
+    ClientJwks::delete(id.as_str()).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Refreshes the JWKS for this client
+///
+/// Fetches the keys from the `jwks_uri` immediately and updates the cache, for instance after a
+/// key rotation on the client side. Returns an error if the `jwks_uri` cannot be reached or does
+/// not contain any valid keys.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/clients/{id}/jwks/refresh",
+    tag = "clients",
+    responses(
+        (status = 200, description = "Ok", body = ClientJwksResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[post("/clients/{id}/jwks/refresh")]
+pub async fn post_client_jwks_refresh(
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Clients, AccessRights::Update)?;
+
+    let jwks = find_client_jwks(id.as_str()).await?;
+    let keys = ClientJwks::refresh(&jwks.client_id).await?;
+
+    Ok(HttpResponse::Ok().json(jwks.into_response(keys)))
+}
+
+async fn find_client_jwks(client_id: &str) -> Result<ClientJwks, ErrorResponse> {
+    ClientJwks::find(client_id)
+        .await?
+        .ok_or_else(|| ErrorResponse::new(ErrorResponseType::NotFound, "This client has no JWKS"))
+}
+
 /// Generates a new client secret
 ///
 /// Generates a new secret for the given client id and sets the client to `confidential` too, if it was
@@ -134,3 +134,12 @@ pub async fn options_openid_configuration() -> HttpResponse {
         .insert_header((ACCESS_CONTROL_MAX_AGE, "31536000"))
         .finish()
 }
+
+#[options("/.well-known/oauth-authorization-server")]
+pub async fn options_oauth_authorization_server() -> HttpResponse {
+    HttpResponse::NoContent()
+        .insert_header((ACCESS_CONTROL_ALLOW_ORIGIN, "*"))
+        .insert_header((ACCESS_CONTROL_ALLOW_METHODS, "GET, OPTIONS"))
+        .insert_header((ACCESS_CONTROL_MAX_AGE, "31536000"))
+        .finish()
+}
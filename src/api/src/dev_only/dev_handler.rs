@@ -2,17 +2,22 @@ use crate::oidc;
 use crate::{auth_providers, users};
 use actix_web::{FromRequest, web};
 use actix_web::{HttpRequest, HttpResponse};
+use chrono::Utc;
 use futures::StreamExt;
 use rauthy_api_types::auth_providers::ProviderCallbackRequest;
 use rauthy_api_types::oidc::{LoginRequest, LogoutRequest};
 use rauthy_api_types::users::NewUserRegistrationRequest;
+use rauthy_data::cache_layer::{self, CacheName};
+use rauthy_data::entity::auth_providers::AuthProviderCallback;
 use rauthy_data::entity::browser_id::{BrowserId, BrowserIdSetNew};
+use rauthy_data::entity::magic_links::MagicLink;
 use rauthy_data::entity::principal::Principal;
 use rauthy_data::html::templates::HtmlTemplate;
 use rauthy_data::rauthy_config::RauthyConfig;
 use rauthy_error::ErrorResponse;
 use rauthy_error::ErrorResponseType;
 use rauthy_service::oidc::logout;
+use serde_json::json;
 use tokio::fs;
 use validator::Validate;
 
@@ -99,6 +104,26 @@ pub async fn post_dev_only_endpoints(
             let session = principal.and_then(|p| p.validate_session_auth().ok().cloned());
             logout::post_logout_handle(req, params, session).await
         }
+        "maintenance" => {
+            // This endpoint is only used in integration tests.
+            // It runs the cleanup of the maintenance scheduler right away instead of waiting for
+            // its next interval.
+            let now = Utc::now().timestamp();
+            let magic_links = MagicLink::delete_expired(now).await?;
+            let auth_provider_callbacks = AuthProviderCallback::delete_expired(now).await?;
+            Ok(HttpResponse::Ok().json(json!({
+                "auth_provider_callbacks": auth_provider_callbacks,
+                "magic_links": magic_links,
+            })))
+        }
+        "provider_callback_cache_delete" => {
+            // This endpoint is only used in integration tests.
+            // It removes an `AuthProviderCallback` from the cache only, like it would happen
+            // with a restart or a login that continues on another node.
+            let callback_id = String::from_utf8(bytes.to_vec())?;
+            cache_layer::delete(CacheName::AuthProviderCallback, callback_id).await?;
+            Ok(HttpResponse::Ok().finish())
+        }
         "providers_callback" => {
             let payload = serde_json::from_slice::<ProviderCallbackRequest>(bytes)?;
             let principal = web::ReqData::<Principal>::extract(&req).await?;
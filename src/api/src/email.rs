@@ -5,7 +5,9 @@ use chrono::Utc;
 use rauthy_api_types::email_jobs::{
     EmailContentType, EmailJobFilterType, EmailJobRequest, EmailJobResponse,
 };
+use rauthy_api_types::generic::SmtpTestResponse;
 use rauthy_common::sanitize_html::sanitize_html;
+use rauthy_data::email::mailer;
 use rauthy_data::entity::email_jobs::{EmailJob, EmailJobFilter, EmailJobStatus};
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use validator::Validate;
@@ -158,3 +160,28 @@ pub async fn post_email_job_cancel(
 
     Ok(HttpResponse::Ok().finish())
 }
+
+/// Test the SMTP config
+///
+/// Opens a real connection to the configured SMTP relay and does the handshake, TLS and
+/// authentication, but never sends a message. Always returns a `200`, a possible error is
+/// part of the response.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/admin/config/test_smtp",
+    tag = "email",
+    responses(
+        (status = 200, description = "Ok", body = SmtpTestResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+    ),
+)]
+#[post("/admin/config/test_smtp")]
+pub async fn post_test_smtp(principal: ReqPrincipal) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_admin_session()?;
+
+    Ok(HttpResponse::Ok().json(mailer::test_smtp().await))
+}
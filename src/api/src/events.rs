@@ -4,7 +4,7 @@ use actix_web::{HttpRequest, HttpResponse, Responder, get, post};
 use actix_web_lab::sse;
 use chrono::Utc;
 use rauthy_api_types::events::{EventResponse, EventsListenParams, EventsRequest};
-use rauthy_common::utils::real_ip_from_req;
+use rauthy_common::utils::real_ip;
 use rauthy_data::entity::api_keys::{AccessGroup, AccessRights};
 use rauthy_data::events::event::Event;
 use rauthy_data::events::listener::EventRouterMsg;
@@ -74,7 +74,7 @@ pub async fn sse_events(
     principal.validate_api_key_or_group_admin(AccessGroup::Events, AccessRights::Read)?;
     params.validate()?;
 
-    let ip = real_ip_from_req(&req)?.to_string();
+    let ip = real_ip(&req)?.to_string();
     let params = params.into_inner();
     let (tx, rx) = mpsc::channel(10);
 
@@ -120,7 +120,7 @@ pub async fn post_event_test(
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_api_key_or_admin_session(AccessGroup::Events, AccessRights::Create)?;
 
-    Event::test(real_ip_from_req(&req)?).send().await?;
+    Event::test(real_ip(&req)?).send().await?;
 
     #[cfg(debug_assertions)]
     if RauthyConfig::get().vars.dev.dev_mode {
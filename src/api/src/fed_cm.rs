@@ -6,19 +6,21 @@ use chrono::Utc;
 use rauthy_api_types::clients::EphemeralClientRequest;
 use rauthy_api_types::fed_cm::{FedCMAssertionRequest, FedCMClientMetadataRequest};
 use rauthy_common::constants::{COOKIE_SESSION_FED_CM, HEADER_ALLOW_ALL_ORIGINS, HEADER_JSON};
-use rauthy_common::utils::real_ip_from_req;
+use rauthy_common::utils::real_ip;
 use rauthy_data::ListenScheme;
 use rauthy_data::api_cookie::ApiCookie;
 use rauthy_data::entity::clients::Client;
 use rauthy_data::entity::fed_cm::{
-    FedCMAccount, FedCMAccounts, FedCMClientMetadata, FedCMIdPConfig, FedCMLoginStatus,
-    FedCMTokenResponse, WebIdentity,
+    FedCMAccounts, FedCMClientMetadata, FedCMIdPConfig, FedCMLoginStatus, FedCMTokenResponse,
+    WebIdentity,
 };
 use rauthy_data::entity::sessions::Session;
 use rauthy_data::entity::users::User;
 use rauthy_data::rauthy_config::RauthyConfig;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
-use rauthy_service::token_set::{AuthCodeFlow, AuthTime, DeviceCodeFlow, TokenNonce, TokenSet};
+use rauthy_service::token_set::{
+    self, AuthCodeFlow, AuthTime, DeviceCodeFlow, RefreshTokenFingerprint, TokenNonce,
+};
 use tracing::{debug, error, warn};
 use validator::Validate;
 
@@ -42,7 +44,7 @@ pub async fn get_fed_cm_accounts(req: HttpRequest) -> Result<HttpResponse, Error
     is_fed_cm_enabled()?;
     is_web_identity_fetch(&req)?;
 
-    let (login_status, user_id) = login_status_from_req(&req).await;
+    let (login_status, user_ids) = login_status_from_req(&req).await;
     if login_status == FedCMLoginStatus::LoggedOut {
         return Ok(HttpResponse::Unauthorized()
             .insert_header(FedCMLoginStatus::LoggedOut.as_header_pair())
@@ -51,11 +53,23 @@ pub async fn get_fed_cm_accounts(req: HttpRequest) -> Result<HttpResponse, Error
             }));
     }
 
-    let user = User::find_for_fed_cm_validated(user_id).await?;
-    let account = FedCMAccount::build(user);
-    let accounts = FedCMAccounts {
-        accounts: vec![account],
-    };
+    // A single disabled or deleted user must not hide the other accounts. We only return an
+    // error, if none of them is usable.
+    let mut users = Vec::with_capacity(user_ids.len());
+    let mut last_err = None;
+    for user_id in user_ids {
+        match User::find_for_fed_cm_validated(user_id).await {
+            Ok(user) => users.push(user),
+            Err(err) => last_err = Some(err),
+        }
+    }
+    if users.is_empty()
+        && let Some(err) = last_err
+    {
+        return Err(err);
+    }
+
+    let accounts = FedCMAccounts::build(users);
     Ok(HttpResponse::Ok()
         .insert_header(FedCMLoginStatus::LoggedIn.as_header_pair())
         .json(accounts))
@@ -244,7 +258,7 @@ pub async fn post_fed_cm_token(
     is_web_identity_fetch(&req)?;
     payload.validate()?;
 
-    let (login_status, user_id) = login_status_from_req(&req).await;
+    let (login_status, user_ids) = login_status_from_req(&req).await;
     if login_status == FedCMLoginStatus::LoggedOut {
         return Ok(HttpResponse::Unauthorized()
             .insert_header(FedCMLoginStatus::LoggedOut.as_header_pair())
@@ -274,24 +288,25 @@ pub async fn post_fed_cm_token(
     debug!("built origin header for client: {:?}", origin_header.1);
 
     // find and check the user
-    let user = User::find_for_fed_cm_validated(user_id).await?;
-    if payload.account_id != user.id {
+    if !user_ids.contains(&payload.account_id) {
         debug!(
-            "payload.account_id != user.id -> {} != {}",
-            payload.account_id, user.id
+            "payload.account_id {} does not match any active session user {:?}",
+            payload.account_id, user_ids
         );
         return Err(ErrorResponse::new(
             ErrorResponseType::WWWAuthenticate("invalid-user".to_string()),
-            "The `account_id` does not match the `user_id` from the active session",
+            "The `account_id` does not match the `user_id` from any active session",
         ));
     }
+    let user = User::find_for_fed_cm_validated(payload.account_id).await?;
 
     // We are good - issue a TokenSet
-    let ts = TokenSet::from_user(
+    let ts = token_set::from_user(
         &user,
         &client,
         AuthTime::given(user.last_login.unwrap_or_else(|| Utc::now().timestamp())),
         None,
+        RefreshTokenFingerprint::from_req(&client, &req)?,
         payload.nonce.map(TokenNonce),
         None,
         None,
@@ -418,40 +433,44 @@ fn client_origin_header(
 }
 
 #[inline(always)]
-async fn login_status_from_req(req: &HttpRequest) -> (FedCMLoginStatus, String) {
-    match ApiCookie::from_req(req, COOKIE_SESSION_FED_CM) {
-        None => {
-            debug!("FedCM session cookie not found -> user_id is logged-out",);
-            (FedCMLoginStatus::LoggedOut, String::default())
+/// Returns the IDs of all users with a valid FedCM session in this browser. A browser may send
+/// multiple session cookies for the same domain, which is why all of them are checked. The IDs are
+/// de-duplicated, because the same user may have multiple sessions.
+async fn login_status_from_req(req: &HttpRequest) -> (FedCMLoginStatus, Vec<String>) {
+    let sids = ApiCookie::all_from_req(req, COOKIE_SESSION_FED_CM);
+    if sids.is_empty() {
+        debug!("FedCM session cookie not found -> user_id is logged-out");
+        return (FedCMLoginStatus::LoggedOut, Vec::default());
+    }
+
+    let session_timeout = RauthyConfig::get().vars.fedcm.session_timeout;
+    let ip = real_ip(req).ok();
+    let mut user_ids: Vec<String> = Vec::with_capacity(sids.len());
+
+    for sid in sids {
+        let Ok(session) = Session::find(sid).await else {
+            debug!("FedCM session not found -> skipping");
+            continue;
+        };
+
+        if !session.is_valid(session_timeout, ip, req.path()) {
+            debug!(
+                "FedCM session is invalid -> user_id {:?} is logged-out",
+                session.user_id
+            );
+            continue;
         }
-        Some(sid) => {
-            let session = match Session::find(sid).await {
-                Ok(s) => s,
-                Err(_) => {
-                    debug!("FedCM session not found -> user_id is logged-out",);
-                    return (FedCMLoginStatus::LoggedOut, String::default());
-                }
-            };
-
-            if session.is_valid(
-                RauthyConfig::get().vars.fedcm.session_timeout,
-                real_ip_from_req(req).ok(),
-                req.path(),
-            ) {
-                (
-                    FedCMLoginStatus::LoggedIn,
-                    session.user_id.unwrap_or_default(),
-                )
-            } else {
-                debug!(
-                    "FedCM session is invalid -> user_id {:?} is logged-out",
-                    session.user_id
-                );
-                (
-                    FedCMLoginStatus::LoggedOut,
-                    session.user_id.unwrap_or_default(),
-                )
-            }
+
+        if let Some(user_id) = session.user_id
+            && !user_ids.contains(&user_id)
+        {
+            user_ids.push(user_id);
         }
     }
+
+    if user_ids.is_empty() {
+        (FedCMLoginStatus::LoggedOut, user_ids)
+    } else {
+        (FedCMLoginStatus::LoggedIn, user_ids)
+    }
 }
@@ -3,24 +3,31 @@ use actix_web::http::header;
 use actix_web::http::header::{CACHE_CONTROL, CONTENT_TYPE, HeaderValue};
 use actix_web::web::{Json, Query};
 use actix_web::{HttpRequest, HttpResponse, Responder, get, post, put, web};
+use actix_web_lab::sse;
 use chrono::Utc;
 use cryptr::EncKeys;
 use rauthy_api_types::generic::{
     AppVersionResponse, Argon2ParamsResponse, EncKeyMigrateRequest, EncKeysResponse,
-    HealthResponse, I18nConfigResponse, LoginTimeResponse, PasswordHashTimesRequest,
-    PasswordPolicyRequest, PasswordPolicyResponse, SearchParams, SearchParamsType,
+    HealthResponse, I18nConfigResponse, LoginTimeResponse, MaintenanceModeRequest,
+    MaintenanceModeResponse, PasswordHashTimesRequest, PasswordPolicyRequest,
+    PasswordPolicyResponse, PiiMigrationResponse, SearchParams, SearchParamsIdx, SearchParamsType,
+    SecretsMigrateParams, SecretsMigrationProgress, SystemInfoResponse,
 };
+use rauthy_api_types::oidc::{TokenPreviewRequest, TokenPreviewResponse};
 use rauthy_common::compression::compress_br;
 use rauthy_common::constants::{
-    APP_START, APPLICATION_JSON, CSRF_HEADER, HEADER_ALLOW_ALL_ORIGINS, IDX_LOGIN_TIME,
-    PWD_CSRF_HEADER, RAUTHY_VERSION,
+    APP_START, APPLICATION_JSON, CSRF_HEADER, HEADER_ALLOW_ALL_ORIGINS, HEADER_SEARCH_EXACT_MATCH,
+    IDX_LOGIN_TIME, PWD_CSRF_HEADER, RAUTHY_VERSION,
 };
-use rauthy_common::utils::real_ip_from_req;
+use rauthy_common::shutdown;
+use rauthy_common::utils::real_ip;
+use rauthy_data::cache_warmup;
 use rauthy_data::database::{Cache, DB};
 use rauthy_data::entity::api_keys::{AccessGroup, AccessRights};
 use rauthy_data::entity::app_version::LatestAppVersion;
 use rauthy_data::entity::ip_blacklist::IpBlacklist;
 use rauthy_data::entity::is_db_alive;
+use rauthy_data::entity::maintenance_mode::MaintenanceMode;
 use rauthy_data::entity::password::{PasswordHashTimes, PasswordPolicy};
 use rauthy_data::entity::pow::PowEntity;
 use rauthy_data::entity::sessions::Session;
@@ -28,44 +35,56 @@ use rauthy_data::entity::users::User;
 use rauthy_data::events::event::Event;
 use rauthy_data::ipgeo;
 use rauthy_data::language::Language;
+use rauthy_data::pii;
 use rauthy_data::rauthy_config::RauthyConfig;
+use rauthy_data::secrets_migration;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
-use rauthy_service::{encryption, suspicious_request_block};
+use rauthy_service::{encryption, suspicious_request_block, token_preview};
 use semver::Version;
+use std::borrow::Cow;
 use std::fmt::Write;
 use std::ops::Sub;
 use std::str::FromStr;
 use std::sync::LazyLock;
+use std::time::Duration;
 use tracing::{error, info, warn};
 use validator::Validate;
 
 pub static I18N_CONFIG: LazyLock<String> = LazyLock::new(|| {
-    let common = RauthyConfig::get().vars.i18n.filter_lang_common
-        .iter()
-        .map(|v| {
-                if !["de", "en", "fr", "ko", "nb", "nl", "ru", "uk", "zhhans"].contains(&v.as_ref()) {
-                    panic!("Invalid config for `i18n.filter_lang_common`.\nAllowed values: en de fr ko nb nl ru uk zhhans\nfound: {v}");
+    let validated = |name: &str, values: &[Cow<'static, str>], allowed: &[&str]| {
+        values
+            .iter()
+            .map(|v| {
+                if !allowed.contains(&v.as_ref()) {
+                    panic!(
+                        "Invalid config for `i18n.{name}`\nAllowed values: {}\nfound: {v}",
+                        allowed.join(" ")
+                    );
                 }
-                Language::from(v.as_ref()).into()
-        })
-        .collect::<Vec<_>>();
+                Language::from(v.as_ref())
+            })
+            .collect::<Vec<_>>()
+    };
 
-    let admin = RauthyConfig::get()
-        .vars
-        .i18n
-        .filter_lang_admin
-        .iter()
-        .map(|v| {
-            if !["de", "en", "fr", "ko", "nb", "nl", "ru", "uk", "zhhans"].contains(&v.as_ref()) {
-                panic!(
-                    "Invalid config for `i18n.filter_lang_admin`\nAllowed values: en de fr ko nb nl ru uk zhhans\nfound: {v}",
-                );
-            }
-            Language::from(v.as_ref()).into()
-        })
-        .collect::<Vec<_>>();
+    let all = Language::all().map(|l| l.as_str()).collect::<Vec<_>>();
+    let built_in = Language::BUILT_IN.map(|l| l.as_str());
+    let vars = &RauthyConfig::get().vars.i18n;
+    let mut common = validated("filter_lang_common", &vars.filter_lang_common, &all);
+    let admin = validated("filter_lang_admin", &vars.filter_lang_admin, &built_in);
+
+    // Custom languages are always available on user-facing pages. The Admin UI only exists
+    // for the built-in languages.
+    for lang in Language::all().filter(|l| !l.is_built_in()) {
+        if !common.contains(&lang) {
+            common.push(lang);
+        }
+    }
 
-    serde_json::to_string(&I18nConfigResponse { common, admin }).unwrap()
+    serde_json::to_string(&I18nConfigResponse {
+        common: common.into_iter().map(From::from).collect(),
+        admin: admin.into_iter().map(From::from).collect(),
+    })
+    .unwrap()
 });
 
 pub static TIMEZONES_BR: LazyLock<Vec<u8>> = LazyLock::new(|| {
@@ -175,7 +194,7 @@ pub async fn post_migrate_enc_key(
     principal.validate_api_key_or_admin_session(AccessGroup::Secrets, AccessRights::Update)?;
     payload.validate()?;
 
-    let ip = real_ip_from_req(&req)?;
+    let ip = real_ip(&req)?;
     encryption::migrate_encryption_alg(&payload.key_id).await?;
 
     RauthyConfig::get()
@@ -187,6 +206,94 @@ pub async fn post_migrate_enc_key(
     Ok(HttpResponse::Ok().finish())
 }
 
+/// Re-encrypts all secrets from one encryption key to another
+///
+/// Migrates all API Keys, Auth Provider secrets, Client secrets and JWKs, which are encrypted
+/// with `from_key_id`, to `to_key_id` in batches of 100. Each batch runs inside its own
+/// transaction and is rolled back on error. In contrast to `/encryption/migrate`, the JWKs are
+/// not rotated.
+///
+/// The progress is streamed as SSE after each batch. The migration keeps running in the
+/// background, even if the client disconnects.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/encryption/migrate_secrets",
+    tag = "generic",
+    params(SecretsMigrateParams),
+    responses(
+        (status = 200, description = "Ok", body = SecretsMigrationProgress),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[post("/encryption/migrate_secrets")]
+pub async fn post_migrate_secrets(
+    req: HttpRequest,
+    principal: ReqPrincipal,
+    params: Query<SecretsMigrateParams>,
+) -> Result<impl Responder, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Secrets, AccessRights::Update)?;
+    params.validate()?;
+
+    let params = params.into_inner();
+    secrets_migration::validate_key_ids(&params.from_key_id, &params.to_key_id)?;
+
+    let ip = real_ip(&req)?;
+    let (tx, rx) = tokio::sync::mpsc::channel(10);
+    tokio::spawn(secrets_migration::migrate_secrets(
+        params.from_key_id,
+        params.to_key_id,
+        ip,
+        tx,
+    ));
+
+    Ok(
+        sse::Sse::from_infallible_receiver(rx).with_keep_alive(Duration::from_secs(
+            RauthyConfig::get().vars.server.see_keep_alive as u64,
+        )),
+    )
+}
+
+/// Encrypts all existing plaintext user PII
+///
+/// Needs to be executed once after `encryption.pii_at_rest` has been enabled. Users are
+/// converted in batches of 100, each inside its own transaction. Only users without an E-Mail
+/// hash are selected, so it can simply be started again, if it has been interrupted.
+///
+/// Until all users have been converted, users without a hash cannot be found by their E-Mail.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/encryption/migrate_pii",
+    tag = "generic",
+    responses(
+        (status = 200, description = "Ok", body = PiiMigrationResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[post("/encryption/migrate_pii")]
+pub async fn post_migrate_pii(
+    req: HttpRequest,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Secrets, AccessRights::Update)?;
+
+    let users = pii::migrate_plaintext().await?;
+    Event::secrets_migration(format!("PII of {users} users encrypted"), real_ip(&req)?)
+        .send()
+        .await?;
+
+    Ok(HttpResponse::Ok().json(PiiMigrationResponse { users }))
+}
+
 /// Returns the languages to show in the UI
 #[utoipa::path(
     get,
@@ -243,6 +350,94 @@ pub async fn get_login_time(principal: ReqPrincipal) -> Result<HttpResponse, Err
     Ok(HttpResponse::Ok().json(resp))
 }
 
+/// Returns the current maintenance mode config
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/maintenance_mode",
+    tag = "generic",
+    responses(
+        (status = 200, description = "Ok", body = MaintenanceModeResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+    ),
+)]
+#[get("/maintenance_mode")]
+pub async fn get_maintenance_mode(principal: ReqPrincipal) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Generic, AccessRights::Read)?;
+
+    let maintenance = MaintenanceMode::find().await?;
+    Ok(HttpResponse::Ok().json(MaintenanceModeResponse::from(maintenance)))
+}
+
+/// Update the maintenance mode
+///
+/// While the maintenance is active, new logins and token requests are rejected with a `503`.
+/// Existing sessions and tokens keep working. If an `end` is given, the maintenance mode will
+/// exit automatically.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    put,
+    path = "/maintenance_mode",
+    tag = "generic",
+    request_body = MaintenanceModeRequest,
+    responses(
+        (status = 200, description = "Ok", body = MaintenanceModeResponse),
+        (status = 400, description = "BadRequest"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+    ),
+)]
+#[put("/maintenance_mode")]
+pub async fn put_maintenance_mode(
+    principal: ReqPrincipal,
+    Json(payload): Json<MaintenanceModeRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Generic, AccessRights::Update)?;
+    payload.validate()?;
+
+    let current = MaintenanceMode::find().await?;
+    let mut maintenance = MaintenanceMode::try_from_req(payload)?;
+    // keep track of an already running maintenance to not send duplicate events
+    maintenance.started = current.started;
+    maintenance.save().await?;
+    maintenance.apply_transition().await?;
+
+    Ok(HttpResponse::Ok().json(MaintenanceModeResponse::from(maintenance)))
+}
+
+/// Get a maintenance bypass cookie
+///
+/// The cookie allows logins for the current admin during an active maintenance. It is valid
+/// until the planned end of the maintenance, but at most for 12 hours.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/maintenance_mode/bypass",
+    tag = "generic",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+    ),
+)]
+#[post("/maintenance_mode/bypass")]
+pub async fn post_maintenance_mode_bypass(
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_admin_session()?;
+
+    let maintenance = MaintenanceMode::find().await?;
+    let cookie = maintenance.bypass_cookie(principal.user_id()?, Utc::now().timestamp());
+    Ok(HttpResponse::Ok().cookie(cookie).finish())
+}
+
 /// Find best the settings for argon2id
 ///
 /// Does multiple argon2id computations to find the best settings for the given target values.
@@ -274,6 +469,43 @@ pub async fn post_password_hash_times(
         .map(|r| HttpResponse::Ok().json(r))
 }
 
+/// Preview the claims a user would receive for a client
+///
+/// This is a dry-run of the token issuance to debug claim mappings. It returns the claims of the
+/// ID token, the access token and the userinfo after all `claims_emission` rules, custom scope
+/// attribute mappings and the claims webhook have been applied, together with a trace of which
+/// rule contributed each claim.
+///
+/// No token will be signed or issued. The claims webhook of the client will be called though,
+/// if one is configured.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/debug/token_preview",
+    tag = "generic",
+    request_body = TokenPreviewRequest,
+    responses(
+        (status = 200, description = "Ok", body = TokenPreviewResponse),
+        (status = 400, description = "BadRequest"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "NotFound"),
+    ),
+)]
+#[post("/debug/token_preview")]
+pub async fn post_token_preview(
+    principal: ReqPrincipal,
+    Json(payload): Json<TokenPreviewRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_admin_session()?;
+    payload.validate()?;
+
+    let resp = token_preview::preview(payload).await?;
+    Ok(HttpResponse::Ok().json(resp))
+}
+
 /// Returns the currently configured password policy
 ///
 /// **Permissions**
@@ -358,6 +590,9 @@ pub async fn post_pow() -> Result<HttpResponse, ErrorResponse> {
 }
 
 /// Search endpoint used for searching from the Admin UI with active server side pagination
+///
+/// With `encryption.pii_at_rest`, users can only be found by their full E-Mail. Such responses
+/// contain the `x-rauthy-search-exact-match: true` header.
 #[utoipa::path(
     get,
     path = "/search",
@@ -384,7 +619,11 @@ pub async fn get_search(
         }
         SearchParamsType::User => {
             let res = User::search(&params.idx, &params.q, limit).await?;
-            Ok(HttpResponse::Ok().json(res))
+            let mut resp = HttpResponse::Ok();
+            if params.idx == SearchParamsIdx::Email && pii::is_enabled() {
+                resp.insert_header((HEADER_SEARCH_EXACT_MATCH, "true"));
+            }
+            Ok(resp.json(res))
         }
     }
 }
@@ -488,17 +727,27 @@ pub async fn get_health() -> impl Responder {
 }
 
 /// Ready endpoint for kubernetes / docker ready checks.
+///
+/// Returns a `503` until the cache warmup has finished and as soon as a graceful shutdown has
+/// been started, so load balancers stop sending new traffic while in-flight requests are drained.
+/// An active maintenance mode does not have any impact, because existing sessions must keep
+/// working.
 #[utoipa::path(
     get,
     path = "/ready",
     tag = "health",
     responses(
         (status = 200, description = "Ok"),
+        (status = 503, description = "ServiceUnavailable"),
     ),
 )]
 #[get("/ready")]
 pub async fn get_ready() -> impl Responder {
-    HttpResponse::Ok().finish()
+    if shutdown::is_draining() || !cache_warmup::is_done() {
+        HttpResponse::ServiceUnavailable().finish()
+    } else {
+        HttpResponse::Ok().finish()
+    }
 }
 
 /// Catch all - redirects from root to the "real root" /auth/v1/
@@ -507,7 +756,7 @@ pub async fn get_ready() -> impl Responder {
 #[get("/{_:.*}")]
 pub async fn catch_all(req: HttpRequest) -> Result<HttpResponse, ErrorResponse> {
     let path = req.path();
-    let ip = real_ip_from_req(&req)?;
+    let ip = real_ip(&req)?;
 
     let vars = &RauthyConfig::get().vars.suspicious_requests;
     if vars.log && path.len() > 1 {
@@ -594,6 +843,34 @@ pub async fn get_version() -> Result<HttpResponse, ErrorResponse> {
     Ok(HttpResponse::Ok().json(resp))
 }
 
+/// Returns static information about this Rauthy instance
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/system_info",
+    tag = "generic",
+    responses(
+        (status = 200, description = "Ok", body = SystemInfoResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+    ),
+)]
+#[get("/system_info")]
+pub async fn get_system_info(principal: ReqPrincipal) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Generic, AccessRights::Read)?;
+
+    Ok(HttpResponse::Ok().json(SystemInfoResponse {
+        version: RAUTHY_VERSION.to_string(),
+        wellknown_extra_claims: RauthyConfig::get()
+            .vars
+            .server
+            .wellknown_extra_claims
+            .clone(),
+    }))
+}
+
 /// Returns the remote IP that Rauthy has extracted for this client
 ///
 /// During development, with `debug_assertions` enabled, this endpoint returns the full set of HTTP
@@ -613,9 +890,7 @@ pub async fn get_whoami(req: HttpRequest) -> String {
     if RauthyConfig::get().vars.access.whoami_headers {
         let mut s = String::with_capacity(32);
 
-        let ip = real_ip_from_req(&req)
-            .map(|ip| ip.to_string())
-            .unwrap_or_default();
+        let ip = real_ip(&req).map(|ip| ip.to_string()).unwrap_or_default();
         let _ = writeln!(s, "{ip}\n");
 
         for (k, v) in req.headers() {
@@ -630,8 +905,6 @@ pub async fn get_whoami(req: HttpRequest) -> String {
 
         s
     } else {
-        real_ip_from_req(&req)
-            .map(|ip| ip.to_string())
-            .unwrap_or_default()
+        real_ip(&req).map(|ip| ip.to_string()).unwrap_or_default()
     }
 }
@@ -1,11 +1,16 @@
 use crate::Assets;
 use actix_web::http::header;
+use actix_web::web::Query;
 use actix_web::{HttpRequest, HttpResponse, get, web};
+use rauthy_api_types::oidc::DeviceHtmlParams;
+use rauthy_data::entity::devices::DeviceAuthCode;
 use rauthy_data::entity::theme::ThemeCssFull;
 use rauthy_data::html::HtmlCached;
+use rauthy_data::html::flow_error::{ErrorFlow, flow_error_response};
 use rauthy_data::rauthy_config::RauthyConfig;
-use rauthy_error::ErrorResponse;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
 use std::borrow::Cow;
+use validator::Validate;
 
 #[get("/{_:.*}")]
 pub async fn get_static_assets(
@@ -216,7 +221,24 @@ pub async fn get_admin_users_html(req: HttpRequest) -> Result<HttpResponse, Erro
 }
 
 #[get("/device")]
-pub async fn get_device_html(req: HttpRequest) -> Result<HttpResponse, ErrorResponse> {
+pub async fn get_device_html(
+    req: HttpRequest,
+    Query(params): Query<DeviceHtmlParams>,
+) -> Result<HttpResponse, ErrorResponse> {
+    // An expired `verification_uri_complete` should show a proper error right away instead of
+    // failing only after the user has logged in.
+    let is_valid = params.validate().is_ok();
+    if let Some(code) = params.code {
+        let found = is_valid && DeviceAuthCode::find(code).await?.is_some();
+        if !found {
+            let err = ErrorResponse::new(
+                ErrorResponseType::NotFound,
+                "Device code not found - timeout reached?",
+            );
+            return Ok(flow_error_response(&req, err, ErrorFlow::DeviceVerification).await);
+        }
+    }
+
     HtmlCached::Device
         .handle(req, ThemeCssFull::find_theme_ts_rauthy().await?, true)
         .await
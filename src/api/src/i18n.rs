@@ -0,0 +1,208 @@
+use crate::ReqPrincipal;
+use actix_web::http::header::{CACHE_CONTROL, CONTENT_TYPE};
+use actix_web::web::Json;
+use actix_web::{HttpResponse, get, post, put, web};
+use rauthy_api_types::i18n::{
+    I18nOverride, I18nOverridesRequest, I18nPage, I18nPageKeysResponse, I18nPreviewRequest,
+};
+use rauthy_common::constants::{APPLICATION_JSON, HEADER_HTML};
+use rauthy_data::entity::api_keys::{AccessGroup, AccessRights};
+use rauthy_data::entity::auth_providers::AuthProviderTemplate;
+use rauthy_data::entity::i18n_overrides::I18nOverrides;
+use rauthy_data::entity::logos::{Logo, LogoType};
+use rauthy_data::entity::theme::ThemeCssFull;
+use rauthy_data::html::templates::{
+    AccountHtml, AuthorizeHtml, DeviceHtml, FrontendAction, HtmlTemplate,
+};
+use rauthy_data::language::Language;
+use rauthy_data::language_pack::LanguagePacks;
+use rauthy_data::rauthy_config::RauthyConfig;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use validator::Validate;
+
+/// Returns the UI translations for a custom language
+///
+/// The translations are a flat JSON object like `{ "common.cancel": "..." }`, which the UI
+/// merges over the built-in English values. Built-in languages are compiled into the UI and
+/// return a `404`.
+#[utoipa::path(
+    get,
+    path = "/i18n/languages/{tag}",
+    tag = "generic",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[get("/i18n/languages/{tag}")]
+pub async fn get_i18n_language(tag: web::Path<String>) -> Result<HttpResponse, ErrorResponse> {
+    let Some(pack) = LanguagePacks::find(&tag) else {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::NotFound,
+            "Custom language does not exist",
+        ));
+    };
+
+    Ok(HttpResponse::Ok()
+        .insert_header((CONTENT_TYPE, APPLICATION_JSON))
+        .insert_header((CACHE_CONTROL, "max-age=300, stale-while-revalidate=2592000"))
+        .body(pack.ui_json()))
+}
+
+/// Returns all existing translation overrides
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/i18n/overrides",
+    tag = "generic",
+    responses(
+        (status = 200, description = "Ok", body = [I18nOverride]),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/i18n/overrides")]
+pub async fn get_i18n_overrides(principal: ReqPrincipal) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Generic, AccessRights::Read)?;
+
+    let overrides = I18nOverrides::find()
+        .await?
+        .overrides
+        .into_iter()
+        .map(I18nOverride::from)
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(overrides))
+}
+
+/// Replaces all translation overrides
+///
+/// Overrides are merged over the built-in translations when a page is rendered. Each key which
+/// has no override for a language falls back to the built-in value.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    put,
+    path = "/i18n/overrides",
+    tag = "generic",
+    request_body = I18nOverridesRequest,
+    responses(
+        (status = 200, description = "Ok", body = [I18nOverride]),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[put("/i18n/overrides")]
+pub async fn put_i18n_overrides(
+    principal: ReqPrincipal,
+    Json(payload): Json<I18nOverridesRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Generic, AccessRights::Update)?;
+    payload.validate()?;
+
+    let overrides = I18nOverrides::save(payload.overrides.into_iter().map(From::from).collect())
+        .await?
+        .overrides
+        .into_iter()
+        .map(I18nOverride::from)
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(overrides))
+}
+
+/// Returns all overridable keys for each page
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/i18n/overrides/keys",
+    tag = "generic",
+    responses(
+        (status = 200, description = "Ok", body = [I18nPageKeysResponse]),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/i18n/overrides/keys")]
+pub async fn get_i18n_override_keys(
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Generic, AccessRights::Read)?;
+    Ok(HttpResponse::Ok().json(I18nOverrides::keys()))
+}
+
+/// Renders a page with pending translation overrides
+///
+/// The pending overrides are merged over the saved ones without persisting them. The login page
+/// is rendered for the `rauthy` client without an active login request.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/i18n/overrides/preview",
+    tag = "generic",
+    request_body = I18nPreviewRequest,
+    responses(
+        (status = 200, description = "Ok", body = String),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[post("/i18n/overrides/preview")]
+pub async fn post_i18n_overrides_preview(
+    principal: ReqPrincipal,
+    Json(payload): Json<I18nPreviewRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Generic, AccessRights::Read)?;
+    payload.validate()?;
+
+    let lang = Language::from(payload.lang);
+    let i18n = I18nOverrides::find()
+        .await?
+        .merged(payload.overrides.into_iter().map(From::from).collect())?
+        .template_json(payload.page, &lang);
+    let theme_ts = ThemeCssFull::find_theme_ts_rauthy().await?;
+
+    let body = match payload.page {
+        I18nPage::Account => {
+            let providers = AuthProviderTemplate::get_all_json_template().await?;
+            AccountHtml::build(
+                &lang,
+                theme_ts,
+                &[
+                    HtmlTemplate::AuthProviders(providers),
+                    HtmlTemplate::I18nOverrides(i18n),
+                    HtmlTemplate::UserValues,
+                ],
+            )
+        }
+        I18nPage::Device => DeviceHtml::build(&lang, theme_ts, i18n),
+        I18nPage::Login => {
+            let providers = AuthProviderTemplate::get_all_json_template().await?;
+            let logo_updated = Logo::find_updated("rauthy", &LogoType::Client).await?;
+            AuthorizeHtml::build(
+                &lang,
+                "rauthy",
+                theme_ts,
+                &[
+                    HtmlTemplate::AuthProviders(providers),
+                    HtmlTemplate::ClientName("Rauthy".to_string()),
+                    HtmlTemplate::ClientUrl(String::default()),
+                    HtmlTemplate::ClientLogoUpdated(logo_updated),
+                    HtmlTemplate::IsRegOpen(RauthyConfig::get().vars.user_registration.enable),
+                    HtmlTemplate::I18nOverrides(i18n),
+                    HtmlTemplate::LoginAction(FrontendAction::None),
+                ],
+            )
+        }
+    };
+
+    Ok(HttpResponse::Ok().insert_header(HEADER_HTML).body(body))
+}
@@ -36,10 +36,13 @@ pub mod fed_cm;
 pub mod generic;
 pub mod groups;
 pub mod html;
+pub mod i18n;
 pub mod kv;
+pub mod migration;
 pub mod oidc;
 pub mod openapi;
 pub mod pam;
+pub mod reports;
 pub mod roles;
 pub mod scopes;
 pub mod sessions;
@@ -100,6 +103,21 @@ pub async fn map_auth_step(
     new_user_created: NewFederatedUserCreated,
     // the bool for Ok() is true is the password has been hashed
     // the bool for Err() means if we need to add a login delay (and none otherwise for better UX)
+) -> Result<HttpResponse, ErrorResponse> {
+    // the session id has been rotated -> the client must receive the new cookies
+    let rotated_session = auth_step.rotated_session().cloned();
+    let mut resp = map_auth_step_inner(auth_step, req, new_user_created)?;
+    if let Some(session) = rotated_session {
+        session.add_cookies(&mut resp)?;
+    }
+
+    Ok(resp)
+}
+
+fn map_auth_step_inner(
+    auth_step: AuthStep,
+    req: &HttpRequest,
+    new_user_created: NewFederatedUserCreated,
 ) -> Result<HttpResponse, ErrorResponse> {
     // we will only get here after a successful login -> always return logged-in header
     let fed_cm_header = FedCMLoginStatus::LoggedIn.as_header_pair();
@@ -0,0 +1,278 @@
+use crate::ReqPrincipal;
+use actix_web::http::header::CONTENT_DISPOSITION;
+use actix_web::mime::APPLICATION_OCTET_STREAM;
+use actix_web::web::{Payload, Query};
+use actix_web::{HttpRequest, HttpResponse, get, post};
+use bytes::Bytes;
+use chrono::Utc;
+use futures::StreamExt;
+use rauthy_api_types::migration::{
+    KeycloakImportAction, KeycloakImportEntity, KeycloakImportParams, KeycloakImportResponse,
+    MigrationImportParams, MigrationImportResponse,
+};
+use rauthy_common::constants::HEADER_MIGRATION_PASSPHRASE;
+use rauthy_common::utils::real_ip;
+use rauthy_data::events::event::Event;
+use rauthy_data::migration::archive::{ArchiveExport, ArchiveImport};
+use rauthy_data::migration::keycloak::KeycloakImport;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use tokio::task;
+use tracing::error;
+
+/// A single line contains at most 100 users including their passkeys, which stays well below.
+const ARCHIVE_LINE_LIMIT: usize = 16 * 1024 * 1024;
+/// Realm exports are a single JSON document including all users.
+const KEYCLOAK_REALM_LIMIT: usize = 64 * 1024 * 1024;
+
+/// Export a migration archive
+///
+/// Creates an encrypted archive of all users (including password hashes and passkeys), clients,
+/// scopes, roles, groups, custom user attributes and auth providers, which can be imported into
+/// another instance. The passphrase must be provided with the `x-rauthy-migration-passphrase`
+/// header and must be at least 16 characters long.
+///
+/// Sessions, tokens, events and user pictures are not part of the archive. The `rauthy` client
+/// is skipped as well, because each instance creates its own.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/migration/export",
+    tag = "backup",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/migration/export")]
+pub async fn get_migration_export(
+    req: HttpRequest,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_admin_session()?;
+
+    let passphrase = passphrase_from_req(&req)?;
+    let ip = real_ip(&req)?;
+    let user_id = principal.user_id()?.to_string();
+
+    let (tx, rx) = futures::channel::mpsc::channel(1);
+    let export = ArchiveExport::new(passphrase, tx).await?;
+
+    task::spawn(async move {
+        match export.run().await {
+            Ok(counts) => {
+                let sections = counts
+                    .iter()
+                    .map(|(section, count)| format!("{count} {section}"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let text = format!("{sections} by user `{user_id}`");
+                if let Err(err) = Event::migration_export(text, ip).send().await {
+                    error!(?err, "Sending MigrationExport event");
+                }
+            }
+            Err(err) => {
+                error!(?err, "Exporting migration archive");
+            }
+        }
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type(APPLICATION_OCTET_STREAM)
+        .insert_header((
+            CONTENT_DISPOSITION,
+            format!(
+                "attachment; filename=\"rauthy_migration_{}.jsonl\"",
+                Utc::now().timestamp()
+            ),
+        ))
+        .streaming(rx.map(|line| Ok::<Bytes, String>(Bytes::from(line)))))
+}
+
+/// Import a migration archive
+///
+/// Imports an archive created with `/migration/export` on another instance. The archive must
+/// be sent as the raw body together with the `x-rauthy-migration-passphrase` header. It can only
+/// be imported into the same minor version it was created with.
+///
+/// Entries which already exist, either by id or by their unique name / E-Mail, are handled
+/// depending on the `strategy`. The import is NOT transactional. If it fails in the middle, all
+/// entries that have been processed until then stay in the database.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/migration/import",
+    tag = "backup",
+    params(MigrationImportParams),
+    responses(
+        (status = 200, description = "Ok", body = MigrationImportResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 406, description = "NotAccepted", body = ErrorResponse),
+    ),
+)]
+#[post("/migration/import")]
+pub async fn post_migration_import(
+    req: HttpRequest,
+    principal: ReqPrincipal,
+    params: Query<MigrationImportParams>,
+    payload: Payload,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_admin_session()?;
+
+    let passphrase = passphrase_from_req(&req)?;
+    let mut import = ArchiveImport::new(passphrase, params.strategy)?;
+
+    let res = import_lines(&mut import, payload).await;
+    ArchiveImport::invalidate_caches().await?;
+    res?;
+    let summary = import.finish()?;
+
+    let sections = summary
+        .sections
+        .iter()
+        .map(|s| {
+            format!(
+                "{} {} ({} skipped, {} overwritten)",
+                s.imported, s.section, s.skipped, s.overwritten
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    Event::migration_import(
+        format!("{sections} by user `{}`", principal.user_id()?),
+        real_ip(&req)?,
+    )
+    .send()
+    .await?;
+
+    Ok(HttpResponse::Ok().json(summary))
+}
+
+/// Import a Keycloak realm export
+///
+/// Maps the roles, groups, clients and users from a Keycloak realm export, created with
+/// `kc.sh export` or the partial export in the admin console, onto Rauthy. The export must be
+/// sent as the raw JSON body. The response contains a report for each entity, including warnings
+/// about features that cannot be mapped. With `dry_run=true`, only the report is built.
+///
+/// Realm roles and groups are created by their name, clients by their `clientId`, and users by
+/// their E-Mail. Existing entities are never modified, so the import can safely be repeated.
+/// Password hashes (PBKDF2, bcrypt, Argon2) are kept and replaced with an Argon2ID hash after
+/// the next successful login. Client secrets cannot be migrated, new ones are generated.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/migration/keycloak",
+    tag = "backup",
+    params(KeycloakImportParams),
+    responses(
+        (status = 200, description = "Ok", body = KeycloakImportResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[post("/migration/keycloak")]
+pub async fn post_migration_keycloak(
+    req: HttpRequest,
+    principal: ReqPrincipal,
+    params: Query<KeycloakImportParams>,
+    mut payload: Payload,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_admin_session()?;
+
+    let mut buf = Vec::with_capacity(64 * 1024);
+    while let Some(chunk) = payload.next().await {
+        buf.extend_from_slice(&chunk?);
+        if buf.len() > KEYCLOAK_REALM_LIMIT {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "The realm export exceeds the size limit",
+            ));
+        }
+    }
+    let import = KeycloakImport::parse(&buf)?;
+    drop(buf);
+
+    if params.dry_run {
+        return Ok(HttpResponse::Ok().json(import.run(true).await?));
+    }
+
+    let res = import.run(false).await;
+    ArchiveImport::invalidate_caches().await?;
+    let report = res?;
+
+    let created = |entities: &[KeycloakImportEntity]| {
+        entities
+            .iter()
+            .filter(|e| e.action == KeycloakImportAction::Create)
+            .count()
+    };
+    Event::migration_import(
+        format!(
+            "Keycloak realm `{}`: {} roles, {} groups, {} clients, {} users by user `{}`",
+            report.realm,
+            created(&report.roles),
+            created(&report.groups),
+            created(&report.clients),
+            created(&report.users),
+            principal.user_id()?
+        ),
+        real_ip(&req)?,
+    )
+    .send()
+    .await?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+fn passphrase_from_req(req: &HttpRequest) -> Result<String, ErrorResponse> {
+    req.headers()
+        .get(HEADER_MIGRATION_PASSPHRASE)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from)
+        .ok_or_else(|| {
+            ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("Missing '{HEADER_MIGRATION_PASSPHRASE}' header"),
+            )
+        })
+}
+
+async fn import_lines(
+    import: &mut ArchiveImport,
+    mut payload: Payload,
+) -> Result<(), ErrorResponse> {
+    let mut buf = Vec::with_capacity(64 * 1024);
+
+    while let Some(chunk) = payload.next().await {
+        buf.extend_from_slice(&chunk?);
+
+        while let Some(pos) = buf.iter().position(|b| *b == b'\n') {
+            let line = buf.drain(..=pos).collect::<Vec<_>>();
+            import.process_line(&line[..pos]).await?;
+        }
+
+        if buf.len() > ARCHIVE_LINE_LIMIT {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Archive line exceeds the size limit",
+            ));
+        }
+    }
+
+    if !buf.trim_ascii().is_empty() {
+        import.process_line(&buf).await?;
+    }
+
+    Ok(())
+}
@@ -10,45 +10,46 @@ use actix_web::{HttpRequest, HttpResponse, HttpResponseBuilder, ResponseError, g
 use chrono::Utc;
 use rauthy_api_types::oidc::{
     AuthRequest, CertsParams, DeviceAcceptedRequest, DeviceCodeResponse, DeviceGrantRequest,
-    DeviceVerifyRequest, DeviceVerifyResponse, JWKSCerts, JWKSPublicKeyCerts, LoginRefreshRequest,
-    LoginRequest, LogoutRequest, OAuth2ErrorResponse, OAuth2ErrorTypeResponse, SessionInfoResponse,
-    TokenInfo, TokenRequest, TokenRevocationRequest, TokenValidationRequest,
+    DeviceVerifyRequest, DeviceVerifyResponse, JWKSCerts, JWKSPublicKeyCerts,
+    JwkHsmReferenceRequest, JwkHsmReferenceResponse, JwkImportRequest, JwkResponse, JwkStatus,
+    LoginRefreshRequest, LoginRequest, LogoutRequest, OAuth2ErrorResponse, OAuth2ErrorTypeResponse,
+    SessionInfoResponse, TokenErrorResponse, TokenInfo, TokenRequest, TokenRevocationRequest,
+    TokenSet, TokenValidationRequest, UpstreamLogoutCompleteRequest, UserinfoRequest,
 };
 use rauthy_api_types::sessions::SessionState;
 use rauthy_api_types::users::{Userinfo, WebauthnLoginResponse};
 use rauthy_common::compression::{compress_br_dyn, compress_gzip};
 use rauthy_common::constants::{
     APPLICATION_JSON, COOKIE_MFA, GRANT_TYPE_DEVICE_CODE, HEADER_HTML, HEADER_RETRY_NOT_BEFORE,
-    PROVIDER_ATPROTO,
 };
-use rauthy_common::utils::real_ip_from_req;
+use rauthy_common::utils::real_ip;
 use rauthy_data::api_cookie::ApiCookie;
 use rauthy_data::entity::api_keys::{AccessGroup, AccessRights};
-use rauthy_data::entity::auth_providers::{
-    AuthProvider, AuthProviderTemplate, NewFederatedUserCreated,
-};
+use rauthy_data::entity::auth_providers::NewFederatedUserCreated;
 use rauthy_data::entity::browser_id::{BrowserId, BrowserIdSetNew};
 use rauthy_data::entity::clients::Client;
 use rauthy_data::entity::devices::DeviceAuthCode;
 use rauthy_data::entity::fed_cm::FedCMLoginStatus;
 use rauthy_data::entity::ip_rate_limit::DeviceIpRateLimit;
-use rauthy_data::entity::jwk::{JWKS, JWKSPublicKey, JwkKeyPair, JwkKeyPairType};
-use rauthy_data::entity::logos::{Logo, LogoType};
+use rauthy_data::entity::jwk::{JWKS, JWKSPublicKey, Jwk, JwkKeyPair, JwkKeyPairType};
+use rauthy_data::entity::jwk_hsm::JwkHsmReference;
 use rauthy_data::entity::pow::PowEntity;
 use rauthy_data::entity::sessions::Session;
 use rauthy_data::entity::theme::ThemeCssFull;
 use rauthy_data::entity::users::User;
 use rauthy_data::entity::webauthn::WebauthnCookie;
-use rauthy_data::entity::well_known::WellKnown;
-use rauthy_data::html::templates::{
-    AuthorizeHtml, CallbackHtml, Error1Html, ErrorHtml, FrontendAction, HtmlTemplate,
-};
+use rauthy_data::entity::well_known::{AuthorizationServerMetadata, DiscoveryDocument, WellKnown};
+use rauthy_data::events::event::Event;
+use rauthy_data::html::flow_error::{ErrorFlow, flow_error_response};
+use rauthy_data::html::login_page_context::LoginPageContext;
+use rauthy_data::html::templates::{AuthorizeHtml, CallbackHtml, FrontendAction, HtmlTemplate};
 use rauthy_data::language::Language;
 use rauthy_data::rauthy_config::RauthyConfig;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
-use rauthy_service::oidc::{authorize, logout, token_info, token_revocation, userinfo, validation};
-use rauthy_service::token_set::TokenSet;
-use rauthy_service::{login_delay, oidc};
+use rauthy_service::oidc::{
+    authorize, helpers, logout, token_info, token_revocation, userinfo, validation,
+};
+use rauthy_service::{break_glass, login_delay, oidc};
 use spow::pow::Pow;
 use std::borrow::Cow;
 use std::ops::Add;
@@ -78,10 +79,23 @@ pub async fn get_authorize(
     Query(params): Query<AuthRequest>,
     principal: ReqPrincipal,
 ) -> Result<HttpResponse, ErrorResponse> {
-    params.validate()?;
-
+    if let Err(err) = params.validate() {
+        return Ok(flow_error_response(&req, err.into(), ErrorFlow::Authorize).await);
+    }
+    if let Err(err) = validation::validate_scope_limits(&req, &params.client_id, &params.scope) {
+        return Ok(flow_error_response(&req, err, ErrorFlow::Authorize).await);
+    }
+    if let Err(err) = validation::validate_auth_param_limits(
+        &req,
+        &params.client_id,
+        &params.redirect_uri,
+        params.state.as_deref(),
+        params.nonce.as_deref(),
+    ) {
+        return Ok(flow_error_response(&req, err, ErrorFlow::Authorize).await);
+    }
     let principal = principal.into_inner();
-    let lang = Language::try_from(&req).unwrap_or_default();
+    let lang = Language::resolve(&req, params.ui_locales.as_deref());
 
     let (client, origin_header) = match validation::validate_auth_req_param(
         &req,
@@ -95,17 +109,26 @@ pub async fn get_authorize(
         Ok(res) => res,
         Err(err) => {
             error!("Client used invalid request parameters: {:?}", err.message);
-            let status = err.status_code();
-            let body = Error1Html::build(
-                &lang,
-                ThemeCssFull::find_theme_ts_rauthy().await?,
-                status,
-                err.message,
-            );
-            return Ok(ErrorHtml::response(body, status));
+            return Ok(flow_error_response(&req, err, ErrorFlow::Authorize).await);
         }
     };
-    let theme_ts = ThemeCssFull::find_theme_ts(client.id.clone()).await?;
+
+    // The `redirect_uri` is valid at this point, so the client can handle the error itself.
+    let response_type_none =
+        match validation::validate_response_type(&client, &params.response_type) {
+            Ok(is_none) => is_none,
+            Err(err) => {
+                error!("Client used an invalid response_type: {}", err.message);
+                let loc = authorize::redirect_location_without_code(
+                    &params.redirect_uri,
+                    Some("unsupported_response_type"),
+                    params.state.as_deref(),
+                );
+                return Ok(HttpResponse::Found()
+                    .insert_header(("location", loc))
+                    .finish());
+            }
+        };
 
     // check prompt and max_age to possibly force a new session
     let mut force_new_session = if params
@@ -143,68 +166,103 @@ pub async fn get_authorize(
         }
     }
 
-    // check for `prompt=none` and redirect if we don't have a valid session
-    if !force_new_session
-        && params
-            .prompt
-            .as_ref()
-            .map(|p| p.contains("none"))
-            .unwrap_or(false)
-        && principal.validate_session_auth().is_err()
-    {
-        let mut loc = params.redirect_uri;
-        let state_len = params.state.as_ref().map(|s| 7 + s.len()).unwrap_or(0);
-        loc.reserve(1 + 20 + state_len);
-
-        // make sure URIs that already contain params work fine
-        if loc.contains('?') {
-            loc.push('&');
-        } else {
-            loc.push('?');
-        }
-        loc.push_str("error=login_required");
+    let prompt_consent = params
+        .prompt
+        .as_ref()
+        .map(|p| p.contains("consent"))
+        .unwrap_or(false);
 
-        if let Some(state) = params.state {
-            loc.push_str("&state=");
-            loc.push_str(&state);
+    // A still valid session must never silently log a user into a client, which is restricted
+    // to other roles / groups. Without a session, this is checked during the login.
+    let access_denied = match principal.validate_session_auth() {
+        Ok(session) if client.has_access_restrictions() => {
+            let user = User::find(session.user_id.clone().unwrap_or_default()).await?;
+            client.validate_user_access(&user).err()
         }
+        _ => None,
+    };
 
+    // `response_type=none` only authenticates the user without issuing a code or creating a
+    // new session, which means we can never show the login page
+    if response_type_none {
+        let error = if prompt_consent {
+            Some("consent_required")
+        } else if force_new_session || principal.validate_session_auth().is_err() {
+            Some("login_required")
+        } else if access_denied.is_some() {
+            Some("access_denied")
+        } else {
+            None
+        };
+        let loc = authorize::redirect_location_without_code(
+            &params.redirect_uri,
+            error,
+            params.state.as_deref(),
+        );
         return Ok(HttpResponse::Found()
             .insert_header(("location", loc))
             .finish());
     }
 
-    let auth_providers_json = AuthProviderTemplate::get_all_json_template().await?;
-    let logo_updated = Logo::find_updated(&client.id, &LogoType::Client).await?;
+    // check for `prompt=none` and redirect if we don't have a valid session
+    let prompt_none = params
+        .prompt
+        .as_ref()
+        .map(|p| p.contains("none"))
+        .unwrap_or(false);
+    if !force_new_session && prompt_none {
+        let error = if principal.validate_session_auth().is_err() {
+            Some("login_required")
+        } else if access_denied.is_some() {
+            Some("access_denied")
+        } else {
+            None
+        };
+        if error.is_some() {
+            let loc = authorize::redirect_location_without_code(
+                &params.redirect_uri,
+                error,
+                params.state.as_deref(),
+            );
+            return Ok(HttpResponse::Found()
+                .insert_header(("location", loc))
+                .finish());
+        }
+    }
+
+    // the immediate refresh below would only fail after the login, show the error right away
+    if !force_new_session
+        && !prompt_consent
+        && let Some(err) = access_denied
+    {
+        return Ok(flow_error_response(&req, err, ErrorFlow::Authorize).await);
+    }
 
-    let mut templates = Vec::with_capacity(8);
-    templates.push(HtmlTemplate::AuthProviders(auth_providers_json));
+    let login_ctx = LoginPageContext::find(&client.id, &lang).await?;
+
+    let mut templates = Vec::with_capacity(9);
+    templates.push(HtmlTemplate::AuthProviders(login_ctx.auth_providers_json));
     templates.push(HtmlTemplate::ClientName(client.name.unwrap_or_default()));
     templates.push(HtmlTemplate::ClientUrl(
         client.client_uri.unwrap_or_default(),
     ));
-    templates.push(HtmlTemplate::ClientLogoUpdated(logo_updated));
+    templates.push(HtmlTemplate::ClientLogoUpdated(login_ctx.logo_updated));
     templates.push(HtmlTemplate::IsRegOpen(
         RauthyConfig::get().vars.user_registration.enable,
     ));
-    if RauthyConfig::get().vars.atproto.enable {
-        let provider_atproto = AuthProvider::find_by_iss(PROVIDER_ATPROTO.to_string()).await?;
-        templates.push(HtmlTemplate::AtprotoId(provider_atproto.id));
+    templates.push(HtmlTemplate::I18nOverrides(login_ctx.i18n_overrides_json));
+    if let Some(atproto_id) = login_ctx.atproto_id {
+        templates.push(HtmlTemplate::AtprotoId(atproto_id));
     }
 
     // if the user is still authenticated and everything is valid -> immediate refresh
-    let prompt_consent = params
-        .prompt
-        .as_ref()
-        .map(|p| p.contains("consent"))
-        .unwrap_or(false);
     if !force_new_session && !prompt_consent && principal.validate_session_auth().is_ok() {
         let csrf = principal.get_session_csrf_token()?;
 
         templates.push(HtmlTemplate::CsrfToken(csrf.to_string()));
         templates.push(HtmlTemplate::LoginAction(FrontendAction::Refresh));
 
-        let body = AuthorizeHtml::build(&lang, &client.id, theme_ts, &templates);
+        let body = AuthorizeHtml::build(&lang, &client.id, login_ctx.theme_ts, &templates);
         build_authorize_resp(accept_encoding, body, None, origin_header, browser_id)
     } else {
         // check if we can re-use a still valid session or need to create a new one
@@ -215,26 +273,24 @@ pub async fn get_authorize(
             } else {
                 Session::new(
                     RauthyConfig::get().vars.lifetimes.session_lifetime,
-                    Some(real_ip_from_req(&req)?),
+                    Some(real_ip(&req)?),
                 )
             }
         } else {
             Session::new(
                 RauthyConfig::get().vars.lifetimes.session_lifetime,
-                Some(real_ip_from_req(&req)?),
+                Some(real_ip(&req)?),
             )
         };
 
         if let Err(err) = session.upsert().await {
-            let status = err.status_code();
-            let body = Error1Html::build(&lang, theme_ts, status, err.message);
-            return Ok(ErrorHtml::response(body, status));
+            return Ok(flow_error_response(&req, err, ErrorFlow::Authorize).await);
         }
 
         templates.push(HtmlTemplate::CsrfToken(session.csrf_token.clone()));
         templates.push(HtmlTemplate::LoginAction(action));
 
-        let body = AuthorizeHtml::build(&lang, &client.id, theme_ts, &templates);
+        let body = AuthorizeHtml::build(&lang, &client.id, login_ctx.theme_ts, &templates);
         build_authorize_resp(
             accept_encoding,
             body,
@@ -334,6 +390,16 @@ pub async fn post_authorize_handle(
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_session_auth_or_init()?;
     payload.validate()?;
+    if let Some(scopes) = &payload.scopes {
+        validation::validate_scope_limits(&req, &payload.client_id, &scopes.join(" "))?;
+    }
+    validation::validate_auth_param_limits(
+        &req,
+        &payload.client_id,
+        &payload.redirect_uri,
+        payload.state.as_deref(),
+        payload.nonce.as_deref(),
+    )?;
 
     let start = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
 
@@ -392,8 +458,78 @@ pub async fn post_authorize_handle(
         }
     };
 
-    let ip = real_ip_from_req(&req)?;
-    login_delay::handle_login_delay(ip, start, res, has_password_been_hashed).await
+    let ip = real_ip(&req)?;
+    login_delay::handle_login_delay(&req, ip, start, res, has_password_been_hashed).await
+}
+
+/// POST login credentials for the break-glass admin login
+///
+/// A recovery path for the Admin UI, if the only upstream auth provider is misconfigured or
+/// down. It only exists if `auth_providers.break_glass_login` is set, or if all upstream
+/// providers have been failing their health checks for `auth_providers.break_glass_auto_secs`.
+/// Only `rauthy_admin`s with a local password or passkey can log in, and MFA is enforced like
+/// for `POST /oidc/authorize`. Each request sends a `BreakGlassLogin` event.
+///
+/// **Permissions**
+/// - `session-init`
+/// - `session-auth`
+#[utoipa::path(
+    post,
+    path = "/oidc/break_glass",
+    tag = "oidc",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, description = "Correct credentials, but needs to continue with Webauthn MFA Login", body = WebauthnLoginResponse),
+        (status = 202, description = "Correct credentials and no MFA Login required, adds Location header"),
+        (status = 400, description = "Missing / bad input data", body = ErrorResponse),
+        (status = 401, description = "Bad input or CSRF Token error", body = ErrorResponse),
+        (status = 404, description = "NotFound if the break-glass login is disabled", body = ErrorResponse),
+        (status = 429, description = "TooManyRequests", body = ErrorResponse),
+    ),
+)]
+#[post("/oidc/break_glass")]
+pub async fn post_break_glass(
+    req: HttpRequest,
+    Json(payload): Json<LoginRequest>,
+    principal: ReqPrincipal,
+    browser_id: BrowserId,
+) -> Result<HttpResponse, ErrorResponse> {
+    if !break_glass::is_enabled().await? {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::NotFound,
+            "The break-glass login is disabled",
+        ));
+    }
+    if payload.client_id != "rauthy" {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "The break-glass login is only available for the Admin UI",
+        ));
+    }
+
+    let ip = real_ip(&req)?;
+    break_glass::rate_limit(ip).await?;
+
+    let email = payload.email.clone();
+    let res = match break_glass::validate_user(&email).await {
+        Ok(()) => post_authorize_handle(req, payload, principal, browser_id).await,
+        Err(err) => {
+            let start = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+            login_delay::handle_login_delay(&req, ip, start, Err(err), false).await
+        }
+    };
+
+    let outcome = match &res {
+        Ok(resp) if resp.status() == StatusCode::ACCEPTED => "successful",
+        Ok(_) => "awaiting further steps",
+        Err(_) => "rejected",
+    };
+    warn!("Break-glass login for {email} from {ip}: {outcome}");
+    Event::break_glass_login(format!("Break-glass login for {email} {outcome}"), ip)
+        .send()
+        .await?;
+
+    res
 }
 
 /// Immediate login refresh with valid session
@@ -420,6 +556,16 @@ pub async fn post_authorize_refresh(
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_session_auth()?;
     payload.validate()?;
+    if let Some(scopes) = &payload.scopes {
+        validation::validate_scope_limits(&req, &payload.client_id, &scopes.join(" "))?;
+    }
+    validation::validate_auth_param_limits(
+        &req,
+        &payload.client_id,
+        &payload.redirect_uri,
+        payload.state.as_deref(),
+        payload.nonce.as_deref(),
+    )?;
 
     let session = principal.into_inner().session.unwrap();
 
@@ -517,7 +663,7 @@ pub async fn post_device_auth(
     }
 
     if RauthyConfig::get().vars.device_grant.rate_limit.is_some() {
-        match real_ip_from_req(&req) {
+        match real_ip(&req) {
             Err(err) => {
                 error!("{err}");
                 return HttpResponse::InternalServerError().json(OAuth2ErrorResponse {
@@ -583,6 +729,13 @@ pub async fn post_device_auth(
     }
 
     let scopes = if let Some(scopes) = payload.scope {
+        if let Err(err) = validation::validate_scope_limits(&req, &client.id, &scopes) {
+            return HttpResponse::BadRequest().json(OAuth2ErrorResponse {
+                error: OAuth2ErrorTypeResponse::InvalidScope,
+                error_description: Some(err.message),
+            });
+        }
+
         let iter = scopes.split(' ').collect::<Vec<&str>>();
         for scope in iter {
             if !client.scopes.contains(scope) {
@@ -712,11 +865,14 @@ pub async fn get_logout(
     principal: Option<ReqPrincipal>,
 ) -> Result<HttpResponse, ErrorResponse> {
     if let Err(err) = params.validate() {
-        return Ok(ErrorResponse::from(err).error_response());
+        return Ok(flow_error_response(&req, err.into(), ErrorFlow::Logout).await);
     }
 
     if params.id_token_hint.is_some() {
-        logout::post_logout_handle(req, params, None).await
+        match logout::post_logout_handle(req.clone(), params, None).await {
+            Ok(resp) => Ok(resp),
+            Err(err) => Ok(flow_error_response(&req, err, ErrorFlow::Logout).await),
+        }
     } else if let Some(principal) = principal {
         // If we get any logout errors, maybe because there is no session anymore or whatever happens,
         // just redirect to Rauthys root page, since the user is not logged-in anyway anymore.
@@ -767,6 +923,46 @@ pub async fn post_logout(
     logout::post_logout_handle(req, payload, session).await
 }
 
+/// Continue an upstream logout
+///
+/// Redirects to the `end_session_endpoint` of the upstream auth provider, if a logout via
+/// `POST /auth/v1/oidc/logout` has prepared one for the current browser. Used by the Rauthy UI,
+/// which cannot follow this redirect during the logout request itself.
+#[utoipa::path(
+    get,
+    path = "/oidc/logout/upstream",
+    tag = "oidc",
+    responses(
+        (status = 302, description = "Found"),
+    ),
+)]
+#[get("/oidc/logout/upstream")]
+pub async fn get_logout_upstream(req: HttpRequest) -> HttpResponse {
+    logout::upstream_logout_start(req).await
+}
+
+/// Upstream logout complete
+///
+/// The `post_logout_redirect_uri` for upstream auth providers. Redirects to the original target
+/// of the logout, like the `post_logout_redirect_uri` of the client.
+#[utoipa::path(
+    get,
+    path = "/oidc/logout/complete",
+    tag = "oidc",
+    params(UpstreamLogoutCompleteRequest),
+    responses(
+        (status = 302, description = "Found"),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+    ),
+)]
+#[get("/oidc/logout/complete")]
+pub async fn get_logout_complete(
+    Query(params): Query<UpstreamLogoutCompleteRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    params.validate()?;
+    logout::upstream_logout_complete(params).await
+}
+
 /// Rotate JWKs
 ///
 /// Rotates all currently exiting JWKs (Json Web Keys) for signing new tokens. This is a manual
@@ -792,6 +988,112 @@ pub async fn rotate_jwk(principal: ReqPrincipal) -> Result<HttpResponse, ErrorRe
     JWKS::rotate().await.map(|_| HttpResponse::Ok().finish())
 }
 
+/// Import an externally generated JWK
+///
+/// The key pair is validated and published in the JWKS for token validation, but it will not be
+/// used for signing new tokens until it is promoted. This makes it possible to announce a new key
+/// before any tokens are signed with it.<br>
+/// RSA keys are mapped to `RS256`, `RS384` or `RS512` by their size, like during a rotation.
+/// `EC` keys are currently not supported for token signing.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/admin/jwks/import",
+    tag = "oidc",
+    request_body = JwkImportRequest,
+    responses(
+        (status = 200, description = "Ok", body = JwkResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[post("/admin/jwks/import")]
+pub async fn post_jwks_import(
+    principal: ReqPrincipal,
+    Json(payload): Json<JwkImportRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Secrets, AccessRights::Create)?;
+    payload.validate()?;
+
+    let jwk = Jwk::import(payload).await?;
+    Ok(HttpResponse::Ok().json(JwkResponse {
+        kid: jwk.kid,
+        alg: jwk.signature.into(),
+        created_at: jwk.created_at,
+        status: JwkStatus::Retiring,
+    }))
+}
+
+/// Promote a JWK to be the current one
+///
+/// All new tokens for the keys' algorithm will be signed with it from this point on. The
+/// previously current key stays published for token validation, exactly like after a rotation.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/admin/jwks/{kid}/promote",
+    tag = "oidc",
+    responses(
+        (status = 200, description = "Ok", body = JwkResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[post("/admin/jwks/{kid}/promote")]
+pub async fn post_jwk_promote(
+    principal: ReqPrincipal,
+    kid: web::Path<String>,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Secrets, AccessRights::Update)?;
+
+    let jwk = Jwk::promote(kid.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(JwkResponse {
+        kid: jwk.kid,
+        alg: jwk.signature.into(),
+        created_at: jwk.created_at,
+        status: JwkStatus::Current,
+    }))
+}
+
+/// Register a reference to a JWK inside an HSM
+///
+/// Only the reference is saved, the key material never leaves the HSM.<br>
+/// CAUTION: There is no HSM signer implementation yet. Registered keys are not published and not
+/// used for signing.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/admin/jwks/hsm_reference",
+    tag = "oidc",
+    request_body = JwkHsmReferenceRequest,
+    responses(
+        (status = 200, description = "Ok", body = JwkHsmReferenceResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[post("/admin/jwks/hsm_reference")]
+pub async fn post_jwks_hsm_reference(
+    principal: ReqPrincipal,
+    Json(payload): Json<JwkHsmReferenceRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Secrets, AccessRights::Create)?;
+    payload.validate()?;
+
+    let reference = JwkHsmReference::create(payload).await?;
+    Ok(HttpResponse::Ok().json(JwkHsmReferenceResponse::from(reference)))
+}
+
 /// Create a new session
 ///
 /// You can use this endpoint to create a new session outside the `/authorize` page when logging
@@ -820,7 +1122,7 @@ pub async fn post_session(
 
     let session = Session::new(
         RauthyConfig::get().vars.lifetimes.session_lifetime,
-        real_ip_from_req(&req).ok(),
+        real_ip(&req).ok(),
     );
     session.upsert().await?;
     let cookie = session.client_cookie();
@@ -967,9 +1269,8 @@ pub async fn get_session_xsrf(principal: ReqPrincipal) -> Result<HttpResponse, E
     request_body(content = TokenRequest, content_type = "application/x-www-form-urlencoded"),
     responses(
         (status = 200, description = "Ok", body = TokenSet),
-        (status = 400, description = "BadRequest", body = ErrorResponse),
-        (status = 401, description = "Unauthorized", body = ErrorResponse),
-        (status = 404, description = "NotFound", body = ErrorResponse),
+        (status = 400, description = "BadRequest", body = TokenErrorResponse),
+        (status = 401, description = "Unauthorized", body = TokenErrorResponse),
     ),
 )]
 #[post("/oidc/token")]
@@ -979,9 +1280,19 @@ pub async fn post_token(
     browser_id: BrowserId,
     Form(payload): Form<TokenRequest>,
 ) -> Result<HttpResponse, ErrorResponse> {
-    payload.validate()?;
+    if let Err(err) = payload.validate() {
+        let err = if err.field_errors().contains_key("grant_type") {
+            ErrorResponse::new(
+                ErrorResponseType::UnsupportedGrantType,
+                "Invalid 'grant_type'",
+            )
+        } else {
+            ErrorResponse::from(err)
+        };
+        return Ok(helpers::token_error_response(&req, err));
+    }
 
-    let ip = real_ip_from_req(&req)?;
+    let ip = real_ip(&req)?;
 
     if payload.grant_type == GRANT_TYPE_DEVICE_CODE {
         // the `urn:ietf:params:oauth:grant-type:device_code` needs
@@ -993,7 +1304,7 @@ pub async fn post_token(
     let start = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
     let has_password_been_hashed = payload.grant_type == "password";
 
-    let res = match oidc::get_token_set(payload, browser_id, req).await {
+    let res = match oidc::get_token_set(payload, browser_id, req.clone()).await {
         Ok((token_set, headers)) => {
             let mut builder = HttpResponseBuilder::new(StatusCode::OK);
             for h in headers {
@@ -1012,7 +1323,10 @@ pub async fn post_token(
         }
     };
 
-    login_delay::handle_login_delay(ip, start, res, has_password_been_hashed).await
+    // RFC 6749 §5.2: errors from the token endpoint have their own format
+    login_delay::handle_login_delay(&req, ip, start, res, has_password_been_hashed)
+        .await
+        .or_else(|err| Ok(helpers::token_error_response(&req, err)))
 }
 
 #[utoipa::path(
@@ -1112,12 +1426,12 @@ pub async fn post_token_introspect(
         (status = 200, description = "Ok", body = Userinfo),
         (status = 400, description = "BadRequest", body = ErrorResponse),
         (status = 401, description = "Unauthorized", body = ErrorResponse),
-        (status = 404, description = "NotFound", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
     ),
 )]
 #[get("/oidc/userinfo")]
 pub async fn get_userinfo(req: HttpRequest) -> Result<HttpResponse, ErrorResponse> {
-    let (info, cors_header) = userinfo::get_userinfo(req).await?;
+    let (info, cors_header) = userinfo::get_userinfo(req, None, true).await?;
     if let Some((n, v)) = cors_header {
         Ok(HttpResponse::Ok()
             .insert_header((n, v))
@@ -1137,22 +1451,28 @@ pub async fn get_userinfo(req: HttpRequest) -> Result<HttpResponse, ErrorRespons
 
 /// The userinfo endpoint for the OIDC standard.
 ///
-/// Depending on the JWT token from the *Authorization* header, it will return information about
-/// the requesting user / token.
+/// Depending on the JWT token from the *Authorization* header or the `access_token` form
+/// field, it will return information about the requesting user / token. The token must be sent
+/// in exactly one of both places.
 #[utoipa::path(
     post,
     path = "/oidc/userinfo",
     tag = "oidc",
+    request_body(content = UserinfoRequest, content_type = "application/x-www-form-urlencoded"),
     responses(
         (status = 200, description = "Ok", body = Userinfo),
         (status = 400, description = "BadRequest", body = ErrorResponse),
         (status = 401, description = "Unauthorized", body = ErrorResponse),
-        (status = 404, description = "NotFound", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
     ),
 )]
 #[post("/oidc/userinfo")]
-pub async fn post_userinfo(req: HttpRequest) -> Result<HttpResponse, ErrorResponse> {
-    let (info, cors_header) = userinfo::get_userinfo(req).await?;
+pub async fn post_userinfo(
+    req: HttpRequest,
+    payload: Option<Form<UserinfoRequest>>,
+) -> Result<HttpResponse, ErrorResponse> {
+    let form_token = payload.and_then(|p| p.into_inner().access_token);
+    let (info, cors_header) = userinfo::get_userinfo(req, form_token, true).await?;
     if let Some((n, v)) = cors_header {
         Ok(HttpResponse::Ok()
             .insert_header((n, v))
@@ -1195,14 +1515,17 @@ pub async fn post_userinfo(req: HttpRequest) -> Result<HttpResponse, ErrorRespon
 )]
 #[get("/oidc/forward_auth")]
 pub async fn get_forward_auth(req: HttpRequest) -> Result<HttpResponse, ErrorResponse> {
-    let (info, _) = userinfo::get_userinfo(req).await?;
+    let (info, _) = userinfo::get_userinfo(req, None, false).await?;
 
     let headers = &RauthyConfig::get().vars.auth_headers;
     if headers.enable {
         let mut builder = HttpResponse::Ok();
         builder
             .insert_header((headers.user.as_ref(), info.id))
-            .insert_header((headers.roles.as_ref(), info.roles.join(",")))
+            .insert_header((
+                headers.roles.as_ref(),
+                info.roles.map(|r| r.join(",")).unwrap_or_default(),
+            ))
             .insert_header((
                 headers.groups.as_ref(),
                 info.groups.map(|g| g.join(",")).unwrap_or_default(),
@@ -1234,39 +1557,87 @@ pub async fn get_forward_auth(req: HttpRequest) -> Result<HttpResponse, ErrorRes
     }
 }
 
-/// The `.well-known` endpoint for OIDC Client auto discovery.
-///
-/// Capable OIDC clients can use this endpoint to auto-discover all necessary OIDC information and
-/// endpoints that are provided by rauthy to automatically choose the best / safest options.
-async fn well_known_response() -> Result<HttpResponse, ErrorResponse> {
-    let wk = WellKnown::json().await?;
-    Ok(HttpResponse::Ok()
+fn well_known_response(req: &HttpRequest, doc: DiscoveryDocument) -> HttpResponse {
+    let if_none_match = req
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok());
+
+    if doc.is_not_modified(if_none_match) {
+        return HttpResponse::NotModified()
+            .insert_header((header::ETAG, doc.etag))
+            .insert_header((
+                header::ACCESS_CONTROL_ALLOW_ORIGIN,
+                HeaderValue::from_static("*"),
+            ))
+            .finish();
+    }
+
+    HttpResponse::Ok()
         .insert_header((CONTENT_TYPE, APPLICATION_JSON))
+        .insert_header((header::ETAG, doc.etag))
         .insert_header((
             header::ACCESS_CONTROL_ALLOW_ORIGIN,
             HeaderValue::from_static("*"),
         ))
-        .body(wk))
+        .body(doc.json)
 }
 
+/// The `.well-known` endpoint for OIDC Client auto discovery.
+///
+/// Capable OIDC clients can use this endpoint to auto-discover all necessary OIDC information and
+/// endpoints that are provided by rauthy to automatically choose the best / safest options.
 #[utoipa::path(
     get,
     path = "/.well-known/openid-configuration",
     tag = "oidc",
     responses(
         (status = 200, description = "Ok", body = WellKnown),
+        (status = 304, description = "NotModified"),
     ),
 )]
 #[get("/.well-known/openid-configuration")]
-pub async fn get_well_known() -> Result<HttpResponse, ErrorResponse> {
-    well_known_response().await
+pub async fn get_well_known(req: HttpRequest) -> Result<HttpResponse, ErrorResponse> {
+    let doc = WellKnown::openid_configuration().await?;
+    Ok(well_known_response(&req, doc))
 }
 
-// RFC 8414 alias for `/.well-known/openid-configuration`. Some MCP clients
-// (ChatGPT's connector backend) prefer the RFC 8414 endpoint for AS-metadata
-// discovery and won't read `client_id_metadata_document_supported` from the
-// OIDC-only doc. Body is identical; we just expose it under the second path.
+/// RFC 8414 OAuth 2.0 Authorization Server Metadata
+///
+/// Pure OAuth clients use this endpoint instead of the OIDC discovery. It is derived from the
+/// same source as `/.well-known/openid-configuration`.
+///
+/// Because the issuer contains a path, the RFC 8414 compliant location is
+/// `/.well-known/oauth-authorization-server/auth/v1` at the root of the domain. It is served
+/// under `/auth/v1` as well for clients that simply append the well-known path to the issuer.
+#[utoipa::path(
+    get,
+    path = "/.well-known/oauth-authorization-server",
+    tag = "oidc",
+    responses(
+        (status = 200, description = "Ok", body = AuthorizationServerMetadata),
+        (status = 304, description = "NotModified"),
+    ),
+)]
 #[get("/.well-known/oauth-authorization-server")]
-pub async fn get_well_known_oauth() -> Result<HttpResponse, ErrorResponse> {
-    well_known_response().await
+pub async fn get_well_known_oauth(req: HttpRequest) -> Result<HttpResponse, ErrorResponse> {
+    let doc = WellKnown::oauth_authorization_server().await?;
+    Ok(well_known_response(&req, doc))
+}
+
+/// The RFC 8414 location for issuers with a path component, which only exists at the root of the
+/// domain and must match the path of the issuer.
+#[get("/.well-known/oauth-authorization-server/{path:.*}")]
+pub async fn get_well_known_oauth_issuer_path(
+    req: HttpRequest,
+) -> Result<HttpResponse, ErrorResponse> {
+    if req.path().trim_end_matches('/') != WellKnown::oauth_metadata_path() {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::NotFound,
+            "No authorization server metadata for this issuer path",
+        ));
+    }
+
+    let doc = WellKnown::oauth_authorization_server().await?;
+    Ok(well_known_response(&req, doc))
 }
@@ -1,12 +1,12 @@
 use crate::{
     api_keys, atproto, auth_providers, backup, blacklist, clients, email, events, fed_cm, generic,
-    groups, kv, oidc, pam, roles, scopes, sessions, themes, tos, users,
+    groups, i18n, kv, migration, oidc, pam, reports, roles, scopes, sessions, themes, tos, users,
 };
 use rauthy_api_types::*;
 use rauthy_api_types::{
     api_keys::*, auth_providers::*, backup::*, blacklist::*, clients::*, email_jobs::*, events::*,
-    fed_cm::*, forward_auth::*, generic::*, groups::*, kv::*, oidc::*, pam::*, roles::*, scopes::*,
-    sessions::*, themes::*, tos::*, users::*,
+    fed_cm::*, forward_auth::*, generic::*, groups::*, i18n::*, kv::*, migration::*, oidc::*,
+    pam::*, reports::*, roles::*, scopes::*, sessions::*, themes::*, tos::*, users::*,
 };
 use rauthy_common::constants::{PROXY_MODE, RAUTHY_VERSION};
 use rauthy_data::ListenScheme;
@@ -17,7 +17,6 @@ use rauthy_data::rauthy_config::{
     UserValueConfigValue, VarsUserPreferredUsername, VarsUserValuesConfig,
 };
 use rauthy_error::{ErrorResponse, ErrorResponseType};
-use rauthy_service::token_set;
 use utoipa::openapi::{ExternalDocs, Server};
 use utoipa::{OpenApi, openapi};
 
@@ -35,6 +34,8 @@ use utoipa::{OpenApi, openapi};
         atproto::get_atproto_client_metadata,
 
         auth_providers::post_providers,
+        auth_providers::get_providers_export,
+        auth_providers::post_providers_import,
         auth_providers::post_provider,
         auth_providers::post_provider_lookup,
         auth_providers::post_provider_login,
@@ -43,8 +44,19 @@ use utoipa::{OpenApi, openapi};
         auth_providers::delete_provider_link,
         auth_providers::get_providers_minimal,
         auth_providers::put_provider,
+        auth_providers::post_provider_secret,
+        auth_providers::post_provider_test_callback,
+        auth_providers::post_provider_test,
         auth_providers::delete_provider,
         auth_providers::get_provider_delete_safe,
+        auth_providers::get_provider_group_mappings,
+        auth_providers::post_provider_group_mapping,
+        auth_providers::put_provider_group_mapping,
+        auth_providers::delete_provider_group_mapping,
+        auth_providers::get_provider_role_mappings,
+        auth_providers::post_provider_role_mapping,
+        auth_providers::put_provider_role_mapping,
+        auth_providers::delete_provider_role_mapping,
         auth_providers::get_provider_img,
         auth_providers::put_provider_img,
         auth_providers::delete_provider_img,
@@ -54,6 +66,10 @@ use utoipa::{OpenApi, openapi};
         backup::get_backup_local,
         backup::get_backup_s3,
 
+        migration::get_migration_export,
+        migration::post_migration_import,
+        migration::post_migration_keycloak,
+
         blacklist::get_blacklist,
         blacklist::post_blacklist,
         blacklist::delete_blacklist,
@@ -63,6 +79,10 @@ use utoipa::{OpenApi, openapi};
         clients::get_client_logo,
         clients::put_client_logo,
         clients::delete_client_logo,
+        clients::get_client_jwks,
+        clients::put_client_jwks,
+        clients::delete_client_jwks,
+        clients::post_client_jwks_refresh,
         clients::get_client_secret,
         clients::post_clients,
         clients::post_clients_dyn,
@@ -75,6 +95,7 @@ use utoipa::{OpenApi, openapi};
         email::get_email_jobs,
         email::post_send_email,
         email::post_email_job_cancel,
+        email::post_test_smtp,
 
         events::post_events,
         events::sse_events,
@@ -91,14 +112,21 @@ use utoipa::{OpenApi, openapi};
         generic::get_auth_check_admin,
         generic::get_enc_keys,
         generic::post_migrate_enc_key,
+        generic::post_migrate_secrets,
+        generic::post_migrate_pii,
         generic::get_login_time,
+        generic::get_maintenance_mode,
+        generic::put_maintenance_mode,
+        generic::post_maintenance_mode_bypass,
         generic::post_password_hash_times,
+        generic::post_token_preview,
         generic::get_password_policy,
         generic::put_password_policy,
         generic::get_health,
         generic::post_pow,
         generic::get_ready,
         generic::ping,
+        generic::get_system_info,
         generic::get_version,
         generic::get_whoami,
 
@@ -156,15 +184,23 @@ use utoipa::{OpenApi, openapi};
         pam::put_pam_user,
         pam::get_validate_user,
 
+        reports::get_reports_expiring,
+
         oidc::get_authorize,
         oidc::post_authorize,
+        oidc::post_break_glass,
         oidc::post_authorize_refresh,
         oidc::get_certs,
         oidc::get_cert_by_kid,
         oidc::post_device_auth,
         oidc::get_logout,
         oidc::post_logout,
+        oidc::get_logout_upstream,
+        oidc::get_logout_complete,
         oidc::rotate_jwk,
+        oidc::post_jwks_import,
+        oidc::post_jwk_promote,
+        oidc::post_jwks_hsm_reference,
         oidc::post_session,
         oidc::get_session_info,
         oidc::get_session_xsrf,
@@ -174,6 +210,7 @@ use utoipa::{OpenApi, openapi};
         oidc::get_userinfo,
         oidc::get_forward_auth,
         oidc::get_well_known,
+        oidc::get_well_known_oauth,
 
         roles::get_roles,
         roles::post_role,
@@ -192,6 +229,11 @@ use utoipa::{OpenApi, openapi};
         themes::get_theme,
         themes::put_theme,
         themes::delete_theme,
+        i18n::get_i18n_language,
+        i18n::get_i18n_overrides,
+        i18n::put_i18n_overrides,
+        i18n::get_i18n_override_keys,
+        i18n::post_i18n_overrides_preview,
 
         tos::get_tos,
         tos::post_tos,
@@ -203,12 +245,21 @@ use utoipa::{OpenApi, openapi};
         users::get_users,
         users::post_users,
         users::get_cust_attr,
+        users::get_cust_attr_values,
         users::post_cust_attr,
         users::put_cust_attr,
         users::delete_cust_attr,
         users::get_users_register,
         users::post_users_register,
+        users::get_users_register_config,
+        users::put_users_register_config,
+        users::post_user_approve,
         users::get_user_by_id,
+        users::get_user_clients,
+        users::get_user_effective_permissions,
+        users::post_user_reindex,
+        users::post_users_reindex_all,
+        users::post_users_disable_bulk,
         users::get_user_attr,
         users::put_user_attr,
         users::post_user_mfa_token,
@@ -218,6 +269,11 @@ use utoipa::{OpenApi, openapi};
         users::get_user_devices,
         users::put_user_device_name,
         users::delete_user_device,
+        users::get_user_grants,
+        users::delete_user_grant,
+        users::get_user_notification_prefs,
+        users::put_user_notification_prefs,
+        users::get_user_federation,
         users::get_user_webid,
         users::get_user_webid_data,
         users::put_user_webid_data,
@@ -226,8 +282,11 @@ use utoipa::{OpenApi, openapi};
         users::post_webauthn_auth_start,
         users::post_webauthn_auth_finish,
         users::delete_webauthn,
+        users::delete_webauthn_counter_anomaly,
         users::post_webauthn_reg_start,
         users::post_webauthn_reg_finish,
+        users::get_user_webauthn_export,
+        users::post_user_webauthn_import,
         users::post_user_password_request_reset,
         users::get_user_by_email,
         users::put_user_by_id,
@@ -236,6 +295,9 @@ use utoipa::{OpenApi, openapi};
         users::get_user_self_delete_config,
         users::delete_user_self,
         users::post_user_self_convert_passkey,
+        users::post_user_provider_unlink,
+        users::post_user_provider_link,
+        users::delete_user_provider_link,
         users::get_user_values_config,
         users::put_user_self_preferred_username,
         users::delete_user_by_id,
@@ -257,6 +319,7 @@ use utoipa::{OpenApi, openapi};
             entity::webauthn::WebauthnAdditionalData,
             entity::webauthn::WebauthnLoginReq,
             entity::webauthn::WebauthnServiceReq,
+            entity::well_known::AuthorizationServerMetadata,
             entity::well_known::WellKnown,
 
             AccessGroup,
@@ -271,11 +334,17 @@ use utoipa::{OpenApi, openapi};
             EventResponse,
             EventType,
             JktClaim,
+            JwkHsmReferenceRequest,
+            JwkHsmReferenceResponse,
+            JwkImportKeyType,
+            JwkImportRequest,
             JwkKeyPairAlg,
             JwkKeyPairType,
+            JwkResponse,
+            JwkStatus,
             Language,
 
-            rauthy_jwt::claims::JwtTokenType,
+            claims::JwtTokenType,
 
             ErrorResponse,
             ErrorResponseType,
@@ -292,6 +361,7 @@ use utoipa::{OpenApi, openapi};
             EmailJobResponse,
             EmailJobStatus,
             EncKeyMigrateRequest,
+            SecretsMigrateParams,
             FedCMAssertionRequest,
             FedCMClientMetadataRequest,
             CertsParams,
@@ -303,6 +373,7 @@ use utoipa::{OpenApi, openapi};
             MfaPurpose,
             NewClientRequest,
             DeviceRequest,
+            DisableUsersBulkRequest,
             DynamicClientRequest,
             EventLevel,
             EventsListenParams,
@@ -313,11 +384,20 @@ use utoipa::{OpenApi, openapi};
             KVNamespaceRequest,
             KVAccessRequest,
             KVValueRequest,
+            MigrationConflictStrategy,
+            MigrationImportParams,
+            KeycloakImportParams,
+            ExpiringReportParams,
             LoginRefreshRequest,
             GroupRequest,
             MfaModTokenRequest,
             NewUserRequest,
             NewUserRegistrationRequest,
+            UserRegistrationConfigRequest,
+            UserRegistrationConfigResponse,
+            NotificationCategory,
+            NotificationPrefRequest,
+            NotificationPrefsRequest,
             PamGroupType,
             PamGroupUserLink,
             PamGroupCreateRequest,
@@ -334,25 +414,42 @@ use utoipa::{OpenApi, openapi};
             PamUserUpdateRequest,
             RoleRequest,
             PaginationParams,
+            MaintenanceModeRequest,
             PasswordHashTimesRequest,
             PasswordPolicyRequest,
             PasswordResetRequest,
             PatchOp,
             PatchValue,
+            ProviderGroupMappingRequest,
+            ProviderRoleMappingRequest,
+            ProviderGroupSyncMode,
+            ProviderSyncPolicy,
             ProviderRequest,
+            ProviderExport,
+            ProviderExportEntry,
+            ProviderExportSecrets,
             ProviderLoginRequest,
             ProviderLookupRequest,
             ProviderCallbackRequest,
+            ProviderSecretRequest,
+            ProviderTestCallbackRequest,
             RequestResetRequest,
             ScopeRequest,
             SessionState,
             TokenRequest,
             TokenRevocationRequest,
             TokenValidationRequest,
+            UserinfoRequest,
             UpdateClientRequest,
+            UpstreamLogoutCompleteRequest,
             ClientSecretRequest,
+            ClientJwksRequest,
             UpdateUserRequest,
             UpdateUserSelfRequest,
+            UserDeleteParams,
+            UserAttrDeleteParams,
+            UserAttrValuesParams,
+            UsersAttrParams,
             UserValuesRequest,
             UserAttrConfigRequest,
             UserAttrValueRequest,
@@ -375,7 +472,11 @@ use utoipa::{OpenApi, openapi};
             DeviceCodeResponse,
             DynamicClientResponse,
             ClientSecretResponse,
+            ClientJwkResponse,
+            ClientJwksResponse,
             EncKeysResponse,
+            SecretsMigrationProgress,
+            PiiMigrationResponse,
             GroupResponse,
             HealthResponse,
             I18nConfigResponse,
@@ -386,10 +487,30 @@ use utoipa::{OpenApi, openapi};
             KVAccessResponse,
             KVAccessTestResponse,
             KVValueResponse,
+            MigrationImportResponse,
+            MigrationSectionSummary,
+            KeycloakImportAction,
+            KeycloakImportEntity,
+            KeycloakImportResponse,
+            ExpiringReportResponse,
+            ExpiringApiKey,
+            ExpiringDeviceToken,
+            ExpiringRefreshTokens,
+            ExpiringUser,
             OAuth2ErrorResponse,
+            MaintenanceModeResponse,
             OAuth2ErrorTypeResponse,
+            TokenErrorResponse,
             PasswordPolicyResponse,
+            SmtpTestResponse,
+            SystemInfoResponse,
+            DisableUsersBulkResponse,
+            ErasureResult,
             MfaModTokenResponse,
+            NotificationPrefResponse,
+            UserFederationChange,
+            UserFederationFieldChange,
+            UserFederationResponse,
             PamGetentResponse,
             PamGroupResponse,
             PamGroupHostsCountResponse,
@@ -404,13 +525,33 @@ use utoipa::{OpenApi, openapi};
             PamUserResponse,
             PamUserDetailsResponse,
             PreferredUsernameRequest,
+            ProviderGroupMappingResponse,
+            ProviderRoleMappingResponse,
             ProviderResponse,
+            ProviderDeleteParams,
             ProviderLinkedUserResponse,
+            ProviderSecretProbeMethod,
+            ProviderSecretProbeResult,
+            ProviderSecretResponse,
+            ProviderTestCallbackResponse,
+            ProviderTestResponse,
+            ProviderTestResult,
+            ProviderTestStep,
+            ProviderTestStepResponse,
+            ProviderImportResponse,
+            ProviderImportResult,
+            ProviderImportStatus,
             ProviderLookupResponse,
             RoleResponse,
             ScopeResponse,
             SessionResponse,
             SessionInfoResponse,
+            I18nOverridableKey,
+            I18nOverride,
+            I18nOverridesRequest,
+            I18nPage,
+            I18nPageKeysResponse,
+            I18nPreviewRequest,
             ThemeCss,
             ThemeRequestResponse,
             TokenInfo,
@@ -425,9 +566,21 @@ use utoipa::{OpenApi, openapi};
             UserAttrConfigValueResponse,
             UserAttrValueResponse,
             UserAttrValuesResponse,
+            UserAttrUsageResponse,
+            UserAttrDistinctValueResponse,
             UserEditableAttrResponse,
             UserEditableAttrsResponse,
+            UserClientResponse,
+            UserEffectivePermissionsResponse,
+            EffectivePermissionApiKey,
+            EffectivePermissionAttr,
+            EffectivePermissionItem,
+            EffectivePermissionSource,
             Userinfo,
+            UserReindexResponse,
+            PasskeyExport,
+            PasskeysExport,
+            UsersReindexProgress,
             UserValuesResponse,
             UserAccountTypeResponse,
             UserResponse,
@@ -437,7 +590,7 @@ use utoipa::{OpenApi, openapi};
             WebId,
             WebIdResponse,
 
-            token_set::TokenSet,
+            TokenSet,
             VarsUserValuesConfig,
             VarsUserPreferredUsername,
             UserValueConfigValue,
@@ -455,6 +608,7 @@ use utoipa::{OpenApi, openapi};
         (name = "events", description = "Events Stream"),
         (name = "kv", description = "Global KV Store"),
         (name = "pam", description = "PAM endpoints"),
+        (name = "reports", description = "Admin reports"),
         (name = "providers", description = "Upstream Auth Providers"),
         (name = "health", description = "Ping, Health, Ready Check"),
         (name = "blacklist", description = "IP Blacklist endpoints"),
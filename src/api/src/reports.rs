@@ -0,0 +1,36 @@
+use crate::ReqPrincipal;
+use actix_web::web::Query;
+use actix_web::{HttpResponse, get};
+use rauthy_api_types::reports::{ExpiringReportParams, ExpiringReportResponse};
+use rauthy_data::entity::api_keys::{AccessGroup, AccessRights};
+use rauthy_data::reports;
+use rauthy_error::ErrorResponse;
+use validator::Validate;
+
+/// Report of everything that will expire soon
+///
+/// Lists API keys, device refresh tokens, users whose latest refresh token expires and user
+/// accounts, which will lapse within the given window. The report is cached for 60 seconds.
+#[utoipa::path(
+    get,
+    path = "/reports/expiring",
+    tag = "reports",
+    params(ExpiringReportParams),
+    responses(
+        (status = 200, description = "Ok", body = ExpiringReportResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/reports/expiring")]
+pub async fn get_reports_expiring(
+    principal: ReqPrincipal,
+    params: Query<ExpiringReportParams>,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Read)?;
+    params.validate()?;
+
+    let report = reports::expiring_report(params.days).await?;
+    Ok(HttpResponse::Ok().json(report))
+}
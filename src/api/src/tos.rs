@@ -12,7 +12,7 @@ use rauthy_api_types::tos::{
 use rauthy_common::constants::HEADER_HTML;
 use rauthy_common::markdown::render_sanitized_markdown;
 use rauthy_common::sanitize_html::sanitize_html;
-use rauthy_common::utils::real_ip_from_req;
+use rauthy_common::utils::real_ip;
 use rauthy_data::entity::auth_codes::{AuthCode, AuthCodeToSAwait};
 use rauthy_data::entity::theme::ThemeCssFull;
 use rauthy_data::entity::tos::ToS;
@@ -246,7 +246,7 @@ async fn handle_tos_accept_deny(
     let tos = ToS::find(payload.tos_ts).await?;
 
     if is_accept {
-        let ip = real_ip_from_req(&req)?;
+        let ip = real_ip(&req)?;
         let loc = get_location(&req, ip)?;
         ToSUserAccept::create(user.id, tos.ts, ip, loc).await?;
     } else if let Some(ts) = tos.opt_until {
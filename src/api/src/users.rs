@@ -1,22 +1,27 @@
-use crate::{ReqPrincipal, content_len_limit};
+use crate::{ReqPrincipal, auth_providers, content_len_limit};
 use actix_web::http::StatusCode;
 use actix_web::http::header::{ACCEPT, HeaderName, HeaderValue, LOCATION};
 use actix_web::mime::TEXT_HTML;
 use actix_web::web::{Json, Query};
-use actix_web::{HttpRequest, HttpResponse, ResponseError, delete, get, patch, post, put, web};
+use actix_web::{
+    HttpRequest, HttpResponse, Responder, ResponseError, delete, get, patch, post, put, web,
+};
+use actix_web_lab::sse;
 use chrono::Utc;
 use rauthy_api_types::PatchOp;
+use rauthy_api_types::auth_providers::ProviderLoginRequest;
+use rauthy_api_types::claims::{JwtCommonClaims, JwtTokenType};
 use rauthy_api_types::generic::{PaginationParams, PasswordPolicyResponse};
 use rauthy_api_types::oidc::PasswordResetResponse;
 use rauthy_api_types::users::*;
 use rauthy_common::constants::{
-    COOKIE_MFA, HEADER_ALLOW_ALL_ORIGINS, HEADER_HTML, HEADER_JSON, PWD_CSRF_HEADER,
-    PWD_RESET_COOKIE, TEXT_TURTLE,
+    COOKIE_MFA, HEADER_ALLOW_ALL_ORIGINS, HEADER_ERASURE_CONFIRM, HEADER_HTML, HEADER_JSON,
+    PWD_CSRF_HEADER, PWD_RESET_COOKIE, TEXT_TURTLE,
 };
-use rauthy_common::utils::real_ip_from_req;
+use rauthy_common::utils::{real_ip, secure_compare};
 use rauthy_data::api_cookie::ApiCookie;
 use rauthy_data::email::email_registered_already::send_email_registered_already;
-use rauthy_data::entity::api_keys::{AccessGroup, AccessRights};
+use rauthy_data::entity::api_keys::{AccessGroup, AccessRights, ApiKeyEntity};
 use rauthy_data::entity::browser_id::BrowserId;
 use rauthy_data::entity::clients::Client;
 use rauthy_data::entity::clients_scim::ClientScim;
@@ -25,31 +30,38 @@ use rauthy_data::entity::devices::DeviceEntity;
 use rauthy_data::entity::email_rate_limit::EmailRateLimit;
 use rauthy_data::entity::groups::Group;
 use rauthy_data::entity::login_locations::LoginLocation;
+use rauthy_data::entity::logos::{Logo, LogoType};
 use rauthy_data::entity::mfa_mod_token::MfaModToken;
 use rauthy_data::entity::password::PasswordPolicy;
 use rauthy_data::entity::pictures::{PICTURE_STORAGE_TYPE, PictureStorage, UserPicture};
 use rauthy_data::entity::pow::PowEntity;
 use rauthy_data::entity::refresh_tokens::RefreshToken;
+use rauthy_data::entity::scopes::Scope;
 use rauthy_data::entity::sessions::Session;
 use rauthy_data::entity::theme::ThemeCssFull;
 use rauthy_data::entity::tos::ToS;
 use rauthy_data::entity::tos_user_accept::ToSUserAccept;
 use rauthy_data::entity::user_attr::{UserAttrConfigEntity, UserAttrValueEntity};
+use rauthy_data::entity::user_federation_claims::UserFederationClaims;
+use rauthy_data::entity::user_grants::UserGrant;
+use rauthy_data::entity::user_notification_prefs::UserNotificationPref;
+use rauthy_data::entity::user_registration_config::UserRegistrationConfig;
 use rauthy_data::entity::user_revoke::UserRevoke;
 use rauthy_data::entity::users::User;
+use rauthy_data::entity::users_erasure::erasure_pseudonym;
 use rauthy_data::entity::users_values::UserValues;
 use rauthy_data::entity::webauthn;
 use rauthy_data::entity::webauthn::{PasskeyEntity, WebauthnAdditionalData, WebauthnServiceReq};
 use rauthy_data::entity::webids::WebId;
 use rauthy_data::events::event::Event;
 use rauthy_data::html::HtmlCached;
+use rauthy_data::html::flow_error::{ErrorFlow, flow_error_response};
 use rauthy_data::html::templates::{Error3Html, ErrorHtml, UserRevokeHtml};
 use rauthy_data::ipgeo;
 use rauthy_data::ipgeo::get_location;
 use rauthy_data::language::Language;
 use rauthy_data::rauthy_config::{RauthyConfig, UserValueConfigValue, VarsUserValuesConfig};
 use rauthy_error::{ErrorResponse, ErrorResponseType};
-use rauthy_jwt::claims::{JwtCommonClaims, JwtTokenType};
 use rauthy_jwt::token::JwtToken;
 use rauthy_service::oidc::helpers::get_bearer_token_from_header;
 use rauthy_service::oidc::logout;
@@ -59,6 +71,8 @@ use spow::pow::Pow;
 use std::cmp::max;
 use std::collections::HashMap;
 use std::net::IpAddr;
+use std::time::Duration;
+use tokio::sync::mpsc;
 use tokio::task;
 use tracing::{debug, error, info, warn};
 use validator::Validate;
@@ -70,13 +84,17 @@ use validator::Validate;
 /// If the response contains all existing users, the status code will be an HTTP 200.
 /// If the backend is in server side pagination mode, it will return an HTTP 206.
 ///
+/// With `attr=key:value`, only users with this custom attribute value will be returned, using
+/// `page_size` and `offset` for the pagination. The total amount of matching users is returned
+/// in the `x-user-count` header.
+///
 /// **Permissions**
 /// - rauthy_admin
 #[utoipa::path(
     get,
     path = "/users",
     tag = "users",
-    params(PaginationParams),
+    params(PaginationParams, UsersAttrParams),
     responses(
         (status = 200, description = "Ok", body = [UserResponseSimple]),
         (status = 206, description = "PartialContent", body = [UserResponse]),
@@ -88,12 +106,34 @@ use validator::Validate;
 pub async fn get_users(
     principal: ReqPrincipal,
     Query(params): Query<PaginationParams>,
+    Query(attr_params): Query<UsersAttrParams>,
 ) -> Result<HttpResponse, ErrorResponse> {
     // Group admins see the full (minified) user list as well. The
     // per-user write scoping is enforced on the individual modify endpoints, never
     // here, so list visibility intentionally does not imply mutate permission.
     principal.validate_api_key_or_group_admin(AccessGroup::Users, AccessRights::Read)?;
     params.validate()?;
+    attr_params.validate()?;
+
+    if let Some(attr) = &attr_params.attr {
+        let Some((key, value)) = attr.split_once(':') else {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "`attr` must be in the format `key:value`",
+            ));
+        };
+        // makes sure we return a 404 for a non-existing attribute
+        UserAttrConfigEntity::find(key.to_string()).await?;
+
+        let page_size = params.page_size.unwrap_or(20) as i64;
+        let offset = params.offset.unwrap_or(0) as i64;
+        let count = UserAttrValueEntity::count_by_key(key, Some(value)).await?;
+        let users = User::search_by_attr(key, value, page_size, offset).await?;
+
+        return Ok(HttpResponse::Ok()
+            .insert_header(("x-user-count", count))
+            .json(users));
+    }
 
     let user_count = User::count().await?;
     let ssp_threshold = RauthyConfig::get().vars.server.ssp_threshold;
@@ -169,7 +209,7 @@ pub async fn post_users(
         .tx_events
         .send_async(Event::new_user(
             user.email.clone(),
-            real_ip_from_req(&req)?.to_string(),
+            real_ip(&req)?.to_string(),
         ))
         .await
         .unwrap();
@@ -178,7 +218,7 @@ pub async fn post_users(
             .tx_events
             .send_async(Event::new_rauthy_admin(
                 user.email.clone(),
-                real_ip_from_req(&req)?.to_string(),
+                real_ip(&req)?.to_string(),
             ))
             .await
             .unwrap();
@@ -289,13 +329,79 @@ pub async fn put_cust_attr(
     Ok(HttpResponse::Ok().json(UserAttrConfigValueResponse::from(entity)))
 }
 
+/// Get the values of all users for an additional custom user attribute
+///
+/// With `distinct=true`, each distinct value is returned together with the amount of users
+/// having it, instead of each single user value. The total amount of results for the pagination
+/// is returned in the `x-value-count` header.
+#[utoipa::path(
+    get,
+    path = "/users/attr/{name}/values",
+    tag = "users",
+    params(UserAttrValuesParams),
+    responses(
+        (status = 200, description = "Ok - `[UserAttrDistinctValueResponse]` with `distinct=true`", body = [UserAttrUsageResponse]),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "NotFound"),
+    ),
+)]
+#[get("/users/attr/{name}/values")]
+pub async fn get_cust_attr_values(
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+    Query(params): Query<UserAttrValuesParams>,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::UserAttributes, AccessRights::Read)?;
+    params.validate()?;
+
+    let attr = UserAttrConfigEntity::find(path.into_inner()).await?;
+    let page_size = params.page_size.unwrap_or(100) as i64;
+    let offset = params.offset.unwrap_or(0) as i64;
+
+    if params.distinct == Some(true) {
+        let count = UserAttrValueEntity::count_distinct_by_key(&attr.name).await?;
+        let values = UserAttrValueEntity::find_distinct_by_key(&attr.name, page_size, offset)
+            .await?
+            .into_iter()
+            .map(|(value, count)| UserAttrDistinctValueResponse {
+                value: serde_json::from_slice(&value).unwrap_or_default(),
+                count,
+            })
+            .collect::<Vec<_>>();
+
+        Ok(HttpResponse::Ok()
+            .insert_header(("x-value-count", count))
+            .json(values))
+    } else {
+        let value = params.value.as_deref();
+        let count = UserAttrValueEntity::count_by_key(&attr.name, value).await?;
+        let values = UserAttrValueEntity::find_by_key(&attr.name, value, page_size, offset)
+            .await?
+            .into_iter()
+            .map(|v| UserAttrUsageResponse {
+                value: serde_json::from_slice(&v.value).unwrap_or_default(),
+                user_id: v.user_id,
+            })
+            .collect::<Vec<_>>();
+
+        Ok(HttpResponse::Ok()
+            .insert_header(("x-value-count", count))
+            .json(values))
+    }
+}
+
 /// Delete an additional custom user attribute
+///
+/// If any user has a value for this attribute, the request will be rejected with the amount of
+/// affected users, unless `confirm=true` is given. The values will be deleted in that case.
 #[utoipa::path(
     delete,
     path = "/users/attr/{name}",
     tag = "users",
+    params(UserAttrDeleteParams),
     responses(
-        (status = 204, description = "NoContent"),
+        (status = 200, description = "Ok"),
+        (status = 400, description = "BadRequest"),
         (status = 403, description = "Forbidden"),
         (status = 404, description = "NotFound"),
     ),
@@ -304,12 +410,26 @@ pub async fn put_cust_attr(
 pub async fn delete_cust_attr(
     path: web::Path<String>,
     principal: ReqPrincipal,
+    Query(params): Query<UserAttrDeleteParams>,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal
         .validate_api_key_or_admin_session(AccessGroup::UserAttributes, AccessRights::Delete)?;
 
     let attr_name = path.into_inner();
 
+    if params.confirm != Some(true) {
+        let count = UserAttrValueEntity::count_by_key(&attr_name, None).await?;
+        if count > 0 {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!(
+                    "{count} users have a value for this attribute - add `confirm=true` to \
+                    delete them together with the attribute"
+                ),
+            ));
+        }
+    }
+
     let clients_scim = ClientScim::find_with_attr_mapping(&attr_name).await?;
     if !clients_scim.is_empty() {
         let groups = Group::find_all().await?;
@@ -405,7 +525,7 @@ pub async fn post_users_register(
 #[inline(always)]
 pub async fn post_users_register_handle(
     req: HttpRequest,
-    payload: NewUserRegistrationRequest,
+    mut payload: NewUserRegistrationRequest,
 ) -> Result<HttpResponse, ErrorResponse> {
     if !RauthyConfig::get().vars.user_registration.enable {
         return Err(ErrorResponse::new(
@@ -423,6 +543,8 @@ pub async fn post_users_register_handle(
     }
     .validate()?;
 
+    // the domain checks below must never be bypassed by different casing
+    payload.email = User::normalize_email(&payload.email);
     let reg = &RauthyConfig::get().vars.user_registration;
 
     if let Some(restriction) = &reg.domain_restriction {
@@ -472,7 +594,7 @@ pub async fn post_users_register_handle(
         Err(err) => {
             // email is probably registered already.
             if let Ok(user) = User::find_by_email(email).await {
-                let ip = real_ip_from_req(&req)?;
+                let ip = real_ip(&req)?;
                 info!("User registration request for already registered account from {ip}");
 
                 let rate_limit = EmailRateLimit::RegisteredAlready {
@@ -494,17 +616,20 @@ pub async fn post_users_register_handle(
         }
     };
 
+    let ip = real_ip(&req)?.to_string();
+    let event = if reg.require_admin_approval {
+        Event::user_registration_pending(user.email.clone(), ip)
+    } else {
+        Event::new_user(user.email.clone(), ip)
+    };
     RauthyConfig::get()
         .tx_events
-        .send_async(Event::new_user(
-            user.email.clone(),
-            real_ip_from_req(&req)?.to_string(),
-        ))
+        .send_async(event)
         .await
         .unwrap();
 
     if let Some(tos) = ToS::find_latest().await? {
-        let ip = real_ip_from_req(&req)?;
+        let ip = real_ip(&req)?;
         let loc = get_location(&req, ip)?;
         ToSUserAccept::create(user.id.clone(), tos.ts, ip, loc).await?;
     }
@@ -524,6 +649,116 @@ pub async fn post_users_register_handle(
         .finish())
 }
 
+/// Returns the defaults for new users from the open registration
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/users/register/config",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok", body = UserRegistrationConfigResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/users/register/config")]
+pub async fn get_users_register_config(
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Read)?;
+
+    let config = UserRegistrationConfig::find().await?;
+    Ok(HttpResponse::Ok().json(UserRegistrationConfigResponse::from(config)))
+}
+
+/// Updates the default roles and groups for new users from the open registration
+///
+/// The `rauthy_admin` role can never be a default.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    put,
+    path = "/users/register/config",
+    tag = "users",
+    request_body = UserRegistrationConfigRequest,
+    responses(
+        (status = 200, description = "Ok", body = UserRegistrationConfigResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[put("/users/register/config")]
+pub async fn put_users_register_config(
+    principal: ReqPrincipal,
+    Json(payload): Json<UserRegistrationConfigRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Update)?;
+    payload.validate()?;
+
+    let config = UserRegistrationConfig::try_from_req(payload).await?;
+    config.save().await?;
+
+    Ok(HttpResponse::Ok().json(UserRegistrationConfigResponse::from(config)))
+}
+
+/// Approves a pending user from the open registration
+///
+/// Only needed with `user_registration.require_admin_approval = true`, where new users are
+/// created as disabled. This endpoint enables the user, which does the same as setting
+/// `enabled: true` via `PUT /users/{id}`.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/users/{id}/approve",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok", body = UserResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[post("/users/{id}/approve")]
+pub async fn post_user_approve(
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Update)?;
+
+    let mut user = User::find(path.into_inner()).await?;
+    if user.enabled {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "User is enabled already",
+        ));
+    }
+
+    user.enabled = true;
+    user.save(None).await?;
+    info!("Registration for user {} has been approved", user.email);
+
+    let cloned = user.clone();
+    task::spawn(async move {
+        let email = cloned.email.clone();
+        if let Err(err) = ClientScim::create_update_user(cloned).await {
+            error!(
+                "Error during SCIM Client user update for {}: {:?}",
+                email, err
+            );
+        }
+    });
+
+    let user_values = UserValues::find(&user.id).await?;
+    Ok(HttpResponse::Ok().json(user.into_response(user_values)))
+}
+
 /// Returns a single user by its *id*
 #[utoipa::path(
     get,
@@ -565,6 +800,198 @@ pub async fn get_user_by_id(
     Ok(HttpResponse::Ok().json(user.into_response(values)))
 }
 
+/// Returns all clients the given user has access to
+///
+/// Only enabled, statically registered clients with the `authorization_code` flow are
+/// returned, which means all the clients a user can log in to via the browser. The list is
+/// meant to be rendered on the account portal.
+///
+/// **Permissions**
+/// - rauthy_admin
+/// - user itself
+/// - api-key: `users`: `read`
+#[utoipa::path(
+    get,
+    path = "/users/{id}/clients",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok", body = [UserClientResponse]),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[get("/users/{id}/clients")]
+pub async fn get_user_clients(
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    let user_id = path.into_inner();
+    if principal
+        .validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Read)
+        .is_err()
+    {
+        principal.validate_user_session(&user_id)?;
+    }
+
+    let user = User::find(user_id).await?;
+
+    let mut resp = Vec::new();
+    for client in Client::find_all().await? {
+        if !client.enabled
+            || client.id == "rauthy"
+            || client.is_dynamic()
+            || client.validate_flow("authorization_code").is_err()
+            || client.validate_user_access(&user).is_err()
+        {
+            continue;
+        }
+
+        let logo_updated = Logo::find_updated(&client.id, &LogoType::Client).await?;
+        resp.push(UserClientResponse {
+            id: client.id,
+            name: client.name,
+            client_uri: client.client_uri,
+            logo_updated,
+        });
+    }
+
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+/// Returns the effective permissions for the given user id
+///
+/// This is a read-only aggregation of all roles, groups, scope mappings, custom attributes,
+/// API keys with access to users, devices and MFA methods for a user, which is helpful when
+/// troubleshooting authorization issues. Each item is annotated with a `source`, showing where it
+/// comes from.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/users/{id}/effective_permissions",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok", body = UserEffectivePermissionsResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[get("/users/{id}/effective_permissions")]
+pub async fn get_user_effective_permissions(
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Read)?;
+
+    let user = User::find(path.into_inner()).await?;
+
+    let roles = user
+        .get_roles()
+        .into_iter()
+        .map(|name| EffectivePermissionItem {
+            name,
+            source: EffectivePermissionSource::Direct,
+        })
+        .collect::<Vec<_>>();
+    let groups = user
+        .get_groups()
+        .into_iter()
+        .map(|name| EffectivePermissionItem {
+            name,
+            source: EffectivePermissionSource::Direct,
+        })
+        .collect::<Vec<_>>();
+
+    let values = UserAttrValueEntity::find_for_user(&user.id).await?;
+    let mut user_attrs = Vec::with_capacity(values.len());
+    for value in values {
+        user_attrs.push(EffectivePermissionAttr {
+            key: value.key,
+            value: serde_json::from_slice(&value.value)?,
+            source: EffectivePermissionSource::Direct,
+        });
+    }
+    for config in UserAttrConfigEntity::find_with_default_value().await? {
+        if user_attrs.iter().any(|a| a.key == config.name) {
+            continue;
+        }
+        if let Some(default) = config.default_value {
+            user_attrs.push(EffectivePermissionAttr {
+                key: config.name,
+                value: serde_json::from_slice(&default)?,
+                source: EffectivePermissionSource::AttrDefault,
+            });
+        }
+    }
+
+    let default_scopes = Scope::find_all()
+        .await?
+        .into_iter()
+        .filter(|s| {
+            Scope::is_custom(&s.name)
+                && s.attr_include_access
+                    .iter()
+                    .chain(s.attr_include_id.iter())
+                    .flat_map(|csv| csv.split(','))
+                    .any(|attr| user_attrs.iter().any(|a| a.key == attr))
+        })
+        .map(|s| EffectivePermissionItem {
+            name: s.name,
+            source: EffectivePermissionSource::ScopeMapping,
+        })
+        .collect::<Vec<_>>();
+
+    let now = Utc::now().timestamp();
+    let mut api_keys = Vec::new();
+    for entity in ApiKeyEntity::find_all().await? {
+        let key = entity.into_api_key()?;
+        if key.expires.is_some_and(|exp| exp < now) {
+            continue;
+        }
+        if let Some(access) = key
+            .access
+            .into_iter()
+            .find(|a| a.group == AccessGroup::Users)
+        {
+            api_keys.push(EffectivePermissionApiKey {
+                name: key.name,
+                expires: key.expires,
+                access_rights: access
+                    .access_rights
+                    .into_iter()
+                    .map(rauthy_api_types::api_keys::AccessRights::from)
+                    .collect(),
+                source: EffectivePermissionSource::ApiKey,
+            });
+        }
+    }
+
+    let trusted_devices = DeviceEntity::find_for_user(&user.id).await?.len();
+
+    let mfa_methods = PasskeyEntity::find_for_user(&user.id)
+        .await?
+        .into_iter()
+        .map(|pk| EffectivePermissionItem {
+            name: pk.name,
+            source: EffectivePermissionSource::Passkey,
+        })
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(UserEffectivePermissionsResponse {
+        user_id: user.id,
+        roles,
+        groups,
+        default_scopes,
+        user_attrs,
+        api_keys,
+        trusted_devices,
+        mfa_methods,
+    }))
+}
+
 /// Returns the additional custom attributes for the given user id
 #[utoipa::path(
     get,
@@ -777,7 +1204,7 @@ pub async fn post_user_mfa_token(
         ));
     }
 
-    let ip = real_ip_from_req(&req)?;
+    let ip = real_ip(&req)?;
     let token = MfaModToken::new(user_id.to_string(), ip).await?;
 
     Ok(HttpResponse::Ok().json(MfaModTokenResponse::from(token)))
@@ -909,9 +1336,14 @@ async fn validate_user_picture_access(
 
     if let Ok(bearer) = get_bearer_token_from_header(req.headers()) {
         let mut buf = Vec::with_capacity(512);
-        if JwtToken::validate_claims_into(&bearer, None, 0, &mut buf)
-            .await
-            .is_ok()
+        if JwtToken::validate_claims_into(
+            &bearer,
+            None,
+            RauthyConfig::get().vars.access.jwt_leeway,
+            &mut buf,
+        )
+        .await
+        .is_ok()
         {
             let claims: JwtCommonClaims = serde_json::from_slice(&buf)?;
             debug!("token claims are ok: {:?}", claims);
@@ -1008,68 +1440,387 @@ pub async fn get_user_devices(
 
 /// GET all devices for this user linked via the `device_code` flow
 #[utoipa::path(
-    put,
-    path = "/users/{id}/devices",
+    put,
+    path = "/users/{id}/devices",
+    tag = "users",
+    request_body = DeviceRequest,
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[put("/users/{id}/devices")]
+pub async fn put_user_device_name(
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+    Json(payload): Json<DeviceRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    let user_id = path.into_inner();
+    principal.validate_user_or_admin(&user_id)?;
+    payload.validate()?;
+
+    if let Some(name) = &payload.name {
+        DeviceEntity::update_name(&payload.device_id, &user_id, name).await?;
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// DELETE all existing refresh tokens for a user device
+///
+/// This does NOT revoke already existing access tokens, since they are stateless!
+#[utoipa::path(
+    delete,
+    path = "/users/{id}/devices",
+    tag = "users",
+    request_body = DeviceRequest,
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[delete("/users/{id}/devices")]
+pub async fn delete_user_device(
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+    Json(payload): Json<DeviceRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    let user_id = path.into_inner();
+    principal.validate_user_or_admin(&user_id)?;
+    payload.validate()?;
+
+    let device = DeviceEntity::find(&payload.device_id).await?;
+    if device.user_id.as_deref() != Some(&user_id) {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::Forbidden,
+            "You don't have access to this device".to_string(),
+        ));
+    }
+
+    DeviceEntity::revoke_refresh_tokens(&payload.device_id).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// GET all applications, which hold refresh tokens for this user
+///
+/// Active refresh tokens are aggregated by client. Token values are never returned. Devices from
+/// the `device_code` flow are managed via `/users/{id}/devices`.
+///
+/// **Permissions**
+/// - authenticated user
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/users/{id}/grants",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok", body = [UserGrantResponse]),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/users/{id}/grants")]
+pub async fn get_user_grants(
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    let user_id = path.into_inner();
+    principal.validate_user_or_admin(&user_id)?;
+
+    let grants = UserGrant::find_for_user(&user_id).await?;
+    let mut resp = Vec::with_capacity(grants.len());
+    for grant in grants {
+        resp.push(grant.into_response().await);
+    }
+
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+/// DELETE all refresh tokens of a single application for this user
+///
+/// This does NOT revoke already existing access tokens, since they are stateless!
+///
+/// **Permissions**
+/// - authenticated user
+/// - rauthy_admin
+#[utoipa::path(
+    delete,
+    path = "/users/{id}/grants/{client_id}",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[delete("/users/{id}/grants/{client_id}")]
+pub async fn delete_user_grant(
+    path: web::Path<(String, String)>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    let (user_id, client_id) = path.into_inner();
+    principal.validate_user_or_admin(&user_id)?;
+
+    UserGrant::revoke(&user_id, &client_id).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// GET the notification preferences for security E-Mails
+///
+/// Categories the user has not set explicitly will return the instance default.
+///
+/// **Permissions**
+/// - authenticated user
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/users/{id}/notification_prefs",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok", body = [NotificationPrefResponse]),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/users/{id}/notification_prefs")]
+pub async fn get_user_notification_prefs(
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    let user_id = path.into_inner();
+    principal.validate_user_or_admin(&user_id)?;
+
+    let prefs = UserNotificationPref::resolve_for_user(&user_id).await?;
+    Ok(HttpResponse::Ok().json(prefs))
+}
+
+/// PUT the notification preferences for security E-Mails
+///
+/// Only the given categories will be updated. Non-suppressible categories, like password resets,
+/// cannot be disabled.
+///
+/// **Permissions**
+/// - authenticated user
+/// - rauthy_admin
+#[utoipa::path(
+    put,
+    path = "/users/{id}/notification_prefs",
+    tag = "users",
+    request_body = NotificationPrefsRequest,
+    responses(
+        (status = 200, description = "Ok", body = [NotificationPrefResponse]),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[put("/users/{id}/notification_prefs")]
+pub async fn put_user_notification_prefs(
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+    Json(payload): Json<NotificationPrefsRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    let user_id = path.into_inner();
+    principal.validate_user_or_admin(&user_id)?;
+    payload.validate()?;
+
+    // make sure we return a proper error instead of a FK violation
+    User::find(user_id.clone()).await?;
+    UserNotificationPref::upsert_for_user(user_id.clone(), payload).await?;
+
+    let prefs = UserNotificationPref::resolve_for_user(&user_id).await?;
+    Ok(HttpResponse::Ok().json(prefs))
+}
+
+/// GET the upstream federation details for a user
+///
+/// Returns the link to the upstream auth provider, the most recent validated claims received from
+/// upstream and the last 10 upstream driven changes of the user's E-Mail and name. If the claim
+/// retention has been disabled via `auth_providers.retain_upstream_claims`, only the link
+/// metadata is returned.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/users/{id}/federation",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok", body = UserFederationResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[get("/users/{id}/federation")]
+pub async fn get_user_federation(
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Read)?;
+
+    let user = User::find(path.into_inner()).await?;
+    let resp = UserFederationClaims::build_response(user).await?;
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+/// Reindex a single user
+///
+/// Rebuilds all derived data for a user, which may be stale after bulk DB migrations or manual
+/// data fixes. All cache entries for the user and its sessions are invalidated, the group
+/// memberships are validated against the existing groups and the cache is warmed up with fresh
+/// data afterward.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/users/{id}/reindex",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok", body = UserReindexResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[post("/users/{id}/reindex")]
+pub async fn post_user_reindex(
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Update)?;
+
+    let groups = User::reindex_groups().await?;
+    let resp = User::reindex(path.into_inner(), &groups).await?;
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+/// Reindex all users
+///
+/// Does the same as `/users/{id}/reindex` for all existing users. The progress is reported as
+/// `UsersReindexProgress` via Server Sent Events. The last message will have `finished: true`.
+/// Once started, the reindex will continue even if the client goes away.
+///
+/// This is allowed only once per hour.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/users/reindex_all",
     tag = "users",
-    request_body = DeviceRequest,
     responses(
-        (status = 200, description = "Ok"),
+        (status = 200, description = "Ok", body = UsersReindexProgress),
         (status = 401, description = "Unauthorized", body = ErrorResponse),
         (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 429, description = "TooManyRequests", body = ErrorResponse),
     ),
 )]
-#[put("/users/{id}/devices")]
-pub async fn put_user_device_name(
-    path: web::Path<String>,
+#[post("/users/reindex_all")]
+pub async fn post_users_reindex_all(
     principal: ReqPrincipal,
-    Json(payload): Json<DeviceRequest>,
-) -> Result<HttpResponse, ErrorResponse> {
-    let user_id = path.into_inner();
-    principal.validate_user_or_admin(&user_id)?;
-    payload.validate()?;
+) -> Result<impl Responder, ErrorResponse> {
+    principal.validate_admin_session()?;
+    User::reindex_all_rate_limit().await?;
 
-    if let Some(name) = &payload.name {
-        DeviceEntity::update_name(&payload.device_id, &user_id, name).await?;
-    }
+    let (tx, rx) = mpsc::channel(10);
+    task::spawn(User::reindex_all(tx));
 
-    Ok(HttpResponse::Ok().finish())
+    Ok(sse::Sse::from_infallible_receiver(rx)
+        .with_keep_alive(Duration::from_secs(
+            RauthyConfig::get().vars.server.see_keep_alive as u64,
+        ))
+        .with_retry_duration(Duration::from_secs(10)))
 }
 
-/// DELETE all existing refresh tokens for a user device
+/// Disable users in bulk
 ///
-/// This does NOT revoke already existing access tokens, since they are stateless!
+/// Off-boards all given users at once. They are disabled with a single statement and all their
+/// sessions, refresh tokens and issued tokens are revoked. A single summary Event is created,
+/// while the per-user audit Events, backchannel logouts and SCIM updates are done in the
+/// background afterward.
+///
+/// **Permissions**
+/// - rauthy_admin
 #[utoipa::path(
-    delete,
-    path = "/users/{id}/devices",
+    post,
+    path = "/users/disable_bulk",
     tag = "users",
-    request_body = DeviceRequest,
+    request_body = DisableUsersBulkRequest,
     responses(
-        (status = 200, description = "Ok"),
+        (status = 200, description = "Ok", body = DisableUsersBulkResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
         (status = 401, description = "Unauthorized", body = ErrorResponse),
         (status = 403, description = "Forbidden", body = ErrorResponse),
     ),
 )]
-#[delete("/users/{id}/devices")]
-pub async fn delete_user_device(
-    path: web::Path<String>,
+#[post("/users/disable_bulk")]
+pub async fn post_users_disable_bulk(
+    req: HttpRequest,
     principal: ReqPrincipal,
-    Json(payload): Json<DeviceRequest>,
+    Json(payload): Json<DisableUsersBulkRequest>,
 ) -> Result<HttpResponse, ErrorResponse> {
-    let user_id = path.into_inner();
-    principal.validate_user_or_admin(&user_id)?;
+    principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Update)?;
     payload.validate()?;
 
-    let device = DeviceEntity::find(&payload.device_id).await?;
-    if device.user_id.as_deref() != Some(&user_id) {
+    if let Ok(uid) = principal.user_id()
+        && payload.user_ids.iter().any(|id| id == uid)
+    {
         return Err(ErrorResponse::new(
-            ErrorResponseType::Forbidden,
-            "You don't have access to this device".to_string(),
+            ErrorResponseType::BadRequest,
+            "You cannot disable your own account",
         ));
     }
 
-    DeviceEntity::revoke_refresh_tokens(&payload.device_id).await?;
+    let res = User::disable_bulk(&payload.user_ids).await?;
+    info!(
+        requested = payload.user_ids.len(),
+        disabled = res.disabled.len(),
+        "Users disabled in bulk"
+    );
 
-    Ok(HttpResponse::Ok().finish())
+    let initiator = principal
+        .user_id()
+        .map(|id| format!("by user {id}"))
+        .unwrap_or_else(|_| "by API key".to_string());
+    Event::users_disabled_bulk(res.disabled.len(), initiator, real_ip(&req)?)
+        .send()
+        .await?;
+
+    let disabled = res.disabled.clone();
+    task::spawn(async move {
+        for user_id in disabled {
+            if let Err(err) = Event::user_disabled(&user_id).insert().await {
+                error!(?err, "Inserting UserDisabled Event for {user_id}");
+            }
+            if let Err(err) = logout::execute_backchannel_logout(None, Some(user_id.clone())).await
+            {
+                error!(?err, "Backchannel logout after bulk disable for {user_id}");
+            }
+            match User::find(user_id.clone()).await {
+                Ok(user) => {
+                    if let Err(err) = ClientScim::create_update_user(user).await {
+                        error!(
+                            ?err,
+                            "SCIM Client user update after bulk disable for {user_id}"
+                        );
+                    }
+                }
+                Err(err) => error!(?err, "Looking up user {user_id} after bulk disable"),
+            }
+        }
+    });
+
+    Ok(HttpResponse::Ok().json(DisableUsersBulkResponse {
+        disabled: res.disabled,
+        revoked: res.revoked,
+    }))
 }
 
 /// Endpoint for resetting passwords
@@ -1137,6 +1888,7 @@ pub async fn get_user_password_reset(
         .unwrap_or("text/html");
     let no_html = accept == "application/json";
 
+    let req_err = req.clone();
     match password_reset::handle_get_pwd_reset(req, user_id, reset_id, no_html).await {
         Ok((content, cookie)) => {
             if no_html {
@@ -1172,16 +1924,7 @@ pub async fn get_user_password_reset(
         }
         Err(err) => {
             debug!("Error during get_user_password_reset: {:?}", err);
-            let status = err.status_code();
-            let body = Error3Html::build(
-                &lang,
-                ThemeCssFull::find_theme_ts_rauthy()
-                    .await
-                    .unwrap_or_else(|_| Utc::now().timestamp()),
-                status,
-                err.message,
-            );
-            ErrorHtml::response(body, status)
+            flow_error_response(&req_err, err, ErrorFlow::MagicLink).await
         }
     }
 }
@@ -1274,7 +2017,7 @@ async fn user_revoke_handle(
     bad_ip: IpAddr,
 ) -> Result<(), ErrorResponse> {
     let revoke = UserRevoke::find(user_id).await?;
-    if revoke.code != code {
+    if !secure_compare(&revoke.code, &code) {
         return Err(ErrorResponse::new(
             ErrorResponseType::BadRequest,
             "invalid revoke code",
@@ -1344,6 +2087,79 @@ pub async fn get_user_webauthn_passkeys(
     Ok(HttpResponse::Ok().json(pks))
 }
 
+/// Exports all WebAuthn Passkeys for a user
+///
+/// The export contains only public key material and can be used for disaster recovery or a
+/// migration to another instance with the same `webauthn.rp_id`.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/users/{id}/webauthn/export",
+    tag = "mfa",
+    responses(
+        (status = 200, description = "Ok", body = PasskeysExport),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[get("/users/{id}/webauthn/export")]
+pub async fn get_user_webauthn_export(
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Read)?;
+
+    let user = User::find(id.into_inner()).await?;
+    let export = PasskeyEntity::export_for_user(&user).await?;
+
+    Ok(HttpResponse::Ok().json(export))
+}
+
+/// Imports WebAuthn Passkeys for a user from an export
+///
+/// Must be enabled with `webauthn.allow_credential_import`. The credentials must have been
+/// registered for the same `webauthn.rp_id` and no credential id may exist already.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/users/{id}/webauthn/import",
+    tag = "mfa",
+    request_body = PasskeysExport,
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[post("/users/{id}/webauthn/import")]
+pub async fn post_user_webauthn_import(
+    req: HttpRequest,
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+    Json(payload): Json<PasskeysExport>,
+) -> Result<HttpResponse, ErrorResponse> {
+    // Note: Currently, this is not allowed with an ApiKey on purpose.
+    principal.validate_admin_session()?;
+    payload.validate()?;
+
+    let user = User::find(id.into_inner()).await?;
+    let email = user.email.clone();
+    let count = PasskeyEntity::import_for_user(user, payload).await?;
+
+    Event::passkey_imported(email, count as i64, real_ip(&req)?)
+        .send()
+        .await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
 /// Starts the authentication process for a WebAuthn Device for this user
 ///
 /// **Permissions**
@@ -1451,9 +2267,50 @@ pub async fn post_webauthn_auth_finish(
     // This here will simply fail, if the secret code from the /start does not exist
     // -> indirect validation through existing code.
 
-    let principal = principal.into_inner();
-    let res = webauthn::auth_finish(id, &req, browser_id, principal.session, payload).await?;
-    Ok(res.into_response())
+    let mut session = principal.into_inner().session;
+    let sid = session.as_ref().map(|s| s.id.clone());
+    let res = webauthn::auth_finish(id, &req, browser_id, session.as_mut(), payload).await?;
+
+    let mut resp = res.into_response();
+    if let Some(session) = session
+        && Some(&session.id) != sid.as_ref()
+    {
+        // the session id has been rotated during the login
+        session.add_cookies(&mut resp)?;
+    }
+    Ok(resp)
+}
+
+/// Clears the sign counter anomaly flag of a Passkey after an investigation
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    delete,
+    path = "/users/{id}/webauthn/counter_anomaly/{name}",
+    tag = "mfa",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[delete("/users/{id}/webauthn/counter_anomaly/{name}")]
+pub async fn delete_webauthn_counter_anomaly(
+    path: web::Path<(String, String)>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Update)?;
+
+    let (id, name) = path.into_inner();
+    PasskeyEntity::clear_counter_anomaly(&id, &name).await?;
+    info!(
+        "Passkey counter anomaly cleared for user {} for key {}",
+        id, name
+    );
+
+    Ok(HttpResponse::Ok().finish())
 }
 
 /// Deletes the WebAuthn Device for this user in the given slot
@@ -1504,7 +2361,7 @@ pub async fn delete_webauthn(
                 ));
             };
             let token = MfaModToken::find(&token_id).await?;
-            let ip = real_ip_from_req(&req)?;
+            let ip = real_ip(&req)?;
             token.validate(principal.user_id()?, ip)?;
 
             warn!("Passkey delete for user {} for key {}", id, name);
@@ -1572,7 +2429,7 @@ pub async fn post_webauthn_reg_start(
             ));
         }
         let mod_token = MfaModToken::find(payload.mfa_mod_token_id.as_ref().unwrap()).await?;
-        let ip = real_ip_from_req(&req)?;
+        let ip = real_ip(&req)?;
         mod_token.validate(principal.user_id()?, ip)?;
 
         // validate that Principal matches the user
@@ -1780,7 +2637,7 @@ pub async fn post_user_password_request_reset(
     info!(
         "Password reset request for '{}' from IP {}",
         payload.email,
-        real_ip_from_req(&req)?
+        real_ip(&req)?
     );
     let challenge = Pow::validate(&payload.pow)?;
     PowEntity::check_prevent_reuse(challenge.to_string()).await?;
@@ -1969,7 +2826,7 @@ async fn handle_put_user_by_id(
             .tx_events
             .send_async(Event::new_rauthy_admin(
                 user.email.clone(),
-                real_ip_from_req(&req)?.to_string(),
+                real_ip(&req)?.to_string(),
             ))
             .await
             .unwrap();
@@ -2179,6 +3036,115 @@ pub async fn post_user_self_convert_passkey(
     Ok(HttpResponse::Ok().finish())
 }
 
+/// Removes the upstream auth provider link and converts the user back to a local account
+///
+/// If the user has neither a password nor a passkey, a magic link will be sent out to set up
+/// a new local login method. Without a configured E-Mail delivery, the request will be refused
+/// in this case.
+///
+/// **Permissions**
+/// - authenticated user
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/users/{id}/provider_unlink",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok", body = UserResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[post("/users/{id}/provider_unlink")]
+pub async fn post_user_provider_unlink(
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+    req: HttpRequest,
+) -> Result<HttpResponse, ErrorResponse> {
+    let id = id.into_inner();
+    principal.validate_user_or_admin(&id)?;
+
+    let user = User::provider_unlink_local(id).await?;
+
+    Event::user_provider_link(
+        format!(
+            "`{}` unlinked from its upstream auth provider by `{}`",
+            user.email,
+            principal.user_id()?
+        ),
+        real_ip(&req)?,
+    )
+    .send()
+    .await?;
+
+    Ok(HttpResponse::Ok().json(user.into_response(None)))
+}
+
+/// Starts linking the user account to an upstream auth provider
+///
+/// Redirects to the login of the upstream provider from `provider_id` in the payload. On the
+/// callback, the existing account is linked instead of creating a new one. The link is bound to
+/// the current session and each one is logged as an Event.
+///
+/// **Permissions**
+/// - authenticated user
+#[utoipa::path(
+    post,
+    path = "/users/{id}/provider_link",
+    tag = "users",
+    request_body = ProviderLoginRequest,
+    responses(
+        (status = 202, description = "Accepted"),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[post("/users/{id}/provider_link")]
+pub async fn post_user_provider_link(
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+    req: HttpRequest,
+    Json(payload): Json<ProviderLoginRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    let id = id.into_inner();
+    principal.validate_user_session(&id)?;
+
+    let provider_id = payload.provider_id.clone();
+    auth_providers::provider_link_start(&req, id, provider_id, payload).await
+}
+
+/// Removes the link between the user account and its upstream auth provider
+///
+/// The user account must have been set up with at least a password or a passkey. Otherwise,
+/// this endpoint will return an error. Each unlink is logged as an Event.
+///
+/// **Permissions**
+/// - authenticated user
+#[utoipa::path(
+    delete,
+    path = "/users/{id}/provider_link",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok", body = UserResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[delete("/users/{id}/provider_link")]
+pub async fn delete_user_provider_link(
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+    req: HttpRequest,
+) -> Result<HttpResponse, ErrorResponse> {
+    let id = id.into_inner();
+    principal.validate_user_session(&id)?;
+
+    auth_providers::provider_unlink(&req, id).await
+}
+
 /// Retrieve the UserValues config.
 ///
 /// This is the same config as the one being inserted into the HTML `<template>` during registration
@@ -2315,24 +3281,82 @@ pub async fn put_user_self_preferred_username(
 
 /// Deletes a user
 ///
+/// With `erasure=true`, the user will be erased permanently for a GDPR Art. 17 request. This
+/// removes all linked data and deletes or pseudonymizes audit Events mentioning the user,
+/// depending on `user_delete.erasure_delete_audit_logs`. It requires the
+/// `x-rauthy-erasure-confirm: ERASE_{user_id}` header as a safeguard.
+///
 /// **Permissions**
 /// - rauthy_admin
 #[utoipa::path(
     delete,
     path = "/users/{id}",
     tag = "users",
+    params(UserDeleteParams),
     responses(
+        (status = 200, description = "Ok - erasure only", body = ErasureResult),
         (status = 204, description = "NoContent"),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
         (status = 401, description = "Unauthorized", body = ErrorResponse),
         (status = 403, description = "Forbidden", body = ErrorResponse),
     ),
 )]
 #[delete("/users/{id}")]
 pub async fn delete_user_by_id(
+    req: HttpRequest,
     id: web::Path<String>,
     principal: ReqPrincipal,
+    params: Query<UserDeleteParams>,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Delete)?;
     let user = User::find(id.into_inner()).await?;
-    handle_user_delete(user).await
+
+    if params.erasure == Some(true) {
+        let confirm = format!("ERASE_{}", user.id);
+        let is_confirmed = req
+            .headers()
+            .get(HEADER_ERASURE_CONFIRM)
+            .map(|v| v.as_bytes() == confirm.as_bytes())
+            .unwrap_or(false);
+        if !is_confirmed {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("erasure requires the '{HEADER_ERASURE_CONFIRM}: {confirm}' header"),
+            ));
+        }
+
+        handle_user_erasure(user, real_ip(&req)?).await
+    } else {
+        handle_user_delete(user).await
+    }
+}
+
+async fn handle_user_erasure(user: User, ip: IpAddr) -> Result<HttpResponse, ErrorResponse> {
+    logout::execute_backchannel_logout(None, Some(user.id.clone())).await?;
+
+    let delete_audit_logs = RauthyConfig::get()
+        .vars
+        .user_delete
+        .erasure_delete_audit_logs;
+    let entities_deleted = user.erase(delete_audit_logs).await?;
+    let pseudonym = erasure_pseudonym(&user.id);
+
+    let clients_scim = ClientScim::find_all().await?;
+    task::spawn(async move {
+        for client_scim in clients_scim {
+            if let Err(err) = client_scim.delete_user(&user).await {
+                error!(
+                    ?err,
+                    "Error during SCIM Client user erasure for {}", user.id
+                );
+            }
+        }
+    });
+
+    Event::user_erased(pseudonym.clone(), ip).send().await?;
+
+    Ok(HttpResponse::Ok().json(ErasureResult {
+        pseudonym: (!delete_audit_logs).then_some(pseudonym),
+        entities_deleted,
+    }))
 }
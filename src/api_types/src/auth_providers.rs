@@ -1,23 +1,25 @@
-use crate::cust_validation::validate_vec_scopes;
+use crate::cust_validation::{validate_uri_or_empty, validate_vec_scopes};
 use rauthy_common::regex::{
-    RE_ALNUM, RE_ATPROTO_HANDLE, RE_CLIENT_ID, RE_CLIENT_NAME, RE_CODE_CHALLENGE, RE_SCOPE_SPACE,
-    RE_URI,
+    RE_ALNUM, RE_ATPROTO_HANDLE, RE_CLAIM_NAME, RE_CLIENT_ID, RE_CLIENT_NAME, RE_CODE_CHALLENGE,
+    RE_SCOPE_SPACE, RE_URI,
 };
 use rauthy_derive::FromPgRow;
 use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
+use std::collections::HashMap;
+use utoipa::{IntoParams, ToSchema};
 use validator::Validate;
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum AuthProviderType {
+    Apple,
     Custom,
     GitHub,
     Google,
     OIDC,
 }
 
-#[derive(Deserialize, Validate, ToSchema)]
+#[derive(Serialize, Deserialize, Validate, ToSchema)]
 pub struct ProviderRequest {
     /// Validation: `[a-zA-Z0-9À-ÿ-\s]{2,128}]`
     #[validate(regex(path = "*RE_CLIENT_NAME", code = "[a-zA-Z0-9À-ɏ-\\s]{2,128}"))]
@@ -34,18 +36,36 @@ pub struct ProviderRequest {
     /// Validation: `[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]`
     #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]"))]
     pub token_endpoint: String,
-    /// Validation: `[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]`
-    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]"))]
+    /// Can only be empty for `apple`, which does not provide a userinfo endpoint.
+    ///
+    /// Validation: `[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]*`
+    #[validate(custom(function = "validate_uri_or_empty"))]
+    #[serde(default)]
     pub userinfo_endpoint: String,
     /// Validation: `[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]`
     #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]"))]
     pub jwks_endpoint: Option<String>,
+    /// The upstream `end_session_endpoint` for RP-initiated logouts. Without it, a Rauthy logout
+    /// will never be propagated to this provider.
+    ///
+    /// Validation: `[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]"))]
+    pub end_session_endpoint: Option<String>,
 
     pub use_pkce: bool,
+    /// Only one of `client_secret_basic` and `client_secret_post` can be set. Without both, the
+    /// client is public and must `use_pkce`.
     pub client_secret_basic: bool,
     pub client_secret_post: bool,
+    /// If `false`, upstream logins are only possible for users which already exist locally,
+    /// either linked via the federation uid or found by their E-Mail. Unknown users will never
+    /// be provisioned and are rejected with a `403`.
     pub auto_onboarding: bool,
     pub auto_link: bool,
+    /// If `true`, a Rauthy logout always redirects to the upstream `end_session_endpoint`.
+    /// Otherwise, the user is only offered the upstream logout on the logout page.
+    #[serde(default)]
+    pub auto_upstream_logout: bool,
 
     // This validation is pretty loose, but if we make it too strict,
     // we will most probably get into compatibility issues.
@@ -55,6 +75,35 @@ pub struct ProviderRequest {
     /// Validation: max length is 256
     #[validate(length(max = 256))]
     pub client_secret: Option<String>,
+    /// PEM client certificate (chain) for mTLS at the upstream `token_endpoint`. Must be given
+    /// together with the `client_key_pem`. During an update, the current key is kept, if only the
+    /// certificate is given.
+    ///
+    /// Validation: max length is 16384
+    #[validate(length(max = 16384))]
+    pub client_cert_pem: Option<String>,
+    /// PEM private key for the `client_cert_pem`. It is never returned in any response.
+    ///
+    /// Validation: max length is 16384
+    #[validate(length(max = 16384))]
+    pub client_key_pem: Option<String>,
+    /// The Apple Developer Team ID, which is the `iss` of the generated client secret. Mandatory
+    /// for `apple`.
+    ///
+    /// Validation: `[a-zA-Z0-9]{1,32}`
+    #[validate(regex(path = "*RE_ALNUM", code = "[a-zA-Z0-9]"), length(max = 32))]
+    pub apple_team_id: Option<String>,
+    /// The Key ID of the `apple_private_key`. Mandatory for `apple`.
+    ///
+    /// Validation: `[a-zA-Z0-9]{1,32}`
+    #[validate(regex(path = "*RE_ALNUM", code = "[a-zA-Z0-9]"), length(max = 32))]
+    pub apple_key_id: Option<String>,
+    /// The PEM (`.p8`) private key for the ES256 client secret JWT. It is never returned in any
+    /// response, and the current one is kept during an update, if it is not given.
+    ///
+    /// Validation: max length is 4096
+    #[validate(length(max = 4096))]
+    pub apple_private_key: Option<String>,
     /// Validation: `[a-zA-Z0-9-_/:\s*]{0,512}`
     #[validate(regex(path = "*RE_SCOPE_SPACE", code = "[a-zA-Z0-9-_/:\\s*]{0,512}"))]
     pub scope: String,
@@ -71,6 +120,92 @@ pub struct ProviderRequest {
     /// Validation: `[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]`
     #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]"))]
     pub mfa_claim_value: Option<String>,
+
+    /// JsonPath to the E-Mail for providers, which do not use the `email` claim, like e.g.
+    /// `$.upn`. Falls back to `email`, if unset or not found.
+    ///
+    /// Validation: max length is 256, must be a valid JsonPath
+    #[validate(length(max = 256))]
+    pub claim_path_email: Option<String>,
+    /// JsonPath to the given name. Falls back to `given_name`, if unset or not found.
+    ///
+    /// Validation: max length is 256, must be a valid JsonPath
+    #[validate(length(max = 256))]
+    pub claim_path_given_name: Option<String>,
+    /// JsonPath to the family name. Falls back to `family_name`, if unset or not found.
+    ///
+    /// Validation: max length is 256, must be a valid JsonPath
+    #[validate(length(max = 256))]
+    pub claim_path_family_name: Option<String>,
+    /// JsonPath to the upstream groups, like e.g. `$.resource_access.myclient.roles`. The values
+    /// will be used as the `groups` claim for the group mappings.
+    ///
+    /// Validation: max length is 256, must be a valid JsonPath
+    #[validate(length(max = 256))]
+    pub claim_path_groups: Option<String>,
+    /// Comma-separated list of Google Workspace domains, like e.g. `example.com`. If set, only
+    /// upstream logins with an `hd` claim matching one of them are accepted.
+    ///
+    /// Validation: max length is 1024, valid domain names
+    #[validate(length(max = 1024))]
+    pub restrict_hd: Option<String>,
+    /// The expected `iss` of multi-tenant providers, like e.g.
+    /// `https://login.microsoftonline.com/{tid}/v2.0` for Azure. The `{tid}` is replaced with the
+    /// `tid` claim of the ID token. If unset, the `iss` must match the `issuer`.
+    ///
+    /// Validation: max length is 256, must contain `{tid}` exactly once
+    #[validate(length(max = 256))]
+    pub issuer_template: Option<String>,
+    /// Comma-separated list of tenant IDs. If set, only ID tokens with a `tid` claim matching one
+    /// of them are accepted.
+    ///
+    /// Validation: max length is 1024, `[a-zA-Z0-9-]` tenant IDs
+    #[validate(length(max = 1024))]
+    pub allowed_tenants: Option<String>,
+    /// How the values of already existing users are updated from the upstream claims during
+    /// login. Defaults to `always`.
+    pub sync_policy: Option<ProviderSyncPolicy>,
+    /// If `true`, the upstream refresh token is stored during login and periodically used to
+    /// sync the user with the latest claims. A revoked refresh token logs the user out. The
+    /// upstream usually only issues refresh tokens with the `offline_access` scope.
+    pub upstream_refresh: Option<bool>,
+    /// If `true`, logins are rejected unless the upstream sends `email_verified: true`.
+    /// Otherwise, new users with an unverified E-Mail receive a confirmation E-Mail.
+    pub require_email_verified: Option<bool>,
+    /// The position on the login page in ascending order. Providers with the same value are
+    /// sorted by their `name`. Defaults to `0`.
+    pub sort_order: Option<i32>,
+    /// Comma-separated list of E-Mail domains. If set, only users with an E-Mail from one of
+    /// these domains can log in via this provider.
+    ///
+    /// Validation: max length is 1024, valid domain names
+    #[validate(length(max = 1024))]
+    pub allowed_email_domains: Option<String>,
+    /// Comma-separated list of E-Mail domains, which can never log in via this provider.
+    ///
+    /// Validation: max length is 1024, valid domain names
+    #[validate(length(max = 1024))]
+    pub denied_email_domains: Option<String>,
+    /// Controls the `userinfo` lookup when the provider issues an ID token. If unset, the
+    /// `userinfo` is only fetched when the ID token lacks mandatory claims like the `email`.
+    /// `true` always merges the `userinfo` into the ID token claims, `false` never fetches it.
+    pub use_userinfo: Option<bool>,
+    /// If `true`, the endpoints are updated automatically, when the upstream
+    /// openid-configuration changes. Defaults to `true` for `oidc` and `google` providers.
+    pub auto_refresh: Option<bool>,
+
+    /// Additional query parameters, which will be appended to the `authorization_endpoint`
+    /// during the upstream login, like e.g. `prompt` or `hd`. They must not override any of the
+    /// parameters Rauthy sets itself.
+    ///
+    /// Validation: max 16 entries, keys `[a-zA-Z0-9-_.]{1,64}`, values max length 256
+    pub extra_auth_params: Option<HashMap<String, String>>,
+    /// Overrides the global `UPSTREAM_AUTH_CALLBACK_TIMEOUT_SECS` for slow upstream logins,
+    /// like e.g. with smart-cards.
+    ///
+    /// Validation: `60 <= callback_timeout_secs <= 3600`
+    #[validate(range(min = 60, max = 3600))]
+    pub callback_timeout_secs: Option<u16>,
 }
 
 #[derive(Deserialize, Validate, ToSchema)]
@@ -94,6 +229,26 @@ pub struct ProviderCallbackRequest {
     pub iss_atproto: Option<String>,
 }
 
+/// The callback of providers with `response_mode=form_post`, like e.g. Apple.
+#[derive(Deserialize, Validate)]
+#[cfg_attr(debug_assertions, derive(Serialize))]
+pub struct ProviderCallbackFormRequest {
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%@]+$`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%@]+$"))]
+    pub state: String,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%@]+$`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%@]+$"))]
+    pub code: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%@]+$`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%@]+$"))]
+    pub error: Option<String>,
+    /// Apple only sends the JSON `user` with the name during the very first consent.
+    ///
+    /// Validation: max length is 2048
+    #[validate(length(max = 2048))]
+    pub user: Option<String>,
+}
+
 #[derive(Deserialize, Validate, ToSchema)]
 #[cfg_attr(debug_assertions, derive(Serialize))]
 pub struct ProviderLoginRequest {
@@ -149,6 +304,136 @@ pub struct ProviderLoginRequest {
     pub handle: Option<String>,
 }
 
+/// How an upstream group claim is applied to the Rauthy groups of a user during login.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderGroupSyncMode {
+    /// Only adds the Rauthy group, if the upstream group is present
+    AddOnly,
+    /// Additionally removes the Rauthy group, if the upstream group is absent
+    Sync,
+}
+
+impl ProviderGroupSyncMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::AddOnly => "add_only",
+            Self::Sync => "sync",
+        }
+    }
+}
+
+impl From<&str> for ProviderGroupSyncMode {
+    fn from(value: &str) -> Self {
+        match value {
+            "sync" => Self::Sync,
+            _ => Self::AddOnly,
+        }
+    }
+}
+
+/// How the `email`, names and user values of an already existing federated user are updated from
+/// the upstream claims during login. New users are always created with all upstream values.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderSyncPolicy {
+    /// Overwrites the local values with each login
+    Always,
+    /// Only fills values, which are currently empty locally
+    OnEmpty,
+    /// Never touches the local values after the user has been created
+    Never,
+}
+
+impl ProviderSyncPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Always => "always",
+            Self::OnEmpty => "on_empty",
+            Self::Never => "never",
+        }
+    }
+
+    /// `true` if a value, which is currently empty on the local side or not, should be updated.
+    pub fn should_sync(&self, is_empty: bool) -> bool {
+        match self {
+            Self::Always => true,
+            Self::OnEmpty => is_empty,
+            Self::Never => false,
+        }
+    }
+}
+
+impl From<&str> for ProviderSyncPolicy {
+    fn from(value: &str) -> Self {
+        match value {
+            "on_empty" => Self::OnEmpty,
+            "never" => Self::Never,
+            _ => Self::Always,
+        }
+    }
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Serialize))]
+pub struct ProviderGroupMappingRequest {
+    /// The upstream claim containing the groups as a JSON array of strings. Defaults to `groups`.
+    ///
+    /// Validation: `^[a-zA-Z0-9-_.:/]{2,128}$`
+    #[validate(regex(path = "*RE_CLAIM_NAME", code = "^[a-zA-Z0-9-_.:/]{2,128}$"))]
+    pub claim_key: Option<String>,
+    /// Validation: length 1 - 256
+    #[validate(length(min = 1, max = 256))]
+    pub upstream_group: String,
+    /// Validation: `[a-zA-Z0-9]`
+    #[validate(regex(path = "*RE_ALNUM", code = "[a-zA-Z0-9]"))]
+    pub group_id: String,
+    pub sync_mode: ProviderGroupSyncMode,
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Serialize))]
+pub struct ProviderRoleMappingRequest {
+    /// JsonPath into the upstream claims, like e.g. `$.roles[*]`.
+    ///
+    /// Validation: length 1 - 256, must be a valid JsonPath
+    #[validate(length(min = 1, max = 256))]
+    pub claim_path: String,
+    /// The value, which must be found at the `claim_path`.
+    ///
+    /// Validation: length 1 - 256
+    #[validate(length(min = 1, max = 256))]
+    pub claim_value: String,
+    /// Validation: `[a-zA-Z0-9]`
+    #[validate(regex(path = "*RE_ALNUM", code = "[a-zA-Z0-9]"))]
+    pub role_id: String,
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Serialize))]
+pub struct ProviderTestCallbackRequest {
+    /// An ID token as it would be returned by the upstream provider. The signature is NOT
+    /// validated.
+    ///
+    /// Validation: length 1 - 16384
+    #[validate(length(min = 1, max = 16384))]
+    pub mock_id_token: String,
+    /// Simulate the login of a new user, even if the user already exists.
+    #[serde(default)]
+    pub simulate_new_user: bool,
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Serialize))]
+pub struct ProviderSecretRequest {
+    /// Validation: length 1 - 256
+    #[validate(length(min = 1, max = 256))]
+    pub client_secret: String,
+    /// Persist the new secret even if the upstream rejected it.
+    #[serde(default)]
+    pub force: bool,
+}
+
 #[derive(Deserialize, Validate, ToSchema)]
 pub struct ProviderLookupRequest {
     /// Validation: `[a-zA-Z0-9,.:/_\-&?=~#!$'()*+%]`
@@ -159,6 +444,62 @@ pub struct ProviderLookupRequest {
     pub metadata_url: Option<String>,
 }
 
+/// All upstream auth providers for `GET /providers/export` and `POST /providers/import`.
+/// ATProto is never included.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ProviderExport {
+    pub version: u16,
+    pub exported: i64,
+    /// Base64 salt for the key derivation from the passphrase. Only set, if the `secrets` are
+    /// included.
+    pub salt: Option<String>,
+    pub providers: Vec<ProviderExportEntry>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ProviderExportEntry {
+    /// The `client_secret`, `client_key_pem` and `apple_private_key` are always `null`.
+    #[serde(flatten)]
+    pub provider: ProviderRequest,
+    /// The encrypted secrets, only if a passphrase has been given during the export.
+    pub secrets: Option<ProviderExportSecrets>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ProviderExportSecrets {
+    /// Base64 nonce
+    pub nonce: String,
+    /// Base64 ciphertext
+    pub data: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderImportStatus {
+    Created,
+    /// An existing provider with the same `issuer` and `client_id` has been updated.
+    Updated,
+    Failed,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Deserialize))]
+pub struct ProviderImportResult {
+    pub name: String,
+    pub issuer: String,
+    pub client_id: String,
+    pub status: ProviderImportStatus,
+    /// The ID of the created or updated provider
+    pub id: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Deserialize))]
+pub struct ProviderImportResponse {
+    pub results: Vec<ProviderImportResult>,
+}
+
 #[derive(Serialize, ToSchema)]
 pub struct ProviderResponse {
     pub id: String,
@@ -171,9 +512,17 @@ pub struct ProviderResponse {
     pub token_endpoint: String,
     pub userinfo_endpoint: String,
     pub jwks_endpoint: Option<String>,
+    pub end_session_endpoint: Option<String>,
 
     pub client_id: String,
     pub client_secret: Option<String>,
+    pub client_cert_pem: Option<String>,
+    /// `true` if a private key for the `client_cert_pem` is set
+    pub client_key_set: bool,
+    pub apple_team_id: Option<String>,
+    pub apple_key_id: Option<String>,
+    /// `true` if an `apple_private_key` is set
+    pub apple_private_key_set: bool,
     pub scope: String,
 
     pub admin_claim_path: Option<String>,
@@ -181,11 +530,59 @@ pub struct ProviderResponse {
     pub mfa_claim_path: Option<String>,
     pub mfa_claim_value: Option<String>,
 
+    pub claim_path_email: Option<String>,
+    pub claim_path_given_name: Option<String>,
+    pub claim_path_family_name: Option<String>,
+    pub claim_path_groups: Option<String>,
+    pub restrict_hd: Option<String>,
+    pub issuer_template: Option<String>,
+    pub allowed_tenants: Option<String>,
+    pub sync_policy: ProviderSyncPolicy,
+    pub upstream_refresh: bool,
+    pub require_email_verified: bool,
+    pub sort_order: i32,
+    pub allowed_email_domains: Option<String>,
+    pub denied_email_domains: Option<String>,
+    pub use_userinfo: Option<bool>,
+    pub auto_refresh: bool,
+
     pub use_pkce: bool,
     pub client_secret_basic: bool,
     pub client_secret_post: bool,
     pub auto_onboarding: bool,
     pub auto_link: bool,
+    pub auto_upstream_logout: bool,
+
+    pub extra_auth_params: Option<HashMap<String, String>>,
+    pub callback_timeout_secs: Option<u16>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Deserialize))]
+pub struct ProviderGroupMappingResponse {
+    pub id: String,
+    pub provider_id: String,
+    pub claim_key: String,
+    pub upstream_group: String,
+    pub group_id: String,
+    pub sync_mode: ProviderGroupSyncMode,
+}
+
+#[derive(Serialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Deserialize))]
+pub struct ProviderRoleMappingResponse {
+    pub id: String,
+    pub provider_id: String,
+    pub claim_path: String,
+    pub claim_value: String,
+    pub role_id: String,
+}
+
+#[derive(Deserialize, Validate, ToSchema, IntoParams)]
+pub struct ProviderDeleteParams {
+    /// Must be `true`, if any users are still linked to this provider. These users will be
+    /// unlinked and converted back to local accounts in the same transaction.
+    pub force: Option<bool>,
 }
 
 #[derive(Serialize, Deserialize, FromPgRow, ToSchema)]
@@ -194,6 +591,106 @@ pub struct ProviderLinkedUserResponse {
     pub email: String,
 }
 
+#[derive(Default, Serialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Deserialize))]
+pub struct ProviderTestCallbackResponse {
+    pub would_create_user: bool,
+    pub would_update_user: bool,
+    /// The user values mapped from the upstream claims
+    pub mapped_fields: HashMap<String, String>,
+    /// The groups the user would end up with
+    pub group_assignments: Vec<String>,
+    /// The roles the user would end up with
+    pub role_assignments: Vec<String>,
+    /// Each error which would reject the login
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderSecretProbeMethod {
+    /// A `client_credentials` grant against the upstream `token_endpoint`
+    ClientCredentials,
+    /// An `authorization_code` grant with an invalid code. The upstream must authenticate the
+    /// client before it can reject the code, which makes the status tell apart bad credentials.
+    AuthorizationCode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderSecretProbeResult {
+    /// The upstream accepted the credentials.
+    Valid,
+    /// The upstream rejected the credentials.
+    Invalid,
+    /// The upstream could not be reached or the response does not tell anything about the
+    /// credentials.
+    Inconclusive,
+}
+
+#[derive(Serialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Deserialize))]
+pub struct ProviderSecretResponse {
+    pub result: ProviderSecretProbeResult,
+    /// The last probe, which has been sent to the upstream
+    pub method: ProviderSecretProbeMethod,
+    /// The HTTP status of the upstream response, if any
+    pub status: Option<u16>,
+    /// The `error` from the upstream response or the connection error
+    pub error: Option<String>,
+    /// `true` if the new secret has been saved
+    pub persisted: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderTestStep {
+    /// The lookup of the openid-configuration at the `issuer`
+    Metadata,
+    /// A `client_credentials` grant against the `token_endpoint`
+    TokenEndpoint,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderTestResult {
+    Success,
+    /// The step does not apply to this provider, e.g. without a client secret.
+    Skipped,
+    /// The TLS handshake failed, e.g. because of an untrusted root certificate.
+    TlsError,
+    /// The upstream could not be reached at all.
+    ConnectionError,
+    Timeout,
+    /// The upstream rejected the client credentials.
+    InvalidCredentials,
+    /// The upstream accepted the credentials, but does not allow the `client_credentials` grant
+    /// for this client.
+    GrantRejected,
+    /// An unexpected HTTP status or a response, which does not match the provider config.
+    InvalidResponse,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Deserialize))]
+pub struct ProviderTestStepResponse {
+    pub step: ProviderTestStep,
+    pub result: ProviderTestResult,
+    /// The HTTP status of the upstream response, if any
+    pub status: Option<u16>,
+    /// The `error` from the upstream response or the connection error
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Deserialize))]
+pub struct ProviderTestResponse {
+    /// `true` if no step has failed
+    pub success: bool,
+    pub steps: Vec<ProviderTestStepResponse>,
+}
+
 #[derive(Serialize, ToSchema)]
 pub struct ProviderLookupResponse {
     pub issuer: String,
@@ -201,6 +698,7 @@ pub struct ProviderLookupResponse {
     pub token_endpoint: String,
     pub userinfo_endpoint: String,
     pub jwks_endpoint: Option<String>,
+    pub end_session_endpoint: Option<String>,
     pub scope: String,
     pub use_pkce: bool,
     pub client_secret_basic: bool,
@@ -1,7 +1,7 @@
 use crate::cust_validation::*;
 use crate::oidc::JwkKeyPairAlg;
 use rauthy_common::regex::{
-    RE_CLIENT_ID, RE_CLIENT_ID_STRICT, RE_CLIENT_NAME, RE_GROUPS, RE_SCOPE_SPACE,
+    RE_CLAIM_NAME, RE_CLIENT_ID, RE_CLIENT_ID_STRICT, RE_CLIENT_NAME, RE_GROUPS, RE_SCOPE_SPACE,
     RE_TOKEN_ENDPOINT_AUTH_METHOD, RE_URI,
 };
 use serde::{Deserialize, Serialize};
@@ -142,6 +142,10 @@ pub struct NewClientRequest {
     /// Validation: `Vec<^[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]+$>`
     #[validate(custom(function = "validate_vec_uri"))]
     pub post_logout_redirect_uris: Option<Vec<String>>,
+    /// `native` clients get the allowed flows and PKCE settings recommended for RFC 8252.
+    /// Missing defaults to `web`.
+    #[serde(default)]
+    pub client_type: Option<ClientType>,
 }
 
 #[derive(Deserialize, Validate, ToSchema)]
@@ -216,8 +220,56 @@ pub struct UpdateClientRequest {
     /// Validation: `Vec<^[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%@]+$>`
     #[validate(custom(function = "validate_vec_uri"))]
     pub default_aud: Option<Vec<String>>,
+    /// If set, only users with at least one of these roles, or one of the `allowed_groups`,
+    /// may use this client. Missing / empty for both means unrestricted.
+    ///
+    /// Validation: `Vec<^[a-zA-Z0-9-_/,:*.]{2,64}$>`
+    #[validate(custom(function = "validate_vec_roles"))]
+    pub allowed_roles: Option<Vec<String>>,
+    /// If set, only users in at least one of these groups, or with one of the `allowed_roles`,
+    /// may use this client. Missing / empty for both means unrestricted.
+    ///
+    /// Validation: `Vec<^[a-zA-Z0-9-_/,:*\\s]{2,64}$>`
+    #[validate(custom(function = "validate_vec_groups"))]
+    pub allowed_groups: Option<Vec<String>>,
+    /// The `response_type`s this client may use at the authorize endpoint.
+    /// Missing keeps the current value.
+    ///
+    /// Validation: `Vec<^(code|none)$>`, must not be empty
+    #[serde(default)]
+    #[validate(custom(function = "validate_vec_response_types"))]
+    pub response_types: Option<Vec<String>>,
+    /// Controls claim names, format and targets for `roles` and `groups`.
+    /// Missing keeps the default behavior.
+    #[serde(default)]
+    #[validate(nested)]
+    pub claims_emission: Option<ClientClaimsEmission>,
+    /// Binds refresh tokens of public clients to the requesting user agent and IP.
+    /// Missing or all levels `off` keeps refresh tokens unbound.
+    #[serde(default)]
+    pub refresh_token_binding: Option<ClientRefreshTokenBinding>,
+    /// `native` clients must be public and use PKCE with `S256` only.
+    /// Missing defaults to `web`.
+    #[serde(default)]
+    pub client_type: Option<ClientType>,
     #[validate(nested)]
     pub scim: Option<ScimClientRequestResponse>,
+    /// Webhook, which is asked for additional claims before each token issuance for a user.
+    #[serde(default)]
+    #[validate(nested)]
+    pub claims_webhook: Option<ClaimsWebhookRequest>,
+}
+
+/// Exactly one of `jwks` or `jwks_uri` must be given.
+#[derive(Default, Validate, Deserialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Serialize))]
+pub struct ClientJwksRequest {
+    /// An inline JWKS in the format `{ "keys": [...] }`
+    #[schema(value_type = Option<Object>)]
+    pub jwks: Option<serde_json::Value>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%@]+$`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%@]+$"))]
+    pub jwks_uri: Option<String>,
 }
 
 #[derive(Default, Validate, Deserialize, ToSchema)]
@@ -242,6 +294,204 @@ pub struct ScimClientRequestResponse {
     pub group_sync_prefix: Option<String>,
 }
 
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct ClaimsWebhookRequest {
+    /// Must be `https://` outside of `dev_mode`.
+    ///
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%@]+$`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%@]+$"))]
+    pub uri: String,
+    /// The shared secret for the HMAC signatures. It is write-only and will never be returned.
+    /// Can be left out on updates to keep the current one.
+    ///
+    /// Validation: `32 <= secret.len() <= 128`
+    #[validate(length(min = 32, max = 128))]
+    pub secret: Option<String>,
+    /// The total time budget for the webhook request in milliseconds, including the response
+    /// body.
+    ///
+    /// Validation: `100 <= timeout_ms <= 5000`
+    #[validate(range(min = 100, max = 5000))]
+    pub timeout_ms: u16,
+    /// How long a webhook result will be cached for the same user, client and scopes in
+    /// seconds. `0` disables the cache.
+    ///
+    /// Validation: `0 <= cache_ttl <= 900`
+    #[validate(range(max = 900))]
+    pub cache_ttl: u16,
+    /// The claim names the webhook may set. `roles` and `groups` allow merging additional
+    /// values into the default claims, any other name will be set as a custom claim at the
+    /// token root.
+    ///
+    /// Validation: `Vec<^[a-zA-Z0-9-_.:/]{2,128}$>`
+    #[validate(custom(function = "validate_vec_claim_name"))]
+    pub allowed_claims: Vec<String>,
+    /// If `true`, a failing webhook will be ignored and tokens are issued with local data only.
+    /// Otherwise, the token request will be rejected.
+    pub fail_open: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Deserialize))]
+pub struct ClaimsWebhookResponse {
+    pub uri: String,
+    pub timeout_ms: u16,
+    pub cache_ttl: u16,
+    pub allowed_claims: Vec<String>,
+    pub fail_open: bool,
+}
+
+/// The token types and the userinfo endpoint a `roles` / `groups` claim can be emitted into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ClaimTarget {
+    IdToken,
+    AccessToken,
+    Userinfo,
+}
+
+impl ClaimTarget {
+    pub fn all() -> Vec<Self> {
+        vec![Self::IdToken, Self::AccessToken, Self::Userinfo]
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ClaimGroupsFormat {
+    /// The plain group names, e.g. `engineering`
+    #[default]
+    Flat,
+    /// Hierarchical paths with a leading `/`, e.g. `/engineering/platform`
+    Path,
+}
+
+/// Per-client emission of the `roles` and `groups` claims. The default value reproduces
+/// the built-in behavior: both claims with their default names in all targets, `groups`
+/// only when the `groups` scope has been granted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Validate, ToSchema)]
+pub struct ClientClaimsEmission {
+    /// Custom claim name for the roles, `roles` if not set.
+    ///
+    /// Validation: `^[a-zA-Z0-9-_.:/]{2,128}$`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(regex(path = "*RE_CLAIM_NAME", code = "^[a-zA-Z0-9-_.:/]{2,128}$"))]
+    pub roles_claim: Option<String>,
+    /// Custom claim name for the groups, `groups` if not set.
+    ///
+    /// Validation: `^[a-zA-Z0-9-_.:/]{2,128}$`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(regex(path = "*RE_CLAIM_NAME", code = "^[a-zA-Z0-9-_.:/]{2,128}$"))]
+    pub groups_claim: Option<String>,
+    #[serde(default)]
+    pub groups_format: ClaimGroupsFormat,
+    /// If `true`, the user's roles are merged into the groups claim.
+    #[serde(default)]
+    pub roles_in_groups: bool,
+    #[serde(default = "ClaimTarget::all")]
+    pub roles_targets: Vec<ClaimTarget>,
+    #[serde(default = "ClaimTarget::all")]
+    pub groups_targets: Vec<ClaimTarget>,
+    /// Emits a `TokenGroupsOverflow` event, when a user has more groups than this value.
+    ///
+    /// Validation: `1 <= groups_max <= 65535`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(range(min = 1))]
+    pub groups_max: Option<u16>,
+    /// If `true` and `groups_max` is exceeded, the groups claim is replaced with
+    /// `groups_overflow: true`.
+    #[serde(default)]
+    pub groups_overflow: bool,
+}
+
+impl Default for ClientClaimsEmission {
+    fn default() -> Self {
+        Self {
+            roles_claim: None,
+            groups_claim: None,
+            groups_format: ClaimGroupsFormat::Flat,
+            roles_in_groups: false,
+            roles_targets: ClaimTarget::all(),
+            groups_targets: ClaimTarget::all(),
+            groups_max: None,
+            groups_overflow: false,
+        }
+    }
+}
+
+/// The kind of application behind a client.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ClientType {
+    /// An application running in or behind a browser.
+    #[default]
+    Web,
+    /// A mobile, desktop or CLI application following RFC 8252. Private-use URI scheme redirects
+    /// and loopback IP redirects with any port are allowed, while wildcards are not.
+    Native,
+}
+
+impl ClientType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Web => "web",
+            Self::Native => "native",
+        }
+    }
+}
+
+impl From<&str> for ClientType {
+    fn from(value: &str) -> Self {
+        match value {
+            "native" => Self::Native,
+            _ => Self::Web,
+        }
+    }
+}
+
+/// How strictly the `User-Agent` of a refresh request must match the one from issuance.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UserAgentBinding {
+    #[default]
+    Off,
+    /// Only the browser family must match, e.g. `Firefox`. Survives browser updates.
+    Family,
+    /// The browser family and its major version must match. A browser update requires a new
+    /// login.
+    FamilyMajor,
+}
+
+/// How strictly the IP of a refresh request must match the one from issuance.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum IpBinding {
+    #[default]
+    Off,
+    /// The IPv4 `/16` or IPv6 `/48` network must match.
+    Wide,
+    /// The IPv4 `/24` or IPv6 `/64` network must match.
+    Narrow,
+}
+
+/// Binds refresh tokens of public clients to a fingerprint of the requesting context.
+/// A refresh request with a non-matching fingerprint is rejected with `invalid_grant` and
+/// revokes all refresh tokens of the same session.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ClientRefreshTokenBinding {
+    #[serde(default)]
+    pub user_agent: UserAgentBinding,
+    #[serde(default)]
+    pub ip: IpBinding,
+}
+
+impl ClientRefreshTokenBinding {
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.user_agent != UserAgentBinding::Off || self.ip != IpBinding::Off
+    }
+}
+
 #[derive(Serialize, ToSchema)]
 #[cfg_attr(debug_assertions, derive(Debug, Deserialize))]
 pub struct ClientResponse {
@@ -281,7 +531,40 @@ pub struct ClientResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub default_aud: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_roles: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_groups: Option<Vec<String>>,
+    pub response_types: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub claims_emission: Option<ClientClaimsEmission>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token_binding: Option<ClientRefreshTokenBinding>,
+    pub client_type: ClientType,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub scim: Option<ScimClientRequestResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub claims_webhook: Option<ClaimsWebhookResponse>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Deserialize))]
+pub struct ClientJwkResponse {
+    pub kid: String,
+    pub kty: String,
+    pub alg: String,
+}
+
+#[derive(Serialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Deserialize))]
+pub struct ClientJwksResponse {
+    pub client_id: String,
+    #[schema(value_type = Option<Object>)]
+    pub jwks: Option<serde_json::Value>,
+    pub jwks_uri: Option<String>,
+    /// Unix timestamp in seconds
+    pub updated: i64,
+    /// The currently usable signing keys
+    pub keys: Vec<ClientJwkResponse>,
 }
 
 #[derive(Serialize, ToSchema)]
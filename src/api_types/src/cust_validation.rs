@@ -1,11 +1,25 @@
-use rauthy_common::constants::CLIENT_CLAIMS_MAX_LEN;
+use rauthy_common::constants::{CLIENT_CLAIMS_MAX_LEN, RESPONSE_TYPES_SUPPORTED};
 use rauthy_common::regex::{
-    RE_ATTR, RE_CODE_CHALLENGE_METHOD, RE_CONTACT, RE_GRANT_TYPES, RE_GROUPS, RE_LINUX_HOSTNAME,
-    RE_ORIGIN, RE_ROLES_SCOPES, RE_URI,
+    RE_ALNUM, RE_ATTR, RE_CLAIM_NAME, RE_CODE_CHALLENGE_METHOD, RE_CONTACT, RE_GRANT_TYPES,
+    RE_GROUPS, RE_LINUX_HOSTNAME, RE_ORIGIN, RE_ROLES_SCOPES, RE_URI,
 };
 use std::borrow::Cow;
 use validator::ValidationError;
 
+#[inline]
+pub fn validate_vec_alnum(value: &[String]) -> Result<(), ValidationError> {
+    let mut err = None;
+    value.iter().for_each(|v| {
+        if !RE_ALNUM.is_match(v) {
+            err = Some("^[a-zA-Z0-9]+$");
+        }
+    });
+    if let Some(e) = err {
+        return Err(ValidationError::new(e));
+    }
+    Ok(())
+}
+
 #[inline]
 pub fn validate_vec_attr(value: &[String]) -> Result<(), ValidationError> {
     let mut err = None;
@@ -70,6 +84,20 @@ pub fn validate_vec_challenge(value: &[String]) -> Result<(), ValidationError> {
     Ok(())
 }
 
+#[inline]
+pub fn validate_vec_claim_name(value: &[String]) -> Result<(), ValidationError> {
+    let mut err = None;
+    value.iter().for_each(|v| {
+        if !RE_CLAIM_NAME.is_match(v) {
+            err = Some("^[a-zA-Z0-9-_.:/]{2,128}$");
+        }
+    });
+    if let Some(e) = err {
+        return Err(ValidationError::new(e));
+    }
+    Ok(())
+}
+
 #[inline]
 pub fn validate_vec_contact(value: &[String]) -> Result<(), ValidationError> {
     let mut err = None;
@@ -148,6 +176,16 @@ pub fn validate_vec_uri(value: &[String]) -> Result<(), ValidationError> {
     Ok(())
 }
 
+/// Like the `RE_URI` regex, but allows an empty value for optional URIs.
+#[inline]
+pub fn validate_uri_or_empty(value: &str) -> Result<(), ValidationError> {
+    if value.is_empty() || RE_URI.is_match(value) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("^[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]*$"))
+    }
+}
+
 #[inline]
 pub fn validate_vec_grant_type(value: &[String]) -> Result<(), ValidationError> {
     let mut err = None;
@@ -179,6 +217,22 @@ pub fn validate_vec_groups(value: &[String]) -> Result<(), ValidationError> {
     Ok(())
 }
 
+#[inline]
+pub fn validate_vec_response_types(value: &[String]) -> Result<(), ValidationError> {
+    if value.is_empty() {
+        return Err(ValidationError::new(
+            "'response_types' must contain at least one value",
+        ));
+    }
+    if value
+        .iter()
+        .any(|v| !RESPONSE_TYPES_SUPPORTED.contains(&v.as_str()))
+    {
+        return Err(ValidationError::new("^(code|none)$"));
+    }
+    Ok(())
+}
+
 #[inline]
 pub fn validate_vec_roles(value: &[String]) -> Result<(), ValidationError> {
     let mut err = None;
@@ -237,4 +291,20 @@ mod tests {
         assert!(serde_json::to_vec(&too_big).unwrap().len() > 1024);
         assert!(validate_claims(&too_big).is_err());
     }
+
+    #[test]
+    fn client_response_types() {
+        let types = |t: &[&str]| t.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+
+        assert!(validate_vec_response_types(&types(&["code"])).is_ok());
+        assert!(validate_vec_response_types(&types(&["none"])).is_ok());
+        assert!(validate_vec_response_types(&types(&["code", "none"])).is_ok());
+
+        // the last remaining one can never be removed
+        assert!(validate_vec_response_types(&[]).is_err());
+        // not supported by the authorize endpoint
+        assert!(validate_vec_response_types(&types(&["code", "token"])).is_err());
+        assert!(validate_vec_response_types(&types(&["id_token"])).is_err());
+        assert!(validate_vec_response_types(&types(&["code id_token"])).is_err());
+    }
 }
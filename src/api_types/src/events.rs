@@ -37,6 +37,30 @@ pub enum EventType {
     TokenIssued,
     CredentialStuffing,
     EmailSendError,
+    TokenGroupsOverflow,
+    MaintenanceSummary,
+    UserErased,
+    PasskeyImported,
+    UserRegistrationPending,
+    ProviderTestCallback,
+    MigrationExport,
+    MigrationImport,
+    RefreshTokenBindingMismatch,
+    UsersDisabledBulk,
+    UserDisabled,
+    MaintenanceModeStarted,
+    MaintenanceModeEnded,
+    ExpiringDigest,
+    AuthProviderUnavailable,
+    AuthProviderAvailable,
+    ProviderSecretRotated,
+    PasskeyCounterAnomaly,
+    BreakGlassLogin,
+    UpstreamAdminRoleChange,
+    UpstreamDomainRejected,
+    UserProviderLink,
+    AuthProviderMetadataUpdated,
+    UpstreamRefreshFailed,
 }
 
 #[derive(Deserialize, Validate, ToSchema, IntoParams)]
@@ -1,3 +1,4 @@
+use crate::cust_validation::validate_vec_alnum;
 use crate::sessions::SessionState;
 use rauthy_common::regex::{RE_ALNUM, RE_SEARCH};
 use serde::{Deserialize, Serialize};
@@ -11,6 +12,16 @@ pub struct EncKeyMigrateRequest {
     pub key_id: String,
 }
 
+#[derive(Deserialize, Validate, ToSchema, IntoParams)]
+pub struct SecretsMigrateParams {
+    /// Validation: `[a-zA-Z0-9]`
+    #[validate(regex(path = "*RE_ALNUM", code = "[a-zA-Z0-9]"))]
+    pub from_key_id: String,
+    /// Validation: `[a-zA-Z0-9]`
+    #[validate(regex(path = "*RE_ALNUM", code = "[a-zA-Z0-9]"))]
+    pub to_key_id: String,
+}
+
 #[derive(PartialEq, Eq, Deserialize)]
 #[cfg_attr(debug_assertions, derive(Serialize))]
 pub struct I18nRequest {
@@ -44,6 +55,9 @@ pub enum Language {
     Ru,
     Uk,
     ZhHans,
+    /// A custom language loaded from `i18n.custom_languages_dir` as its lowercase BCP 47 tag
+    #[serde(untagged)]
+    Custom(String),
 }
 
 #[derive(Deserialize, Validate, ToSchema, IntoParams)]
@@ -75,6 +89,32 @@ pub struct PasswordHashTimesRequest {
     pub p_cost: Option<u32>,
 }
 
+#[derive(Deserialize, Validate, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Serialize))]
+pub struct MaintenanceModeRequest {
+    pub enabled: bool,
+    /// Unix timestamp in seconds. If set, the maintenance only starts at this point in time.
+    pub start: Option<i64>,
+    /// Unix timestamp in seconds. If set, the maintenance ends automatically.
+    pub end: Option<i64>,
+    /// These users can still log in during the maintenance.
+    ///
+    /// Validation: `Vec<^[a-zA-Z0-9]+$>`, max 100 entries
+    #[validate(length(max = 100), custom(function = "validate_vec_alnum"))]
+    pub bypass_user_ids: Vec<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Deserialize))]
+pub struct MaintenanceModeResponse {
+    pub enabled: bool,
+    pub start: Option<i64>,
+    pub end: Option<i64>,
+    pub bypass_user_ids: Vec<String>,
+    /// `true` if the maintenance is in effect right now
+    pub active: bool,
+}
+
 #[derive(Deserialize, Validate, ToSchema)]
 #[cfg_attr(debug_assertions, derive(Serialize))]
 pub struct PasswordPolicyRequest {
@@ -158,6 +198,28 @@ pub struct EncKeysResponse<'a> {
     pub keys: Vec<&'a str>,
 }
 
+/// Sent after each migrated batch. The counts contain all values that have been re-encrypted
+/// so far.
+#[derive(Debug, Default, Clone, Serialize, ToSchema)]
+pub struct SecretsMigrationProgress {
+    pub api_keys: u32,
+    pub auth_providers: u32,
+    pub clients: u32,
+    pub claims_webhooks: u32,
+    pub jwks: u32,
+    pub users: u32,
+    pub upstream_claims: u32,
+    pub upstream_refresh_tokens: u32,
+    pub finished: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PiiMigrationResponse {
+    /// The count of users that have been converted with this run
+    pub users: u32,
+}
+
 #[derive(Default, Serialize, ToSchema)]
 pub struct HealthResponse {
     pub db_healthy: bool,
@@ -177,6 +239,28 @@ pub struct LoginTimeResponse {
     pub num_cpus: usize,
 }
 
+/// The result of a test connection to the configured SMTP relay. No message is ever sent.
+#[derive(Debug, Default, Serialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Deserialize))]
+pub struct SmtpTestResponse {
+    pub tcp_connected: bool,
+    pub tls_negotiated: bool,
+    pub authenticated: bool,
+    pub latency_ms: u64,
+    /// The name the server announced during the `EHLO` handshake
+    pub server_greeting: String,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Deserialize))]
+pub struct SystemInfoResponse {
+    pub version: String,
+    /// Additional static fields injected into the OIDC discovery document
+    #[schema(value_type = Object)]
+    pub wellknown_extra_claims: serde_json::Map<String, serde_json::Value>,
+}
+
 #[derive(Debug, Serialize, ToSchema)]
 #[cfg_attr(debug_assertions, derive(Deserialize))]
 pub struct PasswordPolicyResponse {
@@ -0,0 +1,58 @@
+use crate::generic::Language;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+/// The end user facing pages, which support translation overrides.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum I18nPage {
+    Account,
+    Device,
+    Login,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct I18nOverride {
+    pub page: I18nPage,
+    /// Must be one of the overridable keys for the `page`.
+    #[validate(length(min = 1, max = 64))]
+    pub key: String,
+    pub lang: Language,
+    /// Plain text, may only contain the placeholders the built-in translation uses.
+    #[validate(length(min = 1, max = 2048))]
+    pub value: String,
+}
+
+/// Replaces all existing overrides.
+#[derive(Deserialize, Validate, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Serialize))]
+pub struct I18nOverridesRequest {
+    #[validate(nested, length(max = 1024))]
+    pub overrides: Vec<I18nOverride>,
+}
+
+/// Renders the `page` in `lang` with the pending `overrides` merged over the saved ones.
+#[derive(Deserialize, Validate, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Serialize))]
+pub struct I18nPreviewRequest {
+    pub page: I18nPage,
+    pub lang: Language,
+    #[validate(nested, length(max = 1024))]
+    pub overrides: Vec<I18nOverride>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Deserialize))]
+pub struct I18nOverridableKey {
+    pub key: String,
+    /// Placeholders like `{{count}}`, which must exist in each override for this key.
+    pub placeholders: Vec<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Deserialize))]
+pub struct I18nPageKeysResponse {
+    pub page: I18nPage,
+    pub keys: Vec<I18nOverridableKey>,
+}
@@ -7,6 +7,7 @@ pub mod api_keys;
 pub mod auth_providers;
 pub mod backup;
 pub mod blacklist;
+pub mod claims;
 pub mod clients;
 pub mod cust_validation;
 pub mod email_jobs;
@@ -15,9 +16,12 @@ pub mod fed_cm;
 pub mod forward_auth;
 pub mod generic;
 pub mod groups;
+pub mod i18n;
 pub mod kv;
+pub mod migration;
 pub mod oidc;
 pub mod pam;
+pub mod reports;
 pub mod roles;
 pub mod scopes;
 pub mod sessions;
@@ -0,0 +1,74 @@
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// How entries from a migration archive are handled, which already exist locally.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum MigrationConflictStrategy {
+    /// Keeps the local entry and ignores the one from the archive
+    #[default]
+    Skip,
+    /// Deletes the local entry and inserts the one from the archive
+    Overwrite,
+    /// Aborts the import with the first conflict
+    Fail,
+}
+
+#[derive(Debug, Deserialize, ToSchema, IntoParams)]
+pub struct MigrationImportParams {
+    #[serde(default)]
+    pub strategy: MigrationConflictStrategy,
+}
+
+#[derive(Debug, Default, Clone, Serialize, ToSchema)]
+pub struct MigrationSectionSummary {
+    pub section: String,
+    pub imported: u32,
+    pub skipped: u32,
+    pub overwritten: u32,
+}
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct MigrationImportResponse {
+    pub sections: Vec<MigrationSectionSummary>,
+}
+
+#[derive(Debug, Deserialize, ToSchema, IntoParams)]
+pub struct KeycloakImportParams {
+    /// Only builds the report without writing anything
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// What happens, or happened with a dry run, to a single entity from the Keycloak realm export.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum KeycloakImportAction {
+    /// Does not exist locally and is created
+    Create,
+    /// Already exists locally with the same name, client id or E-Mail and is left untouched
+    Exists,
+    /// Cannot be mapped onto Rauthy, the `warnings` contain the reason
+    Skip,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct KeycloakImportEntity {
+    /// The role or group name, client id or user E-Mail in Rauthy
+    pub key: String,
+    /// The id inside Keycloak, if the export contains it
+    pub keycloak_id: Option<String>,
+    pub action: KeycloakImportAction,
+    /// Unsupported or partially mapped features
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct KeycloakImportResponse {
+    pub realm: String,
+    pub dry_run: bool,
+    pub roles: Vec<KeycloakImportEntity>,
+    pub groups: Vec<KeycloakImportEntity>,
+    pub clients: Vec<KeycloakImportEntity>,
+    pub users: Vec<KeycloakImportEntity>,
+}
@@ -1,11 +1,13 @@
+use crate::claims::JwtTokenType;
+use crate::clients::ClaimTarget;
 use crate::cust_validation::validate_vec_scopes;
 use crate::generic::PasswordPolicyResponse;
 use crate::sessions::SessionState;
-use actix_web::HttpRequest;
 use actix_web::http::header;
+use actix_web::{HttpRequest, ResponseError};
 use rauthy_common::regex::{
     RE_ALNUM, RE_BASE64, RE_CLIENT_ID, RE_CODE_CHALLENGE_METHOD, RE_CODE_VERIFIER, RE_GRANT_TYPES,
-    RE_LOWERCASE, RE_SCOPE_SPACE, RE_URI,
+    RE_LOWERCASE, RE_SCOPE_REQUEST, RE_SCOPE_SPACE, RE_URI,
 };
 use rauthy_common::utils::base64_decode;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
@@ -44,13 +46,16 @@ pub struct AuthRequest {
     /// Validation: `[a-z0-9-_/]{2,128}`
     #[validate(regex(path = "*RE_LOWERCASE", code = "[a-z0-9-_/]{2,128}"))]
     pub response_type: String,
-    /// Validation: `[a-zA-Z0-9-_/:\s*]{0,512}`
-    #[validate(regex(path = "*RE_SCOPE_SPACE", code = "[a-zA-Z0-9-_/:\\s*]{0,512}"))]
+    /// Validation: `[a-zA-Z0-9-_/:\s*]`, max length and count from `access.max_scope_*`
+    #[validate(regex(path = "*RE_SCOPE_REQUEST", code = "[a-zA-Z0-9-_/:\\s*]"))]
     #[serde(default = "default_scope")]
     pub scope: String,
     /// Validation: max length 2048
     #[validate(length(max = 2048))]
     pub state: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%@]+$`, max length from `access.max_nonce_length`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%@]+$"))]
+    pub nonce: Option<String>,
     /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%@]+$`
     #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%@]+$"))]
     pub code_challenge: Option<String>,
@@ -69,6 +74,12 @@ pub struct AuthRequest {
     /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%@]+$`
     #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%@]+$"))]
     pub resource: Option<String>,
+    /// Space-separated BCP 47 tags in order of preference. The first supported one is used,
+    /// unless the user has chosen a language explicitly.
+    ///
+    /// Validation: max length 256
+    #[validate(length(max = 256))]
+    pub ui_locales: Option<String>,
 }
 
 #[inline]
@@ -192,6 +203,19 @@ pub struct LogoutRequest {
     /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%@]+$`
     #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%@]+$"))]
     pub logout_token: Option<String>,
+    /// Propagate the logout to the upstream auth provider of a federated user, if it offers an
+    /// `end_session_endpoint`. Always happens, if the provider has `auto_upstream_logout` set.
+    pub upstream_logout: Option<bool>,
+}
+
+#[derive(Deserialize, Validate, ToSchema, IntoParams)]
+#[cfg_attr(debug_assertions, derive(Serialize))]
+pub struct UpstreamLogoutCompleteRequest {
+    /// The `state` Rauthy has sent to the upstream `end_session_endpoint`
+    ///
+    /// Validation: `[a-zA-Z0-9]`
+    #[validate(regex(path = "*RE_ALNUM", code = "[a-zA-Z0-9]"))]
+    pub state: Option<String>,
 }
 
 #[derive(Deserialize, Validate, ToSchema)]
@@ -203,14 +227,23 @@ pub struct DeviceGrantRequest {
     /// Validation: max length is 256
     #[validate(length(max = 256))]
     pub client_secret: Option<String>,
-    /// Validation: `[a-zA-Z0-9-_/:\s*]{0,512}`
-    #[validate(regex(path = "*RE_SCOPE_SPACE", code = "[a-zA-Z0-9-_/:\\s*]{0,512}"))]
+    /// Validation: `[a-zA-Z0-9-_/:\s*]`, max length and count from `access.max_scope_*`
+    #[validate(regex(path = "*RE_SCOPE_REQUEST", code = "[a-zA-Z0-9-_/:\\s*]"))]
     pub scope: Option<String>,
     /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%@]+$`
     #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%@]+$"))]
     pub nonce: Option<String>,
 }
 
+#[derive(Deserialize, Validate, ToSchema, IntoParams)]
+pub struct DeviceHtmlParams {
+    /// The `user_code` from the `verification_uri_complete`
+    ///
+    /// Validation: `[a-zA-Z0-9]`
+    #[validate(regex(path = "*RE_ALNUM", code = "[a-zA-Z0-9]"))]
+    pub code: Option<String>,
+}
+
 #[derive(Deserialize, Validate, ToSchema)]
 #[cfg_attr(debug_assertions, derive(Serialize))]
 pub struct DeviceVerifyRequest {
@@ -326,6 +359,14 @@ pub struct TokenValidationRequest {
     pub token: String,
 }
 
+/// RFC 6750 §2.2: The `access_token` can be sent inside a form body to `POST /oidc/userinfo`
+/// instead of the `Authorization` header, but never both.
+#[derive(Deserialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Serialize))]
+pub struct UserinfoRequest {
+    pub access_token: Option<String>,
+}
+
 #[derive(Serialize, ToSchema)]
 pub struct DeviceCodeResponse<'a> {
     pub device_code: &'a str,
@@ -370,6 +411,69 @@ impl Display for JwkKeyPairAlg {
     }
 }
 
+/// The type of an externally generated key pair for `POST /admin/jwks/import`.
+#[derive(Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub enum JwkImportKeyType {
+    /// Currently not supported for token signing and always rejected.
+    EC,
+    RSA,
+    Ed25519,
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Serialize))]
+pub struct JwkImportRequest {
+    pub key_type: JwkImportKeyType,
+    /// PKCS#8 or PKCS#1 PEM
+    #[validate(length(max = 16384))]
+    pub private_key_pem: String,
+    /// SPKI PEM, must match the `private_key_pem`
+    #[validate(length(max = 8192))]
+    pub public_key_pem: String,
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Serialize))]
+pub struct JwkHsmReferenceRequest {
+    pub alg: JwkKeyPairAlg,
+    /// The PKCS#11 slot the key lives in
+    pub slot: u64,
+    /// The ID of the key object inside the HSM
+    /// Validation: `[a-zA-Z0-9]`
+    #[validate(
+        regex(path = "*RE_ALNUM", code = "[a-zA-Z0-9]"),
+        length(min = 1, max = 128)
+    )]
+    pub key_id: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub enum JwkStatus {
+    /// Used for signing new tokens
+    Current,
+    /// Published for token validation, but not used for signing new tokens
+    Retiring,
+}
+
+#[derive(Serialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Deserialize))]
+pub struct JwkResponse {
+    pub kid: String,
+    pub alg: JwkKeyPairAlg,
+    pub created_at: i64,
+    pub status: JwkStatus,
+}
+
+#[derive(Serialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Deserialize))]
+pub struct JwkHsmReferenceResponse {
+    pub kid: String,
+    pub alg: JwkKeyPairAlg,
+    pub created_at: i64,
+    pub slot: u64,
+    pub key_id: String,
+}
+
 #[derive(Default, Serialize, Deserialize, ToSchema)]
 pub enum JwkKeyPairType {
     RSA,
@@ -413,7 +517,7 @@ pub struct OAuth2ErrorResponse<'a> {
     pub error_description: Option<Cow<'a, str>>,
 }
 
-#[derive(Serialize, ToSchema)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum OAuth2ErrorTypeResponse {
     InvalidRequest,
@@ -422,6 +526,14 @@ pub enum OAuth2ErrorTypeResponse {
     UnauthorizedClient,
     UnsupportedGrantType,
     InvalidScope,
+    // RFC 8707
+    InvalidTarget,
+    // RFC 9449
+    InvalidDpopProof,
+    UseDpopNonce,
+    // not defined for the token endpoint, but the common choice for server-side errors
+    ServerError,
+    TemporarilyUnavailable,
     // specific to the device grant
     AuthorizationPending,
     SlowDown,
@@ -429,6 +541,42 @@ pub enum OAuth2ErrorTypeResponse {
     ExpiredToken,
 }
 
+/// The RFC 6749 §5.2 error response of the token endpoint. `rauthy_error` is a vendor extension
+/// with Rauthy's own error type, which may give more detail than the RFC error code.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TokenErrorResponse<'a> {
+    pub error: OAuth2ErrorTypeResponse,
+    pub error_description: Cow<'a, str>,
+    pub rauthy_error: ErrorResponseType,
+}
+
+impl From<ErrorResponse> for TokenErrorResponse<'static> {
+    fn from(err: ErrorResponse) -> Self {
+        let error = match &err.error {
+            ErrorResponseType::InvalidClient => OAuth2ErrorTypeResponse::InvalidClient,
+            ErrorResponseType::InvalidGrant => OAuth2ErrorTypeResponse::InvalidGrant,
+            ErrorResponseType::InvalidTarget => OAuth2ErrorTypeResponse::InvalidTarget,
+            ErrorResponseType::UnauthorizedClient => OAuth2ErrorTypeResponse::UnauthorizedClient,
+            ErrorResponseType::UnsupportedGrantType => {
+                OAuth2ErrorTypeResponse::UnsupportedGrantType
+            }
+            ErrorResponseType::DPoP(_) => OAuth2ErrorTypeResponse::InvalidDpopProof,
+            ErrorResponseType::UseDpopNonce(_) => OAuth2ErrorTypeResponse::UseDpopNonce,
+            ErrorResponseType::ServiceUnavailable(_) | ErrorResponseType::TooManyRequests(_) => {
+                OAuth2ErrorTypeResponse::TemporarilyUnavailable
+            }
+            _ if err.status_code().is_server_error() => OAuth2ErrorTypeResponse::ServerError,
+            _ => OAuth2ErrorTypeResponse::InvalidRequest,
+        };
+
+        Self {
+            error,
+            error_description: err.message,
+            rauthy_error: err.error,
+        }
+    }
+}
+
 #[derive(Serialize, ToSchema)]
 #[cfg_attr(debug_assertions, derive(Deserialize))]
 pub struct SessionInfoResponse<'a> {
@@ -498,6 +646,34 @@ impl<'a> Audience<'a> {
     }
 }
 
+/// The response of the `/oidc/token` endpoint.
+///
+/// New optional fields may be added in minor releases, which is why it can only be built with
+/// [`TokenSet::new`] outside of this crate.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[non_exhaustive]
+pub struct TokenSet {
+    pub access_token: String,
+    pub token_type: JwtTokenType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<String>,
+    pub expires_in: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+}
+
+impl TokenSet {
+    pub fn new(access_token: String, token_type: JwtTokenType, expires_in: i32) -> Self {
+        Self {
+            access_token,
+            token_type,
+            id_token: None,
+            expires_in,
+            refresh_token: None,
+        }
+    }
+}
+
 #[derive(Default, Serialize, ToSchema)]
 #[cfg_attr(debug_assertions, derive(Deserialize))]
 pub struct TokenInfo<'a> {
@@ -519,3 +695,73 @@ pub struct TokenInfo<'a> {
     #[serde(borrow, skip_serializing_if = "Option::is_none")]
     pub cnf: Option<JktClaim<'a>>,
 }
+
+/// A dry-run of the claims a user would receive for a client. No token will be signed or issued.
+#[derive(Deserialize, Validate, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Serialize))]
+pub struct TokenPreviewRequest {
+    /// Validation: `[a-zA-Z0-9]`
+    #[validate(regex(path = "*RE_ALNUM", code = "[a-zA-Z0-9]"))]
+    pub user_id: String,
+    /// Validation: `^[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]{2,256}$`
+    #[validate(regex(
+        path = "*RE_CLIENT_ID",
+        code = "^[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]{2,256}$"
+    ))]
+    pub client_id: String,
+    /// The requested scopes. They are matched against the client config in the same way as
+    /// during a login. The client's `default_scopes` are used, if not given.
+    ///
+    /// Validation: `Vec<^[a-z0-9-_/,:*]{2,64}$>`
+    #[validate(custom(function = "validate_vec_scopes"))]
+    pub scopes: Option<Vec<String>>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Deserialize))]
+pub struct TokenPreviewResponse {
+    /// Always `false`. The preview only shows the claims and never signs or issues a token.
+    pub signed: bool,
+    /// The scopes, which would actually be granted
+    pub scope: String,
+    /// The claims of the ID token
+    #[schema(value_type = Object)]
+    pub id_token: serde_json::Value,
+    /// The claims of the access token
+    #[schema(value_type = Object)]
+    pub access_token: serde_json::Value,
+    /// The response of the userinfo endpoint
+    #[schema(value_type = Object)]
+    pub userinfo: serde_json::Value,
+    /// Which rule contributed each claim
+    pub trace: Vec<ClaimTrace>,
+    /// Reasons why a real login would fail or differ from this preview
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ClaimTrace {
+    pub target: ClaimTarget,
+    /// The claim name. Claims nested under `custom` are prefixed with `custom.`.
+    pub claim: String,
+    pub source: ClaimSource,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClaimSource {
+    /// Registered and protocol claims like `iss`, `sub` or `exp`
+    Protocol,
+    /// A standard claim, which is emitted because of the granted `scope`
+    Scope { scope: String },
+    /// The user's roles, shaped by the client's `claims_emission`
+    Roles,
+    /// The user's groups, shaped by the client's `claims_emission`
+    Groups,
+    /// The `groups_max` of the client's `claims_emission` was exceeded
+    GroupsOverflow,
+    /// A custom user attribute, which is mapped by a custom scope
+    ScopeAttribute { scope: String, attribute: String },
+    /// Set or extended by the client's claims webhook
+    Webhook,
+}
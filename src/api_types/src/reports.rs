@@ -0,0 +1,72 @@
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+
+#[derive(Deserialize, Validate, ToSchema, IntoParams)]
+pub struct ExpiringReportParams {
+    /// The window from now on in days. Defaults to `events.expiring_window_days`.
+    ///
+    /// Validation: `1 <= days <= 365`
+    #[validate(range(min = 1, max = 365))]
+    pub days: Option<u16>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ExpiringReportResponse {
+    /// Unix timestamp in seconds
+    pub generated: i64,
+    /// Unix timestamp in seconds for the end of the window
+    pub until: i64,
+    pub api_keys: Vec<ExpiringApiKey>,
+    pub device_tokens: Vec<ExpiringDeviceToken>,
+    pub refresh_tokens: Vec<ExpiringRefreshTokens>,
+    pub users: Vec<ExpiringUser>,
+}
+
+impl ExpiringReportResponse {
+    pub fn is_empty(&self) -> bool {
+        self.api_keys.is_empty()
+            && self.device_tokens.is_empty()
+            && self.refresh_tokens.is_empty()
+            && self.users.is_empty()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ExpiringApiKey {
+    pub name: String,
+    /// Unix timestamp in seconds
+    pub expires: i64,
+}
+
+/// A device from the `device_code` flow with a refresh token, that will not be usable after the
+/// window without a new login.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ExpiringDeviceToken {
+    pub device_id: String,
+    pub device_name: String,
+    pub client_id: String,
+    pub user_id: Option<String>,
+    pub email: Option<String>,
+    /// Unix timestamp in seconds
+    pub expires: i64,
+}
+
+/// Only contains users whose latest refresh token expires within the window. Users with a
+/// refresh token valid beyond it are left out, because these are simply rotated.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ExpiringRefreshTokens {
+    pub user_id: String,
+    pub email: String,
+    pub count: i64,
+    /// Unix timestamp in seconds of the latest expiring refresh token
+    pub expires: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ExpiringUser {
+    pub id: String,
+    pub email: String,
+    /// Unix timestamp in seconds
+    pub expires: i64,
+}
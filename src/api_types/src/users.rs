@@ -1,4 +1,5 @@
-use crate::cust_validation::{validate_vec_groups, validate_vec_roles};
+use crate::api_keys::AccessRights;
+use crate::cust_validation::{validate_vec_alnum, validate_vec_groups, validate_vec_roles};
 use crate::generic::Language;
 use crate::oidc::AddressClaim;
 use hiqlite::macros::FromRow;
@@ -9,9 +10,10 @@ use rauthy_common::regex::{
 use rauthy_derive::FromPgRow;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::net::IpAddr;
 use std::str::FromStr;
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
 use validator::Validate;
 
 #[derive(Serialize, ToSchema)]
@@ -30,6 +32,14 @@ pub struct DeviceRequest {
     pub name: Option<String>,
 }
 
+#[derive(Deserialize, Validate, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Serialize))]
+pub struct DisableUsersBulkRequest {
+    /// Validation: `1 <= user_ids.len() <= 10000`, `Vec<^[a-zA-Z0-9]+$>`
+    #[validate(length(min = 1, max = 10000), custom(function = "validate_vec_alnum"))]
+    pub user_ids: Vec<String>,
+}
+
 #[derive(Deserialize, Validate, ToSchema)]
 pub struct MfaAwaitRequest {
     /// Validation: `^[a-zA-Z0-9]{48}$`
@@ -51,6 +61,82 @@ pub enum MfaPurpose {
     Test,
 }
 
+/// Categories of security related E-Mails, which are sent to users.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationCategory {
+    /// Login from a new IP / location
+    NewLoginLocation,
+    /// Reminder about an upcoming password expiry
+    PasswordExpiry,
+    /// Confirmation link for an E-Mail change
+    EmailChange,
+    /// Password reset link, which includes resets forced by an admin
+    PasswordReset,
+    /// Confirmation of an account deletion
+    AccountDeletion,
+}
+
+impl NotificationCategory {
+    pub const ALL: [Self; 5] = [
+        Self::NewLoginLocation,
+        Self::PasswordExpiry,
+        Self::EmailChange,
+        Self::PasswordReset,
+        Self::AccountDeletion,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::NewLoginLocation => "new_login_location",
+            Self::PasswordExpiry => "password_expiry",
+            Self::EmailChange => "email_change",
+            Self::PasswordReset => "password_reset",
+            Self::AccountDeletion => "account_deletion",
+        }
+    }
+
+    /// Non-suppressible categories contain links a user must be able to act on, or confirm
+    /// something the user must know about. They will always be sent.
+    pub fn is_suppressible(&self) -> bool {
+        match self {
+            Self::NewLoginLocation | Self::PasswordExpiry => true,
+            Self::EmailChange | Self::PasswordReset | Self::AccountDeletion => false,
+        }
+    }
+}
+
+impl TryFrom<&str> for NotificationCategory {
+    type Error = ErrorResponse;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Self::ALL
+            .into_iter()
+            .find(|c| c.as_str() == value)
+            .ok_or_else(|| {
+                ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    format!("Unknown notification category: {value}"),
+                )
+            })
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Serialize))]
+pub struct NotificationPrefRequest {
+    pub category: NotificationCategory,
+    pub enabled: bool,
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Serialize))]
+pub struct NotificationPrefsRequest {
+    /// Validation: max length 16
+    #[validate(length(max = 16))]
+    pub prefs: Vec<NotificationPrefRequest>,
+}
+
 #[derive(Deserialize, Validate, ToSchema)]
 #[cfg_attr(debug_assertions, derive(Serialize))]
 pub struct NewUserRequest {
@@ -104,6 +190,26 @@ pub struct NewUserRegistrationRequest {
     pub redirect_uri: Option<String>,
 }
 
+#[derive(Deserialize, Validate, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Serialize))]
+pub struct UserRegistrationConfigRequest {
+    /// Validation: `Vec<^[a-z0-9-_/,:*]{2,64}$>`
+    #[validate(custom(function = "validate_vec_roles"))]
+    pub default_roles: Vec<String>,
+    /// Validation: `Vec<^[a-z0-9-_/,:*]{2,64}$>`
+    #[validate(custom(function = "validate_vec_groups"))]
+    pub default_groups: Vec<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Deserialize))]
+pub struct UserRegistrationConfigResponse {
+    pub default_roles: Vec<String>,
+    pub default_groups: Vec<String>,
+    /// Read-only, set via `user_registration.require_admin_approval` in the config
+    pub require_admin_approval: bool,
+}
+
 #[derive(Deserialize, Validate, ToSchema)]
 #[cfg_attr(debug_assertions, derive(Serialize))]
 pub struct PasskeyRequest {
@@ -272,9 +378,13 @@ pub struct UserAttrConfigRequest {
     #[validate(regex(path = "*RE_ATTR_DESC", code = "[a-zA-Z0-9À-ÿ-\\s]{2,128}"))]
     pub desc: Option<String>,
     pub default_value: Option<serde_json::Value>,
-    /// Currently ignored - will be implemented in a future version
+    /// Values synced from an upstream claim must match this type.
     pub typ: Option<UserAttrConfigTyp>,
     pub user_editable: Option<bool>,
+    /// If set, the value is synced from this top-level upstream claim during each login via an
+    /// upstream auth provider. A differing claim value overwrites manual edits.
+    #[validate(length(min = 1, max = 128))]
+    pub auto_populate_from_claim: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Validate, ToSchema)]
@@ -293,6 +403,43 @@ pub struct UserAttrValuesUpdateRequest {
     pub values: Vec<UserAttrValueRequest>,
 }
 
+#[derive(Deserialize, Validate, ToSchema, IntoParams)]
+pub struct UserDeleteParams {
+    /// If `true`, the user will be erased permanently including all linked data and audit
+    /// events. Requires the `x-rauthy-erasure-confirm: ERASE_{user_id}` header.
+    pub erasure: Option<bool>,
+}
+
+#[derive(Deserialize, Validate, ToSchema, IntoParams)]
+pub struct UserAttrDeleteParams {
+    /// Must be `true`, if any user has a value for this attribute. These values will be deleted
+    /// together with the attribute.
+    pub confirm: Option<bool>,
+}
+
+#[derive(Deserialize, Validate, ToSchema, IntoParams)]
+pub struct UserAttrValuesParams {
+    /// Only return exact matches. A value, which is valid JSON, like `1234`, matches its typed
+    /// value and the same value as a string.
+    #[validate(length(max = 1024))]
+    pub value: Option<String>,
+    /// If `true`, each distinct value is returned with the amount of users having it.
+    /// `value` is ignored in this mode.
+    pub distinct: Option<bool>,
+    /// Validation: `1 <= page_size <= 1000`, defaults to `100`
+    #[validate(range(min = 1, max = 1000))]
+    pub page_size: Option<u16>,
+    pub offset: Option<u32>,
+}
+
+#[derive(Deserialize, Validate, ToSchema, IntoParams)]
+pub struct UsersAttrParams {
+    /// Reverse lookup of all users with a custom attribute value in the format `key:value`,
+    /// like e.g. `cost_center:1234`. Uses `page_size` and `offset` from the pagination params.
+    #[validate(length(max = 1024))]
+    pub attr: Option<String>,
+}
+
 #[derive(Deserialize, Validate, ToSchema)]
 pub struct UserRevokeParams {
     /// Validation: IpAddr
@@ -383,6 +530,87 @@ pub struct DeviceResponse {
     pub name: String,
 }
 
+/// An application, which holds refresh tokens for a user. Token values are never included.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UserGrantResponse {
+    pub client_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_name: Option<String>,
+    /// Unix timestamp in seconds of the earliest login with a still active refresh token
+    pub first_granted: i64,
+    /// Unix timestamp in seconds of the last `refresh_token` grant. It is buffered and may lag
+    /// behind by up to a minute.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_used: Option<i64>,
+    /// Unix timestamp in seconds of the latest expiring refresh token
+    pub expires: i64,
+    pub scopes: Vec<String>,
+    /// The amount of active refresh tokens
+    pub tokens: i64,
+}
+
+/// An API key with access to the `Users` group, which therefore can act on any user.
+#[derive(Serialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Deserialize))]
+pub struct EffectivePermissionApiKey {
+    pub name: String,
+    /// Unix timestamp in seconds
+    pub expires: Option<i64>,
+    /// The keys' access rights for the `Users` group
+    pub access_rights: Vec<AccessRights>,
+    pub source: EffectivePermissionSource,
+}
+
+#[derive(Serialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Deserialize))]
+pub struct EffectivePermissionAttr {
+    pub key: String,
+    pub value: serde_json::Value,
+    pub source: EffectivePermissionSource,
+}
+
+#[derive(Serialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Deserialize))]
+pub struct EffectivePermissionItem {
+    pub name: String,
+    pub source: EffectivePermissionSource,
+}
+
+/// Where an effective permission item of a user comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Deserialize))]
+#[serde(rename_all = "snake_case")]
+pub enum EffectivePermissionSource {
+    /// Directly assigned to the user
+    Direct,
+    /// Default value from the `UserAttrConfig`, because the user has no value itself
+    AttrDefault,
+    /// A custom scope that maps at least one of the users' attributes
+    ScopeMapping,
+    /// A registered Passkey
+    Passkey,
+    /// An API key with access to the `Users` group
+    ApiKey,
+}
+
+#[derive(Serialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Deserialize))]
+pub struct DisableUsersBulkResponse {
+    /// Ids of all users, which have been enabled before
+    pub disabled: Vec<String>,
+    /// Deleted (or revoked for `issued_tokens`) rows per entity
+    pub revoked: HashMap<String, u64>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Deserialize))]
+pub struct ErasureResult {
+    /// Pseudonym that replaced the user in audit events, if they have not been deleted
+    pub pseudonym: Option<String>,
+    /// Deleted (or pseudonymized for `events`) rows per entity
+    pub entities_deleted: HashMap<String, u64>,
+}
+
 #[derive(Serialize, ToSchema)]
 #[cfg_attr(debug_assertions, derive(Deserialize))]
 pub struct MfaModTokenResponse {
@@ -392,6 +620,51 @@ pub struct MfaModTokenResponse {
     pub ip: String,
 }
 
+#[derive(Serialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Deserialize))]
+pub struct NotificationPrefResponse {
+    pub category: NotificationCategory,
+    pub enabled: bool,
+    /// Non-suppressible categories will always be sent, no matter what `enabled` says.
+    pub suppressible: bool,
+    /// `true` if the user has not set this preference and the instance default applies
+    pub is_default: bool,
+}
+
+/// A single value of a user, which has been changed by an upstream login.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct UserFederationFieldChange {
+    /// One of `email`, `given_name`, `family_name`
+    pub field: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct UserFederationChange {
+    /// Unix timestamp in seconds
+    pub timestamp: i64,
+    pub provider_id: String,
+    pub changes: Vec<UserFederationFieldChange>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Deserialize))]
+pub struct UserFederationResponse {
+    pub user_id: String,
+    pub auth_provider_id: Option<String>,
+    pub auth_provider_name: Option<String>,
+    pub federation_uid: Option<String>,
+    /// `false` if the retention of upstream claims has been disabled via config
+    pub claims_retained: bool,
+    /// The most recent validated upstream claim set
+    pub claims: Option<serde_json::Value>,
+    /// Unix timestamp in seconds
+    pub claims_updated: Option<i64>,
+    /// The last 10 upstream driven changes, newest first
+    pub history: Vec<UserFederationChange>,
+}
+
 #[derive(Serialize, ToSchema)]
 #[cfg_attr(debug_assertions, derive(Deserialize))]
 pub struct PasskeyResponse {
@@ -402,6 +675,51 @@ pub struct PasskeyResponse {
     pub last_used: i64,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub user_verified: Option<bool>,
+    /// Unix timestamp in seconds, when the sign counter indicated a possibly cloned
+    /// authenticator. Cleared by an admin after an investigation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub counter_anomaly: Option<i64>,
+}
+
+/// Portable export of all Passkeys of a single user. The same format is accepted by the import.
+#[derive(Serialize, Deserialize, Validate, ToSchema)]
+pub struct PasskeysExport {
+    /// Format version, currently always `1`
+    #[validate(range(min = 1, max = 1))]
+    pub version: u8,
+    /// The `webauthn.rp_id` the credentials have been registered for
+    #[validate(length(min = 1, max = 256))]
+    pub rp_id: String,
+    /// The WebAuthn user handle the credentials are bound to, as UUID
+    #[validate(length(min = 36, max = 36))]
+    pub user_handle: Option<String>,
+    #[validate(nested, length(min = 1))]
+    pub credentials: Vec<PasskeyExport>,
+}
+
+#[derive(Serialize, Deserialize, Validate, ToSchema)]
+pub struct PasskeyExport {
+    /// Validation: `[a-zA-Z0-9À-ÿ-'\\s]{1,32}`
+    #[validate(regex(path = "*RE_USER_NAME", code = "[a-zA-Z0-9À-ɏ-'\\s]{1,32}"))]
+    pub name: String,
+    /// Base64 URL-safe without padding
+    #[validate(length(min = 16, max = 1366))]
+    pub credential_id: String,
+    /// The COSE public key in the JSON representation of `webauthn-rs`
+    #[schema(value_type = Object)]
+    pub public_key: serde_json::Value,
+    pub sign_count: u32,
+    /// Values: `usb`, `nfc`, `ble`, `internal`, `hybrid`
+    pub transports: Option<Vec<String>>,
+    /// Informational only, ignored during import
+    pub aaguid: Option<String>,
+    pub user_verified: bool,
+    pub backup_eligible: bool,
+    pub backup_state: bool,
+    /// Unix timestamp in seconds, informational only, ignored during import
+    pub registered: i64,
+    /// Unix timestamp in seconds, informational only, ignored during import
+    pub last_used: i64,
 }
 
 #[derive(Serialize, ToSchema)]
@@ -415,6 +733,8 @@ pub struct UserAttrConfigValueResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub typ: Option<UserAttrConfigTyp>,
     pub user_editable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_populate_from_claim: Option<String>,
 }
 
 #[derive(Serialize, ToSchema)]
@@ -428,6 +748,10 @@ pub struct UserAttrConfigResponse {
 pub struct UserAttrValueResponse {
     pub key: String,
     pub value: serde_json::Value,
+    /// Unix timestamp of the last sync from an upstream claim. `None` if the value has been set
+    /// manually.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_synced_from_claim_at: Option<i64>,
 }
 
 #[derive(Serialize, ToSchema)]
@@ -436,6 +760,21 @@ pub struct UserAttrValuesResponse {
     pub values: Vec<UserAttrValueResponse>,
 }
 
+#[derive(Serialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Deserialize))]
+pub struct UserAttrUsageResponse {
+    pub user_id: String,
+    pub value: serde_json::Value,
+}
+
+#[derive(Serialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Deserialize))]
+pub struct UserAttrDistinctValueResponse {
+    pub value: serde_json::Value,
+    /// The amount of users with this value
+    pub count: i64,
+}
+
 #[derive(Serialize, ToSchema)]
 #[cfg_attr(debug_assertions, derive(Deserialize))]
 pub struct UserEditableAttrResponse {
@@ -456,13 +795,41 @@ pub struct UserEditableAttrsResponse {
     pub values: Vec<UserEditableAttrResponse>,
 }
 
+/// A client the user has access to, for rendering the account portal.
+#[derive(Serialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Deserialize))]
+pub struct UserClientResponse {
+    pub id: String,
+    pub name: Option<String>,
+    pub client_uri: Option<String>,
+    /// The logo can be fetched from `/auth/v1/clients/{id}/logo`. This timestamp changes with
+    /// each logo update and can be appended as `?updated=` to bust caches. `None` if no custom
+    /// logo exists.
+    pub logo_updated: Option<i64>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Deserialize))]
+pub struct UserEffectivePermissionsResponse {
+    pub user_id: String,
+    pub roles: Vec<EffectivePermissionItem>,
+    pub groups: Vec<EffectivePermissionItem>,
+    pub default_scopes: Vec<EffectivePermissionItem>,
+    pub user_attrs: Vec<EffectivePermissionAttr>,
+    /// All API keys, which are not expired and have access to users
+    pub api_keys: Vec<EffectivePermissionApiKey>,
+    pub trusted_devices: usize,
+    pub mfa_methods: Vec<EffectivePermissionItem>,
+}
+
 #[derive(Serialize, ToSchema)]
 #[cfg_attr(debug_assertions, derive(Deserialize))]
 pub struct Userinfo {
     pub id: String,
     pub sub: String,
     pub name: String,
-    pub roles: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub roles: Option<Vec<String>>,
     pub mfa_enabled: bool,
 
     // scope: address
@@ -504,6 +871,10 @@ pub struct Userinfo {
     // scope: webid
     #[serde(skip_serializing_if = "Option::is_none")]
     pub webid: Option<String>,
+
+    /// Custom named `roles` / `groups` claims from the client's `claims_emission`
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub custom_flattened: Option<HashMap<String, serde_json::Value>>,
 }
 
 #[derive(Serialize, ToSchema)]
@@ -518,6 +889,29 @@ pub enum UserAccountTypeResponse {
     FederatedPassword,
 }
 
+#[derive(Debug, Default, Serialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Deserialize))]
+pub struct UserReindexResponse {
+    pub cache_entries_invalidated: u32,
+    /// The amount of the users' group memberships, which have been validated against the
+    /// existing groups
+    pub groups_recounted: u32,
+    pub duration_ms: u64,
+}
+
+/// Sent as SSE data during `/users/reindex_all`. The last message has `finished: true`.
+#[derive(Debug, Default, Serialize, ToSchema)]
+#[cfg_attr(debug_assertions, derive(Deserialize))]
+pub struct UsersReindexProgress {
+    pub total: u32,
+    pub processed: u32,
+    pub failed: u32,
+    pub cache_entries_invalidated: u32,
+    pub groups_recounted: u32,
+    pub duration_ms: u64,
+    pub finished: bool,
+}
+
 #[derive(Serialize, ToSchema)]
 #[cfg_attr(debug_assertions, derive(Deserialize))]
 pub struct UserResponse {
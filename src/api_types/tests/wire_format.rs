@@ -0,0 +1,190 @@
+// These tests pin the wire format of all types which are part of the public API. If one of them
+// fails, the change is breaking for every client parsing Rauthy responses or tokens. Only update
+// the snapshot, if the change is intentional and mentioned in the CHANGELOG.
+
+use rauthy_api_types::claims::{
+    AddressClaim, JwtAccessClaims, JwtCommonClaims, JwtIdClaims, JwtLogoutClaims, JwtRefreshClaims,
+    JwtTokenType,
+};
+use rauthy_api_types::oidc::{Audience, JktClaim, TokenErrorResponse, TokenSet};
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+/// Checks that `value` serializes into the `snapshot` and that the `snapshot` can be
+/// deserialized again without losing any information.
+fn assert_snapshot<'a, T>(value: &T, snapshot: &'a str)
+where
+    T: Serialize + Deserialize<'a>,
+{
+    let expected = serde_json::from_str::<serde_json::Value>(snapshot).unwrap();
+    assert_eq!(
+        serde_json::to_value(value).unwrap(),
+        expected,
+        "serialized value does not match the snapshot"
+    );
+
+    let back = serde_json::from_str::<T>(snapshot).unwrap();
+    assert_eq!(
+        serde_json::to_value(&back).unwrap(),
+        expected,
+        "snapshot does not survive a round trip"
+    );
+}
+
+fn common(typ: JwtTokenType) -> JwtCommonClaims<'static> {
+    JwtCommonClaims {
+        iat: 1_700_000_000,
+        nbf: 1_700_000_000,
+        exp: 1_700_003_600,
+        iss: "https://auth.example.com/auth/v1",
+        jti: Some("jti123"),
+        aud: Audience::single("client-id"),
+        sub: Some("user-id"),
+        typ,
+        azp: "client-id",
+        scope: Some(Cow::Borrowed("openid")),
+        did: None,
+        cnf: None,
+    }
+}
+
+#[test]
+fn test_token_set() {
+    let mut ts = TokenSet::new(
+        "eyJhbGciOiJFZERTQSJ9.access".to_string(),
+        JwtTokenType::Bearer,
+        1800,
+    );
+    ts.id_token = Some("eyJhbGciOiJFZERTQSJ9.id".to_string());
+    ts.refresh_token = Some("eyJhbGciOiJFZERTQSJ9.refresh".to_string());
+    assert_snapshot(&ts, include_str!("snapshots/token_set.json"));
+}
+
+#[test]
+fn test_claims_access() {
+    let mut common = common(JwtTokenType::DPoP);
+    common.scope = Some(Cow::Borrowed("openid email custom"));
+    common.cnf = Some(JktClaim { jkt: "fingerprint" });
+
+    let claims = JwtAccessClaims {
+        common,
+        allowed_origins: None,
+        email: Some("admin@example.com"),
+        email_verified: Some(true),
+        roles: Some(vec!["admin"]),
+        groups: Some(vec!["group-a"]),
+        custom: Some(HashMap::from([("department".to_string(), json!("eng"))])),
+        custom_flattened: Some(HashMap::from([("org_id".to_string(), json!("o-1"))])),
+    };
+    assert_snapshot(&claims, include_str!("snapshots/claims_access.json"));
+}
+
+#[test]
+fn test_claims_id() {
+    let mut common = common(JwtTokenType::Id);
+    common.aud = Audience::Multiple(vec![
+        Cow::Borrowed("client-id"),
+        Cow::Borrowed("https://api.example.com"),
+    ]);
+
+    let claims = JwtIdClaims {
+        common,
+        amr: vec!["pwd", "mfa"],
+        auth_time: 1_700_000_000,
+        at_hash: "xsZZrUssMXjL3FBlzoSh2g",
+        sid: Some("sid123"),
+        email: Some("admin@example.com"),
+        email_verified: Some(true),
+        preferred_username: Some("admin"),
+        given_name: Some("Admin"),
+        family_name: Some("Rauthy"),
+        address: Some(AddressClaim {
+            formatted: "Admin Rauthy\nMain Street 1\n10115, Berlin\nGermany\n".to_string(),
+            street_address: Some("Main Street 1"),
+            locality: Some("Berlin"),
+            postal_code: Some("10115"),
+            country: Some("Germany"),
+        }),
+        birthdate: Some("1970-01-01"),
+        picture: None,
+        locale: Some("en"),
+        nonce: Some("nonce123"),
+        phone_number: Some("+49123456789"),
+        phone_number_verified: Some(false),
+        roles: Some(vec!["admin".to_string()]),
+        groups: None,
+        custom: None,
+        custom_flattened: None,
+        webid: None,
+        zoneinfo: Some("Europe/Berlin"),
+    };
+    assert_snapshot(&claims, include_str!("snapshots/claims_id.json"));
+}
+
+#[test]
+fn test_claims_refresh() {
+    let claims = JwtRefreshClaims {
+        common: common(JwtTokenType::Refresh),
+        uid: "user-id",
+        auth_time: Some(1_700_000_000),
+        resource: Some("https://api.example.com"),
+    };
+    assert_snapshot(&claims, include_str!("snapshots/claims_refresh.json"));
+}
+
+#[test]
+fn test_claims_logout() {
+    let claims = JwtLogoutClaims {
+        common: common(JwtTokenType::Logout),
+        events: json!({ "http://schemas.openid.net/event/backchannel-logout": {} }),
+        sid: Some("sid123"),
+        nonce: None,
+    };
+    assert_snapshot(&claims, include_str!("snapshots/claims_logout.json"));
+}
+
+#[test]
+fn test_error_response() {
+    let errors = [
+        (ErrorResponseType::BadRequest, "invalid_request"),
+        (
+            ErrorResponseType::TooManyRequests(1_700_000_060),
+            "Too many requests",
+        ),
+        (ErrorResponseType::InvalidTarget, "Unknown resource"),
+    ]
+    .into_iter()
+    .map(|(error, message)| ErrorResponse {
+        timestamp: 1_700_000_000,
+        error,
+        message: Cow::Borrowed(message),
+    })
+    .collect::<Vec<_>>();
+    assert_snapshot(&errors, include_str!("snapshots/error_response.json"));
+}
+
+#[test]
+fn test_token_error_response() {
+    let errors = [
+        (
+            ErrorResponseType::InvalidGrant,
+            "The Authorization Code has expired",
+        ),
+        (ErrorResponseType::InvalidClient, "Invalid 'client_secret'"),
+        (ErrorResponseType::BadRequest, "'code' is missing"),
+        (ErrorResponseType::ServiceUnavailable(30), "Maintenance"),
+    ]
+    .into_iter()
+    .map(|(error, message)| {
+        TokenErrorResponse::from(ErrorResponse {
+            timestamp: 1_700_000_000,
+            error,
+            message: Cow::Borrowed(message),
+        })
+    })
+    .collect::<Vec<_>>();
+    assert_snapshot(&errors, include_str!("snapshots/token_error_response.json"));
+}
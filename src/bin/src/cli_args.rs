@@ -83,6 +83,11 @@ pub struct ArgsServer {
     #[clap(short, long, default_value = "./config.toml")]
     pub config_file: String,
 
+    /// Only validate the config, print all problems found and exit. Exits with a non-zero
+    /// status code if the config contains errors.
+    #[clap(long, default_value_t = false)]
+    pub check_config: bool,
+
     /// Integration Test Mode
     #[cfg(debug_assertions)]
     #[clap(short, long, default_value_t = false)]
@@ -27,6 +27,9 @@ async fn main() -> Result<(), Box<dyn Error>> {
     dotenvy::dotenv().ok();
 
     match Args::parse() {
+        Args::Serve(args) if args.check_config => {
+            utils::validate_config::check(&args.config_file).await?
+        }
         Args::Serve(args) => {
             let local_test = env::var("LOCAL_TEST")
                 .unwrap_or_else(|_| "false".to_string())
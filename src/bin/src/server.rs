@@ -1,5 +1,6 @@
 use crate::logging::setup_logging;
 use crate::{init_static_vars, logging, tls, version_migration};
+use actix_web::dev::Server;
 use actix_web::rt::System;
 use actix_web::{App, HttpServer, middleware, web};
 use actix_web_prom::PrometheusMetricsBuilder;
@@ -7,8 +8,10 @@ use prometheus::Registry;
 use rauthy_common::constants::BUILD_TIME;
 use rauthy_common::constants::RAUTHY_VERSION;
 use rauthy_common::utils::UseDummyAddress;
-use rauthy_common::{is_hiqlite, password_hasher};
+use rauthy_common::{is_hiqlite, password_hasher, shutdown};
 use rauthy_data::ListenScheme;
+use rauthy_data::cache_warmup;
+use rauthy_data::config_check::check_db_config;
 use rauthy_data::database::{Cache, DB};
 use rauthy_data::email::mailer;
 use rauthy_data::entity;
@@ -16,18 +19,21 @@ use rauthy_data::entity::pictures::UserPicture;
 use rauthy_data::events::health_watch::watch_health;
 use rauthy_data::events::listener::EventListener;
 use rauthy_data::events::notifier::EventNotifier;
+use rauthy_data::language_pack::LanguagePacks;
 use rauthy_data::rauthy_config::RauthyConfig;
 use rauthy_handlers::openapi::ApiDoc;
 use rauthy_handlers::swagger_ui::{OPENAPI_CONFIG, OPENAPI_JSON};
 use rauthy_handlers::{
     api_keys, atproto, auth_providers, backup, blacklist, clients, cors_preflight, dev_only, email,
-    events, fed_cm, generic, groups, html, kv, oidc, pam, roles, scopes, sessions, swagger_ui,
-    themes, tos, users,
+    events, fed_cm, generic, groups, html, i18n, kv, migration, oidc, pam, reports, roles, scopes,
+    sessions, swagger_ui, themes, tos, users,
 };
 use rauthy_middlewares::csrf_protection::CsrfProtectionMiddleware;
 use rauthy_middlewares::ip_blacklist::RauthyIpBlacklistMiddleware;
 use rauthy_middlewares::logging::RauthyLoggingMiddleware;
+use rauthy_middlewares::maintenance_mode::RauthyMaintenanceMiddleware;
 use rauthy_middlewares::principal::RauthyPrincipalMiddleware;
+use rauthy_middlewares::security_headers::RauthySecurityHeadersMiddleware;
 use std::cmp::max;
 use std::error::Error;
 use std::net::Ipv4Addr;
@@ -83,6 +89,7 @@ pub async fn run(config_file: String, test_mode: bool) -> Result<(), Box<dyn Err
     }
 
     RauthyConfig::debug_logs();
+    LanguagePacks::log_status();
     if let Err(err) = rauthy_data::migration::bootstrap::purge_expired_generated_secrets().await {
         warn!("Could not purge bootstrap generated-secret container: {err}");
     }
@@ -99,7 +106,7 @@ pub async fn run(config_file: String, test_mode: bool) -> Result<(), Box<dyn Err
 
     // MUST start before we go into `DB::migrate()` - we may need it inside.
     debug!("Starting Password Hasher");
-    tokio::spawn(password_hasher::run());
+    password_hasher::spawn_workers(RauthyConfig::get().vars.hashing.max_hash_threads as usize);
 
     debug!("Applying database migrations");
     DB::migrate().await.expect("Database migration error");
@@ -122,9 +129,13 @@ pub async fn run(config_file: String, test_mode: bool) -> Result<(), Box<dyn Err
     }
 
     UserPicture::test_config().await.unwrap();
+    check_db_config(RauthyConfig::get().vars.dev.dev_mode)
+        .await
+        .log();
 
     // We need to clear some caches
     DB::hql().clear_cache(Cache::Html).await?;
+    DB::hql().clear_cache(Cache::LoginPageContext).await?;
     // whole App cache to make sure config changes are always updated
     DB::hql().clear_cache(Cache::App).await?;
 
@@ -166,6 +177,12 @@ pub async fn run(config_file: String, test_mode: bool) -> Result<(), Box<dyn Err
             .unwrap();
     }
 
+    if RauthyConfig::get().vars.database.cache_warmup_on_startup {
+        tokio::spawn(cache_warmup::warm());
+    } else {
+        cache_warmup::mark_done();
+    }
+
     rauthy_schedulers::spawn();
 
     if RauthyConfig::get().vars.server.metrics_enable {
@@ -174,6 +191,17 @@ pub async fn run(config_file: String, test_mode: bool) -> Result<(), Box<dyn Err
         server_without_metrics().await?;
     }
 
+    // The HTTP servers are stopped at this point. Give queued E-Mails, event notifications and
+    // a possibly running maintenance batch the chance to finish before we close the DB.
+    shutdown::start_draining();
+    let config = RauthyConfig::get();
+    let timeout = Duration::from_secs(config.vars.server.shutdown_drain_timeout as u64);
+    info!("Waiting for queued E-Mails and background tasks to finish");
+    shutdown::wait_for_pending(timeout, || {
+        config.tx_email.capacity() == config.tx_email.max_capacity() && config.tx_events.is_empty()
+    })
+    .await;
+
     DB::hql().shutdown().await?;
 
     Ok(())
@@ -193,6 +221,7 @@ async fn server_with_metrics() -> std::io::Result<()> {
     let listen_addr = RauthyConfig::get().vars.server.listen_address.to_string();
 
     let shared_registry = Registry::new();
+    rauthy_data::metrics::register(&shared_registry).expect("Cannot register Rauthy metrics");
     let metrics = PrometheusMetricsBuilder::new("api")
         .registry(shared_registry.clone())
         .endpoint("/metrics")
@@ -234,6 +263,7 @@ async fn server_with_metrics() -> std::io::Result<()> {
     let server = HttpServer::new(move || {
         let mut app = App::new()
             .wrap(RauthyLoggingMiddleware)
+            .wrap(RauthyMaintenanceMiddleware)
             .wrap(RauthyPrincipalMiddleware)
             .wrap(CsrfProtectionMiddleware)
             .wrap(default_headers())
@@ -248,10 +278,14 @@ async fn server_with_metrics() -> std::io::Result<()> {
             .wrap(metrics_collector.clone())
             .service(oidc::get_well_known)
             .service(oidc::get_well_known_oauth)
+            .service(oidc::get_well_known_oauth_issuer_path)
             .service(fed_cm::get_fed_cm_well_known)
             // Important: Do not move this middleware do need the least amount of computing
             // for blacklisted IPs -> middlewares are executed in reverse order -> this one first
             .wrap(RauthyIpBlacklistMiddleware)
+            // It must wrap the blacklist to be able to add the headers and the CSP nonce for
+            // its rendered HTML.
+            .wrap(RauthySecurityHeadersMiddleware)
             .service(api_services())
             .service(generic::catch_all);
 
@@ -267,48 +301,41 @@ async fn server_with_metrics() -> std::io::Result<()> {
     })
     // overwrites the number of worker threads -> default == available cpu cores
     .workers(workers())
-    .shutdown_timeout(10);
-
-    match listen_scheme {
-        ListenScheme::Http => {
-            server
-                .bind(format!(
+    // we handle signals ourselves to be able to fail the ready check before draining
+    .disable_signals()
+    .shutdown_timeout(RauthyConfig::get().vars.server.shutdown_drain_timeout as u64);
+
+    let server = match listen_scheme {
+        ListenScheme::Http => server
+            .bind(format!(
+                "{listen_addr}:{}",
+                RauthyConfig::get().vars.server.port_http
+            ))?
+            .run(),
+
+        ListenScheme::Https => server
+            .bind_rustls_0_23(
+                format!(
                     "{listen_addr}:{}",
-                    RauthyConfig::get().vars.server.port_http
-                ))?
-                .run()
-                .await
-        }
-
-        ListenScheme::Https => {
-            server
-                .bind_rustls_0_23(
-                    format!(
-                        "{listen_addr}:{}",
-                        RauthyConfig::get().vars.server.port_https
-                    ),
-                    tls::load_tls().await,
-                )?
-                .run()
-                .await
-        }
-
-        ListenScheme::HttpHttps => {
-            server
-                .bind(format!(
+                    RauthyConfig::get().vars.server.port_https
+                ),
+                tls::load_tls().await,
+            )?
+            .run(),
+
+        ListenScheme::HttpHttps => server
+            .bind(format!(
+                "{listen_addr}:{}",
+                RauthyConfig::get().vars.server.port_http
+            ))?
+            .bind_rustls_0_23(
+                format!(
                     "{listen_addr}:{}",
-                    RauthyConfig::get().vars.server.port_http
-                ))?
-                .bind_rustls_0_23(
-                    format!(
-                        "{listen_addr}:{}",
-                        RauthyConfig::get().vars.server.port_https
-                    ),
-                    tls::load_tls().await,
-                )?
-                .run()
-                .await
-        }
+                    RauthyConfig::get().vars.server.port_https
+                ),
+                tls::load_tls().await,
+            )?
+            .run(),
 
         #[cfg(not(target_os = "windows"))]
         ListenScheme::UnixHttp | ListenScheme::UnixHttps => {
@@ -316,9 +343,11 @@ async fn server_with_metrics() -> std::io::Result<()> {
 
             let server = server.bind_uds(&listen_addr)?;
             tokio::fs::set_permissions(listen_addr, std::fs::Permissions::from_mode(0o666)).await?;
-            server.run().await
+            server.run()
         }
-    }
+    };
+
+    run_until_shutdown(server).await
 }
 
 async fn server_without_metrics() -> std::io::Result<()> {
@@ -328,15 +357,20 @@ async fn server_without_metrics() -> std::io::Result<()> {
     let server = HttpServer::new(move || {
         let mut app = App::new()
             .wrap(RauthyLoggingMiddleware)
+            .wrap(RauthyMaintenanceMiddleware)
             .wrap(RauthyPrincipalMiddleware)
             .wrap(CsrfProtectionMiddleware)
             .wrap(default_headers())
             .service(oidc::get_well_known)
             .service(oidc::get_well_known_oauth)
+            .service(oidc::get_well_known_oauth_issuer_path)
             .service(fed_cm::get_fed_cm_well_known)
             // Important: Do not move this middleware do need the least amount of computing
             // for blacklisted IPs -> middlewares are executed in reverse order -> this one first
             .wrap(RauthyIpBlacklistMiddleware)
+            // It must wrap the blacklist to be able to add the headers and the CSP nonce for
+            // its rendered HTML.
+            .wrap(RauthySecurityHeadersMiddleware)
             .service(api_services())
             .service(generic::catch_all);
 
@@ -352,48 +386,41 @@ async fn server_without_metrics() -> std::io::Result<()> {
     })
     // overwrites the number of worker threads -> default == available cpu cores
     .workers(workers())
-    .shutdown_timeout(10);
-
-    match listen_scheme {
-        ListenScheme::Http => {
-            server
-                .bind(format!(
+    // we handle signals ourselves to be able to fail the ready check before draining
+    .disable_signals()
+    .shutdown_timeout(RauthyConfig::get().vars.server.shutdown_drain_timeout as u64);
+
+    let server = match listen_scheme {
+        ListenScheme::Http => server
+            .bind(format!(
+                "{listen_addr}:{}",
+                RauthyConfig::get().vars.server.port_http
+            ))?
+            .run(),
+
+        ListenScheme::Https => server
+            .bind_rustls_0_23(
+                format!(
                     "{listen_addr}:{}",
-                    RauthyConfig::get().vars.server.port_http
-                ))?
-                .run()
-                .await
-        }
-
-        ListenScheme::Https => {
-            server
-                .bind_rustls_0_23(
-                    format!(
-                        "{listen_addr}:{}",
-                        RauthyConfig::get().vars.server.port_https
-                    ),
-                    tls::load_tls().await,
-                )?
-                .run()
-                .await
-        }
-
-        ListenScheme::HttpHttps => {
-            server
-                .bind(format!(
+                    RauthyConfig::get().vars.server.port_https
+                ),
+                tls::load_tls().await,
+            )?
+            .run(),
+
+        ListenScheme::HttpHttps => server
+            .bind(format!(
+                "{listen_addr}:{}",
+                RauthyConfig::get().vars.server.port_http
+            ))?
+            .bind_rustls_0_23(
+                format!(
                     "{listen_addr}:{}",
-                    RauthyConfig::get().vars.server.port_http
-                ))?
-                .bind_rustls_0_23(
-                    format!(
-                        "{listen_addr}:{}",
-                        RauthyConfig::get().vars.server.port_https
-                    ),
-                    tls::load_tls().await,
-                )?
-                .run()
-                .await
-        }
+                    RauthyConfig::get().vars.server.port_https
+                ),
+                tls::load_tls().await,
+            )?
+            .run(),
 
         #[cfg(not(target_os = "windows"))]
         ListenScheme::UnixHttp | ListenScheme::UnixHttps => {
@@ -401,25 +428,57 @@ async fn server_without_metrics() -> std::io::Result<()> {
 
             let server = server.bind_uds(&listen_addr)?;
             tokio::fs::set_permissions(listen_addr, std::fs::Permissions::from_mode(0o666)).await?;
-            server.run().await
+            server.run()
+        }
+    };
+
+    run_until_shutdown(server).await
+}
+
+/// Runs the `server` until SIGTERM / SIGINT. The ready check fails from the start of the
+/// shutdown, new connections are not accepted anymore, and in-flight requests are drained
+/// until the `shutdown_drain_timeout` is reached.
+async fn run_until_shutdown(server: Server) -> std::io::Result<()> {
+    let handle = server.handle();
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        shutdown::start_draining();
+        handle.stop(true).await;
+    });
+
+    server.await
+}
+
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        let mut sigterm = signal(SignalKind::terminate()).expect("Cannot listen to SIGTERM");
+        tokio::select! {
+            _ = sigterm.recv() => info!("Received SIGTERM"),
+            _ = tokio::signal::ctrl_c() => info!("Received SIGINT"),
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            error!(?err, "Cannot listen to shutdown signal");
         }
     }
 }
 
+/// The `Content-Security-Policy`, `X-Frame-Options` and `Referrer-Policy` are added by the
+/// `RauthySecurityHeadersMiddleware`.
 fn default_headers() -> middleware::DefaultHeaders {
     middleware::DefaultHeaders::new()
-        .add(("x-frame-options", "DENY"))
         .add(("x-content-type-options", "nosniff"))
         .add((
             "strict-transport-security",
             "max-age=31536000;includeSubDomains",
         ))
-        .add(("referrer-policy", "no-referrer"))
         .add(("x-robots-tag", "noindex, nofollow"))
-        .add((
-            "content-security-policy",
-            "frame-ancestors 'none'; object-src 'none';",
-        ))
         .add(("cache-control", "no-store"))
 }
 
@@ -438,14 +497,30 @@ fn api_services() -> actix_web::Scope {
                 .service(atproto::get_atproto_client_metadata)
                 .service(auth_providers::post_providers)
                 .service(auth_providers::get_providers_minimal)
+                .service(auth_providers::get_providers_export)
+                .service(auth_providers::post_providers_import)
                 .service(auth_providers::post_provider)
                 .service(auth_providers::post_provider_login)
                 .service(auth_providers::get_provider_delete_safe)
+                .service(auth_providers::get_provider_group_mappings)
+                .service(auth_providers::post_provider_group_mapping)
+                .service(auth_providers::put_provider_group_mapping)
+                .service(auth_providers::delete_provider_group_mapping)
+                .service(auth_providers::get_provider_role_mappings)
+                .service(auth_providers::post_provider_role_mapping)
+                .service(auth_providers::put_provider_role_mapping)
+                .service(auth_providers::delete_provider_role_mapping)
                 .service(auth_providers::post_provider_lookup)
                 .service(auth_providers::get_provider_callback_html)
+                // must be registered before the JSON callback, which does not check the
+                // content type
+                .service(auth_providers::post_provider_callback_form)
                 .service(auth_providers::post_provider_callback)
                 .service(auth_providers::delete_provider_link)
                 .service(auth_providers::put_provider)
+                .service(auth_providers::post_provider_secret)
+                .service(auth_providers::post_provider_test_callback)
+                .service(auth_providers::post_provider_test)
                 .service(auth_providers::delete_provider)
                 .service(auth_providers::get_provider_img)
                 .service(auth_providers::put_provider_img)
@@ -455,6 +530,9 @@ fn api_services() -> actix_web::Scope {
                 .service(backup::post_backup)
                 .service(backup::get_backup_local)
                 .service(backup::get_backup_s3)
+                .service(migration::get_migration_export)
+                .service(migration::post_migration_import)
+                .service(migration::post_migration_keycloak)
                 .service(blacklist::get_blacklist)
                 .service(blacklist::post_blacklist)
                 .service(blacklist::delete_blacklist)
@@ -471,9 +549,11 @@ fn api_services() -> actix_web::Scope {
                 .service(cors_preflight::options_users_register)
                 .service(cors_preflight::options_atproto_metadata)
                 .service(cors_preflight::options_openid_configuration)
+                .service(cors_preflight::options_oauth_authorization_server)
                 .service(email::get_email_jobs)
                 .service(email::post_send_email)
                 .service(email::post_email_job_cancel)
+                .service(email::post_test_smtp)
                 .service(events::post_events)
                 .service(events::sse_events)
                 .service(events::post_event_test)
@@ -507,6 +587,7 @@ fn api_services() -> actix_web::Scope {
                 .service(generic::get_auth_check_admin)
                 .service(generic::get_timezones)
                 .service(generic::post_update_language)
+                .service(generic::get_system_info)
                 .service(generic::get_version)
                 .service(generic::get_whoami)
                 .service(kv::get_kv_ns)
@@ -531,6 +612,7 @@ fn api_services() -> actix_web::Scope {
                 .service(kv::get_kv_access_test_ext)
                 .service(oidc::get_authorize)
                 .service(oidc::post_authorize)
+                .service(oidc::post_break_glass)
                 .service(oidc::post_authorize_refresh)
                 .service(oidc::post_device_auth)
                 .service(oidc::post_device_verify)
@@ -539,7 +621,12 @@ fn api_services() -> actix_web::Scope {
                 .service(oidc::get_cert_by_kid)
                 .service(oidc::get_logout)
                 .service(oidc::post_logout)
+                .service(oidc::get_logout_upstream)
+                .service(oidc::get_logout_complete)
                 .service(oidc::rotate_jwk)
+                .service(oidc::post_jwks_import)
+                .service(oidc::post_jwk_promote)
+                .service(oidc::post_jwks_hsm_reference)
                 .service(oidc::post_session)
                 .service(oidc::get_session_info)
                 .service(oidc::get_session_xsrf)
@@ -548,6 +635,10 @@ fn api_services() -> actix_web::Scope {
                 .service(clients::get_client_logo)
                 .service(clients::put_client_logo)
                 .service(clients::delete_client_logo)
+                .service(clients::get_client_jwks)
+                .service(clients::put_client_jwks)
+                .service(clients::delete_client_jwks)
+                .service(clients::post_client_jwks_refresh)
                 .service(clients::get_client_secret)
                 .service(clients::post_clients)
                 .service(clients::put_clients)
@@ -559,6 +650,9 @@ fn api_services() -> actix_web::Scope {
                 .service(clients::get_forward_auth_oidc)
                 .service(clients::get_forward_auth_callback)
                 .service(generic::get_login_time)
+                .service(generic::get_maintenance_mode)
+                .service(generic::put_maintenance_mode)
+                .service(generic::post_maintenance_mode_bypass)
                 .service(fed_cm::get_fed_cm_accounts)
                 .service(fed_cm::get_fed_cm_config)
                 .service(fed_cm::get_fed_cm_client_meta)
@@ -597,17 +691,27 @@ fn api_services() -> actix_web::Scope {
                 .service(pam::delete_pam_user)
                 .service(pam::delete_pam_user_authorized_key)
                 .service(pam::get_validate_user)
+                .service(reports::get_reports_expiring)
                 .service(users::get_users)
+                .service(users::get_users_register_config)
+                .service(users::put_users_register_config)
                 .service(users::get_users_register)
                 .service(users::post_users_register)
+                .service(users::post_user_approve)
                 .service(html::get_user_password_reset_fixed)
                 .service(users::get_user_values_config)
                 .service(users::get_cust_attr)
+                .service(users::get_cust_attr_values)
                 .service(users::post_cust_attr)
                 .service(users::put_cust_attr)
                 .service(users::delete_cust_attr)
                 .service(users::get_user_picture_config)
                 .service(users::get_user_by_id)
+                .service(users::get_user_clients)
+                .service(users::get_user_effective_permissions)
+                .service(users::post_user_reindex)
+                .service(users::post_users_reindex_all)
+                .service(users::post_users_disable_bulk)
                 .service(users::get_user_attr)
                 .service(users::get_user_attr_editable)
                 .service(users::put_user_attr)
@@ -618,12 +722,18 @@ fn api_services() -> actix_web::Scope {
                 .service(users::get_user_devices)
                 .service(users::put_user_device_name)
                 .service(users::delete_user_device)
+                .service(users::get_user_grants)
+                .service(users::delete_user_grant)
+                .service(users::get_user_notification_prefs)
+                .service(users::put_user_notification_prefs)
+                .service(users::get_user_federation)
                 .service(users::get_user_webid_data)
                 .service(users::put_user_webid_data)
                 .service(users::get_user_email_confirm)
                 .service(users::post_user_self_convert_passkey)
                 .service(users::put_user_self_preferred_username)
                 .service(generic::post_password_hash_times)
+                .service(generic::post_token_preview)
                 .service(sessions::get_sessions)
                 .service(sessions::delete_sessions)
                 .service(sessions::delete_session_by_id)
@@ -643,15 +753,21 @@ fn api_services() -> actix_web::Scope {
                 .service(users::put_user_self)
                 .service(users::get_user_self_delete_config)
                 .service(users::delete_user_self)
+                .service(users::post_user_provider_unlink)
+                .service(users::post_user_provider_link)
+                .service(users::delete_user_provider_link)
                 .service(users::delete_user_by_id)
                 .service(users::post_user_password_request_reset)
                 .service(users::get_user_revoke)
                 .service(users::get_user_webauthn_passkeys)
+                .service(users::get_user_webauthn_export)
+                .service(users::post_user_webauthn_import)
                 .service(users::post_webauthn_reg_start)
                 .service(users::post_webauthn_reg_finish)
                 .service(users::post_webauthn_auth_start)
                 .service(users::post_webauthn_auth_finish)
                 .service(users::delete_webauthn)
+                .service(users::delete_webauthn_counter_anomaly)
                 .service(generic::get_password_policy)
                 .service(generic::put_password_policy)
                 .service(generic::post_pow)
@@ -676,11 +792,18 @@ fn api_services() -> actix_web::Scope {
                 .service(oidc::get_forward_auth)
                 .service(generic::get_enc_keys)
                 .service(generic::post_migrate_enc_key)
+                .service(generic::post_migrate_secrets)
+                .service(generic::post_migrate_pii)
                 .service(generic::ping)
                 .service(themes::get_theme)
                 .service(themes::post_theme)
                 .service(themes::put_theme)
                 .service(themes::delete_theme)
+                .service(i18n::get_i18n_language)
+                .service(i18n::get_i18n_overrides)
+                .service(i18n::put_i18n_overrides)
+                .service(i18n::get_i18n_override_keys)
+                .service(i18n::post_i18n_overrides_preview)
                 .service(oidc::get_well_known)
                 .service(oidc::get_well_known_oauth)
                 .service(generic::get_health)
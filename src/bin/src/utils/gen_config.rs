@@ -399,8 +399,12 @@ proxy_mode = {}
 # A list of trusted proxy CIDRs. When `proxy_mode = true`
 # or `peer_ip_header_name` is set, these are mandatory to
 # be able to extract the real client IP properly and safely
-# to prevent IP header spoofing. All requests witha
-# different source will be blocked.
+# to prevent IP header spoofing. Forwarding headers from
+# any other source are ignored and the peer IP is used.
+# `Forwarded` and `X-Forwarded-For` are evaluated from
+# right to left, and the first hop which is not a trusted
+# proxy is the client. If you have multiple proxies
+# chained, all of them must be listed here.
 #
 # default: []
 # overwritten by: TRUSTED_PROXIES - single String, \n separated values
@@ -1,17 +1,38 @@
 use crate::cli_args::ArgsValidateConfig;
-use rauthy_data::email::mailer;
-use rauthy_data::rauthy_config::RauthyConfig;
+use rauthy_data::rauthy_config::Vars;
 use std::error::Error;
-use tokio::sync::mpsc;
+use std::process;
 
 pub async fn validate(args: ArgsValidateConfig) -> Result<(), Box<dyn Error>> {
-    let (tx_email, _) = mpsc::channel::<mailer::EMail>(16);
-    let (tx_events, _) = flume::unbounded();
-    let (tx_events_router, _) = flume::unbounded();
+    check(&args.path).await
+}
+
+/// Runs only the config validation, prints all issues found and exits with a non-zero status
+/// code if any of them is a hard error. Suitable for CI pipelines.
+pub async fn check(path: &str) -> Result<(), Box<dyn Error>> {
+    let (vars, node_config) = Vars::load(path).await;
+
+    let issues = vars.check();
+    issues.print();
+
+    let mut errors = issues.errors();
+    if let Err(err) = node_config.is_valid() {
+        eprintln!("ERROR `cluster`: {err}");
+        errors += 1;
+    }
 
-    RauthyConfig::build(args.path.clone(), tx_email, tx_events, tx_events_router).await?;
+    if errors > 0 {
+        eprintln!(
+            "Rauthy Config '{path}' is invalid: {errors} error(s), {} warning(s)",
+            issues.warnings()
+        );
+        process::exit(1);
+    }
 
-    println!("Rauthy Config '{}' is valid", args.path);
+    println!(
+        "Rauthy Config '{path}' is valid with {} warning(s)",
+        issues.warnings()
+    );
 
     Ok(())
 }
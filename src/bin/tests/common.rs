@@ -1,9 +1,11 @@
 #![allow(dead_code)]
-use rauthy_api_types::oidc::{LoginRequest, SessionInfoResponse, TokenRequest};
+use rauthy_api_types::clients::UpdateClientRequest;
+use rauthy_api_types::oidc::{
+    JwkKeyPairAlg, LoginRequest, SessionInfoResponse, TokenRequest, TokenSet,
+};
 use rauthy_common::constants::CSRF_HEADER;
 use rauthy_common::sha256;
 use rauthy_common::utils::base64_url_encode;
-use rauthy_service::token_set::TokenSet;
 use reqwest::header::{HeaderMap, HeaderValue, SET_COOKIE};
 use reqwest::{Response, header};
 use spow::pow::Pow;
@@ -60,22 +62,68 @@ pub async fn get_token_set() -> TokenSet {
     ts
 }
 
-pub async fn get_token_set_init_client() -> TokenSet {
-    // get a token to validate
-    let url_token = format!("{}/oidc/token", get_backend_url());
-    let body = TokenRequest {
-        grant_type: "password".to_string(),
+/// Builds a `TokenRequest` for a confidential client. Only the `password` grant gets the
+/// credentials of the test user. Anything else, like a `refresh_token`, must be set by the caller.
+pub fn token_req(grant_type: &str, client_id: &str, secret: &str) -> TokenRequest {
+    let is_password = grant_type == "password";
+    TokenRequest {
+        grant_type: grant_type.to_string(),
         code: None,
         redirect_uri: None,
-        client_id: Some(CLIENT_ID.to_string()),
-        client_secret: Some(CLIENT_SECRET.to_string()),
+        client_id: Some(client_id.to_string()),
+        client_secret: Some(secret.to_string()),
         code_verifier: None,
         device_code: None,
-        username: Some(USERNAME.to_string()),
-        password: Some(PASSWORD.to_string()),
+        username: is_password.then(|| USERNAME.to_string()),
+        password: is_password.then(|| PASSWORD.to_string()),
         refresh_token: None,
         resource: None,
-    };
+    }
+}
+
+/// Builds an `UpdateClientRequest` for a confidential test client with the given flows.
+/// Everything a test cares about can be overwritten with the struct update syntax.
+pub fn update_req(name: &str, flows_enabled: &[&str]) -> UpdateClientRequest {
+    UpdateClientRequest {
+        name: Some(name.to_string()),
+        confidential: true,
+        redirect_uris: vec!["http://localhost/callback".to_string()],
+        post_logout_redirect_uris: None,
+        allowed_origins: None,
+        enabled: true,
+        flows_enabled: flows_enabled.iter().map(|f| f.to_string()).collect(),
+        access_token_alg: JwkKeyPairAlg::EdDSA,
+        id_token_alg: JwkKeyPairAlg::EdDSA,
+        auth_code_lifetime: 60,
+        // valid for only 60 seconds to make the refresh token valid immediately
+        access_token_lifetime: 60,
+        scopes: vec!["openid".to_string()],
+        default_scopes: vec!["openid".to_string()],
+        challenges: None,
+        force_mfa: false,
+        client_uri: None,
+        contacts: None,
+        backchannel_logout_uri: None,
+        restrict_group_prefix: None,
+        claims: None,
+        claims_at_root: false,
+        allowed_resources: None,
+        default_aud: None,
+        allowed_roles: None,
+        allowed_groups: None,
+        response_types: None,
+        claims_emission: None,
+        refresh_token_binding: None,
+        client_type: None,
+        scim: None,
+        claims_webhook: None,
+    }
+}
+
+pub async fn get_token_set_init_client() -> TokenSet {
+    // get a token to validate
+    let url_token = format!("{}/oidc/token", get_backend_url());
+    let body = token_req("password", CLIENT_ID, CLIENT_SECRET);
 
     let res = reqwest::Client::new()
         .post(&url_token)
@@ -136,6 +184,7 @@ pub async fn session_headers() -> (HeaderMap, TokenSet) {
         .await
         .unwrap();
     assert_eq!(res.status(), 202);
+    let headers = rotated_session_headers(&res, headers);
 
     let (code, _state) = code_state_from_headers(res).unwrap();
     let req_token = TokenRequest {
@@ -214,9 +263,28 @@ pub async fn session_headers_with(email: &str, password: &str) -> HeaderMap {
         let body = res.text().await.unwrap_or_default();
         panic!("group-admin login for {email} failed: {status} - {body}");
     }
+    rotated_session_headers(&res, headers)
+}
+
+/// The session id is rotated after each successful login. Replaces the session cookie in
+/// `headers` with the new one from the login response and keeps the CSRF token.
+pub fn rotated_session_headers(res: &Response, mut headers: HeaderMap) -> HeaderMap {
+    let cookie = session_cookie_from_res(res).expect("rotated session cookie to exist");
+    headers.insert(header::COOKIE, HeaderValue::from_str(&cookie).unwrap());
     headers
 }
 
+/// Returns the `__Host-RauthySession=...` pair from the `Set-Cookie` headers, if any.
+pub fn session_cookie_from_res(res: &Response) -> Option<String> {
+    res.headers()
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .filter_map(|v| v.split_once(';').map(|(c, _)| c))
+        .find(|c| c.starts_with("__Host-RauthySession="))
+        .map(String::from)
+}
+
 /// extractor for the POST `/oidc/session` endpoint
 pub async fn cookie_csrf_headers_from_res_direct(
     res: Response,
@@ -1,5 +1,9 @@
-use crate::common::{get_backend_url, get_issuer};
+use crate::common::{get_auth_headers, get_backend_url, get_issuer};
 use pretty_assertions::assert_eq;
+use rauthy_api_types::generic::{Language, SmtpTestResponse};
+use rauthy_api_types::i18n::{
+    I18nOverride, I18nOverridesRequest, I18nPage, I18nPageKeysResponse, I18nPreviewRequest,
+};
 use serde::Deserialize;
 use std::error::Error;
 
@@ -13,6 +17,15 @@ async fn test_get_ping() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_get_ready_after_cache_warmup() -> Result<(), Box<dyn Error>> {
+    // the cache warmup has long finished as soon as the test server accepts requests
+    let url = format!("{}/ready", get_backend_url());
+    let res = reqwest::get(&url).await?;
+    assert_eq!(res.status(), 200);
+    Ok(())
+}
+
 // Re-defined and copy & pasted here to be able to get rid of lots of
 // memory allocations in prod, because `Deserialize` will not work with
 // `'static` lifetimes.
@@ -42,6 +55,7 @@ pub struct WellKnown {
     pub dpop_signing_alg_values_supported: Vec<String>,
     pub service_documentation: String,
     pub ui_locales_supported: Vec<String>,
+    pub locale_values_supported: Vec<String>,
     pub claims_parameter_supported: bool,
     pub client_id_metadata_document_supported: bool,
 }
@@ -59,3 +73,276 @@ async fn test_get_well_known() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_well_known_oauth_authorization_server() -> Result<(), Box<dyn Error>> {
+    let backend_url = get_backend_url();
+    let client = reqwest::Client::new();
+
+    let res = client
+        .get(format!("{backend_url}/.well-known/openid-configuration"))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let oidc = res.json::<serde_json::Value>().await?;
+
+    // RFC 8414: the well-known path is inserted between the host and the issuer path
+    let root_url = backend_url.strip_suffix("/auth/v1").unwrap();
+    let url = format!("{root_url}/.well-known/oauth-authorization-server/auth/v1");
+    let res = client.get(&url).send().await?;
+    assert_eq!(res.status(), 200);
+    let etag = res.headers().get("etag").unwrap().clone();
+    let oauth = res.json::<serde_json::Value>().await?;
+
+    assert_eq!(oauth["issuer"], oidc["issuer"]);
+    assert!(oauth["response_types_supported"].is_array());
+    assert!(oauth.get("userinfo_endpoint").is_none());
+    for (key, value) in oauth.as_object().unwrap() {
+        if let Some(oidc_value) = oidc.get(key) {
+            assert_eq!(oidc_value, value, "mismatch for {key}");
+        }
+    }
+
+    // the same document is served directly below the issuer
+    let res = client
+        .get(format!(
+            "{backend_url}/.well-known/oauth-authorization-server"
+        ))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers().get("etag"), Some(&etag));
+
+    let res = client
+        .get(&url)
+        .header("if-none-match", etag)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 304);
+
+    let res = client
+        .get(format!(
+            "{root_url}/.well-known/oauth-authorization-server/other/issuer"
+        ))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 404);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_well_known_extra_claims() -> Result<(), Box<dyn Error>> {
+    // the extra claims are set in `config-test.toml`
+    let url = format!("{}/.well-known/openid-configuration", get_backend_url());
+    let res = reqwest::get(&url).await?;
+    assert_eq!(res.status(), 200);
+    let content = res.json::<serde_json::Value>().await?;
+    assert_eq!(content["x_vault_role"], serde_json::json!("rauthy"));
+    assert_eq!(content["x_mfa_enforced"], serde_json::json!(true));
+    assert_eq!(content["x_audiences"], serde_json::json!(["a", "b"]));
+    // standard fields stay untouched
+    assert!(content["issuer"].is_string());
+
+    let url = format!("{}/system_info", get_backend_url());
+    let res = reqwest::get(&url).await?;
+    assert_eq!(res.status(), 401);
+
+    let res = reqwest::Client::new()
+        .get(&url)
+        .headers(get_auth_headers().await?)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let info = res.json::<serde_json::Value>().await?;
+    assert_eq!(
+        info["wellknown_extra_claims"]["x_vault_role"],
+        serde_json::json!("rauthy")
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_smtp_config_check() -> Result<(), Box<dyn Error>> {
+    let url = format!("{}/admin/config/test_smtp", get_backend_url());
+    let client = reqwest::Client::new();
+
+    let res = client.post(&url).send().await?;
+    assert_eq!(res.status(), 401);
+
+    // depending on the test setup, SMTP may not be configured at all, but the check must always
+    // return a 200 with the result in the body
+    let res = client
+        .post(&url)
+        .headers(get_auth_headers().await?)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let res = res.json::<SmtpTestResponse>().await?;
+    if res.error.is_none() {
+        assert!(res.tcp_connected);
+    } else {
+        assert!(!res.authenticated);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_flow_error_content_negotiation() -> Result<(), Box<dyn Error>> {
+    let backend_url = get_backend_url();
+    let client = reqwest::Client::new();
+
+    // invalid client -> browser navigations get a localized error page
+    let url = format!(
+        "{backend_url}/oidc/authorize?client_id=iDoNotExist&redirect_uri=http%3A%2F%2Flocalhost%3A3000&response_type=code&scope=openid"
+    );
+    let res = client
+        .get(&url)
+        .header("accept", "text/html,application/xhtml+xml,*/*;q=0.8")
+        .header("accept-language", "de")
+        .header("sec-fetch-mode", "navigate")
+        .header("sec-fetch-dest", "document")
+        .send()
+        .await?;
+    assert!(res.status().is_client_error());
+    let content_type = res.headers().get("content-type").unwrap().to_str()?;
+    assert!(content_type.starts_with("text/html"));
+    let body = res.text().await?;
+    assert!(body.contains(r#"<html lang="de">"#));
+    assert!(body.contains(r#"href="/auth/v1/""#));
+
+    // API clients and fetch() requests keep receiving JSON
+    for (accept, mode) in [("application/json", None), ("text/html,*/*", Some("cors"))] {
+        let mut req = client.get(&url).header("accept", accept);
+        if let Some(mode) = mode {
+            req = req.header("sec-fetch-mode", mode);
+        }
+        let res = req.send().await?;
+        assert!(res.status().is_client_error());
+        let content_type = res.headers().get("content-type").unwrap().to_str()?;
+        assert!(content_type.starts_with("application/json"));
+        let err = res.json::<serde_json::Value>().await?;
+        assert!(err.get("error").is_some());
+        assert!(err.get("message").is_some());
+    }
+
+    // an expired device code from a `verification_uri_complete`
+    let res = client
+        .get(format!("{backend_url}/device?code=iDoNotExist"))
+        .header("accept", "text/html")
+        .header("accept-language", "en")
+        .send()
+        .await?;
+    assert_eq!(res.status(), 404);
+    let body = res.text().await?;
+    assert!(body.contains("Sign-in expired"));
+    assert!(body.contains(r#"href="/auth/v1/device""#));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_i18n_overrides() -> Result<(), Box<dyn Error>> {
+    let url = format!("{}/i18n/overrides", get_backend_url());
+    let client = reqwest::Client::new();
+    let headers = get_auth_headers().await?;
+
+    let res = client.get(&url).send().await?;
+    assert_eq!(res.status(), 401);
+
+    let res = client
+        .get(format!("{url}/keys"))
+        .headers(headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let keys = res.json::<Vec<I18nPageKeysResponse>>().await?;
+    let device = keys.iter().find(|k| k.page == I18nPage::Device).unwrap();
+    let desc = device.keys.iter().find(|k| k.key == "desc").unwrap();
+    assert_eq!(desc.placeholders, vec!["count".to_string()]);
+
+    let notice = || I18nOverride {
+        page: I18nPage::Login,
+        key: "notice".to_string(),
+        lang: Language::De,
+        value: "Anmeldung nur für Mitarbeiter".to_string(),
+    };
+
+    // unknown keys and invalid placeholders must be rejected
+    for (page, key, value) in [
+        (I18nPage::Login, "unknown", "Hallo"),
+        (I18nPage::Device, "desc", "Code ohne Platzhalter"),
+        (I18nPage::Login, "login", "Hallo {{name}}"),
+    ] {
+        let payload = I18nOverridesRequest {
+            overrides: vec![I18nOverride {
+                page,
+                key: key.to_string(),
+                lang: Language::De,
+                value: value.to_string(),
+            }],
+        };
+        let res = client
+            .put(&url)
+            .headers(headers.clone())
+            .json(&payload)
+            .send()
+            .await?;
+        assert_eq!(res.status(), 400);
+    }
+
+    let payload = I18nOverridesRequest {
+        overrides: vec![notice()],
+    };
+    let res = client
+        .put(&url)
+        .headers(headers.clone())
+        .json(&payload)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    let res = client.get(&url).headers(headers.clone()).send().await?;
+    assert_eq!(res.status(), 200);
+    let overrides = res.json::<Vec<I18nOverride>>().await?;
+    assert_eq!(overrides.len(), 1);
+    assert_eq!(overrides[0].value, notice().value);
+
+    // the preview merges pending values without saving them
+    let payload = I18nPreviewRequest {
+        page: I18nPage::Login,
+        lang: Language::De,
+        overrides: vec![I18nOverride {
+            value: "Nur eine Vorschau".to_string(),
+            ..notice()
+        }],
+    };
+    let res = client
+        .post(format!("{url}/preview"))
+        .headers(headers.clone())
+        .json(&payload)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let body = res.text().await?;
+    assert!(body.contains("tpl_i18n_overrides"));
+    assert!(body.contains("Nur eine Vorschau"));
+
+    let res = client.get(&url).headers(headers.clone()).send().await?;
+    let overrides = res.json::<Vec<I18nOverride>>().await?;
+    assert_eq!(overrides[0].value, notice().value);
+
+    // reset to not influence other tests
+    let payload = I18nOverridesRequest { overrides: vec![] };
+    let res = client
+        .put(&url)
+        .headers(headers)
+        .json(&payload)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    Ok(())
+}
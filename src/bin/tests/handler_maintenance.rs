@@ -0,0 +1,183 @@
+use crate::common::{get_auth_headers, get_backend_url};
+use chrono::Utc;
+use hiqlite::macros::params;
+use pretty_assertions::assert_eq;
+use rauthy_api_types::generic::Language;
+use rauthy_api_types::users::{NewUserRequest, UserResponse};
+use reqwest::StatusCode;
+use serde_json::Value;
+use std::error::Error;
+use std::time::Duration;
+
+mod common;
+
+/// Must match the `sched_maintenance_batch_size` from the test config
+const BATCH_SIZE: usize = 10;
+/// Must match the `sched_maintenance_grace_secs` from the test config
+const GRACE_SECS: i64 = 86400;
+const CSRF_TOKEN: &str = "8jINPnFznLF9o905QuE2n9CD4rTraQO4E4fOWPZTAkbgNHqM";
+
+/// Direct access to the database of the test backend, because expired links can't be created
+/// via the API.
+enum Db {
+    Hiqlite(hiqlite::Client),
+    Postgres(tokio_postgres::Client),
+}
+
+impl Db {
+    async fn connect() -> Result<Self, Box<dyn Error>> {
+        if std::env::var("HIQLITE").as_deref() == Ok("false") {
+            let (client, conn) = tokio_postgres::connect(
+                "host=localhost user=rauthy password=123SuperSafe dbname=rauthy",
+                tokio_postgres::NoTls,
+            )
+            .await?;
+            tokio::spawn(conn);
+            Ok(Self::Postgres(client))
+        } else {
+            let client = hiqlite::Client::remote(
+                vec!["localhost:8200".to_string()],
+                false,
+                false,
+                "SuperSecureSecret1337".to_string(),
+                false,
+            )
+            .await?;
+            Ok(Self::Hiqlite(client))
+        }
+    }
+
+    async fn insert_link(&self, id: &str, user_id: &str, exp: i64) -> Result<(), Box<dyn Error>> {
+        let sql = r#"
+INSERT INTO magic_links (id, user_id, csrf_token, exp, used, usage)
+VALUES ($1, $2, $3, $4, $5, 'password_reset')"#;
+        match self {
+            Self::Hiqlite(client) => {
+                client
+                    .execute(sql, params!(id, user_id, CSRF_TOKEN, exp, false))
+                    .await?;
+            }
+            Self::Postgres(client) => {
+                client
+                    .execute(sql, &[&id, &user_id, &CSRF_TOKEN, &exp, &false])
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn count_links(&self, user_id: &str, id_prefix: &str) -> Result<i64, Box<dyn Error>> {
+        let sql = "SELECT COUNT(*) AS count FROM magic_links WHERE user_id = $1 AND id LIKE $2";
+        let pattern = format!("{id_prefix}%");
+        let count: i64 = match self {
+            Self::Hiqlite(client) => client
+                .query_raw_one(sql, params!(user_id, pattern))
+                .await?
+                .get("count"),
+            Self::Postgres(client) => client
+                .query_one(sql, &[&user_id, &pattern])
+                .await?
+                .get("count"),
+        };
+        Ok(count)
+    }
+
+    async fn expired_attempt(&self, id: &str) -> Result<Option<i64>, Box<dyn Error>> {
+        let sql = "SELECT expired_attempt FROM magic_links WHERE id = $1";
+        let ts = match self {
+            Self::Hiqlite(client) => client
+                .query_raw_one(sql, params!(id))
+                .await?
+                .get("expired_attempt"),
+            Self::Postgres(client) => client.query_one(sql, &[&id]).await?.get("expired_attempt"),
+        };
+        Ok(ts)
+    }
+}
+
+/// Magic link ids are always 64 alphanumeric characters.
+fn link_id(prefix: &str, i: usize) -> String {
+    format!("{prefix}{i:0>width$}", width = 64 - prefix.len())
+}
+
+#[tokio::test]
+async fn test_maintenance_magic_links_gc() -> Result<(), Box<dyn Error>> {
+    let db = Db::connect().await?;
+    let auth_headers = get_auth_headers().await?;
+    let backend_url = get_backend_url();
+    let http = reqwest::Client::new();
+    let now = Utc::now().timestamp();
+
+    let res = http
+        .post(format!("{backend_url}/users"))
+        .headers(auth_headers.clone())
+        .json(&NewUserRequest {
+            given_name: Some("Maintenance".to_string()),
+            family_name: None,
+            email: "maintenance@localhost".to_string(),
+            language: Language::En,
+            roles: vec!["user".to_string()],
+            groups: None,
+            user_expires: None,
+            tz: None,
+        })
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let user_id = res.json::<UserResponse>().await?.id;
+
+    // more than 2 full batches, which are past the grace period
+    let count_old = 2 * BATCH_SIZE + 5;
+    for i in 0..count_old {
+        db.insert_link(&link_id("gcOld", i), &user_id, now - GRACE_SECS - 60)
+            .await?;
+    }
+    // expired, but still inside the grace period
+    let id_grace = link_id("gcGrace", 0);
+    db.insert_link(&id_grace, &user_id, now - 60).await?;
+
+    // only opening an expired link counts as an attempt
+    assert_eq!(db.expired_attempt(&id_grace).await?, None);
+    let res = http
+        .get(format!("{backend_url}/users/{user_id}/reset/{id_grace}"))
+        .send()
+        .await?;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let attempt = db
+        .expired_attempt(&id_grace)
+        .await?
+        .expect("an expired attempt");
+    assert!(attempt >= now);
+
+    // the first attempt is never overwritten
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    let res = http
+        .get(format!("{backend_url}/users/{user_id}/reset/{id_grace}"))
+        .send()
+        .await?;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(db.expired_attempt(&id_grace).await?, Some(attempt));
+
+    let res = http
+        .post(format!("{backend_url}/dev/maintenance"))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let deleted = res.json::<Value>().await?["magic_links"]
+        .as_u64()
+        .expect("the deleted magic links");
+    assert!(deleted >= count_old as u64);
+
+    // all batches have been deleted, but nothing inside the grace period
+    assert_eq!(db.count_links(&user_id, "gcOld").await?, 0);
+    assert_eq!(db.count_links(&user_id, "gcGrace").await?, 1);
+
+    let res = http
+        .delete(format!("{backend_url}/users/{user_id}"))
+        .headers(auth_headers)
+        .send()
+        .await?;
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+
+    Ok(())
+}
@@ -0,0 +1,380 @@
+use crate::common::{
+    code_state_from_headers, cookie_csrf_headers_from_res_direct, get_auth_headers,
+    get_backend_url, get_issuer, get_solved_pow,
+};
+use chrono::Utc;
+use hiqlite::macros::params;
+use pretty_assertions::assert_eq;
+use rauthy_api_types::clients::NewClientRequest;
+use rauthy_api_types::generic::Language;
+use rauthy_api_types::oidc::LoginRequest;
+use rauthy_api_types::users::{NewUserRequest, UpdateUserRequest, UserResponse};
+use rauthy_common::sha256;
+use rauthy_common::utils::base64_url_encode;
+use reqwest::header::{COOKIE, HeaderMap, HeaderValue, LOCATION, SET_COOKIE};
+use reqwest::{Response, Url};
+use serde_json::{Value, json};
+use std::error::Error;
+
+mod common;
+
+const CLIENT_ID: &str = "upstream_callbacks";
+const EMAIL: &str = "upstream.callbacks@localhost.de";
+const PASSWORD: &str = "123SuperSafe123";
+const PKCE_VERIFIER: &str = "Zt4Kq8mWbN2xVf6RjPcL9sHd3YgT7uEa1oXiB5nMwQyJ0rUkSvCeGhDlFpAzO2Wq";
+
+/// Direct access to the database of the test backend to check and manipulate the persisted
+/// callbacks.
+enum Db {
+    Hiqlite(hiqlite::Client),
+    Postgres(tokio_postgres::Client),
+}
+
+impl Db {
+    async fn connect() -> Result<Self, Box<dyn Error>> {
+        if std::env::var("HIQLITE").as_deref() == Ok("false") {
+            let (client, conn) = tokio_postgres::connect(
+                "host=localhost user=rauthy password=123SuperSafe dbname=rauthy",
+                tokio_postgres::NoTls,
+            )
+            .await?;
+            tokio::spawn(conn);
+            Ok(Self::Postgres(client))
+        } else {
+            let client = hiqlite::Client::remote(
+                vec!["localhost:8200".to_string()],
+                false,
+                false,
+                "SuperSecureSecret1337".to_string(),
+                false,
+            )
+            .await?;
+            Ok(Self::Hiqlite(client))
+        }
+    }
+
+    async fn set_expires_at(&self, callback_id: &str, exp: i64) -> Result<(), Box<dyn Error>> {
+        let sql = "UPDATE auth_provider_callbacks SET expires_at = $1 WHERE callback_id = $2";
+        let rows = match self {
+            Self::Hiqlite(client) => client.execute(sql, params!(exp, callback_id)).await?,
+            Self::Postgres(client) => client.execute(sql, &[&exp, &callback_id]).await? as usize,
+        };
+        assert_eq!(rows, 1);
+        Ok(())
+    }
+
+    async fn exists(&self, callback_id: &str) -> Result<bool, Box<dyn Error>> {
+        let sql = "SELECT COUNT(*) AS count FROM auth_provider_callbacks WHERE callback_id = $1";
+        let count: i64 = match self {
+            Self::Hiqlite(client) => client
+                .query_raw_one(sql, params!(callback_id))
+                .await?
+                .get("count"),
+            Self::Postgres(client) => client.query_one(sql, &[&callback_id]).await?.get("count"),
+        };
+        Ok(count > 0)
+    }
+}
+
+/// A started login via the provider, which uses this Rauthy instance as its own upstream.
+struct Started {
+    /// The downstream session with the encrypted callback cookie
+    headers: HeaderMap,
+    location: Url,
+    xsrf_token: String,
+}
+
+impl Started {
+    fn param(&self, name: &str) -> Option<String> {
+        self.location
+            .query_pairs()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.to_string())
+    }
+
+    fn callback_id(&self) -> String {
+        self.param("state").expect("the state")
+    }
+}
+
+fn provider_req() -> Value {
+    let issuer = get_issuer();
+    json!({
+        "name": "Callback Test",
+        "typ": "oidc",
+        "enabled": true,
+        "issuer": issuer,
+        "authorization_endpoint": format!("{issuer}/oidc/authorize"),
+        "token_endpoint": format!("{issuer}/oidc/token"),
+        "userinfo_endpoint": format!("{issuer}/oidc/userinfo"),
+        "jwks_endpoint": format!("{issuer}/oidc/certs"),
+        "use_pkce": true,
+        "client_secret_basic": false,
+        "client_secret_post": false,
+        "auto_onboarding": false,
+        "auto_link": true,
+        "client_id": CLIENT_ID,
+        "scope": "openid email",
+    })
+}
+
+async fn login_start(http: &reqwest::Client, provider_id: &str) -> Result<Started, Box<dyn Error>> {
+    let backend_url = get_backend_url();
+    let pkce_challenge = base64_url_encode(sha256!(PKCE_VERIFIER.as_bytes()));
+
+    let res = http
+        .post(format!("{backend_url}/oidc/session"))
+        .send()
+        .await?;
+    let mut headers = cookie_csrf_headers_from_res_direct(res).await?;
+    let res = http
+        .post(format!("{backend_url}/providers/login"))
+        .headers(headers.clone())
+        .json(&json!({
+            "client_id": "init_client",
+            "redirect_uri": "http://localhost:3000/oidc/callback",
+            "code_challenge": pkce_challenge,
+            "code_challenge_method": "S256",
+            "pow": get_solved_pow().await,
+            "provider_id": provider_id,
+            "pkce_challenge": pkce_challenge,
+        }))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 202);
+    let callback_cookie = res
+        .headers()
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .filter_map(|v| v.split_once(';').map(|(c, _)| c.to_string()))
+        .find(|c| c.starts_with("__Host-UpstreamAuthCallback="))
+        .expect("the upstream callback cookie");
+    let location = Url::parse(res.headers().get(LOCATION).unwrap().to_str()?)?;
+    let xsrf_token = res.text().await?;
+
+    let session_cookie = headers.get(COOKIE).unwrap().to_str()?.to_string();
+    headers.insert(
+        COOKIE,
+        HeaderValue::from_str(&format!("{session_cookie}; {callback_cookie}"))?,
+    );
+
+    Ok(Started {
+        headers,
+        location,
+        xsrf_token,
+    })
+}
+
+/// The login at the upstream, which returns the `code` and `state` for the callback.
+/// The `nonce` ends up in the upstream ID token.
+async fn upstream_login(
+    http: &reqwest::Client,
+    started: &Started,
+    nonce: Option<String>,
+) -> Result<(String, String), Box<dyn Error>> {
+    let res = http
+        .post(format!("{}/oidc/session", get_backend_url()))
+        .send()
+        .await?;
+    let upstream_headers = cookie_csrf_headers_from_res_direct(res).await?;
+    let res = http
+        .post(started.location.as_str())
+        .headers(upstream_headers)
+        .json(&LoginRequest {
+            email: EMAIL.to_string(),
+            password: Some(PASSWORD.to_string()),
+            pow: get_solved_pow().await,
+            client_id: CLIENT_ID.to_string(),
+            redirect_uri: started.param("redirect_uri").expect("the redirect_uri"),
+            scopes: Some(vec!["openid".to_string(), "email".to_string()]),
+            state: started.param("state"),
+            nonce,
+            code_challenge: started.param("code_challenge"),
+            code_challenge_method: started.param("code_challenge_method"),
+            resource: None,
+        })
+        .send()
+        .await?;
+    assert_eq!(res.status(), 202);
+    let (code, state) = code_state_from_headers(res)?;
+
+    Ok((code, state.expect("the state")))
+}
+
+async fn callback(
+    http: &reqwest::Client,
+    started: &Started,
+    code: String,
+    state: String,
+) -> Result<Response, Box<dyn Error>> {
+    let res = http
+        .post(format!("{}/providers/callback", get_backend_url()))
+        .headers(started.headers.clone())
+        .json(&json!({
+            "state": state,
+            "code": code,
+            "xsrf_token": started.xsrf_token,
+            "pkce_verifier": PKCE_VERIFIER,
+        }))
+        .send()
+        .await?;
+    Ok(res)
+}
+
+/// Removes the callback from the cache only, like a restart or another HA node would.
+async fn cache_delete(http: &reqwest::Client, callback_id: String) -> Result<(), Box<dyn Error>> {
+    let res = http
+        .post(format!(
+            "{}/dev/provider_callback_cache_delete",
+            get_backend_url()
+        ))
+        .body(callback_id)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_provider_callback_db_fallback() -> Result<(), Box<dyn Error>> {
+    let db = Db::connect().await?;
+    let auth_headers = get_auth_headers().await?;
+    let backend_url = get_backend_url();
+    let http = reqwest::Client::new();
+
+    // the upstream client and user on this same instance
+    let res = http
+        .post(format!("{backend_url}/clients"))
+        .headers(auth_headers.clone())
+        .json(&NewClientRequest {
+            id: CLIENT_ID.to_string(),
+            secret: None,
+            name: Some("Upstream Callbacks".to_string()),
+            confidential: false,
+            redirect_uris: vec![format!("{backend_url}/providers/callback")],
+            post_logout_redirect_uris: None,
+            client_type: None,
+        })
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    let res = http
+        .post(format!("{backend_url}/users"))
+        .headers(auth_headers.clone())
+        .json(&NewUserRequest {
+            given_name: Some("Upstream".to_string()),
+            family_name: Some("Callbacks".to_string()),
+            email: EMAIL.to_string(),
+            language: Language::En,
+            roles: vec!["user".to_string()],
+            groups: None,
+            user_expires: None,
+            tz: None,
+        })
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let user = res.json::<UserResponse>().await?;
+    let res = http
+        .put(format!("{backend_url}/users/{}", user.id))
+        .headers(auth_headers.clone())
+        .json(&UpdateUserRequest {
+            email: user.email.clone(),
+            given_name: user.given_name.clone(),
+            family_name: user.family_name.clone(),
+            language: Some(Language::En),
+            password: Some(PASSWORD.to_string()),
+            roles: user.roles.clone(),
+            groups: None,
+            enabled: true,
+            email_verified: true,
+            user_expires: None,
+            user_values: None,
+        })
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    let res = http
+        .post(format!("{backend_url}/providers/create"))
+        .headers(auth_headers.clone())
+        .json(&provider_req())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let provider_id = res.json::<Value>().await?["id"]
+        .as_str()
+        .expect("a provider id")
+        .to_string();
+
+    // a login finishes with the callback from the DB after it is gone from the cache
+    let started = login_start(&http, &provider_id).await?;
+    assert!(db.exists(&started.callback_id()).await?);
+    cache_delete(&http, started.callback_id()).await?;
+    let (code, state) = upstream_login(&http, &started, started.param("nonce")).await?;
+    let res = callback(&http, &started, code, state).await?;
+    assert_eq!(res.status(), 202, "{}", res.text().await?);
+
+    // an expired callback is never used from the DB, even if the row still exists
+    let expired = login_start(&http, &provider_id).await?;
+    let pending = login_start(&http, &provider_id).await?;
+    db.set_expires_at(&expired.callback_id(), Utc::now().timestamp() - 1)
+        .await?;
+    cache_delete(&http, expired.callback_id()).await?;
+    let (code, state) = upstream_login(&http, &expired, expired.param("nonce")).await?;
+    let res = callback(&http, &expired, code, state).await?;
+    assert_eq!(res.status(), 404);
+
+    // the maintenance cleans up expired rows only
+    let res = http
+        .post(format!("{backend_url}/dev/maintenance"))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let deleted = res.json::<Value>().await?["auth_provider_callbacks"]
+        .as_u64()
+        .expect("the deleted callbacks");
+    assert!(deleted >= 1);
+    assert!(!db.exists(&expired.callback_id()).await?);
+    assert!(db.exists(&pending.callback_id()).await?);
+
+    // the upstream ID token must contain the nonce from the login start
+    for nonce in [None, Some("SomeOtherNonce".to_string())] {
+        let started = login_start(&http, &provider_id).await?;
+        let (code, state) = upstream_login(&http, &started, nonce).await?;
+        let res = callback(&http, &started, code.clone(), state.clone()).await?;
+        assert_eq!(res.status(), 401);
+        let err = res.json::<Value>().await?;
+        assert_eq!(err["message"], "Invalid nonce in the upstream ID token");
+
+        // the callback is gone and can't be retried
+        assert!(!db.exists(&started.callback_id()).await?);
+        let res = callback(&http, &started, code, state).await?;
+        assert_eq!(res.status(), 404);
+    }
+
+    // cleanup
+    let res = http
+        .delete(format!("{backend_url}/providers/{provider_id}?force=true"))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    // the callbacks are gone with their provider
+    assert!(!db.exists(&pending.callback_id()).await?);
+    for url in [
+        format!("{backend_url}/users/{}", user.id),
+        format!("{backend_url}/clients/{CLIENT_ID}"),
+    ] {
+        let res = http
+            .delete(url)
+            .headers(auth_headers.clone())
+            .send()
+            .await?;
+        assert!(res.status().is_success());
+    }
+
+    Ok(())
+}
@@ -82,7 +82,14 @@ async fn test_scopes() -> Result<(), Box<dyn Error>> {
         claims_at_root: false,
         allowed_resources: None,
         default_aud: None,
+        allowed_roles: None,
+        allowed_groups: None,
+        response_types: None,
+        claims_emission: None,
+        refresh_token_binding: None,
+        client_type: None,
         scim: None,
+        claims_webhook: None,
     };
     let res = client
         .put(&url_client)
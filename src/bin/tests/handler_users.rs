@@ -4,12 +4,17 @@ use crate::common::{
 use pretty_assertions::assert_eq;
 use rauthy_api_types::api_keys::{AccessGroup, AccessRights, ApiKeyAccess, ApiKeyRequest};
 use rauthy_api_types::generic::Language;
+use rauthy_api_types::oidc::UserinfoRequest;
 use rauthy_api_types::users::{
-    NewUserRequest, RequestResetRequest, UserResponse, UserResponseSimple, Userinfo,
+    EffectivePermissionSource, ErasureResult, NewUserRequest, NotificationCategory,
+    NotificationPrefRequest, NotificationPrefResponse, NotificationPrefsRequest, PasskeyExport,
+    PasskeysExport, RequestResetRequest, UserEffectivePermissionsResponse, UserFederationResponse,
+    UserRegistrationConfigRequest, UserRegistrationConfigResponse, UserReindexResponse,
+    UserResponse, UserResponseSimple, Userinfo,
 };
 use rauthy_common::utils::new_store_id;
 use reqwest::StatusCode;
-use reqwest::header::AUTHORIZATION;
+use reqwest::header::{AUTHORIZATION, WWW_AUTHENTICATE};
 use std::error::Error;
 
 mod common;
@@ -105,6 +110,28 @@ async fn test_users() -> Result<(), Box<dyn Error>> {
     let user_by_email = res.json::<UserResponse>().await?;
     assert_eq!(user_by_email.id, alfred.id);
 
+    // lookups and uniqueness checks use the normalized address
+    let url_email = format!("{}/users/email/Alfred@BatCave.IO", get_backend_url());
+    let res = reqwest::Client::new()
+        .get(&url_email)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let user_by_email = res.json::<UserResponse>().await?;
+    assert_eq!(user_by_email.id, alfred.id);
+
+    let res = reqwest::Client::new()
+        .post(&url)
+        .headers(auth_headers.clone())
+        .json(&NewUserRequest {
+            email: "ALFRED@batcave.io".to_string(),
+            ..new_user
+        })
+        .send()
+        .await?;
+    assert!(!res.status().is_success());
+
     // delete the user again
     let res = reqwest::Client::new()
         .delete(&url_id)
@@ -127,6 +154,356 @@ async fn test_users() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_user_registration_config_approve() -> Result<(), Box<dyn Error>> {
+    let auth_headers = get_auth_headers().await?;
+    let backend_url = get_backend_url();
+    let client = reqwest::Client::new();
+    let url_config = format!("{backend_url}/users/register/config");
+
+    let res = client.get(&url_config).send().await?;
+    assert_eq!(res.status(), 401);
+
+    // `rauthy_admin` and unknown values are rejected
+    for (roles, groups) in [
+        (vec!["rauthy_admin"], vec![]),
+        (vec!["iDoNotExist"], vec![]),
+        (vec!["user"], vec!["iDoNotExist"]),
+    ] {
+        let payload = UserRegistrationConfigRequest {
+            default_roles: roles.into_iter().map(String::from).collect(),
+            default_groups: groups.into_iter().map(String::from).collect(),
+        };
+        let res = client
+            .put(&url_config)
+            .headers(auth_headers.clone())
+            .json(&payload)
+            .send()
+            .await?;
+        assert_eq!(res.status(), 400);
+    }
+
+    let payload = UserRegistrationConfigRequest {
+        default_roles: vec!["user".to_string()],
+        default_groups: vec![],
+    };
+    let res = client
+        .put(&url_config)
+        .headers(auth_headers.clone())
+        .json(&payload)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    let res = client
+        .get(&url_config)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let config = res.json::<UserRegistrationConfigResponse>().await?;
+    assert_eq!(config.default_roles, vec!["user".to_string()]);
+    assert!(config.default_groups.is_empty());
+    assert!(!config.require_admin_approval);
+
+    // reset to the defaults to not influence other tests
+    let payload = UserRegistrationConfigRequest {
+        default_roles: vec![],
+        default_groups: vec![],
+    };
+    let res = client
+        .put(&url_config)
+        .headers(auth_headers.clone())
+        .json(&payload)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    // approval of a pending user
+    let new_user = NewUserRequest {
+        given_name: Some("Pending".to_string()),
+        family_name: None,
+        email: "pending@batcave.io".to_string(),
+        language: Language::En,
+        roles: vec!["user".to_string()],
+        groups: None,
+        user_expires: None,
+        tz: None,
+    };
+    let res = client
+        .post(format!("{backend_url}/users"))
+        .headers(auth_headers.clone())
+        .json(&new_user)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let user = res.json::<UserResponse>().await?;
+    assert!(user.enabled);
+
+    let url_approve = format!("{backend_url}/users/{}/approve", user.id);
+    let res = client.post(&url_approve).send().await?;
+    assert_eq!(res.status(), 401);
+
+    // enabled users cannot be approved
+    let res = client
+        .post(&url_approve)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 400);
+
+    let res = client
+        .put(format!("{backend_url}/users/{}", user.id))
+        .headers(auth_headers.clone())
+        .json(&serde_json::json!({
+            "email": user.email,
+            "given_name": "Pending",
+            "roles": ["user"],
+            "enabled": false,
+            "email_verified": false,
+        }))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    let res = client
+        .post(&url_approve)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let approved = res.json::<UserResponse>().await?;
+    assert!(approved.enabled);
+
+    let res = client
+        .delete(format!("{backend_url}/users/{}", user.id))
+        .headers(auth_headers)
+        .send()
+        .await?;
+    assert!(res.status().is_success());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_user_erasure() -> Result<(), Box<dyn Error>> {
+    let auth_headers = get_auth_headers().await?;
+    let backend_url = get_backend_url();
+
+    let new_user = NewUserRequest {
+        given_name: Some("Erasure".to_string()),
+        family_name: Some("Test".to_string()),
+        email: "erasure@batcave.io".to_string(),
+        language: Language::En,
+        roles: vec!["user".to_string()],
+        groups: None,
+        user_expires: None,
+        tz: None,
+    };
+    let res = reqwest::Client::new()
+        .post(format!("{backend_url}/users"))
+        .headers(auth_headers.clone())
+        .json(&new_user)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let user = res.json::<UserResponse>().await?;
+
+    let url_erase = format!("{backend_url}/users/{}?erasure=true", user.id);
+
+    // the confirmation header is mandatory
+    let res = reqwest::Client::new()
+        .delete(&url_erase)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 400);
+
+    // and must match the user id
+    let res = reqwest::Client::new()
+        .delete(&url_erase)
+        .headers(auth_headers.clone())
+        .header("x-rauthy-erasure-confirm", "ERASE_someOtherId")
+        .send()
+        .await?;
+    assert_eq!(res.status(), 400);
+
+    let res = reqwest::Client::new()
+        .delete(&url_erase)
+        .headers(auth_headers.clone())
+        .header("x-rauthy-erasure-confirm", format!("ERASE_{}", user.id))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let result = res.json::<ErasureResult>().await?;
+    assert_eq!(result.entities_deleted.get("users"), Some(&1));
+    assert!(result.entities_deleted.contains_key("sessions"));
+    assert!(result.entities_deleted.contains_key("events"));
+    // audit logs are deleted by default, so there is no pseudonym
+    assert!(result.pseudonym.is_none());
+
+    let res = reqwest::Client::new()
+        .get(format!("{backend_url}/users/{}", user.id))
+        .headers(auth_headers)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 404);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_user_notification_prefs() -> Result<(), Box<dyn Error>> {
+    let auth_headers = get_auth_headers().await?;
+    let backend_url = get_backend_url();
+    let client = reqwest::Client::new();
+
+    let new_user = NewUserRequest {
+        given_name: Some("Notification".to_string()),
+        family_name: Some("Prefs".to_string()),
+        email: "notification.prefs@batcave.io".to_string(),
+        language: Language::En,
+        roles: vec!["user".to_string()],
+        groups: None,
+        user_expires: None,
+        tz: None,
+    };
+    let res = client
+        .post(format!("{backend_url}/users"))
+        .headers(auth_headers.clone())
+        .json(&new_user)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let user = res.json::<UserResponse>().await?;
+    let url = format!("{backend_url}/users/{}/notification_prefs", user.id);
+
+    let res = client.get(&url).send().await?;
+    assert_eq!(res.status(), 401);
+
+    let res = client
+        .get(&url)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let prefs = res.json::<Vec<NotificationPrefResponse>>().await?;
+    assert_eq!(prefs.len(), NotificationCategory::ALL.len());
+    let login_location = prefs
+        .iter()
+        .find(|p| p.category == NotificationCategory::NewLoginLocation)
+        .unwrap();
+    assert!(login_location.enabled);
+    assert!(login_location.suppressible);
+    assert!(login_location.is_default);
+
+    let payload = NotificationPrefsRequest {
+        prefs: vec![NotificationPrefRequest {
+            category: NotificationCategory::NewLoginLocation,
+            enabled: false,
+        }],
+    };
+    let res = client
+        .put(&url)
+        .headers(auth_headers.clone())
+        .json(&payload)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let prefs = res.json::<Vec<NotificationPrefResponse>>().await?;
+    let login_location = prefs
+        .iter()
+        .find(|p| p.category == NotificationCategory::NewLoginLocation)
+        .unwrap();
+    assert!(!login_location.enabled);
+    assert!(!login_location.is_default);
+
+    // non-suppressible categories can never be disabled
+    let payload = NotificationPrefsRequest {
+        prefs: vec![NotificationPrefRequest {
+            category: NotificationCategory::PasswordReset,
+            enabled: false,
+        }],
+    };
+    let res = client
+        .put(&url)
+        .headers(auth_headers.clone())
+        .json(&payload)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 400);
+
+    let res = client
+        .delete(format!("{backend_url}/users/{}", user.id))
+        .headers(auth_headers)
+        .send()
+        .await?;
+    assert!(res.status().is_success());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_user_federation() -> Result<(), Box<dyn Error>> {
+    let auth_headers = get_auth_headers().await?;
+    let backend_url = get_backend_url();
+    let client = reqwest::Client::new();
+
+    let new_user = NewUserRequest {
+        given_name: Some("Federation".to_string()),
+        family_name: None,
+        email: "federation@batcave.io".to_string(),
+        language: Language::En,
+        roles: vec!["user".to_string()],
+        groups: None,
+        user_expires: None,
+        tz: None,
+    };
+    let res = client
+        .post(format!("{backend_url}/users"))
+        .headers(auth_headers.clone())
+        .json(&new_user)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let user = res.json::<UserResponse>().await?;
+    let url = format!("{backend_url}/users/{}/federation", user.id);
+
+    let res = client.get(&url).send().await?;
+    assert_eq!(res.status(), 401);
+
+    // a local user only returns empty link metadata
+    let res = client
+        .get(&url)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let federation = res.json::<UserFederationResponse>().await?;
+    assert_eq!(federation.user_id, user.id);
+    assert!(federation.auth_provider_id.is_none());
+    assert!(federation.federation_uid.is_none());
+    assert!(federation.claims_retained);
+    assert!(federation.claims.is_none());
+    assert!(federation.history.is_empty());
+
+    let res = client
+        .get(format!("{backend_url}/users/{}/federation", new_store_id()))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 404);
+
+    let res = client
+        .delete(format!("{backend_url}/users/{}", user.id))
+        .headers(auth_headers)
+        .send()
+        .await?;
+    assert!(res.status().is_success());
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_password_reset_always_ok() -> Result<(), Box<dyn Error>> {
     let auth_headers = get_auth_headers().await?;
@@ -169,19 +546,294 @@ async fn test_userinfo() -> Result<(), Box<dyn Error>> {
     // Unauthorized without a Bearer
     let res = client.get(&url).send().await?;
     assert_eq!(res.status(), 401);
+    assert_eq!(res.headers().get(WWW_AUTHENTICATE).unwrap(), "Bearer");
+    let res = client.post(&url).send().await?;
+    assert_eq!(res.status(), 401);
 
     // This should be good
     let ts = get_token_set().await;
+    let bearer = format!("Bearer {}", ts.access_token);
     let res = client
         .get(&url)
-        .header(AUTHORIZATION, format!("Bearer {}", ts.access_token))
+        .header(AUTHORIZATION, &bearer)
         .send()
         .await?;
     assert_eq!(res.status(), 200);
     let info = res.json::<Userinfo>().await?;
     assert_eq!(info.sub, "m4PJ3TnyP32LA8hzY23deme3");
     assert_eq!(info.name, "Admin Init");
-    assert!(info.roles.contains(&"rauthy_admin".to_string()));
+    assert!(
+        info.roles
+            .unwrap_or_default()
+            .contains(&"rauthy_admin".to_string())
+    );
+
+    // POST with the header
+    let res = client
+        .post(&url)
+        .header(AUTHORIZATION, &bearer)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.json::<Userinfo>().await?.sub, info.sub);
+
+    // POST with the token in the form body
+    let form = UserinfoRequest {
+        access_token: Some(ts.access_token.clone()),
+    };
+    let res = client.post(&url).form(&form).send().await?;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.json::<Userinfo>().await?.sub, info.sub);
+
+    // a token in more than one place must be rejected
+    let res = client
+        .post(&url)
+        .header(AUTHORIZATION, &bearer)
+        .form(&form)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 400);
+    let challenge = res.headers().get(WWW_AUTHENTICATE).unwrap().to_str()?;
+    assert!(challenge.starts_with(r#"Bearer error="invalid_request""#));
+
+    // tokens in the query string are never accepted
+    let res = client
+        .get(format!("{url}?access_token={}", ts.access_token))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 400);
+    let res = client
+        .get(format!("{url}?access_token={}", ts.access_token))
+        .header(AUTHORIZATION, &bearer)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 400);
+
+    // invalid tokens get the `invalid_token` challenge
+    let res = client
+        .post(&url)
+        .form(&UserinfoRequest {
+            access_token: Some("ey.invalid.token".to_string()),
+        })
+        .send()
+        .await?;
+    assert_eq!(res.status(), 401);
+    let challenge = res.headers().get(WWW_AUTHENTICATE).unwrap().to_str()?;
+    assert!(challenge.starts_with(r#"Bearer error="invalid_token""#));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_user_effective_permissions() -> Result<(), Box<dyn Error>> {
+    let auth_headers = get_auth_headers().await?;
+    let client = reqwest::Client::new();
+
+    let res = client
+        .get(format!("{}/users", get_backend_url()))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let user_id = res
+        .json::<Vec<UserResponseSimple>>()
+        .await?
+        .into_iter()
+        .find(|u| u.email == "admin@localhost")
+        .unwrap()
+        .id;
+
+    let url = format!(
+        "{}/users/{}/effective_permissions",
+        get_backend_url(),
+        user_id
+    );
+
+    // admin only
+    let res = client.get(&url).send().await?;
+    assert_eq!(res.status(), 401);
+
+    let res = client
+        .get(&url)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let perms = res.json::<UserEffectivePermissionsResponse>().await?;
+    assert_eq!(perms.user_id, user_id);
+    assert!(
+        perms
+            .roles
+            .iter()
+            .any(|r| r.name == "rauthy_admin" && r.source == EffectivePermissionSource::Direct)
+    );
+
+    // API keys with access to users are listed, others are not
+    let key_users = new_store_id();
+    let key_groups = new_store_id();
+    for (name, group) in [
+        (&key_users, AccessGroup::Users),
+        (&key_groups, AccessGroup::Groups),
+    ] {
+        let payload = ApiKeyRequest {
+            name: name.clone(),
+            exp: None,
+            access: vec![ApiKeyAccess {
+                group,
+                access_rights: vec![AccessRights::Read, AccessRights::Update],
+            }],
+        };
+        let res = client
+            .post(format!("{}/api_keys", get_backend_url()))
+            .headers(auth_headers.clone())
+            .json(&payload)
+            .send()
+            .await?;
+        assert_eq!(res.status(), 200);
+    }
+
+    let res = client
+        .get(&url)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let perms = res.json::<UserEffectivePermissionsResponse>().await?;
+    let key = perms.api_keys.iter().find(|k| k.name == key_users).unwrap();
+    assert_eq!(
+        key.access_rights,
+        vec![AccessRights::Read, AccessRights::Update]
+    );
+    assert_eq!(key.source, EffectivePermissionSource::ApiKey);
+    assert!(!perms.api_keys.iter().any(|k| k.name == key_groups));
+
+    for name in [key_users, key_groups] {
+        let res = client
+            .delete(format!("{}/api_keys/{}", get_backend_url(), name))
+            .headers(auth_headers.clone())
+            .send()
+            .await?;
+        assert_eq!(res.status(), 200);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_user_reindex() -> Result<(), Box<dyn Error>> {
+    let auth_headers = get_auth_headers().await?;
+    let client = reqwest::Client::new();
+
+    let res = client
+        .get(format!("{}/users", get_backend_url()))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let user = res
+        .json::<Vec<UserResponseSimple>>()
+        .await?
+        .into_iter()
+        .find(|u| u.email == "admin@localhost")
+        .unwrap();
+
+    let url = format!("{}/users/{}/reindex", get_backend_url(), user.id);
+
+    // admin only
+    let res = client.post(&url).send().await?;
+    assert_eq!(res.status(), 401);
+
+    let res = client
+        .post(&url)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let reindex = res.json::<UserReindexResponse>().await?;
+    // user by id + email, values and attributes at least
+    assert!(reindex.cache_entries_invalidated >= 4);
+
+    // the user must be fully functional afterward
+    let res = client
+        .get(format!("{}/users/{}", get_backend_url(), user.id))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let res = res.json::<UserResponse>().await?;
+    assert_eq!(res.email, user.email);
+
+    let url = format!("{}/users/{}/reindex", get_backend_url(), new_store_id());
+    let res = client.post(&url).headers(auth_headers).send().await?;
+    assert_eq!(res.status(), 404);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_user_webauthn_export_import() -> Result<(), Box<dyn Error>> {
+    let auth_headers = get_auth_headers().await?;
+    let client = reqwest::Client::new();
+
+    let res = client
+        .get(format!("{}/users", get_backend_url()))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let user = res
+        .json::<Vec<UserResponseSimple>>()
+        .await?
+        .into_iter()
+        .find(|u| u.email == "admin@localhost")
+        .unwrap();
+
+    let url = format!("{}/users/{}/webauthn/export", get_backend_url(), user.id);
+    let res = client.get(&url).send().await?;
+    assert_eq!(res.status(), 401);
+
+    let res = client
+        .get(&url)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let mut export = res.json::<PasskeysExport>().await?;
+    assert_eq!(export.version, 1);
+    assert!(!export.rp_id.is_empty());
+
+    // an export without any credentials is not a valid import
+    let url = format!("{}/users/{}/webauthn/import", get_backend_url(), user.id);
+    export.credentials = Vec::default();
+    let res = client
+        .post(&url)
+        .headers(auth_headers.clone())
+        .json(&export)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 400);
+
+    // the import is disabled by default
+    export.credentials.push(PasskeyExport {
+        name: "Imported".to_string(),
+        credential_id: "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA".to_string(),
+        public_key: serde_json::json!({}),
+        sign_count: 1,
+        transports: None,
+        aaguid: None,
+        user_verified: true,
+        backup_eligible: false,
+        backup_state: false,
+        registered: 0,
+        last_used: 0,
+    });
+    let res = client
+        .post(&url)
+        .headers(auth_headers)
+        .json(&export)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 403);
 
     Ok(())
 }
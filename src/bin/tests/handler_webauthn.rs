@@ -0,0 +1,367 @@
+use crate::common::{
+    check_status, cookie_csrf_headers_from_res_direct, get_auth_headers, get_backend_url,
+    get_solved_pow, session_cookie_from_res,
+};
+use hiqlite::macros::params;
+use pretty_assertions::assert_eq;
+use rauthy_api_types::generic::Language;
+use rauthy_api_types::oidc::LoginRequest;
+use rauthy_api_types::users::{
+    MfaPurpose, NewUserRequest, UserAccountTypeResponse, UserResponse, WebauthnAuthFinishRequest,
+    WebauthnAuthStartRequest, WebauthnRegFinishRequest, WebauthnRegStartRequest,
+};
+use rauthy_common::constants::PWD_CSRF_HEADER;
+use rauthy_common::sha256;
+use rauthy_common::utils::base64_url_encode;
+use rauthy_error::ErrorResponse;
+use reqwest::header::{COOKIE, HeaderMap, HeaderValue, SET_COOKIE};
+use reqwest::{StatusCode, Url};
+use serde::Deserialize;
+use std::error::Error;
+use webauthn_authenticator_rs::WebauthnAuthenticator;
+use webauthn_authenticator_rs::softpasskey::SoftPasskey;
+use webauthn_rs_proto::{
+    CreationChallengeResponse, RequestChallengeResponse, ResidentKeyRequirement,
+    UserVerificationPolicy,
+};
+
+mod common;
+
+/// Must match the `webauthn.rp_origin` from the test config
+const RP_ORIGIN: &str = "http://localhost:8081";
+const PASSKEY_NAME: &str = "Soft Passkey";
+const CHALLENGE_PLAIN: &str = "oDXug9zfYqfz8ejcqMpALRPXfW8QhbKV2AVuScAt8xrLKDAmaRYQ4yRi2uqcH9ys";
+
+// Re-defined here, because the `WebauthnAuthStartResponse` only derives `Serialize`.
+#[derive(Deserialize)]
+struct AuthStart {
+    code: String,
+    rcr: RequestChallengeResponse,
+}
+
+// Re-defined here, because the `WebauthnLoginResponse` only derives `Serialize`.
+#[derive(Deserialize)]
+struct LoginAwaitWebauthn {
+    code: String,
+    user_id: String,
+}
+
+/// Direct access to the database of the test backend, because magic links are only ever sent
+/// out via E-Mail.
+enum Db {
+    Hiqlite(hiqlite::Client),
+    Postgres(tokio_postgres::Client),
+}
+
+impl Db {
+    async fn connect() -> Result<Self, Box<dyn Error>> {
+        if std::env::var("HIQLITE").as_deref() == Ok("false") {
+            let (client, conn) = tokio_postgres::connect(
+                "host=localhost user=rauthy password=123SuperSafe dbname=rauthy",
+                tokio_postgres::NoTls,
+            )
+            .await?;
+            tokio::spawn(conn);
+            Ok(Self::Postgres(client))
+        } else {
+            let client = hiqlite::Client::remote(
+                vec!["localhost:8200".to_string()],
+                false,
+                false,
+                "SuperSecureSecret1337".to_string(),
+                false,
+            )
+            .await?;
+            Ok(Self::Hiqlite(client))
+        }
+    }
+
+    /// Returns the id and usage of the only unused magic link for this user.
+    async fn magic_link(&self, user_id: &str) -> Result<(String, String), Box<dyn Error>> {
+        let sql = "SELECT id, usage FROM magic_links WHERE user_id = $1 AND used = false";
+        let (id, usage) = match self {
+            Self::Hiqlite(client) => {
+                let mut row = client.query_raw_one(sql, params!(user_id)).await?;
+                (row.get("id"), row.get("usage"))
+            }
+            Self::Postgres(client) => {
+                let row = client.query_one(sql, &[&user_id]).await?;
+                (row.get("id"), row.get("usage"))
+            }
+        };
+        Ok((id, usage))
+    }
+}
+
+/// Opens the magic link like a browser would and registers a new passkey with it.
+async fn register_passkey(
+    client: &reqwest::Client,
+    authenticator: &mut WebauthnAuthenticator<SoftPasskey>,
+    user_id: &str,
+    magic_link_id: &str,
+) -> Result<(), Box<dyn Error>> {
+    let backend_url = get_backend_url();
+
+    let res = client
+        .get(format!(
+            "{backend_url}/users/{user_id}/reset/{magic_link_id}"
+        ))
+        .send()
+        .await?;
+    let res = check_status(res, 200).await?;
+    let set_cookie = res.headers().get(SET_COOKIE).unwrap();
+    let (cookie, _) = set_cookie.to_str()?.split_once(';').unwrap();
+    let mut headers = HeaderMap::new();
+    headers.append(COOKIE, HeaderValue::from_str(cookie)?);
+    let html = res.text().await?;
+    let (_, content_split) = html.split_once("\"csrf_token\":\"").unwrap();
+    let (csrf_token, _) = content_split.split_once('"').unwrap();
+    headers.append(PWD_CSRF_HEADER, HeaderValue::from_str(csrf_token)?);
+
+    let res = client
+        .post(format!(
+            "{backend_url}/users/{user_id}/webauthn/register/start"
+        ))
+        .headers(headers.clone())
+        .json(&WebauthnRegStartRequest {
+            passkey_name: PASSKEY_NAME.to_string(),
+            magic_link_id: Some(magic_link_id.to_string()),
+            mfa_mod_token_id: None,
+        })
+        .send()
+        .await?;
+    let res = check_status(res, 200).await?;
+    let mut ccr = res.json::<CreationChallengeResponse>().await?;
+
+    // an account without a password must always get a discoverable key with UV
+    let auth_sel = ccr.public_key.authenticator_selection.as_mut().unwrap();
+    assert_eq!(
+        auth_sel.resident_key,
+        Some(ResidentKeyRequirement::Required)
+    );
+    assert!(auth_sel.require_resident_key);
+    assert_eq!(auth_sel.user_verification, UserVerificationPolicy::Required);
+    // The soft token can't store resident keys. This does not matter for the ceremony itself,
+    // because the server can't verify it anyway.
+    auth_sel.resident_key = Some(ResidentKeyRequirement::Discouraged);
+    auth_sel.require_resident_key = false;
+
+    let data = authenticator
+        .do_registration(Url::parse(RP_ORIGIN)?, ccr)
+        .unwrap();
+    let res = client
+        .post(format!(
+            "{backend_url}/users/{user_id}/webauthn/register/finish"
+        ))
+        .headers(headers)
+        .json(&WebauthnRegFinishRequest {
+            passkey_name: PASSKEY_NAME.to_string(),
+            data,
+            magic_link_id: Some(magic_link_id.to_string()),
+        })
+        .send()
+        .await?;
+    check_status(res, 201).await?;
+
+    Ok(())
+}
+
+/// Returns the response of the `/oidc/authorize` POST without any password.
+async fn login_start(
+    client: &reqwest::Client,
+    headers: &HeaderMap,
+    email: &str,
+) -> Result<reqwest::Response, Box<dyn Error>> {
+    let redirect_uri = format!("{}/oidc/callback", get_backend_url());
+    let challenge_s256 = base64_url_encode(sha256!(CHALLENGE_PLAIN.as_bytes()));
+    let res = client
+        .post(format!(
+            "{}/oidc/authorize?client_id=rauthy&redirect_uri={redirect_uri}&response_type=code\
+            &code_challenge={challenge_s256}&code_challenge_method=S256",
+            get_backend_url()
+        ))
+        .headers(headers.clone())
+        .json(&LoginRequest {
+            email: email.to_string(),
+            password: None,
+            pow: get_solved_pow().await,
+            client_id: "rauthy".to_string(),
+            redirect_uri,
+            scopes: None,
+            state: None,
+            nonce: None,
+            code_challenge: Some(challenge_s256),
+            code_challenge_method: Some("S256".to_string()),
+            resource: None,
+        })
+        .send()
+        .await?;
+    Ok(res)
+}
+
+/// Does a full passkey-only login and returns the authenticated session headers.
+async fn login_passkey(
+    client: &reqwest::Client,
+    authenticator: &mut WebauthnAuthenticator<SoftPasskey>,
+    email: &str,
+) -> Result<HeaderMap, Box<dyn Error>> {
+    let backend_url = get_backend_url();
+
+    let res = client
+        .post(format!("{backend_url}/oidc/session"))
+        .send()
+        .await?;
+    let mut headers = cookie_csrf_headers_from_res_direct(res).await?;
+
+    let res = login_start(client, &headers, email).await?;
+    let res = check_status(res, 200).await?;
+    if let Some(cookie) = session_cookie_from_res(&res) {
+        headers.insert(COOKIE, HeaderValue::from_str(&cookie)?);
+    }
+    let login = res.json::<LoginAwaitWebauthn>().await?;
+
+    let res = client
+        .post(format!(
+            "{backend_url}/users/{}/webauthn/auth/start",
+            login.user_id
+        ))
+        .headers(headers.clone())
+        .json(&WebauthnAuthStartRequest {
+            purpose: MfaPurpose::Login(login.code),
+        })
+        .send()
+        .await?;
+    let res = check_status(res, 200).await?;
+    let start = res.json::<AuthStart>().await?;
+
+    let data = authenticator
+        .do_authentication(Url::parse(RP_ORIGIN)?, start.rcr)
+        .unwrap();
+    let res = client
+        .post(format!(
+            "{backend_url}/users/{}/webauthn/auth/finish",
+            login.user_id
+        ))
+        .headers(headers.clone())
+        .json(&WebauthnAuthFinishRequest {
+            code: start.code,
+            data,
+        })
+        .send()
+        .await?;
+    let res = check_status(res, 202).await?;
+    let cookie = session_cookie_from_res(&res).expect("rotated session cookie to exist");
+    headers.insert(COOKIE, HeaderValue::from_str(&cookie)?);
+
+    Ok(headers)
+}
+
+async fn account_type(
+    client: &reqwest::Client,
+    auth_headers: &HeaderMap,
+    user_id: &str,
+) -> Result<UserAccountTypeResponse, Box<dyn Error>> {
+    let res = client
+        .get(format!("{}/users/{user_id}", get_backend_url()))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    let res = check_status(res, 200).await?;
+    Ok(res.json::<UserResponse>().await?.account_type)
+}
+
+#[tokio::test]
+async fn test_passkey_only_register_login_recovery() -> Result<(), Box<dyn Error>> {
+    let db = Db::connect().await?;
+    let client = reqwest::Client::new();
+    let auth_headers = get_auth_headers().await?;
+    let backend_url = get_backend_url();
+    let email = "passkey-only@localhost";
+
+    let res = client
+        .post(format!("{backend_url}/users"))
+        .headers(auth_headers.clone())
+        .json(&NewUserRequest {
+            email: email.to_string(),
+            family_name: None,
+            given_name: Some("Passkey".to_string()),
+            language: Language::En,
+            groups: None,
+            roles: vec!["user".to_string()],
+            user_expires: None,
+            tz: None,
+        })
+        .send()
+        .await?;
+    let res = check_status(res, 200).await?;
+    let user_id = res.json::<UserResponse>().await?.id;
+
+    // registration with the magic link for the new account
+    let (ml_id, usage) = db.magic_link(&user_id).await?;
+    assert_eq!(usage, "new_user");
+    let mut authenticator = WebauthnAuthenticator::new(SoftPasskey::new(true));
+    register_passkey(&client, &mut authenticator, &user_id, &ml_id).await?;
+    assert!(matches!(
+        account_type(&client, &auth_headers, &user_id).await?,
+        UserAccountTypeResponse::Passkey
+    ));
+
+    // login without any password
+    let session_headers = login_passkey(&client, &mut authenticator, email).await?;
+    let url_session_info = format!("{backend_url}/oidc/sessioninfo");
+    let res = client
+        .get(&url_session_info)
+        .headers(session_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // deleting the last passkey must not lock the account out
+    let res = client
+        .delete(format!(
+            "{backend_url}/users/{user_id}/webauthn/delete/{PASSKEY_NAME}"
+        ))
+        .headers(auth_headers.clone())
+        .json(&serde_json::json!({}))
+        .send()
+        .await?;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(matches!(
+        account_type(&client, &auth_headers, &user_id).await?,
+        UserAccountTypeResponse::New
+    ));
+
+    // all existing sessions are gone and a passkey login is not possible anymore
+    let res = client
+        .get(&url_session_info)
+        .headers(session_headers)
+        .send()
+        .await?;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    let res = client
+        .post(format!("{backend_url}/oidc/session"))
+        .send()
+        .await?;
+    let headers = cookie_csrf_headers_from_res_direct(res).await?;
+    let res = login_start(&client, &headers, email).await?;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    let err = res.json::<ErrorResponse>().await?;
+    assert_eq!(err.message, "User needs to provide a password");
+
+    // recovery with the fresh link, which replaced the one used during the registration
+    let (recovery_id, usage) = db.magic_link(&user_id).await?;
+    assert_ne!(recovery_id, ml_id);
+    assert_eq!(usage, "new_user");
+    let mut authenticator = WebauthnAuthenticator::new(SoftPasskey::new(true));
+    register_passkey(&client, &mut authenticator, &user_id, &recovery_id).await?;
+    login_passkey(&client, &mut authenticator, email).await?;
+
+    let res = client
+        .delete(format!("{backend_url}/users/{user_id}"))
+        .headers(auth_headers)
+        .send()
+        .await?;
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+
+    Ok(())
+}
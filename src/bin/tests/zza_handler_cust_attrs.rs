@@ -1,14 +1,15 @@
 use crate::common::{get_auth_headers, get_backend_url, get_token_set};
+use rauthy_api_types::claims::JwtAccessClaims;
 use rauthy_api_types::clients::{ClientResponse, UpdateClientRequest};
 use rauthy_api_types::oidc::JwkKeyPairAlg;
 use rauthy_api_types::scopes::{ScopeRequest, ScopeResponse};
 use rauthy_api_types::users::{
-    UserAttrConfigRequest, UserAttrConfigResponse, UserAttrValueRequest, UserAttrValuesResponse,
-    UserAttrValuesUpdateRequest,
+    UserAttrConfigRequest, UserAttrConfigResponse, UserAttrDistinctValueResponse,
+    UserAttrUsageResponse, UserAttrValueRequest, UserAttrValuesResponse,
+    UserAttrValuesUpdateRequest, UserResponseSimple,
 };
 use rauthy_common::utils::base64_url_no_pad_decode;
 use rauthy_data::entity::user_attr::UserAttrConfigEntity;
-use rauthy_jwt::claims::JwtAccessClaims;
 use serde_json::Value;
 use std::error::Error;
 
@@ -37,6 +38,7 @@ async fn test_cust_attrs() -> Result<(), Box<dyn Error>> {
         default_value: None,
         typ: None,
         user_editable: None,
+        auto_populate_from_claim: None,
     };
     let res = client
         .post(&url_attrs)
@@ -135,7 +137,14 @@ async fn test_cust_attrs() -> Result<(), Box<dyn Error>> {
         claims_at_root: false,
         allowed_resources: None,
         default_aud: None,
+        allowed_roles: None,
+        allowed_groups: None,
+        response_types: None,
+        claims_emission: None,
+        refresh_token_binding: None,
+        client_type: None,
         scim: None,
+        claims_webhook: None,
     };
     let res = client
         .put(&url_client)
@@ -171,6 +180,8 @@ async fn test_cust_attrs() -> Result<(), Box<dyn Error>> {
     let user_attr = resp.values.get(0).unwrap();
     assert_eq!(user_attr.key, cust_attr.name);
     assert_eq!(user_attr.value, test_val);
+    // manually set values are never marked as synced
+    assert_eq!(user_attr.last_synced_from_claim_at, None);
 
     // fetch a new token and check the scope mapping
     let token = get_token_set().await;
@@ -248,6 +259,7 @@ async fn test_cust_attrs() -> Result<(), Box<dyn Error>> {
         default_value: None,
         typ: None,
         user_editable: None,
+        auto_populate_from_claim: Some("department".to_string()),
     };
     let url_attr_mod = format!("{}/users/attr/{}", backend_url, cust_attr.name);
     let res = client
@@ -261,6 +273,10 @@ async fn test_cust_attrs() -> Result<(), Box<dyn Error>> {
     let attr = res.json::<UserAttrConfigEntity>().await?;
     assert_eq!(attr.name, cust_attr_mod.name);
     assert_eq!(attr.desc, cust_attr_mod.desc);
+    assert_eq!(
+        attr.auto_populate_from_claim,
+        cust_attr_mod.auto_populate_from_claim
+    );
 
     // make sure the new name has been changed in the scope mapping
     let res = client
@@ -310,14 +326,85 @@ async fn test_cust_attrs() -> Result<(), Box<dyn Error>> {
         &Value::String("Some String".to_string())
     );
 
+    // usage lookups
+    let url_values = format!("{}/users/attr/cust2/values", backend_url);
+    let res = client
+        .get(&url_values)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers().get("x-value-count").unwrap(), "1");
+    let values = res.json::<Vec<UserAttrUsageResponse>>().await?;
+    assert_eq!(values.len(), 1);
+    assert_eq!(values[0].user_id, "m4PJ3TnyP32LA8hzY23deme3");
+    assert_eq!(values[0].value, Value::String("Some String".to_string()));
+
+    let res = client
+        .get(format!("{url_values}?value=Some%20String"))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.json::<Vec<UserAttrUsageResponse>>().await?.len(), 1);
+    let res = client
+        .get(format!("{url_values}?value=Other"))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers().get("x-value-count").unwrap(), "0");
+    assert!(res.json::<Vec<UserAttrUsageResponse>>().await?.is_empty());
+
+    let res = client
+        .get(format!("{url_values}?distinct=true"))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let values = res.json::<Vec<UserAttrDistinctValueResponse>>().await?;
+    assert_eq!(values.len(), 1);
+    assert_eq!(values[0].value, Value::String("Some String".to_string()));
+    assert_eq!(values[0].count, 1);
+
+    let res = client
+        .get(format!("{}/users/attr/doesNotExist/values", backend_url))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 404);
+
+    // reverse lookup
+    let res = client
+        .get(format!("{}/users?attr=cust2:Some%20String", backend_url))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let users = res.json::<Vec<UserResponseSimple>>().await?;
+    assert_eq!(users.len(), 1);
+    assert_eq!(users[0].id, "m4PJ3TnyP32LA8hzY23deme3");
+    let res = client
+        .get(format!("{}/users?attr=cust2", backend_url))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 400);
+
     // delete the custom attr and make sure its properly cleaned up everywhere
-    // add a new attr
+    // existing values must be confirmed to be deleted as well
     let url_attr_del = format!("{}/users/attr/cust2", backend_url);
     let res = client
         .delete(&url_attr_del)
         .headers(auth_headers.clone())
         .send()
         .await?;
+    assert_eq!(res.status(), 400);
+    let res = client
+        .delete(format!("{url_attr_del}?confirm=true"))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
     assert_eq!(res.status(), 200);
 
     // scope mapping should have been cleaned up
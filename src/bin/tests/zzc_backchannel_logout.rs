@@ -39,6 +39,7 @@ async fn test_backchannel_logout() -> Result<(), Box<dyn Error>> {
             post_logout_redirect_uri: None,
             state: None,
             logout_token: None,
+            upstream_logout: None,
         })
         .send()
         .await?;
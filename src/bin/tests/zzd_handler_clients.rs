@@ -1,14 +1,13 @@
 use crate::common::{CLIENT_ID, CLIENT_SECRET, get_auth_headers, get_backend_url};
 use pretty_assertions::{assert_eq, assert_ne};
+use rauthy_api_types::claims::JwtAccessClaims;
 use rauthy_api_types::clients::{
-    ClientResponse, ClientSecretRequest, ClientSecretResponse, NewClientRequest,
-    UpdateClientRequest,
+    ClientJwksRequest, ClientJwksResponse, ClientResponse, ClientSecretRequest,
+    ClientSecretResponse, NewClientRequest, UpdateClientRequest,
 };
-use rauthy_api_types::oidc::{JwkKeyPairAlg, TokenRequest};
+use rauthy_api_types::oidc::{JwkKeyPairAlg, TokenRequest, TokenSet};
 use rauthy_common::constants::APPLICATION_JSON;
 use rauthy_common::utils::base64_url_no_pad_decode;
-use rauthy_jwt::claims::JwtAccessClaims;
-use rauthy_service::token_set::TokenSet;
 use reqwest::header::CONTENT_TYPE;
 use serde_json::{Value, json};
 use std::error::Error;
@@ -68,7 +67,14 @@ async fn put_client_claims(
         claims_at_root,
         allowed_resources: None,
         default_aud: None,
+        allowed_roles: None,
+        allowed_groups: None,
+        response_types: None,
+        claims_emission: None,
+        refresh_token_binding: None,
+        client_type: None,
         scim: None,
+        claims_webhook: None,
     };
 
     let res = client
@@ -125,6 +131,7 @@ async fn test_clients() -> Result<(), Box<dyn Error>> {
         confidential: true,
         redirect_uris: vec!["http://test.client.io/callback".to_string()],
         post_logout_redirect_uris: Some(vec!["http://test.client.io/logout".to_string()]),
+        client_type: None,
     };
     let res = reqwest::Client::new()
         .post(&url)
@@ -158,6 +165,7 @@ async fn test_clients() -> Result<(), Box<dyn Error>> {
         confidential: true,
         redirect_uris: vec!["http://test.client.io/callback".to_string()],
         post_logout_redirect_uris: None,
+        client_type: None,
     };
     let res = reqwest::Client::new()
         .post(&url)
@@ -211,7 +219,14 @@ async fn test_clients() -> Result<(), Box<dyn Error>> {
         claims_at_root: false,
         allowed_resources: None,
         default_aud: None,
+        allowed_roles: None,
+        allowed_groups: None,
+        response_types: None,
+        claims_emission: None,
+        refresh_token_binding: None,
+        client_type: None,
         scim: None,
+        claims_webhook: None,
     };
 
     let url_id = format!("{}/clients/{}", backend_url, client.id);
@@ -277,6 +292,110 @@ async fn test_clients() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_client_jwks() -> Result<(), Box<dyn Error>> {
+    let auth_headers = get_auth_headers().await?;
+    let backend_url = get_backend_url();
+    let client = reqwest::Client::new();
+    let url = format!("{backend_url}/clients/{CLIENT_ID}/jwks");
+
+    let res = client.get(&url).send().await?;
+    assert_eq!(res.status(), 401);
+
+    let res = client
+        .get(&url)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 404);
+
+    // our own JWKS is a perfectly fine inline JWKS
+    let certs = reqwest::get(format!("{backend_url}/oidc/certs"))
+        .await?
+        .json::<Value>()
+        .await?;
+    let res = client
+        .put(&url)
+        .headers(auth_headers.clone())
+        .json(&ClientJwksRequest {
+            jwks: Some(certs.clone()),
+            jwks_uri: None,
+        })
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let resp = res.json::<ClientJwksResponse>().await?;
+    assert_eq!(resp.client_id, CLIENT_ID);
+    assert_eq!(resp.keys.len(), certs["keys"].as_array().unwrap().len());
+
+    let res = client
+        .get(&url)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let resp = res.json::<ClientJwksResponse>().await?;
+    assert_eq!(resp.jwks, Some(certs.clone()));
+    assert!(resp.jwks_uri.is_none());
+
+    // exactly one of both must be given
+    let res = client
+        .put(&url)
+        .headers(auth_headers.clone())
+        .json(&ClientJwksRequest {
+            jwks: Some(certs),
+            jwks_uri: Some("https://client.example.com/jwks".to_string()),
+        })
+        .send()
+        .await?;
+    assert_eq!(res.status(), 400);
+
+    // a JWKS without any usable keys must be rejected
+    let res = client
+        .put(&url)
+        .headers(auth_headers.clone())
+        .json(&ClientJwksRequest {
+            jwks: Some(json!({ "keys": [] })),
+            jwks_uri: None,
+        })
+        .send()
+        .await?;
+    assert_eq!(res.status(), 400);
+
+    // an unreachable URI can be saved, but the refresh must fail
+    let res = client
+        .put(&url)
+        .headers(auth_headers.clone())
+        .json(&ClientJwksRequest {
+            jwks: None,
+            jwks_uri: Some("https://127.0.0.1:1/jwks".to_string()),
+        })
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let resp = res.json::<ClientJwksResponse>().await?;
+    assert!(resp.keys.is_empty());
+
+    let res = client
+        .post(format!("{url}/refresh"))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert!(!res.status().is_success());
+
+    let res = client
+        .delete(&url)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    let res = client.get(&url).headers(auth_headers).send().await?;
+    assert_eq!(res.status(), 404);
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_client_secret() -> Result<(), Box<dyn Error>> {
     let auth_headers = get_auth_headers().await?;
@@ -415,6 +534,7 @@ async fn test_client_credentials_custom_claims() -> Result<(), Box<dyn Error>> {
         confidential: true,
         redirect_uris: vec!["http://claims.client.io/callback".to_string()],
         post_logout_redirect_uris: None,
+        client_type: None,
     };
     let res = client
         .post(&url)
@@ -3,9 +3,8 @@ use pretty_assertions::assert_eq;
 use rauthy_api_types::clients::{
     ClientResponse, ClientSecretResponse, NewClientRequest, UpdateClientRequest,
 };
-use rauthy_api_types::oidc::{JwkKeyPairAlg, TokenRequest};
+use rauthy_api_types::oidc::{JwkKeyPairAlg, TokenRequest, TokenSet};
 use rauthy_common::utils::base64_url_no_pad_decode;
-use rauthy_service::token_set::TokenSet;
 use std::error::Error;
 
 mod common;
@@ -52,7 +51,14 @@ fn base_update() -> UpdateClientRequest {
         claims_at_root: false,
         allowed_resources: None,
         default_aud: None,
+        allowed_roles: None,
+        allowed_groups: None,
+        response_types: None,
+        claims_emission: None,
+        refresh_token_binding: None,
+        client_type: None,
         scim: None,
+        claims_webhook: None,
     }
 }
 
@@ -73,6 +79,7 @@ async fn test_resource_indicators() -> Result<(), Box<dyn Error>> {
         confidential: true,
         redirect_uris: vec!["http://localhost/callback".to_string()],
         post_logout_redirect_uris: None,
+        client_type: None,
     };
     let res = http
         .post(format!("{backend_url}/clients"))
@@ -0,0 +1,269 @@
+use crate::common::{PASSWORD, USERNAME, get_auth_headers, get_backend_url, update_req};
+use pretty_assertions::assert_eq;
+use rauthy_api_types::clients::{
+    ClaimGroupsFormat, ClaimTarget, ClientClaimsEmission, ClientResponse, ClientSecretResponse,
+    NewClientRequest, UpdateClientRequest,
+};
+use rauthy_api_types::oidc::{
+    ClaimSource, ClaimTrace, TokenPreviewRequest, TokenPreviewResponse, TokenRequest, TokenSet,
+};
+use rauthy_common::utils::base64_url_no_pad_decode;
+use reqwest::header::AUTHORIZATION;
+use serde_json::{Value, json};
+use std::error::Error;
+
+mod common;
+
+const ID: &str = "claims_emission_test";
+
+fn client_req() -> UpdateClientRequest {
+    UpdateClientRequest {
+        scopes: vec!["openid".to_string(), "groups".to_string()],
+        default_scopes: vec!["openid".to_string(), "groups".to_string()],
+        ..update_req("Claims Emission Test", &["password"])
+    }
+}
+
+fn decode_claims(token: &str) -> Value {
+    let payload_b64 = token.split('.').nth(1).expect("a JWT payload segment");
+    let bytes = base64_url_no_pad_decode(payload_b64).expect("valid base64url payload");
+    serde_json::from_slice(&bytes).expect("valid JSON claims")
+}
+
+/// Updates the test client and returns the (access token, id token, userinfo) claims of a
+/// freshly issued token set.
+async fn emitted_claims(
+    secret: &str,
+    claims_emission: Option<ClientClaimsEmission>,
+) -> Result<(Value, Value, Value), Box<dyn Error>> {
+    let auth_headers = get_auth_headers().await?;
+    let backend_url = get_backend_url();
+    let http = reqwest::Client::new();
+
+    let res = http
+        .put(format!("{backend_url}/clients/{ID}"))
+        .headers(auth_headers)
+        .json(&UpdateClientRequest {
+            claims_emission: claims_emission.clone(),
+            ..client_req()
+        })
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let client = res.json::<ClientResponse>().await?;
+    assert_eq!(client.claims_emission, claims_emission);
+
+    let token_req = TokenRequest {
+        grant_type: "password".to_string(),
+        code: None,
+        redirect_uri: None,
+        client_id: Some(ID.to_string()),
+        client_secret: Some(secret.to_string()),
+        code_verifier: None,
+        device_code: None,
+        username: Some(USERNAME.to_string()),
+        password: Some(PASSWORD.to_string()),
+        refresh_token: None,
+        resource: None,
+    };
+    let res = http
+        .post(format!("{backend_url}/oidc/token"))
+        .form(&token_req)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let ts = res.json::<TokenSet>().await?;
+
+    let res = http
+        .get(format!("{backend_url}/oidc/userinfo"))
+        .header(AUTHORIZATION, format!("Bearer {}", ts.access_token))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let userinfo = res.json::<Value>().await?;
+
+    Ok((
+        decode_claims(&ts.access_token),
+        decode_claims(&ts.id_token.expect("an id token")),
+        userinfo,
+    ))
+}
+
+fn contains(claims: &Value, claim: &str, value: &str) -> bool {
+    claims
+        .get(claim)
+        .and_then(|v| v.as_array())
+        .map(|arr| arr.iter().any(|v| v.as_str() == Some(value)))
+        .unwrap_or(false)
+}
+
+#[tokio::test]
+async fn test_claims_emission() -> Result<(), Box<dyn Error>> {
+    let auth_headers = get_auth_headers().await?;
+    let backend_url = get_backend_url();
+    let http = reqwest::Client::new();
+
+    let new_client = NewClientRequest {
+        id: ID.to_string(),
+        secret: None,
+        name: Some("Claims Emission Test".to_string()),
+        confidential: true,
+        redirect_uris: vec!["http://localhost/callback".to_string()],
+        post_logout_redirect_uris: None,
+        client_type: None,
+    };
+    let res = http
+        .post(format!("{backend_url}/clients"))
+        .headers(auth_headers.clone())
+        .json(&new_client)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    let res = http
+        .post(format!("{backend_url}/clients/{ID}/secret"))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let secret = res
+        .json::<ClientSecretResponse>()
+        .await?
+        .secret
+        .expect("a confidential client secret");
+
+    // defaults -> unchanged behavior everywhere
+    let (access, id, userinfo) = emitted_claims(&secret, None).await?;
+    for claims in [&access, &id, &userinfo] {
+        assert!(contains(claims, "roles", "rauthy_admin"));
+        assert!(contains(claims, "groups", "admin"));
+        assert!(claims.get("groups_overflow").is_none());
+    }
+
+    // custom names, path format, roles merged into groups, split targets
+    let emission = ClientClaimsEmission {
+        roles_claim: Some("app_roles".to_string()),
+        groups_claim: Some("https://example.com/groups".to_string()),
+        groups_format: ClaimGroupsFormat::Path,
+        roles_in_groups: true,
+        roles_targets: vec![ClaimTarget::AccessToken],
+        groups_targets: vec![ClaimTarget::IdToken, ClaimTarget::Userinfo],
+        groups_max: None,
+        groups_overflow: false,
+    };
+    let (access, id, userinfo) = emitted_claims(&secret, Some(emission)).await?;
+    for claims in [&access, &id, &userinfo] {
+        assert!(claims.get("roles").is_none());
+        assert!(claims.get("groups").is_none());
+    }
+    assert!(contains(&access, "app_roles", "rauthy_admin"));
+    assert!(access.get("https://example.com/groups").is_none());
+    for claims in [&id, &userinfo] {
+        assert!(claims.get("app_roles").is_none());
+        assert!(contains(claims, "https://example.com/groups", "/admin"));
+        assert!(contains(
+            claims,
+            "https://example.com/groups",
+            "rauthy_admin"
+        ));
+    }
+
+    // the preview must match the claims of real tokens without signing one
+    let preview_req = TokenPreviewRequest {
+        user_id: access["sub"].as_str().expect("a user sub").to_string(),
+        client_id: ID.to_string(),
+        scopes: None,
+    };
+    let res = http
+        .post(format!("{backend_url}/debug/token_preview"))
+        .headers(auth_headers.clone())
+        .json(&preview_req)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let preview = res.json::<TokenPreviewResponse>().await?;
+    assert!(!preview.signed);
+    assert!(preview.warnings.is_empty());
+    assert!(preview.id_token.get("at_hash").is_none());
+    assert!(preview.access_token.get("jti").is_none());
+    assert_eq!(
+        preview.access_token.get("app_roles"),
+        access.get("app_roles")
+    );
+    assert_eq!(
+        preview.id_token.get("https://example.com/groups"),
+        id.get("https://example.com/groups")
+    );
+    assert_eq!(
+        preview.userinfo.get("https://example.com/groups"),
+        userinfo.get("https://example.com/groups")
+    );
+    assert!(preview.trace.contains(&ClaimTrace {
+        target: ClaimTarget::AccessToken,
+        claim: "app_roles".to_string(),
+        source: ClaimSource::Roles,
+    }));
+    assert!(preview.trace.contains(&ClaimTrace {
+        target: ClaimTarget::IdToken,
+        claim: "sub".to_string(),
+        source: ClaimSource::Protocol,
+    }));
+
+    // size guard with overflow behavior
+    let emission = ClientClaimsEmission {
+        roles_in_groups: true,
+        groups_max: Some(1),
+        groups_overflow: true,
+        ..Default::default()
+    };
+    let (access, id, userinfo) = emitted_claims(&secret, Some(emission)).await?;
+    for claims in [&access, &id, &userinfo] {
+        assert!(claims.get("groups").is_none());
+        assert_eq!(claims.get("groups_overflow"), Some(&json!(true)));
+        assert!(contains(claims, "roles", "rauthy_admin"));
+    }
+
+    // size guard as warning only
+    let emission = ClientClaimsEmission {
+        roles_in_groups: true,
+        groups_max: Some(1),
+        groups_overflow: false,
+        ..Default::default()
+    };
+    let (access, _, _) = emitted_claims(&secret, Some(emission)).await?;
+    assert!(contains(&access, "groups", "admin"));
+    assert!(access.get("groups_overflow").is_none());
+
+    // custom names must never shadow reserved claims
+    for (roles_claim, groups_claim) in [
+        (Some("email"), None),
+        (None, Some("sub")),
+        (Some("groups"), None),
+        (Some("members"), Some("members")),
+    ] {
+        let emission = ClientClaimsEmission {
+            roles_claim: roles_claim.map(String::from),
+            groups_claim: groups_claim.map(String::from),
+            ..Default::default()
+        };
+        let res = http
+            .put(format!("{backend_url}/clients/{ID}"))
+            .headers(auth_headers.clone())
+            .json(&UpdateClientRequest {
+                claims_emission: Some(emission),
+                ..client_req()
+            })
+            .send()
+            .await?;
+        assert_eq!(res.status(), 400);
+    }
+
+    let res = http
+        .delete(format!("{backend_url}/clients/{ID}"))
+        .headers(auth_headers)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    Ok(())
+}
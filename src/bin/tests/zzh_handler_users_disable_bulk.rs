@@ -0,0 +1,103 @@
+use crate::common::{get_auth_headers, get_backend_url};
+use pretty_assertions::assert_eq;
+use rauthy_api_types::generic::Language;
+use rauthy_api_types::users::{
+    DisableUsersBulkRequest, DisableUsersBulkResponse, NewUserRequest, UserResponse,
+};
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+mod common;
+
+const USERS: usize = 1000;
+
+#[tokio::test]
+async fn test_users_disable_bulk() -> Result<(), Box<dyn Error>> {
+    let auth_headers = get_auth_headers().await?;
+    let backend_url = get_backend_url();
+    let http = reqwest::Client::new();
+
+    let mut user_ids = Vec::with_capacity(USERS);
+    for i in 0..USERS {
+        let res = http
+            .post(format!("{backend_url}/users"))
+            .headers(auth_headers.clone())
+            .json(&NewUserRequest {
+                email: format!("offboarding{i}@batcave.io"),
+                family_name: None,
+                given_name: Some("Offboarding".to_string()),
+                language: Language::En,
+                groups: None,
+                roles: vec!["user".to_string()],
+                user_expires: None,
+                tz: None,
+            })
+            .send()
+            .await?;
+        assert_eq!(res.status(), 200);
+        user_ids.push(res.json::<UserResponse>().await?.id);
+    }
+
+    // an empty request must be rejected
+    let res = http
+        .post(format!("{backend_url}/users/disable_bulk"))
+        .headers(auth_headers.clone())
+        .json(&DisableUsersBulkRequest { user_ids: vec![] })
+        .send()
+        .await?;
+    assert_eq!(res.status(), 400);
+
+    let start = Instant::now();
+    let res = http
+        .post(format!("{backend_url}/users/disable_bulk"))
+        .headers(auth_headers.clone())
+        .json(&DisableUsersBulkRequest {
+            user_ids: user_ids.clone(),
+        })
+        .send()
+        .await?;
+    let elapsed = start.elapsed();
+    assert_eq!(res.status(), 200);
+    assert!(
+        elapsed < Duration::from_secs(5),
+        "disabling {USERS} users took {elapsed:?}"
+    );
+    let disabled = res.json::<DisableUsersBulkResponse>().await?;
+    assert_eq!(disabled.disabled.len(), USERS);
+    assert!(disabled.revoked.contains_key("sessions"));
+    assert!(disabled.revoked.contains_key("refresh_tokens"));
+    assert!(disabled.revoked.contains_key("issued_tokens"));
+
+    // the user cache must have been invalidated
+    let res = http
+        .get(format!("{backend_url}/users/{}", user_ids[0]))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    assert!(!res.json::<UserResponse>().await?.enabled);
+
+    // already disabled users are not reported again
+    let res = http
+        .post(format!("{backend_url}/users/disable_bulk"))
+        .headers(auth_headers.clone())
+        .json(&DisableUsersBulkRequest {
+            user_ids: user_ids.clone(),
+        })
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let disabled = res.json::<DisableUsersBulkResponse>().await?;
+    assert!(disabled.disabled.is_empty());
+
+    for id in user_ids {
+        let res = http
+            .delete(format!("{backend_url}/users/{id}"))
+            .headers(auth_headers.clone())
+            .send()
+            .await?;
+        assert!(res.status().is_success());
+    }
+
+    Ok(())
+}
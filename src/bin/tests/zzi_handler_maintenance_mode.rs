@@ -0,0 +1,179 @@
+use crate::common::{
+    CLIENT_ID, CLIENT_SECRET, USERNAME, get_auth_headers, get_backend_url, token_req,
+};
+use chrono::Utc;
+use pretty_assertions::assert_eq;
+use rauthy_api_types::generic::{MaintenanceModeRequest, MaintenanceModeResponse};
+use rauthy_api_types::users::UserResponse;
+use reqwest::header::{COOKIE, HeaderMap, HeaderValue, RETRY_AFTER, SET_COOKIE};
+use std::error::Error;
+use std::time::Duration;
+
+mod common;
+
+#[tokio::test]
+async fn test_maintenance_mode() -> Result<(), Box<dyn Error>> {
+    let auth_headers = get_auth_headers().await?;
+    let backend_url = get_backend_url();
+    let http = reqwest::Client::new();
+    let url_token = format!("{backend_url}/oidc/token");
+    let url_maintenance = format!("{backend_url}/maintenance_mode");
+    let url_authorize = format!(
+        "{backend_url}/oidc/authorize?client_id={CLIENT_ID}&redirect_uri=http%3A%2F%2Flocalhost%3A3000%2Foidc%2Fcallback&response_type=code"
+    );
+
+    let res = http
+        .get(format!("{backend_url}/users/email/{USERNAME}"))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let admin_id = res.json::<UserResponse>().await?.id;
+
+    // the start must be before the end
+    let now = Utc::now().timestamp();
+    let res = http
+        .put(&url_maintenance)
+        .headers(auth_headers.clone())
+        .json(&MaintenanceModeRequest {
+            enabled: true,
+            start: Some(now + 10),
+            end: Some(now),
+            bypass_user_ids: vec![],
+        })
+        .send()
+        .await?;
+    assert_eq!(res.status(), 400);
+
+    let end = Utc::now().timestamp() + 8;
+    let res = http
+        .put(&url_maintenance)
+        .headers(auth_headers.clone())
+        .json(&MaintenanceModeRequest {
+            enabled: true,
+            start: None,
+            end: Some(end),
+            bypass_user_ids: vec![],
+        })
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    assert!(res.json::<MaintenanceModeResponse>().await?.active);
+
+    // new tokens are rejected with a Retry-After
+    for grant_type in ["client_credentials", "password"] {
+        let res = http
+            .post(&url_token)
+            .form(&token_req(grant_type, CLIENT_ID, CLIENT_SECRET))
+            .send()
+            .await?;
+        assert_eq!(res.status(), 503, "{grant_type}");
+        let retry_after = res
+            .headers()
+            .get(RETRY_AFTER)
+            .expect("Retry-After header to exist")
+            .to_str()?
+            .parse::<i64>()?;
+        assert!(retry_after > 0 && retry_after <= 8);
+    }
+
+    // new logins are rejected as well
+    let res = http.get(&url_authorize).send().await?;
+    assert_eq!(res.status(), 503);
+
+    // existing sessions keep working and the instance stays ready
+    let res = http
+        .get(&url_maintenance)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let res = http.get(format!("{backend_url}/ready")).send().await?;
+    assert_eq!(res.status(), 200);
+
+    // bypass via cookie
+    let res = http
+        .post(format!("{url_maintenance}/bypass"))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let bypass_cookie = res
+        .headers()
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .filter_map(|v| v.split_once(';').map(|(c, _)| c))
+        .find(|c| c.contains("RauthyMaintenanceBypass="))
+        .expect("bypass cookie to exist")
+        .to_string();
+    let mut bypass_headers = HeaderMap::new();
+    bypass_headers.insert(COOKIE, HeaderValue::from_str(&bypass_cookie)?);
+    let res = http
+        .get(&url_authorize)
+        .headers(bypass_headers)
+        .send()
+        .await?;
+    assert_ne!(res.status(), 503);
+
+    // bypass via the user id list
+    let res = http
+        .put(&url_maintenance)
+        .headers(auth_headers.clone())
+        .json(&MaintenanceModeRequest {
+            enabled: true,
+            start: None,
+            end: Some(end),
+            bypass_user_ids: vec![admin_id],
+        })
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let res = http
+        .post(&url_token)
+        .form(&token_req("password", CLIENT_ID, CLIENT_SECRET))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let res = http
+        .post(&url_token)
+        .form(&token_req("client_credentials", CLIENT_ID, CLIENT_SECRET))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 503);
+
+    // the maintenance ends automatically
+    let wait = end - Utc::now().timestamp() + 1;
+    if wait > 0 {
+        tokio::time::sleep(Duration::from_secs(wait as u64)).await;
+    }
+    let res = http
+        .post(&url_token)
+        .form(&token_req("client_credentials", CLIENT_ID, CLIENT_SECRET))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let res = http
+        .get(&url_maintenance)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    assert!(!res.json::<MaintenanceModeResponse>().await?.active);
+
+    // cleanup
+    let res = http
+        .put(&url_maintenance)
+        .headers(auth_headers)
+        .json(&MaintenanceModeRequest {
+            enabled: false,
+            start: None,
+            end: None,
+            bypass_user_ids: vec![],
+        })
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use crate::common::{
+    CLIENT_ID, CLIENT_SECRET, get_auth_headers, get_backend_url, token_req, update_req,
+};
+use pretty_assertions::assert_eq;
+use rauthy_api_types::clients::{ClientSecretResponse, NewClientRequest, UpdateClientRequest};
+use reqwest::header::WWW_AUTHENTICATE;
+use serde_json::Value;
+use std::error::Error;
+
+mod common;
+
+const ID: &str = "token_errors_test";
+
+/// Sends the token request and checks the RFC 6749 §5.2 error response. Client libraries
+/// depend on the exact keys and codes, so the body must never contain anything else.
+async fn assert_token_error(
+    res: reqwest::Response,
+    status: u16,
+    error: &str,
+) -> Result<(), Box<dyn Error>> {
+    assert_eq!(res.status(), status, "{error}");
+    let body = res.json::<Value>().await?;
+    let obj = body.as_object().expect("a JSON object");
+    let mut keys = obj.keys().map(String::as_str).collect::<Vec<_>>();
+    keys.sort();
+    assert_eq!(keys, vec!["error", "error_description", "rauthy_error"]);
+    assert_eq!(obj["error"], error);
+    assert!(obj["error_description"].is_string());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_token_errors() -> Result<(), Box<dyn Error>> {
+    let auth_headers = get_auth_headers().await?;
+    let backend_url = get_backend_url();
+    let url_token = format!("{backend_url}/oidc/token");
+    let http = reqwest::Client::new();
+
+    // unsupported_grant_type
+    let res = http
+        .post(&url_token)
+        .form(&token_req("implicit", CLIENT_ID, CLIENT_SECRET))
+        .send()
+        .await?;
+    assert_token_error(res, 400, "unsupported_grant_type").await?;
+
+    // invalid_request
+    let mut req = token_req("password", CLIENT_ID, CLIENT_SECRET);
+    req.username = None;
+    req.password = None;
+    let res = http.post(&url_token).form(&req).send().await?;
+    assert_token_error(res, 400, "invalid_request").await?;
+
+    // invalid_client - unknown client
+    let mut req = token_req("client_credentials", CLIENT_ID, CLIENT_SECRET);
+    req.client_id = Some("does_not_exist".to_string());
+    let res = http.post(&url_token).form(&req).send().await?;
+    assert_token_error(res, 401, "invalid_client").await?;
+
+    // invalid_client - bad secret, with a `Basic` challenge for header auth
+    let mut req = token_req("client_credentials", CLIENT_ID, CLIENT_SECRET);
+    req.client_secret = Some("IAmSoWrong1337".to_string());
+    let res = http.post(&url_token).form(&req).send().await?;
+    assert!(res.headers().get(WWW_AUTHENTICATE).is_none());
+    assert_token_error(res, 401, "invalid_client").await?;
+
+    let mut req = token_req("client_credentials", CLIENT_ID, CLIENT_SECRET);
+    req.client_id = None;
+    req.client_secret = None;
+    let res = http
+        .post(&url_token)
+        .basic_auth(CLIENT_ID, Some("IAmSoWrong1337"))
+        .form(&req)
+        .send()
+        .await?;
+    assert_eq!(res.headers().get(WWW_AUTHENTICATE).unwrap(), "Basic");
+    assert_token_error(res, 401, "invalid_client").await?;
+
+    // invalid_grant - bad code
+    let mut req = token_req("authorization_code", CLIENT_ID, CLIENT_SECRET);
+    req.code = Some("IDoNotExist1337".to_string());
+    req.redirect_uri = Some("http://localhost:3000/oidc/callback".to_string());
+    let res = http.post(&url_token).form(&req).send().await?;
+    assert_token_error(res, 400, "invalid_grant").await?;
+
+    // invalid_grant - bad refresh token
+    let mut req = token_req("refresh_token", CLIENT_ID, CLIENT_SECRET);
+    req.refresh_token = Some("eyIAmNotARefreshToken.abc.def".to_string());
+    let res = http.post(&url_token).form(&req).send().await?;
+    assert_token_error(res, 400, "invalid_grant").await?;
+
+    // invalid_grant - bad user credentials
+    let mut req = token_req("password", CLIENT_ID, CLIENT_SECRET);
+    req.password = Some("IAmSoWrong1337".to_string());
+    let res = http.post(&url_token).form(&req).send().await?;
+    assert_token_error(res, 400, "invalid_grant").await?;
+
+    // unauthorized_client - flow not enabled and disabled client
+    let res = http
+        .post(format!("{backend_url}/clients"))
+        .headers(auth_headers.clone())
+        .json(&NewClientRequest {
+            id: ID.to_string(),
+            secret: None,
+            name: Some("Token Errors Test".to_string()),
+            confidential: true,
+            redirect_uris: vec!["http://localhost/callback".to_string()],
+            post_logout_redirect_uris: None,
+            client_type: None,
+        })
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let res = http
+        .post(format!("{backend_url}/clients/{ID}/secret"))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let secret = res
+        .json::<ClientSecretResponse>()
+        .await?
+        .secret
+        .expect("a client secret");
+
+    let req = token_req("password", ID, &secret);
+    for enabled in [true, false] {
+        let res = http
+            .put(format!("{backend_url}/clients/{ID}"))
+            .headers(auth_headers.clone())
+            .json(&UpdateClientRequest {
+                enabled,
+                ..update_req("Token Errors Test", &["authorization_code"])
+            })
+            .send()
+            .await?;
+        assert_eq!(res.status(), 200);
+
+        let res = http.post(&url_token).form(&req).send().await?;
+        assert_token_error(res, 400, "unauthorized_client").await?;
+    }
+
+    let res = http
+        .delete(format!("{backend_url}/clients/{ID}"))
+        .headers(auth_headers)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    Ok(())
+}
@@ -0,0 +1,163 @@
+use crate::common::{USERNAME, get_auth_headers, get_backend_url, token_req, update_req};
+use pretty_assertions::assert_eq;
+use rauthy_api_types::clients::{ClientSecretResponse, NewClientRequest, UpdateClientRequest};
+use rauthy_api_types::oidc::TokenSet;
+use rauthy_api_types::users::{UserClientResponse, UserResponse};
+use serde_json::Value;
+use std::error::Error;
+use std::time::Duration;
+use tokio::time;
+
+mod common;
+
+const ID: &str = "client_access_test";
+
+fn client_req(allowed_roles: Vec<String>) -> UpdateClientRequest {
+    UpdateClientRequest {
+        client_uri: Some("http://localhost/portal".to_string()),
+        allowed_roles: Some(allowed_roles),
+        ..update_req(
+            "Client Access Test",
+            &["authorization_code", "password", "refresh_token"],
+        )
+    }
+}
+
+#[tokio::test]
+async fn test_client_access_restrictions() -> Result<(), Box<dyn Error>> {
+    let auth_headers = get_auth_headers().await?;
+    let backend_url = get_backend_url();
+    let url_client = format!("{backend_url}/clients/{ID}");
+    let url_token = format!("{backend_url}/oidc/token");
+    let http = reqwest::Client::new();
+
+    let res = http
+        .get(format!("{backend_url}/users/email/{USERNAME}"))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let user = res.json::<UserResponse>().await?;
+    let url_user_clients = format!("{backend_url}/users/{}/clients", user.id);
+
+    let res = http
+        .post(format!("{backend_url}/clients"))
+        .headers(auth_headers.clone())
+        .json(&NewClientRequest {
+            id: ID.to_string(),
+            secret: None,
+            name: Some("Client Access Test".to_string()),
+            confidential: true,
+            redirect_uris: vec!["http://localhost/callback".to_string()],
+            post_logout_redirect_uris: None,
+            client_type: None,
+        })
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let res = http
+        .post(format!("{url_client}/secret"))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let secret = res
+        .json::<ClientSecretResponse>()
+        .await?
+        .secret
+        .expect("a client secret");
+
+    // restricted to a role the user has
+    let res = http
+        .put(&url_client)
+        .headers(auth_headers.clone())
+        .json(&client_req(vec![user.roles[0].clone()]))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    let res = http
+        .get(&url_user_clients)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let clients = res.json::<Vec<UserClientResponse>>().await?;
+    let client = clients
+        .iter()
+        .find(|c| c.id == ID)
+        .expect("the client to be accessible");
+    assert_eq!(client.name.as_deref(), Some("Client Access Test"));
+    assert_eq!(
+        client.client_uri.as_deref(),
+        Some("http://localhost/portal")
+    );
+    assert!(!clients.iter().any(|c| c.id == "rauthy"));
+
+    let res = http
+        .post(&url_token)
+        .form(&token_req("password", ID, &secret))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let ts = res.json::<TokenSet>().await?;
+    let refresh_token = ts.refresh_token.expect("a refresh token");
+
+    // a role removal must cut off the client at the next refresh
+    let res = http
+        .put(&url_client)
+        .headers(auth_headers.clone())
+        .json(&client_req(vec!["client_access_nobody".to_string()]))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    time::sleep(Duration::from_secs(1)).await;
+    let mut req = token_req("refresh_token", ID, &secret);
+    req.refresh_token = Some(refresh_token);
+    let res = http.post(&url_token).form(&req).send().await?;
+    assert_eq!(res.status(), 400);
+    assert_eq!(res.json::<Value>().await?["error"], "invalid_grant");
+
+    // grant-time denial
+    let res = http
+        .post(&url_token)
+        .form(&token_req("password", ID, &secret))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 400);
+    assert_eq!(res.json::<Value>().await?["error"], "invalid_grant");
+
+    let res = http
+        .get(&url_user_clients)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let clients = res.json::<Vec<UserClientResponse>>().await?;
+    assert!(!clients.iter().any(|c| c.id == ID));
+
+    // empty restrictions are unrestricted again
+    let res = http
+        .put(&url_client)
+        .headers(auth_headers.clone())
+        .json(&client_req(vec![]))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let res = http
+        .post(&url_token)
+        .form(&token_req("password", ID, &secret))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    let res = http
+        .delete(&url_client)
+        .headers(auth_headers)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    Ok(())
+}
@@ -0,0 +1,99 @@
+use crate::common::{CLIENT_ID, CLIENT_SECRET, get_backend_url, token_req};
+use pretty_assertions::assert_eq;
+use reqwest::Response;
+use reqwest::header::RETRY_AFTER;
+use std::error::Error;
+use std::time::Duration;
+
+mod common;
+
+/// Must match the `max_hash_threads` from the test config
+const HASH_THREADS: usize = 1;
+/// The default `hash_queue_limit`
+const HASH_QUEUE_LIMIT: usize = 32;
+/// Way more concurrent password logins than the hash workers and queue can ever accept
+const BURST: usize = 200;
+
+/// Sends `n` sequential `client_credentials` requests, which never need a password hash, and
+/// makes sure none of them is affected by the hash queue.
+async fn client_credentials(
+    http: &reqwest::Client,
+    url: &str,
+    n: usize,
+) -> Result<(), Box<dyn Error>> {
+    let req = token_req("client_credentials", CLIENT_ID, CLIENT_SECRET);
+    for _ in 0..n {
+        let res = http.post(url).form(&req).send().await?;
+        assert_eq!(res.status(), 200);
+    }
+    Ok(())
+}
+
+async fn password_login(http: &reqwest::Client, url: &str) -> reqwest::Result<Response> {
+    http.post(url)
+        .form(&token_req("password", CLIENT_ID, CLIENT_SECRET))
+        .send()
+        .await
+}
+
+// A burst of password verifications must neither block other requests on the async executor,
+// nor queue up without a limit. Each of them either succeeds or is shed with a `503`.
+#[tokio::test]
+async fn test_password_hash_load() -> Result<(), Box<dyn Error>> {
+    let url = format!("{}/oidc/token", get_backend_url());
+    let http = reqwest::Client::new();
+
+    // warm up connections and caches
+    client_credentials(&http, &url, 5).await?;
+
+    let mut handles = Vec::with_capacity(BURST);
+    for _ in 0..BURST {
+        let http = http.clone();
+        let url = url.clone();
+        handles.push(tokio::spawn(
+            async move { password_login(&http, &url).await },
+        ));
+    }
+    // give the burst some time to fill the hash queue
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    // requests without a password hash are never shed, even with a full queue
+    client_credentials(&http, &url, 100).await?;
+
+    let mut ok = 0;
+    let mut shed = 0;
+    for handle in handles {
+        let res = handle.await??;
+        match res.status().as_u16() {
+            200 => ok += 1,
+            503 => {
+                assert!(res.headers().get(RETRY_AFTER).is_some());
+                shed += 1;
+            }
+            status => panic!("unexpected status {status} for a password login"),
+        }
+    }
+    assert!(ok > 0, "all password logins have been shed: {shed}");
+    // The queue is bounded: at most `HASH_THREADS + HASH_QUEUE_LIMIT` logins are accepted at
+    // the same time, which is far below the burst.
+    assert!(
+        shed > 0,
+        "no password login has been shed, accepted: {ok}, capacity: {}",
+        HASH_THREADS + HASH_QUEUE_LIMIT
+    );
+
+    // Shed requests never hold a slot. As soon as the burst is over, the full capacity is
+    // available again.
+    let mut handles = Vec::with_capacity(HASH_THREADS + HASH_QUEUE_LIMIT);
+    for _ in 0..HASH_THREADS + HASH_QUEUE_LIMIT {
+        let http = http.clone();
+        let url = url.clone();
+        handles.push(tokio::spawn(
+            async move { password_login(&http, &url).await },
+        ));
+    }
+    for handle in handles {
+        assert_eq!(handle.await??.status(), 200);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,189 @@
+use crate::common::{CLIENT_ID, CLIENT_SECRET, get_auth_headers, get_backend_url};
+use pretty_assertions::assert_eq;
+use rauthy_api_types::oidc::TokenRequest;
+use rauthy_api_types::users::UserResponse;
+use rauthy_data::entity::groups::Group;
+use rauthy_data::entity::roles::Role;
+use serde_json::Value;
+use std::error::Error;
+
+mod common;
+
+const REALM_EXPORT: &[u8] = include_bytes!("../../data/tests/fixtures/keycloak/realm_export.json");
+
+fn action<'a>(report: &'a Value, section: &str, key: &str) -> &'a str {
+    report[section]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|e| e["key"] == key)
+        .unwrap_or_else(|| panic!("{key} in {section}"))["action"]
+        .as_str()
+        .unwrap()
+}
+
+fn password_req(email: &str) -> TokenRequest {
+    TokenRequest {
+        grant_type: "password".to_string(),
+        code: None,
+        redirect_uri: None,
+        client_id: Some(CLIENT_ID.to_string()),
+        client_secret: Some(CLIENT_SECRET.to_string()),
+        code_verifier: None,
+        device_code: None,
+        username: Some(email.to_string()),
+        password: Some("SuperRandom1337".to_string()),
+        refresh_token: None,
+        resource: None,
+    }
+}
+
+#[tokio::test]
+async fn test_keycloak_import() -> Result<(), Box<dyn Error>> {
+    let auth_headers = get_auth_headers().await?;
+    let backend_url = get_backend_url();
+    let url = format!("{backend_url}/migration/keycloak");
+    let http = reqwest::Client::new();
+
+    // dry run must not write anything
+    let res = http
+        .post(format!("{url}?dry_run=true"))
+        .headers(auth_headers.clone())
+        .body(REALM_EXPORT)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let report = res.json::<Value>().await?;
+    assert_eq!(report["dry_run"], true);
+    assert_eq!(action(&report, "users", "alice@acme.test"), "create");
+    assert_eq!(action(&report, "clients", "inventory"), "create");
+    let res = http
+        .get(format!("{backend_url}/users/email/alice@acme.test"))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 404);
+
+    let res = http
+        .post(&url)
+        .headers(auth_headers.clone())
+        .body(REALM_EXPORT)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let report = res.json::<Value>().await?;
+    assert_eq!(report["realm"], "acme");
+    assert_eq!(action(&report, "roles", "developer"), "create");
+    // exists in the test data already
+    assert_eq!(action(&report, "roles", "admin"), "exists");
+    assert_eq!(action(&report, "groups", "engineering/backend"), "create");
+    assert_eq!(action(&report, "clients", "billing-api"), "skip");
+    assert_eq!(action(&report, "users", "erin"), "skip");
+
+    let res = http
+        .get(format!("{backend_url}/users/email/alice@acme.test"))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let alice = res.json::<UserResponse>().await?;
+    assert_eq!(alice.roles, vec!["admin", "developer", "on-call"]);
+    assert_eq!(alice.groups, Some(vec!["engineering/backend".to_string()]));
+
+    // The imported PBKDF2 hash must be accepted and then upgraded to Argon2ID, which is
+    // accepted with the next login as well.
+    for _ in 0..2 {
+        let res = http
+            .post(format!("{backend_url}/oidc/token"))
+            .form(&password_req("alice@acme.test"))
+            .send()
+            .await?;
+        assert_eq!(res.status(), 200);
+    }
+
+    // re-runs must not create duplicates or modify existing entities
+    let res = http
+        .post(&url)
+        .headers(auth_headers.clone())
+        .body(REALM_EXPORT)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let report = res.json::<Value>().await?;
+    for section in ["roles", "groups", "clients", "users"] {
+        for entity in report[section].as_array().unwrap() {
+            assert_ne!(entity["action"], "create", "{entity}");
+        }
+    }
+    assert_eq!(action(&report, "users", "alice@acme.test"), "exists");
+
+    // cleanup
+    for email in [
+        "alice@acme.test",
+        "Bob@acme.test",
+        "carol@acme.test",
+        "dave@acme.test",
+        "frank@acme.test",
+    ] {
+        let res = http
+            .get(format!("{backend_url}/users/email/{email}"))
+            .headers(auth_headers.clone())
+            .send()
+            .await?;
+        assert_eq!(res.status(), 200, "{email}");
+        let user = res.json::<UserResponse>().await?;
+        let res = http
+            .delete(format!("{backend_url}/users/{}", user.id))
+            .headers(auth_headers.clone())
+            .send()
+            .await?;
+        assert_eq!(res.status(), 200);
+    }
+    for id in ["inventory", "spa-frontend", "acme-cli", "reports"] {
+        let res = http
+            .delete(format!("{backend_url}/clients/{id}"))
+            .headers(auth_headers.clone())
+            .send()
+            .await?;
+        assert_eq!(res.status(), 200);
+    }
+
+    let roles = http
+        .get(format!("{backend_url}/roles"))
+        .headers(auth_headers.clone())
+        .send()
+        .await?
+        .json::<Vec<Role>>()
+        .await?;
+    for role in roles
+        .iter()
+        .filter(|r| ["developer", "on-call"].contains(&r.name.as_str()))
+    {
+        let res = http
+            .delete(format!("{backend_url}/roles/{}", role.id))
+            .headers(auth_headers.clone())
+            .send()
+            .await?;
+        assert_eq!(res.status(), 200);
+    }
+    let groups = http
+        .get(format!("{backend_url}/groups"))
+        .headers(auth_headers.clone())
+        .send()
+        .await?
+        .json::<Vec<Group>>()
+        .await?;
+    for group in groups
+        .iter()
+        .filter(|g| ["engineering", "engineering/backend", "support"].contains(&g.name.as_str()))
+    {
+        let res = http
+            .delete(format!("{backend_url}/groups/{}", group.id))
+            .headers(auth_headers.clone())
+            .send()
+            .await?;
+        assert_eq!(res.status(), 200);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,170 @@
+use crate::common::{
+    CLIENT_ID, CLIENT_SECRET, PASSWORD, USERNAME, get_auth_headers, get_backend_url,
+};
+use chrono::Utc;
+use pretty_assertions::assert_eq;
+use rauthy_api_types::api_keys::{AccessGroup, AccessRights, ApiKeyAccess, ApiKeyRequest};
+use rauthy_api_types::generic::Language;
+use rauthy_api_types::oidc::TokenRequest;
+use rauthy_api_types::reports::ExpiringReportResponse;
+use rauthy_api_types::users::{NewUserRequest, UserResponse};
+use std::error::Error;
+
+mod common;
+
+const DAY: i64 = 24 * 3600;
+
+fn new_user(email: &str, user_expires: i64) -> NewUserRequest {
+    NewUserRequest {
+        email: email.to_string(),
+        family_name: None,
+        given_name: Some("Expiring".to_string()),
+        language: Language::En,
+        groups: None,
+        roles: vec!["user".to_string()],
+        user_expires: Some(user_expires),
+        tz: None,
+    }
+}
+
+#[tokio::test]
+async fn test_reports_expiring() -> Result<(), Box<dyn Error>> {
+    let auth_headers = get_auth_headers().await?;
+    let backend_url = get_backend_url();
+    let url = format!("{backend_url}/reports/expiring");
+    let http = reqwest::Client::new();
+
+    let res = http.get(&url).send().await?;
+    assert_eq!(res.status(), 401);
+    let res = http
+        .get(format!("{url}?days=0"))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 400);
+
+    let now = Utc::now().timestamp();
+
+    // seed artifacts inside and outside the windows
+    for (name, exp) in [
+        ("expiring-soon", now + 2 * DAY),
+        ("expiring-later", now + 20 * DAY),
+    ] {
+        let res = http
+            .post(format!("{backend_url}/api_keys"))
+            .headers(auth_headers.clone())
+            .json(&ApiKeyRequest {
+                name: name.to_string(),
+                exp: Some(exp),
+                access: vec![ApiKeyAccess {
+                    group: AccessGroup::Groups,
+                    access_rights: vec![AccessRights::Read],
+                }],
+            })
+            .send()
+            .await?;
+        assert_eq!(res.status(), 200);
+    }
+
+    let mut user_ids = Vec::with_capacity(2);
+    for (email, exp) in [
+        ("expiring.soon@localhost.de", now + 2 * DAY),
+        ("expiring.later@localhost.de", now + 20 * DAY),
+    ] {
+        let res = http
+            .post(format!("{backend_url}/users"))
+            .headers(auth_headers.clone())
+            .json(&new_user(email, exp))
+            .send()
+            .await?;
+        assert_eq!(res.status(), 200);
+        user_ids.push(res.json::<UserResponse>().await?.id);
+    }
+
+    // a fresh refresh token valid for 48 hours
+    let res = http
+        .post(format!("{backend_url}/oidc/token"))
+        .form(&TokenRequest {
+            grant_type: "password".to_string(),
+            code: None,
+            redirect_uri: None,
+            client_id: Some(CLIENT_ID.to_string()),
+            client_secret: Some(CLIENT_SECRET.to_string()),
+            code_verifier: None,
+            device_code: None,
+            username: Some(USERNAME.to_string()),
+            password: Some(PASSWORD.to_string()),
+            refresh_token: None,
+            resource: None,
+        })
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    let report = |days: u16| {
+        let http = http.clone();
+        let headers = auth_headers.clone();
+        let url = format!("{url}?days={days}");
+        async move {
+            let res = http.get(url).headers(headers).send().await?;
+            assert_eq!(res.status(), 200);
+            Ok::<_, Box<dyn Error>>(res.json::<ExpiringReportResponse>().await?)
+        }
+    };
+    let has_key =
+        |r: &ExpiringReportResponse, name: &str| r.api_keys.iter().any(|k| k.name == name);
+    let has_user =
+        |r: &ExpiringReportResponse, email: &str| r.users.iter().any(|u| u.email == email);
+    let has_rt = |r: &ExpiringReportResponse| r.refresh_tokens.iter().any(|t| t.email == USERNAME);
+
+    // nothing of ours lapses within a single day
+    let r = report(1).await?;
+    assert_eq!(r.until - r.generated, DAY);
+    assert!(!has_key(&r, "expiring-soon"));
+    assert!(!has_key(&r, "expiring-later"));
+    assert!(!has_user(&r, "expiring.soon@localhost.de"));
+    assert!(!has_rt(&r));
+
+    let r = report(3).await?;
+    assert!(has_key(&r, "expiring-soon"));
+    assert!(!has_key(&r, "expiring-later"));
+    assert!(has_user(&r, "expiring.soon@localhost.de"));
+    assert!(!has_user(&r, "expiring.later@localhost.de"));
+    let rt = r
+        .refresh_tokens
+        .iter()
+        .find(|t| t.email == USERNAME)
+        .expect("the admin refresh token to lapse within 3 days");
+    assert!(rt.count >= 1);
+    assert!(rt.expires > now && rt.expires <= r.until);
+
+    let r = report(30).await?;
+    assert!(has_key(&r, "expiring-soon"));
+    assert!(has_key(&r, "expiring-later"));
+    assert!(has_user(&r, "expiring.soon@localhost.de"));
+    assert!(has_user(&r, "expiring.later@localhost.de"));
+    for key in &r.api_keys {
+        assert!(key.expires > now && key.expires <= r.until);
+    }
+    assert!(r.users.windows(2).all(|w| w[0].expires <= w[1].expires));
+
+    // cleanup
+    for name in ["expiring-soon", "expiring-later"] {
+        let res = http
+            .delete(format!("{backend_url}/api_keys/{name}"))
+            .headers(auth_headers.clone())
+            .send()
+            .await?;
+        assert_eq!(res.status(), 200);
+    }
+    for id in user_ids {
+        let res = http
+            .delete(format!("{backend_url}/users/{id}"))
+            .headers(auth_headers.clone())
+            .send()
+            .await?;
+        assert_eq!(res.status(), 200);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,200 @@
+use crate::common::{USERNAME, get_auth_headers, get_backend_url, token_req, update_req};
+use pretty_assertions::assert_eq;
+use rauthy_api_types::clients::{ClientSecretResponse, NewClientRequest, UpdateClientRequest};
+use rauthy_api_types::oidc::{TokenRequest, TokenSet};
+use rauthy_api_types::users::{UserGrantResponse, UserResponse};
+use std::error::Error;
+
+mod common;
+
+const CLIENTS: [&str; 2] = ["grants_test_a", "grants_test_b"];
+
+#[tokio::test]
+async fn test_user_grants() -> Result<(), Box<dyn Error>> {
+    let auth_headers = get_auth_headers().await?;
+    let backend_url = get_backend_url();
+    let url_token = format!("{backend_url}/oidc/token");
+    let http = reqwest::Client::new();
+
+    let res = http
+        .get(format!("{backend_url}/users/email/{USERNAME}"))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let user = res.json::<UserResponse>().await?;
+    let url_grants = format!("{backend_url}/users/{}/grants", user.id);
+
+    let res = http.get(&url_grants).send().await?;
+    assert_eq!(res.status(), 401);
+
+    // 2 clients holding refresh tokens for the same user
+    let mut secrets = Vec::with_capacity(CLIENTS.len());
+    for id in CLIENTS {
+        let res = http
+            .post(format!("{backend_url}/clients"))
+            .headers(auth_headers.clone())
+            .json(&NewClientRequest {
+                id: id.to_string(),
+                secret: None,
+                name: Some(format!("Grants Test {id}")),
+                confidential: true,
+                redirect_uris: vec!["http://localhost/callback".to_string()],
+                post_logout_redirect_uris: None,
+                client_type: None,
+            })
+            .send()
+            .await?;
+        assert_eq!(res.status(), 200);
+        let res = http
+            .put(format!("{backend_url}/clients/{id}"))
+            .headers(auth_headers.clone())
+            .json(&UpdateClientRequest {
+                scopes: vec!["openid".to_string(), "email".to_string()],
+                ..update_req(&format!("Grants Test {id}"), &["password", "refresh_token"])
+            })
+            .send()
+            .await?;
+        assert_eq!(res.status(), 200);
+        let res = http
+            .post(format!("{backend_url}/clients/{id}/secret"))
+            .headers(auth_headers.clone())
+            .send()
+            .await?;
+        assert_eq!(res.status(), 200);
+        secrets.push(
+            res.json::<ClientSecretResponse>()
+                .await?
+                .secret
+                .expect("a client secret"),
+        );
+    }
+
+    let mut refresh_tokens = Vec::with_capacity(CLIENTS.len());
+    for (id, secret) in CLIENTS.iter().zip(secrets.iter()) {
+        let res = http
+            .post(&url_token)
+            .form(&token_req("password", id, secret))
+            .send()
+            .await?;
+        assert_eq!(res.status(), 200);
+        let ts = res.json::<TokenSet>().await?;
+        refresh_tokens.push(ts.refresh_token.expect("a refresh token"));
+    }
+    // a 2nd login for client a
+    let res = http
+        .post(&url_token)
+        .form(&token_req("password", CLIENTS[0], &secrets[0]))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    // use the refresh token of client a to get a `last_used`
+    let res = http
+        .post(&url_token)
+        .form(&TokenRequest {
+            refresh_token: Some(refresh_tokens[0].clone()),
+            ..token_req("refresh_token", CLIENTS[0], &secrets[0])
+        })
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let rotated = res
+        .json::<TokenSet>()
+        .await?
+        .refresh_token
+        .expect("a rotated refresh token");
+
+    let res = http
+        .get(&url_grants)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let body = res.text().await?;
+    for rt in refresh_tokens.iter().chain([&rotated]) {
+        assert!(!body.contains(&rt[rt.len() - 49..]));
+    }
+    let grants = serde_json::from_str::<Vec<UserGrantResponse>>(&body)?;
+
+    let grant_a = grants
+        .iter()
+        .find(|g| g.client_id == CLIENTS[0])
+        .expect("a grant for client a");
+    // both logins, the rotated one has not expired yet
+    assert!(grant_a.tokens >= 2);
+    assert_eq!(
+        grant_a.client_name.as_deref(),
+        Some("Grants Test grants_test_a")
+    );
+    assert!(grant_a.scopes.iter().any(|s| s == "openid"));
+    assert!(grant_a.first_granted <= grant_a.expires);
+    assert!(grant_a.last_used.is_some());
+
+    let grant_b = grants
+        .iter()
+        .find(|g| g.client_id == CLIENTS[1])
+        .expect("a grant for client b");
+    assert_eq!(grant_b.tokens, 1);
+    assert_eq!(grant_b.last_used, None);
+
+    // revoking client a leaves client b untouched
+    let res = http
+        .delete(format!("{url_grants}/{}", CLIENTS[0]))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let res = http
+        .delete(format!("{url_grants}/{}", CLIENTS[0]))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 404);
+
+    let res = http
+        .get(&url_grants)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let grants = res.json::<Vec<UserGrantResponse>>().await?;
+    assert!(!grants.iter().any(|g| g.client_id == CLIENTS[0]));
+    let grant_b_after = grants
+        .iter()
+        .find(|g| g.client_id == CLIENTS[1])
+        .expect("the grant for client b to still exist");
+    assert_eq!(grant_b_after.tokens, grant_b.tokens);
+    assert_eq!(grant_b_after.first_granted, grant_b.first_granted);
+
+    let res = http
+        .post(&url_token)
+        .form(&TokenRequest {
+            refresh_token: Some(rotated),
+            ..token_req("refresh_token", CLIENTS[0], &secrets[0])
+        })
+        .send()
+        .await?;
+    assert!(res.status().is_client_error());
+    let res = http
+        .post(&url_token)
+        .form(&TokenRequest {
+            refresh_token: Some(refresh_tokens[1].clone()),
+            ..token_req("refresh_token", CLIENTS[1], &secrets[1])
+        })
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    // cleanup
+    for id in CLIENTS {
+        let res = http
+            .delete(format!("{backend_url}/clients/{id}"))
+            .headers(auth_headers.clone())
+            .send()
+            .await?;
+        assert_eq!(res.status(), 200);
+    }
+
+    Ok(())
+}
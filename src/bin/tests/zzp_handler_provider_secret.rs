@@ -0,0 +1,126 @@
+use crate::common::{CLIENT_ID, CLIENT_SECRET, get_auth_headers, get_backend_url, get_issuer};
+use pretty_assertions::assert_eq;
+use rauthy_api_types::auth_providers::{
+    ProviderSecretProbeResult, ProviderSecretRequest, ProviderSecretResponse,
+};
+use serde_json::json;
+use std::error::Error;
+
+mod common;
+
+#[tokio::test]
+async fn test_provider_secret_rotation() -> Result<(), Box<dyn Error>> {
+    let auth_headers = get_auth_headers().await?;
+    let backend_url = get_backend_url();
+    let issuer = get_issuer();
+    let http = reqwest::Client::new();
+
+    // Rauthy itself acts as the upstream with the `init_client`
+    let res = http
+        .post(format!("{backend_url}/providers/create"))
+        .headers(auth_headers.clone())
+        .json(&json!({
+            "name": "Secret Rotation Test",
+            "typ": "oidc",
+            "enabled": true,
+            "issuer": "http://localhost:8080/secret_rotation_test",
+            "authorization_endpoint": format!("{issuer}/oidc/authorize"),
+            "token_endpoint": format!("{backend_url}/oidc/token"),
+            "userinfo_endpoint": format!("{issuer}/oidc/userinfo"),
+            "use_pkce": true,
+            "client_secret_basic": true,
+            "client_secret_post": false,
+            "auto_onboarding": false,
+            "auto_link": false,
+            "client_id": CLIENT_ID,
+            "client_secret": "the_old_secret",
+            "scope": "openid",
+        }))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let id = res.json::<serde_json::Value>().await?["id"]
+        .as_str()
+        .expect("a provider id")
+        .to_string();
+    let url = format!("{backend_url}/providers/{id}/secret");
+
+    let rotate = |client_secret: &str, force: bool| {
+        http.post(&url)
+            .headers(auth_headers.clone())
+            .json(&ProviderSecretRequest {
+                client_secret: client_secret.to_string(),
+                force,
+            })
+            .send()
+    };
+
+    let res = http
+        .post(&url)
+        .json(&ProviderSecretRequest {
+            client_secret: CLIENT_SECRET.to_string(),
+            force: false,
+        })
+        .send()
+        .await?;
+    assert_eq!(res.status(), 401);
+
+    // rejected by the upstream -> not saved
+    let res = rotate("invalid_secret", false).await?;
+    assert_eq!(res.status(), 406);
+    let probe = res.json::<ProviderSecretResponse>().await?;
+    assert_eq!(probe.result, ProviderSecretProbeResult::Invalid);
+    assert_eq!(probe.status, Some(401));
+    assert!(!probe.persisted);
+
+    let res = http
+        .post(format!("{backend_url}/providers"))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let providers = res.json::<serde_json::Value>().await?;
+    let secret = |providers: &serde_json::Value| {
+        providers
+            .as_array()
+            .and_then(|p| p.iter().find(|p| p["id"] == id.as_str()))
+            .map(|p| p["client_secret"].as_str().unwrap_or_default().to_string())
+            .expect("the test provider")
+    };
+    assert_eq!(secret(&providers), "the_old_secret");
+
+    // forced -> saved anyway
+    let res = rotate("invalid_secret", true).await?;
+    assert_eq!(res.status(), 200);
+    let probe = res.json::<ProviderSecretResponse>().await?;
+    assert_eq!(probe.result, ProviderSecretProbeResult::Invalid);
+    assert!(probe.persisted);
+
+    // accepted by the upstream
+    let res = rotate(CLIENT_SECRET, false).await?;
+    assert_eq!(res.status(), 200);
+    let probe = res.json::<ProviderSecretResponse>().await?;
+    assert_eq!(probe.result, ProviderSecretProbeResult::Valid);
+    assert!(probe.persisted);
+
+    let res = http
+        .post(format!("{backend_url}/providers"))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    assert_eq!(
+        secret(&res.json::<serde_json::Value>().await?),
+        CLIENT_SECRET
+    );
+
+    // cleanup
+    let res = http
+        .delete(format!("{backend_url}/providers/{id}"))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    Ok(())
+}
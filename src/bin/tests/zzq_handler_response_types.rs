@@ -0,0 +1,134 @@
+use crate::common::{get_auth_headers, get_backend_url, update_req};
+use pretty_assertions::assert_eq;
+use rauthy_api_types::clients::{ClientResponse, NewClientRequest, UpdateClientRequest};
+use std::error::Error;
+
+mod common;
+
+const ID: &str = "response_types_test";
+const REDIRECT_URI: &str = "http://localhost/callback";
+
+fn client_req(response_types: Option<Vec<&str>>) -> UpdateClientRequest {
+    UpdateClientRequest {
+        response_types: response_types.map(|t| t.into_iter().map(String::from).collect()),
+        ..update_req("Response Types Test", &["authorization_code"])
+    }
+}
+
+#[tokio::test]
+async fn test_client_response_types() -> Result<(), Box<dyn Error>> {
+    let auth_headers = get_auth_headers().await?;
+    let backend_url = get_backend_url();
+    let url_client = format!("{backend_url}/clients/{ID}");
+    let http = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?;
+
+    let res = http
+        .post(format!("{backend_url}/clients"))
+        .headers(auth_headers.clone())
+        .json(&NewClientRequest {
+            id: ID.to_string(),
+            secret: None,
+            name: Some("Response Types Test".to_string()),
+            confidential: true,
+            redirect_uris: vec![REDIRECT_URI.to_string()],
+            post_logout_redirect_uris: None,
+            client_type: None,
+        })
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let client = res.json::<ClientResponse>().await?;
+    assert_eq!(client.response_types, vec!["code", "none"]);
+
+    // the last remaining response type can never be removed
+    let res = http
+        .put(&url_client)
+        .headers(auth_headers.clone())
+        .json(&client_req(Some(vec![])))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 400);
+    let res = http
+        .put(&url_client)
+        .headers(auth_headers.clone())
+        .json(&client_req(Some(vec!["code", "token"])))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 400);
+
+    let requested = [
+        "code",
+        "none",
+        "code none",
+        "none code",
+        "token",
+        "code id_token",
+    ];
+    for allowed in [vec!["code"], vec!["none"], vec!["code", "none"]] {
+        let res = http
+            .put(&url_client)
+            .headers(auth_headers.clone())
+            .json(&client_req(Some(allowed.clone())))
+            .send()
+            .await?;
+        assert_eq!(res.status(), 200);
+        let client = res.json::<ClientResponse>().await?;
+        assert_eq!(client.response_types, allowed);
+
+        for response_type in requested {
+            let res = http
+                .get(format!(
+                    "{backend_url}/oidc/authorize?client_id={ID}&redirect_uri={REDIRECT_URI}\
+                    &response_type={}&state=abc",
+                    response_type.replace(' ', "%20")
+                ))
+                .send()
+                .await?;
+            let msg = format!("allowed: {allowed:?}, requested: {response_type}");
+
+            if !allowed.contains(&response_type) {
+                assert_eq!(res.status(), 302, "{msg}");
+                let loc = res.headers().get(reqwest::header::LOCATION).unwrap();
+                assert_eq!(
+                    loc.to_str()?,
+                    format!("{REDIRECT_URI}?error=unsupported_response_type&state=abc"),
+                    "{msg}"
+                );
+            } else if response_type == "none" {
+                // without a session
+                assert_eq!(res.status(), 302, "{msg}");
+                let loc = res.headers().get(reqwest::header::LOCATION).unwrap();
+                assert_eq!(
+                    loc.to_str()?,
+                    format!("{REDIRECT_URI}?error=login_required&state=abc"),
+                    "{msg}"
+                );
+            } else {
+                // the login page
+                assert_eq!(res.status(), 200, "{msg}");
+            }
+        }
+    }
+
+    // missing keeps the current value
+    let res = http
+        .put(&url_client)
+        .headers(auth_headers.clone())
+        .json(&client_req(None))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let client = res.json::<ClientResponse>().await?;
+    assert_eq!(client.response_types, vec!["code", "none"]);
+
+    let res = http
+        .delete(&url_client)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    Ok(())
+}
@@ -0,0 +1,120 @@
+use crate::common::{CLIENT_ID, get_backend_url};
+use pretty_assertions::assert_eq;
+use reqwest::Response;
+use reqwest::header::{CONTENT_SECURITY_POLICY, REFERRER_POLICY, X_FRAME_OPTIONS};
+use std::error::Error;
+
+mod common;
+
+fn csp(res: &Response) -> Vec<String> {
+    res.headers()
+        .get(CONTENT_SECURITY_POLICY)
+        .expect("a Content-Security-Policy header")
+        .to_str()
+        .unwrap()
+        .split(';')
+        .map(|d| d.trim().to_string())
+        .filter(|d| !d.is_empty())
+        .collect()
+}
+
+fn meta_csp(html: &str) -> Vec<Vec<String>> {
+    let mut policies = Vec::new();
+    let mut rest = html;
+    while let Some(idx) = rest.find("http-equiv=\"content-security-policy\"") {
+        let tag = &rest[idx..];
+        let end = tag.find('>').unwrap_or(tag.len());
+        let tag = &tag[..end];
+        if let Some(start) = tag.find("content=\"") {
+            let content = &tag[start + 9..];
+            let content = &content[..content.find('"').unwrap_or(content.len())];
+            policies.push(
+                content
+                    .split(';')
+                    .map(|d| d.trim().to_string())
+                    .filter(|d| !d.is_empty())
+                    .collect(),
+            );
+        }
+        rest = &rest[idx + end..];
+    }
+    policies
+}
+
+#[tokio::test]
+async fn test_security_headers() -> Result<(), Box<dyn Error>> {
+    let backend_url = get_backend_url();
+    let http = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?;
+
+    // SSR pages with the SvelteKit UI, which must not get a `script-src` in the header
+    for url in [
+        format!(
+            "{backend_url}/oidc/authorize?client_id={CLIENT_ID}\
+            &redirect_uri=http://localhost:3000/oidc/callback&response_type=code"
+        ),
+        format!("{backend_url}/account"),
+        format!("{backend_url}/"),
+    ] {
+        let res = http.get(&url).send().await?;
+        assert_eq!(res.status(), 200, "{url}");
+
+        let csp = csp(&res);
+        assert!(csp.contains(&"frame-ancestors 'none'".to_string()), "{url}");
+        assert!(csp.contains(&"object-src 'none'".to_string()), "{url}");
+        assert!(csp.contains(&"base-uri 'self'".to_string()), "{url}");
+        assert!(
+            !csp.iter()
+                .any(|d| d.starts_with("script-src") || d.starts_with("default-src")),
+            "{url}"
+        );
+        assert_eq!(res.headers().get(X_FRAME_OPTIONS).unwrap(), "DENY", "{url}");
+        assert_eq!(
+            res.headers().get(REFERRER_POLICY).unwrap(),
+            "no-referrer",
+            "{url}"
+        );
+
+        // the configured external origins must be allowed by the header
+        assert!(
+            csp.contains(&"img-src 'self' https://logos.example.com".to_string()),
+            "{url}"
+        );
+        assert!(
+            csp.contains(
+                &"style-src 'self' 'unsafe-inline' https://themes.example.com".to_string()
+            ),
+            "{url}"
+        );
+
+        let html = res.text().await?;
+        assert!(!html.contains("nonce=\""), "{url}");
+
+        // Browsers enforce the `<meta>` policy on top of the header. It must only add the
+        // `script-src`, or it would block the configured origins again.
+        for meta in meta_csp(&html) {
+            assert!(
+                meta.iter().all(|d| d.starts_with("script-src")),
+                "{url}: {meta:?}"
+            );
+        }
+    }
+
+    // the logout may be loaded inside an iframe by clients
+    let res = http
+        .get(format!("{backend_url}/oidc/logout"))
+        .send()
+        .await?;
+    let logout_csp = csp(&res);
+    assert!(logout_csp.contains(&"frame-ancestors 'self'".to_string()));
+    assert!(res.headers().get(X_FRAME_OPTIONS).is_none());
+    assert_eq!(res.headers().get(REFERRER_POLICY).unwrap(), "no-referrer");
+
+    // API responses get the same headers
+    let res = http.get(format!("{backend_url}/health")).send().await?;
+    assert!(csp(&res).contains(&"frame-ancestors 'none'".to_string()));
+    assert_eq!(res.headers().get(X_FRAME_OPTIONS).unwrap(), "DENY");
+
+    Ok(())
+}
@@ -0,0 +1,122 @@
+use crate::common::{USERNAME, get_auth_headers, get_backend_url, get_issuer};
+use pretty_assertions::assert_eq;
+use rauthy_api_types::auth_providers::{ProviderTestCallbackRequest, ProviderTestCallbackResponse};
+use rauthy_common::utils::base64_url_no_pad_encode;
+use serde_json::json;
+use std::error::Error;
+
+mod common;
+
+const NO_ACCOUNT_MSG: &str =
+    "There is no account for you yet. Please contact your administrator to get access.";
+
+/// The signature of the mock token is never validated.
+fn mock_id_token(sub: &str, email: &str) -> String {
+    let header = base64_url_no_pad_encode(br#"{"alg":"EdDSA","typ":"JWT"}"#);
+    let claims = base64_url_no_pad_encode(
+        json!({
+            "sub": sub,
+            "email": email,
+            "email_verified": true,
+            "name": "Provisioning Test",
+        })
+        .to_string()
+        .as_bytes(),
+    );
+    format!("{header}.{claims}.c2lnbmF0dXJl")
+}
+
+fn provider_req(auto_onboarding: bool) -> serde_json::Value {
+    let backend_url = get_backend_url();
+    let issuer = get_issuer();
+    json!({
+        "name": "Provisioning Test",
+        "typ": "oidc",
+        "enabled": true,
+        "issuer": "http://localhost:8080/provisioning_test",
+        "authorization_endpoint": format!("{issuer}/oidc/authorize"),
+        "token_endpoint": format!("{backend_url}/oidc/token"),
+        "userinfo_endpoint": format!("{issuer}/oidc/userinfo"),
+        "use_pkce": true,
+        "client_secret_basic": true,
+        "client_secret_post": false,
+        "auto_onboarding": auto_onboarding,
+        "auto_link": true,
+        "client_id": "provisioning_test",
+        "scope": "openid email",
+    })
+}
+
+#[tokio::test]
+async fn test_provider_auto_onboarding() -> Result<(), Box<dyn Error>> {
+    let auth_headers = get_auth_headers().await?;
+    let backend_url = get_backend_url();
+    let http = reqwest::Client::new();
+
+    let res = http
+        .post(format!("{backend_url}/providers/create"))
+        .headers(auth_headers.clone())
+        .json(&provider_req(false))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let id = res.json::<serde_json::Value>().await?["id"]
+        .as_str()
+        .expect("a provider id")
+        .to_string();
+    let url_provider = format!("{backend_url}/providers/{id}");
+
+    let test_callback = |email: &str| {
+        http.post(format!("{url_provider}/test_callback"))
+            .headers(auth_headers.clone())
+            .json(&ProviderTestCallbackRequest {
+                mock_id_token: mock_id_token("provisioning_test_sub", email),
+                simulate_new_user: false,
+            })
+            .send()
+    };
+
+    // an existing user can still log in
+    let res = test_callback(USERNAME).await?;
+    assert_eq!(res.status(), 200);
+    let res = res.json::<ProviderTestCallbackResponse>().await?;
+    assert!(res.errors.is_empty(), "{:?}", res.errors);
+    assert!(!res.would_create_user);
+    assert!(res.would_update_user);
+
+    // an unknown user is never provisioned
+    let res = test_callback("provisioning_test@localhost").await?;
+    assert_eq!(res.status(), 200);
+    let res = res.json::<ProviderTestCallbackResponse>().await?;
+    assert_eq!(res.errors, vec![NO_ACCOUNT_MSG.to_string()]);
+
+    // with the auto onboarding, the unknown user would be created
+    let res = http
+        .put(&url_provider)
+        .headers(auth_headers.clone())
+        .json(&provider_req(true))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    let res = test_callback("provisioning_test@localhost").await?;
+    assert_eq!(res.status(), 200);
+    let res = res.json::<ProviderTestCallbackResponse>().await?;
+    assert!(res.errors.is_empty(), "{:?}", res.errors);
+    assert!(res.would_create_user);
+
+    let res = test_callback(USERNAME).await?;
+    assert_eq!(res.status(), 200);
+    let res = res.json::<ProviderTestCallbackResponse>().await?;
+    assert!(res.errors.is_empty(), "{:?}", res.errors);
+    assert!(!res.would_create_user);
+
+    let res = http
+        .delete(&url_provider)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    Ok(())
+}
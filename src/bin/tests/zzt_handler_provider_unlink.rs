@@ -0,0 +1,281 @@
+use crate::common::{
+    USERNAME, get_auth_headers, get_backend_url, get_issuer, session_headers_with,
+};
+use pretty_assertions::assert_eq;
+use rauthy_api_types::generic::Language;
+use rauthy_api_types::users::{NewUserRequest, UpdateUserRequest, UserResponse};
+use rauthy_common::sha256;
+use rauthy_common::utils::base64_url_encode;
+use rauthy_error::ErrorResponse;
+use reqwest::header::{COOKIE, HeaderValue, LOCATION, SET_COOKIE};
+use serde_json::json;
+use std::error::Error;
+
+mod common;
+
+#[tokio::test]
+async fn test_provider_unlink() -> Result<(), Box<dyn Error>> {
+    let auth_headers = get_auth_headers().await?;
+    let backend_url = get_backend_url();
+    let http = reqwest::Client::new();
+
+    let res = http
+        .get(format!("{backend_url}/users/email/{USERNAME}"))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let user = res.json::<UserResponse>().await?;
+    assert!(user.auth_provider_id.is_none());
+
+    // a local account cannot be unlinked
+    let res = http
+        .post(format!("{backend_url}/users/{}/provider_unlink", user.id))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 400);
+    let err = res.json::<ErrorResponse>().await?;
+    assert_eq!(err.message, "This user is not linked to any auth provider");
+
+    // a provider without any linked users can be deleted without `force`
+    let issuer = get_issuer();
+    let res = http
+        .post(format!("{backend_url}/providers/create"))
+        .headers(auth_headers.clone())
+        .json(&json!({
+            "name": "Unlink Test",
+            "typ": "oidc",
+            "enabled": true,
+            "issuer": "http://localhost:8080/unlink_test",
+            "authorization_endpoint": format!("{issuer}/oidc/authorize"),
+            "token_endpoint": format!("{backend_url}/oidc/token"),
+            "userinfo_endpoint": format!("{issuer}/oidc/userinfo"),
+            "use_pkce": true,
+            "client_secret_basic": true,
+            "client_secret_post": false,
+            "auto_onboarding": false,
+            "auto_link": false,
+            "client_id": "unlink_test",
+            "scope": "openid email",
+        }))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let id = res.json::<serde_json::Value>().await?["id"]
+        .as_str()
+        .expect("a provider id")
+        .to_string();
+
+    let res = http
+        .delete(format!("{backend_url}/providers/{id}"))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_provider_link_session_binding() -> Result<(), Box<dyn Error>> {
+    let auth_headers = get_auth_headers().await?;
+    let backend_url = get_backend_url();
+    let issuer = get_issuer();
+    let http = reqwest::Client::new();
+
+    let res = http
+        .get(format!("{backend_url}/users/email/{USERNAME}"))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let admin = res.json::<UserResponse>().await?;
+
+    // a second user with its own session
+    let email = "provider-link@localhost";
+    let password = "123SuperSafe123";
+    let res = http
+        .post(format!("{backend_url}/users"))
+        .headers(auth_headers.clone())
+        .json(&NewUserRequest {
+            given_name: Some("Provider".to_string()),
+            family_name: Some("Link".to_string()),
+            email: email.to_string(),
+            language: Language::En,
+            roles: vec!["user".to_string()],
+            groups: None,
+            user_expires: None,
+            tz: None,
+        })
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let other = res.json::<UserResponse>().await?;
+
+    let res = http
+        .put(format!("{backend_url}/users/{}", other.id))
+        .headers(auth_headers.clone())
+        .json(&UpdateUserRequest {
+            email: other.email.clone(),
+            given_name: other.given_name.clone(),
+            family_name: other.family_name.clone(),
+            language: Some(Language::En),
+            password: Some(password.to_string()),
+            roles: other.roles.clone(),
+            groups: other.groups.clone(),
+            enabled: true,
+            email_verified: true,
+            user_expires: None,
+            user_values: None,
+        })
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let other_headers = session_headers_with(email, password).await;
+
+    let res = http
+        .post(format!("{backend_url}/providers/create"))
+        .headers(auth_headers.clone())
+        .json(&json!({
+            "name": "Link Test",
+            "typ": "oidc",
+            "enabled": true,
+            "issuer": "http://localhost:8080/link_test",
+            "authorization_endpoint": format!("{issuer}/oidc/authorize"),
+            "token_endpoint": format!("{backend_url}/oidc/token"),
+            "userinfo_endpoint": format!("{issuer}/oidc/userinfo"),
+            "use_pkce": true,
+            "client_secret_basic": true,
+            "client_secret_post": false,
+            "auto_onboarding": false,
+            "auto_link": false,
+            "client_id": "link_test",
+            "scope": "openid email",
+        }))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let provider_id = res.json::<serde_json::Value>().await?["id"]
+        .as_str()
+        .expect("a provider id")
+        .to_string();
+
+    let pkce_verifier = "oDXug9zfYqfz8ejcqMpALRPXfW8QhbKV2AVuScAt8xrLKDAmaRYQ4yRi2uqcH9ys";
+    let link_req = json!({
+        "client_id": "rauthy",
+        "redirect_uri": format!("{backend_url}/oidc/callback"),
+        "pow": "not-needed-for-links",
+        "provider_id": provider_id,
+        "pkce_challenge": base64_url_encode(sha256!(pkce_verifier.as_bytes())),
+    });
+
+    // a link can only be started and removed for the user of the current session
+    let url_link_other = format!("{backend_url}/users/{}/provider_link", other.id);
+    let res = http
+        .post(&url_link_other)
+        .headers(auth_headers.clone())
+        .json(&link_req)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 403);
+    let res = http
+        .delete(&url_link_other)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 403);
+
+    // a local account without a link cannot be unlinked
+    let url_link = format!("{backend_url}/users/{}/provider_link", admin.id);
+    let res = http
+        .delete(&url_link)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 400);
+
+    let res = http
+        .post(&url_link)
+        .headers(auth_headers.clone())
+        .json(&link_req)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 202);
+    let location = res.headers().get(LOCATION).unwrap().to_str()?.to_string();
+    let state = location
+        .split_once("state=")
+        .and_then(|(_, rest)| rest.split('&').next())
+        .expect("`state` in the location")
+        .to_string();
+    let link_cookies = res
+        .headers()
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .filter_map(|v| v.split_once(';').map(|(c, _)| c.to_string()))
+        .collect::<Vec<_>>();
+    let xsrf_token = res.text().await?;
+
+    let with_link_cookies = |mut headers: reqwest::header::HeaderMap| {
+        let session = headers.get(COOKIE).unwrap().to_str().unwrap().to_string();
+        let cookies = format!("{session}; {}", link_cookies.join("; "));
+        headers.insert(COOKIE, HeaderValue::from_str(&cookies).unwrap());
+        headers
+    };
+    let callback_req = json!({
+        "state": state,
+        "code": "SomeUpstreamCode",
+        "xsrf_token": xsrf_token,
+        "pkce_verifier": pkce_verifier,
+    });
+
+    // the link cookie of another user must be rejected
+    let res = http
+        .post(format!("{backend_url}/providers/callback"))
+        .headers(with_link_cookies(other_headers))
+        .json(&callback_req)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 403);
+    let err = res.json::<ErrorResponse>().await?;
+    assert_eq!(
+        err.message,
+        "The provider link does not belong to the current session"
+    );
+
+    // the callback has been deleted and cannot be re-used by the original session
+    let res = http
+        .post(format!("{backend_url}/providers/callback"))
+        .headers(with_link_cookies(auth_headers.clone()))
+        .json(&callback_req)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 404);
+
+    // nothing has been linked
+    for id in [&admin.id, &other.id] {
+        let res = http
+            .get(format!("{backend_url}/users/{id}"))
+            .headers(auth_headers.clone())
+            .send()
+            .await?;
+        assert_eq!(res.status(), 200);
+        assert!(res.json::<UserResponse>().await?.auth_provider_id.is_none());
+    }
+
+    let res = http
+        .delete(format!("{backend_url}/providers/{provider_id}"))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let res = http
+        .delete(format!("{backend_url}/users/{}", other.id))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    Ok(())
+}
@@ -0,0 +1,127 @@
+use crate::common::{CLIENT_ID, CLIENT_SECRET, get_auth_headers, get_backend_url, get_issuer};
+use pretty_assertions::assert_eq;
+use rauthy_api_types::auth_providers::{
+    ProviderTestResponse, ProviderTestResult, ProviderTestStep,
+};
+use serde_json::json;
+use std::error::Error;
+
+mod common;
+
+#[tokio::test]
+async fn test_provider_connectivity() -> Result<(), Box<dyn Error>> {
+    let auth_headers = get_auth_headers().await?;
+    let backend_url = get_backend_url();
+    let issuer = get_issuer();
+    let http = reqwest::Client::new();
+
+    // Rauthy itself acts as the upstream with the `init_client`
+    let provider_req = |issuer: &str, client_secret: &str| {
+        json!({
+            "name": "Connectivity Test",
+            "typ": "oidc",
+            "enabled": true,
+            "issuer": issuer,
+            "authorization_endpoint": format!("{issuer}/oidc/authorize"),
+            "token_endpoint": format!("{backend_url}/oidc/token"),
+            "userinfo_endpoint": format!("{issuer}/oidc/userinfo"),
+            "use_pkce": true,
+            "client_secret_basic": true,
+            "client_secret_post": false,
+            "auto_onboarding": false,
+            "auto_link": false,
+            "client_id": CLIENT_ID,
+            "client_secret": client_secret,
+            "scope": "openid",
+        })
+    };
+
+    let res = http
+        .post(format!("{backend_url}/providers/create"))
+        .headers(auth_headers.clone())
+        .json(&provider_req(&issuer, CLIENT_SECRET))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let id = res.json::<serde_json::Value>().await?["id"]
+        .as_str()
+        .expect("a provider id")
+        .to_string();
+    let url_provider = format!("{backend_url}/providers/{id}");
+    let url_test = format!("{url_provider}/test");
+
+    let res = http.post(&url_test).send().await?;
+    assert_eq!(res.status(), 401);
+
+    let res = http
+        .post(&url_test)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let body = res.text().await?;
+    assert!(!body.contains(CLIENT_SECRET));
+    let res = serde_json::from_str::<ProviderTestResponse>(&body)?;
+    assert!(res.success, "{res:?}");
+    assert_eq!(res.steps.len(), 2);
+    assert_eq!(res.steps[0].step, ProviderTestStep::Metadata);
+    assert_eq!(res.steps[0].result, ProviderTestResult::Success);
+    assert_eq!(res.steps[1].step, ProviderTestStep::TokenEndpoint);
+    assert!(matches!(
+        res.steps[1].result,
+        ProviderTestResult::Success | ProviderTestResult::GrantRejected
+    ));
+
+    // a wrong secret must be reported as such
+    let res = http
+        .put(&url_provider)
+        .headers(auth_headers.clone())
+        .json(&provider_req(&issuer, "invalid_secret"))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    let res = http
+        .post(&url_test)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let res = res.json::<ProviderTestResponse>().await?;
+    assert!(!res.success);
+    assert_eq!(res.steps[1].result, ProviderTestResult::InvalidCredentials);
+    assert_eq!(res.steps[1].status, Some(401));
+
+    // an unreachable upstream
+    let res = http
+        .put(&url_provider)
+        .headers(auth_headers.clone())
+        .json(&provider_req(
+            "http://localhost:1/unreachable",
+            CLIENT_SECRET,
+        ))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    let res = http
+        .post(&url_test)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let res = res.json::<ProviderTestResponse>().await?;
+    assert!(!res.success);
+    assert_eq!(res.steps[0].result, ProviderTestResult::ConnectionError);
+    assert!(res.steps[0].error.is_some());
+
+    // cleanup
+    let res = http
+        .delete(&url_provider)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    Ok(())
+}
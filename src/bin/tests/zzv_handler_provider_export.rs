@@ -0,0 +1,181 @@
+use crate::common::{CLIENT_ID, CLIENT_SECRET, get_auth_headers, get_backend_url, get_issuer};
+use pretty_assertions::assert_eq;
+use rauthy_api_types::auth_providers::{ProviderImportResponse, ProviderImportStatus};
+use serde_json::{Value, json};
+use std::error::Error;
+
+mod common;
+
+const PASSPHRASE: &str = "SuperSafePassphrase1337";
+
+#[tokio::test]
+async fn test_provider_export_import() -> Result<(), Box<dyn Error>> {
+    let auth_headers = get_auth_headers().await?;
+    let backend_url = get_backend_url();
+    let issuer = get_issuer();
+    let http = reqwest::Client::new();
+
+    let res = http
+        .post(format!("{backend_url}/providers/create"))
+        .headers(auth_headers.clone())
+        .json(&json!({
+            "name": "Export Test",
+            "typ": "oidc",
+            "enabled": true,
+            "issuer": issuer,
+            "authorization_endpoint": format!("{issuer}/oidc/authorize"),
+            "token_endpoint": format!("{issuer}/oidc/token"),
+            "userinfo_endpoint": format!("{issuer}/oidc/userinfo"),
+            "use_pkce": false,
+            "client_secret_basic": true,
+            "client_secret_post": false,
+            "auto_onboarding": false,
+            "auto_link": false,
+            "client_id": CLIENT_ID,
+            "client_secret": CLIENT_SECRET,
+            "scope": "openid email",
+        }))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let id = res.json::<Value>().await?["id"]
+        .as_str()
+        .expect("a provider id")
+        .to_string();
+
+    let url_export = format!("{backend_url}/providers/export");
+    let url_import = format!("{backend_url}/providers/import");
+
+    let res = http.get(&url_export).send().await?;
+    assert_eq!(res.status(), 401);
+
+    // without a passphrase, the secret must never be exported
+    let res = http
+        .get(&url_export)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let body = res.text().await?;
+    assert!(!body.contains(CLIENT_SECRET));
+    let export = serde_json::from_str::<Value>(&body)?;
+    assert!(export["salt"].is_null());
+    let entry = export["providers"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|p| p["name"] == "Export Test")
+        .expect("the provider in the export")
+        .clone();
+    assert!(entry["secrets"].is_null());
+    assert_eq!(entry["scope"], "openid email");
+
+    // an upsert by `issuer + client_id` keeps the current secret, and an invalid entry does
+    // not fail the others
+    let mut updated = entry.clone();
+    updated["name"] = json!("Export Test Updated");
+    let mut new = entry.clone();
+    new["name"] = json!("Export Test New");
+    new["client_id"] = json!("export-test-new");
+    new["use_pkce"] = json!(true);
+    let mut invalid = entry.clone();
+    invalid["client_id"] = json!("export-test-invalid");
+    invalid["client_secret_post"] = json!(true);
+    let res = http
+        .post(&url_import)
+        .headers(auth_headers.clone())
+        .json(&json!({
+            "version": export["version"],
+            "exported": export["exported"],
+            "salt": null,
+            "providers": [updated, new, invalid],
+        }))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let res = res.json::<ProviderImportResponse>().await?;
+    assert_eq!(res.results.len(), 3);
+    assert_eq!(res.results[0].status, ProviderImportStatus::Updated);
+    assert_eq!(res.results[0].id.as_deref(), Some(id.as_str()));
+    assert_eq!(res.results[1].status, ProviderImportStatus::Created);
+    assert_eq!(res.results[2].status, ProviderImportStatus::Failed);
+    assert!(res.results[2].error.is_some());
+    let id_new = res.results[1].id.clone().expect("the new provider id");
+
+    let res = http
+        .post(format!("{backend_url}/providers"))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let providers = res.json::<Vec<Value>>().await?;
+    let provider = providers.iter().find(|p| p["id"] == id.as_str()).unwrap();
+    assert_eq!(provider["name"], "Export Test Updated");
+    assert_eq!(provider["client_secret"], CLIENT_SECRET);
+
+    // with a passphrase, the secrets are only included encrypted
+    let res = http
+        .get(&url_export)
+        .headers(auth_headers.clone())
+        .header("x-rauthy-export-passphrase", PASSPHRASE)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let body = res.text().await?;
+    assert!(!body.contains(CLIENT_SECRET));
+    let export = serde_json::from_str::<Value>(&body)?;
+    assert!(export["salt"].is_string());
+
+    let res = http
+        .post(&url_import)
+        .headers(auth_headers.clone())
+        .json(&export)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 400);
+
+    let res = http
+        .post(&url_import)
+        .headers(auth_headers.clone())
+        .header("x-rauthy-export-passphrase", "WrongPassphrase1337")
+        .json(&export)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let res = res.json::<ProviderImportResponse>().await?;
+    assert!(
+        res.results
+            .iter()
+            .all(|r| r.status == ProviderImportStatus::Failed)
+    );
+
+    let res = http
+        .post(&url_import)
+        .headers(auth_headers.clone())
+        .header("x-rauthy-export-passphrase", PASSPHRASE)
+        .json(&export)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let res = res.json::<ProviderImportResponse>().await?;
+    for id in [&id, &id_new] {
+        let result = res
+            .results
+            .iter()
+            .find(|r| r.id.as_ref() == Some(id))
+            .expect("the provider in the import result");
+        assert_eq!(result.status, ProviderImportStatus::Updated);
+    }
+
+    // cleanup
+    for id in [id, id_new] {
+        let res = http
+            .delete(format!("{backend_url}/providers/{id}"))
+            .headers(auth_headers.clone())
+            .send()
+            .await?;
+        assert_eq!(res.status(), 200);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,427 @@
+use crate::common::{
+    code_state_from_headers, cookie_csrf_headers_from_res_direct, get_auth_headers,
+    get_backend_url, get_issuer, get_solved_pow,
+};
+use chrono::Utc;
+use pretty_assertions::assert_eq;
+use rauthy_api_types::api_keys::{AccessGroup, AccessRights, ApiKeyAccess, ApiKeyRequest};
+use rauthy_api_types::clients::NewClientRequest;
+use rauthy_api_types::generic::Language;
+use rauthy_api_types::oidc::LoginRequest;
+use rauthy_api_types::users::{NewUserRequest, UpdateUserRequest, UserResponse};
+use rauthy_common::sha256;
+use rauthy_common::utils::base64_url_encode;
+use reqwest::Url;
+use reqwest::header::{AUTHORIZATION, COOKIE, HeaderValue, LOCATION, SET_COOKIE};
+use serde_json::{Value, json};
+use std::error::Error;
+use std::time::Duration;
+
+mod common;
+
+const CLIENT_ID: &str = "upstream_admin_claim";
+const EMAIL: &str = "upstream.admin@localhost.de";
+const PASSWORD: &str = "123SuperSafe123";
+const API_KEY: &str = "provider-admin-claim";
+const PKCE_VERIFIER: &str = "6g0uFfXWEEdJGNHeBpqFVJ5uS4tXZ9h8WK3rjLQc2T7BbnKxmAzpvY1oDsaUNeRw";
+
+fn provider_req(admin_claim_value: &str) -> Value {
+    let issuer = get_issuer();
+    json!({
+        "name": "Admin Claim Test",
+        "typ": "oidc",
+        "enabled": true,
+        "issuer": issuer,
+        "authorization_endpoint": format!("{issuer}/oidc/authorize"),
+        "token_endpoint": format!("{issuer}/oidc/token"),
+        "userinfo_endpoint": format!("{issuer}/oidc/userinfo"),
+        "jwks_endpoint": format!("{issuer}/oidc/certs"),
+        "use_pkce": true,
+        "client_secret_basic": false,
+        "client_secret_post": false,
+        "auto_onboarding": false,
+        "auto_link": true,
+        "client_id": CLIENT_ID,
+        "scope": "openid email",
+        "admin_claim_path": "$.email",
+        "admin_claim_value": admin_claim_value,
+    })
+}
+
+/// Logs in via the provider, which uses this Rauthy instance as its own upstream.
+async fn upstream_login(http: &reqwest::Client, provider_id: &str) -> Result<(), Box<dyn Error>> {
+    let backend_url = get_backend_url();
+    let pkce_challenge = base64_url_encode(sha256!(PKCE_VERIFIER.as_bytes()));
+
+    let res = http
+        .post(format!("{backend_url}/oidc/session"))
+        .send()
+        .await?;
+    let headers = cookie_csrf_headers_from_res_direct(res).await?;
+    let res = http
+        .post(format!("{backend_url}/providers/login"))
+        .headers(headers.clone())
+        .json(&json!({
+            "client_id": "init_client",
+            "redirect_uri": "http://localhost:3000/oidc/callback",
+            "code_challenge": pkce_challenge,
+            "code_challenge_method": "S256",
+            "pow": get_solved_pow().await,
+            "provider_id": provider_id,
+            "pkce_challenge": pkce_challenge,
+        }))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 202);
+    let callback_cookie = res
+        .headers()
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .filter_map(|v| v.split_once(';').map(|(c, _)| c.to_string()))
+        .find(|c| c.starts_with("__Host-UpstreamAuthCallback="))
+        .expect("the upstream callback cookie");
+    let location = Url::parse(res.headers().get(LOCATION).unwrap().to_str()?)?;
+    let xsrf_token = res.text().await?;
+    let param = |name: &str| {
+        location
+            .query_pairs()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.to_string())
+    };
+
+    // the login at the upstream
+    let res = http
+        .post(format!("{backend_url}/oidc/session"))
+        .send()
+        .await?;
+    let upstream_headers = cookie_csrf_headers_from_res_direct(res).await?;
+    let res = http
+        .post(location.as_str())
+        .headers(upstream_headers)
+        .json(&LoginRequest {
+            email: EMAIL.to_string(),
+            password: Some(PASSWORD.to_string()),
+            pow: get_solved_pow().await,
+            client_id: CLIENT_ID.to_string(),
+            redirect_uri: param("redirect_uri").expect("the redirect_uri"),
+            scopes: Some(vec!["openid".to_string(), "email".to_string()]),
+            state: param("state"),
+            nonce: param("nonce"),
+            code_challenge: param("code_challenge"),
+            code_challenge_method: param("code_challenge_method"),
+            resource: None,
+        })
+        .send()
+        .await?;
+    assert_eq!(res.status(), 202);
+    let (code, state) = code_state_from_headers(res)?;
+
+    let mut headers = headers;
+    let session_cookie = headers.get(COOKIE).unwrap().to_str()?.to_string();
+    headers.insert(
+        COOKIE,
+        HeaderValue::from_str(&format!("{session_cookie}; {callback_cookie}"))?,
+    );
+    let res = http
+        .post(format!("{backend_url}/providers/callback"))
+        .headers(headers)
+        .json(&json!({
+            "state": state.expect("the state"),
+            "code": code,
+            "xsrf_token": xsrf_token,
+            "pkce_verifier": PKCE_VERIFIER,
+        }))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 202, "{}", res.text().await?);
+
+    Ok(())
+}
+
+/// Events are persisted in the background.
+async fn find_admin_role_event(
+    http: &reqwest::Client,
+    from: i64,
+    action: &str,
+) -> Result<Value, Box<dyn Error>> {
+    let auth_headers = get_auth_headers().await?;
+    for _ in 0..20 {
+        let res = http
+            .post(format!("{}/events", get_backend_url()))
+            .headers(auth_headers.clone())
+            .json(&json!({
+                "from": from,
+                "level": "critical",
+                "typ": "UpstreamAdminRoleChange",
+            }))
+            .send()
+            .await?;
+        assert_eq!(res.status(), 200);
+        let events = res.json::<Vec<Value>>().await?;
+        if let Some(event) = events.into_iter().find(|e| {
+            e["text"]
+                .as_str()
+                .is_some_and(|t| t.contains(EMAIL) && t.contains(action))
+        }) {
+            return Ok(event);
+        }
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+    panic!("no `UpstreamAdminRoleChange` event for `{action}`");
+}
+
+async fn is_admin(http: &reqwest::Client, user_id: &str) -> Result<bool, Box<dyn Error>> {
+    let res = http
+        .get(format!("{}/users/{user_id}", get_backend_url()))
+        .headers(get_auth_headers().await?)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let user = res.json::<UserResponse>().await?;
+    Ok(user.roles.iter().any(|r| r == "rauthy_admin"))
+}
+
+#[tokio::test]
+async fn test_provider_admin_claim() -> Result<(), Box<dyn Error>> {
+    let auth_headers = get_auth_headers().await?;
+    let backend_url = get_backend_url();
+    let http = reqwest::Client::new();
+    let start = Utc::now().timestamp() - 1;
+
+    // the upstream client and user on this same instance
+    let res = http
+        .post(format!("{backend_url}/clients"))
+        .headers(auth_headers.clone())
+        .json(&NewClientRequest {
+            id: CLIENT_ID.to_string(),
+            secret: None,
+            name: Some("Upstream Admin Claim".to_string()),
+            confidential: false,
+            redirect_uris: vec![format!("{backend_url}/providers/callback")],
+            post_logout_redirect_uris: None,
+            client_type: None,
+        })
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    let res = http
+        .post(format!("{backend_url}/users"))
+        .headers(auth_headers.clone())
+        .json(&NewUserRequest {
+            given_name: Some("Upstream".to_string()),
+            family_name: Some("Admin".to_string()),
+            email: EMAIL.to_string(),
+            language: Language::En,
+            roles: vec!["user".to_string()],
+            groups: None,
+            user_expires: None,
+            tz: None,
+        })
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let user = res.json::<UserResponse>().await?;
+    let res = http
+        .put(format!("{backend_url}/users/{}", user.id))
+        .headers(auth_headers.clone())
+        .json(&UpdateUserRequest {
+            email: user.email.clone(),
+            given_name: user.given_name.clone(),
+            family_name: user.family_name.clone(),
+            language: Some(Language::En),
+            password: Some(PASSWORD.to_string()),
+            roles: user.roles.clone(),
+            groups: None,
+            enabled: true,
+            email_verified: true,
+            user_expires: None,
+            user_values: None,
+        })
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    // the admin claim can never be set or changed via API key
+    let res = http
+        .post(format!("{backend_url}/api_keys"))
+        .headers(auth_headers.clone())
+        .json(&ApiKeyRequest {
+            name: API_KEY.to_string(),
+            exp: None,
+            access: vec![ApiKeyAccess {
+                group: AccessGroup::AuthProviders,
+                access_rights: vec![
+                    AccessRights::Read,
+                    AccessRights::Create,
+                    AccessRights::Update,
+                    AccessRights::Delete,
+                ],
+            }],
+        })
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let api_key = format!("API-Key {}", res.text().await?);
+
+    let res = http
+        .post(format!("{backend_url}/providers/create"))
+        .header(AUTHORIZATION, &api_key)
+        .json(&provider_req(EMAIL))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 403);
+
+    let res = http
+        .post(format!("{backend_url}/providers/create"))
+        .headers(auth_headers.clone())
+        .json(&provider_req(EMAIL))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let id = res.json::<Value>().await?["id"]
+        .as_str()
+        .expect("a provider id")
+        .to_string();
+    let url_provider = format!("{backend_url}/providers/{id}");
+
+    let res = http
+        .put(&url_provider)
+        .header(AUTHORIZATION, &api_key)
+        .json(&provider_req("nobody@localhost.de"))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 403);
+
+    // other values can still be updated via API key, as long as the admin claim is untouched
+    let mut req = provider_req(EMAIL);
+    req["name"] = json!("Admin Claim Test Updated");
+    let res = http
+        .put(&url_provider)
+        .header(AUTHORIZATION, &api_key)
+        .json(&req)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    // the same applies to upstream role mappings to `rauthy_admin`
+    let res = http
+        .get(format!("{backend_url}/roles"))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let roles = res.json::<Vec<Value>>().await?;
+    let role_id = |name: &str| {
+        roles.iter().find(|r| r["name"] == name).unwrap()["id"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    };
+    let role_admin = role_id("rauthy_admin");
+    let role_user = role_id("user");
+    let mapping_req = |role_id: &str, claim_value: &str| {
+        json!({
+            "claim_path": "$.email",
+            "claim_value": claim_value,
+            "role_id": role_id,
+        })
+    };
+    let url_mappings = format!("{url_provider}/role_mappings");
+
+    let res = http
+        .post(&url_mappings)
+        .header(AUTHORIZATION, &api_key)
+        .json(&mapping_req(&role_admin, "nobody@localhost.de"))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 403);
+
+    let res = http
+        .post(&url_mappings)
+        .header(AUTHORIZATION, &api_key)
+        .json(&mapping_req(&role_user, "nobody@localhost.de"))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let mapping_id = res.json::<Value>().await?["id"]
+        .as_str()
+        .expect("a mapping id")
+        .to_string();
+    let url_mapping = format!("{url_mappings}/{mapping_id}");
+
+    let res = http
+        .put(&url_mapping)
+        .header(AUTHORIZATION, &api_key)
+        .json(&mapping_req(&role_admin, "nobody@localhost.de"))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 403);
+
+    let res = http
+        .put(&url_mapping)
+        .headers(auth_headers.clone())
+        .json(&mapping_req(&role_admin, "nobody@localhost.de"))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    // the claim of an existing admin mapping cannot be changed either
+    let res = http
+        .put(&url_mapping)
+        .header(AUTHORIZATION, &api_key)
+        .json(&mapping_req(&role_admin, EMAIL))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 403);
+
+    let res = http
+        .delete(&url_mapping)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    // the first login links the user and grants the admin role
+    assert!(!is_admin(&http, &user.id).await?);
+    upstream_login(&http, &id).await?;
+    assert!(is_admin(&http, &user.id).await?);
+    let event = find_admin_role_event(&http, start, "granted to").await?;
+    assert_eq!(event["level"], "critical");
+    assert!(event["text"].as_str().unwrap().contains("Admin Claim Test"));
+
+    // it is revoked as soon as the claim does not match anymore
+    let res = http
+        .put(&url_provider)
+        .headers(auth_headers.clone())
+        .json(&provider_req("nobody@localhost.de"))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    upstream_login(&http, &id).await?;
+    assert!(!is_admin(&http, &user.id).await?);
+    find_admin_role_event(&http, start, "revoked from").await?;
+
+    // cleanup
+    let res = http
+        .delete(format!("{url_provider}?force=true"))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    for url in [
+        format!("{backend_url}/users/{}", user.id),
+        format!("{backend_url}/clients/{CLIENT_ID}"),
+        format!("{backend_url}/api_keys/{API_KEY}"),
+    ] {
+        let res = http
+            .delete(url)
+            .headers(auth_headers.clone())
+            .send()
+            .await?;
+        assert_eq!(res.status(), 200);
+    }
+
+    Ok(())
+}
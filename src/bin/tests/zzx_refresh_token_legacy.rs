@@ -0,0 +1,91 @@
+use crate::common::{
+    CLIENT_ID, CLIENT_SECRET, get_backend_url, get_token_set_init_client, token_req,
+};
+use hiqlite::macros::params;
+use pretty_assertions::assert_eq;
+use rauthy_api_types::oidc::TokenRequest;
+use rauthy_common::sha256;
+use std::error::Error;
+
+mod common;
+
+/// Direct access to the database of the test backend, because legacy rows can't be created via
+/// the API anymore.
+enum Db {
+    Hiqlite(hiqlite::Client),
+    Postgres(tokio_postgres::Client),
+}
+
+impl Db {
+    async fn connect() -> Result<Self, Box<dyn Error>> {
+        if std::env::var("HIQLITE").as_deref() == Ok("false") {
+            let (client, conn) = tokio_postgres::connect(
+                "host=localhost user=rauthy password=123SuperSafe dbname=rauthy",
+                tokio_postgres::NoTls,
+            )
+            .await?;
+            tokio::spawn(conn);
+            Ok(Self::Postgres(client))
+        } else {
+            let client = hiqlite::Client::remote(
+                vec!["localhost:8200".to_string()],
+                false,
+                false,
+                "SuperSecureSecret1337".to_string(),
+                false,
+            )
+            .await?;
+            Ok(Self::Hiqlite(client))
+        }
+    }
+
+    async fn set_id(&self, id_new: &str, id_old: &str) -> Result<(), Box<dyn Error>> {
+        let sql = "UPDATE refresh_tokens SET id = $1 WHERE id = $2";
+        let rows = match self {
+            Self::Hiqlite(client) => client.execute(sql, params!(id_new, id_old)).await?,
+            Self::Postgres(client) => client.execute(sql, &[&id_new, &id_old]).await? as usize,
+        };
+        assert_eq!(rows, 1);
+        Ok(())
+    }
+
+    async fn exists(&self, id: &str) -> Result<bool, Box<dyn Error>> {
+        let sql = "SELECT COUNT(*) AS count FROM refresh_tokens WHERE id = $1";
+        let count: i64 = match self {
+            Self::Hiqlite(client) => client.query_raw_one(sql, params!(id)).await?.get("count"),
+            Self::Postgres(client) => client.query_one(sql, &[&id]).await?.get("count"),
+        };
+        Ok(count > 0)
+    }
+}
+
+#[tokio::test]
+async fn test_legacy_refresh_token_rehash() -> Result<(), Box<dyn Error>> {
+    let db = Db::connect().await?;
+
+    let ts = get_token_set_init_client().await;
+    let refresh_token = ts.refresh_token.expect("a refresh token");
+    let (_, validation_str) = refresh_token.split_at(refresh_token.len() - 49);
+    let id = hex::encode(sha256!(validation_str.as_bytes()));
+
+    // turn it into a row like it was saved by older versions
+    assert!(db.exists(&id).await?);
+    db.set_id(validation_str, &id).await?;
+    assert!(db.exists(validation_str).await?);
+
+    let res = reqwest::Client::new()
+        .post(format!("{}/oidc/token", get_backend_url()))
+        .form(&TokenRequest {
+            refresh_token: Some(refresh_token.clone()),
+            ..token_req("refresh_token", CLIENT_ID, CLIENT_SECRET)
+        })
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    // the legacy row has been rewritten and the plain text value can't be found anymore
+    assert!(!db.exists(validation_str).await?);
+    assert!(db.exists(&id).await?);
+
+    Ok(())
+}
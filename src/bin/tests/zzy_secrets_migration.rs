@@ -0,0 +1,127 @@
+use crate::common::{get_auth_headers, get_backend_url};
+use pretty_assertions::assert_eq;
+use rauthy_api_types::api_keys::{AccessGroup, AccessRights, ApiKeyAccess, ApiKeyRequest};
+use reqwest::StatusCode;
+use reqwest::header::{AUTHORIZATION, HeaderMap};
+use serde::Deserialize;
+use std::error::Error;
+
+mod common;
+
+/// The active key from the test config
+const KEY_ACTIVE: &str = "bVCyTsGaggVy5yqQ";
+/// The second, inactive key from the test config
+const KEY_OTHER: &str = "mIgr8Tst2Key";
+/// Must match the `BATCH_SIZE` of the secrets migration
+const BATCH_SIZE: u32 = 100;
+
+// Re-defined here, because the `SecretsMigrationProgress` only derives `Serialize`.
+#[derive(Debug, Deserialize)]
+struct Progress {
+    api_keys: u32,
+    finished: bool,
+    error: Option<String>,
+}
+
+async fn migrate(
+    client: &reqwest::Client,
+    headers: &HeaderMap,
+    from: &str,
+    to: &str,
+) -> Result<Vec<Progress>, Box<dyn Error>> {
+    let url = format!(
+        "{}/encryption/migrate_secrets?from_key_id={from}&to_key_id={to}",
+        get_backend_url()
+    );
+    let res = client.post(&url).headers(headers.clone()).send().await?;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // The stream is closed as soon as the migration has finished.
+    let body = res.text().await?;
+    let mut progress = Vec::new();
+    for line in body.lines() {
+        if let Some(data) = line.strip_prefix("data: ") {
+            progress.push(serde_json::from_str::<Progress>(data)?);
+        }
+    }
+    Ok(progress)
+}
+
+#[tokio::test]
+async fn test_secrets_migration() -> Result<(), Box<dyn Error>> {
+    let client = reqwest::Client::new();
+    let auth_headers = get_auth_headers().await?;
+    let url_api_keys = format!("{}/api_keys", get_backend_url());
+
+    // invalid key combinations are rejected before anything is touched
+    for (from, to) in [(KEY_ACTIVE, KEY_ACTIVE), (KEY_ACTIVE, "doesNotExist")] {
+        let url = format!(
+            "{}/encryption/migrate_secrets?from_key_id={from}&to_key_id={to}",
+            get_backend_url()
+        );
+        let res = client.post(&url).send().await?;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        let res = client
+            .post(&url)
+            .headers(auth_headers.clone())
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    // create enough API keys to need more than a single batch
+    let mut names = Vec::with_capacity(BATCH_SIZE as usize + 1);
+    let mut key_header = String::default();
+    for i in 0..=BATCH_SIZE {
+        let payload = ApiKeyRequest {
+            name: format!("secrets-migration-{i}"),
+            exp: None,
+            access: vec![ApiKeyAccess {
+                group: AccessGroup::Groups,
+                access_rights: vec![AccessRights::Read],
+            }],
+        };
+        let res = client
+            .post(&url_api_keys)
+            .headers(auth_headers.clone())
+            .json(&payload)
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        if i == 0 {
+            key_header = format!("API-Key {}", res.text().await?);
+        }
+        names.push(payload.name);
+    }
+
+    let url_groups = format!("{}/groups", get_backend_url());
+    for (from, to) in [(KEY_ACTIVE, KEY_OTHER), (KEY_OTHER, KEY_ACTIVE)] {
+        let progress = migrate(&client, &auth_headers, from, to).await?;
+
+        // each batch reports its progress on its own
+        assert!(progress.iter().any(|p| p.api_keys == BATCH_SIZE));
+        let last = progress.last().unwrap();
+        assert!(last.finished);
+        assert_eq!(last.error, None);
+        assert!(last.api_keys > BATCH_SIZE);
+
+        // the API key must still be usable with the re-encrypted secret and cleared caches
+        let res = client
+            .get(&url_groups)
+            .header(AUTHORIZATION, &key_header)
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    for name in names {
+        let res = client
+            .delete(format!("{url_api_keys}/{name}"))
+            .headers(auth_headers.clone())
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    Ok(())
+}
@@ -3,6 +3,7 @@ use chrono::Utc;
 use pretty_assertions::assert_eq;
 use rauthy_api_types::blacklist::IpBlacklistRequest;
 use reqwest::StatusCode;
+use reqwest::header::CONTENT_SECURITY_POLICY;
 use std::error::Error;
 use std::net::IpAddr;
 use std::ops::Add;
@@ -64,10 +65,29 @@ async fn test_ip_blacklist() -> Result<(), Box<dyn Error>> {
         .send()
         .await?;
     assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+    let csp = res
+        .headers()
+        .get(CONTENT_SECURITY_POLICY)
+        .expect("a Content-Security-Policy header")
+        .to_str()?
+        .to_string();
     let html = res.text().await?;
     assert!(html.contains("has been blocked"));
     assert!(html.contains("This incident has been reported"));
 
+    // the inline script must be allowed by the per-request nonce
+    let (_, nonce) = csp
+        .split_once("script-src 'nonce-")
+        .expect("a script-src nonce in the CSP");
+    let (nonce, _) = nonce.split_once('\'').unwrap();
+    assert_eq!(nonce.len(), 22);
+    assert!(html.contains(&format!(r#"<script nonce="{nonce}">"#)));
+    let script_src = csp
+        .split(';')
+        .find(|d| d.trim().starts_with("script-src"))
+        .unwrap();
+    assert!(!script_src.contains("unsafe-inline"));
+
     // - wait 1 second
     tokio::time::sleep(Duration::from_secs(1)).await;
 
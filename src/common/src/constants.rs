@@ -42,10 +42,14 @@ pub static BUILD_TIME: LazyLock<DateTime<Utc>> = LazyLock::new(|| {
 pub const RAUTHY_VERSION: &str = env!("CARGO_PKG_VERSION");
 pub static CONTENT_TYPE_WEBP: &str = "image/webp";
 pub static HEADER_DPOP_NONCE: &str = "DPoP-Nonce";
+pub static HEADER_ERASURE_CONFIRM: &str = "x-rauthy-erasure-confirm";
+pub static HEADER_EXPORT_PASSPHRASE: &str = "x-rauthy-export-passphrase";
 pub static HEADER_ALLOW_ALL_ORIGINS: (&str, &str) = ("access-control-allow-origin", "*");
 pub static HEADER_HTML: (&str, &str) = ("content-type", "text/html;charset=utf-8");
+pub static HEADER_MIGRATION_PASSPHRASE: &str = "x-rauthy-migration-passphrase";
 pub static HEADER_JSON: (&str, &str) = ("content-type", "application/json");
 pub static HEADER_RETRY_NOT_BEFORE: &str = "x-retry-not-before";
+pub static HEADER_SEARCH_EXACT_MATCH: &str = "x-rauthy-search-exact-match";
 pub static APPLICATION_JSON: &str = "application/json";
 pub static APPLICATION_JSON_SCIM: &str = "application/scim+json";
 pub static TEXT_TURTLE: &str = "text/turtle";
@@ -58,9 +62,12 @@ pub static COOKIE_SESSION: &str = "RauthySession";
 pub static COOKIE_SESSION_FED_CM: &str = "RauthySessionFedCM";
 pub static COOKIE_MFA: &str = "RauthyMfa";
 pub static COOKIE_LOCALE: &str = "locale";
+pub static COOKIE_MAINTENANCE_BYPASS: &str = "RauthyMaintenanceBypass";
 pub static COOKIE_UPSTREAM_CALLBACK: &str = "UpstreamAuthCallback";
+pub static COOKIE_UPSTREAM_LOGOUT: &str = "UpstreamLogout";
 pub static PROVIDER_ATPROTO: &str = "atproto";
 pub static PROVIDER_LINK_COOKIE: &str = "rauthy-provider-link";
+pub static PROVIDER_RESUME_COOKIE: &str = "rauthy-provider-resume";
 pub static PWD_RESET_COOKIE: &str = "rauthy-pwd-reset";
 pub static APP_ID_HEADER: &str = "mfa-app-id";
 pub static CSRF_HEADER: &str = "x-csrf-token";
@@ -77,34 +84,60 @@ pub static DEVICE_KEY_LENGTH: u8 = 64;
 pub const CLIENT_CLAIMS_MAX_LEN: usize = 1024;
 pub static EVENTS_LATEST_LIMIT: u16 = 100;
 pub static GRANT_TYPE_DEVICE_CODE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+/// All `response_type`s the authorize endpoint can handle. Each client allows a subset of these.
+pub const RESPONSE_TYPES_SUPPORTED: [&str; 2] = ["code", "none"];
 pub const UPSTREAM_AUTH_CALLBACK_TIMEOUT_SECS: u16 = 300;
+/// The resume cookie outlives the upstream callback by this amount of seconds to be able to
+/// offer a retry after a timeout.
+pub const UPSTREAM_AUTH_RESUME_GRACE_SECS: u16 = 3600;
 pub const CACHE_TTL_APP: Option<i64> = Some(43200);
+/// Keeps admins from hammering the expiry queries when the report is polled by a dashboard.
+pub const CACHE_TTL_EXPIRING_REPORT: Option<i64> = Some(60);
+/// `last_used` of a user grant is written to the database at most once per this window.
+pub const CACHE_TTL_GRANT_LAST_USED: Option<i64> = Some(60);
 pub const CACHE_TTL_AUTH_PROVIDER_CALLBACK: Option<i64> =
     Some(UPSTREAM_AUTH_CALLBACK_TIMEOUT_SECS as i64);
 pub const CACHE_TTL_SESSION: Option<i64> = Some(14400);
+// must outlive auth codes, which may await a passkey and ToS accept after a rotation
+pub const CACHE_TTL_SESSION_ROTATED: Option<i64> = Some(3600);
 pub const CACHE_TTL_USER: Option<i64> = Some(600);
+/// A full users reindex is allowed only once per this amount of seconds.
+pub const USERS_REINDEX_RATE_LIMIT_SECS: u16 = 3600;
+pub const CACHE_TTL_USERS_REINDEX: Option<i64> = Some(USERS_REINDEX_RATE_LIMIT_SECS as i64);
 
 pub static IDX_APP_VERSION: &str = "rauthy_app_version";
 pub static IDX_AUTH_PROVIDER: &str = "auth_provider_";
+pub static IDX_AUTH_PROVIDER_HEALTH: &str = "provider_health_";
 pub static IDX_AUTH_PROVIDER_LOGO: &str = "auth_provider_logo_";
 pub static IDX_AUTH_PROVIDER_TEMPLATE: &str = "provider_json_tpl";
+pub static IDX_CLAIMS_WEBHOOK: &str = "claims_webhook_";
 pub static IDX_CLIENTS: &str = "clients_";
+pub static IDX_CLIENT_CLAIMS_WEBHOOK: &str = "client_claims_webhook_";
 pub static IDX_CLIENT_LOGO: &str = "client_logo_";
+pub static IDX_EXPIRING_REPORT: &str = "expiring_report_";
+pub static IDX_GRANT_LAST_USED: &str = "grant_last_used_";
 pub static IDX_GROUPS: &str = "groups_";
+pub static IDX_I18N_OVERRIDES: &str = "i18n_overrides";
 pub static IDX_JWK_KID: &str = "jwk_kid_";
 pub static IDX_JWK_LATEST: &str = "jwk_latest_";
 pub static IDX_JWKS: &str = "jkws_";
+pub static IDX_LOGIN_PAGE_CONTEXT: &str = "login_ctx_";
 pub static IDX_LOGIN_TIME: &str = "login_time_";
+pub static IDX_MAINTENANCE_MODE: &str = "maintenance_mode";
 pub static IDX_MFA_MOD: &str = "mfa_mod_";
 pub static IDX_PASSWORD_RULES: &str = "password_rules_";
 pub static IDX_ROLES: &str = "roles_";
 pub static IDX_SCOPES: &str = "scopes_";
 pub static IDX_SESSIONS: &str = "sessions";
 pub static IDX_SMTP_OAUTH_TOKEN: &str = "smtp_oauth_token";
+pub static IDX_UPSTREAM_LOGOUT: &str = "upstream_logout_";
+pub static IDX_UPSTREAM_SESSION: &str = "upstream_session_";
 pub static IDX_USERS: &str = "users_";
 pub static IDX_USER_COUNT: &str = "users_count_total";
+pub static IDX_USERS_REINDEX: &str = "users_reindex_all";
 pub static IDX_USERS_VALUES: &str = "users_values_";
 pub static IDX_USER_ATTR_CONFIG: &str = "user_attrs_";
+pub static IDX_USER_REG_CONFIG: &str = "user_reg_config";
 pub static IDX_WEBAUTHN: &str = "webauthn_";
 
 pub static APP_START: LazyLock<DateTime<Utc>> = LazyLock::new(Utc::now);
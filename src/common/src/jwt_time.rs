@@ -0,0 +1,114 @@
+use chrono::Utc;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use tracing::debug;
+
+/// The default leeway in seconds for all time based claims of inbound JWTs.
+pub const JWT_LEEWAY_DEFAULT: u16 = 30;
+
+/// The time based claims of an inbound JWT. Missing claims are not validated.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct JwtTimeClaims {
+    pub iat: Option<i64>,
+    pub exp: Option<i64>,
+    pub nbf: Option<i64>,
+}
+
+impl JwtTimeClaims {
+    /// Validates `iat`, `exp` and `nbf` against the current time while allowing a clock skew of
+    /// `leeway` seconds in either direction.
+    #[inline]
+    pub fn validate(&self, leeway: u16) -> Result<(), ErrorResponse> {
+        self.validate_at(Utc::now().timestamp(), leeway)
+    }
+
+    fn validate_at(&self, now: i64, leeway: u16) -> Result<(), ErrorResponse> {
+        let leeway = leeway as i64;
+
+        if let Some(iat) = self.iat {
+            if iat - leeway > now {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::JwtToken,
+                    "Token was issued in the future",
+                ));
+            }
+            if iat > now {
+                debug!(
+                    skew = iat - now,
+                    "JWT `iat` is valid only because of the leeway - check for drifting clocks"
+                );
+            }
+        }
+
+        if let Some(exp) = self.exp {
+            if exp + leeway < now {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::JwtToken,
+                    "Token has expired",
+                ));
+            }
+            if exp < now {
+                debug!(
+                    skew = now - exp,
+                    "JWT `exp` is valid only because of the leeway - check for drifting clocks"
+                );
+            }
+        }
+
+        if let Some(nbf) = self.nbf {
+            if nbf - leeway > now {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::JwtToken,
+                    "Token is not valid yet",
+                ));
+            }
+            if nbf > now {
+                debug!(
+                    skew = nbf - now,
+                    "JWT `nbf` is valid only because of the leeway - check for drifting clocks"
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jwt_time_leeway_boundaries() {
+        let now = 1_700_000_000;
+        let leeway = 30;
+
+        let exp = |exp| JwtTimeClaims {
+            exp: Some(exp),
+            ..Default::default()
+        };
+        assert!(exp(now).validate_at(now, leeway).is_ok());
+        assert!(exp(now - 30).validate_at(now, leeway).is_ok());
+        assert!(exp(now - 31).validate_at(now, leeway).is_err());
+        assert!(exp(now - 1).validate_at(now, 0).is_err());
+
+        let iat = |iat| JwtTimeClaims {
+            iat: Some(iat),
+            ..Default::default()
+        };
+        assert!(iat(now).validate_at(now, leeway).is_ok());
+        assert!(iat(now + 30).validate_at(now, leeway).is_ok());
+        assert!(iat(now + 31).validate_at(now, leeway).is_err());
+        assert!(iat(now - 3600).validate_at(now, leeway).is_ok());
+
+        let nbf = |nbf| JwtTimeClaims {
+            nbf: Some(nbf),
+            ..Default::default()
+        };
+        assert!(nbf(now).validate_at(now, leeway).is_ok());
+        assert!(nbf(now + 30).validate_at(now, leeway).is_ok());
+        assert!(nbf(now + 31).validate_at(now, leeway).is_err());
+        assert!(nbf(now - 3600).validate_at(now, leeway).is_ok());
+
+        assert!(JwtTimeClaims::default().validate_at(now, 0).is_ok());
+    }
+}
@@ -4,11 +4,13 @@ use std::sync::OnceLock;
 
 pub mod compression;
 pub mod constants;
+pub mod jwt_time;
 pub mod logging;
 pub mod markdown;
 pub mod password_hasher;
 pub mod regex;
 pub mod sanitize_html;
+pub mod shutdown;
 pub mod utils;
 
 pub static DB_TYPE: OnceLock<DbType> = OnceLock::new();
@@ -1,11 +1,13 @@
-use actix_web::web;
 use argon2::password_hash::{SaltString, rand_core::OsRng};
 use argon2::{Algorithm, Argon2, PasswordHash, PasswordHasher, PasswordVerifier, Version};
+use prometheus::{Histogram, HistogramOpts, HistogramVec, IntCounter, IntGauge};
 use rauthy_error::{ErrorResponse, ErrorResponseType};
-use std::sync::OnceLock;
+use ring::pbkdf2;
+use std::num::NonZeroU32;
+use std::sync::{LazyLock, OnceLock};
 use std::thread;
-use tokio::time::Instant;
-use tracing::{debug, error, warn};
+use std::time::Instant;
+use tracing::{debug, error, info, warn};
 use zeroize::Zeroize;
 
 pub static ARGON2_PARAMS: OnceLock<argon2::Params> = OnceLock::new();
@@ -15,6 +17,52 @@ pub static HASH_CHANNELS: OnceLock<(
 )> = OnceLock::new();
 pub static HASH_AWAIT_WARN_TIME: OnceLock<u32> = OnceLock::new();
 
+/// The `Retry-After` in seconds for requests that were shed because of a full hash queue.
+const HASH_QUEUE_RETRY_AFTER: i64 = 1;
+
+// These metrics live here instead of `rauthy_data::metrics`, because the hasher must be usable
+// without the data layer. They are registered together with all others though.
+
+pub static HASH_QUEUE_DEPTH: LazyLock<IntGauge> = LazyLock::new(|| {
+    IntGauge::new(
+        "rauthy_password_hash_queue_depth",
+        "Password hash and verify operations waiting for a free hash worker",
+    )
+    .unwrap()
+});
+pub static HASH_QUEUE_WAIT: LazyLock<Histogram> = LazyLock::new(|| {
+    Histogram::with_opts(
+        HistogramOpts::new(
+            "rauthy_password_hash_queue_wait_seconds",
+            "Time password hash and verify operations waited for a free hash worker",
+        )
+        .buckets(HASH_BUCKETS.to_vec()),
+    )
+    .unwrap()
+});
+pub static HASH_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    HistogramVec::new(
+        HistogramOpts::new(
+            "rauthy_password_hash_duration_seconds",
+            "Duration of password hash and verify operations on a hash worker",
+        )
+        .buckets(HASH_BUCKETS.to_vec()),
+        &["op"],
+    )
+    .unwrap()
+});
+pub static HASH_REJECTED: LazyLock<IntCounter> = LazyLock::new(|| {
+    IntCounter::new(
+        "rauthy_password_hash_rejected_total",
+        "Password hash and verify operations rejected because of a full queue",
+    )
+    .unwrap()
+});
+
+const HASH_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
 pub struct HashPassword {
     plain_text: String,
     tx: flume::Sender<String>,
@@ -23,20 +71,17 @@ pub struct HashPassword {
 
 impl HashPassword {
     pub async fn hash_password(plain_text: String) -> Result<String, ErrorResponse> {
-        let (tx, rx) = flume::unbounded();
+        let (tx, rx) = flume::bounded(1);
         let s = Self {
             plain_text,
             tx,
             created: Instant::now(),
         };
 
-        HASH_CHANNELS
-            .get()
-            .unwrap()
-            .0
-            .send_async(PasswordHashMessage::Hash(s))
-            .await
-            .expect("Send PasswordHashMessage::Hash");
+        enqueue(
+            &HASH_CHANNELS.get().unwrap().0,
+            PasswordHashMessage::Hash(s),
+        )?;
         rx.recv_async()
             .await
             .map_err(|err| ErrorResponse::new(ErrorResponseType::Internal, err.to_string()))
@@ -52,7 +97,7 @@ pub struct ComparePasswords {
 
 impl ComparePasswords {
     pub async fn is_match(plain_text: String, hash: String) -> Result<bool, ErrorResponse> {
-        let (tx, rx) = flume::unbounded();
+        let (tx, rx) = flume::bounded(1);
         let c = Self {
             plain_text,
             hash,
@@ -60,13 +105,10 @@ impl ComparePasswords {
             created: Instant::now(),
         };
 
-        HASH_CHANNELS
-            .get()
-            .unwrap()
-            .0
-            .send_async(PasswordHashMessage::Compare(c))
-            .await
-            .expect("Send PasswordHashMessage::Compare");
+        enqueue(
+            &HASH_CHANNELS.get().unwrap().0,
+            PasswordHashMessage::Compare(c),
+        )?;
         rx.recv_async()
             .await
             .map_err(|err| ErrorResponse::new(ErrorResponseType::Internal, err.to_string()))
@@ -78,26 +120,67 @@ pub enum PasswordHashMessage {
     Compare(ComparePasswords),
 }
 
-// This is a simple limiter for concurrent password hashes.
-// The "problem" with argon2id is, that it uses more memory, the safer you want your hashes to be.
-// To limit the theoretical concurrent hashes while still setting a fairly high memory for the
-// operation, this simple function makes sure that at no point in time, any more than the configured
-// amount of max concurrent hashes do happen to not exceed system memory.
-pub async fn run() {
-    while let Ok(msg) = HASH_CHANNELS.get().unwrap().1.recv_async().await {
-        let res = match msg {
-            PasswordHashMessage::Hash(m) => {
-                check_await_threshold(&m.created);
-                web::block(move || hash_password(m)).await
-            }
-            PasswordHashMessage::Compare(m) => {
-                check_await_threshold(&m.created);
-                web::block(move || compare_passwords(m)).await
-            }
+/// Puts the message into the bounded hash queue without waiting. A full queue sheds the request
+/// with a `503` and `Retry-After`, instead of increasing the latency for everyone.
+fn enqueue(
+    tx: &flume::Sender<PasswordHashMessage>,
+    msg: PasswordHashMessage,
+) -> Result<(), ErrorResponse> {
+    match tx.try_send(msg) {
+        Ok(()) => {
+            HASH_QUEUE_DEPTH.inc();
+            Ok(())
+        }
+        Err(flume::TrySendError::Full(_)) => {
+            HASH_REJECTED.inc();
+            warn!("Password hash queue is full - shedding the request");
+            Err(ErrorResponse::new(
+                ErrorResponseType::ServiceUnavailable(HASH_QUEUE_RETRY_AFTER),
+                "Too many concurrent logins, please try again in a moment",
+            ))
+        }
+        Err(flume::TrySendError::Disconnected(_)) => Err(ErrorResponse::new(
+            ErrorResponseType::Internal,
+            "The password hash workers are not running",
+        )),
+    }
+}
+
+// Argon2id is CPU and memory intensive. It must never run on the async executor threads,
+// because a burst of logins would stall all other requests. Instead, a fixed amount of
+// dedicated OS threads does all the hashing. This also limits the memory used for concurrent
+// hashes, since each one needs `argon2_m_cost` KiB.
+/// Spawns the given amount of dedicated password hash worker threads.
+pub fn spawn_workers(threads: usize) {
+    info!("Starting {threads} password hash worker threads");
+
+    for i in 0..threads.max(1) {
+        thread::Builder::new()
+            .name(format!("rauthy-hash-{i}"))
+            .spawn(worker)
+            .expect("Cannot spawn password hash worker thread");
+    }
+}
+
+fn worker() {
+    while let Ok(msg) = HASH_CHANNELS.get().unwrap().1.recv() {
+        HASH_QUEUE_DEPTH.dec();
+
+        let (op, created) = match &msg {
+            PasswordHashMessage::Hash(m) => ("hash", m.created),
+            PasswordHashMessage::Compare(m) => ("verify", m.created),
         };
-        if let Err(err) = res {
-            error!("{}", err);
+        check_await_threshold(&created);
+        HASH_QUEUE_WAIT.observe(created.elapsed().as_secs_f64());
+
+        let start = Instant::now();
+        match msg {
+            PasswordHashMessage::Hash(m) => hash_password(m),
+            PasswordHashMessage::Compare(m) => compare_passwords(m),
         }
+        HASH_DURATION
+            .with_label_values(&[op])
+            .observe(start.elapsed().as_secs_f64());
     }
 }
 
@@ -123,15 +206,17 @@ fn hash_password(mut msg: HashPassword) {
     );
     let salt = SaltString::generate(&mut OsRng);
 
-    let hash = argon2
-        .hash_password(msg.plain_text.as_bytes(), &salt)
-        .expect("Error hashing the Password")
-        .to_string();
-
+    let res = argon2.hash_password(msg.plain_text.as_bytes(), &salt);
     msg.plain_text.as_mut().zeroize();
 
-    if let Err(err) = msg.tx.send(hash) {
-        error!("{}", err);
+    match res {
+        Ok(hash) => {
+            if let Err(err) = msg.tx.send(hash.to_string()) {
+                error!("{}", err);
+            }
+        }
+        // dropping the `tx` makes the caller return an error
+        Err(err) => error!("Error hashing the password: {err}"),
     }
 
     debug!("Finished with password hash on {:?}", thread::current());
@@ -140,22 +225,14 @@ fn hash_password(mut msg: HashPassword) {
 fn compare_passwords(mut msg: ComparePasswords) {
     debug!("Starting password compare on {:?}", thread::current());
 
-    let mut is_match = false;
-
-    match PasswordHash::new(&msg.hash) {
-        Ok(parsed_hash) => {
-            if Argon2::default()
-                .verify_password(msg.plain_text.as_bytes(), &parsed_hash)
-                .is_ok()
-            {
-                is_match = true;
-            }
-            msg.plain_text.as_mut().zeroize();
-        }
+    let is_match = match verify_hash(&msg.plain_text, &msg.hash) {
+        Ok(is_match) => is_match,
         Err(err) => {
             error!("Error parsing the original password hash: {err}");
+            false
         }
-    }
+    };
+    msg.plain_text.as_mut().zeroize();
 
     if let Err(err) = msg.tx.send(is_match) {
         error!("{}", err);
@@ -164,12 +241,105 @@ fn compare_passwords(mut msg: ComparePasswords) {
     debug!("Finished with password compare on {:?}", thread::current());
 }
 
+/// Verifies the password against an Argon2 hash, or one of the legacy formats that can be
+/// imported from other IdPs:
+///
+/// - bcrypt in its MCF form like `$2a$10$...`
+/// - PBKDF2 in the PHC form `$pbkdf2-sha256$i=27500$<salt>$<hash>` with `sha1`, `sha256` or
+///   `sha512`
+///
+/// Legacy hashes are replaced with an up-to-date Argon2ID hash after the next successful login.
+fn verify_hash(plain_text: &str, hash: &str) -> Result<bool, String> {
+    if hash.starts_with("$2") {
+        return bcrypt::verify(plain_text, hash).map_err(|err| err.to_string());
+    }
+
+    let parsed = PasswordHash::new(hash).map_err(|err| err.to_string())?;
+    let alg = match parsed.algorithm.as_str() {
+        "pbkdf2-sha1" => pbkdf2::PBKDF2_HMAC_SHA1,
+        "pbkdf2-sha256" => pbkdf2::PBKDF2_HMAC_SHA256,
+        "pbkdf2-sha512" => pbkdf2::PBKDF2_HMAC_SHA512,
+        _ => {
+            return Ok(Argon2::default()
+                .verify_password(plain_text.as_bytes(), &parsed)
+                .is_ok());
+        }
+    };
+
+    let iterations = parsed
+        .params
+        .get_decimal("i")
+        .and_then(NonZeroU32::new)
+        .ok_or("PBKDF2 hash without iterations")?;
+    let mut buf = [0u8; 64];
+    let salt = parsed
+        .salt
+        .ok_or("PBKDF2 hash without salt")?
+        .decode_b64(&mut buf)
+        .map_err(|err| err.to_string())?;
+    let expected = parsed.hash.ok_or("PBKDF2 hash without output")?;
+
+    Ok(pbkdf2::verify(
+        alg,
+        iterations,
+        salt,
+        plain_text.as_bytes(),
+        expected.as_bytes(),
+    )
+    .is_ok())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use pretty_assertions::assert_eq;
-    use std::time::{Duration, Instant};
-    use tokio::time;
+
+    #[test]
+    fn test_enqueue_sheds_load() {
+        let (tx, rx) = flume::bounded(1);
+        let msg = || {
+            let (tx, _rx) = flume::bounded(1);
+            PasswordHashMessage::Hash(HashPassword {
+                plain_text: "SuperRandom1337".to_string(),
+                tx,
+                created: Instant::now(),
+            })
+        };
+
+        assert!(enqueue(&tx, msg()).is_ok());
+
+        let err = enqueue(&tx, msg()).unwrap_err();
+        assert_eq!(
+            err.error,
+            ErrorResponseType::ServiceUnavailable(HASH_QUEUE_RETRY_AFTER)
+        );
+
+        // a worker took the message -> space again
+        rx.recv().unwrap();
+        assert!(enqueue(&tx, msg()).is_ok());
+
+        drop(rx);
+        let err = enqueue(&tx, msg()).unwrap_err();
+        assert_eq!(err.error, ErrorResponseType::Internal);
+    }
+
+    #[test]
+    fn test_verify_legacy_hashes() {
+        let plain = "SuperRandom1337";
+        let hashes = [
+            "$2b$04$DgtgKkWswLfQzIBaSq2Vc.gK1XxbG1Uuy/3wxR.XZMPn.Akb7IhSq",
+            "$pbkdf2-sha1$i=1000$cmF1dGh5LWtjLXNhbHQxNg$gDXoKWP7Djc5KnLg9cjSJAfycSEq8pgTKU7pc8bQC6e+Zv/iYXOeXv/Vsf5V7JsWMvLdax09JLN1PtrSXcX3zg",
+            "$pbkdf2-sha256$i=27500$cmF1dGh5LWtjLXNhbHQxNg$9C6NJd7W4Ujeb4WRx/DpnEVCBwtdRriunLZSQS7H8T9Z1igZ0RgU465WlbxKkmmYR7xTy/IEEZGFXUyqgS0wLA",
+            "$pbkdf2-sha512$i=210000$cmF1dGh5LWtjLXNhbHQxNg$zI9E5GeX78p9b2gjJ/myiFJtpyp1NnS/9q69CuXri8TwMAQ4MWQvQC9shaMHCO2UfIAgoSbyNZLXbz5pxNcaDA",
+        ];
+
+        for hash in hashes {
+            assert_eq!(verify_hash(plain, hash), Ok(true), "{hash}");
+            assert_eq!(verify_hash("IAmSoWrong1337", hash), Ok(false), "{hash}");
+        }
+
+        assert!(verify_hash(plain, "$pbkdf2-sha256$cmF1dGh5LWtjLXNhbHQxNg$9C6N").is_err());
+    }
 
     // pretty intensive test -> ignored by default
     #[tokio::test]
@@ -177,12 +347,10 @@ mod tests {
     async fn test_limiter() {
         let argon2_params = argon2::Params::new(32768, 3, 2, None).unwrap();
         let _ = ARGON2_PARAMS.set(argon2_params);
-        let _ = HASH_CHANNELS.set(flume::bounded(1));
+        let _ = HASH_CHANNELS.set(flume::bounded(8));
         let _ = HASH_AWAIT_WARN_TIME.set(100);
 
-        let handle = tokio::spawn(run());
-        time::sleep(Duration::from_secs(1)).await;
-        assert_eq!(handle.is_finished(), false);
+        spawn_workers(1);
 
         // hash the password once to get the base time
         // get base time taken to hash a single password
@@ -19,6 +19,8 @@ pub static RE_CODE_CHALLENGE_METHOD: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"^(plain|S256)$").unwrap());
 pub static RE_CITY: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"^[a-zA-Z0-9À-ÿ-\s]{0,48}$").unwrap());
+pub static RE_CLAIM_NAME: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[a-zA-Z0-9-_.:/]{2,128}$").unwrap());
 pub static RE_CLIENT_ID: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"^[a-zA-Z0-9,.:/_\-&?=~#!$'()*+%]{2,256}$").unwrap());
 // Stricter pattern for manually managed (non-ephemeral) clients. Mirrors the admin UI's
@@ -63,6 +65,8 @@ pub static RE_MFA_CODE: LazyLock<Regex> =
 pub static RE_ORIGIN: OnceLock<Regex> = OnceLock::new();
 pub static RE_PHONE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\+[0-9]{0,32}$").unwrap());
 pub static RE_PREFERRED_USERNAME: OnceLock<Regex> = OnceLock::new();
+pub static RE_SCOPE_REQUEST: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[a-zA-Z0-9-_/:\s*.]*$").unwrap());
 pub static RE_SCOPE_SPACE: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"^[a-zA-Z0-9-_/:\s*.]{0,512}$").unwrap());
 pub static RE_SEARCH: LazyLock<Regex> =
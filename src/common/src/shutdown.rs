@@ -0,0 +1,104 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::time::{self, Instant};
+use tracing::{info, warn};
+
+static DRAINING: AtomicBool = AtomicBool::new(false);
+static PENDING_TASKS: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns `true` as soon as a graceful shutdown has been started. The ready check will fail
+/// from this point on, so load balancers can stop sending new traffic.
+#[inline]
+pub fn is_draining() -> bool {
+    DRAINING.load(Ordering::Relaxed)
+}
+
+#[inline]
+pub fn start_draining() {
+    if !DRAINING.swap(true, Ordering::Relaxed) {
+        info!("Graceful shutdown started - draining in-flight requests and background tasks");
+    }
+}
+
+/// Marks a unit of async background work, which must finish before the DB and cache layer
+/// can be shut down, like an E-Mail that is being sent or a running maintenance batch.
+/// The task counts as pending until the guard is dropped.
+#[derive(Debug)]
+pub struct TaskGuard(());
+
+impl TaskGuard {
+    pub fn new() -> Self {
+        PENDING_TASKS.fetch_add(1, Ordering::Relaxed);
+        Self(())
+    }
+}
+
+impl Default for TaskGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        PENDING_TASKS.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[inline]
+pub fn pending_tasks() -> usize {
+    PENDING_TASKS.load(Ordering::Relaxed)
+}
+
+/// Waits until all pending tasks have finished and `queues_empty` returns `true`, but at most
+/// for `timeout`. Returns `false` if the timeout was reached.
+pub async fn wait_for_pending<F>(timeout: Duration, queues_empty: F) -> bool
+where
+    F: Fn() -> bool,
+{
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        let pending = pending_tasks();
+        if pending == 0 && queues_empty() {
+            return true;
+        }
+
+        if Instant::now() >= deadline {
+            warn!(
+                pending,
+                "Graceful shutdown timeout reached with unfinished background tasks"
+            );
+            return false;
+        }
+
+        time::sleep(Duration::from_millis(50)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_wait_for_pending() {
+        assert!(!is_draining());
+        start_draining();
+        assert!(is_draining());
+
+        let guard = TaskGuard::new();
+        assert_eq!(pending_tasks(), 1);
+        assert!(!wait_for_pending(Duration::from_millis(100), || true).await);
+
+        let handle = tokio::spawn(async move {
+            time::sleep(Duration::from_millis(100)).await;
+            drop(guard);
+        });
+        assert!(wait_for_pending(Duration::from_secs(5), || true).await);
+        assert_eq!(pending_tasks(), 0);
+        handle.await.unwrap();
+
+        // non-empty queues keep the shutdown waiting as well
+        assert!(!wait_for_pending(Duration::from_millis(100), || false).await);
+    }
+}
@@ -1,16 +1,17 @@
 use crate::constants::{PEER_IP_HEADER_NAME, PROXY_MODE, TRUSTED_PROXIES};
 use actix_web::HttpRequest;
-use actix_web::dev::ServiceRequest;
+use actix_web::http::header;
 use actix_web::http::header::HeaderMap;
 use base64::{Engine as _, engine, engine::general_purpose};
 use gethostname::gethostname;
 use rand::RngExt;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
-use std::fmt::Debug;
-use std::net::IpAddr;
-use std::net::Ipv4Addr;
+use std::borrow::Cow;
+use std::fmt::{Debug, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::str::FromStr;
 use tracing::{error, trace};
+use unicode_normalization::UnicodeNormalization;
 
 const B64_URL_SAFE: engine::GeneralPurpose = general_purpose::URL_SAFE;
 const B64_URL_SAFE_NO_PAD: engine::GeneralPurpose = general_purpose::URL_SAFE_NO_PAD;
@@ -44,6 +45,11 @@ pub fn get_rand_between(lower: u64, upper: u64) -> u64 {
     rand::rng().random_range(lower..upper)
 }
 
+// Returns `N` random bytes
+pub fn get_rand_bytes<const N: usize>() -> [u8; N] {
+    rand::rng().random()
+}
+
 #[inline(always)]
 pub fn base64_encode(input: &[u8]) -> String {
     B64_STD.encode(input)
@@ -111,6 +117,64 @@ pub fn base64_url_no_pad_decode_buf(b64: &str, buf: &mut Vec<u8>) -> Result<(),
         .map_err(|_| ErrorResponse::new(ErrorResponseType::BadRequest, "B64 decoding error"))
 }
 
+/// Percent-encodes a single query parameter value, so it can be safely appended to a URI, which
+/// will end up inside a `Location` header. Everything apart from visible ASCII is encoded, as well
+/// as the characters which would otherwise split the value into multiple params.
+pub fn encode_query_value(value: &str) -> Cow<'_, str> {
+    let needs_encoding = |b: u8| !b.is_ascii_graphic() || matches!(b, b'%' | b'&' | b'#' | b'+');
+
+    if !value.bytes().any(needs_encoding) {
+        return Cow::Borrowed(value);
+    }
+
+    let mut res = String::with_capacity(value.len() + 16);
+    for b in value.bytes() {
+        if needs_encoding(b) {
+            write!(res, "%{b:02X}").expect("write to String to always succeed");
+        } else {
+            res.push(b as char);
+        }
+    }
+    Cow::Owned(res)
+}
+
+/// Prepares untrusted input for logging. Control characters are escaped, so they can never
+/// inject additional log lines, and the value is truncated after `max_len` bytes with a marker.
+pub fn truncate_for_log(value: &str, max_len: usize) -> Cow<'_, str> {
+    if value.len() <= max_len && !value.chars().any(char::is_control) {
+        return Cow::Borrowed(value);
+    }
+
+    let mut end = max_len.min(value.len());
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    let mut res = String::with_capacity(end + 32);
+    for c in value[..end].chars() {
+        if c.is_control() {
+            write!(res, "{}", c.escape_default()).expect("write to String to always succeed");
+        } else {
+            res.push(c);
+        }
+    }
+    if end < value.len() {
+        write!(res, "...[truncated {} bytes]", value.len() - end)
+            .expect("write to String to always succeed");
+    }
+    Cow::Owned(res)
+}
+
+/// Compares two secret values in constant time to not leak any timing information.
+///
+/// Always use this instead of `==` for anything an attacker could guess, like CSRF tokens,
+/// secrets, PKCE verifiers or one-time codes. Only the length of the inputs may leak, which is
+/// fine, because all of these values have a fixed length anyway.
+#[inline]
+pub fn secure_compare(a: impl AsRef<[u8]>, b: impl AsRef<[u8]>) -> bool {
+    constant_time_eq::constant_time_eq(a.as_ref(), b.as_ref())
+}
+
 #[inline(always)]
 pub fn new_store_id() -> String {
     get_rand(24)
@@ -126,32 +190,28 @@ const DUMMY_ADDRESS: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 0, 8));
 // dummy address should be enabled for UNIX domain socket support
 pub struct UseDummyAddress;
 
-// TODO unify real_ip_from_req and real_ip_from_svc_req by using an impl Trait
-#[inline(always)]
-pub fn real_ip_from_req(req: &HttpRequest) -> Result<IpAddr, ErrorResponse> {
+/// Returns the real client IP for the request.
+///
+/// `PEER_IP_HEADER_NAME`, `Forwarded` and `X-Forwarded-For` are only honored, if the direct peer
+/// is one of the `TRUSTED_PROXIES`. Requests from any other source always resolve to the peer IP.
+/// Forwarding chains are evaluated from right to left, and the first hop which is not a trusted
+/// proxy is the client. Anything left of it may have been set by the client itself.
+pub fn real_ip(req: &HttpRequest) -> Result<IpAddr, ErrorResponse> {
     let use_dummy_addr = req.app_data::<UseDummyAddress>().is_some();
     let peer_ip = parse_peer_addr(req.connection_info().peer_addr(), use_dummy_addr)?;
-    if let Some(ip) = ip_from_cust_header(req.headers()) {
-        check_trusted_proxy(&peer_ip, use_dummy_addr)?;
-        Ok(ip)
-    } else if *PROXY_MODE.get().unwrap() {
-        check_trusted_proxy(&peer_ip, use_dummy_addr)?;
-        parse_peer_addr(req.connection_info().realip_remote_addr(), false)
-    } else {
-        Ok(peer_ip)
+
+    let proxies = TRUSTED_PROXIES.get().unwrap();
+    let is_trusted =
+        |ip: &IpAddr| (use_dummy_addr && *ip == DUMMY_ADDRESS) || is_trusted_proxy(proxies, ip);
+    if !is_trusted(&peer_ip) {
+        return Ok(peer_ip);
     }
-}
 
-#[inline(always)]
-pub fn real_ip_from_svc_req(req: &ServiceRequest) -> Result<IpAddr, ErrorResponse> {
-    let use_dummy_addr = req.app_data::<UseDummyAddress>().is_some();
-    let peer_ip = parse_peer_addr(req.connection_info().peer_addr(), use_dummy_addr)?;
     if let Some(ip) = ip_from_cust_header(req.headers()) {
-        check_trusted_proxy(&peer_ip, use_dummy_addr)?;
         Ok(ip)
     } else if *PROXY_MODE.get().unwrap() {
-        check_trusted_proxy(&peer_ip, use_dummy_addr)?;
-        parse_peer_addr(req.connection_info().realip_remote_addr(), false)
+        let hops = forwarded_hops(req.headers());
+        Ok(client_ip_from_hops(peer_ip, &hops, is_trusted))
     } else {
         Ok(peer_ip)
     }
@@ -180,25 +240,79 @@ fn parse_peer_addr(peer_addr: Option<&str>, use_dummy_addr: bool) -> Result<IpAd
     }
 }
 
-#[inline(always)]
-fn check_trusted_proxy(peer_ip: &IpAddr, use_dummy_addr: bool) -> Result<(), ErrorResponse> {
-    if use_dummy_addr && *peer_ip == DUMMY_ADDRESS {
-        return Ok(());
+#[inline]
+pub fn is_trusted_proxy(proxies: &[cidr::IpCidr], ip: &IpAddr) -> bool {
+    proxies.iter().any(|cidr| cidr.contains(ip))
+}
+
+/// Walks the forwarding `hops` from right to left, starting at the trusted `peer_ip`, and
+/// returns the first one which is not trusted. If all of them are trusted, the left-most hop is
+/// the client. An unknown or obfuscated hop stops the walk at the last proxy in front of it.
+fn client_ip_from_hops<F>(peer_ip: IpAddr, hops: &[Option<IpAddr>], is_trusted: F) -> IpAddr
+where
+    F: Fn(&IpAddr) -> bool,
+{
+    let mut client = peer_ip;
+    for hop in hops.iter().rev() {
+        let Some(ip) = hop else {
+            break;
+        };
+        client = *ip;
+        if !is_trusted(ip) {
+            break;
+        }
     }
-    for cidr in TRUSTED_PROXIES.get().unwrap() {
-        if cidr.contains(peer_ip) {
-            return Ok(());
+    client
+}
+
+/// Extracts all hops in order from the RFC 7239 `Forwarded` headers, or `X-Forwarded-For` as a
+/// fallback. Multiple headers are concatenated in the order they were received. Hops, which
+/// cannot be parsed into an IP, are `None`.
+fn forwarded_hops(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    let mut hops = Vec::new();
+
+    for value in headers.get_all(header::FORWARDED) {
+        let Ok(value) = value.to_str() else {
+            hops.push(None);
+            continue;
+        };
+        for element in value.split(',') {
+            let node = element
+                .split(';')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(key, _)| key.trim().eq_ignore_ascii_case("for"))
+                .and_then(|(_, node)| parse_forwarded_node(node));
+            hops.push(node);
         }
     }
+    if !hops.is_empty() {
+        return hops;
+    }
 
-    error!(
-        "Invalid request from IP {} which is not a trusted proxy",
-        peer_ip
-    );
-    Err(ErrorResponse::new(
-        ErrorResponseType::BadRequest,
-        "Invalid IP Address",
-    ))
+    for value in headers.get_all(header::X_FORWARDED_FOR) {
+        let Ok(value) = value.to_str() else {
+            hops.push(None);
+            continue;
+        };
+        hops.extend(value.split(',').map(parse_forwarded_node));
+    }
+    hops
+}
+
+/// Parses a single node like `192.0.2.43`, `192.0.2.43:47011`, `2001:db8::17` or
+/// `"[2001:db8::17]:4711"`, with or without quotes.
+fn parse_forwarded_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    if let Some(rest) = node.strip_prefix('[') {
+        let (ip, _port) = rest.split_once(']')?;
+        return Ipv6Addr::from_str(ip).ok().map(IpAddr::V6);
+    }
+    if let Ok(ip) = IpAddr::from_str(node) {
+        return Some(ip);
+    }
+    let (ip, port) = node.rsplit_once(':')?;
+    port.parse::<u16>().ok()?;
+    Ipv4Addr::from_str(ip).ok().map(IpAddr::V4)
 }
 
 pub fn build_trusted_proxies(proxies: &[String]) -> Vec<cidr::IpCidr> {
@@ -238,6 +352,24 @@ fn ip_from_cust_header(headers: &HeaderMap) -> Option<IpAddr> {
     None
 }
 
+/// Normalizes an E-Mail address into the form in which it is stored and looked up.
+///
+/// The address is trimmed and NFC normalized, and the domain is always lowercased. The local
+/// part is lowercased only with `lowercase_local`. RFC 5321 allows a case-sensitive local
+/// part, but in practice, almost no mail server treats it this way.
+pub fn normalize_email(email: &str, lowercase_local: bool) -> String {
+    let email = email.trim();
+    let email = match email.rsplit_once('@') {
+        Some((local, domain)) if !lowercase_local => {
+            format!("{local}@{}", domain.to_lowercase())
+        }
+        _ => email.to_lowercase(),
+    };
+
+    // lowercasing may produce decomposed characters, so NFC must come last
+    email.nfc().collect()
+}
+
 #[inline]
 pub fn serialize<T>(value: &T) -> Result<Vec<u8>, ErrorResponse>
 where
@@ -269,8 +401,32 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use actix_web::http::header::HeaderValue;
     use pretty_assertions::assert_eq;
 
+    #[test]
+    fn test_normalize_email() {
+        assert_eq!(
+            normalize_email(" John.Doe@Example.COM ", true),
+            "john.doe@example.com"
+        );
+        assert_eq!(
+            normalize_email("John.Doe@Example.COM", false),
+            "John.Doe@example.com"
+        );
+
+        // decomposed `e` + combining acute accent -> precomposed `é`
+        let decomposed = "Jose\u{301}@example.com";
+        assert_eq!(normalize_email(decomposed, true), "jos\u{e9}@example.com");
+        assert_eq!(
+            normalize_email(decomposed, true),
+            normalize_email("Jos\u{e9}@example.com", true)
+        );
+
+        // invalid input is still normalized as good as possible, validation happens elsewhere
+        assert_eq!(normalize_email("No-At-Sign", false), "no-at-sign");
+    }
+
     #[test]
     fn test_get_rand() {
         let rnd = get_rand(11);
@@ -283,6 +439,74 @@ mod tests {
         assert_eq!(rnd.len(), 1024);
     }
 
+    #[test]
+    fn test_secure_compare() {
+        assert!(secure_compare("", ""));
+        assert!(secure_compare("csrf_token", b"csrf_token"));
+        assert!(!secure_compare("csrf_token", "csrf_tokem"));
+        assert!(!secure_compare("csrf_token", "csrf_token_"));
+    }
+
+    /// Makes sure that no new plain comparisons on known sensitive values sneak in. These must
+    /// always use `secure_compare()` to not leak any timing information.
+    #[test]
+    fn test_no_plain_secret_comparisons() {
+        let fields = "csrf_token|xsrf_token|pkce_challenge|challenge|code_verifier|client_secret\
+            |device_code|user_code|reg_token";
+        let re = regex::Regex::new(&format!(
+            r"\b({fields})\b\s*(==|!=)|(==|!=)\s*[&*]*[\w.]*\b({fields})\b|\b({fields})\b\s*\.eq\("
+        ))
+        .unwrap();
+
+        fn rs_files(dir: &std::path::Path, files: &mut Vec<std::path::PathBuf>) {
+            for entry in std::fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    if !path.ends_with("target") && !path.ends_with("tests") {
+                        rs_files(&path, files);
+                    }
+                } else if path.extension().is_some_and(|ext| ext == "rs") {
+                    files.push(path);
+                }
+            }
+        }
+        let mut files = Vec::new();
+        rs_files(
+            &std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join(".."),
+            &mut files,
+        );
+        assert!(files.iter().any(|p| p.ends_with("common/src/utils.rs")));
+
+        // Counts the existing `secure_compare()` calls on the same values. If there are none,
+        // the scan did not reach the workspace sources, and the test would pass silently.
+        let re_secure = regex::Regex::new(&format!(r"secure_compare\(.*\b({fields})\b")).unwrap();
+        let mut secure_compares = 0;
+        let mut findings = Vec::new();
+        for path in files {
+            let content = std::fs::read_to_string(&path).unwrap();
+            for (i, line) in content.lines().enumerate() {
+                if line.trim_start().starts_with("//") {
+                    continue;
+                }
+                if re_secure.is_match(line) {
+                    secure_compares += 1;
+                }
+                if re.is_match(line) {
+                    findings.push(format!("{}:{} -> {}", path.display(), i + 1, line.trim()));
+                }
+            }
+        }
+        assert!(
+            secure_compares >= 5,
+            "only found {secure_compares} `secure_compare()` calls - are the sources scanned?"
+        );
+        assert!(
+            findings.is_empty(),
+            "Use `secure_compare()` for sensitive values:\n{}",
+            findings.join("\n")
+        );
+    }
+
     #[test]
     fn test_trusted_proxy_check() {
         let raw = vec![
@@ -291,30 +515,217 @@ mod tests {
             "172.16.0.1/32".to_string(),
             "10.10.10.10/31".to_string(),
         ];
-        let _ = TRUSTED_PROXIES.set(build_trusted_proxies(&raw));
-        println!("{:?}", build_trusted_proxies(&raw));
-
-        assert!(check_trusted_proxy(&IpAddr::from_str("192.168.100.1").unwrap(), false).is_ok());
-        assert!(check_trusted_proxy(&IpAddr::from_str("192.168.100.255").unwrap(), false).is_ok());
-        assert!(check_trusted_proxy(&IpAddr::from_str("192.168.99.1").unwrap(), false).is_err());
-        assert!(check_trusted_proxy(&IpAddr::from_str("192.168.99.255").unwrap(), false).is_err());
-
-        assert!(check_trusted_proxy(&IpAddr::from_str("192.168.0.96").unwrap(), false).is_ok());
-        assert!(check_trusted_proxy(&IpAddr::from_str("192.168.0.111").unwrap(), false).is_ok());
-        assert!(check_trusted_proxy(&IpAddr::from_str("192.168.0.95").unwrap(), false).is_err());
-        assert!(check_trusted_proxy(&IpAddr::from_str("192.168.0.112").unwrap(), false).is_err());
-
-        assert!(check_trusted_proxy(&IpAddr::from_str("172.16.0.1").unwrap(), false).is_ok());
-        assert!(check_trusted_proxy(&IpAddr::from_str("172.16.0.2").unwrap(), false).is_err());
-
-        assert!(check_trusted_proxy(&IpAddr::from_str("10.10.10.10").unwrap(), false).is_ok());
-        assert!(check_trusted_proxy(&IpAddr::from_str("10.10.10.11").unwrap(), false).is_ok());
-        assert!(check_trusted_proxy(&IpAddr::from_str("10.10.10.9").unwrap(), false).is_err());
-        assert!(check_trusted_proxy(&IpAddr::from_str("10.10.10.12").unwrap(), false).is_err());
-
-        assert!(check_trusted_proxy(&IpAddr::from_str("192.0.0.8").unwrap(), true).is_ok());
-        assert!(check_trusted_proxy(&IpAddr::from_str("192.0.0.8").unwrap(), false).is_err());
-        assert!(check_trusted_proxy(&IpAddr::from_str("10.10.10.11").unwrap(), true).is_ok());
-        assert!(check_trusted_proxy(&IpAddr::from_str("10.10.10.9").unwrap(), true).is_err());
+        let proxies = build_trusted_proxies(&raw);
+        let is_trusted = |ip: &str| is_trusted_proxy(&proxies, &IpAddr::from_str(ip).unwrap());
+
+        assert!(is_trusted("192.168.100.1"));
+        assert!(is_trusted("192.168.100.255"));
+        assert!(!is_trusted("192.168.99.1"));
+        assert!(!is_trusted("192.168.99.255"));
+
+        assert!(is_trusted("192.168.0.96"));
+        assert!(is_trusted("192.168.0.111"));
+        assert!(!is_trusted("192.168.0.95"));
+        assert!(!is_trusted("192.168.0.112"));
+
+        assert!(is_trusted("172.16.0.1"));
+        assert!(!is_trusted("172.16.0.2"));
+
+        assert!(is_trusted("10.10.10.10"));
+        assert!(is_trusted("10.10.10.11"));
+        assert!(!is_trusted("10.10.10.9"));
+        assert!(!is_trusted("10.10.10.12"));
+    }
+
+    #[test]
+    fn test_forwarded_hops() {
+        let ip = |s: &str| Some(IpAddr::from_str(s).unwrap());
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::X_FORWARDED_FOR,
+            HeaderValue::from_static("203.0.113.7, 10.0.0.1:8080,[2001:db8::17]:4711, unknown"),
+        );
+        headers.append(
+            header::X_FORWARDED_FOR,
+            HeaderValue::from_static("2001:db8::1"),
+        );
+        assert_eq!(
+            forwarded_hops(&headers),
+            vec![
+                ip("203.0.113.7"),
+                ip("10.0.0.1"),
+                ip("2001:db8::17"),
+                None,
+                ip("2001:db8::1"),
+            ]
+        );
+
+        // `Forwarded` takes precedence over `X-Forwarded-For`
+        headers.insert(
+            header::FORWARDED,
+            HeaderValue::from_static(
+                r#"for=192.0.2.60;proto=http;by=203.0.113.43, For="[2001:db8:cafe::17]:4711""#,
+            ),
+        );
+        headers.append(
+            header::FORWARDED,
+            HeaderValue::from_static("proto=https, for=_hidden, for=\"10.0.0.2:443\""),
+        );
+        assert_eq!(
+            forwarded_hops(&headers),
+            vec![
+                ip("192.0.2.60"),
+                ip("2001:db8:cafe::17"),
+                None,
+                None,
+                ip("10.0.0.2"),
+            ]
+        );
+
+        assert!(forwarded_hops(&HeaderMap::new()).is_empty());
+    }
+
+    #[test]
+    fn test_client_ip_from_hops() {
+        let proxies = build_trusted_proxies(&["10.0.0.0/8".to_string(), "fd00::/8".to_string()]);
+        let is_trusted = |ip: &IpAddr| is_trusted_proxy(&proxies, ip);
+        let ip = |s: &str| IpAddr::from_str(s).unwrap();
+        let hops = |list: &[&str]| {
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                header::X_FORWARDED_FOR,
+                HeaderValue::from_str(&list.join(", ")).unwrap(),
+            );
+            forwarded_hops(&headers)
+        };
+        let peer = ip("10.0.0.1");
+
+        // no headers at all
+        assert_eq!(client_ip_from_hops(peer, &[], is_trusted), peer);
+
+        // single proxy
+        assert_eq!(
+            client_ip_from_hops(peer, &hops(&["198.51.100.3"]), is_trusted),
+            ip("198.51.100.3")
+        );
+
+        // chained proxies
+        assert_eq!(
+            client_ip_from_hops(
+                peer,
+                &hops(&["198.51.100.3", "10.1.0.1", "10.2.0.1"]),
+                is_trusted
+            ),
+            ip("198.51.100.3")
+        );
+
+        // A client might prepend anything. Only the right-most untrusted hop, which was
+        // added by our own proxy, can be believed.
+        assert_eq!(
+            client_ip_from_hops(
+                peer,
+                &hops(&["1.1.1.1", "10.3.3.3", "198.51.100.3", "10.2.0.1"]),
+                is_trusted
+            ),
+            ip("198.51.100.3")
+        );
+
+        // all trusted -> left-most
+        assert_eq!(
+            client_ip_from_hops(peer, &hops(&["10.9.9.9", "10.2.0.1"]), is_trusted),
+            ip("10.9.9.9")
+        );
+
+        // an unparsable hop stops at the last known proxy
+        assert_eq!(
+            client_ip_from_hops(
+                peer,
+                &hops(&["198.51.100.3", "unknown", "10.2.0.1"]),
+                is_trusted
+            ),
+            ip("10.2.0.1")
+        );
+        assert_eq!(
+            client_ip_from_hops(peer, &hops(&["garbage"]), is_trusted),
+            peer
+        );
+
+        // IPv6
+        let peer_v6 = ip("fd00::1");
+        assert_eq!(
+            client_ip_from_hops(
+                peer_v6,
+                &hops(&["2001:db8::99", "[2001:db8::17]:4711", "fd12::2"]),
+                is_trusted
+            ),
+            ip("2001:db8::17")
+        );
+    }
+
+    #[test]
+    fn test_real_ip_untrusted_peer() {
+        use actix_web::test::TestRequest;
+
+        let _ = PEER_IP_HEADER_NAME.set(None);
+        let _ = PROXY_MODE.set(true);
+        let _ = TRUSTED_PROXIES.set(build_trusted_proxies(&["10.0.0.0/8".to_string()]));
+
+        // spoofed headers from an untrusted source must be ignored entirely
+        let req = TestRequest::default()
+            .peer_addr("198.51.100.3:40000".parse().unwrap())
+            .insert_header((header::X_FORWARDED_FOR, "10.0.0.5, 1.1.1.1"))
+            .insert_header((header::FORWARDED, "for=1.1.1.1"))
+            .to_http_request();
+        assert_eq!(
+            real_ip(&req).unwrap(),
+            IpAddr::from_str("198.51.100.3").unwrap()
+        );
+
+        let req = TestRequest::default()
+            .peer_addr("[2001:db8::5]:40000".parse().unwrap())
+            .insert_header((header::X_FORWARDED_FOR, "1.1.1.1"))
+            .to_http_request();
+        assert_eq!(
+            real_ip(&req).unwrap(),
+            IpAddr::from_str("2001:db8::5").unwrap()
+        );
+
+        // trusted peer
+        let req = TestRequest::default()
+            .peer_addr("10.0.0.1:40000".parse().unwrap())
+            .insert_header((header::X_FORWARDED_FOR, "1.1.1.1, 198.51.100.3"))
+            .to_http_request();
+        assert_eq!(
+            real_ip(&req).unwrap(),
+            IpAddr::from_str("198.51.100.3").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_encode_query_value() {
+        assert_eq!(encode_query_value("abc-123_~.!"), "abc-123_~.!");
+        assert!(matches!(encode_query_value("abc"), Cow::Borrowed(_)));
+
+        assert_eq!(encode_query_value("a b"), "a%20b");
+        assert_eq!(encode_query_value("a&b=c#d"), "a%26b=c%23d");
+        assert_eq!(encode_query_value("100%+1"), "100%25%2B1");
+        assert_eq!(
+            encode_query_value("a\r\nSet-Cookie: x"),
+            "a%0D%0ASet-Cookie:%20x"
+        );
+        assert_eq!(encode_query_value("\0"), "%00");
+        assert_eq!(encode_query_value("ä"), "%C3%A4");
+    }
+
+    #[test]
+    fn test_truncate_for_log() {
+        assert_eq!(truncate_for_log("abc", 8), "abc");
+        assert_eq!(truncate_for_log("abcdef", 3), "abc...[truncated 3 bytes]");
+        assert_eq!(truncate_for_log("a\r\nb\0", 16), "a\\r\\nb\\u{0}");
+
+        // never splits multi-byte characters
+        assert_eq!(truncate_for_log("ääää", 3), "ä...[truncated 6 bytes]");
+        assert_eq!(truncate_for_log(&"ä".repeat(10_000), 4).len(), 4 + 26);
     }
 }
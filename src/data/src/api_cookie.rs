@@ -73,6 +73,29 @@ impl ApiCookie {
         Self::cookie_into_value(req.cookie(&name))
     }
 
+    /// Returns the values of all cookies with the given name. A browser may send multiple cookies
+    /// with the same name, for instance with different paths or when multiple instances are
+    /// running on the same domain during development.
+    pub fn all_from_req<'c, N>(req: &HttpRequest, cookie_name: N) -> Vec<String>
+    where
+        N: Into<Cow<'c, str>> + Display,
+    {
+        let name = match RauthyConfig::get().vars.access.cookie_mode {
+            CookieMode::Host => format!("__Host-{cookie_name}"),
+            CookieMode::Secure => format!("__Secure-{cookie_name}"),
+            CookieMode::DangerInsecure => cookie_name.to_string(),
+        };
+
+        let Ok(cookies) = req.cookies() else {
+            return Vec::default();
+        };
+        cookies
+            .iter()
+            .filter(|c| c.name() == name)
+            .filter_map(|c| Self::cookie_into_value(Some(c.clone())))
+            .collect()
+    }
+
     pub fn from_svc_req<'c, N>(req: &ServiceRequest, cookie_name: N) -> Option<String>
     where
         N: Into<Cow<'c, str>> + Display,
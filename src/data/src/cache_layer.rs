@@ -0,0 +1,538 @@
+use crate::database::{Cache, DB};
+use crate::metrics;
+use crate::rauthy_config::RauthyConfig;
+use chrono::Utc;
+use rauthy_error::ErrorResponse;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::sync::{LazyLock, Mutex};
+use tracing::{debug, error, warn};
+
+/// A value put with `put_swr()` is considered stale during the last `SWR_STALE_PCT` percent of
+/// its TTL.
+const SWR_STALE_PCT: i64 = 10;
+
+/// Keys with a stale-while-revalidate refresh currently running on this node.
+static SWR_REFRESHING: LazyLock<Mutex<HashSet<String>>> =
+    LazyLock::new(|| Mutex::new(HashSet::new()));
+
+/// Decides what happens with a failed cache operation, when `database.cache_degradation` is
+/// enabled. Without the degradation mode, every error is returned to the caller.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CacheCriticality {
+    /// The cache is the only source of truth, or a silently skipped write would make it
+    /// possible to re-use or forge a value. Errors are always fatal.
+    Critical,
+    /// The cached value can be re-created from the DB at any time. Read errors are treated as a
+    /// miss, write errors are logged and ignored.
+    BestEffort,
+}
+
+/// Every value that goes through the instrumented cache layer needs a name. It is used as the
+/// `cache` label for the metrics and decides about the `CacheCriticality`. There is no catch-all
+/// on purpose, so each new call site must make a conscious choice.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CacheName {
+    AuthProvider,
+    AuthProviderCallback,
+    AuthProviderLogout,
+    AuthProviderSession,
+    AuthProviderTemplate,
+    Client,
+    Html,
+    I18nOverrides,
+    LoginPageContext,
+    Logo,
+    MaintenanceMode,
+    Session,
+    ThemeCss,
+    WellKnown,
+}
+
+impl CacheName {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::AuthProvider => "auth_provider",
+            Self::AuthProviderCallback => "auth_provider_callback",
+            Self::AuthProviderLogout => "auth_provider_logout",
+            Self::AuthProviderSession => "auth_provider_session",
+            Self::AuthProviderTemplate => "auth_provider_template",
+            Self::Client => "client",
+            Self::Html => "html",
+            Self::I18nOverrides => "i18n_overrides",
+            Self::LoginPageContext => "login_page_context",
+            Self::Logo => "logo",
+            Self::MaintenanceMode => "maintenance_mode",
+            Self::Session => "session",
+            Self::ThemeCss => "theme_css",
+            Self::WellKnown => "well_known",
+        }
+    }
+
+    /// The `hiqlite` cache index the values live in.
+    pub fn cache(&self) -> Cache {
+        match self {
+            Self::AuthProvider
+            | Self::AuthProviderTemplate
+            | Self::Client
+            | Self::I18nOverrides
+            | Self::Logo
+            | Self::MaintenanceMode
+            | Self::WellKnown => Cache::App,
+            Self::AuthProviderCallback | Self::AuthProviderLogout => Cache::AuthProviderCallback,
+            Self::AuthProviderSession | Self::Session => Cache::Session,
+            Self::Html | Self::ThemeCss => Cache::Html,
+            Self::LoginPageContext => Cache::LoginPageContext,
+        }
+    }
+
+    pub fn criticality(&self) -> CacheCriticality {
+        match self {
+            // Callbacks and logout tokens only exist inside the cache. A missed write would
+            // break the login, and a "miss" on a failed delete would allow a replay.
+            Self::AuthProviderCallback | Self::AuthProviderLogout => CacheCriticality::Critical,
+            // A session which has been deleted or invalidated could be read from a stale cache
+            // entry, if a failed delete was ignored. The upstream session reference lives
+            // inside the cache only and is bound to the session.
+            Self::AuthProviderSession | Self::Session => CacheCriticality::Critical,
+            // An auth provider can be disabled or have its secret rotated, which must never be
+            // hidden by a stale cache entry. The same goes for the start or end of a maintenance.
+            Self::AuthProvider | Self::MaintenanceMode => CacheCriticality::Critical,
+            // A disabled client or removed redirect URIs must never be hidden either.
+            Self::Client => CacheCriticality::Critical,
+            Self::AuthProviderTemplate
+            | Self::Html
+            | Self::I18nOverrides
+            | Self::LoginPageContext
+            | Self::Logo
+            | Self::ThemeCss
+            | Self::WellKnown => CacheCriticality::BestEffort,
+        }
+    }
+}
+
+#[inline]
+fn degradation() -> bool {
+    RauthyConfig::get().vars.database.cache_degradation
+}
+
+/// Instrumented `get` for the given cache name, counting hits, misses and errors.
+pub async fn get<K, V>(name: CacheName, key: K) -> Result<Option<V>, ErrorResponse>
+where
+    K: Into<Cow<'static, str>>,
+    V: DeserializeOwned,
+{
+    let res = DB::hql().get(name.cache(), key.into()).await;
+    handle_read(name, res, degradation())
+}
+
+/// Instrumented `get_bytes` for the given cache name, counting hits, misses and errors.
+pub async fn get_bytes<K>(name: CacheName, key: K) -> Result<Option<Vec<u8>>, ErrorResponse>
+where
+    K: Into<Cow<'static, str>>,
+{
+    let res = DB::hql().get_bytes(name.cache(), key.into()).await;
+    handle_read(name, res, degradation())
+}
+
+/// Instrumented `put` for the given cache name.
+pub async fn put<K, V>(
+    name: CacheName,
+    key: K,
+    value: &V,
+    ttl: Option<i64>,
+) -> Result<(), ErrorResponse>
+where
+    K: Into<Cow<'static, str>>,
+    V: Serialize,
+{
+    let res = DB::hql().put(name.cache(), key.into(), value, ttl).await;
+    handle_write(name, "put", res, degradation())
+}
+
+/// Instrumented `put_bytes` for the given cache name.
+pub async fn put_bytes<K>(
+    name: CacheName,
+    key: K,
+    value: Vec<u8>,
+    ttl: Option<i64>,
+) -> Result<(), ErrorResponse>
+where
+    K: Into<Cow<'static, str>>,
+{
+    let res = DB::hql()
+        .put_bytes(name.cache(), key.into(), value, ttl)
+        .await;
+    handle_write(name, "put", res, degradation())
+}
+
+/// Instrumented `delete` for the given cache name.
+pub async fn delete<K>(name: CacheName, key: K) -> Result<(), ErrorResponse>
+where
+    K: Into<Cow<'static, str>>,
+{
+    let res = DB::hql().delete(name.cache(), key.into()).await;
+    handle_write(name, "delete", res, degradation())
+}
+
+/// The wrapper for values put with `put_swr()`.
+#[derive(Serialize, Deserialize)]
+struct SwrEntry<V> {
+    inserted_at: i64,
+    value: V,
+}
+
+/// Stale-while-revalidate `put`, which must be used for values read with `get_swr()`.
+pub async fn put_swr<K, V>(
+    name: CacheName,
+    key: K,
+    value: &V,
+    ttl: Option<i64>,
+) -> Result<(), ErrorResponse>
+where
+    K: Into<Cow<'static, str>>,
+    V: Serialize,
+{
+    let entry = SwrEntry {
+        inserted_at: Utc::now().timestamp(),
+        value,
+    };
+    put(name, key, &entry, ttl).await
+}
+
+/// Stale-while-revalidate `get` for values put with `put_swr()`.
+///
+/// A miss, a value after its hard expiry or a failed read returns `None` and the caller needs to
+/// fall back to the DB as usual. A value inside the last `SWR_STALE_PCT` percent of its `ttl` is returned
+/// immediately, while `refresh` is spawned in the background to re-populate the cache before
+/// it expires. This prevents all entries from expiring at once after quiet periods, which would
+/// make all requests hit the DB at the same time. A single refresh per key runs on this node,
+/// and a cache counter makes sure that only one of them executes in the whole cluster.
+pub async fn get_swr<V, F, Fut>(
+    name: CacheName,
+    key: String,
+    ttl: Option<i64>,
+    refresh: F,
+) -> Result<Option<V>, ErrorResponse>
+where
+    V: DeserializeOwned,
+    F: FnOnce() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<(), ErrorResponse>> + Send + 'static,
+{
+    let res: Result<Option<SwrEntry<V>>, _> = DB::hql().get(name.cache(), key.clone()).await;
+    let entry = match res {
+        Ok(entry) => handle_read(name, Ok::<_, hiqlite::Error>(entry), false)?,
+        // A value without the `SwrEntry` wrapper, e.g. persisted by an older version, cannot be
+        // deserialized. Falling back to the DB is always safe and overwrites it.
+        Err(err) => {
+            let cache = name.as_str();
+            metrics::CACHE_ERRORS
+                .with_label_values(&[cache, "get"])
+                .inc();
+            metrics::CACHE_MISSES.with_label_values(&[cache]).inc();
+            warn!(cache, error = %err, "Cache read failed - falling back to the database");
+            None
+        }
+    };
+    let Some(entry) = entry else {
+        return Ok(None);
+    };
+
+    if let Some(ttl) = ttl
+        && is_stale(entry.inserted_at, ttl, Utc::now().timestamp())
+    {
+        // The `inserted_at` makes sure that a lock, which has never been released because of a
+        // crashed node, can only block the refresh of an entry that does not exist anymore.
+        let lock_key = format!("{key}_swr_{}", entry.inserted_at);
+        spawn_refresh_single_flight(key, async move {
+            let hql = DB::hql();
+            match hql.counter_add(name.cache(), lock_key.clone(), 1).await {
+                Ok(1) => {
+                    debug!(cache = name.as_str(), "Refreshing stale cache entry");
+                    if let Err(err) = refresh().await {
+                        warn!(cache = name.as_str(), error = %err, "Stale cache refresh failed");
+                    }
+                    if let Err(err) = hql.counter_del(name.cache(), lock_key).await {
+                        warn!(cache = name.as_str(), error = %err, "Cache lock release failed");
+                    }
+                }
+                // another node is refreshing the value already
+                Ok(_) => {}
+                Err(err) => {
+                    warn!(cache = name.as_str(), error = %err, "Cache lock acquire failed");
+                }
+            }
+        });
+    }
+
+    Ok(Some(entry.value))
+}
+
+#[inline]
+fn is_stale(inserted_at: i64, ttl: i64, now: i64) -> bool {
+    now - inserted_at >= ttl - ttl * SWR_STALE_PCT / 100
+}
+
+/// Spawns the `refresh`, unless a refresh for the same `key` is still running on this node.
+/// Returns `true` if it has been spawned.
+fn spawn_refresh_single_flight<Fut>(key: String, refresh: Fut) -> bool
+where
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    if !SWR_REFRESHING.lock().unwrap().insert(key.clone()) {
+        return false;
+    }
+
+    tokio::spawn(async move {
+        refresh.await;
+        SWR_REFRESHING.lock().unwrap().remove(&key);
+    });
+    true
+}
+
+fn handle_read<V, E>(
+    name: CacheName,
+    res: Result<Option<V>, E>,
+    degradation: bool,
+) -> Result<Option<V>, ErrorResponse>
+where
+    E: Display + Into<ErrorResponse>,
+{
+    let cache = name.as_str();
+    match res {
+        Ok(Some(v)) => {
+            metrics::CACHE_HITS.with_label_values(&[cache]).inc();
+            Ok(Some(v))
+        }
+        Ok(None) => {
+            metrics::CACHE_MISSES.with_label_values(&[cache]).inc();
+            Ok(None)
+        }
+        Err(err) => {
+            metrics::CACHE_ERRORS
+                .with_label_values(&[cache, "get"])
+                .inc();
+            if degradation && name.criticality() == CacheCriticality::BestEffort {
+                warn!(cache, error = %err, "Cache read failed - falling back to the database");
+                metrics::CACHE_MISSES.with_label_values(&[cache]).inc();
+                Ok(None)
+            } else {
+                error!(cache, error = %err, "Cache read failed");
+                Err(err.into())
+            }
+        }
+    }
+}
+
+fn handle_write<E>(
+    name: CacheName,
+    op: &'static str,
+    res: Result<(), E>,
+    degradation: bool,
+) -> Result<(), ErrorResponse>
+where
+    E: Display + Into<ErrorResponse>,
+{
+    let Err(err) = res else {
+        return Ok(());
+    };
+
+    let cache = name.as_str();
+    metrics::CACHE_ERRORS.with_label_values(&[cache, op]).inc();
+    if degradation && name.criticality() == CacheCriticality::BestEffort {
+        warn!(cache, op, error = %err, "Cache write failed - continuing without it");
+        Ok(())
+    } else {
+        error!(cache, op, error = %err, "Cache write failed");
+        Err(err.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rauthy_error::ErrorResponseType;
+
+    const ALL: [CacheName; 14] = [
+        CacheName::AuthProvider,
+        CacheName::AuthProviderCallback,
+        CacheName::AuthProviderLogout,
+        CacheName::AuthProviderSession,
+        CacheName::AuthProviderTemplate,
+        CacheName::Client,
+        CacheName::Html,
+        CacheName::I18nOverrides,
+        CacheName::LoginPageContext,
+        CacheName::Logo,
+        CacheName::MaintenanceMode,
+        CacheName::Session,
+        CacheName::ThemeCss,
+        CacheName::WellKnown,
+    ];
+
+    fn failure() -> ErrorResponse {
+        ErrorResponse::new(ErrorResponseType::Connection, "quorum lost")
+    }
+
+    #[test]
+    fn test_cache_criticality() {
+        for name in [
+            CacheName::AuthProvider,
+            CacheName::Client,
+            CacheName::MaintenanceMode,
+            CacheName::AuthProviderCallback,
+            CacheName::AuthProviderLogout,
+            CacheName::AuthProviderSession,
+            CacheName::Session,
+        ] {
+            assert_eq!(name.criticality(), CacheCriticality::Critical, "{name:?}");
+        }
+        for name in [
+            CacheName::AuthProviderTemplate,
+            CacheName::Html,
+            CacheName::I18nOverrides,
+            CacheName::LoginPageContext,
+            CacheName::Logo,
+            CacheName::ThemeCss,
+            CacheName::WellKnown,
+        ] {
+            assert_eq!(name.criticality(), CacheCriticality::BestEffort, "{name:?}");
+        }
+    }
+
+    #[test]
+    fn test_cache_fallback_matrix() {
+        for name in ALL {
+            let best_effort = name.criticality() == CacheCriticality::BestEffort;
+
+            for degradation in [false, true] {
+                let falls_back = degradation && best_effort;
+
+                let res = handle_read::<u8, _>(name, Err(failure()), degradation);
+                if falls_back {
+                    assert!(matches!(res, Ok(None)), "{name:?} / {degradation}");
+                } else {
+                    let err = res.unwrap_err();
+                    assert_eq!(err.error, ErrorResponseType::Connection);
+                }
+
+                for op in ["put", "delete"] {
+                    let res = handle_write(name, op, Err(failure()), degradation);
+                    assert_eq!(res.is_ok(), falls_back, "{name:?} / {op} / {degradation}");
+                }
+
+                // successful operations are never touched
+                let res = handle_read(name, Ok::<_, ErrorResponse>(Some(7u8)), degradation);
+                assert_eq!(res.unwrap(), Some(7));
+                let res = handle_read::<u8, _>(name, Ok::<_, ErrorResponse>(None), degradation);
+                assert_eq!(res.unwrap(), None);
+                assert!(handle_write(name, "put", Ok::<_, ErrorResponse>(()), degradation).is_ok());
+            }
+        }
+    }
+
+    #[test]
+    fn test_swr_is_stale() {
+        let ttl = 43200;
+        let inserted_at = 1_000_000;
+
+        assert!(!is_stale(inserted_at, ttl, inserted_at));
+        // the last 10% of the TTL
+        assert!(!is_stale(inserted_at, ttl, inserted_at + 38879));
+        assert!(is_stale(inserted_at, ttl, inserted_at + 38880));
+        assert!(is_stale(inserted_at, ttl, inserted_at + ttl));
+    }
+
+    #[tokio::test]
+    async fn test_swr_single_flight() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::time::Duration;
+        use tokio::sync::Semaphore;
+
+        let executed = Arc::new(AtomicUsize::new(0));
+        // keeps the refresh running until we release it
+        let running = Arc::new(Semaphore::new(0));
+
+        // concurrent stale reads while the first refresh is still running
+        let mut handles = Vec::with_capacity(32);
+        for _ in 0..32 {
+            let executed = executed.clone();
+            let running = running.clone();
+            handles.push(tokio::spawn(async move {
+                spawn_refresh_single_flight("swr_test".to_string(), async move {
+                    executed.fetch_add(1, Ordering::SeqCst);
+                    let _permit = running.acquire().await;
+                })
+            }));
+        }
+        let mut spawned = 0;
+        for handle in handles {
+            if handle.await.unwrap() {
+                spawned += 1;
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(spawned, 1);
+        assert_eq!(executed.load(Ordering::SeqCst), 1);
+
+        running.add_permits(1);
+        for _ in 0..100 {
+            if !SWR_REFRESHING.lock().unwrap().contains("swr_test") {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        // a new refresh can run once the previous one has finished
+        let executed_2 = executed.clone();
+        assert!(spawn_refresh_single_flight(
+            "swr_test".to_string(),
+            async move {
+                executed_2.fetch_add(1, Ordering::SeqCst);
+            }
+        ));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(executed.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_cache_metrics() {
+        let name = CacheName::ThemeCss;
+        let label = name.as_str();
+
+        let hits = metrics::CACHE_HITS.with_label_values(&[label]).get();
+        let misses = metrics::CACHE_MISSES.with_label_values(&[label]).get();
+        let errors_get = metrics::CACHE_ERRORS
+            .with_label_values(&[label, "get"])
+            .get();
+        let errors_put = metrics::CACHE_ERRORS
+            .with_label_values(&[label, "put"])
+            .get();
+
+        let _ = handle_read(name, Ok::<_, ErrorResponse>(Some(1u8)), false);
+        let _ = handle_read::<u8, _>(name, Ok::<_, ErrorResponse>(None), false);
+        let _ = handle_read::<u8, _>(name, Err(failure()), true);
+        let _ = handle_write(name, "put", Err(failure()), true);
+
+        // other tests may run in parallel and touch the same counters
+        assert!(metrics::CACHE_HITS.with_label_values(&[label]).get() > hits);
+        // the degraded read counts as a miss as well
+        assert!(metrics::CACHE_MISSES.with_label_values(&[label]).get() >= misses + 2);
+        assert!(
+            metrics::CACHE_ERRORS
+                .with_label_values(&[label, "get"])
+                .get()
+                > errors_get
+        );
+        assert!(
+            metrics::CACHE_ERRORS
+                .with_label_values(&[label, "put"])
+                .get()
+                > errors_put
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use crate::entity::auth_providers::{AuthProvider, AuthProviderTemplate};
+use crate::entity::clients::Client;
+use crate::entity::groups::Group;
+use crate::entity::jwk::{JWKS, JwkKeyPair, JwkKeyPairAlg};
+use crate::entity::roles::Role;
+use crate::entity::scopes::Scope;
+use rauthy_error::ErrorResponse;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::time::Instant;
+use tracing::{error, info};
+
+static WARMUP_DONE: AtomicBool = AtomicBool::new(false);
+
+/// Returns `true` as soon as the cache warmup has finished, or if it is disabled.
+/// The ready check will fail until then.
+#[inline]
+pub fn is_done() -> bool {
+    WARMUP_DONE.load(Ordering::Relaxed)
+}
+
+#[inline]
+pub fn mark_done() {
+    WARMUP_DONE.store(true, Ordering::Relaxed);
+}
+
+/// Pre-populates the `App` cache with all values that are needed on the hot paths, like
+/// the login page or token creation, so the first requests after a restart don't have to
+/// hit the DB. Errors are logged, but never prevent the startup.
+pub async fn warm() {
+    let start = Instant::now();
+
+    match warm_entries().await {
+        Ok(count) => {
+            info!(
+                count,
+                millis = start.elapsed().as_millis() as u64,
+                "Cache warmup finished"
+            );
+        }
+        Err(error) => {
+            error!(%error, "Cache warmup failed - values will be cached lazily");
+        }
+    }
+
+    mark_done();
+}
+
+async fn warm_entries() -> Result<usize, ErrorResponse> {
+    let mut count = 0;
+
+    count += AuthProvider::find_all().await?.len();
+    AuthProviderTemplate::update_cache().await?;
+
+    for client in Client::find_all().await? {
+        client.save_cache().await?;
+        count += 1;
+    }
+
+    count += Scope::find_all().await?.len();
+    count += Role::find_all().await?.len();
+    count += Group::find_all().await?.len();
+    count += JWKS::find_pk().await?.keys.len();
+
+    for alg in [
+        JwkKeyPairAlg::RS256,
+        JwkKeyPairAlg::RS384,
+        JwkKeyPairAlg::RS512,
+        JwkKeyPairAlg::EdDSA,
+    ] {
+        // A fresh instance may not have a key for each algorithm yet.
+        if JwkKeyPair::find_latest(alg).await.is_ok() {
+            count += 1;
+        }
+    }
+
+    Ok(count)
+}
@@ -0,0 +1,857 @@
+use crate::email::mailer::SmtpConnMode;
+use crate::entity::auth_providers::AuthProvider;
+use crate::entity::password::PasswordPolicy;
+use crate::html::csp::{parse_csp_directive, validate_csp_source, validate_referrer_policy};
+use crate::rauthy_config::Vars;
+use rauthy_common::logging::LogLevelAccess;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use tracing::{error, warn};
+
+/// The absolute minimum for `hashing.argon2_m_cost`, which is still considered safe.
+const ARGON2_M_COST_MIN: u32 = 32768;
+/// Below this value, `hashing.argon2_m_cost` works, but should be increased, if possible.
+const ARGON2_M_COST_WARN: u32 = 65536;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigSeverity {
+    /// Rauthy refuses to start.
+    Error,
+    /// Rauthy starts, but the config should be fixed.
+    Warning,
+}
+
+/// A single problem found during the config validation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigIssue {
+    pub severity: ConfigSeverity,
+    /// The config value as `section.key` for the config file or the name of the DB entity.
+    pub var: &'static str,
+    /// The matching environment variable, if the value can be set via env.
+    pub env: Option<&'static str>,
+    pub msg: String,
+    /// An example for a valid value.
+    pub example: &'static str,
+}
+
+impl Display for ConfigIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let level = match self.severity {
+            ConfigSeverity::Error => "ERROR",
+            ConfigSeverity::Warning => "WARN ",
+        };
+        write!(f, "{level} `{}`", self.var)?;
+        if let Some(env) = self.env {
+            write!(f, " / {env}")?;
+        }
+        write!(f, ": {}", self.msg)?;
+        if !self.example.is_empty() {
+            write!(f, "\n      e.g. {}", self.example)?;
+        }
+        Ok(())
+    }
+}
+
+/// Collects all problems found during validation, instead of aborting on the first one.
+#[derive(Debug, Default)]
+pub struct ConfigIssues(pub Vec<ConfigIssue>);
+
+impl ConfigIssues {
+    fn error(
+        &mut self,
+        var: &'static str,
+        env: Option<&'static str>,
+        msg: impl Into<String>,
+        example: &'static str,
+    ) {
+        self.0.push(ConfigIssue {
+            severity: ConfigSeverity::Error,
+            var,
+            env,
+            msg: msg.into(),
+            example,
+        });
+    }
+
+    fn warn(
+        &mut self,
+        var: &'static str,
+        env: Option<&'static str>,
+        msg: impl Into<String>,
+        example: &'static str,
+    ) {
+        self.0.push(ConfigIssue {
+            severity: ConfigSeverity::Warning,
+            var,
+            env,
+            msg: msg.into(),
+            example,
+        });
+    }
+
+    pub fn errors(&self) -> usize {
+        self.0
+            .iter()
+            .filter(|i| i.severity == ConfigSeverity::Error)
+            .count()
+    }
+
+    pub fn warnings(&self) -> usize {
+        self.0.len() - self.errors()
+    }
+
+    /// Prints all issues to stderr. The static config is validated before logging has been
+    /// set up, which means we cannot use `tracing` here.
+    pub fn print(&self) {
+        for issue in &self.0 {
+            eprintln!("{issue}");
+        }
+    }
+
+    /// Logs all issues. Used for validations that happen after the logging setup.
+    pub fn log(&self) {
+        for issue in &self.0 {
+            match issue.severity {
+                ConfigSeverity::Error => error!("{issue}"),
+                ConfigSeverity::Warning => warn!("{issue}"),
+            }
+        }
+    }
+}
+
+impl Vars {
+    /// Validates the parsed config including cross-field consistency and returns every problem
+    /// found.
+    pub fn check(&self) -> ConfigIssues {
+        let mut issues = ConfigIssues::default();
+
+        self.check_database(&mut issues);
+        self.check_email(&mut issues);
+        self.check_hashing(&mut issues);
+        self.check_server(&mut issues);
+        self.check_webauthn(&mut issues);
+
+        if self.device_grant.user_code_length > 255 {
+            issues.error(
+                "device_grant.user_code_length",
+                Some("DEVICE_GRANT_USER_CODE_LENGTH"),
+                "must be <= 255",
+                "user_code_length = 8",
+            );
+        }
+
+        if self.dynamic_clients.enable && self.dynamic_clients.reg_token.is_none() {
+            issues.warn(
+                "dynamic_clients.reg_token",
+                Some("DYN_CLIENT_REG_TOKEN"),
+                "Open dynamic client registration - consider setting a registration token, if \
+                possible",
+                "reg_token = '123SuperSafeToken321'",
+            );
+        }
+
+        if self.encryption.keys.is_empty() {
+            issues.error(
+                "encryption.keys",
+                Some("ENC_KEYS"),
+                "is missing",
+                "keys = ['bVCyTsGaggVy5yqQ/UzluN29DZW41M3hTSkx6Y3NtZmRuQkR2TnJxUTYzcjQ=']",
+            );
+        }
+        if self.encryption.key_active.is_empty() {
+            issues.error(
+                "encryption.key_active",
+                Some("ENC_KEY_ACTIVE"),
+                "is missing",
+                "key_active = 'bVCyTsGaggVy5yqQ'",
+            );
+        } else if !self.encryption.keys.is_empty()
+            && !self.encryption.keys.iter().any(|k| {
+                k.trim().split_once('/').map(|(id, _)| id)
+                    == Some(self.encryption.key_active.as_str())
+            })
+        {
+            issues.error(
+                "encryption.key_active",
+                Some("ENC_KEY_ACTIVE"),
+                format!(
+                    "the key '{}' does not exist in `encryption.keys`",
+                    self.encryption.key_active
+                ),
+                "key_active = 'bVCyTsGaggVy5yqQ'",
+            );
+        }
+
+        if !(1..=365).contains(&self.events.expiring_window_days) {
+            issues.error(
+                "events.expiring_window_days",
+                Some("EVENT_EXPIRING_WINDOW_DAYS"),
+                "must be between 1 and 365",
+                "expiring_window_days = 14",
+            );
+        }
+
+        if self.auth_providers.health_check_threshold == 0 {
+            issues.error(
+                "auth_providers.health_check_threshold",
+                Some("AUTH_PROVIDERS_HEALTH_CHECK_THRESHOLD"),
+                "must be greater than 0",
+                "health_check_threshold = 3",
+            );
+        }
+
+        if self.geo.block_is_whitelist.unwrap_or(false) && self.geo.country_list.is_empty() {
+            issues.error(
+                "geolocation.country_list",
+                None,
+                "`geolocation.country_list_type` is set to `whitelist` but the list is empty, \
+                which would block all requests",
+                "country_list = ['DE', 'FR']",
+            );
+        }
+
+        if LogLevelAccess::from_str(&self.logging.level_access).is_err() {
+            issues.error(
+                "logging.level_access",
+                Some("LOG_LEVEL_ACCESS"),
+                format!("invalid value '{}'", self.logging.level_access),
+                "level_access = 'modifying'",
+            );
+        }
+
+        issues
+    }
+
+    fn check_database(&self, issues: &mut ConfigIssues) {
+        if self.database.sched_maintenance_mins == 0 {
+            issues.error(
+                "database.sched_maintenance_mins",
+                Some("SCHED_MAINTENANCE_MINS"),
+                "The maintenance scheduler interval must be at least 1 minute",
+                "sched_maintenance_mins = 15",
+            );
+        }
+
+        if self.database.hiqlite {
+            return;
+        }
+
+        if self.database.pg_host.is_none() {
+            issues.error(
+                "database.pg_host",
+                Some("PG_HOST"),
+                "Database set to Postgres but the host is missing",
+                "pg_host = 'localhost'",
+            );
+        }
+        if self.database.pg_user.is_none() {
+            issues.error(
+                "database.pg_user",
+                Some("PG_USER"),
+                "Database set to Postgres but the user is missing",
+                "pg_user = 'rauthy'",
+            );
+        }
+        if self.database.pg_password.is_none() {
+            issues.error(
+                "database.pg_password",
+                Some("PG_PASSWORD"),
+                "Database set to Postgres but the password is missing",
+                "pg_password = '123SuperSafe'",
+            );
+        }
+    }
+
+    fn check_email(&self, issues: &mut ConfigIssues) {
+        if let Some(url) = &self.email.smtp_url {
+            if url.contains("://") {
+                issues.error(
+                    "email.smtp_url",
+                    Some("SMTP_URL"),
+                    "must be the plain host without any scheme",
+                    "smtp_url = 'smtp.example.com'",
+                );
+            } else if let Some((_, port)) = url.rsplit_once(':')
+                && port.parse::<u16>().is_ok()
+            {
+                issues.error(
+                    "email.smtp_url",
+                    Some("SMTP_URL"),
+                    "must not contain the port, use `email.smtp_port` instead",
+                    "smtp_url = 'smtp.example.com' + smtp_port = 587",
+                );
+            }
+        }
+
+        if self.email.smtp_conn_mode == SmtpConnMode::XOauth2
+            || self.email.smtp_conn_mode == SmtpConnMode::MicrosoftGraph
+        {
+            if self.email.xoauth_url.is_none() {
+                issues.error(
+                    "email.xoauth_url",
+                    Some("SMTP_XOAUTH2_URL"),
+                    "is required for the configured `email.smtp_conn_mode`",
+                    "xoauth_url = 'https://login.microsoftonline.com/<tenant>/oauth2/v2.0/token'",
+                );
+            }
+            if self.email.xoauth_client_id.is_none() {
+                issues.error(
+                    "email.xoauth_client_id",
+                    Some("SMTP_XOAUTH2_CLIENT_ID"),
+                    "is required for the configured `email.smtp_conn_mode`",
+                    "xoauth_client_id = 'rauthy-smtp'",
+                );
+            }
+            if self.email.xoauth_client_secret.is_none() {
+                issues.error(
+                    "email.xoauth_client_secret",
+                    Some("SMTP_XOAUTH2_CLIENT_SECRET"),
+                    "is required for the configured `email.smtp_conn_mode`",
+                    "xoauth_client_secret = 'SuperSecret'",
+                );
+            }
+            if self.email.xoauth_scope.is_none() {
+                issues.error(
+                    "email.xoauth_scope",
+                    Some("SMTP_XOAUTH2_SCOPE"),
+                    "is required for the configured `email.smtp_conn_mode`",
+                    "xoauth_scope = 'https://graph.microsoft.com/.default'",
+                );
+            }
+        }
+        if self.email.smtp_conn_mode == SmtpConnMode::MicrosoftGraph
+            && self.email.microsoft_graph_uri.is_none()
+        {
+            issues.error(
+                "email.microsoft_graph_uri",
+                Some("SMTP_MICROSOFT_GRAPH_URI"),
+                "is required for `email.smtp_conn_mode = 'microsoft_graph'`",
+                "microsoft_graph_uri = 'https://graph.microsoft.com/v1.0/users/<id>/sendMail'",
+            );
+        }
+    }
+
+    fn check_hashing(&self, issues: &mut ConfigIssues) {
+        let m_cost = self.hashing.argon2_m_cost;
+        let t_cost = self.hashing.argon2_t_cost;
+        let p_cost = self.hashing.argon2_p_cost;
+
+        if m_cost < ARGON2_M_COST_MIN {
+            issues.error(
+                "hashing.argon2_m_cost",
+                Some("ARGON2_M_COST"),
+                format!("{m_cost} is below the safe minimum of {ARGON2_M_COST_MIN}"),
+                "argon2_m_cost = 131072",
+            );
+        } else if m_cost < ARGON2_M_COST_WARN {
+            issues.warn(
+                "hashing.argon2_m_cost",
+                Some("ARGON2_M_COST"),
+                format!("{m_cost} is weak, increase it if your hardware allows it"),
+                "argon2_m_cost = 131072",
+            );
+        }
+        if t_cost < 1 {
+            issues.error(
+                "hashing.argon2_t_cost",
+                Some("ARGON2_T_COST"),
+                "must be at least 1",
+                "argon2_t_cost = 4",
+            );
+        }
+        if p_cost < 1 {
+            issues.error(
+                "hashing.argon2_p_cost",
+                Some("ARGON2_P_COST"),
+                "must be at least 1",
+                "argon2_p_cost = 8",
+            );
+        }
+
+        if m_cost >= ARGON2_M_COST_MIN
+            && t_cost >= 1
+            && p_cost >= 1
+            && let Err(err) = argon2::Params::new(m_cost, t_cost, p_cost, None)
+        {
+            issues.error(
+                "hashing.argon2_m_cost",
+                Some("ARGON2_M_COST"),
+                format!("invalid Argon2id params: {err}"),
+                "argon2_m_cost = 131072 (must be >= 8 * argon2_p_cost)",
+            );
+        }
+    }
+
+    fn check_server(&self, issues: &mut ConfigIssues) {
+        #[cfg(not(target_os = "windows"))]
+        let schemes = ["http", "https", "http_https", "unix_http", "unix_https"];
+        #[cfg(target_os = "windows")]
+        let schemes = ["http", "https", "http_https"];
+        if !schemes.contains(&self.server.scheme.as_ref()) {
+            issues.error(
+                "server.scheme",
+                Some("LISTEN_SCHEME"),
+                format!(
+                    "invalid value '{}', must be one of: {}",
+                    self.server.scheme,
+                    schemes.join(", ")
+                ),
+                "scheme = 'http_https'",
+            );
+        }
+
+        let pub_url = &self.server.pub_url;
+        if pub_url.is_empty() {
+            issues.error(
+                "server.pub_url",
+                Some("PUB_URL"),
+                "is empty",
+                "pub_url = 'auth.example.com'",
+            );
+        } else if pub_url.contains("://") {
+            issues.error(
+                "server.pub_url",
+                Some("PUB_URL"),
+                "must not contain the scheme, Rauthy builds it automatically",
+                "pub_url = 'auth.example.com'",
+            );
+        } else if pub_url.ends_with('/') {
+            issues.error(
+                "server.pub_url",
+                Some("PUB_URL"),
+                "must not end with a '/', otherwise the issuer would contain '//'",
+                "pub_url = 'auth.example.com'",
+            );
+        } else if pub_url.contains('/') {
+            issues.error(
+                "server.pub_url",
+                Some("PUB_URL"),
+                "must not contain a path, Rauthy is always served under '/auth/v1'",
+                "pub_url = 'auth.example.com'",
+            );
+        }
+
+        for (var, env, sources, example) in [
+            (
+                "server.csp_img_src",
+                "CSP_IMG_SRC",
+                &self.server.csp_img_src,
+                "csp_img_src = ['https://static.example.com']",
+            ),
+            (
+                "server.csp_style_src",
+                "CSP_STYLE_SRC",
+                &self.server.csp_style_src,
+                "csp_style_src = ['https://static.example.com']",
+            ),
+            (
+                "server.frame_ancestors",
+                "FRAME_ANCESTORS",
+                &self.server.frame_ancestors,
+                "frame_ancestors = ['https://app.example.com']",
+            ),
+        ] {
+            for source in sources {
+                if let Err(err) = validate_csp_source(source) {
+                    issues.error(var, Some(env), err, example);
+                }
+            }
+        }
+        for directive in &self.server.csp_directives {
+            if let Err(err) = parse_csp_directive(directive) {
+                issues.error(
+                    "server.csp_directives",
+                    Some("CSP_DIRECTIVES"),
+                    err,
+                    "csp_directives = ['font-src https://fonts.example.com']",
+                );
+            }
+        }
+        if let Err(err) = validate_referrer_policy(&self.server.referrer_policy) {
+            issues.error(
+                "server.referrer_policy",
+                Some("REFERRER_POLICY"),
+                err,
+                "referrer_policy = 'no-referrer'",
+            );
+        }
+
+        if self.server.proxy_mode && self.server.trusted_proxies.is_empty() {
+            issues.error(
+                "server.trusted_proxies",
+                Some("TRUSTED_PROXIES"),
+                "`server.proxy_mode` is set but no trusted proxies are configured",
+                "trusted_proxies = ['192.168.14.0/24']",
+            );
+        }
+
+        if let Some(url) = &self.dev.provider_callback_url {
+            if !self.dev.dev_mode {
+                issues.warn(
+                    "dev.provider_callback_url",
+                    Some("DEV_MODE_PROVIDER_CALLBACK_URL"),
+                    "is set but will be ignored, because `dev.dev_mode` is disabled",
+                    "",
+                );
+            } else if url.contains("://") || url.ends_with('/') {
+                issues.error(
+                    "dev.provider_callback_url",
+                    Some("DEV_MODE_PROVIDER_CALLBACK_URL"),
+                    "must be a plain host without scheme and trailing '/' like `server.pub_url`",
+                    "provider_callback_url = 'localhost:5173'",
+                );
+            } else if url != pub_url {
+                issues.warn(
+                    "dev.provider_callback_url",
+                    Some("DEV_MODE_PROVIDER_CALLBACK_URL"),
+                    format!(
+                        "the upstream provider callback URI is not under the public URL '{pub_url}'"
+                    ),
+                    "provider_callback_url = 'localhost:5173'",
+                );
+            }
+        }
+    }
+
+    fn check_webauthn(&self, issues: &mut ConfigIssues) {
+        if self.webauthn.rp_id.is_empty() {
+            issues.error(
+                "webauthn.rp_id",
+                Some("RP_ID"),
+                "is missing",
+                "rp_id = 'auth.example.com'",
+            );
+        }
+
+        if self.webauthn.rp_origin.is_empty() {
+            issues.error(
+                "webauthn.rp_origin",
+                Some("RP_ORIGIN"),
+                "is missing",
+                "rp_origin = 'https://auth.example.com:443'",
+            );
+        } else {
+            let port_valid = self
+                .webauthn
+                .rp_origin
+                .rsplit_once(':')
+                .map(|(_, port)| port.parse::<u16>().is_ok())
+                .unwrap_or(false);
+            if !port_valid || webauthn_rs::prelude::Url::parse(&self.webauthn.rp_origin).is_err() {
+                issues.error(
+                    "webauthn.rp_origin",
+                    Some("RP_ORIGIN"),
+                    "must be a full URL including scheme and port",
+                    "rp_origin = 'https://auth.example.com:443'",
+                );
+            }
+        }
+
+        let pub_host = self
+            .server
+            .pub_url
+            .split([':', '/'])
+            .next()
+            .unwrap_or_default();
+        let rp_id = &self.webauthn.rp_id;
+        if !rp_id.is_empty()
+            && !pub_host.is_empty()
+            && pub_host != rp_id
+            && !pub_host.ends_with(&format!(".{rp_id}"))
+        {
+            issues.warn(
+                "webauthn.rp_id",
+                Some("RP_ID"),
+                format!(
+                    "'{rp_id}' does not match the host of `server.pub_url`, passkeys will not work"
+                ),
+                "rp_id = 'auth.example.com'",
+            );
+        }
+    }
+
+    /// Validates the config and panics with the amount of errors, if any were found.
+    pub fn validate(&self) {
+        let issues = self.check();
+        issues.print();
+
+        let errors = issues.errors();
+        if errors > 0 {
+            panic!("Found {errors} error(s) in the Rauthy config, refusing to start");
+        }
+    }
+}
+
+/// Validates the config entities stored inside the database. These can only be fixed via the
+/// Admin UI, which means we only report problems and never prevent the start.
+pub async fn check_db_config(dev_mode: bool) -> ConfigIssues {
+    let mut issues = ConfigIssues::default();
+
+    match PasswordPolicy::find().await {
+        Ok(policy) => {
+            if policy.length_min > policy.length_max {
+                issues.warn(
+                    "password_policy.length_min",
+                    None,
+                    format!(
+                        "{} is greater than `length_max` {}, no password can be valid",
+                        policy.length_min, policy.length_max
+                    ),
+                    "length_min = 14, length_max = 128",
+                );
+            } else if policy.length_min < 8 {
+                issues.warn(
+                    "password_policy.length_min",
+                    None,
+                    format!("{} is too short for a safe password", policy.length_min),
+                    "length_min = 14",
+                );
+            }
+        }
+        Err(err) => error!(?err, "Cannot read the password policy for validation"),
+    }
+
+    match AuthProvider::find_all().await {
+        Ok(providers) => {
+            for provider in providers.iter().filter(|p| p.enabled) {
+                let insecure = [
+                    provider.authorization_endpoint.as_str(),
+                    provider.token_endpoint.as_str(),
+                    provider.userinfo_endpoint.as_str(),
+                ]
+                .iter()
+                .any(|ep| ep.starts_with("http://"));
+
+                if insecure && !dev_mode {
+                    issues.warn(
+                        "auth_providers",
+                        None,
+                        format!(
+                            "upstream provider '{}' uses plain HTTP endpoints",
+                            provider.name
+                        ),
+                        "token_endpoint = 'https://idp.example.com/token'",
+                    );
+                }
+            }
+        }
+        Err(err) => error!(?err, "Cannot read auth providers for validation"),
+    }
+
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn valid_vars() -> Vars {
+        let mut vars = Vars::default();
+        vars.encryption.keys =
+            vec!["bVCyTsGaggVy5yqQ/UzluN29DZW41M3hTSkx6Y3NtZmRuQkR2TnJxUTYzcjQ=".to_string()];
+        vars.encryption.key_active = "bVCyTsGaggVy5yqQ".to_string();
+        vars.server.pub_url = "auth.example.com".to_string();
+        vars.webauthn.rp_id = "auth.example.com".to_string();
+        vars.webauthn.rp_origin = "https://auth.example.com:443".to_string();
+        vars
+    }
+
+    #[test]
+    fn test_config_check() {
+        let issues = valid_vars().check();
+        assert!(issues.0.is_empty(), "{:?}", issues.0);
+
+        type Breaker = fn(&mut Vars);
+        let table: Vec<(Breaker, &str, ConfigSeverity)> = vec![
+            (
+                |v| v.server.pub_url = "auth.example.com/".to_string(),
+                "server.pub_url",
+                ConfigSeverity::Error,
+            ),
+            (
+                |v| v.server.pub_url = "https://auth.example.com".to_string(),
+                "server.pub_url",
+                ConfigSeverity::Error,
+            ),
+            (
+                |v| v.server.pub_url = "example.com/auth".to_string(),
+                "server.pub_url",
+                ConfigSeverity::Error,
+            ),
+            (
+                |v| v.server.pub_url = String::default(),
+                "server.pub_url",
+                ConfigSeverity::Error,
+            ),
+            (
+                |v| v.email.smtp_url = Some("smtp.example.com:587".to_string()),
+                "email.smtp_url",
+                ConfigSeverity::Error,
+            ),
+            (
+                |v| v.email.smtp_url = Some("smtps://smtp.example.com".to_string()),
+                "email.smtp_url",
+                ConfigSeverity::Error,
+            ),
+            (
+                |v| v.hashing.argon2_m_cost = 16384,
+                "hashing.argon2_m_cost",
+                ConfigSeverity::Error,
+            ),
+            (
+                |v| v.hashing.argon2_m_cost = 32768,
+                "hashing.argon2_m_cost",
+                ConfigSeverity::Warning,
+            ),
+            (
+                |v| v.hashing.argon2_t_cost = 0,
+                "hashing.argon2_t_cost",
+                ConfigSeverity::Error,
+            ),
+            (
+                |v| v.hashing.argon2_p_cost = 0,
+                "hashing.argon2_p_cost",
+                ConfigSeverity::Error,
+            ),
+            (
+                |v| {
+                    v.dev.dev_mode = true;
+                    v.dev.provider_callback_url = Some("localhost:5173".to_string());
+                },
+                "dev.provider_callback_url",
+                ConfigSeverity::Warning,
+            ),
+            (
+                |v| {
+                    v.dev.dev_mode = true;
+                    v.dev.provider_callback_url = Some("http://localhost:5173".to_string());
+                },
+                "dev.provider_callback_url",
+                ConfigSeverity::Error,
+            ),
+            (
+                |v| v.dev.provider_callback_url = Some("localhost:5173".to_string()),
+                "dev.provider_callback_url",
+                ConfigSeverity::Warning,
+            ),
+            (
+                |v| v.database.hiqlite = false,
+                "database.pg_host",
+                ConfigSeverity::Error,
+            ),
+            (
+                |v| v.database.sched_maintenance_mins = 0,
+                "database.sched_maintenance_mins",
+                ConfigSeverity::Error,
+            ),
+            (
+                |v| v.device_grant.user_code_length = 256,
+                "device_grant.user_code_length",
+                ConfigSeverity::Error,
+            ),
+            (
+                |v| v.encryption.key_active = "unknown".to_string(),
+                "encryption.key_active",
+                ConfigSeverity::Error,
+            ),
+            (
+                |v| v.encryption.keys = Vec::default(),
+                "encryption.keys",
+                ConfigSeverity::Error,
+            ),
+            (
+                |v| v.email.smtp_conn_mode = SmtpConnMode::MicrosoftGraph,
+                "email.microsoft_graph_uri",
+                ConfigSeverity::Error,
+            ),
+            (
+                |v| v.logging.level_access = "everything".into(),
+                "logging.level_access",
+                ConfigSeverity::Error,
+            ),
+            (
+                |v| v.server.scheme = "ftp".into(),
+                "server.scheme",
+                ConfigSeverity::Error,
+            ),
+            (
+                |v| v.server.proxy_mode = true,
+                "server.trusted_proxies",
+                ConfigSeverity::Error,
+            ),
+            (
+                |v| v.server.csp_img_src = vec!["https://a.example.com;script-src".to_string()],
+                "server.csp_img_src",
+                ConfigSeverity::Error,
+            ),
+            (
+                |v| v.server.csp_style_src = vec!["a b".to_string()],
+                "server.csp_style_src",
+                ConfigSeverity::Error,
+            ),
+            (
+                |v| v.server.frame_ancestors = vec!["https://a.example.com,".to_string()],
+                "server.frame_ancestors",
+                ConfigSeverity::Error,
+            ),
+            (
+                |v| v.server.csp_directives = vec!["script-src *".to_string()],
+                "server.csp_directives",
+                ConfigSeverity::Error,
+            ),
+            (
+                |v| v.server.referrer_policy = "none".to_string(),
+                "server.referrer_policy",
+                ConfigSeverity::Error,
+            ),
+            (
+                |v| v.webauthn.rp_origin = "https://auth.example.com".to_string(),
+                "webauthn.rp_origin",
+                ConfigSeverity::Error,
+            ),
+            (
+                |v| v.webauthn.rp_id = "other.com".to_string(),
+                "webauthn.rp_id",
+                ConfigSeverity::Warning,
+            ),
+        ];
+
+        for (i, (breaker, var, severity)) in table.into_iter().enumerate() {
+            let mut vars = valid_vars();
+            breaker(&mut vars);
+            let issues = vars.check();
+            assert!(
+                issues
+                    .0
+                    .iter()
+                    .any(|issue| issue.var == var && issue.severity == severity),
+                "case {i}: expected {severity:?} for {var}, got {:?}",
+                issues.0
+            );
+        }
+
+        // every problem is reported, not just the first one
+        let mut vars = valid_vars();
+        vars.server.pub_url = "auth.example.com/".to_string();
+        vars.hashing.argon2_m_cost = 1024;
+        vars.email.smtp_url = Some("smtp.example.com:25".to_string());
+        let issues = vars.check();
+        assert_eq!(issues.errors(), 3);
+        assert_eq!(issues.warnings(), 0);
+    }
+
+    #[test]
+    fn test_config_issue_display() {
+        let mut issues = ConfigIssues::default();
+        issues.error(
+            "server.pub_url",
+            Some("PUB_URL"),
+            "is empty",
+            "pub_url = 'auth.example.com'",
+        );
+        assert_eq!(
+            issues.0[0].to_string(),
+            "ERROR `server.pub_url` / PUB_URL: is empty\n      e.g. pub_url = 'auth.example.com'"
+        );
+    }
+}
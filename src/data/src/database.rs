@@ -1,6 +1,7 @@
 use crate::entity::db_version::DbVersion;
 use crate::migration::db_migrate_dev::migrate_dev_data;
 use crate::migration::{anti_lockout, bootstrap, db_migrate};
+use crate::pii;
 use crate::rauthy_config::RauthyConfig;
 use futures_util::StreamExt;
 use hiqlite::macros::{CacheVariants, embed::*};
@@ -60,6 +61,7 @@ pub enum Cache {
     ToS,
     EmailRateLimit,
     CredStuffDetect,
+    LoginPageContext,
 }
 
 pub struct DB;
@@ -240,6 +242,9 @@ impl DB {
             debug!(?report, "Database Migration Report");
         }
 
+        // The E-Mail hash key is needed as soon as the first user is inserted or looked up.
+        pii::init().await?;
+
         // migrate dynamic DB data
         let config = RauthyConfig::get();
         if !config.vars.dev.dev_mode && config.is_primary_node {
@@ -94,6 +94,7 @@ pub async fn send_custom(
         subject: format!("{email_sub_prefix} - {subject}"),
         text,
         html,
+        result: None,
     };
 
     RauthyConfig::get()
@@ -1,9 +1,11 @@
 use crate::email::i18n::confirm_change::I18nEmailConfirmChange;
 use crate::email::mailer::{EMail, EmailType};
 use crate::entity::theme::ThemeCssFull;
+use crate::entity::user_notification_prefs::should_notify;
 use crate::entity::users::User;
 use crate::rauthy_config::RauthyConfig;
 use askama::Template;
+use rauthy_api_types::users::NotificationCategory;
 use std::time::Duration;
 use tracing::error;
 
@@ -35,6 +37,10 @@ pub async fn send_email_confirm_change(
     email_changed_to: &str,
     was_admin_action: bool,
 ) {
+    if !should_notify(user, NotificationCategory::EmailChange).await {
+        return;
+    }
+
     let i18n = I18nEmailConfirmChange::build(&user.language);
     let email_sub_prefix = &RauthyConfig::get().vars.email.sub_prefix;
     let text = EMailConfirmChangeTxt {
@@ -75,6 +81,7 @@ pub async fn send_email_confirm_change(
             html.render()
                 .expect("Template rendering: EMailConfirmChangeHtml"),
         ),
+        result: None,
     };
 
     let res = RauthyConfig::get()
@@ -1,13 +1,14 @@
 use crate::email::email_ts_prettify;
 use crate::email::i18n::change_info_new::I18nEmailChangeInfoNew;
-use crate::email::mailer::{EMail, EmailType};
+use crate::email::mailer::{EMail, EmailFeedback, EmailType, send_email};
 use crate::entity::magic_links::MagicLink;
 use crate::entity::theme::ThemeCssFull;
+use crate::entity::user_notification_prefs::should_notify;
 use crate::entity::users::User;
 use crate::rauthy_config::RauthyConfig;
 use askama::Template;
-use std::time::Duration;
-use tracing::error;
+use rauthy_api_types::users::NotificationCategory;
+use rauthy_error::ErrorResponse;
 
 #[derive(Default, Template)]
 #[template(path = "email/change_info.html")]
@@ -43,7 +44,12 @@ pub async fn send_email_change_info_new(
     user: &User,
     user_tz: Option<&str>,
     new_email: String,
-) {
+    feedback: EmailFeedback,
+) -> Result<(), ErrorResponse> {
+    if !should_notify(user, NotificationCategory::EmailChange).await {
+        return Ok(());
+    }
+
     let link = format!(
         "{}users/{}/email_confirm/{}",
         RauthyConfig::get().issuer,
@@ -83,7 +89,7 @@ pub async fn send_email_change_info_new(
     let req = EMail {
         typ: EmailType::EmailChangeInfo,
         recipient_name: user.email_recipient_name(),
-        address: new_email.clone(),
+        address: new_email,
         subject: format!("{} - {}", email_sub_prefix, i18n.subject),
         text: Some(
             text.render()
@@ -93,19 +99,8 @@ pub async fn send_email_change_info_new(
             html.render()
                 .expect("Template rendering: EMailChangeInfoNewHtml"),
         ),
+        result: None,
     };
 
-    let res = RauthyConfig::get()
-        .tx_email
-        .send_timeout(req, Duration::from_secs(10))
-        .await;
-    match res {
-        Ok(_) => {}
-        Err(ref e) => {
-            error!(
-                new_email, error = ?e,
-                "sending magic link email request",
-            );
-        }
-    }
+    send_email(&RauthyConfig::get().tx_email, req, feedback).await
 }
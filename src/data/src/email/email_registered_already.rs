@@ -80,6 +80,7 @@ pub async fn send_email_registered_already(user: &User) {
             html.render()
                 .expect("Template rendering: EmailRegisteredAlreadyHtml"),
         ),
+        result: None,
     };
 
     let res = RauthyConfig::get()
@@ -13,7 +13,7 @@ pub struct I18nEmailChangeInfoNew<'a> {
 
 impl I18nEmailChangeInfoNew<'_> {
     pub fn build(lang: &Language) -> Self {
-        match lang {
+        match *lang {
             Language::De => Self::build_de(),
             Language::En => Self::build_en(),
             Language::Fr => Self::build_fr(),
@@ -23,6 +23,19 @@ impl I18nEmailChangeInfoNew<'_> {
             Language::Ru => Self::build_ru(),
             Language::Uk => Self::build_uk(),
             Language::ZhHans => Self::build_zh_hans(),
+            _ => Self::build_custom(lang),
+        }
+    }
+
+    fn build_custom(lang: &Language) -> Self {
+        let en = Self::build_en();
+        Self {
+            subject: lang.translate("email.change_info_new.subject", en.subject),
+            header: lang.translate("email.change_info_new.header", en.header),
+            click_link: lang.translate("email.change_info_new.click_link", en.click_link),
+            validity: lang.translate("email.change_info_new.validity", en.validity),
+            expires: lang.translate("email.change_info_new.expires", en.expires),
+            button_text: lang.translate("email.change_info_new.button_text", en.button_text),
         }
     }
 }
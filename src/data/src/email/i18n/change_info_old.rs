@@ -14,7 +14,7 @@ pub struct I18nEmailChangeInfoOld<'a> {
 
 impl I18nEmailChangeInfoOld<'_> {
     pub fn build(lang: &Language) -> Self {
-        match lang {
+        match *lang {
             Language::De => Self::build_de(),
             Language::En => Self::build_en(),
             Language::Fr => Self::build_fr(),
@@ -24,6 +24,20 @@ impl I18nEmailChangeInfoOld<'_> {
             Language::Ru => Self::build_ru(),
             Language::Uk => Self::build_uk(),
             Language::ZhHans => Self::build_zh_hans(),
+            _ => Self::build_custom(lang),
+        }
+    }
+
+    fn build_custom(lang: &Language) -> Self {
+        let en = Self::build_en();
+        Self {
+            subject: lang.translate("email.change_info_old.subject", en.subject),
+            header: lang.translate("email.change_info_old.header", en.header),
+            change_info: lang.translate("email.change_info_old.change_info", en.change_info),
+            click_link: lang.translate("email.change_info_old.click_link", en.click_link),
+            validity: lang.translate("email.change_info_old.validity", en.validity),
+            expires: lang.translate("email.change_info_old.expires", en.expires),
+            button_text: lang.translate("email.change_info_old.button_text", en.button_text),
         }
     }
 }
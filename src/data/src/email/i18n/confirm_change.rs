@@ -10,7 +10,7 @@ pub struct I18nEmailConfirmChange<'a> {
 
 impl I18nEmailConfirmChange<'_> {
     pub fn build(lang: &Language) -> Self {
-        match lang {
+        match *lang {
             Language::De => Self::build_de(),
             Language::En => Self::build_en(),
             Language::Fr => Self::build_fr(),
@@ -20,6 +20,17 @@ impl I18nEmailConfirmChange<'_> {
             Language::Ru => Self::build_ru(),
             Language::Uk => Self::build_uk(),
             Language::ZhHans => Self::build_zh_hans(),
+            _ => Self::build_custom(lang),
+        }
+    }
+
+    fn build_custom(lang: &Language) -> Self {
+        let en = Self::build_en();
+        Self {
+            subject: lang.translate("email.confirm_change.subject", en.subject),
+            msg: lang.translate("email.confirm_change.msg", en.msg),
+            msg_from_admin: lang
+                .translate("email.confirm_change.msg_from_admin", en.msg_from_admin),
         }
     }
 }
@@ -11,7 +11,7 @@ pub struct I18nEmailConfirmChangeHtml<'a> {
 
 impl I18nEmailConfirmChangeHtml<'_> {
     pub fn build(lang: &Language) -> Self {
-        match lang {
+        match *lang {
             Language::De => Self::build_de(),
             Language::En => Self::build_en(),
             Language::Fr => Self::build_fr(),
@@ -21,6 +21,17 @@ impl I18nEmailConfirmChangeHtml<'_> {
             Language::Ru => Self::build_ru(),
             Language::Uk => Self::build_uk(),
             Language::ZhHans => Self::build_zh_hans(),
+            _ => Self::build_custom(lang),
+        }
+    }
+
+    fn build_custom(lang: &Language) -> Self {
+        let en = Self::build_en();
+        Self {
+            title: lang.translate("email.confirm_change_html.title", en.title),
+            text_changed: lang.translate("email.confirm_change_html.text_changed", en.text_changed),
+            text_login: lang.translate("email.confirm_change_html.text_login", en.text_login),
+            to: lang.translate("email.confirm_change_html.to", en.to),
         }
     }
 }
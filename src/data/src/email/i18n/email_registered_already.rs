@@ -32,7 +32,7 @@ impl From<&'static VarsTemplate> for I18nEmailRegisteredAlready<'_> {
 impl I18nEmailRegisteredAlready<'_> {
     pub fn build(lang: &Language) -> Self {
         let tpls = &RauthyConfig::get().vars.templates.email_registered_already;
-        match lang {
+        match *lang {
             Language::De => (&tpls.de).into(),
             Language::En => (&tpls.en).into(),
             Language::Fr => (&tpls.fr).into(),
@@ -42,6 +42,20 @@ impl I18nEmailRegisteredAlready<'_> {
             Language::Ru => (&tpls.ru).into(),
             Language::Uk => (&tpls.uk).into(),
             Language::ZhHans => (&tpls.zhhans).into(),
+            _ => Self::build_custom(lang, (&tpls.en).into()),
+        }
+    }
+
+    fn build_custom(lang: &Language, en: Self) -> Self {
+        Self {
+            subject: lang.translate("email.email_registered_already.subject", en.subject),
+            header: lang.translate("email.email_registered_already.header", en.header),
+            text: lang.translate("email.email_registered_already.text", en.text),
+            footer: lang.translate_opt("email.email_registered_already.footer", en.footer),
+            button_text_request_new: lang.translate(
+                "email.email_registered_already.button_text_request_new",
+                en.button_text_request_new,
+            ),
         }
     }
 }
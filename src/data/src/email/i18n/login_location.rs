@@ -12,7 +12,7 @@ pub struct I18nEmailLoginLocation<'a> {
 
 impl I18nEmailLoginLocation<'_> {
     pub fn build(lang: &Language) -> Self {
-        match lang {
+        match *lang {
             Language::De => Self::build_de(),
             Language::En => Self::build_en(),
             Language::Fr => Self::build_fr(),
@@ -22,6 +22,19 @@ impl I18nEmailLoginLocation<'_> {
             Language::Ru => Self::build_ru(),
             Language::Uk => Self::build_uk(),
             Language::ZhHans => Self::build_zh_hans(),
+            _ => Self::build_custom(lang),
+        }
+    }
+
+    fn build_custom(lang: &Language) -> Self {
+        let en = Self::build_en();
+        Self {
+            subject: lang.translate("email.login_location.subject", en.subject),
+            unknown_location: lang
+                .translate("email.login_location.unknown_location", en.unknown_location),
+            if_invalid: lang.translate("email.login_location.if_invalid", en.if_invalid),
+            revoke_link: lang.translate("email.login_location.revoke_link", en.revoke_link),
+            account_link: lang.translate("email.login_location.account_link", en.account_link),
         }
     }
 }
@@ -44,7 +44,7 @@ impl From<&'static VarsTemplate> for I18nEmailPasswordNew<'_> {
 impl I18nEmailPasswordNew<'_> {
     pub fn build(lang: &Language) -> Self {
         let tpls = &RauthyConfig::get().vars.templates.password_new;
-        match lang {
+        match *lang {
             Language::De => (&tpls.de).into(),
             Language::En => (&tpls.en).into(),
             Language::Fr => (&tpls.fr).into(),
@@ -54,6 +54,20 @@ impl I18nEmailPasswordNew<'_> {
             Language::Ru => (&tpls.ru).into(),
             Language::Uk => (&tpls.uk).into(),
             Language::ZhHans => (&tpls.zhhans).into(),
+            _ => Self::build_custom(lang, (&tpls.en).into()),
+        }
+    }
+
+    fn build_custom(lang: &Language, en: Self) -> Self {
+        Self {
+            subject: lang.translate("email.password_new.subject", en.subject),
+            header: lang.translate("email.password_new.header", en.header),
+            text: lang.translate_opt("email.password_new.text", en.text),
+            click_link: lang.translate("email.password_new.click_link", en.click_link),
+            validity: lang.translate("email.password_new.validity", en.validity),
+            expires: lang.translate("email.password_new.expires", en.expires),
+            button_text: lang.translate("email.password_new.button_text", en.button_text),
+            footer: lang.translate_opt("email.password_new.footer", en.footer),
         }
     }
 }
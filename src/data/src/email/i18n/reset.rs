@@ -46,7 +46,7 @@ impl From<&'static VarsTemplate> for I18nEmailReset<'_> {
 impl I18nEmailReset<'_> {
     pub fn build(lang: &Language) -> Self {
         let tpls = &RauthyConfig::get().vars.templates.password_reset;
-        match lang {
+        match *lang {
             Language::De => (&tpls.de).into(),
             Language::En => (&tpls.en).into(),
             Language::Fr => (&tpls.fr).into(),
@@ -56,6 +56,24 @@ impl I18nEmailReset<'_> {
             Language::Ru => (&tpls.ru).into(),
             Language::Uk => (&tpls.uk).into(),
             Language::ZhHans => (&tpls.zhhans).into(),
+            _ => Self::build_custom(lang, (&tpls.en).into()),
+        }
+    }
+
+    fn build_custom(lang: &Language, en: Self) -> Self {
+        Self {
+            subject: lang.translate("email.reset.subject", en.subject),
+            header: lang.translate("email.reset.header", en.header),
+            text: lang.translate_opt("email.reset.text", en.text),
+            click_link: lang.translate("email.reset.click_link", en.click_link),
+            validity: lang.translate("email.reset.validity", en.validity),
+            expires: lang.translate("email.reset.expires", en.expires),
+            button_text: lang.translate("email.reset.button_text", en.button_text),
+            footer: lang.translate_opt("email.reset.footer", en.footer),
+            button_text_request_new: lang.translate_opt(
+                "email.reset.button_text_request_new",
+                en.button_text_request_new,
+            ),
         }
     }
 }
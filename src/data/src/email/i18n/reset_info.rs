@@ -12,7 +12,7 @@ pub struct I18nEmailResetInfo<'a> {
 
 impl I18nEmailResetInfo<'_> {
     pub fn build(lang: &Language) -> Self {
-        match lang {
+        match *lang {
             Language::De => Self::build_de(),
             Language::En => Self::build_en(),
             Language::Fr => Self::build_fr(),
@@ -22,6 +22,18 @@ impl I18nEmailResetInfo<'_> {
             Language::Ru => Self::build_ru(),
             Language::Uk => Self::build_uk(),
             Language::ZhHans => Self::build_zh_hans(),
+            _ => Self::build_custom(lang),
+        }
+    }
+
+    fn build_custom(lang: &Language) -> Self {
+        let en = Self::build_en();
+        Self {
+            subject: lang.translate("email.reset_info.subject", en.subject),
+            expires_1: lang.translate("email.reset_info.expires_1", en.expires_1),
+            expires_2: lang.translate("email.reset_info.expires_2", en.expires_2),
+            update: lang.translate("email.reset_info.update", en.update),
+            button_text: lang.translate("email.reset_info.button_text", en.button_text),
         }
     }
 }
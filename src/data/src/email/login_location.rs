@@ -1,9 +1,11 @@
 use crate::email::i18n::login_location::I18nEmailLoginLocation;
 use crate::email::mailer::{EMail, EmailType};
 use crate::entity::theme::ThemeCssFull;
+use crate::entity::user_notification_prefs::should_notify;
 use crate::entity::users::User;
 use crate::rauthy_config::RauthyConfig;
 use askama::Template;
+use rauthy_api_types::users::NotificationCategory;
 use std::time::Duration;
 use tracing::error;
 
@@ -48,6 +50,10 @@ pub async fn send_login_location(
     location: Option<String>,
     revoke_code: String,
 ) {
+    if !should_notify(user, NotificationCategory::NewLoginLocation).await {
+        return;
+    }
+
     let pub_url = &RauthyConfig::get().pub_url_with_scheme;
     let link_revoke = format!(
         "{pub_url}/auth/v1/users/{}/revoke/{revoke_code}?ip={ip}",
@@ -102,6 +108,7 @@ pub async fn send_login_location(
             html.render()
                 .expect("Template rendering: EMailLoginLocationHtml"),
         ),
+        result: None,
     };
 
     let res = RauthyConfig::get()
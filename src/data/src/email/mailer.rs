@@ -2,16 +2,23 @@ use crate::database::DB;
 use crate::email::mailer_microsoft_graph::sender_microsoft_graph;
 use crate::email::smtp_oauth_token::SmtpOauthToken;
 use crate::events::event::Event;
+use crate::metrics;
 use crate::rauthy_config::RauthyConfig;
 use lettre::message::{MultiPart, SinglePart};
 use lettre::transport::smtp::authentication::Mechanism;
-use lettre::transport::smtp::{authentication, client};
+use lettre::transport::smtp::extension::ClientId;
+use lettre::transport::smtp::{SUBMISSION_PORT, SUBMISSIONS_PORT, authentication, client};
 use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor, message};
+use rauthy_api_types::generic::SmtpTestResponse;
+use rauthy_common::shutdown::TaskGuard;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use serde::Deserialize;
 use std::fmt::{Display, Formatter};
-use std::time::Duration;
-use tokio::sync::mpsc;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::error::SendTimeoutError;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time;
 use tracing::{debug, error, info, warn};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -41,6 +48,12 @@ impl Display for EmailType {
     }
 }
 
+/// Max time to wait for a free slot in the E-Mail queue
+const QUEUE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Max time to wait for the result with `EmailFeedback::Confirm`. The sender does a retry with
+/// 10 second timeouts each.
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(25);
+
 #[derive(Debug)]
 pub struct EMail {
     pub typ: EmailType,
@@ -49,6 +62,99 @@ pub struct EMail {
     pub subject: String,
     pub text: Option<String>,
     pub html: Option<String>,
+    /// Receives the send result, if the flow waits for it
+    pub result: Option<oneshot::Sender<Result<(), ErrorResponse>>>,
+}
+
+/// How a failed E-Mail is reported back to the flow, which triggered it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EmailFeedback {
+    /// Waits until the E-Mail has been sent and returns an error, if it failed. Only use this
+    /// for authenticated flows.
+    Confirm,
+    /// Only queues the E-Mail and never returns an error, because the response of an
+    /// unauthenticated flow must not differ, which would make user enumeration possible.
+    /// Failures are only visible as `EmailSendError` events and in the
+    /// `rauthy_emails_failed_total` metric.
+    Silent,
+}
+
+/// Queues the `email` for the sender and reports failures depending on the `feedback`.
+pub async fn send_email(
+    tx: &mpsc::Sender<EMail>,
+    mut email: EMail,
+    feedback: EmailFeedback,
+) -> Result<(), ErrorResponse> {
+    let typ = email.typ;
+    let rx = if feedback == EmailFeedback::Confirm {
+        let (tx_res, rx) = oneshot::channel();
+        email.result = Some(tx_res);
+        Some(rx)
+    } else {
+        None
+    };
+
+    if let Err(err) = tx.send_timeout(email, QUEUE_TIMEOUT).await {
+        let address = match &err {
+            SendTimeoutError::Timeout(email) | SendTimeoutError::Closed(email) => {
+                email.address.clone()
+            }
+        };
+        error!(%typ, address, "Could not queue E-Mail: {err}");
+        email_send_failed(typ, &address).await;
+
+        return match feedback {
+            EmailFeedback::Confirm => Err(email_err()),
+            EmailFeedback::Silent => Ok(()),
+        };
+    }
+
+    let Some(rx) = rx else {
+        return Ok(());
+    };
+    match time::timeout(CONFIRM_TIMEOUT, rx).await {
+        Ok(Ok(Ok(()))) => Ok(()),
+        Ok(Ok(Err(err))) => {
+            debug!(%typ, ?err, "E-Mail could not be sent");
+            Err(email_err())
+        }
+        // the sender dropped the E-Mail without a result
+        Ok(Err(_)) => Err(email_err()),
+        Err(_) => Err(ErrorResponse::new(
+            ErrorResponseType::Timeout,
+            "Timeout while sending the E-Mail, please try again later",
+        )),
+    }
+}
+
+#[inline]
+fn email_err() -> ErrorResponse {
+    ErrorResponse::new(
+        ErrorResponseType::Connection,
+        "The E-Mail could not be sent, please try again later",
+    )
+}
+
+/// Must be called by each sender for E-Mails, which could not be sent even after retries.
+pub(crate) async fn email_send_failed(typ: EmailType, address: &str) {
+    metrics::EMAILS_FAILED
+        .with_label_values(&[typ.to_string().as_str()])
+        .inc();
+    if let Err(err) = Event::email_send_err(typ, address).send().await {
+        error!(?err, "Could not push EmailSendError event");
+    }
+}
+
+/// Reports the send `res` back to the flow, if it waits for it.
+#[inline]
+pub(crate) fn reply(
+    result: Option<oneshot::Sender<Result<(), ErrorResponse>>>,
+    res: Result<(), ErrorResponse>,
+) {
+    if let Some(tx) = result {
+        // the flow may not wait anymore
+        let _ = tx.send(res);
+    }
 }
 
 #[derive(Debug, PartialEq, Deserialize)]
@@ -71,6 +177,16 @@ impl From<&str> for SmtpConnMode {
     }
 }
 
+/// Returns `true` if E-Mails can actually be delivered and are not only logged.
+pub fn is_configured() -> bool {
+    let vars = &RauthyConfig::get().vars.email;
+    match vars.smtp_conn_mode {
+        SmtpConnMode::Default | SmtpConnMode::XOauth2 => vars.smtp_url.is_some(),
+        SmtpConnMode::MicrosoftGraph => true,
+        SmtpConnMode::Test => false,
+    }
+}
+
 pub async fn sender(rx: mpsc::Receiver<EMail>) {
     debug!("E-Mail sender started");
 
@@ -95,6 +211,7 @@ async fn sender_test_debug(mut rx: mpsc::Receiver<EMail>) {
     loop {
         if let Some(email) = rx.recv().await {
             debug!("New E-Mail for address: {}", email.address);
+            reply(email.result, Ok(()));
         } else {
             warn!("Received 'None' in email 'sender' - exiting");
             return;
@@ -113,8 +230,11 @@ async fn sender_default_smtp(smtp_url: &str, mut rx: mpsc::Receiver<EMail>) {
     let mut mailer = create_mailer(smtp_url).await;
     loop {
         debug!("Listening for incoming send E-Mail requests");
-        if let Some(req) = rx.recv().await {
+        if let Some(mut req) = rx.recv().await {
             debug!("New E-Mail for address: {:?}", req.address);
+            // keeps the shutdown waiting until this E-Mail has been handled
+            let _guard = TaskGuard::new();
+            let result = req.result.take();
 
             let to = format!("{} <{}>", req.recipient_name, req.address);
 
@@ -154,6 +274,7 @@ async fn sender_default_smtp(smtp_url: &str, mut rx: mpsc::Receiver<EMail>) {
                     match mailer.send(message.clone()).await {
                         Ok(_) => {
                             info!("E-Mail to '{}' sent successfully!", req.address);
+                            reply(result, Ok(()));
                             continue;
                         }
                         Err(err) => {
@@ -174,6 +295,7 @@ async fn sender_default_smtp(smtp_url: &str, mut rx: mpsc::Receiver<EMail>) {
                     match mailer.send(message.clone()).await {
                         Ok(_) => {
                             info!("E-Mail to '{}' sent successfully after retry!", req.address);
+                            reply(result, Ok(()));
                         }
                         Err(err) => {
                             // Log loudly and emit an event so admins see the failure.
@@ -182,17 +304,21 @@ async fn sender_default_smtp(smtp_url: &str, mut rx: mpsc::Receiver<EMail>) {
                                 "Could not send E-Mail to '{}' even after retrying - dropping it",
                                 req.address
                             );
-                            if let Err(err) =
-                                Event::email_send_err(req.typ, &req.address).send().await
-                            {
-                                error!(?err, "Could not push EmailSendError event");
-                            }
+                            email_send_failed(req.typ, &req.address).await;
+                            reply(result, Err(smtp_err(err)));
                         }
                     }
                 }
                 Err(err) => {
                     // this should never happen
                     error!("Error building the E-Mail to '{}': {:?}", req.address, err);
+                    reply(
+                        result,
+                        Err(ErrorResponse::new(
+                            ErrorResponseType::Internal,
+                            format!("Error building the E-Mail: {err}"),
+                        )),
+                    );
                 }
             }
         } else {
@@ -247,33 +373,9 @@ async fn connect_test_smtp(
     smtp_port: Option<u16>,
 ) -> Result<AsyncSmtpTransport<lettre::Tokio1Executor>, ErrorResponse> {
     let vars = &RauthyConfig::get().vars.email;
-    let username = vars
-        .smtp_username
-        .as_deref()
-        .expect("SMTP_USERNAME is not set")
-        .trim()
-        .to_string();
-
-    let mut mechanisms = Vec::with_capacity(2);
-    let creds = if vars.smtp_conn_mode == SmtpConnMode::XOauth2 {
-        mechanisms.push(Mechanism::Xoauth2);
-
-        let token = SmtpOauthToken::get()
-            .await
-            .expect("Could not retrieve a `client_credntials` token for SMTP XOAUTH2");
-        authentication::Credentials::new(username, token.access_token)
-    } else {
-        mechanisms.push(Mechanism::Plain);
-        mechanisms.push(Mechanism::Login);
-
-        let password = vars
-            .smtp_password
-            .as_deref()
-            .expect("SMTP_PASSWORD is not set")
-            .trim()
-            .to_string();
-        authentication::Credentials::new(username, password)
-    };
+    let (mechanisms, creds) = smtp_credentials()
+        .await
+        .unwrap_or_else(|err| panic!("{}", err.message));
 
     let mut builder = if vars.starttls_only {
         AsyncSmtpTransport::<lettre::Tokio1Executor>::starttls_relay(smtp_url)
@@ -347,6 +449,175 @@ async fn connect_test_smtp(
     ))
 }
 
+async fn smtp_credentials() -> Result<(Vec<Mechanism>, authentication::Credentials), ErrorResponse>
+{
+    let vars = &RauthyConfig::get().vars.email;
+    let username = vars
+        .smtp_username
+        .as_deref()
+        .ok_or_else(|| ErrorResponse::new(ErrorResponseType::Internal, "SMTP_USERNAME is not set"))?
+        .trim()
+        .to_string();
+
+    let mut mechanisms = Vec::with_capacity(2);
+    let creds = if vars.smtp_conn_mode == SmtpConnMode::XOauth2 {
+        mechanisms.push(Mechanism::Xoauth2);
+
+        let token = SmtpOauthToken::get().await.map_err(|err| {
+            ErrorResponse::new(
+                ErrorResponseType::Internal,
+                format!(
+                    "Could not retrieve a `client_credentials` token for SMTP XOAUTH2: {}",
+                    err.message
+                ),
+            )
+        })?;
+        authentication::Credentials::new(username, token.access_token)
+    } else {
+        mechanisms.push(Mechanism::Plain);
+        mechanisms.push(Mechanism::Login);
+
+        let password = vars
+            .smtp_password
+            .as_deref()
+            .ok_or_else(|| {
+                ErrorResponse::new(ErrorResponseType::Internal, "SMTP_PASSWORD is not set")
+            })?
+            .trim()
+            .to_string();
+        authentication::Credentials::new(username, password)
+    };
+
+    Ok((mechanisms, creds))
+}
+
+/// Validates the SMTP config with a real connection to the configured relay. It does the
+/// handshake, TLS and authentication, but closes the connection before any message is sent.
+/// Errors are returned inside the response.
+pub async fn test_smtp() -> SmtpTestResponse {
+    let start = Instant::now();
+
+    let mut res = SmtpTestResponse::default();
+    if let Err(err) = test_smtp_connection(&mut res).await {
+        res.error = Some(err.message.to_string());
+    }
+    res.latency_ms = start.elapsed().as_millis() as u64;
+
+    res
+}
+
+async fn test_smtp_connection(res: &mut SmtpTestResponse) -> Result<(), ErrorResponse> {
+    let vars = &RauthyConfig::get().vars.email;
+    if vars.smtp_conn_mode == SmtpConnMode::MicrosoftGraph {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "SMTP is not used with `smtp_conn_mode = 'microsoft_graph'`",
+        ));
+    }
+    let Some(smtp_url) = vars.smtp_url.as_deref() else {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "`email.smtp_url` is not configured",
+        ));
+    };
+
+    let port = vars.smtp_port.unwrap_or(if vars.danger_insecure {
+        1025
+    } else if vars.starttls_only {
+        SUBMISSION_PORT
+    } else {
+        SUBMISSIONS_PORT
+    });
+    let timeout = Duration::from_secs(10);
+
+    // a plain TCP connection first to be able to tell network and TLS issues apart
+    match time::timeout(timeout, TcpStream::connect((smtp_url, port))).await {
+        Ok(Ok(_)) => res.tcp_connected = true,
+        Ok(Err(err)) => {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Connection,
+                format!("Cannot connect to {smtp_url}:{port}: {err}"),
+            ));
+        }
+        Err(_) => {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Connection,
+                format!("Timeout connecting to {smtp_url}:{port}"),
+            ));
+        }
+    }
+
+    let tls_params = if vars.danger_insecure {
+        None
+    } else {
+        let mut builder = client::TlsParameters::builder(smtp_url.to_string());
+        if let Some(root_ca) = &vars.root_ca {
+            let cert = client::Certificate::from_pem(root_ca.as_bytes()).map_err(|err| {
+                ErrorResponse::new(
+                    ErrorResponseType::Internal,
+                    format!("Invalid `email.root_ca`: {err}"),
+                )
+            })?;
+            builder = builder.add_root_certificate(cert);
+        }
+        Some(builder.build_rustls().map_err(smtp_err)?)
+    };
+
+    let hello_name = ClientId::default();
+    let implicit_tls = if vars.starttls_only {
+        None
+    } else {
+        tls_params.clone()
+    };
+    let mut conn = client::AsyncSmtpConnection::connect_tokio1(
+        (smtp_url, port),
+        Some(timeout),
+        &hello_name,
+        implicit_tls,
+        None,
+    )
+    .await
+    .map_err(smtp_err)?;
+    res.tls_negotiated = conn.is_encrypted();
+    res.server_greeting = conn.server_info().name().to_string();
+
+    let result: Result<(), ErrorResponse> = async {
+        if vars.starttls_only
+            && let Some(params) = tls_params
+        {
+            if !conn.can_starttls() {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::Connection,
+                    "The server does not support STARTTLS",
+                ));
+            }
+            conn.starttls(params, &hello_name).await.map_err(smtp_err)?;
+            res.tls_negotiated = conn.is_encrypted();
+        }
+
+        // the insecure mode never authenticates, like the sender
+        if !vars.danger_insecure {
+            let (mechanisms, creds) = smtp_credentials().await?;
+            conn.auth(&mechanisms, &creds).await.map_err(smtp_err)?;
+            res.authenticated = true;
+        }
+
+        Ok(())
+    }
+    .await;
+
+    if let Err(err) = conn.quit().await {
+        debug!(?err, "Error closing the SMTP test connection");
+    }
+
+    result
+}
+
+#[inline]
+fn smtp_err(err: lettre::transport::smtp::Error) -> ErrorResponse {
+    ErrorResponse::new(ErrorResponseType::Connection, err.to_string())
+}
+
 async fn conn_test_smtp_insecure(
     smtp_url: &str,
     smtp_port: Option<u16>,
@@ -385,3 +656,73 @@ async fn conn_test_smtp_insecure(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email() -> EMail {
+        EMail {
+            typ: EmailType::PasswordReset,
+            recipient_name: "Admin".to_string(),
+            address: "admin@localhost".to_string(),
+            subject: "Reset".to_string(),
+            text: Some("Reset".to_string()),
+            html: None,
+            result: None,
+        }
+    }
+
+    /// Answers each E-Mail like a sender with the given `res`.
+    fn mailer(res: Result<(), ErrorResponseType>) -> (mpsc::Sender<EMail>, oneshot::Receiver<()>) {
+        let (tx, mut rx) = mpsc::channel::<EMail>(1);
+        let (tx_done, rx_done) = oneshot::channel();
+        tokio::spawn(async move {
+            let email = rx.recv().await.unwrap();
+            let res = res.map_err(|typ| ErrorResponse::new(typ, "Connection refused"));
+            reply(email.result, res);
+            tx_done.send(()).unwrap();
+        });
+        (tx, rx_done)
+    }
+
+    #[tokio::test]
+    async fn test_send_email_failing_mailer() {
+        // authenticated flows get the error
+        let (tx, done) = mailer(Err(ErrorResponseType::Connection));
+        let err = send_email(&tx, email(), EmailFeedback::Confirm)
+            .await
+            .unwrap_err();
+        assert_eq!(err.error, ErrorResponseType::Connection);
+        done.await.unwrap();
+
+        // unauthenticated flows must not see any difference
+        let (tx, done) = mailer(Err(ErrorResponseType::Connection));
+        send_email(&tx, email(), EmailFeedback::Silent)
+            .await
+            .unwrap();
+        done.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_send_email_success() {
+        for feedback in [EmailFeedback::Confirm, EmailFeedback::Silent] {
+            let (tx, done) = mailer(Ok(()));
+            send_email(&tx, email(), feedback).await.unwrap();
+            done.await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_email_dropped() {
+        // a sender which drops the E-Mail without an answer
+        let (tx, mut rx) = mpsc::channel::<EMail>(1);
+        tokio::spawn(async move {
+            drop(rx.recv().await);
+        });
+        let err = send_email(&tx, email(), EmailFeedback::Confirm)
+            .await
+            .unwrap_err();
+        assert_eq!(err.error, ErrorResponseType::Connection);
+    }
+}
@@ -1,9 +1,9 @@
-use crate::email::mailer::EMail;
+use crate::email::mailer::{EMail, email_send_failed, reply};
 use crate::email::smtp_oauth_token::SmtpOauthToken;
-use crate::events::event::Event;
 use crate::rauthy_config::RauthyConfig;
 use lettre::message;
 use rauthy_common::HTTP_CLIENT;
+use rauthy_common::shutdown::TaskGuard;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use serde::Serialize;
 use std::time::Duration;
@@ -77,8 +77,11 @@ pub async fn sender_microsoft_graph(mut rx: mpsc::Receiver<EMail>) {
 
     loop {
         debug!("Listening for incoming send E-Mail requests");
-        if let Some(req) = rx.recv().await {
+        if let Some(mut req) = rx.recv().await {
             debug!("New E-Mail for address: {:?}", req.address);
+            // keeps the shutdown waiting until this E-Mail has been handled
+            let _guard = TaskGuard::new();
+            let result = req.result.take();
 
             let body = if let Some(content) = req.html {
                 EmailBody {
@@ -131,11 +134,12 @@ pub async fn sender_microsoft_graph(mut rx: mpsc::Receiver<EMail>) {
                         "Could not send E-Mail to '{}' even after retrying - dropping it",
                         recipient
                     );
-                    if let Err(err) = Event::email_send_err(email_typ, &recipient).send().await {
-                        error!(?err, "Could not push EmailSendError event");
-                    }
+                    email_send_failed(email_typ, &recipient).await;
+                    reply(result, Err(err));
+                    continue;
                 }
             }
+            reply(result, Ok(()));
         } else {
             warn!("Received 'None' in email 'sender' - exiting");
             break;
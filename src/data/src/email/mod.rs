@@ -35,7 +35,7 @@ fn email_ts_prettify_with(
         .and_then(|tz| Tz::from_str(tz).ok())
         .unwrap_or_else(|| Tz::from_str(&config.tz_fallback).unwrap());
 
-    let fmt_str = match lang {
+    let fmt_str = match *lang {
         Language::De => config.de.as_ref(),
         Language::En => config.en.as_ref(),
         Language::Fr => config.fr.as_ref(),
@@ -45,6 +45,7 @@ fn email_ts_prettify_with(
         Language::Ru => config.ru.as_ref(),
         Language::Uk => config.uk.as_ref(),
         Language::ZhHans => config.zhhans.as_ref(),
+        _ => config.en.as_ref(),
     };
 
     dt.with_timezone(&tz).format(fmt_str).to_string()
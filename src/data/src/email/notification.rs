@@ -54,6 +54,7 @@ pub async fn send_email_notification(
         subject: notification.head.to_string(),
         text: Some(text.render().expect("Template rendering: EMailEventTxt")),
         html: Some(html.render().expect("Template rendering: EMailEventHtml")),
+        result: None,
     };
 
     let res = tx_email.send_timeout(req, Duration::from_secs(10)).await;
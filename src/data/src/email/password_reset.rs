@@ -1,14 +1,15 @@
 use crate::email::email_ts_prettify;
 use crate::email::i18n::password_new::I18nEmailPasswordNew;
 use crate::email::i18n::reset::I18nEmailReset;
-use crate::email::mailer::{EMail, EmailType};
+use crate::email::mailer::{EMail, EmailFeedback, EmailType, send_email};
 use crate::entity::magic_links::MagicLink;
 use crate::entity::theme::ThemeCssFull;
+use crate::entity::user_notification_prefs::should_notify;
 use crate::entity::users::User;
 use crate::rauthy_config::RauthyConfig;
 use askama::Template;
-use std::time::Duration;
-use tracing::error;
+use rauthy_api_types::users::NotificationCategory;
+use rauthy_error::ErrorResponse;
 
 #[derive(Default, Template)]
 #[template(path = "email/reset.html")]
@@ -46,7 +47,16 @@ pub struct EmailResetTxt<'a> {
     pub link_request_new: &'a str,
 }
 
-pub async fn send_pwd_reset(magic_link: &MagicLink, user: &User, user_tz: Option<&str>) {
+pub async fn send_pwd_reset(
+    magic_link: &MagicLink,
+    user: &User,
+    user_tz: Option<&str>,
+    feedback: EmailFeedback,
+) -> Result<(), ErrorResponse> {
+    if !should_notify(user, NotificationCategory::PasswordReset).await {
+        return Ok(());
+    }
+
     let link = format!(
         "{}users/{}/reset/{}?type={}",
         RauthyConfig::get().issuer,
@@ -144,19 +154,8 @@ pub async fn send_pwd_reset(magic_link: &MagicLink, user: &User, user_tz: Option
         subject: format!("{email_sub_prefix} - {subject}"),
         text: Some(text.render().expect("Template rendering: EmailResetTxt")),
         html: Some(html.render().expect("Template rendering: EmailResetHtml")),
+        result: None,
     };
 
-    let res = RauthyConfig::get()
-        .tx_email
-        .send_timeout(req, Duration::from_secs(10))
-        .await;
-    match res {
-        Ok(_) => {}
-        Err(ref e) => {
-            error!(
-                user.email, error = ?e,
-                "sending password reset email",
-            );
-        }
-    }
+    send_email(&RauthyConfig::get().tx_email, req, feedback).await
 }
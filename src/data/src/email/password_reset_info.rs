@@ -2,9 +2,11 @@ use crate::email::email_ts_prettify;
 use crate::email::i18n::reset_info::I18nEmailResetInfo;
 use crate::email::mailer::{EMail, EmailType};
 use crate::entity::theme::ThemeCssFull;
+use crate::entity::user_notification_prefs::should_notify;
 use crate::entity::users::User;
 use crate::rauthy_config::RauthyConfig;
 use askama::Template;
+use rauthy_api_types::users::NotificationCategory;
 use std::time::Duration;
 use tracing::error;
 
@@ -36,6 +38,10 @@ pub struct EmailResetInfoTxt<'a> {
 }
 
 pub async fn send_pwd_reset_info(user: &User) {
+    if !should_notify(user, NotificationCategory::PasswordExpiry).await {
+        return;
+    }
+
     let exp = email_ts_prettify(user.password_expires.unwrap(), &user.language, None);
     let link = format!(
         "{}/auth/v1/account",
@@ -81,6 +87,7 @@ pub async fn send_pwd_reset_info(user: &User) {
             html.render()
                 .expect("Template rendering: EmailResetInfoHtml"),
         ),
+        result: None,
     };
 
     let res = RauthyConfig::get()
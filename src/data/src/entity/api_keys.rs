@@ -4,7 +4,7 @@ use cryptr::{EncKeys, EncValue};
 use hiqlite::macros::params;
 use rauthy_api_types::api_keys::ApiKeyResponse;
 use rauthy_common::constants::{API_KEY_LENGTH, CACHE_TTL_APP};
-use rauthy_common::utils::{deserialize, get_rand, serialize};
+use rauthy_common::utils::{deserialize, get_rand, secure_compare, serialize};
 use rauthy_common::{is_hiqlite, sha256};
 use rauthy_derive::FromPgRow;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
@@ -408,7 +408,7 @@ impl ApiKey {
             ));
         }
 
-        if self.secret.as_slice() == sha256!(secret.as_bytes()) {
+        if secure_compare(&self.secret, sha256!(secret.as_bytes())) {
             Ok(())
         } else {
             Err(ErrorResponse::new(
@@ -528,17 +528,6 @@ impl From<rauthy_api_types::api_keys::ApiKeyAccess> for ApiKeyAccess {
 mod tests {
     use super::*;
     use cryptr::EncKeys;
-    use std::sync::Once;
-
-    static INIT_KEYS: Once = Once::new();
-
-    fn init_keys() {
-        INIT_KEYS.call_once(|| {
-            let _ = EncKeys::generate_with_id("test".to_string())
-                .unwrap()
-                .init();
-        });
-    }
 
     fn access() -> Vec<ApiKeyAccess> {
         vec![ApiKeyAccess {
@@ -592,7 +581,7 @@ mod tests {
 
     #[test]
     fn generated_api_key_token_validates() {
-        init_keys();
+        crate::init_test_enc_keys();
         let mut secret_plain = get_rand(API_KEY_LENGTH);
         let token = format!("provision${secret_plain}");
         let (name, secret) = token.split_once('$').unwrap();
@@ -1,4 +1,5 @@
 use crate::database::{Cache, DB};
+use crate::rauthy_config::RauthyConfig;
 use chrono::Utc;
 use hiqlite::macros::params;
 use rauthy_common::constants::{CACHE_TTL_APP, IDX_APP_VERSION, RAUTHY_VERSION};
@@ -96,11 +97,14 @@ ON CONFLICT(id) DO UPDATE SET data = $1"#;
 
     pub async fn lookup() -> Result<(semver::Version, String), ErrorResponse> {
         // it makes no sense to use the glopbal client here - no benefit from connection pooling
-        let client = reqwest::Client::builder()
+        let mut builder = reqwest::Client::builder()
             .connect_timeout(Duration::from_secs(10))
             .timeout(Duration::from_secs(10))
-            .user_agent(format!("Rauthy v{RAUTHY_VERSION} App Version Checker"))
-            .build()?;
+            .user_agent(format!("Rauthy v{RAUTHY_VERSION} App Version Checker"));
+        if let Some(proxy) = RauthyConfig::get().vars.http_client.proxy() {
+            builder = builder.proxy(proxy);
+        }
+        let client = builder.build()?;
 
         let res = client
             .get("https://api.github.com/repos/sebadob/rauthy/releases/latest")
@@ -144,23 +144,47 @@ impl Client {
             token_endpoint: String::new(),
             userinfo_endpoint: String::new(),
             jwks_endpoint: None,
+            end_session_endpoint: None,
             use_pkce: true,
             client_secret_basic: false,
             client_secret_post: false,
             auto_onboarding: false,
             auto_link: false,
+            auto_upstream_logout: false,
             client_id: "rauthy".to_owned(),
             client_secret: None,
+            client_cert_pem: None,
+            client_key_pem: None,
+            apple_team_id: None,
+            apple_key_id: None,
+            apple_private_key: None,
+            issuer_template: None,
+            allowed_tenants: None,
+            sync_policy: None,
+            upstream_refresh: None,
+            require_email_verified: None,
+            sort_order: None,
             scope: String::new(),
             admin_claim_path: None,
             admin_claim_value: None,
             mfa_claim_path: None,
             mfa_claim_value: None,
+            claim_path_email: None,
+            claim_path_given_name: None,
+            claim_path_family_name: None,
+            claim_path_groups: None,
+            restrict_hd: None,
+            allowed_email_domains: None,
+            denied_email_domains: None,
+            use_userinfo: None,
+            auto_refresh: Some(false),
+            extra_auth_params: None,
+            callback_timeout_secs: None,
         };
 
         match AuthProvider::find_by_iss(payload.issuer.clone()).await {
             Ok(provider) if !config.vars.atproto.enable => {
-                AuthProvider::delete(&provider.id).await?;
+                AuthProvider::delete(&provider.id, true).await?;
             }
             Err(_) if config.vars.atproto.enable => {
                 AuthProvider::create(payload).await?;
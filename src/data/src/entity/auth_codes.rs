@@ -1,7 +1,8 @@
 use crate::database::{Cache, DB};
+use crate::metrics;
 use crate::rauthy_config::RauthyConfig;
 use chrono::Utc;
-use rauthy_common::utils::get_rand;
+use rauthy_common::utils::{encode_query_value, get_rand};
 use rauthy_error::ErrorResponse;
 use serde::{Deserialize, Serialize};
 use std::fmt::Write;
@@ -46,6 +47,7 @@ impl AuthCode {
     // Deletes an Authorization Code from the cache
     pub async fn delete(&self) -> Result<(), ErrorResponse> {
         DB::hql().delete(Cache::AuthCode, self.id.clone()).await?;
+        metrics::AUTH_CODES_CONSUMED.inc();
         Ok(())
     }
 
@@ -73,7 +75,7 @@ impl AuthCode {
         let append_char = if redirect_uri.contains('?') { '&' } else { '?' };
         let mut loc = format!("{}{}code={}", redirect_uri, append_char, self.id);
         if let Some(state) = state {
-            write!(loc, "&state={state}")?;
+            write!(loc, "&state={}", encode_query_value(state))?;
         };
         Ok(loc)
     }
@@ -94,6 +96,7 @@ impl AuthCode {
         let exp = Utc::now()
             .add(chrono::Duration::seconds(lifetime_secs as i64))
             .timestamp();
+        metrics::AUTH_CODES_CREATED.inc();
         Self {
             id,
             exp,
@@ -1,11 +1,38 @@
 use crate::entity::auth_providers::AuthProviderIdClaims;
+use chrono::Utc;
+use openssl::ecdsa::EcdsaSig;
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Private};
+use openssl::sign::Signer;
+use rauthy_api_types::auth_providers::ProviderLookupResponse;
 use rauthy_common::constants::APPLICATION_JSON;
 use rauthy_common::http_client;
+use rauthy_common::utils::base64_url_no_pad_encode;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use reqwest::header::{ACCEPT, AUTHORIZATION};
 use serde::Deserialize;
+use serde_json::json;
 use tracing::debug;
 
+const GITHUB_ISSUER: &str = "github.com";
+const APPLE_ISSUER: &str = "https://appleid.apple.com";
+/// Apple accepts client secrets which are valid for up to 6 months, but we create a new one for
+/// each token request anyway.
+const APPLE_CLIENT_SECRET_LIFETIME: i64 = 300;
+
+#[derive(Debug, Deserialize)]
+struct AppleUser {
+    name: Option<AppleUserName>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AppleUserName {
+    first_name: Option<String>,
+    last_name: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 struct GitHubEmailPrivateResponse {
     email: String,
@@ -15,12 +42,140 @@ struct GitHubEmailPrivateResponse {
     // visibility: Option<String>,
 }
 
+/// GitHub does not implement the OIDC metadata lookup. Returns its fixed config, if the
+/// `issuer` points to GitHub.
+pub fn github_lookup(issuer: &str) -> Option<ProviderLookupResponse> {
+    let issuer = issuer
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .trim_end_matches('/');
+    if issuer != GITHUB_ISSUER {
+        return None;
+    }
+
+    Some(ProviderLookupResponse {
+        issuer: GITHUB_ISSUER.to_string(),
+        authorization_endpoint: "https://github.com/login/oauth/authorize".to_string(),
+        token_endpoint: "https://github.com/login/oauth/access_token".to_string(),
+        userinfo_endpoint: "https://api.github.com/user".to_string(),
+        jwks_endpoint: None,
+        end_session_endpoint: None,
+        // `user:email` is mandatory for the `/user/emails` lookup
+        scope: "read:user user:email".to_string(),
+        use_pkce: false,
+        client_secret_basic: true,
+        client_secret_post: false,
+    })
+}
+
+/// Apple only provides a partial openid-configuration without a `userinfo_endpoint`. Returns its
+/// fixed config, if the `issuer` points to Apple.
+pub fn apple_lookup(issuer: &str) -> Option<ProviderLookupResponse> {
+    let issuer = issuer.trim_start_matches("https://").trim_end_matches('/');
+    if issuer != APPLE_ISSUER.trim_start_matches("https://") {
+        return None;
+    }
+
+    Some(ProviderLookupResponse {
+        issuer: APPLE_ISSUER.to_string(),
+        authorization_endpoint: format!("{APPLE_ISSUER}/auth/authorize"),
+        token_endpoint: format!("{APPLE_ISSUER}/auth/token"),
+        userinfo_endpoint: String::default(),
+        jwks_endpoint: Some(format!("{APPLE_ISSUER}/auth/keys")),
+        end_session_endpoint: None,
+        // the `name` is only ever sent via the `form_post` `user` value
+        scope: "name email".to_string(),
+        use_pkce: false,
+        client_secret_basic: false,
+        client_secret_post: true,
+    })
+}
+
+/// Parses the Apple `.p8` private key, which must be an EC P-256 key.
+pub fn apple_signing_key(pem: &str) -> Result<PKey<Private>, ErrorResponse> {
+    let key = PKey::private_key_from_pem(pem.as_bytes()).map_err(|_| {
+        ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "`apple_private_key` is not a valid PEM private key",
+        )
+    })?;
+
+    let is_p256 = key
+        .ec_key()
+        .ok()
+        .and_then(|ec| ec.group().curve_name())
+        .is_some_and(|nid| nid == Nid::X9_62_PRIME256V1);
+    if !is_p256 {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "`apple_private_key` must be an EC P-256 key",
+        ));
+    }
+
+    Ok(key)
+}
+
+/// Apple does not use a static client secret. Instead, it must be an ES256 JWT signed with the
+/// private key from the Apple Developer account.
+pub fn apple_client_secret(
+    team_id: &str,
+    key_id: &str,
+    client_id: &str,
+    key_pem: &str,
+) -> Result<String, ErrorResponse> {
+    let key = apple_signing_key(key_pem)?;
+    let now = Utc::now().timestamp();
+
+    let header = json!({
+        "alg": "ES256",
+        "kid": key_id,
+    });
+    let claims = json!({
+        "iss": team_id,
+        "iat": now,
+        "exp": now + APPLE_CLIENT_SECRET_LIFETIME,
+        "aud": APPLE_ISSUER,
+        "sub": client_id,
+    });
+
+    let mut token = base64_url_no_pad_encode(&serde_json::to_vec(&header)?);
+    token.push('.');
+    token.push_str(&base64_url_no_pad_encode(&serde_json::to_vec(&claims)?));
+
+    // openssl creates DER signatures, while JWS needs the fixed size `r || s`
+    let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+    let der = signer.sign_oneshot_to_vec(token.as_bytes())?;
+    let sig = EcdsaSig::from_der(&der)?;
+    let mut raw = sig.r().to_vec_padded(32)?;
+    raw.extend(sig.s().to_vec_padded(32)?);
+
+    token.push('.');
+    token.push_str(&base64_url_no_pad_encode(&raw));
+    Ok(token)
+}
+
+/// Apple never includes the name in the ID token. It is only sent once during the very first
+/// consent as the JSON `user` form value. Returns `(given_name, family_name)`.
+pub fn apple_user_names(user: &str) -> Result<(Option<String>, Option<String>), ErrorResponse> {
+    let user = serde_json::from_str::<AppleUser>(user).map_err(|err| {
+        ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            format!("Invalid Apple `user` value: {err}"),
+        )
+    })?;
+    let non_empty = |value: Option<String>| value.filter(|v| !v.trim().is_empty());
+
+    Ok(match user.name {
+        Some(name) => (non_empty(name.first_name), non_empty(name.last_name)),
+        None => (None, None),
+    })
+}
+
 /// GitHub is very special and does its own thing, which is super annoying.
-/// If a user has no public E-Mail and changed the visibility settings, the
-/// user info endpoint will not return any address, even if a valid access token
-/// has been provided. In this case, there is another special endpoint only for
-/// E-Mail addresses which needs to be used to actually retrieve the address.
-/// This means we need a 3rd request to GitHub.
+/// It does not issue an ID token, and the user info endpoint only returns the public E-Mail,
+/// if the user has one at all, without telling us if it has been verified. There is another
+/// special endpoint only for E-Mail addresses which needs to be used to actually retrieve the
+/// primary address and its verification status. This means we need a 3rd request to GitHub.
 ///
 /// Note: The user endpoint is hardcoded because it is very unlikely to ever
 /// change in the future. If we allowed this to be customizable, everything
@@ -44,28 +199,11 @@ pub async fn get_github_private_email(
     debug!("GET /user/emails status: {status}\n{res:?}");
 
     if status < 300 {
-        let mut emails = res.json::<Vec<GitHubEmailPrivateResponse>>().await?;
-        debug!("GET /user/emails status: {status}");
-
-        if emails.len() == 1 {
-            let email = emails.swap_remove(0);
-            claims.email = Some(email.email.into());
-            claims.email_verified = Some(email.verified);
-            return Ok(());
-        }
-
-        for email in emails {
-            if email.primary {
-                claims.email = Some(email.email.into());
-                claims.email_verified = Some(email.verified);
-                return Ok(());
-            }
-        }
-
-        Err(ErrorResponse::new(
-            ErrorResponseType::Internal,
-            "Could not find a the primary user E-Mail in GitHub response",
-        ))
+        let emails = res.json::<Vec<GitHubEmailPrivateResponse>>().await?;
+        let email = primary_email(emails)?;
+        claims.email = Some(email.into());
+        claims.email_verified = Some(true);
+        Ok(())
     } else {
         let text = res.text().await?;
         Err(ErrorResponse::new(
@@ -74,3 +212,144 @@ pub async fn get_github_private_email(
         ))
     }
 }
+
+/// Only the primary E-Mail is ever used, and only if it has been verified.
+fn primary_email(emails: Vec<GitHubEmailPrivateResponse>) -> Result<String, ErrorResponse> {
+    let Some(email) = emails.into_iter().find(|e| e.primary) else {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::Internal,
+            "Could not find the primary user E-Mail in the GitHub response",
+        ));
+    };
+
+    if email.verified {
+        Ok(email.email)
+    } else {
+        Err(ErrorResponse::new(
+            ErrorResponseType::Forbidden,
+            "The primary E-Mail of your GitHub account has not been verified. Please verify \
+            it at GitHub before logging in.",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email(email: &str, primary: bool, verified: bool) -> GitHubEmailPrivateResponse {
+        GitHubEmailPrivateResponse {
+            email: email.to_string(),
+            primary,
+            verified,
+        }
+    }
+
+    #[test]
+    fn test_primary_email() {
+        let emails = vec![
+            email("other@example.com", false, true),
+            email("primary@example.com", true, true),
+        ];
+        assert_eq!(primary_email(emails).unwrap(), "primary@example.com");
+
+        // a verified secondary address must never be used instead
+        let emails = vec![
+            email("other@example.com", false, true),
+            email("primary@example.com", true, false),
+        ];
+        let err = primary_email(emails).unwrap_err();
+        assert_eq!(err.error, ErrorResponseType::Forbidden);
+
+        let emails = vec![email("other@example.com", false, true)];
+        assert!(primary_email(emails).is_err());
+    }
+
+    #[test]
+    fn test_github_lookup() {
+        for issuer in ["github.com", "https://github.com", "https://github.com/"] {
+            let config = github_lookup(issuer).unwrap();
+            assert_eq!(config.issuer, "github.com");
+            assert_eq!(config.userinfo_endpoint, "https://api.github.com/user");
+            assert!(config.scope.contains("user:email"));
+        }
+        assert!(github_lookup("accounts.google.com").is_none());
+        assert!(github_lookup("github.com.example.com").is_none());
+    }
+
+    #[test]
+    fn test_apple_lookup() {
+        for issuer in ["appleid.apple.com", "https://appleid.apple.com/"] {
+            let config = apple_lookup(issuer).unwrap();
+            assert_eq!(config.issuer, APPLE_ISSUER);
+            assert!(config.userinfo_endpoint.is_empty());
+            assert!(config.client_secret_post);
+        }
+        assert!(apple_lookup("github.com").is_none());
+    }
+
+    #[test]
+    fn test_apple_client_secret() {
+        use openssl::bn::BigNum;
+        use openssl::ec::{EcGroup, EcKey};
+        use rauthy_common::utils::base64_url_no_pad_decode;
+
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let ec = EcKey::generate(&group).unwrap();
+        let key = PKey::from_ec_key(ec.clone()).unwrap();
+        let pem = String::from_utf8(key.private_key_to_pem_pkcs8().unwrap()).unwrap();
+
+        let token =
+            apple_client_secret("TEAM123456", "KEY1234567", "com.example.app", &pem).unwrap();
+        let (message, sig) = token.rsplit_once('.').unwrap();
+        let (header, claims) = message.split_once('.').unwrap();
+
+        let header: serde_json::Value =
+            serde_json::from_slice(&base64_url_no_pad_decode(header).unwrap()).unwrap();
+        assert_eq!(header["alg"], "ES256");
+        assert_eq!(header["kid"], "KEY1234567");
+        let claims: serde_json::Value =
+            serde_json::from_slice(&base64_url_no_pad_decode(claims).unwrap()).unwrap();
+        assert_eq!(claims["iss"], "TEAM123456");
+        assert_eq!(claims["sub"], "com.example.app");
+        assert_eq!(claims["aud"], APPLE_ISSUER);
+
+        let sig = base64_url_no_pad_decode(sig).unwrap();
+        assert_eq!(sig.len(), 64);
+        let sig = EcdsaSig::from_private_components(
+            BigNum::from_slice(&sig[..32]).unwrap(),
+            BigNum::from_slice(&sig[32..]).unwrap(),
+        )
+        .unwrap();
+        let digest = openssl::sha::sha256(message.as_bytes());
+        assert!(sig.verify(&digest, &ec).unwrap());
+
+        // Apple only issues P-256 keys
+        let group = EcGroup::from_curve_name(Nid::SECP384R1).unwrap();
+        let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+        let pem = String::from_utf8(key.private_key_to_pem_pkcs8().unwrap()).unwrap();
+        assert!(apple_signing_key(&pem).is_err());
+        assert!(apple_signing_key("not a key").is_err());
+    }
+
+    #[test]
+    fn test_apple_user_names() {
+        let user = r#"{"name":{"firstName":"John","lastName":"Doe"},"email":"jdoe@example.com"}"#;
+        assert_eq!(
+            apple_user_names(user).unwrap(),
+            (Some("John".to_string()), Some("Doe".to_string()))
+        );
+
+        let user = r#"{"name":{"firstName":"John","lastName":""}}"#;
+        assert_eq!(
+            apple_user_names(user).unwrap(),
+            (Some("John".to_string()), None)
+        );
+
+        assert_eq!(
+            apple_user_names(r#"{"email":"jdoe@example.com"}"#).unwrap(),
+            (None, None)
+        );
+        assert!(apple_user_names("John Doe").is_err());
+    }
+}
@@ -0,0 +1,236 @@
+use crate::entity::auth_providers::AuthProvider;
+use crate::migration::archive::{ArchiveCipher, validate_passphrase};
+use chrono::Utc;
+use rauthy_api_types::auth_providers::{
+    ProviderExport, ProviderExportEntry, ProviderExportSecrets, ProviderImportResponse,
+    ProviderImportResult, ProviderImportStatus, ProviderRequest,
+};
+use rauthy_common::constants::PROVIDER_ATPROTO;
+use rauthy_common::utils::{base64_decode, base64_encode, get_rand_bytes};
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+/// The current version of the provider export format
+pub const PROVIDER_EXPORT_VERSION: u16 = 1;
+
+/// The cleartext inside `ProviderExportSecrets`
+#[derive(Default, Serialize, Deserialize)]
+struct ExportSecrets {
+    client_secret: Option<String>,
+    client_key_pem: Option<String>,
+    apple_private_key: Option<String>,
+}
+
+impl AuthProvider {
+    /// Exports all providers except for ATProto. The secrets are only included, if a
+    /// `passphrase` is given, and then only encrypted with a key derived from it.
+    pub async fn export(passphrase: Option<String>) -> Result<ProviderExport, ErrorResponse> {
+        let cipher = match passphrase {
+            Some(passphrase) => {
+                validate_passphrase(&passphrase)?;
+                Some(ArchiveCipher::derive(passphrase, get_rand_bytes::<16>()).await?)
+            }
+            None => None,
+        };
+
+        let mut providers = Vec::new();
+        for provider in Self::find_all().await? {
+            if provider.issuer == PROVIDER_ATPROTO {
+                continue;
+            }
+
+            let secrets = match &cipher {
+                Some(cipher) => {
+                    let secrets = ExportSecrets {
+                        client_secret: Self::secret_cleartext(&provider.secret)?,
+                        client_key_pem: Self::secret_cleartext(&provider.client_key_pem)?,
+                        apple_private_key: Self::secret_cleartext(&provider.apple_private_key)?,
+                    };
+                    let (nonce, data) = cipher.encrypt(0, &serde_json::to_vec(&secrets)?)?;
+                    Some(ProviderExportSecrets { nonce, data })
+                }
+                None => None,
+            };
+
+            providers.push(ProviderExportEntry {
+                provider: provider.into_export_request()?,
+                secrets,
+            });
+        }
+
+        Ok(ProviderExport {
+            version: PROVIDER_EXPORT_VERSION,
+            exported: Utc::now().timestamp(),
+            salt: cipher.map(|c| base64_encode(&c.salt)),
+            providers,
+        })
+    }
+
+    /// Upserts all providers by their `issuer` and `client_id`. Each entry is validated on its
+    /// own, and a failed one never prevents the others from being imported.
+    pub async fn import(
+        export: ProviderExport,
+        passphrase: Option<String>,
+    ) -> Result<ProviderImportResponse, ErrorResponse> {
+        if export.version != PROVIDER_EXPORT_VERSION {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("Unsupported provider export version {}", export.version),
+            ));
+        }
+
+        let cipher = match (&export.salt, passphrase) {
+            (Some(salt), Some(passphrase)) => {
+                let salt: [u8; 16] = base64_decode(salt)?.try_into().map_err(|_| {
+                    ErrorResponse::new(ErrorResponseType::BadRequest, "Invalid export salt")
+                })?;
+                Some(ArchiveCipher::derive(passphrase, salt).await?)
+            }
+            (Some(_), None) => {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    "The export contains encrypted secrets, which need the passphrase",
+                ));
+            }
+            (None, _) => None,
+        };
+
+        let mut existing = Self::find_all().await?;
+        let mut results = Vec::with_capacity(export.providers.len());
+        for entry in export.providers {
+            let name = entry.provider.name.clone();
+            let issuer = entry.provider.issuer.clone();
+            let client_id = entry.provider.client_id.clone();
+
+            let (status, id, error) =
+                match Self::import_entry(entry, cipher.as_ref(), &mut existing).await {
+                    Ok((status, id)) => (status, Some(id), None),
+                    Err(err) => (
+                        ProviderImportStatus::Failed,
+                        None,
+                        Some(err.message.to_string()),
+                    ),
+                };
+            results.push(ProviderImportResult {
+                name,
+                issuer,
+                client_id,
+                status,
+                id,
+                error,
+            });
+        }
+
+        Ok(ProviderImportResponse { results })
+    }
+
+    async fn import_entry(
+        entry: ProviderExportEntry,
+        cipher: Option<&ArchiveCipher>,
+        existing: &mut Vec<Self>,
+    ) -> Result<(ProviderImportStatus, String), ErrorResponse> {
+        let mut req = entry.provider;
+        req.validate()?;
+        if req.issuer == PROVIDER_ATPROTO {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Must not contain a reserved name",
+            ));
+        }
+
+        let secrets = match (entry.secrets, cipher) {
+            (Some(secrets), Some(cipher)) => serde_json::from_slice::<ExportSecrets>(
+                &cipher.decrypt(0, &secrets.nonce, &secrets.data)?,
+            )?,
+            _ => ExportSecrets::default(),
+        };
+
+        let current = existing
+            .iter()
+            .find(|p| p.issuer == req.issuer && p.client_id == req.client_id);
+        let current_id = current.map(|p| p.id.clone());
+        // Without exported secrets, the current ones are kept. `update()` already does this for
+        // the `client_key_pem` and `apple_private_key`.
+        req.client_secret = match (secrets.client_secret, current) {
+            (Some(secret), _) => Some(secret),
+            (None, Some(p)) => Self::secret_cleartext(&p.secret)?,
+            (None, None) => None,
+        };
+        req.client_key_pem = secrets.client_key_pem;
+        req.apple_private_key = secrets.apple_private_key;
+
+        if !req.use_pkce && req.client_secret.is_none() {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Must at least be a confidential client or use PKCE",
+            ));
+        }
+
+        if let Some(id) = current_id {
+            Self::update(id.clone(), req).await?;
+            Ok((ProviderImportStatus::Updated, id))
+        } else {
+            let provider = Self::create(req).await?;
+            let id = provider.id.clone();
+            // a duplicate entry inside the same export must update this one
+            existing.push(provider);
+            Ok((ProviderImportStatus::Created, id))
+        }
+    }
+
+    /// Converts the provider back into its request, which always goes through the same
+    /// validation during the import. The secrets are never included.
+    fn into_export_request(self) -> Result<ProviderRequest, ErrorResponse> {
+        let extra_auth_params = self.get_extra_auth_params()?;
+        let sync_policy = self.sync_policy();
+
+        Ok(ProviderRequest {
+            name: self.name,
+            typ: self.typ.into(),
+            enabled: self.enabled,
+            issuer: self.issuer,
+            authorization_endpoint: self.authorization_endpoint,
+            token_endpoint: self.token_endpoint,
+            userinfo_endpoint: self.userinfo_endpoint,
+            jwks_endpoint: self.jwks_endpoint,
+            end_session_endpoint: self.end_session_endpoint,
+            use_pkce: self.use_pkce,
+            client_secret_basic: self.client_secret_basic,
+            client_secret_post: self.client_secret_post,
+            auto_onboarding: self.auto_onboarding,
+            auto_link: self.auto_link,
+            auto_upstream_logout: self.auto_upstream_logout,
+            client_id: self.client_id,
+            client_secret: None,
+            client_cert_pem: Self::secret_cleartext(&self.client_cert_pem)?,
+            client_key_pem: None,
+            apple_team_id: self.apple_team_id,
+            apple_key_id: self.apple_key_id,
+            apple_private_key: None,
+            // stored in its URL-encoded form
+            scope: self.scope.replace('+', " "),
+            admin_claim_path: self.admin_claim_path,
+            admin_claim_value: self.admin_claim_value,
+            mfa_claim_path: self.mfa_claim_path,
+            mfa_claim_value: self.mfa_claim_value,
+            claim_path_email: self.claim_path_email,
+            claim_path_given_name: self.claim_path_given_name,
+            claim_path_family_name: self.claim_path_family_name,
+            claim_path_groups: self.claim_path_groups,
+            restrict_hd: self.restrict_hd,
+            issuer_template: self.issuer_template,
+            allowed_tenants: self.allowed_tenants,
+            sync_policy: Some(sync_policy),
+            upstream_refresh: Some(self.upstream_refresh),
+            require_email_verified: Some(self.require_email_verified),
+            sort_order: Some(self.sort_order),
+            allowed_email_domains: self.allowed_email_domains,
+            denied_email_domains: self.denied_email_domains,
+            use_userinfo: self.use_userinfo,
+            auto_refresh: Some(self.auto_refresh),
+            extra_auth_params,
+            callback_timeout_secs: self.callback_timeout_secs.map(|secs| secs as u16),
+        })
+    }
+}
@@ -0,0 +1,357 @@
+use crate::database::DB;
+use crate::entity::auth_providers::AuthProvider;
+use crate::entity::groups::Group;
+use hiqlite::macros::params;
+use rauthy_api_types::auth_providers::{
+    ProviderGroupMappingRequest, ProviderGroupMappingResponse, ProviderGroupSyncMode,
+};
+use rauthy_common::is_hiqlite;
+use rauthy_common::utils::new_store_id;
+use rauthy_derive::FromPgRow;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// The upstream claim, which is used if a mapping does not specify one.
+pub const DEFAULT_GROUPS_CLAIM: &str = "groups";
+
+/// Maps a group from an upstream ID token claim to a Rauthy group.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromPgRow)]
+pub struct AuthProviderGroupMapping {
+    pub id: String,
+    pub provider_id: String,
+    pub claim_key: String,
+    pub upstream_group: String,
+    pub group_id: String,
+    pub sync_mode: String,
+}
+
+// CRUD
+impl AuthProviderGroupMapping {
+    pub async fn create(
+        provider_id: String,
+        payload: ProviderGroupMappingRequest,
+    ) -> Result<Self, ErrorResponse> {
+        // make sure we return a proper error instead of a FK violation
+        AuthProvider::find(&provider_id).await?;
+        Group::find(payload.group_id.clone()).await?;
+
+        let slf = Self {
+            id: new_store_id(),
+            provider_id,
+            claim_key: payload
+                .claim_key
+                .unwrap_or_else(|| DEFAULT_GROUPS_CLAIM.to_string()),
+            upstream_group: payload.upstream_group,
+            group_id: payload.group_id,
+            sync_mode: payload.sync_mode.as_str().to_string(),
+        };
+
+        if Self::find_for_provider(&slf.provider_id)
+            .await?
+            .iter()
+            .any(|m| m.is_same_mapping(&slf))
+        {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "This group mapping exists already",
+            ));
+        }
+
+        let sql = r#"
+INSERT INTO auth_provider_group_mappings
+(id, provider_id, claim_key, upstream_group, group_id, sync_mode)
+VALUES ($1, $2, $3, $4, $5, $6)"#;
+        if is_hiqlite() {
+            DB::hql()
+                .execute(
+                    sql,
+                    params!(
+                        slf.id.clone(),
+                        slf.provider_id.clone(),
+                        slf.claim_key.clone(),
+                        slf.upstream_group.clone(),
+                        slf.group_id.clone(),
+                        slf.sync_mode.clone()
+                    ),
+                )
+                .await?;
+        } else {
+            DB::pg_execute(
+                sql,
+                &[
+                    &slf.id,
+                    &slf.provider_id,
+                    &slf.claim_key,
+                    &slf.upstream_group,
+                    &slf.group_id,
+                    &slf.sync_mode,
+                ],
+            )
+            .await?;
+        }
+
+        Ok(slf)
+    }
+
+    pub async fn delete(provider_id: &str, id: &str) -> Result<(), ErrorResponse> {
+        let sql = "DELETE FROM auth_provider_group_mappings WHERE id = $1 AND provider_id = $2";
+        let rows_affected = if is_hiqlite() {
+            DB::hql().execute(sql, params!(id, provider_id)).await?
+        } else {
+            DB::pg_execute(sql, &[&id, &provider_id]).await?
+        };
+
+        if rows_affected == 0 {
+            Err(ErrorResponse::new(
+                ErrorResponseType::NotFound,
+                "Group mapping not found",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub async fn find(provider_id: &str, id: &str) -> Result<Self, ErrorResponse> {
+        let sql = "SELECT * FROM auth_provider_group_mappings WHERE id = $1 AND provider_id = $2";
+        let slf = if is_hiqlite() {
+            DB::hql()
+                .query_as_one(sql, params!(id, provider_id))
+                .await?
+        } else {
+            DB::pg_query_one(sql, &[&id, &provider_id]).await?
+        };
+
+        Ok(slf)
+    }
+
+    pub async fn find_for_provider(provider_id: &str) -> Result<Vec<Self>, ErrorResponse> {
+        let sql = "SELECT * FROM auth_provider_group_mappings WHERE provider_id = $1";
+        let res = if is_hiqlite() {
+            DB::hql().query_as(sql, params!(provider_id)).await?
+        } else {
+            DB::pg_query(sql, &[&provider_id], 4).await?
+        };
+
+        Ok(res)
+    }
+
+    pub async fn update(
+        provider_id: &str,
+        id: &str,
+        payload: ProviderGroupMappingRequest,
+    ) -> Result<Self, ErrorResponse> {
+        let mut slf = Self::find(provider_id, id).await?;
+        if slf.group_id != payload.group_id {
+            Group::find(payload.group_id.clone()).await?;
+        }
+
+        slf.claim_key = payload
+            .claim_key
+            .unwrap_or_else(|| DEFAULT_GROUPS_CLAIM.to_string());
+        slf.upstream_group = payload.upstream_group;
+        slf.group_id = payload.group_id;
+        slf.sync_mode = payload.sync_mode.as_str().to_string();
+
+        if Self::find_for_provider(provider_id)
+            .await?
+            .iter()
+            .any(|m| m.id != slf.id && m.is_same_mapping(&slf))
+        {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "This group mapping exists already",
+            ));
+        }
+
+        let sql = r#"
+UPDATE auth_provider_group_mappings
+SET claim_key = $1, upstream_group = $2, group_id = $3, sync_mode = $4
+WHERE id = $5"#;
+        if is_hiqlite() {
+            DB::hql()
+                .execute(
+                    sql,
+                    params!(
+                        slf.claim_key.clone(),
+                        slf.upstream_group.clone(),
+                        slf.group_id.clone(),
+                        slf.sync_mode.clone(),
+                        slf.id.clone()
+                    ),
+                )
+                .await?;
+        } else {
+            DB::pg_execute(
+                sql,
+                &[
+                    &slf.claim_key,
+                    &slf.upstream_group,
+                    &slf.group_id,
+                    &slf.sync_mode,
+                    &slf.id,
+                ],
+            )
+            .await?;
+        }
+
+        Ok(slf)
+    }
+}
+
+impl From<AuthProviderGroupMapping> for ProviderGroupMappingResponse {
+    fn from(value: AuthProviderGroupMapping) -> Self {
+        Self {
+            sync_mode: value.sync_mode(),
+            id: value.id,
+            provider_id: value.provider_id,
+            claim_key: value.claim_key,
+            upstream_group: value.upstream_group,
+            group_id: value.group_id,
+        }
+    }
+}
+
+impl AuthProviderGroupMapping {
+    #[inline]
+    pub fn sync_mode(&self) -> ProviderGroupSyncMode {
+        ProviderGroupSyncMode::from(self.sync_mode.as_str())
+    }
+
+    #[inline]
+    fn is_same_mapping(&self, other: &Self) -> bool {
+        self.claim_key == other.claim_key
+            && self.upstream_group == other.upstream_group
+            && self.group_id == other.group_id
+    }
+
+    /// Applies the `mappings` from the upstream ID token `claims` to the comma separated
+    /// `user_groups` and returns the new value.
+    ///
+    /// A Rauthy group is added, as soon as any mapping for it matches. It is only removed, if no
+    /// mapping matches and at least one `Sync` mapping for it found its claim. If the claim is
+    /// missing completely, the mapping is skipped, so a provider that does not always include
+    /// the claim does not wipe existing groups.
+    pub fn map_user_groups(
+        mappings: &[Self],
+        groups: &[Group],
+        claims: &Value,
+        user_groups: Option<&str>,
+    ) -> Option<String> {
+        let mut add = HashSet::with_capacity(mappings.len());
+        let mut remove = HashSet::with_capacity(mappings.len());
+
+        for mapping in mappings {
+            let Some(upstream) = claims.get(&mapping.claim_key).and_then(|v| v.as_array()) else {
+                continue;
+            };
+            let Some(group) = groups.iter().find(|g| g.id == mapping.group_id) else {
+                continue;
+            };
+
+            if upstream
+                .iter()
+                .any(|v| v.as_str() == Some(mapping.upstream_group.as_str()))
+            {
+                add.insert(group.name.as_str());
+            } else if mapping.sync_mode() == ProviderGroupSyncMode::Sync {
+                remove.insert(group.name.as_str());
+            }
+        }
+
+        let mut res = user_groups
+            .unwrap_or_default()
+            .split(',')
+            .filter(|g| !g.is_empty() && (add.contains(g) || !remove.contains(g)))
+            .map(String::from)
+            .collect::<Vec<_>>();
+        for group in add {
+            if !res.iter().any(|g| g == group) {
+                res.push(group.to_string());
+            }
+        }
+
+        if res.is_empty() {
+            None
+        } else {
+            Some(res.join(","))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn mapping(upstream_group: &str, group_id: &str, sync_mode: &str) -> AuthProviderGroupMapping {
+        AuthProviderGroupMapping {
+            id: new_store_id(),
+            provider_id: "provider".to_string(),
+            claim_key: DEFAULT_GROUPS_CLAIM.to_string(),
+            upstream_group: upstream_group.to_string(),
+            group_id: group_id.to_string(),
+            sync_mode: sync_mode.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_map_user_groups() {
+        let groups = ["admins", "devs", "ops"]
+            .into_iter()
+            .map(|name| Group {
+                id: format!("{name}Id"),
+                name: name.to_string(),
+                meta: None,
+            })
+            .collect::<Vec<_>>();
+        let mappings = vec![
+            mapping("upstream_admins", "adminsId", "sync"),
+            mapping("upstream_devs", "devsId", "add_only"),
+            mapping("upstream_ops", "opsId", "sync"),
+        ];
+
+        // missing claim -> no changes at all
+        let claims = json!({ "sub": "123" });
+        let res = AuthProviderGroupMapping::map_user_groups(
+            &mappings,
+            &groups,
+            &claims,
+            Some("admins,local"),
+        );
+        assert_eq!(res.as_deref(), Some("admins,local"));
+
+        // add and remove, keep unmapped local groups
+        let claims = json!({ "groups": ["upstream_devs", "upstream_ops"] });
+        let res = AuthProviderGroupMapping::map_user_groups(
+            &mappings,
+            &groups,
+            &claims,
+            Some("admins,local"),
+        )
+        .unwrap();
+        let mut res = res.split(',').collect::<Vec<_>>();
+        res.sort();
+        assert_eq!(res, vec!["devs", "local", "ops"]);
+
+        // add_only never removes
+        let claims = json!({ "groups": [] });
+        let res =
+            AuthProviderGroupMapping::map_user_groups(&mappings, &groups, &claims, Some("devs"));
+        assert_eq!(res.as_deref(), Some("devs"));
+
+        // sync removes the last group
+        let res =
+            AuthProviderGroupMapping::map_user_groups(&mappings, &groups, &claims, Some("ops"));
+        assert_eq!(res, None);
+
+        // any matching mapping wins over a non-matching sync mapping for the same group
+        let mut mappings = mappings;
+        mappings.push(mapping("upstream_other", "adminsId", "add_only"));
+        let claims = json!({ "groups": ["upstream_other"] });
+        let res =
+            AuthProviderGroupMapping::map_user_groups(&mappings, &groups, &claims, Some("admins"));
+        assert_eq!(res.as_deref(), Some("admins"));
+    }
+}
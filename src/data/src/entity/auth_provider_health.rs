@@ -0,0 +1,279 @@
+use crate::database::{Cache, DB};
+use crate::events::event::Event;
+use crate::rauthy_config::RauthyConfig;
+use chrono::Utc;
+use rauthy_common::constants::{CACHE_TTL_APP, IDX_AUTH_PROVIDER_HEALTH};
+use rauthy_common::http_client;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::debug;
+
+/// The timeout for a single check against an upstream `authorization_endpoint`.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+/// Logins are retried after the next check at the earliest.
+const FAIL_FAST_RETRY_AFTER: i64 = 60;
+
+/// The availability of an upstream auth provider, as seen by the `auth_provider_health`
+/// scheduler. The status only changes after `auth_providers.health_check_threshold`
+/// consecutive checks with a contradicting result, which keeps a flapping provider from
+/// spamming events.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuthProviderHealth {
+    pub available: bool,
+    /// Unix timestamp in seconds of the last status change
+    pub since: i64,
+    /// Consecutive checks, which contradicted the current status
+    pub contradicting: u16,
+}
+
+impl Default for AuthProviderHealth {
+    fn default() -> Self {
+        Self {
+            available: true,
+            since: Utc::now().timestamp(),
+            contradicting: 0,
+        }
+    }
+}
+
+/// A confirmed status change of a provider, which should emit an event.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AuthProviderHealthTransition {
+    Unavailable,
+    Available,
+}
+
+// CRUD
+impl AuthProviderHealth {
+    #[inline]
+    fn cache_idx(provider_id: &str) -> String {
+        format!("{IDX_AUTH_PROVIDER_HEALTH}{provider_id}")
+    }
+
+    /// Returns the current health, which defaults to available for unchecked providers.
+    pub async fn find(provider_id: &str) -> Result<Self, ErrorResponse> {
+        let slf = DB::hql()
+            .get(Cache::App, Self::cache_idx(provider_id))
+            .await?
+            .unwrap_or_default();
+        Ok(slf)
+    }
+
+    pub async fn save(&self, provider_id: &str) -> Result<(), ErrorResponse> {
+        DB::hql()
+            .put(
+                Cache::App,
+                Self::cache_idx(provider_id),
+                self,
+                CACHE_TTL_APP,
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn delete(provider_id: &str) -> Result<(), ErrorResponse> {
+        DB::hql()
+            .delete(Cache::App, Self::cache_idx(provider_id))
+            .await?;
+        Ok(())
+    }
+}
+
+impl AuthProviderHealth {
+    /// Applies the result of a single check and returns a transition, if the status has
+    /// changed. Any check that agrees with the current status resets the hysteresis.
+    pub fn apply_check(
+        &mut self,
+        is_up: bool,
+        now: i64,
+        threshold: u16,
+    ) -> Option<AuthProviderHealthTransition> {
+        if is_up == self.available {
+            self.contradicting = 0;
+            return None;
+        }
+
+        self.contradicting += 1;
+        if self.contradicting < threshold {
+            return None;
+        }
+
+        self.available = is_up;
+        self.since = now;
+        self.contradicting = 0;
+        if is_up {
+            Some(AuthProviderHealthTransition::Available)
+        } else {
+            Some(AuthProviderHealthTransition::Unavailable)
+        }
+    }
+
+    /// `true` if the provider has been unavailable for at least `secs`.
+    #[inline]
+    pub fn is_fail_fast(&self, now: i64, secs: u32) -> bool {
+        !self.available && now - self.since >= secs as i64
+    }
+
+    /// Rejects new logins via a provider, which has been down for longer than
+    /// `auth_providers.health_fail_fast_secs`.
+    pub async fn validate_login(provider_id: &str, name: &str) -> Result<(), ErrorResponse> {
+        let config = &RauthyConfig::get().vars.auth_providers;
+        if !config.health_check {
+            return Ok(());
+        }
+
+        let slf = Self::find(provider_id).await?;
+        if slf.is_fail_fast(Utc::now().timestamp(), config.health_fail_fast_secs) {
+            Err(ErrorResponse::new(
+                ErrorResponseType::ServiceUnavailable(FAIL_FAST_RETRY_AFTER),
+                format!("The upstream provider '{name}' is temporarily unavailable"),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// A provider is considered up, if its `authorization_endpoint` answers with anything else
+    /// than a `5xx`. Without any auth params, most providers will respond with an error page,
+    /// which is still fine for this purpose.
+    pub async fn probe(authorization_endpoint: &str) -> bool {
+        match http_client()
+            .get(authorization_endpoint)
+            .timeout(CHECK_TIMEOUT)
+            .send()
+            .await
+        {
+            Ok(res) => !res.status().is_server_error(),
+            Err(err) => {
+                debug!(
+                    ?err,
+                    "Upstream auth provider check for {authorization_endpoint}"
+                );
+                false
+            }
+        }
+    }
+
+    /// Checks a single provider, persists the new state and sends an event on a transition.
+    /// Returns `true` if the status has changed.
+    pub async fn check(
+        provider_id: &str,
+        name: &str,
+        authorization_endpoint: &str,
+    ) -> Result<bool, ErrorResponse> {
+        let is_up = Self::probe(authorization_endpoint).await;
+        let now = Utc::now().timestamp();
+        let threshold = RauthyConfig::get()
+            .vars
+            .auth_providers
+            .health_check_threshold;
+
+        let mut slf = Self::find(provider_id).await?;
+        let transition = slf.apply_check(is_up, now, threshold);
+        slf.save(provider_id).await?;
+
+        match transition {
+            None => return Ok(false),
+            Some(AuthProviderHealthTransition::Unavailable) => {
+                Event::auth_provider_unavailable(format!(
+                    "Upstream auth provider '{name}' is unavailable"
+                ))
+                .send()
+                .await?;
+            }
+            Some(AuthProviderHealthTransition::Available) => {
+                Event::auth_provider_available(format!(
+                    "Upstream auth provider '{name}' is available again"
+                ))
+                .send()
+                .await?;
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn health() -> AuthProviderHealth {
+        AuthProviderHealth {
+            available: true,
+            since: 0,
+            contradicting: 0,
+        }
+    }
+
+    #[test]
+    fn test_apply_check_transitions() {
+        let mut h = health();
+
+        assert_eq!(h.apply_check(true, 60, 3), None);
+        assert_eq!(h.apply_check(false, 120, 3), None);
+        assert_eq!(h.apply_check(false, 180, 3), None);
+        assert!(h.available);
+        assert_eq!(h.contradicting, 2);
+
+        assert_eq!(
+            h.apply_check(false, 240, 3),
+            Some(AuthProviderHealthTransition::Unavailable)
+        );
+        assert!(!h.available);
+        assert_eq!(h.since, 240);
+        assert_eq!(h.contradicting, 0);
+
+        // staying down does not emit anything else
+        assert_eq!(h.apply_check(false, 300, 3), None);
+        assert_eq!(h.since, 240);
+
+        assert_eq!(h.apply_check(true, 360, 3), None);
+        assert_eq!(h.apply_check(true, 420, 3), None);
+        assert_eq!(
+            h.apply_check(true, 480, 3),
+            Some(AuthProviderHealthTransition::Available)
+        );
+        assert!(h.available);
+        assert_eq!(h.since, 480);
+    }
+
+    #[test]
+    fn test_apply_check_flapping() {
+        let mut h = health();
+
+        // a flapping provider never reaches the threshold
+        for i in 0..10 {
+            let is_up = i % 3 == 2;
+            assert_eq!(h.apply_check(is_up, i * 60, 3), None);
+        }
+        assert!(h.available);
+        assert_eq!(h.since, 0);
+
+        // a threshold of 1 reacts to every single result
+        assert_eq!(
+            h.apply_check(false, 600, 1),
+            Some(AuthProviderHealthTransition::Unavailable)
+        );
+        assert_eq!(
+            h.apply_check(true, 660, 1),
+            Some(AuthProviderHealthTransition::Available)
+        );
+    }
+
+    #[test]
+    fn test_is_fail_fast() {
+        let mut h = health();
+        assert!(!h.is_fail_fast(1_000, 300));
+
+        for now in [60, 120, 180] {
+            h.apply_check(false, now, 3);
+        }
+        assert!(!h.available);
+        assert!(!h.is_fail_fast(180, 300));
+        assert!(!h.is_fail_fast(479, 300));
+        assert!(h.is_fail_fast(480, 300));
+        assert!(h.is_fail_fast(180, 0));
+    }
+}
@@ -0,0 +1,261 @@
+use crate::cache_layer::{self, CacheName};
+use crate::entity::auth_providers::AuthProvider;
+use crate::entity::sessions::Session;
+use crate::html::templates::TplUpstreamLogout;
+use crate::rauthy_config::RauthyConfig;
+use chrono::Utc;
+use rauthy_common::constants::{IDX_UPSTREAM_LOGOUT, IDX_UPSTREAM_SESSION};
+use rauthy_common::utils::get_rand;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use serde::{Deserialize, Serialize};
+
+/// Reference to the upstream login of a session. It is only retained for providers with an
+/// `end_session_endpoint` and lives as long as the session itself.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthProviderSession {
+    pub provider_id: String,
+    /// The upstream `id_token`, which will be used as `id_token_hint` during the logout
+    pub id_token: String,
+}
+
+impl AuthProviderSession {
+    #[inline(always)]
+    fn cache_idx(sid: &str) -> String {
+        format!("{IDX_UPSTREAM_SESSION}{sid}")
+    }
+
+    pub async fn save(&self, session: &Session) -> Result<(), ErrorResponse> {
+        let ttl = session.exp - Utc::now().timestamp();
+        if ttl < 1 {
+            return Ok(());
+        }
+
+        cache_layer::put(
+            CacheName::AuthProviderSession,
+            Self::cache_idx(&session.id),
+            self,
+            Some(ttl),
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn find(sid: &str) -> Result<Option<Self>, ErrorResponse> {
+        cache_layer::get(CacheName::AuthProviderSession, Self::cache_idx(sid)).await
+    }
+
+    /// Returns and deletes the reference for the given session.
+    pub async fn take(sid: &str) -> Result<Option<Self>, ErrorResponse> {
+        let slf = Self::find(sid).await?;
+        if slf.is_some() {
+            cache_layer::delete(CacheName::AuthProviderSession, Self::cache_idx(sid)).await?;
+        }
+        Ok(slf)
+    }
+
+    /// Returns the template for the logout page, if an upstream logout is possible for the
+    /// given session.
+    pub async fn find_template(sid: &str) -> Result<Option<TplUpstreamLogout>, ErrorResponse> {
+        let Some(slf) = Self::find(sid).await? else {
+            return Ok(None);
+        };
+        let provider = match AuthProvider::find(&slf.provider_id).await {
+            Ok(p) => p,
+            Err(err) if err.error == ErrorResponseType::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        if provider.end_session_endpoint.is_none() {
+            return Ok(None);
+        }
+
+        Ok(Some(TplUpstreamLogout {
+            provider_name: provider.name,
+            auto: provider.auto_upstream_logout,
+        }))
+    }
+
+    /// Moves the reference to the new id after a `Session::rotate_id()`.
+    pub async fn rotate(old_sid: &str, session: &Session) -> Result<(), ErrorResponse> {
+        if let Some(slf) = Self::take(old_sid).await? {
+            slf.save(session).await?;
+        }
+        Ok(())
+    }
+}
+
+/// A pending logout at an upstream provider. It is created during the Rauthy logout and consumed
+/// when the provider redirects back to `/oidc/logout/complete`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpstreamLogout {
+    /// Will be sent as the `state` to the upstream provider
+    pub id: String,
+    /// The full upstream `end_session_endpoint` URI including all query params
+    pub end_session_uri: String,
+    /// The location the user will be redirected to after the upstream logout
+    pub target: String,
+}
+
+impl UpstreamLogout {
+    #[inline(always)]
+    fn cache_idx(id: &str) -> String {
+        format!("{IDX_UPSTREAM_LOGOUT}{id}")
+    }
+
+    /// Builds the upstream logout, if the provider has an `end_session_endpoint` and the logout
+    /// should be propagated. `requested` is the user choice on the logout page, which only
+    /// matters if the provider does not propagate each logout automatically.
+    pub async fn build(
+        upstream: AuthProviderSession,
+        target: String,
+        requested: bool,
+    ) -> Result<Option<(Self, i64)>, ErrorResponse> {
+        let provider = match AuthProvider::find(&upstream.provider_id).await {
+            Ok(p) => p,
+            Err(err) if err.error == ErrorResponseType::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let Some(end_session_endpoint) = Self::propagated_endpoint(
+            provider.end_session_endpoint.as_deref(),
+            provider.auto_upstream_logout,
+            requested,
+        ) else {
+            return Ok(None);
+        };
+
+        let id = get_rand(32);
+        let end_session_uri = Self::end_session_uri(
+            end_session_endpoint,
+            &provider.client_id,
+            &upstream.id_token,
+            &RauthyConfig::get().issuer,
+            &id,
+        )?;
+        let slf = Self {
+            id,
+            end_session_uri,
+            target,
+        };
+
+        let ttl = provider.callback_timeout();
+        cache_layer::put(
+            CacheName::AuthProviderLogout,
+            Self::cache_idx(&slf.id),
+            &slf,
+            Some(ttl),
+        )
+        .await?;
+
+        Ok(Some((slf, ttl)))
+    }
+
+    pub async fn find(id: &str) -> Result<Self, ErrorResponse> {
+        let opt: Option<Self> =
+            cache_layer::get(CacheName::AuthProviderLogout, Self::cache_idx(id)).await?;
+        opt.ok_or_else(|| {
+            ErrorResponse::new(
+                ErrorResponseType::NotFound,
+                "Upstream logout not found - timeout reached?",
+            )
+        })
+    }
+
+    pub async fn delete(&self) -> Result<(), ErrorResponse> {
+        cache_layer::delete(CacheName::AuthProviderLogout, Self::cache_idx(&self.id)).await?;
+        Ok(())
+    }
+
+    /// Returns the `end_session_endpoint`, if the logout should be propagated to it.
+    #[inline]
+    fn propagated_endpoint(
+        end_session_endpoint: Option<&str>,
+        auto_upstream_logout: bool,
+        requested: bool,
+    ) -> Option<&str> {
+        end_session_endpoint.filter(|_| auto_upstream_logout || requested)
+    }
+
+    /// Builds the RP-initiated logout URI for the upstream provider, which redirects back to
+    /// Rauthy's logout-complete endpoint afterward.
+    fn end_session_uri(
+        end_session_endpoint: &str,
+        client_id: &str,
+        id_token: &str,
+        issuer: &str,
+        state: &str,
+    ) -> Result<String, ErrorResponse> {
+        let mut url = reqwest::Url::parse(end_session_endpoint).map_err(|err| {
+            ErrorResponse::new(
+                ErrorResponseType::Internal,
+                format!("Invalid upstream `end_session_endpoint`: {err}"),
+            )
+        })?;
+        url.query_pairs_mut()
+            .append_pair("id_token_hint", id_token)
+            .append_pair("client_id", client_id)
+            .append_pair(
+                "post_logout_redirect_uri",
+                &format!("{issuer}/oidc/logout/complete"),
+            )
+            .append_pair("state", state);
+        Ok(url.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(None, false, false, None)]
+    #[case(None, true, false, None)]
+    #[case(None, true, true, None)]
+    #[case(Some("https://idp/logout"), false, false, None)]
+    #[case(Some("https://idp/logout"), false, true, Some("https://idp/logout"))]
+    #[case(Some("https://idp/logout"), true, false, Some("https://idp/logout"))]
+    #[case(Some("https://idp/logout"), true, true, Some("https://idp/logout"))]
+    fn test_propagated_endpoint(
+        #[case] endpoint: Option<&str>,
+        #[case] auto: bool,
+        #[case] requested: bool,
+        #[case] expected: Option<&str>,
+    ) {
+        assert_eq!(
+            UpstreamLogout::propagated_endpoint(endpoint, auto, requested),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_end_session_uri() {
+        let uri = UpstreamLogout::end_session_uri(
+            "https://idp.example.com/logout",
+            "rauthy",
+            "ey.token.sig",
+            "https://auth.example.com/auth/v1",
+            "state123",
+        )
+        .unwrap();
+        assert_eq!(
+            uri,
+            "https://idp.example.com/logout?id_token_hint=ey.token.sig&client_id=rauthy\
+            &post_logout_redirect_uri=https%3A%2F%2Fauth.example.com%2Fauth%2Fv1%2Foidc%2Flogout%2Fcomplete\
+            &state=state123"
+        );
+
+        // existing query params of the endpoint must be kept
+        let uri = UpstreamLogout::end_session_uri(
+            "https://idp.example.com/logout?tenant=a",
+            "rauthy",
+            "ey.token.sig",
+            "https://auth.example.com/auth/v1",
+            "state123",
+        )
+        .unwrap();
+        assert!(uri.starts_with("https://idp.example.com/logout?tenant=a&id_token_hint="));
+
+        assert!(
+            UpstreamLogout::end_session_uri("/logout", "rauthy", "ey", "https://a.b", "s").is_err()
+        );
+    }
+}
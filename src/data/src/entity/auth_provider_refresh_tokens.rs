@@ -0,0 +1,258 @@
+use crate::database::DB;
+use crate::entity::auth_provider_cust_impls;
+use crate::entity::auth_providers::{
+    AuthProvider, AuthProviderIdClaims, AuthProviderTokenSet, AuthProviderType,
+};
+use crate::rauthy_config::RauthyConfig;
+use chrono::Utc;
+use cryptr::EncValue;
+use hiqlite::macros::params;
+use rauthy_common::is_hiqlite;
+use rauthy_common::jwt_time::JwtTimeClaims;
+use rauthy_derive::FromPgRow;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+/// The outcome of a single background refresh at the upstream.
+#[derive(Debug, PartialEq)]
+pub enum UpstreamRefresh {
+    /// The user has been synced with the latest upstream claims.
+    Synced,
+    /// The upstream revoked the refresh token, or the latest claims are not allowed to log in
+    /// anymore. Contains the reason.
+    Revoked(String),
+}
+
+#[derive(Serialize)]
+struct RefreshTokenParams<'a> {
+    grant_type: &'static str,
+    client_id: &'a str,
+    refresh_token: &'a str,
+}
+
+/// The latest upstream refresh token of a federated user. Only exists for providers with
+/// `upstream_refresh` enabled. The token is always stored encrypted.
+#[derive(Debug, Clone, Serialize, Deserialize, FromPgRow)]
+pub struct AuthProviderRefreshToken {
+    pub user_id: String,
+    pub provider_id: String,
+    pub refresh_token: Vec<u8>,
+    pub refreshed: i64,
+}
+
+// CRUD
+impl AuthProviderRefreshToken {
+    pub async fn delete(user_id: &str) -> Result<(), ErrorResponse> {
+        let sql = "DELETE FROM auth_provider_refresh_tokens WHERE user_id = $1";
+        if is_hiqlite() {
+            DB::hql().execute(sql, params!(user_id)).await?;
+        } else {
+            DB::pg_execute(sql, &[&user_id]).await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn delete_by_provider(provider_id: &str) -> Result<(), ErrorResponse> {
+        let sql = "DELETE FROM auth_provider_refresh_tokens WHERE provider_id = $1";
+        if is_hiqlite() {
+            DB::hql().execute(sql, params!(provider_id)).await?;
+        } else {
+            DB::pg_execute(sql, &[&provider_id]).await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn find_all() -> Result<Vec<Self>, ErrorResponse> {
+        let sql = "SELECT * FROM auth_provider_refresh_tokens";
+        let res = if is_hiqlite() {
+            DB::hql().query_as(sql, params!()).await?
+        } else {
+            DB::pg_query(sql, &[], 0).await?
+        };
+
+        Ok(res)
+    }
+
+    /// Returns all tokens for the provider, which have not been refreshed since `before`.
+    pub async fn find_due(provider_id: &str, before: i64) -> Result<Vec<Self>, ErrorResponse> {
+        let sql = r#"
+SELECT * FROM auth_provider_refresh_tokens
+WHERE provider_id = $1 AND refreshed < $2"#;
+        let res = if is_hiqlite() {
+            DB::hql()
+                .query_as(sql, params!(provider_id, before))
+                .await?
+        } else {
+            DB::pg_query(sql, &[&provider_id, &before], 0).await?
+        };
+
+        Ok(res)
+    }
+
+    pub async fn upsert(
+        user_id: &str,
+        provider_id: &str,
+        refresh_token: &str,
+    ) -> Result<(), ErrorResponse> {
+        let now = Utc::now().timestamp();
+        let refresh_token = EncValue::encrypt(refresh_token.as_bytes())?
+            .into_bytes()
+            .to_vec();
+
+        let sql = r#"
+INSERT INTO auth_provider_refresh_tokens (user_id, provider_id, refresh_token, refreshed)
+VALUES ($1, $2, $3, $4)
+ON CONFLICT (user_id) DO UPDATE
+SET provider_id = $2, refresh_token = $3, refreshed = $4"#;
+        if is_hiqlite() {
+            DB::hql()
+                .execute(
+                    sql,
+                    params!(
+                        user_id.to_string(),
+                        provider_id.to_string(),
+                        refresh_token,
+                        now
+                    ),
+                )
+                .await?;
+        } else {
+            DB::pg_execute(sql, &[&user_id, &provider_id, &refresh_token, &now]).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Re-encrypts the token with the currently active key. Used for secret migration.
+    pub async fn save_re_encrypted(self) -> Result<(), ErrorResponse> {
+        let dec = EncValue::try_from(self.refresh_token)?.decrypt()?;
+        let refresh_token = EncValue::encrypt(dec.as_ref())?.into_bytes().to_vec();
+
+        let sql = "UPDATE auth_provider_refresh_tokens SET refresh_token = $1 WHERE user_id = $2";
+        if is_hiqlite() {
+            DB::hql()
+                .execute(sql, params!(refresh_token, self.user_id))
+                .await?;
+        } else {
+            DB::pg_execute(sql, &[&refresh_token, &self.user_id]).await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl AuthProviderRefreshToken {
+    /// Refreshes the token at the upstream and syncs the user with the claims from the new ID
+    /// token, or the `userinfo`. Transient errors like an unreachable upstream are returned as
+    /// `Err` and can simply be retried later on.
+    pub async fn refresh(&self, provider: &AuthProvider) -> Result<UpstreamRefresh, ErrorResponse> {
+        let dec = EncValue::try_from(self.refresh_token.clone())?.decrypt()?;
+        let refresh_token = String::from_utf8_lossy(dec.as_ref()).to_string();
+        let params = RefreshTokenParams {
+            grant_type: "refresh_token",
+            client_id: &provider.client_id,
+            refresh_token: &refresh_token,
+        };
+        let secret = provider.client_secret()?;
+
+        let res = provider
+            .token_request(&provider.build_client()?, &params, secret.as_deref())
+            .send()
+            .await?;
+        let status = res.status().as_u16();
+        debug!("POST /token refresh auth provider status: {status}");
+
+        let ts = res.json::<AuthProviderTokenSet>().await.map_err(|err| {
+            ErrorResponse::new(
+                ErrorResponseType::Internal,
+                format!("HTTP {status} with an invalid upstream /token response: {err}"),
+            )
+        })?;
+        if let Some(err) = ts.error {
+            // Any other error might be caused by the upstream or our own config and must not log
+            // out any users.
+            return if err == "invalid_grant" {
+                Ok(UpstreamRefresh::Revoked(format!(
+                    "invalid_grant: {}",
+                    ts.error_description.unwrap_or_default()
+                )))
+            } else {
+                Err(ErrorResponse::new(
+                    ErrorResponseType::Internal,
+                    format!(
+                        "HTTP {status} /token refresh error: {err}: {}",
+                        ts.error_description.unwrap_or_default()
+                    ),
+                ))
+            };
+        }
+        if !(200..300).contains(&status) {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Internal,
+                format!("HTTP {status} during the upstream /token refresh"),
+            ));
+        }
+
+        // Most providers rotate the refresh token with each use.
+        Self::upsert(
+            &self.user_id,
+            &self.provider_id,
+            ts.refresh_token.as_deref().unwrap_or(&refresh_token),
+        )
+        .await?;
+
+        let mut claims_bytes = None;
+        if let Some(id_token) = &ts.id_token
+            && provider.jwks_endpoint.is_some()
+        {
+            provider.validate_id_token_signature(id_token).await?;
+            let bytes = AuthProviderIdClaims::self_as_bytes_from_token(id_token)?;
+            let claims = AuthProviderIdClaims::try_from(bytes.as_slice())?;
+            JwtTimeClaims {
+                iat: claims.iat,
+                exp: claims.exp,
+                nbf: claims.nbf,
+            }
+            .validate(RauthyConfig::get().vars.access.jwt_leeway)?;
+            claims_bytes = Some(bytes);
+        }
+
+        let use_userinfo = match &claims_bytes {
+            Some(bytes) => provider.use_userinfo.unwrap_or_else(|| {
+                AuthProviderIdClaims::try_from(bytes.as_slice())
+                    .map(|claims| claims.email(provider).is_none())
+                    .unwrap_or(true)
+            }),
+            None => provider.use_userinfo != Some(false),
+        };
+        if use_userinfo && let Some(access_token) = &ts.access_token {
+            let userinfo = provider.fetch_userinfo(access_token).await?;
+            claims_bytes = Some(match claims_bytes {
+                Some(bytes) => AuthProviderIdClaims::merge_userinfo(&bytes, &userinfo)?,
+                None => userinfo,
+            });
+        }
+
+        // The refresh itself succeeded, but there is nothing to sync.
+        let Some(claims_bytes) = claims_bytes else {
+            return Ok(UpstreamRefresh::Synced);
+        };
+        let mut claims = AuthProviderIdClaims::try_from(claims_bytes.as_slice())?;
+        if provider.typ == AuthProviderType::GitHub
+            && let Some(access_token) = &ts.access_token
+        {
+            auth_provider_cust_impls::get_github_private_email(access_token, &mut claims).await?;
+        }
+
+        match claims.sync_refreshed_user(provider, &self.user_id).await {
+            Ok(_) => Ok(UpstreamRefresh::Synced),
+            Err(err) if err.error == ErrorResponseType::Forbidden => {
+                Ok(UpstreamRefresh::Revoked(err.message.to_string()))
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
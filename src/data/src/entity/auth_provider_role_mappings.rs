@@ -0,0 +1,412 @@
+use crate::database::DB;
+use crate::entity::auth_providers::AuthProvider;
+use crate::entity::roles::Role;
+use hiqlite::macros::params;
+use rauthy_api_types::auth_providers::{ProviderRoleMappingRequest, ProviderRoleMappingResponse};
+use rauthy_common::is_hiqlite;
+use rauthy_common::utils::new_store_id;
+use rauthy_derive::FromPgRow;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use serde::{Deserialize, Serialize};
+use serde_json_path::JsonPath;
+
+/// Maps a value from the upstream ID token claims to a Rauthy role.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromPgRow)]
+pub struct AuthProviderRoleMapping {
+    pub id: String,
+    pub provider_id: String,
+    pub claim_path: String,
+    pub claim_value: String,
+    pub role_id: String,
+}
+
+/// Tracks a role, which has been added to a user by an `AuthProviderRoleMapping`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromPgRow)]
+pub struct UserMappedRole {
+    pub user_id: String,
+    pub role_id: String,
+}
+
+// CRUD
+impl AuthProviderRoleMapping {
+    pub async fn create(
+        provider_id: String,
+        payload: ProviderRoleMappingRequest,
+    ) -> Result<Self, ErrorResponse> {
+        // make sure we return a proper error instead of a FK violation
+        AuthProvider::find(&provider_id).await?;
+        Role::find(&payload.role_id).await?;
+
+        let slf = Self {
+            id: new_store_id(),
+            provider_id,
+            claim_path: Self::validate_claim_path(payload.claim_path)?,
+            claim_value: payload.claim_value,
+            role_id: payload.role_id,
+        };
+
+        if Self::find_for_provider(&slf.provider_id)
+            .await?
+            .iter()
+            .any(|m| m.is_same_mapping(&slf))
+        {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "This role mapping exists already",
+            ));
+        }
+
+        let sql = r#"
+INSERT INTO auth_provider_role_mappings (id, provider_id, claim_path, claim_value, role_id)
+VALUES ($1, $2, $3, $4, $5)"#;
+        if is_hiqlite() {
+            DB::hql()
+                .execute(
+                    sql,
+                    params!(
+                        slf.id.clone(),
+                        slf.provider_id.clone(),
+                        slf.claim_path.clone(),
+                        slf.claim_value.clone(),
+                        slf.role_id.clone()
+                    ),
+                )
+                .await?;
+        } else {
+            DB::pg_execute(
+                sql,
+                &[
+                    &slf.id,
+                    &slf.provider_id,
+                    &slf.claim_path,
+                    &slf.claim_value,
+                    &slf.role_id,
+                ],
+            )
+            .await?;
+        }
+
+        Ok(slf)
+    }
+
+    pub async fn delete(provider_id: &str, id: &str) -> Result<(), ErrorResponse> {
+        let sql = "DELETE FROM auth_provider_role_mappings WHERE id = $1 AND provider_id = $2";
+        let rows_affected = if is_hiqlite() {
+            DB::hql().execute(sql, params!(id, provider_id)).await?
+        } else {
+            DB::pg_execute(sql, &[&id, &provider_id]).await?
+        };
+
+        if rows_affected == 0 {
+            Err(ErrorResponse::new(
+                ErrorResponseType::NotFound,
+                "Role mapping not found",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub async fn find(provider_id: &str, id: &str) -> Result<Self, ErrorResponse> {
+        let sql = "SELECT * FROM auth_provider_role_mappings WHERE id = $1 AND provider_id = $2";
+        let slf = if is_hiqlite() {
+            DB::hql()
+                .query_as_one(sql, params!(id, provider_id))
+                .await?
+        } else {
+            DB::pg_query_one(sql, &[&id, &provider_id]).await?
+        };
+
+        Ok(slf)
+    }
+
+    pub async fn find_for_provider(provider_id: &str) -> Result<Vec<Self>, ErrorResponse> {
+        let sql = "SELECT * FROM auth_provider_role_mappings WHERE provider_id = $1";
+        let res = if is_hiqlite() {
+            DB::hql().query_as(sql, params!(provider_id)).await?
+        } else {
+            DB::pg_query(sql, &[&provider_id], 4).await?
+        };
+
+        Ok(res)
+    }
+
+    pub async fn update(
+        provider_id: &str,
+        id: &str,
+        payload: ProviderRoleMappingRequest,
+    ) -> Result<Self, ErrorResponse> {
+        let mut slf = Self::find(provider_id, id).await?;
+        if slf.role_id != payload.role_id {
+            Role::find(&payload.role_id).await?;
+        }
+
+        slf.claim_path = Self::validate_claim_path(payload.claim_path)?;
+        slf.claim_value = payload.claim_value;
+        slf.role_id = payload.role_id;
+
+        if Self::find_for_provider(provider_id)
+            .await?
+            .iter()
+            .any(|m| m.id != slf.id && m.is_same_mapping(&slf))
+        {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "This role mapping exists already",
+            ));
+        }
+
+        let sql = r#"
+UPDATE auth_provider_role_mappings
+SET claim_path = $1, claim_value = $2, role_id = $3
+WHERE id = $4"#;
+        if is_hiqlite() {
+            DB::hql()
+                .execute(
+                    sql,
+                    params!(
+                        slf.claim_path.clone(),
+                        slf.claim_value.clone(),
+                        slf.role_id.clone(),
+                        slf.id.clone()
+                    ),
+                )
+                .await?;
+        } else {
+            DB::pg_execute(
+                sql,
+                &[&slf.claim_path, &slf.claim_value, &slf.role_id, &slf.id],
+            )
+            .await?;
+        }
+
+        Ok(slf)
+    }
+
+    /// Returns the ids of all roles, which have been added to the user by a mapping.
+    pub async fn find_mapped_role_ids(user_id: &str) -> Result<Vec<String>, ErrorResponse> {
+        let sql = "SELECT role_id FROM user_mapped_roles WHERE user_id = $1";
+        let res = if is_hiqlite() {
+            DB::hql()
+                .query_raw(sql, params!(user_id))
+                .await?
+                .into_iter()
+                .map(|mut r| r.get::<String>("role_id"))
+                .collect()
+        } else {
+            DB::pg_query_rows(sql, &[&user_id], 4)
+                .await?
+                .into_iter()
+                .map(|r| r.get::<_, String>("role_id"))
+                .collect()
+        };
+
+        Ok(res)
+    }
+
+    /// Replaces the roles, which are tracked as mapped for this user.
+    pub async fn save_mapped_role_ids(
+        user_id: &str,
+        role_ids: &[String],
+    ) -> Result<(), ErrorResponse> {
+        let sql_del = "DELETE FROM user_mapped_roles WHERE user_id = $1";
+        let sql_ins = "INSERT INTO user_mapped_roles (user_id, role_id) VALUES ($1, $2)";
+
+        if is_hiqlite() {
+            let mut txn = Vec::with_capacity(role_ids.len() + 1);
+            txn.push((sql_del, params!(user_id.to_string())));
+            for role_id in role_ids {
+                txn.push((sql_ins, params!(user_id.to_string(), role_id.clone())));
+            }
+            DB::hql().txn(txn).await?;
+        } else {
+            let mut cl = DB::pg().await?;
+            let txn = cl.transaction().await?;
+            DB::pg_txn_append(&txn, sql_del, &[&user_id]).await?;
+            for role_id in role_ids {
+                DB::pg_txn_append(&txn, sql_ins, &[&user_id, role_id]).await?;
+            }
+            txn.commit().await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl From<AuthProviderRoleMapping> for ProviderRoleMappingResponse {
+    fn from(value: AuthProviderRoleMapping) -> Self {
+        Self {
+            id: value.id,
+            provider_id: value.provider_id,
+            claim_path: value.claim_path,
+            claim_value: value.claim_value,
+            role_id: value.role_id,
+        }
+    }
+}
+
+impl AuthProviderRoleMapping {
+    #[inline]
+    fn is_same_mapping(&self, other: &Self) -> bool {
+        self.claim_path == other.claim_path
+            && self.claim_value == other.claim_value
+            && self.role_id == other.role_id
+    }
+
+    fn validate_claim_path(path: String) -> Result<String, ErrorResponse> {
+        let path = path.trim().to_string();
+        if let Err(err) = JsonPath::parse(&path) {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("invalid JsonPath for `claim_path`: {err}"),
+            ));
+        }
+        Ok(path)
+    }
+
+    /// Returns the names of all roles with at least one mapping, for which `matches` returns
+    /// `true`. Mappings for roles, which do not exist anymore, are ignored.
+    pub fn matched_roles<F>(mappings: &[Self], roles: &[Role], matches: F) -> Vec<String>
+    where
+        F: Fn(&str, &str) -> bool,
+    {
+        let mut res: Vec<String> = Vec::with_capacity(mappings.len());
+        for mapping in mappings {
+            let Some(role) = roles.iter().find(|r| r.id == mapping.role_id) else {
+                continue;
+            };
+            if !res.contains(&role.name) && matches(&mapping.claim_path, &mapping.claim_value) {
+                res.push(role.name.clone());
+            }
+        }
+        res
+    }
+
+    /// Recalculates the comma separated `user_roles` from the `matched` role names of the
+    /// current login.
+    ///
+    /// `mapped_before` contains the roles, which have been added by a mapping during an earlier
+    /// login. Only these will be removed again, as soon as no mapping matches anymore. A matching
+    /// role, which the user had already without a mapping, has been assigned manually and will
+    /// neither be tracked nor ever be removed.
+    ///
+    /// Returns the new roles and the role names, which are tracked as mapped from now on.
+    pub fn apply_user_roles(
+        user_roles: &str,
+        mapped_before: &[String],
+        matched: &[String],
+    ) -> (String, Vec<String>) {
+        let mut roles = user_roles
+            .split(',')
+            .filter(|r| !r.is_empty())
+            .filter(|r| {
+                let r = r.to_string();
+                !mapped_before.contains(&r) || matched.contains(&r)
+            })
+            .map(String::from)
+            .collect::<Vec<_>>();
+
+        let mut mapped = Vec::with_capacity(matched.len());
+        for role in matched {
+            if mapped_before.contains(role) {
+                // may have been removed manually in the meantime
+                if !roles.contains(role) {
+                    roles.push(role.clone());
+                }
+                mapped.push(role.clone());
+            } else if !roles.contains(role) {
+                roles.push(role.clone());
+                mapped.push(role.clone());
+            }
+        }
+
+        (roles.join(","), mapped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapping(claim_value: &str, role_id: &str) -> AuthProviderRoleMapping {
+        AuthProviderRoleMapping {
+            id: new_store_id(),
+            provider_id: "provider".to_string(),
+            claim_path: "$.roles[*]".to_string(),
+            claim_value: claim_value.to_string(),
+            role_id: role_id.to_string(),
+        }
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn test_matched_roles() {
+        let roles = ["admin", "dev"]
+            .into_iter()
+            .map(|name| Role {
+                id: format!("{name}Id"),
+                name: name.to_string(),
+                meta: None,
+            })
+            .collect::<Vec<_>>();
+        let mappings = vec![
+            mapping("upstream_admin", "adminId"),
+            mapping("upstream_root", "adminId"),
+            mapping("upstream_dev", "devId"),
+            // the role has been deleted in the meantime
+            mapping("upstream_ops", "opsId"),
+        ];
+        let upstream = ["upstream_admin", "upstream_root", "upstream_ops"];
+
+        let matched = AuthProviderRoleMapping::matched_roles(&mappings, &roles, |path, value| {
+            assert_eq!(path, "$.roles[*]");
+            upstream.contains(&value)
+        });
+        assert_eq!(matched, names(&["admin"]));
+
+        let matched = AuthProviderRoleMapping::matched_roles(&mappings, &roles, |_, _| false);
+        assert!(matched.is_empty());
+    }
+
+    #[test]
+    fn test_apply_user_roles() {
+        // first login: mapped roles are added and tracked
+        let (roles, mapped) =
+            AuthProviderRoleMapping::apply_user_roles("", &[], &names(&["admin", "dev"]));
+        assert_eq!(roles, "admin,dev");
+        assert_eq!(mapped, names(&["admin", "dev"]));
+
+        // the upstream claim is gone -> only the mapped role is removed
+        let (roles, mapped) = AuthProviderRoleMapping::apply_user_roles(
+            "admin,dev,local",
+            &names(&["admin", "dev"]),
+            &names(&["dev"]),
+        );
+        assert_eq!(roles, "dev,local");
+        assert_eq!(mapped, names(&["dev"]));
+
+        // the mapping has been deleted -> nothing matches anymore
+        let (roles, mapped) =
+            AuthProviderRoleMapping::apply_user_roles("dev,local", &names(&["dev"]), &[]);
+        assert_eq!(roles, "local");
+        assert!(mapped.is_empty());
+
+        // manually assigned before the mapping matched -> never tracked, never removed
+        let (roles, mapped) =
+            AuthProviderRoleMapping::apply_user_roles("local,admin", &[], &names(&["admin"]));
+        assert_eq!(roles, "local,admin");
+        assert!(mapped.is_empty());
+        let (roles, mapped) = AuthProviderRoleMapping::apply_user_roles("local,admin", &[], &[]);
+        assert_eq!(roles, "local,admin");
+        assert!(mapped.is_empty());
+
+        // manually removed by an admin, but still mapped -> added again
+        let (roles, mapped) =
+            AuthProviderRoleMapping::apply_user_roles("local", &names(&["dev"]), &names(&["dev"]));
+        assert_eq!(roles, "local,dev");
+        assert_eq!(mapped, names(&["dev"]));
+    }
+}
@@ -0,0 +1,269 @@
+use crate::entity::auth_providers::AuthProvider;
+use crate::rauthy_config::RauthyConfig;
+use rauthy_api_types::auth_providers::{
+    ProviderSecretProbeMethod, ProviderSecretProbeResult, ProviderSecretResponse,
+};
+use rauthy_common::utils::get_rand;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::debug;
+
+/// The timeout for a single probe against an upstream `token_endpoint`.
+pub(crate) const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Errors some providers return instead of a `401` for bad client credentials.
+pub(crate) const BAD_CREDENTIALS_ERRORS: [&str; 2] =
+    ["invalid_client", "incorrect_client_credentials"];
+
+#[derive(Serialize)]
+struct ProbeRequestParams<'a> {
+    grant_type: &'a str,
+    client_id: &'a str,
+    code: Option<String>,
+    code_verifier: Option<String>,
+    redirect_uri: Option<&'a str>,
+}
+
+#[derive(Deserialize)]
+struct ProbeErrorResponse {
+    error: Option<String>,
+}
+
+/// The result of a single probe request against the upstream `token_endpoint`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthProviderSecretProbe {
+    pub method: ProviderSecretProbeMethod,
+    pub result: ProviderSecretProbeResult,
+    pub status: Option<u16>,
+    pub error: Option<String>,
+}
+
+impl AuthProviderSecretProbe {
+    /// Checks the given secret against the upstream without persisting anything.
+    ///
+    /// A `client_credentials` grant is tried first. If the upstream does not allow this grant for
+    /// the client, an `authorization_code` grant with an invalid code follows, which the upstream
+    /// rejects with a `400` for valid and a `401` for bad credentials.
+    pub async fn run(provider: &AuthProvider, secret: &str) -> Self {
+        let probe = Self::send(
+            provider,
+            secret,
+            ProviderSecretProbeMethod::ClientCredentials,
+        )
+        .await;
+        match probe {
+            Some(probe) => probe,
+            None => Self::send(
+                provider,
+                secret,
+                ProviderSecretProbeMethod::AuthorizationCode,
+            )
+            .await
+            .unwrap_or(Self {
+                method: ProviderSecretProbeMethod::AuthorizationCode,
+                result: ProviderSecretProbeResult::Inconclusive,
+                status: None,
+                error: None,
+            }),
+        }
+    }
+
+    /// Returns `None` if the upstream does not support this probe.
+    async fn send(
+        provider: &AuthProvider,
+        secret: &str,
+        method: ProviderSecretProbeMethod,
+    ) -> Option<Self> {
+        let mut params = ProbeRequestParams {
+            grant_type: "client_credentials",
+            client_id: &provider.client_id,
+            code: None,
+            code_verifier: None,
+            redirect_uri: None,
+        };
+        if method == ProviderSecretProbeMethod::AuthorizationCode {
+            params.grant_type = "authorization_code";
+            params.code = Some(get_rand(32));
+            params.code_verifier = provider.use_pkce.then(|| get_rand(64));
+            params.redirect_uri = Some(&RauthyConfig::get().provider_callback_uri);
+        }
+
+        let client = match provider.build_client() {
+            Ok(client) => client,
+            Err(err) => {
+                return Some(Self {
+                    method,
+                    result: ProviderSecretProbeResult::Inconclusive,
+                    status: None,
+                    error: Some(err.message.to_string()),
+                });
+            }
+        };
+        let res = match provider
+            .token_request(&client, &params, Some(secret))
+            .timeout(PROBE_TIMEOUT)
+            .send()
+            .await
+        {
+            Ok(res) => res,
+            Err(err) => {
+                debug!(?err, "Secret probe for {}", provider.token_endpoint);
+                return Some(Self {
+                    method,
+                    result: ProviderSecretProbeResult::Inconclusive,
+                    status: None,
+                    error: Some(err.to_string()),
+                });
+            }
+        };
+
+        let status = res.status().as_u16();
+        let error = res
+            .json::<ProbeErrorResponse>()
+            .await
+            .ok()
+            .and_then(|body| body.error);
+        debug!(
+            "Secret probe {method:?} for {}: {status} {error:?}",
+            provider.token_endpoint
+        );
+
+        Self::classify(method, status, error.as_deref()).map(|result| Self {
+            method,
+            result,
+            status: Some(status),
+            error,
+        })
+    }
+
+    /// Maps an upstream response to a probe result. Returns `None` if the upstream refused
+    /// the grant itself without telling anything about the credentials.
+    fn classify(
+        method: ProviderSecretProbeMethod,
+        status: u16,
+        error: Option<&str>,
+    ) -> Option<ProviderSecretProbeResult> {
+        if status == 401 || error.is_some_and(|e| BAD_CREDENTIALS_ERRORS.contains(&e)) {
+            return Some(ProviderSecretProbeResult::Invalid);
+        }
+        if status >= 500 {
+            return Some(ProviderSecretProbeResult::Inconclusive);
+        }
+        let is_success = (200..300).contains(&status);
+        if is_success && error.is_none() {
+            return Some(ProviderSecretProbeResult::Valid);
+        }
+
+        // Some providers (e.g. GitHub) answer with a `200` and an `error` in the body.
+        let is_rejected = status == 400 || (is_success && error.is_some());
+        match method {
+            ProviderSecretProbeMethod::ClientCredentials if is_rejected || status == 403 => None,
+            ProviderSecretProbeMethod::AuthorizationCode if is_rejected => {
+                Some(ProviderSecretProbeResult::Valid)
+            }
+            _ => Some(ProviderSecretProbeResult::Inconclusive),
+        }
+    }
+
+    /// `true` if the secret may be persisted after this probe.
+    #[inline]
+    pub fn allows_persist(&self, force: bool) -> bool {
+        force || self.result != ProviderSecretProbeResult::Invalid
+    }
+
+    pub fn into_response(self, persisted: bool) -> ProviderSecretResponse {
+        ProviderSecretResponse {
+            result: self.result,
+            method: self.method,
+            status: self.status,
+            error: self.error,
+            persisted,
+        }
+    }
+}
+
+impl AuthProvider {
+    /// Probes the new secret against the upstream and only saves it, if the upstream did not
+    /// reject it, or if `force` is set.
+    pub async fn rotate_secret(
+        id: &str,
+        secret: &str,
+        force: bool,
+    ) -> Result<AuthProviderSecretProbe, ErrorResponse> {
+        let mut provider = Self::find(id).await?;
+        if !provider.client_secret_basic && !provider.client_secret_post {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "This provider is not configured as a confidential client",
+            ));
+        }
+
+        let probe = AuthProviderSecretProbe::run(&provider, secret).await;
+        if probe.allows_persist(force) {
+            provider.secret = Self::secret_encrypted(&Some(secret.to_string()))?;
+            provider.save().await?;
+        }
+
+        Ok(probe)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ProviderSecretProbeMethod::{AuthorizationCode, ClientCredentials};
+    use ProviderSecretProbeResult::{Inconclusive, Invalid, Valid};
+
+    #[test]
+    fn test_classify_client_credentials() {
+        let classify =
+            |status, error| AuthProviderSecretProbe::classify(ClientCredentials, status, error);
+
+        assert_eq!(classify(200, None), Some(Valid));
+        assert_eq!(classify(401, None), Some(Invalid));
+        assert_eq!(classify(400, Some("invalid_client")), Some(Invalid));
+        assert_eq!(
+            classify(200, Some("incorrect_client_credentials")),
+            Some(Invalid)
+        );
+        assert_eq!(classify(503, None), Some(Inconclusive));
+        assert_eq!(classify(404, None), Some(Inconclusive));
+
+        // the grant is not allowed -> fall back to the next probe
+        assert_eq!(classify(400, Some("unauthorized_client")), None);
+        assert_eq!(classify(400, Some("unsupported_grant_type")), None);
+        assert_eq!(classify(200, Some("unsupported_grant_type")), None);
+        assert_eq!(classify(403, None), None);
+    }
+
+    #[test]
+    fn test_classify_authorization_code() {
+        let classify =
+            |status, error| AuthProviderSecretProbe::classify(AuthorizationCode, status, error);
+
+        // the client has been authenticated before the code was rejected
+        assert_eq!(classify(400, Some("invalid_grant")), Some(Valid));
+        assert_eq!(classify(400, None), Some(Valid));
+        assert_eq!(classify(200, Some("bad_verification_code")), Some(Valid));
+
+        assert_eq!(classify(401, None), Some(Invalid));
+        assert_eq!(classify(400, Some("invalid_client")), Some(Invalid));
+        assert_eq!(classify(403, None), Some(Inconclusive));
+        assert_eq!(classify(502, None), Some(Inconclusive));
+    }
+
+    #[test]
+    fn test_allows_persist() {
+        let probe = |result| AuthProviderSecretProbe {
+            method: ClientCredentials,
+            result,
+            status: None,
+            error: None,
+        };
+
+        assert!(probe(Valid).allows_persist(false));
+        assert!(probe(Inconclusive).allows_persist(false));
+        assert!(!probe(Invalid).allows_persist(false));
+        assert!(probe(Invalid).allows_persist(true));
+    }
+}
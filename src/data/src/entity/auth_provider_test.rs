@@ -0,0 +1,268 @@
+use crate::entity::auth_provider_secret::{BAD_CREDENTIALS_ERRORS, PROBE_TIMEOUT};
+use crate::entity::auth_providers::{AuthProvider, AuthProviderType, UpstreamWellKnown};
+use rauthy_api_types::auth_providers::{
+    ProviderTestResponse, ProviderTestResult, ProviderTestStep, ProviderTestStepResponse,
+};
+use rauthy_common::http_client;
+use rauthy_error::ErrorResponse;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::time::Instant;
+use tracing::debug;
+
+/// Errors which tell that the upstream authenticated the client, but refused the grant.
+const GRANT_REJECTED_ERRORS: [&str; 3] = [
+    "unauthorized_client",
+    "unsupported_grant_type",
+    "invalid_scope",
+];
+
+#[derive(Serialize)]
+struct ClientCredentialsParams<'a> {
+    grant_type: &'static str,
+    client_id: &'a str,
+}
+
+#[derive(Deserialize)]
+struct TokenErrorResponse {
+    error: Option<String>,
+}
+
+/// The outcome of a single step before it is timed.
+struct StepOutcome {
+    result: ProviderTestResult,
+    status: Option<u16>,
+    error: Option<String>,
+}
+
+impl StepOutcome {
+    fn skipped() -> Self {
+        Self {
+            result: ProviderTestResult::Skipped,
+            status: None,
+            error: None,
+        }
+    }
+
+    fn from_reqwest(err: reqwest::Error) -> Self {
+        let mut chain = err.to_string();
+        let mut source = err.source();
+        while let Some(err) = source {
+            chain.push_str(": ");
+            chain.push_str(&err.to_string());
+            source = err.source();
+        }
+
+        Self {
+            result: classify_transport(err.is_timeout(), &chain),
+            status: None,
+            error: Some(chain),
+        }
+    }
+
+    fn into_step(self, step: ProviderTestStep, start: Instant) -> ProviderTestStepResponse {
+        ProviderTestStepResponse {
+            step,
+            result: self.result,
+            status: self.status,
+            error: self.error,
+            duration_ms: start.elapsed().as_millis() as u64,
+        }
+    }
+}
+
+impl AuthProvider {
+    /// Checks the connectivity to the upstream without touching any logins. The decrypted secret
+    /// is only ever sent to the `token_endpoint` and never included in the response.
+    pub async fn test_connectivity(&self) -> Result<ProviderTestResponse, ErrorResponse> {
+        let mut steps = Vec::with_capacity(2);
+
+        let start = Instant::now();
+        let outcome = self.test_metadata().await;
+        steps.push(outcome.into_step(ProviderTestStep::Metadata, start));
+
+        let start = Instant::now();
+        let outcome = match self.client_secret()? {
+            Some(secret) if self.client_secret_basic || self.client_secret_post => {
+                self.test_token_endpoint(&self.build_client()?, &secret)
+                    .await
+            }
+            _ => StepOutcome::skipped(),
+        };
+        steps.push(outcome.into_step(ProviderTestStep::TokenEndpoint, start));
+
+        Ok(ProviderTestResponse {
+            success: steps.iter().all(|s| {
+                matches!(
+                    s.result,
+                    ProviderTestResult::Success
+                        | ProviderTestResult::Skipped
+                        | ProviderTestResult::GrantRejected
+                )
+            }),
+            steps,
+        })
+    }
+
+    async fn test_metadata(&self) -> StepOutcome {
+        // GitHub does not provide an openid-configuration
+        if self.typ == AuthProviderType::GitHub {
+            return StepOutcome::skipped();
+        }
+
+        let url = Self::openid_configuration_url(&self.issuer);
+        let res = match http_client().get(&url).timeout(PROBE_TIMEOUT).send().await {
+            Ok(res) => res,
+            Err(err) => {
+                debug!(?err, "Connectivity test to {url}");
+                return StepOutcome::from_reqwest(err);
+            }
+        };
+
+        let status = res.status().as_u16();
+        if !res.status().is_success() {
+            return StepOutcome {
+                result: ProviderTestResult::InvalidResponse,
+                status: Some(status),
+                error: None,
+            };
+        }
+
+        let error = match res.json::<UpstreamWellKnown>().await {
+            Ok(well_known)
+                if well_known.issuer.trim_end_matches('/') == self.issuer.trim_end_matches('/') =>
+            {
+                None
+            }
+            Ok(well_known) => Some(format!(
+                "The openid-configuration contains a different issuer: {}",
+                well_known.issuer
+            )),
+            Err(err) => Some(format!("Invalid openid-configuration: {err}")),
+        };
+        StepOutcome {
+            result: if error.is_none() {
+                ProviderTestResult::Success
+            } else {
+                ProviderTestResult::InvalidResponse
+            },
+            status: Some(status),
+            error,
+        }
+    }
+
+    async fn test_token_endpoint(&self, client: &reqwest::Client, secret: &str) -> StepOutcome {
+        let params = ClientCredentialsParams {
+            grant_type: "client_credentials",
+            client_id: &self.client_id,
+        };
+        let res = match self
+            .token_request(client, &params, Some(secret))
+            .timeout(PROBE_TIMEOUT)
+            .send()
+            .await
+        {
+            Ok(res) => res,
+            Err(err) => {
+                debug!(?err, "Connectivity test to {}", self.token_endpoint);
+                return StepOutcome::from_reqwest(err);
+            }
+        };
+
+        let status = res.status().as_u16();
+        let error = res
+            .json::<TokenErrorResponse>()
+            .await
+            .ok()
+            .and_then(|body| body.error);
+
+        StepOutcome {
+            result: classify_token_response(status, error.as_deref()),
+            status: Some(status),
+            error,
+        }
+    }
+}
+
+/// `chain` is the error message including all of its sources.
+fn classify_transport(is_timeout: bool, chain: &str) -> ProviderTestResult {
+    if is_timeout {
+        return ProviderTestResult::Timeout;
+    }
+
+    let chain = chain.to_lowercase();
+    if ["certificate", "tls", "handshake"]
+        .iter()
+        .any(|s| chain.contains(s))
+    {
+        ProviderTestResult::TlsError
+    } else {
+        ProviderTestResult::ConnectionError
+    }
+}
+
+fn classify_token_response(status: u16, error: Option<&str>) -> ProviderTestResult {
+    if status == 401 || error.is_some_and(|e| BAD_CREDENTIALS_ERRORS.contains(&e)) {
+        return ProviderTestResult::InvalidCredentials;
+    }
+    if (200..300).contains(&status) && error.is_none() {
+        return ProviderTestResult::Success;
+    }
+    if status == 403 || error.is_some_and(|e| GRANT_REJECTED_ERRORS.contains(&e)) {
+        return ProviderTestResult::GrantRejected;
+    }
+    ProviderTestResult::InvalidResponse
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ProviderTestResult::*;
+
+    #[test]
+    fn test_classify_transport() {
+        assert_eq!(classify_transport(true, "operation timed out"), Timeout);
+        assert_eq!(
+            classify_transport(
+                false,
+                "error sending request: client error (Connect): invalid peer certificate: UnknownIssuer"
+            ),
+            TlsError
+        );
+        assert_eq!(
+            classify_transport(
+                false,
+                "error sending request: received fatal alert: HandshakeFailure"
+            ),
+            TlsError
+        );
+        assert_eq!(
+            classify_transport(
+                false,
+                "error sending request: tcp connect error: Connection refused"
+            ),
+            ConnectionError
+        );
+    }
+
+    #[test]
+    fn test_classify_token_response() {
+        assert_eq!(classify_token_response(200, None), Success);
+        assert_eq!(classify_token_response(401, None), InvalidCredentials);
+        assert_eq!(
+            classify_token_response(400, Some("invalid_client")),
+            InvalidCredentials
+        );
+        assert_eq!(
+            classify_token_response(200, Some("incorrect_client_credentials")),
+            InvalidCredentials
+        );
+        assert_eq!(
+            classify_token_response(400, Some("unauthorized_client")),
+            GrantRejected
+        );
+        assert_eq!(classify_token_response(403, None), GrantRejected);
+        assert_eq!(classify_token_response(404, None), InvalidResponse);
+        assert_eq!(classify_token_response(502, None), InvalidResponse);
+    }
+}
@@ -1,10 +1,14 @@
+use crate::cache_layer::{self, CacheName};
 use crate::database::{Cache, DB};
 use crate::entity::auth_providers::ProviderMfaLogin;
+use crate::entity::clients_claims_webhook::ClientClaimsWebhook;
 use crate::entity::clients_dyn::ClientDyn;
+use crate::entity::clients_jwks::ClientJwks;
 use crate::entity::clients_scim::ClientScim;
 use crate::entity::jwk::JwkKeyPairAlg;
 use crate::entity::scopes::Scope;
 use crate::entity::users::User;
+use crate::html::login_page_context::LoginPageContext;
 use crate::rauthy_config::RauthyConfig;
 use actix_web::HttpRequest;
 use actix_web::http::header;
@@ -15,11 +19,15 @@ use deadpool_postgres::GenericClient;
 use hiqlite::Params;
 use hiqlite::macros::params;
 use rauthy_api_types::clients::{
-    ClientResponse, DynamicClientRequest, DynamicClientResponse, EphemeralClientRequest,
+    ClaimsWebhookResponse, ClientClaimsEmission, ClientRefreshTokenBinding, ClientResponse,
+    ClientType, DynamicClientRequest, DynamicClientResponse, EphemeralClientRequest,
     NewClientRequest, ScimClientRequestResponse,
 };
-use rauthy_common::constants::{APPLICATION_JSON, CACHE_TTL_APP, SECRET_LEN_CLIENTS};
-use rauthy_common::utils::{get_rand, real_ip_from_req};
+use rauthy_common::constants::{
+    APPLICATION_JSON, CACHE_TTL_APP, GRANT_TYPE_DEVICE_CODE, RESPONSE_TYPES_SUPPORTED,
+    SECRET_LEN_CLIENTS,
+};
+use rauthy_common::utils::{get_rand, real_ip, secure_compare, truncate_for_log};
 use rauthy_common::{http_client, is_hiqlite};
 use rauthy_derive::FromPgRow;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
@@ -43,8 +51,10 @@ SET name = $1, enabled = $2, confidential = $3, secret = $4, secret_kid = $5, re
     id_token_alg = $11, auth_code_lifetime = $12, access_token_lifetime = $13, scopes = $14,
     default_scopes = $15, challenge = $16, force_mfa= $17, client_uri = $18, contacts = $19,
     backchannel_logout_uri = $20, restrict_group_prefix = $21, claims = $22,
-    claims_at_root = $23, allowed_resources = $24, default_aud = $25
-WHERE id = $26"#;
+    claims_at_root = $23, allowed_resources = $24, default_aud = $25, claims_emission = $26,
+    refresh_token_binding = $27, client_type = $28, allowed_roles = $29, allowed_groups = $30,
+    response_types = $31
+WHERE id = $32"#;
 
 /**
 # OIDC Client
@@ -85,7 +95,7 @@ pub struct Client {
     // Admin-defined custom claims, serialized JSON object. We don't store
     // `serde_json::Value` directly, because it might produce a
     // `Bincode: Serde(AnyNotSupported)`. Emitted into `client_credentials`
-    // tokens; see `TokenSet::build_access_token`.
+    // tokens; see `token_set::build_access_token`.
     pub claims: Option<Vec<u8>>,
     // When `true`, `claims` are emitted at the token root (flattened) instead of
     // nested under `custom`, guarded by `validate_no_reserved_collision()`.
@@ -94,6 +104,21 @@ pub struct Client {
     pub allowed_resources: Option<String>,
     /// Audiences always added to this client's tokens, independent of any request (CSV).
     pub default_aud: Option<String>,
+    /// Serialized `ClientClaimsEmission` controlling how `roles` / `groups` are emitted.
+    /// `None` keeps the default behavior.
+    pub claims_emission: Option<Vec<u8>>,
+    // Serialized `ClientRefreshTokenBinding` for public clients.
+    // `None` does not bind refresh tokens to the requesting context.
+    pub refresh_token_binding: Option<Vec<u8>>,
+    // `web` or `native`, see `ClientType`
+    pub client_type: String,
+    /// Only users with at least one of these roles or `allowed_groups` may use this client (CSV).
+    /// Both being empty means unrestricted.
+    pub allowed_roles: Option<String>,
+    /// Only users with at least one of these groups or `allowed_roles` may use this client (CSV).
+    pub allowed_groups: Option<String>,
+    /// The allowed `response_type`s for `/oidc/authorize` (CSV), never empty.
+    pub response_types: String,
 }
 
 impl Debug for Client {
@@ -105,8 +130,9 @@ impl Debug for Client {
         flows_enabled: {}, access_token_alg: {}, id_token_alg: {}, auth_code_lifetime: {}, \
         access_token_lifetime: {}, scopes: {}, default_scopes: {}, challenge: {:?}, force_mfa: {}, \
         client_uri: {:?}, contacts: {:?}, backchannel_logout_uri: {:?}, restrict_group_prefix: {:?}, \
-        claims: {:?}, claims_at_root: {}, allowed_resources: {:?}, default_aud: {:?} \
-        }}",
+        claims: {:?}, claims_at_root: {}, allowed_resources: {:?}, default_aud: {:?}, \
+        claims_emission: {:?}, refresh_token_binding: {:?}, client_type: {}, allowed_roles: {:?}, \
+        allowed_groups: {:?}, response_types: {} }}",
             self.id,
             self.name,
             self.enabled,
@@ -131,6 +157,14 @@ impl Debug for Client {
             self.claims_at_root,
             self.allowed_resources,
             self.default_aud,
+            self.claims_emission.as_deref().map(String::from_utf8_lossy),
+            self.refresh_token_binding
+                .as_deref()
+                .map(String::from_utf8_lossy),
+            self.client_type,
+            self.allowed_roles,
+            self.allowed_groups,
+            self.response_types,
         )
     }
 }
@@ -159,9 +193,9 @@ INSERT INTO clients (id, name, enabled, confidential, secret, secret_kid, redire
 post_logout_redirect_uris, allowed_origins, flows_enabled, access_token_alg, id_token_alg,
 auth_code_lifetime, access_token_lifetime, scopes, default_scopes, challenge, force_mfa,
 client_uri, contacts, backchannel_logout_uri, restrict_group_prefix, allowed_resources,
-default_aud)
+default_aud, client_type)
 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17,
-$18, $19, $20, $21, $22, $23, $24)"#;
+$18, $19, $20, $21, $22, $23, $24, $25)"#;
 
         if is_hiqlite() {
             DB::hql()
@@ -191,7 +225,8 @@ $18, $19, $20, $21, $22, $23, $24)"#;
                         &client.backchannel_logout_uri,
                         &client.restrict_group_prefix,
                         &client.allowed_resources,
-                        &client.default_aud
+                        &client.default_aud,
+                        &client.client_type
                     ),
                 )
                 .await?;
@@ -223,6 +258,7 @@ $18, $19, $20, $21, $22, $23, $24)"#;
                     &client.restrict_group_prefix,
                     &client.allowed_resources,
                     &client.default_aud,
+                    &client.client_type,
                 ],
             )
             .await?;
@@ -367,6 +403,8 @@ VALUES ($1, $2, $3, $4)"#;
         }
 
         self.delete_cache().await?;
+        ClientJwks::clear_cache(&self.id).await?;
+        LoginPageContext::invalidate_client(&self.id).await?;
 
         // We only clean up the cache. The database uses foreign key a cascade.
         if self.is_dynamic() {
@@ -377,36 +415,42 @@ VALUES ($1, $2, $3, $4)"#;
     }
 
     pub async fn delete_cache(&self) -> Result<(), ErrorResponse> {
-        DB::hql()
-            .delete(Cache::App, Self::cache_idx(&self.id))
-            .await?;
-
+        cache_layer::delete(CacheName::Client, Self::cache_idx(&self.id)).await?;
         Ok(())
     }
 
     pub async fn delete_cache_for(id: &str) -> Result<(), ErrorResponse> {
-        DB::hql().delete(Cache::App, Self::cache_idx(id)).await?;
+        cache_layer::delete(CacheName::Client, Self::cache_idx(id)).await?;
         Ok(())
     }
 
     // Returns a client by id without its secret.
     pub async fn find(id: String) -> Result<Self, ErrorResponse> {
-        let client = DB::hql();
-        if let Some(slf) = client.get(Cache::App, Self::cache_idx(&id)).await? {
+        let refresh_id = id.clone();
+        if let Some(slf) = cache_layer::get_swr(
+            CacheName::Client,
+            Self::cache_idx(&id),
+            CACHE_TTL_APP,
+            || async move { Self::find_db(refresh_id).await.map(|_| ()) },
+        )
+        .await?
+        {
             return Ok(slf);
         };
 
+        Self::find_db(id).await
+    }
+
+    /// Loads the client from the DB and (re-)populates the cache.
+    async fn find_db(id: String) -> Result<Self, ErrorResponse> {
         let sql = "SELECT * FROM clients WHERE id = $1";
         let slf: Self = if is_hiqlite() {
-            client.query_as_one(sql, params!(id)).await?
+            DB::hql().query_as_one(sql, params!(id)).await?
         } else {
             DB::pg_query_one(sql, &[&id]).await?
         };
 
-        client
-            .put(Cache::App, Self::cache_idx(&slf.id), &slf, CACHE_TTL_APP)
-            .await?;
-
+        slf.save_cache().await?;
         Ok(slf)
     }
 
@@ -519,6 +563,8 @@ VALUES ($1, $2, $3, $4)"#;
             .filter(|uri| !uri.is_empty());
         let allowed_resources = self.allowed_resources.clone().filter(|r| !r.is_empty());
         let default_aud = self.default_aud.clone().filter(|a| !a.is_empty());
+        let allowed_roles = self.allowed_roles.clone().filter(|r| !r.is_empty());
+        let allowed_groups = self.allowed_groups.clone().filter(|g| !g.is_empty());
 
         txn.push((
             SQL_SAVE,
@@ -548,6 +594,12 @@ VALUES ($1, $2, $3, $4)"#;
                 self.claims_at_root,
                 allowed_resources,
                 default_aud,
+                &self.claims_emission,
+                &self.refresh_token_binding,
+                &self.client_type,
+                allowed_roles,
+                allowed_groups,
+                &self.response_types,
                 &self.id
             ),
         ));
@@ -569,6 +621,8 @@ VALUES ($1, $2, $3, $4)"#;
             .filter(|uri| !uri.is_empty());
         let allowed_resources = self.allowed_resources.clone().filter(|r| !r.is_empty());
         let default_aud = self.default_aud.clone().filter(|a| !a.is_empty());
+        let allowed_roles = self.allowed_roles.clone().filter(|r| !r.is_empty());
+        let allowed_groups = self.allowed_groups.clone().filter(|g| !g.is_empty());
 
         DB::pg_txn_append(
             txn,
@@ -599,6 +653,12 @@ VALUES ($1, $2, $3, $4)"#;
                 &self.claims_at_root,
                 &allowed_resources,
                 &default_aud,
+                &self.claims_emission,
+                &self.refresh_token_binding,
+                &self.client_type,
+                &allowed_roles,
+                &allowed_groups,
+                &self.response_types,
                 &self.id,
             ],
         )
@@ -608,10 +668,13 @@ VALUES ($1, $2, $3, $4)"#;
     }
 
     pub async fn save_cache(&self) -> Result<(), ErrorResponse> {
-        DB::hql()
-            .put(Cache::App, Client::cache_idx(&self.id), self, CACHE_TTL_APP)
-            .await?;
-        Ok(())
+        cache_layer::put_swr(
+            CacheName::Client,
+            Client::cache_idx(&self.id),
+            self,
+            CACHE_TTL_APP,
+        )
+        .await
     }
 
     pub async fn save(&self) -> Result<(), ErrorResponse> {
@@ -627,6 +690,8 @@ VALUES ($1, $2, $3, $4)"#;
             .filter(|uri| !uri.is_empty());
         let allowed_resources = self.allowed_resources.clone().filter(|r| !r.is_empty());
         let default_aud = self.default_aud.clone().filter(|a| !a.is_empty());
+        let allowed_roles = self.allowed_roles.clone().filter(|r| !r.is_empty());
+        let allowed_groups = self.allowed_groups.clone().filter(|g| !g.is_empty());
 
         if is_hiqlite() {
             DB::hql()
@@ -658,6 +723,12 @@ VALUES ($1, $2, $3, $4)"#;
                         self.claims_at_root,
                         allowed_resources,
                         default_aud,
+                        &self.claims_emission,
+                        &self.refresh_token_binding,
+                        &self.client_type,
+                        allowed_roles,
+                        allowed_groups,
+                        &self.response_types,
                         self.id.clone()
                     ),
                 )
@@ -691,17 +762,19 @@ VALUES ($1, $2, $3, $4)"#;
                     &self.claims_at_root,
                     &allowed_resources,
                     &default_aud,
+                    &self.claims_emission,
+                    &self.refresh_token_binding,
+                    &self.client_type,
+                    &allowed_roles,
+                    &allowed_groups,
+                    &self.response_types,
                     &self.id,
                 ],
             )
             .await?;
         }
 
-        DB::hql()
-            .put(Cache::App, Client::cache_idx(&self.id), self, CACHE_TTL_APP)
-            .await?;
-
-        Ok(())
+        self.save_cache().await
     }
 
     pub async fn update_dynamic(
@@ -945,6 +1018,32 @@ impl Client {
             .filter(|s| !s.is_empty())
     }
 
+    /// Borrowed, allocation-free view of the `response_types` CSV (empties skipped).
+    #[inline]
+    pub fn response_types_iter(&self) -> impl Iterator<Item = &str> {
+        self.response_types.split(',').filter(|s| !s.is_empty())
+    }
+
+    /// Borrowed, allocation-free view of the `allowed_roles` CSV (empties skipped).
+    #[inline]
+    pub fn allowed_roles_iter(&self) -> impl Iterator<Item = &str> {
+        self.allowed_roles
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .filter(|s| !s.is_empty())
+    }
+
+    /// Borrowed, allocation-free view of the `allowed_groups` CSV (empties skipped).
+    #[inline]
+    pub fn allowed_groups_iter(&self) -> impl Iterator<Item = &str> {
+        self.allowed_groups
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .filter(|s| !s.is_empty())
+    }
+
     #[inline]
     pub fn get_allowed_resources(&self) -> Option<Vec<String>> {
         self.allowed_resources.as_ref()?;
@@ -957,6 +1056,43 @@ impl Client {
         Some(self.default_aud_iter().map(String::from).collect())
     }
 
+    #[inline]
+    pub fn get_allowed_roles(&self) -> Option<Vec<String>> {
+        self.allowed_roles.as_ref()?;
+        Some(self.allowed_roles_iter().map(String::from).collect())
+    }
+
+    #[inline]
+    pub fn get_allowed_groups(&self) -> Option<Vec<String>> {
+        self.allowed_groups.as_ref()?;
+        Some(self.allowed_groups_iter().map(String::from).collect())
+    }
+
+    /// Returns the `roles` / `groups` emission settings, or the defaults if none are set.
+    pub fn get_claims_emission(&self) -> Result<ClientClaimsEmission, ErrorResponse> {
+        match self.claims_emission.as_deref() {
+            Some(bytes) => Ok(serde_json::from_slice(bytes)?),
+            None => Ok(ClientClaimsEmission::default()),
+        }
+    }
+
+    /// Returns the refresh token binding, if any is configured. Confidential clients
+    /// authenticate themselves on each refresh and never bind their tokens.
+    pub fn get_refresh_token_binding(
+        &self,
+    ) -> Result<Option<ClientRefreshTokenBinding>, ErrorResponse> {
+        if self.confidential {
+            return Ok(None);
+        }
+        match self.refresh_token_binding.as_deref() {
+            Some(bytes) => {
+                let binding: ClientRefreshTokenBinding = serde_json::from_slice(bytes)?;
+                Ok(binding.is_enabled().then_some(binding))
+            }
+            None => Ok(None),
+        }
+    }
+
     /// Validates an RFC 8707 `resource` request value against this client's policy: it
     /// must match one of the client's configured `allowed_resources`. The entries are
     /// matched verbatim, so an operator decides what a valid value looks like. Ephemeral
@@ -1105,6 +1241,11 @@ impl Client {
         self.id.starts_with("dyn$")
     }
 
+    #[inline]
+    pub fn is_native(&self) -> bool {
+        self.client_type == ClientType::Native.as_str()
+    }
+
     #[inline]
     pub fn is_ephemeral(&self) -> bool {
         // A non-dynamic client_id can never be a valid URL because of payload validation
@@ -1244,6 +1385,12 @@ impl Client {
         pub_url_with_scheme: &str,
         additional_allowed_origin_schemes: &[String],
     ) -> Result<Option<(HeaderName, HeaderValue)>, ErrorResponse> {
+        // Native apps have no browser context. An `Origin` from an embedded web view is
+        // meaningless and CORS does not apply to them anyway.
+        if self.is_native() {
+            return Ok(None);
+        }
+
         let origin = match extract_external_origin(
             req,
             pub_url_with_scheme,
@@ -1290,9 +1437,15 @@ impl Client {
 
     #[inline]
     pub fn validate_redirect_uri(&self, redirect_uri: &str) -> Result<(), ErrorResponse> {
+        let is_native = self.is_native();
         let has_any = self.get_redirect_uris().iter().any(|uri| {
-            (uri.ends_with('*') && redirect_uri.starts_with(uri.split_once('*').unwrap().0))
-                || uri.as_str().eq(redirect_uri)
+            if is_native {
+                // RFC 8252: exact matches only, apart from the port of loopback redirects
+                uri.as_str().eq(redirect_uri) || native_loopback_matches(uri, redirect_uri)
+            } else {
+                (uri.ends_with('*') && redirect_uri.starts_with(uri.split_once('*').unwrap().0))
+                    || uri.as_str().eq(redirect_uri)
+            }
         });
 
         if has_any {
@@ -1300,7 +1453,8 @@ impl Client {
         } else {
             debug!(
                 "Invalid `redirect_uri`: {} / expected one of: {}",
-                redirect_uri, self.redirect_uris
+                truncate_for_log(redirect_uri, 256),
+                self.redirect_uris
             );
             Err(ErrorResponse::new(
                 ErrorResponseType::BadRequest,
@@ -1309,6 +1463,44 @@ impl Client {
         }
     }
 
+    /// Validates the registered `redirect_uris` of a `native` client. Each one must be either a
+    /// claimed `https` URI, a loopback IP `http` URI, or a private-use URI scheme in reverse
+    /// domain name notation. Wildcards are never allowed.
+    pub fn validate_native_redirect_uris<'a>(
+        uris: impl Iterator<Item = &'a str>,
+    ) -> Result<(), ErrorResponse> {
+        for uri in uris {
+            let err = |reason: &str| {
+                Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    format!("Invalid `redirect_uri` '{uri}' for a native client: {reason}"),
+                ))
+            };
+
+            if uri.contains('*') {
+                return err("wildcards are not allowed");
+            }
+            let Ok(url) = Url::parse(uri) else {
+                return err("not a valid URI");
+            };
+            match url.scheme() {
+                "https" => {}
+                "http" => {
+                    if native_loopback(uri).is_none() {
+                        return err("`http` is only allowed for `127.0.0.1` and `[::1]`");
+                    }
+                }
+                scheme => {
+                    if !scheme.contains('.') {
+                        return err("custom schemes must use reverse domain name notation");
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     #[inline]
     pub fn validate_post_logout_redirect_uri(
         &self,
@@ -1341,6 +1533,14 @@ impl Client {
         code_challenge: &Option<String>,
         code_challenge_method: &Option<String>,
     ) -> Result<(), ErrorResponse> {
+        if self.is_native() && code_challenge_method.as_deref() != Some("S256") {
+            trace!("native client without 'code_challenge_method=S256'");
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "native clients require 'code_challenge_method=S256'",
+            ));
+        }
+
         if let Some(methods_allowed) = &self.challenge {
             if code_challenge.is_none() {
                 trace!("'code_challenge' is missing");
@@ -1447,9 +1647,7 @@ impl Client {
         // make sure this function is updated if the secret length ever changes
         debug_assert_eq!(cleartext.len(), SECRET_LEN_CLIENTS);
 
-        let a = <&[u8; 64]>::try_from(cleartext.as_ref()).unwrap();
-        let b = <&[u8; 64]>::try_from(secret.as_bytes()).unwrap();
-        if constant_time_eq::constant_time_eq_64(a, b)
+        if secure_compare(&*cleartext, &secret)
             || Client::validate_cached_secret(&self.id, &secret)
                 .await
                 .is_ok()
@@ -1461,7 +1659,7 @@ impl Client {
         warn!(
             "Invalid login for client '{}' from '{}'",
             self.id,
-            real_ip_from_req(req)?
+            real_ip(req)?
         );
 
         Err(ErrorResponse::new(
@@ -1470,19 +1668,46 @@ impl Client {
         ))
     }
 
+    /// Returns `true` if any of `restrict_group_prefix`, `allowed_roles` or `allowed_groups`
+    /// is set and the client is not reachable for every user.
     #[inline]
-    pub fn validate_user_groups(&self, user: &User) -> Result<(), ErrorResponse> {
-        if let Some(prefix) = &self.restrict_group_prefix {
-            if user.get_groups().iter().any(|g| g.starts_with(prefix)) {
-                Ok(())
-            } else {
-                Err(ErrorResponse::new(
-                    ErrorResponseType::Forbidden,
-                    "You don't have access to this client, please contact your Administrator",
-                ))
-            }
-        } else {
+    pub fn has_access_restrictions(&self) -> bool {
+        self.restrict_group_prefix.is_some()
+            || self.allowed_roles_iter().next().is_some()
+            || self.allowed_groups_iter().next().is_some()
+    }
+
+    /// Validates that the user may use this client at all. The `restrict_group_prefix` must
+    /// match, if it is set. If any `allowed_roles` or `allowed_groups` are set, the user needs
+    /// at least one of them.
+    pub fn validate_user_access(&self, user: &User) -> Result<(), ErrorResponse> {
+        let err = || {
+            Err(ErrorResponse::new(
+                ErrorResponseType::Forbidden,
+                "You don't have access to this client, please contact your Administrator",
+            ))
+        };
+
+        if let Some(prefix) = &self.restrict_group_prefix
+            && !user.get_groups().iter().any(|g| g.starts_with(prefix))
+        {
+            return err();
+        }
+
+        let mut allowed_roles = self.allowed_roles_iter().peekable();
+        let mut allowed_groups = self.allowed_groups_iter().peekable();
+        if allowed_roles.peek().is_none() && allowed_groups.peek().is_none() {
+            return Ok(());
+        }
+
+        let roles = user.get_roles();
+        let groups = user.get_groups();
+        if allowed_roles.any(|r| roles.iter().any(|ur| ur == r))
+            || allowed_groups.any(|g| groups.iter().any(|ug| ug == g))
+        {
             Ok(())
+        } else {
+            err()
         }
     }
 }
@@ -1534,7 +1759,11 @@ impl Client {
 }
 
 impl Client {
-    pub fn into_response(self, scim: Option<ClientScim>) -> ClientResponse {
+    pub fn into_response(
+        self,
+        scim: Option<ClientScim>,
+        claims_webhook: Option<ClientClaimsWebhook>,
+    ) -> ClientResponse {
         let redirect_uris = self.get_redirect_uris();
         let post_logout_redirect_uris = self.get_post_logout_uris();
         let allowed_origins = self.get_allowed_origins();
@@ -1549,6 +1778,17 @@ impl Client {
             .and_then(|bytes| serde_json::from_slice(bytes).ok());
         let allowed_resources = self.get_allowed_resources();
         let default_aud = self.get_default_aud();
+        let allowed_roles = self.get_allowed_roles();
+        let allowed_groups = self.get_allowed_groups();
+        let response_types = self.response_types_iter().map(String::from).collect();
+        let claims_emission = self
+            .claims_emission
+            .as_deref()
+            .and_then(|bytes| serde_json::from_slice(bytes).ok());
+        let refresh_token_binding = self
+            .refresh_token_binding
+            .as_deref()
+            .and_then(|bytes| serde_json::from_slice(bytes).ok());
 
         let access_token_alg = JwkKeyPairAlg::from_str(&self.access_token_alg)
             .expect("internal JwkKeyPairAlg conversion to always succeed")
@@ -1582,12 +1822,19 @@ impl Client {
             claims_at_root: self.claims_at_root,
             allowed_resources,
             default_aud,
+            allowed_roles,
+            allowed_groups,
+            response_types,
+            claims_emission,
+            refresh_token_binding,
+            client_type: ClientType::from(self.client_type.as_str()),
             scim: scim.map(|scim| ScimClientRequestResponse {
                 bearer_token: scim.bearer_token,
                 base_uri: scim.base_uri,
                 sync_groups: scim.sync_groups,
                 group_sync_prefix: scim.group_sync_prefix,
             }),
+            claims_webhook: claims_webhook.map(ClaimsWebhookResponse::from),
         }
     }
 }
@@ -1637,6 +1884,12 @@ impl From<EphemeralClientRequest> for Client {
             claims_at_root: false,
             allowed_resources: value.allowed_resources.map(|r| r.join(",")),
             default_aud: None,
+            claims_emission: None,
+            refresh_token_binding: None,
+            client_type: ClientType::Web.as_str().to_string(),
+            allowed_roles: None,
+            allowed_groups: None,
+            response_types: RESPONSE_TYPES_SUPPORTED.join(","),
         }
     }
 }
@@ -1680,6 +1933,12 @@ impl Default for Client {
             claims_at_root: false,
             allowed_resources: None,
             default_aud: None,
+            claims_emission: None,
+            refresh_token_binding: None,
+            client_type: ClientType::Web.as_str().to_string(),
+            allowed_roles: None,
+            allowed_groups: None,
+            response_types: RESPONSE_TYPES_SUPPORTED.join(","),
         }
     }
 }
@@ -1712,6 +1971,33 @@ impl TryFrom<NewClientRequest> for Client {
                 None
             };
 
+        let client_type = client.client_type.unwrap_or_default();
+        if client_type == ClientType::Native {
+            if client.confidential {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    "native clients must be public",
+                ));
+            }
+            Self::validate_native_redirect_uris(
+                redirect_uris.split(',').filter(|u| !u.is_empty()),
+            )?;
+
+            // Native apps usually want to stay logged in, and the device flow is the only option
+            // for CLI apps on headless machines. Refresh tokens are always rotated on use.
+            return Ok(Self {
+                id: client.id,
+                name: client.name,
+                confidential: false,
+                redirect_uris,
+                post_logout_redirect_uris,
+                flows_enabled: format!("authorization_code,refresh_token,{GRANT_TYPE_DEVICE_CODE}"),
+                challenge: Some("S256".to_string()),
+                client_type: client_type.as_str().to_string(),
+                ..Default::default()
+            });
+        }
+
         Ok(Self {
             id: client.id,
             secret: client.secret,
@@ -1856,6 +2142,37 @@ impl Client {
     }
 }
 
+/// Returns the parsed URL if `uri` is a loopback IP redirect URI in the sense of RFC 8252.
+/// `localhost` is not accepted, because it may resolve to something else than the loopback.
+#[inline]
+fn native_loopback(uri: &str) -> Option<Url> {
+    let url = Url::parse(uri).ok()?;
+    if url.scheme() != "http" {
+        return None;
+    }
+    match url.host_str()? {
+        "127.0.0.1" | "[::1]" => Some(url),
+        _ => None,
+    }
+}
+
+/// RFC 8252 - Section 7.3: The port of a loopback redirect URI is chosen by the native app at
+/// runtime, which means any port must be accepted. Everything else must match exactly.
+fn native_loopback_matches(registered: &str, requested: &str) -> bool {
+    let (Some(registered), Some(requested)) =
+        (native_loopback(registered), native_loopback(requested))
+    else {
+        return false;
+    };
+    registered.host_str() == requested.host_str()
+        && registered.username() == requested.username()
+        && registered.password().is_none()
+        && requested.password().is_none()
+        && registered.path() == requested.path()
+        && registered.query() == requested.query()
+        && requested.fragment().is_none()
+}
+
 #[inline]
 fn extract_external_origin<'a>(
     req: &'a HttpRequest,
@@ -1929,6 +2246,12 @@ mod tests {
             claims_at_root: false,
             allowed_resources: None,
             default_aud: None,
+            claims_emission: None,
+            refresh_token_binding: None,
+            client_type: ClientType::Web.as_str().to_string(),
+            allowed_roles: None,
+            allowed_groups: None,
+            response_types: RESPONSE_TYPES_SUPPORTED.join(","),
         };
 
         assert_eq!(client.get_access_token_alg().unwrap(), JwkKeyPairAlg::EdDSA);
@@ -2097,6 +2420,175 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_native_redirect_uris() {
+        let valid = [
+            "com.example.app:/callback",
+            "com.example.app:/oauth2redirect/example-provider",
+            "http://127.0.0.1/callback",
+            "http://127.0.0.1:8080/callback",
+            "http://[::1]/callback",
+            "https://app.example.com/callback",
+        ];
+        for uri in valid {
+            assert!(
+                Client::validate_native_redirect_uris([uri].into_iter()).is_ok(),
+                "{uri}"
+            );
+        }
+
+        let invalid = [
+            // no wildcards at all
+            "com.example.app:/*",
+            "http://127.0.0.1:*/callback",
+            "https://app.example.com/*",
+            // not in reverse domain name notation
+            "myapp:/callback",
+            // plain http for non-loopback hosts
+            "http://localhost/callback",
+            "http://app.example.com/callback",
+            "http://127.0.0.2/callback",
+            "not a uri",
+        ];
+        for uri in invalid {
+            assert!(
+                Client::validate_native_redirect_uris([uri].into_iter()).is_err(),
+                "{uri}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_native_redirect_uri_matching() {
+        let mut client = Client {
+            redirect_uris: "com.example.app:/callback,http://127.0.0.1/callback,\
+                http://[::1]:3000/cb?x=1"
+                .to_string(),
+            client_type: ClientType::Native.as_str().to_string(),
+            ..Default::default()
+        };
+
+        // custom schemes must match exactly
+        assert!(
+            client
+                .validate_redirect_uri("com.example.app:/callback")
+                .is_ok()
+        );
+        assert!(
+            client
+                .validate_redirect_uri("com.example.app:/callback2")
+                .is_err()
+        );
+        assert!(
+            client
+                .validate_redirect_uri("com.example.ap:/callback")
+                .is_err()
+        );
+        assert!(
+            client
+                .validate_redirect_uri("com.example.app.evil:/callback")
+                .is_err()
+        );
+        assert!(
+            client
+                .validate_redirect_uri("Com.example.app:/callback")
+                .is_err()
+        );
+
+        // loopback accepts any port, but nothing else may differ
+        assert!(
+            client
+                .validate_redirect_uri("http://127.0.0.1/callback")
+                .is_ok()
+        );
+        assert!(
+            client
+                .validate_redirect_uri("http://127.0.0.1:51234/callback")
+                .is_ok()
+        );
+        assert!(
+            client
+                .validate_redirect_uri("http://127.0.0.1:8080/callback")
+                .is_ok()
+        );
+        assert!(
+            client
+                .validate_redirect_uri("http://127.0.0.1:8080/other")
+                .is_err()
+        );
+        assert!(
+            client
+                .validate_redirect_uri("http://127.0.0.1:8080/callback?a=b")
+                .is_err()
+        );
+        assert!(
+            client
+                .validate_redirect_uri("http://127.0.0.1:8080/callback#a")
+                .is_err()
+        );
+        assert!(
+            client
+                .validate_redirect_uri("https://127.0.0.1:8080/callback")
+                .is_err()
+        );
+        assert!(
+            client
+                .validate_redirect_uri("http://localhost:8080/callback")
+                .is_err()
+        );
+        assert!(
+            client
+                .validate_redirect_uri("http://[::1]:8080/callback")
+                .is_err()
+        );
+        assert!(
+            client
+                .validate_redirect_uri("http://[::1]:8080/cb?x=1")
+                .is_ok()
+        );
+        assert!(client.validate_redirect_uri("http://[::1]/cb?x=1").is_ok());
+        assert!(
+            client
+                .validate_redirect_uri("http://127.0.0.1:8080/cb?x=1")
+                .is_err()
+        );
+
+        // the loopback port wildcard only applies to native clients
+        client.client_type = ClientType::Web.as_str().to_string();
+        assert!(
+            client
+                .validate_redirect_uri("http://127.0.0.1/callback")
+                .is_ok()
+        );
+        assert!(
+            client
+                .validate_redirect_uri("http://127.0.0.1:51234/callback")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_native_code_challenge() {
+        let client = Client {
+            client_type: ClientType::Native.as_str().to_string(),
+            challenge: Some("S256".to_string()),
+            ..Default::default()
+        };
+        let challenge = Some("Ew2cFtNlrWxmnRMfw9BeqsuWN9BvTlXqUeKnQ6pF7TO".to_string());
+
+        assert!(
+            client
+                .validate_code_challenge(&challenge, &Some("S256".to_string()))
+                .is_ok()
+        );
+        assert!(
+            client
+                .validate_code_challenge(&challenge, &Some("plain".to_string()))
+                .is_err()
+        );
+        assert!(client.validate_code_challenge(&None, &None).is_err());
+    }
+
     // TODO: Currently out-commented because of issues with static RauthyConfig init missing
     //  in unit tests. Should be added into integration tests.
     // #[tokio::test]
@@ -2154,6 +2646,47 @@ mod tests {
     //     })
     // }
 
+    #[test]
+    fn test_validate_user_access() {
+        let user = User {
+            roles: "user,editor".to_string(),
+            groups: Some("dev,ops".to_string()),
+            ..Default::default()
+        };
+
+        let mut client = Client::default();
+        assert!(!client.has_access_restrictions());
+        assert!(client.validate_user_access(&user).is_ok());
+
+        // empty values are unrestricted as well
+        client.allowed_roles = Some(String::default());
+        assert!(!client.has_access_restrictions());
+        assert!(client.validate_user_access(&user).is_ok());
+
+        client.allowed_roles = Some("admin".to_string());
+        assert!(client.has_access_restrictions());
+        assert!(client.validate_user_access(&user).is_err());
+
+        client.allowed_roles = Some("admin,editor".to_string());
+        assert!(client.validate_user_access(&user).is_ok());
+
+        // either a role or a group is enough
+        client.allowed_roles = Some("admin".to_string());
+        client.allowed_groups = Some("ops".to_string());
+        assert!(client.validate_user_access(&user).is_ok());
+
+        client.allowed_roles = None;
+        client.allowed_groups = Some("qa".to_string());
+        assert!(client.validate_user_access(&user).is_err());
+
+        // the group prefix must always match on top
+        client.allowed_groups = Some("dev".to_string());
+        client.restrict_group_prefix = Some("ops".to_string());
+        assert!(client.validate_user_access(&user).is_ok());
+        client.restrict_group_prefix = Some("sec".to_string());
+        assert!(client.validate_user_access(&user).is_err());
+    }
+
     #[test]
     fn test_delete_client_custom_scope() {
         let mut client = Client::default();
@@ -0,0 +1,319 @@
+use rauthy_api_types::clients::{ClaimGroupsFormat, ClaimTarget, ClientClaimsEmission};
+use std::collections::HashMap;
+
+pub const CLAIM_ROLES: &str = "roles";
+pub const CLAIM_GROUPS: &str = "groups";
+pub const CLAIM_GROUPS_OVERFLOW: &str = "groups_overflow";
+
+/// The `roles` and `groups` of a user, shaped by a client's `ClientClaimsEmission` for a
+/// single target.
+#[derive(Debug, Default, PartialEq)]
+pub struct EmittedRolesGroups {
+    /// Values for the default `roles` claim.
+    pub roles: Option<Vec<String>>,
+    /// Values for the default `groups` claim.
+    pub groups: Option<Vec<String>>,
+    /// Custom named claims and the `groups_overflow` marker, which must be emitted at the
+    /// token root.
+    pub flattened: HashMap<String, serde_json::Value>,
+    /// The length of the groups list, if it exceeded `groups_max`.
+    pub groups_exceeded: Option<usize>,
+}
+
+impl EmittedRolesGroups {
+    /// Builds the claims for the given `target`.
+    ///
+    /// `groups` must be `None`, if the `groups` scope has not been granted. In that case,
+    /// neither the groups claim nor merged roles will be emitted.
+    pub fn build<'a>(
+        emission: &ClientClaimsEmission,
+        target: ClaimTarget,
+        roles: impl Iterator<Item = &'a str>,
+        groups: Option<impl Iterator<Item = &'a str>>,
+    ) -> Self {
+        let mut slf = Self::default();
+        let roles = roles.map(String::from).collect::<Vec<_>>();
+
+        if let Some(groups) = groups
+            && emission.groups_targets.contains(&target)
+        {
+            let mut values = match emission.groups_format {
+                ClaimGroupsFormat::Flat => groups.map(String::from).collect::<Vec<_>>(),
+                ClaimGroupsFormat::Path => groups
+                    .map(|g| {
+                        if g.starts_with('/') {
+                            g.to_string()
+                        } else {
+                            format!("/{g}")
+                        }
+                    })
+                    .collect::<Vec<_>>(),
+            };
+            if emission.roles_in_groups {
+                for role in &roles {
+                    if !values.contains(role) {
+                        values.push(role.clone());
+                    }
+                }
+            }
+
+            let exceeded = emission
+                .groups_max
+                .map(|max| values.len() > max as usize)
+                .unwrap_or(false);
+            if exceeded {
+                slf.groups_exceeded = Some(values.len());
+            }
+
+            if exceeded && emission.groups_overflow {
+                slf.flattened.insert(
+                    CLAIM_GROUPS_OVERFLOW.to_string(),
+                    serde_json::Value::Bool(true),
+                );
+            } else {
+                match emission.groups_claim.as_deref() {
+                    None | Some(CLAIM_GROUPS) => slf.groups = Some(values),
+                    Some(name) => {
+                        slf.flattened.insert(name.to_string(), values.into());
+                    }
+                }
+            }
+        }
+
+        if emission.roles_targets.contains(&target) {
+            match emission.roles_claim.as_deref() {
+                None | Some(CLAIM_ROLES) => slf.roles = Some(roles),
+                Some(name) => {
+                    slf.flattened.insert(name.to_string(), roles.into());
+                }
+            }
+        }
+
+        slf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    const ROLES: [&str; 2] = ["admin", "user"];
+    const GROUPS: [&str; 3] = ["engineering", "engineering/platform", "/sales"];
+
+    fn build(
+        emission: &ClientClaimsEmission,
+        target: ClaimTarget,
+        with_groups: bool,
+    ) -> EmittedRolesGroups {
+        EmittedRolesGroups::build(
+            emission,
+            target,
+            ROLES.into_iter(),
+            with_groups.then(|| GROUPS.into_iter()),
+        )
+    }
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn test_default_emission() {
+        let emission = ClientClaimsEmission::default();
+
+        for target in ClaimTarget::all() {
+            let emitted = build(&emission, target, true);
+            assert_eq!(emitted.roles, Some(strings(&ROLES)));
+            assert_eq!(emitted.groups, Some(strings(&GROUPS)));
+            assert!(emitted.flattened.is_empty());
+            assert_eq!(emitted.groups_exceeded, None);
+
+            // without the `groups` scope
+            let emitted = build(&emission, target, false);
+            assert_eq!(emitted.roles, Some(strings(&ROLES)));
+            assert_eq!(emitted.groups, None);
+            assert!(emitted.flattened.is_empty());
+        }
+
+        // explicitly set default names must behave the same
+        let emission = ClientClaimsEmission {
+            roles_claim: Some(CLAIM_ROLES.to_string()),
+            groups_claim: Some(CLAIM_GROUPS.to_string()),
+            ..Default::default()
+        };
+        let emitted = build(&emission, ClaimTarget::IdToken, true);
+        assert_eq!(emitted.roles, Some(strings(&ROLES)));
+        assert_eq!(emitted.groups, Some(strings(&GROUPS)));
+        assert!(emitted.flattened.is_empty());
+    }
+
+    #[test]
+    fn test_custom_claim_names() {
+        let emission = ClientClaimsEmission {
+            roles_claim: Some("app_roles".to_string()),
+            groups_claim: Some("https://example.com/groups".to_string()),
+            ..Default::default()
+        };
+
+        for target in ClaimTarget::all() {
+            let emitted = build(&emission, target, true);
+            assert_eq!(emitted.roles, None);
+            assert_eq!(emitted.groups, None);
+            assert_eq!(emitted.flattened.len(), 2);
+            assert_eq!(emitted.flattened.get("app_roles"), Some(&json!(ROLES)));
+            assert_eq!(
+                emitted.flattened.get("https://example.com/groups"),
+                Some(&json!(GROUPS))
+            );
+
+            let emitted = build(&emission, target, false);
+            assert_eq!(emitted.flattened.len(), 1);
+            assert!(emitted.flattened.contains_key("app_roles"));
+        }
+    }
+
+    #[test]
+    fn test_groups_format_path() {
+        let emission = ClientClaimsEmission {
+            groups_format: ClaimGroupsFormat::Path,
+            ..Default::default()
+        };
+
+        let emitted = build(&emission, ClaimTarget::AccessToken, true);
+        assert_eq!(
+            emitted.groups,
+            Some(strings(&[
+                "/engineering",
+                "/engineering/platform",
+                "/sales"
+            ]))
+        );
+        // roles are never formatted
+        assert_eq!(emitted.roles, Some(strings(&ROLES)));
+    }
+
+    #[test]
+    fn test_roles_in_groups() {
+        let emission = ClientClaimsEmission {
+            roles_in_groups: true,
+            ..Default::default()
+        };
+
+        let emitted = build(&emission, ClaimTarget::Userinfo, true);
+        assert_eq!(
+            emitted.groups,
+            Some(strings(&[
+                "engineering",
+                "engineering/platform",
+                "/sales",
+                "admin",
+                "user"
+            ]))
+        );
+        assert_eq!(emitted.roles, Some(strings(&ROLES)));
+
+        // the `groups` scope is still required
+        let emitted = build(&emission, ClaimTarget::Userinfo, false);
+        assert_eq!(emitted.groups, None);
+
+        // roles may be emitted inside the groups claim only
+        let emission = ClientClaimsEmission {
+            roles_in_groups: true,
+            roles_targets: Vec::default(),
+            ..Default::default()
+        };
+        let emitted = build(&emission, ClaimTarget::Userinfo, true);
+        assert_eq!(emitted.roles, None);
+        assert_eq!(emitted.groups.unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_targets() {
+        let emission = ClientClaimsEmission {
+            roles_targets: vec![ClaimTarget::AccessToken],
+            groups_targets: vec![ClaimTarget::IdToken, ClaimTarget::Userinfo],
+            ..Default::default()
+        };
+
+        let emitted = build(&emission, ClaimTarget::AccessToken, true);
+        assert_eq!(emitted.roles, Some(strings(&ROLES)));
+        assert_eq!(emitted.groups, None);
+
+        let emitted = build(&emission, ClaimTarget::IdToken, true);
+        assert_eq!(emitted.roles, None);
+        assert_eq!(emitted.groups, Some(strings(&GROUPS)));
+
+        let emitted = build(&emission, ClaimTarget::Userinfo, true);
+        assert_eq!(emitted.roles, None);
+        assert_eq!(emitted.groups, Some(strings(&GROUPS)));
+
+        let emission = ClientClaimsEmission {
+            roles_targets: Vec::default(),
+            groups_targets: Vec::default(),
+            ..Default::default()
+        };
+        for target in ClaimTarget::all() {
+            assert_eq!(
+                build(&emission, target, true),
+                EmittedRolesGroups::default()
+            );
+        }
+    }
+
+    #[test]
+    fn test_groups_max() {
+        // exactly at the limit
+        let emission = ClientClaimsEmission {
+            groups_max: Some(3),
+            groups_overflow: true,
+            ..Default::default()
+        };
+        let emitted = build(&emission, ClaimTarget::IdToken, true);
+        assert_eq!(emitted.groups, Some(strings(&GROUPS)));
+        assert_eq!(emitted.groups_exceeded, None);
+        assert!(emitted.flattened.is_empty());
+
+        // warning only
+        let emission = ClientClaimsEmission {
+            groups_max: Some(2),
+            groups_overflow: false,
+            ..Default::default()
+        };
+        let emitted = build(&emission, ClaimTarget::IdToken, true);
+        assert_eq!(emitted.groups, Some(strings(&GROUPS)));
+        assert_eq!(emitted.groups_exceeded, Some(3));
+        assert!(emitted.flattened.is_empty());
+
+        // overflow replaces the list
+        let emission = ClientClaimsEmission {
+            groups_claim: Some("memberOf".to_string()),
+            groups_max: Some(2),
+            groups_overflow: true,
+            ..Default::default()
+        };
+        let emitted = build(&emission, ClaimTarget::AccessToken, true);
+        assert_eq!(emitted.groups, None);
+        assert_eq!(emitted.groups_exceeded, Some(3));
+        assert_eq!(
+            emitted.flattened.get(CLAIM_GROUPS_OVERFLOW),
+            Some(&json!(true))
+        );
+        assert!(!emitted.flattened.contains_key("memberOf"));
+        assert_eq!(emitted.roles, Some(strings(&ROLES)));
+
+        // merged roles count towards the limit
+        let emission = ClientClaimsEmission {
+            roles_in_groups: true,
+            groups_max: Some(4),
+            ..Default::default()
+        };
+        let emitted = build(&emission, ClaimTarget::Userinfo, true);
+        assert_eq!(emitted.groups_exceeded, Some(5));
+
+        // no groups scope -> no check
+        let emitted = build(&emission, ClaimTarget::Userinfo, false);
+        assert_eq!(emitted.groups_exceeded, None);
+    }
+}
@@ -0,0 +1,592 @@
+use crate::database::{Cache, DB};
+use crate::entity::users::User;
+use chrono::Utc;
+use cryptr::EncValue;
+use hiqlite::macros::params;
+use rauthy_api_types::clients::{ClaimsWebhookRequest, ClaimsWebhookResponse};
+use rauthy_common::constants::{CACHE_TTL_APP, IDX_CLAIMS_WEBHOOK, IDX_CLIENT_CLAIMS_WEBHOOK};
+use rauthy_common::utils::{get_rand, secure_compare};
+use rauthy_common::{http_client, is_hiqlite};
+use rauthy_derive::FromPgRow;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use reqwest::header::CONTENT_TYPE;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+pub const HEADER_WEBHOOK_ID: &str = "rauthy-webhook-id";
+pub const HEADER_WEBHOOK_TIMESTAMP: &str = "rauthy-webhook-timestamp";
+pub const HEADER_WEBHOOK_SIGNATURE: &str = "rauthy-webhook-signature";
+
+/// Larger responses are rejected. The webhook should only ever return a few claims.
+const RESPONSE_MAX_BYTES: usize = 64 * 1024;
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, FromPgRow)]
+pub struct ClientClaimsWebhook {
+    pub client_id: String,
+    pub uri: String,
+    /// The encrypted HMAC secret
+    pub secret: Vec<u8>,
+    pub timeout_ms: i32,
+    pub cache_ttl: i32,
+    /// CSV of the claim names the webhook may set
+    pub allowed_claims: String,
+    pub fail_open: bool,
+}
+
+impl Debug for ClientClaimsWebhook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "ClientClaimsWebhook {{ client_id: {}, uri: {}, secret: <hidden>, timeout_ms: {}, \
+            cache_ttl: {}, allowed_claims: {}, fail_open: {} }}",
+            self.client_id,
+            self.uri,
+            self.timeout_ms,
+            self.cache_ttl,
+            self.allowed_claims,
+            self.fail_open
+        )
+    }
+}
+
+impl From<ClientClaimsWebhook> for ClaimsWebhookResponse {
+    fn from(value: ClientClaimsWebhook) -> Self {
+        Self {
+            allowed_claims: value.allowed_claims_iter().map(String::from).collect(),
+            uri: value.uri,
+            timeout_ms: value.timeout_ms as u16,
+            cache_ttl: value.cache_ttl as u16,
+            fail_open: value.fail_open,
+        }
+    }
+}
+
+impl ClientClaimsWebhook {
+    #[inline(always)]
+    fn cache_idx(client_id: &str) -> String {
+        format!("{IDX_CLIENT_CLAIMS_WEBHOOK}{client_id}")
+    }
+
+    /// The `secret` may only be `None`, if a webhook exists already. The current secret will
+    /// be kept in that case.
+    pub async fn upsert(
+        client_id: String,
+        req: ClaimsWebhookRequest,
+    ) -> Result<Self, ErrorResponse> {
+        let secret = match req.secret {
+            Some(secret) => EncValue::encrypt(secret.as_bytes())?.into_bytes().to_vec(),
+            None => match Self::find_opt(&client_id).await? {
+                Some(current) => current.secret,
+                None => {
+                    return Err(ErrorResponse::new(
+                        ErrorResponseType::BadRequest,
+                        "A `secret` is required for a new claims webhook",
+                    ));
+                }
+            },
+        };
+
+        let slf = Self {
+            client_id,
+            uri: req.uri,
+            secret,
+            timeout_ms: req.timeout_ms as i32,
+            cache_ttl: req.cache_ttl as i32,
+            allowed_claims: req.allowed_claims.join(","),
+            fail_open: req.fail_open,
+        };
+        slf.save().await?;
+
+        Ok(slf)
+    }
+
+    async fn save(&self) -> Result<(), ErrorResponse> {
+        let sql = r#"
+INSERT INTO clients_claims_webhook
+(client_id, uri, secret, timeout_ms, cache_ttl, allowed_claims, fail_open)
+VALUES ($1, $2, $3, $4, $5, $6, $7)
+ON CONFLICT (client_id) DO UPDATE
+SET uri = $2, secret = $3, timeout_ms = $4, cache_ttl = $5, allowed_claims = $6,
+    fail_open = $7"#;
+
+        if is_hiqlite() {
+            DB::hql()
+                .execute(
+                    sql,
+                    params!(
+                        self.client_id.clone(),
+                        self.uri.clone(),
+                        self.secret.clone(),
+                        self.timeout_ms,
+                        self.cache_ttl,
+                        self.allowed_claims.clone(),
+                        self.fail_open
+                    ),
+                )
+                .await?;
+        } else {
+            DB::pg_execute(
+                sql,
+                &[
+                    &self.client_id,
+                    &self.uri,
+                    &self.secret,
+                    &self.timeout_ms,
+                    &self.cache_ttl,
+                    &self.allowed_claims,
+                    &self.fail_open,
+                ],
+            )
+            .await?;
+        }
+
+        DB::hql()
+            .put(
+                Cache::App,
+                Self::cache_idx(&self.client_id),
+                &Some(self),
+                CACHE_TTL_APP,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// This will be called during each token issuance. Non-existing webhooks are cached as well.
+    pub async fn find_opt(client_id: &str) -> Result<Option<Self>, ErrorResponse> {
+        let idx = Self::cache_idx(client_id);
+        if let Some(slf) = DB::hql().get(Cache::App, idx.clone()).await? {
+            return Ok(slf);
+        }
+
+        let sql = "SELECT * FROM clients_claims_webhook WHERE client_id = $1";
+        let slf: Option<Self> = if is_hiqlite() {
+            DB::hql()
+                .query_as_optional(sql, params!(client_id.to_string()))
+                .await?
+        } else {
+            DB::pg_query_opt(sql, &[&client_id]).await?
+        };
+
+        DB::hql().put(Cache::App, idx, &slf, CACHE_TTL_APP).await?;
+
+        Ok(slf)
+    }
+
+    pub async fn find_all() -> Result<Vec<Self>, ErrorResponse> {
+        let sql = "SELECT * FROM clients_claims_webhook";
+        let res = if is_hiqlite() {
+            DB::hql().query_as(sql, params!()).await?
+        } else {
+            DB::pg_query(sql, &[], 0).await?
+        };
+        Ok(res)
+    }
+
+    pub async fn delete(client_id: String) -> Result<(), ErrorResponse> {
+        let sql = "DELETE FROM clients_claims_webhook WHERE client_id = $1";
+        if is_hiqlite() {
+            DB::hql().execute(sql, params!(client_id.clone())).await?;
+        } else {
+            DB::pg_execute(sql, &[&client_id]).await?;
+        }
+
+        DB::hql()
+            .delete(Cache::App, Self::cache_idx(&client_id))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Re-encrypts the secret with the given key.
+    pub async fn re_encrypt(mut self, new_kid: &str) -> Result<(), ErrorResponse> {
+        let dec = EncValue::try_from(self.secret)?.decrypt()?;
+        self.secret = EncValue::encrypt_with_key_id(dec.as_ref(), new_kid.to_string())?
+            .into_bytes()
+            .to_vec();
+        self.save().await
+    }
+}
+
+impl ClientClaimsWebhook {
+    #[inline]
+    pub fn allowed_claims_iter(&self) -> impl Iterator<Item = &str> {
+        self.allowed_claims.split(',').filter(|c| !c.is_empty())
+    }
+
+    /// Fetches the claims for the given user from the webhook. Results are cached for
+    /// `cache_ttl` seconds per user, client and scope.
+    ///
+    /// Returns `Ok(None)`, if the webhook failed and the client is configured as `fail_open`.
+    pub async fn fetch(
+        &self,
+        user: &User,
+        scope: &str,
+    ) -> Result<Option<WebhookClaims>, ErrorResponse> {
+        let idx = format!(
+            "{IDX_CLAIMS_WEBHOOK}{}_{}_{}",
+            self.client_id,
+            user.id,
+            hex::encode(hmac_sha256::Hash::hash(scope.as_bytes()))
+        );
+        if self.cache_ttl > 0
+            && let Some(claims) = DB::hql().get(Cache::App, idx.clone()).await?
+        {
+            return Ok(Some(claims));
+        }
+
+        let payload = WebhookPayload {
+            user_id: &user.id,
+            email: &user.email,
+            client_id: &self.client_id,
+            scopes: scope.split(' ').filter(|s| !s.is_empty()).collect(),
+        };
+        let secret = EncValue::try_from(self.secret.clone())?.decrypt()?;
+
+        let res = call_webhook(
+            http_client(),
+            &self.uri,
+            secret.as_ref(),
+            Duration::from_millis(self.timeout_ms as u64),
+            serde_json::to_vec(&payload)?,
+        )
+        .await;
+
+        match res {
+            Ok(claims) => {
+                let claims = claims.filter_allowed(self.allowed_claims_iter());
+                if self.cache_ttl > 0 {
+                    DB::hql()
+                        .put(Cache::App, idx, &claims, Some(self.cache_ttl as i64))
+                        .await?;
+                }
+                Ok(Some(claims))
+            }
+            Err(err) if self.fail_open => {
+                warn!(
+                    client_id = self.client_id,
+                    ?err,
+                    "Claims webhook failed, issuing tokens with local data only"
+                );
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct WebhookPayload<'a> {
+    user_id: &'a str,
+    email: &'a str,
+    client_id: &'a str,
+    scopes: Vec<&'a str>,
+}
+
+/// The claims returned by a claims webhook.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebhookClaims {
+    /// Will be merged into the user's roles
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub roles: Option<Vec<String>>,
+    /// Will be merged into the user's groups
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub groups: Option<Vec<String>>,
+    /// Custom claims, which will be set at the token root
+    #[serde(default)]
+    pub claims: HashMap<String, serde_json::Value>,
+}
+
+impl WebhookClaims {
+    /// Drops everything the webhook is not allowed to set.
+    fn filter_allowed<'a>(mut self, allowed: impl Iterator<Item = &'a str>) -> Self {
+        let allowed = allowed.collect::<Vec<_>>();
+        if !allowed.contains(&"roles") {
+            self.roles = None;
+        }
+        if !allowed.contains(&"groups") {
+            self.groups = None;
+        }
+        self.claims.retain(|name, _| {
+            name != "roles" && name != "groups" && allowed.contains(&name.as_str())
+        });
+        self
+    }
+}
+
+/// Merges the `remote` values into the `local` ones without duplicates.
+pub fn merge_values<'a>(
+    local: impl Iterator<Item = &'a str>,
+    remote: Option<&'a [String]>,
+) -> Vec<&'a str> {
+    let mut res = local.collect::<Vec<_>>();
+    for value in remote.unwrap_or_default() {
+        if !res.contains(&value.as_str()) {
+            res.push(value.as_str());
+        }
+    }
+    res
+}
+
+/// The signature over the request is built from `{id}.{timestamp}.{body}`, the one over the
+/// response from `{id}.{body}`, which binds the response to the request.
+fn signature(secret: &[u8], parts: &[&[u8]]) -> String {
+    let mut mac = hmac_sha256::HMAC::new(secret);
+    for (i, part) in parts.iter().enumerate() {
+        if i > 0 {
+            mac.update(b".");
+        }
+        mac.update(part);
+    }
+    format!("v1={}", hex::encode(mac.finalize()))
+}
+
+/// Sends the signed `body` to the webhook and validates the response signature.
+/// The `timeout` is the budget for the whole request, including the response body.
+async fn call_webhook(
+    client: &reqwest::Client,
+    uri: &str,
+    secret: &[u8],
+    timeout: Duration,
+    body: Vec<u8>,
+) -> Result<WebhookClaims, ErrorResponse> {
+    let id = get_rand(24);
+    let ts = Utc::now().timestamp().to_string();
+    let sig = signature(secret, &[id.as_bytes(), ts.as_bytes(), &body]);
+
+    let req = async {
+        let resp = client
+            .post(uri)
+            .header(CONTENT_TYPE, "application/json")
+            .header(HEADER_WEBHOOK_ID, &id)
+            .header(HEADER_WEBHOOK_TIMESTAMP, &ts)
+            .header(HEADER_WEBHOOK_SIGNATURE, sig)
+            .body(body)
+            .send()
+            .await
+            .map_err(|err| {
+                ErrorResponse::new(
+                    ErrorResponseType::Connection,
+                    format!("Claims webhook request failed: {err}"),
+                )
+            })?;
+
+        if !resp.status().is_success() {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Connection,
+                format!("Claims webhook returned status {}", resp.status()),
+            ));
+        }
+        if resp.content_length().unwrap_or_default() > RESPONSE_MAX_BYTES as u64 {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Claims webhook response is too large",
+            ));
+        }
+
+        let resp_sig = resp
+            .headers()
+            .get(HEADER_WEBHOOK_SIGNATURE)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from)
+            .unwrap_or_default();
+        let bytes = resp.bytes().await.map_err(|err| {
+            ErrorResponse::new(
+                ErrorResponseType::Connection,
+                format!("Reading the claims webhook response: {err}"),
+            )
+        })?;
+        if bytes.len() > RESPONSE_MAX_BYTES {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Claims webhook response is too large",
+            ));
+        }
+
+        if !secure_compare(signature(secret, &[id.as_bytes(), &bytes]), resp_sig) {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Forbidden,
+                "Invalid claims webhook response signature",
+            ));
+        }
+
+        Ok(serde_json::from_slice::<WebhookClaims>(&bytes)?)
+    };
+
+    match tokio::time::timeout(timeout, req).await {
+        Ok(res) => res,
+        Err(_) => {
+            debug!(uri, ?timeout, "Claims webhook timed out");
+            Err(ErrorResponse::new(
+                ErrorResponseType::Timeout,
+                "Claims webhook timed out",
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    const SECRET: &[u8] = b"QpV5mbbxwG2LDDBTJkQ2NiTxVx2rFhw8";
+
+    /// Accepts a single request, validates its signature and answers with `body` after `delay`.
+    /// The response is signed with `resp_secret`.
+    async fn mock_webhook(
+        body: &'static str,
+        delay: Duration,
+        resp_secret: &'static [u8],
+    ) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+
+            let mut req = Vec::new();
+            let mut buf = [0u8; 4096];
+            let (head, req_body) = loop {
+                let n = stream.read(&mut buf).await.unwrap();
+                req.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&req).to_string();
+                if let Some((head, req_body)) = text.split_once("\r\n\r\n") {
+                    let len = head
+                        .lines()
+                        .find_map(|l| {
+                            l.to_lowercase()
+                                .strip_prefix("content-length: ")
+                                .map(String::from)
+                        })
+                        .map(|l| l.parse::<usize>().unwrap())
+                        .unwrap_or_default();
+                    if req_body.len() >= len || n == 0 {
+                        break (head.to_string(), req_body.to_string());
+                    }
+                }
+            };
+            // only the header names are case-insensitive, the random id is not
+            let header = |name: &str| {
+                head.lines()
+                    .find_map(|l| {
+                        let (key, value) = l.split_once(": ")?;
+                        key.eq_ignore_ascii_case(name).then(|| value.to_string())
+                    })
+                    .unwrap()
+            };
+            let id = header(HEADER_WEBHOOK_ID);
+            let ts = header(HEADER_WEBHOOK_TIMESTAMP);
+            let expected = signature(SECRET, &[id.as_bytes(), ts.as_bytes(), req_body.as_bytes()]);
+            assert_eq!(header(HEADER_WEBHOOK_SIGNATURE), expected);
+
+            let payload = serde_json::from_str::<serde_json::Value>(&req_body).unwrap();
+            assert_eq!(payload["user_id"], "user123");
+            assert_eq!(payload["scopes"], json!(["openid", "groups"]));
+
+            tokio::time::sleep(delay).await;
+            let sig = signature(resp_secret, &[id.as_bytes(), body.as_bytes()]);
+            let resp = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\
+                {HEADER_WEBHOOK_SIGNATURE}: {sig}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            let _ = stream.write_all(resp.as_bytes()).await;
+        });
+
+        format!("http://{addr}/claims")
+    }
+
+    fn payload() -> Vec<u8> {
+        serde_json::to_vec(&WebhookPayload {
+            user_id: "user123",
+            email: "user@example.com",
+            client_id: "client123",
+            scopes: vec!["openid", "groups"],
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_webhook_merge() {
+        let body = r#"{
+            "roles": ["admin", "billing"],
+            "groups": ["ops"],
+            "claims": { "tenant": "t1", "plan": "pro", "email": "evil@example.com" }
+        }"#;
+        let uri = mock_webhook(body, Duration::ZERO, SECRET).await;
+
+        let claims = call_webhook(
+            &reqwest::Client::new(),
+            &uri,
+            SECRET,
+            Duration::from_secs(2),
+            payload(),
+        )
+        .await
+        .unwrap()
+        .filter_allowed(["roles", "tenant", "email"].into_iter());
+
+        assert_eq!(
+            claims.roles,
+            Some(vec!["admin".to_string(), "billing".to_string()])
+        );
+        // `groups` and `plan` are not in the allowlist
+        assert_eq!(claims.groups, None);
+        assert_eq!(
+            claims.claims,
+            HashMap::from([
+                ("tenant".to_string(), json!("t1")),
+                ("email".to_string(), json!("evil@example.com")),
+            ])
+        );
+
+        let merged = merge_values("user,admin".split(','), claims.roles.as_deref());
+        assert_eq!(merged, vec!["user", "admin", "billing"]);
+        let merged = merge_values("ops".split(','), claims.groups.as_deref());
+        assert_eq!(merged, vec!["ops"]);
+    }
+
+    #[tokio::test]
+    async fn test_webhook_timeout() {
+        let uri = mock_webhook(r#"{"roles":["admin"]}"#, Duration::from_secs(1), SECRET).await;
+
+        let err = call_webhook(
+            &reqwest::Client::new(),
+            &uri,
+            SECRET,
+            Duration::from_millis(100),
+            payload(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.error, ErrorResponseType::Timeout);
+    }
+
+    #[tokio::test]
+    async fn test_webhook_signature_mismatch() {
+        let uri = mock_webhook(
+            r#"{"roles":["admin"]}"#,
+            Duration::ZERO,
+            b"AnotherSecretAnotherSecretAnother",
+        )
+        .await;
+
+        let err = call_webhook(
+            &reqwest::Client::new(),
+            &uri,
+            SECRET,
+            Duration::from_secs(2),
+            payload(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.error, ErrorResponseType::Forbidden);
+    }
+}
@@ -0,0 +1,755 @@
+use crate::database::{Cache, DB};
+use crate::entity::clients::Client;
+use crate::entity::jwk::{JWKSPublicKey, JwkKeyPairAlg};
+use crate::rauthy_config::RauthyConfig;
+use chrono::Utc;
+use hiqlite::macros::params;
+use rauthy_api_types::clients::{ClientJwkResponse, ClientJwksRequest, ClientJwksResponse};
+use rauthy_common::constants::APPLICATION_JSON;
+use rauthy_common::{http_client, is_hiqlite};
+use rauthy_derive::FromPgRow;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use reqwest::header::ACCEPT;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use tracing::{debug, error};
+
+/// The max size of a JWKS in bytes, either inline or fetched from a `jwks_uri`.
+const JWKS_MAX_LEN: usize = 64 * 1024;
+/// The max amount of keys a JWKS may contain.
+const JWKS_MAX_KEYS: usize = 16;
+/// The keys of a client are cached for this amount of seconds before they will be fetched again.
+const JWKS_CACHE_TTL: i64 = 3600;
+/// An unknown `kid` triggers a refetch only if the last fetch is older than this, so an
+/// attacker cannot make us hammer a `jwks_uri` with random `kid`s.
+const JWKS_REFETCH_MIN_SECS: i64 = 60;
+
+/// The public keys of a client, used to validate inbound signed requests like `private_key_jwt`
+/// client authentication or signed request objects. Either `jwks` or `jwks_uri` is set.
+///
+/// Every feature, which validates client signatures, must get the keys via
+/// `ClientJwks::find_key()` to have consistent caching and key rotation handling.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromPgRow)]
+pub struct ClientJwks {
+    pub client_id: String,
+    pub jwks: Option<String>,
+    pub jwks_uri: Option<String>,
+    pub updated: i64,
+}
+
+/// The parsed keys of a client or an upstream auth provider. Errors are cached as well, so an
+/// unreachable `jwks_uri` will not be requested again for each inbound request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct JwksCached {
+    keys: Result<Vec<JWKSPublicKey>, ErrorResponse>,
+    fetched_at: i64,
+}
+
+impl JwksCached {
+    fn find(&self, kid: &str) -> Result<JWKSPublicKey, ErrorResponse> {
+        match &self.keys {
+            Ok(keys) => keys
+                .iter()
+                .find(|k| k.kid.as_deref() == Some(kid))
+                .cloned()
+                .ok_or_else(|| {
+                    ErrorResponse::new(
+                        ErrorResponseType::NotFound,
+                        "cannot find given `kid` in JWKS",
+                    )
+                }),
+            Err(err) => Err(err.clone()),
+        }
+    }
+}
+
+// CRUD
+impl ClientJwks {
+    pub async fn delete(client_id: &str) -> Result<(), ErrorResponse> {
+        let sql = "DELETE FROM clients_jwks WHERE client_id = $1";
+        let rows_affected = if is_hiqlite() {
+            DB::hql().execute(sql, params!(client_id)).await?
+        } else {
+            DB::pg_execute(sql, &[&client_id]).await?
+        };
+
+        Self::clear_cache(client_id).await?;
+
+        if rows_affected == 0 {
+            Err(ErrorResponse::new(
+                ErrorResponseType::NotFound,
+                "This client has no JWKS",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub async fn find(client_id: &str) -> Result<Option<Self>, ErrorResponse> {
+        let sql = "SELECT * FROM clients_jwks WHERE client_id = $1";
+        let slf = if is_hiqlite() {
+            DB::hql().query_as_optional(sql, params!(client_id)).await?
+        } else {
+            DB::pg_query_opt(sql, &[&client_id]).await?
+        };
+
+        Ok(slf)
+    }
+
+    pub async fn upsert(
+        client_id: String,
+        payload: ClientJwksRequest,
+    ) -> Result<Self, ErrorResponse> {
+        // make sure we return a proper error instead of a FK violation
+        Client::find(client_id.clone()).await?;
+
+        let (jwks, jwks_uri) = match (payload.jwks, payload.jwks_uri) {
+            (Some(jwks), None) => {
+                let jwks = jwks.to_string();
+                parse_jwks(jwks.as_bytes())?;
+                (Some(jwks), None)
+            }
+            (None, Some(uri)) => {
+                if !uri.starts_with("https://") && !RauthyConfig::get().vars.dev.dev_mode {
+                    return Err(ErrorResponse::new(
+                        ErrorResponseType::BadRequest,
+                        "The `jwks_uri` must use https",
+                    ));
+                }
+                (None, Some(uri))
+            }
+            _ => {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    "Exactly one of `jwks` or `jwks_uri` must be given",
+                ));
+            }
+        };
+
+        let slf = Self {
+            client_id,
+            jwks,
+            jwks_uri,
+            updated: Utc::now().timestamp(),
+        };
+
+        let sql = r#"
+INSERT INTO clients_jwks (client_id, jwks, jwks_uri, updated)
+VALUES ($1, $2, $3, $4)
+ON CONFLICT (client_id) DO UPDATE
+SET jwks = $2, jwks_uri = $3, updated = $4"#;
+        if is_hiqlite() {
+            DB::hql()
+                .execute(
+                    sql,
+                    params!(
+                        slf.client_id.clone(),
+                        slf.jwks.clone(),
+                        slf.jwks_uri.clone(),
+                        slf.updated
+                    ),
+                )
+                .await?;
+        } else {
+            DB::pg_execute(
+                sql,
+                &[&slf.client_id, &slf.jwks, &slf.jwks_uri, &slf.updated],
+            )
+            .await?;
+        }
+
+        Self::clear_cache(&slf.client_id).await?;
+
+        Ok(slf)
+    }
+}
+
+impl ClientJwks {
+    #[inline]
+    fn cache_idx(client_id: &str) -> String {
+        format!("client_jwks_{client_id}")
+    }
+
+    pub async fn clear_cache(client_id: &str) -> Result<(), ErrorResponse> {
+        DB::hql()
+            .delete(Cache::JwksRemote, Self::cache_idx(client_id))
+            .await?;
+        Ok(())
+    }
+
+    /// Returns the clients' public key for the given `kid`.
+    ///
+    /// The keys are cached for `JWKS_CACHE_TTL`. If the `kid` cannot be found, the keys will be
+    /// fetched again exactly once to handle a key rotation on the client side.
+    pub async fn find_key(client_id: &str, kid: &str) -> Result<JWKSPublicKey, ErrorResponse> {
+        let client = DB::hql();
+        let idx = Self::cache_idx(client_id);
+
+        let cached: Option<JwksCached> = client.get(Cache::JwksRemote, &idx).await?;
+        let (res, fetched) = lookup_kid(cached, kid, Utc::now().timestamp(), || async {
+            match Self::find(client_id).await? {
+                Some(slf) => slf.keys().await,
+                None => Err(ErrorResponse::new(
+                    ErrorResponseType::NotFound,
+                    "This client has no JWKS",
+                )),
+            }
+        })
+        .await;
+
+        if let Some(fetched) = fetched {
+            client
+                .put(Cache::JwksRemote, idx, &fetched, Some(JWKS_CACHE_TTL))
+                .await?;
+        }
+
+        res
+    }
+
+    /// Validates the signature of the given `token` with the clients' key for `kid`.
+    pub async fn validate_token_signature(
+        client_id: &str,
+        token: &str,
+        kid: &str,
+        alg: JwkKeyPairAlg,
+    ) -> Result<(), ErrorResponse> {
+        let jwk = Self::find_key(client_id, kid).await?;
+        validate_signature(jwk, token, alg)
+    }
+
+    /// Returns the parsed inline keys, or the keys fetched the last time from the `jwks_uri`,
+    /// if they still exist in the cache. Never fetches the keys.
+    pub async fn keys_cached(&self) -> Result<Vec<JWKSPublicKey>, ErrorResponse> {
+        if let Some(jwks) = &self.jwks {
+            return parse_jwks(jwks.as_bytes());
+        }
+
+        let cached: Option<JwksCached> = DB::hql()
+            .get(Cache::JwksRemote, Self::cache_idx(&self.client_id))
+            .await?;
+        Ok(cached.and_then(|c| c.keys.ok()).unwrap_or_default())
+    }
+
+    /// Fetches the keys again and updates the cache, no matter when they were fetched the
+    /// last time.
+    pub async fn refresh(client_id: &str) -> Result<Vec<JWKSPublicKey>, ErrorResponse> {
+        let slf = Self::find(client_id).await?.ok_or_else(|| {
+            ErrorResponse::new(ErrorResponseType::NotFound, "This client has no JWKS")
+        })?;
+
+        let cached = JwksCached {
+            keys: slf.keys().await,
+            fetched_at: Utc::now().timestamp(),
+        };
+        DB::hql()
+            .put(
+                Cache::JwksRemote,
+                Self::cache_idx(client_id),
+                &cached,
+                Some(JWKS_CACHE_TTL),
+            )
+            .await?;
+
+        cached.keys
+    }
+
+    /// Returns the parsed inline keys, or fetches them from the `jwks_uri`.
+    pub async fn keys(&self) -> Result<Vec<JWKSPublicKey>, ErrorResponse> {
+        match (&self.jwks, &self.jwks_uri) {
+            (Some(jwks), _) => parse_jwks(jwks.as_bytes()),
+            (None, Some(uri)) => fetch_jwks(http_client(), uri).await,
+            (None, None) => Err(ErrorResponse::new(
+                ErrorResponseType::Internal,
+                "Client JWKS without `jwks` and `jwks_uri`",
+            )),
+        }
+    }
+
+    pub fn into_response(self, keys: Vec<JWKSPublicKey>) -> ClientJwksResponse {
+        ClientJwksResponse {
+            client_id: self.client_id,
+            jwks: self.jwks.and_then(|jwks| serde_json::from_str(&jwks).ok()),
+            jwks_uri: self.jwks_uri,
+            updated: self.updated,
+            keys: keys
+                .into_iter()
+                .map(|k| ClientJwkResponse {
+                    kid: k.kid.unwrap_or_default(),
+                    kty: k.kty.as_str().to_string(),
+                    alg: k.alg.map(|alg| alg.to_string()).unwrap_or_default(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Fetches the JWKS from the given `jwks_uri`. The body is read in chunks and the request is
+/// aborted as soon as it exceeds `JWKS_MAX_LEN`.
+pub(crate) async fn fetch_jwks(
+    client: &reqwest::Client,
+    jwks_uri: &str,
+) -> Result<Vec<JWKSPublicKey>, ErrorResponse> {
+    let mut res = client
+        .get(jwks_uri)
+        .header(ACCEPT, APPLICATION_JSON)
+        .send()
+        .await
+        .map_err(|err| {
+            error!(?err, "Error fetching JWKS from {jwks_uri}");
+            ErrorResponse::new(
+                ErrorResponseType::Connection,
+                format!("Error connecting to {jwks_uri}"),
+            )
+        })?;
+
+    if !res.status().is_success() {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::Connection,
+            format!("Error fetching JWKS from {jwks_uri}: {}", res.status()),
+        ));
+    }
+
+    // the `content-length` may be missing, so we read the chunks manually to never buffer
+    // more than the max size
+    let mut body = Vec::with_capacity(1024);
+    while let Some(chunk) = res.chunk().await.map_err(|err| {
+        ErrorResponse::new(
+            ErrorResponseType::Connection,
+            format!("Error reading JWKS from {jwks_uri}: {err}"),
+        )
+    })? {
+        if body.len() + chunk.len() > JWKS_MAX_LEN {
+            return Err(err_too_big());
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    parse_jwks(&body)
+}
+
+/// Parses and validates the given JWKS. Keys with an unsupported `kty` or `alg` and keys,
+/// which are not meant for signatures, are ignored.
+///
+/// The `alg` is optional for a JWK. Keys without it are validated against the `alg` from the
+/// token header in `validate_signature()`.
+pub(crate) fn parse_jwks(bytes: &[u8]) -> Result<Vec<JWKSPublicKey>, ErrorResponse> {
+    #[derive(Deserialize)]
+    struct RawJwks {
+        keys: Vec<serde_json::Value>,
+    }
+
+    if bytes.len() > JWKS_MAX_LEN {
+        return Err(err_too_big());
+    }
+
+    let raw = serde_json::from_slice::<RawJwks>(bytes).map_err(|err| {
+        ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            format!("Invalid JWKS: {err}"),
+        )
+    })?;
+    if raw.keys.len() > JWKS_MAX_KEYS {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            format!("A JWKS must not contain more than {JWKS_MAX_KEYS} keys"),
+        ));
+    }
+
+    let mut keys: Vec<JWKSPublicKey> = Vec::with_capacity(raw.keys.len());
+    for value in raw.keys {
+        if value
+            .get("use")
+            .and_then(|u| u.as_str())
+            .is_some_and(|u| u != "sig")
+        {
+            continue;
+        }
+
+        let key = match serde_json::from_value::<JWKSPublicKey>(value) {
+            Ok(key) => key,
+            Err(err) => {
+                debug!("Ignoring unsupported key in JWKS: {err}");
+                continue;
+            }
+        };
+        if key.alg.is_some() {
+            key.validate_self()?;
+        }
+
+        let Some(kid) = key.kid.as_deref() else {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "All keys in a JWKS must have a `kid`",
+            ));
+        };
+        if keys.iter().any(|k| k.kid.as_deref() == Some(kid)) {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("Duplicate `kid` in JWKS: {kid}"),
+            ));
+        }
+
+        keys.push(key);
+    }
+
+    if keys.is_empty() {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "The JWKS does not contain any supported signing keys",
+        ));
+    }
+
+    Ok(keys)
+}
+
+/// Validates the signature of the given `token` with `jwk`. `alg` is the one from the token
+/// header and must match the key.
+pub(crate) fn validate_signature(
+    mut jwk: JWKSPublicKey,
+    token: &str,
+    alg: JwkKeyPairAlg,
+) -> Result<(), ErrorResponse> {
+    match &jwk.alg {
+        Some(jwk_alg) if jwk_alg != &alg => {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "`alg` mismatch between token header and JWK",
+            ));
+        }
+        Some(_) => {}
+        None => {
+            jwk.alg = Some(alg);
+            jwk.validate_self()?;
+        }
+    }
+
+    let mut buf = Vec::with_capacity(256);
+    jwk.validate_token_signature(token, &mut buf)
+}
+
+#[inline]
+fn err_too_big() -> ErrorResponse {
+    ErrorResponse::new(
+        ErrorResponseType::BadRequest,
+        format!("A JWKS must not be bigger than {JWKS_MAX_LEN} bytes"),
+    )
+}
+
+/// Looks up the `kid` in the `cached` keys. If it cannot be found, the keys will be loaded
+/// again via `fetch` exactly once, but only if the last fetch is older than
+/// `JWKS_REFETCH_MIN_SECS`.
+///
+/// Returns the result and the freshly fetched keys, which must be cached, if any.
+pub(crate) async fn lookup_kid<F, Fut>(
+    cached: Option<JwksCached>,
+    kid: &str,
+    now: i64,
+    fetch: F,
+) -> (Result<JWKSPublicKey, ErrorResponse>, Option<JwksCached>)
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<Vec<JWKSPublicKey>, ErrorResponse>>,
+{
+    if let Some(cached) = cached {
+        match cached.find(kid) {
+            Ok(key) => return (Ok(key), None),
+            Err(err) if now - cached.fetched_at < JWKS_REFETCH_MIN_SECS => {
+                return (Err(err), None);
+            }
+            Err(_) => {
+                debug!("Unknown `kid` {kid} in cached JWKS - fetching again");
+            }
+        }
+    }
+
+    let fetched = JwksCached {
+        keys: fetch().await,
+        fetched_at: now,
+    };
+    (fetched.find(kid), Some(fetched))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::jwk::JwkKeyPairType;
+    use pretty_assertions::assert_eq;
+    use rauthy_common::utils::base64_url_encode;
+    use ring::rand::SystemRandom;
+    use ring::signature::{ECDSA_P256_SHA256_FIXED_SIGNING, EcdsaKeyPair, KeyPair};
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn key(kid: &str) -> JWKSPublicKey {
+        JWKSPublicKey {
+            kty: JwkKeyPairType::OKP,
+            alg: Some(JwkKeyPairAlg::EdDSA),
+            crv: Some("Ed25519".to_string()),
+            kid: Some(kid.to_string()),
+            n: None,
+            e: None,
+            x: Some("11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo".to_string()),
+            y: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lookup_kid() {
+        let fetches = AtomicUsize::new(0);
+        let fetch = |kids: &'static [&'static str]| {
+            let fetches = &fetches;
+            move || async move {
+                fetches.fetch_add(1, Ordering::Relaxed);
+                Ok::<_, ErrorResponse>(kids.iter().map(|kid| key(kid)).collect::<Vec<_>>())
+            }
+        };
+
+        // nothing cached -> a single fetch
+        let (res, cached) = lookup_kid(None, "k1", 100, fetch(&["k1"])).await;
+        assert_eq!(res.unwrap().kid.as_deref(), Some("k1"));
+        assert_eq!(fetches.load(Ordering::Relaxed), 1);
+        let cached = cached.unwrap();
+
+        // known kid -> served from cache
+        let (res, update) = lookup_kid(Some(cached.clone()), "k1", 500, fetch(&["k1"])).await;
+        assert!(res.is_ok());
+        assert!(update.is_none());
+        assert_eq!(fetches.load(Ordering::Relaxed), 1);
+
+        // unknown kid shortly after the last fetch -> no refetch
+        let now = 100 + JWKS_REFETCH_MIN_SECS - 1;
+        let (res, update) = lookup_kid(Some(cached.clone()), "k2", now, fetch(&["k2"])).await;
+        assert_eq!(res.unwrap_err().error, ErrorResponseType::NotFound);
+        assert!(update.is_none());
+        assert_eq!(fetches.load(Ordering::Relaxed), 1);
+
+        // unknown kid after a client side key rotation -> exactly one refetch
+        let now = 100 + JWKS_REFETCH_MIN_SECS;
+        let (res, update) = lookup_kid(Some(cached.clone()), "k2", now, fetch(&["k1", "k2"])).await;
+        assert_eq!(res.unwrap().kid.as_deref(), Some("k2"));
+        assert_eq!(fetches.load(Ordering::Relaxed), 2);
+        let update = update.unwrap();
+        assert_eq!(update.fetched_at, now);
+        assert_eq!(update.keys.unwrap().len(), 2);
+
+        // still unknown after the refetch -> error, but again only a single fetch
+        let (res, update) = lookup_kid(Some(cached), "k3", now, fetch(&["k1", "k2"])).await;
+        assert_eq!(res.unwrap_err().error, ErrorResponseType::NotFound);
+        assert!(update.is_some());
+        assert_eq!(fetches.load(Ordering::Relaxed), 3);
+    }
+
+    #[tokio::test]
+    async fn test_lookup_kid_unreachable() {
+        let fetches = AtomicUsize::new(0);
+        let fetch = || async {
+            fetches.fetch_add(1, Ordering::Relaxed);
+            Err::<Vec<JWKSPublicKey>, _>(ErrorResponse::new(
+                ErrorResponseType::Connection,
+                "Error connecting to https://client.example.com/jwks",
+            ))
+        };
+
+        // the error is returned and must be cached
+        let (res, cached) = lookup_kid(None, "k1", 100, fetch).await;
+        assert_eq!(res.unwrap_err().error, ErrorResponseType::Connection);
+        let cached = cached.unwrap();
+        assert!(cached.keys.is_err());
+        assert_eq!(fetches.load(Ordering::Relaxed), 1);
+
+        // the cached error prevents hammering the unreachable URI
+        let (res, update) = lookup_kid(Some(cached.clone()), "k1", 120, fetch).await;
+        assert_eq!(res.unwrap_err().error, ErrorResponseType::Connection);
+        assert!(update.is_none());
+        assert_eq!(fetches.load(Ordering::Relaxed), 1);
+
+        // and is retried once the min interval has passed
+        let now = 100 + JWKS_REFETCH_MIN_SECS;
+        let (res, update) = lookup_kid(Some(cached), "k1", now, fetch).await;
+        assert!(res.is_err());
+        assert!(update.is_some());
+        assert_eq!(fetches.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_parse_jwks() {
+        let valid = serde_json::to_value(key("k1")).unwrap();
+
+        let jwks = json!({ "keys": [
+            valid,
+            // not a signing key
+            {
+                "kty": "RSA",
+                "use": "enc",
+                "alg": "RSA-OAEP",
+                "kid": "enc",
+                "n": "abc",
+                "e": "AQAB"
+            },
+            // unsupported kty
+            {
+                "kty": "oct",
+                "alg": "HS256",
+                "kid": "oct",
+                "k": "abc"
+            },
+            // `alg` is optional, like in the JWKS from Azure AD
+            {
+                "kty": "RSA",
+                "use": "sig",
+                "kid": "rsa",
+                "n": "abc",
+                "e": "AQAB"
+            },
+            {
+                "kty": "EC",
+                "alg": "ES256",
+                "crv": "P-256",
+                "kid": "ec",
+                "x": "abc",
+                "y": "abc"
+            }
+        ]});
+        let keys = parse_jwks(jwks.to_string().as_bytes()).unwrap();
+        assert_eq!(
+            keys.iter().map(|k| k.kid.as_deref()).collect::<Vec<_>>(),
+            vec![Some("k1"), Some("rsa"), Some("ec")]
+        );
+        assert_eq!(keys[1].alg, None);
+
+        // no usable keys at all
+        let jwks = json!({ "keys": [] });
+        assert!(parse_jwks(jwks.to_string().as_bytes()).is_err());
+
+        // duplicate kid
+        let jwks = json!({ "keys": [key("k1"), key("k1")] });
+        assert!(parse_jwks(jwks.to_string().as_bytes()).is_err());
+
+        // missing kid
+        let mut no_kid = key("k1");
+        no_kid.kid = None;
+        let jwks = json!({ "keys": [no_kid] });
+        assert!(parse_jwks(jwks.to_string().as_bytes()).is_err());
+
+        // RSA kty with EdDSA alg
+        let mut invalid = key("k1");
+        invalid.kty = JwkKeyPairType::RSA;
+        let jwks = json!({ "keys": [invalid] });
+        assert!(parse_jwks(jwks.to_string().as_bytes()).is_err());
+
+        // too many keys
+        let keys = (0..=JWKS_MAX_KEYS)
+            .map(|i| key(&i.to_string()))
+            .collect::<Vec<_>>();
+        let jwks = json!({ "keys": keys });
+        assert!(parse_jwks(jwks.to_string().as_bytes()).is_err());
+
+        // too big
+        let bytes = vec![b' '; JWKS_MAX_LEN + 1];
+        assert!(parse_jwks(&bytes).is_err());
+    }
+
+    fn sign_es256(kp: &EcdsaKeyPair, header: &str) -> String {
+        let message = format!(
+            "{}.{}",
+            base64_url_encode(header.as_bytes()),
+            base64_url_encode(br#"{"sub":"1337"}"#)
+        );
+        let sig = kp.sign(&SystemRandom::new(), message.as_bytes()).unwrap();
+        format!("{message}.{}", base64_url_encode(sig.as_ref()))
+    }
+
+    #[test]
+    fn test_validate_signature() {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng).unwrap();
+        let kp = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref(), &rng)
+            .unwrap();
+        // uncompressed point: 0x04 | x | y
+        let point = kp.public_key().as_ref();
+        let jwk = JWKSPublicKey {
+            kty: JwkKeyPairType::EC,
+            alg: Some(JwkKeyPairAlg::ES256),
+            crv: Some("P-256".to_string()),
+            kid: Some("ec".to_string()),
+            n: None,
+            e: None,
+            x: Some(base64_url_encode(&point[1..33])),
+            y: Some(base64_url_encode(&point[33..])),
+        };
+        let token = sign_es256(&kp, r#"{"alg":"ES256","kid":"ec"}"#);
+        assert!(validate_signature(jwk.clone(), &token, JwkKeyPairAlg::ES256).is_ok());
+
+        // a JWK without `alg` takes the one from the token header, if it fits the `kty`
+        let mut no_alg = jwk.clone();
+        no_alg.alg = None;
+        assert!(validate_signature(no_alg.clone(), &token, JwkKeyPairAlg::ES256).is_ok());
+        assert!(validate_signature(no_alg, &token, JwkKeyPairAlg::RS256).is_err());
+
+        // `alg` mismatch
+        let err = validate_signature(jwk.clone(), &token, JwkKeyPairAlg::EdDSA).unwrap_err();
+        assert_eq!(err.error, ErrorResponseType::BadRequest);
+
+        // modified claims
+        let (header, rest) = token.split_once('.').unwrap();
+        let (_, sig) = rest.split_once('.').unwrap();
+        let forged = format!(
+            "{header}.{}.{sig}",
+            base64_url_encode(br#"{"sub":"admin"}"#)
+        );
+        let err = validate_signature(jwk, &forged, JwkKeyPairAlg::ES256).unwrap_err();
+        assert_eq!(err.error, ErrorResponseType::Unauthorized);
+    }
+
+    /// Accepts a single request and answers with a chunked `body`. With `endless`, the body is
+    /// repeated until the client closes the connection.
+    async fn mock_jwks_uri(body: String, endless: bool) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 4096];
+            let _ = stream.read(&mut buf).await.unwrap();
+
+            let head = "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\n\
+                transfer-encoding: chunked\r\nconnection: close\r\n\r\n";
+            if stream.write_all(head.as_bytes()).await.is_err() {
+                return;
+            }
+            let chunk = format!("{:x}\r\n{body}\r\n", body.len());
+            loop {
+                if stream.write_all(chunk.as_bytes()).await.is_err() || !endless {
+                    break;
+                }
+            }
+            let _ = stream.write_all(b"0\r\n\r\n").await;
+        });
+
+        format!("http://{addr}/jwks")
+    }
+
+    #[tokio::test]
+    async fn test_fetch_jwks() {
+        let client = reqwest::Client::new();
+
+        let jwks = json!({ "keys": [key("k1")] }).to_string();
+        let uri = mock_jwks_uri(jwks, false).await;
+        let keys = fetch_jwks(&client, &uri).await.unwrap();
+        assert_eq!(keys.len(), 1);
+
+        // an endless body without a `content-length` must be aborted after the max size
+        let uri = mock_jwks_uri(" ".repeat(1024), true).await;
+        let err = tokio::time::timeout(
+            std::time::Duration::from_secs(10),
+            fetch_jwks(&client, &uri),
+        )
+        .await
+        .expect("the fetch to be aborted")
+        .unwrap_err();
+        assert_eq!(err.error, ErrorResponseType::BadRequest);
+        assert!(err.message.contains("must not be bigger"));
+    }
+}
@@ -891,6 +891,7 @@ impl ClientScim {
             country: None,
             preferred_username: None,
             tz: None,
+            locale: None,
         };
 
         for client_scim in clients_scim {
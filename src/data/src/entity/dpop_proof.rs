@@ -5,6 +5,7 @@ use actix_web::http::header::{HeaderName, HeaderValue};
 use actix_web::{HttpRequest, http};
 use chrono::{DateTime, Utc};
 use rauthy_common::constants::{DPOP_TOKEN_ENDPOINT, TOKEN_DPOP};
+use rauthy_common::jwt_time::JwtTimeClaims;
 use rauthy_common::regex::RE_TOKEN_68;
 use rauthy_common::utils::{base64_url_no_pad_decode, get_rand};
 use rauthy_error::{ErrorResponse, ErrorResponseType};
@@ -212,7 +213,8 @@ impl DPoPProof {
                 } else {
                     let slf = Self::try_from_str(origin.as_deref(), b64)?;
 
-                    if let Err(msg) = slf.validate(b64) {
+                    if let Err(msg) = slf.validate(b64, RauthyConfig::get().vars.access.jwt_leeway)
+                    {
                         return Err(ErrorResponse::new(ErrorResponseType::DPoP(origin), msg));
                     }
                     if let Err(nonce) = slf.validate_nonce().await {
@@ -254,7 +256,7 @@ impl DPoPProof {
     /// - ensure that the value of the ath claim equals the hash of that access token, and
     /// - confirm that the public key to which the access token is bound matches the
     ///   public key from the DPoP proof.
-    pub fn validate(&self, raw_token: &str) -> Result<(), String> {
+    pub fn validate(&self, raw_token: &str, leeway: u16) -> Result<(), String> {
         // 1. we do not need to validate that there is only one head field with DPoP since
         // actix serializes into a HashMap which implies this anyway
 
@@ -304,10 +306,16 @@ impl DPoPProof {
         // claim or a server managed timestamp via the nonce claim, is within an
         // acceptable window (see Section 11.1).
         //
-        // We will accept an 'iat' of 1 minute old, and it must not be in the future
-        let now = Utc::now().timestamp();
-        let now_minus_1 = now - 60;
-        if self.claims.iat < now_minus_1 || self.claims.iat > now {
+        // We will accept an 'iat' of 1 minute old, and it must not be in the future, both with
+        // the configured leeway for clock skew.
+        let iat_limit = Utc::now().timestamp() - 60 - leeway as i64;
+        let is_future = JwtTimeClaims {
+            iat: Some(self.claims.iat),
+            ..Default::default()
+        }
+        .validate(leeway)
+        .is_err();
+        if is_future || self.claims.iat < iat_limit {
             return Err("DPoP 'iat' claim is out of range".to_string());
         }
 
@@ -349,6 +357,7 @@ mod tests {
     use chrono::Utc;
     use ed25519_compact::Noise;
     use rauthy_common::constants::DPOP_TOKEN_ENDPOINT;
+    use rauthy_common::jwt_time::JWT_LEEWAY_DEFAULT;
     use rauthy_common::utils::{base64_url_encode, base64_url_no_pad_encode};
     use rsa::sha2::Sha256;
     use rsa::traits::PublicKeyParts;
@@ -371,6 +380,7 @@ mod tests {
                 n: None,
                 e: None,
                 x: Some(base64_url_encode(kp.pk.as_slice())),
+                y: None,
             },
             kid: None,
         };
@@ -399,7 +409,7 @@ mod tests {
 
         // now we have our token like it should come in with the DPoP header -> try to verify it
         let dpop = DPoPProof::try_from_str(None, token_raw.as_str()).unwrap();
-        dpop.validate(&token_raw).unwrap();
+        dpop.validate(&token_raw, JWT_LEEWAY_DEFAULT).unwrap();
 
         // Note: we cannot validate the nonce in this unit test because of missing AppState and
         // cache -> will be done in integration tests
@@ -428,6 +438,7 @@ mod tests {
                 e: Some(base64_url_no_pad_encode(&e)),
                 // e: Some(base64_url_encode(&e)),
                 x: None,
+                y: None,
             },
             kid: None,
         };
@@ -458,7 +469,7 @@ mod tests {
 
         // now we have our token like it should come in with the DPoP header -> try to verify it
         let dpop = DPoPProof::try_from_str(None, token_raw.as_str()).unwrap();
-        dpop.validate(&token_raw).unwrap();
+        dpop.validate(&token_raw, JWT_LEEWAY_DEFAULT).unwrap();
 
         // This only tests the RS256 validation. The logic for 384 and 512 is the same, and the
         // token signature validation itself for the 2 others is tested already in
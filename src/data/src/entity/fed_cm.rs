@@ -15,6 +15,10 @@ pub struct FedCMAccount {
     pub name: String,
     pub email: String,
     pub given_name: Option<String>,
+    // A label to tell multiple accounts apart in the account chooser, built from the user's
+    // `given_name` and `family_name`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account_label: Option<String>,
     // URL for the account’s picture.
     pub picture: Option<String>,
     // A list of RPs (that gets matched against the requesting clientId) this account is already
@@ -33,14 +37,20 @@ pub struct FedCMAccount {
 
 impl FedCMAccount {
     pub fn build(user: User) -> Self {
+        Self::build_with_domain(user, RauthyConfig::get().vars.server.pub_url.clone())
+    }
+
+    fn build_with_domain(user: User, domain_hint: String) -> Self {
         let name = user.email_recipient_name();
         let login_hint = format!("login_hint={}", user.email);
+        let account_label = Self::account_label(&user);
 
         Self {
             id: user.id,
             name,
             email: user.email,
             given_name: Some(user.given_name),
+            account_label,
             // Rauthy does not store user pictures
             picture: None,
             // TODO how should we decide which clients to return here? How to make this dynamic?
@@ -49,7 +59,21 @@ impl FedCMAccount {
             //  not sure if it produces errors and problems if we populate this value at all
             approved_clients: Vec::default(),
             login_hints: vec![login_hint, "state=fedcm".to_string()],
-            domain_hints: vec![RauthyConfig::get().vars.server.pub_url.clone()],
+            domain_hints: vec![domain_hint],
+        }
+    }
+
+    fn account_label(user: &User) -> Option<String> {
+        let label = match &user.family_name {
+            Some(family_name) => format!("{} {family_name}", user.given_name),
+            None => user.given_name.clone(),
+        };
+        let label = label.trim();
+
+        if label.is_empty() {
+            None
+        } else {
+            Some(label.to_string())
         }
     }
 }
@@ -59,6 +83,27 @@ pub struct FedCMAccounts {
     pub accounts: Vec<FedCMAccount>,
 }
 
+impl FedCMAccounts {
+    /// Builds the accounts for all `users` logged in with the current browser. The same user can
+    /// have multiple sessions, which is why they are de-duplicated by their ID.
+    pub fn build(users: Vec<User>) -> Self {
+        Self::build_with_domain(users, &RauthyConfig::get().vars.server.pub_url)
+    }
+
+    fn build_with_domain(users: Vec<User>, domain_hint: &str) -> Self {
+        let mut accounts: Vec<FedCMAccount> = Vec::with_capacity(users.len());
+        for user in users {
+            if !accounts.iter().any(|acc| acc.id == user.id) {
+                accounts.push(FedCMAccount::build_with_domain(
+                    user,
+                    domain_hint.to_string(),
+                ));
+            }
+        }
+        Self { accounts }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, ToSchema)]
 pub struct FedCMTokenResponse {
     pub token: String,
@@ -199,3 +244,72 @@ impl Default for WebIdentity {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn user(id: &str, email: &str, given_name: &str, family_name: Option<&str>) -> User {
+        User {
+            id: id.to_string(),
+            email: email.to_string(),
+            given_name: given_name.to_string(),
+            family_name: family_name.map(String::from),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_fed_cm_multi_accounts() {
+        let users = vec![
+            user("id1", "alice@example.com", "Alice", Some("Liddell")),
+            user("id2", "bob@example.com", "Bob", None),
+            // the same user with a second session from the same browser
+            user("id1", "alice@example.com", "Alice", Some("Liddell")),
+            user("id3", "anon@example.com", "", None),
+        ];
+
+        let accounts = FedCMAccounts::build_with_domain(users, "iam.example.com");
+        let value = serde_json::to_value(&accounts).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "accounts": [
+                    {
+                        "id": "id1",
+                        "name": "Alice Liddell",
+                        "email": "alice@example.com",
+                        "given_name": "Alice",
+                        "account_label": "Alice Liddell",
+                        "picture": null,
+                        "approved_clients": [],
+                        "login_hints": ["login_hint=alice@example.com", "state=fedcm"],
+                        "domain_hints": ["iam.example.com"],
+                    },
+                    {
+                        "id": "id2",
+                        "name": "Bob",
+                        "email": "bob@example.com",
+                        "given_name": "Bob",
+                        "account_label": "Bob",
+                        "picture": null,
+                        "approved_clients": [],
+                        "login_hints": ["login_hint=bob@example.com", "state=fedcm"],
+                        "domain_hints": ["iam.example.com"],
+                    },
+                    {
+                        "id": "id3",
+                        "name": "",
+                        "email": "anon@example.com",
+                        "given_name": "",
+                        "picture": null,
+                        "approved_clients": [],
+                        "login_hints": ["login_hint=anon@example.com", "state=fedcm"],
+                        "domain_hints": ["iam.example.com"],
+                    },
+                ]
+            })
+        );
+    }
+}
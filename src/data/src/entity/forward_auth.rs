@@ -6,7 +6,7 @@ use chrono::Utc;
 use cryptr::EncValue;
 use rauthy_common::utils::{
     base64_decode, base64_encode, base64_url_no_pad_decode, base64_url_no_pad_encode, deserialize,
-    real_ip_from_req, serialize,
+    real_ip, secure_compare, serialize,
 };
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use serde::{Deserialize, Serialize};
@@ -75,7 +75,7 @@ impl ForwardAuthSession {
     #[inline]
     pub fn validate(&self, req: &HttpRequest) -> Result<(), ErrorResponse> {
         let remote_ip = if RauthyConfig::get().vars.access.session_validate_ip {
-            real_ip_from_req(req).ok()
+            real_ip(req).ok()
         } else {
             None
         };
@@ -111,7 +111,7 @@ impl ForwardAuthSession {
         let decrypted = EncValue::try_from_bytes(decoded)?.decrypt()?;
         let token = String::from_utf8_lossy(decrypted.as_ref());
 
-        if token != self.inner.csrf_token {
+        if !secure_compare(token.as_bytes(), &self.inner.csrf_token) {
             warn!("CSRF Token mismatch");
             return Err(ErrorResponse::new(
                 ErrorResponseType::BadRequest,
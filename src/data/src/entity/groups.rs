@@ -75,7 +75,7 @@ impl Group {
 
             for mut user in users {
                 user.delete_group(&group.name);
-                user.save_txn_append(&mut txn);
+                user.save_txn_append(&mut txn)?;
             }
 
             txn.push((sql, params!(group.id.clone())));
@@ -180,7 +180,7 @@ impl Group {
                         .unwrap()
                         .replace(&group.name, &new_group.name),
                 );
-                user.save_txn_append(&mut txn);
+                user.save_txn_append(&mut txn)?;
             }
 
             txn.push((
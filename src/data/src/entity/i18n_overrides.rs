@@ -0,0 +1,368 @@
+use crate::cache_layer::{self, CacheName};
+use crate::database::{Cache, DB};
+use crate::html::login_page_context::LoginPageContext;
+use crate::language::Language;
+use hiqlite::macros::params;
+use rauthy_api_types::i18n::{I18nOverridableKey, I18nOverride, I18nPage, I18nPageKeysResponse};
+use rauthy_common::constants::{CACHE_TTL_APP, IDX_I18N_OVERRIDES};
+use rauthy_common::is_hiqlite;
+use rauthy_common::utils::{deserialize, serialize};
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// `(key, placeholders)` for each overridable message on the login page
+static KEYS_LOGIN: [(&str, &[&str]); 14] = [
+    ("clientForceMfa", &[]),
+    ("email", &[]),
+    ("emailSentMsg", &[]),
+    ("invalidCredentials", &[]),
+    ("login", &[]),
+    ("needsUserUpdate", &[]),
+    ("notice", &[]),
+    ("orLoginWith", &[]),
+    ("password", &[]),
+    ("passwordForgotten", &[]),
+    ("passwordRequest", &[]),
+    ("passwordResetDesc", &[]),
+    ("passwordResetSuccess", &[]),
+    ("signUp", &[]),
+];
+
+/// `(key, placeholders)` for each overridable message on the account page
+static KEYS_ACCOUNT: [(&str, &[&str]); 14] = [
+    ("account", &[]),
+    ("convertAccountP1", &[]),
+    ("deleteAccount.deleteAccount", &[]),
+    ("deleteAccount.deleteAccountDesc", &[]),
+    ("devicesDesc", &[]),
+    ("emailUpdateConfirm", &[]),
+    ("navEdit", &[]),
+    ("navInfo", &[]),
+    ("navLogout", &[]),
+    ("navMfa", &[]),
+    ("notice", &[]),
+    ("passwordPolicyFollow", &[]),
+    ("providerLinkDesc", &[]),
+    ("providerUnlinkDesc", &[]),
+];
+
+/// `(key, placeholders)` for each overridable message on the device code page
+static KEYS_DEVICE: [(&str, &[&str]); 7] = [
+    ("accept", &[]),
+    ("closeWindow", &[]),
+    ("decline", &[]),
+    ("desc", &["count"]),
+    ("descScopes", &[]),
+    ("submit", &[]),
+    ("title", &[]),
+];
+
+/// A single admin defined translation, which is merged over the built-in one for the same
+/// `key` inside the frontend.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct I18nOverrideEntity {
+    pub page: I18nPage,
+    pub key: String,
+    pub lang: Language,
+    pub value: String,
+}
+
+impl From<I18nOverride> for I18nOverrideEntity {
+    fn from(value: I18nOverride) -> Self {
+        Self {
+            page: value.page,
+            key: value.key,
+            lang: Language::from(value.lang),
+            value: value.value,
+        }
+    }
+}
+
+impl From<I18nOverrideEntity> for I18nOverride {
+    fn from(value: I18nOverrideEntity) -> Self {
+        Self {
+            page: value.page,
+            key: value.key,
+            lang: value.lang.into(),
+            value: value.value,
+        }
+    }
+}
+
+/// All translation overrides are stored as a single value inside the `config` table.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct I18nOverrides {
+    pub overrides: Vec<I18nOverrideEntity>,
+}
+
+// CRUD
+impl I18nOverrides {
+    pub async fn find() -> Result<Self, ErrorResponse> {
+        let client = DB::hql();
+        if let Some(slf) = cache_layer::get(CacheName::I18nOverrides, IDX_I18N_OVERRIDES).await? {
+            return Ok(slf);
+        }
+
+        let sql = "SELECT data FROM config WHERE id = 'i18n_overrides'";
+        let bytes: Option<Vec<u8>> = if is_hiqlite() {
+            client
+                .query_raw(sql, params!())
+                .await?
+                .into_iter()
+                .next()
+                .map(|mut row| row.get("data"))
+        } else {
+            DB::pg_query_rows(sql, &[], 1)
+                .await?
+                .into_iter()
+                .next()
+                .map(|row| row.get("data"))
+        };
+        let slf = match bytes {
+            Some(bytes) => deserialize::<Self>(&bytes)?,
+            None => Self::default(),
+        };
+
+        cache_layer::put(
+            CacheName::I18nOverrides,
+            IDX_I18N_OVERRIDES,
+            &slf,
+            CACHE_TTL_APP,
+        )
+        .await?;
+
+        Ok(slf)
+    }
+
+    /// Validates and replaces all existing overrides.
+    pub async fn save(overrides: Vec<I18nOverrideEntity>) -> Result<Self, ErrorResponse> {
+        let slf = Self::validated(overrides)?;
+        let data = serialize(&slf)?;
+
+        let sql = r#"
+INSERT INTO config (id, data)
+VALUES ('i18n_overrides', $1)
+ON CONFLICT (id) DO UPDATE SET data = $1"#;
+        if is_hiqlite() {
+            DB::hql().execute(sql, params!(data)).await?;
+        } else {
+            DB::pg_execute(sql, &[&data]).await?;
+        }
+
+        // The cache is replicated, which makes sure all HA nodes see the new values
+        // immediately. The pre-rendered HTML contains the overrides and must be rebuilt.
+        let client = DB::hql();
+        cache_layer::put(
+            CacheName::I18nOverrides,
+            IDX_I18N_OVERRIDES,
+            &slf,
+            CACHE_TTL_APP,
+        )
+        .await?;
+        client.clear_cache(Cache::Html).await?;
+        LoginPageContext::invalidate_all().await?;
+
+        Ok(slf)
+    }
+}
+
+impl I18nOverrides {
+    pub fn keys() -> Vec<I18nPageKeysResponse> {
+        [I18nPage::Account, I18nPage::Device, I18nPage::Login]
+            .into_iter()
+            .map(|page| I18nPageKeysResponse {
+                page,
+                keys: Self::page_keys(page)
+                    .iter()
+                    .map(|(key, placeholders)| I18nOverridableKey {
+                        key: key.to_string(),
+                        placeholders: placeholders.iter().map(|p| p.to_string()).collect(),
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Merges the `pending` overrides over the existing ones, for instance for a preview.
+    pub fn merged(mut self, pending: Vec<I18nOverrideEntity>) -> Result<Self, ErrorResponse> {
+        for ovr in pending {
+            self.overrides
+                .retain(|o| !(o.page == ovr.page && o.key == ovr.key && o.lang == ovr.lang));
+            self.overrides.push(ovr);
+        }
+        Self::validated(self.overrides)
+    }
+
+    /// Returns the overrides for the given `page` and `lang` as a JSON object, which maps the full
+    /// i18n path inside the frontend to the value. The JSON is safe to be rendered into HTML.
+    pub fn template_json(&self, page: I18nPage, lang: &Language) -> String {
+        let prefix = Self::frontend_section(page);
+        let values = self
+            .overrides
+            .iter()
+            .filter(|o| o.page == page && &o.lang == lang)
+            .map(|o| (format!("{prefix}.{}", o.key), o.value.as_str()))
+            .collect::<BTreeMap<_, _>>();
+
+        serde_json::to_string(&values)
+            .unwrap()
+            .replace('<', "\\u003c")
+            .replace('>', "\\u003e")
+            .replace('&', "\\u0026")
+    }
+
+    fn frontend_section(page: I18nPage) -> &'static str {
+        match page {
+            I18nPage::Account => "account",
+            I18nPage::Device => "device",
+            I18nPage::Login => "authorize",
+        }
+    }
+
+    fn page_keys(page: I18nPage) -> &'static [(&'static str, &'static [&'static str])] {
+        match page {
+            I18nPage::Account => &KEYS_ACCOUNT,
+            I18nPage::Device => &KEYS_DEVICE,
+            I18nPage::Login => &KEYS_LOGIN,
+        }
+    }
+
+    fn validated(overrides: Vec<I18nOverrideEntity>) -> Result<Self, ErrorResponse> {
+        for (i, ovr) in overrides.iter().enumerate() {
+            let Some((_, placeholders)) = Self::page_keys(ovr.page)
+                .iter()
+                .find(|(key, _)| *key == ovr.key)
+            else {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    format!(
+                        "'{}' cannot be overridden for page '{}'",
+                        ovr.key,
+                        Self::frontend_section(ovr.page)
+                    ),
+                ));
+            };
+
+            validate_placeholders(&ovr.value, placeholders).map_err(|err| {
+                ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    format!("Invalid value for '{}': {err}", ovr.key),
+                )
+            })?;
+
+            if overrides[..i]
+                .iter()
+                .any(|o| o.page == ovr.page && o.key == ovr.key && o.lang == ovr.lang)
+            {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    format!("Duplicate override for '{}' in '{}'", ovr.key, ovr.lang),
+                ));
+            }
+        }
+
+        Ok(Self { overrides })
+    }
+}
+
+/// Makes sure that the `value` contains each of the `allowed` placeholders in the format
+/// `{{name}}` and no other ones.
+fn validate_placeholders(value: &str, allowed: &[&str]) -> Result<(), String> {
+    let mut found = Vec::with_capacity(allowed.len());
+
+    let mut rest = value;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            return Err("unclosed placeholder".to_string());
+        };
+        let name = &rest[start + 2..start + end];
+        if !allowed.contains(&name) {
+            return Err(format!("unknown placeholder '{{{{{name}}}}}'"));
+        }
+        found.push(name);
+        rest = &rest[start + end + 2..];
+    }
+
+    if let Some(missing) = allowed.iter().find(|p| !found.contains(p)) {
+        return Err(format!("missing placeholder '{{{{{missing}}}}}'"));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn ovr(page: I18nPage, key: &str, lang: Language, value: &str) -> I18nOverrideEntity {
+        I18nOverrideEntity {
+            page,
+            key: key.to_string(),
+            lang,
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_validate_placeholders() {
+        assert!(validate_placeholders("Hello", &[]).is_ok());
+        assert!(validate_placeholders("Enter the {{count}} characters", &["count"]).is_ok());
+
+        assert_eq!(
+            validate_placeholders("Enter the characters", &["count"]),
+            Err("missing placeholder '{{count}}'".to_string())
+        );
+        assert_eq!(
+            validate_placeholders("Hello {{name}}", &[]),
+            Err("unknown placeholder '{{name}}'".to_string())
+        );
+        assert!(validate_placeholders("Hello {{count", &["count"]).is_err());
+    }
+
+    #[test]
+    fn test_i18n_overrides() {
+        let notice = ovr(
+            I18nPage::Login,
+            "notice",
+            Language::De,
+            "Impressum & <Hinweis>",
+        );
+
+        // unknown keys and duplicates are rejected
+        assert!(
+            I18nOverrides::validated(vec![ovr(I18nPage::Login, "nope", Language::De, "x")])
+                .is_err()
+        );
+        assert!(I18nOverrides::validated(vec![notice.clone(), notice.clone()]).is_err());
+
+        let slf = I18nOverrides::validated(vec![
+            notice.clone(),
+            ovr(I18nPage::Login, "login", Language::En, "Sign in"),
+        ])
+        .unwrap();
+
+        // missing overrides fall back to the built-ins inside the frontend
+        assert_eq!(slf.template_json(I18nPage::Account, &Language::De), "{}");
+        let json = slf.template_json(I18nPage::Login, &Language::De);
+        assert!(!json.contains('<'));
+        assert!(!json.contains('&'));
+        let values: BTreeMap<String, String> = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            values.get("authorize.notice").unwrap(),
+            "Impressum & <Hinweis>"
+        );
+        assert_eq!(values.len(), 1);
+
+        // pending values replace existing ones for the preview
+        let slf = slf
+            .merged(vec![ovr(I18nPage::Login, "notice", Language::De, "Neu")])
+            .unwrap();
+        assert_eq!(slf.overrides.len(), 2);
+        assert_eq!(
+            slf.template_json(I18nPage::Login, &Language::De),
+            r#"{"authorize.notice":"Neu"}"#
+        );
+    }
+}
@@ -5,8 +5,9 @@ use actix_web::web;
 use cryptr::{EncKeys, EncValue};
 use ed25519_compact::Noise;
 use hiqlite::macros::params;
+use openssl::pkey::{Id, PKey};
 use postgres_types::Type;
-use rauthy_api_types::oidc::{JWKSCerts, JWKSPublicKeyCerts};
+use rauthy_api_types::oidc::{JWKSCerts, JWKSPublicKeyCerts, JwkImportKeyType, JwkImportRequest};
 use rauthy_common::constants::{
     APPLICATION_JSON, CACHE_TTL_APP, IDX_JWK_KID, IDX_JWK_LATEST, IDX_JWKS,
 };
@@ -38,14 +39,18 @@ pub struct Jwk {
     pub signature: JwkKeyPairAlg,
     pub enc_key_id: String,
     pub jwk: Vec<u8>,
+    /// Inactive keys are published in the JWKS for token validation, but never used for signing
+    /// until they are promoted.
+    pub inactive: bool,
 }
 
 impl Debug for Jwk {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "Jwk {{ kid: {}, created_at: {}, signature: {:?}, enc_key_id: {}, jwk: <hidden> }}",
-            self.kid, self.created_at, self.signature, self.enc_key_id,
+            "Jwk {{ kid: {}, created_at: {}, signature: {:?}, enc_key_id: {}, jwk: <hidden>, \
+            inactive: {} }}",
+            self.kid, self.created_at, self.signature, self.enc_key_id, self.inactive,
         )
     }
 }
@@ -55,8 +60,8 @@ impl Jwk {
     pub async fn save(&self) -> Result<(), ErrorResponse> {
         let sig_str = self.signature.as_str();
         let sql = r#"
-INSERT INTO jwks (kid, created_at, signature, enc_key_id, jwk)
-VALUES ($1, $2, $3, $4, $5)"#;
+INSERT INTO jwks (kid, created_at, signature, enc_key_id, jwk, inactive)
+VALUES ($1, $2, $3, $4, $5, $6)"#;
 
         if is_hiqlite() {
             DB::hql()
@@ -67,7 +72,8 @@ VALUES ($1, $2, $3, $4, $5)"#;
                         self.created_at,
                         sig_str,
                         self.enc_key_id.clone(),
-                        self.jwk.clone()
+                        self.jwk.clone(),
+                        self.inactive
                     ),
                 )
                 .await?;
@@ -80,6 +86,7 @@ VALUES ($1, $2, $3, $4, $5)"#;
                     &sig_str,
                     &self.enc_key_id,
                     &self.jwk,
+                    &self.inactive,
                 ],
             )
             .await?;
@@ -90,6 +97,136 @@ VALUES ($1, $2, $3, $4, $5)"#;
 }
 
 impl Jwk {
+    /// Imports an externally generated key pair. The key will be `inactive` and only published
+    /// for token validation until it is promoted, which allows an overlap with the current key.
+    pub async fn import(payload: JwkImportRequest) -> Result<Self, ErrorResponse> {
+        let (signature, der) = web::block(move || Self::validate_import(&payload)).await??;
+
+        let jwk = EncValue::encrypt(&der)?.into_bytes().to_vec();
+        let slf = Self {
+            kid: get_rand(24),
+            created_at: OffsetDateTime::now_utc().unix_timestamp(),
+            signature,
+            enc_key_id: EncKeys::get_static().enc_key_active.to_string(),
+            jwk,
+            inactive: true,
+        };
+        slf.save().await?;
+
+        DB::hql().delete(Cache::App, IDX_JWKS).await?;
+
+        Ok(slf)
+    }
+
+    /// Promotes the key with the given `kid` to be the current one for its algorithm. The
+    /// currently active key will be retiring afterward, exactly like after a rotation.
+    pub async fn promote(kid: String) -> Result<Self, ErrorResponse> {
+        let sql = "SELECT * FROM jwks WHERE kid = $1";
+        let mut slf: Self = if is_hiqlite() {
+            DB::hql().query_as_one(sql, params!(kid.clone())).await?
+        } else {
+            DB::pg_query_one(sql, &[&kid]).await?
+        };
+
+        if let Ok(current) = JwkKeyPair::find_latest(slf.signature.clone()).await
+            && current.kid == slf.kid
+        {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "This JWK is the current one already",
+            ));
+        }
+
+        slf.created_at = OffsetDateTime::now_utc().unix_timestamp();
+        slf.inactive = false;
+
+        let sql = "UPDATE jwks SET created_at = $1, inactive = false WHERE kid = $2";
+        if is_hiqlite() {
+            DB::hql().execute(sql, params!(slf.created_at, kid)).await?;
+        } else {
+            DB::pg_execute(sql, &[&slf.created_at, &kid]).await?;
+        }
+
+        let client = DB::hql();
+        client
+            .delete(
+                Cache::App,
+                format!("{IDX_JWK_LATEST}{}", slf.signature.as_str()),
+            )
+            .await?;
+        client.delete(Cache::App, IDX_JWKS).await?;
+
+        info!(
+            "Promoted JWK {} to be the current {}",
+            slf.kid, slf.signature
+        );
+        RauthyConfig::get()
+            .tx_events
+            .send_async(Event::jwks_rotated())
+            .await
+            .unwrap();
+
+        Ok(slf)
+    }
+
+    /// Validates imported PEMs and returns the algorithm together with the private key as
+    /// PKCS#8 DER, which is the format all other JWKs are saved in.
+    fn validate_import(
+        payload: &JwkImportRequest,
+    ) -> Result<(JwkKeyPairAlg, Vec<u8>), ErrorResponse> {
+        let private =
+            PKey::private_key_from_pem(payload.private_key_pem.as_bytes()).map_err(|_| {
+                ErrorResponse::new(ErrorResponseType::BadRequest, "Invalid private key")
+            })?;
+        let public = PKey::public_key_from_pem(payload.public_key_pem.as_bytes())
+            .map_err(|_| ErrorResponse::new(ErrorResponseType::BadRequest, "Invalid public key"))?;
+        if !private.public_eq(&public) {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "The public key does not belong to the private key",
+            ));
+        }
+
+        // RSA keys are mapped to the algorithm by their size, the same way as Rauthy generates
+        // them during a rotation.
+        let alg = match (&payload.key_type, private.id()) {
+            (JwkImportKeyType::EC, _) => {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    "EC keys are not supported for token signing",
+                ));
+            }
+            (JwkImportKeyType::RSA, Id::RSA) => match private.bits() {
+                ..2048 => {
+                    return Err(ErrorResponse::new(
+                        ErrorResponseType::BadRequest,
+                        "RSA keys must have at least 2048 bits",
+                    ));
+                }
+                2048..3072 => JwkKeyPairAlg::RS256,
+                3072..4096 => JwkKeyPairAlg::RS384,
+                _ => JwkKeyPairAlg::RS512,
+            },
+            (JwkImportKeyType::Ed25519, Id::ED25519) => JwkKeyPairAlg::EdDSA,
+            _ => {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    "`key_type` does not match the given key",
+                ));
+            }
+        };
+
+        // make sure that we can actually sign with the converted key later on
+        let kp = JwkKeyPair {
+            kid: String::default(),
+            typ: alg,
+            bytes: private.private_key_to_pkcs8()?,
+        };
+        kp.sign(b"rauthy")?;
+
+        Ok((kp.typ, kp.bytes))
+    }
+
     pub fn new(
         kid: String,
         created_at: time::OffsetDateTime,
@@ -104,6 +241,7 @@ impl Jwk {
             signature,
             enc_key_id,
             jwk,
+            inactive: false,
         }
     }
 }
@@ -182,6 +320,7 @@ impl JWKS {
             signature: JwkKeyPairAlg::RS256,
             enc_key_id: enc_key_active.to_string(),
             jwk,
+            inactive: false,
         };
         entity.save().await?;
 
@@ -203,6 +342,7 @@ impl JWKS {
             signature: JwkKeyPairAlg::RS384,
             enc_key_id: enc_key_active.to_string(),
             jwk,
+            inactive: false,
         };
         entity.save().await?;
 
@@ -224,6 +364,7 @@ impl JWKS {
             signature: JwkKeyPairAlg::RS512,
             enc_key_id: enc_key_active.to_string(),
             jwk,
+            inactive: false,
         };
         entity.save().await?;
 
@@ -238,6 +379,7 @@ impl JWKS {
             signature: JwkKeyPairAlg::EdDSA,
             enc_key_id: enc_key_active.to_string(),
             jwk,
+            inactive: false,
         };
         entity.save().await?;
 
@@ -303,7 +445,8 @@ pub struct JWKSPublicKey {
     pub kid: Option<String>,
     pub n: Option<String>, // RSA
     pub e: Option<String>, // RSA
-    pub x: Option<String>, // OKP
+    pub x: Option<String>, // OKP + EC
+    pub y: Option<String>, // EC
 }
 
 impl JWKSPublicKey {
@@ -357,6 +500,18 @@ impl JWKSPublicKey {
         }
     }
 
+    #[inline]
+    pub fn y(&self) -> Result<Vec<u8>, ErrorResponse> {
+        if let Some(y) = &self.y {
+            Ok(base64_url_no_pad_decode(y)?)
+        } else {
+            Err(ErrorResponse::new(
+                ErrorResponseType::Internal,
+                "No 'y' in JwkKeyPublicKey",
+            ))
+        }
+    }
+
     #[inline]
     pub fn from_key_pair(key_pair: &JwkKeyPair) -> Result<Self, ErrorResponse> {
         let slf = match key_pair.typ {
@@ -371,6 +526,7 @@ impl JWKSPublicKey {
                     n: Some(base64_url_encode(&pubkey.n().to_bytes_be())),
                     e: Some(base64_url_encode(&pubkey.e().to_bytes_be())),
                     x: None,
+                    y: None,
                 }
             }
             JwkKeyPairAlg::EdDSA => {
@@ -384,8 +540,10 @@ impl JWKSPublicKey {
                     n: None,
                     e: None,
                     x: Some(x),
+                    y: None,
                 }
             }
+            JwkKeyPairAlg::ES256 => return Err(JwkKeyPairAlg::err_remote_only()),
         };
 
         Ok(slf)
@@ -498,6 +656,24 @@ impl JWKSPublicKey {
                     self.kty.as_str(),
                 )
             }
+
+            JwkKeyPairType::EC => {
+                if self.crv.is_none() || self.x.is_none() || self.y.is_none() {
+                    return Err(ErrorResponse::new(
+                        ErrorResponseType::Internal,
+                        "Incorrect format for EC JWK: crv / x / y missing",
+                    ));
+                }
+
+                // mandatory keys for EC are in order: crv, kty, x, y
+                let crv = self.crv.as_deref().unwrap();
+                let x = self.x.as_deref().unwrap();
+                let y = self.y.as_deref().unwrap();
+                format!(
+                    "{{\"crv\":\"{crv}\",\"kty\":\"{}\",\"x\":\"{x}\",\"y\":\"{y}\"}}",
+                    self.kty.as_str(),
+                )
+            }
         };
 
         let hash = hmac_sha256::Hash::hash(s.as_bytes());
@@ -515,10 +691,10 @@ impl JWKSPublicKey {
             Some(alg) => {
                 match self.kty {
                     JwkKeyPairType::RSA => {
-                        if alg == &JwkKeyPairAlg::EdDSA {
+                        if alg == &JwkKeyPairAlg::EdDSA || alg == &JwkKeyPairAlg::ES256 {
                             return Err(ErrorResponse::new(
                                 ErrorResponseType::BadRequest,
-                                "RSA kty cannot have EdDSA alg",
+                                format!("RSA kty cannot have {alg} alg"),
                             ));
                         }
 
@@ -529,10 +705,10 @@ impl JWKSPublicKey {
                             ));
                         }
 
-                        if self.x.is_some() {
+                        if self.x.is_some() || self.y.is_some() {
                             return Err(ErrorResponse::new(
                                 ErrorResponseType::BadRequest,
-                                "RSA key cannot have 'x' public key component",
+                                "RSA key cannot have 'x' or 'y' public key component",
                             ));
                         }
                     }
@@ -573,6 +749,43 @@ impl JWKSPublicKey {
                                 "OKP key must have 'x' public key component",
                             ));
                         }
+
+                        if self.y.is_some() {
+                            return Err(ErrorResponse::new(
+                                ErrorResponseType::BadRequest,
+                                "OKP key cannot have 'y' public key component",
+                            ));
+                        }
+                    }
+
+                    JwkKeyPairType::EC => {
+                        if alg != &JwkKeyPairAlg::ES256 {
+                            return Err(ErrorResponse::new(
+                                ErrorResponseType::BadRequest,
+                                "EC kty must have ES256 alg",
+                            ));
+                        }
+
+                        if self.crv.as_deref() != Some("P-256") {
+                            return Err(ErrorResponse::new(
+                                ErrorResponseType::BadRequest,
+                                "Only 'P-256' for EC 'crv' is supported",
+                            ));
+                        }
+
+                        if self.n.is_some() || self.e.is_some() {
+                            return Err(ErrorResponse::new(
+                                ErrorResponseType::BadRequest,
+                                "EC key cannot have 'n' or 'e' public key components",
+                            ));
+                        }
+
+                        if self.x.is_none() || self.y.is_none() {
+                            return Err(ErrorResponse::new(
+                                ErrorResponseType::BadRequest,
+                                "EC key must have 'x' and 'y' public key components",
+                            ));
+                        }
                     }
                 }
 
@@ -587,12 +800,14 @@ impl From<JWKSPublicKey> for JWKSPublicKeyCerts {
         let kty = match pk.kty {
             JwkKeyPairType::RSA => rauthy_api_types::oidc::JwkKeyPairType::RSA,
             JwkKeyPairType::OKP => rauthy_api_types::oidc::JwkKeyPairType::OKP,
+            JwkKeyPairType::EC => unreachable!("EC keys are never part of Rauthy's own JWKS"),
         };
         let alg = match pk.alg.unwrap_or_default() {
             JwkKeyPairAlg::RS256 => rauthy_api_types::oidc::JwkKeyPairAlg::RS256,
             JwkKeyPairAlg::RS384 => rauthy_api_types::oidc::JwkKeyPairAlg::RS384,
             JwkKeyPairAlg::RS512 => rauthy_api_types::oidc::JwkKeyPairAlg::RS512,
             JwkKeyPairAlg::EdDSA => rauthy_api_types::oidc::JwkKeyPairAlg::EdDSA,
+            JwkKeyPairAlg::ES256 => unreachable!("ES256 keys are never part of Rauthy's own JWKS"),
         };
 
         Self {
@@ -654,6 +869,7 @@ impl JwkKeyPair {
                 typ: JwkKeyPairAlg::EdDSA,
                 bytes: jwk_decrypted,
             },
+            JwkKeyPairAlg::ES256 => return Err(JwkKeyPairAlg::err_remote_only()),
         };
 
         Ok(res)
@@ -695,7 +911,7 @@ impl JwkKeyPair {
         let signature = key_pair_alg.as_str().to_string();
         let sql = r#"
 SELECT * FROM jwks
-WHERE signature = $1
+WHERE signature = $1 AND inactive = false
 ORDER BY created_at DESC
 LIMIT 1"#;
 
@@ -741,6 +957,8 @@ impl JwkKeyPair {
                 let sig = key.sign(input, Some(Noise::generate()));
                 Ok(sig.to_vec())
             }
+
+            JwkKeyPairAlg::ES256 => Err(JwkKeyPairAlg::err_remote_only()),
         }
     }
 
@@ -822,6 +1040,8 @@ impl JwkKeyPair {
                     return Ok(message);
                 }
             }
+
+            JwkKeyPairAlg::ES256 => return Err(JwkKeyPairAlg::err_remote_only()),
         };
 
         warn!("JWT Token validation error");
@@ -837,6 +1057,7 @@ pub enum JwkKeyPairType {
     RSA,
     #[default]
     OKP,
+    EC,
 }
 
 impl JwkKeyPairType {
@@ -845,6 +1066,7 @@ impl JwkKeyPairType {
         match self {
             JwkKeyPairType::RSA => "RSA",
             JwkKeyPairType::OKP => "OKP",
+            JwkKeyPairType::EC => "EC",
         }
     }
 }
@@ -856,6 +1078,9 @@ pub enum JwkKeyPairAlg {
     RS512,
     #[default]
     EdDSA,
+    /// Only used to validate tokens signed by remote keys, e.g. from an upstream auth provider.
+    /// Rauthy itself never signs with it, which is why `from_str()` rejects it.
+    ES256,
 }
 
 impl From<&mut hiqlite::Row<'_>> for JwkKeyPairAlg {
@@ -899,8 +1124,30 @@ impl JwkKeyPairAlg {
             JwkKeyPairAlg::RS384 => "RS384",
             JwkKeyPairAlg::RS512 => "RS512",
             JwkKeyPairAlg::EdDSA => "EdDSA",
+            JwkKeyPairAlg::ES256 => "ES256",
+        }
+    }
+
+    /// Parses the `alg` from the header of a token signed by a remote key, which may use an
+    /// algorithm that Rauthy does not sign with itself.
+    pub fn from_remote(alg: &str) -> Result<Self, ErrorResponse> {
+        match alg {
+            "ES256" => Ok(Self::ES256),
+            "none" => Err(ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                "Unsigned tokens are not accepted",
+            )),
+            alg => Self::from_str(alg),
         }
     }
+
+    #[inline]
+    fn err_remote_only() -> ErrorResponse {
+        ErrorResponse::new(
+            ErrorResponseType::Internal,
+            "ES256 is only supported for the validation of remote tokens",
+        )
+    }
 }
 
 impl Display for JwkKeyPairAlg {
@@ -927,6 +1174,18 @@ impl FromStr for JwkKeyPairAlg {
     }
 }
 
+impl From<rauthy_api_types::oidc::JwkKeyPairAlg> for JwkKeyPairAlg {
+    #[inline]
+    fn from(value: rauthy_api_types::oidc::JwkKeyPairAlg) -> Self {
+        match value {
+            rauthy_api_types::oidc::JwkKeyPairAlg::RS256 => Self::RS256,
+            rauthy_api_types::oidc::JwkKeyPairAlg::RS384 => Self::RS384,
+            rauthy_api_types::oidc::JwkKeyPairAlg::RS512 => Self::RS512,
+            rauthy_api_types::oidc::JwkKeyPairAlg::EdDSA => Self::EdDSA,
+        }
+    }
+}
+
 impl From<JwkKeyPairAlg> for rauthy_api_types::oidc::JwkKeyPairAlg {
     #[inline]
     fn from(value: JwkKeyPairAlg) -> Self {
@@ -935,6 +1194,7 @@ impl From<JwkKeyPairAlg> for rauthy_api_types::oidc::JwkKeyPairAlg {
             JwkKeyPairAlg::RS384 => Self::RS384,
             JwkKeyPairAlg::RS512 => Self::RS512,
             JwkKeyPairAlg::EdDSA => Self::EdDSA,
+            JwkKeyPairAlg::ES256 => unreachable!("Rauthy never signs with ES256"),
         }
     }
 }
@@ -954,6 +1214,7 @@ mod tests {
             n: Some("0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw".to_string()),
             e: Some("AQAB".to_string()),
             x: None,
+            y: None,
         }.fingerprint().unwrap();
         assert_eq!(tp.as_str(), "NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs");
 
@@ -965,6 +1226,7 @@ mod tests {
             n: Some("0OJuIbD0k90-Xod2cnqcGWu0xP4Z3Eyfi3CXBxdzlEwFHSNat6Vjts2g5Uzbdvmgm2ys-UWUaCcw2zPEbn25dtcv0MVK26J71OV0Q38yB701SniEJqLXf3OehSR7lfd9HNasZF_-2u6oJMwvKLe10qlSGYLzeUCWIV4LDPDv7lxsWFx0WntgLlHpKfVmYuvW_AQ1Q8XSO53K4Xk3n84zzAXvCUyW8Z4tmE4tc3ibriHH63AYpKbB8oDR-zhbIoGHtZnDdRo02JvS11KNINLdmMOE2zre7hPgXVbgnYS9qbpz4nsc4sPCiGclM2c2faSkwyxI60Ng6272e3fIEkBTKtYidoaG00tM1j42kD-b7bNjWJIsY92F15SdRA4stpic2KcAnyphNrLeDMKd_c-h3PC22eR-a8pb5nE1VvDSagn9g8WE3TSMEJxEmAgVcOcldSV9EDpSz4uk2CqRdytwAZOnRDEwehnRQiLNiwgyNEygLAcaVWDR8ym8ARRLWCRL".to_string()),
             e: Some("AQAB".to_string()),
             x: None,
+            y: None,
         }.fingerprint().unwrap();
         assert_eq!(tp.as_str(), "EunK2QL42BZ2Eb4urUxXiFFomdjus4UtGB9qJ8Vnjtw");
 
@@ -976,6 +1238,7 @@ mod tests {
             n: Some("1UjNug4a3OEo8saHbM14jhEqpgRHvjMaQ0lB_1rRuK4yMNPLxhdes8PcMXfEuCOYrC4jxkeVb31QgM5OFwxRtyBT-T1SmiWCtXX2beFtRrvZcGYQrd_LooKLrcjww-P8atQBBYKgf82e9aqb5I-4BFYTBdDQ5lQKQtZDwiU-lUVYP103SphHQMkkWLKsC7oFcthN2m8IliQnJ3-XeqgYt9dc6AszDEjNTDZMeC-HWwRXI9JGYjIgNIZj_u0n6UgaqhdjR1sEHxRGI_t6xQX_L9zRecdDM6-e_lNxIaeROZJ2FU-t9GmZZWyyDWUHk7tk4dS1cU5CdtwvL75dXMHsmwyTs8QK9YUvCWmLeCp6JNPOpCalwyW8YcqJphINhKgonsMinxWLPlO4jtSXKzrpGDLxOF_8xVMW3gNmnIWuUY0_29p7-DzdVm44GEYhQRNNX7yh850uYpwoi42fFvXa5wXm6Hy5QHh_Aqv3tTZgG2f20xCKOzzGzWB28BdJJa9EPu2WLrxaPbn8Qi536979UvMhlZsnUc4fW3TSy20coMb1NIatZaJCDu-uQuGFz7FHBFWjJV6fjF7gqiNqu8cZTeOedGjMitdCnMtOjCz8SASphF12_opWTvtFjq0IMNo4kR8zgZQ24Kt2o2qDhH7fYJI1cLj0RBGDCUU3AlozG_U".to_string()),
             e: Some("AQAB".to_string()),
             x: None,
+            y: None,
         }.fingerprint().unwrap();
         assert_eq!(tp.as_str(), "rSJa_34h-WFCVMoSG7ORvEvxhF45iCvcm1FRZlxSRio");
 
@@ -987,6 +1250,7 @@ mod tests {
             n: None,
             e: None,
             x: Some("suwfa9fyMHqS0yOh9T-Bsdkji0naFVRRGZFBNrGX_RQ".to_string()),
+            y: None,
         }
         .fingerprint()
         .unwrap();
@@ -1004,6 +1268,7 @@ mod tests {
             n: Some("r5Xn8yuwc7ekL5NLFnBw76cRUiYbIQqNgPq6XYw6_Mgle3BSJ-UTKTWjGLDoTSlFC7k2xCZNOt8pqix2R_qoGwlNo8kYXlgMpAEo00rSKoG1RO1PMj1M_--swijR8l1bnb-VfIPgT_kM3zv7RLPLEEjYHMuT7N5liFVq1Xh-So8i3X1UeWGHyJPHjF5koB_XO1vleYQCZQeGFaomJgrFJsxdmtFueJaMEMQ1-mPwuPjvSwOtMMAu0nO9DJm3-xwkygPqGmEbbDHLeEO1dEOlDdEYlYle5Pa70FGinCBqaAl7lDaJ1umAvpcLBUHtFOM7VBmt-xUjzOU7VDPareR6Ww".to_string()),
             e: Some("AQAB".to_string()),
             x: None,
+            y: None,
         }.validate_self().unwrap();
 
         JWKSPublicKey {
@@ -1014,6 +1279,7 @@ mod tests {
             n: Some("0OJuIbD0k90-Xod2cnqcGWu0xP4Z3Eyfi3CXBxdzlEwFHSNat6Vjts2g5Uzbdvmgm2ys-UWUaCcw2zPEbn25dtcv0MVK26J71OV0Q38yB701SniEJqLXf3OehSR7lfd9HNasZF_-2u6oJMwvKLe10qlSGYLzeUCWIV4LDPDv7lxsWFx0WntgLlHpKfVmYuvW_AQ1Q8XSO53K4Xk3n84zzAXvCUyW8Z4tmE4tc3ibriHH63AYpKbB8oDR-zhbIoGHtZnDdRo02JvS11KNINLdmMOE2zre7hPgXVbgnYS9qbpz4nsc4sPCiGclM2c2faSkwyxI60Ng6272e3fIEkBTKtYidoaG00tM1j42kD-b7bNjWJIsY92F15SdRA4stpic2KcAnyphNrLeDMKd_c-h3PC22eR-a8pb5nE1VvDSagn9g8WE3TSMEJxEmAgVcOcldSV9EDpSz4uk2CqRdytwAZOnRDEwehnRQiLNiwgyNEygLAcaVWDR8ym8ARRLWCRL".to_string()),
             e: Some("AQAB".to_string()),
             x: None,
+            y: None,
         }.validate_self().unwrap();
 
         JWKSPublicKey {
@@ -1024,6 +1290,7 @@ mod tests {
             n: Some("1UjNug4a3OEo8saHbM14jhEqpgRHvjMaQ0lB_1rRuK4yMNPLxhdes8PcMXfEuCOYrC4jxkeVb31QgM5OFwxRtyBT-T1SmiWCtXX2beFtRrvZcGYQrd_LooKLrcjww-P8atQBBYKgf82e9aqb5I-4BFYTBdDQ5lQKQtZDwiU-lUVYP103SphHQMkkWLKsC7oFcthN2m8IliQnJ3-XeqgYt9dc6AszDEjNTDZMeC-HWwRXI9JGYjIgNIZj_u0n6UgaqhdjR1sEHxRGI_t6xQX_L9zRecdDM6-e_lNxIaeROZJ2FU-t9GmZZWyyDWUHk7tk4dS1cU5CdtwvL75dXMHsmwyTs8QK9YUvCWmLeCp6JNPOpCalwyW8YcqJphINhKgonsMinxWLPlO4jtSXKzrpGDLxOF_8xVMW3gNmnIWuUY0_29p7-DzdVm44GEYhQRNNX7yh850uYpwoi42fFvXa5wXm6Hy5QHh_Aqv3tTZgG2f20xCKOzzGzWB28BdJJa9EPu2WLrxaPbn8Qi536979UvMhlZsnUc4fW3TSy20coMb1NIatZaJCDu-uQuGFz7FHBFWjJV6fjF7gqiNqu8cZTeOedGjMitdCnMtOjCz8SASphF12_opWTvtFjq0IMNo4kR8zgZQ24Kt2o2qDhH7fYJI1cLj0RBGDCUU3AlozG_U".to_string()),
             e: Some("AQAB".to_string()),
             x: None,
+            y: None,
         }.validate_self().unwrap();
 
         JWKSPublicKey {
@@ -1034,6 +1301,7 @@ mod tests {
             n: None,
             e: None,
             x: Some("suwfa9fyMHqS0yOh9T-Bsdkji0naFVRRGZFBNrGX_RQ".to_string()),
+            y: None,
         }
         .validate_self()
         .unwrap();
@@ -1047,6 +1315,7 @@ mod tests {
             n: Some("r5Xn8yuwc7ekL5NLFnBw76cRUiYbIQqNgPq6XYw6_Mgle3BSJ-UTKTWjGLDoTSlFC7k2xCZNOt8pqix2R_qoGwlNo8kYXlgMpAEo00rSKoG1RO1PMj1M_--swijR8l1bnb-VfIPgT_kM3zv7RLPLEEjYHMuT7N5liFVq1Xh-So8i3X1UeWGHyJPHjF5koB_XO1vleYQCZQeGFaomJgrFJsxdmtFueJaMEMQ1-mPwuPjvSwOtMMAu0nO9DJm3-xwkygPqGmEbbDHLeEO1dEOlDdEYlYle5Pa70FGinCBqaAl7lDaJ1umAvpcLBUHtFOM7VBmt-xUjzOU7VDPareR6Ww".to_string()),
             e: Some("AQAB".to_string()),
             x: None,
+            y: None,
         }.validate_self();
         assert!(key.is_err());
 
@@ -1058,6 +1327,7 @@ mod tests {
             n: Some("r5Xn8yuwc7ekL5NLFnBw76cRUiYbIQqNgPq6XYw6_Mgle3BSJ-UTKTWjGLDoTSlFC7k2xCZNOt8pqix2R_qoGwlNo8kYXlgMpAEo00rSKoG1RO1PMj1M_--swijR8l1bnb-VfIPgT_kM3zv7RLPLEEjYHMuT7N5liFVq1Xh-So8i3X1UeWGHyJPHjF5koB_XO1vleYQCZQeGFaomJgrFJsxdmtFueJaMEMQ1-mPwuPjvSwOtMMAu0nO9DJm3-xwkygPqGmEbbDHLeEO1dEOlDdEYlYle5Pa70FGinCBqaAl7lDaJ1umAvpcLBUHtFOM7VBmt-xUjzOU7VDPareR6Ww".to_string()),
             e: Some("AQAB".to_string()),
             x: None,
+            y: None,
         }.validate_self();
         assert!(key.is_err());
 
@@ -1069,6 +1339,7 @@ mod tests {
             n: Some("r5Xn8yuwc7ekL5NLFnBw76cRUiYbIQqNgPq6XYw6_Mgle3BSJ-UTKTWjGLDoTSlFC7k2xCZNOt8pqix2R_qoGwlNo8kYXlgMpAEo00rSKoG1RO1PMj1M_--swijR8l1bnb-VfIPgT_kM3zv7RLPLEEjYHMuT7N5liFVq1Xh-So8i3X1UeWGHyJPHjF5koB_XO1vleYQCZQeGFaomJgrFJsxdmtFueJaMEMQ1-mPwuPjvSwOtMMAu0nO9DJm3-xwkygPqGmEbbDHLeEO1dEOlDdEYlYle5Pa70FGinCBqaAl7lDaJ1umAvpcLBUHtFOM7VBmt-xUjzOU7VDPareR6Ww".to_string()),
             e: Some("AQAB".to_string()),
             x: None,
+            y: None,
         }.validate_self();
         assert!(key.is_err());
 
@@ -1080,6 +1351,7 @@ mod tests {
             n: None,
             e: Some("AQAB".to_string()),
             x: None,
+            y: None,
         }
         .validate_self();
         assert!(key.is_err());
@@ -1092,6 +1364,7 @@ mod tests {
             n: Some("r5Xn8yuwc7ekL5NLFnBw76cRUiYbIQqNgPq6XYw6_Mgle3BSJ-UTKTWjGLDoTSlFC7k2xCZNOt8pqix2R_qoGwlNo8kYXlgMpAEo00rSKoG1RO1PMj1M_--swijR8l1bnb-VfIPgT_kM3zv7RLPLEEjYHMuT7N5liFVq1Xh-So8i3X1UeWGHyJPHjF5koB_XO1vleYQCZQeGFaomJgrFJsxdmtFueJaMEMQ1-mPwuPjvSwOtMMAu0nO9DJm3-xwkygPqGmEbbDHLeEO1dEOlDdEYlYle5Pa70FGinCBqaAl7lDaJ1umAvpcLBUHtFOM7VBmt-xUjzOU7VDPareR6Ww".to_string()),
             e: None,
             x: None,
+            y: None,
         }
             .validate_self();
         assert!(key.is_err());
@@ -1104,6 +1377,7 @@ mod tests {
             n: None,
             e: None,
             x: None,
+            y: None,
         }
         .validate_self();
         assert!(key.is_err());
@@ -1116,6 +1390,7 @@ mod tests {
             n: None,
             e: None,
             x: None,
+            y: None,
         }
         .validate_self();
         assert!(key.is_err());
@@ -1128,6 +1403,7 @@ mod tests {
             n: Some("n".to_string()),
             e: None,
             x: None,
+            y: None,
         }
         .validate_self();
         assert!(key.is_err());
@@ -1140,6 +1416,7 @@ mod tests {
             n: Some("n".to_string()),
             e: None,
             x: Some("suwfa9fyMHqS0yOh9T-Bsdkji0naFVRRGZFBNrGX_RQ".to_string()),
+            y: None,
         }
         .validate_self();
         assert!(key.is_err());
@@ -1152,8 +1429,63 @@ mod tests {
             n: None,
             e: Some("e".to_string()),
             x: Some("suwfa9fyMHqS0yOh9T-Bsdkji0naFVRRGZFBNrGX_RQ".to_string()),
+            y: None,
         }
         .validate_self();
         assert!(key.is_err());
     }
+
+    #[test]
+    fn test_jwk_validate_self_ec() {
+        let valid = JWKSPublicKey {
+            kty: JwkKeyPairType::EC,
+            alg: Some(JwkKeyPairAlg::ES256),
+            crv: Some("P-256".to_string()),
+            kid: None,
+            n: None,
+            e: None,
+            x: Some("f83OJ3D2xF1Bg8vub9tLe1gHMzV76e8Tus9uPHvRVEU".to_string()),
+            y: Some("x_FEzRu9m36HLN_tue659LNpXW6pCyStikYjKIWI5a0".to_string()),
+        };
+        assert!(valid.validate_self().is_ok());
+        assert!(valid.fingerprint().is_ok());
+
+        let mut key = valid.clone();
+        key.alg = Some(JwkKeyPairAlg::EdDSA);
+        assert!(key.validate_self().is_err());
+
+        let mut key = valid.clone();
+        key.crv = Some("P-384".to_string());
+        assert!(key.validate_self().is_err());
+
+        let mut key = valid.clone();
+        key.y = None;
+        assert!(key.validate_self().is_err());
+        assert!(key.fingerprint().is_err());
+
+        let mut key = valid;
+        key.kty = JwkKeyPairType::RSA;
+        assert!(key.validate_self().is_err());
+    }
+
+    #[test]
+    fn test_alg_from_remote() {
+        assert_eq!(
+            JwkKeyPairAlg::from_remote("RS256").unwrap(),
+            JwkKeyPairAlg::RS256
+        );
+        assert_eq!(
+            JwkKeyPairAlg::from_remote("EdDSA").unwrap(),
+            JwkKeyPairAlg::EdDSA
+        );
+        assert_eq!(
+            JwkKeyPairAlg::from_remote("ES256").unwrap(),
+            JwkKeyPairAlg::ES256
+        );
+        assert!(JwkKeyPairAlg::from_remote("none").is_err());
+        assert!(JwkKeyPairAlg::from_remote("HS256").is_err());
+
+        // Rauthy itself never signs with ES256
+        assert!("ES256".parse::<JwkKeyPairAlg>().is_err());
+    }
 }
@@ -0,0 +1,119 @@
+use crate::database::DB;
+use crate::entity::jwk::{JWKSPublicKey, JwkKeyPairAlg};
+use hiqlite::macros::params;
+use rauthy_api_types::oidc::{JwkHsmReferenceRequest, JwkHsmReferenceResponse};
+use rauthy_common::is_hiqlite;
+use rauthy_common::utils::get_rand;
+use rauthy_derive::FromPgRow;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+/// A reference to a signing key inside an HSM. The key material never leaves the HSM, which
+/// means only the slot and the key object ID are saved.
+#[derive(Debug, Clone, Serialize, Deserialize, FromPgRow)]
+pub struct JwkHsmReference {
+    pub kid: String,
+    pub created_at: i64,
+    pub signature: JwkKeyPairAlg,
+    pub slot: i64,
+    pub key_id: String,
+}
+
+// CRUD
+impl JwkHsmReference {
+    pub async fn create(payload: JwkHsmReferenceRequest) -> Result<Self, ErrorResponse> {
+        let slot = i64::try_from(payload.slot).map_err(|_| {
+            ErrorResponse::new(ErrorResponseType::BadRequest, "`slot` is out of range")
+        })?;
+
+        let sql = "SELECT * FROM jwks_hsm_refs WHERE slot = $1 AND key_id = $2";
+        let existing: Vec<Self> = if is_hiqlite() {
+            DB::hql()
+                .query_as(sql, params!(slot, payload.key_id.clone()))
+                .await?
+        } else {
+            DB::pg_query(sql, &[&slot, &payload.key_id], 1).await?
+        };
+        if !existing.is_empty() {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "A reference for this HSM key exists already",
+            ));
+        }
+
+        let slf = Self {
+            kid: get_rand(24),
+            created_at: OffsetDateTime::now_utc().unix_timestamp(),
+            signature: JwkKeyPairAlg::from(payload.alg),
+            slot,
+            key_id: payload.key_id,
+        };
+
+        let sql = r#"
+INSERT INTO jwks_hsm_refs (kid, created_at, signature, slot, key_id)
+VALUES ($1, $2, $3, $4, $5)"#;
+        if is_hiqlite() {
+            DB::hql()
+                .execute(
+                    sql,
+                    params!(
+                        slf.kid.clone(),
+                        slf.created_at,
+                        slf.signature.as_str(),
+                        slf.slot,
+                        slf.key_id.clone()
+                    ),
+                )
+                .await?;
+        } else {
+            DB::pg_execute(
+                sql,
+                &[
+                    &slf.kid,
+                    &slf.created_at,
+                    &slf.signature.as_str(),
+                    &slf.slot,
+                    &slf.key_id,
+                ],
+            )
+            .await?;
+        }
+
+        Ok(slf)
+    }
+}
+
+impl JwkHsmReference {
+    /// Returns the `HsmSigner` for this reference.
+    ///
+    /// There is no implementation yet. HSM keys can be registered, but not be used for signing.
+    pub fn signer(&self) -> Result<&'static dyn HsmSigner, ErrorResponse> {
+        Err(ErrorResponse::new(
+            ErrorResponseType::Internal,
+            format!("No HSM signer available for JWK {}", self.kid),
+        ))
+    }
+}
+
+impl From<JwkHsmReference> for JwkHsmReferenceResponse {
+    fn from(value: JwkHsmReference) -> Self {
+        Self {
+            kid: value.kid,
+            alg: value.signature.into(),
+            created_at: value.created_at,
+            slot: value.slot as u64,
+            key_id: value.key_id,
+        }
+    }
+}
+
+/// Signs tokens with a key, which lives inside an HSM, while Rauthy only knows the
+/// `JwkHsmReference`. This is the extension point for a PKCS#11 implementation.
+pub trait HsmSigner: Send + Sync {
+    /// The public key to be published in the JWKS.
+    fn public_key(&self, reference: &JwkHsmReference) -> Result<JWKSPublicKey, ErrorResponse>;
+
+    /// Signs the `input` with the referenced key and returns the raw signature.
+    fn sign(&self, reference: &JwkHsmReference, input: &[u8]) -> Result<Vec<u8>, ErrorResponse>;
+}
@@ -1,6 +1,7 @@
 use crate::entity::jwk::{JWKSPublicKey, JwkKeyPairAlg};
 use rauthy_common::utils::base64_url_no_pad_decode_buf;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
+use ring::signature::{ECDSA_P256_SHA256_FIXED, UnparsedPublicKey};
 use rsa::sha2;
 use tracing::warn;
 
@@ -71,6 +72,20 @@ impl JWKSPublicKey {
                     return Ok(());
                 }
             }
+
+            JwkKeyPairAlg::ES256 => {
+                // uncompressed point: 0x04 | x | y
+                let mut point = Vec::with_capacity(65);
+                point.push(0x04);
+                point.extend(self.x()?);
+                point.extend(self.y()?);
+                if UnparsedPublicKey::new(&ECDSA_P256_SHA256_FIXED, point)
+                    .verify(message.as_bytes(), buf)
+                    .is_ok()
+                {
+                    return Ok(());
+                }
+            }
         };
 
         warn!("JWT Token validation error");
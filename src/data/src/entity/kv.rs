@@ -7,6 +7,7 @@ use rauthy_api_types::kv::{
     KVAccessResponse, KVNamespaceResponse, KVParams, KVValueRequest, KVValueResponse,
 };
 use rauthy_common::is_hiqlite;
+use rauthy_common::utils::secure_compare;
 use rauthy_derive::FromPgRow;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 
@@ -542,7 +543,7 @@ impl KVAccess {
     #[inline]
     pub fn validate_secret(&self, secret: &str) -> Result<(), ErrorResponse> {
         let plain = EncValue::try_from(self.secret.clone()).unwrap().decrypt()?;
-        if constant_time_eq::constant_time_eq(secret.as_bytes(), plain.as_ref()) {
+        if secure_compare(secret, &*plain) {
             Ok(())
         } else {
             Err(ErrorResponse::new(
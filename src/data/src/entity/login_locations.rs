@@ -10,7 +10,7 @@ use actix_web::http::header::USER_AGENT;
 use chrono::Utc;
 use hiqlite::macros::{FromRow, params};
 use rauthy_common::is_hiqlite;
-use rauthy_common::utils::real_ip_from_req;
+use rauthy_common::utils::real_ip;
 use rauthy_derive::FromPgRow;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use std::net::IpAddr;
@@ -173,7 +173,7 @@ impl LoginLocation {
         req: &HttpRequest,
         browser_id: BrowserId,
     ) -> Result<(), ErrorResponse> {
-        let ip = real_ip_from_req(req)?;
+        let ip = real_ip(req)?;
 
         let user_agent = match req.headers().get(USER_AGENT) {
             None => {
@@ -1,5 +1,7 @@
-use crate::database::{Cache, DB};
+use crate::cache_layer::{self, CacheName};
+use crate::database::DB;
 use crate::entity::auth_providers::AuthProviderTemplate;
+use crate::html::login_page_context::LoginPageContext;
 use actix_web::web;
 use chrono::Utc;
 use hiqlite::macros::{FromRow, params};
@@ -95,12 +97,13 @@ impl Logo {
             }
         };
 
-        DB::hql()
-            .delete(Cache::App, Self::cache_idx(typ, id))
-            .await?;
-        DB::hql()
-            .delete(Cache::App, Self::cache_idx_updated(typ, id))
-            .await?;
+        cache_layer::delete(CacheName::Logo, Self::cache_idx(typ, id)).await?;
+        cache_layer::delete(CacheName::Logo, Self::cache_idx_updated(typ, id)).await?;
+
+        match typ {
+            LogoType::AuthProvider => AuthProviderTemplate::update_cache().await?,
+            LogoType::Client => LoginPageContext::invalidate_client(id).await?,
+        }
 
         Ok(())
     }
@@ -267,25 +270,24 @@ SET content_type = $3, data = $4, updated = $5"#
         }
 
         if with_cache {
-            DB::hql()
-                .put(
-                    Cache::App,
-                    Self::cache_idx(typ, &self.id),
-                    self,
-                    CACHE_TTL_APP,
-                )
-                .await?;
-            DB::hql()
-                .put(
-                    Cache::App,
-                    Self::cache_idx_updated(typ, &self.id),
-                    &Some(self.updated),
-                    CACHE_TTL_APP,
-                )
-                .await?;
+            cache_layer::put(
+                CacheName::Logo,
+                Self::cache_idx(typ, &self.id),
+                self,
+                CACHE_TTL_APP,
+            )
+            .await?;
+            cache_layer::put(
+                CacheName::Logo,
+                Self::cache_idx_updated(typ, &self.id),
+                &Some(self.updated),
+                CACHE_TTL_APP,
+            )
+            .await?;
 
-            if typ == &LogoType::AuthProvider {
-                AuthProviderTemplate::update_cache().await?;
+            match typ {
+                LogoType::AuthProvider => AuthProviderTemplate::update_cache().await?,
+                LogoType::Client => LoginPageContext::invalidate_client(&self.id).await?,
             }
         }
 
@@ -324,25 +326,26 @@ WHERE auth_provider_id = $1 AND (res = $2 OR res = $3)"#
 
     /// special fn because we only want to cache the small logos
     pub async fn find_cached(id: &str, typ: &LogoType) -> Result<Self, ErrorResponse> {
-        let client = DB::hql();
-        if let Some(slf) = client.get(Cache::App, Self::cache_idx(typ, id)).await? {
+        if let Some(slf) = cache_layer::get(CacheName::Logo, Self::cache_idx(typ, id)).await? {
             return Ok(slf);
         }
 
         let slf = Self::find(id, LogoRes::Small, typ).await?;
 
-        client
-            .put(Cache::App, Self::cache_idx(typ, id), &slf, CACHE_TTL_APP)
-            .await?;
+        cache_layer::put(
+            CacheName::Logo,
+            Self::cache_idx(typ, id),
+            &slf,
+            CACHE_TTL_APP,
+        )
+        .await?;
 
         Ok(slf)
     }
 
     pub async fn find_updated(id: &str, typ: &LogoType) -> Result<Option<i64>, ErrorResponse> {
-        let client = DB::hql();
-        if let Some(updated) = client
-            .get(Cache::App, Self::cache_idx_updated(typ, id))
-            .await?
+        if let Some(updated) =
+            cache_layer::get(CacheName::Logo, Self::cache_idx_updated(typ, id)).await?
         {
             return Ok(updated);
         }
@@ -366,14 +369,13 @@ WHERE auth_provider_id = $1 AND (res = $2 OR res = $3)"#
                 .map(|r| r.get::<_, i64>("updated"))
         };
 
-        client
-            .put(
-                Cache::App,
-                Self::cache_idx_updated(typ, id),
-                &updated,
-                CACHE_TTL_APP,
-            )
-            .await?;
+        cache_layer::put(
+            CacheName::Logo,
+            Self::cache_idx_updated(typ, id),
+            &updated,
+            CACHE_TTL_APP,
+        )
+        .await?;
 
         Ok(updated)
     }
@@ -6,7 +6,7 @@ use chrono::Utc;
 use hiqlite::macros::params;
 use rauthy_common::constants::{PWD_CSRF_HEADER, PWD_RESET_COOKIE};
 use rauthy_common::is_hiqlite;
-use rauthy_common::utils::{get_rand, real_ip_from_req};
+use rauthy_common::utils::{get_rand, real_ip, secure_compare};
 use rauthy_derive::FromPgRow;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use serde::{Deserialize, Serialize};
@@ -96,6 +96,16 @@ pub struct MagicLink {
     pub exp: i64,
     pub used: bool,
     pub usage: String,
+    /// Timestamp of the first attempt to use this link after it has expired.
+    pub expired_attempt: Option<i64>,
+}
+
+/// Current amounts of Magic Links, used for the metrics gauges.
+#[derive(Debug, Default)]
+pub struct MagicLinkStats {
+    pub active: i64,
+    pub expired: i64,
+    pub expired_attempted: i64,
 }
 
 impl Debug for MagicLink {
@@ -103,20 +113,40 @@ impl Debug for MagicLink {
         write!(
             f,
             "MagicLink {{ id: {}(...), user_id: {}, csrf_token: {}(...), cookie: {:?}, exp: {}, \
-            used: {}, usage: {} }}",
+            used: {}, usage: {}, expired_attempt: {:?} }}",
             &self.id[..5],
             self.user_id,
             &self.csrf_token[..5],
             self.cookie,
             self.exp,
             self.used,
-            self.usage
+            self.usage,
+            self.expired_attempt,
         )
     }
 }
 
 // CRUD
 impl MagicLink {
+    /// Counts the links, which have been opened for the first time after their expiry
+    /// in `[from, until)`.
+    pub async fn count_expired_attempts(from: i64, until: i64) -> Result<i64, ErrorResponse> {
+        let sql = r#"
+SELECT COUNT(*) AS count
+FROM magic_links
+WHERE expired_attempt >= $1 AND expired_attempt < $2"#;
+
+        let count: i64 = if is_hiqlite() {
+            let mut row = DB::hql().query_raw_one(sql, params!(from, until)).await?;
+            row.get("count")
+        } else {
+            let row = DB::pg_query_one_row(sql, &[&from, &until]).await?;
+            row.get("count")
+        };
+
+        Ok(count)
+    }
+
     pub async fn create(
         user_id: String,
         lifetime_minutes: i64,
@@ -132,6 +162,7 @@ impl MagicLink {
             exp,
             used: false,
             usage: usage.to_string(),
+            expired_attempt: None,
         };
 
         let sql = r#"
@@ -181,6 +212,41 @@ VALUES ($1, $2, $3, $4, $5, $6)"#;
         Ok(())
     }
 
+    /// Deletes all links, which expired more than `sched_maintenance_grace_secs` before `now`.
+    /// Each batch of `sched_maintenance_batch_size` is a single statement, so a huge backlog
+    /// never blocks the DB for long.
+    pub async fn delete_expired(now: i64) -> Result<usize, ErrorResponse> {
+        let vars = &RauthyConfig::get().vars.database;
+        let exp = now - vars.sched_maintenance_grace_secs as i64;
+        let batch_size = vars.sched_maintenance_batch_size.max(1) as usize;
+
+        let mut deleted_total = 0;
+        loop {
+            let deleted = Self::delete_expired_batch(exp, batch_size as i64).await?;
+            deleted_total += deleted;
+            if deleted < batch_size {
+                break;
+            }
+        }
+
+        Ok(deleted_total)
+    }
+
+    /// Deletes up to `batch_size` links, which expired before `exp`.
+    async fn delete_expired_batch(exp: i64, batch_size: i64) -> Result<usize, ErrorResponse> {
+        let sql = r#"
+DELETE FROM magic_links
+WHERE id IN (SELECT id FROM magic_links WHERE exp < $1 LIMIT $2)"#;
+
+        let rows_affected = if is_hiqlite() {
+            DB::hql().execute(sql, params!(exp, batch_size)).await?
+        } else {
+            DB::pg_execute(sql, &[&exp, &batch_size]).await?
+        };
+
+        Ok(rows_affected)
+    }
+
     pub async fn find(id: &str) -> Result<Self, ErrorResponse> {
         let sql = "SELECT * FROM magic_links WHERE id = $1";
         let res = if is_hiqlite() {
@@ -226,6 +292,34 @@ VALUES ($1, $2, $3, $4, $5, $6)"#;
 
         Ok(())
     }
+
+    pub async fn stats() -> Result<MagicLinkStats, ErrorResponse> {
+        let sql = r#"
+SELECT
+    COUNT(*) FILTER (WHERE exp >= $1 AND used = false) AS active,
+    COUNT(*) FILTER (WHERE exp < $1 OR used = true) AS expired,
+    COUNT(*) FILTER (WHERE exp < $1 AND expired_attempt IS NOT NULL) AS expired_attempted
+FROM magic_links"#;
+        let now = Utc::now().timestamp();
+
+        let stats = if is_hiqlite() {
+            let mut row = DB::hql().query_raw_one(sql, params!(now)).await?;
+            MagicLinkStats {
+                active: row.get("active"),
+                expired: row.get("expired"),
+                expired_attempted: row.get("expired_attempted"),
+            }
+        } else {
+            let row = DB::pg_query_one_row(sql, &[&now]).await?;
+            MagicLinkStats {
+                active: row.get("active"),
+                expired: row.get("expired"),
+                expired_attempted: row.get("expired_attempted"),
+            }
+        };
+
+        Ok(stats)
+    }
 }
 
 impl MagicLink {
@@ -236,6 +330,28 @@ impl MagicLink {
         self.save().await
     }
 
+    /// Remembers the first attempt to open this link after its expiry, to be able to detect
+    /// users hitting dead links. Must be called when a user opens the link, not on each lookup.
+    pub async fn record_expired_attempt(&mut self) -> Result<(), ErrorResponse> {
+        let now = Utc::now().timestamp();
+        if self.used || self.exp >= now || self.expired_attempt.is_some() {
+            return Ok(());
+        }
+
+        let sql = r#"
+UPDATE magic_links
+SET expired_attempt = $1
+WHERE id = $2 AND expired_attempt IS NULL"#;
+        if is_hiqlite() {
+            DB::hql().execute(sql, params!(now, &self.id)).await?;
+        } else {
+            DB::pg_execute(sql, &[&now, &self.id]).await?;
+        }
+        self.expired_attempt = Some(now);
+
+        Ok(())
+    }
+
     pub fn validate(
         &self,
         user_id: &str,
@@ -259,7 +375,7 @@ impl MagicLink {
                     if cookie_binding {
                         return Err(err);
                     } else {
-                        let ip = real_ip_from_req(req)?;
+                        let ip = real_ip(req)?;
                         warn!(
                             ?ip,
                             "PASSWORD_RESET_COOKIE_BINDING disabled -> ignoring invalid binding \
@@ -270,7 +386,7 @@ impl MagicLink {
             } else if cookie_binding {
                 return Err(err);
             } else {
-                let ip = real_ip_from_req(req)?;
+                let ip = real_ip(req)?;
                 warn!(
                     ?ip,
                     "PASSWORD_RESET_COOKIE_BINDING disabled -> ignoring invalid binding cookie",
@@ -288,7 +404,7 @@ impl MagicLink {
                     ));
                 }
                 Some(token) => {
-                    if self.csrf_token != token.to_str().unwrap_or("") {
+                    if !secure_compare(&self.csrf_token, token.as_bytes()) {
                         return Err(ErrorResponse::new(
                             ErrorResponseType::Unauthorized,
                             "Invalid CSRF Token",
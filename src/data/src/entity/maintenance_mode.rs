@@ -0,0 +1,393 @@
+use crate::api_cookie::ApiCookie;
+use crate::cache_layer::{self, CacheName};
+use crate::database::DB;
+use crate::events::event::Event;
+use actix_web::HttpRequest;
+use actix_web::cookie::Cookie;
+use actix_web::dev::ServiceRequest;
+use chrono::Utc;
+use hiqlite::macros::params;
+use rauthy_api_types::generic::{MaintenanceModeRequest, MaintenanceModeResponse};
+use rauthy_common::constants::{CACHE_TTL_APP, COOKIE_MAINTENANCE_BYPASS, IDX_MAINTENANCE_MODE};
+use rauthy_common::is_hiqlite;
+use rauthy_common::utils::{deserialize, serialize};
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use serde::{Deserialize, Serialize};
+
+/// A bypass cookie is never valid for longer than this, even if the maintenance has no end.
+const BYPASS_COOKIE_MAX_LIFETIME: i64 = 12 * 3600;
+
+/// A planned maintenance, during which new logins and token issuance are rejected with a
+/// `503`. Existing sessions and tokens keep working. It is stored as a single value inside the
+/// `config` table.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MaintenanceMode {
+    pub enabled: bool,
+    /// Unix timestamp in seconds
+    pub start: Option<i64>,
+    /// Unix timestamp in seconds
+    pub end: Option<i64>,
+    pub bypass_user_ids: Vec<String>,
+    /// Tracks if the `MaintenanceModeStarted` event has been sent for the current maintenance.
+    pub started: bool,
+}
+
+/// A state change of the maintenance, which should emit an event.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MaintenanceTransition {
+    Started,
+    Ended,
+}
+
+// CRUD
+impl MaintenanceMode {
+    pub async fn find() -> Result<Self, ErrorResponse> {
+        if let Some(slf) =
+            cache_layer::get(CacheName::MaintenanceMode, IDX_MAINTENANCE_MODE).await?
+        {
+            return Ok(slf);
+        }
+
+        let sql = "SELECT data FROM config WHERE id = 'maintenance_mode'";
+        let bytes: Option<Vec<u8>> = if is_hiqlite() {
+            DB::hql()
+                .query_raw(sql, params!())
+                .await?
+                .into_iter()
+                .next()
+                .map(|mut row| row.get("data"))
+        } else {
+            DB::pg_query_rows(sql, &[], 1)
+                .await?
+                .into_iter()
+                .next()
+                .map(|row| row.get("data"))
+        };
+        let slf = match bytes {
+            Some(bytes) => deserialize::<Self>(&bytes)?,
+            None => Self::default(),
+        };
+
+        cache_layer::put(
+            CacheName::MaintenanceMode,
+            IDX_MAINTENANCE_MODE,
+            &slf,
+            CACHE_TTL_APP,
+        )
+        .await?;
+
+        Ok(slf)
+    }
+
+    /// Returns the maintenance only, if it is in effect right now.
+    pub async fn find_active() -> Result<Option<Self>, ErrorResponse> {
+        let slf = Self::find().await?;
+        if slf.is_active(Utc::now().timestamp()) {
+            Ok(Some(slf))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub async fn save(&self) -> Result<(), ErrorResponse> {
+        let data = serialize(self)?;
+
+        let sql = r#"
+INSERT INTO config (id, data)
+VALUES ('maintenance_mode', $1)
+ON CONFLICT (id) DO UPDATE SET data = $1"#;
+        if is_hiqlite() {
+            DB::hql().execute(sql, params!(data)).await?;
+        } else {
+            DB::pg_execute(sql, &[&data]).await?;
+        }
+
+        cache_layer::put(
+            CacheName::MaintenanceMode,
+            IDX_MAINTENANCE_MODE,
+            self,
+            CACHE_TTL_APP,
+        )
+        .await?;
+
+        Ok(())
+    }
+}
+
+impl MaintenanceMode {
+    pub fn try_from_req(req: MaintenanceModeRequest) -> Result<Self, ErrorResponse> {
+        if let (Some(start), Some(end)) = (req.start, req.end)
+            && start >= end
+        {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "The maintenance 'start' must be before its 'end'",
+            ));
+        }
+
+        Ok(Self {
+            enabled: req.enabled,
+            start: req.start,
+            end: req.end,
+            bypass_user_ids: req.bypass_user_ids,
+            started: false,
+        })
+    }
+
+    #[inline]
+    pub fn is_active(&self, now: i64) -> bool {
+        self.enabled
+            && self.start.map(|start| start <= now).unwrap_or(true)
+            && !self.is_expired(now)
+    }
+
+    #[inline]
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.end.map(|end| end <= now).unwrap_or(false)
+    }
+
+    /// Seconds until the planned end, `0` if there is none.
+    #[inline]
+    pub fn retry_after(&self, now: i64) -> i64 {
+        self.end.map(|end| (end - now).max(1)).unwrap_or(0)
+    }
+
+    pub fn err(&self, now: i64) -> ErrorResponse {
+        ErrorResponse::new(
+            ErrorResponseType::ServiceUnavailable(self.retry_after(now)),
+            "Rauthy is in maintenance mode - please try again later",
+        )
+    }
+
+    #[inline]
+    pub fn can_bypass_user(&self, user_id: &str) -> bool {
+        self.bypass_user_ids.iter().any(|id| id == user_id)
+    }
+
+    /// Builds a bypass cookie for the given admin. It is only valid until the end of the
+    /// maintenance, but never longer than 12 hours.
+    pub fn bypass_cookie<'c>(&self, user_id: &str, now: i64) -> Cookie<'c> {
+        let max_exp = now + BYPASS_COOKIE_MAX_LIFETIME;
+        let exp = self.end.map(|end| end.min(max_exp)).unwrap_or(max_exp);
+        ApiCookie::build(
+            COOKIE_MAINTENANCE_BYPASS,
+            format!("{user_id}:{exp}"),
+            exp - now,
+        )
+    }
+
+    /// The cookie value itself is encrypted and cannot be forged. We still check the
+    /// expiry, because the `max-age` is only a hint for the browser.
+    fn is_valid_bypass_value(value: &str, now: i64) -> bool {
+        let Some((user_id, exp)) = value.split_once(':') else {
+            return false;
+        };
+        if user_id.is_empty() {
+            return false;
+        }
+        exp.parse::<i64>().map(|exp| exp > now).unwrap_or(false)
+    }
+
+    pub fn has_bypass_cookie(req: &ServiceRequest, now: i64) -> bool {
+        ApiCookie::from_svc_req(req, COOKIE_MAINTENANCE_BYPASS)
+            .map(|value| Self::is_valid_bypass_value(&value, now))
+            .unwrap_or(false)
+    }
+
+    /// Checks if the given user may log in right now.
+    pub async fn validate_login(req: &HttpRequest, user_id: &str) -> Result<(), ErrorResponse> {
+        let Some(slf) = Self::find_active().await? else {
+            return Ok(());
+        };
+        if slf.can_bypass_user(user_id) {
+            return Ok(());
+        }
+
+        let now = Utc::now().timestamp();
+        let has_cookie = ApiCookie::from_req(req, COOKIE_MAINTENANCE_BYPASS)
+            .map(|value| Self::is_valid_bypass_value(&value, now))
+            .unwrap_or(false);
+        if has_cookie {
+            Ok(())
+        } else {
+            Err(slf.err(now))
+        }
+    }
+
+    /// Checks if new tokens may be issued for grants without a prior login, like `password` or
+    /// `client_credentials`. The token endpoint is never called with cookies, so only users
+    /// from the bypass list are allowed. Code exchanges and refresh tokens are not checked,
+    /// because they belong to a login that has been allowed already.
+    pub async fn validate_token(user_id: Option<&str>) -> Result<(), ErrorResponse> {
+        let Some(slf) = Self::find_active().await? else {
+            return Ok(());
+        };
+        if let Some(user_id) = user_id
+            && slf.can_bypass_user(user_id)
+        {
+            return Ok(());
+        }
+        Err(slf.err(Utc::now().timestamp()))
+    }
+
+    /// Updates the internal state and returns a transition, if an event should be sent.
+    /// An expired maintenance is disabled automatically.
+    pub fn transition(&mut self, now: i64) -> Option<MaintenanceTransition> {
+        if self.is_active(now) {
+            if self.started {
+                None
+            } else {
+                self.started = true;
+                Some(MaintenanceTransition::Started)
+            }
+        } else if self.enabled && self.is_expired(now) {
+            self.enabled = false;
+            if self.started {
+                self.started = false;
+                Some(MaintenanceTransition::Ended)
+            } else {
+                // it has never been active, for instance after a long downtime
+                None
+            }
+        } else if self.started {
+            // disabled manually
+            self.started = false;
+            Some(MaintenanceTransition::Ended)
+        } else {
+            None
+        }
+    }
+
+    /// Applies a possible `transition()`, persists the new state and sends the matching event.
+    pub async fn apply_transition(&mut self) -> Result<(), ErrorResponse> {
+        let before = self.clone();
+        let transition = self.transition(Utc::now().timestamp());
+        if *self != before {
+            self.save().await?;
+        }
+
+        match transition {
+            None => {}
+            Some(MaintenanceTransition::Started) => {
+                Event::maintenance_mode_started(self.event_text())
+                    .send()
+                    .await?;
+            }
+            Some(MaintenanceTransition::Ended) => {
+                Event::maintenance_mode_ended(self.event_text())
+                    .send()
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Human-readable description for the events.
+    pub fn event_text(&self) -> String {
+        match self.end {
+            None => "Maintenance mode without planned end".to_string(),
+            Some(end) => match chrono::DateTime::from_timestamp(end, 0) {
+                Some(dt) => format!("Maintenance mode until {}", dt.format("%Y-%m-%d %H:%M UTC")),
+                None => "Maintenance mode".to_string(),
+            },
+        }
+    }
+}
+
+impl From<MaintenanceMode> for MaintenanceModeResponse {
+    fn from(value: MaintenanceMode) -> Self {
+        Self {
+            active: value.is_active(Utc::now().timestamp()),
+            enabled: value.enabled,
+            start: value.start,
+            end: value.end,
+            bypass_user_ids: value.bypass_user_ids,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn maintenance(start: Option<i64>, end: Option<i64>) -> MaintenanceMode {
+        MaintenanceMode {
+            enabled: true,
+            start,
+            end,
+            bypass_user_ids: vec!["admin123".to_string()],
+            started: false,
+        }
+    }
+
+    #[test]
+    fn test_is_active() {
+        let now = 1_000;
+
+        assert!(maintenance(None, None).is_active(now));
+        assert!(maintenance(Some(now), Some(now + 1)).is_active(now));
+        assert!(!maintenance(Some(now + 1), None).is_active(now));
+        assert!(!maintenance(None, Some(now)).is_active(now));
+
+        let mut disabled = maintenance(None, None);
+        disabled.enabled = false;
+        assert!(!disabled.is_active(now));
+
+        assert_eq!(maintenance(None, None).retry_after(now), 0);
+        assert_eq!(maintenance(None, Some(now + 30)).retry_after(now), 30);
+        let err = maintenance(None, Some(now + 30)).err(now);
+        assert_eq!(err.error, ErrorResponseType::ServiceUnavailable(30));
+    }
+
+    #[test]
+    fn test_bypass() {
+        let now = 1_000;
+        let slf = maintenance(None, None);
+
+        assert!(slf.can_bypass_user("admin123"));
+        assert!(!slf.can_bypass_user("user123"));
+
+        assert!(MaintenanceMode::is_valid_bypass_value("admin123:1001", now));
+        assert!(!MaintenanceMode::is_valid_bypass_value(
+            "admin123:1000",
+            now
+        ));
+        assert!(!MaintenanceMode::is_valid_bypass_value(":1001", now));
+        assert!(!MaintenanceMode::is_valid_bypass_value("admin123", now));
+        assert!(!MaintenanceMode::is_valid_bypass_value("admin123:abc", now));
+    }
+
+    #[test]
+    fn test_transition_auto_expiry() {
+        let now = 1_000;
+        let mut slf = maintenance(Some(now + 10), Some(now + 20));
+
+        // planned, not yet started
+        assert_eq!(slf.transition(now), None);
+        assert!(!slf.started);
+
+        assert_eq!(
+            slf.transition(now + 10),
+            Some(MaintenanceTransition::Started)
+        );
+        assert!(slf.started);
+        assert_eq!(slf.transition(now + 15), None);
+
+        assert_eq!(slf.transition(now + 20), Some(MaintenanceTransition::Ended));
+        assert!(!slf.enabled);
+        assert!(!slf.started);
+        assert_eq!(slf.transition(now + 30), None);
+
+        // expired before it was ever picked up -> disabled without an event
+        let mut slf = maintenance(None, Some(now));
+        assert_eq!(slf.transition(now + 1), None);
+        assert!(!slf.enabled);
+
+        // disabled manually while active
+        let mut slf = maintenance(None, None);
+        assert_eq!(slf.transition(now), Some(MaintenanceTransition::Started));
+        slf.enabled = false;
+        assert_eq!(slf.transition(now), Some(MaintenanceTransition::Ended));
+    }
+}
@@ -7,11 +7,22 @@ pub mod app_version;
 pub mod atproto;
 pub mod auth_codes;
 pub mod auth_provider_cust_impls;
+pub mod auth_provider_export;
+pub mod auth_provider_group_mappings;
+pub mod auth_provider_health;
+pub mod auth_provider_logout;
+pub mod auth_provider_refresh_tokens;
+pub mod auth_provider_role_mappings;
+pub mod auth_provider_secret;
+pub mod auth_provider_test;
 pub mod auth_providers;
 pub mod browser_id;
 pub mod ca_self_signed;
 pub mod clients;
+pub mod clients_claims;
+pub mod clients_claims_webhook;
 pub mod clients_dyn;
+pub mod clients_jwks;
 pub mod clients_scim;
 pub mod config;
 pub mod continuation_token;
@@ -27,15 +38,18 @@ pub mod failed_scim_tasks;
 pub mod fed_cm;
 pub mod forward_auth;
 pub mod groups;
+pub mod i18n_overrides;
 pub mod ip_blacklist;
 pub mod ip_rate_limit;
 pub mod issued_tokens;
 pub mod jwk;
+pub mod jwk_hsm;
 pub mod jwk_token_validation;
 pub mod kv;
 pub mod login_locations;
 pub mod logos;
 pub mod magic_links;
+pub mod maintenance_mode;
 pub mod mfa_mod_token;
 pub mod pam;
 pub mod password;
@@ -52,9 +66,15 @@ pub mod theme;
 pub mod tos;
 pub mod tos_user_accept;
 pub mod user_attr;
+pub mod user_federation_claims;
+pub mod user_grants;
 pub mod user_login_states;
+pub mod user_notification_prefs;
+pub mod user_registration_config;
 pub mod user_revoke;
 pub mod users;
+pub mod users_erasure;
+pub mod users_offboarding;
 pub mod users_values;
 pub mod webauthn;
 pub mod webids;
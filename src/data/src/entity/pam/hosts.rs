@@ -9,6 +9,7 @@ use rauthy_api_types::pam::{
 };
 use rauthy_common::constants::{PAM_WHEEL_ID, PAM_WHEEL_NAME, SECRET_LEN_CLIENTS};
 use rauthy_common::is_hiqlite;
+use rauthy_common::utils::secure_compare;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
@@ -555,9 +556,7 @@ WHERE pu.gid = $1 AND pu.wheel = $2
         debug_assert_eq!(SECRET_LEN_CLIENTS, 64);
 
         let dec = EncValue::try_from_bytes(self.secret.clone())?.decrypt()?;
-        let a = <&[u8; 64]>::try_from(dec.as_ref()).unwrap();
-        let b = <&[u8; 64]>::try_from(secret.as_bytes()).unwrap();
-        if constant_time_eq::constant_time_eq_64(a, b) {
+        if secure_compare(&*dec, &secret) {
             secret.zeroize();
             Ok(())
         } else {
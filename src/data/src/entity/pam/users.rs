@@ -1,10 +1,11 @@
 use crate::database::DB;
 use crate::entity::pam::authorized_keys::AuthorizedKey;
 use crate::entity::pam::groups::{PamGroup, PamGroupType};
+use crate::pii;
 use hiqlite::macros::params;
 use rauthy_api_types::pam::{PamGroupUserLink, PamSshAuthKeyResponse, PamUserResponse};
 use rauthy_common::is_hiqlite;
-use rauthy_error::ErrorResponse;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -45,6 +46,14 @@ impl From<tokio_postgres::Row> for PamUser {
 
 impl PamUser {
     pub async fn insert(username: String, email: String) -> Result<Self, ErrorResponse> {
+        // PAM users are linked via the plaintext E-Mail, which does not exist in this mode.
+        if pii::is_enabled() {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "PAM users cannot be used together with `encryption.pii_at_rest`",
+            ));
+        }
+
         let group = PamGroup::insert(username, PamGroupType::User).await?;
 
         let sql_user = r#"
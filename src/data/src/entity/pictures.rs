@@ -136,7 +136,7 @@ VALUES ($1, $2, $3, $4)"#;
         Ok(())
     }
 
-    async fn find(id: String) -> Result<Self, ErrorResponse> {
+    pub(crate) async fn find(id: String) -> Result<Self, ErrorResponse> {
         let sql = "SELECT * FROM pictures WHERE id = $1";
         let slf = if is_hiqlite() {
             DB::hql().query_as_one(sql, params!(id)).await?
@@ -149,6 +149,31 @@ VALUES ($1, $2, $3, $4)"#;
 }
 
 impl UserPicture {
+    /// Cleans up the file on the storage, if any. Errors are only logged, because the picture
+    /// cannot be reached anymore after its DB row is gone.
+    pub(crate) async fn delete_storage(&self) {
+        match PictureStorage::from(self.storage.as_str()) {
+            PictureStorage::DB => {
+                // no additional data exists
+            }
+            PictureStorage::File => {
+                let path = Self::local_file_path(&self.id, &self.content_type);
+                if let Err(err) = fs::remove_file(&path).await {
+                    error!(path, ?err, "Error cleaning up local picture")
+                }
+            }
+            PictureStorage::S3 => {
+                if let Err(err) = PICTURE_S3_BUCKET
+                    .delete(&Self::file_name(&self.id, &self.content_type))
+                    .await
+                {
+                    error!(object_id = self.id, ?err, "Error cleaning up s3 picture")
+                }
+            }
+            PictureStorage::Disabled => unreachable!(),
+        }
+    }
+
     #[inline]
     fn local_file_path(picture_id: &str, content_type: &str) -> String {
         format!(
@@ -293,28 +318,7 @@ impl UserPicture {
                 ));
             }
             let slf = Self::find(id).await?;
-
-            match PictureStorage::from(slf.storage.as_str()) {
-                PictureStorage::DB => {
-                    // no additional data exists
-                }
-                PictureStorage::File => {
-                    let path = Self::local_file_path(&slf.id, &slf.content_type);
-                    if let Err(err) = fs::remove_file(&path).await {
-                        error!(path, ?err, "Error cleaning up local picture")
-                    }
-                }
-                PictureStorage::S3 => {
-                    if let Err(err) = PICTURE_S3_BUCKET
-                        .delete(&Self::file_name(&slf.id, &slf.content_type))
-                        .await
-                    {
-                        error!(object_id = slf.id, ?err, "Error cleaning up s3 picture")
-                    }
-                }
-                PictureStorage::Disabled => unreachable!(),
-            }
-
+            slf.delete_storage().await;
             Self::delete(slf.id).await?;
 
             user.picture_id = None;
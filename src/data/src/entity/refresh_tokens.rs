@@ -1,12 +1,17 @@
 use crate::database::DB;
 use chrono::Utc;
 use hiqlite::macros::params;
-use rauthy_common::is_hiqlite;
+use rauthy_api_types::clients::{ClientRefreshTokenBinding, IpBinding, UserAgentBinding};
+use rauthy_common::utils::secure_compare;
+use rauthy_common::{is_hiqlite, sha256};
 use rauthy_derive::FromPgRow;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use serde::Deserialize;
 use std::fmt::{Debug, Formatter};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
+/// Refresh tokens are never saved in plain text. The `id` is the hex encoded SHA-256 digest of the
+/// last 49 characters of the token.
 #[derive(Deserialize, FromPgRow)]
 pub struct RefreshToken {
     pub id: String,
@@ -17,6 +22,15 @@ pub struct RefreshToken {
     pub is_mfa: bool,
     pub session_id: Option<String>,
     pub access_token_jti: Option<String>,
+    /// Only set for public clients with a `refresh_token_binding`,
+    /// see `RefreshToken::build_fingerprint()`.
+    pub fingerprint: Option<String>,
+    /// Only `None` for tokens issued by older versions.
+    pub client_id: Option<String>,
+    /// Unix timestamp in seconds of the original login, which is kept across rotations.
+    pub granted: Option<i64>,
+    /// Unix timestamp in seconds, see `UserGrant::touch_last_used()`.
+    pub last_used: Option<i64>,
 }
 
 impl Debug for RefreshToken {
@@ -24,7 +38,7 @@ impl Debug for RefreshToken {
         write!(
             f,
             "RefreshToken {{ id: {}(...), user_id: {}, nbf: {}, exp: {}, scope: {:?}, is_mfa: {}, \
-            session_id: {:?} }}",
+            session_id: {:?}, fingerprint: {}, client_id: {:?}, granted: {:?}, last_used: {:?} }}",
             &self.id[..5],
             self.user_id,
             self.nbf,
@@ -32,6 +46,10 @@ impl Debug for RefreshToken {
             self.scope,
             self.is_mfa,
             self.session_id.as_ref().map(|sid| &sid[..5]),
+            self.fingerprint.is_some(),
+            self.client_id,
+            self.granted,
+            self.last_used,
         )
     }
 }
@@ -40,7 +58,7 @@ impl Debug for RefreshToken {
 impl RefreshToken {
     #[allow(clippy::too_many_arguments)]
     pub async fn create(
-        id: String,
+        validation_str: &str,
         user_id: String,
         nbf: i64,
         exp: i64,
@@ -51,9 +69,12 @@ impl RefreshToken {
         is_mfa: bool,
         session_id: Option<String>,
         access_token_jti: Option<String>,
+        fingerprint: Option<String>,
+        client_id: String,
+        granted: i64,
     ) -> Result<Self, ErrorResponse> {
         let rt = Self {
-            id,
+            id: hash_validation_str(validation_str),
             user_id,
             nbf,
             exp,
@@ -61,6 +82,10 @@ impl RefreshToken {
             is_mfa,
             session_id,
             access_token_jti,
+            fingerprint,
+            client_id: Some(client_id),
+            granted: Some(granted),
+            last_used: None,
         };
 
         rt.save().await?;
@@ -87,6 +112,15 @@ impl RefreshToken {
         Ok(())
     }
 
+    /// Revokes the whole family of this token. Tokens, which are linked to a session, share it
+    /// with all tokens rotated from the same login. Without a session, only this token exists.
+    pub async fn delete_family(&self) -> Result<(), ErrorResponse> {
+        match &self.session_id {
+            Some(sid) => Self::delete_by_sid(sid.clone()).await,
+            None => self.delete().await,
+        }
+    }
+
     pub async fn find_all() -> Result<Vec<Self>, ErrorResponse> {
         let sql = "SELECT * FROM refresh_tokens";
         let res = if is_hiqlite() {
@@ -136,44 +170,86 @@ impl RefreshToken {
         Ok(())
     }
 
-    pub async fn find(id: &str) -> Result<Self, ErrorResponse> {
+    /// Finds a valid refresh token by the last 49 characters of the raw token.
+    ///
+    /// The lookup happens by the digest, which makes sure that the comparison does not leak any
+    /// timing information about the stored value. Tokens issued by older versions were saved in
+    /// plain text. They are still accepted once, and their `id` is replaced with the digest
+    /// right away, so the plain text value can never be found again.
+    ///
+    /// The `OR id = $2` fallback here and in `find_opt()` can be removed as soon as no legacy token
+    /// can be valid anymore, which is the case when the highest configured refresh token lifetime
+    /// (`lifetimes.refresh_token_lifetime`, 48h by default) has passed after the update to the
+    /// version that introduced the digest. It should be dropped with the next major release.
+    pub async fn find(validation_str: &str) -> Result<Self, ErrorResponse> {
+        let id = hash_validation_str(validation_str);
         let now = Utc::now().timestamp();
-        let sql = "SELECT * FROM refresh_tokens WHERE id = $1 AND exp > $2";
+        let sql = "SELECT * FROM refresh_tokens WHERE (id = $1 OR id = $2) AND exp > $3";
 
-        let slf: Self = if is_hiqlite() {
+        let mut slf: Self = if is_hiqlite() {
             DB::hql()
-                .query_as_one(sql, params!(id, now))
+                .query_as_one(sql, params!(id, validation_str, now))
                 .await
                 .map_err(|_| {
                     ErrorResponse::new(ErrorResponseType::NotFound, "Refresh Token does not exist")
                 })?
         } else {
-            DB::pg_query_one(sql, &[&id, &now]).await.map_err(|_| {
-                ErrorResponse::new(ErrorResponseType::NotFound, "Refresh Token does not exist")
-            })?
+            DB::pg_query_one(sql, &[&id, &validation_str, &now])
+                .await
+                .map_err(|_| {
+                    ErrorResponse::new(ErrorResponseType::NotFound, "Refresh Token does not exist")
+                })?
         };
 
+        if slf.id == validation_str {
+            slf.rehash_legacy_id().await?;
+        }
+
         Ok(slf)
     }
 
-    pub async fn find_opt(id: &str) -> Result<Option<Self>, ErrorResponse> {
-        let sql = "SELECT * FROM refresh_tokens WHERE id = $1";
+    /// Finds a refresh token by the last 49 characters of the raw token, even if it has expired.
+    pub async fn find_opt(validation_str: &str) -> Result<Option<Self>, ErrorResponse> {
+        let id = hash_validation_str(validation_str);
+        let sql = "SELECT * FROM refresh_tokens WHERE id = $1 OR id = $2";
 
         let slf = if is_hiqlite() {
-            DB::hql().query_as_optional(sql, params!(id)).await?
+            DB::hql()
+                .query_as_optional(sql, params!(id, validation_str))
+                .await?
         } else {
-            DB::pg_query_opt(sql, &[&id]).await?
+            DB::pg_query_opt(sql, &[&id, &validation_str]).await?
         };
 
         Ok(slf)
     }
 
+    /// Replaces a legacy plain text `id` with its digest.
+    async fn rehash_legacy_id(&mut self) -> Result<(), ErrorResponse> {
+        let id = hash_validation_str(&self.id);
+        let sql = "UPDATE refresh_tokens SET id = $1 WHERE id = $2";
+
+        if is_hiqlite() {
+            DB::hql()
+                .execute(sql, params!(id.clone(), self.id.clone()))
+                .await?;
+        } else {
+            DB::pg_execute(sql, &[&id, &self.id]).await?;
+        }
+
+        self.id = id;
+        Ok(())
+    }
+
     pub async fn save(&self) -> Result<(), ErrorResponse> {
         let sql = r#"
-INSERT INTO refresh_tokens (id, user_id, nbf, exp, scope, is_mfa, session_id, access_token_jti)
-VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+INSERT INTO refresh_tokens
+(id, user_id, nbf, exp, scope, is_mfa, session_id, access_token_jti, fingerprint, client_id,
+ granted, last_used)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
 ON CONFLICT(id) DO UPDATE
-SET user_id = $2, nbf = $3, exp = $4, scope = $5, session_id = $7, access_token_jti = $8"#;
+SET user_id = $2, nbf = $3, exp = $4, scope = $5, session_id = $7, access_token_jti = $8,
+    fingerprint = $9, client_id = $10, granted = $11, last_used = $12"#;
 
         if is_hiqlite() {
             DB::hql()
@@ -187,7 +263,11 @@ SET user_id = $2, nbf = $3, exp = $4, scope = $5, session_id = $7, access_token_
                         self.scope.clone(),
                         self.is_mfa,
                         self.session_id.clone(),
-                        self.access_token_jti.clone()
+                        self.access_token_jti.clone(),
+                        self.fingerprint.clone(),
+                        self.client_id.clone(),
+                        self.granted,
+                        self.last_used
                     ),
                 )
                 .await?;
@@ -203,6 +283,10 @@ SET user_id = $2, nbf = $3, exp = $4, scope = $5, session_id = $7, access_token_
                     &self.is_mfa,
                     &self.session_id,
                     &self.access_token_jti,
+                    &self.fingerprint,
+                    &self.client_id,
+                    &self.granted,
+                    &self.last_used,
                 ],
             )
             .await?;
@@ -211,3 +295,223 @@ SET user_id = $2, nbf = $3, exp = $4, scope = $5, session_id = $7, access_token_
         Ok(())
     }
 }
+
+impl RefreshToken {
+    /// Builds the fingerprint of a refresh request context for the given `binding`.
+    ///
+    /// The value is prefixed with the strictness levels it has been built with. Only the digest
+    /// of the browser family and IP network is stored and never the raw values.
+    pub fn build_fingerprint(
+        binding: &ClientRefreshTokenBinding,
+        user_agent: &str,
+        ip: IpAddr,
+    ) -> String {
+        let (ua_level, ua) = match binding.user_agent {
+            UserAgentBinding::Off => ("-", String::default()),
+            UserAgentBinding::Family => ("f", user_agent_family(user_agent).0.to_string()),
+            UserAgentBinding::FamilyMajor => {
+                let (family, major) = user_agent_family(user_agent);
+                ("fm", format!("{family}/{major}"))
+            }
+        };
+        let (ip_level, network) = match binding.ip {
+            IpBinding::Off => ("-", String::default()),
+            IpBinding::Wide => ("w", ip_network(ip, 16, 48)),
+            IpBinding::Narrow => ("n", ip_network(ip, 24, 64)),
+        };
+
+        let digest = hex::encode(sha256!(format!("{ua}\n{network}").as_bytes()));
+        format!("{ua_level}.{ip_level}:{digest}")
+    }
+
+    /// Returns `true` if this token has been bound to another context than `fingerprint`.
+    ///
+    /// Tokens without a fingerprint, or with one built for other strictness levels, are
+    /// accepted, because they were issued before the client's binding has been changed. They
+    /// will be bound with the current levels on rotation.
+    pub fn is_fingerprint_mismatch(&self, fingerprint: &str) -> bool {
+        let Some(stored) = &self.fingerprint else {
+            return false;
+        };
+        match (stored.split_once(':'), fingerprint.split_once(':')) {
+            (Some((levels_stored, _)), Some((levels, _))) if levels_stored == levels => {
+                !secure_compare(stored, fingerprint)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Extracts the browser family and its major version from a `User-Agent`.
+///
+/// Only the common browsers are detected explicitly. Any other agent falls back to its first
+/// product token, which is good enough to detect a stolen token used with another tool.
+fn user_agent_family(user_agent: &str) -> (&str, &str) {
+    // The order matters, because most browsers include the tokens of the ones they are based on.
+    const FAMILIES: [(&str, &str); 9] = [
+        ("Edg/", "Edge"),
+        ("EdgA/", "Edge"),
+        ("OPR/", "Opera"),
+        ("SamsungBrowser/", "Samsung Internet"),
+        ("Firefox/", "Firefox"),
+        ("FxiOS/", "Firefox"),
+        ("CriOS/", "Chrome"),
+        ("Chrome/", "Chrome"),
+        ("Version/", "Safari"),
+    ];
+
+    for (token, family) in FAMILIES {
+        if let Some((_, rest)) = user_agent.split_once(token)
+            && (family != "Safari" || user_agent.contains("Safari/"))
+        {
+            return (family, major_version(rest));
+        }
+    }
+
+    let product = user_agent.split_whitespace().next().unwrap_or_default();
+    match product.split_once('/') {
+        Some((name, version)) => (name, major_version(version)),
+        None => (product, ""),
+    }
+}
+
+#[inline]
+fn major_version(version: &str) -> &str {
+    let end = version
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(version.len());
+    &version[..end]
+}
+
+/// Returns the network of `ip` for the given prefix lengths.
+fn ip_network(ip: IpAddr, v4_prefix: u8, v6_prefix: u8) -> String {
+    match ip.to_canonical() {
+        IpAddr::V4(ip) => {
+            let mask = u32::MAX << (32 - v4_prefix);
+            let network = Ipv4Addr::from(u32::from(ip) & mask);
+            format!("{network}/{v4_prefix}")
+        }
+        IpAddr::V6(ip) => {
+            let mask = u128::MAX << (128 - v6_prefix);
+            let network = Ipv6Addr::from(u128::from(ip) & mask);
+            format!("{network}/{v6_prefix}")
+        }
+    }
+}
+
+/// Returns the hex encoded SHA-256 digest of the `validation_str`, which is the last 49 characters
+/// of a refresh token. The value is a part of the token signature and has enough entropy to not
+/// need a salt.
+#[inline]
+pub(crate) fn hash_validation_str(validation_str: &str) -> String {
+    hex::encode(sha256!(validation_str.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_validation_str() {
+        let validation_str = "aWN1vEWlPKiUbl0RLn2nsQ2jHUiSspmSyXxHmIE2HXJ7BUjlr";
+        assert_eq!(validation_str.len(), 49);
+
+        let id = hash_validation_str(validation_str);
+        assert_eq!(id.len(), 64);
+        assert_eq!(id, hash_validation_str(validation_str));
+        assert_ne!(id, hash_validation_str(&validation_str[1..]));
+
+        // legacy plain text values can never collide with a digest
+        assert_ne!(id.len(), validation_str.len());
+    }
+
+    #[test]
+    fn test_user_agent_family() {
+        let firefox = "Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0";
+        assert_eq!(user_agent_family(firefox), ("Firefox", "128"));
+
+        let chrome = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 \
+            (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36";
+        assert_eq!(user_agent_family(chrome), ("Chrome", "126"));
+
+        let edge = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 \
+            (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36 Edg/126.0.2592.87";
+        assert_eq!(user_agent_family(edge), ("Edge", "126"));
+
+        let safari = "Mozilla/5.0 (Macintosh; Intel Mac OS X 14_5) AppleWebKit/605.1.15 \
+            (KHTML, like Gecko) Version/17.5 Safari/605.1.15";
+        assert_eq!(user_agent_family(safari), ("Safari", "17"));
+
+        assert_eq!(user_agent_family("curl/8.8.0"), ("curl", "8"));
+        assert_eq!(user_agent_family(""), ("", ""));
+    }
+
+    #[test]
+    fn test_ip_network() {
+        let ip = IpAddr::from([192, 168, 14, 2]);
+        assert_eq!(ip_network(ip, 16, 48), "192.168.0.0/16");
+        assert_eq!(ip_network(ip, 24, 64), "192.168.14.0/24");
+
+        let mapped = IpAddr::from(Ipv4Addr::new(192, 168, 14, 2).to_ipv6_mapped());
+        assert_eq!(ip_network(mapped, 24, 64), "192.168.14.0/24");
+
+        let ip: IpAddr = "2001:db8:1234:5678::1".parse().unwrap();
+        assert_eq!(ip_network(ip, 16, 48), "2001:db8:1234::/48");
+        assert_eq!(ip_network(ip, 24, 64), "2001:db8:1234:5678::/64");
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let ua_old = "Mozilla/5.0 (X11; Linux x86_64; rv:127.0) Gecko/20100101 Firefox/127.0";
+        let ua_new = "Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0";
+        let ip = IpAddr::from([192, 168, 14, 2]);
+        let ip_near = IpAddr::from([192, 168, 20, 7]);
+
+        let mut rt = RefreshToken {
+            id: String::default(),
+            user_id: String::default(),
+            nbf: 0,
+            exp: 0,
+            scope: None,
+            is_mfa: false,
+            session_id: None,
+            access_token_jti: None,
+            fingerprint: None,
+            client_id: None,
+            granted: None,
+            last_used: None,
+        };
+        let family_wide = ClientRefreshTokenBinding {
+            user_agent: UserAgentBinding::Family,
+            ip: IpBinding::Wide,
+        };
+        let strict = ClientRefreshTokenBinding {
+            user_agent: UserAgentBinding::FamilyMajor,
+            ip: IpBinding::Narrow,
+        };
+
+        // unbound tokens are always accepted
+        let fp = RefreshToken::build_fingerprint(&family_wide, ua_old, ip);
+        assert!(!rt.is_fingerprint_mismatch(&fp));
+
+        // a browser update and a new IP in the same /16 are accepted with loose levels
+        rt.fingerprint = Some(fp);
+        let fp = RefreshToken::build_fingerprint(&family_wide, ua_new, ip_near);
+        assert!(!rt.is_fingerprint_mismatch(&fp));
+        let fp = RefreshToken::build_fingerprint(&family_wide, "curl/8.8.0", ip);
+        assert!(rt.is_fingerprint_mismatch(&fp));
+
+        // strict levels reject both
+        rt.fingerprint = Some(RefreshToken::build_fingerprint(&strict, ua_old, ip));
+        let fp = RefreshToken::build_fingerprint(&strict, ua_new, ip);
+        assert!(rt.is_fingerprint_mismatch(&fp));
+        let fp = RefreshToken::build_fingerprint(&strict, ua_old, ip_near);
+        assert!(rt.is_fingerprint_mismatch(&fp));
+        let fp = RefreshToken::build_fingerprint(&strict, ua_old, ip);
+        assert!(!rt.is_fingerprint_mismatch(&fp));
+
+        // a changed binding accepts tokens with older levels
+        let fp = RefreshToken::build_fingerprint(&family_wide, "curl/8.8.0", ip_near);
+        assert!(!rt.is_fingerprint_mismatch(&fp));
+    }
+}
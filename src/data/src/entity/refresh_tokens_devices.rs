@@ -1,4 +1,5 @@
 use crate::database::DB;
+use crate::entity::refresh_tokens::hash_validation_str;
 use chrono::Utc;
 use hiqlite::macros::params;
 use rauthy_common::is_hiqlite;
@@ -8,6 +9,8 @@ use serde::{Deserialize, Serialize};
 use std::fmt::{Debug, Formatter};
 use time::OffsetDateTime;
 
+/// The `id` is the hex encoded SHA-256 digest of the last 49 characters of the token, exactly like
+/// for a `RefreshToken`.
 #[derive(Serialize, Deserialize, FromPgRow)]
 pub struct RefreshTokenDevice {
     pub id: String,
@@ -38,7 +41,7 @@ impl Debug for RefreshTokenDevice {
 // CRUD
 impl RefreshTokenDevice {
     pub async fn create(
-        id: String,
+        validation_str: &str,
         device_id: String,
         user_id: String,
         nbf: i64,
@@ -47,7 +50,7 @@ impl RefreshTokenDevice {
         access_token_jti: Option<String>,
     ) -> Result<Self, ErrorResponse> {
         let rt = Self {
-            id,
+            id: hash_validation_str(validation_str),
             device_id,
             user_id,
             nbf,
@@ -104,37 +107,56 @@ impl RefreshTokenDevice {
     //     Ok(())
     // }
 
-    pub async fn find(id: &str) -> Result<Self, ErrorResponse> {
+    /// Finds a valid refresh token by the last 49 characters of the raw token. Legacy, plain text
+    /// values are accepted once and get their `id` replaced with the digest. See
+    /// `RefreshToken::find()`.
+    ///
+    /// The `OR id = $2` fallback here and in `find_opt()` can be removed at the same time as the
+    /// one for `RefreshToken`s, but only after `device_grant.refresh_token_lifetime` (72h by
+    /// default) has passed since the update as well.
+    pub async fn find(validation_str: &str) -> Result<Self, ErrorResponse> {
+        let id = hash_validation_str(validation_str);
         let now = Utc::now().timestamp();
-        let sql = "SELECT * FROM refresh_tokens_devices WHERE id = $1 AND exp > $2";
+        let sql = "SELECT * FROM refresh_tokens_devices WHERE (id = $1 OR id = $2) AND exp > $3";
 
-        if is_hiqlite() {
+        let mut slf: Self = if is_hiqlite() {
             DB::hql()
-                .query_as_one(sql, params!(id, now))
+                .query_as_one(sql, params!(id, validation_str, now))
                 .await
                 .map_err(|_| {
                     ErrorResponse::new(
                         ErrorResponseType::NotFound,
                         "Device Refresh Token does not exist",
                     )
-                })
+                })?
         } else {
-            DB::pg_query_one(sql, &[&id, &now]).await.map_err(|_| {
-                ErrorResponse::new(
-                    ErrorResponseType::NotFound,
-                    "Device Refresh Token does not exist",
-                )
-            })
+            DB::pg_query_one(sql, &[&id, &validation_str, &now])
+                .await
+                .map_err(|_| {
+                    ErrorResponse::new(
+                        ErrorResponseType::NotFound,
+                        "Device Refresh Token does not exist",
+                    )
+                })?
+        };
+
+        if slf.id == validation_str {
+            slf.rehash_legacy_id().await?;
         }
+
+        Ok(slf)
     }
 
-    pub async fn find_opt(id: &str) -> Result<Option<Self>, ErrorResponse> {
-        let sql = "SELECT * FROM refresh_tokens_devices WHERE id = $1";
+    pub async fn find_opt(validation_str: &str) -> Result<Option<Self>, ErrorResponse> {
+        let id = hash_validation_str(validation_str);
+        let sql = "SELECT * FROM refresh_tokens_devices WHERE id = $1 OR id = $2";
 
         let slf = if is_hiqlite() {
-            DB::hql().query_as_optional(sql, params!(id)).await?
+            DB::hql()
+                .query_as_optional(sql, params!(id, validation_str))
+                .await?
         } else {
-            DB::pg_query_opt(sql, &[&id]).await?
+            DB::pg_query_opt(sql, &[&id, &validation_str]).await?
         };
 
         Ok(slf)
@@ -180,6 +202,23 @@ impl RefreshTokenDevice {
         Ok(())
     }
 
+    /// Replaces a legacy plain text `id` with its digest.
+    async fn rehash_legacy_id(&mut self) -> Result<(), ErrorResponse> {
+        let id = hash_validation_str(&self.id);
+        let sql = "UPDATE refresh_tokens_devices SET id = $1 WHERE id = $2";
+
+        if is_hiqlite() {
+            DB::hql()
+                .execute(sql, params!(id.clone(), self.id.clone()))
+                .await?;
+        } else {
+            DB::pg_execute(sql, &[&id, &self.id]).await?;
+        }
+
+        self.id = id;
+        Ok(())
+    }
+
     pub async fn save(&self) -> Result<(), ErrorResponse> {
         let sql = r#"
 INSERT INTO refresh_tokens_devices
@@ -81,7 +81,7 @@ impl Role {
 
             for mut user in users {
                 user.delete_role(&role.name);
-                user.save_txn_append(&mut txn);
+                user.save_txn_append(&mut txn)?;
             }
 
             txn.push((sql, params!(role.id.clone())));
@@ -178,7 +178,7 @@ impl Role {
 
             for mut user in users {
                 user.roles = user.roles.replace(&role.name, &new_role.name);
-                user.save_txn_append(&mut txn);
+                user.save_txn_append(&mut txn)?;
             }
 
             txn.push((
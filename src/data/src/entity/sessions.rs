@@ -1,25 +1,29 @@
 use crate::api_cookie::ApiCookie;
+use crate::cache_layer::{self, CacheName};
 use crate::database::{Cache, DB};
+use crate::entity::auth_provider_logout::AuthProviderSession;
 use crate::entity::continuation_token::ContinuationToken;
 use crate::entity::users::User;
 use crate::rauthy_config::RauthyConfig;
 use actix_web::cookie::SameSite;
 use actix_web::http::header::{HeaderName, HeaderValue};
-use actix_web::{HttpRequest, cookie, web};
+use actix_web::{HttpRequest, HttpResponse, cookie, web};
 use chrono::Utc;
 use hiqlite::macros::{FromRow, params};
 use rauthy_api_types::generic::SearchParamsIdx;
 use rauthy_common::constants::{
-    CACHE_TTL_SESSION, COOKIE_SESSION, COOKIE_SESSION_FED_CM, CSRF_HEADER,
+    CACHE_TTL_SESSION, CACHE_TTL_SESSION_ROTATED, COOKIE_SESSION, COOKIE_SESSION_FED_CM,
+    CSRF_HEADER,
 };
 use rauthy_common::is_hiqlite;
-use rauthy_common::utils::get_rand;
+use rauthy_common::utils::{get_rand, secure_compare};
 use rauthy_derive::FromPgRow;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 use std::cmp::max;
 use std::fmt::{Debug, Formatter};
+use std::mem;
 use std::net::IpAddr;
 use std::ops::Add;
 use std::str::FromStr;
@@ -125,7 +129,7 @@ impl Session {
             DB::pg_execute(sql, &[&self.id]).await?;
         }
 
-        DB::hql().delete(Cache::Session, self.id).await?;
+        cache_layer::delete(CacheName::Session, self.id).await?;
 
         Ok(())
     }
@@ -150,16 +154,42 @@ impl Session {
         };
 
         for id in sids {
-            DB::hql().delete(Cache::Session, id).await?;
+            cache_layer::delete(CacheName::Session, id).await?;
         }
 
         Ok(())
     }
 
+    /// Removes all cached sessions for the given user without deleting them. Returns the amount
+    /// of removed cache entries.
+    pub async fn clear_cache_for_user(user_id: &str) -> Result<u32, ErrorResponse> {
+        let sql = "SELECT id FROM sessions WHERE user_id = $1";
+        let sids: Vec<String> = if is_hiqlite() {
+            DB::hql()
+                .query_raw(sql, params!(user_id))
+                .await?
+                .into_iter()
+                .map(|mut row| row.get("id"))
+                .collect()
+        } else {
+            DB::pg_query_rows(sql, &[&user_id], 2)
+                .await?
+                .into_iter()
+                .map(|row| row.get("id"))
+                .collect()
+        };
+
+        for id in &sids {
+            cache_layer::delete(CacheName::Session, id.clone()).await?;
+        }
+
+        Ok(sids.len() as u32)
+    }
+
     pub async fn find(id: String) -> Result<Self, ErrorResponse> {
         let client = DB::hql();
 
-        if let Some(slf) = client.get(Cache::Session, id.clone()).await? {
+        if let Some(slf) = cache_layer::get(CacheName::Session, id.clone()).await? {
             return Ok(slf);
         }
 
@@ -170,9 +200,7 @@ impl Session {
             DB::pg_query_one(sql, &[&id]).await?
         };
 
-        client
-            .put(Cache::Session, slf.id.clone(), &slf, CACHE_TTL_SESSION)
-            .await?;
+        cache_layer::put(CacheName::Session, slf.id.clone(), &slf, CACHE_TTL_SESSION).await?;
 
         Ok(slf)
     }
@@ -353,9 +381,8 @@ OFFSET $3"#;
             ids
         };
 
-        let client = DB::hql();
         for sid in &sids {
-            client.delete(Cache::Session, sid.clone()).await?;
+            cache_layer::delete(CacheName::Session, sid.clone()).await?;
         }
 
         Ok(sids)
@@ -409,9 +436,7 @@ SET user_id = $3, roles = $4, groups = $5, is_mfa = $6, state = $7, exp = $8, la
             .await?;
         }
 
-        DB::hql()
-            .put(Cache::Session, self.id.clone(), self, CACHE_TTL_SESSION)
-            .await?;
+        cache_layer::put(CacheName::Session, self.id.clone(), self, CACHE_TTL_SESSION).await?;
 
         Ok(())
     }
@@ -453,6 +478,87 @@ SET user_id = $3, roles = $4, groups = $5, is_mfa = $6, state = $7, exp = $8, la
         Ok(res)
     }
 
+    /// Issues a new `id` for this session to prevent session fixation. Must be called whenever
+    /// the privilege level of the session changes, like after a successful password, upstream
+    /// provider or passkey login.
+    ///
+    /// The old id is invalid immediately. Rows referencing it are migrated, and server-side
+    /// artifacts like authorization codes created before the rotation can still find the current
+    /// session via `Session::resolve_id()`. The caller must send out the new cookies.
+    /// The CSRF token is kept on purpose, because it is useless without the session cookie and
+    /// an already loaded UI must keep working.
+    pub async fn rotate_id(&mut self) -> Result<(), ErrorResponse> {
+        let old_id = mem::replace(&mut self.id, get_rand(32));
+
+        let sql_session = "UPDATE sessions SET id = $1 WHERE id = $2";
+        let sql_refresh_tokens = "UPDATE refresh_tokens SET session_id = $1 WHERE session_id = $2";
+        let sql_login_states = "UPDATE user_login_states SET session_id = $1 WHERE session_id = $2";
+
+        if is_hiqlite() {
+            for res in DB::hql()
+                .txn([
+                    (sql_session, params!(self.id.clone(), old_id.clone())),
+                    (sql_refresh_tokens, params!(self.id.clone(), old_id.clone())),
+                    (sql_login_states, params!(self.id.clone(), old_id.clone())),
+                ])
+                .await?
+            {
+                res?;
+            }
+        } else {
+            let mut cl = DB::pg().await?;
+            let txn = cl.transaction().await?;
+            DB::pg_txn_append(&txn, sql_session, &[&self.id, &old_id]).await?;
+            DB::pg_txn_append(&txn, sql_refresh_tokens, &[&self.id, &old_id]).await?;
+            DB::pg_txn_append(&txn, sql_login_states, &[&self.id, &old_id]).await?;
+            txn.commit().await?;
+        }
+
+        let client = DB::hql();
+        cache_layer::delete(CacheName::Session, old_id.clone()).await?;
+        // makes sure the session exists even if it has not been persisted so far
+        self.upsert().await?;
+        AuthProviderSession::rotate(&old_id, self).await?;
+
+        client
+            .put(
+                Cache::AuthCode,
+                Self::cache_idx_rotated(&old_id),
+                &self.id,
+                CACHE_TTL_SESSION_ROTATED,
+            )
+            .await?;
+
+        debug!("Session id has been rotated");
+
+        Ok(())
+    }
+
+    /// Resolves a session `id`, that may have been rotated after it has been saved somewhere else,
+    /// to the current one. Must never be used for ids coming from a client.
+    pub async fn resolve_id(id: String) -> Result<String, ErrorResponse> {
+        let client = DB::hql();
+
+        let mut id = id;
+        // one rotation for the login and possibly one for the MFA step
+        for _ in 0..4 {
+            let new_id: Option<String> = client
+                .get(Cache::AuthCode, Self::cache_idx_rotated(&id))
+                .await?;
+            match new_id {
+                Some(new_id) => id = new_id,
+                None => break,
+            }
+        }
+
+        Ok(id)
+    }
+
+    #[inline]
+    fn cache_idx_rotated(old_id: &str) -> String {
+        format!("sid_rot_{old_id}")
+    }
+
     #[inline]
     pub async fn set_authenticated(&mut self, user: &User) -> Result<(), ErrorResponse> {
         self.last_seen = Utc::now().timestamp();
@@ -549,6 +655,25 @@ impl Session {
         )
     }
 
+    /// Adds the session cookies to an already built response, e.g. after `Session::rotate_id()`.
+    pub fn add_cookies(&self, resp: &mut HttpResponse) -> Result<(), ErrorResponse> {
+        let mut cookies = vec![self.client_cookie()];
+        if RauthyConfig::get().vars.fedcm.experimental_enable {
+            cookies.push(self.client_cookie_fed_cm());
+        }
+
+        for cookie in cookies {
+            resp.add_cookie(&cookie).map_err(|err| {
+                ErrorResponse::new(
+                    ErrorResponseType::Internal,
+                    format!("Error adding session cookie: {err}"),
+                )
+            })?;
+        }
+
+        Ok(())
+    }
+
     pub fn extract_from_req(
         session_req: web::ReqData<Option<Session>>,
     ) -> Result<Session, ErrorResponse> {
@@ -592,7 +717,7 @@ impl Session {
             DB::pg_execute(sql, &[&now, &state, &self.id]).await?;
         }
 
-        DB::hql().delete(Cache::Session, self.id).await?;
+        cache_layer::delete(CacheName::Session, self.id).await?;
 
         Ok(())
     }
@@ -737,7 +862,7 @@ impl Session {
                 "CSRF Token not present in HTTP Header",
             ));
         }
-        if self.csrf_token.eq(csrf?) {
+        if secure_compare(&self.csrf_token, csrf?) {
             return Ok(());
         }
 
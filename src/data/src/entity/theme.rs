@@ -1,4 +1,6 @@
+use crate::cache_layer::{self, CacheName};
 use crate::database::{Cache, DB};
+use crate::html::login_page_context::LoginPageContext;
 use chrono::Utc;
 use hiqlite::macros::params;
 use rauthy_api_types::themes::ThemeRequestResponse;
@@ -153,9 +155,8 @@ impl ThemeCssFull {
     /// Returns the CSS variables for the light theme to be inserted directly into
     /// an E-Mail `body { }` CSS.
     pub async fn find_theme_variables_email() -> Result<String, ErrorResponse> {
-        if let Some(bytes) = DB::hql()
-            .get_bytes(Cache::Html, CACHE_KEY_EMAIL_CSS)
-            .await?
+        if let Some(bytes) =
+            cache_layer::get_bytes(CacheName::ThemeCss, CACHE_KEY_EMAIL_CSS).await?
         {
             let vars = String::from_utf8_lossy(&bytes);
             return Ok(vars.to_string());
@@ -166,14 +167,13 @@ impl ThemeCssFull {
         full.light.append_css(&mut vars)?;
         write!(vars, "--border-radius:{};", full.border_radius)?;
 
-        DB::hql()
-            .put_bytes(
-                Cache::Html,
-                CACHE_KEY_EMAIL_CSS,
-                vars.as_bytes().to_vec(),
-                None,
-            )
-            .await?;
+        cache_layer::put_bytes(
+            CacheName::ThemeCss,
+            CACHE_KEY_EMAIL_CSS,
+            vars.as_bytes().to_vec(),
+            None,
+        )
+        .await?;
 
         Ok(vars)
     }
@@ -204,6 +204,7 @@ impl ThemeCssFull {
 
         DB::hql().clear_cache(Cache::ThemeTs).await?;
         DB::hql().clear_cache(Cache::Html).await?;
+        LoginPageContext::invalidate_all().await?;
 
         Ok(())
     }
@@ -250,6 +251,7 @@ SET last_update = $2, version = $3, light = $4, dark = $5, border_radius = $6
 
         DB::hql().clear_cache(Cache::ThemeTs).await?;
         DB::hql().clear_cache(Cache::Html).await?;
+        LoginPageContext::invalidate_all().await?;
 
         Ok(())
     }
@@ -273,45 +275,41 @@ impl ThemeCssFull {
     }
 
     pub async fn br(client_id: &str) -> Result<Vec<u8>, ErrorResponse> {
-        if let Some(bytes) = DB::hql()
-            .get_bytes(Cache::Html, Self::cache_key_br(client_id))
-            .await?
+        if let Some(bytes) =
+            cache_layer::get_bytes(CacheName::ThemeCss, Self::cache_key_br(client_id)).await?
         {
             return Ok(bytes);
         }
 
         let plain = Self::plain(client_id.to_string()).await?;
         let compressed = compress_br(plain.as_bytes())?;
-        DB::hql()
-            .put_bytes(
-                Cache::Html,
-                Self::cache_key_br(client_id),
-                compressed.clone(),
-                None,
-            )
-            .await?;
+        cache_layer::put_bytes(
+            CacheName::ThemeCss,
+            Self::cache_key_br(client_id),
+            compressed.clone(),
+            None,
+        )
+        .await?;
 
         Ok(compressed)
     }
 
     pub async fn gzip(client_id: &str) -> Result<Vec<u8>, ErrorResponse> {
-        if let Some(bytes) = DB::hql()
-            .get_bytes(Cache::Html, Self::cache_key_gzip(client_id))
-            .await?
+        if let Some(bytes) =
+            cache_layer::get_bytes(CacheName::ThemeCss, Self::cache_key_gzip(client_id)).await?
         {
             return Ok(bytes);
         }
 
         let plain = Self::plain(client_id.to_string()).await?;
         let compressed = compress_gzip(plain.as_bytes())?;
-        DB::hql()
-            .put_bytes(
-                Cache::Html,
-                Self::cache_key_gzip(client_id),
-                compressed.clone(),
-                None,
-            )
-            .await?;
+        cache_layer::put_bytes(
+            CacheName::ThemeCss,
+            Self::cache_key_gzip(client_id),
+            compressed.clone(),
+            None,
+        )
+        .await?;
 
         Ok(compressed)
     }
@@ -1,6 +1,7 @@
 use crate::database::{Cache, DB};
 use crate::entity::scopes::Scope;
 use crate::entity::users::User;
+use chrono::Utc;
 use deadpool_postgres::GenericClient;
 use hiqlite::Params;
 use hiqlite::macros::{FromRow, params};
@@ -15,6 +16,8 @@ use rauthy_error::{ErrorResponse, ErrorResponseType};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::{HashMap, HashSet};
+use tracing::warn;
+use validator::ValidateEmail;
 
 // Additional custom attributes for users. These can be set for every user and then mapped to a
 // scope, to include them in JWT tokens.
@@ -26,6 +29,7 @@ pub struct UserAttrConfigEntity {
     #[column(parse)]
     pub typ: Option<UserAttrConfigTyp>,
     pub user_editable: bool,
+    pub auto_populate_from_claim: Option<String>,
 }
 
 // CRUD
@@ -60,28 +64,32 @@ impl UserAttrConfigEntity {
             DB::hql()
                 .execute(
                     r#"
-INSERT INTO user_attr_config (name, desc, default_value, typ, user_editable)
-VALUES ($1, $2, $3, $4, $5)"#,
+INSERT INTO user_attr_config
+(name, desc, default_value, typ, user_editable, auto_populate_from_claim)
+VALUES ($1, $2, $3, $4, $5, $6)"#,
                     params!(
                         &new_attr.name,
                         &new_attr.desc,
                         &default_value,
                         typ,
-                        user_editable
+                        user_editable,
+                        &new_attr.auto_populate_from_claim
                     ),
                 )
                 .await?;
         } else {
             DB::pg_execute(
                 r#"
-INSERT INTO user_attr_config (name, "desc", default_value, typ, user_editable)
-VALUES ($1, $2, $3, $4, $5)"#,
+INSERT INTO user_attr_config
+(name, "desc", default_value, typ, user_editable, auto_populate_from_claim)
+VALUES ($1, $2, $3, $4, $5, $6)"#,
                 &[
                     &new_attr.name,
                     &new_attr.desc,
                     &default_value,
                     &typ,
                     &user_editable,
+                    &new_attr.auto_populate_from_claim,
                 ],
             )
             .await?;
@@ -95,6 +103,7 @@ VALUES ($1, $2, $3, $4, $5)"#,
             default_value: default_value.clone(),
             typ: new_attr.typ.clone(),
             user_editable: new_attr.user_editable.unwrap_or(false),
+            auto_populate_from_claim: new_attr.auto_populate_from_claim,
         };
         attrs.push(slf.clone());
         DB::hql()
@@ -274,6 +283,8 @@ VALUES ($1, $2, $3, $4, $5)"#,
         slf.name.clone_from(&req_data.name);
         slf.desc.clone_from(&req_data.desc);
         slf.user_editable = req_data.user_editable.unwrap_or(false);
+        slf.auto_populate_from_claim
+            .clone_from(&req_data.auto_populate_from_claim);
         slf.default_value = if let Some(v) = &req_data.default_value {
             Some(serde_json::to_vec(v)?)
         } else {
@@ -358,14 +369,16 @@ VALUES ($1, $2, $3, $4, $5)"#,
             txn.push((
                 r#"
 UPDATE user_attr_config
-SET name  = $1, desc = $2, default_value = $3, typ = $4, user_editable = $5
-WHERE name = $6"#,
+SET name  = $1, desc = $2, default_value = $3, typ = $4, user_editable = $5,
+    auto_populate_from_claim = $6
+WHERE name = $7"#,
                 params!(
                     &slf.name,
                     &slf.desc,
                     &slf.default_value,
                     typ,
                     slf.user_editable,
+                    &slf.auto_populate_from_claim,
                     name
                 ),
             ));
@@ -383,14 +396,16 @@ WHERE name = $6"#,
                 &txn,
                 r#"
 UPDATE user_attr_config
-SET name  = $1, "desc" = $2, default_value = $3, typ = $4, user_editable = $5
-WHERE name = $6"#,
+SET name  = $1, "desc" = $2, default_value = $3, typ = $4, user_editable = $5,
+    auto_populate_from_claim = $6
+WHERE name = $7"#,
                 &[
                     &slf.name,
                     &slf.desc,
                     &slf.default_value,
                     typ,
                     &slf.user_editable,
+                    &slf.auto_populate_from_claim,
                     &name,
                 ],
             )
@@ -422,6 +437,14 @@ impl UserAttrConfigEntity {
         Ok(set)
     }
 
+    /// Checks if an upstream claim `value` can be used for an attribute of the given `typ`.
+    fn claim_matches_typ(typ: Option<&UserAttrConfigTyp>, value: &Value) -> bool {
+        match typ {
+            None => true,
+            Some(UserAttrConfigTyp::Email) => value.as_str().is_some_and(|v| v.validate_email()),
+        }
+    }
+
     pub fn names_hash_set(mut slf: Vec<Self>) -> HashSet<String> {
         let mut res = HashSet::with_capacity(slf.len());
         slf.drain(..).for_each(|s| {
@@ -441,6 +464,7 @@ impl From<UserAttrConfigEntity> for UserAttrConfigValueResponse {
                 .map(|v| serde_json::from_slice(&v).unwrap_or_default()),
             typ: value.typ,
             user_editable: value.user_editable,
+            auto_populate_from_claim: value.auto_populate_from_claim,
         }
     }
 }
@@ -452,6 +476,9 @@ pub struct UserAttrValueEntity {
     pub user_id: String,
     pub key: String,
     pub value: Vec<u8>,
+    /// Only set if the current value has been synced from an upstream claim. Any manual update
+    /// with a different value resets it.
+    pub last_synced_from_claim_at: Option<i64>,
 }
 
 impl UserAttrValueEntity {
@@ -460,6 +487,10 @@ impl UserAttrValueEntity {
         Ok(())
     }
 
+    pub async fn clear_cache_for_user(user_id: &str) -> Result<(), ErrorResponse> {
+        Self::clear_cache(Self::cache_idx(user_id)).await
+    }
+
     /// You MUST `UserAttrValueEntity::clear_cache()` for returned UserCacheKeys
     /// after successful txn commit!
     pub async fn delete_all_by_key(
@@ -542,6 +573,7 @@ impl UserAttrValueEntity {
                         user_id: user_id.to_string(),
                         key: default.name,
                         value,
+                        last_synced_from_claim_at: None,
                     });
                 }
             }
@@ -630,7 +662,9 @@ impl UserAttrValueEntity {
                         r#"
 INSERT INTO user_attr_values (user_id, key, value)
 VALUES ($1, $2, $3)
-ON CONFLICT(user_id, key) DO UPDATE SET value = $3"#,
+ON CONFLICT(user_id, key) DO UPDATE SET value = $3, last_synced_from_claim_at = CASE
+    WHEN user_attr_values.value = $3 THEN user_attr_values.last_synced_from_claim_at
+END"#,
                         params!(user_id, value.key, v),
                     ));
                 }
@@ -662,7 +696,9 @@ ON CONFLICT(user_id, key) DO UPDATE SET value = $3"#,
                         r#"
     INSERT INTO user_attr_values (user_id, key, value)
     VALUES ($1, $2, $3)
-    ON CONFLICT(user_id, key) DO UPDATE SET value = $3"#,
+    ON CONFLICT(user_id, key) DO UPDATE SET value = $3, last_synced_from_claim_at = CASE
+        WHEN user_attr_values.value = $3 THEN user_attr_values.last_synced_from_claim_at
+    END"#,
                         &[&user_id, &value.key, &v],
                     )
                     .await?;
@@ -686,7 +722,234 @@ ON CONFLICT(user_id, key) DO UPDATE SET value = $3"#,
     }
 }
 
+// usage lookups
 impl UserAttrValueEntity {
+    pub async fn count_by_key(key: &str, value: Option<&str>) -> Result<i64, ErrorResponse> {
+        let count: i64 = if let Some(value) = value {
+            let [v1, v2] = Self::value_candidates(value);
+            let sql = r#"
+SELECT COUNT(*) AS count FROM user_attr_values
+WHERE key = $1 AND (value = $2 OR value = $3)"#;
+            if is_hiqlite() {
+                DB::hql()
+                    .query_raw(sql, params!(key, v1, v2))
+                    .await?
+                    .remove(0)
+                    .get("count")
+            } else {
+                DB::pg_query_rows(sql, &[&key, &v1, &v2], 1)
+                    .await?
+                    .remove(0)
+                    .get("count")
+            }
+        } else {
+            let sql = "SELECT COUNT(*) AS count FROM user_attr_values WHERE key = $1";
+            if is_hiqlite() {
+                DB::hql()
+                    .query_raw(sql, params!(key))
+                    .await?
+                    .remove(0)
+                    .get("count")
+            } else {
+                DB::pg_query_rows(sql, &[&key], 1)
+                    .await?
+                    .remove(0)
+                    .get("count")
+            }
+        };
+        Ok(count)
+    }
+
+    pub async fn count_distinct_by_key(key: &str) -> Result<i64, ErrorResponse> {
+        let sql = "SELECT COUNT(DISTINCT value) AS count FROM user_attr_values WHERE key = $1";
+        let count: i64 = if is_hiqlite() {
+            DB::hql()
+                .query_raw(sql, params!(key))
+                .await?
+                .remove(0)
+                .get("count")
+        } else {
+            DB::pg_query_rows(sql, &[&key], 1)
+                .await?
+                .remove(0)
+                .get("count")
+        };
+        Ok(count)
+    }
+
+    /// Returns all user values for the given `key`, ordered by `user_id`. If `value` is given,
+    /// only exactly matching values are returned.
+    pub async fn find_by_key(
+        key: &str,
+        value: Option<&str>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<Self>, ErrorResponse> {
+        let size_hint = limit as usize;
+
+        let res = if let Some(value) = value {
+            let [v1, v2] = Self::value_candidates(value);
+            let sql = r#"
+SELECT * FROM user_attr_values
+WHERE key = $1 AND (value = $2 OR value = $3)
+ORDER BY user_id
+LIMIT $4
+OFFSET $5"#;
+            if is_hiqlite() {
+                DB::hql()
+                    .query_as(sql, params!(key, v1, v2, limit, offset))
+                    .await?
+            } else {
+                DB::pg_query(sql, &[&key, &v1, &v2, &limit, &offset], size_hint).await?
+            }
+        } else {
+            let sql = r#"
+SELECT * FROM user_attr_values
+WHERE key = $1
+ORDER BY user_id
+LIMIT $2
+OFFSET $3"#;
+            if is_hiqlite() {
+                DB::hql().query_as(sql, params!(key, limit, offset)).await?
+            } else {
+                DB::pg_query(sql, &[&key, &limit, &offset], size_hint).await?
+            }
+        };
+
+        Ok(res)
+    }
+
+    /// Returns each distinct value for the given `key` together with the amount of users having
+    /// it, the most used values first.
+    pub async fn find_distinct_by_key(
+        key: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<(Vec<u8>, i64)>, ErrorResponse> {
+        let sql = r#"
+SELECT value, COUNT(*) AS count FROM user_attr_values
+WHERE key = $1
+GROUP BY value
+ORDER BY count DESC, value
+LIMIT $2
+OFFSET $3"#;
+
+        let res = if is_hiqlite() {
+            DB::hql()
+                .query_raw(sql, params!(key, limit, offset))
+                .await?
+                .into_iter()
+                .map(|mut r| (r.get::<Vec<u8>>("value"), r.get::<i64>("count")))
+                .collect()
+        } else {
+            DB::pg_query_rows(sql, &[&key, &limit, &offset], limit as usize)
+                .await?
+                .into_iter()
+                .map(|r| (r.get::<_, Vec<u8>>("value"), r.get::<_, i64>("count")))
+                .collect()
+        };
+
+        Ok(res)
+    }
+
+    /// Values are stored as serialized JSON. A query value, which is valid JSON itself, like
+    /// `1234` or `true`, matches its typed value as well as the same value as a string.
+    pub fn value_candidates(value: &str) -> [Vec<u8>; 2] {
+        let as_string = serde_json::to_vec(&Value::String(value.to_string()))
+            .expect("a String to always be serializable");
+        let as_json = serde_json::from_str::<Value>(value)
+            .ok()
+            .and_then(|v| serde_json::to_vec(&v).ok())
+            .unwrap_or_else(|| as_string.clone());
+        [as_string, as_json]
+    }
+}
+
+impl UserAttrValueEntity {
+    /// Syncs all values for attributes with an `auto_populate_from_claim` from the upstream
+    /// `claims` of a login via an upstream auth provider.
+    ///
+    /// The upstream claim is the source of truth. A manually set value is kept as long as the
+    /// claim does not exist, but it will be overwritten as soon as the claim contains a different
+    /// value.
+    pub async fn sync_from_claims(user_id: &str, claims: &[u8]) -> Result<(), ErrorResponse> {
+        let configs = UserAttrConfigEntity::find_all()
+            .await?
+            .into_iter()
+            .filter(|c| c.auto_populate_from_claim.is_some())
+            .collect::<Vec<_>>();
+        if configs.is_empty() {
+            return Ok(());
+        }
+
+        let claims = serde_json::from_slice::<Value>(claims)?;
+        let existing = Self::find_for_user(user_id).await?;
+        let updates = Self::claim_updates(&configs, &existing, &claims)?;
+        if updates.is_empty() {
+            return Ok(());
+        }
+
+        let now = Utc::now().timestamp();
+        let sql = r#"
+INSERT INTO user_attr_values (user_id, key, value, last_synced_from_claim_at)
+VALUES ($1, $2, $3, $4)
+ON CONFLICT(user_id, key) DO UPDATE SET value = $3, last_synced_from_claim_at = $4"#;
+
+        if is_hiqlite() {
+            let txn = updates
+                .into_iter()
+                .map(|(key, value)| (sql, params!(user_id, key, value, now)))
+                .collect::<Vec<_>>();
+            DB::hql().txn(txn).await?;
+        } else {
+            let mut cl = DB::pg().await?;
+            let txn = cl.transaction().await?;
+            for (key, value) in updates {
+                DB::pg_txn_append(&txn, sql, &[&user_id, &key, &value, &now]).await?;
+            }
+            txn.commit().await?;
+        }
+
+        Self::clear_cache_for_user(user_id).await
+    }
+
+    /// Returns `(key, value)` for each attribute, which needs an update from the upstream
+    /// `claims`.
+    fn claim_updates(
+        configs: &[UserAttrConfigEntity],
+        existing: &[Self],
+        claims: &Value,
+    ) -> Result<Vec<(String, Vec<u8>)>, ErrorResponse> {
+        let mut res = Vec::new();
+
+        for config in configs {
+            let Some(claim) = config.auto_populate_from_claim.as_deref() else {
+                continue;
+            };
+            let Some(value) = claims.get(claim).filter(|v| !v.is_null()) else {
+                continue;
+            };
+            if !UserAttrConfigEntity::claim_matches_typ(config.typ.as_ref(), value) {
+                warn!(
+                    "Upstream claim '{claim}' does not match the type of user attribute '{}' \
+                    - ignoring it",
+                    config.name
+                );
+                continue;
+            }
+
+            let value = serde_json::to_vec(value)?;
+            if !existing
+                .iter()
+                .any(|v| v.key == config.name && v.value == value)
+            {
+                res.push((config.name.clone(), value));
+            }
+        }
+
+        Ok(res)
+    }
+
     #[inline]
     fn cache_idx(user_id: &str) -> String {
         format!("{IDX_USER_ATTR_CONFIG}{user_id}")
@@ -699,6 +962,115 @@ impl From<UserAttrValueEntity> for UserAttrValueResponse {
         Self {
             key: value.key,
             value: val,
+            last_synced_from_claim_at: value.last_synced_from_claim_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    fn config(name: &str, claim: &str, typ: Option<UserAttrConfigTyp>) -> UserAttrConfigEntity {
+        UserAttrConfigEntity {
+            name: name.to_string(),
+            desc: None,
+            default_value: None,
+            typ,
+            user_editable: false,
+            auto_populate_from_claim: Some(claim.to_string()),
+        }
+    }
+
+    fn value(key: &str, value: Value, synced: Option<i64>) -> UserAttrValueEntity {
+        UserAttrValueEntity {
+            user_id: "user1".to_string(),
+            key: key.to_string(),
+            value: serde_json::to_vec(&value).unwrap(),
+            last_synced_from_claim_at: synced,
         }
     }
+
+    #[test]
+    fn test_value_candidates() {
+        let [s, v] = UserAttrValueEntity::value_candidates("1234");
+        assert_eq!(s, br#""1234""#);
+        assert_eq!(v, b"1234");
+
+        let [s, v] = UserAttrValueEntity::value_candidates("true");
+        assert_eq!(s, br#""true""#);
+        assert_eq!(v, b"true");
+
+        // not valid JSON -> a string only
+        let [s, v] = UserAttrValueEntity::value_candidates("cost center");
+        assert_eq!(s, br#""cost center""#);
+        assert_eq!(v, s);
+
+        let [s, v] = UserAttrValueEntity::value_candidates(r#"{"a": 1}"#);
+        assert_eq!(s, br#""{\"a\": 1}""#);
+        assert_eq!(v, br#"{"a":1}"#);
+    }
+
+    #[test]
+    fn test_claim_updates() {
+        let configs = [
+            config("department", "department", None),
+            config("cost_center", "cc", None),
+            config("contact", "contact_email", Some(UserAttrConfigTyp::Email)),
+        ];
+        let claims = json!({
+            "department": "Engineering",
+            "cc": 4711,
+            "contact_email": "no email",
+        });
+
+        // new values are populated, invalid types are ignored
+        let updates = UserAttrValueEntity::claim_updates(&configs, &[], &claims).unwrap();
+        assert_eq!(
+            updates,
+            vec![
+                ("department".to_string(), b"\"Engineering\"".to_vec()),
+                ("cost_center".to_string(), b"4711".to_vec()),
+            ]
+        );
+
+        // nothing to do for unchanged values
+        let existing = [
+            value("department", json!("Engineering"), Some(1)),
+            value("cost_center", json!(4711), Some(1)),
+        ];
+        let updates = UserAttrValueEntity::claim_updates(&configs, &existing, &claims).unwrap();
+        assert!(updates.is_empty());
+
+        let claims = json!({
+            "contact_email": "hr@localhost.de",
+            "cc": null,
+        });
+        let updates = UserAttrValueEntity::claim_updates(&configs, &existing, &claims).unwrap();
+        assert_eq!(
+            updates,
+            vec![("contact".to_string(), b"\"hr@localhost.de\"".to_vec())]
+        );
+    }
+
+    #[test]
+    fn test_claim_updates_manual_edits() {
+        let configs = [config("department", "department", None)];
+        // manually set by an admin -> no sync timestamp
+        let existing = [value("department", json!("Sales"), None)];
+
+        // manual edits are kept as long as the claim does not exist
+        let updates = UserAttrValueEntity::claim_updates(&configs, &existing, &json!({})).unwrap();
+        assert!(updates.is_empty());
+
+        // the upstream claim is the source of truth and overwrites differing manual edits
+        let claims = json!({ "department": "Engineering" });
+        let updates = UserAttrValueEntity::claim_updates(&configs, &existing, &claims).unwrap();
+        assert_eq!(
+            updates,
+            vec![("department".to_string(), b"\"Engineering\"".to_vec())]
+        );
+    }
 }
@@ -0,0 +1,251 @@
+use crate::database::DB;
+use crate::entity::auth_providers::AuthProvider;
+use crate::entity::users::User;
+use crate::rauthy_config::RauthyConfig;
+use chrono::Utc;
+use cryptr::EncValue;
+use hiqlite::macros::params;
+use rauthy_api_types::users::{
+    UserFederationChange, UserFederationFieldChange, UserFederationResponse,
+};
+use rauthy_common::is_hiqlite;
+use rauthy_derive::FromPgRow;
+use rauthy_error::ErrorResponse;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+/// The amount of upstream driven changes we keep for each user.
+const HISTORY_LEN: usize = 10;
+
+/// The most recent, validated claim set a federated user received from its upstream provider.
+/// The claims are always stored encrypted, the `history` contains the last upstream driven
+/// changes as JSON.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromPgRow)]
+pub struct UserFederationClaims {
+    pub user_id: String,
+    pub provider_id: String,
+    pub claims: Vec<u8>,
+    pub updated: i64,
+    pub history: String,
+}
+
+// CRUD
+impl UserFederationClaims {
+    pub async fn delete(user_id: &str) -> Result<(), ErrorResponse> {
+        let sql = "DELETE FROM user_federation_claims WHERE user_id = $1";
+        if is_hiqlite() {
+            DB::hql().execute(sql, params!(user_id)).await?;
+        } else {
+            DB::pg_execute(sql, &[&user_id]).await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn find(user_id: &str) -> Result<Option<Self>, ErrorResponse> {
+        let sql = "SELECT * FROM user_federation_claims WHERE user_id = $1";
+        let slf = if is_hiqlite() {
+            DB::hql().query_as_optional(sql, params!(user_id)).await?
+        } else {
+            DB::pg_query_opt(sql, &[&user_id]).await?
+        };
+
+        Ok(slf)
+    }
+
+    pub async fn find_all() -> Result<Vec<Self>, ErrorResponse> {
+        let sql = "SELECT * FROM user_federation_claims";
+        let res = if is_hiqlite() {
+            DB::hql().query_as(sql, params!()).await?
+        } else {
+            DB::pg_query(sql, &[], 0).await?
+        };
+
+        Ok(res)
+    }
+
+    /// Saves the latest upstream `claims` and appends the `changes` to the history, if any.
+    pub async fn upsert(
+        user_id: String,
+        provider_id: String,
+        claims: &[u8],
+        changes: Vec<UserFederationFieldChange>,
+    ) -> Result<(), ErrorResponse> {
+        let now = Utc::now().timestamp();
+        let history = Self::find(&user_id)
+            .await?
+            .map(|slf| slf.history)
+            .unwrap_or_default();
+        let history = Self::history_push(
+            &history,
+            UserFederationChange {
+                timestamp: now,
+                provider_id: provider_id.clone(),
+                changes,
+            },
+        );
+        let claims = EncValue::encrypt(claims)?.into_bytes().to_vec();
+
+        let sql = r#"
+INSERT INTO user_federation_claims (user_id, provider_id, claims, updated, history)
+VALUES ($1, $2, $3, $4, $5)
+ON CONFLICT (user_id) DO UPDATE
+SET provider_id = $2, claims = $3, updated = $4, history = $5"#;
+        if is_hiqlite() {
+            DB::hql()
+                .execute(sql, params!(user_id, provider_id, claims, now, history))
+                .await?;
+        } else {
+            DB::pg_execute(sql, &[&user_id, &provider_id, &claims, &now, &history]).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Re-encrypts the claims with the currently active key. Used for secret migration.
+    pub async fn save_re_encrypted(self) -> Result<(), ErrorResponse> {
+        let dec = EncValue::try_from(self.claims)?.decrypt()?;
+        let claims = EncValue::encrypt(dec.as_ref())?.into_bytes().to_vec();
+
+        let sql = "UPDATE user_federation_claims SET claims = $1 WHERE user_id = $2";
+        if is_hiqlite() {
+            DB::hql()
+                .execute(sql, params!(claims, self.user_id))
+                .await?;
+        } else {
+            DB::pg_execute(sql, &[&claims, &self.user_id]).await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl UserFederationClaims {
+    /// Called after each successful upstream login. Does not return an error on purpose, because
+    /// it must never block a login.
+    pub async fn save_from_login(
+        user_id: &str,
+        provider_id: &str,
+        claims: Option<&[u8]>,
+        changes: Vec<UserFederationFieldChange>,
+    ) {
+        let res = match claims {
+            Some(claims)
+                if RauthyConfig::get()
+                    .vars
+                    .auth_providers
+                    .retain_upstream_claims =>
+            {
+                Self::upsert(
+                    user_id.to_string(),
+                    provider_id.to_string(),
+                    claims,
+                    changes,
+                )
+                .await
+            }
+            // make sure to purge old data after the retention has been disabled
+            _ => Self::delete(user_id).await,
+        };
+
+        if let Err(err) = res {
+            error!(?err, "Error saving upstream claims for user {user_id}");
+        }
+    }
+
+    pub fn history(&self) -> Vec<UserFederationChange> {
+        serde_json::from_str(&self.history).unwrap_or_default()
+    }
+
+    /// Prepends the `change` to the JSON `history`, if it contains any changes, and caps it to
+    /// the last `HISTORY_LEN` entries.
+    fn history_push(history: &str, change: UserFederationChange) -> String {
+        let mut entries: Vec<UserFederationChange> =
+            serde_json::from_str(history).unwrap_or_default();
+        if !change.changes.is_empty() {
+            entries.insert(0, change);
+            entries.truncate(HISTORY_LEN);
+        }
+        serde_json::to_string(&entries).unwrap()
+    }
+}
+
+impl UserFederationClaims {
+    /// Builds the admin view of the `user`'s upstream federation.
+    pub async fn build_response(user: User) -> Result<UserFederationResponse, ErrorResponse> {
+        let auth_provider_name = match &user.auth_provider_id {
+            Some(id) => AuthProvider::find(id).await.ok().map(|p| p.name),
+            None => None,
+        };
+
+        let claims_retained = RauthyConfig::get()
+            .vars
+            .auth_providers
+            .retain_upstream_claims;
+        let (claims, claims_updated, history) = match Self::find(&user.id).await? {
+            Some(slf) if claims_retained => {
+                let history = slf.history();
+                let dec = EncValue::try_from(slf.claims)?.decrypt()?;
+                let claims = serde_json::from_slice(dec.as_ref()).ok();
+                (claims, Some(slf.updated), history)
+            }
+            _ => (None, None, Vec::default()),
+        };
+
+        Ok(UserFederationResponse {
+            user_id: user.id,
+            auth_provider_id: user.auth_provider_id,
+            auth_provider_name,
+            federation_uid: user.federation_uid,
+            claims_retained,
+            claims,
+            claims_updated,
+            history,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(timestamp: i64, field: &str) -> UserFederationChange {
+        UserFederationChange {
+            timestamp,
+            provider_id: "provider".to_string(),
+            changes: vec![UserFederationFieldChange {
+                field: field.to_string(),
+                old: Some("old".to_string()),
+                new: Some("new".to_string()),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_history_push() {
+        let history = UserFederationClaims::history_push("", change(1, "email"));
+        let entries: Vec<UserFederationChange> = serde_json::from_str(&history).unwrap();
+        assert_eq!(entries, vec![change(1, "email")]);
+
+        // logins without any changes do not pollute the history
+        let history = UserFederationClaims::history_push(
+            &history,
+            UserFederationChange {
+                timestamp: 2,
+                provider_id: "provider".to_string(),
+                changes: Vec::default(),
+            },
+        );
+        let entries: Vec<UserFederationChange> = serde_json::from_str(&history).unwrap();
+        assert_eq!(entries.len(), 1);
+
+        let mut history = history;
+        for ts in 2..=15 {
+            history = UserFederationClaims::history_push(&history, change(ts, "given_name"));
+        }
+        let entries: Vec<UserFederationChange> = serde_json::from_str(&history).unwrap();
+        assert_eq!(entries.len(), HISTORY_LEN);
+        assert_eq!(entries.first().unwrap().timestamp, 15);
+        assert_eq!(entries.last().unwrap().timestamp, 6);
+    }
+}
@@ -0,0 +1,256 @@
+use crate::database::{Cache, DB};
+use crate::entity::clients::Client;
+use chrono::Utc;
+use hiqlite::macros::params;
+use rauthy_api_types::users::UserGrantResponse;
+use rauthy_common::constants::{CACHE_TTL_GRANT_LAST_USED, IDX_GRANT_LAST_USED};
+use rauthy_common::is_hiqlite;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use std::collections::BTreeMap;
+
+/// A single refresh token row without any token value, which is all we need for the
+/// aggregation.
+#[derive(Debug)]
+struct GrantRow {
+    client_id: String,
+    scope: Option<String>,
+    exp: i64,
+    nbf: i64,
+    granted: Option<i64>,
+    last_used: Option<i64>,
+}
+
+/// The refresh tokens a single client holds for a user, aggregated into a single grant.
+/// Device tokens from the `device_code` flow are managed via the user devices and are not part
+/// of a grant.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserGrant {
+    pub client_id: String,
+    pub first_granted: i64,
+    pub last_used: Option<i64>,
+    pub expires: i64,
+    pub scopes: Vec<String>,
+    pub tokens: i64,
+}
+
+impl UserGrant {
+    #[inline]
+    fn cache_idx_last_used(user_id: &str, client_id: &str) -> String {
+        format!("{IDX_GRANT_LAST_USED}{user_id}_{client_id}")
+    }
+
+    /// Tokens issued by older versions have no `client_id` and cannot be attributed to a grant.
+    /// Expired tokens are fetched as well, because a rotated token keeps the latest `last_used`
+    /// until the cleanup scheduler removes it.
+    pub async fn find_for_user(user_id: &str) -> Result<Vec<Self>, ErrorResponse> {
+        let sql = r#"
+SELECT client_id, scope, exp, nbf, granted, last_used
+FROM refresh_tokens
+WHERE user_id = $1 AND client_id IS NOT NULL"#;
+
+        let rows = if is_hiqlite() {
+            DB::hql()
+                .query_raw(sql, params!(user_id))
+                .await?
+                .into_iter()
+                .map(|mut row| GrantRow {
+                    client_id: row.get("client_id"),
+                    scope: row.get("scope"),
+                    exp: row.get("exp"),
+                    nbf: row.get("nbf"),
+                    granted: row.get("granted"),
+                    last_used: row.get("last_used"),
+                })
+                .collect()
+        } else {
+            DB::pg_query_rows(sql, &[&user_id], 0)
+                .await?
+                .into_iter()
+                .map(|row| GrantRow {
+                    client_id: row.get("client_id"),
+                    scope: row.get("scope"),
+                    exp: row.get("exp"),
+                    nbf: row.get("nbf"),
+                    granted: row.get("granted"),
+                    last_used: row.get("last_used"),
+                })
+                .collect()
+        };
+
+        Ok(Self::aggregate(rows, Utc::now().timestamp()))
+    }
+
+    /// Deletes all refresh tokens of the given client for this user.
+    pub async fn revoke(user_id: &str, client_id: &str) -> Result<(), ErrorResponse> {
+        let sql = "DELETE FROM refresh_tokens WHERE user_id = $1 AND client_id = $2";
+        let rows_affected = if is_hiqlite() {
+            DB::hql().execute(sql, params!(user_id, client_id)).await?
+        } else {
+            DB::pg_execute(sql, &[&user_id, &client_id]).await?
+        };
+
+        DB::hql()
+            .delete(Cache::App, Self::cache_idx_last_used(user_id, client_id))
+            .await?;
+
+        if rows_affected == 0 {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::NotFound,
+                "No grant exists for this client",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Updates `last_used` for all refresh tokens of this client after a `refresh_token` grant.
+    /// The write is buffered through the cache and happens at most once per minute for each
+    /// user and client, no matter how often a client refreshes.
+    pub async fn touch_last_used(user_id: &str, client_id: &str) -> Result<(), ErrorResponse> {
+        let idx = Self::cache_idx_last_used(user_id, client_id);
+        let buffered: Option<i64> = DB::hql().get(Cache::App, idx.clone()).await?;
+        if buffered.is_some() {
+            return Ok(());
+        }
+
+        let now = Utc::now().timestamp();
+        let sql = "UPDATE refresh_tokens SET last_used = $1 WHERE user_id = $2 AND client_id = $3";
+        if is_hiqlite() {
+            DB::hql()
+                .execute(sql, params!(now, user_id, client_id))
+                .await?;
+        } else {
+            DB::pg_execute(sql, &[&now, &user_id, &client_id]).await?;
+        }
+
+        DB::hql()
+            .put(Cache::App, idx, &now, CACHE_TTL_GRANT_LAST_USED)
+            .await?;
+
+        Ok(())
+    }
+
+    fn aggregate(rows: Vec<GrantRow>, now: i64) -> Vec<Self> {
+        let mut grants: BTreeMap<String, Self> = BTreeMap::new();
+        let mut last_used: BTreeMap<String, i64> = BTreeMap::new();
+
+        for row in rows {
+            if let Some(ts) = row.last_used {
+                let lu = last_used.entry(row.client_id.clone()).or_insert(ts);
+                *lu = (*lu).max(ts);
+            }
+            if row.exp <= now {
+                continue;
+            }
+
+            let granted = row.granted.unwrap_or(row.nbf);
+            let grant = grants.entry(row.client_id.clone()).or_insert_with(|| Self {
+                client_id: row.client_id,
+                first_granted: granted,
+                last_used: None,
+                expires: row.exp,
+                scopes: Vec::new(),
+                tokens: 0,
+            });
+            grant.first_granted = grant.first_granted.min(granted);
+            grant.expires = grant.expires.max(row.exp);
+            grant.tokens += 1;
+            for scope in row.scope.as_deref().unwrap_or_default().split(' ') {
+                if !scope.is_empty() && !grant.scopes.iter().any(|s| s == scope) {
+                    grant.scopes.push(scope.to_string());
+                }
+            }
+        }
+
+        grants
+            .into_values()
+            .map(|mut grant| {
+                grant.last_used = last_used.get(&grant.client_id).copied();
+                grant.scopes.sort();
+                grant
+            })
+            .collect()
+    }
+
+    pub async fn into_response(self) -> UserGrantResponse {
+        let client_name = Client::find(self.client_id.clone())
+            .await
+            .ok()
+            .and_then(|c| c.name);
+
+        UserGrantResponse {
+            client_id: self.client_id,
+            client_name,
+            first_granted: self.first_granted,
+            last_used: self.last_used,
+            expires: self.expires,
+            scopes: self.scopes,
+            tokens: self.tokens,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(
+        client_id: &str,
+        scope: &str,
+        exp: i64,
+        granted: i64,
+        last_used: Option<i64>,
+    ) -> GrantRow {
+        GrantRow {
+            client_id: client_id.to_string(),
+            scope: Some(scope.to_string()),
+            exp,
+            nbf: granted + 10,
+            granted: Some(granted),
+            last_used,
+        }
+    }
+
+    #[test]
+    fn test_aggregate_multi_client() {
+        let now = 1_000;
+        let rows = vec![
+            row("app1", "openid offline_access", 2_000, 100, Some(900)),
+            row("app1", "openid email offline_access", 3_000, 50, None),
+            // rotated and already expired, but still holds the latest usage
+            row("app1", "openid profile", 990, 10, Some(950)),
+            row("app2", "openid", 1_500, 700, None),
+            // fully expired grants do not show up at all
+            row("app3", "openid", 999, 1, Some(998)),
+        ];
+
+        let grants = UserGrant::aggregate(rows, now);
+        assert_eq!(grants.len(), 2);
+
+        let app1 = &grants[0];
+        assert_eq!(app1.client_id, "app1");
+        assert_eq!(app1.first_granted, 50);
+        assert_eq!(app1.last_used, Some(950));
+        assert_eq!(app1.expires, 3_000);
+        assert_eq!(app1.tokens, 2);
+        assert_eq!(app1.scopes, vec!["email", "offline_access", "openid"]);
+
+        let app2 = &grants[1];
+        assert_eq!(app2.client_id, "app2");
+        assert_eq!(app2.first_granted, 700);
+        assert_eq!(app2.last_used, None);
+        assert_eq!(app2.expires, 1_500);
+        assert_eq!(app2.tokens, 1);
+        assert_eq!(app2.scopes, vec!["openid"]);
+    }
+
+    #[test]
+    fn test_aggregate_legacy_granted() {
+        let mut legacy = row("app1", "", 2_000, 100, None);
+        legacy.granted = None;
+        legacy.scope = None;
+
+        let grants = UserGrant::aggregate(vec![legacy], 1_000);
+        assert_eq!(grants[0].first_granted, 110);
+        assert!(grants[0].scopes.is_empty());
+    }
+}
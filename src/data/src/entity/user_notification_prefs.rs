@@ -0,0 +1,145 @@
+use crate::database::DB;
+use crate::entity::users::User;
+use crate::rauthy_config::RauthyConfig;
+use hiqlite::macros::params;
+use rauthy_api_types::users::{
+    NotificationCategory, NotificationPrefResponse, NotificationPrefsRequest,
+};
+use rauthy_common::is_hiqlite;
+use rauthy_derive::FromPgRow;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error};
+
+/// A users' opt-in or opt-out for a single `NotificationCategory`. If no row exists, the
+/// instance default from the `[email]` config applies.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromPgRow)]
+pub struct UserNotificationPref {
+    pub user_id: String,
+    pub category: String,
+    pub enabled: bool,
+}
+
+// CRUD
+impl UserNotificationPref {
+    pub async fn find_for_user(user_id: &str) -> Result<Vec<Self>, ErrorResponse> {
+        let sql = "SELECT * FROM user_notification_prefs WHERE user_id = $1";
+        let res = if is_hiqlite() {
+            DB::hql().query_as(sql, params!(user_id)).await?
+        } else {
+            DB::pg_query(sql, &[&user_id], NotificationCategory::ALL.len()).await?
+        };
+
+        Ok(res)
+    }
+
+    pub async fn upsert_for_user(
+        user_id: String,
+        payload: NotificationPrefsRequest,
+    ) -> Result<(), ErrorResponse> {
+        if let Some(pref) = payload
+            .prefs
+            .iter()
+            .find(|p| !p.enabled && !p.category.is_suppressible())
+        {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!(
+                    "Notifications for '{}' cannot be disabled",
+                    pref.category.as_str()
+                ),
+            ));
+        }
+
+        let sql = r#"
+INSERT INTO user_notification_prefs (user_id, category, enabled)
+VALUES ($1, $2, $3)
+ON CONFLICT (user_id, category) DO UPDATE SET enabled = $3"#;
+        for pref in payload.prefs {
+            let category = pref.category.as_str();
+            if is_hiqlite() {
+                DB::hql()
+                    .execute(sql, params!(user_id.clone(), category, pref.enabled))
+                    .await?;
+            } else {
+                DB::pg_execute(sql, &[&user_id, &category, &pref.enabled]).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl UserNotificationPref {
+    /// Returns the effective preferences for all existing categories.
+    pub async fn resolve_for_user(
+        user_id: &str,
+    ) -> Result<Vec<NotificationPrefResponse>, ErrorResponse> {
+        let prefs = Self::find_for_user(user_id).await?;
+
+        let res = NotificationCategory::ALL
+            .into_iter()
+            .map(|category| {
+                let pref = prefs.iter().find(|p| p.category == category.as_str());
+                NotificationPrefResponse {
+                    category,
+                    enabled: !category.is_suppressible()
+                        || pref
+                            .map(|p| p.enabled)
+                            .unwrap_or_else(|| Self::default_enabled(category)),
+                    suppressible: category.is_suppressible(),
+                    is_default: pref.is_none(),
+                }
+            })
+            .collect();
+
+        Ok(res)
+    }
+
+    fn default_enabled(category: NotificationCategory) -> bool {
+        let config = &RauthyConfig::get().vars.email;
+        match category {
+            NotificationCategory::NewLoginLocation => config.notify_new_login_location,
+            NotificationCategory::PasswordExpiry => config.notify_password_expiry,
+            NotificationCategory::EmailChange
+            | NotificationCategory::PasswordReset
+            | NotificationCategory::AccountDeletion => true,
+        }
+    }
+}
+
+/// The single gate for all security E-Mails. Must be checked before sending any E-Mail of the
+/// given `category` to the `user`.
+///
+/// Non-suppressible categories always return `true`. If the preferences cannot be read, the
+/// instance default is used to never lose an important E-Mail because of a DB hiccup.
+pub async fn should_notify(user: &User, category: NotificationCategory) -> bool {
+    if !category.is_suppressible() {
+        return true;
+    }
+
+    let enabled = match UserNotificationPref::find_for_user(&user.id).await {
+        Ok(prefs) => prefs
+            .into_iter()
+            .find(|p| p.category == category.as_str())
+            .map(|p| p.enabled)
+            .unwrap_or_else(|| UserNotificationPref::default_enabled(category)),
+        Err(err) => {
+            error!(
+                ?err,
+                "Error looking up notification preferences for user {}", user.id
+            );
+            UserNotificationPref::default_enabled(category)
+        }
+    };
+
+    if !enabled {
+        debug!(
+            user_id = %user.id,
+            category = category.as_str(),
+            "E-Mail notification suppressed by user preference"
+        );
+    }
+
+    enabled
+}
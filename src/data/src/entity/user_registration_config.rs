@@ -0,0 +1,130 @@
+use crate::database::{Cache, DB};
+use crate::entity::groups::Group;
+use crate::entity::roles::Role;
+use crate::rauthy_config::RauthyConfig;
+use hiqlite::macros::params;
+use rauthy_api_types::users::{UserRegistrationConfigRequest, UserRegistrationConfigResponse};
+use rauthy_common::constants::{CACHE_TTL_APP, IDX_USER_REG_CONFIG, RAUTHY_ADMIN_ROLE};
+use rauthy_common::is_hiqlite;
+use rauthy_common::utils::{deserialize, serialize};
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use serde::{Deserialize, Serialize};
+
+/// Defaults for users created via the open registration endpoint. They are stored as a single
+/// value inside the `config` table.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserRegistrationConfig {
+    pub default_roles: Vec<String>,
+    pub default_groups: Vec<String>,
+}
+
+// CRUD
+impl UserRegistrationConfig {
+    pub async fn find() -> Result<Self, ErrorResponse> {
+        let client = DB::hql();
+        if let Some(slf) = client.get(Cache::App, IDX_USER_REG_CONFIG).await? {
+            return Ok(slf);
+        }
+
+        let sql = "SELECT data FROM config WHERE id = 'user_registration'";
+        let bytes: Option<Vec<u8>> = if is_hiqlite() {
+            client
+                .query_raw(sql, params!())
+                .await?
+                .into_iter()
+                .next()
+                .map(|mut row| row.get("data"))
+        } else {
+            DB::pg_query_rows(sql, &[], 1)
+                .await?
+                .into_iter()
+                .next()
+                .map(|row| row.get("data"))
+        };
+        let slf = match bytes {
+            Some(bytes) => deserialize::<Self>(&bytes)?,
+            None => Self::default(),
+        };
+
+        client
+            .put(Cache::App, IDX_USER_REG_CONFIG, &slf, CACHE_TTL_APP)
+            .await?;
+
+        Ok(slf)
+    }
+
+    pub async fn save(&self) -> Result<(), ErrorResponse> {
+        let data = serialize(self)?;
+
+        let sql = r#"
+INSERT INTO config (id, data)
+VALUES ('user_registration', $1)
+ON CONFLICT (id) DO UPDATE SET data = $1"#;
+        if is_hiqlite() {
+            DB::hql().execute(sql, params!(data)).await?;
+        } else {
+            DB::pg_execute(sql, &[&data]).await?;
+        }
+
+        DB::hql()
+            .put(Cache::App, IDX_USER_REG_CONFIG, self, CACHE_TTL_APP)
+            .await?;
+
+        Ok(())
+    }
+}
+
+impl UserRegistrationConfig {
+    /// Makes sure that all given roles and groups exist. In contrast to the sanitization for
+    /// new users, unknown values are rejected instead of silently dropped.
+    pub async fn try_from_req(req: UserRegistrationConfigRequest) -> Result<Self, ErrorResponse> {
+        if req.default_roles.iter().any(|r| r == RAUTHY_ADMIN_ROLE) {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("'{RAUTHY_ADMIN_ROLE}' can never be a default role for new users"),
+            ));
+        }
+
+        let roles = Role::find_all().await?;
+        if let Some(role) = req
+            .default_roles
+            .iter()
+            .find(|name| !roles.iter().any(|r| &r.name == *name))
+        {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("Role '{role}' does not exist"),
+            ));
+        }
+
+        let groups = Group::find_all().await?;
+        if let Some(group) = req
+            .default_groups
+            .iter()
+            .find(|name| !groups.iter().any(|g| &g.name == *name))
+        {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("Group '{group}' does not exist"),
+            ));
+        }
+
+        Ok(Self {
+            default_roles: req.default_roles,
+            default_groups: req.default_groups,
+        })
+    }
+}
+
+impl From<UserRegistrationConfig> for UserRegistrationConfigResponse {
+    fn from(value: UserRegistrationConfig) -> Self {
+        Self {
+            default_roles: value.default_roles,
+            default_groups: value.default_groups,
+            require_admin_approval: RauthyConfig::get()
+                .vars
+                .user_registration
+                .require_admin_approval,
+        }
+    }
+}
@@ -1,7 +1,10 @@
 use crate::database::{Cache, DB};
 use crate::email::email_change_confirm::send_email_confirm_change;
 use crate::email::email_change_info::send_email_change_info_new;
+use crate::email::mailer::{self, EmailFeedback};
 use crate::email::password_reset::send_pwd_reset;
+use crate::entity::auth_provider_refresh_tokens::AuthProviderRefreshToken;
+use crate::entity::auth_providers::NewFederatedUserCreated;
 use crate::entity::continuation_token::ContinuationToken;
 use crate::entity::groups::Group;
 use crate::entity::magic_links::{MagicLink, MagicLinkUsage};
@@ -15,13 +18,18 @@ use crate::entity::sessions::Session;
 use crate::entity::theme::ThemeCssFull;
 use crate::entity::tos::ToS;
 use crate::entity::tos_user_accept::ToSUserAccept;
+use crate::entity::user_attr::UserAttrValueEntity;
+use crate::entity::user_federation_claims::UserFederationClaims;
+use crate::entity::user_registration_config::UserRegistrationConfig;
 use crate::entity::users_values::UserValues;
 use crate::entity::webauthn::{PasskeyEntity, WebauthnServiceReq};
 use crate::events::event::Event;
 use crate::html::templates::{HtmlTemplate, UserEmailChangeConfirmHtml};
 use crate::language::Language;
+use crate::pii::{self, UserPii};
 use crate::rauthy_config::RauthyConfig;
 use actix_web::HttpRequest;
+use actix_web_lab::sse;
 use argon2::PasswordHash;
 use chrono::Utc;
 use core::str::Split;
@@ -31,15 +39,17 @@ use rauthy_api_types::PatchOp;
 use rauthy_api_types::generic::SearchParamsIdx;
 use rauthy_api_types::users::{
     NewUserRegistrationRequest, NewUserRequest, UpdateUserRequest, UpdateUserSelfRequest,
-    UserAccountTypeResponse, UserResponse, UserResponseSimple, UserValuesRequest,
-    UserValuesResponse,
+    UserAccountTypeResponse, UserReindexResponse, UserResponse, UserResponseSimple,
+    UserValuesRequest, UserValuesResponse, UsersReindexProgress,
 };
 use rauthy_common::constants::{
-    CACHE_TTL_APP, CACHE_TTL_USER, IDX_USER_COUNT, IDX_USERS, RAUTHY_ADMIN_ROLE,
+    CACHE_TTL_APP, CACHE_TTL_USER, CACHE_TTL_USERS_REINDEX, IDX_GROUPS, IDX_USER_COUNT, IDX_USERS,
+    IDX_USERS_REINDEX, RAUTHY_ADMIN_ROLE, USERS_REINDEX_RATE_LIMIT_SECS,
 };
 use rauthy_common::is_hiqlite;
 use rauthy_common::password_hasher::{ComparePasswords, HashPassword};
-use rauthy_common::utils::{new_store_id, real_ip_from_req};
+use rauthy_common::shutdown::{TaskGuard, is_draining};
+use rauthy_common::utils::{new_store_id, normalize_email, real_ip};
 use rauthy_derive::FromPgRow;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use serde::{Deserialize, Serialize};
@@ -47,16 +57,18 @@ use std::cmp::max;
 use std::default::Default;
 use std::fmt::{Debug, Formatter};
 use std::ops::Add;
+use std::time::Instant;
 use time::OffsetDateTime;
-use tracing::{debug, error, trace};
+use tokio::sync::mpsc;
+use tracing::{debug, error, info, trace, warn};
 
 static SQL_SAVE: &str = r#"
 UPDATE USERS SET
 email = $1, given_name = $2, family_name = $3, password = $4, roles = $5, groups = $6, enabled = $7,
 email_verified = $8, password_expires = $9, last_login = $10, last_failed_login = $11,
 failed_login_attempts = $12, language = $13, webauthn_user_id = $14, user_expires = $15,
-auth_provider_id = $16, federation_uid = $17, picture_id = $18
-WHERE id = $19"#;
+auth_provider_id = $16, federation_uid = $17, picture_id = $18, email_hash = $19
+WHERE id = $20"#;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum AccountType {
@@ -87,8 +99,14 @@ impl From<AccountType> for UserAccountTypeResponse {
 #[derive(Clone, Serialize, Deserialize, FromPgRow)]
 pub struct User {
     pub id: String,
+    #[serde(deserialize_with = "pii::deserialize")]
+    #[column(pii)]
     pub email: String,
+    #[serde(deserialize_with = "pii::deserialize")]
+    #[column(pii)]
     pub given_name: String,
+    #[serde(deserialize_with = "pii::deserialize_opt")]
+    #[column(pii)]
     pub family_name: Option<String>,
     pub password: Option<String>,
     pub roles: String,
@@ -203,10 +221,13 @@ impl User {
         Ok(())
     }
 
+    /// With `EmailFeedback::Confirm`, the user is deleted again, if the invite E-Mail could not
+    /// be sent, so the creation can simply be retried.
     pub async fn create(
         new_user: User,
         post_reset_redirect_uri: Option<String>,
         user_tz: Option<&str>,
+        feedback: EmailFeedback,
     ) -> Result<Self, ErrorResponse> {
         let slf = Self::insert(new_user).await?;
 
@@ -216,19 +237,75 @@ impl User {
             MagicLinkUsage::NewUser(post_reset_redirect_uri),
         )
         .await?;
-        send_pwd_reset(&magic_link, &slf, user_tz).await;
+        if let Err(err) = send_pwd_reset(&magic_link, &slf, user_tz, feedback).await {
+            slf.delete().await?;
+            return Err(err);
+        }
 
         Ok(slf)
     }
 
-    pub async fn create_federated(new_user: User) -> Result<Self, ErrorResponse> {
-        Self::insert(new_user).await
+    /// Inserts a new federated user. Concurrent first logins of the same upstream user will all
+    /// miss the lookups before and race for the insert here. Only one of them can win because of
+    /// the unique `(auth_provider_id, federation_uid)` and `email_hash` indexes. All others get the
+    /// winner's row with their own values applied, so that all logins end up with the same user.
+    pub async fn create_federated(
+        new_user: User,
+    ) -> Result<(Self, NewFederatedUserCreated), ErrorResponse> {
+        Self::create_federated_with(&DbFederatedUsers, new_user).await
+    }
+
+    async fn create_federated_with<S>(
+        store: &S,
+        new_user: User,
+    ) -> Result<(Self, NewFederatedUserCreated), ErrorResponse>
+    where
+        S: FederatedUserStore,
+    {
+        let (Some(provider_id), Some(federation_uid)) = (
+            new_user.auth_provider_id.clone(),
+            new_user.federation_uid.clone(),
+        ) else {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Internal,
+                "federated user without `auth_provider_id` or `federation_uid`",
+            ));
+        };
+
+        let err = match store.insert(new_user.clone()).await {
+            Ok(user) => return Ok((user, NewFederatedUserCreated::Yes)),
+            Err(err) => err,
+        };
+
+        // Any other conflict, like an existing local user with the same email, is a real error.
+        let Ok(mut user) = store
+            .find_by_federation(&provider_id, &federation_uid)
+            .await
+        else {
+            return Err(err);
+        };
+        debug!(
+            "Federated user {} has been created by a concurrent login",
+            user.id
+        );
+
+        user.email = new_user.email;
+        user.given_name = new_user.given_name;
+        user.family_name = new_user.family_name;
+        user.roles = new_user.roles;
+        user.groups = new_user.groups;
+        user.email_verified = new_user.email_verified;
+        user.language = new_user.language;
+        user.last_login = new_user.last_login;
+        store.save(&user).await?;
+
+        Ok((user, NewFederatedUserCreated::No))
     }
 
     pub async fn create_from_new(new_user_req: NewUserRequest) -> Result<User, ErrorResponse> {
         let tz = new_user_req.tz.clone();
         let new_user = User::from_new_user_req(new_user_req).await?;
-        let user = User::create(new_user, None, tz.as_deref()).await?;
+        let user = User::create(new_user, None, tz.as_deref(), EmailFeedback::Confirm).await?;
 
         if tz.is_some() && tz.as_deref() != Some("UTC") && tz.as_deref() != Some("Etc/UTC") {
             UserValues::insert(
@@ -256,15 +333,27 @@ impl User {
         lang: Language,
     ) -> Result<User, ErrorResponse> {
         // pre-uniqueness checks for better UX and error handling
-        User::validate_email_free(req_data.email.clone()).await?;
+        let email = User::normalize_email(&req_data.email);
+        User::validate_email_free(email.clone()).await?;
         if let Some(preferred_username) = &req_data.preferred_username {
             UserValues::validate_preferred_username_free(preferred_username.clone()).await?;
         }
 
+        // Roles or groups may have been deleted since the config was saved.
+        let reg_config = UserRegistrationConfig::find().await?;
+        let roles = Role::sanitize(reg_config.default_roles).await?;
+        let groups = Group::sanitize(Some(reg_config.default_groups)).await?;
+
         let mut new_user = Self {
-            email: req_data.email.to_lowercase(),
+            email,
             given_name: req_data.given_name.unwrap_or_default(),
             family_name: req_data.family_name,
+            roles,
+            groups,
+            enabled: !RauthyConfig::get()
+                .vars
+                .user_registration
+                .require_admin_approval,
             ..Default::default()
         };
         new_user.language = lang;
@@ -275,6 +364,7 @@ impl User {
                 .user_values
                 .as_ref()
                 .and_then(|uv| uv.tz.as_deref()),
+            EmailFeedback::Silent,
         )
         .await?;
 
@@ -356,7 +446,7 @@ impl User {
     }
 
     pub async fn find_by_email(email: String) -> Result<User, ErrorResponse> {
-        let email = email.to_lowercase();
+        let email = User::normalize_email(&email);
 
         let idx = format!("{IDX_USERS}_{email}");
         let client = DB::hql();
@@ -365,11 +455,16 @@ impl User {
             return Ok(slf);
         }
 
-        let sql = "SELECT * FROM users WHERE email = $1";
+        // With encrypted PII, the E-Mail can only be found by its hash.
+        let (sql, value) = if let Some(hash) = pii::email_hash(&email) {
+            ("SELECT * FROM users WHERE email_hash = $1", hash)
+        } else {
+            ("SELECT * FROM users WHERE email = $1", email)
+        };
         let slf = if is_hiqlite() {
-            client.query_as_one(sql, params!(email)).await?
+            client.query_as_one(sql, params!(value)).await?
         } else {
-            DB::pg_query_one(sql, &[&email]).await?
+            DB::pg_query_one(sql, &[&value]).await?
         };
 
         client.put(Cache::User, idx, &slf, CACHE_TTL_USER).await?;
@@ -412,7 +507,7 @@ SELECT id, email, given_name, family_name, created_at, last_login, picture_id
 FROM users
 ORDER BY created_at ASC"#;
 
-        let res = if is_hiqlite() {
+        let mut res: Vec<UserResponseSimple> = if is_hiqlite() {
             DB::hql().query_as(sql, params!()).await?
         } else {
             // for big instances, fetching the count from the cache upfront is a speed improvement
@@ -420,6 +515,7 @@ ORDER BY created_at ASC"#;
             let count = Self::count().await?;
             DB::pg_query(sql, &[], count as usize).await?
         };
+        pii::decrypt_users_simple(&mut res);
 
         Ok(res)
     }
@@ -429,21 +525,37 @@ ORDER BY created_at ASC"#;
         from_created_at: i64,
         batch_size: u16,
     ) -> Result<Vec<Self>, ErrorResponse> {
-        let sql = r#"
+        // Encrypted E-Mails cannot be ordered, but their hash can.
+        let (sql, after) = if pii::is_enabled() {
+            let sql = r#"
+SELECT * FROM users
+WHERE created_at >= $1 AND email_hash > $2
+ORDER BY created_at ASC, email_hash ASC
+LIMIT $3"#;
+            let after = if after_email.is_empty() {
+                String::default()
+            } else {
+                pii::email_hash(after_email).unwrap_or_default()
+            };
+            (sql, after)
+        } else {
+            let sql = r#"
 SELECT * FROM users
 WHERE created_at >= $1 AND email > $2
 ORDER BY created_at ASC, email ASC
 LIMIT $3"#;
+            (sql, after_email.to_string())
+        };
 
         let batch_size = batch_size as i64;
         let res = if is_hiqlite() {
             DB::hql()
-                .query_as(sql, params!(from_created_at, after_email, batch_size))
+                .query_as(sql, params!(from_created_at, after, batch_size))
                 .await?
         } else {
             DB::pg_query(
                 sql,
-                &[&from_created_at, &after_email, &batch_size],
+                &[&from_created_at, &after, &batch_size],
                 batch_size as usize,
             )
             .await?
@@ -529,7 +641,7 @@ LIMIT $3"#;
     ) -> Result<(Vec<UserResponseSimple>, Option<ContinuationToken>), ErrorResponse> {
         let size_hint = page_size as usize;
 
-        let res = if let Some(token) = continuation_token {
+        let mut res = if let Some(token) = continuation_token {
             if backwards {
                 let sql = r#"
 SELECT id, email, given_name, family_name, created_at, last_login, picture_id
@@ -598,6 +710,7 @@ OFFSET $2"#;
                 DB::pg_query(sql, &[&page_size, &offset], size_hint).await?
             }
         };
+        pii::decrypt_users_simple(&mut res);
 
         let token = res
             .last()
@@ -608,11 +721,12 @@ OFFSET $2"#;
 
     pub async fn insert(new_user: User) -> Result<Self, ErrorResponse> {
         let lang = new_user.language.as_str();
+        let pii = new_user.pii()?;
         let sql = r#"
 INSERT INTO users
 (id, email, given_name, family_name, roles, groups, enabled, email_verified, created_at,
-last_login, language, user_expires, auth_provider_id, federation_uid, picture_id)
-VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)"#;
+last_login, language, user_expires, auth_provider_id, federation_uid, picture_id, email_hash)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)"#;
 
         if is_hiqlite() {
             DB::hql()
@@ -620,9 +734,9 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)"#;
                     sql,
                     params!(
                         &new_user.id,
-                        &new_user.email,
-                        &new_user.given_name,
-                        &new_user.family_name,
+                        &pii.email,
+                        &pii.given_name,
+                        &pii.family_name,
                         &new_user.roles,
                         &new_user.groups,
                         new_user.enabled,
@@ -633,7 +747,8 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)"#;
                         new_user.user_expires,
                         &new_user.auth_provider_id,
                         &new_user.federation_uid,
-                        &new_user.picture_id
+                        &new_user.picture_id,
+                        &pii.email_hash
                     ),
                 )
                 .await
@@ -653,9 +768,9 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)"#;
                 sql,
                 &[
                     &new_user.id,
-                    &new_user.email,
-                    &new_user.given_name,
-                    &new_user.family_name,
+                    &pii.email,
+                    &pii.given_name,
+                    &pii.family_name,
                     &new_user.roles,
                     &new_user.groups,
                     &new_user.enabled,
@@ -667,6 +782,7 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)"#;
                     &new_user.auth_provider_id,
                     &new_user.federation_uid,
                     &new_user.picture_id,
+                    &pii.email_hash,
                 ],
             )
             .await
@@ -711,9 +827,9 @@ LIMIT $2"#;
             for mut row in rows {
                 let user = Self {
                     id: row.get("user_id"),
-                    email: row.get("email"),
-                    given_name: row.get("given_name"),
-                    family_name: row.get("family_name"),
+                    email: pii::decrypt(row.get("email")),
+                    given_name: pii::decrypt(row.get("given_name")),
+                    family_name: pii::decrypt_opt(row.get("family_name")),
                     password: None,
                     roles: row.get("roles"),
                     groups: row.get("groups"),
@@ -734,13 +850,14 @@ LIMIT $2"#;
                 let values = UserValues {
                     id: user.id.clone(),
                     birthdate: row.get("birthdate"),
-                    phone: row.get("phone"),
+                    phone: pii::decrypt_opt(row.get("phone")),
                     street: row.get("street"),
                     zip: row.get("zip"),
                     city: row.get("city"),
                     country: row.get("country"),
                     preferred_username: row.get("preferred_username"),
                     tz: row.get("tz"),
+                    locale: None,
                 };
                 res.push((user, values));
             }
@@ -750,9 +867,9 @@ LIMIT $2"#;
             for row in rows {
                 let user = Self {
                     id: row.get("user_id"),
-                    email: row.get("email"),
-                    given_name: row.get("given_name"),
-                    family_name: row.get("family_name"),
+                    email: pii::decrypt(row.get("email")),
+                    given_name: pii::decrypt(row.get("given_name")),
+                    family_name: pii::decrypt_opt(row.get("family_name")),
                     password: None,
                     roles: row.get("roles"),
                     groups: row.get("groups"),
@@ -773,13 +890,14 @@ LIMIT $2"#;
                 let values = UserValues {
                     id: user.id.clone(),
                     birthdate: row.get("birthdate"),
-                    phone: row.get("phone"),
+                    phone: pii::decrypt_opt(row.get("phone")),
                     street: row.get("street"),
                     zip: row.get("zip"),
                     city: row.get("city"),
                     country: row.get("country"),
                     preferred_username: row.get("preferred_username"),
                     tz: row.get("tz"),
+                    locale: None,
                 };
                 res.push((user, values));
             }
@@ -803,6 +921,44 @@ LIMIT $2"#;
         slf.auth_provider_id = None;
         slf.federation_uid = None;
         slf.save(None).await?;
+        UserFederationClaims::delete(&slf.id).await?;
+        AuthProviderRefreshToken::delete(&slf.id).await?;
+
+        Ok(slf)
+    }
+
+    /// Removes the upstream provider link and converts the user back into a local account.
+    ///
+    /// A user without any password or passkey will receive a new magic link to set up a local
+    /// login method. If E-Mails cannot be delivered, the unlink is refused, because it would
+    /// leave the account without any way to log in.
+    pub async fn provider_unlink_local(user_id: String) -> Result<Self, ErrorResponse> {
+        let mut slf = Self::find(user_id).await?;
+        if slf.auth_provider_id.is_none() {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "This user is not linked to any auth provider",
+            ));
+        }
+
+        let needs_local_login = slf.password.is_none() && !slf.has_webauthn_enabled();
+        if needs_local_login && !mailer::is_configured() {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "The user has neither a password nor a passkey and E-Mail delivery is not \
+                configured - the account would be left without any login method",
+            ));
+        }
+
+        slf.auth_provider_id = None;
+        slf.federation_uid = None;
+        slf.save(None).await?;
+        UserFederationClaims::delete(&slf.id).await?;
+        AuthProviderRefreshToken::delete(&slf.id).await?;
+
+        if needs_local_login {
+            slf.request_password_reset(None).await?;
+        }
 
         Ok(slf)
     }
@@ -816,13 +972,14 @@ LIMIT $2"#;
     /// CAUTION:
     /// DO NOT use this function to update a user's `email` or `enabled` state, as this would
     /// need additional cache cleanup and E-Mail handling!
-    pub fn save_txn_append(self, txn: &mut Vec<(&str, Params)>) {
+    pub fn save_txn_append(self, txn: &mut Vec<(&str, Params)>) -> Result<(), ErrorResponse> {
+        let pii = self.pii()?;
         txn.push((
             SQL_SAVE,
             params!(
-                self.email,
-                self.given_name,
-                self.family_name,
+                pii.email,
+                pii.given_name,
+                pii.family_name,
                 self.password,
                 self.roles,
                 self.groups,
@@ -838,9 +995,11 @@ LIMIT $2"#;
                 self.auth_provider_id,
                 self.federation_uid,
                 self.picture_id,
+                pii.email_hash,
                 self.id
             ),
         ));
+        Ok(())
     }
 
     /// CAUTION:
@@ -854,14 +1013,15 @@ LIMIT $2"#;
         txn: &deadpool_postgres::Transaction<'_>,
     ) -> Result<(), ErrorResponse> {
         let lang = self.language.as_str();
+        let pii = self.pii()?;
 
         DB::pg_txn_append(
             txn,
             SQL_SAVE,
             &[
-                &self.email,
-                &self.given_name,
-                &self.family_name,
+                &pii.email,
+                &pii.given_name,
+                &pii.family_name,
                 &self.password,
                 &self.roles,
                 &self.groups,
@@ -877,6 +1037,7 @@ LIMIT $2"#;
                 &self.auth_provider_id,
                 &self.federation_uid,
                 &self.picture_id,
+                &pii.email_hash,
                 &self.id,
             ],
         )
@@ -891,6 +1052,7 @@ LIMIT $2"#;
         }
 
         let lang = self.language.as_str();
+        let pii = self.pii()?;
         let client = DB::hql();
 
         if is_hiqlite() {
@@ -898,9 +1060,9 @@ LIMIT $2"#;
                 .execute(
                     SQL_SAVE,
                     params!(
-                        &self.email,
-                        &self.given_name,
-                        &self.family_name,
+                        &pii.email,
+                        &pii.given_name,
+                        &pii.family_name,
                         &self.password,
                         &self.roles,
                         &self.groups,
@@ -916,6 +1078,7 @@ LIMIT $2"#;
                         &self.auth_provider_id,
                         &self.federation_uid,
                         &self.picture_id,
+                        &pii.email_hash,
                         &self.id
                     ),
                 )
@@ -924,9 +1087,9 @@ LIMIT $2"#;
             DB::pg_execute(
                 SQL_SAVE,
                 &[
-                    &self.email,
-                    &self.given_name,
-                    &self.family_name,
+                    &pii.email,
+                    &pii.given_name,
+                    &pii.family_name,
                     &self.password,
                     &self.roles,
                     &self.groups,
@@ -942,6 +1105,7 @@ LIMIT $2"#;
                     &self.auth_provider_id,
                     &self.federation_uid,
                     &self.picture_id,
+                    &pii.email_hash,
                     &self.id,
                 ],
             )
@@ -970,15 +1134,16 @@ LIMIT $2"#;
     }
 
     /// Caution: Uses regex / LIKE on the database -> very costly query
+    ///
+    /// With `encryption.pii_at_rest`, an E-Mail search only finds exact matches.
     pub async fn search(
         idx: &SearchParamsIdx,
         q: &str,
         limit: i64,
     ) -> Result<Vec<UserResponseSimple>, ErrorResponse> {
-        let q = format!("%{q}%");
         let size_hint = max(limit, 1) as usize;
 
-        let res = match idx {
+        let mut res = match idx {
             SearchParamsIdx::Id | SearchParamsIdx::UserId => {
                 let sql = r#"
 SELECT id, email, given_name, family_name, created_at, last_login, picture_id
@@ -986,6 +1151,7 @@ FROM users
 WHERE id LIKE $1
 ORDER BY created_at ASC
 LIMIT $2"#;
+                let q = format!("%{q}%");
 
                 if is_hiqlite() {
                     DB::hql().query_as(sql, params!(q, limit)).await?
@@ -994,17 +1160,33 @@ LIMIT $2"#;
                 }
             }
             SearchParamsIdx::Email => {
-                let sql = r#"
+                if let Some(hash) = pii::email_hash(&User::normalize_email(q)) {
+                    // Encrypted E-Mails can only be found with an exact match.
+                    let sql = r#"
+SELECT id, email, given_name, family_name, created_at, last_login, picture_id
+FROM users
+WHERE email_hash = $1
+LIMIT $2"#;
+
+                    if is_hiqlite() {
+                        DB::hql().query_as(sql, params!(hash, limit)).await?
+                    } else {
+                        DB::pg_query(sql, &[&hash, &limit], size_hint).await?
+                    }
+                } else {
+                    let sql = r#"
 SELECT id, email, given_name, family_name, created_at, last_login, picture_id
 FROM users
 WHERE email LIKE $1
 ORDER BY created_at ASC
 LIMIT $2"#;
+                    let q = format!("%{q}%");
 
-                if is_hiqlite() {
-                    DB::hql().query_as(sql, params!(q, limit)).await?
-                } else {
-                    DB::pg_query(sql, &[&q, &limit], size_hint).await?
+                    if is_hiqlite() {
+                        DB::hql().query_as(sql, params!(q, limit)).await?
+                    } else {
+                        DB::pg_query(sql, &[&q, &limit], size_hint).await?
+                    }
                 }
             }
             _ => {
@@ -1014,6 +1196,41 @@ LIMIT $2"#;
                 ));
             }
         };
+        pii::decrypt_users_simple(&mut res);
+
+        Ok(res)
+    }
+
+    /// Reverse lookup of all users with the custom attribute `key` set to `value`.
+    pub async fn search_by_attr(
+        key: &str,
+        value: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<UserResponseSimple>, ErrorResponse> {
+        let [v1, v2] = UserAttrValueEntity::value_candidates(value);
+        let sql = r#"
+SELECT u.id, u.email, u.given_name, u.family_name, u.created_at, u.last_login, u.picture_id
+FROM users u
+JOIN user_attr_values v ON v.user_id = u.id
+WHERE v.key = $1 AND (v.value = $2 OR v.value = $3)
+ORDER BY u.created_at ASC
+LIMIT $4
+OFFSET $5"#;
+
+        let mut res: Vec<UserResponseSimple> = if is_hiqlite() {
+            DB::hql()
+                .query_as(sql, params!(key, v1, v2, limit, offset))
+                .await?
+        } else {
+            DB::pg_query(
+                sql,
+                &[&key, &v1, &v2, &limit, &offset],
+                max(limit, 1) as usize,
+            )
+            .await?
+        };
+        pii::decrypt_users_simple(&mut res);
 
         Ok(res)
     }
@@ -1220,7 +1437,7 @@ LIMIT $2"#;
             None => User::find(id).await?,
             Some(user) => user,
         };
-        upd_user.email = upd_user.email.to_lowercase();
+        upd_user.email = User::normalize_email(&upd_user.email);
         let old_email = if user.email != upd_user.email {
             Some(user.email.clone())
         } else {
@@ -1340,27 +1557,32 @@ LIMIT $2"#;
             password = Some(pwd_new);
         }
 
-        let email_updated = if let Some(email) = upd_user.email.map(|email| email.to_lowercase()) {
+        let email_updated = if let Some(email) = upd_user.email.map(|e| User::normalize_email(&e)) {
             // if the email should be updated, we do not do it directly -> send out confirmation
             // email to old AND new address
             if email != user.email {
                 // invalidate possibly other existing MagicLinks of the same type
                 MagicLink::invalidate_all_email_change(&user.id).await?;
 
-                let ml = MagicLink::create(
+                let mut ml = MagicLink::create(
                     user.id.clone(),
                     60,
                     MagicLinkUsage::EmailChange(email.clone()),
                 )
                 .await?;
 
-                send_email_change_info_new(
+                if let Err(err) = send_email_change_info_new(
                     &ml,
                     &user,
                     upd_user.user_values.as_ref().and_then(|v| v.tz.as_deref()),
                     email,
+                    EmailFeedback::Confirm,
                 )
-                .await;
+                .await
+                {
+                    ml.invalidate().await?;
+                    return Err(err);
+                }
 
                 true
             } else {
@@ -1434,12 +1656,17 @@ LIMIT $2"#;
     }
 
     pub async fn validate_email_free(email: String) -> Result<(), ErrorResponse> {
-        let sql = "SELECT 1 FROM users WHERE email = $1";
+        let email = User::normalize_email(&email);
+        let (sql, value) = if let Some(hash) = pii::email_hash(&email) {
+            ("SELECT 1 FROM users WHERE email_hash = $1", hash)
+        } else {
+            ("SELECT 1 FROM users WHERE email = $1", email)
+        };
 
         let is_free = if is_hiqlite() {
-            DB::hql().query_raw_one(sql, params!(email)).await.is_err()
+            DB::hql().query_raw_one(sql, params!(value)).await.is_err()
         } else {
-            DB::pg_query_one_row(sql, &[&email]).await.is_err()
+            DB::pg_query_one_row(sql, &[&value]).await.is_err()
         };
 
         if is_free {
@@ -1631,6 +1858,7 @@ impl User {
         confirm_id: String,
     ) -> Result<String, ErrorResponse> {
         let mut ml = MagicLink::find(&confirm_id).await?;
+        ml.record_expired_attempt().await?;
         ml.validate(&user_id, &req, false)?;
 
         let usage = MagicLinkUsage::try_from(&ml.usage)?;
@@ -1646,6 +1874,24 @@ impl User {
 
         let mut user = Self::find(user_id).await?;
 
+        // only a verification of the current address without any change
+        if new_email == user.email {
+            user.email_verified = true;
+            user.save(None).await?;
+            ml.invalidate().await?;
+
+            let lang = Language::try_from(&req).unwrap_or_default();
+            let html = UserEmailChangeConfirmHtml::build(
+                &lang,
+                ThemeCssFull::find_theme_ts_rauthy().await?,
+                &[
+                    HtmlTemplate::EmailOld(new_email.clone()),
+                    HtmlTemplate::EmailNew(new_email),
+                ],
+            );
+            return Ok(html);
+        }
+
         // save data
         let old_email = user.email;
         user.email = new_email;
@@ -1661,7 +1907,7 @@ impl User {
         send_email_confirm_change(&user, &old_email, &user.email, false).await;
 
         let event_text = format!("{old_email} -> {}", user.email);
-        let ip = real_ip_from_req(&req).ok();
+        let ip = real_ip(&req).ok();
         RauthyConfig::get()
             .tx_events
             .send_async(Event::user_email_change(event_text, ip))
@@ -1749,7 +1995,7 @@ impl User {
         let groups = Group::sanitize(new_user.groups).await?;
 
         let user = Self {
-            email: new_user.email.to_lowercase(),
+            email: User::normalize_email(&new_user.email),
             email_verified: false,
             given_name: new_user.given_name.unwrap_or_default(),
             family_name: new_user.family_name,
@@ -1849,8 +2095,13 @@ impl User {
                 "Cannot validate argon2 param - password is not set",
             ));
         }
-        let hash = PasswordHash::new(self.password.as_ref().unwrap())
-            .expect("Could not build Hash from password string");
+        // Imported legacy hashes like bcrypt or PBKDF2 are always outdated.
+        let Ok(hash) = PasswordHash::new(self.password.as_ref().unwrap()) else {
+            return Ok(false);
+        };
+        if hash.algorithm.as_str() != "argon2id" {
+            return Ok(false);
+        }
         let curr_params =
             argon2::Params::try_from(&hash).expect("Could not extract params from hash");
 
@@ -1863,6 +2114,22 @@ impl User {
         Ok(false)
     }
 
+    /// The PII columns as they must be written to the database.
+    #[inline]
+    fn pii(&self) -> Result<UserPii, ErrorResponse> {
+        UserPii::new(&self.email, &self.given_name, self.family_name.as_deref())
+    }
+
+    /// Normalizes an E-Mail with the configured `access.email_lowercase_local_part` policy.
+    /// Each address must go through this function before it is saved or looked up.
+    #[inline]
+    pub fn normalize_email(email: &str) -> String {
+        normalize_email(
+            email,
+            RauthyConfig::get().vars.access.email_lowercase_local_part,
+        )
+    }
+
     #[inline]
     pub fn is_admin(&self) -> bool {
         self.roles_iter().any(|r| r == RAUTHY_ADMIN_ROLE)
@@ -1937,6 +2204,23 @@ impl User {
         }
     }
 
+    /// Sends a confirmation link for the current E-Mail address, which sets `email_verified`
+    /// once it has been opened.
+    pub async fn request_email_verification(&self) -> Result<(), ErrorResponse> {
+        MagicLink::invalidate_all_email_change(&self.id).await?;
+        let ml = MagicLink::create(
+            self.id.clone(),
+            60,
+            MagicLinkUsage::EmailChange(self.email.clone()),
+        )
+        .await?;
+
+        let values = UserValues::find(&self.id).await?;
+        let tz = values.as_ref().and_then(|uv| uv.tz.as_deref());
+
+        send_email_change_info_new(&ml, self, tz, self.email.clone(), EmailFeedback::Silent).await
+    }
+
     pub async fn request_password_reset(
         &self,
         redirect_uri: Option<String>,
@@ -1964,7 +2248,7 @@ impl User {
         let values = UserValues::find(&self.id).await?;
         let tz = values.as_ref().and_then(|uv| uv.tz.as_deref());
 
-        send_pwd_reset(&new_ml, self, tz).await;
+        send_pwd_reset(&new_ml, self, tz, EmailFeedback::Silent).await?;
 
         Ok(())
     }
@@ -1999,7 +2283,7 @@ impl User {
                 let values = UserValues::find(&self.id).await?;
                 let tz = values.as_ref().and_then(|uv| uv.tz.as_deref());
 
-                send_pwd_reset(&magic_link, self, tz).await;
+                send_pwd_reset(&magic_link, self, tz, EmailFeedback::Silent).await?;
 
                 Err(ErrorResponse::new(
                     ErrorResponseType::PasswordRefresh,
@@ -2024,6 +2308,209 @@ impl User {
     }
 }
 
+// Reindex
+impl User {
+    /// Rebuilds all derived data for the user with the given `id`. All cache entries are dropped,
+    /// the group memberships are validated against the existing `groups` and the cache is warmed
+    /// up with fresh data from the database afterward.
+    pub async fn reindex(
+        id: String,
+        groups: &[Group],
+    ) -> Result<UserReindexResponse, ErrorResponse> {
+        let start = Instant::now();
+
+        // the cached value may be outdated, but we need the email for the cache keys anyway
+        let cached = Self::find(id).await?;
+        Self::invalidate_cache(&cached.id, &cached.email).await?;
+        let mut user = Self::find(cached.id).await?;
+        let mut cache_entries_invalidated = 2;
+        if user.email != cached.email {
+            let idx = format!("{IDX_USERS}_{}", user.email);
+            DB::hql().delete(Cache::User, idx).await?;
+            cache_entries_invalidated += 1;
+        }
+
+        UserValues::clear_cache(&user.id).await?;
+        UserAttrValueEntity::clear_cache_for_user(&user.id).await?;
+        cache_entries_invalidated += 2;
+        cache_entries_invalidated += Session::clear_cache_for_user(&user.id).await?;
+
+        let groups_recounted = user.groups_iter().filter(|g| !g.is_empty()).count() as u32;
+        let existing = Self::retain_existing_groups(user.groups.as_deref(), groups);
+        if existing != user.groups {
+            debug!(
+                user_id = %user.id,
+                "Removing non-existing groups during reindex: {:?} -> {:?}",
+                user.groups,
+                existing
+            );
+            user.groups = existing;
+            user.save(None).await?;
+        }
+
+        // warm up the cache
+        Self::find_by_email(user.email.clone()).await?;
+        UserValues::find(&user.id).await?;
+        UserAttrValueEntity::find_for_user(&user.id).await?;
+
+        Ok(UserReindexResponse {
+            cache_entries_invalidated,
+            groups_recounted,
+            duration_ms: start.elapsed().as_millis() as u64,
+        })
+    }
+
+    /// Reindexes all users and sends the progress as SSE data via `tx`. The reindex will
+    /// continue, even if the client has gone away.
+    pub async fn reindex_all(tx: mpsc::Sender<sse::Event>) {
+        let _guard = TaskGuard::new();
+        let start = Instant::now();
+
+        let send = |progress: &UsersReindexProgress| {
+            let tx = tx.clone();
+            let data = sse::Data::new(serde_json::to_string(progress).unwrap());
+            async move {
+                // the client may have closed the connection in the meantime
+                let _ = tx.send(sse::Event::Data(data)).await;
+            }
+        };
+
+        let (users, groups) = match Self::reindex_prepare().await {
+            Ok(res) => res,
+            Err(err) => {
+                error!(?err, "Cannot start users reindex");
+                send(&UsersReindexProgress {
+                    finished: true,
+                    ..Default::default()
+                })
+                .await;
+                return;
+            }
+        };
+
+        let mut progress = UsersReindexProgress {
+            total: users.len() as u32,
+            ..Default::default()
+        };
+        info!("Starting reindex for {} users", progress.total);
+
+        for user in users {
+            if is_draining() {
+                warn!("Aborting users reindex because of a shutdown");
+                break;
+            }
+
+            match Self::reindex(user.id, &groups).await {
+                Ok(res) => {
+                    progress.cache_entries_invalidated += res.cache_entries_invalidated;
+                    progress.groups_recounted += res.groups_recounted;
+                }
+                Err(err) => {
+                    error!(?err, "Error during users reindex");
+                    progress.failed += 1;
+                }
+            }
+            progress.processed += 1;
+
+            if progress.processed.is_multiple_of(100) {
+                progress.duration_ms = start.elapsed().as_millis() as u64;
+                send(&progress).await;
+            }
+        }
+
+        progress.duration_ms = start.elapsed().as_millis() as u64;
+        progress.finished = true;
+        info!(
+            processed = progress.processed,
+            failed = progress.failed,
+            "Users reindex finished in {} ms",
+            progress.duration_ms
+        );
+        send(&progress).await;
+    }
+
+    /// Returns an error if a full reindex has been started within the last hour.
+    pub async fn reindex_all_rate_limit() -> Result<(), ErrorResponse> {
+        let client = DB::hql();
+
+        let ts: Option<i64> = client.get(Cache::App, IDX_USERS_REINDEX).await?;
+        if let Some(ts) = ts {
+            let retry_at = ts + USERS_REINDEX_RATE_LIMIT_SECS as i64;
+            return Err(ErrorResponse::new(
+                ErrorResponseType::TooManyRequests(retry_at),
+                format!("A full reindex is allowed only once per hour. Try again at: {retry_at}"),
+            ));
+        }
+
+        let now = Utc::now().timestamp();
+        client
+            .put(Cache::App, IDX_USERS_REINDEX, &now, CACHE_TTL_USERS_REINDEX)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Returns the groups fresh from the database to not validate against an outdated cache.
+    pub async fn reindex_groups() -> Result<Vec<Group>, ErrorResponse> {
+        DB::hql().delete(Cache::App, IDX_GROUPS).await?;
+        Group::find_all().await
+    }
+
+    async fn reindex_prepare() -> Result<(Vec<UserResponseSimple>, Vec<Group>), ErrorResponse> {
+        let users = Self::find_all_simple().await?;
+        let groups = Self::reindex_groups().await?;
+        Ok((users, groups))
+    }
+
+    /// Removes all groups from the comma separated `groups`, which do not exist anymore.
+    fn retain_existing_groups(groups: Option<&str>, existing: &[Group]) -> Option<String> {
+        let res = groups
+            .unwrap_or_default()
+            .split(',')
+            .filter(|name| !name.is_empty() && existing.iter().any(|g| g.name == *name))
+            .collect::<Vec<_>>();
+
+        if res.is_empty() {
+            None
+        } else {
+            Some(res.join(","))
+        }
+    }
+}
+
+/// The lookups needed for a race-safe `User::create_federated()`.
+trait FederatedUserStore {
+    async fn insert(&self, user: User) -> Result<User, ErrorResponse>;
+
+    async fn find_by_federation(
+        &self,
+        auth_provider_id: &str,
+        federation_uid: &str,
+    ) -> Result<User, ErrorResponse>;
+
+    async fn save(&self, user: &User) -> Result<(), ErrorResponse>;
+}
+
+struct DbFederatedUsers;
+
+impl FederatedUserStore for DbFederatedUsers {
+    async fn insert(&self, user: User) -> Result<User, ErrorResponse> {
+        User::insert(user).await
+    }
+
+    async fn find_by_federation(
+        &self,
+        auth_provider_id: &str,
+        federation_uid: &str,
+    ) -> Result<User, ErrorResponse> {
+        User::find_by_federation(auth_provider_id, federation_uid).await
+    }
+
+    async fn save(&self, user: &User) -> Result<(), ErrorResponse> {
+        user.save(None).await
+    }
+}
+
 impl Default for User {
     fn default() -> Self {
         Self {
@@ -2075,6 +2562,8 @@ mod tests {
     use crate::entity::sessions::{Session, SessionState};
     use pretty_assertions::assert_eq;
     use std::ops::Sub;
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicUsize, Ordering};
 
     #[test]
     fn test_session_impl() {
@@ -2177,6 +2666,17 @@ mod tests {
         std::thread::sleep(core::time::Duration::from_secs(2));
         assert!(check_password_expired(&user).is_err());
 
+        // account type
+        assert_eq!(user.account_type(), AccountType::Password);
+        let pwd = user.password.take();
+        assert_eq!(user.account_type(), AccountType::New);
+        user.webauthn_user_id = Some("wa_123".to_string());
+        assert_eq!(user.account_type(), AccountType::Passkey);
+        // deleting the last passkey of a passkey-only account falls back to `New`
+        user.webauthn_user_id = None;
+        assert_eq!(user.account_type(), AccountType::New);
+        user.password = pwd;
+
         // groups
         assert_eq!(
             user.get_groups(),
@@ -2257,6 +2757,138 @@ mod tests {
         let res = user.is_argon2_uptodate(&wrapped_params)?;
         assert_eq!(res, false);
 
+        // imported legacy hashes
+        wrapped_params = argon2::Params::new(16384, 3, 2, None)?;
+        user.password =
+            Some("$2b$04$DgtgKkWswLfQzIBaSq2Vc.gK1XxbG1Uuy/3wxR.XZMPn.Akb7IhSq".to_string());
+        let res = user.is_argon2_uptodate(&wrapped_params)?;
+        assert_eq!(res, false);
+        user.password = Some(
+            "$pbkdf2-sha256$i=27500$cmF1dGh5LWtjLXNhbHQxNg$9C6NJd7W4Ujeb4WRx/DpnEVCBwtdRriunLZSQS7H8T9Z1igZ0RgU465WlbxKkmmYR7xTy/IEEZGFXUyqgS0wLA"
+                .to_string(),
+        );
+        let res = user.is_argon2_uptodate(&wrapped_params)?;
+        assert_eq!(res, false);
+
         Ok(())
     }
+
+    #[test]
+    fn test_retain_existing_groups() {
+        let existing = ["admin", "user"]
+            .into_iter()
+            .map(|name| Group {
+                id: format!("{name}Id"),
+                name: name.to_string(),
+                meta: None,
+            })
+            .collect::<Vec<_>>();
+
+        let res = User::retain_existing_groups(Some("admin,deleted,user"), &existing);
+        assert_eq!(res.as_deref(), Some("admin,user"));
+
+        let res = User::retain_existing_groups(Some("deleted"), &existing);
+        assert_eq!(res, None);
+
+        let res = User::retain_existing_groups(None, &existing);
+        assert_eq!(res, None);
+    }
+
+    /// Behaves like the database with the unique indexes on the `users` table.
+    #[derive(Default)]
+    struct MemFederatedUsers {
+        rows: Mutex<Vec<User>>,
+        saves: AtomicUsize,
+    }
+
+    impl FederatedUserStore for MemFederatedUsers {
+        async fn insert(&self, user: User) -> Result<User, ErrorResponse> {
+            // let all concurrent logins reach the insert before any of them has finished
+            tokio::task::yield_now().await;
+
+            let mut rows = self.rows.lock().unwrap();
+            let is_conflict = rows.iter().any(|u| {
+                u.email == user.email
+                    || (u.auth_provider_id == user.auth_provider_id
+                        && u.federation_uid == user.federation_uid)
+            });
+            if is_conflict {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::NotAccepted,
+                    "UNIQUE constraint on: 'email'",
+                ));
+            }
+            rows.push(user.clone());
+            Ok(user)
+        }
+
+        async fn find_by_federation(
+            &self,
+            auth_provider_id: &str,
+            federation_uid: &str,
+        ) -> Result<User, ErrorResponse> {
+            self.rows
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|u| {
+                    u.auth_provider_id.as_deref() == Some(auth_provider_id)
+                        && u.federation_uid.as_deref() == Some(federation_uid)
+                })
+                .cloned()
+                .ok_or_else(|| ErrorResponse::new(ErrorResponseType::NotFound, "not found"))
+        }
+
+        async fn save(&self, user: &User) -> Result<(), ErrorResponse> {
+            self.saves.fetch_add(1, Ordering::Relaxed);
+            let mut rows = self.rows.lock().unwrap();
+            let row = rows.iter_mut().find(|u| u.id == user.id).unwrap();
+            *row = user.clone();
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_federated_concurrent() {
+        let store = MemFederatedUsers::default();
+        let new_user = |provider_id: &str, uid: &str, email: &str| User {
+            email: email.to_string(),
+            given_name: "Federated".to_string(),
+            last_login: Some(1337),
+            auth_provider_id: Some(provider_id.to_string()),
+            federation_uid: Some(uid.to_string()),
+            ..Default::default()
+        };
+
+        // e.g. a double click on the provider button
+        let logins = 5;
+        let res =
+            futures::future::join_all((0..logins).map(|_| {
+                User::create_federated_with(&store, new_user("p1", "uid1", "fed@localhost"))
+            }))
+            .await;
+
+        assert_eq!(store.rows.lock().unwrap().len(), 1);
+        let id = store.rows.lock().unwrap()[0].id.clone();
+        let mut created = 0;
+        for r in res {
+            let (user, is_new) = r.unwrap();
+            assert_eq!(user.id, id);
+            if is_new == NewFederatedUserCreated::Yes {
+                created += 1;
+            }
+        }
+        assert_eq!(created, 1);
+        assert_eq!(store.saves.load(Ordering::Relaxed), logins - 1);
+
+        // an unrelated user with an already existing email is still an error
+        let res =
+            User::create_federated_with(&store, new_user("p2", "uid2", "fed@localhost")).await;
+        assert_eq!(res.unwrap_err().error, ErrorResponseType::NotAccepted);
+        assert_eq!(store.rows.lock().unwrap().len(), 1);
+
+        // no federation values at all
+        let res = User::create_federated_with(&store, User::default()).await;
+        assert_eq!(res.unwrap_err().error, ErrorResponseType::Internal);
+    }
 }
@@ -0,0 +1,211 @@
+use crate::cache_layer::{self, CacheName};
+use crate::database::DB;
+use crate::entity::pam::groups::PamGroupType;
+use crate::entity::pam::users::PamUser;
+use crate::entity::pictures::UserPicture;
+use crate::entity::users::User;
+use hiqlite::Params;
+use hiqlite::macros::params;
+use rauthy_common::is_hiqlite;
+use rauthy_common::sha256;
+use rauthy_error::ErrorResponse;
+use std::collections::HashMap;
+
+/// Tables with a `user_id`, which are cleaned up via `ON DELETE CASCADE` when the user row is
+/// deleted. They are only counted upfront for the `ErasureResult`.
+const CASCADING_TABLES: [&str; 15] = [
+    "devices",
+    "login_locations",
+    "magic_links",
+    "passkeys",
+    "recent_passwords",
+    "refresh_tokens",
+    "refresh_tokens_devices",
+    "sessions",
+    "tos_user_accept",
+    "user_attr_values",
+    "user_federation_claims",
+    "user_notification_prefs",
+    "user_revoke",
+    "users_values",
+    "webids",
+];
+
+/// Tables with a `user_id` without a cascading FK, which must be cleaned up manually.
+const MANUAL_TABLES: [&str; 2] = ["issued_tokens", "user_login_states"];
+const SQL_MANUAL_TABLES: [&str; 2] = [
+    "DELETE FROM issued_tokens WHERE user_id = $1",
+    "DELETE FROM user_login_states WHERE user_id = $1",
+];
+
+const SQL_PAM_USER: &str = "DELETE FROM pam_users WHERE id = $1";
+const SQL_PAM_GROUP: &str = "DELETE FROM pam_groups WHERE name = $1 AND typ = $2";
+const SQL_PICTURE: &str = "DELETE FROM pictures WHERE id = $1";
+const SQL_USER: &str = "DELETE FROM users WHERE id = $1";
+
+/// Returns a stable pseudonym for the given user id, which does not leak any PII.
+pub fn erasure_pseudonym(user_id: &str) -> String {
+    format!("erased-{}", hex::encode(&sha256!(user_id.as_bytes())[..12]))
+}
+
+impl User {
+    /// Permanently erases the user and all linked data for a GDPR Art. 17 request.
+    ///
+    /// Events which mention the user are either deleted, or pseudonymized if
+    /// `delete_audit_logs == false`. Returns the amount of affected rows per entity.
+    pub async fn erase(
+        &self,
+        delete_audit_logs: bool,
+    ) -> Result<HashMap<String, u64>, ErrorResponse> {
+        let mut entities = HashMap::with_capacity(CASCADING_TABLES.len() + 6);
+
+        for table in CASCADING_TABLES {
+            let sql = format!("SELECT COUNT(*) AS count FROM {table} WHERE user_id = $1");
+            let count: i64 = if is_hiqlite() {
+                let mut row = DB::hql().query_raw_one(sql, params!(&self.id)).await?;
+                row.get("count")
+            } else {
+                let row = DB::pg_query_one_row(&sql, &[&self.id]).await?;
+                row.get("count")
+            };
+            entities.insert(table.to_string(), count as u64);
+        }
+
+        // Sessions are deleted via the cascade, but their cache entries must be cleared as well.
+        let sql = "SELECT id FROM sessions WHERE user_id = $1";
+        let sids: Vec<String> = if is_hiqlite() {
+            DB::hql()
+                .query_raw(sql, params!(&self.id))
+                .await?
+                .into_iter()
+                .map(|mut row| row.get("id"))
+                .collect()
+        } else {
+            DB::pg_query_rows(sql, &[&self.id], 2)
+                .await?
+                .into_iter()
+                .map(|row| row.get("id"))
+                .collect()
+        };
+
+        // PAM data is cleaned up manually instead of using FKs, like in `User::delete()`.
+        let pam_user = PamUser::find_by_user_id(self.id.clone()).await.ok();
+        entities.insert("pam_users".to_string(), pam_user.is_some() as u64);
+        let picture = match &self.picture_id {
+            Some(id) => Some(UserPicture::find(id.clone()).await?),
+            None => None,
+        };
+        entities.insert("user_pictures".to_string(), picture.is_some() as u64);
+
+        let sql_events = match (delete_audit_logs, is_hiqlite()) {
+            (true, true) => "DELETE FROM events WHERE instr(text, $1) > 0 OR instr(text, $2) > 0",
+            (true, false) => {
+                "DELETE FROM events WHERE strpos(text, $1) > 0 OR strpos(text, $2) > 0"
+            }
+            (false, true) => {
+                r#"
+UPDATE events SET text = REPLACE(REPLACE(text, $1, $3), $2, $3)
+WHERE instr(text, $1) > 0 OR instr(text, $2) > 0"#
+            }
+            (false, false) => {
+                r#"
+UPDATE events SET text = REPLACE(REPLACE(text, $1, $3), $2, $3)
+WHERE strpos(text, $1) > 0 OR strpos(text, $2) > 0"#
+            }
+        };
+        let pseudonym = erasure_pseudonym(&self.id);
+
+        // Everything is deleted inside a single txn to never end up with a partially erased user.
+        let (rows_manual, events) = if is_hiqlite() {
+            let mut txn: Vec<(&str, Params)> = Vec::with_capacity(MANUAL_TABLES.len() + 5);
+            for sql in SQL_MANUAL_TABLES {
+                txn.push((sql, params!(&self.id)));
+            }
+            if let Some(pam_user) = &pam_user {
+                txn.push((SQL_PAM_USER, params!(pam_user.id)));
+                txn.push((
+                    SQL_PAM_GROUP,
+                    params!(&pam_user.name, PamGroupType::User.as_str()),
+                ));
+            }
+            txn.push((SQL_USER, params!(&self.id)));
+            if let Some(picture) = &picture {
+                txn.push((SQL_PICTURE, params!(&picture.id)));
+            }
+            if delete_audit_logs {
+                txn.push((sql_events, params!(&self.email, &self.id)));
+            } else {
+                txn.push((sql_events, params!(&self.email, &self.id, &pseudonym)));
+            }
+
+            let mut rows_affected = Vec::with_capacity(txn.len());
+            for res in DB::hql().txn(txn).await? {
+                rows_affected.push(res? as u64);
+            }
+            let events = rows_affected.last().copied().unwrap_or_default();
+            rows_affected.truncate(MANUAL_TABLES.len());
+            (rows_affected, events)
+        } else {
+            let mut cl = DB::pg().await?;
+            let txn = cl.transaction().await?;
+
+            let mut rows_affected = Vec::with_capacity(MANUAL_TABLES.len());
+            for sql in SQL_MANUAL_TABLES {
+                rows_affected.push(DB::pg_txn_append(&txn, sql, &[&self.id]).await?);
+            }
+            if let Some(pam_user) = &pam_user {
+                DB::pg_txn_append(&txn, SQL_PAM_USER, &[&(pam_user.id as i64)]).await?;
+                DB::pg_txn_append(
+                    &txn,
+                    SQL_PAM_GROUP,
+                    &[&pam_user.name, &PamGroupType::User.as_str()],
+                )
+                .await?;
+            }
+            DB::pg_txn_append(&txn, SQL_USER, &[&self.id]).await?;
+            if let Some(picture) = &picture {
+                DB::pg_txn_append(&txn, SQL_PICTURE, &[&picture.id]).await?;
+            }
+            let events = if delete_audit_logs {
+                DB::pg_txn_append(&txn, sql_events, &[&self.email, &self.id]).await?
+            } else {
+                DB::pg_txn_append(&txn, sql_events, &[&self.email, &self.id, &pseudonym]).await?
+            };
+
+            txn.commit().await?;
+            (rows_affected, events)
+        };
+
+        for (table, rows) in MANUAL_TABLES.into_iter().zip(rows_manual) {
+            entities.insert(table.to_string(), rows);
+        }
+        entities.insert("users".to_string(), 1);
+        entities.insert("events".to_string(), events);
+
+        for sid in sids {
+            cache_layer::delete(CacheName::Session, sid).await?;
+        }
+        if let Some(picture) = picture {
+            picture.delete_storage().await;
+        }
+        Self::invalidate_cache(&self.id, &self.email).await?;
+        Self::count_dec().await?;
+
+        Ok(entities)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_erasure_pseudonym() {
+        let pseudonym = erasure_pseudonym("za9UxpH7XVxqrtpEbThoqvn2");
+        assert_eq!(pseudonym, erasure_pseudonym("za9UxpH7XVxqrtpEbThoqvn2"));
+        assert_ne!(pseudonym, erasure_pseudonym("2PYV3STNz3MN7VnPjJVcPQap"));
+        assert!(pseudonym.starts_with("erased-"));
+        assert_eq!(pseudonym.len(), 7 + 24);
+        assert!(!pseudonym.contains("za9UxpH7XVxqrtpEbThoqvn2"));
+    }
+}
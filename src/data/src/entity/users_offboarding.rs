@@ -0,0 +1,98 @@
+use crate::database::{Cache, DB};
+use crate::entity::users::User;
+use hiqlite::macros::params;
+use rauthy_common::is_hiqlite;
+use rauthy_error::ErrorResponse;
+use std::collections::HashMap;
+
+/// Tables with a `user_id`, whose rows keep a user logged in. They are deleted for all off-boarded
+/// users at once.
+const REVOKE_DELETE_TABLES: [&str; 3] = ["sessions", "refresh_tokens", "refresh_tokens_devices"];
+
+/// Result of `User::disable_bulk()`.
+#[derive(Debug, Default)]
+pub struct UsersDisabledBulk {
+    /// Ids of all users, which have been enabled before.
+    pub disabled: Vec<String>,
+    /// Deleted (or revoked for `issued_tokens`) rows per entity
+    pub revoked: HashMap<String, u64>,
+}
+
+/// Returns the `WHERE` condition matching all `col` values from the user ids param `$1`.
+///
+/// Hiqlite gets the ids as a JSON array, Postgres as a native `TEXT[]`. This keeps the amount of
+/// params fixed, no matter how many users are affected.
+#[inline]
+fn in_user_ids(col: &str) -> String {
+    if is_hiqlite() {
+        format!("{col} IN (SELECT value FROM json_each($1))")
+    } else {
+        format!("{col} = ANY($1)")
+    }
+}
+
+impl User {
+    /// Off-boards all given users at once.
+    ///
+    /// Disables them with a single statement and revokes their sessions and tokens set-based
+    /// instead of going through `User::save()` for each one of them. The `User` and `Session`
+    /// caches are cleared once at the end.
+    ///
+    /// Backchannel logouts, SCIM updates and Events are left to the caller, because they are
+    /// not needed for every use case and should usually happen asynchronously.
+    pub async fn disable_bulk(user_ids: &[String]) -> Result<UsersDisabledBulk, ErrorResponse> {
+        let mut res = UsersDisabledBulk {
+            disabled: Vec::with_capacity(user_ids.len()),
+            revoked: HashMap::with_capacity(REVOKE_DELETE_TABLES.len() + 1),
+        };
+        if user_ids.is_empty() {
+            return Ok(res);
+        }
+        let ids_json = serde_json::to_string(user_ids)?;
+
+        let sql = format!(
+            "UPDATE users SET enabled = false WHERE enabled = true AND {} RETURNING id",
+            in_user_ids("id")
+        );
+        if is_hiqlite() {
+            for row in DB::hql()
+                .execute_returning(sql, params!(ids_json.clone()))
+                .await?
+            {
+                res.disabled.push(row?.get("id"));
+            }
+        } else {
+            for row in DB::pg_query_rows(&sql, &[&user_ids], user_ids.len()).await? {
+                res.disabled.push(row.get("id"));
+            }
+        }
+
+        for table in REVOKE_DELETE_TABLES {
+            let sql = format!("DELETE FROM {table} WHERE {}", in_user_ids("user_id"));
+            let rows_affected = if is_hiqlite() {
+                DB::hql().execute(sql, params!(ids_json.clone())).await?
+            } else {
+                DB::pg_execute(&sql, &[&user_ids]).await?
+            };
+            res.revoked.insert(table.to_string(), rows_affected as u64);
+        }
+
+        let sql = format!(
+            "UPDATE issued_tokens SET revoked = true WHERE revoked = false AND {}",
+            in_user_ids("user_id")
+        );
+        let rows_affected = if is_hiqlite() {
+            DB::hql().execute(sql, params!(ids_json)).await?
+        } else {
+            DB::pg_execute(&sql, &[&user_ids]).await?
+        };
+        res.revoked
+            .insert("issued_tokens".to_string(), rows_affected as u64);
+
+        let client = DB::hql();
+        client.clear_cache(Cache::User).await?;
+        client.clear_cache(Cache::Session).await?;
+
+        Ok(res)
+    }
+}
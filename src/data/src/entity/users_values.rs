@@ -1,5 +1,6 @@
 use crate::database::{Cache, DB};
-use hiqlite::macros::{FromRow, params};
+use crate::pii;
+use hiqlite::macros::params;
 use rauthy_api_types::users::{UserValuesRequest, UserValuesResponse};
 use rauthy_common::constants::{CACHE_TTL_USER, IDX_USERS_VALUES};
 use rauthy_common::is_hiqlite;
@@ -7,10 +8,12 @@ use rauthy_derive::FromPgRow;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Default, Clone, Serialize, Deserialize, FromRow, FromPgRow)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize, FromPgRow)]
 pub struct UserValues {
     pub id: String,
     pub birthdate: Option<String>,
+    #[serde(deserialize_with = "pii::deserialize_opt")]
+    #[column(pii)]
     pub phone: Option<String>,
     pub street: Option<String>,
     pub zip: Option<String>,
@@ -18,6 +21,25 @@ pub struct UserValues {
     pub country: Option<String>,
     pub preferred_username: Option<String>,
     pub tz: Option<String>,
+    /// The raw BCP 47 locale from an upstream auth provider
+    pub locale: Option<String>,
+}
+
+impl From<&mut hiqlite::Row<'_>> for UserValues {
+    fn from(row: &mut hiqlite::Row<'_>) -> Self {
+        Self {
+            id: row.get("id"),
+            birthdate: row.get("birthdate"),
+            phone: pii::decrypt_opt(row.get("phone")),
+            street: row.get("street"),
+            zip: row.get("zip"),
+            city: row.get("city"),
+            country: row.get("country"),
+            preferred_username: row.get("preferred_username"),
+            tz: row.get("tz"),
+            locale: row.get("locale"),
+        }
+    }
 }
 
 impl UserValues {
@@ -36,6 +58,7 @@ impl UserValues {
 INSERT INTO
 users_values (id, birthdate, phone, street, zip, city, country, preferred_username, tz)
 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#;
+        let phone = pii::encrypt_opt(values.phone.as_deref())?;
 
         if is_hiqlite() {
             DB::hql()
@@ -44,7 +67,7 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#;
                     params!(
                         user_id,
                         values.birthdate,
-                        values.phone,
+                        phone,
                         values.street,
                         values.zip,
                         values.city,
@@ -71,7 +94,7 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#;
                 &[
                     &user_id,
                     &values.birthdate,
-                    &values.phone,
+                    &phone,
                     &values.street,
                     &values.zip,
                     &values.city,
@@ -160,6 +183,7 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
 ON CONFLICT(id) DO UPDATE
 SET birthdate = $2, phone = $3, street = $4, zip = $5, city = $6, country = $7, tz = $8
 RETURNING *"#;
+        let phone = pii::encrypt_opt(values.phone.as_deref())?;
 
         let values: Self = if is_hiqlite() {
             DB::hql()
@@ -168,7 +192,7 @@ RETURNING *"#;
                     params!(
                         user_id,
                         values.birthdate,
-                        values.phone,
+                        phone,
                         values.street,
                         values.zip,
                         values.city,
@@ -183,7 +207,7 @@ RETURNING *"#;
                 &[
                     &user_id,
                     &values.birthdate,
-                    &values.phone,
+                    &phone,
                     &values.street,
                     &values.zip,
                     &values.city,
@@ -202,6 +226,32 @@ RETURNING *"#;
         Ok(slf)
     }
 
+    /// Sets the raw `locale` from an upstream provider login.
+    pub async fn upsert_locale(user_id: String, locale: String) -> Result<(), ErrorResponse> {
+        let idx = Self::cache_idx(&user_id);
+        let sql = r#"
+INSERT INTO
+users_values (id, locale)
+VALUES ($1, $2)
+ON CONFLICT(id) DO UPDATE
+SET locale = $2
+RETURNING *"#;
+
+        let values: Self = if is_hiqlite() {
+            DB::hql()
+                .execute_returning_map_one(sql, params!(user_id, locale))
+                .await?
+        } else {
+            DB::pg_query_one(sql, &[&user_id, &locale]).await?
+        };
+
+        DB::hql()
+            .put(Cache::User, idx, &Some(values), CACHE_TTL_USER)
+            .await?;
+
+        Ok(())
+    }
+
     pub async fn upsert_preferred_username(
         user_id: String,
         preferred_username: String,
@@ -253,6 +303,13 @@ RETURNING *"#;
         Ok(slf)
     }
 
+    pub async fn clear_cache(user_id: &str) -> Result<(), ErrorResponse> {
+        DB::hql()
+            .delete(Cache::User, Self::cache_idx(user_id))
+            .await?;
+        Ok(())
+    }
+
     pub async fn delete(user_id: String) -> Result<(), ErrorResponse> {
         let cache_idx = Self::cache_idx(&user_id);
         let sql = "DELETE FROM users_values WHERE id = $1";
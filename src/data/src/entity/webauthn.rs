@@ -6,6 +6,7 @@ use crate::entity::login_locations::LoginLocation;
 use crate::entity::password::PasswordPolicy;
 use crate::entity::sessions::Session;
 use crate::entity::users::{AccountType, User};
+use crate::events::event::Event;
 use crate::rauthy_config::RauthyConfig;
 use actix_web::cookie::Cookie;
 use actix_web::http::header::{
@@ -20,12 +21,16 @@ use hiqlite::Params;
 use hiqlite::macros::params;
 use rauthy_api_types::tos::ToSAwaitLoginResponse;
 use rauthy_api_types::users::{
-    MfaPurpose, PasskeyResponse, WebauthnAuthFinishRequest, WebauthnAuthStartResponse,
-    WebauthnLoginFinishResponse, WebauthnRegFinishRequest, WebauthnRegStartRequest,
+    MfaPurpose, PasskeyExport, PasskeyResponse, PasskeysExport, WebauthnAuthFinishRequest,
+    WebauthnAuthStartResponse, WebauthnLoginFinishResponse, WebauthnRegFinishRequest,
+    WebauthnRegStartRequest,
 };
 use rauthy_common::constants::{COOKIE_MFA, IDX_WEBAUTHN};
 use rauthy_common::is_hiqlite;
-use rauthy_common::utils::{base64_decode, base64_encode, deserialize, get_rand, serialize};
+use rauthy_common::utils::{
+    base64_decode, base64_encode, base64_url_no_pad_decode, base64_url_no_pad_encode, deserialize,
+    get_rand, real_ip, serialize,
+};
 use rauthy_derive::FromPgRow;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use serde::{Deserialize, Serialize};
@@ -37,9 +42,13 @@ use tracing::{error, info, warn};
 use utoipa::ToSchema;
 use webauthn_rs::prelude::*;
 use webauthn_rs_proto::{
-    AuthenticatorSelectionCriteria, ResidentKeyRequirement, UserVerificationPolicy,
+    AttestationFormat, AuthenticatorSelectionCriteria, AuthenticatorTransport,
+    RegisteredExtensions, ResidentKeyRequirement, UserVerificationPolicy,
 };
 
+/// The current version of the `PasskeysExport` format
+const PASSKEYS_EXPORT_VERSION: u8 = 1;
+
 #[derive(Clone, Deserialize, Serialize, FromPgRow)]
 pub struct PasskeyEntity {
     pub user_id: String,
@@ -50,6 +59,9 @@ pub struct PasskeyEntity {
     pub registered: i64,
     pub last_used: i64,
     pub user_verified: Option<bool>,
+    /// Unix timestamp in seconds, when a sign counter anomaly has been detected first
+    #[serde(default)]
+    pub counter_anomaly: Option<i64>,
 }
 
 impl Debug for PasskeyEntity {
@@ -57,13 +69,15 @@ impl Debug for PasskeyEntity {
         write!(
             f,
             "PasskeyEntity {{ user_id: {}, name: {}, passkey_user_id: {}, passkey: <hidden>, \
-        credential_id: <hidden>, registered: {}, last_used: {}, user_verified: {:?} }}",
+        credential_id: <hidden>, registered: {}, last_used: {}, user_verified: {:?}, \
+        counter_anomaly: {:?} }}",
             self.user_id,
             self.name,
             self.passkey_user_id,
             self.registered,
             self.last_used,
-            self.user_verified
+            self.user_verified,
+            self.counter_anomaly
         )
     }
 }
@@ -92,6 +106,7 @@ impl PasskeyEntity {
             registered: now,
             last_used: now,
             user_verified: Some(user_verified),
+            counter_anomaly: None,
         };
 
         let user_email = user.as_ref().map(|u| u.email.clone());
@@ -107,7 +122,7 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#;
 
             if let Some(user) = user {
                 debug_assert!(user.webauthn_user_id.is_some());
-                user.save_txn_append(&mut txn);
+                user.save_txn_append(&mut txn)?;
             }
 
             txn.push((
@@ -191,9 +206,11 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#;
 
         let mut user_to_save: Option<User> = None;
         let mut user_email: Option<String> = None;
+        let mut needs_recovery = false;
 
         if pk_count < 2 {
             let mut user = User::find(user_id.clone()).await?;
+            needs_recovery = user.account_type() == AccountType::Passkey;
             user.webauthn_user_id = None;
 
             // We need to check against the current password policy
@@ -219,8 +236,8 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#;
             let mut txn = Vec::with_capacity(2);
 
             Self::delete_by_id_name_append(user_id.clone(), name.clone(), &mut txn);
-            if let Some(user) = user_to_save {
-                user.save_txn_append(&mut txn);
+            if let Some(user) = &user_to_save {
+                user.clone().save_txn_append(&mut txn)?;
             }
 
             DB::hql().txn(txn).await?;
@@ -229,7 +246,7 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#;
             let txn = cl.transaction().await?;
 
             Self::delete_by_id_name(&user_id, &name, &txn).await?;
-            if let Some(user) = user_to_save {
+            if let Some(user) = &user_to_save {
                 user.save_txn(&txn).await?;
             }
 
@@ -238,6 +255,22 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#;
 
         Self::clear_caches_by_id_name(&user_id, user_email, &name).await?;
 
+        // A passkey-only account without any passkey left has no way to log in anymore.
+        // It is back to `AccountType::New` and always gets a fresh link to set up new
+        // credentials, so it can never end up in a silent lockout.
+        if needs_recovery && let Some(user) = user_to_save {
+            Session::invalidate_for_user(&user.id).await?;
+            // The passkey is gone already at this point. A failing E-Mail must not turn the
+            // successful delete into an error, and an admin can always trigger a new reset.
+            if let Err(err) = user.request_password_reset(None).await {
+                error!(
+                    ?err,
+                    "Requesting a password reset for user {} after the last passkey delete",
+                    user.id
+                );
+            }
+        }
+
         Ok(())
     }
 
@@ -385,25 +418,53 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#;
         Ok(pks)
     }
 
+    pub async fn exists_cred_id(credential_id: &[u8]) -> Result<bool, ErrorResponse> {
+        let sql = "SELECT 1 FROM passkeys WHERE credential_id = $1";
+        let exists = if is_hiqlite() {
+            !DB::hql()
+                .query_raw(sql, params!(credential_id.to_vec()))
+                .await?
+                .is_empty()
+        } else {
+            !DB::pg_query_rows(sql, &[&credential_id], 1)
+                .await?
+                .is_empty()
+        };
+
+        Ok(exists)
+    }
+
     pub async fn update_passkey(&self) -> Result<(), ErrorResponse> {
         let client = DB::hql();
 
         let sql = r#"
 UPDATE passkeys
-SET passkey = $1, last_used = $2
-WHERE user_id = $3 AND name = $4"#;
+SET passkey = $1, last_used = $2, counter_anomaly = $3
+WHERE user_id = $4 AND name = $5"#;
 
         if is_hiqlite() {
             client
                 .execute(
                     sql,
-                    params!(&self.passkey, self.last_used, &self.user_id, &self.name),
+                    params!(
+                        &self.passkey,
+                        self.last_used,
+                        self.counter_anomaly,
+                        &self.user_id,
+                        &self.name
+                    ),
                 )
                 .await?;
         } else {
             DB::pg_execute(
                 sql,
-                &[&self.passkey, &self.last_used, &self.user_id, &self.name],
+                &[
+                    &self.passkey,
+                    &self.last_used,
+                    &self.counter_anomaly,
+                    &self.user_id,
+                    &self.name,
+                ],
             )
             .await?;
         }
@@ -427,6 +488,21 @@ WHERE user_id = $3 AND name = $4"#;
 
         Ok(())
     }
+
+    /// Clears the `counter_anomaly` flag after an admin has investigated it. The stored counter
+    /// is kept, so the next anomaly will be detected again.
+    pub async fn clear_counter_anomaly(user_id: &str, name: &str) -> Result<(), ErrorResponse> {
+        let mut slf = Self::find(user_id, name).await?;
+        if slf.counter_anomaly.is_none() {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::NotFound,
+                "This passkey has no counter anomaly",
+            ));
+        }
+
+        slf.counter_anomaly = None;
+        slf.update_passkey().await
+    }
 }
 
 impl PasskeyEntity {
@@ -461,6 +537,225 @@ impl PasskeyEntity {
     }
 }
 
+// Import / Export
+impl PasskeyEntity {
+    pub async fn export_for_user(user: &User) -> Result<PasskeysExport, ErrorResponse> {
+        let credentials = Self::find_for_user(&user.id)
+            .await?
+            .into_iter()
+            .map(|entity| {
+                let pk = entity.get_pk();
+                passkey_to_export(entity.name, entity.registered, entity.last_used, pk)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(PasskeysExport {
+            version: PASSKEYS_EXPORT_VERSION,
+            rp_id: RauthyConfig::get().vars.webauthn.rp_id.clone(),
+            user_handle: user.webauthn_user_id.clone(),
+            credentials,
+        })
+    }
+
+    /// Imports all credentials from the `payload` for the given user. Everything is validated
+    /// upfront, so a single invalid credential rejects the whole import.
+    /// Returns the amount of imported credentials.
+    pub async fn import_for_user(
+        mut user: User,
+        payload: PasskeysExport,
+    ) -> Result<usize, ErrorResponse> {
+        let cfg = &RauthyConfig::get().vars.webauthn;
+        if !cfg.allow_credential_import {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Forbidden,
+                "Passkey import is disabled",
+            ));
+        }
+        if payload.version != PASSKEYS_EXPORT_VERSION {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("Unsupported export version: {}", payload.version),
+            ));
+        }
+        if payload.rp_id != cfg.rp_id {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!(
+                    "The credentials have been registered for rp_id '{}' and cannot be used \
+                    with '{}'",
+                    payload.rp_id, cfg.rp_id
+                ),
+            ));
+        }
+
+        // all credentials of a user are bound to the same user handle
+        let passkey_user_id = match (&user.webauthn_user_id, &payload.user_handle) {
+            (Some(current), Some(handle)) if current != handle => {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    "The `user_handle` does not match the existing passkeys of this user",
+                ));
+            }
+            (Some(id), _) | (None, Some(id)) => Uuid::from_str(id).map_err(|_| {
+                ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    "`user_handle` is not a valid UUID",
+                )
+            })?,
+            (None, None) => Uuid::new_v4(),
+        };
+
+        let force_uv = user.account_type() != AccountType::Password || cfg.force_uv;
+        let existing = Self::find_for_user(&user.id).await?;
+        let mut pks: Vec<(String, Passkey, bool)> = Vec::with_capacity(payload.credentials.len());
+
+        for export in payload.credentials {
+            if existing.iter().any(|e| e.name == export.name)
+                || pks.iter().any(|(name, _, _)| name == &export.name)
+            {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    format!("Passkey name '{}' is already in use", export.name),
+                ));
+            }
+
+            let pk = passkey_from_export(&export)?;
+            if force_uv && !export.user_verified {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    format!(
+                        "Passkey '{}' has no User Verification, which is required for this user",
+                        export.name
+                    ),
+                ));
+            }
+            if pks.iter().any(|(_, p, _)| p.cred_id() == pk.cred_id())
+                || Self::exists_cred_id(pk.cred_id()).await?
+            {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    format!("The credential id for '{}' does already exist", export.name),
+                ));
+            }
+
+            pks.push((export.name, pk, export.user_verified));
+        }
+
+        let user_id = user.id.clone();
+        let mut create_user = if user.webauthn_user_id.is_none() {
+            user.webauthn_user_id = Some(passkey_user_id.to_string());
+            if user.password.is_none() || cfg.no_password_exp {
+                user.password_expires = None;
+            }
+            Some(user)
+        } else {
+            None
+        };
+
+        let count = pks.len();
+        for (name, pk, user_verified) in pks {
+            PasskeyEntity::create(
+                user_id.clone(),
+                create_user.take(),
+                passkey_user_id,
+                name,
+                pk,
+                user_verified,
+            )
+            .await?;
+        }
+
+        warn!(user_id, count, "Passkeys have been imported");
+
+        Ok(count)
+    }
+}
+
+fn passkey_to_export(
+    name: String,
+    registered: i64,
+    last_used: i64,
+    pk: Passkey,
+) -> Result<PasskeyExport, ErrorResponse> {
+    let cred = Credential::from(pk);
+
+    let aaguid = match &cred.attestation.metadata {
+        AttestationMetadata::Packed { aaguid } | AttestationMetadata::Tpm { aaguid, .. } => {
+            Some(aaguid.to_string())
+        }
+        _ => None,
+    };
+
+    Ok(PasskeyExport {
+        name,
+        credential_id: base64_url_no_pad_encode(&cred.cred_id),
+        public_key: serde_json::to_value(&cred.cred)?,
+        sign_count: cred.counter,
+        transports: serde_json::from_value(serde_json::to_value(&cred.transports)?)?,
+        aaguid,
+        user_verified: cred.user_verified,
+        backup_eligible: cred.backup_eligible,
+        backup_state: cred.backup_state,
+        registered,
+        last_used,
+    })
+}
+
+/// Builds the `Passkey` from an export. The sign counter is preserved, so cloned authenticators
+/// will still be detected after the import.
+fn passkey_from_export(export: &PasskeyExport) -> Result<Passkey, ErrorResponse> {
+    let cred_id = base64_url_no_pad_decode(&export.credential_id)?;
+    let public_key =
+        serde_json::from_value::<COSEKey>(export.public_key.clone()).map_err(|err| {
+            ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("Invalid `public_key` for '{}': {err}", export.name),
+            )
+        })?;
+    // `FromStr` rejects unknown values, while serde would silently map them to `Unknown`.
+    let transports = export
+        .transports
+        .as_ref()
+        .map(|transports| {
+            transports
+                .iter()
+                .map(|t| {
+                    AuthenticatorTransport::from_str(t).map_err(|_| {
+                        ErrorResponse::new(
+                            ErrorResponseType::BadRequest,
+                            format!("Invalid `transports` for '{}': {t}", export.name),
+                        )
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()?;
+
+    let cred = Credential {
+        cred_id: CredentialID::from(cred_id),
+        cred: public_key,
+        counter: export.sign_count,
+        transports,
+        user_verified: export.user_verified,
+        backup_eligible: export.backup_eligible,
+        backup_state: export.backup_state,
+        registration_policy: if export.user_verified {
+            UserVerificationPolicy::Required
+        } else {
+            UserVerificationPolicy::Preferred
+        },
+        extensions: RegisteredExtensions::none(),
+        // the original attestation cannot be verified anymore after an export
+        attestation: ParsedAttestation {
+            data: ParsedAttestationData::None,
+            metadata: AttestationMetadata::None,
+        },
+        attestation_format: AttestationFormat::None,
+    };
+
+    Ok(Passkey::from(cred))
+}
+
 impl From<PasskeyEntity> for PasskeyResponse {
     fn from(value: PasskeyEntity) -> Self {
         Self {
@@ -468,10 +763,45 @@ impl From<PasskeyEntity> for PasskeyResponse {
             registered: value.registered,
             last_used: value.last_used,
             user_verified: value.user_verified,
+            counter_anomaly: value.counter_anomaly,
+        }
+    }
+}
+
+/// The result of the sign counter check of a successful assertion.
+#[derive(Debug, PartialEq)]
+enum SignCounterCheck {
+    Ok,
+    /// The authenticator may have been cloned, but the assertion is allowed.
+    AnomalyAllowed,
+    /// The authenticator may have been cloned and the assertion must be rejected.
+    AnomalyRejected,
+}
+
+impl SignCounterCheck {
+    /// Compares the `counter` of an assertion with the `stored` one. Synced passkeys always
+    /// report a counter of `0` and are exempt, which is the case as long as the stored counter
+    /// has never been increased.
+    fn new(stored: u32, counter: u32, reject: bool) -> Self {
+        if stored == 0 || counter > stored {
+            Self::Ok
+        } else if reject {
+            Self::AnomalyRejected
+        } else {
+            Self::AnomalyAllowed
         }
     }
 }
 
+/// Resets the sign counter for the ceremony itself. `webauthn-rs` would otherwise reject
+/// anomalies on its own, before `SignCounterCheck` can apply the configured policy against the
+/// stored counter.
+fn without_sign_counter(pk: Passkey) -> Passkey {
+    let mut cred = Credential::from(pk);
+    cred.counter = 0;
+    Passkey::from(cred)
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct WebauthnCookie {
     pub email: String,
@@ -754,13 +1084,13 @@ pub async fn auth_start(
         PasskeyEntity::find_for_user_with_uv(&user.id)
             .await?
             .iter()
-            .map(|pk_entity| pk_entity.get_pk())
+            .map(|pk_entity| without_sign_counter(pk_entity.get_pk()))
             .collect::<Vec<Passkey>>()
     } else {
         PasskeyEntity::find_for_user(&user.id)
             .await?
             .iter()
-            .map(|pk_entity| pk_entity.get_pk())
+            .map(|pk_entity| without_sign_counter(pk_entity.get_pk()))
             .collect::<Vec<Passkey>>()
     };
 
@@ -817,7 +1147,7 @@ pub async fn auth_finish(
     user_id: String,
     req: &HttpRequest,
     browser_id: BrowserId,
-    session: Option<Session>,
+    session: Option<&mut Session>,
     payload: WebauthnAuthFinishRequest,
 ) -> Result<WebauthnAdditionalData, ErrorResponse> {
     let auth_data = WebauthnData::find(payload.code).await?;
@@ -828,7 +1158,7 @@ pub async fn auth_finish(
     let force_uv =
         user.account_type() == AccountType::Passkey || RauthyConfig::get().vars.webauthn.force_uv;
 
-    let pks = PasskeyEntity::find_for_user(&user.id).await?;
+    let mut pks = PasskeyEntity::find_for_user(&user.id).await?;
 
     match RauthyConfig::get()
         .webauthn
@@ -847,11 +1177,61 @@ pub async fn auth_finish(
             }
             let uid = user.id.clone();
 
+            let now = Utc::now().timestamp();
+            if let Some(pk_entity) = pks
+                .iter_mut()
+                .find(|pk_entity| pk_entity.get_pk().cred_id() == auth_result.cred_id())
+            {
+                let mut pk = pk_entity.get_pk();
+                let check = SignCounterCheck::new(
+                    Credential::from(pk.clone()).counter,
+                    auth_result.counter(),
+                    RauthyConfig::get()
+                        .vars
+                        .webauthn
+                        .reject_sign_counter_anomaly,
+                );
+
+                if check != SignCounterCheck::Ok {
+                    warn!(
+                        user.id,
+                        passkey = pk_entity.name,
+                        counter = auth_result.counter(),
+                        "Webauthn sign counter anomaly - the authenticator may have been cloned",
+                    );
+                    Event::passkey_counter_anomaly(
+                        format!("`{}` of user `{}`", pk_entity.name, user.email),
+                        real_ip(req)?,
+                    )
+                    .send()
+                    .await?;
+                    if pk_entity.counter_anomaly.is_none() {
+                        pk_entity.counter_anomaly = Some(now);
+                        pk_entity.update_passkey().await?;
+                    }
+                }
+                if check == SignCounterCheck::AnomalyRejected {
+                    return Err(ErrorResponse::new(
+                        ErrorResponseType::Unauthorized,
+                        "Invalid sign counter for this Passkey",
+                    ));
+                }
+
+                // the stored counter only ever increases
+                if pk.update_credential(&auth_result) == Some(true) {
+                    pk_entity.passkey = serde_json::to_string(&pk)?;
+                    pk_entity.last_used = now;
+                    pk_entity.update_passkey().await?;
+                }
+            }
+
             LoginLocation::spawn_background_check(user.clone(), req, browser_id)?;
 
             if matches!(auth_data.data, WebauthnAdditionalData::Login(_))
-                && let Some(mut session) = session
+                && let Some(session) = session
             {
+                // the passkey elevates the session to MFA -> prevent session fixation
+                session.rotate_id().await?;
                 session.set_authenticated(&user).await?;
                 user.last_login = Some(Utc::now().timestamp());
                 user.last_failed_login = None;
@@ -859,18 +1239,6 @@ pub async fn auth_finish(
                 user.save(None).await?;
             }
 
-            if auth_result.needs_update() {
-                let now = Utc::now().timestamp();
-                for mut pk_entity in pks {
-                    let mut pk = pk_entity.get_pk();
-                    if pk.update_credential(&auth_result) == Some(true) {
-                        pk_entity.passkey = serde_json::to_string(&pk)?;
-                        pk_entity.last_used = now;
-                        pk_entity.update_passkey().await?;
-                    }
-                }
-            }
-
             info!(user.id = uid, "Webauthn Authentication successful");
 
             if let WebauthnAdditionalData::Login(data) = auth_data.data {
@@ -941,21 +1309,13 @@ pub async fn reg_start(
             let cfg = &RauthyConfig::get().vars.webauthn;
             ccr.public_key.timeout = Some(cfg.req_exp as u32 * 1000);
 
-            if cfg.force_uv || user.account_type() == AccountType::Passkey {
-                // in this case we need to force UV no matter what is set in the config
-                ccr.public_key.authenticator_selection =
-                    if let Some(mut auth_sel) = ccr.public_key.authenticator_selection {
-                        auth_sel.user_verification = UserVerificationPolicy::Required;
-                        Some(auth_sel)
-                    } else {
-                        Some(AuthenticatorSelectionCriteria {
-                            authenticator_attachment: None,
-                            resident_key: Some(ResidentKeyRequirement::Discouraged),
-                            require_resident_key: false,
-                            user_verification: UserVerificationPolicy::Required,
-                        })
-                    };
-            };
+            let passkey_only =
+                matches!(user.account_type(), AccountType::New | AccountType::Passkey);
+            ccr.public_key.authenticator_selection = authenticator_selection(
+                ccr.public_key.authenticator_selection,
+                cfg.force_uv,
+                passkey_only,
+            );
 
             let reg_data = WebauthnReg {
                 user_id: user.id.clone(),
@@ -983,6 +1343,32 @@ pub async fn reg_start(
     }
 }
 
+/// Accounts without a password (passkey-only, or a new user that registers without ever
+/// setting one) always need UV and a resident key, so the credential can be used on its own.
+/// With `force_uv`, UV is required for all other accounts as well.
+fn authenticator_selection(
+    auth_sel: Option<AuthenticatorSelectionCriteria>,
+    force_uv: bool,
+    passkey_only: bool,
+) -> Option<AuthenticatorSelectionCriteria> {
+    if !force_uv && !passkey_only {
+        return auth_sel;
+    }
+
+    let mut auth_sel = auth_sel.unwrap_or(AuthenticatorSelectionCriteria {
+        authenticator_attachment: None,
+        resident_key: Some(ResidentKeyRequirement::Discouraged),
+        require_resident_key: false,
+        user_verification: UserVerificationPolicy::Required,
+    });
+    auth_sel.user_verification = UserVerificationPolicy::Required;
+    if passkey_only {
+        auth_sel.resident_key = Some(ResidentKeyRequirement::Required);
+        auth_sel.require_resident_key = true;
+    }
+    Some(auth_sel)
+}
+
 pub async fn reg_finish(
     id: String,
     payload: WebauthnRegFinishRequest,
@@ -1058,3 +1444,151 @@ pub async fn reg_finish(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn export() -> PasskeyExport {
+        let public_key = COSEKey {
+            type_: COSEAlgorithm::ES256,
+            key: COSEKeyType::EC_EC2(COSEEC2Key {
+                curve: ECDSACurve::SECP256R1,
+                x: vec![1; 32].into(),
+                y: vec![2; 32].into(),
+            }),
+        };
+
+        PasskeyExport {
+            name: "Yubikey 5".to_string(),
+            credential_id: base64_url_no_pad_encode(&[7; 32]),
+            public_key: serde_json::to_value(&public_key).unwrap(),
+            sign_count: 1337,
+            transports: Some(vec!["usb".to_string(), "nfc".to_string()]),
+            aaguid: None,
+            user_verified: true,
+            backup_eligible: false,
+            backup_state: false,
+            registered: 1,
+            last_used: 2,
+        }
+    }
+
+    #[test]
+    fn test_authenticator_selection() {
+        let discouraged = AuthenticatorSelectionCriteria {
+            authenticator_attachment: None,
+            resident_key: Some(ResidentKeyRequirement::Discouraged),
+            require_resident_key: false,
+            user_verification: UserVerificationPolicy::Preferred,
+        };
+
+        // password accounts without `force_uv` are untouched
+        let sel = authenticator_selection(Some(discouraged.clone()), false, false).unwrap();
+        assert!(matches!(
+            sel.user_verification,
+            UserVerificationPolicy::Preferred
+        ));
+        assert!(authenticator_selection(None, false, false).is_none());
+
+        let sel = authenticator_selection(Some(discouraged.clone()), true, false).unwrap();
+        assert!(matches!(
+            sel.user_verification,
+            UserVerificationPolicy::Required
+        ));
+        assert!(matches!(
+            sel.resident_key,
+            Some(ResidentKeyRequirement::Discouraged)
+        ));
+        assert!(!sel.require_resident_key);
+
+        // passkey-only registration always needs UV and a resident key
+        for auth_sel in [Some(discouraged), None] {
+            let sel = authenticator_selection(auth_sel, false, true).unwrap();
+            assert!(matches!(
+                sel.user_verification,
+                UserVerificationPolicy::Required
+            ));
+            assert!(matches!(
+                sel.resident_key,
+                Some(ResidentKeyRequirement::Required)
+            ));
+            assert!(sel.require_resident_key);
+        }
+    }
+
+    #[test]
+    fn test_passkey_export_round_trip() {
+        let exp = export();
+
+        let pk = passkey_from_export(&exp).unwrap();
+        assert_eq!(pk.cred_id().to_vec(), vec![7; 32]);
+        assert_eq!(Credential::from(pk.clone()).counter, 1337);
+
+        let res = passkey_to_export(exp.name.clone(), 1, 2, pk).unwrap();
+        assert_eq!(
+            serde_json::to_value(&res).unwrap(),
+            serde_json::to_value(&exp).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_sign_counter_strict() {
+        assert_eq!(SignCounterCheck::new(5, 6, true), SignCounterCheck::Ok);
+        assert_eq!(
+            SignCounterCheck::new(5, 5, true),
+            SignCounterCheck::AnomalyRejected
+        );
+        assert_eq!(
+            SignCounterCheck::new(5, 2, true),
+            SignCounterCheck::AnomalyRejected
+        );
+        // a counter reset to 0 after it has been increased is suspicious as well
+        assert_eq!(
+            SignCounterCheck::new(5, 0, true),
+            SignCounterCheck::AnomalyRejected
+        );
+    }
+
+    #[test]
+    fn test_sign_counter_permissive() {
+        assert_eq!(SignCounterCheck::new(5, 6, false), SignCounterCheck::Ok);
+        assert_eq!(
+            SignCounterCheck::new(5, 5, false),
+            SignCounterCheck::AnomalyAllowed
+        );
+        assert_eq!(
+            SignCounterCheck::new(5, 0, false),
+            SignCounterCheck::AnomalyAllowed
+        );
+    }
+
+    #[test]
+    fn test_sign_counter_synced_exempt() {
+        for reject in [true, false] {
+            assert_eq!(SignCounterCheck::new(0, 0, reject), SignCounterCheck::Ok);
+            assert_eq!(SignCounterCheck::new(0, 1, reject), SignCounterCheck::Ok);
+        }
+
+        let pk = passkey_from_export(&export()).unwrap();
+        let ceremony = without_sign_counter(pk.clone());
+        assert_eq!(Credential::from(ceremony.clone()).counter, 0);
+        assert_eq!(ceremony.cred_id(), pk.cred_id());
+    }
+
+    #[test]
+    fn test_passkey_import_invalid() {
+        let mut exp = export();
+        exp.credential_id = "not base64 !".to_string();
+        assert!(passkey_from_export(&exp).is_err());
+
+        let mut exp = export();
+        exp.public_key = serde_json::json!({ "kty": "unknown" });
+        assert!(passkey_from_export(&exp).is_err());
+
+        let mut exp = export();
+        exp.transports = Some(vec!["carrier-pigeon".to_string()]);
+        assert!(passkey_from_export(&exp).is_err());
+    }
+}
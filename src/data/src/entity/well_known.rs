@@ -1,11 +1,12 @@
-use crate::database::{Cache, DB};
+use crate::cache_layer::{self, CacheName};
 use crate::entity::scopes::Scope;
 use crate::language::Language;
 use crate::rauthy_config::RauthyConfig;
-use rauthy_common::constants::{CACHE_TTL_APP, GRANT_TYPE_DEVICE_CODE};
+use rauthy_common::constants::{CACHE_TTL_APP, GRANT_TYPE_DEVICE_CODE, RESPONSE_TYPES_SUPPORTED};
+use rauthy_common::sha256;
 use rauthy_error::ErrorResponse;
-use serde::Serialize;
-use strum::IntoEnumIterator;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use utoipa::ToSchema;
 
 /// The struct for the `.well-known` endpoint for automatic OIDC discovery.
@@ -27,7 +28,7 @@ pub struct WellKnown {
     pub registration_endpoint: Option<String>,
     pub jwks_uri: String,
     pub grant_types_supported: [&'static str; 5],
-    pub response_types_supported: [&'static str; 1],
+    pub response_types_supported: [&'static str; 2],
     pub subject_types_supported: [&'static str; 1],
     pub id_token_signing_alg_values_supported: [&'static str; 4],
     pub token_endpoint_auth_methods_supported: [&'static str; 2],
@@ -39,6 +40,9 @@ pub struct WellKnown {
     pub dpop_signing_alg_values_supported: [&'static str; 4],
     pub service_documentation: &'static str,
     pub ui_locales_supported: Vec<&'static str>,
+    /// The languages each `locale` claim value is mapped to. Raw upstream locales are emitted
+    /// as they are.
+    pub locale_values_supported: Vec<&'static str>,
     pub claims_parameter_supported: bool,
     /// SEP-991 / draft-jonesmichael-oauth-cimd. Signals that this AS accepts
     /// clients identified by a Client ID Metadata Document URL (Rauthy already
@@ -47,60 +51,342 @@ pub struct WellKnown {
     pub client_id_metadata_document_supported: bool,
 }
 
-static IDX: &str = ".well-known";
+/// RFC 8414 OAuth 2.0 Authorization Server Metadata for pure OAuth clients.
+///
+/// It is always derived from the [WellKnown] for OIDC discovery, which makes sure that both
+/// documents can never drift apart.
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct AuthorizationServerMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub registration_endpoint: Option<String>,
+    pub scopes_supported: Vec<String>,
+    pub response_types_supported: [&'static str; 2],
+    pub grant_types_supported: [&'static str; 5],
+    pub token_endpoint_auth_methods_supported: [&'static str; 2],
+    pub token_endpoint_auth_signing_alg_values_supported: [&'static str; 4],
+    pub service_documentation: &'static str,
+    pub ui_locales_supported: Vec<&'static str>,
+    pub revocation_endpoint: String,
+    pub revocation_endpoint_auth_methods_supported: [&'static str; 2],
+    pub introspection_endpoint: String,
+    pub introspection_endpoint_auth_methods_supported: [&'static str; 1],
+    pub code_challenge_methods_supported: [&'static str; 2],
+    /// RFC 8628
+    pub device_authorization_endpoint: String,
+    /// RFC 9449
+    pub dpop_signing_alg_values_supported: [&'static str; 4],
+    pub client_id_metadata_document_supported: bool,
+}
 
-impl WellKnown {
-    pub async fn json() -> Result<String, ErrorResponse> {
-        let client = DB::hql();
-        if let Some(slf) = client.get(Cache::App, IDX).await? {
-            return Ok(slf);
+impl From<WellKnown> for AuthorizationServerMetadata {
+    fn from(wk: WellKnown) -> Self {
+        Self {
+            issuer: wk.issuer,
+            authorization_endpoint: wk.authorization_endpoint,
+            token_endpoint: wk.token_endpoint,
+            jwks_uri: wk.jwks_uri,
+            registration_endpoint: wk.registration_endpoint,
+            scopes_supported: wk.scopes_supported,
+            response_types_supported: wk.response_types_supported,
+            grant_types_supported: wk.grant_types_supported,
+            token_endpoint_auth_methods_supported: wk.token_endpoint_auth_methods_supported,
+            token_endpoint_auth_signing_alg_values_supported: wk
+                .token_endpoint_auth_signing_alg_values_supported,
+            service_documentation: wk.service_documentation,
+            ui_locales_supported: wk.ui_locales_supported,
+            revocation_endpoint: wk.revocation_endpoint,
+            // `/token/revoke` accepts the same client auth as `/token`
+            revocation_endpoint_auth_methods_supported: wk.token_endpoint_auth_methods_supported,
+            introspection_endpoint: wk.introspection_endpoint,
+            introspection_endpoint_auth_methods_supported: ["client_secret_basic"],
+            code_challenge_methods_supported: wk.code_challenge_methods_supported,
+            device_authorization_endpoint: wk.device_authorization_endpoint,
+            dpop_signing_alg_values_supported: wk.dpop_signing_alg_values_supported,
+            client_id_metadata_document_supported: wk.client_id_metadata_document_supported,
         }
+    }
+}
 
-        let scopes = Scope::find_all()
-            .await?
-            .into_iter()
-            .map(|s| s.name)
-            .collect::<Vec<String>>();
-        let slf = Self::new(scopes);
-        let json = serde_json::to_string(&slf)?;
+/// A pre-serialized discovery document together with its `ETag`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DiscoveryDocument {
+    pub json: String,
+    pub etag: String,
+}
+
+impl DiscoveryDocument {
+    fn new(json: String) -> Self {
+        let etag = format!("\"{}\"", hex::encode(sha256!(json.as_bytes())));
+        Self { json, etag }
+    }
+
+    /// Returns `true` if the given `If-None-Match` header value matches the current `ETag`.
+    pub fn is_not_modified(&self, if_none_match: Option<&str>) -> bool {
+        if_none_match.is_some_and(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || tag.trim_start_matches("W/") == self.etag)
+        })
+    }
+}
+
+static IDX_OIDC: &str = ".well-known_oidc";
+static IDX_OAUTH: &str = ".well-known_oauth";
+
+/// IANA registered OAuth Authorization Server Metadata and OpenID Provider Metadata, which must
+/// never be overwritten by `wellknown_extra_claims`.
+static IANA_DISCOVERY_FIELDS: [&str; 81] = [
+    "acr_values_supported",
+    "authorization_details_types_supported",
+    "authorization_encryption_alg_values_supported",
+    "authorization_encryption_enc_values_supported",
+    "authorization_endpoint",
+    "authorization_response_iss_parameter_supported",
+    "authorization_signing_alg_values_supported",
+    "backchannel_authentication_endpoint",
+    "backchannel_authentication_request_signing_alg_values_supported",
+    "backchannel_logout_session_supported",
+    "backchannel_logout_supported",
+    "backchannel_token_delivery_modes_supported",
+    "backchannel_user_code_parameter_supported",
+    "check_session_iframe",
+    "claim_types_supported",
+    "claims_locales_supported",
+    "claims_parameter_supported",
+    "claims_supported",
+    "client_id_metadata_document_supported",
+    "client_registration_types_supported",
+    "code_challenge_methods_supported",
+    "device_authorization_endpoint",
+    "display_values_supported",
+    "dpop_signing_alg_values_supported",
+    "end_session_endpoint",
+    "federation_registration_endpoint",
+    "frontchannel_logout_session_supported",
+    "frontchannel_logout_supported",
+    "grant_types_supported",
+    "id_token_encryption_alg_values_supported",
+    "id_token_encryption_enc_values_supported",
+    "id_token_signing_alg_values_supported",
+    "introspection_encryption_alg_values_supported",
+    "introspection_encryption_enc_values_supported",
+    "introspection_endpoint",
+    "introspection_endpoint_auth_methods_supported",
+    "introspection_endpoint_auth_signing_alg_values_supported",
+    "introspection_signing_alg_values_supported",
+    "issuer",
+    "jwks",
+    "jwks_uri",
+    "mtls_endpoint_aliases",
+    "nfv_token_encryption_alg_values_supported",
+    "nfv_token_encryption_enc_values_supported",
+    "nfv_token_signing_alg_values_supported",
+    "op_policy_uri",
+    "op_tos_uri",
+    "organization_name",
+    "prompt_values_supported",
+    "protected_resources",
+    "pushed_authorization_request_endpoint",
+    "registration_endpoint",
+    "request_authentication_methods_supported",
+    "request_authentication_signing_alg_values_supported",
+    "request_object_encryption_alg_values_supported",
+    "request_object_encryption_enc_values_supported",
+    "request_object_signing_alg_values_supported",
+    "request_parameter_supported",
+    "request_uri_parameter_supported",
+    "require_pushed_authorization_requests",
+    "require_request_uri_registration",
+    "require_signed_request_object",
+    "response_modes_supported",
+    "response_types_supported",
+    "revocation_endpoint",
+    "revocation_endpoint_auth_methods_supported",
+    "revocation_endpoint_auth_signing_alg_values_supported",
+    "scopes_supported",
+    "service_documentation",
+    "signed_jwks_uri",
+    "signed_metadata",
+    "subject_types_supported",
+    "tls_client_certificate_bound_access_tokens",
+    "token_endpoint",
+    "token_endpoint_auth_methods_supported",
+    "token_endpoint_auth_signing_alg_values_supported",
+    "ui_locales_supported",
+    "userinfo_encryption_alg_values_supported",
+    "userinfo_encryption_enc_values_supported",
+    "userinfo_endpoint",
+    "userinfo_signing_alg_values_supported",
+];
+
+/// Parses the `wellknown_extra_claims` config in the format `key1=value1,key2=value2`.
+///
+/// Values are interpreted as `true` / `false` booleans, `[a,b,c]` arrays of strings, or plain
+/// strings otherwise. Returns an error message, if a pair is invalid or keys collide with IANA
+/// registered discovery fields.
+pub fn parse_wellknown_extra_claims(value: &str) -> Result<Map<String, Value>, String> {
+    let mut pairs = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in value.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            ',' if depth == 0 => {
+                pairs.push(&value[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    pairs.push(&value[start..]);
+
+    let mut res = Map::with_capacity(pairs.len());
+    let mut collisions = Vec::new();
+    for pair in pairs.into_iter().map(str::trim).filter(|p| !p.is_empty()) {
+        let Some((key, value)) = pair.split_once('=') else {
+            return Err(format!("Invalid pair '{pair}', expected 'key=value'"));
+        };
+        let key = key.trim();
+        if key.is_empty() {
+            return Err(format!("Empty key in pair '{pair}'"));
+        }
+        if IANA_DISCOVERY_FIELDS.contains(&key) {
+            collisions.push(key);
+            continue;
+        }
 
-        client.put(Cache::App, IDX, &json, CACHE_TTL_APP).await?;
+        let value = value.trim();
+        let value = match value {
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            v if v.starts_with('[') && v.ends_with(']') => Value::Array(
+                v[1..v.len() - 1]
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|v| !v.is_empty())
+                    .map(|v| Value::String(v.to_string()))
+                    .collect(),
+            ),
+            v => Value::String(v.to_string()),
+        };
+        res.insert(key.to_string(), value);
+    }
+
+    if collisions.is_empty() {
+        Ok(res)
+    } else {
+        Err(format!(
+            "Keys collide with registered discovery document fields: {}",
+            collisions.join(", ")
+        ))
+    }
+}
+
+impl WellKnown {
+    /// Returns the OIDC discovery document for `/.well-known/openid-configuration`.
+    pub async fn openid_configuration() -> Result<DiscoveryDocument, ErrorResponse> {
+        Self::find_document(IDX_OIDC).await
+    }
+
+    /// Returns the RFC 8414 metadata for `/.well-known/oauth-authorization-server`.
+    pub async fn oauth_authorization_server() -> Result<DiscoveryDocument, ErrorResponse> {
+        Self::find_document(IDX_OAUTH).await
+    }
 
-        Ok(json)
+    async fn find_document(idx: &'static str) -> Result<DiscoveryDocument, ErrorResponse> {
+        if let Some(doc) = cache_layer::get_swr(
+            CacheName::WellKnown,
+            idx.to_string(),
+            CACHE_TTL_APP,
+            || async { Self::build_documents().await.map(|_| ()) },
+        )
+        .await?
+        {
+            return Ok(doc);
+        }
+
+        let (oidc, oauth) = Self::build_documents().await?;
+        if idx == IDX_OIDC { Ok(oidc) } else { Ok(oauth) }
     }
 
-    /// Rebuilds the WellKnown, serializes it as json and updates it inside the cache.
+    /// Rebuilds both discovery documents and updates them inside the cache.
     /// Should be called after any update on the Scopes.
     pub async fn rebuild() -> Result<(), ErrorResponse> {
+        Self::build_documents().await?;
+        Ok(())
+    }
+
+    async fn build_documents() -> Result<(DiscoveryDocument, DiscoveryDocument), ErrorResponse> {
         let scopes = Scope::find_all()
             .await?
             .into_iter()
             .map(|s| s.name)
             .collect::<Vec<String>>();
         let slf = Self::new(scopes);
-        let json = serde_json::to_string(&slf)?;
 
-        DB::hql().put(Cache::App, IDX, &json, CACHE_TTL_APP).await?;
+        let oidc = DiscoveryDocument::new(Self::to_json(&slf)?);
+        let oauth = DiscoveryDocument::new(Self::to_json(&AuthorizationServerMetadata::from(slf))?);
 
-        Ok(())
+        cache_layer::put_swr(CacheName::WellKnown, IDX_OIDC, &oidc, CACHE_TTL_APP).await?;
+        cache_layer::put_swr(CacheName::WellKnown, IDX_OAUTH, &oauth, CACHE_TTL_APP).await?;
+
+        Ok((oidc, oauth))
+    }
+
+    /// The RFC 8414 well-known path for the current issuer. For an issuer with a path component,
+    /// the well-known part is inserted between the host and the path.
+    pub fn oauth_metadata_path() -> String {
+        Self::oauth_metadata_path_for(&RauthyConfig::get().issuer)
+    }
+
+    fn oauth_metadata_path_for(issuer: &str) -> String {
+        let without_scheme = issuer.split_once("://").map(|(_, r)| r).unwrap_or(issuer);
+        let path = without_scheme
+            .find('/')
+            .map(|idx| without_scheme[idx..].trim_end_matches('/'))
+            .unwrap_or_default();
+        format!("/.well-known/oauth-authorization-server{path}")
     }
 }
 
 impl WellKnown {
+    /// Serializes a discovery document including the configured `wellknown_extra_claims`.
+    fn to_json<T: Serialize>(doc: &T) -> Result<String, ErrorResponse> {
+        let extra = &RauthyConfig::get().vars.server.wellknown_extra_claims;
+        if extra.is_empty() {
+            return Ok(serde_json::to_string(doc)?);
+        }
+
+        let mut value = serde_json::to_value(doc)?;
+        if let Value::Object(map) = &mut value {
+            map.extend(extra.clone());
+        }
+        Ok(serde_json::to_string(&value)?)
+    }
+
     pub fn new(scopes_supported: Vec<String>) -> Self {
-        let issuer = &RauthyConfig::get().issuer;
+        let config = RauthyConfig::get();
+        Self::build(
+            &config.issuer,
+            config.vars.dynamic_clients.enable,
+            scopes_supported,
+        )
+    }
 
+    fn build(issuer: &str, dyn_clients_enabled: bool, scopes_supported: Vec<String>) -> Self {
         let authorization_endpoint = format!("{issuer}oidc/authorize");
         let device_authorization_endpoint = format!("{issuer}oidc/device");
         let token_endpoint = format!("{issuer}oidc/token");
         let introspection_endpoint = format!("{issuer}oidc/introspect");
         let revocation_endpoint = format!("{issuer}oidc/token/revoke");
         let userinfo_endpoint = format!("{issuer}oidc/userinfo");
-        let registration_endpoint = RauthyConfig::get()
-            .vars
-            .dynamic_clients
-            .enable
-            .then_some(format!("{issuer}clients_dyn"));
+        let registration_endpoint = dyn_clients_enabled.then_some(format!("{issuer}clients_dyn"));
         let end_session_endpoint = format!("{issuer}oidc/logout");
         let jwks_uri = format!("{issuer}oidc/certs");
 
@@ -124,7 +410,7 @@ impl WellKnown {
                 "refresh_token",
                 GRANT_TYPE_DEVICE_CODE,
             ],
-            response_types_supported: ["code"],
+            response_types_supported: RESPONSE_TYPES_SUPPORTED,
             subject_types_supported: ["public"],
             id_token_signing_alg_values_supported: ["RS256", "RS384", "RS512", "EdDSA"],
             token_endpoint_auth_methods_supported: ["client_secret_post", "client_secret_basic"],
@@ -148,9 +434,113 @@ impl WellKnown {
             code_challenge_methods_supported: ["plain", "S256"],
             dpop_signing_alg_values_supported: ["RS256", "RS384", "RS512", "EdDSA"],
             service_documentation: "https://sebadob.github.io/rauthy/",
-            ui_locales_supported: Language::iter().map(|l| l.as_str()).collect(),
+            ui_locales_supported: Language::all().map(|l| l.as_str()).collect(),
+            locale_values_supported: Language::all().map(|l| l.as_str()).collect(),
             claims_parameter_supported: true,
             client_id_metadata_document_supported: true,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_wellknown_extra_claims() {
+        let claims = parse_wellknown_extra_claims(
+            "vault_role=rauthy, mfa_supported=true,legacy=false,aud_list=[a, b,c],empty=[]",
+        )
+        .unwrap();
+        assert_eq!(
+            Value::Object(claims),
+            json!({
+                "vault_role": "rauthy",
+                "mfa_supported": true,
+                "legacy": false,
+                "aud_list": ["a", "b", "c"],
+                "empty": [],
+            })
+        );
+
+        assert!(parse_wellknown_extra_claims("").unwrap().is_empty());
+        assert!(parse_wellknown_extra_claims("no_value").is_err());
+        assert!(parse_wellknown_extra_claims("=value").is_err());
+
+        let err = parse_wellknown_extra_claims("issuer=x,custom=1,jwks_uri=y").unwrap_err();
+        assert!(err.contains("issuer, jwks_uri"));
+    }
+
+    #[test]
+    fn test_oauth_authorization_server_metadata() {
+        let issuer = "https://iam.example.com/auth/v1/";
+        let wk = WellKnown::build(issuer, true, vec!["openid".to_string()]);
+        let oidc = serde_json::to_value(&wk).unwrap();
+        let oauth = serde_json::to_value(AuthorizationServerMetadata::from(wk)).unwrap();
+
+        // RFC 8414 section 2 - REQUIRED and the endpoints we support
+        for key in [
+            "issuer",
+            "authorization_endpoint",
+            "token_endpoint",
+            "jwks_uri",
+            "registration_endpoint",
+            "scopes_supported",
+            "response_types_supported",
+            "grant_types_supported",
+            "token_endpoint_auth_methods_supported",
+            "revocation_endpoint",
+            "revocation_endpoint_auth_methods_supported",
+            "introspection_endpoint",
+            "introspection_endpoint_auth_methods_supported",
+            "code_challenge_methods_supported",
+        ] {
+            assert!(oauth.get(key).is_some(), "missing RFC 8414 field {key}");
+        }
+        assert_eq!(oauth["issuer"], json!(issuer));
+
+        // OIDC-only values must not leak into the OAuth metadata
+        for key in [
+            "userinfo_endpoint",
+            "subject_types_supported",
+            "id_token_signing_alg_values_supported",
+            "claims_supported",
+        ] {
+            assert!(oauth.get(key).is_none(), "unexpected field {key}");
+        }
+
+        // every shared value must be identical in both documents
+        for (key, value) in oauth.as_object().unwrap() {
+            if let Some(oidc_value) = oidc.get(key) {
+                assert_eq!(oidc_value, value, "mismatch for {key}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_oauth_metadata_path() {
+        assert_eq!(
+            WellKnown::oauth_metadata_path_for("https://iam.example.com/auth/v1/"),
+            "/.well-known/oauth-authorization-server/auth/v1"
+        );
+        assert_eq!(
+            WellKnown::oauth_metadata_path_for("https://example.com/iam/auth/v1/"),
+            "/.well-known/oauth-authorization-server/iam/auth/v1"
+        );
+        assert_eq!(
+            WellKnown::oauth_metadata_path_for("https://iam.example.com"),
+            "/.well-known/oauth-authorization-server"
+        );
+    }
+
+    #[test]
+    fn test_discovery_document_etag() {
+        let doc = DiscoveryDocument::new(r#"{"issuer":"x"}"#.to_string());
+        assert!(doc.etag.starts_with('"') && doc.etag.ends_with('"'));
+        assert!(doc.is_not_modified(Some(&doc.etag)));
+        assert!(doc.is_not_modified(Some(&format!("W/{}, \"other\"", doc.etag))));
+        assert!(!doc.is_not_modified(Some("\"other\"")));
+        assert!(!doc.is_not_modified(None));
+    }
+}
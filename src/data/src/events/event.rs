@@ -165,6 +165,30 @@ pub enum EventType {
     TokenIssued,
     CredentialStuffing,
     EmailSendError,
+    TokenGroupsOverflow,
+    MaintenanceSummary,
+    UserErased,
+    PasskeyImported,
+    UserRegistrationPending,
+    ProviderTestCallback,
+    MigrationExport,
+    MigrationImport,
+    RefreshTokenBindingMismatch,
+    UsersDisabledBulk,
+    UserDisabled,
+    MaintenanceModeStarted,
+    MaintenanceModeEnded,
+    ExpiringDigest,
+    AuthProviderUnavailable,
+    AuthProviderAvailable,
+    ProviderSecretRotated,
+    PasskeyCounterAnomaly,
+    BreakGlassLogin,
+    UpstreamAdminRoleChange,
+    UpstreamDomainRejected,
+    UserProviderLink,
+    AuthProviderMetadataUpdated,
+    UpstreamRefreshFailed,
 }
 
 impl Display for EventType {
@@ -194,6 +218,32 @@ impl Display for EventType {
             Self::TokenIssued => write!(f, "JWT Token issued"),
             Self::CredentialStuffing => write!(f, "Possible credential stuffing"),
             Self::EmailSendError => write!(f, "E-Mail send error"),
+            Self::TokenGroupsOverflow => write!(f, "Token groups limit exceeded"),
+            Self::MaintenanceSummary => write!(f, "Maintenance summary"),
+            Self::UserErased => write!(f, "User has been erased"),
+            Self::PasskeyImported => write!(f, "Passkey imported"),
+            Self::UserRegistrationPending => write!(f, "User registration pending approval"),
+            Self::ProviderTestCallback => write!(f, "Auth provider test callback"),
+            Self::MigrationExport => write!(f, "Migration archive exported"),
+            Self::MigrationImport => write!(f, "Migration archive imported"),
+            Self::RefreshTokenBindingMismatch => write!(f, "Refresh token binding mismatch"),
+            Self::UsersDisabledBulk => write!(f, "Users disabled in bulk"),
+            Self::UserDisabled => write!(f, "User has been disabled"),
+            Self::MaintenanceModeStarted => write!(f, "Maintenance mode started"),
+            Self::MaintenanceModeEnded => write!(f, "Maintenance mode ended"),
+            Self::ExpiringDigest => write!(f, "Expiring soon digest"),
+            Self::AuthProviderUnavailable => write!(f, "Upstream auth provider unavailable"),
+            Self::AuthProviderAvailable => write!(f, "Upstream auth provider available again"),
+            Self::ProviderSecretRotated => write!(f, "Auth provider secret rotated"),
+            Self::PasskeyCounterAnomaly => write!(f, "Passkey sign counter anomaly"),
+            Self::BreakGlassLogin => write!(f, "Break-glass admin login"),
+            Self::UpstreamAdminRoleChange => write!(f, "Rauthy_admin role changed by upstream"),
+            Self::UpstreamDomainRejected => write!(f, "Upstream login from a foreign domain"),
+            Self::UserProviderLink => write!(f, "User auth provider link changed"),
+            Self::AuthProviderMetadataUpdated => {
+                write!(f, "Upstream auth provider metadata updated")
+            }
+            Self::UpstreamRefreshFailed => write!(f, "Upstream refresh failed"),
         }
     }
 }
@@ -227,6 +277,52 @@ impl From<rauthy_api_types::events::EventType> for EventType {
             rauthy_api_types::events::EventType::TokenIssued => Self::TokenIssued,
             rauthy_api_types::events::EventType::CredentialStuffing => Self::CredentialStuffing,
             rauthy_api_types::events::EventType::EmailSendError => Self::EmailSendError,
+            rauthy_api_types::events::EventType::TokenGroupsOverflow => Self::TokenGroupsOverflow,
+            rauthy_api_types::events::EventType::MaintenanceSummary => Self::MaintenanceSummary,
+            rauthy_api_types::events::EventType::UserErased => Self::UserErased,
+            rauthy_api_types::events::EventType::PasskeyImported => Self::PasskeyImported,
+            rauthy_api_types::events::EventType::UserRegistrationPending => {
+                Self::UserRegistrationPending
+            }
+            rauthy_api_types::events::EventType::ProviderTestCallback => Self::ProviderTestCallback,
+            rauthy_api_types::events::EventType::MigrationExport => Self::MigrationExport,
+            rauthy_api_types::events::EventType::MigrationImport => Self::MigrationImport,
+            rauthy_api_types::events::EventType::RefreshTokenBindingMismatch => {
+                Self::RefreshTokenBindingMismatch
+            }
+            rauthy_api_types::events::EventType::UsersDisabledBulk => Self::UsersDisabledBulk,
+            rauthy_api_types::events::EventType::UserDisabled => Self::UserDisabled,
+            rauthy_api_types::events::EventType::MaintenanceModeStarted => {
+                Self::MaintenanceModeStarted
+            }
+            rauthy_api_types::events::EventType::MaintenanceModeEnded => Self::MaintenanceModeEnded,
+            rauthy_api_types::events::EventType::ExpiringDigest => Self::ExpiringDigest,
+            rauthy_api_types::events::EventType::AuthProviderUnavailable => {
+                Self::AuthProviderUnavailable
+            }
+            rauthy_api_types::events::EventType::AuthProviderAvailable => {
+                Self::AuthProviderAvailable
+            }
+            rauthy_api_types::events::EventType::ProviderSecretRotated => {
+                Self::ProviderSecretRotated
+            }
+            rauthy_api_types::events::EventType::PasskeyCounterAnomaly => {
+                Self::PasskeyCounterAnomaly
+            }
+            rauthy_api_types::events::EventType::BreakGlassLogin => Self::BreakGlassLogin,
+            rauthy_api_types::events::EventType::UpstreamAdminRoleChange => {
+                Self::UpstreamAdminRoleChange
+            }
+            rauthy_api_types::events::EventType::UpstreamDomainRejected => {
+                Self::UpstreamDomainRejected
+            }
+            rauthy_api_types::events::EventType::UserProviderLink => Self::UserProviderLink,
+            rauthy_api_types::events::EventType::AuthProviderMetadataUpdated => {
+                Self::AuthProviderMetadataUpdated
+            }
+            rauthy_api_types::events::EventType::UpstreamRefreshFailed => {
+                Self::UpstreamRefreshFailed
+            }
         }
     }
 }
@@ -258,6 +354,30 @@ impl From<EventType> for rauthy_api_types::events::EventType {
             EventType::TokenIssued => Self::TokenIssued,
             EventType::CredentialStuffing => Self::CredentialStuffing,
             EventType::EmailSendError => Self::EmailSendError,
+            EventType::TokenGroupsOverflow => Self::TokenGroupsOverflow,
+            EventType::MaintenanceSummary => Self::MaintenanceSummary,
+            EventType::UserErased => Self::UserErased,
+            EventType::PasskeyImported => Self::PasskeyImported,
+            EventType::UserRegistrationPending => Self::UserRegistrationPending,
+            EventType::ProviderTestCallback => Self::ProviderTestCallback,
+            EventType::MigrationExport => Self::MigrationExport,
+            EventType::MigrationImport => Self::MigrationImport,
+            EventType::RefreshTokenBindingMismatch => Self::RefreshTokenBindingMismatch,
+            EventType::UsersDisabledBulk => Self::UsersDisabledBulk,
+            EventType::UserDisabled => Self::UserDisabled,
+            EventType::MaintenanceModeStarted => Self::MaintenanceModeStarted,
+            EventType::MaintenanceModeEnded => Self::MaintenanceModeEnded,
+            EventType::ExpiringDigest => Self::ExpiringDigest,
+            EventType::AuthProviderUnavailable => Self::AuthProviderUnavailable,
+            EventType::AuthProviderAvailable => Self::AuthProviderAvailable,
+            EventType::ProviderSecretRotated => Self::ProviderSecretRotated,
+            EventType::PasskeyCounterAnomaly => Self::PasskeyCounterAnomaly,
+            EventType::BreakGlassLogin => Self::BreakGlassLogin,
+            EventType::UpstreamAdminRoleChange => Self::UpstreamAdminRoleChange,
+            EventType::UpstreamDomainRejected => Self::UpstreamDomainRejected,
+            EventType::UserProviderLink => Self::UserProviderLink,
+            EventType::AuthProviderMetadataUpdated => Self::AuthProviderMetadataUpdated,
+            EventType::UpstreamRefreshFailed => Self::UpstreamRefreshFailed,
         }
     }
 }
@@ -289,6 +409,30 @@ impl EventType {
             Self::TokenIssued => "TokenIssued",
             Self::CredentialStuffing => "CredentialStuffing",
             Self::EmailSendError => "EmailSendError",
+            Self::TokenGroupsOverflow => "TokenGroupsOverflow",
+            Self::MaintenanceSummary => "MaintenanceSummary",
+            Self::UserErased => "UserErased",
+            Self::PasskeyImported => "PasskeyImported",
+            Self::UserRegistrationPending => "UserRegistrationPending",
+            Self::ProviderTestCallback => "ProviderTestCallback",
+            Self::MigrationExport => "MigrationExport",
+            Self::MigrationImport => "MigrationImport",
+            Self::RefreshTokenBindingMismatch => "RefreshTokenBindingMismatch",
+            Self::UsersDisabledBulk => "UsersDisabledBulk",
+            Self::UserDisabled => "UserDisabled",
+            Self::MaintenanceModeStarted => "MaintenanceModeStarted",
+            Self::MaintenanceModeEnded => "MaintenanceModeEnded",
+            Self::ExpiringDigest => "ExpiringDigest",
+            Self::AuthProviderUnavailable => "AuthProviderUnavailable",
+            Self::AuthProviderAvailable => "AuthProviderAvailable",
+            Self::ProviderSecretRotated => "ProviderSecretRotated",
+            Self::PasskeyCounterAnomaly => "PasskeyCounterAnomaly",
+            Self::BreakGlassLogin => "BreakGlassLogin",
+            Self::UpstreamAdminRoleChange => "UpstreamAdminRoleChange",
+            Self::UpstreamDomainRejected => "UpstreamDomainRejected",
+            Self::UserProviderLink => "UserProviderLink",
+            Self::AuthProviderMetadataUpdated => "AuthProviderMetadataUpdated",
+            Self::UpstreamRefreshFailed => "UpstreamRefreshFailed",
         }
     }
 
@@ -321,6 +465,30 @@ impl EventType {
             EventType::TokenIssued => 21,
             EventType::CredentialStuffing => 22,
             EventType::EmailSendError => 23,
+            EventType::TokenGroupsOverflow => 24,
+            EventType::MaintenanceSummary => 25,
+            EventType::UserErased => 26,
+            EventType::PasskeyImported => 27,
+            EventType::UserRegistrationPending => 28,
+            EventType::ProviderTestCallback => 29,
+            EventType::MigrationExport => 30,
+            EventType::MigrationImport => 31,
+            EventType::RefreshTokenBindingMismatch => 32,
+            EventType::UsersDisabledBulk => 33,
+            EventType::UserDisabled => 34,
+            EventType::MaintenanceModeStarted => 35,
+            EventType::MaintenanceModeEnded => 36,
+            EventType::ExpiringDigest => 37,
+            EventType::AuthProviderUnavailable => 38,
+            EventType::AuthProviderAvailable => 39,
+            EventType::ProviderSecretRotated => 40,
+            EventType::PasskeyCounterAnomaly => 41,
+            EventType::BreakGlassLogin => 42,
+            EventType::UpstreamAdminRoleChange => 43,
+            EventType::UpstreamDomainRejected => 44,
+            EventType::UserProviderLink => 45,
+            EventType::AuthProviderMetadataUpdated => 46,
+            EventType::UpstreamRefreshFailed => 47,
         }
     }
 }
@@ -352,6 +520,30 @@ impl From<String> for EventType {
             "TokenIssued" => Self::TokenIssued,
             "CredentialStuffing" => Self::CredentialStuffing,
             "EmailSendError" => Self::EmailSendError,
+            "TokenGroupsOverflow" => Self::TokenGroupsOverflow,
+            "MaintenanceSummary" => Self::MaintenanceSummary,
+            "UserErased" => Self::UserErased,
+            "PasskeyImported" => Self::PasskeyImported,
+            "UserRegistrationPending" => Self::UserRegistrationPending,
+            "ProviderTestCallback" => Self::ProviderTestCallback,
+            "MigrationExport" => Self::MigrationExport,
+            "MigrationImport" => Self::MigrationImport,
+            "RefreshTokenBindingMismatch" => Self::RefreshTokenBindingMismatch,
+            "UsersDisabledBulk" => Self::UsersDisabledBulk,
+            "UserDisabled" => Self::UserDisabled,
+            "MaintenanceModeStarted" => Self::MaintenanceModeStarted,
+            "MaintenanceModeEnded" => Self::MaintenanceModeEnded,
+            "ExpiringDigest" => Self::ExpiringDigest,
+            "AuthProviderUnavailable" => Self::AuthProviderUnavailable,
+            "AuthProviderAvailable" => Self::AuthProviderAvailable,
+            "ProviderSecretRotated" => Self::ProviderSecretRotated,
+            "PasskeyCounterAnomaly" => Self::PasskeyCounterAnomaly,
+            "BreakGlassLogin" => Self::BreakGlassLogin,
+            "UpstreamAdminRoleChange" => Self::UpstreamAdminRoleChange,
+            "UpstreamDomainRejected" => Self::UpstreamDomainRejected,
+            "UserProviderLink" => Self::UserProviderLink,
+            "AuthProviderMetadataUpdated" => Self::AuthProviderMetadataUpdated,
+            "UpstreamRefreshFailed" => Self::UpstreamRefreshFailed,
             // just return test to never panic
             s => {
                 error!("EventType::from() for invalid String: {s}");
@@ -394,6 +586,30 @@ impl From<i64> for EventType {
             21 => EventType::TokenIssued,
             22 => EventType::CredentialStuffing,
             23 => EventType::EmailSendError,
+            24 => EventType::TokenGroupsOverflow,
+            25 => EventType::MaintenanceSummary,
+            26 => EventType::UserErased,
+            27 => EventType::PasskeyImported,
+            28 => EventType::UserRegistrationPending,
+            29 => EventType::ProviderTestCallback,
+            30 => EventType::MigrationExport,
+            31 => EventType::MigrationImport,
+            32 => EventType::RefreshTokenBindingMismatch,
+            33 => EventType::UsersDisabledBulk,
+            34 => EventType::UserDisabled,
+            35 => EventType::MaintenanceModeStarted,
+            36 => EventType::MaintenanceModeEnded,
+            37 => EventType::ExpiringDigest,
+            38 => EventType::AuthProviderUnavailable,
+            39 => EventType::AuthProviderAvailable,
+            40 => EventType::ProviderSecretRotated,
+            41 => EventType::PasskeyCounterAnomaly,
+            42 => EventType::BreakGlassLogin,
+            43 => EventType::UpstreamAdminRoleChange,
+            44 => EventType::UpstreamDomainRejected,
+            45 => EventType::UserProviderLink,
+            46 => EventType::AuthProviderMetadataUpdated,
+            47 => EventType::UpstreamRefreshFailed,
             _ => EventType::Test,
         }
     }
@@ -478,7 +694,13 @@ impl From<&Event> for Notification {
             EventType::RauthyStarted => value.text.clone(),
             EventType::RauthyHealthy => value.text.clone(),
             EventType::RauthyUnhealthy => value.text.clone(),
-            EventType::SecretsMigrated => value.ip.clone(),
+            EventType::SecretsMigrated => match &value.text {
+                Some(text) => Some(format!(
+                    "{text} - IP `{}`",
+                    value.ip.as_deref().unwrap_or_default()
+                )),
+                None => value.ip.clone(),
+            },
             EventType::UserEmailChange => value.text.clone(),
             EventType::UserPasswordReset => value.text.clone(),
             EventType::Test => value.text.clone(),
@@ -505,6 +727,86 @@ impl From<&Event> for Notification {
                 value.ip.as_deref().unwrap_or_default()
             )),
             EventType::EmailSendError => value.text.clone(),
+            EventType::TokenGroupsOverflow => Some(format!(
+                "Groups limit exceeded ({}) for: {}",
+                value.data.unwrap_or_default(),
+                value.text.as_deref().unwrap_or_default()
+            )),
+            EventType::MaintenanceSummary => value.text.clone(),
+            EventType::UserErased => Some(format!(
+                "User erased: {}",
+                value.text.as_deref().unwrap_or_default()
+            )),
+            EventType::PasskeyImported => Some(format!(
+                "{} Passkey(s) imported for: {}",
+                value.data.unwrap_or_default(),
+                value.text.as_deref().unwrap_or_default()
+            )),
+            EventType::UserRegistrationPending => Some(format!(
+                "E-Mail `{}` registered from IP `{}` and awaits approval",
+                value.text.as_deref().unwrap_or_default(),
+                value.ip.as_deref().unwrap_or_default()
+            )),
+            EventType::ProviderTestCallback => Some(format!(
+                "Test callback for {} from IP `{}`",
+                value.text.as_deref().unwrap_or_default(),
+                value.ip.as_deref().unwrap_or_default()
+            )),
+            EventType::MigrationExport => Some(format!(
+                "Exported {} from IP `{}`",
+                value.text.as_deref().unwrap_or_default(),
+                value.ip.as_deref().unwrap_or_default()
+            )),
+            EventType::MigrationImport => Some(format!(
+                "Imported {} from IP `{}`",
+                value.text.as_deref().unwrap_or_default(),
+                value.ip.as_deref().unwrap_or_default()
+            )),
+            EventType::RefreshTokenBindingMismatch => Some(format!(
+                "Refresh token for {} used from IP `{}`",
+                value.text.as_deref().unwrap_or_default(),
+                value.ip.as_deref().unwrap_or_default()
+            )),
+            EventType::UsersDisabledBulk => Some(format!(
+                "{} user(s) disabled from IP `{}`",
+                value.data.unwrap_or_default(),
+                value.ip.as_deref().unwrap_or_default()
+            )),
+            EventType::UserDisabled => Some(format!(
+                "User disabled: {}",
+                value.text.as_deref().unwrap_or_default()
+            )),
+            EventType::MaintenanceModeStarted | EventType::MaintenanceModeEnded => {
+                value.text.clone()
+            }
+            EventType::ExpiringDigest => value.text.clone(),
+            EventType::AuthProviderUnavailable | EventType::AuthProviderAvailable => {
+                value.text.clone()
+            }
+            EventType::ProviderSecretRotated => Some(format!(
+                "Secret rotated for {} from IP `{}`",
+                value.text.as_deref().unwrap_or_default(),
+                value.ip.as_deref().unwrap_or_default()
+            )),
+            EventType::PasskeyCounterAnomaly => Some(format!(
+                "Possibly cloned Passkey {} used from IP `{}`",
+                value.text.as_deref().unwrap_or_default(),
+                value.ip.as_deref().unwrap_or_default()
+            )),
+            EventType::BreakGlassLogin => Some(format!(
+                "{} from IP `{}`",
+                value.text.as_deref().unwrap_or_default(),
+                value.ip.as_deref().unwrap_or_default()
+            )),
+            EventType::UpstreamAdminRoleChange => value.text.clone(),
+            EventType::UpstreamDomainRejected => value.text.clone(),
+            EventType::UserProviderLink => Some(format!(
+                "{} from IP `{}`",
+                value.text.as_deref().unwrap_or_default(),
+                value.ip.as_deref().unwrap_or_default()
+            )),
+            EventType::AuthProviderMetadataUpdated => value.text.clone(),
+            EventType::UpstreamRefreshFailed => value.text.clone(),
         };
 
         Self {
@@ -759,6 +1061,22 @@ impl Event {
         )
     }
 
+    pub fn maintenance_summary(links_deleted: usize, links_expired_attempts: i64) -> Self {
+        Self::new(
+            RauthyConfig::get()
+                .vars
+                .events
+                .level_maintenance_summary
+                .clone(),
+            EventType::MaintenanceSummary,
+            None,
+            Some(links_expired_attempts),
+            Some(format!(
+                "Magic Links deleted: {links_deleted} / opened after expiry: {links_expired_attempts}"
+            )),
+        )
+    }
+
     pub fn new_login_location(user: &User, loc: &LoginLocation) -> Self {
         let text = if let Some(location) = &loc.location {
             format!("{} / {} / {}", user.email, loc.user_agent, location)
@@ -922,6 +1240,21 @@ impl Event {
         )
     }
 
+    /// Used for the single steps of `migrate_secrets()`. `text` should contain the step.
+    pub fn secrets_migration(text: String, ip: IpAddr) -> Self {
+        Self::new(
+            RauthyConfig::get()
+                .vars
+                .events
+                .level_secrets_migrated
+                .clone(),
+            EventType::SecretsMigrated,
+            Some(ip.to_string()),
+            None,
+            Some(text),
+        )
+    }
+
     pub fn suspicious_request(path: &str, ip: IpAddr, location: Option<String>) -> Self {
         let loc = location.as_deref().unwrap_or("Unknown Location");
         let text = format!("Suspicious request to '{path}' from {ip} ({loc})");
@@ -949,6 +1282,20 @@ impl Event {
         )
     }
 
+    pub fn token_groups_overflow(client_id: &str, user_id: &str, groups: usize) -> Self {
+        Self::new(
+            RauthyConfig::get()
+                .vars
+                .events
+                .level_token_groups_overflow
+                .clone(),
+            EventType::TokenGroupsOverflow,
+            None,
+            Some(groups as i64),
+            Some(format!("{client_id} / {user_id}")),
+        )
+    }
+
     pub fn token_issued(flow: &str, client_id: &str, email: Option<&str>) -> Self {
         Self::new(
             RauthyConfig::get().vars.events.level_token_issued.clone(),
@@ -978,6 +1325,306 @@ impl Event {
         )
     }
 
+    /// `pseudonym` must never contain any PII of the erased user.
+    pub fn user_erased(pseudonym: String, ip: IpAddr) -> Self {
+        Self::new(
+            RauthyConfig::get().vars.events.level_user_erased.clone(),
+            EventType::UserErased,
+            Some(ip.to_string()),
+            None,
+            Some(pseudonym),
+        )
+    }
+
+    pub fn passkey_imported(email: String, count: i64, ip: IpAddr) -> Self {
+        Self::new(
+            RauthyConfig::get()
+                .vars
+                .events
+                .level_passkey_imported
+                .clone(),
+            EventType::PasskeyImported,
+            Some(ip.to_string()),
+            Some(count),
+            Some(email),
+        )
+    }
+
+    pub fn user_registration_pending(email: String, ip: String) -> Self {
+        Self::new(
+            RauthyConfig::get()
+                .vars
+                .events
+                .level_user_registration_pending
+                .clone(),
+            EventType::UserRegistrationPending,
+            Some(ip),
+            None,
+            Some(email),
+        )
+    }
+
+    /// `text` should contain the provider and the admin who triggered the test.
+    pub fn provider_test_callback(text: String, ip: IpAddr) -> Self {
+        Self::new(
+            RauthyConfig::get()
+                .vars
+                .events
+                .level_provider_test_callback
+                .clone(),
+            EventType::ProviderTestCallback,
+            Some(ip.to_string()),
+            None,
+            Some(text),
+        )
+    }
+
+    /// `text` should contain a summary of all exported sections.
+    pub fn migration_export(text: String, ip: IpAddr) -> Self {
+        Self::new(
+            RauthyConfig::get()
+                .vars
+                .events
+                .level_migration_export
+                .clone(),
+            EventType::MigrationExport,
+            Some(ip.to_string()),
+            None,
+            Some(text),
+        )
+    }
+
+    /// `text` should contain a summary of all imported sections.
+    pub fn migration_import(text: String, ip: IpAddr) -> Self {
+        Self::new(
+            RauthyConfig::get()
+                .vars
+                .events
+                .level_migration_import
+                .clone(),
+            EventType::MigrationImport,
+            Some(ip.to_string()),
+            None,
+            Some(text),
+        )
+    }
+
+    /// `text` should contain the client and user id of the revoked refresh token.
+    pub fn refresh_token_binding_mismatch(client_id: &str, user_id: &str, ip: IpAddr) -> Self {
+        Self::new(
+            RauthyConfig::get()
+                .vars
+                .events
+                .level_refresh_token_binding_mismatch
+                .clone(),
+            EventType::RefreshTokenBindingMismatch,
+            Some(ip.to_string()),
+            None,
+            Some(format!("{client_id} / {user_id}")),
+        )
+    }
+
+    /// Summary for `User::disable_bulk()`. `text` should describe the initiator.
+    pub fn users_disabled_bulk(count: usize, text: String, ip: IpAddr) -> Self {
+        Self::new(
+            RauthyConfig::get()
+                .vars
+                .events
+                .level_users_disabled_bulk
+                .clone(),
+            EventType::UsersDisabledBulk,
+            Some(ip.to_string()),
+            Some(count as i64),
+            Some(text),
+        )
+    }
+
+    /// Per-user audit entry after a bulk disable. These are always `Info` and are meant to be
+    /// written via `Event::insert()` directly, without going through the notifiers.
+    pub fn user_disabled(user_id: &str) -> Self {
+        Self::new(
+            EventLevel::Info,
+            EventType::UserDisabled,
+            None,
+            None,
+            Some(user_id.to_string()),
+        )
+    }
+
+    /// `text` should contain the planned end of the maintenance, if there is any.
+    pub fn maintenance_mode_started(text: String) -> Self {
+        Self::new(
+            RauthyConfig::get()
+                .vars
+                .events
+                .level_maintenance_mode
+                .clone(),
+            EventType::MaintenanceModeStarted,
+            None,
+            None,
+            Some(text),
+        )
+    }
+
+    /// `text` should describe, if the maintenance has been ended manually or automatically.
+    pub fn maintenance_mode_ended(text: String) -> Self {
+        Self::new(
+            RauthyConfig::get()
+                .vars
+                .events
+                .level_maintenance_mode
+                .clone(),
+            EventType::MaintenanceModeEnded,
+            None,
+            None,
+            Some(text),
+        )
+    }
+
+    /// `text` should contain the summary of the `/reports/expiring` report and `count` the
+    /// total amount of entries in it.
+    pub fn expiring_digest(count: i64, text: String) -> Self {
+        Self::new(
+            RauthyConfig::get()
+                .vars
+                .events
+                .level_expiring_digest
+                .clone(),
+            EventType::ExpiringDigest,
+            None,
+            Some(count),
+            Some(text),
+        )
+    }
+
+    /// `text` should contain the name of the provider and the endpoints, that have changed.
+    pub fn auth_provider_metadata_updated(text: String) -> Self {
+        Self::new(
+            EventLevel::Notice,
+            EventType::AuthProviderMetadataUpdated,
+            None,
+            None,
+            Some(text),
+        )
+    }
+
+    /// `text` should contain the user, the name of the provider and the reason.
+    pub fn upstream_refresh_failed(text: String) -> Self {
+        Self::new(
+            EventLevel::Warning,
+            EventType::UpstreamRefreshFailed,
+            None,
+            None,
+            Some(text),
+        )
+    }
+
+    /// `text` should contain the name of the provider and since when it is unreachable.
+    pub fn auth_provider_unavailable(text: String) -> Self {
+        Self::new(
+            RauthyConfig::get()
+                .vars
+                .events
+                .level_auth_provider_health
+                .clone(),
+            EventType::AuthProviderUnavailable,
+            None,
+            None,
+            Some(text),
+        )
+    }
+
+    pub fn auth_provider_available(text: String) -> Self {
+        Self::new(
+            RauthyConfig::get()
+                .vars
+                .events
+                .level_auth_provider_health
+                .clone(),
+            EventType::AuthProviderAvailable,
+            None,
+            None,
+            Some(text),
+        )
+    }
+
+    /// `text` should contain the provider, the probe result and the admin who rotated the secret.
+    pub fn provider_secret_rotated(text: String, ip: IpAddr) -> Self {
+        Self::new(
+            RauthyConfig::get()
+                .vars
+                .events
+                .level_provider_secret_rotated
+                .clone(),
+            EventType::ProviderSecretRotated,
+            Some(ip.to_string()),
+            None,
+            Some(text),
+        )
+    }
+
+    /// `text` should contain the passkey name and the user.
+    pub fn passkey_counter_anomaly(text: String, ip: IpAddr) -> Self {
+        Self::new(
+            RauthyConfig::get()
+                .vars
+                .events
+                .level_passkey_counter_anomaly
+                .clone(),
+            EventType::PasskeyCounterAnomaly,
+            Some(ip.to_string()),
+            None,
+            Some(text),
+        )
+    }
+
+    /// `text` should contain the user and the outcome of the login.
+    pub fn break_glass_login(text: String, ip: IpAddr) -> Self {
+        Self::new(
+            RauthyConfig::get()
+                .vars
+                .events
+                .level_break_glass_login
+                .clone(),
+            EventType::BreakGlassLogin,
+            Some(ip.to_string()),
+            None,
+            Some(text),
+        )
+    }
+
+    /// Always `Critical`, because it grants or revokes full admin access without any manual
+    /// interaction.
+    pub fn upstream_admin_role_change(text: String) -> Self {
+        Self::new(
+            EventLevel::Critical,
+            EventType::UpstreamAdminRoleChange,
+            None,
+            None,
+            Some(text),
+        )
+    }
+
+    pub fn upstream_domain_rejected(text: String) -> Self {
+        Self::new(
+            EventLevel::Warning,
+            EventType::UpstreamDomainRejected,
+            None,
+            None,
+            Some(text),
+        )
+    }
+
+    pub fn user_provider_link(text: String, ip: IpAddr) -> Self {
+        Self::new(
+            EventLevel::Notice,
+            EventType::UserProviderLink,
+            Some(ip.to_string()),
+            None,
+            Some(text),
+        )
+    }
+
     pub fn user_password_reset(text: String, ip: Option<String>) -> Self {
         Self::new(
             RauthyConfig::get()
@@ -1023,7 +1670,7 @@ impl Event {
             EventType::RauthyStarted => self.text.clone().unwrap(),
             EventType::RauthyHealthy => self.text.clone().unwrap(),
             EventType::RauthyUnhealthy => self.text.clone().unwrap(),
-            EventType::SecretsMigrated => String::default(),
+            EventType::SecretsMigrated => self.text.clone().unwrap_or_default(),
             EventType::UserEmailChange => {
                 format!("User E-Mail: {}", self.text.as_deref().unwrap_or_default())
             }
@@ -1061,6 +1708,107 @@ impl Event {
             EventType::LoginNewLocation => self.text.clone().unwrap_or_default(),
             EventType::TokenIssued => self.text.clone().unwrap_or_default(),
             EventType::EmailSendError => self.text.clone().unwrap_or_default(),
+            EventType::TokenGroupsOverflow => {
+                format!(
+                    "Groups limit exceeded ({}) for: {}",
+                    self.data.unwrap_or_default(),
+                    self.text.as_deref().unwrap_or_default()
+                )
+            }
+            EventType::MaintenanceSummary => self.text.clone().unwrap_or_default(),
+            EventType::UserErased => {
+                format!(
+                    "User `{}` has been erased",
+                    self.text.as_deref().unwrap_or_default()
+                )
+            }
+            EventType::PasskeyImported => {
+                format!(
+                    "{} Passkey(s) imported for user `{}`",
+                    self.data.unwrap_or_default(),
+                    self.text.as_deref().unwrap_or_default()
+                )
+            }
+            EventType::UserRegistrationPending => {
+                format!(
+                    "User `{}` awaits registration approval",
+                    self.text.as_deref().unwrap_or_default()
+                )
+            }
+            EventType::ProviderTestCallback => {
+                format!(
+                    "Test callback for {}",
+                    self.text.as_deref().unwrap_or_default()
+                )
+            }
+            EventType::MigrationExport => {
+                format!(
+                    "Migration archive exported: {}",
+                    self.text.as_deref().unwrap_or_default()
+                )
+            }
+            EventType::MigrationImport => {
+                format!(
+                    "Migration archive imported: {}",
+                    self.text.as_deref().unwrap_or_default()
+                )
+            }
+            EventType::RefreshTokenBindingMismatch => {
+                format!(
+                    "Refresh token binding mismatch for: {}",
+                    self.text.as_deref().unwrap_or_default()
+                )
+            }
+            EventType::UsersDisabledBulk => {
+                format!(
+                    "{} user(s) disabled in bulk: {}",
+                    self.data.unwrap_or_default(),
+                    self.text.as_deref().unwrap_or_default()
+                )
+            }
+            EventType::UserDisabled => {
+                format!(
+                    "User disabled: {}",
+                    self.text.as_deref().unwrap_or_default()
+                )
+            }
+            EventType::MaintenanceModeStarted | EventType::MaintenanceModeEnded => {
+                self.text.clone().unwrap_or_default()
+            }
+            EventType::ExpiringDigest => {
+                format!(
+                    "{} item(s) expiring soon: {}",
+                    self.data.unwrap_or_default(),
+                    self.text.as_deref().unwrap_or_default()
+                )
+            }
+            EventType::AuthProviderUnavailable | EventType::AuthProviderAvailable => {
+                self.text.clone().unwrap_or_default()
+            }
+            EventType::ProviderSecretRotated => {
+                format!(
+                    "Secret rotated for {}",
+                    self.text.as_deref().unwrap_or_default()
+                )
+            }
+            EventType::PasskeyCounterAnomaly => {
+                format!(
+                    "Sign counter anomaly for Passkey {}",
+                    self.text.as_deref().unwrap_or_default()
+                )
+            }
+            EventType::BreakGlassLogin => self.text.clone().unwrap_or_default(),
+            EventType::UpstreamAdminRoleChange => self.text.clone().unwrap_or_default(),
+            EventType::UpstreamDomainRejected => self.text.clone().unwrap_or_default(),
+            EventType::UserProviderLink => {
+                format!(
+                    "{} from IP `{}`",
+                    self.text.as_deref().unwrap_or_default(),
+                    self.ip.as_deref().unwrap_or_default()
+                )
+            }
+            EventType::AuthProviderMetadataUpdated => self.text.clone().unwrap_or_default(),
+            EventType::UpstreamRefreshFailed => self.text.clone().unwrap_or_default(),
         }
     }
 
@@ -1092,3 +1840,39 @@ impl From<Event> for EventResponse {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_user_provider_link() {
+        let ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1));
+        let linked = Event::user_provider_link(
+            "`admin@localhost` linked to auth provider `Test`".to_string(),
+            ip,
+        );
+        assert_eq!(linked.level, EventLevel::Notice);
+        assert_eq!(linked.typ, EventType::UserProviderLink);
+        assert_eq!(linked.ip.as_deref(), Some("192.168.1.1"));
+        assert_eq!(
+            linked.fmt_data(),
+            "`admin@localhost` linked to auth provider `Test` from IP `192.168.1.1`"
+        );
+
+        let unlinked = Event::user_provider_link(
+            "`admin@localhost` unlinked from its upstream auth provider".to_string(),
+            ip,
+        );
+        assert_eq!(unlinked.typ, EventType::UserProviderLink);
+        assert_eq!(
+            unlinked.fmt_data(),
+            "`admin@localhost` unlinked from its upstream auth provider from IP `192.168.1.1`"
+        );
+
+        let typ = EventType::UserProviderLink;
+        assert_eq!(EventType::from(typ.as_str().to_string()), typ);
+        assert_eq!(EventType::from(typ.value() as i64), typ);
+    }
+}
@@ -4,6 +4,7 @@ use crate::events::notifier::EventNotifier;
 use crate::rauthy_config::RauthyConfig;
 use actix_web_lab::sse;
 use rauthy_common::constants::EVENTS_LATEST_LIMIT;
+use rauthy_common::shutdown::TaskGuard;
 use rauthy_error::ErrorResponse;
 use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use std::time::Duration;
@@ -45,6 +46,8 @@ impl EventListener {
 
     #[tracing::instrument(level = "debug", skip_all)]
     async fn handle_event(event: Event) {
+        let _guard = TaskGuard::new();
+
         // insert into DB
         if event.level.value() >= RauthyConfig::get().vars.events.persist_level.value() {
             while let Err(err) = event.insert().await {
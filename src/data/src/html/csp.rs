@@ -0,0 +1,363 @@
+use crate::rauthy_config::{RauthyConfig, VarsServer};
+use actix_web::HttpMessage;
+use rauthy_common::utils::{base64_url_no_pad_encode, get_rand_bytes};
+use std::sync::LazyLock;
+
+static SECURITY_HEADERS: LazyLock<SecurityHeaders> =
+    LazyLock::new(|| SecurityHeaders::from_config(&RauthyConfig::get().vars.server));
+
+/// These may be embedded by other origins: the FedCM endpoints and page, and the logout, which
+/// clients may load inside a hidden iframe.
+const FRAMEABLE_PATHS: [&str; 3] = ["/auth/v1/fed_cm/", "/auth/v1/fedcm", "/auth/v1/oidc/logout"];
+
+/// Directives, which cannot be appended via `server.csp_directives`. Any `script-src` in the
+/// header would block the inline scripts of the SvelteKit UI, and `default-src` would do the
+/// same as its fallback. Framing is configured via `server.frame_ancestors`.
+const DIRECTIVES_FORBIDDEN: [&str; 5] = [
+    "default-src",
+    "script-src",
+    "script-src-attr",
+    "script-src-elem",
+    "frame-ancestors",
+];
+
+const REFERRER_POLICIES: [&str; 8] = [
+    "no-referrer",
+    "no-referrer-when-downgrade",
+    "origin",
+    "origin-when-cross-origin",
+    "same-origin",
+    "strict-origin",
+    "strict-origin-when-cross-origin",
+    "unsafe-url",
+];
+
+/// A `Content-Security-Policy` with its directives in insertion order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CspPolicy(Vec<(String, Vec<String>)>);
+
+impl CspPolicy {
+    /// The strict default for all responses.
+    ///
+    /// The SvelteKit UI ships its own policy with hashes for its inline scripts inside a `<meta>`
+    /// tag. A `script-src` directive must therefore only be added for pages rendered by Rauthy
+    /// itself, which use a [CspNonce]. The `<meta>` policy contains nothing else, so this is the
+    /// only one that restricts all other fetches, including the config extensions. Without a
+    /// `default-src`, the directives it would cover must be listed explicitly.
+    fn strict() -> Self {
+        let mut slf = Self(Vec::with_capacity(10));
+        slf.add("connect-src", ["'self'"]);
+        slf.add("img-src", ["'self'"]);
+        slf.add("style-src", ["'self'", "'unsafe-inline'"]);
+        slf.add("font-src", ["'self'"]);
+        slf.add("media-src", ["'none'"]);
+        slf.add("frame-src", ["'none'"]);
+        slf.add("manifest-src", ["'none'"]);
+        slf.add("base-uri", ["'self'"]);
+        slf.add("object-src", ["'none'"]);
+        slf.add("frame-ancestors", ["'none'"]);
+        slf
+    }
+
+    /// Adds the sources to an already existing directive, or appends a new one.
+    fn add<I, S>(&mut self, name: &str, sources: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let idx = match self.0.iter().position(|(n, _)| n == name) {
+            Some(idx) => idx,
+            None => {
+                self.0.push((name.to_string(), Vec::with_capacity(2)));
+                self.0.len() - 1
+            }
+        };
+        let existing = &mut self.0[idx].1;
+        for source in sources {
+            let source = source.into();
+            if !existing.contains(&source) {
+                existing.push(source);
+            }
+        }
+    }
+
+    fn set(&mut self, name: &str, sources: Vec<String>) {
+        match self.0.iter_mut().find(|(n, _)| n == name) {
+            Some((_, existing)) => *existing = sources,
+            None => self.0.push((name.to_string(), sources)),
+        }
+    }
+
+    fn render(&self, nonce: Option<&CspNonce>) -> String {
+        let mut csp = String::with_capacity(256);
+        for (name, sources) in &self.0 {
+            if !csp.is_empty() {
+                csp.push(' ');
+            }
+            csp.push_str(name);
+            for source in sources {
+                csp.push(' ');
+                csp.push_str(source);
+            }
+            csp.push(';');
+        }
+        if let Some(nonce) = nonce {
+            csp.push_str(" script-src 'nonce-");
+            csp.push_str(&nonce.0);
+            csp.push_str("';");
+        }
+        csp
+    }
+}
+
+/// Parses a single CSP directive like `font-src https://fonts.example.com`, which should be
+/// appended to the default policy.
+pub fn parse_csp_directive(directive: &str) -> Result<(String, Vec<String>), String> {
+    let mut parts = directive.split_whitespace();
+    let name = parts
+        .next()
+        .ok_or_else(|| "a directive must not be empty".to_string())?
+        .to_ascii_lowercase();
+    if !name.bytes().all(|b| b.is_ascii_lowercase() || b == b'-') {
+        return Err(format!("invalid directive name: {name}"));
+    }
+    if DIRECTIVES_FORBIDDEN.contains(&name.as_str()) {
+        return Err(format!("`{name}` cannot be changed"));
+    }
+
+    let sources = parts
+        .map(|source| validate_csp_source(source).map(String::from))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((name, sources))
+}
+
+/// A source must not be able to break out of its directive.
+pub fn validate_csp_source(source: &str) -> Result<&str, String> {
+    if source.is_empty()
+        || source
+            .bytes()
+            .any(|b| !b.is_ascii_graphic() || b == b';' || b == b',')
+    {
+        Err(format!("invalid CSP source: {source}"))
+    } else {
+        Ok(source)
+    }
+}
+
+pub fn validate_referrer_policy(policy: &str) -> Result<(), String> {
+    if REFERRER_POLICIES.contains(&policy) {
+        Ok(())
+    } else {
+        Err(format!(
+            "invalid Referrer-Policy `{policy}`, expected one of: {}",
+            REFERRER_POLICIES.join(", ")
+        ))
+    }
+}
+
+/// The security headers for all responses, built once from the config.
+///
+/// Handlers may set their own `Content-Security-Policy`, which will be kept, unless they have
+/// rendered inline scripts with a [CspNonce].
+#[derive(Debug)]
+pub struct SecurityHeaders {
+    csp: CspPolicy,
+    csp_frameable: CspPolicy,
+    pub referrer_policy: String,
+}
+
+impl SecurityHeaders {
+    #[inline]
+    pub fn get() -> &'static Self {
+        &SECURITY_HEADERS
+    }
+
+    /// The config values have been validated by the config check during startup.
+    fn from_config(server: &VarsServer) -> Self {
+        let mut csp = CspPolicy::strict();
+        csp.add("img-src", server.csp_img_src.iter().map(String::as_str));
+        csp.add("style-src", server.csp_style_src.iter().map(String::as_str));
+        for directive in &server.csp_directives {
+            let (name, sources) =
+                parse_csp_directive(directive).expect("`server.csp_directives` to be validated");
+            csp.add(&name, sources);
+        }
+        if let Some(uri) = &server.csp_report_uri {
+            csp.set("report-uri", vec![uri.clone()]);
+        }
+
+        let mut csp_frameable = csp.clone();
+        let mut ancestors = Vec::with_capacity(server.frame_ancestors.len() + 1);
+        ancestors.push("'self'".to_string());
+        ancestors.extend(server.frame_ancestors.iter().cloned());
+        csp_frameable.set("frame-ancestors", ancestors);
+
+        Self {
+            csp,
+            csp_frameable,
+            referrer_policy: server.referrer_policy.clone(),
+        }
+    }
+
+    /// `true` if the response for this path may be embedded by other origins.
+    #[inline]
+    pub fn is_frameable(path: &str) -> bool {
+        FRAMEABLE_PATHS.iter().any(|p| path.starts_with(p))
+    }
+
+    /// Builds the `Content-Security-Policy` header value for a response to the given path.
+    pub fn csp(&self, path: &str, nonce: Option<&CspNonce>) -> String {
+        if Self::is_frameable(path) {
+            self.csp_frameable.render(nonce)
+        } else {
+            self.csp.render(nonce)
+        }
+    }
+}
+
+/// A random, per-request nonce for inline `<script>`s in server side rendered HTML.
+///
+/// It is created lazily and stored in the request `Extensions`. The
+/// `RauthySecurityHeadersMiddleware` will add a matching `script-src` directive to the
+/// `Content-Security-Policy` header, if a nonce exists for the request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CspNonce(String);
+
+impl CspNonce {
+    /// Returns the nonce for this request and creates it, if it does not exist yet.
+    pub fn from_req(req: &impl HttpMessage) -> Self {
+        if let Some(slf) = Self::try_from_req(req) {
+            return slf;
+        }
+
+        let slf = Self(base64_url_no_pad_encode(&get_rand_bytes::<16>()));
+        req.extensions_mut().insert(slf.clone());
+        slf
+    }
+
+    /// Returns the nonce for this request, if one has been created.
+    pub fn try_from_req(req: &impl HttpMessage) -> Option<Self> {
+        req.extensions().get::<Self>().cloned()
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rauthy_config::Vars;
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn test_csp_nonce() {
+        let req = TestRequest::default().to_http_request();
+        assert_eq!(CspNonce::try_from_req(&req), None);
+
+        let nonce = CspNonce::from_req(&req);
+        // 128 bit -> 22 characters without padding
+        assert_eq!(nonce.as_str().len(), 22);
+        assert_eq!(CspNonce::try_from_req(&req), Some(nonce.clone()));
+        assert_eq!(CspNonce::from_req(&req), nonce);
+
+        let other = CspNonce::from_req(&TestRequest::default().to_http_request());
+        assert_ne!(nonce, other);
+    }
+
+    fn server_vars() -> VarsServer {
+        Vars::default().server
+    }
+
+    #[test]
+    fn test_csp_default() {
+        let headers = SecurityHeaders::from_config(&server_vars());
+        let strict = "connect-src 'self'; img-src 'self'; style-src 'self' 'unsafe-inline'; \
+            font-src 'self'; media-src 'none'; frame-src 'none'; manifest-src 'none'; \
+            base-uri 'self'; object-src 'none';";
+
+        assert_eq!(
+            headers.csp("/auth/v1/oidc/authorize", None),
+            format!("{strict} frame-ancestors 'none';")
+        );
+        assert_eq!(
+            headers.csp("/auth/v1/oidc/logout", None),
+            format!("{strict} frame-ancestors 'self';")
+        );
+
+        let nonce = CspNonce("abc".to_string());
+        assert_eq!(
+            headers.csp("/auth/v1/users/register", Some(&nonce)),
+            format!("{strict} frame-ancestors 'none'; script-src 'nonce-abc';")
+        );
+        assert_eq!(headers.referrer_policy, "no-referrer");
+    }
+
+    #[test]
+    fn test_csp_from_config() {
+        let mut server = server_vars();
+        server.csp_img_src = vec!["https://logos.example.com".to_string()];
+        server.csp_style_src = vec!["https://themes.example.com".to_string()];
+        server.csp_directives = vec![
+            "font-src https://fonts.example.com".to_string(),
+            "img-src data:".to_string(),
+            "form-action 'self'".to_string(),
+        ];
+        server.frame_ancestors = vec!["https://app.example.com".to_string()];
+        server.csp_report_uri = Some("https://csp.example.com/report".to_string());
+        let headers = SecurityHeaders::from_config(&server);
+
+        let csp = headers.csp("/auth/v1/account", None);
+        assert_eq!(
+            csp,
+            "connect-src 'self'; img-src 'self' https://logos.example.com data:; \
+            style-src 'self' 'unsafe-inline' https://themes.example.com; \
+            font-src 'self' https://fonts.example.com; media-src 'none'; frame-src 'none'; \
+            manifest-src 'none'; base-uri 'self'; object-src 'none'; frame-ancestors 'none'; form-action 'self'; \
+            report-uri https://csp.example.com/report;"
+        );
+
+        let csp = headers.csp("/auth/v1/fed_cm/accounts", None);
+        assert!(csp.contains(" frame-ancestors 'self' https://app.example.com;"));
+        assert!(!csp.contains("'none'; form-action"));
+    }
+
+    #[test]
+    fn test_frameable() {
+        assert!(SecurityHeaders::is_frameable("/auth/v1/fedcm"));
+        assert!(SecurityHeaders::is_frameable("/auth/v1/fed_cm/status"));
+        assert!(SecurityHeaders::is_frameable("/auth/v1/oidc/logout"));
+
+        assert!(!SecurityHeaders::is_frameable("/auth/v1/oidc/authorize"));
+        assert!(!SecurityHeaders::is_frameable("/auth/v1/account"));
+        assert!(!SecurityHeaders::is_frameable("/auth/v1/admin"));
+    }
+
+    #[test]
+    fn test_parse_csp_directive() {
+        assert_eq!(
+            parse_csp_directive("Font-Src https://a.example.com 'self'").unwrap(),
+            (
+                "font-src".to_string(),
+                vec!["https://a.example.com".to_string(), "'self'".to_string()]
+            )
+        );
+
+        assert!(parse_csp_directive("").is_err());
+        assert!(parse_csp_directive("script-src https://evil.example.com").is_err());
+        assert!(parse_csp_directive("default-src *").is_err());
+        assert!(parse_csp_directive("frame-ancestors *").is_err());
+        assert!(parse_csp_directive("img-src a;script-src *").is_err());
+        assert!(parse_csp_directive("img_src a").is_err());
+    }
+
+    #[test]
+    fn test_referrer_policy() {
+        assert!(validate_referrer_policy("no-referrer").is_ok());
+        assert!(validate_referrer_policy("strict-origin-when-cross-origin").is_ok());
+        assert!(validate_referrer_policy("none").is_err());
+        assert!(validate_referrer_policy("no-referrer\r\nx: y").is_err());
+    }
+}
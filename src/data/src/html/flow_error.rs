@@ -0,0 +1,318 @@
+use crate::entity::theme::ThemeCssFull;
+use crate::html::i18n::flow_error::I18nFlowError;
+use crate::html::templates::FlowErrorHtml;
+use crate::language::Language;
+use actix_web::http::header::{ACCEPT, RETRY_AFTER};
+use actix_web::{HttpRequest, HttpResponse, HttpResponseBuilder, ResponseError};
+use rauthy_common::constants::HEADER_HTML;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+
+/// Browser facing flows, which render an HTML error page instead of a raw JSON body.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorFlow {
+    Authorize,
+    DeviceVerification,
+    Logout,
+    MagicLink,
+    ProviderCallback,
+}
+
+impl ErrorFlow {
+    /// The link for the action button. This must never be built from any request input, so it
+    /// can't be abused as an open redirect.
+    fn continue_uri(&self) -> &'static str {
+        match self {
+            Self::Authorize | Self::Logout => "/auth/v1/",
+            Self::DeviceVerification => "/auth/v1/device",
+            Self::MagicLink | Self::ProviderCallback => "/auth/v1/account",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlowErrorKind {
+    LinkExpired,
+    SessionExpired,
+    InvalidRequest,
+    Forbidden,
+    Maintenance,
+    Generic,
+}
+
+impl FlowErrorKind {
+    pub fn new(flow: ErrorFlow, err: &ErrorResponse) -> Self {
+        let is_client_err = err.status_code().is_client_error();
+
+        match (flow, &err.error) {
+            (_, ErrorResponseType::ServiceUnavailable(_)) => Self::Maintenance,
+            // Magic Links can only fail for a user, if they are expired, used or invalid.
+            (ErrorFlow::MagicLink, _) if is_client_err => Self::LinkExpired,
+            // Callbacks and device codes live in the cache and are only gone after their TTL.
+            (
+                ErrorFlow::DeviceVerification | ErrorFlow::ProviderCallback,
+                ErrorResponseType::NotFound,
+            ) => Self::SessionExpired,
+            (
+                _,
+                ErrorResponseType::SessionExpired
+                | ErrorResponseType::SessionTimeout
+                | ErrorResponseType::NoSession,
+            ) => Self::SessionExpired,
+            (
+                _,
+                ErrorResponseType::Forbidden
+                | ErrorResponseType::Unauthorized
+                | ErrorResponseType::Disabled
+                | ErrorResponseType::CSRFTokenError,
+            ) => Self::Forbidden,
+            _ if is_client_err => Self::InvalidRequest,
+            _ => Self::Generic,
+        }
+    }
+}
+
+/// Returns `true` if the request is a top-level browser navigation, which should receive an
+/// HTML page. `fetch()` / XHR requests and API clients keep receiving JSON.
+///
+/// `Sec-Fetch-*` headers are preferred, because browsers send `Accept: text/html` only for
+/// navigations, but the header can be set freely by any other client.
+pub fn prefers_html(req: &HttpRequest) -> bool {
+    let headers = req.headers();
+
+    if let Some(mode) = headers.get("sec-fetch-mode") {
+        return mode.as_bytes() == b"navigate";
+    }
+    if let Some(dest) = headers.get("sec-fetch-dest") {
+        return matches!(dest.as_bytes(), b"document" | b"iframe");
+    }
+
+    headers
+        .get(ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .map(|accept| {
+            accept
+                .split(',')
+                .any(|typ| typ.trim().starts_with("text/html"))
+        })
+        .unwrap_or(false)
+}
+
+/// Converts an error during a browser facing `flow` into a response. Browser navigations get a
+/// localized and themed HTML page with an actionable message, everything else the default
+/// `ErrorResponse` JSON.
+pub async fn flow_error_response(
+    req: &HttpRequest,
+    err: ErrorResponse,
+    flow: ErrorFlow,
+) -> HttpResponse {
+    flow_error_response_with_retry(req, err, flow, flow.continue_uri()).await
+}
+
+/// Same as [flow_error_response], but the action button points to the given `retry_uri`. It
+/// must only ever be built from values Rauthy has created itself, like an encrypted cookie, and
+/// never from plain request input.
+pub async fn flow_error_response_with_retry(
+    req: &HttpRequest,
+    err: ErrorResponse,
+    flow: ErrorFlow,
+    retry_uri: &str,
+) -> HttpResponse {
+    if !prefers_html(req)
+        || matches!(
+            err.error,
+            ErrorResponseType::Blocked | ErrorResponseType::TooManyRequests(_)
+        )
+    {
+        // these types already render HTML themselves
+        return err.error_response();
+    }
+
+    let status = err.status_code();
+    let lang = Language::try_from(req).unwrap_or_default();
+    let theme_ts = ThemeCssFull::find_theme_ts_rauthy()
+        .await
+        .unwrap_or_default();
+    let body = flow_error_html(&lang, theme_ts, flow, &err, retry_uri);
+
+    let mut builder = HttpResponseBuilder::new(status);
+    if let ErrorResponseType::ServiceUnavailable(retry_after) = err.error
+        && retry_after > 0
+    {
+        builder.insert_header((RETRY_AFTER, retry_after));
+    }
+    builder.insert_header(HEADER_HTML).body(body)
+}
+
+fn flow_error_html(
+    lang: &Language,
+    theme_ts: i64,
+    flow: ErrorFlow,
+    err: &ErrorResponse,
+    continue_uri: &str,
+) -> String {
+    let i18n = I18nFlowError::build(lang);
+    let (title, text, action) = match FlowErrorKind::new(flow, err) {
+        FlowErrorKind::LinkExpired => (
+            i18n.link_expired_title,
+            i18n.link_expired_text,
+            i18n.request_link_label,
+        ),
+        FlowErrorKind::SessionExpired => (
+            i18n.session_expired_title,
+            i18n.session_expired_text,
+            i18n.retry_label,
+        ),
+        FlowErrorKind::InvalidRequest => (
+            i18n.invalid_request_title,
+            i18n.invalid_request_text,
+            i18n.continue_label,
+        ),
+        FlowErrorKind::Forbidden => (
+            i18n.forbidden_title,
+            i18n.forbidden_text,
+            i18n.continue_label,
+        ),
+        FlowErrorKind::Maintenance => (
+            i18n.maintenance_title,
+            i18n.maintenance_text,
+            i18n.retry_label,
+        ),
+        FlowErrorKind::Generic => (i18n.generic_title, i18n.generic_text, i18n.retry_label),
+    };
+
+    // only the plain variant without any possibly sensitive inner data
+    let error = format!("{:?}", err.error);
+    let error = error.split('(').next().unwrap_or_default();
+
+    FlowErrorHtml {
+        lang: lang.as_str(),
+        client_id: "rauthy",
+        theme_ts,
+        title,
+        text,
+        action,
+        continue_uri,
+        status: err.status_code().as_u16(),
+        error,
+        details: &err.message,
+    }
+    .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn test_prefers_html() {
+        // browser navigation
+        let req = TestRequest::default()
+            .insert_header((ACCEPT, "text/html,application/xhtml+xml,*/*;q=0.8"))
+            .insert_header(("sec-fetch-mode", "navigate"))
+            .insert_header(("sec-fetch-dest", "document"))
+            .to_http_request();
+        assert!(prefers_html(&req));
+
+        // fetch() from the UI must keep JSON, even if it accepts anything
+        let req = TestRequest::default()
+            .insert_header((ACCEPT, "text/html,*/*"))
+            .insert_header(("sec-fetch-mode", "cors"))
+            .insert_header(("sec-fetch-dest", "empty"))
+            .to_http_request();
+        assert!(!prefers_html(&req));
+
+        // older browsers without Sec-Fetch-*
+        let req = TestRequest::default()
+            .insert_header((ACCEPT, "text/html,application/xhtml+xml"))
+            .to_http_request();
+        assert!(prefers_html(&req));
+
+        // API clients
+        let req = TestRequest::default()
+            .insert_header((ACCEPT, "application/json"))
+            .to_http_request();
+        assert!(!prefers_html(&req));
+        let req = TestRequest::default()
+            .insert_header((ACCEPT, "*/*"))
+            .to_http_request();
+        assert!(!prefers_html(&req));
+        let req = TestRequest::default().to_http_request();
+        assert!(!prefers_html(&req));
+    }
+
+    #[test]
+    fn test_flow_error_kind() {
+        let err = ErrorResponse::new(ErrorResponseType::BadRequest, "This link has expired");
+        assert_eq!(
+            FlowErrorKind::new(ErrorFlow::MagicLink, &err),
+            FlowErrorKind::LinkExpired
+        );
+        assert_eq!(
+            FlowErrorKind::new(ErrorFlow::Authorize, &err),
+            FlowErrorKind::InvalidRequest
+        );
+
+        let err = ErrorResponse::new(ErrorResponseType::NotFound, "Callback Code not found");
+        assert_eq!(
+            FlowErrorKind::new(ErrorFlow::ProviderCallback, &err),
+            FlowErrorKind::SessionExpired
+        );
+
+        let err = ErrorResponse::new(ErrorResponseType::Forbidden, "");
+        assert_eq!(
+            FlowErrorKind::new(ErrorFlow::Logout, &err),
+            FlowErrorKind::Forbidden
+        );
+
+        let err = ErrorResponse::new(ErrorResponseType::Database, "");
+        assert_eq!(
+            FlowErrorKind::new(ErrorFlow::MagicLink, &err),
+            FlowErrorKind::Generic
+        );
+
+        let err = ErrorResponse::new(ErrorResponseType::ServiceUnavailable(60), "");
+        assert_eq!(
+            FlowErrorKind::new(ErrorFlow::Authorize, &err),
+            FlowErrorKind::Maintenance
+        );
+    }
+
+    #[test]
+    fn test_flow_error_html() {
+        let err = ErrorResponse::new(
+            ErrorResponseType::WWWAuthenticate("secret".to_string()),
+            "<script>",
+        );
+        let flow = ErrorFlow::ProviderCallback;
+        let html = flow_error_html(&Language::De, 1, flow, &err, flow.continue_uri());
+        assert!(html.contains(r#"<html lang="de">"#));
+        assert!(html.contains("Ungültige Anfrage"));
+        assert!(html.contains(r#"href="/auth/v1/account""#));
+        assert!(html.contains("401 - WWWAuthenticate"));
+        assert!(!html.contains("secret"));
+        assert!(!html.contains("<script>"));
+
+        let err = ErrorResponse::new(ErrorResponseType::NotFound, "");
+        for lang in Language::BUILT_IN {
+            let i18n = I18nFlowError::build(&lang);
+            let flow = ErrorFlow::MagicLink;
+            let html = flow_error_html(&lang, 1, flow, &err, flow.continue_uri());
+            assert!(html.contains(i18n.link_expired_title));
+            assert!(html.contains(i18n.request_link_label));
+        }
+
+        // a retry after an upstream callback timeout
+        let html = flow_error_html(
+            &Language::En,
+            1,
+            ErrorFlow::ProviderCallback,
+            &err,
+            "https://iam.example.com/auth/v1/oidc/authorize?client_id=app&idp_hint=abc",
+        );
+        assert!(html.contains(
+            r#"href="https://iam.example.com/auth/v1/oidc/authorize?client_id=app&#38;idp_hint=abc""#
+        ));
+        assert!(html.contains(I18nFlowError::build(&Language::En).retry_label));
+    }
+}
@@ -0,0 +1,278 @@
+use crate::language::Language;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct I18nFlowError<'a> {
+    pub link_expired_title: &'a str,
+    pub link_expired_text: &'a str,
+    pub session_expired_title: &'a str,
+    pub session_expired_text: &'a str,
+    pub invalid_request_title: &'a str,
+    pub invalid_request_text: &'a str,
+    pub forbidden_title: &'a str,
+    pub forbidden_text: &'a str,
+    pub generic_title: &'a str,
+    pub generic_text: &'a str,
+    pub continue_label: &'a str,
+    pub retry_label: &'a str,
+    pub request_link_label: &'a str,
+    pub maintenance_title: &'a str,
+    pub maintenance_text: &'a str,
+}
+
+impl I18nFlowError<'_> {
+    pub fn build(lang: &Language) -> Self {
+        match *lang {
+            Language::De => Self::build_de(),
+            Language::En => Self::build_en(),
+            Language::Fr => Self::build_fr(),
+            Language::Ko => Self::build_ko(),
+            Language::Nb => Self::build_nb(),
+            Language::Nl => Self::build_nl(),
+            Language::Ru => Self::build_ru(),
+            Language::Uk => Self::build_uk(),
+            Language::ZhHans => Self::build_zh_hans(),
+            _ => Self::build_custom(lang),
+        }
+    }
+
+    fn build_custom(lang: &Language) -> Self {
+        let en = Self::build_en();
+        Self {
+            link_expired_title: lang
+                .translate("flow_error.link_expired_title", en.link_expired_title),
+            link_expired_text: lang.translate("flow_error.link_expired_text", en.link_expired_text),
+            session_expired_title: lang
+                .translate("flow_error.session_expired_title", en.session_expired_title),
+            session_expired_text: lang
+                .translate("flow_error.session_expired_text", en.session_expired_text),
+            invalid_request_title: lang
+                .translate("flow_error.invalid_request_title", en.invalid_request_title),
+            invalid_request_text: lang
+                .translate("flow_error.invalid_request_text", en.invalid_request_text),
+            forbidden_title: lang.translate("flow_error.forbidden_title", en.forbidden_title),
+            forbidden_text: lang.translate("flow_error.forbidden_text", en.forbidden_text),
+            generic_title: lang.translate("flow_error.generic_title", en.generic_title),
+            generic_text: lang.translate("flow_error.generic_text", en.generic_text),
+            continue_label: lang.translate("flow_error.continue_label", en.continue_label),
+            retry_label: lang.translate("flow_error.retry_label", en.retry_label),
+            request_link_label: lang
+                .translate("flow_error.request_link_label", en.request_link_label),
+            maintenance_title: lang.translate("flow_error.maintenance_title", en.maintenance_title),
+            maintenance_text: lang.translate("flow_error.maintenance_text", en.maintenance_text),
+        }
+    }
+}
+
+impl I18nFlowError<'_> {
+    fn build_de() -> Self {
+        Self {
+            link_expired_title: "Link abgelaufen",
+            link_expired_text: "Dieser Link ist abgelaufen oder wurde bereits verwendet. \
+                Bitte fordere einen neuen an.",
+            session_expired_title: "Anmeldung abgelaufen",
+            session_expired_text: "Die Anmeldung hat zu lange gedauert und die Sitzung ist \
+                abgelaufen. Bitte starte die Anmeldung erneut.",
+            invalid_request_title: "Ungültige Anfrage",
+            invalid_request_text: "Die Anwendung hat eine ungültige Anfrage gesendet. Sollte \
+                dies erneut passieren, wende dich bitte an den Administrator.",
+            forbidden_title: "Zugriff verweigert",
+            forbidden_text: "Du hast keine Berechtigung für diese Aktion.",
+            generic_title: "Etwas ist schiefgelaufen",
+            generic_text: "Ein unerwarteter Fehler ist aufgetreten. Bitte versuche es später \
+                erneut.",
+            continue_label: "Weiter",
+            retry_label: "Erneut versuchen",
+            request_link_label: "Neuen Link anfordern",
+            maintenance_title: "Wartungsarbeiten",
+            maintenance_text: "Aufgrund von Wartungsarbeiten ist die Anmeldung gerade nicht \
+                möglich. Bitte versuche es später erneut.",
+        }
+    }
+
+    fn build_en() -> Self {
+        Self {
+            link_expired_title: "Link expired",
+            link_expired_text: "This link has expired or has been used already. Please request \
+                a new one.",
+            session_expired_title: "Sign-in expired",
+            session_expired_text: "The sign-in took too long and your session has expired. \
+                Please start again.",
+            invalid_request_title: "Invalid request",
+            invalid_request_text: "The application sent an invalid request. If this keeps \
+                happening, please contact the administrator.",
+            forbidden_title: "Access denied",
+            forbidden_text: "You are not allowed to perform this action.",
+            generic_title: "Something went wrong",
+            generic_text: "An unexpected error occurred. Please try again later.",
+            continue_label: "Continue",
+            retry_label: "Try again",
+            request_link_label: "Request a new link",
+            maintenance_title: "Maintenance",
+            maintenance_text: "Signing in is currently not possible because of scheduled \
+                maintenance. Please try again later.",
+        }
+    }
+
+    fn build_fr() -> Self {
+        Self {
+            link_expired_title: "Lien expiré",
+            link_expired_text: "Ce lien a expiré ou a déjà été utilisé. Veuillez en demander \
+                un nouveau.",
+            session_expired_title: "Connexion expirée",
+            session_expired_text: "La connexion a pris trop de temps et votre session a expiré. \
+                Veuillez recommencer.",
+            invalid_request_title: "Requête invalide",
+            invalid_request_text: "L’application a envoyé une requête invalide. Si le problème \
+                persiste, veuillez contacter l’administrateur.",
+            forbidden_title: "Accès refusé",
+            forbidden_text: "Vous n’êtes pas autorisé à effectuer cette action.",
+            generic_title: "Une erreur est survenue",
+            generic_text: "Une erreur inattendue s’est produite. Veuillez réessayer plus tard.",
+            continue_label: "Continuer",
+            retry_label: "Réessayer",
+            request_link_label: "Demander un nouveau lien",
+            maintenance_title: "Maintenance",
+            maintenance_text: "La connexion est momentanément impossible en raison d'une \
+                maintenance. Veuillez réessayer plus tard.",
+        }
+    }
+
+    fn build_ko() -> Self {
+        Self {
+            link_expired_title: "링크 만료",
+            link_expired_text: "이 링크는 만료되었거나 이미 사용되었습니다. 새 링크를 \
+                요청해 주세요.",
+            session_expired_title: "로그인 만료",
+            session_expired_text: "로그인 시간이 너무 오래 걸려 세션이 만료되었습니다. \
+                다시 시도해 주세요.",
+            invalid_request_title: "잘못된 요청",
+            invalid_request_text: "애플리케이션이 잘못된 요청을 보냈습니다. 문제가 계속되면 \
+                관리자에게 문의하세요.",
+            forbidden_title: "접근 거부",
+            forbidden_text: "이 작업을 수행할 권한이 없습니다.",
+            generic_title: "문제가 발생했습니다",
+            generic_text: "예기치 않은 오류가 발생했습니다. 나중에 다시 시도해 주세요.",
+            continue_label: "계속",
+            retry_label: "다시 시도",
+            request_link_label: "새 링크 요청",
+            maintenance_title: "점검 중",
+            maintenance_text: "예정된 점검으로 인해 현재 로그인할 수 없습니다. 나중에 다시 시도해 주세요.",
+        }
+    }
+
+    fn build_nb() -> Self {
+        Self {
+            link_expired_title: "Lenken har utløpt",
+            link_expired_text: "Denne lenken har utløpt eller er allerede brukt. Vennligst be \
+                om en ny.",
+            session_expired_title: "Innloggingen har utløpt",
+            session_expired_text: "Innloggingen tok for lang tid og økten har utløpt. \
+                Vennligst start på nytt.",
+            invalid_request_title: "Ugyldig forespørsel",
+            invalid_request_text: "Applikasjonen sendte en ugyldig forespørsel. Hvis dette \
+                fortsetter, vennligst kontakt administratoren.",
+            forbidden_title: "Ingen tilgang",
+            forbidden_text: "Du har ikke tillatelse til å utføre denne handlingen.",
+            generic_title: "Noe gikk galt",
+            generic_text: "En uventet feil oppstod. Vennligst prøv igjen senere.",
+            continue_label: "Fortsett",
+            retry_label: "Prøv igjen",
+            request_link_label: "Be om ny lenke",
+            maintenance_title: "Vedlikehold",
+            maintenance_text: "Innlogging er for øyeblikket ikke mulig på grunn av vedlikehold. \
+                Vennligst prøv igjen senere.",
+        }
+    }
+
+    fn build_nl() -> Self {
+        Self {
+            link_expired_title: "Link verlopen",
+            link_expired_text: "Deze link is verlopen of is al gebruikt. Vraag een nieuwe aan.",
+            session_expired_title: "Inloggen verlopen",
+            session_expired_text: "Het inloggen duurde te lang en uw sessie is verlopen. \
+                Begin opnieuw.",
+            invalid_request_title: "Ongeldig verzoek",
+            invalid_request_text: "De applicatie heeft een ongeldig verzoek verstuurd. Neem \
+                contact op met de beheerder als dit blijft gebeuren.",
+            forbidden_title: "Toegang geweigerd",
+            forbidden_text: "U heeft geen toestemming voor deze actie.",
+            generic_title: "Er is iets misgegaan",
+            generic_text: "Er is een onverwachte fout opgetreden. Probeer het later opnieuw.",
+            continue_label: "Doorgaan",
+            retry_label: "Opnieuw proberen",
+            request_link_label: "Nieuwe link aanvragen",
+            maintenance_title: "Onderhoud",
+            maintenance_text: "Inloggen is momenteel niet mogelijk vanwege gepland onderhoud. \
+                Probeer het later opnieuw.",
+        }
+    }
+
+    fn build_ru() -> Self {
+        Self {
+            link_expired_title: "Срок действия ссылки истёк",
+            link_expired_text: "Срок действия этой ссылки истёк или она уже была использована. \
+                Пожалуйста, запросите новую.",
+            session_expired_title: "Время входа истекло",
+            session_expired_text: "Вход занял слишком много времени, и ваша сессия истекла. \
+                Пожалуйста, начните заново.",
+            invalid_request_title: "Неверный запрос",
+            invalid_request_text: "Приложение отправило неверный запрос. Если это повторится, \
+                обратитесь к администратору.",
+            forbidden_title: "Доступ запрещён",
+            forbidden_text: "У вас нет прав на выполнение этого действия.",
+            generic_title: "Что-то пошло не так",
+            generic_text: "Произошла непредвиденная ошибка. Пожалуйста, повторите попытку позже.",
+            continue_label: "Продолжить",
+            retry_label: "Повторить",
+            request_link_label: "Запросить новую ссылку",
+            maintenance_title: "Техническое обслуживание",
+            maintenance_text: "Вход в систему сейчас невозможен из-за технического обслуживания. \
+                Пожалуйста, попробуйте позже.",
+        }
+    }
+
+    fn build_uk() -> Self {
+        Self {
+            link_expired_title: "Термін дії посилання минув",
+            link_expired_text: "Термін дії цього посилання минув або його вже було використано. \
+                Будь ласка, запросіть нове.",
+            session_expired_title: "Час входу минув",
+            session_expired_text: "Вхід тривав занадто довго, і ваша сесія закінчилася. \
+                Будь ласка, почніть знову.",
+            invalid_request_title: "Недійсний запит",
+            invalid_request_text: "Застосунок надіслав недійсний запит. Якщо це повториться, \
+                зверніться до адміністратора.",
+            forbidden_title: "Доступ заборонено",
+            forbidden_text: "У вас немає дозволу на виконання цієї дії.",
+            generic_title: "Щось пішло не так",
+            generic_text: "Сталася неочікувана помилка. Будь ласка, спробуйте пізніше.",
+            continue_label: "Продовжити",
+            retry_label: "Спробувати знову",
+            request_link_label: "Запросити нове посилання",
+            maintenance_title: "Технічне обслуговування",
+            maintenance_text: "Вхід наразі неможливий через технічне обслуговування. Будь ласка, \
+                спробуйте пізніше.",
+        }
+    }
+
+    fn build_zh_hans() -> Self {
+        Self {
+            link_expired_title: "链接已过期",
+            link_expired_text: "此链接已过期或已被使用。请重新申请一个新链接。",
+            session_expired_title: "登录已过期",
+            session_expired_text: "登录耗时过长，您的会话已过期。请重新开始。",
+            invalid_request_title: "无效的请求",
+            invalid_request_text: "应用程序发送了无效的请求。如果问题持续出现，请联系管理员。",
+            forbidden_title: "拒绝访问",
+            forbidden_text: "您无权执行此操作。",
+            generic_title: "出现错误",
+            generic_text: "发生了意外错误。请稍后再试。",
+            continue_label: "继续",
+            retry_label: "重试",
+            request_link_label: "申请新链接",
+            maintenance_title: "系统维护",
+            maintenance_text: "由于系统维护，目前无法登录。请稍后再试。",
+        }
+    }
+}
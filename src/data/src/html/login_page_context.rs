@@ -0,0 +1,256 @@
+use crate::cache_layer::{self, CacheName};
+use crate::database::{Cache, DB};
+use crate::entity::auth_providers::{AuthProvider, AuthProviderTemplate};
+use crate::entity::i18n_overrides::I18nOverrides;
+use crate::entity::logos::{Logo, LogoType};
+use crate::entity::theme::ThemeCssFull;
+use crate::language::Language;
+use crate::rauthy_config::RauthyConfig;
+use rauthy_api_types::i18n::I18nPage;
+use rauthy_common::constants::{CACHE_TTL_APP, IDX_LOGIN_PAGE_CONTEXT, PROVIDER_ATPROTO};
+use rauthy_error::ErrorResponse;
+use serde::{Deserialize, Serialize};
+
+/// Everything the login page needs, which does not depend on the current session, bundled into
+/// a single cache entry for each client and language. A warm `GET /oidc/authorize` only needs
+/// this one cache read instead of a lookup for each part.
+///
+/// The entries are invalidated on any change of a part, which is replicated to all HA nodes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoginPageContext {
+    pub theme_ts: i64,
+    pub logo_updated: Option<i64>,
+    /// See `AuthProviderTemplate::get_all_json_template()`
+    pub auth_providers_json: String,
+    /// See `I18nOverrides::template_json()`
+    pub i18n_overrides_json: String,
+    pub atproto_id: Option<String>,
+}
+
+/// The lookups needed to build a `LoginPageContext`. Each of them may end up in a database
+/// query on a cold cache.
+trait LoginPageSource {
+    async fn theme_ts(&self, client_id: &str) -> Result<i64, ErrorResponse>;
+
+    async fn logo_updated(&self, client_id: &str) -> Result<Option<i64>, ErrorResponse>;
+
+    async fn auth_providers_json(&self) -> Result<String, ErrorResponse>;
+
+    async fn i18n_overrides_json(&self, lang: &Language) -> Result<String, ErrorResponse>;
+
+    async fn atproto_id(&self) -> Result<Option<String>, ErrorResponse>;
+}
+
+trait LoginPageCache {
+    async fn get(&self, idx: String) -> Result<Option<LoginPageContext>, ErrorResponse>;
+
+    async fn put(&self, idx: String, ctx: &LoginPageContext) -> Result<(), ErrorResponse>;
+}
+
+struct DbSource;
+
+impl LoginPageSource for DbSource {
+    async fn theme_ts(&self, client_id: &str) -> Result<i64, ErrorResponse> {
+        ThemeCssFull::find_theme_ts(client_id.to_string()).await
+    }
+
+    async fn logo_updated(&self, client_id: &str) -> Result<Option<i64>, ErrorResponse> {
+        Logo::find_updated(client_id, &LogoType::Client).await
+    }
+
+    async fn auth_providers_json(&self) -> Result<String, ErrorResponse> {
+        AuthProviderTemplate::get_all_json_template().await
+    }
+
+    async fn i18n_overrides_json(&self, lang: &Language) -> Result<String, ErrorResponse> {
+        Ok(I18nOverrides::find()
+            .await?
+            .template_json(I18nPage::Login, lang))
+    }
+
+    async fn atproto_id(&self) -> Result<Option<String>, ErrorResponse> {
+        if !RauthyConfig::get().vars.atproto.enable {
+            return Ok(None);
+        }
+        let provider = AuthProvider::find_by_iss(PROVIDER_ATPROTO.to_string()).await?;
+        Ok(Some(provider.id))
+    }
+}
+
+struct HiqliteCache;
+
+impl LoginPageCache for HiqliteCache {
+    async fn get(&self, idx: String) -> Result<Option<LoginPageContext>, ErrorResponse> {
+        cache_layer::get(CacheName::LoginPageContext, idx).await
+    }
+
+    async fn put(&self, idx: String, ctx: &LoginPageContext) -> Result<(), ErrorResponse> {
+        cache_layer::put(CacheName::LoginPageContext, idx, ctx, CACHE_TTL_APP).await
+    }
+}
+
+impl LoginPageContext {
+    #[inline]
+    fn cache_idx(client_id: &str, lang: &Language) -> String {
+        format!("{IDX_LOGIN_PAGE_CONTEXT}{client_id}_{}", lang.as_str())
+    }
+
+    pub async fn find(client_id: &str, lang: &Language) -> Result<Self, ErrorResponse> {
+        Self::find_with(&HiqliteCache, &DbSource, client_id, lang).await
+    }
+
+    async fn find_with<C, S>(
+        cache: &C,
+        source: &S,
+        client_id: &str,
+        lang: &Language,
+    ) -> Result<Self, ErrorResponse>
+    where
+        C: LoginPageCache,
+        S: LoginPageSource,
+    {
+        let idx = Self::cache_idx(client_id, lang);
+        if let Some(slf) = cache.get(idx.clone()).await? {
+            return Ok(slf);
+        }
+
+        let slf = Self {
+            theme_ts: source.theme_ts(client_id).await?,
+            logo_updated: source.logo_updated(client_id).await?,
+            auth_providers_json: source.auth_providers_json().await?,
+            i18n_overrides_json: source.i18n_overrides_json(lang).await?,
+            atproto_id: source.atproto_id().await?,
+        };
+        cache.put(idx, &slf).await?;
+
+        Ok(slf)
+    }
+
+    /// Must be called after any change to the auth providers, themes or translation overrides.
+    /// Clearing the whole cache is replicated via Raft, which covers all HA nodes.
+    pub async fn invalidate_all() -> Result<(), ErrorResponse> {
+        DB::hql().clear_cache(Cache::LoginPageContext).await?;
+        Ok(())
+    }
+
+    /// Must be called after a change to the branding of a single client.
+    pub async fn invalidate_client(client_id: &str) -> Result<(), ErrorResponse> {
+        for lang in Language::all() {
+            cache_layer::delete(
+                CacheName::LoginPageContext,
+                Self::cache_idx(client_id, &lang),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Each of these lookups was done on every `GET /oidc/authorize` before.
+    const LOOKUPS_PER_BUILD: usize = 5;
+
+    #[derive(Default)]
+    struct CountingSource {
+        lookups: AtomicUsize,
+    }
+
+    impl CountingSource {
+        fn count(&self) {
+            self.lookups.fetch_add(1, Ordering::Relaxed);
+        }
+
+        fn lookups(&self) -> usize {
+            self.lookups.load(Ordering::Relaxed)
+        }
+    }
+
+    impl LoginPageSource for CountingSource {
+        async fn theme_ts(&self, _client_id: &str) -> Result<i64, ErrorResponse> {
+            self.count();
+            Ok(1337)
+        }
+
+        async fn logo_updated(&self, _client_id: &str) -> Result<Option<i64>, ErrorResponse> {
+            self.count();
+            Ok(Some(42))
+        }
+
+        async fn auth_providers_json(&self) -> Result<String, ErrorResponse> {
+            self.count();
+            Ok("[]".to_string())
+        }
+
+        async fn i18n_overrides_json(&self, lang: &Language) -> Result<String, ErrorResponse> {
+            self.count();
+            Ok(format!("{{\"lang\":\"{}\"}}", lang.as_str()))
+        }
+
+        async fn atproto_id(&self) -> Result<Option<String>, ErrorResponse> {
+            self.count();
+            Ok(None)
+        }
+    }
+
+    #[derive(Default)]
+    struct MemCache {
+        entries: Mutex<HashMap<String, LoginPageContext>>,
+        reads: AtomicUsize,
+    }
+
+    impl LoginPageCache for MemCache {
+        async fn get(&self, idx: String) -> Result<Option<LoginPageContext>, ErrorResponse> {
+            self.reads.fetch_add(1, Ordering::Relaxed);
+            Ok(self.entries.lock().unwrap().get(&idx).cloned())
+        }
+
+        async fn put(&self, idx: String, ctx: &LoginPageContext) -> Result<(), ErrorResponse> {
+            self.entries.lock().unwrap().insert(idx, ctx.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lookups_per_request() -> Result<(), ErrorResponse> {
+        let cache = MemCache::default();
+        let source = CountingSource::default();
+
+        // cold cache
+        let ctx = LoginPageContext::find_with(&cache, &source, "app", &Language::En).await?;
+        assert_eq!(source.lookups(), LOOKUPS_PER_BUILD);
+        assert_eq!(ctx.theme_ts, 1337);
+        assert_eq!(ctx.logo_updated, Some(42));
+        assert_eq!(ctx.i18n_overrides_json, "{\"lang\":\"en\"}");
+
+        // warm cache: a single cache read and no lookups at all, compared to
+        // `LOOKUPS_PER_BUILD` for each request before
+        let requests = 10;
+        let reads_before = cache.reads.load(Ordering::Relaxed);
+        for _ in 0..requests {
+            let warm = LoginPageContext::find_with(&cache, &source, "app", &Language::En).await?;
+            assert_eq!(warm, ctx);
+        }
+        assert_eq!(source.lookups(), LOOKUPS_PER_BUILD);
+        assert_eq!(cache.reads.load(Ordering::Relaxed) - reads_before, requests);
+
+        // each client and language has its own entry
+        LoginPageContext::find_with(&cache, &source, "app", &Language::De).await?;
+        LoginPageContext::find_with(&cache, &source, "other", &Language::En).await?;
+        assert_eq!(source.lookups(), 3 * LOOKUPS_PER_BUILD);
+
+        // an invalidation leads to exactly one rebuild
+        cache.entries.lock().unwrap().clear();
+        for _ in 0..requests {
+            LoginPageContext::find_with(&cache, &source, "app", &Language::En).await?;
+        }
+        assert_eq!(source.lookups(), 4 * LOOKUPS_PER_BUILD);
+
+        Ok(())
+    }
+}
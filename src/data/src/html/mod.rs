@@ -1,17 +1,19 @@
-use crate::database::{Cache, DB};
+use crate::cache_layer::{self, CacheName};
 use crate::entity::auth_providers::AuthProviderTemplate;
+use crate::entity::i18n_overrides::I18nOverrides;
 use crate::entity::logos::{Logo, LogoType};
 use crate::html::templates::{
     AccountHtml, AdminApiKeysHtml, AdminAttributesHtml, AdminBlacklistHtml, AdminClientsHtml,
     AdminConfigArgon2Html, AdminConfigBackupsHtml, AdminConfigEncryptionHtml, AdminConfigJwksHtml,
     AdminConfigPolicyHtml, AdminEventsHtml, AdminGroupsHtml, AdminHtml, AdminKVHtml, AdminPAMHtml,
     AdminRolesHtml, AdminScopesHtml, AdminSessionsHtml, AdminUsersHtml, DeviceHtml, FedCMHtml,
-    HtmlTemplate, IndexHtml, LogoutHtml, ProviderCallbackHtml, ProvidersHtml,
+    HtmlTemplate, IndexHtml, LogoutHtml, ProviderCallbackHtml, ProvidersHtml, TplUpstreamLogout,
     UserPasswordResetHtml, UserRegisterHtml,
 };
 use crate::language::Language;
 use actix_web::http::header::ACCEPT_ENCODING;
 use actix_web::{HttpRequest, HttpResponse};
+use rauthy_api_types::i18n::I18nPage;
 use rauthy_common::compression::{compress_br_9, compress_br_dyn, compress_gzip};
 use rauthy_common::constants::HEADER_HTML;
 use rauthy_error::ErrorResponse;
@@ -19,6 +21,10 @@ use serde::Deserialize;
 use templates::AdminDocsHtml;
 use tracing::debug;
 
+pub mod csp;
+pub mod flow_error;
+pub mod i18n;
+pub mod login_page_context;
 pub mod templates;
 #[cfg(debug_assertions)]
 pub mod templates_dev;
@@ -50,7 +56,7 @@ pub enum HtmlCached {
     Docs,
     FedCM,
     Index,
-    Logout(String),
+    Logout(String, Option<TplUpstreamLogout>),
     PasswordReset,
     UserRegistration,
 }
@@ -84,7 +90,7 @@ impl HtmlCached {
             Self::Docs => "docs",
             Self::FedCM => "fed_cm",
             Self::Index => "index",
-            Self::Logout(_) => "logout",
+            Self::Logout(_, _) => "logout",
             Self::PasswordReset => "password_reset",
             Self::UserRegistration => "user_reg",
         }
@@ -119,9 +125,8 @@ impl HtmlCached {
         let lang = Language::try_from(&req).unwrap_or_default();
         debug!(language = ?lang);
         let cache_key = if with_cache {
-            if let Some(bytes) = DB::hql()
-                .get_bytes(Cache::Html, self.cache_key(&lang, encoding))
-                .await?
+            if let Some(bytes) =
+                cache_layer::get_bytes(CacheName::Html, self.cache_key(&lang, encoding)).await?
             {
                 return Ok(HttpResponse::Ok()
                     .insert_header(("content-encoding", encoding))
@@ -136,11 +141,15 @@ impl HtmlCached {
         let body = match self {
             Self::Account => {
                 let providers = AuthProviderTemplate::get_all_json_template().await?;
+                let i18n = I18nOverrides::find()
+                    .await?
+                    .template_json(I18nPage::Account, &lang);
                 AccountHtml::build(
                     &lang,
                     theme_ts,
                     &[
                         HtmlTemplate::AuthProviders(providers),
+                        HtmlTemplate::I18nOverrides(i18n),
                         HtmlTemplate::UserValues,
                     ],
                 )
@@ -165,11 +174,18 @@ impl HtmlCached {
             Self::ConfigEncryption => AdminConfigEncryptionHtml::build(&lang, theme_ts),
             Self::ConfigJwks => AdminConfigJwksHtml::build(&lang, theme_ts),
             Self::ConfigPolicy => AdminConfigPolicyHtml::build(&lang, theme_ts),
-            Self::Device => DeviceHtml::build(&lang, theme_ts),
+            Self::Device => {
+                let i18n = I18nOverrides::find()
+                    .await?
+                    .template_json(I18nPage::Device, &lang);
+                DeviceHtml::build(&lang, theme_ts, i18n)
+            }
             Self::Docs => AdminDocsHtml::build(&lang, theme_ts),
             Self::FedCM => FedCMHtml::build(&lang, theme_ts),
             Self::Index => IndexHtml::build(&lang, theme_ts),
-            Self::Logout(csrf_token) => LogoutHtml::build(csrf_token, &lang, theme_ts),
+            Self::Logout(csrf_token, upstream_logout) => {
+                LogoutHtml::build(csrf_token, upstream_logout, &lang, theme_ts)
+            }
             Self::PasswordReset => {
                 let logo_updated = Logo::find_updated("rauthy", &LogoType::Client).await?;
                 UserPasswordResetHtml::build(&lang, theme_ts, logo_updated)
@@ -196,9 +212,7 @@ impl HtmlCached {
         };
 
         if with_cache {
-            DB::hql()
-                .put_bytes(Cache::Html, cache_key, body_bytes.clone(), None)
-                .await?;
+            cache_layer::put_bytes(CacheName::Html, cache_key, body_bytes.clone(), None).await?;
         }
 
         Ok(HttpResponse::Ok()
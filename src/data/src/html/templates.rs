@@ -1,3 +1,4 @@
+use crate::html::csp::CspNonce;
 use crate::language::Language;
 use crate::rauthy_config::RauthyConfig;
 use actix_web::http::StatusCode;
@@ -5,7 +6,7 @@ use actix_web::{HttpResponse, HttpResponseBuilder};
 use askama::Template;
 use rauthy_api_types::generic::PasswordPolicyResponse;
 use rauthy_common::constants::HEADER_HTML;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 use std::fmt::{Debug, Display, Formatter};
 
@@ -41,6 +42,14 @@ pub struct TplPasswordReset {
     pub user_id: String,
 }
 
+/// The upstream logout, which is possible for the current session
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TplUpstreamLogout {
+    pub provider_name: String,
+    /// If `true`, the logout will always be propagated, and the user does not have a choice.
+    pub auto: bool,
+}
+
 // If you add new values to this template, make sure to also create a
 // matching constant in the UI and make proper use of it:
 // -> frontend/src/utils/constants.ts -> TPL_* values
@@ -59,11 +68,14 @@ pub enum HtmlTemplate {
     ErrorDetails(Cow<'static, str>),
     ErrorText(Cow<'static, str>),
     DeviceUserCodeLength(u8),
+    /// Translation overrides as pre-built JSON value, see `I18nOverrides::template_json()`
+    I18nOverrides(String),
     IsRegOpen(bool),
     LoginAction(FrontendAction),
     PasswordReset(TplPasswordReset),
     RestrictedEmailDomain(String),
     StatusCode(StatusCode),
+    UpstreamLogout(TplUpstreamLogout),
     UserValues,
 }
 
@@ -83,11 +95,13 @@ impl HtmlTemplate {
             Self::ErrorDetails(_) => "tpl_error_details",
             Self::ErrorText(_) => "tpl_error_text",
             Self::DeviceUserCodeLength(_) => "tpl_device_user_code_length",
+            Self::I18nOverrides(_) => "tpl_i18n_overrides",
             Self::IsRegOpen(_) => "tpl_is_reg_open",
             Self::LoginAction(_) => "tpl_login_action",
             Self::PasswordReset(_) => "tpl_password_reset",
             Self::RestrictedEmailDomain(_) => "tpl_restricted_email_domain",
             Self::StatusCode(_) => "tpl_status_code",
+            Self::UpstreamLogout(_) => "tpl_upstream_logout",
             Self::UserValues => "tpl_user_values_config",
         }
     }
@@ -108,11 +122,13 @@ impl HtmlTemplate {
             Self::ErrorDetails(i) => i.to_string(),
             Self::ErrorText(i) => i.to_string(),
             Self::DeviceUserCodeLength(i) => i.to_string(),
+            Self::I18nOverrides(i) => i.to_string(),
             Self::IsRegOpen(i) => i.to_string(),
             Self::LoginAction(i) => i.to_string(),
             Self::PasswordReset(i) => serde_json::to_string(i).unwrap(),
             Self::StatusCode(i) => i.to_string(),
             Self::RestrictedEmailDomain(i) => i.to_string(),
+            Self::UpstreamLogout(i) => serde_json::to_string(i).unwrap(),
             Self::UserValues => {
                 serde_json::to_string(&RauthyConfig::get().vars.user_values).unwrap()
             }
@@ -198,14 +214,17 @@ pub struct DeviceHtml<'a> {
 }
 
 impl DeviceHtml<'_> {
-    pub fn build(lang: &Language, theme_ts: i64) -> String {
+    pub fn build(lang: &Language, theme_ts: i64, i18n_overrides: String) -> String {
         let res = DeviceHtml {
             lang: lang.as_str(),
             client_id: "rauthy",
             theme_ts,
-            templates: &[HtmlTemplate::DeviceUserCodeLength(
-                RauthyConfig::get().vars.device_grant.user_code_length as u8,
-            )],
+            templates: &[
+                HtmlTemplate::DeviceUserCodeLength(
+                    RauthyConfig::get().vars.device_grant.user_code_length as u8,
+                ),
+                HtmlTemplate::I18nOverrides(i18n_overrides),
+            ],
         };
 
         res.render().unwrap()
@@ -877,12 +896,22 @@ pub struct LogoutHtml<'a> {
 }
 
 impl LogoutHtml<'_> {
-    pub fn build(csrf_token: String, lang: &Language, theme_ts: i64) -> String {
+    pub fn build(
+        csrf_token: String,
+        upstream_logout: Option<TplUpstreamLogout>,
+        lang: &Language,
+        theme_ts: i64,
+    ) -> String {
+        let mut templates = vec![HtmlTemplate::CsrfToken(csrf_token)];
+        if let Some(tpl) = upstream_logout {
+            templates.push(HtmlTemplate::UpstreamLogout(tpl));
+        }
+
         let res = LogoutHtml {
             lang: lang.as_str(),
             client_id: "rauthy",
             theme_ts,
-            templates: &[HtmlTemplate::CsrfToken(csrf_token)],
+            templates: &templates,
         };
 
         res.render().unwrap()
@@ -925,14 +954,42 @@ impl BlockedHtml {
 
 #[derive(Default, Template)]
 #[template(path = "error/429.html")]
-pub struct TooManyRequestsHtml {
+pub struct TooManyRequestsHtml<'a> {
     pub ip: String,
     pub exp: i64,
+    pub csp_nonce: &'a str,
 }
 
-impl TooManyRequestsHtml {
-    pub fn build(ip: String, exp: i64) -> String {
-        TooManyRequestsHtml { ip, exp }.render().unwrap()
+impl TooManyRequestsHtml<'_> {
+    pub fn build(ip: String, exp: i64, csp_nonce: &CspNonce) -> String {
+        TooManyRequestsHtml {
+            ip,
+            exp,
+            csp_nonce: csp_nonce.as_str(),
+        }
+        .render()
+        .unwrap()
+    }
+}
+
+#[derive(Default, Template)]
+#[template(path = "error/flow.html")]
+pub struct FlowErrorHtml<'a> {
+    pub lang: &'a str,
+    pub client_id: &'a str,
+    pub theme_ts: i64,
+    pub title: &'a str,
+    pub text: &'a str,
+    pub action: &'a str,
+    pub continue_uri: &'a str,
+    pub status: u16,
+    pub error: &'a str,
+    pub details: &'a str,
+}
+
+impl FlowErrorHtml<'_> {
+    pub fn build(self) -> String {
+        self.render().expect("rendering error/flow.html")
     }
 }
 
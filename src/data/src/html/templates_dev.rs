@@ -1,4 +1,5 @@
 use crate::api_cookie::ApiCookie;
+use crate::entity::auth_provider_logout::AuthProviderSession;
 use crate::entity::auth_providers::{AuthProvider, AuthProviderTemplate};
 use crate::entity::magic_links::{MagicLink, MagicLinkUsage};
 use crate::entity::password::PasswordPolicy;
@@ -149,6 +150,19 @@ impl HtmlTemplate {
 
                 Ok((Self::PasswordReset(tpl), Some(cookie)))
             }
+            "tpl_upstream_logout" => {
+                let tpl = match session {
+                    Some(s) => AuthProviderSession::find_template(&s.id).await?,
+                    None => None,
+                };
+                match tpl {
+                    Some(tpl) => Ok((Self::UpstreamLogout(tpl), None)),
+                    None => Err(ErrorResponse::new(
+                        ErrorResponseType::NotFound,
+                        "no upstream logout",
+                    )),
+                }
+            }
             "tpl_user_values_config" => Ok((Self::UserValues, None)),
             _ => Err(ErrorResponse::new(
                 ErrorResponseType::NotFound,
@@ -97,7 +97,7 @@ fn location_from_header(headers: &HeaderMap) -> Option<String> {
     // direct peer IP is a trusted proxy here. However, this function is only called from
     // the other 2 `get_location` fns here, which requires the `IpAddr` to be given as well.
     // This ip is extracted in other parts of the code, but no matter from where it comes,
-    // it's always extracted via the `real_ip_from_req()` fns, which validate the trusted proxy,
+    // it's always extracted via the `real_ip()` fns, which validate the trusted proxy,
     // if `proxy_mode` is enabled. This prevents spoofing this header, if an attacker would
     // know it somehow.
     // Doing another trusted proxy check here would be a waste of resources.
@@ -1,49 +1,169 @@
+use crate::language_pack::{LanguagePack, LanguagePacks};
 use actix_web::HttpRequest;
 use actix_web::http::header::{ACCEPT_LANGUAGE, HeaderValue};
 use rauthy_common::constants::COOKIE_LOCALE;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::fmt::{Display, Formatter};
 use tracing::debug;
 
-// Note: Updating this enum will require an update on the LANGUAGES constant for the frontend too
-#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize, strum::EnumIter)]
-#[serde(rename_all = "lowercase")]
-pub enum Language {
-    De,
-    #[default]
-    En,
-    Fr,
-    Ko,
-    Nb,
-    Nl,
-    Ru,
-    Uk,
-    ZhHans,
+/// A validated, lowercase locale tag. The built-in languages exist as associated constants.
+/// Additional languages can be loaded at startup from `i18n.custom_languages_dir`, see
+/// `LanguagePacks`. Values can only be created from one of these.
+// Note: Updating the built-in languages will require an update on the LANGUAGES constant for the
+// frontend too
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Language(&'static str);
+
+#[allow(non_upper_case_globals)]
+impl Language {
+    pub const De: Self = Self("de");
+    pub const En: Self = Self("en");
+    pub const Fr: Self = Self("fr");
+    pub const Ko: Self = Self("ko");
+    pub const Nb: Self = Self("nb");
+    pub const Nl: Self = Self("nl");
+    pub const Ru: Self = Self("ru");
+    pub const Uk: Self = Self("uk");
+    pub const ZhHans: Self = Self("zhhans");
+
+    pub const BUILT_IN: [Self; 9] = [
+        Self::De,
+        Self::En,
+        Self::Fr,
+        Self::Ko,
+        Self::Nb,
+        Self::Nl,
+        Self::Ru,
+        Self::Uk,
+        Self::ZhHans,
+    ];
+}
+
+impl Default for Language {
+    fn default() -> Self {
+        Self::En
+    }
 }
 
 impl Language {
-    fn all_available<'a>() -> [&'a str; 18] {
-        [
+    /// All built-in languages, followed by all loaded custom ones.
+    pub fn all() -> impl Iterator<Item = Self> {
+        Self::BUILT_IN
+            .into_iter()
+            .chain(LanguagePacks::all().iter().map(|p| Self(p.tag())))
+    }
+
+    fn all_available() -> Vec<&'static str> {
+        let mut available = vec![
             "en", "en-US", "de", "de-DE", "fr", "fr-FR", "ko", "nb", "nb-NO", "no-NO", "nl",
             "nl-NL", "ru", "ru-RU", "uk", "uk-UA", "zh", "zh-Hans",
-        ]
+        ];
+        for pack in LanguagePacks::all() {
+            available.push(pack.tag());
+            if let Some(canonical) = pack.tag_canonical() {
+                available.push(canonical);
+            }
+        }
+        available
     }
 
     pub fn as_str(&self) -> &'static str {
-        // must return results that work with serde::Deserialize from lowercase
-        match self {
-            Language::De => "de",
-            Language::En => "en",
-            Language::Fr => "fr",
-            Language::Ko => "ko",
-            Language::Nb => "nb",
-            Language::Nl => "nl",
-            Language::Ru => "ru",
-            Language::Uk => "uk",
-            Language::ZhHans => "zhhans",
+        // must return results that work with `Language::from()`
+        self.0
+    }
+
+    /// `true` for all languages, which are compiled into Rauthy.
+    #[inline]
+    pub fn is_built_in(&self) -> bool {
+        Self::BUILT_IN.contains(self)
+    }
+
+    /// The custom translation, if this is not a built-in language.
+    #[inline]
+    pub fn pack(&self) -> Option<&'static LanguagePack> {
+        if self.is_built_in() {
+            None
+        } else {
+            LanguagePacks::find(self.0)
+        }
+    }
+
+    /// Returns the custom translation for the given `key`, or the `fallback` if this language
+    /// does not have one. Keys are the paths inside the language pack, like
+    /// `email.reset.subject`.
+    pub fn translate<'a>(&self, key: &str, fallback: &'a str) -> &'a str {
+        self.pack().and_then(|p| p.get(key)).unwrap_or(fallback)
+    }
+
+    /// Same as `translate()`, for values which are optional in the built-in translation.
+    pub fn translate_opt<'a>(&self, key: &str, fallback: Option<&'a str>) -> Option<&'a str> {
+        self.pack().and_then(|p| p.get(key)).or(fallback)
+    }
+
+    /// Resolves a built-in language, including some common aliases.
+    pub(crate) fn built_in(value: &str) -> Option<Self> {
+        match value {
+            "en" | "en-US" => Some(Self::En),
+            "de" | "de-DE" => Some(Self::De),
+            "fr" | "fr-FR" => Some(Self::Fr),
+            "ko" | "ko-KR" => Some(Self::Ko),
+            "nb" | "nb-NO" | "no-NO" => Some(Self::Nb),
+            "nl" | "nl-NL" => Some(Self::Nl),
+            "ru" | "ru-RU" => Some(Self::Ru),
+            "uk" | "uk-UA" => Some(Self::Uk),
+            "zh" | "zhhans" | "zh-hans" | "zh-Hans" => Some(Self::ZhHans),
+            _ => None,
+        }
+    }
+
+    /// Best-effort mapping for BCP 47 language tags like `en-GB` or `de_AT`. A loaded custom
+    /// language matching the full tag wins, otherwise only the primary language subtag is
+    /// checked. Unknown languages fall back to the default.
+    pub fn from_bcp47(tag: &str) -> Self {
+        if let Some(pack) = LanguagePacks::find(tag.trim()) {
+            return Self(pack.tag());
+        }
+
+        let primary = tag
+            .trim()
+            .split(['-', '_'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match primary.as_str() {
+            // `no` is the macrolanguage for `nb` and `nn`
+            "no" => Self::Nb,
+            p => Self::from(p),
         }
     }
+
+    /// Resolves the first supported language from a space-separated list of BCP 47 tags, like
+    /// the `ui_locales` parameter during an `/authorize` request.
+    pub fn from_ui_locales(ui_locales: &str) -> Option<Self> {
+        ui_locales.split_whitespace().find_map(|tag| {
+            if let Some(pack) = LanguagePacks::find(tag) {
+                return Some(Self(pack.tag()));
+            }
+            Self::built_in(tag).or_else(|| {
+                let primary = tag.split(['-', '_']).next().unwrap_or_default();
+                Self::built_in(&primary.to_ascii_lowercase())
+            })
+        })
+    }
+
+    /// Resolves the language for a request with a given `ui_locales` parameter. A language
+    /// the user has chosen explicitly via the locale cookie always wins, followed by the
+    /// `ui_locales` and the `Accept-Language` header.
+    pub fn resolve(req: &HttpRequest, ui_locales: Option<&str>) -> Self {
+        if let Some(cookie) = req.cookie(COOKIE_LOCALE) {
+            return Self::from(cookie.value());
+        }
+        ui_locales
+            .and_then(Self::from_ui_locales)
+            .or_else(|| Self::try_from(req).ok())
+            .unwrap_or_default()
+    }
 }
 
 impl Display for Language {
@@ -66,18 +186,9 @@ impl From<String> for Language {
 
 impl From<&str> for Language {
     fn from(value: &str) -> Self {
-        match value {
-            "en" | "en-US" => Self::En,
-            "de" | "de-DE" => Self::De,
-            "fr" | "fr-FR" => Self::Fr,
-            "ko" | "ko-KR" => Self::Ko,
-            "nb" | "nb-NO" | "no-NO" => Self::Nb,
-            "nl" | "nl-NL" => Self::Nl,
-            "ru" | "ru-RU" => Self::Ru,
-            "uk" | "uk-UA" => Self::Uk,
-            "zh" | "zhhans" | "zh-hans" | "zh-Hans" => Self::ZhHans,
-            _ => Self::default(),
-        }
+        Self::built_in(value)
+            .or_else(|| LanguagePacks::find(value).map(|p| Self(p.tag())))
+            .unwrap_or_default()
     }
 }
 
@@ -121,6 +232,7 @@ impl From<rauthy_api_types::generic::Language> for Language {
             rauthy_api_types::generic::Language::Ru => Self::Ru,
             rauthy_api_types::generic::Language::Uk => Self::Uk,
             rauthy_api_types::generic::Language::ZhHans => Self::ZhHans,
+            rauthy_api_types::generic::Language::Custom(tag) => Self::from(tag.as_str()),
         }
     }
 }
@@ -137,6 +249,105 @@ impl From<Language> for rauthy_api_types::generic::Language {
             Language::Ru => Self::Ru,
             Language::Uk => Self::Uk,
             Language::ZhHans => Self::ZhHans,
+            custom => Self::Custom(custom.as_str().to_string()),
         }
     }
 }
+
+/// The representation for binary formats like the cache, which is compatible with the former
+/// `enum Language` for all built-in languages.
+#[derive(Serialize, Deserialize)]
+enum LanguageRepr {
+    De,
+    En,
+    Fr,
+    Ko,
+    Nb,
+    Nl,
+    Ru,
+    Uk,
+    ZhHans,
+    Custom(String),
+}
+
+impl Serialize for Language {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if serializer.is_human_readable() {
+            return serializer.serialize_str(self.as_str());
+        }
+
+        let repr = match *self {
+            Self::De => LanguageRepr::De,
+            Self::En => LanguageRepr::En,
+            Self::Fr => LanguageRepr::Fr,
+            Self::Ko => LanguageRepr::Ko,
+            Self::Nb => LanguageRepr::Nb,
+            Self::Nl => LanguageRepr::Nl,
+            Self::Ru => LanguageRepr::Ru,
+            Self::Uk => LanguageRepr::Uk,
+            Self::ZhHans => LanguageRepr::ZhHans,
+            custom => LanguageRepr::Custom(custom.as_str().to_string()),
+        };
+        repr.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Language {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            let value = String::deserialize(deserializer)?;
+            return Ok(Self::from(value.as_str()));
+        }
+
+        // A custom language, which has been removed in the meantime, falls back to the default
+        Ok(match LanguageRepr::deserialize(deserializer)? {
+            LanguageRepr::De => Self::De,
+            LanguageRepr::En => Self::En,
+            LanguageRepr::Fr => Self::Fr,
+            LanguageRepr::Ko => Self::Ko,
+            LanguageRepr::Nb => Self::Nb,
+            LanguageRepr::Nl => Self::Nl,
+            LanguageRepr::Ru => Self::Ru,
+            LanguageRepr::Uk => Self::Uk,
+            LanguageRepr::ZhHans => Self::ZhHans,
+            LanguageRepr::Custom(tag) => Self::from(tag.as_str()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_language_from_bcp47() {
+        assert_eq!(Language::from_bcp47("en-US"), Language::En);
+        assert_eq!(Language::from_bcp47("en-GB"), Language::En);
+        assert_eq!(Language::from_bcp47("de-AT"), Language::De);
+        assert_eq!(Language::from_bcp47("de_CH"), Language::De);
+        assert_eq!(Language::from_bcp47("FR-ca"), Language::Fr);
+        assert_eq!(Language::from_bcp47("no-NO"), Language::Nb);
+        assert_eq!(Language::from_bcp47("zh-Hans-CN"), Language::ZhHans);
+        assert_eq!(Language::from_bcp47(" nl "), Language::Nl);
+        assert_eq!(Language::from_bcp47("pt-BR"), Language::En);
+        assert_eq!(Language::from_bcp47(""), Language::En);
+    }
+
+    #[test]
+    fn test_language_from_ui_locales() {
+        assert_eq!(Language::from_ui_locales("de"), Some(Language::De));
+        assert_eq!(
+            Language::from_ui_locales("es-MX fr-CA en"),
+            Some(Language::Fr)
+        );
+        assert_eq!(Language::from_ui_locales("zh-Hans"), Some(Language::ZhHans));
+        assert_eq!(Language::from_ui_locales("xx yy"), None);
+        assert_eq!(Language::from_ui_locales(""), None);
+    }
+}
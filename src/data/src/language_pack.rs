@@ -0,0 +1,534 @@
+use crate::language::Language;
+use crate::rauthy_config::RauthyConfig;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::sync::OnceLock;
+use tracing::{info, warn};
+
+static LANGUAGE_PACKS: OnceLock<Vec<LanguagePack>> = OnceLock::new();
+
+/// `(section, keys)` for each server-side translation a language pack can provide. These must
+/// match the fields of the built-in translations.
+static KEYS_SERVER: [(&str, &[&str]); 10] = [
+    (
+        "email.change_info_new",
+        &[
+            "subject",
+            "header",
+            "click_link",
+            "validity",
+            "expires",
+            "button_text",
+        ],
+    ),
+    (
+        "email.change_info_old",
+        &[
+            "subject",
+            "header",
+            "change_info",
+            "click_link",
+            "validity",
+            "expires",
+            "button_text",
+        ],
+    ),
+    (
+        "email.confirm_change",
+        &["subject", "msg", "msg_from_admin"],
+    ),
+    (
+        "email.confirm_change_html",
+        &["title", "text_changed", "text_login", "to"],
+    ),
+    (
+        "email.email_registered_already",
+        &[
+            "subject",
+            "header",
+            "text",
+            "footer",
+            "button_text_request_new",
+        ],
+    ),
+    (
+        "email.login_location",
+        &[
+            "subject",
+            "unknown_location",
+            "if_invalid",
+            "revoke_link",
+            "account_link",
+        ],
+    ),
+    (
+        "email.password_new",
+        &[
+            "subject",
+            "header",
+            "text",
+            "click_link",
+            "validity",
+            "expires",
+            "button_text",
+            "footer",
+        ],
+    ),
+    (
+        "email.reset",
+        &[
+            "subject",
+            "header",
+            "text",
+            "click_link",
+            "validity",
+            "expires",
+            "button_text",
+            "footer",
+            "button_text_request_new",
+        ],
+    ),
+    (
+        "email.reset_info",
+        &["subject", "expires_1", "expires_2", "update", "button_text"],
+    ),
+    (
+        "flow_error",
+        &[
+            "link_expired_title",
+            "link_expired_text",
+            "session_expired_title",
+            "session_expired_text",
+            "invalid_request_title",
+            "invalid_request_text",
+            "forbidden_title",
+            "forbidden_text",
+            "generic_title",
+            "generic_text",
+            "continue_label",
+            "retry_label",
+            "request_link_label",
+            "maintenance_title",
+            "maintenance_text",
+        ],
+    ),
+];
+
+/// The translation for a language which is not built into Rauthy, loaded from a
+/// `<tag>.json` file inside `i18n.custom_languages_dir`. Each missing key falls back to the
+/// built-in English value.
+#[derive(Debug, PartialEq)]
+pub struct LanguagePack {
+    tag: String,
+    /// `tag` with an uppercase region, like `pt-BR`, if it has one
+    tag_canonical: Option<String>,
+    /// Flat `section.key` paths for the frontend, which are merged over the built-in English
+    /// translation inside the browser.
+    ui: BTreeMap<String, String>,
+    /// Flat `email.<template>.<key>` and `flow_error.<key>` paths
+    server: BTreeMap<String, String>,
+    /// All server-side keys without a translation
+    missing: Vec<String>,
+}
+
+impl LanguagePack {
+    /// Parses and validates a language pack. Unknown or invalid keys are an error, while missing
+    /// keys fall back to English.
+    pub fn parse(tag: &str, json: &str) -> Result<Self, ErrorResponse> {
+        if !Self::is_valid_tag(tag) || Language::built_in(tag).is_some() {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!(
+                    "Invalid custom language tag '{tag}': must be a lowercase BCP 47 tag, which \
+                    is not a built-in language"
+                ),
+            ));
+        }
+
+        let Value::Object(sections) = serde_json::from_str::<Value>(json)? else {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("The language pack for '{tag}' must be a JSON object"),
+            ));
+        };
+
+        let mut ui = BTreeMap::new();
+        let mut server = BTreeMap::new();
+        for (section, value) in sections {
+            match section.as_str() {
+                "ui" => Self::flatten(None, value, &mut ui)?,
+                "email" | "flow_error" => Self::flatten(Some(section), value, &mut server)?,
+                _ => {
+                    return Err(ErrorResponse::new(
+                        ErrorResponseType::BadRequest,
+                        format!("Unknown section '{section}' in the language pack for '{tag}'"),
+                    ));
+                }
+            }
+        }
+
+        if let Some(key) = server.keys().find(|key| !Self::is_server_key(key)) {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("Unknown key '{key}' in the language pack for '{tag}'"),
+            ));
+        }
+
+        let missing = KEYS_SERVER
+            .iter()
+            .flat_map(|(section, keys)| keys.iter().map(move |key| format!("{section}.{key}")))
+            .filter(|path| !server.contains_key(path))
+            .collect();
+
+        let tag_canonical = tag.split_once('-').and_then(|(primary, region)| {
+            (region.len() == 2).then(|| format!("{primary}-{}", region.to_ascii_uppercase()))
+        });
+
+        Ok(Self {
+            tag: tag.to_string(),
+            tag_canonical,
+            ui,
+            server,
+            missing,
+        })
+    }
+
+    #[inline]
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    #[inline]
+    pub fn tag_canonical(&self) -> Option<&str> {
+        self.tag_canonical.as_deref()
+    }
+
+    /// The frontend translations as a flat JSON object, like `{ "common.cancel": "..." }`.
+    pub fn ui_json(&self) -> String {
+        serde_json::to_string(&self.ui).unwrap()
+    }
+
+    /// The server-side translation for the given `key`, like `email.reset.subject`.
+    #[inline]
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.server.get(key).map(String::as_str)
+    }
+
+    /// Lowercase BCP 47 tags with a 2-3 letter primary language, like `pt` or `pt-br`.
+    fn is_valid_tag(tag: &str) -> bool {
+        let mut subtags = tag.split('-');
+        let primary = subtags.next().unwrap_or_default();
+        (2..=3).contains(&primary.len())
+            && primary.bytes().all(|b| b.is_ascii_lowercase())
+            && subtags.all(|sub| {
+                (1..=8).contains(&sub.len())
+                    && sub
+                        .bytes()
+                        .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit())
+            })
+    }
+
+    fn is_server_key(path: &str) -> bool {
+        path.rsplit_once('.').is_some_and(|(section, key)| {
+            KEYS_SERVER
+                .iter()
+                .any(|(s, keys)| *s == section && keys.contains(&key))
+        })
+    }
+
+    fn flatten(
+        prefix: Option<String>,
+        value: Value,
+        out: &mut BTreeMap<String, String>,
+    ) -> Result<(), ErrorResponse> {
+        match (prefix, value) {
+            (Some(path), Value::String(s)) => {
+                out.insert(path, s);
+            }
+            (prefix, Value::Object(map)) => {
+                for (key, value) in map {
+                    let path = match &prefix {
+                        Some(prefix) => format!("{prefix}.{key}"),
+                        None => key,
+                    };
+                    Self::flatten(Some(path), value, out)?;
+                }
+            }
+            (prefix, _) => {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    format!(
+                        "Invalid value for '{}' in a language pack: only strings are allowed",
+                        prefix.unwrap_or_default()
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// All custom languages loaded at startup.
+pub struct LanguagePacks;
+
+impl LanguagePacks {
+    /// Loads all language packs from `i18n.custom_languages_dir`. Panics on any invalid file,
+    /// the same way as for any other invalid config.
+    pub fn init() {
+        let packs = match &RauthyConfig::get().vars.i18n.custom_languages_dir {
+            Some(dir) => Self::load_dir(dir).unwrap_or_else(|err| {
+                panic!("Cannot load custom languages from '{dir}': {}", err.message)
+            }),
+            None => Vec::new(),
+        };
+        LANGUAGE_PACKS
+            .set(packs)
+            .expect("LanguagePacks::init() must only be called once");
+    }
+
+    fn load_dir(dir: &str) -> Result<Vec<LanguagePack>, ErrorResponse> {
+        let mut packs = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            let tag = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or_default();
+            let json = fs::read_to_string(&path)?;
+            packs.push(LanguagePack::parse(tag, &json)?);
+        }
+        packs.sort_by(|a, b| a.tag.cmp(&b.tag));
+        Ok(packs)
+    }
+
+    /// Logs all loaded languages and their missing keys. Must be called after `init()` as soon
+    /// as logging is set up.
+    pub fn log_status() {
+        for pack in Self::all() {
+            info!(
+                "Loaded custom language '{}' with {} UI translations",
+                pack.tag,
+                pack.ui.len()
+            );
+            if !pack.missing.is_empty() {
+                warn!(
+                    "Custom language '{}' is missing {} translations, which fall back to \
+                    English: {}",
+                    pack.tag,
+                    pack.missing.len(),
+                    pack.missing.join(", ")
+                );
+            }
+        }
+    }
+
+    /// Empty before `init()`.
+    #[inline]
+    pub fn all() -> &'static [LanguagePack] {
+        LANGUAGE_PACKS.get().map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Finds a pack case-insensitively, accepting `_` as separator as well.
+    pub fn find(tag: &str) -> Option<&'static LanguagePack> {
+        let packs = Self::all();
+        if packs.is_empty() {
+            return None;
+        }
+        let tag = tag.to_ascii_lowercase().replace('_', "-");
+        packs.iter().find(|p| p.tag == tag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::email::i18n::change_info_new::I18nEmailChangeInfoNew;
+    use crate::email::i18n::change_info_old::I18nEmailChangeInfoOld;
+    use crate::email::i18n::confirm_change::I18nEmailConfirmChange;
+    use crate::email::i18n::confirm_change_html::I18nEmailConfirmChangeHtml;
+    use crate::email::i18n::login_location::I18nEmailLoginLocation;
+    use crate::email::i18n::reset_info::I18nEmailResetInfo;
+    use crate::html::i18n::flow_error::I18nFlowError;
+    use serde::Serialize;
+    use std::env;
+
+    const PARTIAL: &str = r#"{
+        "ui": {
+            "lang": "pt-PT",
+            "common": { "cancel": "Cancelar" }
+        },
+        "email": {
+            "reset_info": { "subject": "A palavra-passe vai expirar" }
+        },
+        "flow_error": { "retry_label": "Tentar novamente" }
+    }"#;
+
+    /// Every test shares the same global packs.
+    fn init_packs() {
+        LANGUAGE_PACKS.get_or_init(|| {
+            // every server key translated with its own path
+            let mut email = serde_json::Map::new();
+            let mut flow_error = Value::Null;
+            for (section, keys) in KEYS_SERVER.iter() {
+                let values = keys
+                    .iter()
+                    .map(|key| (key.to_string(), Value::from(format!("{section}.{key}"))))
+                    .collect::<serde_json::Map<_, _>>();
+                match section.strip_prefix("email.") {
+                    Some(tpl) => {
+                        email.insert(tpl.to_string(), Value::Object(values));
+                    }
+                    None => flow_error = Value::Object(values),
+                }
+            }
+            let full = serde_json::json!({ "email": email, "flow_error": flow_error });
+
+            vec![
+                LanguagePack::parse("pt-pt", PARTIAL).unwrap(),
+                LanguagePack::parse("xx-test", &full.to_string()).unwrap(),
+            ]
+        });
+    }
+
+    fn to_map<T: Serialize>(value: T) -> BTreeMap<String, Option<String>> {
+        serde_json::from_value(serde_json::to_value(value).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_language_pack_parse() {
+        let pack = LanguagePack::parse("pt-pt", PARTIAL).unwrap();
+        assert_eq!(pack.tag(), "pt-pt");
+        assert_eq!(pack.tag_canonical(), Some("pt-PT"));
+        assert_eq!(
+            pack.ui_json(),
+            r#"{"common.cancel":"Cancelar","lang":"pt-PT"}"#
+        );
+        assert_eq!(
+            pack.get("email.reset_info.subject"),
+            Some("A palavra-passe vai expirar")
+        );
+        assert_eq!(pack.get("email.reset_info.update"), None);
+        assert!(
+            pack.missing
+                .contains(&"email.reset_info.update".to_string())
+        );
+        assert!(
+            !pack
+                .missing
+                .contains(&"email.reset_info.subject".to_string())
+        );
+
+        // built-in, invalid tags
+        assert!(LanguagePack::parse("de", "{}").is_err());
+        assert!(LanguagePack::parse("zh-hans", "{}").is_err());
+        assert!(LanguagePack::parse("pt-BR", "{}").is_err());
+        assert!(LanguagePack::parse("portuguese", "{}").is_err());
+        assert!(LanguagePack::parse("../pt", "{}").is_err());
+
+        // unknown sections and keys, non-string values
+        assert!(LanguagePack::parse("pt", r#"{"admin":{}}"#).is_err());
+        assert!(LanguagePack::parse("pt", r#"{"email":{"reset":{"typo":"x"}}}"#).is_err());
+        assert!(LanguagePack::parse("pt", r#"{"ui":{"common":{"cancel":1}}}"#).is_err());
+        assert!(LanguagePack::parse("pt", r#"{"ui":"x"}"#).is_err());
+        assert!(LanguagePack::parse("pt", "[]").is_err());
+    }
+
+    #[test]
+    fn test_language_pack_load_dir() {
+        let dir = env::temp_dir().join(format!("rauthy_i18n_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("pt-pt.json"), PARTIAL).unwrap();
+        fs::write(dir.join("eo.json"), "{}").unwrap();
+        fs::write(dir.join("README.md"), "ignored").unwrap();
+
+        let packs = LanguagePacks::load_dir(dir.to_str().unwrap()).unwrap();
+        assert_eq!(
+            packs.iter().map(|p| p.tag()).collect::<Vec<_>>(),
+            vec!["eo", "pt-pt"]
+        );
+
+        fs::write(dir.join("DE.json"), "{}").unwrap();
+        assert!(LanguagePacks::load_dir(dir.to_str().unwrap()).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_custom_language_fallback() {
+        init_packs();
+
+        let lang = Language::from("pt-PT");
+        assert_eq!(lang.as_str(), "pt-pt");
+        assert!(!lang.is_built_in());
+        assert_eq!(Language::from("pt_pt"), lang);
+        assert_eq!(Language::from_bcp47("PT-pt"), lang);
+        assert_eq!(Language::from_ui_locales("es pt-PT de"), Some(lang));
+        assert!(Language::all().any(|l| l == lang));
+        // unknown languages still fall back to the default
+        assert_eq!(Language::from("pt-br"), Language::En);
+
+        // translated keys are used, each missing key falls back to English
+        let en = I18nEmailResetInfo::build(&Language::En);
+        let pt = I18nEmailResetInfo::build(&lang);
+        assert_eq!(pt.subject, "A palavra-passe vai expirar");
+        assert_eq!(pt.expires_1, en.expires_1);
+        assert_eq!(pt.expires_2, en.expires_2);
+        assert_eq!(pt.update, en.update);
+        assert_eq!(pt.button_text, en.button_text);
+
+        let en = I18nFlowError::build(&Language::En);
+        let pt = I18nFlowError::build(&lang);
+        assert_eq!(pt.retry_label, "Tentar novamente");
+        assert_eq!(pt.generic_title, en.generic_title);
+
+        let en = I18nEmailConfirmChange::build(&Language::En);
+        let pt = I18nEmailConfirmChange::build(&lang);
+        assert_eq!(pt.subject, en.subject);
+        assert_eq!(pt.msg, en.msg);
+    }
+
+    #[test]
+    fn test_custom_language_keys() {
+        init_packs();
+        let lang = Language::from("xx-test");
+
+        // Makes sure each translation reads exactly the keys from `KEYS_SERVER`.
+        // The templates from the config cannot be checked without a config.
+        let built = [
+            (
+                "email.change_info_new",
+                to_map(I18nEmailChangeInfoNew::build(&lang)),
+            ),
+            (
+                "email.change_info_old",
+                to_map(I18nEmailChangeInfoOld::build(&lang)),
+            ),
+            (
+                "email.confirm_change",
+                to_map(I18nEmailConfirmChange::build(&lang)),
+            ),
+            (
+                "email.confirm_change_html",
+                to_map(I18nEmailConfirmChangeHtml::build(&lang)),
+            ),
+            (
+                "email.login_location",
+                to_map(I18nEmailLoginLocation::build(&lang)),
+            ),
+            ("email.reset_info", to_map(I18nEmailResetInfo::build(&lang))),
+            ("flow_error", to_map(I18nFlowError::build(&lang))),
+        ];
+        for (section, values) in built {
+            let (_, keys) = KEYS_SERVER.iter().find(|(s, _)| *s == section).unwrap();
+            assert_eq!(values.len(), keys.len(), "{section}");
+            for (key, value) in values {
+                assert_eq!(value, Some(format!("{section}.{key}")));
+            }
+        }
+    }
+}
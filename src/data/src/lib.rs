@@ -5,6 +5,9 @@ use actix_web::http::header::{HeaderName, HeaderValue};
 use std::fmt::{Display, Formatter};
 
 pub mod api_cookie;
+pub mod cache_layer;
+pub mod cache_warmup;
+pub mod config_check;
 pub mod database;
 pub mod email;
 pub mod entity;
@@ -12,11 +15,30 @@ pub mod events;
 pub mod html;
 pub mod ipgeo;
 pub mod language;
+pub mod language_pack;
+pub mod metrics;
 pub mod migration;
+pub mod pii;
 pub mod rauthy_config;
+pub mod reports;
+pub mod secrets_migration;
 pub mod temp_migrations;
 pub mod vault_config;
 
+/// Initializes the static encryption keys for tests. They can only be initialized once per test
+/// binary, which is why all tests must share the same set. `test` is the active key, while
+/// `test_old` only exists for key migration tests.
+#[cfg(test)]
+pub(crate) fn init_test_enc_keys() {
+    static INIT_KEYS: std::sync::Once = std::sync::Once::new();
+
+    INIT_KEYS.call_once(|| {
+        let mut keys = cryptr::EncKeys::generate_with_id("test_old".to_string()).unwrap();
+        keys.append_new_random_with_id("test".to_string()).unwrap();
+        keys.init().unwrap();
+    });
+}
+
 pub enum AuthStep {
     LoggedIn(AuthStepLoggedIn),
     AwaitToSAccept(AwaitToSAccept),
@@ -24,6 +46,18 @@ pub enum AuthStep {
     ProviderLink,
 }
 
+impl AuthStep {
+    /// The session, if its id has been rotated during this step and new cookies must be set.
+    pub fn rotated_session(&self) -> Option<&Session> {
+        match self {
+            Self::LoggedIn(step) => step.rotated_session.as_ref(),
+            Self::AwaitToSAccept(step) => step.rotated_session.as_ref(),
+            Self::AwaitWebauthn(step) => step.rotated_session.as_ref(),
+            Self::ProviderLink => None,
+        }
+    }
+}
+
 pub struct AuthStepLoggedIn {
     pub user_id: String,
     pub email: String,
@@ -31,6 +65,7 @@ pub struct AuthStepLoggedIn {
     pub header_csrf: (HeaderName, HeaderValue),
     pub header_origin: Option<(HeaderName, HeaderValue)>,
     pub needs_user_update: bool,
+    pub rotated_session: Option<Session>,
 }
 
 pub struct AwaitToSAccept {
@@ -38,6 +73,7 @@ pub struct AwaitToSAccept {
     pub header_csrf: (HeaderName, HeaderValue),
     pub header_origin: Option<(HeaderName, HeaderValue)>,
     pub user_id: String,
+    pub rotated_session: Option<Session>,
 }
 
 pub struct AuthStepAwaitWebauthn {
@@ -48,6 +84,7 @@ pub struct AuthStepAwaitWebauthn {
     pub email: String,
     pub exp: u64,
     pub session: Session,
+    pub rotated_session: Option<Session>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
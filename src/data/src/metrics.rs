@@ -0,0 +1,145 @@
+use prometheus::{IntCounter, IntCounterVec, IntGauge, Opts, Registry};
+use rauthy_common::password_hasher;
+use std::sync::LazyLock;
+
+// Magic Links live inside the database. These gauges are updated by the maintenance
+// scheduler on each node, so they always reflect the cluster-wide state.
+
+pub static MAGIC_LINKS_ACTIVE: LazyLock<IntGauge> = LazyLock::new(|| {
+    IntGauge::new(
+        "rauthy_magic_links_active",
+        "Magic Links which are neither expired nor used",
+    )
+    .unwrap()
+});
+pub static MAGIC_LINKS_EXPIRED: LazyLock<IntGauge> = LazyLock::new(|| {
+    IntGauge::new(
+        "rauthy_magic_links_expired",
+        "Expired or used Magic Links waiting for cleanup",
+    )
+    .unwrap()
+});
+pub static MAGIC_LINKS_EXPIRED_ATTEMPTED: LazyLock<IntGauge> = LazyLock::new(|| {
+    IntGauge::new(
+        "rauthy_magic_links_expired_attempted",
+        "Expired Magic Links which have been opened after their expiry",
+    )
+    .unwrap()
+});
+
+// Authorization Codes and upstream Auth Provider callbacks only live inside the cache and
+// are cleaned up by their TTL. They can't be listed, which is why we only count them
+// per node. `created - consumed` gives an idea about how many are in flight.
+
+pub static AUTH_CODES_CREATED: LazyLock<IntCounter> = LazyLock::new(|| {
+    IntCounter::new(
+        "rauthy_auth_codes_created_total",
+        "Authorization Codes created on this node",
+    )
+    .unwrap()
+});
+pub static AUTH_CODES_CONSUMED: LazyLock<IntCounter> = LazyLock::new(|| {
+    IntCounter::new(
+        "rauthy_auth_codes_consumed_total",
+        "Authorization Codes consumed on this node",
+    )
+    .unwrap()
+});
+pub static AUTH_CODES_EXPIRED_ATTEMPTS: LazyLock<IntCounter> = LazyLock::new(|| {
+    IntCounter::new(
+        "rauthy_auth_codes_expired_attempts_total",
+        "Authorization Codes used after their expiry on this node",
+    )
+    .unwrap()
+});
+pub static AUTH_CALLBACKS_CREATED: LazyLock<IntCounter> = LazyLock::new(|| {
+    IntCounter::new(
+        "rauthy_auth_provider_callbacks_created_total",
+        "Upstream Auth Provider callbacks created on this node",
+    )
+    .unwrap()
+});
+pub static AUTH_CALLBACKS_CONSUMED: LazyLock<IntCounter> = LazyLock::new(|| {
+    IntCounter::new(
+        "rauthy_auth_provider_callbacks_consumed_total",
+        "Upstream Auth Provider callbacks consumed on this node",
+    )
+    .unwrap()
+});
+pub static AUTH_CALLBACKS_EXPIRED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "rauthy_auth_provider_callbacks_expired_total",
+            "Upstream Auth Provider callbacks which timed out before the user came back on this node",
+        ),
+        &["provider"],
+    )
+    .unwrap()
+});
+
+// E-Mails are sent from a queue in the background. Failures for unauthenticated flows can't be
+// returned to the user without making user enumeration possible, so they are counted instead.
+
+pub static EMAILS_FAILED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "rauthy_emails_failed_total",
+            "E-Mails which could not be queued or sent even after retrying on this node",
+        ),
+        &["type"],
+    )
+    .unwrap()
+});
+
+// Counters for the instrumented cache layer per `CacheName`. A rising error rate usually means
+// that the HA cache has lost its quorum.
+
+pub static CACHE_HITS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    IntCounterVec::new(
+        Opts::new("rauthy_cache_hits_total", "Cache hits on this node"),
+        &["cache"],
+    )
+    .unwrap()
+});
+pub static CACHE_MISSES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "rauthy_cache_misses_total",
+            "Cache misses on this node, including failed reads in degradation mode",
+        ),
+        &["cache"],
+    )
+    .unwrap()
+});
+pub static CACHE_ERRORS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "rauthy_cache_errors_total",
+            "Failed cache operations on this node",
+        ),
+        &["cache", "op"],
+    )
+    .unwrap()
+});
+
+/// Registers all Rauthy specific metrics with the given registry.
+pub fn register(registry: &Registry) -> prometheus::Result<()> {
+    registry.register(Box::new(MAGIC_LINKS_ACTIVE.clone()))?;
+    registry.register(Box::new(MAGIC_LINKS_EXPIRED.clone()))?;
+    registry.register(Box::new(MAGIC_LINKS_EXPIRED_ATTEMPTED.clone()))?;
+    registry.register(Box::new(AUTH_CODES_CREATED.clone()))?;
+    registry.register(Box::new(AUTH_CODES_CONSUMED.clone()))?;
+    registry.register(Box::new(AUTH_CODES_EXPIRED_ATTEMPTS.clone()))?;
+    registry.register(Box::new(AUTH_CALLBACKS_CREATED.clone()))?;
+    registry.register(Box::new(AUTH_CALLBACKS_CONSUMED.clone()))?;
+    registry.register(Box::new(AUTH_CALLBACKS_EXPIRED.clone()))?;
+    registry.register(Box::new(EMAILS_FAILED.clone()))?;
+    registry.register(Box::new(CACHE_HITS.clone()))?;
+    registry.register(Box::new(CACHE_MISSES.clone()))?;
+    registry.register(Box::new(CACHE_ERRORS.clone()))?;
+    registry.register(Box::new(password_hasher::HASH_QUEUE_DEPTH.clone()))?;
+    registry.register(Box::new(password_hasher::HASH_QUEUE_WAIT.clone()))?;
+    registry.register(Box::new(password_hasher::HASH_DURATION.clone()))?;
+    registry.register(Box::new(password_hasher::HASH_REJECTED.clone()))?;
+    Ok(())
+}
@@ -2,6 +2,8 @@ use crate::database::DB;
 use crate::entity::clients::Client;
 use crate::rauthy_config::RauthyConfig;
 use deadpool_postgres::GenericClient;
+use rauthy_api_types::clients::ClientType;
+use rauthy_common::constants::RESPONSE_TYPES_SUPPORTED;
 use rauthy_common::is_hiqlite;
 use rauthy_error::ErrorResponse;
 use tracing::debug;
@@ -64,6 +66,12 @@ pub async fn anti_lockout() -> Result<(), ErrorResponse> {
         claims_at_root: false,
         allowed_resources: None,
         default_aud: None,
+        claims_emission: None,
+        refresh_token_binding: None,
+        client_type: ClientType::Web.as_str().to_string(),
+        allowed_roles: None,
+        allowed_groups: None,
+        response_types: RESPONSE_TYPES_SUPPORTED.join(","),
     };
     debug!(client = ?rauthy, "Rauthy client anti-lockout");
 
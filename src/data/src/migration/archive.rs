@@ -0,0 +1,964 @@
+use crate::database::{Cache, DB};
+use crate::entity::auth_provider_group_mappings::AuthProviderGroupMapping;
+use crate::entity::auth_provider_role_mappings::AuthProviderRoleMapping;
+use crate::entity::auth_providers::{AuthProvider, AuthProviderTemplate};
+use crate::entity::clients::Client;
+use crate::entity::db_version::DbVersion;
+use crate::entity::groups::Group;
+use crate::entity::roles::Role;
+use crate::entity::scopes::Scope;
+use crate::entity::user_attr::{UserAttrConfigEntity, UserAttrValueEntity};
+use crate::entity::users::User;
+use crate::entity::users_values::UserValues;
+use crate::entity::webauthn::PasskeyEntity;
+use crate::migration::inserts;
+use crate::pii;
+use actix_web::web;
+use chacha20poly1305::aead::{Aead, Payload};
+use chacha20poly1305::{Key, KeyInit, XChaCha20Poly1305, XNonce};
+use chrono::Utc;
+use cryptr::{EncKeys, EncValue};
+use futures::SinkExt;
+use futures::channel::mpsc;
+use hiqlite::macros::params;
+use rauthy_api_types::migration::{
+    MigrationConflictStrategy, MigrationImportResponse, MigrationSectionSummary,
+};
+use rauthy_common::is_hiqlite;
+use rauthy_common::utils::{base64_decode, base64_encode, get_rand_bytes};
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+/// The current version of the migration archive format
+pub const ARCHIVE_VERSION: u16 = 1;
+/// The minimum length for the passphrase of a migration archive
+pub const ARCHIVE_PASSPHRASE_MIN_LEN: usize = 16;
+
+/// The first line of each archive. It is not encrypted, so a wrong version can be rejected
+/// before the (expensive) key derivation.
+#[derive(Debug, Serialize, Deserialize)]
+struct ArchiveHeader {
+    version: u16,
+    db_version: String,
+    created: i64,
+    salt: String,
+}
+
+/// Each line after the header contains exactly one encrypted `ArchiveSection`.
+#[derive(Debug, Serialize, Deserialize)]
+struct ArchiveChunk {
+    idx: u32,
+    nonce: String,
+    data: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ArchiveUser {
+    user: User,
+    values: Option<UserValues>,
+    passkeys: Vec<PasskeyEntity>,
+    attrs: Vec<UserAttrValueEntity>,
+}
+
+/// All `secret`s inside `AuthProviders` and `Clients` are stored in cleartext, because the
+/// target instance will most likely use different encryption keys.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "section", content = "data", rename_all = "snake_case")]
+enum ArchiveSection {
+    Scopes(Vec<Scope>),
+    Roles(Vec<Role>),
+    Groups(Vec<Group>),
+    UserAttrConfig(Vec<UserAttrConfigEntity>),
+    AuthProviders(Vec<AuthProvider>),
+    AuthProviderGroupMappings(Vec<AuthProviderGroupMapping>),
+    AuthProviderRoleMappings(Vec<AuthProviderRoleMapping>),
+    Clients(Vec<Client>),
+    Users(Vec<ArchiveUser>),
+    /// Always the last section. Makes it possible to detect truncated archives.
+    End {
+        chunks: u32,
+    },
+}
+
+impl ArchiveSection {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Scopes(_) => "scopes",
+            Self::Roles(_) => "roles",
+            Self::Groups(_) => "groups",
+            Self::UserAttrConfig(_) => "user_attr_config",
+            Self::AuthProviders(_) => "auth_providers",
+            Self::AuthProviderGroupMappings(_) => "auth_provider_group_mappings",
+            Self::AuthProviderRoleMappings(_) => "auth_provider_role_mappings",
+            Self::Clients(_) => "clients",
+            Self::Users(_) => "users",
+            Self::End { .. } => "end",
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Self::Scopes(v) => v.len(),
+            Self::Roles(v) => v.len(),
+            Self::Groups(v) => v.len(),
+            Self::UserAttrConfig(v) => v.len(),
+            Self::AuthProviders(v) => v.len(),
+            Self::AuthProviderGroupMappings(v) => v.len(),
+            Self::AuthProviderRoleMappings(v) => v.len(),
+            Self::Clients(v) => v.len(),
+            Self::Users(v) => v.len(),
+            Self::End { .. } => 0,
+        }
+    }
+}
+
+/// Also used for the secrets inside the auth provider export.
+pub(crate) struct ArchiveCipher {
+    cipher: XChaCha20Poly1305,
+    pub(crate) salt: [u8; 16],
+}
+
+impl ArchiveCipher {
+    pub(crate) async fn derive(passphrase: String, salt: [u8; 16]) -> Result<Self, ErrorResponse> {
+        let key = web::block(move || {
+            let params = argon2::Params::new(65536, 3, 2, Some(32))?;
+            let mut key = [0u8; 32];
+            argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params)
+                .hash_password_into(passphrase.as_bytes(), &salt, &mut key)?;
+            Ok::<_, ErrorResponse>(key)
+        })
+        .await??;
+
+        Ok(Self::from_key(&key, salt))
+    }
+
+    fn from_key(key: &[u8; 32], salt: [u8; 16]) -> Self {
+        Self {
+            cipher: XChaCha20Poly1305::new(Key::from_slice(key)),
+            salt,
+        }
+    }
+
+    /// The chunk index is part of the AAD, which prevents re-ordering and mixing chunks from
+    /// different archives.
+    fn aad(&self, idx: u32) -> [u8; 20] {
+        let mut aad = [0u8; 20];
+        aad[..16].copy_from_slice(&self.salt);
+        aad[16..].copy_from_slice(&idx.to_be_bytes());
+        aad
+    }
+
+    /// Returns the base64 encoded `(nonce, data)`.
+    pub(crate) fn encrypt(
+        &self,
+        idx: u32,
+        plain: &[u8],
+    ) -> Result<(String, String), ErrorResponse> {
+        let nonce = get_rand_bytes::<24>();
+        let data = self.cipher.encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: plain,
+                aad: &self.aad(idx),
+            },
+        )?;
+
+        Ok((base64_encode(&nonce), base64_encode(&data)))
+    }
+
+    pub(crate) fn decrypt(
+        &self,
+        idx: u32,
+        nonce: &str,
+        data: &str,
+    ) -> Result<Vec<u8>, ErrorResponse> {
+        let nonce = base64_decode(nonce)?;
+        if nonce.len() != 24 {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Invalid archive chunk nonce",
+            ));
+        }
+        let data = base64_decode(data)?;
+
+        self.cipher
+            .decrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: &data,
+                    aad: &self.aad(idx),
+                },
+            )
+            .map_err(|_| {
+                ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    "Cannot decrypt the archive - wrong passphrase or corrupted data",
+                )
+            })
+    }
+
+    fn seal(&self, idx: u32, section: &ArchiveSection) -> Result<String, ErrorResponse> {
+        let plain = serde_json::to_vec(section)?;
+        let (nonce, data) = self.encrypt(idx, &plain)?;
+
+        let chunk = ArchiveChunk { idx, nonce, data };
+        Ok(format!("{}\n", serde_json::to_string(&chunk)?))
+    }
+
+    fn open(&self, chunk: &ArchiveChunk) -> Result<ArchiveSection, ErrorResponse> {
+        let plain = self.decrypt(chunk.idx, &chunk.nonce, &chunk.data)?;
+        Ok(serde_json::from_slice(&plain)?)
+    }
+}
+
+pub(crate) fn validate_passphrase(passphrase: &str) -> Result<(), ErrorResponse> {
+    if passphrase.chars().count() < ARCHIVE_PASSPHRASE_MIN_LEN {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            format!("The passphrase must be at least {ARCHIVE_PASSPHRASE_MIN_LEN} characters long"),
+        ));
+    }
+    Ok(())
+}
+
+/// An archive can only be imported into the same minor version it was created with, and never
+/// into an older instance. The schema is the same for all patch versions.
+fn validate_header(header: &ArchiveHeader, local: &Version) -> Result<(), ErrorResponse> {
+    if header.version != ARCHIVE_VERSION {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            format!("Unsupported archive version: {}", header.version),
+        ));
+    }
+
+    let db_version = Version::parse(&header.db_version).map_err(|_| {
+        ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            format!("Invalid archive db_version: {}", header.db_version),
+        )
+    })?;
+    if db_version.major != local.major || db_version.minor != local.minor || &db_version > local {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            format!(
+                "The archive has been created with v{db_version}, which cannot be imported into \
+                v{local}"
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+fn secret_cleartext(secret: Option<Vec<u8>>) -> Result<Option<Vec<u8>>, ErrorResponse> {
+    match secret {
+        None => Ok(None),
+        Some(secret) => Ok(Some(EncValue::try_from(secret)?.decrypt()?.to_vec())),
+    }
+}
+
+fn secret_encrypted(secret: Option<Vec<u8>>) -> Result<Option<Vec<u8>>, ErrorResponse> {
+    match secret {
+        None => Ok(None),
+        Some(secret) => Ok(Some(EncValue::encrypt(&secret)?.into_bytes().to_vec())),
+    }
+}
+
+/// Writes an encrypted archive of all users, clients and everything they depend on, which can
+/// be imported into another instance. Sessions, tokens and events are not part of it.
+pub struct ArchiveExport {
+    cipher: ArchiveCipher,
+    tx: mpsc::Sender<String>,
+    idx: u32,
+    counts: Vec<(&'static str, usize)>,
+}
+
+impl ArchiveExport {
+    pub async fn new(passphrase: String, tx: mpsc::Sender<String>) -> Result<Self, ErrorResponse> {
+        validate_passphrase(&passphrase)?;
+        let cipher = ArchiveCipher::derive(passphrase, get_rand_bytes::<16>()).await?;
+
+        Ok(Self {
+            cipher,
+            tx,
+            idx: 0,
+            counts: Vec::with_capacity(8),
+        })
+    }
+
+    /// Sends the archive line by line and returns the amount of entries for each section.
+    pub async fn run(mut self) -> Result<Vec<(&'static str, usize)>, ErrorResponse> {
+        let header = ArchiveHeader {
+            version: ARCHIVE_VERSION,
+            db_version: DbVersion::app_version().to_string(),
+            created: Utc::now().timestamp(),
+            salt: base64_encode(&self.cipher.salt),
+        };
+        self.send(format!("{}\n", serde_json::to_string(&header)?))
+            .await?;
+
+        self.write(ArchiveSection::Scopes(Scope::find_all().await?))
+            .await?;
+        self.write(ArchiveSection::Roles(Role::find_all().await?))
+            .await?;
+        self.write(ArchiveSection::Groups(Group::find_all().await?))
+            .await?;
+        self.write(ArchiveSection::UserAttrConfig(
+            UserAttrConfigEntity::find_all().await?,
+        ))
+        .await?;
+
+        let mut providers = AuthProvider::find_all().await?;
+        let mut mappings = Vec::new();
+        let mut role_mappings = Vec::new();
+        for provider in providers.iter_mut() {
+            provider.secret = secret_cleartext(provider.secret.take())?;
+            mappings.extend(AuthProviderGroupMapping::find_for_provider(&provider.id).await?);
+            role_mappings.extend(AuthProviderRoleMapping::find_for_provider(&provider.id).await?);
+        }
+        self.write(ArchiveSection::AuthProviders(providers)).await?;
+        self.write(ArchiveSection::AuthProviderGroupMappings(mappings))
+            .await?;
+        self.write(ArchiveSection::AuthProviderRoleMappings(role_mappings))
+            .await?;
+
+        // The `rauthy` client is created during the bootstrap of each instance and is
+        // tied to its public URL.
+        let mut clients = Client::find_all()
+            .await?
+            .into_iter()
+            .filter(|c| c.id != "rauthy")
+            .collect::<Vec<_>>();
+        for client in clients.iter_mut() {
+            client.secret = secret_cleartext(client.secret.take())?;
+            client.secret_kid = None;
+        }
+        self.write(ArchiveSection::Clients(clients)).await?;
+
+        // Users are paginated by id to keep the memory usage and chunk size predictable.
+        let mut last_id = String::default();
+        loop {
+            let sql = "SELECT * FROM users WHERE id > $1 ORDER BY id LIMIT 100";
+            let users: Vec<User> = if is_hiqlite() {
+                DB::hql().query_as(sql, params!(last_id.clone())).await?
+            } else {
+                DB::pg_query(sql, &[&last_id], 100).await?
+            };
+            let Some(last) = users.last() else {
+                break;
+            };
+            last_id = last.id.clone();
+
+            let mut entries = Vec::with_capacity(users.len());
+            for user in users {
+                entries.push(ArchiveUser {
+                    values: UserValues::find(&user.id).await?,
+                    passkeys: PasskeyEntity::find_for_user(&user.id).await?,
+                    attrs: UserAttrValueEntity::find_for_user(&user.id).await?,
+                    user,
+                });
+            }
+            self.write(ArchiveSection::Users(entries)).await?;
+        }
+
+        let chunks = self.idx + 1;
+        self.write(ArchiveSection::End { chunks }).await?;
+
+        Ok(self.counts)
+    }
+
+    async fn write(&mut self, section: ArchiveSection) -> Result<(), ErrorResponse> {
+        let name = section.name();
+        let len = section.len();
+
+        let line = self.cipher.seal(self.idx, &section)?;
+        self.send(line).await?;
+        self.idx += 1;
+
+        if let ArchiveSection::End { .. } = section {
+            return Ok(());
+        }
+        match self.counts.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, count)) => *count += len,
+            None => self.counts.push((name, len)),
+        }
+
+        Ok(())
+    }
+
+    async fn send(&mut self, line: String) -> Result<(), ErrorResponse> {
+        self.tx.send(line).await.map_err(|_| {
+            ErrorResponse::new(
+                ErrorResponseType::Connection,
+                "The receiver for the migration archive has been closed",
+            )
+        })
+    }
+}
+
+/// Imports an archive created by `ArchiveExport` line by line.
+///
+/// The import is NOT transactional. If it fails in the middle, everything that has been
+/// processed so far stays in the database. Overwriting an auth provider unlinks all local users
+/// which are not part of the archive from it.
+pub struct ArchiveImport {
+    passphrase: Option<String>,
+    strategy: MigrationConflictStrategy,
+    cipher: Option<ArchiveCipher>,
+    next_idx: u32,
+    finished: bool,
+    sections: Vec<MigrationSectionSummary>,
+}
+
+impl ArchiveImport {
+    pub fn new(
+        passphrase: String,
+        strategy: MigrationConflictStrategy,
+    ) -> Result<Self, ErrorResponse> {
+        validate_passphrase(&passphrase)?;
+
+        Ok(Self {
+            passphrase: Some(passphrase),
+            strategy,
+            cipher: None,
+            next_idx: 0,
+            finished: false,
+            sections: Vec::with_capacity(8),
+        })
+    }
+
+    /// Processes a single line of the archive. Lines must be passed in the original order.
+    pub async fn process_line(&mut self, line: &[u8]) -> Result<(), ErrorResponse> {
+        if self.finished {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Unexpected data after the end of the archive",
+            ));
+        }
+
+        let Some(cipher) = &self.cipher else {
+            return self.process_header(line).await;
+        };
+        let chunk = serde_json::from_slice::<ArchiveChunk>(line)?;
+        if chunk.idx != self.next_idx {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Archive chunks are missing or out of order",
+            ));
+        }
+        let section = cipher.open(&chunk)?;
+        self.next_idx += 1;
+
+        debug!(section = section.name(), len = section.len(), "Importing");
+        match section {
+            ArchiveSection::Scopes(scopes) => self.import_scopes(scopes).await,
+            ArchiveSection::Roles(roles) => self.import_roles(roles).await,
+            ArchiveSection::Groups(groups) => self.import_groups(groups).await,
+            ArchiveSection::UserAttrConfig(configs) => self.import_user_attr_config(configs).await,
+            ArchiveSection::AuthProviders(providers) => self.import_auth_providers(providers).await,
+            ArchiveSection::AuthProviderGroupMappings(mappings) => {
+                self.import_auth_provider_group_mappings(mappings).await
+            }
+            ArchiveSection::AuthProviderRoleMappings(mappings) => {
+                self.import_auth_provider_role_mappings(mappings).await
+            }
+            ArchiveSection::Clients(clients) => self.import_clients(clients).await,
+            ArchiveSection::Users(users) => self.import_users(users).await,
+            ArchiveSection::End { chunks } => {
+                if chunks != self.next_idx {
+                    return Err(ErrorResponse::new(
+                        ErrorResponseType::BadRequest,
+                        "The archive is incomplete",
+                    ));
+                }
+                self.finished = true;
+                Ok(())
+            }
+        }
+    }
+
+    /// Must be called after the last line to make sure that the archive was complete.
+    pub fn finish(self) -> Result<MigrationImportResponse, ErrorResponse> {
+        if !self.finished {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "The archive is incomplete",
+            ));
+        }
+
+        Ok(MigrationImportResponse {
+            sections: self.sections,
+        })
+    }
+
+    /// All inserts bypass the caches. This must always be called after an import, even if it
+    /// failed, because a partial import may have modified data already.
+    pub async fn invalidate_caches() -> Result<(), ErrorResponse> {
+        let client = DB::hql();
+        for cache in [
+            Cache::App,
+            Cache::ClientSecret,
+            Cache::Html,
+            Cache::LoginPageContext,
+            Cache::User,
+            Cache::Webauthn,
+        ] {
+            client.clear_cache(cache).await?;
+        }
+        AuthProviderTemplate::update_cache().await?;
+
+        Ok(())
+    }
+
+    async fn process_header(&mut self, line: &[u8]) -> Result<(), ErrorResponse> {
+        let header = serde_json::from_slice::<ArchiveHeader>(line)?;
+        validate_header(&header, &DbVersion::app_version())?;
+
+        let salt: [u8; 16] = base64_decode(&header.salt)?.try_into().map_err(|_| {
+            ErrorResponse::new(ErrorResponseType::BadRequest, "Invalid archive salt")
+        })?;
+        let passphrase = self.passphrase.take().unwrap_or_default();
+        self.cipher = Some(ArchiveCipher::derive(passphrase, salt).await?);
+
+        info!(
+            db_version = header.db_version,
+            created = header.created,
+            "Importing migration archive"
+        );
+        Ok(())
+    }
+
+    fn summary(&mut self, section: &str) -> &mut MigrationSectionSummary {
+        let idx = match self.sections.iter().position(|s| s.section == section) {
+            Some(idx) => idx,
+            None => {
+                self.sections.push(MigrationSectionSummary {
+                    section: section.to_string(),
+                    ..Default::default()
+                });
+                self.sections.len() - 1
+            }
+        };
+        &mut self.sections[idx]
+    }
+
+    /// Checks if the entry exists locally by either its `id` or unique `key` and resolves
+    /// possible conflicts with the configured strategy. Returns `true` if the entry from the
+    /// archive should be inserted.
+    async fn resolve(
+        &mut self,
+        table: &'static str,
+        key_col: &str,
+        id: &str,
+        key: &str,
+    ) -> Result<bool, ErrorResponse> {
+        let sql = format!("SELECT 1 FROM {table} WHERE id = $1 OR {key_col} = $2");
+        if !exists(&sql, id, key).await? {
+            self.summary(table).imported += 1;
+            return Ok(true);
+        }
+
+        match self.strategy {
+            MigrationConflictStrategy::Skip => {
+                self.summary(table).skipped += 1;
+                Ok(false)
+            }
+            MigrationConflictStrategy::Fail => Err(ErrorResponse::new(
+                ErrorResponseType::NotAccepted,
+                format!("Conflict in '{table}' for '{key}'"),
+            )),
+            MigrationConflictStrategy::Overwrite => {
+                if table == "users" {
+                    // Users need some manual cleanup, which is handled by `User::delete()`.
+                    if let Ok(user) = User::find(id.to_string()).await {
+                        user.delete().await?;
+                    }
+                    let sql = format!("SELECT id FROM users WHERE {key_col} = $1");
+                    let existing = if is_hiqlite() {
+                        DB::hql()
+                            .query_raw(sql, params!(key))
+                            .await?
+                            .pop()
+                            .map(|mut row| row.get::<String>("id"))
+                    } else {
+                        DB::pg_query_rows(&sql, &[&key], 1)
+                            .await?
+                            .pop()
+                            .map(|row| row.get::<_, String>("id"))
+                    };
+                    if let Some(existing_id) = existing
+                        && let Ok(user) = User::find(existing_id).await
+                    {
+                        user.delete().await?;
+                    }
+                } else {
+                    let sql = format!("DELETE FROM {table} WHERE id = $1 OR {key_col} = $2");
+                    if is_hiqlite() {
+                        DB::hql().execute(sql, params!(id, key)).await?;
+                    } else {
+                        DB::pg_execute(&sql, &[&id, &key]).await?;
+                    }
+                }
+
+                self.summary(table).overwritten += 1;
+                Ok(true)
+            }
+        }
+    }
+
+    async fn import_scopes(&mut self, scopes: Vec<Scope>) -> Result<(), ErrorResponse> {
+        let mut insert = Vec::with_capacity(scopes.len());
+        for scope in scopes {
+            if self
+                .resolve("scopes", "name", &scope.id, &scope.name)
+                .await?
+            {
+                insert.push(scope);
+            }
+        }
+        inserts::insert_scopes(insert).await
+    }
+
+    async fn import_roles(&mut self, roles: Vec<Role>) -> Result<(), ErrorResponse> {
+        let mut insert = Vec::with_capacity(roles.len());
+        for role in roles {
+            if self.resolve("roles", "name", &role.id, &role.name).await? {
+                insert.push(role);
+            }
+        }
+        inserts::insert_roles(insert).await
+    }
+
+    async fn import_groups(&mut self, groups: Vec<Group>) -> Result<(), ErrorResponse> {
+        let mut insert = Vec::with_capacity(groups.len());
+        for group in groups {
+            if self
+                .resolve("groups", "name", &group.id, &group.name)
+                .await?
+            {
+                insert.push(group);
+            }
+        }
+        inserts::insert_groups(insert).await
+    }
+
+    async fn import_user_attr_config(
+        &mut self,
+        configs: Vec<UserAttrConfigEntity>,
+    ) -> Result<(), ErrorResponse> {
+        let mut insert = Vec::with_capacity(configs.len());
+        for config in configs {
+            let sql = "SELECT 1 FROM user_attr_config WHERE name = $1 OR name = $2";
+            let exists = exists(sql, &config.name, &config.name).await?;
+            if !exists {
+                self.summary("user_attr_config").imported += 1;
+                insert.push(config);
+                continue;
+            }
+
+            // The config has no separate id and values reference it by name. This means we
+            // never need to delete it, and it is only updated in place.
+            match self.strategy {
+                MigrationConflictStrategy::Skip => self.summary("user_attr_config").skipped += 1,
+                MigrationConflictStrategy::Fail => {
+                    return Err(ErrorResponse::new(
+                        ErrorResponseType::NotAccepted,
+                        format!("Conflict in 'user_attr_config' for '{}'", config.name),
+                    ));
+                }
+                MigrationConflictStrategy::Overwrite => {
+                    let sql = r#"
+UPDATE user_attr_config
+SET "desc" = $1, default_value = $2, typ = $3, user_editable = $4, auto_populate_from_claim = $5
+WHERE name = $6"#;
+                    let typ = config.typ.as_ref().map(|t| t.as_str());
+                    if is_hiqlite() {
+                        DB::hql()
+                            .execute(
+                                sql,
+                                params!(
+                                    config.desc,
+                                    config.default_value,
+                                    typ,
+                                    config.user_editable,
+                                    config.auto_populate_from_claim,
+                                    config.name
+                                ),
+                            )
+                            .await?;
+                    } else {
+                        DB::pg_execute(
+                            sql,
+                            &[
+                                &config.desc,
+                                &config.default_value,
+                                &typ,
+                                &config.user_editable,
+                                &config.auto_populate_from_claim,
+                                &config.name,
+                            ],
+                        )
+                        .await?;
+                    }
+                    self.summary("user_attr_config").overwritten += 1;
+                }
+            }
+        }
+        inserts::insert_user_attr_config(insert).await
+    }
+
+    async fn import_auth_providers(
+        &mut self,
+        providers: Vec<AuthProvider>,
+    ) -> Result<(), ErrorResponse> {
+        let mut insert = Vec::with_capacity(providers.len());
+        for mut provider in providers {
+            if self
+                .resolve("auth_providers", "name", &provider.id, &provider.name)
+                .await?
+            {
+                provider.secret = secret_encrypted(provider.secret.take())?;
+                insert.push(provider);
+            }
+        }
+        inserts::insert_auth_providers(insert).await
+    }
+
+    async fn import_auth_provider_group_mappings(
+        &mut self,
+        mappings: Vec<AuthProviderGroupMapping>,
+    ) -> Result<(), ErrorResponse> {
+        let mut insert = Vec::with_capacity(mappings.len());
+        for mapping in mappings {
+            // The provider or group may have been skipped because of a conflict by name, which
+            // means it exists with a different id.
+            let provider_exists = exists(
+                "SELECT 1 FROM auth_providers WHERE id = $1 OR id = $2",
+                &mapping.provider_id,
+                &mapping.provider_id,
+            )
+            .await?;
+            let group_exists = exists(
+                "SELECT 1 FROM groups WHERE id = $1 OR id = $2",
+                &mapping.group_id,
+                &mapping.group_id,
+            )
+            .await?;
+            if !provider_exists || !group_exists {
+                self.summary("auth_provider_group_mappings").skipped += 1;
+                continue;
+            }
+
+            if self
+                .resolve(
+                    "auth_provider_group_mappings",
+                    "id",
+                    &mapping.id,
+                    &mapping.id,
+                )
+                .await?
+            {
+                insert.push(mapping);
+            }
+        }
+        inserts::insert_auth_provider_group_mappings(insert).await
+    }
+
+    async fn import_auth_provider_role_mappings(
+        &mut self,
+        mappings: Vec<AuthProviderRoleMapping>,
+    ) -> Result<(), ErrorResponse> {
+        let mut insert = Vec::with_capacity(mappings.len());
+        for mapping in mappings {
+            let provider_exists = exists(
+                "SELECT 1 FROM auth_providers WHERE id = $1 OR id = $2",
+                &mapping.provider_id,
+                &mapping.provider_id,
+            )
+            .await?;
+            let role_exists = exists(
+                "SELECT 1 FROM roles WHERE id = $1 OR id = $2",
+                &mapping.role_id,
+                &mapping.role_id,
+            )
+            .await?;
+            if !provider_exists || !role_exists {
+                self.summary("auth_provider_role_mappings").skipped += 1;
+                continue;
+            }
+
+            if self
+                .resolve(
+                    "auth_provider_role_mappings",
+                    "id",
+                    &mapping.id,
+                    &mapping.id,
+                )
+                .await?
+            {
+                insert.push(mapping);
+            }
+        }
+        inserts::insert_auth_provider_role_mappings(insert).await
+    }
+
+    async fn import_clients(&mut self, clients: Vec<Client>) -> Result<(), ErrorResponse> {
+        let mut insert = Vec::with_capacity(clients.len());
+        for mut client in clients {
+            if client.id == "rauthy" {
+                self.summary("clients").skipped += 1;
+                continue;
+            }
+
+            if self
+                .resolve("clients", "id", &client.id, &client.id)
+                .await?
+            {
+                if client.secret.is_some() {
+                    client.secret = secret_encrypted(client.secret.take())?;
+                    client.secret_kid = Some(EncKeys::get_static().enc_key_active.clone());
+                }
+                insert.push(client);
+            }
+        }
+        inserts::insert_clients(insert).await
+    }
+
+    async fn import_users(&mut self, users: Vec<ArchiveUser>) -> Result<(), ErrorResponse> {
+        let mut insert = Vec::with_capacity(users.len());
+        let mut values = Vec::with_capacity(users.len());
+        let mut passkeys = Vec::new();
+        let mut attrs = Vec::new();
+
+        for entry in users {
+            let mut user = entry.user;
+            // With encrypted PII, existing E-Mails can only be found by their hash.
+            let (key_col, key) = match pii::email_hash(&user.email) {
+                Some(hash) => ("email_hash", hash),
+                None => ("email", user.email.clone()),
+            };
+            if !self.resolve("users", key_col, &user.id, &key).await? {
+                continue;
+            }
+
+            // Pictures are not part of the archive.
+            user.picture_id = None;
+            if let Some(provider_id) = &user.auth_provider_id {
+                let linked = exists(
+                    "SELECT 1 FROM auth_providers WHERE id = $1 OR id = $2",
+                    provider_id,
+                    provider_id,
+                )
+                .await?;
+                if !linked {
+                    user.auth_provider_id = None;
+                    user.federation_uid = None;
+                }
+            }
+
+            insert.push(user);
+            values.extend(entry.values);
+            passkeys.extend(entry.passkeys);
+            attrs.extend(entry.attrs);
+        }
+
+        // Users, which are disabled in the archive, must not keep sessions or tokens that may
+        // still exist locally for the same id.
+        let disabled = insert
+            .iter()
+            .filter(|user| !user.enabled)
+            .map(|user| user.id.clone())
+            .collect::<Vec<_>>();
+
+        inserts::insert_users(insert).await?;
+        inserts::insert_users_values(values).await?;
+        inserts::insert_passkeys(passkeys).await?;
+        inserts::insert_user_attr_values(attrs).await?;
+
+        User::disable_bulk(&disabled).await?;
+
+        Ok(())
+    }
+}
+
+pub(super) async fn exists(sql: &str, id: &str, key: &str) -> Result<bool, ErrorResponse> {
+    let exists = if is_hiqlite() {
+        !DB::hql()
+            .query_raw(sql.to_string(), params!(id, key))
+            .await?
+            .is_empty()
+    } else {
+        !DB::pg_query_rows(sql, &[&id, &key], 1).await?.is_empty()
+    };
+    Ok(exists)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(db_version: &str) -> ArchiveHeader {
+        ArchiveHeader {
+            version: ARCHIVE_VERSION,
+            db_version: db_version.to_string(),
+            created: 0,
+            salt: String::default(),
+        }
+    }
+
+    #[test]
+    fn test_archive_header_versions() {
+        let local = Version::parse("0.35.2").unwrap();
+
+        assert!(validate_header(&header("0.35.2"), &local).is_ok());
+        assert!(validate_header(&header("0.35.0"), &local).is_ok());
+        assert!(validate_header(&header("0.35.3"), &local).is_err());
+        assert!(validate_header(&header("0.34.9"), &local).is_err());
+        assert!(validate_header(&header("0.36.0"), &local).is_err());
+        assert!(validate_header(&header("nope"), &local).is_err());
+
+        let mut h = header("0.35.2");
+        h.version = ARCHIVE_VERSION + 1;
+        assert!(validate_header(&h, &local).is_err());
+    }
+
+    #[test]
+    fn test_archive_cipher() {
+        let salt = [7u8; 16];
+        let cipher = ArchiveCipher::from_key(&[1u8; 32], salt);
+        let section = ArchiveSection::End { chunks: 3 };
+
+        let line = cipher.seal(2, &section).unwrap();
+        assert!(line.ends_with('\n'));
+        let chunk: ArchiveChunk = serde_json::from_str(line.trim_end()).unwrap();
+        assert!(matches!(
+            cipher.open(&chunk).unwrap(),
+            ArchiveSection::End { chunks: 3 }
+        ));
+
+        // chunks cannot be re-ordered
+        let moved = ArchiveChunk { idx: 1, ..chunk };
+        assert!(cipher.open(&moved).is_err());
+
+        // a wrong key or salt must fail
+        let chunk: ArchiveChunk = serde_json::from_str(line.trim_end()).unwrap();
+        assert!(
+            ArchiveCipher::from_key(&[2u8; 32], salt)
+                .open(&chunk)
+                .is_err()
+        );
+        assert!(
+            ArchiveCipher::from_key(&[1u8; 32], [8u8; 16])
+                .open(&chunk)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_passphrase_len() {
+        assert!(validate_passphrase("too short").is_err());
+        assert!(validate_passphrase("a long enough passphrase").is_ok());
+    }
+}
@@ -195,18 +195,6 @@ fn api_key_secret_plain(secret: ApiKeySecret) -> String {
 mod tests {
     use super::*;
     use crate::migration::bootstrap::generated_secrets::read_container;
-    use cryptr::EncKeys;
-    use std::sync::Once;
-
-    static INIT_KEYS: Once = Once::new();
-
-    fn init_keys() {
-        INIT_KEYS.call_once(|| {
-            let _ = EncKeys::generate_with_id("test".to_string())
-                .unwrap()
-                .init();
-        });
-    }
 
     fn test_path(name: &str) -> std::path::PathBuf {
         std::env::temp_dir().join(format!(
@@ -217,7 +205,7 @@ mod tests {
 
     #[tokio::test]
     async fn generated_api_key_token_is_retrievable_from_container() {
-        init_keys();
+        crate::init_test_enc_keys();
         let path = test_path("roundtrip");
         let _ = tokio::fs::remove_file(&path).await;
 
@@ -238,7 +226,7 @@ mod tests {
 
     #[tokio::test]
     async fn generated_api_key_container_write_failure_is_visible_before_insert() {
-        init_keys();
+        crate::init_test_enc_keys();
         let path = test_path("blocked-parent");
         let _ = tokio::fs::remove_file(&path).await;
         tokio::fs::write(&path, b"not a directory").await.unwrap();
@@ -349,18 +349,7 @@ fn tmp_path(path: &Path) -> Result<PathBuf, ErrorResponse> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use cryptr::EncKeys;
     use std::os::unix::fs::PermissionsExt;
-    use std::sync::Once;
-
-    static INIT_KEYS: Once = Once::new();
-
-    fn init_keys() {
-        INIT_KEYS.call_once(|| {
-            let keys = EncKeys::generate_with_id("test".to_string()).unwrap();
-            let _ = keys.init();
-        });
-    }
 
     fn test_path(name: &str) -> PathBuf {
         std::env::temp_dir().join(format!(
@@ -371,7 +360,7 @@ mod tests {
 
     #[tokio::test]
     async fn roundtrip_writes_mode_0600_and_reads_entries() {
-        init_keys();
+        crate::init_test_enc_keys();
         let path = test_path("roundtrip");
         let _ = tokio::fs::remove_file(&path).await;
 
@@ -398,7 +387,7 @@ mod tests {
 
     #[tokio::test]
     async fn garbage_file_fails_to_decrypt() {
-        init_keys();
+        crate::init_test_enc_keys();
         let path = test_path("garbage");
         tokio::fs::write(&path, b"not-a-valid-enc-container")
             .await
@@ -412,7 +401,7 @@ mod tests {
 
     #[tokio::test]
     async fn missing_container_error_names_path() {
-        init_keys();
+        crate::init_test_enc_keys();
         let path = test_path("missing");
         let _ = tokio::fs::remove_file(&path).await;
 
@@ -427,7 +416,7 @@ mod tests {
 
     #[tokio::test]
     async fn upsert_recovers_from_unreadable_existing_container() {
-        init_keys();
+        crate::init_test_enc_keys();
         let path = test_path("recover-garbage");
         tokio::fs::write(&path, b"not-a-valid-enc-container")
             .await
@@ -453,7 +442,7 @@ mod tests {
 
     #[tokio::test]
     async fn write_container_overwrites_stale_temp_file() {
-        init_keys();
+        crate::init_test_enc_keys();
         let path = test_path("stale-temp");
         let tmp = tmp_path(&path).unwrap();
         let _ = tokio::fs::remove_file(&path).await;
@@ -478,7 +467,7 @@ mod tests {
 
     #[tokio::test]
     async fn expired_container_is_rejected() {
-        init_keys();
+        crate::init_test_enc_keys();
         let path = test_path("expired");
         let _ = tokio::fs::remove_file(&path).await;
 
@@ -499,7 +488,7 @@ mod tests {
 
     #[tokio::test]
     async fn purge_removes_expired_file() {
-        init_keys();
+        crate::init_test_enc_keys();
         let path = test_path("purge");
         let _ = tokio::fs::remove_file(&path).await;
 
@@ -518,7 +507,7 @@ mod tests {
 
     #[tokio::test]
     async fn ttl_zero_disables_expiry_and_auto_purge() {
-        init_keys();
+        crate::init_test_enc_keys();
         let path = test_path("ttl-zero");
         let _ = tokio::fs::remove_file(&path).await;
 
@@ -31,6 +31,7 @@ pub async fn bootstrap() -> Result<(), ErrorResponse> {
         signature: JwkKeyPairAlg::RS256,
         enc_key_id: enc_key_active.clone(),
         jwk,
+        inactive: false,
     });
 
     // RS384
@@ -51,6 +52,7 @@ pub async fn bootstrap() -> Result<(), ErrorResponse> {
         signature: JwkKeyPairAlg::RS384,
         enc_key_id: enc_key_active.clone(),
         jwk,
+        inactive: false,
     });
 
     // RSA512
@@ -71,6 +73,7 @@ pub async fn bootstrap() -> Result<(), ErrorResponse> {
         signature: JwkKeyPairAlg::RS512,
         enc_key_id: enc_key_active.clone(),
         jwk,
+        inactive: false,
     });
 
     // Ed25519
@@ -84,6 +87,7 @@ pub async fn bootstrap() -> Result<(), ErrorResponse> {
         signature: JwkKeyPairAlg::EdDSA,
         enc_key_id: enc_key_active.clone(),
         jwk,
+        inactive: false,
     });
 
     for e in entities {
@@ -1,4 +1,6 @@
 use crate::database::DB;
+use crate::entity::users::User;
+use crate::pii;
 use crate::rauthy_config::RauthyConfig;
 use argon2::password_hash::SaltString;
 use argon2::password_hash::rand_core::OsRng;
@@ -79,10 +81,17 @@ WHERE email = 'admin@localhost'"#;
     // for new instances is impossible to guess.
     let new_id = new_store_id();
     if is_hiqlite() {
-        DB::hql().execute(sql, params!(new_id, email, hash)).await?;
+        DB::hql()
+            .execute(sql, params!(new_id.clone(), email, hash))
+            .await?;
     } else {
         DB::pg_execute(sql, &[&new_id, &email, &hash]).await?;
     }
 
+    // The default admin comes from the SQL migrations, which cannot encrypt its PII.
+    if pii::is_enabled() {
+        User::find(new_id).await?.save(None).await?;
+    }
+
     Ok(())
 }
@@ -6,6 +6,7 @@ use crate::entity::users_values::UserValues;
 use crate::migration::bootstrap::bootstrap_data;
 use crate::migration::bootstrap::generated_secrets::{GeneratedSecretEntry, GeneratedSecretKey};
 use crate::migration::bootstrap::types::{User, UserPassword};
+use crate::pii;
 use crate::rauthy_config::RauthyConfig;
 use chrono::Utc;
 use cryptr::utils::secure_random_alnum;
@@ -41,9 +42,9 @@ pub async fn bootstrap() -> Result<(), ErrorResponse> {
     let sql = r#"
 INSERT INTO users (
     id, email, given_name, family_name, password, roles, groups, enabled, email_verified,
-    password_expires, created_at, language, user_expires
+    password_expires, created_at, language, user_expires, email_hash
 )
-VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)"#;
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)"#;
 
     // We want to fake the `created_at` a tiny bit. We modify the value slightly so that every
     // user has a unique value for that field. That may be important / interesting for testing and
@@ -96,6 +97,10 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)"#;
         };
 
         let language = user.language.unwrap_or_default().to_string();
+        let email = pii::encrypt(&user.email)?;
+        let email_hash = pii::email_hash(&user.email);
+        let given_name = pii::encrypt_opt(user.given_name.as_deref())?;
+        let family_name = pii::encrypt_opt(user.family_name.as_deref())?;
 
         if is_hiqlite() {
             DB::hql()
@@ -103,9 +108,9 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)"#;
                     sql,
                     params!(
                         &id,
-                        &user.email,
-                        user.given_name,
-                        user.family_name,
+                        email,
+                        given_name,
+                        family_name,
                         password,
                         roles,
                         groups,
@@ -114,7 +119,8 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)"#;
                         user.user_expires,
                         now,
                         language,
-                        user.user_expires
+                        user.user_expires,
+                        email_hash
                     ),
                 )
                 .await?;
@@ -123,9 +129,9 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)"#;
                 sql,
                 &[
                     &id,
-                    &user.email,
-                    &user.given_name,
-                    &user.family_name,
+                    &email,
+                    &given_name,
+                    &family_name,
                     &password,
                     &roles,
                     &groups,
@@ -135,6 +141,7 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)"#;
                     &now,
                     &language,
                     &user.user_expires,
+                    &email_hash,
                 ],
             )
             .await?;
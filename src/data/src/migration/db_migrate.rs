@@ -1,8 +1,11 @@
 use crate::database::DB;
 use crate::entity::api_keys::ApiKeyEntity;
+use crate::entity::auth_provider_group_mappings::AuthProviderGroupMapping;
+use crate::entity::auth_provider_role_mappings::{AuthProviderRoleMapping, UserMappedRole};
 use crate::entity::auth_providers::AuthProvider;
 use crate::entity::clients::Client;
 use crate::entity::clients_dyn::ClientDyn;
+use crate::entity::clients_jwks::ClientJwks;
 use crate::entity::clients_scim::ClientScim;
 use crate::entity::config::ConfigEntity;
 use crate::entity::db_version::DbVersion;
@@ -13,6 +16,7 @@ use crate::entity::failed_scim_tasks::FailedScimTask;
 use crate::entity::groups::Group;
 use crate::entity::issued_tokens::IssuedToken;
 use crate::entity::jwk::Jwk;
+use crate::entity::jwk_hsm::JwkHsmReference;
 use crate::entity::kv::{KVAccess, KVNamespace, KVValue};
 use crate::entity::login_locations::LoginLocation;
 use crate::entity::logos::Logo;
@@ -32,7 +36,9 @@ use crate::entity::theme::{ThemeCss, ThemeCssFull};
 use crate::entity::tos::ToS;
 use crate::entity::tos_user_accept::ToSUserAccept;
 use crate::entity::user_attr::{UserAttrConfigEntity, UserAttrValueEntity};
+use crate::entity::user_federation_claims::UserFederationClaims;
 use crate::entity::user_login_states::UserLoginState;
+use crate::entity::user_notification_prefs::UserNotificationPref;
 use crate::entity::user_revoke::UserRevoke;
 use crate::entity::users::User;
 use crate::entity::users_values::UserValues;
@@ -169,16 +175,35 @@ pub async fn migrate_from_sqlite(db_from: &str) -> Result<(), ErrorResponse> {
         .collect_vec();
     inserts::clients_scim(before).await?;
 
+    // CLIENTS JWKS
+    debug!("Migrating table: clients_jwks");
+    let before = query_sqlite::<ClientJwks>(&conn, "SELECT * FROM clients_jwks").await?;
+    inserts::clients_jwks(before).await?;
+
     // GROUPS
     debug!("Migrating table: groups");
     let before = query_sqlite::<Group>(&conn, "SELECT * FROM groups").await?;
     inserts::groups(before).await?;
 
+    // AUTH PROVIDER GROUP MAPPINGS
+    debug!("Migrating table: auth_provider_group_mappings");
+    let before = query_sqlite::<AuthProviderGroupMapping>(
+        &conn,
+        "SELECT * FROM auth_provider_group_mappings",
+    )
+    .await?;
+    inserts::auth_provider_group_mappings(before).await?;
+
     // JWKS
     debug!("Migrating table: jwks");
     let before = query_sqlite::<Jwk>(&conn, "SELECT * FROM jwks").await?;
     inserts::jwks(before).await?;
 
+    // JWKS HSM REFS
+    debug!("Migrating table: jwks_hsm_refs");
+    let before = query_sqlite::<JwkHsmReference>(&conn, "SELECT * FROM jwks_hsm_refs").await?;
+    inserts::jwks_hsm_refs(before).await?;
+
     // MAGIC LINKS
     debug!("Migrating table: magic_links");
     let before = query_sqlite::<MagicLink>(&conn, "SELECT * FROM magic_links").await?;
@@ -194,6 +219,18 @@ pub async fn migrate_from_sqlite(db_from: &str) -> Result<(), ErrorResponse> {
     let before = query_sqlite::<Role>(&conn, "SELECT * FROM roles").await?;
     inserts::roles(before).await?;
 
+    // AUTH PROVIDER ROLE MAPPINGS
+    debug!("Migrating table: auth_provider_role_mappings");
+    let before =
+        query_sqlite::<AuthProviderRoleMapping>(&conn, "SELECT * FROM auth_provider_role_mappings")
+            .await?;
+    inserts::auth_provider_role_mappings(before).await?;
+
+    // USER MAPPED ROLES
+    debug!("Migrating table: user_mapped_roles");
+    let before = query_sqlite::<UserMappedRole>(&conn, "SELECT * FROM user_mapped_roles").await?;
+    inserts::user_mapped_roles(before).await?;
+
     // SCOPES
     debug!("Migrating table: scopes");
     let before = query_sqlite::<Scope>(&conn, "SELECT * FROM scopes").await?;
@@ -249,6 +286,27 @@ pub async fn migrate_from_sqlite(db_from: &str) -> Result<(), ErrorResponse> {
         .collect_vec();
     inserts::user_revoke(before).await?;
 
+    // USER NOTIFICATION PREFS
+    debug!("Migrating table: user_notification_prefs");
+    let mut stmt = conn.prepare("SELECT * FROM user_notification_prefs")?;
+    let before = stmt
+        .query_map([], |row| {
+            Ok(UserNotificationPref {
+                user_id: row.get("user_id")?,
+                category: row.get("category")?,
+                enabled: row.get("enabled")?,
+            })
+        })?
+        .map(|r| r.unwrap())
+        .collect_vec();
+    inserts::user_notification_prefs(before).await?;
+
+    // USER FEDERATION CLAIMS
+    debug!("Migrating table: user_federation_claims");
+    let before =
+        query_sqlite::<UserFederationClaims>(&conn, "SELECT * FROM user_federation_claims").await?;
+    inserts::user_federation_claims(before).await?;
+
     // DEVICES
     debug!("Migrating table: devices");
     let before = query_sqlite::<DeviceEntity>(&conn, "SELECT * FROM devices").await?;
@@ -711,16 +769,32 @@ pub async fn migrate_from_postgres() -> Result<(), ErrorResponse> {
         .collect::<Vec<_>>();
     inserts::clients_scim(before).await?;
 
+    // CLIENTS JWKS
+    debug!("Migrating table: clients_jwks");
+    let before = DB::pg_query_map_with(&cl, "SELECT * FROM clients_jwks", &[], 0).await?;
+    inserts::clients_jwks(before).await?;
+
     // GROUPS
     debug!("Migrating table: groups");
     let before = DB::pg_query_map_with(&cl, "SELECT * FROM groups", &[], 4).await?;
     inserts::groups(before).await?;
 
+    // AUTH PROVIDER GROUP MAPPINGS
+    debug!("Migrating table: auth_provider_group_mappings");
+    let before =
+        DB::pg_query_map_with(&cl, "SELECT * FROM auth_provider_group_mappings", &[], 0).await?;
+    inserts::auth_provider_group_mappings(before).await?;
+
     // JWKS
     debug!("Migrating table: jwks");
     let before = DB::pg_query_map_with(&cl, "SELECT * FROM jwks", &[], 8).await?;
     inserts::jwks(before).await?;
 
+    // JWKS HSM REFS
+    debug!("Migrating table: jwks_hsm_refs");
+    let before = DB::pg_query_map_with(&cl, "SELECT * FROM jwks_hsm_refs", &[], 0).await?;
+    inserts::jwks_hsm_refs(before).await?;
+
     // MAGIC LINKS
     debug!("Migrating table: magic_links");
     let before = DB::pg_query_map_with(&cl, "SELECT * FROM magic_links", &[], 0).await?;
@@ -736,6 +810,17 @@ pub async fn migrate_from_postgres() -> Result<(), ErrorResponse> {
     let before = DB::pg_query_map_with(&cl, "SELECT * FROM roles", &[], 6).await?;
     inserts::roles(before).await?;
 
+    // AUTH PROVIDER ROLE MAPPINGS
+    debug!("Migrating table: auth_provider_role_mappings");
+    let before =
+        DB::pg_query_map_with(&cl, "SELECT * FROM auth_provider_role_mappings", &[], 0).await?;
+    inserts::auth_provider_role_mappings(before).await?;
+
+    // USER MAPPED ROLES
+    debug!("Migrating table: user_mapped_roles");
+    let before = DB::pg_query_map_with(&cl, "SELECT * FROM user_mapped_roles", &[], 0).await?;
+    inserts::user_mapped_roles(before).await?;
+
     // SCOPES
     debug!("Migrating table: scopes");
     let before = DB::pg_query_map_with(&cl, "SELECT * FROM scopes", &[], 0).await?;
@@ -766,6 +851,17 @@ pub async fn migrate_from_postgres() -> Result<(), ErrorResponse> {
     let before = DB::pg_query_map_with(&cl, "SELECT * FROM user_revoke", &[], 0).await?;
     inserts::user_revoke(before).await?;
 
+    // USER NOTIFICATION PREFS
+    debug!("Migrating table: user_notification_prefs");
+    let before =
+        DB::pg_query_map_with(&cl, "SELECT * FROM user_notification_prefs", &[], 0).await?;
+    inserts::user_notification_prefs(before).await?;
+
+    // USER FEDERATION CLAIMS
+    debug!("Migrating table: user_federation_claims");
+    let before = DB::pg_query_map_with(&cl, "SELECT * FROM user_federation_claims", &[], 0).await?;
+    inserts::user_federation_claims(before).await?;
+
     // DEVICES
     debug!("Migrating table: devices");
     let before = DB::pg_query_map_with(&cl, "SELECT * FROM devices", &[], 0).await?;
@@ -183,6 +183,7 @@ ON CONFLICT DO NOTHING"#;
         exp: Utc::now().add(chrono::Duration::days(1)).timestamp(),
         used: false,
         usage: MagicLinkUsage::PasswordReset(None).to_string(),
+        expired_attempt: None,
     };
 
     let sql_1 = "UPDATE clients SET backchannel_logout_uri = $1 WHERE id = 'init_client'";
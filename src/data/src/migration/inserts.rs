@@ -1,8 +1,16 @@
+//! Each of the plain table functions replaces all existing rows during a database migration.
+//! The `insert_*` variants never delete anything first. They are used to merge rows into a
+//! running instance, like during an archive restore or a Keycloak import, and the caller must
+//! resolve any conflicts with existing rows beforehand.
+
 use crate::database::DB;
 use crate::entity::api_keys::ApiKeyEntity;
+use crate::entity::auth_provider_group_mappings::AuthProviderGroupMapping;
+use crate::entity::auth_provider_role_mappings::{AuthProviderRoleMapping, UserMappedRole};
 use crate::entity::auth_providers::AuthProvider;
 use crate::entity::clients::Client;
 use crate::entity::clients_dyn::ClientDyn;
+use crate::entity::clients_jwks::ClientJwks;
 use crate::entity::clients_scim::ClientScim;
 use crate::entity::config::ConfigEntity;
 use crate::entity::devices::DeviceEntity;
@@ -12,6 +20,7 @@ use crate::entity::failed_scim_tasks::FailedScimTask;
 use crate::entity::groups::Group;
 use crate::entity::issued_tokens::IssuedToken;
 use crate::entity::jwk::Jwk;
+use crate::entity::jwk_hsm::JwkHsmReference;
 use crate::entity::kv::{KVAccess, KVNamespace, KVValue};
 use crate::entity::login_locations::LoginLocation;
 use crate::entity::logos::Logo;
@@ -31,13 +40,16 @@ use crate::entity::theme::ThemeCssFull;
 use crate::entity::tos::ToS;
 use crate::entity::tos_user_accept::ToSUserAccept;
 use crate::entity::user_attr::{UserAttrConfigEntity, UserAttrValueEntity};
+use crate::entity::user_federation_claims::UserFederationClaims;
 use crate::entity::user_login_states::UserLoginState;
+use crate::entity::user_notification_prefs::UserNotificationPref;
 use crate::entity::user_revoke::UserRevoke;
 use crate::entity::users::User;
 use crate::entity::users_values::UserValues;
 use crate::entity::webauthn::PasskeyEntity;
 use crate::entity::webids::WebId;
 use crate::events::event::Event;
+use crate::pii::{self, UserPii};
 use cryptr::EncValue;
 use hiqlite::macros::params;
 use rauthy_common::is_hiqlite;
@@ -120,23 +132,38 @@ SET content_type = $3, data = $4, updated = $5"#;
 }
 
 pub async fn auth_providers(data_before: Vec<AuthProvider>) -> Result<(), ErrorResponse> {
-    let sql_1 = "DELETE FROM auth_providers";
-    let sql_2 = r#"
+    if is_hiqlite() {
+        DB::hql()
+            .execute("DELETE FROM auth_providers", params!())
+            .await?;
+    } else {
+        DB::pg_execute("DELETE FROM auth_providers", &[]).await?;
+    }
+    insert_auth_providers(data_before).await
+}
+
+pub async fn insert_auth_providers(data_before: Vec<AuthProvider>) -> Result<(), ErrorResponse> {
+    let sql = r#"
 INSERT INTO
 auth_providers (id, enabled, name, typ, issuer, authorization_endpoint, token_endpoint,
 userinfo_endpoint, client_id, secret, scope, admin_claim_path, admin_claim_value, mfa_claim_path,
 mfa_claim_value, use_pkce, client_secret_basic, client_secret_post, jwks_endpoint, auto_onboarding,
-auto_link)
+auto_link, extra_auth_params, callback_timeout_secs, end_session_endpoint, auto_upstream_logout,
+claim_path_email, claim_path_given_name, claim_path_family_name, claim_path_groups, restrict_hd,
+allowed_email_domains, denied_email_domains, use_userinfo, auto_refresh, client_cert_pem,
+client_key_pem, apple_team_id, apple_key_id, apple_private_key, issuer_template, allowed_tenants,
+sync_policy, upstream_refresh, require_email_verified, sort_order)
 VALUES (
-    $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21
+    $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21,
+    $22, $23, $24, $25, $26, $27, $28, $29, $30, $31, $32, $33, $34, $35, $36, $37, $38, $39, $40,
+    $41, $42, $43, $44, $45
 )"#;
 
     if is_hiqlite() {
-        DB::hql().execute(sql_1, params!()).await?;
         for b in data_before {
             DB::hql()
                 .execute(
-                    sql_2,
+                    sql,
                     params!(
                         b.id,
                         b.enabled,
@@ -158,16 +185,39 @@ VALUES (
                         b.client_secret_post,
                         b.jwks_endpoint,
                         b.auto_onboarding,
-                        b.auto_link
+                        b.auto_link,
+                        b.extra_auth_params,
+                        b.callback_timeout_secs,
+                        b.end_session_endpoint,
+                        b.auto_upstream_logout,
+                        b.claim_path_email,
+                        b.claim_path_given_name,
+                        b.claim_path_family_name,
+                        b.claim_path_groups,
+                        b.restrict_hd,
+                        b.allowed_email_domains,
+                        b.denied_email_domains,
+                        b.use_userinfo,
+                        b.auto_refresh,
+                        b.client_cert_pem,
+                        b.client_key_pem,
+                        b.apple_team_id,
+                        b.apple_key_id,
+                        b.apple_private_key,
+                        b.issuer_template,
+                        b.allowed_tenants,
+                        b.sync_policy,
+                        b.upstream_refresh,
+                        b.require_email_verified,
+                        b.sort_order
                     ),
                 )
                 .await?;
         }
     } else {
-        DB::pg_execute(sql_1, &[]).await?;
         for b in data_before {
             DB::pg_execute(
-                sql_2,
+                sql,
                 &[
                     &b.id,
                     &b.enabled,
@@ -190,6 +240,30 @@ VALUES (
                     &b.jwks_endpoint,
                     &b.auto_onboarding,
                     &b.auto_link,
+                    &b.extra_auth_params,
+                    &b.callback_timeout_secs,
+                    &b.end_session_endpoint,
+                    &b.auto_upstream_logout,
+                    &b.claim_path_email,
+                    &b.claim_path_given_name,
+                    &b.claim_path_family_name,
+                    &b.claim_path_groups,
+                    &b.restrict_hd,
+                    &b.allowed_email_domains,
+                    &b.denied_email_domains,
+                    &b.use_userinfo,
+                    &b.auto_refresh,
+                    &b.client_cert_pem,
+                    &b.client_key_pem,
+                    &b.apple_team_id,
+                    &b.apple_key_id,
+                    &b.apple_private_key,
+                    &b.issuer_template,
+                    &b.allowed_tenants,
+                    &b.sync_policy,
+                    &b.upstream_refresh,
+                    &b.require_email_verified,
+                    &b.sort_order,
                 ],
             )
             .await?;
@@ -228,24 +302,32 @@ VALUES ($1, $2, $3, $4, $5)"#;
 }
 
 pub async fn clients(data_before: Vec<Client>) -> Result<(), ErrorResponse> {
-    let sql_1 = "DELETE FROM clients";
-    let sql_2 = r#"
+    if is_hiqlite() {
+        DB::hql().execute("DELETE FROM clients", params!()).await?;
+    } else {
+        DB::pg_execute("DELETE FROM clients", &[]).await?;
+    }
+    insert_clients(data_before).await
+}
+
+pub async fn insert_clients(data_before: Vec<Client>) -> Result<(), ErrorResponse> {
+    let sql = r#"
 INSERT INTO clients
 (id, name, enabled, confidential, secret, secret_kid, redirect_uris, post_logout_redirect_uris,
 allowed_origins, flows_enabled, access_token_alg, id_token_alg, auth_code_lifetime,
 access_token_lifetime, scopes, default_scopes, challenge, force_mfa, client_uri, contacts,
-backchannel_logout_uri, restrict_group_prefix, allowed_resources, default_aud)
+backchannel_logout_uri, restrict_group_prefix, allowed_resources, default_aud, claims_emission,
+claims, claims_at_root, refresh_token_binding, client_type, allowed_roles, allowed_groups,
+response_types)
 VALUES
 ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21,
-$22, $23, $24)"#;
+$22, $23, $24, $25, $26, $27, $28, $29, $30, $31, $32)"#;
 
     if is_hiqlite() {
-        DB::hql().execute(sql_1, params!()).await?;
-
         for b in data_before {
             DB::hql()
                 .execute(
-                    sql_2,
+                    sql,
                     params!(
                         b.id,
                         b.name,
@@ -270,16 +352,23 @@ $22, $23, $24)"#;
                         b.backchannel_logout_uri,
                         b.restrict_group_prefix,
                         b.allowed_resources,
-                        b.default_aud
+                        b.default_aud,
+                        b.claims_emission,
+                        b.claims,
+                        b.claims_at_root,
+                        b.refresh_token_binding,
+                        b.client_type,
+                        b.allowed_roles,
+                        b.allowed_groups,
+                        b.response_types
                     ),
                 )
                 .await?;
         }
     } else {
-        DB::pg_execute(sql_1, &[]).await?;
         for b in data_before {
             DB::pg_execute(
-                sql_2,
+                sql,
                 &[
                     &b.id,
                     &b.name,
@@ -305,6 +394,14 @@ $22, $23, $24)"#;
                     &b.restrict_group_prefix,
                     &b.allowed_resources,
                     &b.default_aud,
+                    &b.claims_emission,
+                    &b.claims,
+                    &b.claims_at_root,
+                    &b.refresh_token_binding,
+                    &b.client_type,
+                    &b.allowed_roles,
+                    &b.allowed_groups,
+                    &b.response_types,
                 ],
             )
             .await?;
@@ -355,6 +452,28 @@ VALUES ($1, $2, $3, $4, $5)"#;
     Ok(())
 }
 
+pub async fn clients_jwks(data_before: Vec<ClientJwks>) -> Result<(), ErrorResponse> {
+    let sql_1 = "DELETE FROM clients_jwks";
+    let sql_2 = r#"
+INSERT INTO clients_jwks (client_id, jwks, jwks_uri, updated)
+VALUES ($1, $2, $3, $4)"#;
+
+    if is_hiqlite() {
+        DB::hql().execute(sql_1, params!()).await?;
+        for b in data_before {
+            DB::hql()
+                .execute(sql_2, params!(b.client_id, b.jwks, b.jwks_uri, b.updated))
+                .await?;
+        }
+    } else {
+        DB::pg_execute(sql_1, &[]).await?;
+        for b in data_before {
+            DB::pg_execute(sql_2, &[&b.client_id, &b.jwks, &b.jwks_uri, &b.updated]).await?;
+        }
+    }
+    Ok(())
+}
+
 pub async fn clients_scim(data_before: Vec<ClientScim>) -> Result<(), ErrorResponse> {
     let sql_1 = "DELETE FROM clients_scim";
     let sql_2 = r#"
@@ -399,16 +518,22 @@ VALUES ($1, $2, $3, $4, $5)"#;
 }
 
 pub async fn config(data_before: Vec<ConfigEntity>) -> Result<(), ErrorResponse> {
-    let sql_1 = "DELETE FROM config";
+    // The PII hash key is in use already and all users are inserted with it.
+    let sql_1 = "DELETE FROM config WHERE id != $1";
     let sql_2 = "INSERT INTO config (id, data) VALUES ($1, $2)";
+    let data_before = data_before
+        .into_iter()
+        .filter(|b| b.id != pii::CONFIG_ID_HASH_KEY);
 
     if is_hiqlite() {
-        DB::hql().execute(sql_1, params!()).await?;
+        DB::hql()
+            .execute(sql_1, params!(pii::CONFIG_ID_HASH_KEY))
+            .await?;
         for b in data_before {
             DB::hql().execute(sql_2, params!(b.id, b.data)).await?;
         }
     } else {
-        DB::pg_execute(sql_1, &[]).await?;
+        DB::pg_execute(sql_1, &[&pii::CONFIG_ID_HASH_KEY]).await?;
         for b in data_before {
             DB::pg_execute(sql_2, &[&b.id, &b.data]).await?;
         }
@@ -612,20 +737,149 @@ VALUES ($1, $2, $3)"#;
 }
 
 pub async fn groups(data_before: Vec<Group>) -> Result<(), ErrorResponse> {
-    let sql_1 = "DELETE FROM groups";
-    let sql_2 = "INSERT INTO groups (id, name, meta) VALUES ($1, $2, $3)";
+    if is_hiqlite() {
+        DB::hql().execute("DELETE FROM groups", params!()).await?;
+    } else {
+        DB::pg_execute("DELETE FROM groups", &[]).await?;
+    }
+    insert_groups(data_before).await
+}
+
+pub async fn insert_groups(data_before: Vec<Group>) -> Result<(), ErrorResponse> {
+    let sql = "INSERT INTO groups (id, name, meta) VALUES ($1, $2, $3)";
+
+    if is_hiqlite() {
+        for b in data_before {
+            DB::hql()
+                .execute(sql, params!(b.id, b.name, b.meta))
+                .await?;
+        }
+    } else {
+        for b in data_before {
+            DB::pg_execute(sql, &[&b.id, &b.name, &b.meta]).await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn auth_provider_group_mappings(
+    data_before: Vec<AuthProviderGroupMapping>,
+) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::hql()
+            .execute("DELETE FROM auth_provider_group_mappings", params!())
+            .await?;
+    } else {
+        DB::pg_execute("DELETE FROM auth_provider_group_mappings", &[]).await?;
+    }
+    insert_auth_provider_group_mappings(data_before).await
+}
+
+pub async fn insert_auth_provider_group_mappings(
+    data_before: Vec<AuthProviderGroupMapping>,
+) -> Result<(), ErrorResponse> {
+    let sql = r#"
+INSERT INTO auth_provider_group_mappings
+(id, provider_id, claim_key, upstream_group, group_id, sync_mode)
+VALUES ($1, $2, $3, $4, $5, $6)"#;
+
+    if is_hiqlite() {
+        for b in data_before {
+            DB::hql()
+                .execute(
+                    sql,
+                    params!(
+                        b.id,
+                        b.provider_id,
+                        b.claim_key,
+                        b.upstream_group,
+                        b.group_id,
+                        b.sync_mode
+                    ),
+                )
+                .await?;
+        }
+    } else {
+        for b in data_before {
+            DB::pg_execute(
+                sql,
+                &[
+                    &b.id,
+                    &b.provider_id,
+                    &b.claim_key,
+                    &b.upstream_group,
+                    &b.group_id,
+                    &b.sync_mode,
+                ],
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn auth_provider_role_mappings(
+    data_before: Vec<AuthProviderRoleMapping>,
+) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::hql()
+            .execute("DELETE FROM auth_provider_role_mappings", params!())
+            .await?;
+    } else {
+        DB::pg_execute("DELETE FROM auth_provider_role_mappings", &[]).await?;
+    }
+    insert_auth_provider_role_mappings(data_before).await
+}
+
+pub async fn insert_auth_provider_role_mappings(
+    data_before: Vec<AuthProviderRoleMapping>,
+) -> Result<(), ErrorResponse> {
+    let sql = r#"
+INSERT INTO auth_provider_role_mappings (id, provider_id, claim_path, claim_value, role_id)
+VALUES ($1, $2, $3, $4, $5)"#;
+
+    if is_hiqlite() {
+        for b in data_before {
+            DB::hql()
+                .execute(
+                    sql,
+                    params!(b.id, b.provider_id, b.claim_path, b.claim_value, b.role_id),
+                )
+                .await?;
+        }
+    } else {
+        for b in data_before {
+            DB::pg_execute(
+                sql,
+                &[
+                    &b.id,
+                    &b.provider_id,
+                    &b.claim_path,
+                    &b.claim_value,
+                    &b.role_id,
+                ],
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn user_mapped_roles(data_before: Vec<UserMappedRole>) -> Result<(), ErrorResponse> {
+    let sql_1 = "DELETE FROM user_mapped_roles";
+    let sql_2 = "INSERT INTO user_mapped_roles (user_id, role_id) VALUES ($1, $2)";
 
     if is_hiqlite() {
         DB::hql().execute(sql_1, params!()).await?;
         for b in data_before {
             DB::hql()
-                .execute(sql_2, params!(b.id, b.name, b.meta))
+                .execute(sql_2, params!(b.user_id, b.role_id))
                 .await?;
         }
     } else {
         DB::pg_execute(sql_1, &[]).await?;
         for b in data_before {
-            DB::pg_execute(sql_2, &[&b.id, &b.name, &b.meta]).await?;
+            DB::pg_execute(sql_2, &[&b.user_id, &b.role_id]).await?;
         }
     }
     Ok(())
@@ -663,8 +917,8 @@ VALUES ($1, $2, $3, $4, $5, $6)"#;
 pub async fn jwks(data_before: Vec<Jwk>) -> Result<(), ErrorResponse> {
     let sql_1 = "DELETE FROM jwks";
     let sql_2 = r#"
-INSERT INTO jwks (kid, created_at, signature, enc_key_id, jwk)
-VALUES ($1, $2, $3, $4, $5)"#;
+INSERT INTO jwks (kid, created_at, signature, enc_key_id, jwk, inactive)
+VALUES ($1, $2, $3, $4, $5, $6)"#;
 
     if is_hiqlite() {
         DB::hql().execute(sql_1, params!()).await?;
@@ -677,7 +931,8 @@ VALUES ($1, $2, $3, $4, $5)"#;
                         b.created_at,
                         b.signature.as_str(),
                         &b.enc_key_id,
-                        b.jwk
+                        b.jwk,
+                        b.inactive
                     ),
                 )
                 .await?;
@@ -693,6 +948,42 @@ VALUES ($1, $2, $3, $4, $5)"#;
                     &b.signature.as_str(),
                     &b.enc_key_id,
                     &b.jwk,
+                    &b.inactive,
+                ],
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn jwks_hsm_refs(data_before: Vec<JwkHsmReference>) -> Result<(), ErrorResponse> {
+    let sql_1 = "DELETE FROM jwks_hsm_refs";
+    let sql_2 = r#"
+INSERT INTO jwks_hsm_refs (kid, created_at, signature, slot, key_id)
+VALUES ($1, $2, $3, $4, $5)"#;
+
+    if is_hiqlite() {
+        DB::hql().execute(sql_1, params!()).await?;
+        for b in data_before {
+            DB::hql()
+                .execute(
+                    sql_2,
+                    params!(b.kid, b.created_at, b.signature.as_str(), b.slot, b.key_id),
+                )
+                .await?;
+        }
+    } else {
+        DB::pg_execute(sql_1, &[]).await?;
+        for b in data_before {
+            DB::pg_execute(
+                sql_2,
+                &[
+                    &b.kid,
+                    &b.created_at,
+                    &b.signature.as_str(),
+                    &b.slot,
+                    &b.key_id,
                 ],
             )
             .await?;
@@ -813,8 +1104,8 @@ pub async fn magic_links(data_before: Vec<MagicLink>) -> Result<(), ErrorRespons
     let sql_1 = "DELETE FROM magic_links";
     let sql_2 = r#"
 INSERT INTO magic_links
-(id, user_id, csrf_token, cookie, exp, used, usage)
-VALUES ($1, $2, $3, $4, $5, $6, $7)"#;
+(id, user_id, csrf_token, cookie, exp, used, usage, expired_attempt)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#;
 
     if is_hiqlite() {
         DB::hql().execute(sql_1, params!()).await?;
@@ -829,7 +1120,8 @@ VALUES ($1, $2, $3, $4, $5, $6, $7)"#;
                         b.cookie,
                         b.exp,
                         b.used,
-                        b.usage
+                        b.usage,
+                        b.expired_attempt
                     ),
                 )
                 .await?;
@@ -847,6 +1139,7 @@ VALUES ($1, $2, $3, $4, $5, $6, $7)"#;
                     &b.exp,
                     &b.used,
                     &b.usage,
+                    &b.expired_attempt,
                 ],
             )
             .await?;
@@ -1085,18 +1378,26 @@ VALUES ($1, $2, $3)"#;
 }
 
 pub async fn passkeys(data_before: Vec<PasskeyEntity>) -> Result<(), ErrorResponse> {
-    let sql_1 = "DELETE FROM passkeys";
-    let sql_2 = r#"
+    if is_hiqlite() {
+        DB::hql().execute("DELETE FROM passkeys", params!()).await?;
+    } else {
+        DB::pg_execute("DELETE FROM passkeys", &[]).await?;
+    }
+    insert_passkeys(data_before).await
+}
+
+pub async fn insert_passkeys(data_before: Vec<PasskeyEntity>) -> Result<(), ErrorResponse> {
+    let sql = r#"
 INSERT INTO passkeys
-(user_id, name, passkey_user_id, passkey, credential_id, registered, last_used, user_verified)
-VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#;
+(user_id, name, passkey_user_id, passkey, credential_id, registered, last_used, user_verified,
+counter_anomaly)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#;
 
     if is_hiqlite() {
-        DB::hql().execute(sql_1, params!()).await?;
         for b in data_before {
             DB::hql()
                 .execute(
-                    sql_2,
+                    sql,
                     params!(
                         b.user_id,
                         b.name,
@@ -1105,16 +1406,16 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#;
                         b.credential_id,
                         b.registered,
                         b.last_used,
-                        b.user_verified
+                        b.user_verified,
+                        b.counter_anomaly
                     ),
                 )
                 .await?;
         }
     } else {
-        DB::pg_execute(sql_1, &[]).await?;
         for b in data_before {
             DB::pg_execute(
-                sql_2,
+                sql,
                 &[
                     &b.user_id,
                     &b.name,
@@ -1124,6 +1425,7 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#;
                     &b.registered,
                     &b.last_used,
                     &b.user_verified,
+                    &b.counter_anomaly,
                 ],
             )
             .await?;
@@ -1181,8 +1483,10 @@ pub async fn recent_passwords(
 pub async fn refresh_tokens(data_before: Vec<RefreshToken>) -> Result<(), ErrorResponse> {
     let sql_1 = "DELETE FROM refresh_tokens";
     let sql_2 = r#"
-INSERT INTO refresh_tokens (id, user_id, nbf, exp, scope, is_mfa, session_id, access_token_jti)
-VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#;
+INSERT INTO refresh_tokens
+(id, user_id, nbf, exp, scope, is_mfa, session_id, access_token_jti, fingerprint, client_id,
+ granted, last_used)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)"#;
 
     if is_hiqlite() {
         DB::hql().execute(sql_1, params!()).await?;
@@ -1198,7 +1502,11 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#;
                         b.scope,
                         b.is_mfa,
                         b.session_id,
-                        b.access_token_jti
+                        b.access_token_jti,
+                        b.fingerprint,
+                        b.client_id,
+                        b.granted,
+                        b.last_used
                     ),
                 )
                 .await?;
@@ -1217,6 +1525,10 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#;
                     &b.is_mfa,
                     &b.session_id,
                     &b.access_token_jti,
+                    &b.fingerprint,
+                    &b.client_id,
+                    &b.granted,
+                    &b.last_used,
                 ],
             )
             .await?;
@@ -1274,46 +1586,58 @@ VALUES ($1, $2, $3, $4, $5, $6, $7)"#;
 }
 
 pub async fn roles(data_before: Vec<Role>) -> Result<(), ErrorResponse> {
-    let sql_1 = "DELETE FROM roles";
-    let sql_2 = "INSERT INTO roles (id, name, meta) VALUES ($1, $2, $3)";
+    if is_hiqlite() {
+        DB::hql().execute("DELETE FROM roles", params!()).await?;
+    } else {
+        DB::pg_execute("DELETE FROM roles", &[]).await?;
+    }
+    insert_roles(data_before).await
+}
+
+pub async fn insert_roles(data_before: Vec<Role>) -> Result<(), ErrorResponse> {
+    let sql = "INSERT INTO roles (id, name, meta) VALUES ($1, $2, $3)";
 
     if is_hiqlite() {
-        DB::hql().execute(sql_1, params!()).await?;
         for b in data_before {
             DB::hql()
-                .execute(sql_2, params!(b.id, b.name, b.meta))
+                .execute(sql, params!(b.id, b.name, b.meta))
                 .await?;
         }
     } else {
-        DB::pg_execute(sql_1, &[]).await?;
         for b in data_before {
-            DB::pg_execute(sql_2, &[&b.id, &b.name, &b.meta]).await?;
+            DB::pg_execute(sql, &[&b.id, &b.name, &b.meta]).await?;
         }
     }
     Ok(())
 }
 
 pub async fn scopes(data_before: Vec<Scope>) -> Result<(), ErrorResponse> {
-    let sql_1 = "DELETE FROM scopes";
-    let sql_2 = r#"
+    if is_hiqlite() {
+        DB::hql().execute("DELETE FROM scopes", params!()).await?;
+    } else {
+        DB::pg_execute("DELETE FROM scopes", &[]).await?;
+    }
+    insert_scopes(data_before).await
+}
+
+pub async fn insert_scopes(data_before: Vec<Scope>) -> Result<(), ErrorResponse> {
+    let sql = r#"
 INSERT INTO scopes (id, name, attr_include_access, attr_include_id)
 VALUES ($1, $2, $3, $4)"#;
 
     if is_hiqlite() {
-        DB::hql().execute(sql_1, params!()).await?;
         for b in data_before {
             DB::hql()
                 .execute(
-                    sql_2,
+                    sql,
                     params!(b.id, b.name, b.attr_include_access, b.attr_include_id),
                 )
                 .await?;
         }
     } else {
-        DB::pg_execute(sql_1, &[]).await?;
         for b in data_before {
             DB::pg_execute(
-                sql_2,
+                sql,
                 &[&b.id, &b.name, &b.attr_include_access, &b.attr_include_id],
             )
             .await?;
@@ -1524,29 +1848,54 @@ VALUES ($1, $2, $3, $4)"#;
 }
 
 pub async fn user_attr_config(data_before: Vec<UserAttrConfigEntity>) -> Result<(), ErrorResponse> {
-    let sql_1 = "DELETE FROM user_attr_config";
-    let sql_2 = r#"
-INSERT INTO user_attr_config (name, "desc", default_value, typ, user_editable)
-VALUES ($1, $2, $3, $4, $5)"#;
+    if is_hiqlite() {
+        DB::hql()
+            .execute("DELETE FROM user_attr_config", params!())
+            .await?;
+    } else {
+        DB::pg_execute("DELETE FROM user_attr_config", &[]).await?;
+    }
+    insert_user_attr_config(data_before).await
+}
+
+pub async fn insert_user_attr_config(
+    data_before: Vec<UserAttrConfigEntity>,
+) -> Result<(), ErrorResponse> {
+    let sql = r#"
+INSERT INTO user_attr_config
+(name, "desc", default_value, typ, user_editable, auto_populate_from_claim)
+VALUES ($1, $2, $3, $4, $5, $6)"#;
 
     if is_hiqlite() {
-        DB::hql().execute(sql_1, params!()).await?;
         for b in data_before {
             let typ = b.typ.as_ref().map(|t| t.as_str());
             DB::hql()
                 .execute(
-                    sql_2,
-                    params!(b.name, b.desc, b.default_value, typ, b.user_editable),
+                    sql,
+                    params!(
+                        b.name,
+                        b.desc,
+                        b.default_value,
+                        typ,
+                        b.user_editable,
+                        b.auto_populate_from_claim
+                    ),
                 )
                 .await?;
         }
     } else {
-        DB::pg_execute(sql_1, &[]).await?;
         for b in data_before {
             let typ = b.typ.as_ref().map(|t| t.as_str());
             DB::pg_execute(
-                sql_2,
-                &[&b.name, &b.desc, &b.default_value, &typ, &b.user_editable],
+                sql,
+                &[
+                    &b.name,
+                    &b.desc,
+                    &b.default_value,
+                    &typ,
+                    &b.user_editable,
+                    &b.auto_populate_from_claim,
+                ],
             )
             .await?;
         }
@@ -1555,20 +1904,39 @@ VALUES ($1, $2, $3, $4, $5)"#;
 }
 
 pub async fn user_attr_values(data_before: Vec<UserAttrValueEntity>) -> Result<(), ErrorResponse> {
-    let sql_1 = "DELETE FROM user_attr_values";
-    let sql_2 = "INSERT INTO user_attr_values (user_id, key, value) VALUES ($1, $2, $3)";
+    if is_hiqlite() {
+        DB::hql()
+            .execute("DELETE FROM user_attr_values", params!())
+            .await?;
+    } else {
+        DB::pg_execute("DELETE FROM user_attr_values", &[]).await?;
+    }
+    insert_user_attr_values(data_before).await
+}
+
+pub async fn insert_user_attr_values(
+    data_before: Vec<UserAttrValueEntity>,
+) -> Result<(), ErrorResponse> {
+    let sql = r#"
+INSERT INTO user_attr_values (user_id, key, value, last_synced_from_claim_at)
+VALUES ($1, $2, $3, $4)"#;
 
     if is_hiqlite() {
-        DB::hql().execute(sql_1, params!()).await?;
         for b in data_before {
             DB::hql()
-                .execute(sql_2, params!(b.user_id, b.key, b.value))
+                .execute(
+                    sql,
+                    params!(b.user_id, b.key, b.value, b.last_synced_from_claim_at),
+                )
                 .await?;
         }
     } else {
-        DB::pg_execute(sql_1, &[]).await?;
         for b in data_before {
-            DB::pg_execute(sql_2, &[&b.user_id, &b.key, &b.value]).await?;
+            DB::pg_execute(
+                sql,
+                &[&b.user_id, &b.key, &b.value, &b.last_synced_from_claim_at],
+            )
+            .await?;
         }
     }
     Ok(())
@@ -1609,27 +1977,38 @@ DO NOTHING"#;
 pub async fn users(data_before: Vec<User>) -> Result<(), ErrorResponse> {
     // the user_login_states restrict a deletion to prevent logic errors, which we can ignore
     // during a migration
-    let sql_1 = "DELETE FROM user_login_states";
-    let sql_2 = "DELETE FROM users";
-    let sql_3 = r#"
+    if is_hiqlite() {
+        DB::hql()
+            .execute("DELETE FROM user_login_states", params!())
+            .await?;
+        DB::hql().execute("DELETE FROM users", params!()).await?;
+    } else {
+        DB::pg_execute("DELETE FROM user_login_states", &[]).await?;
+        DB::pg_execute("DELETE FROM users", &[]).await?;
+    }
+    insert_users(data_before).await
+}
+
+pub async fn insert_users(data_before: Vec<User>) -> Result<(), ErrorResponse> {
+    let sql = r#"
 INSERT INTO users
 (id, email, given_name, family_name, password, roles, groups, enabled, email_verified,
 password_expires, created_at, last_login, last_failed_login, failed_login_attempts, language,
-webauthn_user_id, user_expires, auth_provider_id, federation_uid, picture_id)
-VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20)"#;
+webauthn_user_id, user_expires, auth_provider_id, federation_uid, picture_id, email_hash)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20,
+$21)"#;
 
     if is_hiqlite() {
-        DB::hql().execute(sql_1, params!()).await?;
-        DB::hql().execute(sql_2, params!()).await?;
         for b in data_before {
+            let pii = UserPii::new(&b.email, &b.given_name, b.family_name.as_deref())?;
             DB::hql()
                 .execute(
-                    sql_3,
+                    sql,
                     params!(
                         b.id,
-                        b.email,
-                        b.given_name,
-                        b.family_name,
+                        pii.email,
+                        pii.given_name,
+                        pii.family_name,
                         b.password,
                         b.roles,
                         b.groups,
@@ -1645,22 +2024,22 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $
                         b.user_expires,
                         b.auth_provider_id,
                         b.federation_uid,
-                        b.picture_id
+                        b.picture_id,
+                        pii.email_hash
                     ),
                 )
                 .await?;
         }
     } else {
-        DB::pg_execute(sql_1, &[]).await?;
-        DB::pg_execute(sql_2, &[]).await?;
         for b in data_before {
+            let pii = UserPii::new(&b.email, &b.given_name, b.family_name.as_deref())?;
             DB::pg_execute(
-                sql_3,
+                sql,
                 &[
                     &b.id,
-                    &b.email,
-                    &b.given_name,
-                    &b.family_name,
+                    &pii.email,
+                    &pii.given_name,
+                    &pii.family_name,
                     &b.password,
                     &b.roles,
                     &b.groups,
@@ -1677,6 +2056,7 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $
                     &b.auth_provider_id,
                     &b.federation_uid,
                     &b.picture_id,
+                    &pii.email_hash,
                 ],
             )
             .await?;
@@ -1686,47 +2066,57 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $
 }
 
 pub async fn users_values(data_before: Vec<UserValues>) -> Result<(), ErrorResponse> {
-    let sql_1 = "DELETE FROM users_values";
-    let sql_2 = r#"
+    if is_hiqlite() {
+        DB::hql()
+            .execute("DELETE FROM users_values", params!())
+            .await?;
+    } else {
+        DB::pg_execute("DELETE FROM users_values", &[]).await?;
+    }
+    insert_users_values(data_before).await
+}
+
+pub async fn insert_users_values(data_before: Vec<UserValues>) -> Result<(), ErrorResponse> {
+    let sql = r#"
 INSERT INTO
-users_values (id, birthdate, phone, street, zip, city, country, preferred_username, tz)
-VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#;
+users_values (id, birthdate, phone, street, zip, city, country, preferred_username, tz, locale)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"#;
 
     if is_hiqlite() {
-        DB::hql().execute(sql_1, params!()).await?;
         for b in data_before {
             DB::hql()
                 .execute(
-                    sql_2,
+                    sql,
                     params!(
                         b.id,
                         b.birthdate,
-                        b.phone,
+                        pii::encrypt_opt(b.phone.as_deref())?,
                         b.street,
                         b.zip,
                         b.city,
                         b.country,
                         b.preferred_username,
-                        b.tz
+                        b.tz,
+                        b.locale
                     ),
                 )
                 .await?;
         }
     } else {
-        DB::pg_execute(sql_1, &[]).await?;
         for b in data_before {
             DB::pg_execute(
-                sql_2,
+                sql,
                 &[
                     &b.id,
                     &b.birthdate,
-                    &b.phone,
+                    &pii::encrypt_opt(b.phone.as_deref())?,
                     &b.street,
                     &b.zip,
                     &b.city,
                     &b.country,
                     &b.preferred_username,
                     &b.tz,
+                    &b.locale,
                 ],
             )
             .await?;
@@ -1755,6 +2145,67 @@ VALUES ($1, $2)"#;
     Ok(())
 }
 
+pub async fn user_federation_claims(
+    data_before: Vec<UserFederationClaims>,
+) -> Result<(), ErrorResponse> {
+    let sql_1 = "DELETE FROM user_federation_claims";
+    let sql_2 = r#"
+INSERT INTO user_federation_claims (user_id, provider_id, claims, updated, history)
+VALUES ($1, $2, $3, $4, $5)"#;
+
+    if is_hiqlite() {
+        DB::hql().execute(sql_1, params!()).await?;
+        for b in data_before {
+            DB::hql()
+                .execute(
+                    sql_2,
+                    params!(b.user_id, b.provider_id, b.claims, b.updated, b.history),
+                )
+                .await?;
+        }
+    } else {
+        DB::pg_execute(sql_1, &[]).await?;
+        for b in data_before {
+            DB::pg_execute(
+                sql_2,
+                &[
+                    &b.user_id,
+                    &b.provider_id,
+                    &b.claims,
+                    &b.updated,
+                    &b.history,
+                ],
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn user_notification_prefs(
+    data_before: Vec<UserNotificationPref>,
+) -> Result<(), ErrorResponse> {
+    let sql_1 = "DELETE FROM user_notification_prefs";
+    let sql_2 = r#"
+INSERT INTO user_notification_prefs (user_id, category, enabled)
+VALUES ($1, $2, $3)"#;
+
+    if is_hiqlite() {
+        DB::hql().execute(sql_1, params!()).await?;
+        for b in data_before {
+            DB::hql()
+                .execute(sql_2, params!(b.user_id, b.category, b.enabled))
+                .await?;
+        }
+    } else {
+        DB::pg_execute(sql_1, &[]).await?;
+        for b in data_before {
+            DB::pg_execute(sql_2, &[&b.user_id, &b.category, &b.enabled]).await?;
+        }
+    }
+    Ok(())
+}
+
 pub async fn webids(data_before: Vec<WebId>) -> Result<(), ErrorResponse> {
     let sql_1 = "DELETE FROM webids";
     let sql_2 = "INSERT INTO webids (user_id, custom_triples, expose_email) VALUES ($1, $2, $3)";
@@ -0,0 +1,1232 @@
+use crate::entity::clients::Client;
+use crate::entity::groups::Group;
+use crate::entity::roles::Role;
+use crate::entity::users::User;
+use crate::migration::archive::exists;
+use crate::migration::inserts;
+use crate::pii;
+use chrono::Utc;
+use cryptr::EncKeys;
+use rauthy_api_types::migration::{
+    KeycloakImportAction, KeycloakImportEntity, KeycloakImportResponse,
+};
+use rauthy_common::constants::GRANT_TYPE_DEVICE_CODE;
+use rauthy_common::regex::{
+    RE_CLIENT_ID_STRICT, RE_CLIENT_NAME, RE_GROUPS, RE_ROLES_SCOPES, RE_URI,
+};
+use rauthy_common::utils::{base64_decode, base64_encode, new_store_id};
+use rauthy_error::ErrorResponse;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use tracing::info;
+
+/// Clients that exist in each Keycloak realm and have no counterpart in Rauthy.
+const KC_BUILTIN_CLIENTS: [&str; 6] = [
+    "account",
+    "account-console",
+    "admin-cli",
+    "broker",
+    "realm-management",
+    "security-admin-console",
+];
+/// Roles that exist in each Keycloak realm. `default-roles-<realm>` is handled separately.
+const KC_BUILTIN_ROLES: [&str; 2] = ["offline_access", "uma_authorization"];
+/// Client scopes that exist in each Keycloak realm. `email` and `profile` map onto the Rauthy
+/// scopes with the same name, the others are covered by Rauthy's defaults or not supported.
+const KC_BUILTIN_SCOPES: [&str; 13] = [
+    "acr",
+    "address",
+    "basic",
+    "email",
+    "microprofile-jwt",
+    "offline_access",
+    "organization",
+    "phone",
+    "profile",
+    "role_list",
+    "roles",
+    "saml_organization",
+    "web-origins",
+];
+
+/// The parts of a Keycloak realm export, either from `kc.sh export` or the partial export in
+/// the admin console, that can be mapped onto Rauthy.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KcRealm {
+    realm: String,
+    access_token_lifespan: Option<i32>,
+    #[serde(default)]
+    roles: KcRoles,
+    #[serde(default)]
+    groups: Vec<KcGroup>,
+    #[serde(default)]
+    clients: Vec<KcClient>,
+    #[serde(default)]
+    users: Vec<KcUser>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct KcRoles {
+    #[serde(default)]
+    realm: Vec<KcRole>,
+    #[serde(default)]
+    client: HashMap<String, Vec<KcRole>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KcRole {
+    id: Option<String>,
+    name: String,
+    #[serde(default)]
+    composite: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KcGroup {
+    id: Option<String>,
+    name: String,
+    path: Option<String>,
+    #[serde(default)]
+    realm_roles: Vec<String>,
+    #[serde(default)]
+    client_roles: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    sub_groups: Vec<KcGroup>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KcClient {
+    id: Option<String>,
+    client_id: String,
+    name: Option<String>,
+    #[serde(default = "default_true")]
+    enabled: bool,
+    client_authenticator_type: Option<String>,
+    root_url: Option<String>,
+    base_url: Option<String>,
+    #[serde(default)]
+    redirect_uris: Vec<String>,
+    #[serde(default)]
+    web_origins: Vec<String>,
+    #[serde(default)]
+    bearer_only: bool,
+    #[serde(default)]
+    consent_required: bool,
+    #[serde(default = "default_true")]
+    standard_flow_enabled: bool,
+    #[serde(default)]
+    implicit_flow_enabled: bool,
+    #[serde(default)]
+    direct_access_grants_enabled: bool,
+    #[serde(default)]
+    service_accounts_enabled: bool,
+    #[serde(default)]
+    authorization_services_enabled: bool,
+    #[serde(default)]
+    public_client: bool,
+    protocol: Option<String>,
+    #[serde(default)]
+    attributes: HashMap<String, String>,
+    #[serde(default)]
+    protocol_mappers: Vec<serde_json::Value>,
+    #[serde(default)]
+    default_client_scopes: Vec<String>,
+    #[serde(default)]
+    optional_client_scopes: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KcUser {
+    id: Option<String>,
+    username: String,
+    email: Option<String>,
+    first_name: Option<String>,
+    last_name: Option<String>,
+    #[serde(default = "default_true")]
+    enabled: bool,
+    #[serde(default)]
+    email_verified: bool,
+    created_timestamp: Option<i64>,
+    federation_link: Option<String>,
+    service_account_client_id: Option<String>,
+    #[serde(default)]
+    credentials: Vec<KcCredential>,
+    #[serde(default)]
+    required_actions: Vec<String>,
+    #[serde(default)]
+    realm_roles: Vec<String>,
+    #[serde(default)]
+    client_roles: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    groups: Vec<String>,
+    #[serde(default)]
+    federated_identities: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KcCredential {
+    #[serde(rename = "type")]
+    typ: String,
+    secret_data: Option<String>,
+    credential_data: Option<String>,
+}
+
+/// The `secretData` of a credential, which is a JSON string inside the export.
+#[derive(Debug, Deserialize)]
+struct KcSecretData {
+    value: String,
+    salt: Option<String>,
+}
+
+/// The `credentialData` of a credential, which is a JSON string inside the export.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KcCredentialData {
+    algorithm: String,
+    hash_iterations: u32,
+    #[serde(default)]
+    additional_parameters: HashMap<String, Vec<String>>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// A mapped entity together with its report entry. The `value` is `None` for skipped entities.
+struct Planned<T> {
+    value: Option<T>,
+    report: KeycloakImportEntity,
+}
+
+impl<T> Planned<T> {
+    fn new(key: String, keycloak_id: Option<String>) -> Self {
+        Self {
+            value: None,
+            report: KeycloakImportEntity {
+                key,
+                keycloak_id,
+                action: KeycloakImportAction::Create,
+                warnings: Vec::new(),
+            },
+        }
+    }
+
+    fn warn(&mut self, warning: impl Display) {
+        self.report.warnings.push(warning.to_string());
+    }
+
+    fn skip(mut self, reason: impl Display) -> Self {
+        self.report.action = KeycloakImportAction::Skip;
+        self.value = None;
+        self.warn(reason);
+        self
+    }
+
+    /// Returns the value that must be inserted, unless it exists locally already.
+    fn take_new(&mut self, exists: bool) -> Option<T> {
+        if exists {
+            self.report.action = KeycloakImportAction::Exists;
+            self.value = None;
+        }
+        self.value.take()
+    }
+}
+
+/// Maps a Keycloak realm export onto Rauthy roles, groups, clients and users.
+///
+/// - realm roles become roles, client roles are not supported
+/// - nested groups are flattened into their full path like `engineering/backend`, and their
+///   realm roles are granted to all members directly
+/// - clients keep their `clientId`, relative redirect URIs are resolved against the `rootUrl`,
+///   and the standard, direct access, service account and device flows are translated into
+///   `authorization_code`, `password`, `client_credentials` and `device_code`
+/// - users keep their PBKDF2, bcrypt or Argon2 password hash, which is replaced with an
+///   up-to-date Argon2ID hash after the next successful login
+///
+/// Entities, which already exist by name, client id or E-Mail, are never modified, which makes
+/// it safe to run the same import multiple times. The import is NOT transactional.
+pub struct KeycloakImport {
+    realm: String,
+    roles: Vec<Planned<Role>>,
+    groups: Vec<Planned<Group>>,
+    clients: Vec<Planned<Client>>,
+    users: Vec<Planned<User>>,
+}
+
+impl KeycloakImport {
+    pub fn parse(json: &[u8]) -> Result<Self, ErrorResponse> {
+        let realm = serde_json::from_slice::<KcRealm>(json)?;
+        Ok(Self::from_realm(realm))
+    }
+
+    /// Compares all mapped entities with the local database and inserts the missing ones, unless
+    /// this is a `dry_run`.
+    pub async fn run(self, dry_run: bool) -> Result<KeycloakImportResponse, ErrorResponse> {
+        info!(
+            realm = self.realm,
+            dry_run, "Importing Keycloak realm export"
+        );
+
+        let mut res = KeycloakImportResponse {
+            realm: self.realm,
+            dry_run,
+            ..Default::default()
+        };
+
+        let mut roles = Vec::with_capacity(self.roles.len());
+        for mut planned in self.roles {
+            if let Some(role) = &planned.value {
+                let sql = "SELECT 1 FROM roles WHERE id = $1 OR name = $2";
+                let exists = exists(sql, &role.id, &role.name).await?;
+                roles.extend(planned.take_new(exists));
+            }
+            res.roles.push(planned.report);
+        }
+
+        let mut groups = Vec::with_capacity(self.groups.len());
+        for mut planned in self.groups {
+            if let Some(group) = &planned.value {
+                let sql = "SELECT 1 FROM groups WHERE id = $1 OR name = $2";
+                let exists = exists(sql, &group.id, &group.name).await?;
+                groups.extend(planned.take_new(exists));
+            }
+            res.groups.push(planned.report);
+        }
+
+        let mut clients = Vec::with_capacity(self.clients.len());
+        for mut planned in self.clients {
+            if let Some(client) = &planned.value {
+                let sql = "SELECT 1 FROM clients WHERE id = $1 OR id = $2";
+                let exists = exists(sql, &client.id, &client.id).await?;
+                if let Some(mut client) = planned.take_new(exists) {
+                    if client.confidential && !dry_run {
+                        let (_, secret) = Client::generate_new_secret()?;
+                        client.secret = Some(secret);
+                        client.secret_kid = Some(EncKeys::get_static().enc_key_active.clone());
+                    }
+                    clients.push(client);
+                }
+            }
+            res.clients.push(planned.report);
+        }
+
+        let mut users = Vec::with_capacity(self.users.len());
+        for mut planned in self.users {
+            if let Some(user) = &mut planned.value {
+                user.email = User::normalize_email(&user.email);
+                planned.report.key.clone_from(&user.email);
+
+                // With encrypted PII, existing E-Mails can only be found by their hash.
+                let (key_col, key) = match pii::email_hash(&user.email) {
+                    Some(hash) => ("email_hash", hash),
+                    None => ("email", user.email.clone()),
+                };
+                let sql = format!("SELECT 1 FROM users WHERE id = $1 OR {key_col} = $2");
+                let exists = exists(&sql, &user.id, &key).await?;
+                users.extend(planned.take_new(exists));
+            }
+            res.users.push(planned.report);
+        }
+
+        if !dry_run {
+            inserts::insert_roles(roles).await?;
+            inserts::insert_groups(groups).await?;
+            inserts::insert_clients(clients).await?;
+            inserts::insert_users(users).await?;
+        }
+
+        Ok(res)
+    }
+}
+
+impl KeycloakImport {
+    fn from_realm(realm: KcRealm) -> Self {
+        let roles = map_roles(&realm);
+        let role_names = roles
+            .iter()
+            .filter_map(|r| r.value.as_ref().map(|role| role.name.as_str()))
+            .collect::<HashSet<_>>();
+
+        let mut groups = Vec::new();
+        let mut group_roles = HashMap::new();
+        map_groups(&realm.groups, "", &[], &mut groups, &mut group_roles);
+        let group_names = groups
+            .iter()
+            .filter_map(|g| g.value.as_ref().map(|group| group.name.as_str()))
+            .collect::<HashSet<_>>();
+
+        let clients = realm
+            .clients
+            .iter()
+            .map(|c| map_client(&realm, c))
+            .collect::<Vec<_>>();
+
+        let default_roles = format!("default-roles-{}", realm.realm);
+        let mut emails = HashSet::with_capacity(realm.users.len());
+        let users = realm
+            .users
+            .iter()
+            .map(|u| {
+                let planned = map_user(u, &role_names, &group_names, &group_roles, &default_roles);
+                let is_duplicate = planned
+                    .value
+                    .as_ref()
+                    .is_some_and(|user| !emails.insert(user.email.to_lowercase()));
+                if is_duplicate {
+                    planned.skip("Duplicate E-Mail in the export")
+                } else {
+                    planned
+                }
+            })
+            .collect::<Vec<_>>();
+
+        Self {
+            realm: realm.realm.clone(),
+            roles,
+            groups,
+            clients,
+            users,
+        }
+    }
+}
+
+fn map_roles(realm: &KcRealm) -> Vec<Planned<Role>> {
+    let default_roles = format!("default-roles-{}", realm.realm);
+    let mut res = Vec::with_capacity(realm.roles.realm.len());
+
+    for role in &realm.roles.realm {
+        let mut planned = Planned::new(role.name.clone(), role.id.clone());
+        if KC_BUILTIN_ROLES.contains(&role.name.as_str()) || role.name == default_roles {
+            res.push(planned.skip("Keycloak built-in role"));
+            continue;
+        }
+        if !RE_ROLES_SCOPES.is_match(&role.name) || role.name.contains(',') {
+            res.push(planned.skip("Invalid role name"));
+            continue;
+        }
+        if role.composite {
+            planned.warn("Composite roles are not supported, included roles are not granted");
+        }
+        planned.value = Some(Role {
+            id: new_store_id(),
+            name: role.name.clone(),
+            meta: None,
+        });
+        res.push(planned);
+    }
+
+    let mut client_roles = realm
+        .roles
+        .client
+        .iter()
+        .filter(|(client_id, _)| !KC_BUILTIN_CLIENTS.contains(&client_id.as_str()))
+        .flat_map(|(client_id, roles)| {
+            roles
+                .iter()
+                .map(move |role| (format!("{client_id}:{}", role.name), role.id.clone()))
+        })
+        .collect::<Vec<_>>();
+    client_roles.sort();
+    for (key, id) in client_roles {
+        res.push(Planned::new(key, id).skip("Client roles are not supported"));
+    }
+
+    res
+}
+
+/// Flattens the group tree. `group_roles` collects the realm roles for each group path
+/// including the ones from all parent groups, just like Keycloak grants them to members.
+fn map_groups(
+    groups: &[KcGroup],
+    parent: &str,
+    parent_roles: &[String],
+    res: &mut Vec<Planned<Group>>,
+    group_roles: &mut HashMap<String, Vec<String>>,
+) {
+    for group in groups {
+        let path = match &group.path {
+            Some(path) => path.trim_start_matches('/').to_string(),
+            None if parent.is_empty() => group.name.clone(),
+            None => format!("{parent}/{}", group.name),
+        };
+
+        let mut roles = parent_roles.to_vec();
+        roles.extend(group.realm_roles.iter().cloned());
+        map_groups(&group.sub_groups, &path, &roles, res, group_roles);
+
+        let mut planned = Planned::new(path.clone(), group.id.clone());
+        if !RE_GROUPS.is_match(&path) || path.contains(',') {
+            res.push(planned.skip("Invalid group name"));
+            continue;
+        }
+        if !group.realm_roles.is_empty() {
+            planned.warn(format!(
+                "Group roles are granted to its members directly: {}",
+                group.realm_roles.join(", ")
+            ));
+        }
+        if !group.client_roles.is_empty() {
+            planned.warn("Client roles are not supported");
+        }
+        planned.value = Some(Group {
+            id: new_store_id(),
+            name: path.clone(),
+            meta: None,
+        });
+        res.push(planned);
+        group_roles.insert(path, roles);
+    }
+}
+
+fn map_client(realm: &KcRealm, kc: &KcClient) -> Planned<Client> {
+    let mut planned = Planned::new(kc.client_id.clone(), kc.id.clone());
+
+    let is_builtin = KC_BUILTIN_CLIENTS.contains(&kc.client_id.as_str())
+        || (realm.realm == "master" && kc.client_id.ends_with("-realm"));
+    if is_builtin {
+        return planned.skip("Keycloak built-in client");
+    }
+    if kc
+        .protocol
+        .as_deref()
+        .is_some_and(|p| p != "openid-connect")
+    {
+        return planned.skip("Only OpenID Connect clients are supported");
+    }
+    if kc.bearer_only {
+        return planned.skip(
+            "Bearer-only clients are not needed, resource servers validate tokens with the JWKS",
+        );
+    }
+    if !RE_CLIENT_ID_STRICT.is_match(&kc.client_id) {
+        return planned.skip("Invalid client id");
+    }
+
+    let root_url = kc.root_url.as_deref().filter(|url| !url.is_empty());
+    let confidential = !kc.public_client;
+
+    let mut redirect_uris = Vec::with_capacity(kc.redirect_uris.len());
+    for uri in &kc.redirect_uris {
+        match translate_uri(uri, root_url) {
+            Ok(uri) => redirect_uris.push(uri),
+            Err(err) => planned.warn(err),
+        }
+    }
+
+    let post_logout_redirect_uris = match kc.attributes.get("post.logout.redirect.uris") {
+        Some(uris) => {
+            let mut res = Vec::new();
+            for uri in uris.split("##").filter(|u| !u.is_empty()) {
+                if uri == "+" {
+                    res.extend(redirect_uris.iter().cloned());
+                    continue;
+                }
+                match translate_uri(uri, root_url) {
+                    Ok(uri) => res.push(uri),
+                    Err(err) => planned.warn(err),
+                }
+            }
+            res
+        }
+        None => Vec::new(),
+    };
+
+    let mut allowed_origins = Vec::new();
+    for origin in &kc.web_origins {
+        if origin == "+" {
+            allowed_origins.extend(redirect_uris.iter().filter_map(|uri| origin_of(uri)));
+        } else if origin == "*" {
+            planned.warn("The wildcard web origin '*' is not supported");
+        } else if origin_of(origin).is_some_and(|o| o == *origin) {
+            allowed_origins.push(origin.clone());
+        } else {
+            planned.warn(format!("Invalid web origin '{origin}'"));
+        }
+    }
+    allowed_origins.sort();
+    allowed_origins.dedup();
+
+    let mut flows = Vec::with_capacity(5);
+    if kc.standard_flow_enabled {
+        flows.push("authorization_code");
+    }
+    if kc.direct_access_grants_enabled {
+        flows.push("password");
+    }
+    if kc.service_accounts_enabled {
+        if confidential {
+            flows.push("client_credentials");
+        } else {
+            planned.warn("Service accounts are only supported for confidential clients");
+        }
+    }
+    if kc
+        .attributes
+        .get("oauth2.device.authorization.grant.enabled")
+        .is_some_and(|v| v == "true")
+    {
+        flows.push(GRANT_TYPE_DEVICE_CODE);
+    }
+    let use_refresh_tokens = kc
+        .attributes
+        .get("use.refresh.tokens")
+        .is_none_or(|v| v != "false");
+    if use_refresh_tokens && flows.iter().any(|f| *f != "client_credentials") {
+        flows.push("refresh_token");
+    }
+    if kc.implicit_flow_enabled {
+        planned.warn("The implicit flow is not supported");
+    }
+    if flows.is_empty() {
+        planned.warn("No supported flow is enabled");
+    }
+
+    let challenge = match kc
+        .attributes
+        .get("pkce.code.challenge.method")
+        .map(String::as_str)
+    {
+        Some(method @ ("S256" | "plain")) => Some(method.to_string()),
+        _ if confidential => None,
+        _ => {
+            planned.warn("PKCE with S256 is enforced for public clients");
+            Some("S256".to_string())
+        }
+    };
+
+    let access_token_lifetime = kc
+        .attributes
+        .get("access.token.lifespan")
+        .and_then(|v| v.parse::<i32>().ok())
+        .or(realm.access_token_lifespan)
+        .unwrap_or(1800)
+        .clamp(10, 86400);
+
+    let mut default_scopes = vec!["openid"];
+    for scope in ["profile", "email"] {
+        if kc.default_client_scopes.iter().any(|s| s == scope) {
+            default_scopes.push(scope);
+        }
+    }
+    let custom_scopes = kc
+        .default_client_scopes
+        .iter()
+        .chain(kc.optional_client_scopes.iter())
+        .filter(|s| !KC_BUILTIN_SCOPES.contains(&s.as_str()))
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    if !custom_scopes.is_empty() {
+        planned.warn(format!(
+            "Client scopes are not imported: {}",
+            custom_scopes.join(", ")
+        ));
+    }
+
+    if confidential {
+        match kc.client_authenticator_type.as_deref() {
+            None | Some("client-secret") => {
+                planned.warn("Client secrets cannot be migrated, a new one is generated")
+            }
+            Some(typ) => planned.warn(format!(
+                "The client authenticator '{typ}' is not supported, a client secret is generated"
+            )),
+        }
+    }
+    if kc.authorization_services_enabled {
+        planned.warn("Authorization services are not supported");
+    }
+    if kc.consent_required {
+        planned.warn("User consent is not supported");
+    }
+    if !kc.protocol_mappers.is_empty() {
+        planned.warn(format!(
+            "{} protocol mapper(s) are not imported",
+            kc.protocol_mappers.len()
+        ));
+    }
+
+    // Keycloak uses placeholders like `${client_account}` for translated names.
+    let name = kc
+        .name
+        .as_ref()
+        .filter(|name| !name.is_empty() && !name.starts_with("${"));
+    let name = match name {
+        Some(name) if RE_CLIENT_NAME.is_match(name) => Some(name.clone()),
+        Some(name) => {
+            planned.warn(format!("Invalid client name '{name}' is dropped"));
+            None
+        }
+        None => None,
+    };
+
+    let client_uri = match (root_url, kc.base_url.as_deref()) {
+        (_, Some(base)) if base.starts_with("http") => Some(base.to_string()),
+        (Some(root), Some(base)) => Some(format!("{}{base}", root.trim_end_matches('/'))),
+        (Some(root), None) => Some(root.to_string()),
+        (None, _) => None,
+    }
+    .filter(|uri| RE_URI.is_match(uri));
+
+    let backchannel_logout_uri = kc
+        .attributes
+        .get("backchannel.logout.url")
+        .filter(|uri| !uri.is_empty())
+        .and_then(|uri| match translate_uri(uri, root_url) {
+            Ok(uri) => Some(uri),
+            Err(err) => {
+                planned.warn(err);
+                None
+            }
+        });
+
+    planned.value = Some(Client {
+        id: kc.client_id.clone(),
+        name,
+        enabled: kc.enabled,
+        confidential,
+        redirect_uris: redirect_uris.join(","),
+        post_logout_redirect_uris: opt_csv(&post_logout_redirect_uris),
+        allowed_origins: opt_csv(&allowed_origins),
+        flows_enabled: flows.join(","),
+        access_token_lifetime,
+        default_scopes: default_scopes.join(","),
+        challenge,
+        client_uri,
+        backchannel_logout_uri,
+        ..Default::default()
+    });
+    planned
+}
+
+fn map_user(
+    kc: &KcUser,
+    role_names: &HashSet<&str>,
+    group_names: &HashSet<&str>,
+    group_roles: &HashMap<String, Vec<String>>,
+    default_roles: &str,
+) -> Planned<User> {
+    let email = kc
+        .email
+        .as_deref()
+        .map(str::trim)
+        .filter(|email| !email.is_empty());
+    let mut planned = Planned::new(email.unwrap_or(&kc.username).to_string(), kc.id.clone());
+
+    if kc.service_account_client_id.is_some() {
+        return planned.skip("Service account users are replaced by the client_credentials flow");
+    }
+    let Some(email) = email else {
+        return planned.skip("Users without an E-Mail cannot be imported");
+    };
+
+    let mut groups = Vec::with_capacity(kc.groups.len());
+    let mut roles = Vec::with_capacity(kc.realm_roles.len());
+    for path in &kc.groups {
+        let name = path.trim_start_matches('/');
+        if group_names.contains(name) {
+            groups.push(name.to_string());
+            roles.extend(group_roles.get(name).into_iter().flatten().cloned());
+        } else {
+            planned.warn(format!("Group '{name}' was not imported"));
+        }
+    }
+    roles.extend(kc.realm_roles.iter().cloned());
+    roles.sort();
+    roles.dedup();
+    roles.retain(|role| {
+        if role_names.contains(role.as_str()) {
+            return true;
+        }
+        if role != default_roles && !KC_BUILTIN_ROLES.contains(&role.as_str()) {
+            planned.warn(format!("Role '{role}' was not imported"));
+        }
+        false
+    });
+    if !kc.client_roles.is_empty() {
+        planned.warn("Client roles are not supported");
+    }
+
+    let mut password = None;
+    for cred in &kc.credentials {
+        match cred.typ.as_str() {
+            "password" if kc.federation_link.is_none() => match map_password(cred) {
+                Ok(hash) => password = Some(hash),
+                Err(err) => planned.warn(err),
+            },
+            "password" => {}
+            "otp" => planned.warn("OTP is not supported, the user must register a passkey"),
+            typ if typ.starts_with("webauthn") => {
+                planned.warn("Passkeys cannot be migrated and must be registered again")
+            }
+            typ => planned.warn(format!("Unsupported credential '{typ}'")),
+        }
+    }
+    if kc.federation_link.is_some() {
+        planned.warn("User federation is not supported, the user must reset the password");
+    }
+    if !kc.federated_identities.is_empty() {
+        planned.warn("Linked identity providers are not imported");
+    }
+
+    let mut password_expires = None;
+    for action in &kc.required_actions {
+        match action.as_str() {
+            "UPDATE_PASSWORD" if password.is_some() => {
+                password_expires = Some(Utc::now().timestamp());
+                planned.warn("The password must be renewed with the next login");
+            }
+            "UPDATE_PASSWORD" | "VERIFY_EMAIL" => {}
+            action => planned.warn(format!("Required action '{action}' is not supported")),
+        }
+    }
+
+    planned.value = Some(User {
+        email: email.to_string(),
+        given_name: kc.first_name.clone().unwrap_or_default(),
+        family_name: kc.last_name.clone().filter(|name| !name.is_empty()),
+        password,
+        roles: roles.join(","),
+        groups: opt_csv(&groups),
+        enabled: kc.enabled,
+        email_verified: kc.email_verified,
+        password_expires,
+        created_at: kc
+            .created_timestamp
+            .map(|ts| ts / 1000)
+            .unwrap_or_else(|| Utc::now().timestamp()),
+        ..Default::default()
+    });
+    planned
+}
+
+/// Converts the Keycloak password credential into a hash, which can be verified by
+/// `rauthy_common::password_hasher`.
+fn map_password(cred: &KcCredential) -> Result<String, String> {
+    let (Some(secret), Some(data)) = (&cred.secret_data, &cred.credential_data) else {
+        return Err("Unsupported password credential format".to_string());
+    };
+    let secret = serde_json::from_str::<KcSecretData>(secret).map_err(|err| err.to_string())?;
+    let data = serde_json::from_str::<KcCredentialData>(data).map_err(|err| err.to_string())?;
+
+    // The PHC string format uses base64 without padding.
+    let b64 = |value: &str| {
+        base64_decode(value)
+            .map(|bytes| base64_encode(&bytes).trim_end_matches('=').to_string())
+            .map_err(|_| "Invalid password hash encoding".to_string())
+    };
+    let param = |name: &str| {
+        data.additional_parameters
+            .get(name)
+            .and_then(|values| values.first())
+            .map(String::as_str)
+    };
+
+    match data.algorithm.as_str() {
+        "pbkdf2" | "pbkdf2-sha256" | "pbkdf2-sha512" => {
+            let alg = if data.algorithm == "pbkdf2" {
+                "pbkdf2-sha1"
+            } else {
+                data.algorithm.as_str()
+            };
+            let salt = b64(secret.salt.as_deref().ok_or("Missing password salt")?)?;
+            let hash = b64(&secret.value)?;
+            Ok(format!("${alg}$i={}${salt}${hash}", data.hash_iterations))
+        }
+        "argon2" => {
+            let typ = param("type").unwrap_or("id");
+            let version = match param("version").unwrap_or("1.3") {
+                "1.3" => 19,
+                _ => 16,
+            };
+            let memory = param("memory").unwrap_or("7168");
+            let parallelism = param("parallelism").unwrap_or("1");
+            let salt = b64(secret.salt.as_deref().ok_or("Missing password salt")?)?;
+            let hash = b64(&secret.value)?;
+            Ok(format!(
+                "$argon2{typ}$v={version}$m={memory},t={},p={parallelism}${salt}${hash}",
+                data.hash_iterations
+            ))
+        }
+        "bcrypt" if secret.value.starts_with("$2") => Ok(secret.value),
+        alg => Err(format!(
+            "Unsupported password hash algorithm '{alg}', the user must reset the password"
+        )),
+    }
+}
+
+/// Resolves a Keycloak redirect URI against the client's `rootUrl` and validates it. Keycloak
+/// and Rauthy both only allow wildcards at the very end.
+fn translate_uri(uri: &str, root_url: Option<&str>) -> Result<String, String> {
+    let uri = uri.trim();
+    let abs = if uri.starts_with('/') {
+        let Some(root) = root_url else {
+            return Err(format!("Relative URI '{uri}' without a root URL"));
+        };
+        format!("{}{uri}", root.trim_end_matches('/'))
+    } else {
+        uri.to_string()
+    };
+
+    // native apps may use private-use schemes like `com.example.app:/callback`
+    let has_scheme = abs
+        .split_once(':')
+        .is_some_and(|(scheme, _)| !scheme.is_empty() && !scheme.contains(['/', '*']));
+    if !has_scheme {
+        return Err(format!("Invalid URI '{uri}'"));
+    }
+    if abs.find('*').is_some_and(|idx| idx != abs.len() - 1) {
+        return Err(format!("Wildcards are only supported at the end: '{uri}'"));
+    }
+    if !RE_URI.is_match(&abs) {
+        return Err(format!("Invalid URI '{uri}'"));
+    }
+    Ok(abs)
+}
+
+/// Returns the `scheme://host[:port]` part of an absolute URI.
+fn origin_of(uri: &str) -> Option<String> {
+    let (scheme, rest) = uri.split_once("://")?;
+    let host = rest.split(['/', '?', '#']).next()?;
+    if scheme.is_empty() || host.is_empty() || host.contains('*') {
+        return None;
+    }
+    Some(format!("{scheme}://{host}"))
+}
+
+fn opt_csv<T: AsRef<str>>(values: &[T]) -> Option<String> {
+    if values.is_empty() {
+        None
+    } else {
+        Some(
+            values
+                .iter()
+                .map(AsRef::as_ref)
+                .collect::<Vec<_>>()
+                .join(","),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn fixture() -> KeycloakImport {
+        KeycloakImport::parse(include_bytes!(
+            "../../tests/fixtures/keycloak/realm_export.json"
+        ))
+        .unwrap()
+    }
+
+    fn actions<T>(planned: &[Planned<T>]) -> Vec<(&str, KeycloakImportAction)> {
+        planned
+            .iter()
+            .map(|p| (p.report.key.as_str(), p.report.action))
+            .collect()
+    }
+
+    fn find<'a, T>(planned: &'a [Planned<T>], key: &str) -> &'a Planned<T> {
+        planned.iter().find(|p| p.report.key == key).unwrap()
+    }
+
+    #[test]
+    fn test_keycloak_roles_groups() {
+        let import = fixture();
+        assert_eq!(import.realm, "acme");
+
+        assert_eq!(
+            actions(&import.roles),
+            vec![
+                ("offline_access", KeycloakImportAction::Skip),
+                ("uma_authorization", KeycloakImportAction::Skip),
+                ("default-roles-acme", KeycloakImportAction::Skip),
+                ("admin", KeycloakImportAction::Create),
+                ("developer", KeycloakImportAction::Create),
+                ("on-call", KeycloakImportAction::Create),
+                ("read only", KeycloakImportAction::Skip),
+                ("inventory:inventory-admin", KeycloakImportAction::Skip),
+            ]
+        );
+        let on_call = find(&import.roles, "on-call");
+        assert_eq!(on_call.value.as_ref().unwrap().name, "on-call");
+        assert_eq!(on_call.report.warnings.len(), 1);
+
+        assert_eq!(
+            actions(&import.groups),
+            vec![
+                ("engineering/backend", KeycloakImportAction::Create),
+                ("engineering", KeycloakImportAction::Create),
+                ("support", KeycloakImportAction::Create),
+            ]
+        );
+        let backend = find(&import.groups, "engineering/backend");
+        assert_eq!(
+            backend.report.warnings,
+            vec![
+                "Group roles are granted to its members directly: on-call".to_string(),
+                "Client roles are not supported".to_string(),
+            ]
+        );
+        assert!(find(&import.groups, "support").report.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_keycloak_clients() {
+        let import = fixture();
+
+        assert_eq!(
+            actions(&import.clients),
+            vec![
+                ("account", KeycloakImportAction::Skip),
+                ("realm-management", KeycloakImportAction::Skip),
+                ("inventory", KeycloakImportAction::Create),
+                ("spa-frontend", KeycloakImportAction::Create),
+                ("acme-cli", KeycloakImportAction::Create),
+                ("reports", KeycloakImportAction::Create),
+                ("billing-api", KeycloakImportAction::Skip),
+                ("https://legacy.acme.test/saml", KeycloakImportAction::Skip),
+            ]
+        );
+
+        let inventory = find(&import.clients, "inventory");
+        let client = inventory.value.as_ref().unwrap();
+        assert_eq!(client.name.as_deref(), Some("Inventory"));
+        assert!(client.confidential);
+        assert!(client.secret.is_none());
+        assert_eq!(
+            client.redirect_uris,
+            "https://inventory.acme.test/oidc/callback/*,https://inventory-staging.acme.test/oidc/callback"
+        );
+        assert_eq!(
+            client.post_logout_redirect_uris.as_deref(),
+            Some(
+                "https://inventory.acme.test/oidc/callback/*,https://inventory-staging.acme.test/oidc/callback,https://inventory.acme.test/logged-out"
+            )
+        );
+        assert_eq!(
+            client.allowed_origins.as_deref(),
+            Some("https://inventory-staging.acme.test,https://inventory.acme.test")
+        );
+        assert_eq!(
+            client.flows_enabled,
+            "authorization_code,password,client_credentials,refresh_token"
+        );
+        assert_eq!(client.challenge, None);
+        assert_eq!(client.access_token_lifetime, 600);
+        assert_eq!(client.default_scopes, "openid,profile,email");
+        assert_eq!(
+            client.client_uri.as_deref(),
+            Some("https://inventory.acme.test/dashboard")
+        );
+        assert_eq!(
+            client.backchannel_logout_uri.as_deref(),
+            Some("https://inventory.acme.test/oidc/backchannel")
+        );
+        assert_eq!(
+            inventory.report.warnings,
+            vec![
+                "Invalid URI '*'".to_string(),
+                "Client scopes are not imported: inventory-scope".to_string(),
+                "Client secrets cannot be migrated, a new one is generated".to_string(),
+                "1 protocol mapper(s) are not imported".to_string(),
+            ]
+        );
+
+        let spa = find(&import.clients, "spa-frontend");
+        let client = spa.value.as_ref().unwrap();
+        assert!(!client.confidential);
+        assert_eq!(
+            client.redirect_uris,
+            "https://portal.acme.test/*,http://localhost:5173/callback"
+        );
+        assert_eq!(
+            client.allowed_origins.as_deref(),
+            Some("http://localhost:5173,https://portal.acme.test")
+        );
+        assert_eq!(client.flows_enabled, "authorization_code,refresh_token");
+        assert_eq!(client.challenge.as_deref(), Some("S256"));
+        // from the realm
+        assert_eq!(client.access_token_lifetime, 300);
+        assert_eq!(client.default_scopes, "openid,profile");
+        assert_eq!(
+            client.client_uri.as_deref(),
+            Some("https://portal.acme.test/")
+        );
+        assert_eq!(
+            spa.report.warnings,
+            vec![
+                "The wildcard web origin '*' is not supported".to_string(),
+                "The implicit flow is not supported".to_string(),
+                "User consent is not supported".to_string(),
+            ]
+        );
+
+        let cli = find(&import.clients, "acme-cli");
+        let client = cli.value.as_ref().unwrap();
+        assert_eq!(
+            client.redirect_uris,
+            "http://127.0.0.1/callback,com.acme.cli:/oauth2redirect"
+        );
+        assert_eq!(
+            client.flows_enabled,
+            format!("authorization_code,{GRANT_TYPE_DEVICE_CODE}")
+        );
+        assert_eq!(client.challenge.as_deref(), Some("S256"));
+        assert_eq!(
+            cli.report.warnings,
+            vec!["PKCE with S256 is enforced for public clients".to_string()]
+        );
+
+        let reports = find(&import.clients, "reports");
+        let client = reports.value.as_ref().unwrap();
+        assert!(!client.enabled);
+        assert_eq!(client.name, None);
+        assert_eq!(client.redirect_uris, "https://reports.acme.test/callback");
+        assert_eq!(
+            client.flows_enabled,
+            "authorization_code,client_credentials,refresh_token"
+        );
+        assert_eq!(client.challenge.as_deref(), Some("plain"));
+        assert_eq!(
+            reports.report.warnings,
+            vec![
+                "Wildcards are only supported at the end: '/app/*/callback'".to_string(),
+                "The client authenticator 'client-jwt' is not supported, a client secret is generated".to_string(),
+                "Authorization services are not supported".to_string(),
+                "Invalid client name 'Reports (internal)' is dropped".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_keycloak_users() {
+        let import = fixture();
+
+        assert_eq!(
+            actions(&import.users),
+            vec![
+                ("alice@acme.test", KeycloakImportAction::Create),
+                ("Bob@acme.test", KeycloakImportAction::Create),
+                ("carol@acme.test", KeycloakImportAction::Create),
+                ("dave@acme.test", KeycloakImportAction::Create),
+                ("erin", KeycloakImportAction::Skip),
+                ("frank@acme.test", KeycloakImportAction::Create),
+                ("ALICE@acme.test", KeycloakImportAction::Skip),
+                ("service-account-inventory", KeycloakImportAction::Skip),
+            ]
+        );
+
+        // the same hashes are verified in `rauthy_common::password_hasher`
+        let alice = find(&import.users, "alice@acme.test");
+        let user = alice.value.as_ref().unwrap();
+        assert_eq!(user.given_name, "Alice");
+        assert_eq!(user.family_name.as_deref(), Some("Example"));
+        assert!(user.email_verified);
+        assert_eq!(user.created_at, 1714041311);
+        // inherited from `/engineering/backend` and its parent `/engineering`
+        assert_eq!(user.roles, "admin,developer,on-call");
+        assert_eq!(user.groups.as_deref(), Some("engineering/backend"));
+        assert_eq!(
+            user.password.as_deref(),
+            Some(
+                "$pbkdf2-sha256$i=27500$cmF1dGh5LWtjLXNhbHQxNg$9C6NJd7W4Ujeb4WRx/DpnEVCBwtdRriunLZSQS7H8T9Z1igZ0RgU465WlbxKkmmYR7xTy/IEEZGFXUyqgS0wLA"
+            )
+        );
+        assert!(alice.report.warnings.is_empty());
+
+        let bob = find(&import.users, "Bob@acme.test");
+        let user = bob.value.as_ref().unwrap();
+        assert_eq!(user.roles, "");
+        assert_eq!(user.groups.as_deref(), Some("support"));
+        assert_eq!(
+            user.password.as_deref(),
+            Some(
+                "$pbkdf2-sha512$i=210000$cmF1dGh5LWtjLXNhbHQxNg$zI9E5GeX78p9b2gjJ/myiFJtpyp1NnS/9q69CuXri8TwMAQ4MWQvQC9shaMHCO2UfIAgoSbyNZLXbz5pxNcaDA"
+            )
+        );
+        assert_eq!(
+            bob.report.warnings,
+            vec!["OTP is not supported, the user must register a passkey".to_string()]
+        );
+
+        let carol = find(&import.users, "carol@acme.test");
+        let user = carol.value.as_ref().unwrap();
+        assert_eq!(user.roles, "on-call");
+        assert_eq!(user.groups, None);
+        assert!(!user.email_verified);
+        assert_eq!(
+            user.password.as_deref(),
+            Some("$2b$04$DgtgKkWswLfQzIBaSq2Vc.gK1XxbG1Uuy/3wxR.XZMPn.Akb7IhSq")
+        );
+        assert!(user.password_expires.is_some());
+        assert_eq!(
+            carol.report.warnings,
+            vec![
+                "Role 'read only' was not imported".to_string(),
+                "The password must be renewed with the next login".to_string(),
+            ]
+        );
+
+        let dave = find(&import.users, "dave@acme.test");
+        let user = dave.value.as_ref().unwrap();
+        assert!(!user.enabled);
+        assert_eq!(user.roles, "developer");
+        assert_eq!(
+            user.password.as_deref(),
+            Some(
+                "$argon2id$v=19$m=7168,t=5,p=1$c2FsdHNhbHRzYWx0c2FsdA$3q2+7zLP0yRtV0cSDg9nR3a+AnsGG9XnAk71SGoN3cE"
+            )
+        );
+        assert_eq!(
+            dave.report.warnings,
+            vec![
+                "Client roles are not supported".to_string(),
+                "Passkeys cannot be migrated and must be registered again".to_string(),
+                "Required action 'CONFIGURE_TOTP' is not supported".to_string(),
+            ]
+        );
+
+        let frank = find(&import.users, "frank@acme.test");
+        let user = frank.value.as_ref().unwrap();
+        assert_eq!(user.password, None);
+        assert_eq!(user.groups.as_deref(), Some("support"));
+        assert_eq!(
+            frank.report.warnings,
+            vec![
+                "Group 'contractors' was not imported".to_string(),
+                "User federation is not supported, the user must reset the password".to_string(),
+                "Linked identity providers are not imported".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_keycloak_translate_uri() {
+        let root = Some("https://app.example.com/");
+        assert_eq!(
+            translate_uri("/callback/*", root).as_deref(),
+            Ok("https://app.example.com/callback/*")
+        );
+        assert_eq!(
+            translate_uri("https://other.example.com/cb", root).as_deref(),
+            Ok("https://other.example.com/cb")
+        );
+        assert!(translate_uri("/callback", None).is_err());
+        assert!(translate_uri("*", root).is_err());
+        assert!(translate_uri("/*", None).is_err());
+        assert!(translate_uri("https://*.example.com/cb", root).is_err());
+
+        assert_eq!(
+            origin_of("https://app.example.com:8443/cb?a=b").as_deref(),
+            Some("https://app.example.com:8443")
+        );
+        assert_eq!(origin_of("com.example.app:/cb"), None);
+    }
+}
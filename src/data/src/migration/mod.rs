@@ -1,5 +1,7 @@
 pub mod anti_lockout;
+pub mod archive;
 pub mod bootstrap;
 pub mod db_migrate;
 pub mod db_migrate_dev;
 mod inserts;
+pub mod keycloak;
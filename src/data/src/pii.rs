@@ -0,0 +1,518 @@
+use crate::database::DB;
+use crate::rauthy_config::RauthyConfig;
+use cryptr::{EncKeys, EncValue};
+use hiqlite::Params;
+use hiqlite::macros::params;
+use rauthy_api_types::users::UserResponseSimple;
+use rauthy_common::is_hiqlite;
+use rauthy_common::utils::{base64_decode, base64_encode, get_rand_bytes};
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use serde::{Deserialize, Deserializer};
+use std::sync::OnceLock;
+use tracing::{error, info};
+
+/// Encrypted PII values are stored as `PII_PREFIX` + base64(EncValue). Everything else is
+/// plaintext, which makes it possible to convert existing rows step by step.
+const PII_PREFIX: &str = "$pii$";
+const BATCH_SIZE: i64 = 100;
+pub const CONFIG_ID_HASH_KEY: &str = "pii_hash_key";
+
+static HASH_KEY: OnceLock<[u8; 32]> = OnceLock::new();
+
+/// Returns `true` if `encryption.pii_at_rest` is set.
+#[inline]
+pub fn is_enabled() -> bool {
+    RauthyConfig::get().vars.encryption.pii_at_rest
+}
+
+/// Loads the key for the E-Mail hashes, or generates it on the very first start.
+/// Must be called after the DB migrations and before any user is inserted or looked up.
+pub async fn init() -> Result<(), ErrorResponse> {
+    if !is_enabled() {
+        return Ok(());
+    }
+
+    let key = match find_hash_key().await? {
+        Some(key) => key,
+        None => {
+            info!("Generating new key for PII E-Mail hashes");
+            let enc = EncValue::encrypt(&get_rand_bytes::<32>())?
+                .into_bytes()
+                .to_vec();
+
+            // Another node may have been faster -> always read the key back afterward.
+            let sql = "INSERT INTO config (id, data) VALUES ($1, $2) ON CONFLICT (id) DO NOTHING";
+            if is_hiqlite() {
+                DB::hql()
+                    .execute(sql, params!(CONFIG_ID_HASH_KEY, enc))
+                    .await?;
+            } else {
+                DB::pg_execute(sql, &[&CONFIG_ID_HASH_KEY, &enc]).await?;
+            }
+
+            find_hash_key().await?.ok_or_else(|| {
+                ErrorResponse::new(
+                    ErrorResponseType::Internal,
+                    "PII hash key missing after insert",
+                )
+            })?
+        }
+    };
+
+    let _ = HASH_KEY.set(key);
+    Ok(())
+}
+
+async fn find_hash_key() -> Result<Option<[u8; 32]>, ErrorResponse> {
+    let Some(enc) = find_hash_key_enc().await? else {
+        return Ok(None);
+    };
+
+    let dec = EncValue::try_from(enc)?.decrypt()?;
+    let key = <[u8; 32]>::try_from(dec.as_ref()).map_err(|_| {
+        ErrorResponse::new(ErrorResponseType::Internal, "Invalid PII hash key length")
+    })?;
+    Ok(Some(key))
+}
+
+async fn find_hash_key_enc() -> Result<Option<Vec<u8>>, ErrorResponse> {
+    let sql = "SELECT data FROM config WHERE id = $1";
+    let enc = if is_hiqlite() {
+        let mut rows = DB::hql()
+            .query_raw(sql, params!(CONFIG_ID_HASH_KEY))
+            .await?;
+        rows.pop().map(|mut row| row.get::<Vec<u8>>("data"))
+    } else {
+        let mut rows = DB::pg_query_rows(sql, &[&CONFIG_ID_HASH_KEY], 1).await?;
+        rows.pop().map(|row| row.get::<_, Vec<u8>>("data"))
+    };
+    Ok(enc)
+}
+
+/// Returns the keyed hash for an already normalized `email`, or `None` if PII encryption is
+/// disabled.
+pub fn email_hash(email: &str) -> Option<String> {
+    if !is_enabled() {
+        return None;
+    }
+    let key = HASH_KEY
+        .get()
+        .expect("pii::init() must be called at startup");
+    Some(hash_with_key(key, email))
+}
+
+#[inline]
+fn hash_with_key(key: &[u8; 32], email: &str) -> String {
+    hex::encode(hmac_sha256::HMAC::mac(email.as_bytes(), key))
+}
+
+#[inline]
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(PII_PREFIX)
+}
+
+/// Encrypts `value` with the active key if PII encryption is enabled, and returns it unchanged
+/// otherwise.
+pub fn encrypt(value: &str) -> Result<String, ErrorResponse> {
+    if !is_enabled() || is_encrypted(value) {
+        return Ok(value.to_string());
+    }
+    encrypt_with_key_id(value, EncKeys::get_static().enc_key_active.clone())
+}
+
+#[inline]
+pub fn encrypt_opt(value: Option<&str>) -> Result<Option<String>, ErrorResponse> {
+    value.map(encrypt).transpose()
+}
+
+fn encrypt_with_key_id(value: &str, kid: String) -> Result<String, ErrorResponse> {
+    let enc = EncValue::encrypt_with_key_id(value.as_bytes(), kid)?;
+    Ok(format!(
+        "{PII_PREFIX}{}",
+        base64_encode(enc.into_bytes().as_ref())
+    ))
+}
+
+fn try_decrypt(value: &str) -> Result<String, ErrorResponse> {
+    let b64 = value.strip_prefix(PII_PREFIX).unwrap_or(value);
+    let dec = EncValue::try_from(base64_decode(b64)?)?.decrypt()?;
+    String::from_utf8(dec.to_vec()).map_err(|_| {
+        ErrorResponse::new(ErrorResponseType::Internal, "PII value is not valid UTF-8")
+    })
+}
+
+/// Decrypts `value` if it is encrypted and returns plaintext unchanged. This makes it safe to
+/// be used for values from the DB and the cache alike.
+///
+/// A value that cannot be decrypted is logged and returned as it is, because the callers
+/// cannot handle errors.
+pub fn decrypt(value: String) -> String {
+    if !is_encrypted(&value) {
+        return value;
+    }
+    match try_decrypt(&value) {
+        Ok(plain) => plain,
+        Err(err) => {
+            error!(?err, "Cannot decrypt PII value");
+            value
+        }
+    }
+}
+
+#[inline]
+pub fn decrypt_opt(value: Option<String>) -> Option<String> {
+    value.map(decrypt)
+}
+
+/// `UserResponseSimple` is selected directly from the `users` table.
+pub fn decrypt_users_simple(users: &mut [UserResponseSimple]) {
+    for user in users {
+        user.email = decrypt(std::mem::take(&mut user.email));
+        user.given_name = decrypt_opt(user.given_name.take());
+        user.family_name = decrypt_opt(user.family_name.take());
+    }
+}
+
+pub fn deserialize<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    String::deserialize(deserializer).map(decrypt)
+}
+
+pub fn deserialize_opt<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer).map(decrypt_opt)
+}
+
+/// The PII columns of a user, as they need to be written to the `users` table.
+pub struct UserPii {
+    pub email: String,
+    pub email_hash: Option<String>,
+    pub given_name: String,
+    pub family_name: Option<String>,
+}
+
+impl UserPii {
+    pub fn new(
+        email: &str,
+        given_name: &str,
+        family_name: Option<&str>,
+    ) -> Result<Self, ErrorResponse> {
+        Ok(Self {
+            email: encrypt(email)?,
+            email_hash: email_hash(email),
+            given_name: encrypt(given_name)?,
+            family_name: encrypt_opt(family_name)?,
+        })
+    }
+}
+
+/// The raw, possibly encrypted PII of a single user.
+struct RawPii {
+    id: String,
+    email: String,
+    email_hash: Option<String>,
+    given_name: String,
+    family_name: Option<String>,
+    phone: Option<String>,
+}
+
+impl RawPii {
+    async fn find_batch(sql: &'static str, after_id: &str) -> Result<Vec<Self>, ErrorResponse> {
+        let mut res = Vec::with_capacity(BATCH_SIZE as usize);
+
+        if is_hiqlite() {
+            let rows = DB::hql()
+                .query_raw(sql, params!(after_id, BATCH_SIZE))
+                .await?;
+            for mut row in rows {
+                res.push(Self {
+                    id: row.get("id"),
+                    email: row.get("email"),
+                    email_hash: row.get("email_hash"),
+                    given_name: row.get("given_name"),
+                    family_name: row.get("family_name"),
+                    phone: row.get("phone"),
+                });
+            }
+        } else {
+            let rows =
+                DB::pg_query_rows(sql, &[&after_id, &BATCH_SIZE], BATCH_SIZE as usize).await?;
+            for row in rows {
+                res.push(Self {
+                    id: row.get("id"),
+                    email: row.get("email"),
+                    email_hash: row.get("email_hash"),
+                    given_name: row.get("given_name"),
+                    family_name: row.get("family_name"),
+                    phone: row.get("phone"),
+                });
+            }
+        }
+
+        Ok(res)
+    }
+}
+
+async fn update_batch(
+    users: Vec<(String, UserPii)>,
+    phones: Vec<(String, String)>,
+) -> Result<(), ErrorResponse> {
+    let sql_users = r#"
+UPDATE users SET email = $1, email_hash = $2, given_name = $3, family_name = $4
+WHERE id = $5"#;
+    let sql_values = "UPDATE users_values SET phone = $1 WHERE id = $2";
+
+    if is_hiqlite() {
+        let mut txn: Vec<(&str, Params)> = Vec::with_capacity(users.len() + phones.len());
+        for (id, pii) in users {
+            txn.push((
+                sql_users,
+                params!(
+                    pii.email,
+                    pii.email_hash,
+                    pii.given_name,
+                    pii.family_name,
+                    id
+                ),
+            ));
+        }
+        for (id, phone) in phones {
+            txn.push((sql_values, params!(phone, id)));
+        }
+        DB::hql().txn(txn).await?;
+    } else {
+        let mut cl = DB::pg().await?;
+        let txn = cl.transaction().await?;
+        for (id, pii) in &users {
+            DB::pg_txn_append(
+                &txn,
+                sql_users,
+                &[
+                    &pii.email,
+                    &pii.email_hash,
+                    &pii.given_name,
+                    &pii.family_name,
+                    id,
+                ],
+            )
+            .await?;
+        }
+        for (id, phone) in &phones {
+            DB::pg_txn_append(&txn, sql_values, &[phone, id]).await?;
+        }
+        txn.commit().await?;
+    }
+
+    Ok(())
+}
+
+/// Converts all users, which still have plaintext PII, in batches. Each batch runs inside its
+/// own transaction. Converted users are marked by their `email_hash`, so the migration can
+/// simply be started again, if it has been interrupted.
+///
+/// Returns the count of converted users.
+pub async fn migrate_plaintext() -> Result<u32, ErrorResponse> {
+    if !is_enabled() {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "PII encryption is not enabled",
+        ));
+    }
+
+    let sql = r#"
+SELECT u.id, u.email, u.email_hash, u.given_name, u.family_name, uv.phone
+FROM users u
+LEFT JOIN users_values uv ON u.id = uv.id
+WHERE u.email_hash IS NULL AND u.id > $1
+ORDER BY u.id ASC
+LIMIT $2"#;
+
+    let mut count = 0;
+    let mut after_id = String::default();
+    loop {
+        let batch = RawPii::find_batch(sql, &after_id).await?;
+        let Some(last) = batch.last() else {
+            break;
+        };
+        after_id = last.id.clone();
+
+        let mut users = Vec::with_capacity(batch.len());
+        let mut phones = Vec::new();
+        for raw in batch {
+            let email = decrypt(raw.email);
+            let given_name = decrypt(raw.given_name);
+            let family_name = decrypt_opt(raw.family_name);
+            users.push((
+                raw.id.clone(),
+                UserPii::new(&email, &given_name, family_name.as_deref())?,
+            ));
+
+            if let Some(phone) = raw.phone
+                && !is_encrypted(&phone)
+            {
+                phones.push((raw.id, encrypt(&phone)?));
+            }
+        }
+
+        count += users.len() as u32;
+        update_batch(users, phones).await?;
+        info!(count, "PII plaintext migration batch done");
+    }
+
+    Ok(count)
+}
+
+/// Re-encrypts the PII of the next batch of users with an `id` greater than `after_id` with
+/// `to_kid`. Only values encrypted with `from_kid` are updated, or with any other key than
+/// `to_kid` if `None`.
+///
+/// Returns the last `id` of this batch and the count of updated users, or `None` when all
+/// users have been processed.
+pub async fn re_encrypt_batch(
+    from_kid: Option<&str>,
+    to_kid: &str,
+    after_id: &str,
+) -> Result<Option<(String, u32)>, ErrorResponse> {
+    let sql = r#"
+SELECT u.id, u.email, u.email_hash, u.given_name, u.family_name, uv.phone
+FROM users u
+LEFT JOIN users_values uv ON u.id = uv.id
+WHERE u.id > $1
+ORDER BY u.id ASC
+LIMIT $2"#;
+
+    let batch = RawPii::find_batch(sql, after_id).await?;
+    let Some(last_id) = batch.last().map(|raw| raw.id.clone()) else {
+        return Ok(None);
+    };
+
+    let needs_update = |value: &str| -> Result<bool, ErrorResponse> {
+        if !is_encrypted(value) {
+            return Ok(false);
+        }
+        let b64 = value.strip_prefix(PII_PREFIX).unwrap_or(value);
+        let kid = EncValue::try_from(base64_decode(b64)?)?.header.enc_key_id;
+        Ok(match from_kid {
+            Some(from) => kid == from,
+            None => kid != to_kid,
+        })
+    };
+    // Partially converted users may still contain plaintext values, which are kept as they are.
+    let re_encrypt = |value: &str| -> Result<String, ErrorResponse> {
+        if is_encrypted(value) {
+            encrypt_with_key_id(&try_decrypt(value)?, to_kid.to_string())
+        } else {
+            Ok(value.to_string())
+        }
+    };
+
+    let mut users = Vec::with_capacity(batch.len());
+    let mut phones = Vec::new();
+    for raw in batch {
+        let family_name = raw.family_name.as_deref().unwrap_or_default();
+        if needs_update(&raw.email)? || needs_update(&raw.given_name)? || needs_update(family_name)?
+        {
+            let pii = UserPii {
+                email: re_encrypt(&raw.email)?,
+                email_hash: raw.email_hash,
+                given_name: re_encrypt(&raw.given_name)?,
+                family_name: raw.family_name.as_deref().map(re_encrypt).transpose()?,
+            };
+            users.push((raw.id.clone(), pii));
+        }
+
+        if let Some(phone) = raw.phone
+            && needs_update(&phone)?
+        {
+            phones.push((raw.id, re_encrypt(&phone)?));
+        }
+    }
+
+    let count = users.len() as u32;
+    if !users.is_empty() || !phones.is_empty() {
+        update_batch(users, phones).await?;
+    }
+
+    Ok(Some((last_id, count)))
+}
+
+/// Re-encrypts all PII and the E-Mail hash key, that are not encrypted with `to_kid` yet.
+/// Returns the count of updated users.
+pub async fn re_encrypt_all(to_kid: &str) -> Result<u32, ErrorResponse> {
+    re_encrypt_hash_key(None, to_kid).await?;
+
+    let mut count = 0;
+    let mut after_id = String::default();
+    while let Some((last_id, updated)) = re_encrypt_batch(None, to_kid, &after_id).await? {
+        after_id = last_id;
+        count += updated;
+    }
+    Ok(count)
+}
+
+/// Re-encrypts the E-Mail hash key itself, if it exists and is encrypted with `from_kid`, or
+/// any other key than `to_kid` if `None`. The key value does not change, so all existing
+/// hashes stay valid.
+pub async fn re_encrypt_hash_key(
+    from_kid: Option<&str>,
+    to_kid: &str,
+) -> Result<(), ErrorResponse> {
+    let Some(enc) = find_hash_key_enc().await? else {
+        return Ok(());
+    };
+    let enc = EncValue::try_from(enc)?;
+    let kid = enc.header.enc_key_id.as_str();
+    let matches = match from_kid {
+        Some(from) => kid == from,
+        None => kid != to_kid,
+    };
+    if !matches {
+        return Ok(());
+    }
+
+    let data = EncValue::encrypt_with_key_id(enc.decrypt()?.as_ref(), to_kid.to_string())?
+        .into_bytes()
+        .to_vec();
+    let sql = "UPDATE config SET data = $1 WHERE id = $2";
+    if is_hiqlite() {
+        DB::hql()
+            .execute(sql, params!(data, CONFIG_ID_HASH_KEY))
+            .await?;
+    } else {
+        DB::pg_execute(sql, &[&data, &CONFIG_ID_HASH_KEY]).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pii_plaintext_passthrough() {
+        let plain = "admin@localhost".to_string();
+        assert!(!is_encrypted(&plain));
+        assert_eq!(decrypt(plain.clone()), plain);
+        assert_eq!(decrypt_opt(None), None);
+
+        // an invalid ciphertext must never panic and is returned as it is
+        let invalid = format!("{PII_PREFIX}not-base64");
+        assert!(is_encrypted(&invalid));
+        assert_eq!(decrypt(invalid.clone()), invalid);
+    }
+
+    #[test]
+    fn test_pii_email_hash() {
+        let key = [7u8; 32];
+        let hash = hash_with_key(&key, "admin@localhost");
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, hash_with_key(&key, "admin@localhost"));
+        assert_ne!(hash, hash_with_key(&key, "admin@localhost.de"));
+        assert_ne!(hash, hash_with_key(&[8u8; 32], "admin@localhost"));
+    }
+}
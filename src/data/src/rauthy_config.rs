@@ -1,15 +1,18 @@
 use crate::ListenScheme;
 use crate::email::mailer::{EMail, SmtpConnMode};
+use crate::entity::well_known::parse_wellknown_extra_claims;
 use crate::events::event::{Event, EventLevel};
 use crate::events::listener::EventRouterMsg;
 use crate::migration::bootstrap::generated_secrets;
 use crate::vault_config::VaultConfig;
 use cryptr::EncKeys;
 use hiqlite::NodeConfig;
-use rauthy_common::constants::CookieMode;
+use rauthy_common::constants::{CookieMode, RAUTHY_VERSION};
+use rauthy_common::jwt_time::JWT_LEEWAY_DEFAULT;
 use rauthy_common::logging::LogLevelAccess;
 use rauthy_common::regex::{RE_LINUX_USERNAME, RE_PREFERRED_USERNAME};
 use regex::Regex;
+use reqwest::tls;
 use serde::Serialize;
 use spow::pow::Pow;
 use std::borrow::Cow;
@@ -17,6 +20,7 @@ use std::env;
 use std::error::Error;
 use std::str::FromStr;
 use std::sync::OnceLock;
+use std::time::Duration;
 use tokio::fs;
 use tokio::sync::mpsc;
 use tokio_postgres::config;
@@ -250,6 +254,7 @@ pub struct Vars {
     pub dev: VarsDev,
     pub access: VarsAccess,
     pub auth_headers: VarsAuthHeaders,
+    pub auth_providers: VarsAuthProviders,
     pub atproto: VarsAtproto,
     pub backchannel_logout: VarsBackchannelLogout,
     pub bootstrap: VarsBootstrap,
@@ -307,11 +312,18 @@ impl Default for Vars {
                 cookie_set_path: true,
                 client_credentials_map_sub: false,
                 token_len_limit: 4096,
+                jwt_leeway: JWT_LEEWAY_DEFAULT,
+                max_scope_length: 1024,
+                max_scope_count: 20,
+                max_state_length: 2048,
+                max_nonce_length: 512,
+                max_redirect_uri_length: 2048,
                 token_revoke_on_logout: false,
                 token_revoke_device_tokens: false,
                 whoami_headers: false,
                 admin_button_hide: false,
                 redirect_root_to_account: false,
+                email_lowercase_local_part: true,
             },
             auth_headers: VarsAuthHeaders {
                 enable: false,
@@ -326,6 +338,16 @@ impl Default for Vars {
                 mfa: "x-forwarded-user-mfa".into(),
                 preferred_username: "x-forwarded-user-pref-username".into(),
             },
+            auth_providers: VarsAuthProviders {
+                retain_upstream_claims: true,
+                health_check: true,
+                health_check_threshold: 3,
+                health_fail_fast_secs: 300,
+                metadata_refresh_hours: 24,
+                upstream_refresh_mins: 60,
+                break_glass_login: false,
+                break_glass_auto_secs: 0,
+            },
             backchannel_logout: VarsBackchannelLogout {
                 retry_count: 100,
                 danger_allow_http: false,
@@ -352,6 +374,8 @@ impl Default for Vars {
             database: VarsDatabase {
                 hiqlite: true,
                 health_check_delay_secs: 30,
+                cache_warmup_on_startup: true,
+                cache_degradation: false,
                 pg_host: None,
                 pg_port: 5432,
                 pg_user: None,
@@ -368,6 +392,9 @@ impl Default for Vars {
                 migrate_pg_db_name: "rauthy".into(),
                 sched_user_exp_mins: 60,
                 sched_user_exp_delete_mins: None,
+                sched_maintenance_mins: 15,
+                sched_maintenance_batch_size: 1000,
+                sched_maintenance_grace_secs: 86400,
             },
             device_grant: VarsDeviceGrant {
                 code_lifetime: 300,
@@ -421,6 +448,8 @@ impl Default for Vars {
                 root_ca: None,
                 starttls_only: false,
                 danger_insecure: false,
+                notify_new_login_location: true,
+                notify_password_expiry: true,
                 tz_fmt: VarsEmailTzFmt {
                     de: "%d.%m.%Y %T (%Z)".into(),
                     en: "%m/%d/%Y %T (%Z)".into(),
@@ -437,6 +466,7 @@ impl Default for Vars {
             encryption: VarsEncryption {
                 key_active: String::default(),
                 keys: Vec::default(),
+                pii_at_rest: false,
             },
             ephemeral_clients: VarsEphemeralClients {
                 enable: false,
@@ -470,6 +500,8 @@ impl Default for Vars {
                 persist_level: EventLevel::Info,
                 cleanup_days: 31,
                 generate_token_issued: true,
+                expiring_window_days: 14,
+                expiring_digest: false,
                 level_new_user: EventLevel::Info,
                 level_user_email_change: EventLevel::Notice,
                 level_user_password_reset: EventLevel::Notice,
@@ -490,6 +522,22 @@ impl Default for Vars {
                 level_suspicious_request: EventLevel::Notice,
                 level_new_login_location: EventLevel::Notice,
                 level_token_issued: EventLevel::Info,
+                level_token_groups_overflow: EventLevel::Warning,
+                level_maintenance_summary: EventLevel::Info,
+                level_user_erased: EventLevel::Notice,
+                level_passkey_imported: EventLevel::Warning,
+                level_user_registration_pending: EventLevel::Warning,
+                level_provider_test_callback: EventLevel::Info,
+                level_migration_export: EventLevel::Warning,
+                level_migration_import: EventLevel::Warning,
+                level_refresh_token_binding_mismatch: EventLevel::Warning,
+                level_users_disabled_bulk: EventLevel::Notice,
+                level_maintenance_mode: EventLevel::Notice,
+                level_expiring_digest: EventLevel::Notice,
+                level_auth_provider_health: EventLevel::Warning,
+                level_provider_secret_rotated: EventLevel::Notice,
+                level_passkey_counter_anomaly: EventLevel::Warning,
+                level_break_glass_login: EventLevel::Critical,
                 level_failed_logins_25: EventLevel::Critical,
                 level_failed_logins_20: EventLevel::Critical,
                 level_failed_logins_15: EventLevel::Warning,
@@ -518,7 +566,9 @@ impl Default for Vars {
                 argon2_m_cost: 131072,
                 argon2_t_cost: 4,
                 argon2_p_cost: 8,
-                max_hash_threads: 2,
+                // resolved to physical cores - 1 during parsing
+                max_hash_threads: 0,
+                hash_queue_limit: 32,
                 hash_await_warn_time: 500,
             },
             http_client: VarsHttpClient {
@@ -529,6 +579,10 @@ impl Default for Vars {
                 danger_unencrypted: false,
                 danger_insecure: false,
                 root_ca_bundle: None,
+                proxy_url: None,
+                proxy_user: None,
+                proxy_password: None,
+                no_proxy: Vec::default(),
             },
             i18n: VarsI18n {
                 filter_lang_common: vec![
@@ -546,6 +600,7 @@ impl Default for Vars {
                     "nb".into(),
                     "uk".into(),
                 ],
+                custom_languages_dir: None,
             },
             lifetimes: VarsLifetimes {
                 refresh_token_grace_time: 5,
@@ -607,6 +662,14 @@ impl Default for Vars {
                 swagger_ui_public: false,
                 see_keep_alive: 30,
                 ssp_threshold: 1000,
+                shutdown_drain_timeout: 10,
+                csp_report_uri: None,
+                csp_img_src: Vec::default(),
+                csp_style_src: Vec::default(),
+                csp_directives: Vec::default(),
+                frame_ancestors: Vec::default(),
+                referrer_policy: "no-referrer".into(),
+                wellknown_extra_claims: serde_json::Map::default(),
             },
             suspicious_requests: VarsSuspiciousRequests {
                 blacklist: 1440,
@@ -1028,6 +1091,7 @@ Your account has not been compromised and no data was leaked."#.into()),
             },
             user_delete: VarsUserDelete {
                 enable_self_delete: false,
+                erasure_delete_audit_logs: true,
             },
             user_pictures: VarsUserPictures {
                 storage_type: "db".into(),
@@ -1046,6 +1110,7 @@ Your account has not been compromised and no data was leaked."#.into()),
                 domain_restriction: None,
                 domain_blacklist: Vec::default(),
                 allow_open_redirect: false,
+                require_admin_approval: false,
             },
             user_values: VarsUserValuesConfig {
                 given_name: UserValueConfigValue::Required,
@@ -1075,6 +1140,8 @@ Your account has not been compromised and no data was leaked."#.into()),
                 renew_exp: 2160,
                 force_uv: false,
                 no_password_exp: true,
+                allow_credential_import: false,
+                reject_sign_counter_anomaly: false,
             },
             atproto: VarsAtproto { enable: false },
         }
@@ -1122,6 +1189,7 @@ impl Vars {
         slf.parse_atproto(&mut table);
         slf.parse_access(&mut table);
         slf.parse_auth_headers(&mut table);
+        slf.parse_auth_providers(&mut table);
         slf.parse_backchannel_logout(&mut table);
         slf.parse_bootstrap(&mut table);
         slf.parse_cred_stuff(&mut table);
@@ -1264,6 +1332,29 @@ impl Vars {
         if let Some(v) = t_u32(&mut table, "access", "token_len_limit", "TOKEN_LEN_LIMIT") {
             self.access.token_len_limit = v;
         }
+        if let Some(v) = t_u16(&mut table, "access", "jwt_leeway", "JWT_LEEWAY") {
+            self.access.jwt_leeway = v;
+        }
+        if let Some(v) = t_u16(&mut table, "access", "max_scope_length", "MAX_SCOPE_LENGTH") {
+            self.access.max_scope_length = v;
+        }
+        if let Some(v) = t_u16(&mut table, "access", "max_scope_count", "MAX_SCOPE_COUNT") {
+            self.access.max_scope_count = v;
+        }
+        if let Some(v) = t_u16(&mut table, "access", "max_state_length", "MAX_STATE_LENGTH") {
+            self.access.max_state_length = v;
+        }
+        if let Some(v) = t_u16(&mut table, "access", "max_nonce_length", "MAX_NONCE_LENGTH") {
+            self.access.max_nonce_length = v;
+        }
+        if let Some(v) = t_u16(
+            &mut table,
+            "access",
+            "max_redirect_uri_length",
+            "MAX_REDIRECT_URI_LENGTH",
+        ) {
+            self.access.max_redirect_uri_length = v;
+        }
         if let Some(v) = t_bool(
             &mut table,
             "access",
@@ -1299,6 +1390,14 @@ impl Vars {
         ) {
             self.access.redirect_root_to_account = v;
         }
+        if let Some(v) = t_bool(
+            &mut table,
+            "access",
+            "email_lowercase_local_part",
+            "EMAIL_LOWERCASE_LOCAL_PART",
+        ) {
+            self.access.email_lowercase_local_part = v;
+        }
 
         check_empty(table, "access");
     }
@@ -1370,6 +1469,77 @@ impl Vars {
         check_empty(table, "auth_headers");
     }
 
+    fn parse_auth_providers(&mut self, table: &mut toml::Table) {
+        let mut table = t_table(table, "auth_providers");
+
+        if let Some(v) = t_bool(
+            &mut table,
+            "auth_providers",
+            "retain_upstream_claims",
+            "AUTH_PROVIDERS_RETAIN_UPSTREAM_CLAIMS",
+        ) {
+            self.auth_providers.retain_upstream_claims = v;
+        }
+        if let Some(v) = t_bool(
+            &mut table,
+            "auth_providers",
+            "health_check",
+            "AUTH_PROVIDERS_HEALTH_CHECK",
+        ) {
+            self.auth_providers.health_check = v;
+        }
+        if let Some(v) = t_u16(
+            &mut table,
+            "auth_providers",
+            "health_check_threshold",
+            "AUTH_PROVIDERS_HEALTH_CHECK_THRESHOLD",
+        ) {
+            self.auth_providers.health_check_threshold = v;
+        }
+        if let Some(v) = t_u32(
+            &mut table,
+            "auth_providers",
+            "health_fail_fast_secs",
+            "AUTH_PROVIDERS_HEALTH_FAIL_FAST_SECS",
+        ) {
+            self.auth_providers.health_fail_fast_secs = v;
+        }
+        if let Some(v) = t_u32(
+            &mut table,
+            "auth_providers",
+            "metadata_refresh_hours",
+            "AUTH_PROVIDERS_METADATA_REFRESH_HOURS",
+        ) {
+            self.auth_providers.metadata_refresh_hours = v;
+        }
+        if let Some(v) = t_u32(
+            &mut table,
+            "auth_providers",
+            "upstream_refresh_mins",
+            "AUTH_PROVIDERS_UPSTREAM_REFRESH_MINS",
+        ) {
+            self.auth_providers.upstream_refresh_mins = v;
+        }
+        if let Some(v) = t_bool(
+            &mut table,
+            "auth_providers",
+            "break_glass_login",
+            "AUTH_PROVIDERS_BREAK_GLASS_LOGIN",
+        ) {
+            self.auth_providers.break_glass_login = v;
+        }
+        if let Some(v) = t_u32(
+            &mut table,
+            "auth_providers",
+            "break_glass_auto_secs",
+            "AUTH_PROVIDERS_BREAK_GLASS_AUTO_SECS",
+        ) {
+            self.auth_providers.break_glass_auto_secs = v;
+        }
+
+        check_empty(table, "auth_providers");
+    }
+
     fn parse_backchannel_logout(&mut self, table: &mut toml::Table) {
         let mut table = t_table(table, "backchannel_logout");
 
@@ -1538,6 +1708,22 @@ impl Vars {
         ) {
             self.database.health_check_delay_secs = v;
         }
+        if let Some(v) = t_bool(
+            &mut table,
+            "database",
+            "cache_warmup_on_startup",
+            "CACHE_WARMUP_ON_STARTUP",
+        ) {
+            self.database.cache_warmup_on_startup = v;
+        }
+        if let Some(v) = t_bool(
+            &mut table,
+            "database",
+            "cache_degradation",
+            "CACHE_DEGRADATION",
+        ) {
+            self.database.cache_degradation = v;
+        }
 
         if let Some(v) = t_str(&mut table, "database", "pg_host", "PG_HOST") {
             self.database.pg_host = Some(v);
@@ -1619,6 +1805,30 @@ impl Vars {
         ) {
             self.database.sched_user_exp_delete_mins = Some(v);
         }
+        if let Some(v) = t_u32(
+            &mut table,
+            "database",
+            "sched_maintenance_mins",
+            "SCHED_MAINTENANCE_MINS",
+        ) {
+            self.database.sched_maintenance_mins = v;
+        }
+        if let Some(v) = t_u32(
+            &mut table,
+            "database",
+            "sched_maintenance_batch_size",
+            "SCHED_MAINTENANCE_BATCH_SIZE",
+        ) {
+            self.database.sched_maintenance_batch_size = v;
+        }
+        if let Some(v) = t_u32(
+            &mut table,
+            "database",
+            "sched_maintenance_grace_secs",
+            "SCHED_MAINTENANCE_GRACE_SECS",
+        ) {
+            self.database.sched_maintenance_grace_secs = v;
+        }
 
         check_empty(table, "database");
     }
@@ -1835,6 +2045,22 @@ impl Vars {
         ) {
             self.email.danger_insecure = v;
         }
+        if let Some(v) = t_bool(
+            &mut table,
+            "email",
+            "notify_new_login_location",
+            "EMAIL_NOTIFY_NEW_LOGIN_LOCATION",
+        ) {
+            self.email.notify_new_login_location = v;
+        }
+        if let Some(v) = t_bool(
+            &mut table,
+            "email",
+            "notify_password_expiry",
+            "EMAIL_NOTIFY_PASSWORD_EXPIRY",
+        ) {
+            self.email.notify_password_expiry = v;
+        }
 
         // [email.jobs]
         let mut jobs = t_table(&mut table, "jobs");
@@ -1924,6 +2150,9 @@ impl Vars {
         if let Some(v) = t_str_vec(&mut table, "encryption", "keys", "ENC_KEYS") {
             self.encryption.keys = v;
         }
+        if let Some(v) = t_bool(&mut table, "encryption", "pii_at_rest", "ENC_PII_AT_REST") {
+            self.encryption.pii_at_rest = v;
+        }
 
         check_empty(table, "encryption");
     }
@@ -2122,6 +2351,22 @@ impl Vars {
         ) {
             self.events.generate_token_issued = v;
         }
+        if let Some(v) = t_u16(
+            &mut table,
+            "events",
+            "expiring_window_days",
+            "EVENT_EXPIRING_WINDOW_DAYS",
+        ) {
+            self.events.expiring_window_days = v;
+        }
+        if let Some(v) = t_bool(
+            &mut table,
+            "events",
+            "expiring_digest",
+            "EVENT_EXPIRING_DIGEST",
+        ) {
+            self.events.expiring_digest = v;
+        }
 
         if let Some(v) = t_str(
             &mut table,
@@ -2303,6 +2548,150 @@ impl Vars {
             self.events.level_token_issued =
                 EventLevel::from_str(&v).expect("Cannot parse EventLevel for level_token_issued");
         }
+        if let Some(v) = t_str(
+            &mut table,
+            "events",
+            "level_token_groups_overflow",
+            "EVENT_LEVEL_TOKEN_GROUPS_OVERFLOW",
+        ) {
+            self.events.level_token_groups_overflow = EventLevel::from_str(&v)
+                .expect("Cannot parse EventLevel for level_token_groups_overflow");
+        }
+        if let Some(v) = t_str(
+            &mut table,
+            "events",
+            "level_maintenance_summary",
+            "EVENT_LEVEL_MAINTENANCE_SUMMARY",
+        ) {
+            self.events.level_maintenance_summary = EventLevel::from_str(&v)
+                .expect("Cannot parse EventLevel for level_maintenance_summary");
+        }
+        if let Some(v) = t_str(
+            &mut table,
+            "events",
+            "level_user_erased",
+            "EVENT_LEVEL_USER_ERASED",
+        ) {
+            self.events.level_user_erased =
+                EventLevel::from_str(&v).expect("Cannot parse EventLevel for level_user_erased");
+        }
+        if let Some(v) = t_str(
+            &mut table,
+            "events",
+            "level_passkey_imported",
+            "EVENT_LEVEL_PASSKEY_IMPORTED",
+        ) {
+            self.events.level_passkey_imported = EventLevel::from_str(&v)
+                .expect("Cannot parse EventLevel for level_passkey_imported");
+        }
+        if let Some(v) = t_str(
+            &mut table,
+            "events",
+            "level_user_registration_pending",
+            "EVENT_LEVEL_USER_REGISTRATION_PENDING",
+        ) {
+            self.events.level_user_registration_pending = EventLevel::from_str(&v)
+                .expect("Cannot parse EventLevel for level_user_registration_pending");
+        }
+        if let Some(v) = t_str(
+            &mut table,
+            "events",
+            "level_provider_test_callback",
+            "EVENT_LEVEL_PROVIDER_TEST_CALLBACK",
+        ) {
+            self.events.level_provider_test_callback = EventLevel::from_str(&v)
+                .expect("Cannot parse EventLevel for level_provider_test_callback");
+        }
+        if let Some(v) = t_str(
+            &mut table,
+            "events",
+            "level_migration_export",
+            "EVENT_LEVEL_MIGRATION_EXPORT",
+        ) {
+            self.events.level_migration_export = EventLevel::from_str(&v)
+                .expect("Cannot parse EventLevel for level_migration_export");
+        }
+        if let Some(v) = t_str(
+            &mut table,
+            "events",
+            "level_migration_import",
+            "EVENT_LEVEL_MIGRATION_IMPORT",
+        ) {
+            self.events.level_migration_import = EventLevel::from_str(&v)
+                .expect("Cannot parse EventLevel for level_migration_import");
+        }
+        if let Some(v) = t_str(
+            &mut table,
+            "events",
+            "level_refresh_token_binding_mismatch",
+            "EVENT_LEVEL_REFRESH_TOKEN_BINDING_MISMATCH",
+        ) {
+            self.events.level_refresh_token_binding_mismatch = EventLevel::from_str(&v)
+                .expect("Cannot parse EventLevel for level_refresh_token_binding_mismatch");
+        }
+        if let Some(v) = t_str(
+            &mut table,
+            "events",
+            "level_users_disabled_bulk",
+            "EVENT_LEVEL_USERS_DISABLED_BULK",
+        ) {
+            self.events.level_users_disabled_bulk = EventLevel::from_str(&v)
+                .expect("Cannot parse EventLevel for level_users_disabled_bulk");
+        }
+        if let Some(v) = t_str(
+            &mut table,
+            "events",
+            "level_maintenance_mode",
+            "EVENT_LEVEL_MAINTENANCE_MODE",
+        ) {
+            self.events.level_maintenance_mode = EventLevel::from_str(&v)
+                .expect("Cannot parse EventLevel for level_maintenance_mode");
+        }
+        if let Some(v) = t_str(
+            &mut table,
+            "events",
+            "level_expiring_digest",
+            "EVENT_LEVEL_EXPIRING_DIGEST",
+        ) {
+            self.events.level_expiring_digest = EventLevel::from_str(&v)
+                .expect("Cannot parse EventLevel for level_expiring_digest");
+        }
+        if let Some(v) = t_str(
+            &mut table,
+            "events",
+            "level_auth_provider_health",
+            "EVENT_LEVEL_AUTH_PROVIDER_HEALTH",
+        ) {
+            self.events.level_auth_provider_health = EventLevel::from_str(&v)
+                .expect("Cannot parse EventLevel for level_auth_provider_health");
+        }
+        if let Some(v) = t_str(
+            &mut table,
+            "events",
+            "level_provider_secret_rotated",
+            "EVENT_LEVEL_PROVIDER_SECRET_ROTATED",
+        ) {
+            self.events.level_provider_secret_rotated = EventLevel::from_str(&v)
+                .expect("Cannot parse EventLevel for level_provider_secret_rotated");
+        }
+        if let Some(v) = t_str(
+            &mut table,
+            "events",
+            "level_passkey_counter_anomaly",
+            "EVENT_LEVEL_PASSKEY_COUNTER_ANOMALY",
+        ) {
+            self.events.level_passkey_counter_anomaly = EventLevel::from_str(&v)
+                .expect("Cannot parse EventLevel for level_passkey_counter_anomaly");
+        }
+        if let Some(v) = t_str(
+            &mut table,
+            "events",
+            "level_break_glass_login",
+            "EVENT_LEVEL_BREAK_GLASS_LOGIN",
+        ) {
+            self.events.level_break_glass_login = EventLevel::from_str(&v)
+                .expect("Cannot parse EventLevel for level_break_glass_login");
+        }
 
         if let Some(v) = t_str(
             &mut table,
@@ -2495,6 +2884,21 @@ impl Vars {
         ) {
             self.hashing.max_hash_threads = v;
         }
+        if self.hashing.max_hash_threads == 0 {
+            self.hashing.max_hash_threads =
+                (num_cpus::get_physical() as u32).saturating_sub(1).max(1);
+        }
+        if let Some(v) = t_u32(
+            &mut table,
+            "hashing",
+            "hash_queue_limit",
+            "HASH_QUEUE_LIMIT",
+        ) {
+            if v == 0 {
+                panic!("`hashing.hash_queue_limit` must be greater than 0");
+            }
+            self.hashing.hash_queue_limit = v;
+        }
         if let Some(v) = t_u32(
             &mut table,
             "hashing",
@@ -2583,6 +2987,19 @@ impl Vars {
         ) {
             self.http_client.root_ca_bundle = Some(v);
         }
+        self.http_client.proxy_url =
+            t_str(&mut table, "http_client", "proxy_url", "HTTP_PROXY_URL");
+        self.http_client.proxy_user =
+            t_str(&mut table, "http_client", "proxy_user", "HTTP_PROXY_USER");
+        self.http_client.proxy_password = t_str(
+            &mut table,
+            "http_client",
+            "proxy_password",
+            "HTTP_PROXY_PASSWORD",
+        );
+        if let Some(v) = t_str_vec(&mut table, "http_client", "no_proxy", "HTTP_NO_PROXY") {
+            self.http_client.no_proxy = v;
+        }
 
         check_empty(table, "http_client");
     }
@@ -2601,6 +3018,14 @@ impl Vars {
         if let Some(v) = t_str_vec(&mut table, "i18n", "filter_lang_admin", "FILTER_LANG_ADMIN") {
             self.i18n.filter_lang_admin = v.into_iter().map(Cow::from).collect::<Vec<_>>();
         }
+        if let Some(v) = t_str(
+            &mut table,
+            "i18n",
+            "custom_languages_dir",
+            "I18N_CUSTOM_LANGUAGES_DIR",
+        ) {
+            self.i18n.custom_languages_dir = Some(v);
+        }
 
         check_empty(table, "i18n");
     }
@@ -2910,6 +3335,46 @@ impl Vars {
         if let Some(v) = t_u16(&mut table, "server", "ssp_threshold", "SSP_THRESHOLD") {
             self.server.ssp_threshold = v;
         }
+        if let Some(v) = t_u16(
+            &mut table,
+            "server",
+            "shutdown_drain_timeout",
+            "SHUTDOWN_DRAIN_TIMEOUT",
+        ) {
+            self.server.shutdown_drain_timeout = v;
+        }
+        if let Some(v) = t_str(
+            &mut table,
+            "server",
+            "csp_report_uri",
+            "CONTENT_SECURITY_POLICY_REPORT_URI",
+        ) {
+            self.server.csp_report_uri = Some(v);
+        }
+        if let Some(v) = t_str_vec(&mut table, "server", "csp_img_src", "CSP_IMG_SRC") {
+            self.server.csp_img_src = v;
+        }
+        if let Some(v) = t_str_vec(&mut table, "server", "csp_style_src", "CSP_STYLE_SRC") {
+            self.server.csp_style_src = v;
+        }
+        if let Some(v) = t_str_vec(&mut table, "server", "csp_directives", "CSP_DIRECTIVES") {
+            self.server.csp_directives = v;
+        }
+        if let Some(v) = t_str_vec(&mut table, "server", "frame_ancestors", "FRAME_ANCESTORS") {
+            self.server.frame_ancestors = v;
+        }
+        if let Some(v) = t_str(&mut table, "server", "referrer_policy", "REFERRER_POLICY") {
+            self.server.referrer_policy = v;
+        }
+        if let Some(v) = t_str(
+            &mut table,
+            "server",
+            "wellknown_extra_claims",
+            "WELLKNOWN_EXTRA_CLAIMS",
+        ) {
+            self.server.wellknown_extra_claims = parse_wellknown_extra_claims(&v)
+                .unwrap_or_else(|err| panic!("Invalid `server.wellknown_extra_claims`: {err}"));
+        }
 
         check_empty(table, "server");
     }
@@ -3175,6 +3640,14 @@ impl Vars {
         ) {
             self.user_delete.enable_self_delete = v;
         }
+        if let Some(v) = t_bool(
+            &mut table,
+            "user_delete",
+            "erasure_delete_audit_logs",
+            "ERASURE_DELETE_AUDIT_LOGS",
+        ) {
+            self.user_delete.erasure_delete_audit_logs = v;
+        }
 
         check_empty(table, "user_delete");
     }
@@ -3248,7 +3721,7 @@ impl Vars {
             "domain_restriction",
             "USER_REG_DOMAIN_RESTRICTION",
         ) {
-            self.user_registration.domain_restriction = Some(v);
+            self.user_registration.domain_restriction = Some(v.to_lowercase());
         }
         if let Some(v) = t_str_vec(
             &mut table,
@@ -3256,7 +3729,16 @@ impl Vars {
             "domain_blacklist",
             "USER_REG_DOMAIN_BLACKLIST",
         ) {
-            self.user_registration.domain_blacklist = v;
+            self.user_registration.domain_blacklist =
+                v.into_iter().map(|domain| domain.to_lowercase()).collect();
+        }
+        if let Some(v) = t_bool(
+            &mut table,
+            "user_registration",
+            "require_admin_approval",
+            "USER_REG_REQUIRE_ADMIN_APPROVAL",
+        ) {
+            self.user_registration.require_admin_approval = v;
         }
 
         check_empty(table, "user_registration");
@@ -3399,91 +3881,24 @@ impl Vars {
         ) {
             self.webauthn.no_password_exp = v;
         }
-
-        check_empty(table, "webauthn");
-    }
-
-    pub fn validate(&self) {
-        if !self.database.hiqlite
-            && (self.database.pg_host.is_none()
-                || self.database.pg_user.is_none()
-                || self.database.pg_password.is_none())
-        {
-            panic!("Database set to Postgres but missing `database.pg_*` config");
-        }
-
-        if self.device_grant.user_code_length > 255 {
-            panic!("device_grant.user_code_length must be <=255");
-        }
-
-        if self.dynamic_clients.enable && self.dynamic_clients.reg_token.is_none() {
-            warn!(
-                "Open dynamic client registration - consider setting a registration token, if possible."
-            );
-        }
-
-        if self.email.smtp_conn_mode == SmtpConnMode::XOauth2
-            || self.email.smtp_conn_mode == SmtpConnMode::MicrosoftGraph
-        {
-            if self.email.xoauth_url.is_none() {
-                panic!("'xoauth_url' not set");
-            }
-            if self.email.xoauth_client_id.is_none() {
-                panic!("'xoauth_client_id' not set");
-            }
-            if self.email.xoauth_client_secret.is_none() {
-                panic!("'xoauth_client_secret' not set");
-            }
-            if self.email.xoauth_scope.is_none() {
-                panic!("'xoauth_scope' not set");
-            }
-        }
-        if self.email.smtp_conn_mode == SmtpConnMode::MicrosoftGraph
-            && self.email.microsoft_graph_uri.is_none()
-        {
-            panic!("'microsoft_graph_uri' not set");
-        }
-
-        if self.encryption.keys.is_empty() || self.encryption.key_active.is_empty() {
-            panic!("Missing `encryption.keys` / `encryption.key_active`");
-        }
-
-        if self.geo.block_is_whitelist.unwrap_or(false) && self.geo.country_list.is_empty() {
-            panic!(
-                "`geolocation.country_list_type` is set to `whitelist` but \
-                `geolocation.country_list` is empty, this will not work, because it would block \
-                all requests."
-            );
-        }
-
-        if self.server.pub_url.is_empty() {
-            panic!("Empty `server.pub_url`");
-        }
-        if self.server.pub_url.contains("://") {
-            panic!(
-                "The `server.pub_url` must not contain the Scheme. Rauthy builds it automatically \
-                depending on a few other values."
-            );
+        if let Some(v) = t_bool(
+            &mut table,
+            "webauthn",
+            "allow_credential_import",
+            "WEBAUTHN_ALLOW_CREDENTIAL_IMPORT",
+        ) {
+            self.webauthn.allow_credential_import = v;
         }
-
-        if self.server.proxy_mode && self.server.trusted_proxies.is_empty() {
-            panic!("`server.proxy_mode` is set but `server.trusted_proxies` is empty");
+        if let Some(v) = t_bool(
+            &mut table,
+            "webauthn",
+            "reject_sign_counter_anomaly",
+            "WEBAUTHN_REJECT_SIGN_COUNTER_ANOMALY",
+        ) {
+            self.webauthn.reject_sign_counter_anomaly = v;
         }
 
-        if self.webauthn.rp_id.is_empty() {
-            panic!("`webauthn.rp_id` is missing");
-        }
-        if self.webauthn.rp_origin.is_empty() {
-            panic!("`webauthn.rp_origin` is missing");
-        }
-        let (_, webauthn_port) = self
-            .webauthn
-            .rp_origin
-            .rsplit_once(':')
-            .expect("Invalid format for `webauthn.rp_origin` - missing port");
-        if webauthn_port.parse::<u16>().is_err() {
-            panic!("Invalid value for `webauthn.rp_origin` port");
-        }
+        check_empty(table, "webauthn");
     }
 }
 
@@ -3508,11 +3923,18 @@ pub struct VarsAccess {
     pub cookie_set_path: bool,
     pub client_credentials_map_sub: bool,
     pub token_len_limit: u32,
+    pub jwt_leeway: u16,
+    pub max_scope_length: u16,
+    pub max_scope_count: u16,
+    pub max_state_length: u16,
+    pub max_nonce_length: u16,
+    pub max_redirect_uri_length: u16,
     pub token_revoke_on_logout: bool,
     pub token_revoke_device_tokens: bool,
     pub whoami_headers: bool,
     pub admin_button_hide: bool,
     pub redirect_root_to_account: bool,
+    pub email_lowercase_local_part: bool,
 }
 
 #[derive(Debug)]
@@ -3530,6 +3952,18 @@ pub struct VarsAuthHeaders {
     pub preferred_username: Cow<'static, str>,
 }
 
+#[derive(Debug)]
+pub struct VarsAuthProviders {
+    pub retain_upstream_claims: bool,
+    pub health_check: bool,
+    pub health_check_threshold: u16,
+    pub health_fail_fast_secs: u32,
+    pub metadata_refresh_hours: u32,
+    pub upstream_refresh_mins: u32,
+    pub break_glass_login: bool,
+    pub break_glass_auto_secs: u32,
+}
+
 #[derive(Debug)]
 pub struct VarsBackchannelLogout {
     pub retry_count: u16,
@@ -3563,6 +3997,8 @@ pub struct VarsCredStuff {
 pub struct VarsDatabase {
     pub hiqlite: bool,
     pub health_check_delay_secs: u32,
+    pub cache_warmup_on_startup: bool,
+    pub cache_degradation: bool,
 
     pub pg_host: Option<String>,
     pub pg_user: Option<String>,
@@ -3582,6 +4018,9 @@ pub struct VarsDatabase {
 
     pub sched_user_exp_mins: u32,
     pub sched_user_exp_delete_mins: Option<u32>,
+    pub sched_maintenance_mins: u32,
+    pub sched_maintenance_batch_size: u32,
+    pub sched_maintenance_grace_secs: u32,
 }
 
 #[derive(Debug)]
@@ -3633,6 +4072,8 @@ pub struct VarsEmail {
     pub root_ca: Option<String>,
     pub starttls_only: bool,
     pub danger_insecure: bool,
+    pub notify_new_login_location: bool,
+    pub notify_password_expiry: bool,
     pub tz_fmt: VarsEmailTzFmt,
 }
 
@@ -3662,6 +4103,7 @@ pub struct VarsEmailTzFmt {
 pub struct VarsEncryption {
     pub key_active: String,
     pub keys: Vec<String>,
+    pub pii_at_rest: bool,
 }
 
 #[derive(Debug)]
@@ -3702,6 +4144,8 @@ pub struct VarsEvents {
     pub persist_level: EventLevel,
     pub cleanup_days: u32,
     pub generate_token_issued: bool,
+    pub expiring_window_days: u16,
+    pub expiring_digest: bool,
 
     pub level_new_user: EventLevel,
     pub level_user_email_change: EventLevel,
@@ -3723,6 +4167,22 @@ pub struct VarsEvents {
     pub level_suspicious_request: EventLevel,
     pub level_new_login_location: EventLevel,
     pub level_token_issued: EventLevel,
+    pub level_token_groups_overflow: EventLevel,
+    pub level_maintenance_summary: EventLevel,
+    pub level_user_erased: EventLevel,
+    pub level_passkey_imported: EventLevel,
+    pub level_user_registration_pending: EventLevel,
+    pub level_provider_test_callback: EventLevel,
+    pub level_migration_export: EventLevel,
+    pub level_migration_import: EventLevel,
+    pub level_refresh_token_binding_mismatch: EventLevel,
+    pub level_users_disabled_bulk: EventLevel,
+    pub level_maintenance_mode: EventLevel,
+    pub level_expiring_digest: EventLevel,
+    pub level_auth_provider_health: EventLevel,
+    pub level_provider_secret_rotated: EventLevel,
+    pub level_passkey_counter_anomaly: EventLevel,
+    pub level_break_glass_login: EventLevel,
 
     pub level_failed_logins_25: EventLevel,
     pub level_failed_logins_20: EventLevel,
@@ -3760,6 +4220,7 @@ pub struct VarsHashing {
     pub argon2_t_cost: u32,
     pub argon2_p_cost: u32,
     pub max_hash_threads: u32,
+    pub hash_queue_limit: u32,
     pub hash_await_warn_time: u32,
 }
 
@@ -3772,12 +4233,93 @@ pub struct VarsHttpClient {
     pub danger_unencrypted: bool,
     pub danger_insecure: bool,
     pub root_ca_bundle: Option<String>,
+    pub proxy_url: Option<String>,
+    pub proxy_user: Option<String>,
+    pub proxy_password: Option<String>,
+    pub no_proxy: Vec<String>,
+}
+
+impl VarsHttpClient {
+    /// The builder for the global HTTP client. Clients for special cases like mTLS to an upstream
+    /// start from here too, so they share the same TLS, root CA and proxy config.
+    pub fn client_builder(&self, dev_mode: bool) -> reqwest::ClientBuilder {
+        let tls_version = match self.min_tls.as_ref() {
+            "1.3" => tls::Version::TLS_1_3,
+            "1.2" => tls::Version::TLS_1_2,
+            "1.1" => {
+                warn!(
+                    r#"
+    You are allowing TLS 1.1 for the global HTTP client.
+    Only do this, if you know what you are doing!
+    "#
+                );
+                tls::Version::TLS_1_1
+            }
+            "1.0" => {
+                warn!(
+                    r#"
+    You are allowing TLS 1.0 for the global HTTP client.
+    Only do this, if you know what you are doing!
+    "#
+                );
+                tls::Version::TLS_1_0
+            }
+            _ => panic!("Invalid value for HTTP_MIN_TLS, allowed: '1.3', '1.2', '1.1', '1.0'"),
+        };
+
+        let mut builder = reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(self.connect_timeout as u64))
+            .timeout(Duration::from_secs(self.request_timeout as u64))
+            .pool_idle_timeout(Duration::from_secs(self.idle_timeout as u64))
+            .min_tls_version(tls_version)
+            .user_agent(format!("Rauthy Client v{RAUTHY_VERSION}"))
+            .https_only(!self.danger_unencrypted || !dev_mode)
+            .danger_accept_invalid_certs(self.danger_insecure || dev_mode)
+            .use_rustls_tls();
+
+        if let Some(bundle) = self.root_ca_bundle.as_ref() {
+            let certs = reqwest::Certificate::from_pem_bundle(bundle.trim().as_bytes())
+                .expect("Cannot parse given HTTP_CUST_ROOT_CA_BUNDLE");
+            debug!(
+                "Adding {} custom Root CA certificates to HTTP Client",
+                certs.len()
+            );
+
+            for cert in certs {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+
+        if let Some(proxy) = self.proxy() {
+            debug!("Using an HTTP proxy for all outgoing requests");
+            builder = builder.proxy(proxy);
+        }
+
+        builder
+    }
+
+    /// Builds the proxy for all outgoing requests, if a `proxy_url` is configured.
+    /// Panics at startup for an invalid `proxy_url`.
+    pub fn proxy(&self) -> Option<reqwest::Proxy> {
+        let url = self.proxy_url.as_ref()?;
+        let mut proxy = reqwest::Proxy::all(url).expect("Cannot parse given HTTP_PROXY_URL");
+
+        if let Some(user) = &self.proxy_user {
+            proxy = proxy.basic_auth(user, self.proxy_password.as_deref().unwrap_or_default());
+        }
+        if !self.no_proxy.is_empty() {
+            proxy = proxy.no_proxy(reqwest::NoProxy::from_string(&self.no_proxy.join(",")));
+        }
+
+        Some(proxy)
+    }
 }
 
 #[derive(Debug)]
 pub struct VarsI18n {
     pub filter_lang_common: Vec<Cow<'static, str>>,
     pub filter_lang_admin: Vec<Cow<'static, str>>,
+    pub custom_languages_dir: Option<String>,
 }
 
 #[derive(Debug)]
@@ -3858,6 +4400,14 @@ pub struct VarsServer {
     pub swagger_ui_public: bool,
     pub see_keep_alive: u16,
     pub ssp_threshold: u16,
+    pub shutdown_drain_timeout: u16,
+    pub csp_report_uri: Option<String>,
+    pub csp_img_src: Vec<String>,
+    pub csp_style_src: Vec<String>,
+    pub csp_directives: Vec<String>,
+    pub frame_ancestors: Vec<String>,
+    pub referrer_policy: String,
+    pub wellknown_extra_claims: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug)]
@@ -3914,6 +4464,7 @@ pub struct VarsToS {
 #[derive(Debug)]
 pub struct VarsUserDelete {
     pub enable_self_delete: bool,
+    pub erasure_delete_audit_logs: bool,
 }
 
 #[derive(Debug)]
@@ -3936,6 +4487,7 @@ pub struct VarsUserRegistration {
     pub domain_restriction: Option<String>,
     pub domain_blacklist: Vec<String>,
     pub allow_open_redirect: bool,
+    pub require_admin_approval: bool,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -3995,6 +4547,8 @@ pub struct VarsWebauthn {
     pub renew_exp: u16,
     pub force_uv: bool,
     pub no_password_exp: bool,
+    pub allow_credential_import: bool,
+    pub reject_sign_counter_anomaly: bool,
 }
 
 #[derive(Debug)]
@@ -4152,3 +4706,103 @@ pub fn err_t(key: &str, parent: &str, typ: &str) -> String {
     let sep = if parent.is_empty() { "" } else { "." };
     format!("Expected type `{typ}` for {parent}{sep}{key}")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rauthy_common::utils::base64_encode;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Answers each request with a `200` and sends the request head through the channel.
+    async fn mock_server() -> (String, mpsc::UnboundedReceiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut req = Vec::new();
+                let mut buf = [0u8; 4096];
+                loop {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    req.extend_from_slice(&buf[..n]);
+                    if n == 0 || req.windows(4).any(|w| w == b"\r\n\r\n") {
+                        break;
+                    }
+                }
+                tx.send(String::from_utf8_lossy(&req).to_string()).unwrap();
+
+                stream
+                    .write_all(
+                        b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok",
+                    )
+                    .await
+                    .unwrap();
+            }
+        });
+
+        (addr, rx)
+    }
+
+    fn http_client_config(proxy_addr: &str, no_proxy: Vec<String>) -> VarsHttpClient {
+        VarsHttpClient {
+            connect_timeout: 10,
+            request_timeout: 10,
+            min_tls: "1.3".into(),
+            idle_timeout: 900,
+            danger_unencrypted: false,
+            danger_insecure: false,
+            root_ca_bundle: None,
+            proxy_url: Some(format!("http://{proxy_addr}")),
+            proxy_user: Some("rauthy".to_string()),
+            proxy_password: Some("proxy_secret".to_string()),
+            no_proxy,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_http_client_proxy() {
+        let (proxy_addr, mut proxy_rx) = mock_server().await;
+        let (direct_addr, mut direct_rx) = mock_server().await;
+
+        let config = http_client_config(&proxy_addr, vec!["127.0.0.1".to_string()]);
+        let client = reqwest::Client::builder()
+            .proxy(config.proxy().unwrap())
+            .build()
+            .unwrap();
+
+        // the upstream host does not even exist and can only be reached via the proxy
+        let res = client
+            .get("http://upstream.rauthy.invalid/.well-known/openid-configuration")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 200);
+        let head = proxy_rx.recv().await.unwrap().to_lowercase();
+        assert!(head.starts_with(
+            "get http://upstream.rauthy.invalid/.well-known/openid-configuration http/1.1"
+        ));
+        let auth = base64_encode(b"rauthy:proxy_secret");
+        assert!(head.contains(&format!("proxy-authorization: basic {auth}").to_lowercase()));
+
+        // hosts on the `no_proxy` list bypass the proxy
+        let res = client
+            .get(format!("http://{direct_addr}/jwks"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 200);
+        let head = direct_rx.recv().await.unwrap();
+        assert!(head.starts_with("GET /jwks HTTP/1.1"));
+        assert!(proxy_rx.try_recv().is_err());
+
+        let config = http_client_config(&proxy_addr, Vec::default());
+        assert!(config.proxy().is_some());
+        let config = VarsHttpClient {
+            proxy_url: None,
+            ..config
+        };
+        assert!(config.proxy().is_none());
+    }
+}
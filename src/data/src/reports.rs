@@ -0,0 +1,186 @@
+use crate::database::{Cache, DB};
+use crate::pii;
+use crate::rauthy_config::RauthyConfig;
+use chrono::Utc;
+use hiqlite::macros::params;
+use rauthy_api_types::reports::{
+    ExpiringApiKey, ExpiringDeviceToken, ExpiringRefreshTokens, ExpiringReportResponse,
+    ExpiringUser,
+};
+use rauthy_common::constants::{CACHE_TTL_EXPIRING_REPORT, IDX_EXPIRING_REPORT};
+use rauthy_common::is_hiqlite;
+use rauthy_error::ErrorResponse;
+
+/// Builds the report for everything that will lapse within the next `days`, which defaults to
+/// `events.expiring_window_days`. Each window is cached for a short time.
+pub async fn expiring_report(days: Option<u16>) -> Result<ExpiringReportResponse, ErrorResponse> {
+    let days = days.unwrap_or(RauthyConfig::get().vars.events.expiring_window_days);
+    let idx = format!("{IDX_EXPIRING_REPORT}{days}");
+    if let Some(report) = DB::hql().get(Cache::App, idx.clone()).await? {
+        return Ok(report);
+    }
+
+    let now = Utc::now().timestamp();
+    let until = now + days as i64 * 24 * 3600;
+    let report = ExpiringReportResponse {
+        generated: now,
+        until,
+        api_keys: api_keys(now, until).await?,
+        device_tokens: device_tokens(now, until).await?,
+        refresh_tokens: refresh_tokens(now, until).await?,
+        users: users(now, until).await?,
+    };
+
+    DB::hql()
+        .put(Cache::App, idx, &report, CACHE_TTL_EXPIRING_REPORT)
+        .await?;
+
+    Ok(report)
+}
+
+async fn api_keys(now: i64, until: i64) -> Result<Vec<ExpiringApiKey>, ErrorResponse> {
+    let sql = r#"
+SELECT name, expires
+FROM api_keys
+WHERE expires > $1 AND expires <= $2
+ORDER BY expires"#;
+
+    let res = if is_hiqlite() {
+        DB::hql()
+            .query_raw(sql, params!(now, until))
+            .await?
+            .into_iter()
+            .map(|mut row| ExpiringApiKey {
+                name: row.get("name"),
+                expires: row.get("expires"),
+            })
+            .collect()
+    } else {
+        DB::pg_query_rows(sql, &[&now, &until], 0)
+            .await?
+            .into_iter()
+            .map(|row| ExpiringApiKey {
+                name: row.get("name"),
+                expires: row.get("expires"),
+            })
+            .collect()
+    };
+
+    Ok(res)
+}
+
+/// Refresh tokens for devices are rotated as well, which means only devices whose latest
+/// refresh token expires within the window will stop working.
+async fn device_tokens(now: i64, until: i64) -> Result<Vec<ExpiringDeviceToken>, ErrorResponse> {
+    let sql = r#"
+SELECT d.id, d.name, d.client_id, d.user_id, u.email, MAX(rt.exp) AS expires
+FROM refresh_tokens_devices rt
+JOIN devices d ON d.id = rt.device_id
+LEFT JOIN users u ON u.id = d.user_id
+WHERE rt.exp > $1
+GROUP BY d.id, d.name, d.client_id, d.user_id, u.email
+HAVING MAX(rt.exp) <= $2
+ORDER BY expires"#;
+
+    let res = if is_hiqlite() {
+        DB::hql()
+            .query_raw(sql, params!(now, until))
+            .await?
+            .into_iter()
+            .map(|mut row| ExpiringDeviceToken {
+                device_id: row.get("id"),
+                device_name: row.get("name"),
+                client_id: row.get("client_id"),
+                user_id: row.get("user_id"),
+                email: pii::decrypt_opt(row.get("email")),
+                expires: row.get("expires"),
+            })
+            .collect()
+    } else {
+        DB::pg_query_rows(sql, &[&now, &until], 0)
+            .await?
+            .into_iter()
+            .map(|row| ExpiringDeviceToken {
+                device_id: row.get("id"),
+                device_name: row.get("name"),
+                client_id: row.get("client_id"),
+                user_id: row.get("user_id"),
+                email: pii::decrypt_opt(row.get("email")),
+                expires: row.get("expires"),
+            })
+            .collect()
+    };
+
+    Ok(res)
+}
+
+async fn refresh_tokens(now: i64, until: i64) -> Result<Vec<ExpiringRefreshTokens>, ErrorResponse> {
+    let sql = r#"
+SELECT rt.user_id, u.email, COUNT(*) AS count, MAX(rt.exp) AS expires
+FROM refresh_tokens rt
+JOIN users u ON u.id = rt.user_id
+WHERE rt.exp > $1
+GROUP BY rt.user_id, u.email
+HAVING MAX(rt.exp) <= $2
+ORDER BY expires"#;
+
+    let res = if is_hiqlite() {
+        DB::hql()
+            .query_raw(sql, params!(now, until))
+            .await?
+            .into_iter()
+            .map(|mut row| ExpiringRefreshTokens {
+                user_id: row.get("user_id"),
+                email: pii::decrypt(row.get("email")),
+                count: row.get("count"),
+                expires: row.get("expires"),
+            })
+            .collect()
+    } else {
+        DB::pg_query_rows(sql, &[&now, &until], 0)
+            .await?
+            .into_iter()
+            .map(|row| ExpiringRefreshTokens {
+                user_id: row.get("user_id"),
+                email: pii::decrypt(row.get("email")),
+                count: row.get("count"),
+                expires: row.get("expires"),
+            })
+            .collect()
+    };
+
+    Ok(res)
+}
+
+async fn users(now: i64, until: i64) -> Result<Vec<ExpiringUser>, ErrorResponse> {
+    let sql = r#"
+SELECT id, email, user_expires
+FROM users
+WHERE user_expires > $1 AND user_expires <= $2
+ORDER BY user_expires"#;
+
+    let res = if is_hiqlite() {
+        DB::hql()
+            .query_raw(sql, params!(now, until))
+            .await?
+            .into_iter()
+            .map(|mut row| ExpiringUser {
+                id: row.get("id"),
+                email: pii::decrypt(row.get("email")),
+                expires: row.get("user_expires"),
+            })
+            .collect()
+    } else {
+        DB::pg_query_rows(sql, &[&now, &until], 0)
+            .await?
+            .into_iter()
+            .map(|row| ExpiringUser {
+                id: row.get("id"),
+                email: pii::decrypt(row.get("email")),
+                expires: row.get("user_expires"),
+            })
+            .collect()
+    };
+
+    Ok(res)
+}
@@ -0,0 +1,546 @@
+use crate::database::{Cache, DB};
+use crate::entity::api_keys::ApiKeyEntity;
+use crate::entity::auth_provider_refresh_tokens::AuthProviderRefreshToken;
+use crate::entity::auth_providers::AuthProvider;
+use crate::entity::clients::Client;
+use crate::entity::clients_claims_webhook::ClientClaimsWebhook;
+use crate::entity::jwk::Jwk;
+use crate::entity::user_federation_claims::UserFederationClaims;
+use crate::events::event::Event;
+use crate::pii;
+use actix_web_lab::sse;
+use cryptr::{EncKeys, EncValue};
+use hiqlite::Params;
+use hiqlite::macros::params;
+use rauthy_api_types::generic::SecretsMigrationProgress;
+use rauthy_common::is_hiqlite;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use std::net::IpAddr;
+use tokio::sync::mpsc;
+use tracing::{error, info};
+
+/// Each batch is re-encrypted and written inside a single transaction.
+const BATCH_SIZE: usize = 100;
+
+/// All caches, which may contain entities with values encrypted with the old key.
+const CACHES_ENCRYPTED: [Cache; 2] = [Cache::App, Cache::User];
+
+/// Re-encrypts all values that are currently encrypted with `from_kid` with `to_kid`.
+///
+/// Each batch is committed inside its own transaction. If a batch fails, only this one is rolled
+/// back and all previous batches stay migrated. Because only values encrypted with `from_kid`
+/// are selected, the migration can simply be started again afterward.
+///
+/// The progress is sent to `tx` after each batch. Start, the end of each entity type and the
+/// completion create an Event.
+pub async fn migrate_secrets(
+    from_kid: String,
+    to_kid: String,
+    ip: IpAddr,
+    tx: mpsc::Sender<sse::Event>,
+) {
+    let mut migration = SecretsMigration {
+        from_kid,
+        to_kid,
+        ip,
+        tx,
+        progress: SecretsMigrationProgress::default(),
+    };
+
+    if let Err(err) = migration.run().await {
+        error!(?err, "Secrets migration failed");
+        migration.progress.error = Some(err.message.to_string());
+        migration.send_progress().await;
+        migration
+            .event(format!(
+                "Secrets migration from key `{}` to `{}` failed",
+                migration.from_kid, migration.to_kid
+            ))
+            .await;
+    }
+
+    // The caches may contain values encrypted with the old key.
+    for cache in CACHES_ENCRYPTED {
+        if let Err(err) = DB::hql().clear_cache(cache).await {
+            error!(?err, "Clearing the cache after the secrets migration");
+        }
+    }
+}
+
+/// Validates that both keys exist and differ before the migration can be started.
+pub fn validate_key_ids(from_kid: &str, to_kid: &str) -> Result<(), ErrorResponse> {
+    if from_kid == to_kid {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "`from_key_id` and `to_key_id` must differ",
+        ));
+    }
+    EncKeys::get_static_key(from_kid)?;
+    EncKeys::get_static_key(to_kid)?;
+    Ok(())
+}
+
+struct SecretsMigration {
+    from_kid: String,
+    to_kid: String,
+    ip: IpAddr,
+    tx: mpsc::Sender<sse::Event>,
+    progress: SecretsMigrationProgress,
+}
+
+impl SecretsMigration {
+    async fn run(&mut self) -> Result<(), ErrorResponse> {
+        info!(
+            from = self.from_kid,
+            to = self.to_kid,
+            "Starting secrets migration"
+        );
+        self.event(format!(
+            "Secrets migration from key `{}` to `{}` started",
+            self.from_kid, self.to_kid
+        ))
+        .await;
+
+        self.migrate_api_keys().await?;
+        self.migrate_auth_providers().await?;
+        self.migrate_clients().await?;
+        self.migrate_claims_webhooks().await?;
+        self.migrate_jwks().await?;
+        self.migrate_users().await?;
+        self.migrate_upstream_claims().await?;
+        self.migrate_upstream_refresh_tokens().await?;
+
+        self.progress.finished = true;
+        self.send_progress().await;
+
+        let p = &self.progress;
+        let text = format!(
+            "Secrets migration from key `{}` to `{}` finished: {} API Keys, {} Auth Providers, \
+            {} Clients, {} Claims Webhooks, {} JWKs, {} Users, {} Upstream Claims, \
+            {} Upstream Refresh Tokens",
+            self.from_kid,
+            self.to_kid,
+            p.api_keys,
+            p.auth_providers,
+            p.clients,
+            p.claims_webhooks,
+            p.jwks,
+            p.users,
+            p.upstream_claims,
+            p.upstream_refresh_tokens,
+        );
+        info!("{text}");
+        self.event(text).await;
+
+        Ok(())
+    }
+
+    async fn migrate_api_keys(&mut self) -> Result<(), ErrorResponse> {
+        let keys = ApiKeyEntity::find_all()
+            .await?
+            .into_iter()
+            .filter(|k| k.enc_key_id == self.from_kid)
+            .collect::<Vec<_>>();
+
+        for batch in keys.chunks(BATCH_SIZE) {
+            let mut rows = Vec::with_capacity(batch.len());
+            for key in batch {
+                rows.push((
+                    key.name.clone(),
+                    self.re_encrypt(key.secret.clone())?,
+                    self.re_encrypt(key.access.clone())?,
+                ));
+            }
+
+            let sql =
+                "UPDATE api_keys SET secret = $1, access = $2, enc_key_id = $3 WHERE name = $4";
+            if is_hiqlite() {
+                let mut txn: Vec<(&str, Params)> = Vec::with_capacity(rows.len());
+                for (name, secret, access) in rows {
+                    txn.push((sql, params!(secret, access, self.to_kid.clone(), name)));
+                }
+                DB::hql().txn(txn).await?;
+            } else {
+                let mut cl = DB::pg().await?;
+                let txn = cl.transaction().await?;
+                for (name, secret, access) in &rows {
+                    DB::pg_txn_append(&txn, sql, &[secret, access, &self.to_kid, name]).await?;
+                }
+                txn.commit().await?;
+            }
+
+            self.progress.api_keys += batch.len() as u32;
+            self.send_progress().await;
+        }
+
+        self.milestone("API Keys", self.progress.api_keys).await;
+        Ok(())
+    }
+
+    async fn migrate_auth_providers(&mut self) -> Result<(), ErrorResponse> {
+        // Providers don't store the key id separately, it is only part of the encrypted values.
+        let mut providers = Vec::new();
+        for mut provider in AuthProvider::find_all().await? {
+            if provider.re_encrypt_secrets(Some(self.from_kid.as_str()), &self.to_kid)? {
+                providers.push(provider);
+            }
+        }
+
+        let sql = r#"
+UPDATE auth_providers
+SET secret = $1, client_cert_pem = $2, client_key_pem = $3, apple_private_key = $4
+WHERE id = $5"#;
+        for batch in providers.chunks(BATCH_SIZE) {
+            if is_hiqlite() {
+                let mut txn: Vec<(&str, Params)> = Vec::with_capacity(batch.len());
+                for p in batch {
+                    txn.push((
+                        sql,
+                        params!(
+                            p.secret.clone(),
+                            p.client_cert_pem.clone(),
+                            p.client_key_pem.clone(),
+                            p.apple_private_key.clone(),
+                            p.id.clone()
+                        ),
+                    ));
+                }
+                DB::hql().txn(txn).await?;
+            } else {
+                let mut cl = DB::pg().await?;
+                let txn = cl.transaction().await?;
+                for p in batch {
+                    DB::pg_txn_append(
+                        &txn,
+                        sql,
+                        &[
+                            &p.secret,
+                            &p.client_cert_pem,
+                            &p.client_key_pem,
+                            &p.apple_private_key,
+                            &p.id,
+                        ],
+                    )
+                    .await?;
+                }
+                txn.commit().await?;
+            }
+
+            self.progress.auth_providers += batch.len() as u32;
+            self.send_progress().await;
+        }
+
+        self.milestone("Auth Providers", self.progress.auth_providers)
+            .await;
+        Ok(())
+    }
+
+    async fn migrate_clients(&mut self) -> Result<(), ErrorResponse> {
+        let clients = Client::find_all()
+            .await?
+            .into_iter()
+            .filter(|c| {
+                c.secret.is_some() && c.secret_kid.as_deref() == Some(self.from_kid.as_str())
+            })
+            .collect::<Vec<_>>();
+
+        for batch in clients.chunks(BATCH_SIZE) {
+            let mut rows = Vec::with_capacity(batch.len());
+            for client in batch {
+                if let Some(secret) = &client.secret {
+                    rows.push((client.id.clone(), self.re_encrypt(secret.clone())?));
+                }
+            }
+
+            let sql = "UPDATE clients SET secret = $1, secret_kid = $2 WHERE id = $3";
+            if is_hiqlite() {
+                let mut txn: Vec<(&str, Params)> = Vec::with_capacity(rows.len());
+                for (id, secret) in rows {
+                    txn.push((sql, params!(secret, self.to_kid.clone(), id)));
+                }
+                DB::hql().txn(txn).await?;
+            } else {
+                let mut cl = DB::pg().await?;
+                let txn = cl.transaction().await?;
+                for (id, secret) in &rows {
+                    DB::pg_txn_append(&txn, sql, &[secret, &self.to_kid, id]).await?;
+                }
+                txn.commit().await?;
+            }
+
+            self.progress.clients += batch.len() as u32;
+            self.send_progress().await;
+        }
+
+        self.milestone("Clients", self.progress.clients).await;
+        Ok(())
+    }
+
+    async fn migrate_claims_webhooks(&mut self) -> Result<(), ErrorResponse> {
+        let mut rows = Vec::new();
+        for webhook in ClientClaimsWebhook::find_all().await? {
+            if self.is_from_kid(&webhook.secret)? {
+                rows.push((webhook.client_id, webhook.secret));
+            }
+        }
+
+        let sql = "UPDATE clients_claims_webhook SET secret = $1 WHERE client_id = $2";
+        for batch in rows.chunks(BATCH_SIZE) {
+            self.update_batch(sql, self.re_encrypt_rows(batch)?).await?;
+            self.progress.claims_webhooks += batch.len() as u32;
+            self.send_progress().await;
+        }
+
+        self.milestone("Claims Webhooks", self.progress.claims_webhooks)
+            .await;
+        Ok(())
+    }
+
+    /// In contrast to `migrate_encryption_alg()`, the JWKs are re-encrypted instead of rotated,
+    /// so tokens issued with them stay valid.
+    async fn migrate_jwks(&mut self) -> Result<(), ErrorResponse> {
+        let sql = "SELECT * FROM jwks WHERE enc_key_id = $1";
+        let jwks: Vec<Jwk> = if is_hiqlite() {
+            DB::hql()
+                .query_as(sql, params!(self.from_kid.clone()))
+                .await?
+        } else {
+            DB::pg_query(sql, &[&self.from_kid], 8).await?
+        };
+
+        for batch in jwks.chunks(BATCH_SIZE) {
+            let mut rows = Vec::with_capacity(batch.len());
+            for jwk in batch {
+                rows.push((jwk.kid.clone(), self.re_encrypt(jwk.jwk.clone())?));
+            }
+
+            let sql = "UPDATE jwks SET jwk = $1, enc_key_id = $2 WHERE kid = $3";
+            if is_hiqlite() {
+                let mut txn: Vec<(&str, Params)> = Vec::with_capacity(rows.len());
+                for (kid, jwk) in rows {
+                    txn.push((sql, params!(jwk, self.to_kid.clone(), kid)));
+                }
+                DB::hql().txn(txn).await?;
+            } else {
+                let mut cl = DB::pg().await?;
+                let txn = cl.transaction().await?;
+                for (kid, jwk) in &rows {
+                    DB::pg_txn_append(&txn, sql, &[jwk, &self.to_kid, kid]).await?;
+                }
+                txn.commit().await?;
+            }
+
+            self.progress.jwks += batch.len() as u32;
+            self.send_progress().await;
+        }
+
+        self.milestone("JWKs", self.progress.jwks).await;
+        Ok(())
+    }
+
+    /// Users only contain encrypted values with `encryption.pii_at_rest`. The E-Mail hash key
+    /// is re-encrypted as well, while the hashes themselves stay the same.
+    async fn migrate_users(&mut self) -> Result<(), ErrorResponse> {
+        pii::re_encrypt_hash_key(Some(self.from_kid.as_str()), &self.to_kid).await?;
+
+        let mut after_id = String::default();
+        while let Some((last_id, count)) =
+            pii::re_encrypt_batch(Some(self.from_kid.as_str()), &self.to_kid, &after_id).await?
+        {
+            after_id = last_id;
+            if count > 0 {
+                self.progress.users += count;
+                self.send_progress().await;
+            }
+        }
+
+        self.milestone("Users", self.progress.users).await;
+        Ok(())
+    }
+
+    async fn migrate_upstream_claims(&mut self) -> Result<(), ErrorResponse> {
+        let mut rows = Vec::new();
+        for claims in UserFederationClaims::find_all().await? {
+            if self.is_from_kid(&claims.claims)? {
+                rows.push((claims.user_id, claims.claims));
+            }
+        }
+
+        let sql = "UPDATE user_federation_claims SET claims = $1 WHERE user_id = $2";
+        for batch in rows.chunks(BATCH_SIZE) {
+            self.update_batch(sql, self.re_encrypt_rows(batch)?).await?;
+            self.progress.upstream_claims += batch.len() as u32;
+            self.send_progress().await;
+        }
+
+        self.milestone("Upstream Claims", self.progress.upstream_claims)
+            .await;
+        Ok(())
+    }
+
+    async fn migrate_upstream_refresh_tokens(&mut self) -> Result<(), ErrorResponse> {
+        let mut rows = Vec::new();
+        for token in AuthProviderRefreshToken::find_all().await? {
+            if self.is_from_kid(&token.refresh_token)? {
+                rows.push((token.user_id, token.refresh_token));
+            }
+        }
+
+        let sql = "UPDATE auth_provider_refresh_tokens SET refresh_token = $1 WHERE user_id = $2";
+        for batch in rows.chunks(BATCH_SIZE) {
+            self.update_batch(sql, self.re_encrypt_rows(batch)?).await?;
+            self.progress.upstream_refresh_tokens += batch.len() as u32;
+            self.send_progress().await;
+        }
+
+        self.milestone(
+            "Upstream Refresh Tokens",
+            self.progress.upstream_refresh_tokens,
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Writes `(id, value)` rows for an `UPDATE .. SET value = $1 WHERE id = $2` inside a
+    /// single transaction.
+    async fn update_batch(
+        &self,
+        sql: &'static str,
+        rows: Vec<(String, Vec<u8>)>,
+    ) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            let mut txn: Vec<(&str, Params)> = Vec::with_capacity(rows.len());
+            for (id, value) in rows {
+                txn.push((sql, params!(value, id)));
+            }
+            for res in DB::hql().txn(txn).await? {
+                res?;
+            }
+        } else {
+            let mut cl = DB::pg().await?;
+            let txn = cl.transaction().await?;
+            for (id, value) in &rows {
+                DB::pg_txn_append(&txn, sql, &[value, id]).await?;
+            }
+            txn.commit().await?;
+        }
+        Ok(())
+    }
+
+    /// Re-encrypts the whole batch upfront. If a single value fails, the whole batch fails
+    /// before anything has been written.
+    fn re_encrypt_rows(
+        &self,
+        batch: &[(String, Vec<u8>)],
+    ) -> Result<Vec<(String, Vec<u8>)>, ErrorResponse> {
+        batch
+            .iter()
+            .map(|(id, value)| Ok((id.clone(), self.re_encrypt(value.clone())?)))
+            .collect()
+    }
+
+    /// For entities, which don't store the key id separately.
+    fn is_from_kid(&self, value: &[u8]) -> Result<bool, ErrorResponse> {
+        Ok(EncValue::try_from(value.to_vec())?.header.enc_key_id == self.from_kid)
+    }
+
+    fn re_encrypt(&self, value: Vec<u8>) -> Result<Vec<u8>, ErrorResponse> {
+        let dec = EncValue::try_from(value)?.decrypt()?;
+        Ok(
+            EncValue::encrypt_with_key_id(dec.as_ref(), self.to_kid.clone())?
+                .into_bytes()
+                .to_vec(),
+        )
+    }
+
+    async fn milestone(&self, entity: &str, count: u32) {
+        info!(count, "Secrets migration finished for {entity}");
+        self.event(format!(
+            "Secrets migration from key `{}` to `{}`: {count} {entity} done",
+            self.from_kid, self.to_kid
+        ))
+        .await;
+    }
+
+    async fn event(&self, text: String) {
+        if let Err(err) = Event::secrets_migration(text, self.ip).send().await {
+            error!(?err, "Sending secrets migration Event");
+        }
+    }
+
+    /// A closed stream does not abort the migration. The client may have just disconnected.
+    async fn send_progress(&self) {
+        let json = serde_json::to_string(&self.progress).unwrap();
+        let _ = self.tx.send(sse::Event::Data(sse::Data::new(json))).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn migration() -> SecretsMigration {
+        crate::init_test_enc_keys();
+        let (tx, _rx) = mpsc::channel(1);
+        SecretsMigration {
+            from_kid: "test_old".to_string(),
+            to_kid: "test".to_string(),
+            ip: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            tx,
+            progress: SecretsMigrationProgress::default(),
+        }
+    }
+
+    fn encrypt(value: &str, kid: &str) -> Vec<u8> {
+        EncValue::encrypt_with_key_id(value.as_bytes(), kid.to_string())
+            .unwrap()
+            .into_bytes()
+            .to_vec()
+    }
+
+    #[test]
+    fn test_re_encrypt_rows() {
+        let migration = migration();
+        let batch = (0..BATCH_SIZE + 1)
+            .map(|i| (i.to_string(), encrypt(&format!("secret{i}"), "test_old")))
+            .collect::<Vec<_>>();
+        assert!(migration.is_from_kid(&batch[0].1).unwrap());
+        assert!(!migration.is_from_kid(&encrypt("secret", "test")).unwrap());
+
+        let chunks = batch.chunks(BATCH_SIZE).collect::<Vec<_>>();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[1].len(), 1);
+
+        for chunk in chunks {
+            let rows = migration.re_encrypt_rows(chunk).unwrap();
+            assert_eq!(rows.len(), chunk.len());
+            for ((id, value), (id_before, _)) in rows.into_iter().zip(chunk) {
+                assert_eq!(&id, id_before);
+                assert!(!migration.is_from_kid(&value).unwrap());
+
+                let enc = EncValue::try_from(value).unwrap();
+                assert_eq!(enc.header.enc_key_id, "test");
+                assert_eq!(
+                    enc.decrypt().unwrap().as_ref(),
+                    format!("secret{id}").as_bytes()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_re_encrypt_rows_fails_whole_batch() {
+        let migration = migration();
+        let mut corrupted = encrypt("secret1", "test_old");
+        let last = corrupted.len() - 1;
+        corrupted[last] ^= 0xff;
+
+        let batch = vec![
+            ("0".to_string(), encrypt("secret0", "test_old")),
+            ("1".to_string(), corrupted),
+            ("2".to_string(), encrypt("secret2", "test_old")),
+        ];
+        assert!(migration.re_encrypt_rows(&batch).is_err());
+    }
+}
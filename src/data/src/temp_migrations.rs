@@ -1,10 +1,13 @@
 use crate::database::DB;
 use crate::entity::roles::Role;
+use crate::entity::users::User;
+use crate::pii;
 use hiqlite::macros::params;
 use rauthy_common::constants::RAUTHY_ADMIN_GROUP_PREFIX;
 use rauthy_common::is_hiqlite;
 use rauthy_error::ErrorResponse;
-use tracing::{info, warn};
+use std::collections::HashMap;
+use tracing::{error, info, warn};
 
 pub async fn apply_temp_migrations() -> Result<(), ErrorResponse> {
     // cleanup possibly lingering PAM user groups
@@ -24,6 +27,77 @@ WHERE typ = 'user' AND NOT EXISTS (
     }
 
     warn_existing_group_admin_roles().await?;
+    normalize_user_emails().await?;
+
+    Ok(())
+}
+
+/// Older versions did not normalize E-Mail addresses on each path, which means users may exist
+/// that can't log in with the normalized address, or multiple users only differ in case.
+/// Addresses without a conflict are normalized. Conflicting users are reported on each start
+/// and never merged automatically, because they might belong to different people.
+async fn normalize_user_emails() -> Result<(), ErrorResponse> {
+    let sql = "SELECT id, email FROM users";
+    let users: Vec<(String, String)> = if is_hiqlite() {
+        DB::hql()
+            .query_raw(sql, params!())
+            .await?
+            .into_iter()
+            .map(|mut row| (row.get("id"), pii::decrypt(row.get("email"))))
+            .collect()
+    } else {
+        DB::pg_query_rows(sql, &[], 0)
+            .await?
+            .into_iter()
+            .map(|row| (row.get("id"), pii::decrypt(row.get("email"))))
+            .collect()
+    };
+
+    let mut normalized: HashMap<String, Vec<(String, String)>> = HashMap::new();
+    for (id, email) in users {
+        normalized
+            .entry(User::normalize_email(&email))
+            .or_default()
+            .push((id, email));
+    }
+
+    let mut updated = 0;
+    for (email_norm, mut users) in normalized {
+        if users.len() > 1 {
+            users.sort();
+            error!(
+                normalized = email_norm,
+                ?users,
+                "Found users with E-Mail addresses that only differ in case or unicode \
+                normalization. They will NOT be merged automatically. Only the user with the \
+                normalized address (if any) can log in via E-Mail. Please resolve this conflict \
+                manually by changing or deleting all but one of these users."
+            );
+            continue;
+        }
+
+        let (id, email) = users.pop().expect("at least one user per entry");
+        if email == email_norm {
+            continue;
+        }
+
+        let sql = "UPDATE users SET email = $1, email_hash = $2 WHERE id = $3";
+        let email_enc = pii::encrypt(&email_norm)?;
+        let email_hash = pii::email_hash(&email_norm);
+        if is_hiqlite() {
+            DB::hql()
+                .execute(sql, params!(email_enc, email_hash, id.clone()))
+                .await?;
+        } else {
+            DB::pg_execute(sql, &[&email_enc, &email_hash, &id]).await?;
+        }
+        User::invalidate_cache(&id, &email).await?;
+        updated += 1;
+    }
+
+    if updated > 0 {
+        info!("Normalized the E-Mail address for {updated} users");
+    }
 
     Ok(())
 }
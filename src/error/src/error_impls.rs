@@ -48,6 +48,22 @@ impl ErrorResponse {
         Self::new(ErrorResponseType::Internal, message)
     }
 
+    /// Builds the RFC 6750 `WWW-Authenticate` value for bearer token errors. Double quotes and
+    /// backslashes are not allowed inside the `error_description` and will be dropped.
+    fn bearer_challenge(&self) -> String {
+        let code = match self.error {
+            ErrorResponseType::InvalidRequest => "invalid_request",
+            ErrorResponseType::InsufficientScope => "insufficient_scope",
+            _ => "invalid_token",
+        };
+        let desc = self
+            .message
+            .chars()
+            .filter(|c| *c != '"' && *c != '\\' && (' '..='~').contains(c))
+            .collect::<String>();
+        format!(r#"Bearer error="{code}", error_description="{desc}""#)
+    }
+
     pub fn error_response_html(&self, body: String) -> HttpResponse {
         HttpResponseBuilder::new(self.status_code())
             .append_header(HEADER_HTML)
@@ -59,10 +75,15 @@ impl ResponseError for ErrorResponse {
     fn status_code(&self) -> StatusCode {
         match self.error {
             ErrorResponseType::BadRequest
+            | ErrorResponseType::InvalidGrant
+            | ErrorResponseType::InvalidRequest
             | ErrorResponseType::InvalidTarget
+            | ErrorResponseType::UnauthorizedClient
+            | ErrorResponseType::UnsupportedGrantType
             | ErrorResponseType::UseDpopNonce(_) => StatusCode::BAD_REQUEST,
             ErrorResponseType::Blocked
             | ErrorResponseType::Forbidden
+            | ErrorResponseType::InsufficientScope
             | ErrorResponseType::PasswordRefresh => StatusCode::FORBIDDEN,
             ErrorResponseType::MfaRequired | ErrorResponseType::NotAccepted => {
                 StatusCode::NOT_ACCEPTABLE
@@ -72,6 +93,8 @@ impl ResponseError for ErrorResponse {
             ErrorResponseType::Disabled
             | ErrorResponseType::CSRFTokenError
             | ErrorResponseType::DPoP(_)
+            | ErrorResponseType::InvalidClient
+            | ErrorResponseType::InvalidToken
             | ErrorResponseType::JwtToken
             | ErrorResponseType::PasswordExpired
             | ErrorResponseType::SessionExpired
@@ -81,6 +104,7 @@ impl ResponseError for ErrorResponse {
             ErrorResponseType::TooManyRequests(_not_before_timestamp) => {
                 StatusCode::TOO_MANY_REQUESTS
             }
+            ErrorResponseType::ServiceUnavailable(_retry_after) => StatusCode::SERVICE_UNAVAILABLE,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
@@ -99,6 +123,16 @@ impl ResponseError for ErrorResponse {
                     .body(self.message.clone())
             }
 
+            ErrorResponseType::ServiceUnavailable(retry_after) => {
+                let mut builder = HttpResponseBuilder::new(status);
+                if *retry_after > 0 {
+                    builder.insert_header((header::RETRY_AFTER, *retry_after));
+                }
+                builder
+                    .content_type(APPLICATION_JSON)
+                    .body(serde_json::to_string(&self).unwrap())
+            }
+
             ErrorResponseType::DPoP(header_origin) => {
                 if let Some(origin) = header_origin {
                     HttpResponseBuilder::new(status)
@@ -133,6 +167,13 @@ impl ResponseError for ErrorResponse {
                 }
             }
 
+            ErrorResponseType::InvalidRequest
+            | ErrorResponseType::InvalidToken
+            | ErrorResponseType::InsufficientScope => HttpResponseBuilder::new(status)
+                .insert_header((WWW_AUTHENTICATE, self.bearer_challenge()))
+                .content_type(APPLICATION_JSON)
+                .body(serde_json::to_string(self).unwrap()),
+
             ErrorResponseType::WWWAuthenticate(msg) => HttpResponseBuilder::new(status)
                 .insert_header((WWW_AUTHENTICATE, msg.as_str()))
                 .content_type(APPLICATION_JSON)
@@ -8,6 +8,7 @@ use utoipa::ToSchema;
 pub mod error_impls;
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[non_exhaustive]
 pub enum ErrorResponseType {
     BadRequest,
     Blocked,
@@ -27,6 +28,26 @@ pub enum ErrorResponseType {
     /// allowed for the client. Serialized as the RFC error code `invalid_target`.
     #[serde(rename = "invalid_target")]
     InvalidTarget,
+    /// RFC 6749 §5.2: the provided grant is invalid, expired, revoked, or has been used from
+    /// another context than it was issued to. Serialized as the RFC error code `invalid_grant`.
+    #[serde(rename = "invalid_grant")]
+    InvalidGrant,
+    /// RFC 6749 §5.2: client authentication failed, for instance because of an unknown client,
+    /// a missing or invalid secret. Serialized as the RFC error code `invalid_client`.
+    #[serde(rename = "invalid_client")]
+    InvalidClient,
+    /// RFC 6750 §3.1: the bearer request is malformed, for instance because the token has been
+    /// sent in more than one place. Responds with a `Bearer` challenge.
+    #[serde(rename = "invalid_request")]
+    InvalidRequest,
+    /// RFC 6750 §3.1: the bearer token is expired, revoked, malformed or invalid for other
+    /// reasons. Responds with a `Bearer` challenge.
+    #[serde(rename = "invalid_token")]
+    InvalidToken,
+    /// RFC 6750 §3.1: the bearer token is valid, but is missing a required scope. Responds with
+    /// a `Bearer` challenge.
+    #[serde(rename = "insufficient_scope")]
+    InsufficientScope,
     JwtToken,
     JoseError,
     MfaRequired,
@@ -36,11 +57,22 @@ pub enum ErrorResponseType {
     PasswordRefresh,
     PreconditionRequired,
     Scim,
+    /// Rauthy is in maintenance mode. Contains the seconds until its planned end, or `0` if
+    /// there is none.
+    ServiceUnavailable(i64),
     SessionExpired,
     SessionTimeout,
     Timeout,
     TooManyRequests(i64),
     Unauthorized,
+    /// RFC 6749 §5.2: the client is not allowed to use the requested grant type. Serialized as
+    /// the RFC error code `unauthorized_client`.
+    #[serde(rename = "unauthorized_client")]
+    UnauthorizedClient,
+    /// RFC 6749 §5.2: the grant type is not supported at all. Serialized as the RFC error code
+    /// `unsupported_grant_type`.
+    #[serde(rename = "unsupported_grant_type")]
+    UnsupportedGrantType,
     NotAccepted,
     WWWAuthenticate(String),
 }
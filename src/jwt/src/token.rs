@@ -1,5 +1,5 @@
-use crate::claims::JwtTokenType;
-use chrono::Utc;
+use rauthy_api_types::claims::JwtTokenType;
+use rauthy_common::jwt_time::JwtTimeClaims;
 use rauthy_common::utils::{base64_url_no_pad_decode_buf, base64_url_no_pad_encode_buf};
 use rauthy_data::entity::jwk::{JwkKeyPair, JwkKeyPairAlg};
 use rauthy_data::rauthy_config::RauthyConfig;
@@ -45,10 +45,13 @@ impl JwtToken {
 
     /// Validates the given, raw JWT token, deserializes and validates it. On success, the raw
     /// claims bytes will be written into `buf`.
+    ///
+    /// The `leeway` should be `access.jwt_leeway` from the config, unless a specific use case
+    /// needs a bigger one.
     pub async fn validate_claims_into(
         token: &str,
         expected_type: Option<JwtTokenType>,
-        allowed_clock_skew_seconds: u16,
+        leeway: u16,
         buf: &mut Vec<u8>,
     ) -> Result<(), ErrorResponse> {
         debug_assert!(buf.is_empty());
@@ -115,7 +118,7 @@ impl JwtToken {
         serde_json::from_slice::<ValidationClaims>(buf)?.validate(
             &RauthyConfig::get().issuer,
             expected_type,
-            allowed_clock_skew_seconds,
+            leeway,
         )?;
 
         Ok(())
@@ -140,29 +143,15 @@ impl ValidationClaims<'_> {
         &self,
         issuer: &str,
         expected_type: Option<JwtTokenType>,
-        allowed_clock_skew_seconds: u16,
+        leeway: u16,
     ) -> Result<(), ErrorResponse> {
-        let now = Utc::now().timestamp();
-        let skew = allowed_clock_skew_seconds as i64;
-
-        if self.iat - skew > now {
-            return Err(ErrorResponse::new(
-                ErrorResponseType::JwtToken,
-                "Token was issued in the future",
-            ));
-        }
-        if self.exp + skew < now {
-            return Err(ErrorResponse::new(
-                ErrorResponseType::JwtToken,
-                "Token has expired",
-            ));
-        }
-        if self.nbf - skew > now {
-            return Err(ErrorResponse::new(
-                ErrorResponseType::JwtToken,
-                "Token is not valid yet",
-            ));
+        JwtTimeClaims {
+            iat: Some(self.iat),
+            exp: Some(self.exp),
+            nbf: Some(self.nbf),
         }
+        .validate(leeway)?;
+
         if let Some(typ) = expected_type
             && self.typ != typ
         {
@@ -184,9 +173,9 @@ impl ValidationClaims<'_> {
 
 #[cfg(test)]
 mod tests {
-    use crate::claims::JwtTokenType;
     use crate::token::ValidationClaims;
     use chrono::Utc;
+    use rauthy_api_types::claims::JwtTokenType;
     use rauthy_error::{ErrorResponse, ErrorResponseType};
 
     #[test]
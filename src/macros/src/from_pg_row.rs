@@ -64,6 +64,16 @@ pub fn impl_from_pg_row(input: DeriveInput) -> proc_macro::TokenStream {
                                 quote! {#id: row.get::<_, String>(#name).into(),}
                             }
                         }
+                        ColumnAttr::Pii => {
+                            // Only usable inside `rauthy-data`, which provides the `pii` module.
+                            if is_opt {
+                                quote! {
+                                    #id: crate::pii::decrypt_opt(row.get::<_, Option<String>>(#name)),
+                                }
+                            } else {
+                                quote! {#id: crate::pii::decrypt(row.get::<_, String>(#name)),}
+                            }
+                        }
                         ColumnAttr::None => {
                             quote! {#id: row.get(#name),}
                         }
@@ -123,6 +133,7 @@ enum ColumnAttr {
     FromI64,
     Parse,
     FromString,
+    Pii,
     None,
     Skip,
 }
@@ -142,9 +153,10 @@ Invalid syntax for '#[column]' - '{idx}' attribute, expected one of:
 - from_i64
 - from_string
 - parse
+- pii
 - rename = "my_column"
 - skip
-- rename may be combined with one of the from_* or parse attributes
+- rename may be combined with one of the from_*, parse or pii attributes
 "#
             )
         };
@@ -195,6 +207,7 @@ Invalid syntax for '#[column]' - '{idx}' attribute, expected one of:
                                 "from_i64" => attr = ColumnAttr::FromI64,
                                 "from_string" => attr = ColumnAttr::FromString,
                                 "parse" => attr = ColumnAttr::Parse,
+                                "pii" => attr = ColumnAttr::Pii,
                                 _ => do_panic(format!(
                                     "Invalid syntax for 'from_*' after 'rename': {value}"
                                 )),
@@ -210,6 +223,7 @@ Invalid syntax for '#[column]' - '{idx}' attribute, expected one of:
                         "from_i64" => attr = ColumnAttr::FromI64,
                         "from_string" => attr = ColumnAttr::FromString,
                         "parse" => attr = ColumnAttr::Parse,
+                        "pii" => attr = ColumnAttr::Pii,
                         _ => do_panic(format!("Invalid syntax for 'from_*': {other}")),
                     }
                     if let Some(tree) = stream.next() {
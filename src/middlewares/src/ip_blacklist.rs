@@ -4,8 +4,9 @@ use actix_web::{
 };
 use chrono::Utc;
 use futures::future::LocalBoxFuture;
-use rauthy_common::utils::real_ip_from_svc_req;
+use rauthy_common::utils::real_ip;
 use rauthy_data::entity::ip_blacklist::IpBlacklist;
+use rauthy_data::html::csp::CspNonce;
 use rauthy_data::html::templates::{BlockedHtml, TooManyRequestsHtml};
 use rauthy_data::ipgeo;
 use rauthy_data::rauthy_config::RauthyConfig;
@@ -58,7 +59,7 @@ where
 
         Box::pin(async move {
             let config = &RauthyConfig::get().vars.geo;
-            let ip = real_ip_from_svc_req(&req)?;
+            let ip = real_ip(req.request())?;
 
             // If the `block_is_whitelist` is `None`, Geoblocking is not configured at all
             if let Some(is_whitelist) = config.block_is_whitelist {
@@ -87,7 +88,7 @@ where
                 let ts = blacklisted.exp.timestamp();
                 return Err(Error::from(ErrorResponse::new(
                     ErrorResponseType::TooManyRequests(ts),
-                    TooManyRequestsHtml::build(ip.to_string(), ts),
+                    TooManyRequestsHtml::build(ip.to_string(), ts, &CspNonce::from_req(&req)),
                 )));
             }
 
@@ -1,4 +1,6 @@
 pub mod csrf_protection;
 pub mod ip_blacklist;
 pub mod logging;
+pub mod maintenance_mode;
 pub mod principal;
+pub mod security_headers;
@@ -0,0 +1,122 @@
+use actix_web::error::InternalError;
+use actix_web::http::Method;
+use actix_web::web::Query;
+use actix_web::{
+    Error, HttpMessage, ResponseError,
+    dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+};
+use chrono::Utc;
+use futures::future::LocalBoxFuture;
+use rauthy_data::entity::maintenance_mode::MaintenanceMode;
+use rauthy_data::entity::principal::Principal;
+use rauthy_data::html::flow_error::{ErrorFlow, flow_error_response};
+use std::collections::HashMap;
+use std::future::{Ready, ready};
+use std::rc::Rc;
+
+/// Rejects all requests, that would start a new login, while a maintenance is active.
+/// The login and token endpoints themselves are checked inside the handlers, because only
+/// they know the user.
+pub struct RauthyMaintenanceMiddleware;
+
+// `S` - type of the next service
+// `B` - type of response's body
+impl<S, B> Transform<S, ServiceRequest> for RauthyMaintenanceMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = MaintenanceMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(MaintenanceMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct MaintenanceMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for MaintenanceMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+
+        Box::pin(async move {
+            let Some(flow) = gated_flow(&req) else {
+                return service.call(req).await;
+            };
+            let Some(maintenance) = MaintenanceMode::find_active().await? else {
+                return service.call(req).await;
+            };
+
+            let now = Utc::now().timestamp();
+            if MaintenanceMode::has_bypass_cookie(&req, now) {
+                return service.call(req).await;
+            }
+            let is_bypass_user = req
+                .extensions()
+                .get::<Principal>()
+                .and_then(|p| p.session.as_ref())
+                .and_then(|s| s.user_id.as_deref())
+                .map(|id| maintenance.can_bypass_user(id))
+                .unwrap_or(false);
+            if is_bypass_user {
+                return service.call(req).await;
+            }
+
+            let err = maintenance.err(now);
+            let resp = match flow {
+                Some(flow) => flow_error_response(req.request(), err, flow).await,
+                None => err.error_response(),
+            };
+            Err(InternalError::from_response("maintenance mode", resp).into())
+        })
+    }
+}
+
+/// Returns `Some(_)` for all requests that would start a new login or registration. The inner
+/// `Option<ErrorFlow>` is set for browser-facing flows, which get an HTML error page.
+fn gated_flow(req: &ServiceRequest) -> Option<Option<ErrorFlow>> {
+    let path = req.path();
+    match *req.method() {
+        Method::GET => match path {
+            // the Admin UI login must stay reachable, the handler checks the user itself
+            "/auth/v1/oidc/authorize" if !is_rauthy_client(req) => Some(Some(ErrorFlow::Authorize)),
+            "/auth/v1/providers/callback" => Some(Some(ErrorFlow::ProviderCallback)),
+            _ => None,
+        },
+        Method::POST => match path {
+            "/auth/v1/oidc/device"
+            | "/auth/v1/providers/callback"
+            | "/auth/v1/providers/login"
+            | "/auth/v1/users/register" => Some(None),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[inline]
+fn is_rauthy_client(req: &ServiceRequest) -> bool {
+    Query::<HashMap<String, String>>::from_query(req.query_string())
+        .map(|q| q.get("client_id").map(|id| id == "rauthy").unwrap_or(false))
+        .unwrap_or(false)
+}
@@ -0,0 +1,100 @@
+use actix_web::body::EitherBody;
+use actix_web::http::header::{
+    CONTENT_SECURITY_POLICY, HeaderValue, REFERRER_POLICY, X_FRAME_OPTIONS,
+};
+use actix_web::{
+    Error,
+    dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+};
+use futures::future::LocalBoxFuture;
+use rauthy_data::html::csp::{CspNonce, SecurityHeaders};
+use std::future::{Ready, ready};
+use std::rc::Rc;
+use tracing::error;
+
+/// Adds the `Content-Security-Policy`, `X-Frame-Options` and `Referrer-Policy` headers to each
+/// response, as configured in `SecurityHeaders`.
+///
+/// A response, that has rendered inline scripts with a `CspNonce` from the request extensions,
+/// always gets the policy with the matching `script-src 'nonce-...'` directive. Any other
+/// response keeps a policy, that has been set by its handler already.
+///
+/// Must be the outermost middleware, so it also catches responses for errors returned by other
+/// middlewares, like the `RauthyIpBlacklistMiddleware`.
+pub struct RauthySecurityHeadersMiddleware;
+
+impl<S, B> Transform<S, ServiceRequest> for RauthySecurityHeadersMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = SecurityHeadersMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(SecurityHeadersMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct SecurityHeadersMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for SecurityHeadersMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let http_req = req.request().clone();
+
+        Box::pin(async move {
+            let mut res = match service.call(req).await {
+                Ok(res) => res.map_into_left_body(),
+                Err(err) => ServiceResponse::from_err(err, http_req).map_into_right_body(),
+            };
+
+            let headers = SecurityHeaders::get();
+            let path = res.request().path().to_string();
+            let nonce = CspNonce::try_from_req(res.request());
+
+            if nonce.is_some() || !res.headers().contains_key(CONTENT_SECURITY_POLICY) {
+                match HeaderValue::from_str(&headers.csp(&path, nonce.as_ref())) {
+                    Ok(value) => {
+                        res.headers_mut().insert(CONTENT_SECURITY_POLICY, value);
+                    }
+                    Err(err) => {
+                        error!(?err, "Cannot build Content-Security-Policy header");
+                    }
+                }
+            }
+            if !SecurityHeaders::is_frameable(&path) && !res.headers().contains_key(X_FRAME_OPTIONS)
+            {
+                res.headers_mut()
+                    .insert(X_FRAME_OPTIONS, HeaderValue::from_static("DENY"));
+            }
+            if !res.headers().contains_key(REFERRER_POLICY) {
+                // validated during config parsing
+                if let Ok(value) = HeaderValue::from_str(&headers.referrer_policy) {
+                    res.headers_mut().insert(REFERRER_POLICY, value);
+                }
+            }
+
+            Ok(res)
+        })
+    }
+}
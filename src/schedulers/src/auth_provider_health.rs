@@ -0,0 +1,66 @@
+use rauthy_common::constants::PROVIDER_ATPROTO;
+use rauthy_data::database::DB;
+use rauthy_data::entity::auth_provider_health::AuthProviderHealth;
+use rauthy_data::entity::auth_providers::{AuthProvider, AuthProviderTemplate};
+use rauthy_data::rauthy_config::RauthyConfig;
+use std::time::Duration;
+use tokio::time;
+use tracing::{debug, error};
+
+/// Checks the availability of all enabled upstream auth providers once a minute, if
+/// `auth_providers.health_check` is enabled. The login page template is rebuilt after each
+/// confirmed status change.
+pub async fn auth_provider_health_checker() {
+    if !RauthyConfig::get().vars.auth_providers.health_check {
+        return;
+    }
+
+    let mut interval = time::interval(Duration::from_secs(60));
+
+    loop {
+        interval.tick().await;
+
+        if !DB::hql().is_leader_cache().await {
+            debug!(
+                "Running HA mode without being the leader - skipping auth_provider_health_checker scheduler"
+            );
+            continue;
+        }
+
+        let providers = match AuthProvider::find_all().await {
+            Ok(p) => p,
+            Err(err) => {
+                error!(?err, "Error looking up auth providers for health checks");
+                continue;
+            }
+        };
+
+        let mut changed = false;
+        for provider in providers {
+            // atproto has no single upstream to check
+            if !provider.enabled || provider.issuer == PROVIDER_ATPROTO {
+                continue;
+            }
+
+            match AuthProviderHealth::check(
+                &provider.id,
+                &provider.name,
+                &provider.authorization_endpoint,
+            )
+            .await
+            {
+                Ok(c) => changed |= c,
+                Err(err) => {
+                    error!(
+                        ?err,
+                        "Error during health check for auth provider {}", provider.id
+                    );
+                }
+            }
+        }
+
+        if changed && let Err(err) = AuthProviderTemplate::update_cache().await {
+            error!(?err, "Error updating the auth provider template cache");
+        }
+    }
+}
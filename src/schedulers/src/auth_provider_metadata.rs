@@ -0,0 +1,84 @@
+use rauthy_common::constants::PROVIDER_ATPROTO;
+use rauthy_data::database::DB;
+use rauthy_data::entity::auth_providers::{AuthProvider, AuthProviderType};
+use rauthy_data::rauthy_config::RauthyConfig;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::{self, Instant};
+use tracing::{debug, error, info};
+
+const TICK: Duration = Duration::from_secs(300);
+
+/// Keeps the endpoints of all enabled upstream auth providers with `auto_refresh` in sync with
+/// their openid-configuration every `auth_providers.metadata_refresh_hours`. Failed lookups are
+/// retried with an exponential backoff, starting at the tick interval.
+pub async fn auth_provider_metadata_refresh() {
+    let hours = RauthyConfig::get()
+        .vars
+        .auth_providers
+        .metadata_refresh_hours;
+    if hours == 0 {
+        return;
+    }
+    let refresh = Duration::from_secs(hours as u64 * 3600);
+
+    // provider id -> (next refresh, failures in a row)
+    let mut schedule: HashMap<String, (Instant, u32)> = HashMap::new();
+    let mut interval = time::interval(TICK);
+
+    loop {
+        interval.tick().await;
+
+        if !DB::hql().is_leader_cache().await {
+            debug!(
+                "Running HA mode without being the leader - skipping auth_provider_metadata_refresh scheduler"
+            );
+            continue;
+        }
+
+        let providers = match AuthProvider::find_all().await {
+            Ok(p) => p,
+            Err(err) => {
+                error!(?err, "Error looking up auth providers for metadata refresh");
+                continue;
+            }
+        };
+        schedule.retain(|id, _| providers.iter().any(|p| &p.id == id));
+
+        let now = Instant::now();
+        for mut provider in providers {
+            // GitHub and atproto do not provide an openid-configuration
+            if !provider.enabled
+                || !provider.auto_refresh
+                || provider.typ == AuthProviderType::GitHub
+                || provider.issuer == PROVIDER_ATPROTO
+            {
+                continue;
+            }
+
+            let (next, failures) = schedule.entry(provider.id.clone()).or_insert((now, 0));
+            if *next > now {
+                continue;
+            }
+
+            match provider.refresh_metadata().await {
+                Ok(changed) => {
+                    if changed {
+                        info!("Updated the metadata for auth provider {}", provider.id);
+                    }
+                    *next = now + refresh;
+                    *failures = 0;
+                }
+                Err(err) => {
+                    error!(
+                        ?err,
+                        "Error refreshing the metadata for auth provider {}", provider.id
+                    );
+                    let backoff = TICK.saturating_mul(2u32.saturating_pow(*failures));
+                    *next = now + backoff.min(refresh);
+                    *failures = failures.saturating_add(1);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use chrono::Utc;
+use rauthy_data::database::DB;
+use rauthy_data::entity::auth_provider_refresh_tokens::{
+    AuthProviderRefreshToken, UpstreamRefresh,
+};
+use rauthy_data::entity::auth_providers::AuthProvider;
+use rauthy_data::entity::refresh_tokens::RefreshToken;
+use rauthy_data::entity::sessions::Session;
+use rauthy_data::entity::users::User;
+use rauthy_data::events::event::Event;
+use rauthy_data::rauthy_config::RauthyConfig;
+use rauthy_error::ErrorResponse;
+use rauthy_service::oidc::logout;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::{self, Instant};
+use tracing::{debug, error, info};
+
+const TICK: Duration = Duration::from_secs(60);
+
+/// Refreshes the stored upstream refresh tokens for all providers with `upstream_refresh`
+/// every `auth_providers.upstream_refresh_mins` and syncs the users with the latest claims.
+/// If the upstream is unavailable, the provider is retried with an exponential backoff.
+pub async fn auth_provider_upstream_refresh() {
+    let mins = RauthyConfig::get()
+        .vars
+        .auth_providers
+        .upstream_refresh_mins;
+    if mins == 0 {
+        return;
+    }
+    let refresh_secs = mins as i64 * 60;
+
+    // provider id -> (next retry, failures in a row)
+    let mut backoff: HashMap<String, (Instant, u32)> = HashMap::new();
+    let mut interval = time::interval(TICK);
+
+    loop {
+        interval.tick().await;
+
+        if !DB::hql().is_leader_cache().await {
+            debug!(
+                "Running HA mode without being the leader - skipping auth_provider_upstream_refresh scheduler"
+            );
+            continue;
+        }
+
+        let providers = match AuthProvider::find_all().await {
+            Ok(p) => p,
+            Err(err) => {
+                error!(?err, "Error looking up auth providers for upstream refresh");
+                continue;
+            }
+        };
+        backoff.retain(|id, _| providers.iter().any(|p| &p.id == id));
+
+        let now = Instant::now();
+        for provider in providers {
+            if !provider.enabled || !provider.upstream_refresh {
+                continue;
+            }
+            if backoff
+                .get(&provider.id)
+                .is_some_and(|(next, _)| *next > now)
+            {
+                continue;
+            }
+
+            match refresh_provider(&provider, refresh_secs).await {
+                Ok(()) => {
+                    backoff.remove(&provider.id);
+                }
+                Err(err) => {
+                    error!(
+                        ?err,
+                        "Error during the upstream refresh for auth provider {}", provider.id
+                    );
+                    let failures = backoff.get(&provider.id).map(|(_, f)| *f).unwrap_or(0);
+                    let wait = TICK.saturating_mul(2u32.saturating_pow(failures));
+                    let max = Duration::from_secs(refresh_secs as u64);
+                    backoff.insert(
+                        provider.id.clone(),
+                        (now + wait.min(max), failures.saturating_add(1)),
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Stops at the first error, because it is most likely caused by the upstream itself.
+async fn refresh_provider(provider: &AuthProvider, refresh_secs: i64) -> Result<(), ErrorResponse> {
+    let before = Utc::now().timestamp() - refresh_secs;
+    for token in AuthProviderRefreshToken::find_due(&provider.id, before).await? {
+        match token.refresh(provider).await? {
+            UpstreamRefresh::Synced => {
+                debug!("Synced user {} via upstream refresh", token.user_id);
+            }
+            UpstreamRefresh::Revoked(reason) => {
+                revoke_user(provider, &token.user_id, &reason).await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Logs out the user everywhere, so it must authenticate at the upstream again.
+async fn revoke_user(
+    provider: &AuthProvider,
+    user_id: &str,
+    reason: &str,
+) -> Result<(), ErrorResponse> {
+    AuthProviderRefreshToken::delete(user_id).await?;
+    Session::invalidate_for_user(user_id).await?;
+    RefreshToken::invalidate_for_user(user_id).await?;
+    logout::execute_backchannel_logout(None, Some(user_id.to_string())).await?;
+
+    let email = User::find(user_id.to_string())
+        .await
+        .map(|u| u.email)
+        .unwrap_or_else(|_| user_id.to_string());
+    let text = format!(
+        "Upstream refresh for `{email}` via auth provider `{}` failed - the user must log in \
+        again: {reason}",
+        provider.name
+    );
+    info!("{text}");
+    Event::upstream_refresh_failed(text).send().await?;
+
+    Ok(())
+}
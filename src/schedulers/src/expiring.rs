@@ -0,0 +1,57 @@
+use crate::sleep_schedule_next;
+use rauthy_data::database::DB;
+use rauthy_data::events::event::Event;
+use rauthy_data::rauthy_config::RauthyConfig;
+use rauthy_data::reports;
+use rauthy_error::ErrorResponse;
+use std::str::FromStr;
+use tracing::{debug, error};
+
+/// Sends a daily digest event about everything from the `/reports/expiring` report, if
+/// `events.expiring_digest` is enabled. Runs once every night at 05:00.
+pub async fn expiring_digest() {
+    if !RauthyConfig::get().vars.events.expiring_digest {
+        return;
+    }
+
+    // sec min hour day_of_month month day_of_week year
+    let schedule = cron::Schedule::from_str("0 0 5 * * * *").unwrap();
+
+    loop {
+        sleep_schedule_next(&schedule).await;
+
+        if !DB::hql().is_leader_cache().await {
+            debug!("Running HA mode without being the leader - skipping expiring_digest scheduler");
+            continue;
+        }
+
+        debug!("Running expiring_digest scheduler");
+
+        if let Err(err) = send_digest().await {
+            error!(?err, "Error during expiring_digest");
+        }
+    }
+}
+
+async fn send_digest() -> Result<(), ErrorResponse> {
+    let report = reports::expiring_report(None).await?;
+    if report.is_empty() {
+        return Ok(());
+    }
+
+    let count = report.api_keys.len()
+        + report.device_tokens.len()
+        + report.refresh_tokens.len()
+        + report.users.len();
+    let text = format!(
+        "within {} days: {} API key(s), {} device(s), {} user(s) with refresh tokens, {} user \
+        account(s) - details at /auth/v1/reports/expiring",
+        RauthyConfig::get().vars.events.expiring_window_days,
+        report.api_keys.len(),
+        report.device_tokens.len(),
+        report.refresh_tokens.len(),
+        report.users.len(),
+    );
+
+    Event::expiring_digest(count as i64, text).send().await
+}
@@ -4,16 +4,22 @@ use std::time::Duration;
 use tokio::time;
 use tracing::info;
 mod app_version;
+mod auth_provider_health;
+mod auth_provider_metadata;
+mod auth_provider_refresh;
 mod authorized_keys;
 mod backchannel_logout;
 mod devices;
 mod dyn_clients;
 mod email_jobs;
 mod events;
+mod expiring;
 mod ip_geo_db;
 mod issued_tokens;
 mod jwks;
 mod magic_links;
+mod maintenance;
+mod maintenance_mode;
 mod passwords;
 mod scim_tasks;
 mod sessions;
@@ -25,15 +31,20 @@ mod users;
 pub fn spawn() {
     info!("Starting schedulers");
 
+    tokio::spawn(auth_provider_health::auth_provider_health_checker());
+    tokio::spawn(auth_provider_metadata::auth_provider_metadata_refresh());
+    tokio::spawn(auth_provider_refresh::auth_provider_upstream_refresh());
     tokio::spawn(authorized_keys::cleanup_authorized_keys());
     tokio::spawn(backchannel_logout::backchannel_logout_retry());
     tokio::spawn(scim_tasks::scim_task_retry());
     tokio::spawn(dyn_clients::dyn_client_cleanup());
     tokio::spawn(email_jobs::orphaned_email_jobs());
     tokio::spawn(events::events_cleanup());
+    tokio::spawn(expiring::expiring_digest());
     tokio::spawn(ip_geo_db::update_ip_geo_db());
     tokio::spawn(devices::devices_cleanup());
-    tokio::spawn(magic_links::magic_link_cleanup());
+    tokio::spawn(maintenance::maintenance());
+    tokio::spawn(maintenance_mode::maintenance_mode_checker());
     tokio::spawn(tokens::refresh_tokens_cleanup());
     tokio::spawn(user_login_states::user_login_states_cleanup());
     tokio::spawn(sessions::sessions_cleanup());
@@ -1,50 +1,15 @@
-use chrono::Utc;
 use hiqlite::macros::params;
 use rauthy_common::is_hiqlite;
 use rauthy_data::database::DB;
 use rauthy_data::entity::user_login_states::UserLoginState;
 use rauthy_data::entity::users::User;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
-use std::ops::Sub;
-use std::time::Duration;
-use tokio::time;
-use tracing::{debug, error, info};
-
-/// Cleans up old / expired magic links and deletes users, that have never used their
-/// 'set first ever password' magic link to keep the database clean in case of an open user registration.
-/// Runs every 6 hours.
-pub async fn magic_link_cleanup() {
-    let mut interval = tokio::time::interval(Duration::from_secs(3600 * 6));
-
-    loop {
-        interval.tick().await;
-
-        if !DB::hql().is_leader_cache().await {
-            debug!(
-                "Running HA mode without being the leader - skipping magic_link_cleanup scheduler"
-            );
-            continue;
-        }
-
-        debug!("Running magic_link_cleanup scheduler");
-
-        // allow 300 seconds of clock skew before cleaning up magic links
-        let exp = Utc::now().sub(chrono::Duration::seconds(300)).timestamp();
-
-        // Check for expired and unused magic links that are bound to a user which has no password
-        // at all. These users should be deleted since they never cared about the (very important)
-        // password E-Mail.
-        if let Err(err) = cleanup(exp).await {
-            error!(error = ?err, "magic_link_cleanup");
-        }
+use tracing::{error, info};
 
-        // For some reason, the interval could `.tick()` multiple times,
-        // if it finished too quickly.
-        time::sleep(Duration::from_secs(3)).await;
-    }
-}
-
-async fn cleanup(exp: i64) -> Result<(), ErrorResponse> {
+/// Deletes users, that have never used their 'set first ever password' magic link to keep the
+/// database clean in case of an open user registration. Expired magic links themselves are
+/// cleaned up by the `maintenance` scheduler afterward.
+pub async fn cleanup_unused_users(exp: i64) -> Result<(), ErrorResponse> {
     let sql = r#"
 DELETE FROM users
 WHERE id IN (
@@ -78,15 +43,6 @@ AND password IS NULL AND webauthn_user_id IS NULL"#;
                 for _ in 0..rows_affected {
                     User::count_dec().await?;
                 }
-
-                // now we can just delete all expired magic links
-                let sql = "DELETE FROM magic_links WHERE exp < $1";
-                let rows_affected = if is_hiqlite() {
-                    DB::hql().execute(sql, params!(exp)).await?
-                } else {
-                    DB::pg_execute(sql, &[&exp]).await?
-                };
-                debug!("Cleaned up {rows_affected} expired and used magic links");
             }
         }
         Err(err) => {
@@ -0,0 +1,118 @@
+use crate::magic_links;
+use chrono::Utc;
+use rauthy_common::shutdown;
+use rauthy_common::shutdown::TaskGuard;
+use rauthy_data::database::DB;
+use rauthy_data::entity::auth_providers::AuthProviderCallback;
+use rauthy_data::entity::magic_links::MagicLink;
+use rauthy_data::events::event::Event;
+use rauthy_data::metrics;
+use rauthy_data::rauthy_config::RauthyConfig;
+use rauthy_error::ErrorResponse;
+use std::time::Duration;
+use tokio::time;
+use tracing::{debug, error, info};
+
+/// Collected values between two daily summary events.
+#[derive(Debug, Default)]
+struct Summary {
+    links_deleted: usize,
+    links_expired_attempts: i64,
+}
+
+/// Unified maintenance job for expiring data that is not cleaned up by a cache TTL:
+/// - updates the Magic Link gauges for the metrics endpoint on each node
+/// - the leader deletes expired Magic Links in batches and emits a daily summary event
+/// - the leader deletes expired upstream login callbacks from the DB
+pub async fn maintenance() {
+    let vars = &RauthyConfig::get().vars.database;
+    let mut interval = time::interval(Duration::from_secs(vars.sched_maintenance_mins as u64 * 60));
+
+    let mut summary = Summary::default();
+    let mut last_run = Utc::now().timestamp();
+    let mut last_summary = last_run;
+
+    loop {
+        interval.tick().await;
+
+        if RauthyConfig::get().vars.server.metrics_enable
+            && let Err(err) = update_gauges().await
+        {
+            error!(?err, "Error updating maintenance metrics");
+        }
+
+        if !DB::hql().is_leader_cache().await {
+            debug!("Running HA mode without being the leader - skipping maintenance scheduler");
+            // If this node becomes the leader, it should not report attempts it has not seen.
+            last_run = Utc::now().timestamp();
+            continue;
+        }
+
+        if shutdown::is_draining() {
+            debug!("Graceful shutdown in progress - skipping maintenance scheduler");
+            return;
+        }
+
+        debug!("Running maintenance scheduler");
+
+        // a started batch will always be finished before the DB is closed
+        let guard = TaskGuard::new();
+        match execute(last_run, &mut summary).await {
+            Ok(counted_until) => last_run = counted_until,
+            Err(err) => error!(?err, "Error during maintenance"),
+        }
+        drop(guard);
+
+        let now = Utc::now().timestamp();
+        if now - last_summary >= 24 * 3600 {
+            if let Err(err) =
+                Event::maintenance_summary(summary.links_deleted, summary.links_expired_attempts)
+                    .send()
+                    .await
+            {
+                error!(?err, "Cannot send MaintenanceSummary event");
+            }
+            summary = Summary::default();
+            last_summary = now;
+        }
+
+        // For some reason, the interval could `.tick()` multiple times,
+        // if it finished too quickly.
+        time::sleep(Duration::from_secs(3)).await;
+    }
+}
+
+/// Returns the timestamp until which expired attempts have been counted.
+async fn execute(last_run: i64, summary: &mut Summary) -> Result<i64, ErrorResponse> {
+    let now = Utc::now().timestamp();
+
+    // allow 300 seconds of clock skew before cleaning up users with unused magic links
+    magic_links::cleanup_unused_users(now - 300).await?;
+
+    // Must be counted before the deletion, because links that have been attempted since the
+    // last run may already be expired for longer than the grace period.
+    summary.links_expired_attempts += MagicLink::count_expired_attempts(last_run, now).await?;
+
+    let deleted_total = MagicLink::delete_expired(now).await?;
+    if deleted_total > 0 {
+        info!("Cleaned up {deleted_total} expired magic links");
+    }
+    summary.links_deleted += deleted_total;
+
+    let deleted = AuthProviderCallback::delete_expired(now).await?;
+    if deleted > 0 {
+        debug!("Cleaned up {deleted} expired auth provider callbacks");
+    }
+
+    Ok(now)
+}
+
+async fn update_gauges() -> Result<(), ErrorResponse> {
+    let stats = MagicLink::stats().await?;
+
+    metrics::MAGIC_LINKS_ACTIVE.set(stats.active);
+    metrics::MAGIC_LINKS_EXPIRED.set(stats.expired);
+    metrics::MAGIC_LINKS_EXPIRED_ATTEMPTED.set(stats.expired_attempted);
+
+    Ok(())
+}
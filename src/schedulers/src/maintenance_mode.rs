@@ -0,0 +1,38 @@
+use rauthy_data::database::DB;
+use rauthy_data::entity::maintenance_mode::MaintenanceMode;
+use std::time::Duration;
+use tokio::time;
+use tracing::{debug, error};
+
+/// Picks up the planned start and end of a maintenance and sends the matching events. The
+/// maintenance itself is enforced by its timestamps and does not depend on this scheduler. It
+/// only disables an expired maintenance afterward.
+pub async fn maintenance_mode_checker() {
+    let mut interval = time::interval(Duration::from_secs(10));
+
+    loop {
+        interval.tick().await;
+
+        if !DB::hql().is_leader_cache().await {
+            debug!(
+                "Running HA mode without being the leader - skipping maintenance_mode_checker scheduler"
+            );
+            continue;
+        }
+
+        let mut maintenance = match MaintenanceMode::find().await {
+            Ok(m) => m,
+            Err(err) => {
+                error!(?err, "Error looking up the maintenance mode");
+                continue;
+            }
+        };
+        if !maintenance.enabled && !maintenance.started {
+            continue;
+        }
+
+        if let Err(err) = maintenance.apply_transition().await {
+            error!(?err, "Error updating the maintenance mode");
+        }
+    }
+}
@@ -37,7 +37,10 @@ pub async fn password_expiry_checker() {
         let lower = now.add(chrono::Duration::days(9)).timestamp();
         let upper = now.add(chrono::Duration::days(10)).timestamp();
 
-        let sql = "SELECT * FROM users WHERE password_expires <= $1 AND password_expires > $2";
+        // passkey-only accounts never have a password that could expire
+        let sql = r#"
+SELECT * FROM users
+WHERE password IS NOT NULL AND password_expires <= $1 AND password_expires > $2"#;
         let expiring_users: Result<Vec<User>, ErrorResponse> = if is_hiqlite() {
             DB::hql()
                 .query_as(sql, params!(upper, lower))
@@ -0,0 +1,212 @@
+use chrono::Utc;
+use rauthy_common::constants::PROVIDER_ATPROTO;
+use rauthy_data::database::{Cache, DB};
+use rauthy_data::entity::auth_provider_health::AuthProviderHealth;
+use rauthy_data::entity::auth_providers::AuthProvider;
+use rauthy_data::entity::users::User;
+use rauthy_data::rauthy_config::RauthyConfig;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+/// The amount of break-glass login requests allowed from a single IP inside `WINDOW_SECS`.
+/// A single password login needs 2 requests, because the password input is only shown after
+/// the first one.
+const MAX_ATTEMPTS: u16 = 10;
+const WINDOW_SECS: i64 = 300;
+
+/// Counts the break-glass login requests from a single IP.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct BreakGlassAttempts {
+    count: u16,
+    window_start: i64,
+}
+
+#[inline]
+fn cache_idx(ip: IpAddr) -> String {
+    format!("break_glass_{ip}")
+}
+
+/// `true` if the break-glass login is currently enabled, either via
+/// `auth_providers.break_glass_login`, or automatically because all upstream providers have
+/// been failing their health checks for at least `auth_providers.break_glass_auto_secs`.
+pub async fn is_enabled() -> Result<bool, ErrorResponse> {
+    let config = &RauthyConfig::get().vars.auth_providers;
+    if config.break_glass_login {
+        return Ok(true);
+    }
+    if !config.health_check || config.break_glass_auto_secs == 0 {
+        return Ok(false);
+    }
+
+    let mut health = Vec::with_capacity(1);
+    for provider in AuthProvider::find_all().await? {
+        // atproto has no single upstream to check
+        if !provider.enabled || provider.issuer == PROVIDER_ATPROTO {
+            continue;
+        }
+        health.push(AuthProviderHealth::find(&provider.id).await?);
+    }
+
+    Ok(is_auto_enabled(
+        &health,
+        Utc::now().timestamp(),
+        config.break_glass_auto_secs,
+    ))
+}
+
+/// Without any checked provider, there is nothing to break out of.
+fn is_auto_enabled(health: &[AuthProviderHealth], now: i64, secs: u32) -> bool {
+    !health.is_empty() && health.iter().all(|h| h.is_fail_fast(now, secs))
+}
+
+/// Applies the rate limit for the break-glass login, which is independent of the failed
+/// logins counter.
+pub async fn rate_limit(ip: IpAddr) -> Result<(), ErrorResponse> {
+    let now = Utc::now().timestamp();
+    let idx = cache_idx(ip);
+    let attempts: Option<BreakGlassAttempts> =
+        DB::hql().get(Cache::IpRateLimit, idx.clone()).await?;
+    let attempts = next_attempts(attempts, now)?;
+
+    let ttl = attempts.window_start + WINDOW_SECS - now;
+    DB::hql()
+        .put(Cache::IpRateLimit, idx, &attempts, Some(ttl))
+        .await?;
+
+    Ok(())
+}
+
+fn next_attempts(
+    attempts: Option<BreakGlassAttempts>,
+    now: i64,
+) -> Result<BreakGlassAttempts, ErrorResponse> {
+    match attempts {
+        Some(attempts) if now < attempts.window_start + WINDOW_SECS => {
+            if attempts.count >= MAX_ATTEMPTS {
+                let retry_at = attempts.window_start + WINDOW_SECS;
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::TooManyRequests(retry_at),
+                    format!("You hit a rate limit. You may try again at: {retry_at}"),
+                ));
+            }
+            Ok(BreakGlassAttempts {
+                count: attempts.count + 1,
+                window_start: attempts.window_start,
+            })
+        }
+        _ => Ok(BreakGlassAttempts {
+            count: 1,
+            window_start: now,
+        }),
+    }
+}
+
+/// Only `rauthy_admin`s with a local credential may use the break-glass login. Any other user
+/// gets the same error as for invalid credentials to prevent information enumeration.
+pub async fn validate_user(email: &str) -> Result<(), ErrorResponse> {
+    let user = User::find_by_email(email.to_string()).await.ok();
+    if user.as_ref().is_some_and(is_allowed) {
+        Ok(())
+    } else {
+        Err(ErrorResponse::new(
+            ErrorResponseType::Unauthorized,
+            "Invalid user credentials",
+        ))
+    }
+}
+
+#[inline]
+fn is_allowed(user: &User) -> bool {
+    user.is_admin() && (user.password.is_some() || user.has_webauthn_enabled())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn health(available: bool, since: i64) -> AuthProviderHealth {
+        AuthProviderHealth {
+            available,
+            since,
+            contradicting: 0,
+        }
+    }
+
+    #[test]
+    fn test_is_auto_enabled() {
+        let now = 10_000;
+
+        assert!(!is_auto_enabled(&[], now, 600));
+
+        // down long enough
+        assert!(is_auto_enabled(&[health(false, now - 600)], now, 600));
+        assert!(is_auto_enabled(
+            &[health(false, now - 601), health(false, now - 3600)],
+            now,
+            600
+        ));
+
+        // not down long enough yet
+        assert!(!is_auto_enabled(&[health(false, now - 599)], now, 600));
+        // a single provider is still up
+        assert!(!is_auto_enabled(
+            &[health(false, now - 3600), health(true, now - 3600)],
+            now,
+            600
+        ));
+        assert!(!is_auto_enabled(&[health(true, now - 3600)], now, 600));
+    }
+
+    #[test]
+    fn test_next_attempts() {
+        let now = 10_000;
+
+        let mut attempts = None;
+        for count in 1..=MAX_ATTEMPTS {
+            let next = next_attempts(attempts, now).unwrap();
+            assert_eq!(next.count, count);
+            assert_eq!(next.window_start, now);
+            attempts = Some(next);
+        }
+
+        let err = next_attempts(attempts.clone(), now + WINDOW_SECS - 1).unwrap_err();
+        assert_eq!(
+            err.error,
+            ErrorResponseType::TooManyRequests(now + WINDOW_SECS)
+        );
+
+        // a new window
+        let next = next_attempts(attempts, now + WINDOW_SECS).unwrap();
+        assert_eq!(next.count, 1);
+        assert_eq!(next.window_start, now + WINDOW_SECS);
+    }
+
+    #[test]
+    fn test_is_allowed() {
+        let mut user = User {
+            roles: "admin,rauthy_admin".to_string(),
+            password: Some("$argon2id$hash".to_string()),
+            ..Default::default()
+        };
+        assert!(is_allowed(&user));
+
+        // passkey only
+        user.password = None;
+        user.webauthn_user_id = Some("webauthn_id".to_string());
+        assert!(is_allowed(&user));
+
+        // no local credential, e.g. an upstream-only account
+        user.webauthn_user_id = None;
+        assert!(!is_allowed(&user));
+
+        // not an admin
+        let user = User {
+            roles: "admin,user".to_string(),
+            password: Some("$argon2id$hash".to_string()),
+            webauthn_user_id: Some("webauthn_id".to_string()),
+            ..Default::default()
+        };
+        assert!(!is_allowed(&user));
+    }
+}
@@ -1,16 +1,37 @@
-use rauthy_api_types::clients::{ClientSecretResponse, UpdateClientRequest};
+use rauthy_api_types::claims::RESERVED_ROOT_CLAIMS;
+use rauthy_api_types::clients::{
+    ClaimsWebhookRequest, ClientClaimsEmission, ClientSecretResponse, ClientType,
+    UpdateClientRequest,
+};
 use rauthy_data::entity::clients::Client;
+use rauthy_data::entity::clients_claims::{CLAIM_GROUPS, CLAIM_GROUPS_OVERFLOW, CLAIM_ROLES};
+use rauthy_data::entity::clients_claims_webhook::ClientClaimsWebhook;
 use rauthy_data::entity::clients_scim::ClientScim;
+use rauthy_data::rauthy_config::RauthyConfig;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 
 /// Returns `true` inside `Option<(ClientScim, bool)>` if `ClientScim`
 /// has been updated and therefore needs a full sync.
+#[allow(clippy::type_complexity)]
 pub async fn update_client(
     id: String,
     client_req: UpdateClientRequest,
-) -> Result<(Client, Option<(ClientScim, bool)>), ErrorResponse> {
+) -> Result<
+    (
+        Client,
+        Option<(ClientScim, bool)>,
+        Option<ClientClaimsWebhook>,
+    ),
+    ErrorResponse,
+> {
     let mut client = Client::find(id).await?;
 
+    let client_type = client_req.client_type.unwrap_or_default();
+    if client_type == ClientType::Native {
+        validate_native_client(&client_req)?;
+    }
+    client.client_type = client_type.as_str().to_string();
+
     client.name = client_req.name;
     if client_req.confidential {
         // only set a new secret if this value has been changed
@@ -65,9 +86,50 @@ pub async fn update_client(
         .default_aud
         .map(|a| a.join(","))
         .filter(|a| !a.is_empty());
+    client.allowed_roles = client_req
+        .allowed_roles
+        .map(|r| r.join(","))
+        .filter(|r| !r.is_empty());
+    client.allowed_groups = client_req
+        .allowed_groups
+        .map(|g| g.join(","))
+        .filter(|g| !g.is_empty());
+    if let Some(response_types) = client_req.response_types {
+        client.response_types = response_types.join(",");
+    }
+    client.claims_emission = match client_req.claims_emission {
+        Some(emission) => {
+            validate_claims_emission(&emission)?;
+            Some(serde_json::to_vec(&emission)?)
+        }
+        None => None,
+    };
+    client.refresh_token_binding = match client_req.refresh_token_binding {
+        Some(binding) if binding.is_enabled() => {
+            if client.confidential {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    "`refresh_token_binding` is only available for public clients",
+                ));
+            }
+            Some(serde_json::to_vec(&binding)?)
+        }
+        _ => None,
+    };
+    if let Some(webhook) = &client_req.claims_webhook {
+        validate_claims_webhook(webhook)?;
+    }
 
     client.save().await?;
 
+    let claims_webhook = match client_req.claims_webhook {
+        Some(req) => Some(ClientClaimsWebhook::upsert(client.id.clone(), req).await?),
+        None => {
+            ClientClaimsWebhook::delete(client.id.clone()).await?;
+            None
+        }
+    };
+
     let scim = if let Some(scim_req) = client_req.scim {
         let base_uri = scim_req
             .base_uri
@@ -122,7 +184,7 @@ pub async fn update_client(
         None
     };
 
-    Ok((client, scim))
+    Ok((client, scim, claims_webhook))
 }
 
 /// Returns the clients secret in cleartext.
@@ -149,6 +211,12 @@ pub async fn generate_new_secret(
     cache_current_hours: Option<u8>,
 ) -> Result<ClientSecretResponse, ErrorResponse> {
     let mut client = Client::find(id).await?;
+    if client.is_native() {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "native clients cannot have a secret",
+        ));
+    }
 
     client.cache_current_secret(cache_current_hours).await?;
 
@@ -163,3 +231,77 @@ pub async fn generate_new_secret(
         secret: Some(clear),
     })
 }
+
+/// `native` clients are public, must use PKCE with `S256` only and cannot use wildcard redirects.
+fn validate_native_client(client_req: &UpdateClientRequest) -> Result<(), ErrorResponse> {
+    if client_req.confidential {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "native clients must be public",
+        ));
+    }
+
+    let is_s256_only =
+        matches!(client_req.challenges.as_deref(), Some([method]) if method == "S256");
+    if !is_s256_only {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "native clients require PKCE with `S256` only",
+        ));
+    }
+
+    Client::validate_native_redirect_uris(client_req.redirect_uris.iter().map(String::as_str))
+}
+
+/// Custom `roles` / `groups` claim names are emitted at the token root and must therefore
+/// never shadow a reserved claim or each other.
+fn validate_claims_emission(emission: &ClientClaimsEmission) -> Result<(), ErrorResponse> {
+    let roles = emission.roles_claim.as_deref().unwrap_or(CLAIM_ROLES);
+    let groups = emission.groups_claim.as_deref().unwrap_or(CLAIM_GROUPS);
+
+    if roles == groups {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "`roles_claim` and `groups_claim` must be different",
+        ));
+    }
+
+    for (name, default) in [(roles, CLAIM_ROLES), (groups, CLAIM_GROUPS)] {
+        if name != default
+            && (name == CLAIM_GROUPS_OVERFLOW || RESERVED_ROOT_CLAIMS.contains(&name))
+        {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("`{name}` is a reserved claim and cannot be used as a custom name"),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// The webhook may only set `roles`, `groups` and custom claims, which never shadow a reserved
+/// one. Outside of `dev_mode`, it must be reachable via `https://`.
+fn validate_claims_webhook(webhook: &ClaimsWebhookRequest) -> Result<(), ErrorResponse> {
+    if !webhook.uri.starts_with("https://") && !RauthyConfig::get().vars.dev.dev_mode {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "The claims webhook `uri` must use `https://`",
+        ));
+    }
+
+    for name in &webhook.allowed_claims {
+        let name = name.as_str();
+        if name != CLAIM_ROLES
+            && name != CLAIM_GROUPS
+            && (name == CLAIM_GROUPS_OVERFLOW || RESERVED_ROOT_CLAIMS.contains(&name))
+        {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("`{name}` is a reserved claim and cannot be set by a webhook"),
+            ));
+        }
+    }
+
+    Ok(())
+}
@@ -1,10 +1,14 @@
 use cryptr::{EncKeys, EncValue};
 use rauthy_data::entity::api_keys::ApiKeyEntity;
+use rauthy_data::entity::auth_provider_refresh_tokens::AuthProviderRefreshToken;
 use rauthy_data::entity::auth_providers::AuthProvider;
 use rauthy_data::entity::clients::Client;
+use rauthy_data::entity::clients_claims_webhook::ClientClaimsWebhook;
 use rauthy_data::entity::clients_scim::ClientScim;
 use rauthy_data::entity::jwk::JWKS;
 use rauthy_data::entity::kv::{KVAccess, KVValue};
+use rauthy_data::entity::user_federation_claims::UserFederationClaims;
+use rauthy_data::pii;
 use rauthy_error::ErrorResponse;
 use tracing::{error, info};
 
@@ -78,23 +82,16 @@ pub async fn migrate_encryption_alg(new_kid: &str) -> Result<(), ErrorResponse>
     // migrate auth providers
     let providers = AuthProvider::find_all().await?;
     for mut provider in providers {
-        match AuthProvider::secret_cleartext(&provider.secret) {
-            Ok(plaintext_opt) => {
-                if let Some(plaintext) = plaintext_opt {
-                    provider.secret = Some(
-                        EncValue::encrypt_with_key_id(plaintext.as_ref(), new_kid.to_string())?
-                            .into_bytes()
-                            .to_vec(),
-                    );
-                    provider.save().await?;
-
-                    modified += 1;
-                };
+        match provider.re_encrypt_secrets(None, new_kid) {
+            Ok(true) => {
+                provider.save().await?;
+                modified += 1;
             }
+            Ok(false) => {}
             Err(err) => {
                 error!(
                     ?err,
-                    "decrypting AuthProvider secret, this should never happen!",
+                    "decrypting AuthProvider secrets, this should never happen!",
                 );
             }
         }
@@ -122,6 +119,16 @@ pub async fn migrate_encryption_alg(new_kid: &str) -> Result<(), ErrorResponse>
         new_kid
     );
 
+    // migrate client claims webhooks
+    for webhook in ClientClaimsWebhook::find_all().await? {
+        webhook.re_encrypt(new_kid).await?;
+        modified += 1;
+    }
+    info!(
+        "Finished client claims webhook secrets migration to key id: {}",
+        new_kid
+    );
+
     // migrate KV Access keys
     for access in KVAccess::find_all_no_ns().await? {
         access.save_re_encrypt_secret().await?;
@@ -140,6 +147,27 @@ pub async fn migrate_encryption_alg(new_kid: &str) -> Result<(), ErrorResponse>
         new_kid
     );
 
+    // migrate upstream claims
+    for claims in UserFederationClaims::find_all().await? {
+        claims.save_re_encrypted().await?;
+        modified += 1;
+    }
+    info!("Finished upstream claims migration to key id: {}", new_kid);
+
+    // migrate upstream refresh tokens
+    for token in AuthProviderRefreshToken::find_all().await? {
+        token.save_re_encrypted().await?;
+        modified += 1;
+    }
+    info!(
+        "Finished upstream refresh tokens migration to key id: {}",
+        new_kid
+    );
+
+    // migrate encrypted PII
+    modified += pii::re_encrypt_all(new_kid).await?;
+    info!("Finished PII migration to key id: {}", new_kid);
+
     info!(
         "Finished secrets migration to key id: {new_kid} after {} ms. Modified {modified} \
         encryption's",
@@ -4,13 +4,14 @@ use actix_web::{HttpRequest, HttpResponse};
 use chrono::Utc;
 use rauthy_api_types::forward_auth::{ForwardAuthCallbackParams, ForwardAuthParams};
 use rauthy_common::constants::TRUSTED_PROXIES;
-use rauthy_common::utils::real_ip_from_req;
+use rauthy_common::utils::{is_trusted_proxy, real_ip};
 use rauthy_data::entity::auth_codes::AuthCode;
 use rauthy_data::entity::clients::Client;
 use rauthy_data::entity::forward_auth::{ForwardAuthCallbackState, ForwardAuthSession};
 use rauthy_data::entity::sessions::{Session, SessionState};
 use rauthy_data::entity::users::User;
 use rauthy_data::entity::users_values::UserValues;
+use rauthy_data::metrics;
 use rauthy_data::rauthy_config::RauthyConfig;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use std::net::IpAddr;
@@ -106,7 +107,7 @@ pub async fn get_forward_auth_client(
         let cid = client.id;
         let state = ForwardAuthCallbackState {
             client_id: cid.clone(),
-            peer_ip: real_ip_from_req(&req)?,
+            peer_ip: real_ip(&req)?,
             origin,
             forwarded_uri: forwarded_uri.to_string(),
             danger_cookie_insecure: params.danger_cookie_insecure,
@@ -144,7 +145,7 @@ pub async fn get_forward_auth_client(
     let user = User::find(user_id).await?;
     user.check_enabled()?;
     user.check_expired()?;
-    client.validate_user_groups(&user)?;
+    client.validate_user_access(&user)?;
     client.validate_mfa(&user, None)?;
 
     let headers = &RauthyConfig::get().vars.auth_headers;
@@ -190,7 +191,7 @@ pub async fn get_forward_auth_client_callback(
 ) -> Result<HttpResponse, ErrorResponse> {
     validate_proxy(&req)?;
 
-    let ip = real_ip_from_req(&req)?;
+    let ip = real_ip(&req)?;
     let proto = get_header("X-Forwarded-Proto", &req)?;
     let host = get_header("X-Forwarded-Host", &req)?;
     let origin = format!("{proto}://{host}");
@@ -245,6 +246,7 @@ pub async fn get_forward_auth_client_callback(
     }
     let now = Utc::now().timestamp();
     if auth_code.exp < now {
+        metrics::AUTH_CODES_EXPIRED_ATTEMPTS.inc();
         return Err(ErrorResponse::new(
             ErrorResponseType::BadRequest,
             "AuthCode has expired",
@@ -260,7 +262,7 @@ pub async fn get_forward_auth_client_callback(
     let user = User::find(auth_code.user_id).await?;
     user.check_enabled()?;
     user.check_expired()?;
-    client.validate_user_groups(&user)?;
+    client.validate_user_access(&user)?;
     client.validate_mfa(&user, None)?;
 
     // all good
@@ -361,8 +363,6 @@ fn get_header_alt<'a>(
 
 #[inline]
 fn validate_proxy(req: &HttpRequest) -> Result<(), ErrorResponse> {
-    let mut is_match = false;
-
     let proxy_ip = match req.connection_info().peer_addr() {
         None => {
             debug!("Cannot extract peer IP from HTTP request");
@@ -383,14 +383,7 @@ fn validate_proxy(req: &HttpRequest) -> Result<(), ErrorResponse> {
         },
     };
 
-    for cidr in TRUSTED_PROXIES.get().unwrap() {
-        if cidr.contains(&proxy_ip) {
-            is_match = true;
-            break;
-        }
-    }
-
-    if is_match {
+    if is_trusted_proxy(TRUSTED_PROXIES.get().unwrap(), &proxy_ip) {
         Ok(())
     } else {
         debug!("Invalid proxy peer IP");
@@ -2,6 +2,7 @@
 
 #![forbid(unsafe_code)]
 
+pub mod break_glass;
 pub mod client;
 pub mod encryption;
 pub mod forward_auth;
@@ -9,5 +10,6 @@ pub mod login_delay;
 pub mod oidc;
 pub mod password_reset;
 pub mod suspicious_request_block;
+pub mod token_preview;
 pub mod token_set;
 pub mod user_values_validator;
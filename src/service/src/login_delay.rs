@@ -1,10 +1,11 @@
-use actix_web::HttpResponse;
+use actix_web::{HttpRequest, HttpResponse};
 use chrono::Utc;
 use rauthy_common::constants::IDX_LOGIN_TIME;
 use rauthy_data::database::{Cache, DB};
 use rauthy_data::entity::failed_login_counter::FailedLoginCounter;
 use rauthy_data::entity::ip_blacklist::IpBlacklist;
 use rauthy_data::events::event::Event;
+use rauthy_data::html::csp::CspNonce;
 use rauthy_data::html::templates::TooManyRequestsHtml;
 use rauthy_data::rauthy_config::RauthyConfig;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
@@ -22,6 +23,7 @@ long it took for a successful login. If a login failed though, the answer will b
 current average for a successful login, to prevent things like username enumeration.
  */
 pub async fn handle_login_delay(
+    req: &HttpRequest,
     peer_ip: IpAddr,
     start: Duration,
     res: Result<HttpResponse, ErrorResponse>,
@@ -50,6 +52,9 @@ pub async fn handle_login_delay(
 
             Ok(resp)
         }
+        // The request was shed because of a full hash queue or the maintenance mode. This is
+        // not a failed login and must never lead to a blacklisting during a login storm.
+        Err(err) if matches!(err.error, ErrorResponseType::ServiceUnavailable(_)) => Err(err),
         Err(err) => {
             let failed_logins = FailedLoginCounter::increase(peer_ip.to_string()).await?;
             let failed_logins = min(failed_logins, u32::MAX as i64) as u32;
@@ -74,23 +79,23 @@ pub async fn handle_login_delay(
             let sleep_time = match failed_logins as u64 {
                 // n-th blacklist -> blocks for 24h with each invalid request
                 t if t >= 25 => {
-                    return build_send_event(&peer_ip, 86400).await;
+                    return build_send_event(req, &peer_ip, 86400).await;
                 }
                 t if t > 20 => sleep_time_median + t * 20_000,
                 20 => {
-                    return build_send_event(&peer_ip, 3600).await;
+                    return build_send_event(req, &peer_ip, 3600).await;
                 }
                 t if t > 15 => sleep_time_median + t * 15_000,
                 15 => {
-                    return build_send_event(&peer_ip, 900).await;
+                    return build_send_event(req, &peer_ip, 900).await;
                 }
                 t if t > 10 => sleep_time_median + t * 10_000,
                 10 => {
-                    return build_send_event(&peer_ip, 600).await;
+                    return build_send_event(req, &peer_ip, 600).await;
                 }
                 t if t > 7 => sleep_time_median + t * 5_000,
                 7 => {
-                    return build_send_event(&peer_ip, 60).await;
+                    return build_send_event(req, &peer_ip, 60).await;
                 }
                 t if t >= 5 => sleep_time_median + t * 3_000,
                 t if t >= 3 => sleep_time_median + t * 2_000,
@@ -106,12 +111,13 @@ pub async fn handle_login_delay(
 }
 
 async fn build_send_event(
+    req: &HttpRequest,
     peer_ip: &IpAddr,
     nbf_seconds: u32,
 ) -> Result<HttpResponse, ErrorResponse> {
     let not_before = Utc::now().add(chrono::Duration::seconds(nbf_seconds as i64));
     let ts = not_before.timestamp();
-    let html = TooManyRequestsHtml::build(peer_ip.to_string(), ts);
+    let html = TooManyRequestsHtml::build(peer_ip.to_string(), ts, &CspNonce::from_req(req));
 
     RauthyConfig::get()
         .tx_events
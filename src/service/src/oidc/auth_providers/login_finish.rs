@@ -5,15 +5,18 @@ use actix_web::cookie::Cookie;
 use rauthy_api_types::auth_providers::ProviderCallbackRequest;
 use rauthy_common::constants::{COOKIE_UPSTREAM_CALLBACK, PROVIDER_ATPROTO, PROVIDER_LINK_COOKIE};
 use rauthy_common::sha256;
-use rauthy_common::utils::base64_url_encode;
+use rauthy_common::utils::{base64_url_encode, real_ip, secure_compare};
 use rauthy_data::AuthStep;
 use rauthy_data::api_cookie::ApiCookie;
+use rauthy_data::entity::auth_provider_logout::AuthProviderSession;
+use rauthy_data::entity::auth_provider_refresh_tokens::AuthProviderRefreshToken;
 use rauthy_data::entity::auth_providers::{
     AuthProvider, AuthProviderCallback, AuthProviderLinkCookie, NewFederatedUserCreated,
-    ProviderMfaLogin,
+    ProviderMfaLogin, UpstreamLoginTokens,
 };
 use rauthy_data::entity::clients::Client;
 use rauthy_data::entity::sessions::Session;
+use rauthy_data::events::event::Event;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use tracing::error;
 
@@ -44,7 +47,7 @@ pub async fn login_finish<'a>(
 
     // validate csrf token
     let slf = AuthProviderCallback::find(callback_id).await?;
-    if slf.xsrf_token != payload.xsrf_token {
+    if !secure_compare(&slf.xsrf_token, &payload.xsrf_token) {
         AuthProviderCallback::delete(slf.callback_id).await?;
 
         error!("invalid CSRF token");
@@ -56,7 +59,7 @@ pub async fn login_finish<'a>(
 
     // validate PKCE verifier
     let hash_base64 = base64_url_encode(sha256!(payload.pkce_verifier.as_bytes()));
-    if slf.pkce_challenge != hash_base64 {
+    if !secure_compare(&slf.pkce_challenge, &hash_base64) {
         AuthProviderCallback::delete(slf.callback_id).await?;
 
         error!("invalid PKCE verifier");
@@ -74,10 +77,25 @@ pub async fn login_finish<'a>(
     let link_cookie = ApiCookie::from_req(req, PROVIDER_LINK_COOKIE)
         .and_then(|value| AuthProviderLinkCookie::try_from(value.as_str()).ok());
 
+    // A link must only ever be created for the user of the currently active session.
+    if let Some(link) = &link_cookie
+        && session.user_id.as_deref() != Some(link.user_id.as_str())
+    {
+        AuthProviderCallback::delete(slf.callback_id).await?;
+
+        error!("provider link cookie does not belong to the current session");
+        return Err(ErrorResponse::new(
+            ErrorResponseType::Forbidden,
+            "The provider link does not belong to the current session",
+        ));
+    }
+
     // deserialize payload and validate the information
-    let (user, provider_mfa_login, is_new_user) = if provider.issuer == PROVIDER_ATPROTO {
-        slf.extract_user_at_proto(&provider, &link_cookie, payload)
-            .await?
+    let (user, provider_mfa_login, is_new_user, tokens) = if provider.issuer == PROVIDER_ATPROTO {
+        let (user, mfa_login, is_new) = slf
+            .extract_user_at_proto(&provider, &link_cookie, payload)
+            .await?;
+        (user, mfa_login, is_new, UpstreamLoginTokens::default())
     } else {
         slf.extract_user(&provider, &link_cookie, payload).await?
     };
@@ -85,7 +103,23 @@ pub async fn login_finish<'a>(
     user.check_enabled()?;
     user.check_expired()?;
 
+    if provider.upstream_refresh
+        && let Some(refresh_token) = &tokens.refresh_token
+    {
+        AuthProviderRefreshToken::upsert(&user.id, &provider.id, refresh_token).await?;
+    }
+
     if link_cookie.is_some() {
+        Event::user_provider_link(
+            format!(
+                "`{}` linked to auth provider `{}`",
+                user.email, provider.name
+            ),
+            real_ip(req)?,
+        )
+        .send()
+        .await?;
+
         // If this is the case, we don't need to validate any further client values.
         // We will not generate a new auth code at all -> this is just a request to federate
         // an existing account. The federation has been done in the step above already.
@@ -122,12 +156,25 @@ pub async fn login_finish<'a>(
             resource: None,
             header_origin,
             require_webauthn,
+            rotate_session: true,
         },
         None,
         Some(provider_mfa_login),
     )
     .await?;
 
+    // The upstream `id_token` is only needed for a possible logout at the provider later on.
+    if provider.end_session_endpoint.is_some()
+        && let Some(id_token) = tokens.id_token
+    {
+        AuthProviderSession {
+            provider_id: provider.id,
+            id_token,
+        }
+        .save(&session)
+        .await?;
+    }
+
     // callback data deletion cookie
     let cookie = ApiCookie::build(COOKIE_UPSTREAM_CALLBACK, "", 0);
 
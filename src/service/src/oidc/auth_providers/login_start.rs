@@ -3,23 +3,31 @@ use actix_web::http::header::HeaderValue;
 use atrium_oauth::{AuthorizeOptions, KnownScope, Scope as ScopeAtproto};
 use cryptr::utils::secure_random_alnum;
 use rauthy_api_types::auth_providers::ProviderLoginRequest;
-use rauthy_common::constants::{
-    COOKIE_UPSTREAM_CALLBACK, PROVIDER_ATPROTO, UPSTREAM_AUTH_CALLBACK_TIMEOUT_SECS,
-};
+use rauthy_common::constants::{COOKIE_UPSTREAM_CALLBACK, PROVIDER_ATPROTO};
 use rauthy_data::api_cookie::ApiCookie;
 use rauthy_data::entity::atproto;
-use rauthy_data::entity::auth_providers::{AuthProvider, AuthProviderCallback};
+use rauthy_data::entity::auth_provider_health::AuthProviderHealth;
+use rauthy_data::entity::auth_providers::{
+    AuthProvider, AuthProviderCallback, AuthProviderResumeCookie, AuthProviderType,
+};
 use rauthy_data::entity::clients::Client;
 use rauthy_data::rauthy_config::RauthyConfig;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
+use reqwest::Url;
 use std::fmt::Write;
 use tracing::error;
 
-/// returns (encrypted cookie, xsrf token, location header, optional allowed origins)
+/// returns (encrypted cookie, optional resume cookie, xsrf token, location header)
 pub async fn login_start<'a>(
     payload: ProviderLoginRequest,
-) -> Result<(Cookie<'a>, String, HeaderValue), ErrorResponse> {
+) -> Result<(Cookie<'a>, Option<Cookie<'a>>, String, HeaderValue), ErrorResponse> {
     let provider = AuthProvider::find(&payload.provider_id).await?;
+    if !provider.enabled {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::Disabled,
+            format!("The login provider `{}` is disabled", provider.name),
+        ));
+    }
 
     if !RauthyConfig::get().vars.atproto.enable && provider.issuer == PROVIDER_ATPROTO {
         return Err(ErrorResponse::new(
@@ -28,7 +36,12 @@ pub async fn login_start<'a>(
         ));
     }
 
+    AuthProviderHealth::validate_login(&provider.id, &provider.name).await?;
+
     let client = Client::find(payload.client_id).await?;
+    let extra_auth_params = provider.get_extra_auth_params()?;
+    let hd = provider.hd_auth_param().map(String::from);
+    let timeout = provider.callback_timeout();
 
     let slf = AuthProviderCallback {
         callback_id: secure_random_alnum(32),
@@ -46,10 +59,12 @@ pub async fn login_start<'a>(
         provider_id: provider.id,
 
         pkce_challenge: payload.pkce_challenge,
+        nonce: secure_random_alnum(32),
+        upstream_user: None,
     };
 
     let mut location = format!(
-        "{}{}client_id={}&redirect_uri={}&response_type=code&scope={}&state={}",
+        "{}{}client_id={}&redirect_uri={}&response_type=code&scope={}&state={}&nonce={}",
         provider.authorization_endpoint,
         // append parameters if there are already some parameters
         if provider.authorization_endpoint.contains('?') {
@@ -60,7 +75,8 @@ pub async fn login_start<'a>(
         provider.client_id,
         RauthyConfig::get().provider_callback_uri_encoded,
         provider.scope,
-        slf.callback_id
+        slf.callback_id,
+        slf.nonce,
     );
     if provider.use_pkce {
         write!(
@@ -71,6 +87,28 @@ pub async fn login_start<'a>(
         .expect("write to always succeed");
     }
 
+    // Apple requires a `form_post` as soon as the `name` or `email` scope is requested.
+    if slf.typ == AuthProviderType::Apple {
+        location.push_str("&response_mode=form_post");
+    }
+
+    // only a hint for the upstream login page, the `hd` claim is validated afterward
+    if let Some(hd) = hd {
+        write!(location, "&hd={hd}").expect("write to always succeed");
+    }
+
+    if let Some(params) = extra_auth_params {
+        // The keys have been validated on save and never collide with the params above.
+        let mut url = Url::parse(&location).map_err(|err| {
+            ErrorResponse::new(
+                ErrorResponseType::Internal,
+                format!("Invalid upstream authorization_endpoint: {err}"),
+            )
+        })?;
+        url.query_pairs_mut().extend_pairs(params.iter());
+        location = url.to_string();
+    }
+
     if let Some(input) = payload
         .handle
         .filter(|_| provider.issuer == PROVIDER_ATPROTO)
@@ -88,26 +126,29 @@ pub async fn login_start<'a>(
             ..Default::default()
         };
 
-        location = atproto
-            .authorize(input, options)
-            .await
-            .map_err(|error| {
-                error!(%error, "failed to start authorization for ATProto");
-            })
-            .unwrap();
+        location = atproto.authorize(input, options).await.map_err(|error| {
+            error!(%error, "failed to start authorization for ATProto");
+            ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Cannot start the authorization for this ATProto handle",
+            )
+        })?;
     }
 
-    let cookie = ApiCookie::build(
-        COOKIE_UPSTREAM_CALLBACK,
-        &slf.callback_id,
-        UPSTREAM_AUTH_CALLBACK_TIMEOUT_SECS as i64,
-    );
+    let cookie = ApiCookie::build(COOKIE_UPSTREAM_CALLBACK, &slf.callback_id, timeout);
+    // atproto logins need the handle as additional input and can't be resumed automatically
+    let resume_cookie = if provider.issuer != PROVIDER_ATPROTO {
+        Some(AuthProviderResumeCookie::from(&slf).build_cookie(timeout)?)
+    } else {
+        None
+    };
 
-    slf.save().await?;
+    slf.save(timeout).await?;
 
     Ok((
         cookie,
+        resume_cookie,
         slf.xsrf_token,
-        HeaderValue::from_str(&location).expect("Location HeaderValue to be correct"),
+        HeaderValue::from_str(&location)?,
     ))
 }
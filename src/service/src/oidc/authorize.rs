@@ -5,7 +5,8 @@ use actix_web::http::header::{HeaderName, HeaderValue};
 use chrono::Utc;
 use rauthy_api_types::oidc::{LoginRefreshRequest, LoginRequest};
 use rauthy_common::constants::COOKIE_MFA;
-use rauthy_common::utils::{get_rand, real_ip_from_req};
+use rauthy_common::password_hasher::HashPassword;
+use rauthy_common::utils::{encode_query_value, get_rand, real_ip};
 use rauthy_data::api_cookie::ApiCookie;
 use rauthy_data::entity::auth_codes::{AuthCode, AuthCodeToSAwait};
 use rauthy_data::entity::auth_providers::ProviderMfaLogin;
@@ -13,15 +14,44 @@ use rauthy_data::entity::browser_id::BrowserId;
 use rauthy_data::entity::clients::Client;
 use rauthy_data::entity::cred_stuff_detect::CredStuffDetect;
 use rauthy_data::entity::login_locations::LoginLocation;
+use rauthy_data::entity::maintenance_mode::MaintenanceMode;
 use rauthy_data::entity::sessions::Session;
 use rauthy_data::entity::users::{AccountType, User};
 use rauthy_data::entity::webauthn::{WebauthnCookie, WebauthnLoginReq, WebauthnToSAwaitData};
 use rauthy_data::rauthy_config::RauthyConfig;
 use rauthy_data::{AuthStep, AuthStepAwaitWebauthn, AuthStepLoggedIn, AwaitToSAccept};
 use rauthy_error::{ErrorResponse, ErrorResponseType};
-use tracing::trace;
+use tracing::{info, trace};
 use zeroize::Zeroize;
 
+/// Builds the redirect location for authorize responses without a `code`, which is used for
+/// `response_type=none` and errors like `login_required` with `prompt=none`.
+pub fn redirect_location_without_code(
+    redirect_uri: &str,
+    error: Option<&str>,
+    state: Option<&str>,
+) -> String {
+    let mut loc =
+        String::with_capacity(redirect_uri.len() + 32 + state.map(|s| s.len()).unwrap_or_default());
+    loc.push_str(redirect_uri);
+
+    // make sure URIs that already contain params work fine
+    let mut append_char = if redirect_uri.contains('?') { '&' } else { '?' };
+    if let Some(error) = error {
+        loc.push(append_char);
+        loc.push_str("error=");
+        loc.push_str(error);
+        append_char = '&';
+    }
+    if let Some(state) = state {
+        loc.push(append_char);
+        loc.push_str("state=");
+        loc.push_str(&encode_query_value(state));
+    }
+
+    loc
+}
+
 pub async fn post_authorize(
     req: &HttpRequest,
     req_data: LoginRequest,
@@ -44,7 +74,7 @@ pub async fn post_authorize(
                 *add_login_delay = false;
             }
 
-            let ip = real_ip_from_req(req)?;
+            let ip = real_ip(req)?;
             CredStuffDetect::trigger(ip, &req_data.email, req_data.password.as_deref()).await;
 
             if let Some(mut pwd) = req_data.password {
@@ -97,16 +127,23 @@ pub async fn post_authorize(
 
     user.check_enabled()?;
     user.check_expired()?;
+    MaintenanceMode::validate_login(req, &user.id).await?;
 
     if let Some(pwd) = req_data.password {
         *has_password_been_hashed = true;
         if let Err(err) = user.validate_password(pwd.clone()).await {
-            let ip = real_ip_from_req(req)?;
+            let ip = real_ip(req)?;
             CredStuffDetect::trigger(ip, &user.email, Some(&pwd)).await;
 
             return Err(err);
         }
 
+        // upgrade outdated Argon2 params and imported legacy hashes
+        if !user.is_argon2_uptodate(&RauthyConfig::get().argon2_params)? {
+            info!("Updating the password hash for user '{}'", &user.email);
+            user.password = Some(HashPassword::hash_password(pwd).await?);
+        }
+
         // This would also send a location notification if an attacker only knows a password, but
         // is later on unable to fully compromise an account when MFA is missing. However, this is
         // not really a false positive. We want to inform a user even if only the password got
@@ -147,6 +184,7 @@ pub async fn post_authorize(
             resource: req_data.resource,
             header_origin,
             require_webauthn,
+            rotate_session: true,
         },
         Some(user_needs_mfa),
         None,
@@ -188,6 +226,7 @@ pub async fn post_authorize_refresh(
             resource: None,
             header_origin,
             require_webauthn,
+            rotate_session: false,
         },
         None,
         None,
@@ -205,6 +244,8 @@ pub(crate) struct AuthorizeData {
     pub resource: Option<String>,
     pub header_origin: Option<(HeaderName, HeaderValue)>,
     pub require_webauthn: bool,
+    /// Must be `true` after a fresh authentication to prevent session fixation.
+    pub rotate_session: bool,
 }
 
 /// Expects the user checks already been done, but does all the necessary client validations.
@@ -228,7 +269,7 @@ pub(crate) async fn finish_authorize(
                 *needs_mfa = true;
             }
         })?;
-    client.validate_user_groups(&user)?;
+    client.validate_user_access(&user)?;
     client.validate_redirect_uri(&data.redirect_uri)?;
     client.validate_code_challenge(&data.code_challenge, &data.code_challenge_method)?;
 
@@ -249,6 +290,12 @@ pub(crate) async fn finish_authorize(
     }
     let needs_user_update = UserValuesValidator::does_user_need_update(&user, &client.id).await?;
 
+    // The login changes the privilege level of the session. The id must be rotated before the
+    // auth code is created, so it references the new one.
+    if data.rotate_session {
+        session.rotate_id().await?;
+    }
+
     let code = AuthCode::new(
         user.id.clone(),
         client.id,
@@ -280,6 +327,7 @@ pub(crate) async fn finish_authorize(
             email: user.email,
             exp: config.vars.webauthn.req_exp as u64,
             session: session.clone(),
+            rotated_session: data.rotate_session.then(|| session.clone()),
         };
 
         WebauthnLoginReq {
@@ -323,6 +371,7 @@ pub(crate) async fn finish_authorize(
                 user_id: user.id,
                 header_csrf: Session::get_csrf_header(&session.csrf_token),
                 header_origin: data.header_origin,
+                rotated_session: data.rotate_session.then(|| session.clone()),
             }))
         } else {
             Ok(AuthStep::LoggedIn(AuthStepLoggedIn {
@@ -332,7 +381,56 @@ pub(crate) async fn finish_authorize(
                 header_csrf: Session::get_csrf_header(&session.csrf_token),
                 header_origin: data.header_origin,
                 needs_user_update,
+                rotated_session: data.rotate_session.then(|| session.clone()),
             }))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redirect_location_without_code() {
+        let uri = "https://app.example.com/callback";
+
+        assert_eq!(redirect_location_without_code(uri, None, None), uri);
+        assert_eq!(
+            redirect_location_without_code(uri, None, Some("abc123")),
+            "https://app.example.com/callback?state=abc123"
+        );
+        assert_eq!(
+            redirect_location_without_code(uri, Some("login_required"), Some("abc123")),
+            "https://app.example.com/callback?error=login_required&state=abc123"
+        );
+        assert_eq!(
+            redirect_location_without_code(uri, Some("login_required"), None),
+            "https://app.example.com/callback?error=login_required"
+        );
+
+        let uri = "https://app.example.com/callback?a=b";
+        assert_eq!(
+            redirect_location_without_code(uri, None, Some("abc123")),
+            "https://app.example.com/callback?a=b&state=abc123"
+        );
+
+        // the `state` must never be able to inject additional params or headers
+        assert_eq!(
+            redirect_location_without_code(uri, None, Some("a&code=1#x")),
+            "https://app.example.com/callback?a=b&state=a%26code=1%23x"
+        );
+        assert_eq!(
+            redirect_location_without_code(uri, None, Some("a\r\nSet-Cookie: x\0")),
+            "https://app.example.com/callback?a=b&state=a%0D%0ASet-Cookie:%20x%00"
+        );
+        assert!(
+            HeaderValue::from_str(&redirect_location_without_code(
+                uri,
+                Some("login_required"),
+                Some(&"ä\r\n".repeat(10_000)),
+            ))
+            .is_ok()
+        );
+    }
+}
@@ -1,13 +1,14 @@
 use chrono::Utc;
 use cryptr::utils::secure_random_alnum;
+use rauthy_api_types::claims::{JwtCommonClaims, JwtLogoutClaims, JwtTokenType};
 use rauthy_api_types::oidc::Audience;
+use rauthy_common::jwt_time::JwtTimeClaims;
 use rauthy_common::utils::{base64_url_no_pad_decode, base64_url_no_pad_decode_buf};
 use rauthy_data::entity::auth_providers::AuthProvider;
 use rauthy_data::entity::jwk::{JWKSPublicKey, JwkKeyPair, JwkKeyPairAlg};
 use rauthy_data::rauthy_config::RauthyConfig;
 use rauthy_error::ErrorResponse;
 use rauthy_error::ErrorResponseType;
-use rauthy_jwt::claims::{JwtCommonClaims, JwtLogoutClaims, JwtTokenType};
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 use std::str::FromStr;
@@ -200,7 +201,11 @@ impl LogoutToken<'_> {
         header: serde_json::Value,
     ) -> Result<(String, JwkKeyPairAlg), ErrorResponse> {
         let lifetime = RauthyConfig::get().vars.backchannel_logout.token_lifetime as i64;
-        let skew = RauthyConfig::get().vars.backchannel_logout.allow_clock_skew as i64;
+        let skew = RauthyConfig::get()
+            .vars
+            .backchannel_logout
+            .allow_clock_skew
+            .min(u16::MAX as u32) as u16;
         self.validate_claims_with(header, lifetime, skew)
     }
 
@@ -209,7 +214,7 @@ impl LogoutToken<'_> {
         &self,
         header: serde_json::Value,
         token_lifetime: i64,
-        allow_clock_skew: i64,
+        allow_clock_skew: u16,
     ) -> Result<(String, JwkKeyPairAlg), ErrorResponse> {
         let alg = JwkKeyPairAlg::from_str(
             header
@@ -237,23 +242,21 @@ impl LogoutToken<'_> {
             ));
         }
 
-        let now = Utc::now().timestamp();
-
-        let iat_limit = now - token_lifetime - allow_clock_skew;
-        let exp_limit = now + allow_clock_skew;
+        JwtTimeClaims {
+            iat: Some(self.iat),
+            exp: Some(self.exp),
+            nbf: None,
+        }
+        .validate(allow_clock_skew)
+        .map_err(|err| ErrorResponse::new(ErrorResponseType::BadRequest, err.message))?;
 
+        let iat_limit = Utc::now().timestamp() - token_lifetime - allow_clock_skew as i64;
         if self.iat < iat_limit {
             return Err(ErrorResponse::new(
                 ErrorResponseType::BadRequest,
                 "`iat` is too long ago",
             ));
         }
-        if self.exp < exp_limit {
-            return Err(ErrorResponse::new(
-                ErrorResponseType::BadRequest,
-                "token has expired",
-            ));
-        }
         if self.exp <= self.iat {
             return Err(ErrorResponse::new(
                 ErrorResponseType::BadRequest,
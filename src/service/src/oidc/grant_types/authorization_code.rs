@@ -1,15 +1,16 @@
+use crate::oidc::grant_types::rfc_err;
 use crate::token_set::{
-    AuthCodeFlow, AuthTime, DeviceCodeFlow, DpopFingerprint, SessionId, TokenNonce, TokenScopes,
-    TokenSet,
+    self, AuthCodeFlow, AuthTime, DeviceCodeFlow, DpopFingerprint, RefreshTokenFingerprint,
+    SessionId, TokenNonce, TokenScopes,
 };
 use actix_web::HttpRequest;
 use actix_web::http::header::{
     ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_METHODS, HeaderName, HeaderValue,
 };
 use chrono::Utc;
-use rauthy_api_types::oidc::TokenRequest;
+use rauthy_api_types::oidc::{TokenRequest, TokenSet};
 use rauthy_common::constants::HEADER_DPOP_NONCE;
-use rauthy_common::utils::{base64_url_encode, real_ip_from_req};
+use rauthy_common::utils::{base64_url_encode, real_ip, secure_compare};
 use rauthy_data::entity::auth_codes::AuthCode;
 use rauthy_data::entity::clients::Client;
 use rauthy_data::entity::clients_dyn::ClientDyn;
@@ -18,6 +19,7 @@ use rauthy_data::entity::sessions::Session;
 use rauthy_data::entity::user_login_states::UserLoginState;
 use rauthy_data::entity::users::User;
 use rauthy_data::events::event::Event;
+use rauthy_data::metrics;
 use rauthy_data::rauthy_config::RauthyConfig;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use ring::digest;
@@ -48,26 +50,40 @@ pub async fn grant_type_authorization_code(
     }
 
     // check the client for external origin and oidc flow
-    let (client_id, client_secret) = req_data.try_get_client_id_secret(&req)?;
+    let (client_id, client_secret) = req_data
+        .try_get_client_id_secret(&req)
+        .map_err(rfc_err(ErrorResponseType::InvalidClient))?;
     let client = Client::find_maybe_ephemeral(client_id.clone())
         .await
         .map_err(|_| {
             ErrorResponse::new(
-                ErrorResponseType::NotFound,
+                ErrorResponseType::InvalidClient,
                 format!("Client '{client_id}' not found"),
             )
         })?;
-    client.validate_enabled()?;
+    client
+        .validate_enabled()
+        .map_err(rfc_err(ErrorResponseType::UnauthorizedClient))?;
     let header_origin = client.get_validated_origin_header(&req)?;
     if client.confidential {
         let secret = client_secret.ok_or_else(|| {
             warn!("'client_secret' is missing");
-            ErrorResponse::new(ErrorResponseType::BadRequest, "'client_secret' is missing")
+            ErrorResponse::new(
+                ErrorResponseType::InvalidClient,
+                "'client_secret' is missing",
+            )
         })?;
-        client.validate_secret(secret, &req).await?;
+        client
+            .validate_secret(secret, &req)
+            .await
+            .map_err(rfc_err(ErrorResponseType::InvalidClient))?;
     }
-    client.validate_flow("authorization_code")?;
-    client.validate_redirect_uri(req_data.redirect_uri.as_deref().unwrap_or_default())?;
+    client
+        .validate_flow("authorization_code")
+        .map_err(rfc_err(ErrorResponseType::UnauthorizedClient))?;
+    client
+        .validate_redirect_uri(req_data.redirect_uri.as_deref().unwrap_or_default())
+        .map_err(rfc_err(ErrorResponseType::InvalidGrant))?;
 
     // check for DPoP header
     let mut headers = Vec::new();
@@ -97,14 +113,14 @@ pub async fn grant_type_authorization_code(
 
     // get the oidc code from the cache
     let idx = req_data.code.as_ref().unwrap().to_owned();
-    let code = match AuthCode::find(idx).await? {
+    let mut code = match AuthCode::find(idx).await? {
         None => {
             warn!(
                 "'auth_code' could not be found inside the cache - Host: {}",
-                real_ip_from_req(&req)?,
+                real_ip(&req)?,
             );
             return Err(ErrorResponse::new(
-                ErrorResponseType::Unauthorized,
+                ErrorResponseType::InvalidGrant,
                 "'auth_code' could not be found inside the cache",
             ));
         }
@@ -114,29 +130,36 @@ pub async fn grant_type_authorization_code(
     if code.client_id != client_id {
         let err = format!("Wrong 'code' for client_id '{client_id}'");
         warn!(err);
-        return Err(ErrorResponse::new(ErrorResponseType::Unauthorized, err));
+        return Err(ErrorResponse::new(ErrorResponseType::InvalidGrant, err));
     }
     if code.exp < Utc::now().timestamp() {
+        metrics::AUTH_CODES_EXPIRED_ATTEMPTS.inc();
         warn!("The Authorization Code has expired");
         return Err(ErrorResponse::new(
-            ErrorResponseType::SessionExpired,
+            ErrorResponseType::InvalidGrant,
             "The Authorization Code has expired",
         ));
     }
     if code.challenge.is_some() {
         if req_data.code_verifier.is_none() {
             warn!("'code_verifier' is missing");
+            // RFC 7636 §4.6: a missing verifier is handled like a mismatch
             return Err(ErrorResponse::new(
-                ErrorResponseType::BadRequest,
+                ErrorResponseType::InvalidGrant,
                 "'code_verifier' is missing",
             ));
         }
 
         if code.challenge_method.as_ref().unwrap().eq("plain") {
-            if !code.challenge.eq(&req_data.code_verifier) {
+            let verifier = req_data.code_verifier.as_deref().unwrap_or_default();
+            if !code
+                .challenge
+                .as_deref()
+                .is_some_and(|challenge| secure_compare(challenge, verifier))
+            {
                 warn!("'code_verifier' does not match the challenge");
                 return Err(ErrorResponse::new(
-                    ErrorResponseType::Unauthorized,
+                    ErrorResponseType::InvalidGrant,
                     "'code_verifier' does not match the challenge",
                 ));
             }
@@ -144,10 +167,14 @@ pub async fn grant_type_authorization_code(
             let hash = digest::digest(&digest::SHA256, req_data.code_verifier.unwrap().as_bytes());
             let hash_base64 = base64_url_encode(hash.as_ref());
 
-            if code.challenge != Some(hash_base64) {
+            if !code
+                .challenge
+                .as_deref()
+                .is_some_and(|challenge| secure_compare(challenge, &hash_base64))
+            {
                 warn!("'code_verifier' does not match the challenge");
                 return Err(ErrorResponse::new(
-                    ErrorResponseType::Unauthorized,
+                    ErrorResponseType::InvalidGrant,
                     "'code_verifier' does not match the challenge",
                 ));
             }
@@ -168,12 +195,20 @@ pub async fn grant_type_authorization_code(
         (None, granted) => granted.map(String::from),
     };
 
-    let user = User::find(code.user_id.clone()).await?;
-    let token_set = TokenSet::from_user(
+    // the session id may have been rotated after the code has been issued
+    if let Some(sid) = code.session_id.take() {
+        code.session_id = Some(Session::resolve_id(sid).await?);
+    }
+
+    let user = User::find(code.user_id.clone())
+        .await
+        .map_err(rfc_err(ErrorResponseType::InvalidGrant))?;
+    let token_set = token_set::from_user(
         &user,
         &client,
         AuthTime::given(user.last_login.unwrap_or_else(|| Utc::now().timestamp())),
         dpop_fingerprint,
+        RefreshTokenFingerprint::from_req(&client, &req)?,
         code.nonce.clone().map(TokenNonce),
         Some(TokenScopes(code.scopes.join(" "))),
         code.session_id.clone().map(SessionId),
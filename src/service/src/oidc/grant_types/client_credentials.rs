@@ -1,11 +1,13 @@
-use crate::token_set::{DpopFingerprint, TokenSet};
+use crate::oidc::grant_types::rfc_err;
+use crate::token_set::{self, DpopFingerprint};
 use actix_web::HttpRequest;
 use actix_web::http::header::{HeaderName, HeaderValue};
-use rauthy_api_types::oidc::TokenRequest;
+use rauthy_api_types::oidc::{TokenRequest, TokenSet};
 use rauthy_common::constants::HEADER_DPOP_NONCE;
 use rauthy_data::entity::clients::Client;
 use rauthy_data::entity::clients_dyn::ClientDyn;
 use rauthy_data::entity::dpop_proof::DPoPProof;
+use rauthy_data::entity::maintenance_mode::MaintenanceMode;
 use rauthy_data::events::event::Event;
 use rauthy_data::rauthy_config::RauthyConfig;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
@@ -16,27 +18,35 @@ pub async fn grant_type_credentials(
     req: HttpRequest,
     req_data: TokenRequest,
 ) -> Result<(TokenSet, Vec<(HeaderName, HeaderValue)>), ErrorResponse> {
-    if req_data.client_secret.is_none() {
-        return Err(ErrorResponse::new(
-            ErrorResponseType::BadRequest,
+    let (client_id, client_secret) = req_data
+        .try_get_client_id_secret(&req)
+        .map_err(rfc_err(ErrorResponseType::InvalidClient))?;
+    let secret = client_secret.ok_or_else(|| {
+        ErrorResponse::new(
+            ErrorResponseType::InvalidClient,
             "'client_secret' is missing",
-        ));
-    }
-
-    let (client_id, client_secret) = req_data.try_get_client_id_secret(&req)?;
-    let client = Client::find(client_id).await?;
-    client.validate_enabled()?;
+        )
+    })?;
+    let client = Client::find(client_id)
+        .await
+        .map_err(rfc_err(ErrorResponseType::InvalidClient))?;
+    client
+        .validate_enabled()
+        .map_err(rfc_err(ErrorResponseType::UnauthorizedClient))?;
     if !client.confidential {
         return Err(ErrorResponse::new(
-            ErrorResponseType::BadRequest,
+            ErrorResponseType::UnauthorizedClient,
             "'client_credentials' flow is allowed for confidential clients only",
         ));
     }
-    let secret = client_secret.ok_or_else(|| {
-        ErrorResponse::new(ErrorResponseType::BadRequest, "'client_secret' is missing")
-    })?;
-    client.validate_secret(secret, &req).await?;
-    client.validate_flow("client_credentials")?;
+    client
+        .validate_secret(secret, &req)
+        .await
+        .map_err(rfc_err(ErrorResponseType::InvalidClient))?;
+    client
+        .validate_flow("client_credentials")
+        .map_err(rfc_err(ErrorResponseType::UnauthorizedClient))?;
+    MaintenanceMode::validate_token(None).await?;
     let header_origin = client.get_validated_origin_header(&req)?;
 
     let mut headers = Vec::new();
@@ -65,7 +75,7 @@ pub async fn grant_type_credentials(
     }
 
     let ts =
-        TokenSet::for_client_credentials(&client, dpop_fingerprint, req_data.resource.as_deref())
+        token_set::for_client_credentials(&client, dpop_fingerprint, req_data.resource.as_deref())
             .await?;
 
     if RauthyConfig::get().vars.events.generate_token_issued {
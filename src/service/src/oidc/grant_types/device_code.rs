@@ -1,8 +1,8 @@
-use crate::token_set::{AuthCodeFlow, AuthTime, DeviceCodeFlow, TokenNonce, TokenScopes, TokenSet};
+use crate::token_set::{self, AuthCodeFlow, AuthTime, DeviceCodeFlow, TokenNonce, TokenScopes};
 use actix_web::HttpResponse;
 use chrono::Utc;
 use rauthy_api_types::oidc::{OAuth2ErrorResponse, OAuth2ErrorTypeResponse, TokenRequest};
-use rauthy_common::utils::new_store_id;
+use rauthy_common::utils::{new_store_id, secure_compare};
 use rauthy_data::entity::clients::Client;
 use rauthy_data::entity::devices::{DeviceAuthCode, DeviceEntity};
 use rauthy_data::entity::users::User;
@@ -13,7 +13,7 @@ use std::net::IpAddr;
 use std::ops::{Add, Sub};
 use tracing::{debug, error, warn};
 
-/// Return a [TokenSet](crate::models::response::TokenSet) for the `device_code` flow
+/// Return a [TokenSet](rauthy_api_types::oidc::TokenSet) for the `device_code` flow
 #[tracing::instrument(skip_all, fields(client_id = payload.client_id))]
 pub async fn grant_type_device_code(peer_ip: IpAddr, payload: TokenRequest) -> HttpResponse {
     let device_code = match &payload.device_code {
@@ -45,14 +45,19 @@ pub async fn grant_type_device_code(peer_ip: IpAddr, payload: TokenRequest) -> H
     // We need to check the device_code again, because the `find_by_device_code` uses
     // the `user_code` as cache index under the hood for smaller footprints and the
     // ability to find it in both ways without duplicated data.
-    if &code.device_code != device_code {
+    if !secure_compare(&code.device_code, device_code) {
         return HttpResponse::BadRequest().json(OAuth2ErrorResponse {
             error: OAuth2ErrorTypeResponse::UnauthorizedClient,
             error_description: Some(Cow::from("Invalid `device_code`")),
         });
     }
 
-    if code.client_secret != payload.client_secret {
+    let secret_matches = match (&code.client_secret, &payload.client_secret) {
+        (Some(expected), Some(given)) => secure_compare(expected, given),
+        (None, None) => true,
+        _ => false,
+    };
+    if !secret_matches {
         return HttpResponse::BadRequest().json(OAuth2ErrorResponse {
             error: OAuth2ErrorTypeResponse::UnauthorizedClient,
             error_description: Some(Cow::from("Invalid `client_secret`")),
@@ -155,11 +160,12 @@ pub async fn grant_type_device_code(peer_ip: IpAddr, payload: TokenRequest) -> H
         }
         debug!("New Device with ID {id} has been created");
 
-        let ts = match TokenSet::from_user(
+        let ts = match token_set::from_user(
             &user,
             &client,
             AuthTime::now(),
             None,
+            None,
             code.nonce.map(TokenNonce),
             code.scopes.map(TokenScopes),
             None,
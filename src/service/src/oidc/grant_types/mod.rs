@@ -1,5 +1,49 @@
+use actix_web::ResponseError;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+
 pub mod authorization_code;
 pub mod client_credentials;
 pub mod device_code;
 pub mod password;
 pub mod refresh_token;
+
+/// Returns a closure for `map_err`, which changes client errors into the given RFC 6749 error
+/// type and keeps the message. Server errors, rate limits and DPoP errors have their own codes
+/// and are passed through unchanged.
+fn rfc_err(error: ErrorResponseType) -> impl FnOnce(ErrorResponse) -> ErrorResponse {
+    move |err| match err.error {
+        ErrorResponseType::DPoP(_)
+        | ErrorResponseType::UseDpopNonce(_)
+        | ErrorResponseType::ServiceUnavailable(_)
+        | ErrorResponseType::TooManyRequests(_) => err,
+        _ if err.status_code().is_client_error() => ErrorResponse { error, ..err },
+        _ => err,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rfc_err() {
+        let err = rfc_err(ErrorResponseType::InvalidClient)(ErrorResponse::new(
+            ErrorResponseType::NotFound,
+            "Client 'test' not found",
+        ));
+        assert_eq!(err.error, ErrorResponseType::InvalidClient);
+        assert_eq!(err.message, "Client 'test' not found");
+
+        let err = rfc_err(ErrorResponseType::InvalidGrant)(ErrorResponse::new(
+            ErrorResponseType::Database,
+            "connection lost",
+        ));
+        assert_eq!(err.error, ErrorResponseType::Database);
+
+        let err = rfc_err(ErrorResponseType::InvalidGrant)(ErrorResponse::new(
+            ErrorResponseType::DPoP(None),
+            "invalid proof",
+        ));
+        assert_eq!(err.error, ErrorResponseType::DPoP(None));
+    }
+}
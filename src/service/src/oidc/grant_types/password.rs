@@ -1,18 +1,22 @@
-use crate::token_set::{AuthCodeFlow, AuthTime, DeviceCodeFlow, DpopFingerprint, TokenSet};
+use crate::oidc::grant_types::rfc_err;
+use crate::token_set::{
+    self, AuthCodeFlow, AuthTime, DeviceCodeFlow, DpopFingerprint, RefreshTokenFingerprint,
+};
 use actix_web::HttpRequest;
 use actix_web::http::header::{
     ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_METHODS, HeaderName, HeaderValue,
 };
 use chrono::Utc;
-use rauthy_api_types::oidc::TokenRequest;
+use rauthy_api_types::oidc::{TokenRequest, TokenSet};
 use rauthy_common::constants::HEADER_DPOP_NONCE;
 use rauthy_common::password_hasher::HashPassword;
-use rauthy_common::utils::real_ip_from_req;
+use rauthy_common::utils::real_ip;
 use rauthy_data::entity::browser_id::BrowserId;
 use rauthy_data::entity::clients::Client;
 use rauthy_data::entity::clients_dyn::ClientDyn;
 use rauthy_data::entity::dpop_proof::DPoPProof;
 use rauthy_data::entity::login_locations::LoginLocation;
+use rauthy_data::entity::maintenance_mode::MaintenanceMode;
 use rauthy_data::entity::user_login_states::UserLoginState;
 use rauthy_data::entity::users::User;
 use rauthy_data::events::event::Event;
@@ -40,20 +44,31 @@ pub async fn grant_type_password(
         ));
     }
 
-    let (client_id, client_secret) = req_data.try_get_client_id_secret(&req)?;
+    let (client_id, client_secret) = req_data
+        .try_get_client_id_secret(&req)
+        .map_err(rfc_err(ErrorResponseType::InvalidClient))?;
     let email = req_data.username.as_ref().unwrap();
     let password = req_data.password.unwrap();
 
-    let client = Client::find(client_id).await?;
-    client.validate_enabled()?;
+    let client = Client::find(client_id)
+        .await
+        .map_err(rfc_err(ErrorResponseType::InvalidClient))?;
+    client
+        .validate_enabled()
+        .map_err(rfc_err(ErrorResponseType::UnauthorizedClient))?;
     let header_origin = client.get_validated_origin_header(&req)?;
     if client.confidential {
         let secret = client_secret.ok_or_else(|| {
-            ErrorResponse::new(ErrorResponseType::BadRequest, "Missing 'client_secret'")
+            ErrorResponse::new(ErrorResponseType::InvalidClient, "Missing 'client_secret'")
         })?;
-        client.validate_secret(secret, &req).await?;
+        client
+            .validate_secret(secret, &req)
+            .await
+            .map_err(rfc_err(ErrorResponseType::InvalidClient))?;
     }
-    client.validate_flow("password")?;
+    client
+        .validate_flow("password")
+        .map_err(rfc_err(ErrorResponseType::UnauthorizedClient))?;
 
     let mut headers = Vec::new();
     let dpop_fingerprint =
@@ -82,13 +97,21 @@ pub async fn grant_type_password(
 
     // This Error must be the same if user does not exist AND passwords do not match to prevent
     // username enumeration
-    let mut user = User::find_by_email(String::from(email)).await?;
-    user.check_enabled()?;
-    user.check_expired()?;
+    // -> all of them are an `invalid_grant`, because the resource owner credentials are invalid
+    let mut user = User::find_by_email(String::from(email))
+        .await
+        .map_err(rfc_err(ErrorResponseType::InvalidGrant))?;
+    user.check_enabled()
+        .map_err(rfc_err(ErrorResponseType::InvalidGrant))?;
+    user.check_expired()
+        .map_err(rfc_err(ErrorResponseType::InvalidGrant))?;
 
     match user.validate_password(password.clone()).await {
         Ok(_) => {
-            client.validate_user_groups(&user)?;
+            client
+                .validate_user_access(&user)
+                .map_err(rfc_err(ErrorResponseType::InvalidGrant))?;
+            MaintenanceMode::validate_token(Some(&user.id)).await?;
 
             user.last_login = Some(Utc::now().timestamp());
             user.last_failed_login = None;
@@ -108,11 +131,12 @@ pub async fn grant_type_password(
                 ClientDyn::update_used(&client.id).await?;
             }
 
-            let ts = TokenSet::from_user(
+            let ts = token_set::from_user(
                 &user,
                 &client,
                 AuthTime::now(),
                 dpop_fingerprint,
+                RefreshTokenFingerprint::from_req(&client, &req)?,
                 None,
                 None,
                 None,
@@ -138,7 +162,7 @@ pub async fn grant_type_password(
         Err(err) => {
             warn!(
                 "False Login attempt from Host: '{}' for user: '{}'",
-                real_ip_from_req(&req)?,
+                real_ip(&req)?,
                 user.email
             );
 
@@ -148,7 +172,7 @@ pub async fn grant_type_password(
             user.save(None).await?;
 
             // TODO add expo increasing sleeps after failed login attempts here?
-            Err(err)
+            Err(rfc_err(ErrorResponseType::InvalidGrant)(err))
         }
     }
 }
@@ -1,10 +1,10 @@
+use crate::oidc::grant_types::rfc_err;
 use crate::oidc::validation;
-use crate::token_set::TokenSet;
 use actix_web::HttpRequest;
 use actix_web::http::header::{
     ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_METHODS, HeaderName, HeaderValue,
 };
-use rauthy_api_types::oidc::TokenRequest;
+use rauthy_api_types::oidc::{TokenRequest, TokenSet};
 use rauthy_common::constants::HEADER_DPOP_NONCE;
 use rauthy_data::entity::clients::Client;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
@@ -21,26 +21,43 @@ pub async fn grant_type_refresh(
             "'refresh_token' is missing",
         ));
     }
-    let (client_id, client_secret) = req_data.try_get_client_id_secret(&req)?;
-    let client = Client::find_maybe_ephemeral(client_id).await?;
-    client.validate_enabled()?;
+    let (client_id, client_secret) = req_data
+        .try_get_client_id_secret(&req)
+        .map_err(rfc_err(ErrorResponseType::InvalidClient))?;
+    let client = Client::find_maybe_ephemeral(client_id)
+        .await
+        .map_err(rfc_err(ErrorResponseType::InvalidClient))?;
+    client
+        .validate_enabled()
+        .map_err(rfc_err(ErrorResponseType::UnauthorizedClient))?;
 
     let header_origin = client.get_validated_origin_header(&req)?;
 
     if client.confidential {
         let secret = client_secret.ok_or_else(|| {
-            ErrorResponse::new(ErrorResponseType::BadRequest, "'client_secret' is missing")
+            ErrorResponse::new(
+                ErrorResponseType::InvalidClient,
+                "'client_secret' is missing",
+            )
         })?;
-        client.validate_secret(secret, &req).await?;
+        client
+            .validate_secret(secret, &req)
+            .await
+            .map_err(rfc_err(ErrorResponseType::InvalidClient))?;
     }
 
-    client.validate_flow("refresh_token")?;
+    client
+        .validate_flow("refresh_token")
+        .map_err(rfc_err(ErrorResponseType::UnauthorizedClient))?;
 
     let refresh_token = req_data.refresh_token.unwrap();
 
     // validate common refresh token claims first and get the payload
+    // -> an expired, revoked or otherwise invalid refresh token is an `invalid_grant`
     let (ts, dpop_none) =
-        validation::validate_and_refresh_token(Some(client), &refresh_token, &req).await?;
+        validation::validate_and_refresh_token(Some(client), &refresh_token, &req)
+            .await
+            .map_err(rfc_err(ErrorResponseType::InvalidGrant))?;
 
     let mut headers = Vec::new();
     if let Some(h) = header_origin {
@@ -3,10 +3,14 @@ use actix_web::cookie::SameSite;
 use actix_web::http::header::{ACCESS_CONTROL_ALLOW_METHODS, HeaderValue};
 use actix_web::http::{StatusCode, header};
 use actix_web::{HttpRequest, HttpResponse};
-use rauthy_api_types::oidc::{BackchannelLogoutRequest, LogoutRequest};
-use rauthy_common::constants::{COOKIE_SESSION, COOKIE_SESSION_FED_CM};
+use rauthy_api_types::claims::{JwtIdClaims, JwtTokenType};
+use rauthy_api_types::oidc::{
+    BackchannelLogoutRequest, LogoutRequest, UpstreamLogoutCompleteRequest,
+};
+use rauthy_common::constants::{COOKIE_SESSION, COOKIE_SESSION_FED_CM, COOKIE_UPSTREAM_LOGOUT};
 use rauthy_common::http_client;
 use rauthy_data::api_cookie::ApiCookie;
+use rauthy_data::entity::auth_provider_logout::{AuthProviderSession, UpstreamLogout};
 use rauthy_data::entity::clients::Client;
 use rauthy_data::entity::failed_backchannel_logout::FailedBackchannelLogout;
 use rauthy_data::entity::issued_tokens::IssuedToken;
@@ -17,9 +21,9 @@ use rauthy_data::entity::theme::ThemeCssFull;
 use rauthy_data::entity::user_login_states::UserLoginState;
 use rauthy_data::entity::users::User;
 use rauthy_data::html::HtmlCached;
+use rauthy_data::html::flow_error::prefers_html;
 use rauthy_data::rauthy_config::RauthyConfig;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
-use rauthy_jwt::claims::{JwtIdClaims, JwtTokenType};
 use rauthy_jwt::token::JwtToken;
 use std::borrow::Cow;
 use std::str::FromStr;
@@ -39,8 +43,9 @@ pub async fn get_logout_html(
     session: Session,
 ) -> Result<HttpResponse, ErrorResponse> {
     let theme_ts = ThemeCssFull::find_theme_ts_rauthy().await?;
+    let upstream_logout = AuthProviderSession::find_template(&session.id).await?;
     if logout_request.id_token_hint.is_none() {
-        return HtmlCached::Logout(session.csrf_token)
+        return HtmlCached::Logout(session.csrf_token, upstream_logout)
             .handle(req, theme_ts, false)
             .await;
     }
@@ -87,7 +92,7 @@ pub async fn get_logout_html(
         }
     }
 
-    HtmlCached::Logout(session.csrf_token)
+    HtmlCached::Logout(session.csrf_token, upstream_logout)
         .handle(req, theme_ts, false)
         .await
 }
@@ -148,6 +153,11 @@ pub async fn post_logout_handle(
     let token_revoke = RauthyConfig::get().vars.access.token_revoke_on_logout;
 
     let sid = session.as_ref().map(|s| s.id.clone());
+    // must be taken before the session is gone
+    let upstream = match &sid {
+        Some(sid) => AuthProviderSession::take(sid).await?,
+        None => None,
+    };
     if let Some(session) = session {
         let uid = session.user_id.clone();
         if token_revoke {
@@ -187,10 +197,32 @@ pub async fn post_logout_handle(
             .unwrap_or_default();
         let loc = format!("{uri}{state}");
 
+        let upstream_logout = match upstream {
+            Some(upstream) => {
+                UpstreamLogout::build(
+                    upstream,
+                    loc.clone(),
+                    params.upstream_logout.unwrap_or(false),
+                )
+                .await?
+            }
+            None => None,
+        };
+        let (location, continue_upstream) = logout_location(
+            &loc,
+            upstream_logout.as_ref().map(|(ul, _)| ul),
+            prefers_html(&req),
+        );
+
         let mut resp = HttpResponse::build(StatusCode::from_u16(302).unwrap())
-            .append_header((header::LOCATION, loc))
+            .append_header((header::LOCATION, location))
             .finish();
 
+        if continue_upstream && let Some((ul, ttl)) = &upstream_logout {
+            let cookie = ApiCookie::build(COOKIE_UPSTREAM_LOGOUT, &ul.id, *ttl);
+            resp.add_cookie(&cookie)?;
+        }
+
         if let Some(sid) = sid {
             let cookie_session = ApiCookie::build(COOKIE_SESSION, &sid, 0);
             resp.add_cookie(&cookie_session)?;
@@ -218,6 +250,70 @@ pub async fn post_logout_handle(
     }
 }
 
+/// Returns the `Location` after a logout and if the upstream logout must be continued via
+/// `/oidc/logout/upstream` afterward.
+///
+/// A top-level navigation can follow the redirect to the upstream provider directly. The Rauthy
+/// UI logs out via `fetch()`, which can't follow it.
+#[inline]
+fn logout_location<'a>(
+    target: &'a str,
+    upstream_logout: Option<&'a UpstreamLogout>,
+    is_navigation: bool,
+) -> (&'a str, bool) {
+    match upstream_logout {
+        Some(ul) if is_navigation => (ul.end_session_uri.as_str(), false),
+        Some(_) => (target, true),
+        None => (target, false),
+    }
+}
+
+/// Continues a logout prepared by [post_logout_handle] for a request, which could not follow
+/// the redirect to the upstream `end_session_endpoint` directly, like the Rauthy UI.
+pub async fn upstream_logout_start(req: HttpRequest) -> HttpResponse {
+    let location = match ApiCookie::from_req(&req, COOKIE_UPSTREAM_LOGOUT) {
+        Some(id) => UpstreamLogout::find(&id)
+            .await
+            .map(|ul| ul.end_session_uri)
+            .ok(),
+        None => None,
+    };
+
+    HttpResponse::build(StatusCode::from_u16(302).unwrap())
+        .insert_header((
+            header::LOCATION,
+            location.unwrap_or_else(|| "/auth/v1/".to_string()),
+        ))
+        .finish()
+}
+
+/// The `post_logout_redirect_uri` for upstream providers. Redirects to the original target of
+/// the logout.
+pub async fn upstream_logout_complete(
+    params: UpstreamLogoutCompleteRequest,
+) -> Result<HttpResponse, ErrorResponse> {
+    let mut location = None;
+    if let Some(state) = params.state {
+        match UpstreamLogout::find(&state).await {
+            Ok(ul) => {
+                ul.delete().await?;
+                location = Some(ul.target);
+            }
+            Err(err) => debug!("Upstream logout for `state` not found: {err}"),
+        }
+    }
+
+    let mut resp = HttpResponse::build(StatusCode::from_u16(302).unwrap())
+        .insert_header((
+            header::LOCATION,
+            location.unwrap_or_else(|| "/auth/v1/".to_string()),
+        ))
+        .finish();
+    resp.add_cookie(&ApiCookie::build(COOKIE_UPSTREAM_LOGOUT, "", 0))?;
+
+    Ok(resp)
+}
+
 async fn find_session_with_user_fallback(
     sid: Option<String>,
     uid: Option<String>,
@@ -473,3 +569,33 @@ pub async fn send_backchannel_logout(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_logout_location() {
+        let target = "https://app.example.com/logout?state=abc";
+        let ul = UpstreamLogout {
+            id: "state123".to_string(),
+            end_session_uri: "https://idp.example.com/logout?id_token_hint=ey&client_id=rauthy\
+                &post_logout_redirect_uri=https%3A%2F%2Fauth.example.com%2Fauth%2Fv1%2Foidc%2Flogout%2Fcomplete\
+                &state=state123"
+                .to_string(),
+            target: target.to_string(),
+        };
+
+        // without an upstream logout, nothing changes for any request
+        assert_eq!(logout_location(target, None, true), (target, false));
+        assert_eq!(logout_location(target, None, false), (target, false));
+
+        // navigations are chained: Rauthy -> upstream -> `/oidc/logout/complete` -> target
+        let (loc, continue_upstream) = logout_location(target, Some(&ul), true);
+        assert_eq!(loc, ul.end_session_uri);
+        assert!(!continue_upstream);
+
+        // `fetch()` requests get the target and need to continue via `/oidc/logout/upstream`
+        assert_eq!(logout_location(target, Some(&ul), false), (target, true));
+    }
+}
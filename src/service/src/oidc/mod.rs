@@ -2,10 +2,9 @@ use crate::oidc::grant_types::authorization_code::grant_type_authorization_code;
 use crate::oidc::grant_types::client_credentials::grant_type_credentials;
 use crate::oidc::grant_types::password::grant_type_password;
 use crate::oidc::grant_types::refresh_token::grant_type_refresh;
-use crate::token_set::TokenSet;
 use actix_web::HttpRequest;
 use actix_web::http::header::{HeaderName, HeaderValue};
-use rauthy_api_types::oidc::TokenRequest;
+use rauthy_api_types::oidc::{TokenRequest, TokenSet};
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 
 pub use grant_types::device_code::grant_type_device_code;
@@ -22,7 +21,7 @@ pub mod token_revocation;
 pub mod userinfo;
 pub mod validation;
 
-/// Main entrance function for returning a whole new [TokenSet](crate::models::response::TokenSet)
+/// Main entrance function for returning a whole new [TokenSet](rauthy_api_types::oidc::TokenSet)
 pub async fn get_token_set(
     req_data: TokenRequest,
     browser_id: BrowserId,
@@ -34,7 +33,7 @@ pub async fn get_token_set(
         "password" => grant_type_password(req, browser_id, req_data).await,
         "refresh_token" => grant_type_refresh(req, req_data).await,
         _ => Err(ErrorResponse::new(
-            ErrorResponseType::BadRequest,
+            ErrorResponseType::UnsupportedGrantType,
             "Invalid 'grant_type'",
         )),
     }
@@ -1,12 +1,12 @@
 use actix_web::HttpRequest;
 use actix_web::http::header::{AUTHORIZATION, HeaderName, HeaderValue};
+use rauthy_api_types::claims::{JwtAccessClaims, JwtCommonClaims, JwtTokenType};
 use rauthy_api_types::oidc::TokenInfo;
 use rauthy_common::utils::base64_decode_buf;
 use rauthy_data::entity::clients::Client;
 use rauthy_data::entity::issued_tokens::IssuedToken;
 use rauthy_data::rauthy_config::RauthyConfig;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
-use rauthy_jwt::claims::{JwtAccessClaims, JwtCommonClaims, JwtTokenType};
 use rauthy_jwt::token::JwtToken;
 use tracing::error;
 use zeroize::Zeroize;
@@ -16,9 +16,14 @@ pub async fn get_token_info(
     token: &str,
 ) -> Result<(String, Option<(HeaderName, HeaderValue)>), ErrorResponse> {
     let mut buf = Vec::with_capacity(512);
-    if JwtToken::validate_claims_into(token, Some(JwtTokenType::Bearer), 0, &mut buf)
-        .await
-        .is_err()
+    if JwtToken::validate_claims_into(
+        token,
+        Some(JwtTokenType::Bearer),
+        RauthyConfig::get().vars.access.jwt_leeway,
+        &mut buf,
+    )
+    .await
+    .is_err()
     {
         return Ok((
             serde_json::to_string(&TokenInfo {
@@ -93,7 +98,13 @@ async fn check_client_auth(
     let header = header_value.to_str().unwrap_or_default();
 
     if let Some(token) = header.strip_prefix("Bearer ") {
-        JwtToken::validate_claims_into(token, Some(JwtTokenType::Bearer), 0, buf).await?;
+        JwtToken::validate_claims_into(
+            token,
+            Some(JwtTokenType::Bearer),
+            RauthyConfig::get().vars.access.jwt_leeway,
+            buf,
+        )
+        .await?;
         let claims = serde_json::from_slice::<JwtAccessClaims>(buf)?;
 
         // If a different client was used for authentication, make sure it exists and is enabled.
@@ -1,13 +1,14 @@
 use actix_web::HttpRequest;
 use actix_web::http::header;
+use rauthy_api_types::claims::{JwtCommonClaims, JwtTokenType};
 use rauthy_api_types::oidc::TokenRevocationRequest;
 use rauthy_common::utils::base64_decode;
 use rauthy_data::entity::clients::Client;
 use rauthy_data::entity::issued_tokens::IssuedToken;
 use rauthy_data::entity::refresh_tokens::RefreshToken;
 use rauthy_data::entity::refresh_tokens_devices::RefreshTokenDevice;
+use rauthy_data::rauthy_config::RauthyConfig;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
-use rauthy_jwt::claims::{JwtCommonClaims, JwtTokenType};
 use rauthy_jwt::token::JwtToken;
 
 #[inline(always)]
@@ -54,13 +55,18 @@ pub async fn handle_token_revocation(
 
     let mut buf: Vec<u8> = Vec::with_capacity(256);
     if is_refresh_token {
-        JwtToken::validate_claims_into(&payload.token, Some(JwtTokenType::Refresh), 10, &mut buf)
-            .await?;
+        JwtToken::validate_claims_into(
+            &payload.token,
+            Some(JwtTokenType::Refresh),
+            RauthyConfig::get().vars.access.jwt_leeway,
+            &mut buf,
+        )
+        .await?;
     } else {
         JwtToken::validate_claims_into(
             &payload.token,
             Some(JwtTokenType::Bearer),
-            10,
+            RauthyConfig::get().vars.access.jwt_leeway,
             buf.as_mut(),
         )
         .await?;
@@ -1,6 +1,10 @@
 use crate::oidc::helpers;
-use actix_web::HttpRequest;
+use crate::token_set::map_claims;
 use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::{HttpRequest, ResponseError};
+use rauthy_api_types::claims::{JwtCommonClaims, JwtTokenType};
+use rauthy_api_types::clients::{ClaimTarget, ClientClaimsEmission};
+use rauthy_api_types::oidc::ClaimTrace;
 use rauthy_api_types::users::Userinfo;
 use rauthy_data::entity::clients::Client;
 use rauthy_data::entity::devices::DeviceEntity;
@@ -10,84 +14,89 @@ use rauthy_data::entity::users_values::UserValues;
 use rauthy_data::entity::webids::WebId;
 use rauthy_data::rauthy_config::RauthyConfig;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
-use rauthy_jwt::claims::{AddressClaim, JwtCommonClaims, JwtTokenType};
+use rauthy_jwt::claims::build_address_claim;
 use std::borrow::Cow;
 
+/// With `apply_claims_emission`, `roles` and `groups` are shaped by the `claims_emission` of
+/// the client the token was issued for. Otherwise, they are always returned in their default
+/// format, which is what e.g. forward auth needs.
+///
+/// The `form_token` is the `access_token` from a form body, which is only allowed for `POST`
+/// requests. All token related errors respond with an RFC 6750 `Bearer` challenge.
 pub async fn get_userinfo(
     req: HttpRequest,
+    form_token: Option<String>,
+    apply_claims_emission: bool,
 ) -> Result<(Userinfo, Option<(HeaderName, HeaderValue)>), ErrorResponse> {
-    let bearer = helpers::get_bearer_token_from_header(req.headers())?;
+    let bearer = helpers::get_bearer_token(&req, form_token)?;
 
     let mut buf: Vec<u8> = Vec::with_capacity(256);
     rauthy_jwt::token::JwtToken::validate_claims_into(
         &bearer,
         Some(JwtTokenType::Bearer),
-        0,
+        RauthyConfig::get().vars.access.jwt_leeway,
         buf.as_mut(),
     )
-    .await?;
+    .await
+    .map_err(|err| {
+        if err.status_code().is_client_error() {
+            ErrorResponse::new(ErrorResponseType::InvalidToken, err.message)
+        } else {
+            err
+        }
+    })?;
     let claims = serde_json::from_slice::<JwtCommonClaims>(&buf)?;
 
     let Some(sub) = claims.sub else {
-        return Err(ErrorResponse::new(
-            ErrorResponseType::BadRequest,
-            "Not a user token",
-        ));
+        return Err(invalid_token("Not a user token"));
     };
 
     if let Some(jti) = claims.jti
         && IssuedToken::validate_not_revoked(jti).await.is_err()
     {
+        return Err(invalid_token("The token has been revoked"));
+    }
+
+    let scope = claims.scope.unwrap_or_else(|| Cow::from("openid"));
+    if !scope.split(' ').any(|s| s == "openid") {
         return Err(ErrorResponse::new(
-            ErrorResponseType::WWWAuthenticate("token-revoked".to_string()),
-            "The token has been revoked",
+            ErrorResponseType::InsufficientScope,
+            "The 'openid' scope is required",
         ));
     }
 
-    let scope = claims.scope.unwrap_or_else(|| Cow::from("openid"));
-    let user = User::find(sub.to_string()).await.map_err(|_| {
-        ErrorResponse::new(
-            ErrorResponseType::WWWAuthenticate("user-not-found".to_string()),
-            "The user has not been found",
-        )
-    })?;
+    let user = User::find(sub.to_string())
+        .await
+        .map_err(|_| invalid_token("The user has not been found"))?;
 
     // reject the request if user has been disabled, even when the token is still valid
     if !user.enabled || user.check_expired().is_err() {
-        return Err(ErrorResponse::new(
-            ErrorResponseType::WWWAuthenticate("user-disabled".to_string()),
-            "The user has been disabled",
-        ));
+        return Err(invalid_token("The user has been disabled"));
     }
 
+    let is_ephemeral = claims.azp.starts_with("http://") || claims.azp.starts_with("https://");
+    let mut client = None;
     let cors_header = if RauthyConfig::get().vars.access.userinfo_strict {
         // if the token has been issued to a device, make sure it still exists and is valid
         if let Some(device_id) = claims.did {
             // make sure it still exists
-            DeviceEntity::find(device_id).await.map_err(|_| {
-                ErrorResponse::new(
-                    ErrorResponseType::WWWAuthenticate("user-device-not-found".to_string()),
-                    "The user device has not been found",
-                )
-            })?;
+            DeviceEntity::find(device_id)
+                .await
+                .map_err(|_| invalid_token("The user device has not been found"))?;
         }
 
         // make sure the original client still exists and is enabled
         // skip this check if the client is ephemeral
-        if !(claims.azp.starts_with("http://") || claims.azp.starts_with("https://")) {
-            let client = Client::find(claims.azp.to_string()).await.map_err(|_| {
-                ErrorResponse::new(
-                    ErrorResponseType::WWWAuthenticate("client-not-found".to_string()),
-                    "The client has not been found",
-                )
-            })?;
-            if !client.enabled {
-                return Err(ErrorResponse::new(
-                    ErrorResponseType::WWWAuthenticate("client-disabled".to_string()),
-                    "The client has been disabled",
-                ));
+        if !is_ephemeral {
+            let c = Client::find(claims.azp.to_string())
+                .await
+                .map_err(|_| invalid_token("The client has not been found"))?;
+            if !c.enabled {
+                return Err(invalid_token("The client has been disabled"));
             }
-            client.get_validated_origin_header(&req)?
+            let header = c.get_validated_origin_header(&req)?;
+            client = Some(c);
+            header
         } else {
             None
         }
@@ -95,8 +104,48 @@ pub async fn get_userinfo(
         None
     };
 
-    let roles = user.get_roles();
-    let groups = scope.contains("groups").then(|| user.get_groups());
+    let emission = if apply_claims_emission && !is_ephemeral {
+        let client = match client {
+            Some(client) => Some(client),
+            None => Client::find(claims.azp.to_string()).await.ok(),
+        };
+        match client {
+            Some(client) => client.get_claims_emission()?,
+            None => ClientClaimsEmission::default(),
+        }
+    } else {
+        ClientClaimsEmission::default()
+    };
+    let userinfo = build_userinfo(user, &scope, &emission, None).await?;
+
+    Ok((userinfo, cors_header))
+}
+
+#[inline]
+fn invalid_token(msg: &'static str) -> ErrorResponse {
+    ErrorResponse::new(ErrorResponseType::InvalidToken, msg)
+}
+
+/// Builds the `Userinfo` for the given `scope` after all validation has been successful.
+pub async fn build_userinfo(
+    user: User,
+    scope: &str,
+    emission: &ClientClaimsEmission,
+    trace: Option<&mut Vec<ClaimTrace>>,
+) -> Result<Userinfo, ErrorResponse> {
+    let user_roles = user.get_roles();
+    let user_groups = user.get_groups();
+    let mapped = map_claims(
+        ClaimTarget::Userinfo,
+        emission,
+        user_roles.iter().map(String::as_str),
+        user_groups.iter().map(String::as_str),
+        scope,
+        None,
+        None,
+        trace,
+    )?;
+
     let webid = (RauthyConfig::get().vars.ephemeral_clients.enable_web_id
         && scope.contains("webid"))
     .then(|| WebId::resolve_webid_uri(&user.id));
@@ -105,7 +154,7 @@ pub async fn get_userinfo(
         id: user.id.clone(),
         sub: user.id.clone(),
         name: user.email_recipient_name(),
-        roles,
+        roles: mapped.roles,
         mfa_enabled: user.has_webauthn_enabled(),
 
         // scope: address
@@ -116,7 +165,7 @@ pub async fn get_userinfo(
         email_verified: None,
 
         // scope: groups
-        groups,
+        groups: mapped.groups,
 
         // scope: profile
         preferred_username: None,
@@ -133,6 +182,8 @@ pub async fn get_userinfo(
 
         // scope: webid
         webid,
+
+        custom_flattened: mapped.custom_flattened,
     };
 
     let has_email = scope.contains("email");
@@ -148,12 +199,13 @@ pub async fn get_userinfo(
         && let Some(values) = UserValues::find(&user.id).await?
     {
         if has_addr {
-            userinfo.address = AddressClaim::try_build(&user, &values).map(|claim| claim.into());
+            userinfo.address = build_address_claim(&user, &values).map(|claim| claim.into());
         }
 
         if has_profile {
             userinfo.birthdate = values.birthdate;
             userinfo.zoneinfo = values.tz;
+            userinfo.locale = values.locale;
 
             if let Some(username) = values.preferred_username {
                 userinfo.preferred_username = Some(username);
@@ -182,8 +234,11 @@ pub async fn get_userinfo(
     if has_profile {
         userinfo.given_name = Some(user.given_name);
         userinfo.family_name = user.family_name;
-        userinfo.locale = Some(user.language.to_string());
+        // the raw upstream locale is more accurate than the mapped language, if it exists
+        if userinfo.locale.is_none() {
+            userinfo.locale = Some(user.language.to_string());
+        }
     }
 
-    Ok((userinfo, cors_header))
+    Ok(userinfo)
 }
@@ -1,20 +1,129 @@
 use crate::token_set::{
-    AuthCodeFlow, AuthTime, DeviceCodeFlow, DpopFingerprint, TokenScopes, TokenSet,
+    self, AuthCodeFlow, AuthTime, DeviceCodeFlow, DpopFingerprint, RefreshTokenFingerprint,
+    TokenScopes,
 };
 use actix_web::HttpRequest;
 use actix_web::http::header::{HeaderName, HeaderValue};
 use chrono::Utc;
+use rauthy_api_types::claims::{JwtRefreshClaims, JwtTokenType};
+use rauthy_api_types::oidc::TokenSet;
+use rauthy_common::utils::{real_ip, truncate_for_log};
 use rauthy_data::entity::clients::Client;
 use rauthy_data::entity::dpop_proof::DPoPProof;
 use rauthy_data::entity::refresh_tokens::RefreshToken;
 use rauthy_data::entity::refresh_tokens_devices::RefreshTokenDevice;
+use rauthy_data::entity::user_grants::UserGrant;
 use rauthy_data::entity::users::User;
 use rauthy_data::events::event::Event;
 use rauthy_data::rauthy_config::RauthyConfig;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
-use rauthy_jwt::claims::{JwtRefreshClaims, JwtTokenType};
 use rauthy_jwt::token::JwtToken;
-use tracing::debug;
+use tracing::{debug, error, warn};
+
+/// Validates the size of a requested, space separated `scope` against the configured
+/// `access.max_scope_length` and `access.max_scope_count`.
+pub fn validate_scope_limits(
+    req: &HttpRequest,
+    client_id: &str,
+    scope: &str,
+) -> Result<(), ErrorResponse> {
+    let access = &RauthyConfig::get().vars.access;
+    check_scope_limits(scope, access.max_scope_length, access.max_scope_count).inspect_err(|err| {
+        let ip = real_ip(req).map(|ip| ip.to_string()).unwrap_or_default();
+        warn!(ip, client_id, "Scope limits exceeded: {}", err.message);
+    })
+}
+
+#[inline]
+fn check_scope_limits(scope: &str, max_length: u16, max_count: u16) -> Result<(), ErrorResponse> {
+    if scope.len() > max_length as usize {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            format!("invalid_scope: `scope` must not be longer than {max_length} characters"),
+        ));
+    }
+    if scope.split_whitespace().count() > max_count as usize {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            format!("invalid_scope: not more than {max_count} scopes are allowed"),
+        ));
+    }
+    Ok(())
+}
+
+/// Validates the length and content of untrusted authorization request parameters, which end up
+/// in `Location` headers and log lines later on.
+pub fn validate_auth_param_limits(
+    req: &HttpRequest,
+    client_id: &str,
+    redirect_uri: &str,
+    state: Option<&str>,
+    nonce: Option<&str>,
+) -> Result<(), ErrorResponse> {
+    let access = &RauthyConfig::get().vars.access;
+    check_auth_param_limits("redirect_uri", redirect_uri, access.max_redirect_uri_length)
+        .and_then(|_| check_auth_param_limits_opt("state", state, access.max_state_length))
+        .and_then(|_| check_auth_param_limits_opt("nonce", nonce, access.max_nonce_length))
+        .inspect_err(|err| {
+            let ip = real_ip(req).map(|ip| ip.to_string()).unwrap_or_default();
+            warn!(
+                ip,
+                client_id = %truncate_for_log(client_id, 128),
+                "Authorization request parameter limits exceeded: {}",
+                err.message
+            );
+        })
+}
+
+#[inline]
+fn check_auth_param_limits_opt(
+    name: &str,
+    value: Option<&str>,
+    max_length: u16,
+) -> Result<(), ErrorResponse> {
+    match value {
+        Some(value) => check_auth_param_limits(name, value, max_length),
+        None => Ok(()),
+    }
+}
+
+#[inline]
+fn check_auth_param_limits(name: &str, value: &str, max_length: u16) -> Result<(), ErrorResponse> {
+    if value.len() > max_length as usize {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            format!("invalid_request: `{name}` must not be longer than {max_length} bytes"),
+        ));
+    }
+    if value.chars().any(char::is_control) {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            format!("invalid_request: `{name}` must not contain control characters"),
+        ));
+    }
+    Ok(())
+}
+
+/// Validates the requested `response_type` against the ones allowed for the client. Returns
+/// `true` for `response_type=none`, which authenticates the user without issuing a `code`.
+///
+/// None of the supported response types can be combined with another one.
+pub fn validate_response_type(client: &Client, response_type: &str) -> Result<bool, ErrorResponse> {
+    let mut types = response_type.split_whitespace();
+    let typ = match (types.next(), types.next()) {
+        (Some(typ), None) if client.response_types_iter().any(|allowed| allowed == typ) => typ,
+        _ => {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!(
+                    "unsupported_response_type: `{}` is not allowed for this client",
+                    truncate_for_log(response_type, 32)
+                ),
+            ));
+        }
+    };
+    Ok(typ == "none")
+}
 
 /// Validates request parameters for the authorization and refresh endpoints
 pub async fn validate_auth_req_param(
@@ -67,7 +176,13 @@ pub async fn validate_and_refresh_token(
     req: &HttpRequest,
 ) -> Result<(TokenSet, Option<String>), ErrorResponse> {
     let mut buf = Vec::with_capacity(256);
-    JwtToken::validate_claims_into(refresh_token, Some(JwtTokenType::Refresh), 0, &mut buf).await?;
+    JwtToken::validate_claims_into(
+        refresh_token,
+        Some(JwtTokenType::Refresh),
+        RauthyConfig::get().vars.access.jwt_leeway,
+        &mut buf,
+    )
+    .await?;
     let claims: JwtRefreshClaims = serde_json::from_slice(&buf)?;
 
     let client = if let Some(c) = client_opt {
@@ -103,7 +218,14 @@ pub async fn validate_and_refresh_token(
     let mut user = User::find(claims.uid.to_string()).await?;
     user.check_enabled()?;
     user.check_expired()?;
-    client.validate_user_groups(&user)?;
+    client.validate_user_access(&user)?;
+
+    // device tokens are never bound, because the device flow is not browser-based
+    let rt_fingerprint = if claims.common.did.is_none() {
+        RefreshTokenFingerprint::from_req(&client, req)?
+    } else {
+        None
+    };
 
     // validate that it exists in the db and invalidate it afterward
     let (_, validation_str) = refresh_token.split_at(refresh_token.len() - 49);
@@ -132,6 +254,28 @@ pub async fn validate_and_refresh_token(
         rt.scope
     } else {
         let mut rt = RefreshToken::find(validation_str).await?;
+
+        if let Some(fingerprint) = &rt_fingerprint
+            && rt.is_fingerprint_mismatch(&fingerprint.0)
+        {
+            let ip = real_ip(req)?;
+            warn!(
+                %ip,
+                client_id = %client.id,
+                user_id = %user.id,
+                "Refresh token used from another context - revoking the token family"
+            );
+            rt.delete_family().await?;
+            Event::refresh_token_binding_mismatch(&client.id, &user.id, ip)
+                .send()
+                .await?;
+
+            return Err(ErrorResponse::new(
+                ErrorResponseType::InvalidGrant,
+                "The refresh token is bound to another context",
+            ));
+        }
+
         if rt.exp > exp_at_secs + 1 {
             rt.exp = exp_at_secs;
             rt.save().await?;
@@ -156,11 +300,12 @@ pub async fn validate_and_refresh_token(
         AuthTime::now()
     };
 
-    let ts = TokenSet::from_user(
+    let ts = token_set::from_user(
         &user,
         &client,
         auth_time,
         dpop_fingerprint,
+        rt_fingerprint,
         None,
         rt_scope.map(TokenScopes),
         None,
@@ -172,6 +317,13 @@ pub async fn validate_and_refresh_token(
     )
     .await?;
 
+    // runs after the rotation to include the new token
+    if claims.common.did.is_none()
+        && let Err(err) = UserGrant::touch_last_used(&user.id, &client.id).await
+    {
+        error!(?err, "Error updating last_used for the user grant");
+    }
+
     if RauthyConfig::get().vars.events.generate_token_issued {
         Event::token_issued("refresh", &client.id, Some(&user.email))
             .send()
@@ -180,3 +332,87 @@ pub async fn validate_and_refresh_token(
 
     Ok((ts, dpop_nonce))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("openid", true)]
+    #[case(&"a".repeat(20), true)]
+    #[case(&"a".repeat(21), false)]
+    #[case(&"a ".repeat(10), true)]
+    #[case(&format!("{}a", "a ".repeat(10)), false)]
+    fn test_scope_length(#[case] scope: &str, #[case] ok: bool) {
+        assert_eq!(check_scope_limits(scope, 20, 20).is_ok(), ok);
+    }
+
+    #[rstest]
+    #[case("", true)]
+    #[case("openid email profile", true)]
+    #[case("openid email profile groups", false)]
+    #[case("  openid  email   profile ", true)]
+    fn test_scope_count(#[case] scope: &str, #[case] ok: bool) {
+        assert_eq!(check_scope_limits(scope, 1024, 3).is_ok(), ok);
+    }
+
+    #[rstest]
+    #[case("abc-123_~.", true)]
+    #[case("", true)]
+    #[case("äöü 😀", true)]
+    #[case("a\r\nLocation: https://evil.example.com", false)]
+    #[case("a\nb", false)]
+    #[case("a\0b", false)]
+    #[case("a\u{7f}b", false)]
+    #[case("\u{85}", false)]
+    fn test_auth_param_content(#[case] value: &str, #[case] ok: bool) {
+        assert_eq!(check_auth_param_limits("state", value, 2048).is_ok(), ok);
+    }
+
+    #[test]
+    fn test_auth_param_length() {
+        assert!(check_auth_param_limits("state", &"a".repeat(2048), 2048).is_ok());
+        assert!(check_auth_param_limits("state", &"a".repeat(2049), 2048).is_err());
+        // the limit is applied on bytes and not on chars
+        assert!(check_auth_param_limits("nonce", &"ä".repeat(257), 512).is_err());
+        assert!(check_auth_param_limits("nonce", &"😀".repeat(100_000), 512).is_err());
+
+        assert!(check_auth_param_limits_opt("nonce", None, 0).is_ok());
+        assert!(check_auth_param_limits_opt("nonce", Some("a"), 0).is_err());
+
+        let err = check_auth_param_limits("state", "\r\n", 2048).unwrap_err();
+        assert_eq!(err.error, ErrorResponseType::BadRequest);
+        assert!(err.message.starts_with("invalid_request"));
+    }
+
+    #[rstest]
+    #[case("code,none", "code", Some(false))]
+    #[case("code,none", "none", Some(true))]
+    #[case("code,none", " none ", Some(true))]
+    #[case("code,none", "code none", None)]
+    #[case("code,none", "none code", None)]
+    #[case("code,none", "none none", None)]
+    #[case("code,none", "token", None)]
+    #[case("code,none", "code id_token", None)]
+    #[case("code,none", "", None)]
+    #[case("code", "code", Some(false))]
+    #[case("code", "none", None)]
+    #[case("none", "none", Some(true))]
+    #[case("none", "code", None)]
+    fn test_validate_response_type(
+        #[case] allowed: &str,
+        #[case] response_type: &str,
+        #[case] expected: Option<bool>,
+    ) {
+        let client = Client {
+            response_types: allowed.to_string(),
+            ..Default::default()
+        };
+        let res = validate_response_type(&client, response_type);
+        assert_eq!(res.as_ref().ok(), expected.as_ref());
+        if let Err(err) = res {
+            assert!(err.message.starts_with("unsupported_response_type"));
+        }
+    }
+}
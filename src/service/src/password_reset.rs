@@ -5,7 +5,7 @@ use rauthy_api_types::users::{
     PasswordResetRequest, WebauthnRegFinishRequest, WebauthnRegStartRequest,
 };
 use rauthy_common::constants::{PWD_CSRF_HEADER, PWD_RESET_COOKIE};
-use rauthy_common::utils::{get_rand, real_ip_from_req};
+use rauthy_common::utils::{get_rand, real_ip, secure_compare};
 use rauthy_data::api_cookie::ApiCookie;
 use rauthy_data::entity::magic_links::{MagicLink, MagicLinkUsage};
 use rauthy_data::entity::password::PasswordPolicy;
@@ -29,6 +29,7 @@ pub async fn handle_get_pwd_reset<'a>(
     no_html: bool,
 ) -> Result<(String, cookie::Cookie<'a>), ErrorResponse> {
     let mut ml = MagicLink::find(&reset_id).await?;
+    ml.record_expired_attempt().await?;
     ml.validate(&user_id, &req, false)?;
 
     let user = User::find(ml.user_id.clone()).await?;
@@ -115,7 +116,7 @@ pub async fn handle_put_user_passkey_finish(
             ));
         }
         Some(token) => {
-            if ml.csrf_token != token.to_str().unwrap_or("") {
+            if !secure_compare(&ml.csrf_token, token.as_bytes()) {
                 return Err(ErrorResponse::new(
                     ErrorResponseType::Unauthorized,
                     "Invalid CSRF Token",
@@ -177,7 +178,7 @@ pub async fn handle_put_user_password_reset<'a>(
     user.email_verified = true;
     user.save(None).await?;
 
-    let ip = match real_ip_from_req(&req).ok() {
+    let ip = match real_ip(&req).ok() {
         None => {
             error!("Extracting clients real IP from HttpRequest during password reset");
             "UNKNOWN".to_string()
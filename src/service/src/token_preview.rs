@@ -0,0 +1,245 @@
+use crate::oidc::userinfo::build_userinfo;
+use crate::token_set::{
+    AuthCodeFlow, AuthTime, ScopeCustoms, access_token_claims, id_token_claims, map_claims,
+};
+use chrono::Utc;
+use rauthy_api_types::clients::ClaimTarget;
+use rauthy_api_types::oidc::{ClaimSource, ClaimTrace, TokenPreviewRequest, TokenPreviewResponse};
+use rauthy_data::entity::clients::Client;
+use rauthy_data::entity::clients_claims_webhook::ClientClaimsWebhook;
+use rauthy_data::entity::users::User;
+use rauthy_data::entity::users_values::UserValues;
+use rauthy_error::ErrorResponse;
+use std::borrow::Cow;
+
+/// Builds all claims the user would receive for the client after all mappings have been
+/// applied, without signing or issuing any token.
+///
+/// Everything that would reject a real login is returned as a warning instead. The claims
+/// webhook of the client will be called, if one exists.
+pub async fn preview(req: TokenPreviewRequest) -> Result<TokenPreviewResponse, ErrorResponse> {
+    let user = User::find(req.user_id).await?;
+    let client = Client::find(req.client_id).await?;
+
+    let mut warnings = Vec::new();
+    if !client.enabled {
+        warnings.push("The client is disabled".to_string());
+    }
+    if !user.enabled {
+        warnings.push("The user is disabled".to_string());
+    }
+    if user.check_expired().is_err() {
+        warnings.push("The user has expired".to_string());
+    }
+    if let Err(err) = client.validate_user_access(&user) {
+        warnings.push(err.message.to_string());
+    }
+
+    let scope = client.sanitize_login_scopes(&req.scopes)?.join(" ");
+    let emission = client.get_claims_emission()?;
+    let customs = ScopeCustoms::load(&user.id, &scope).await?;
+
+    let webhook_claims = match ClientClaimsWebhook::find_opt(&client.id).await? {
+        Some(webhook) => match webhook.fetch(&user, &scope).await {
+            Ok(claims) => claims,
+            Err(err) => {
+                warnings.push(format!(
+                    "The claims webhook failed, a real token request would be rejected: {}",
+                    err.message
+                ));
+                None
+            }
+        },
+        None => None,
+    };
+
+    let now = Utc::now().timestamp();
+    let lifetime = client.access_token_lifetime.unsigned_abs() as i64;
+    let mut mapped_trace = Vec::new();
+
+    let mapped = map_claims(
+        ClaimTarget::AccessToken,
+        &emission,
+        user.roles_iter(),
+        user.groups_iter(),
+        &scope,
+        customs.access(),
+        webhook_claims.as_ref(),
+        Some(&mut mapped_trace),
+    )?;
+    let claims = access_token_claims(
+        Some(&user),
+        &client,
+        None,
+        now,
+        now + lifetime,
+        Cow::Borrowed(scope.as_str()),
+        &mapped,
+        None,
+        None,
+        None,
+    )?;
+    let access_token = serde_json::to_value(&claims)?;
+
+    let user_roles = user.get_roles();
+    let user_groups = user.get_groups();
+    let mapped = map_claims(
+        ClaimTarget::IdToken,
+        &emission,
+        user_roles.iter().map(String::as_str),
+        user_groups.iter().map(String::as_str),
+        &scope,
+        customs.id(),
+        webhook_claims.as_ref(),
+        Some(&mut mapped_trace),
+    )?;
+    let user_values = UserValues::find(&user.id).await?;
+    let claims = id_token_claims(
+        &user,
+        user_values.as_ref(),
+        &client,
+        &AuthTime::now(),
+        None,
+        "",
+        now,
+        lifetime,
+        None,
+        &scope,
+        None,
+        AuthCodeFlow::Yes,
+        mapped,
+    );
+    let mut id_token = serde_json::to_value(&claims)?;
+    // the `at_hash` only exists for a signed access token
+    if let Some(obj) = id_token.as_object_mut() {
+        obj.remove("at_hash");
+    }
+
+    let userinfo = build_userinfo(user, &scope, &emission, Some(&mut mapped_trace)).await?;
+    let userinfo = serde_json::to_value(&userinfo)?;
+
+    let mut trace = Vec::with_capacity(mapped_trace.len() + 32);
+    for (target, claims) in [
+        (ClaimTarget::IdToken, &id_token),
+        (ClaimTarget::AccessToken, &access_token),
+        (ClaimTarget::Userinfo, &userinfo),
+    ] {
+        let mapped = mapped_trace
+            .iter()
+            .filter(|t| t.target == target)
+            .collect::<Vec<_>>();
+        trace.extend(standard_trace(target, claims, &mapped));
+        trace.extend(mapped.into_iter().cloned());
+    }
+
+    Ok(TokenPreviewResponse {
+        signed: false,
+        scope,
+        id_token,
+        access_token,
+        userinfo,
+        trace,
+        warnings,
+    })
+}
+
+/// Traces all claims in `claims`, which have not been set by one of the `mapped` rules.
+fn standard_trace(
+    target: ClaimTarget,
+    claims: &serde_json::Value,
+    mapped: &[&ClaimTrace],
+) -> Vec<ClaimTrace> {
+    let Some(obj) = claims.as_object() else {
+        return Vec::new();
+    };
+
+    obj.keys()
+        .filter(|claim| *claim != "custom" && !mapped.iter().any(|t| &t.claim == *claim))
+        .map(|claim| ClaimTrace {
+            target,
+            claim: claim.clone(),
+            source: standard_source(claim),
+        })
+        .collect()
+}
+
+/// Returns the scope a standard claim is emitted for.
+fn standard_source(claim: &str) -> ClaimSource {
+    let scope = match claim {
+        "email" | "email_verified" => "email",
+        "preferred_username" | "given_name" | "family_name" | "birthdate" | "picture"
+        | "locale" | "zoneinfo" => "profile",
+        "address" => "address",
+        "phone_number" | "phone_number_verified" => "phone",
+        "webid" => "webid",
+        _ => return ClaimSource::Protocol,
+    };
+    ClaimSource::Scope {
+        scope: scope.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_standard_trace() {
+        let claims = json!({
+            "iss": "https://auth.example.com/auth/v1",
+            "sub": "user123",
+            "email": "admin@example.com",
+            "given_name": "Admin",
+            "roles": ["admin"],
+            "custom": { "tenant": "t1" },
+        });
+        let mapped = [
+            ClaimTrace {
+                target: ClaimTarget::IdToken,
+                claim: "roles".to_string(),
+                source: ClaimSource::Roles,
+            },
+            ClaimTrace {
+                target: ClaimTarget::IdToken,
+                claim: "custom.tenant".to_string(),
+                source: ClaimSource::ScopeAttribute {
+                    scope: "tenant".to_string(),
+                    attribute: "tenant".to_string(),
+                },
+            },
+        ];
+
+        let mut trace = standard_trace(
+            ClaimTarget::IdToken,
+            &claims,
+            &mapped.iter().collect::<Vec<_>>(),
+        );
+        trace.sort_by(|a, b| a.claim.cmp(&b.claim));
+
+        let expected = [
+            (
+                "email",
+                ClaimSource::Scope {
+                    scope: "email".to_string(),
+                },
+            ),
+            (
+                "given_name",
+                ClaimSource::Scope {
+                    scope: "profile".to_string(),
+                },
+            ),
+            ("iss", ClaimSource::Protocol),
+            ("sub", ClaimSource::Protocol),
+        ]
+        .into_iter()
+        .map(|(claim, source)| ClaimTrace {
+            target: ClaimTarget::IdToken,
+            claim: claim.to_string(),
+            source,
+        })
+        .collect::<Vec<_>>();
+        assert_eq!(trace, expected);
+    }
+}
@@ -1,8 +1,21 @@
+use actix_web::HttpRequest;
+use actix_web::http::header::USER_AGENT;
 use chrono::Utc;
 use cryptr::utils::secure_random_alnum;
-use rauthy_api_types::oidc::{Audience, JktClaim};
-use rauthy_common::utils::base64_url_no_pad_encode;
+use rauthy_api_types::claims::{
+    JwtAccessClaims, JwtAmrValue, JwtCommonClaims, JwtIdClaims, JwtTokenType,
+    validate_no_reserved_collision,
+};
+use rauthy_api_types::clients::{ClaimTarget, ClientClaimsEmission};
+use rauthy_api_types::oidc::{Audience, ClaimSource, ClaimTrace, JktClaim, TokenSet};
+use rauthy_common::utils::{base64_url_no_pad_encode, real_ip};
 use rauthy_data::entity::clients::Client;
+use rauthy_data::entity::clients_claims::{
+    CLAIM_GROUPS, CLAIM_GROUPS_OVERFLOW, CLAIM_ROLES, EmittedRolesGroups,
+};
+use rauthy_data::entity::clients_claims_webhook::{
+    ClientClaimsWebhook, WebhookClaims, merge_values,
+};
 use rauthy_data::entity::issued_tokens::IssuedToken;
 use rauthy_data::entity::jwk::{JwkKeyPair, JwkKeyPairAlg};
 use rauthy_data::entity::refresh_tokens::RefreshToken;
@@ -12,19 +25,15 @@ use rauthy_data::entity::user_attr::UserAttrValueEntity;
 use rauthy_data::entity::users::User;
 use rauthy_data::entity::users_values::UserValues;
 use rauthy_data::entity::webids::WebId;
+use rauthy_data::events::event::Event;
 use rauthy_data::rauthy_config::RauthyConfig;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
-use rauthy_jwt::claims::{
-    JwtAccessClaims, JwtAmrValue, JwtCommonClaims, JwtIdClaims, JwtTokenType,
-    validate_no_reserved_collision,
-};
 use rauthy_jwt::token::JwtToken;
 use ring::digest;
-use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::str::FromStr;
-use utoipa::ToSchema;
+use tracing::error;
 
 pub struct AccessTokenJti(String);
 
@@ -103,6 +112,28 @@ pub enum DeviceCodeFlow {
 
 #[derive(Clone)]
 pub struct DpopFingerprint(pub String);
+/// The context fingerprint for refresh tokens of clients with a `refresh_token_binding`
+pub struct RefreshTokenFingerprint(pub String);
+
+impl RefreshTokenFingerprint {
+    /// Returns `None` if the client does not bind its refresh tokens.
+    pub fn from_req(client: &Client, req: &HttpRequest) -> Result<Option<Self>, ErrorResponse> {
+        let Some(binding) = client.get_refresh_token_binding()? else {
+            return Ok(None);
+        };
+        let user_agent = req
+            .headers()
+            .get(USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+        let ip = real_ip(req)?;
+
+        Ok(Some(Self(RefreshToken::build_fingerprint(
+            &binding, user_agent, ip,
+        ))))
+    }
+}
+
 #[derive(Clone)]
 pub struct SessionId(pub String);
 pub struct TokenNonce(pub String);
@@ -110,627 +141,849 @@ pub struct TokenNonce(pub String);
 /// Contains the scopes as a single String separated by `\s`
 pub struct TokenScopes(pub String);
 
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
-pub struct TokenSet {
-    pub access_token: String,
-    pub token_type: JwtTokenType,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub id_token: Option<String>,
-    pub expires_in: i32,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub refresh_token: Option<String>,
+/// The selected custom scopes together with the user attributes they need.
+pub type ScopeCustomsSelection<'a> = (Vec<&'a Scope>, &'a Option<HashMap<String, Vec<u8>>>);
+
+/// The custom scopes of a token request with attribute mappings and the user attributes
+/// they need.
+pub struct ScopeCustoms {
+    scopes: Vec<Scope>,
+    attrs: Option<HashMap<String, Vec<u8>>>,
 }
 
-impl TokenSet {
-    /// Builds the access token for a user after all validation has been successful
-    #[allow(clippy::too_many_arguments, clippy::type_complexity)]
-    pub async fn build_access_token(
-        user: Option<&User>,
-        client: &Client,
-        dpop_fingerprint: Option<DpopFingerprint>,
-        lifetime: i64,
-        scope: Option<TokenScopes>,
-        scope_customs: Option<(Vec<&Scope>, &Option<HashMap<String, Vec<u8>>>)>,
-        sid: Option<SessionId>,
-        resource: Option<&str>,
-        device_code_flow: DeviceCodeFlow,
-    ) -> Result<(AccessTokenJti, String), ErrorResponse> {
-        let did = match device_code_flow {
-            DeviceCodeFlow::Yes(did) => Some(did),
-            DeviceCodeFlow::No => None,
-        };
-        let scope = scope
-            .map(|s| Cow::from(s.0))
-            .unwrap_or_else(|| Cow::from(client.default_scopes.replace(',', " ")));
+impl ScopeCustoms {
+    pub async fn load(user_id: &str, scope: &str) -> Result<Self, ErrorResponse> {
+        let cust = Scope::extract_custom(scope);
+        if cust.is_empty() {
+            return Ok(Self {
+                scopes: Vec::new(),
+                attrs: None,
+            });
+        }
 
-        let email = if scope.contains("email") {
-            user.as_ref().map(|u| u.email.as_str())
-        } else {
+        let scopes = Scope::find_all()
+            .await?
+            .into_iter()
+            .filter(|s| {
+                cust.contains(s.name.as_str())
+                    && (s.attr_include_access.is_some() || s.attr_include_id.is_some())
+            })
+            .collect::<Vec<_>>();
+
+        // if there was any custom mapping, we need the additional user attributes
+        let attrs = if scopes.is_empty() {
             None
-        };
-        let email_verified = if scope.contains("email") {
-            user.as_ref().map(|u| u.email_verified)
-        } else {
-            None
-        };
-        let roles = user.map(|u| u.roles_iter().collect());
-        let groups = if scope.contains("groups") {
-            user.map(|u| u.groups_iter().collect())
         } else {
-            None
+            let attrs = UserAttrValueEntity::find_for_user_with_defaults(user_id).await?;
+            Some(attrs.into_iter().map(|a| (a.key, a.value)).collect())
         };
 
-        let user_id = user.map(|u| u.id.as_str());
-        let now = Utc::now().timestamp();
-        let exp = now + lifetime;
+        Ok(Self { scopes, attrs })
+    }
 
-        let issued_token =
-            IssuedToken::create(user_id, did.as_deref(), sid.map(|sid| sid.0), exp).await?;
+    #[inline]
+    pub fn access(&self) -> Option<ScopeCustomsSelection<'_>> {
+        self.select(|s| s.attr_include_access.is_some())
+    }
 
-        let sub = if let Some(user) = user {
-            Some(user.id.as_str())
-        } else if RauthyConfig::get().vars.access.client_credentials_map_sub {
-            Some(client.id.as_str())
-        } else {
-            None
-        };
+    #[inline]
+    pub fn id(&self) -> Option<ScopeCustomsSelection<'_>> {
+        self.select(|s| s.attr_include_id.is_some())
+    }
 
-        // RFC 8707: the access token audience is the client itself, plus any always-on
-        // `default_aud` entries, plus the granted `resource` (de-duplicated).
-        let mut auds: Vec<Cow<'_, str>> = Vec::with_capacity(1);
-        auds.push(Cow::Borrowed(client.id.as_str()));
-        for a in client.default_aud_iter() {
-            if !auds.iter().any(|x| x.as_ref() == a) {
-                auds.push(Cow::Borrowed(a));
-            }
-        }
-        if let Some(resource) = resource
-            && !auds.iter().any(|x| x.as_ref() == resource)
-        {
-            auds.push(Cow::Borrowed(resource));
+    fn select(&self, filter: impl Fn(&Scope) -> bool) -> Option<ScopeCustomsSelection<'_>> {
+        let scopes = self.scopes.iter().filter(|s| filter(s)).collect::<Vec<_>>();
+        if scopes.is_empty() {
+            None
+        } else {
+            Some((scopes, &self.attrs))
         }
-        let aud = Audience::from_values(auds);
+    }
+}
 
-        let mut claims_new_impl = JwtAccessClaims {
-            common: JwtCommonClaims {
-                iat: now,
-                nbf: now,
-                exp,
-                iss: &RauthyConfig::get().issuer,
-                jti: Some(&issued_token.jti),
-                aud,
-                sub,
-                typ: JwtTokenType::Bearer,
-                azp: &client.id,
-                scope: Some(scope),
-                did: did.as_deref(),
-                cnf: dpop_fingerprint
-                    .as_ref()
-                    .map(|jkt| JktClaim { jkt: &jkt.0 }),
-            },
-            allowed_origins: None,
-            email,
-            email_verified,
-            roles,
-            groups,
-            custom: None,
-            custom_flattened: None,
-        };
+/// The `roles`, `groups` and custom claims of a token after all mappings have been applied.
+#[derive(Debug, Default, PartialEq)]
+pub struct MappedClaims {
+    pub roles: Option<Vec<String>>,
+    pub groups: Option<Vec<String>>,
+    /// Custom claims nested under `custom`
+    pub custom: Option<HashMap<String, serde_json::Value>>,
+    /// Custom claims at the token root
+    pub custom_flattened: Option<HashMap<String, serde_json::Value>>,
+    /// The length of the groups list, if it exceeded `groups_max`.
+    pub groups_exceeded: Option<usize>,
+}
+
+/// Applies the client's `claims_emission`, the attribute mappings of custom scopes and the
+/// claims webhook result for the given `target`. Each contributing rule is pushed into `trace`,
+/// if given.
+///
+/// This does not access the DB or config and is the single place, where these claims are
+/// built for real tokens and for the token preview.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn map_claims<'a>(
+    target: ClaimTarget,
+    emission: &ClientClaimsEmission,
+    roles: impl Iterator<Item = &'a str>,
+    groups: impl Iterator<Item = &'a str>,
+    scope: &str,
+    scope_customs: Option<ScopeCustomsSelection<'_>>,
+    webhook_claims: Option<&'a WebhookClaims>,
+    mut trace: Option<&mut Vec<ClaimTrace>>,
+) -> Result<MappedClaims, ErrorResponse> {
+    let emitted = EmittedRolesGroups::build(
+        emission,
+        target,
+        merge_values(roles, webhook_claims.and_then(|w| w.roles.as_deref())).into_iter(),
+        scope.contains("groups").then(|| {
+            merge_values(groups, webhook_claims.and_then(|w| w.groups.as_deref())).into_iter()
+        }),
+    );
+    if let Some(trace) = trace.as_deref_mut() {
+        trace_roles_groups(trace, target, emission, &emitted, webhook_claims);
+    }
+
+    let mut slf = MappedClaims {
+        roles: emitted.roles,
+        groups: emitted.groups,
+        custom: None,
+        custom_flattened: None,
+        groups_exceeded: emitted.groups_exceeded,
+    };
+
+    if let Some((cust, user_attrs)) = scope_customs {
+        let user_attrs = user_attrs.as_ref().unwrap();
+        // Attributes of scopes flagged `claims_at_root` go to the token root
+        // (flattened); all others stay nested under `custom`. Routing is
+        // per-scope, so a single token can mix nested and root-level claims.
+        let mut nested = HashMap::new();
+        let mut flattened = HashMap::new();
+        for c in cust {
+            let csv = match target {
+                ClaimTarget::AccessToken => c.attr_include_access.as_deref(),
+                ClaimTarget::IdToken => c.attr_include_id.as_deref(),
+                ClaimTarget::Userinfo => None,
+            };
+            let Some(csv) = csv else {
+                continue;
+            };
 
-        if let Some((cust, user_attrs)) = scope_customs {
-            let user_attrs = user_attrs.as_ref().unwrap();
-            // Attributes of scopes flagged `claims_at_root` go to the token root
-            // (flattened); all others stay nested under `custom`. Routing is
-            // per-scope, so a single token can mix nested and root-level claims.
-            let mut nested = HashMap::new();
-            let mut flattened = HashMap::new();
-            for c in cust {
-                if let Some(csv) = &c.attr_include_access {
-                    let target = if c.claims_at_root {
-                        &mut flattened
-                    } else {
-                        &mut nested
-                    };
-                    for cust_name in csv.split(',') {
-                        if let Some(value) = user_attrs.get(cust_name) {
-                            let json = serde_json::from_slice(value.as_slice())
-                                .expect("Converting cust user id attr to json");
-                            target.insert(cust_name.to_string(), json);
-                        }
+            let map = if c.claims_at_root {
+                &mut flattened
+            } else {
+                &mut nested
+            };
+            for cust_name in csv.split(',') {
+                if let Some(value) = user_attrs.get(cust_name) {
+                    let json = serde_json::from_slice(value.as_slice())
+                        .expect("Converting cust user id attr to json");
+                    map.insert(cust_name.to_string(), json);
+
+                    if let Some(trace) = trace.as_deref_mut() {
+                        let claim = if c.claims_at_root {
+                            cust_name.to_string()
+                        } else {
+                            format!("custom.{cust_name}")
+                        };
+                        trace.push(ClaimTrace {
+                            target,
+                            claim,
+                            source: ClaimSource::ScopeAttribute {
+                                scope: c.name.clone(),
+                                attribute: cust_name.to_string(),
+                            },
+                        });
                     }
                 }
             }
-            if !nested.is_empty() {
-                claims_new_impl.custom = Some(nested);
-            }
-            if !flattened.is_empty() {
-                // Fail issuance rather than emit a token that shadows a reserved claim.
-                validate_no_reserved_collision(&flattened)?;
-                claims_new_impl.custom_flattened = Some(flattened);
-            }
         }
-
-        // `client_credentials` tokens have no user (`user.is_none()`), so they
-        // carry the client's admin-defined custom claims. Routed to the token root
-        // (flattened) or nested under `custom` by the client's `claims_at_root`
-        // flag, mirroring the per-scope routing above without the scope checks.
-        // Only the admin API / UI can set these; the dynamic client registration
-        // path never populates `client.claims`.
-        if user.is_none()
-            && let Some(claims) = &client.claims
-        {
-            let value: serde_json::Value = serde_json::from_slice(claims)?;
-            if let serde_json::Value::Object(map) = value {
-                if client.claims_at_root {
-                    let flattened = map.into_iter().collect::<HashMap<_, _>>();
-                    // Fail issuance rather than emit a token that shadows a reserved claim.
-                    validate_no_reserved_collision(&flattened)?;
-                    claims_new_impl
-                        .custom_flattened
-                        .get_or_insert_with(HashMap::new)
-                        .extend(flattened);
-                } else {
-                    claims_new_impl
-                        .custom
-                        .get_or_insert_with(HashMap::new)
-                        .extend(map);
-                }
-            }
+        if !nested.is_empty() {
+            slf.custom = Some(nested);
         }
-
-        let key_pair_alg = JwkKeyPairAlg::from_str(&client.access_token_alg)?;
-        let kp = JwkKeyPair::find_latest(key_pair_alg).await?;
-        let token = JwtToken::build(&kp, &claims_new_impl)?;
-
-        Ok((AccessTokenJti(issued_token.jti), token))
-    }
-
-    /// Builds the id token for a user after all validation has been successful
-    #[allow(clippy::too_many_arguments, clippy::type_complexity)]
-    pub async fn build_id_token(
-        user: &User,
-        client: &Client,
-        auth_time: AuthTime,
-        dpop_fingerprint: Option<DpopFingerprint>,
-        at_hash: AtHash,
-        lifetime: i64,
-        nonce: Option<TokenNonce>,
-        scope: &str,
-        scope_customs: Option<(Vec<&Scope>, &Option<HashMap<String, Vec<u8>>>)>,
-        sid: Option<SessionId>,
-        auth_code_flow: AuthCodeFlow,
-    ) -> Result<String, ErrorResponse> {
-        let config = RauthyConfig::get();
-
-        let amr = if user.has_webauthn_enabled() && auth_code_flow == AuthCodeFlow::Yes {
-            JwtAmrValue::Mfa.as_str()
-        } else {
-            JwtAmrValue::Pwd.as_str()
-        };
-        // Solid-OIDC ephemeral clients additionally carry the `solid` audience.
-        let aud = if client.is_ephemeral() && config.vars.ephemeral_clients.enable_solid_aud {
-            Audience::Multiple(vec![
-                Cow::Borrowed(client.id.as_str()),
-                Cow::Borrowed("solid"),
-            ])
-        } else {
-            Audience::single(client.id.as_str())
-        };
-
-        let user_values = UserValues::find(&user.id).await?;
-
-        let webid = (config.vars.ephemeral_clients.enable_web_id && scope.contains("webid"))
-            .then(|| Cow::from(WebId::resolve_webid_uri(&user.id)));
-
-        let now = Utc::now().timestamp();
-        let mut claims = JwtIdClaims {
-            common: JwtCommonClaims {
-                iat: now,
-                nbf: now,
-                exp: now + lifetime,
-                iss: &config.issuer,
-                jti: None,
-                aud,
-                sub: Some(user.id.as_str()),
-                typ: JwtTokenType::Id,
-                azp: &client.id,
-                scope: Some(Cow::Borrowed(scope)),
-                did: None,
-                cnf: dpop_fingerprint
-                    .as_ref()
-                    .map(|jkt| JktClaim { jkt: &jkt.0 }),
-            },
-            amr: vec![amr],
-            auth_time: auth_time.get(),
-            at_hash: at_hash.0.as_str(),
-            sid: sid.as_ref().map(|sid| sid.0.as_str()),
-            email: None,
-            email_verified: None,
-            preferred_username: None,
-            given_name: None,
-            family_name: None,
-            address: None,
-            birthdate: None,
-            picture: None,
-            locale: None,
-            nonce: nonce.as_ref().map(|n| n.0.as_str()),
-            phone_number: None,
-            phone_number_verified: None,
-            roles: user.get_roles(),
-            groups: None,
-            custom: None,
-            custom_flattened: None,
-            webid,
-            zoneinfo: None,
-        };
-
-        if scope.contains("email") {
-            claims.email = Some(user.email.as_str());
-            claims.email_verified = Some(user.email_verified);
+        if !flattened.is_empty() {
+            // Fail issuance rather than emit a token that shadows a reserved claim.
+            validate_no_reserved_collision(&flattened)?;
+            slf.custom_flattened = Some(flattened);
         }
-        if scope.contains("profile") {
-            claims.given_name = Some(user.given_name.as_str());
-            claims.family_name = user.family_name.as_deref();
-            claims.locale = Some(user.language.as_str());
-
-            if let Some(uv) = &user_values {
-                if let Some(username) = &uv.preferred_username {
-                    claims.preferred_username = Some(username);
-                }
+    }
 
-                if let Some(birthdate) = &uv.birthdate {
-                    claims.birthdate = Some(birthdate.as_str());
-                }
+    if !emitted.flattened.is_empty() {
+        validate_no_reserved_collision(&emitted.flattened)?;
+        slf.custom_flattened
+            .get_or_insert_with(HashMap::new)
+            .extend(emitted.flattened);
+    }
 
-                if let Some(zone) = &uv.tz {
-                    claims.zoneinfo = Some(zone)
-                }
+    if let Some(webhook_claims) = webhook_claims
+        && !webhook_claims.claims.is_empty()
+    {
+        validate_no_reserved_collision(&webhook_claims.claims)?;
+        if let Some(trace) = trace {
+            for claim in webhook_claims.claims.keys() {
+                trace.push(ClaimTrace {
+                    target,
+                    claim: claim.clone(),
+                    source: ClaimSource::Webhook,
+                });
             }
+        }
+        slf.custom_flattened
+            .get_or_insert_with(HashMap::new)
+            .extend(webhook_claims.claims.clone());
+    }
 
-            if config.vars.user_values.preferred_username.email_fallback
-                && claims.preferred_username.is_none()
-            {
-                claims.preferred_username = Some(user.email.as_str());
-            }
+    Ok(slf)
+}
+
+fn trace_roles_groups(
+    trace: &mut Vec<ClaimTrace>,
+    target: ClaimTarget,
+    emission: &ClientClaimsEmission,
+    emitted: &EmittedRolesGroups,
+    webhook_claims: Option<&WebhookClaims>,
+) {
+    let roles_claim = emission.roles_claim.as_deref().unwrap_or(CLAIM_ROLES);
+    let groups_claim = emission.groups_claim.as_deref().unwrap_or(CLAIM_GROUPS);
+    let mut push = |claim: &str, source: ClaimSource| {
+        trace.push(ClaimTrace {
+            target,
+            claim: claim.to_string(),
+            source,
+        })
+    };
 
-            claims.picture = user.picture_uri().map(Cow::from);
+    if emitted.roles.is_some() || emitted.flattened.contains_key(roles_claim) {
+        push(roles_claim, ClaimSource::Roles);
+        if webhook_claims.is_some_and(|w| w.roles.is_some()) {
+            push(roles_claim, ClaimSource::Webhook);
         }
-        if scope.contains("address")
-            && let Some(values) = &user_values
-        {
-            claims.address = rauthy_jwt::claims::AddressClaim::try_build(user, values);
+    }
+    if emitted.groups.is_some() || emitted.flattened.contains_key(groups_claim) {
+        push(groups_claim, ClaimSource::Groups);
+        if emission.roles_in_groups {
+            push(groups_claim, ClaimSource::Roles);
         }
-        if scope.contains("phone")
-            && let Some(values) = &user_values
-            && let Some(phone) = &values.phone
+        if webhook_claims
+            .is_some_and(|w| w.groups.is_some() || (emission.roles_in_groups && w.roles.is_some()))
         {
-            claims.phone_number = Some(phone.as_str());
-            // Note: Rauthy does not have a mechanism to send SMS and therefore verify a number.
-            claims.phone_number_verified = Some(false);
-        }
-        if scope.contains("groups") {
-            claims.groups = Some(user.get_groups());
-        }
-
-        if let Some((cust, user_attrs)) = scope_customs {
-            let user_attrs = user_attrs.as_ref().unwrap();
-            // See `build_access_token`: per-scope routing to root vs nested `custom`.
-            let mut nested = HashMap::new();
-            let mut flattened = HashMap::new();
-            for c in cust {
-                if let Some(csv) = &c.attr_include_id {
-                    let target = if c.claims_at_root {
-                        &mut flattened
-                    } else {
-                        &mut nested
-                    };
-                    for cust_name in csv.split(',') {
-                        if let Some(value) = user_attrs.get(cust_name) {
-                            let json = serde_json::from_slice(value.as_slice())
-                                .expect("Converting cust user id attr to json");
-                            target.insert(cust_name.to_string(), json);
-                        }
-                    }
-                }
-            }
-            if !nested.is_empty() {
-                claims.custom = Some(nested);
-            }
-            if !flattened.is_empty() {
-                // Fail issuance rather than emit a token that shadows a reserved claim.
-                validate_no_reserved_collision(&flattened)?;
-                claims.custom_flattened = Some(flattened);
-            }
+            push(groups_claim, ClaimSource::Webhook);
         }
+    }
+    if emitted.flattened.contains_key(CLAIM_GROUPS_OVERFLOW) {
+        push(CLAIM_GROUPS_OVERFLOW, ClaimSource::GroupsOverflow);
+    }
+}
 
-        let key_pair_alg = JwkKeyPairAlg::from_str(&client.id_token_alg)?;
-        let kp = JwkKeyPair::find_latest(key_pair_alg).await?;
-        JwtToken::build(&kp, &claims)
-    }
-
-    /// Builds the refresh token for a user after all validation has been successful
-    #[allow(clippy::too_many_arguments)]
-    pub async fn build_refresh_token(
-        user: &User,
-        dpop_fingerprint: Option<DpopFingerprint>,
-        client: &Client,
-        auth_time: AuthTime,
-        access_token_lifetime: i64,
-        scope: Option<TokenScopes>,
-        is_mfa: bool,
-        device_code_flow: DeviceCodeFlow,
-        sid: Option<SessionId>,
-        resource: Option<&str>,
-        jti: AccessTokenJti,
-    ) -> Result<String, ErrorResponse> {
-        let did = if let DeviceCodeFlow::Yes(device_id) = device_code_flow {
-            Some(device_id)
-        } else {
-            None
-        };
-
-        let now = Utc::now().timestamp();
-        let nbf = if RauthyConfig::get().vars.access.disable_refresh_token_nbf {
-            now
-        } else {
-            // allow 60 second early usage
-            now + access_token_lifetime - 60
-        };
-        let exp = if did.is_some() {
-            nbf + 3600 * RauthyConfig::get().vars.device_grant.refresh_token_lifetime as i64
-        } else {
-            nbf + 3600 * RauthyConfig::get().vars.lifetimes.refresh_token_lifetime as i64
-        };
-
-        let token = {
-            let jti = secure_random_alnum(8);
-
-            let claims = rauthy_jwt::claims::JwtRefreshClaims {
-                common: JwtCommonClaims {
-                    iat: now,
-                    nbf,
-                    exp,
-                    iss: &RauthyConfig::get().issuer,
-                    // jti is not really used for any validation, it just exists
-                    // to bring a bit more randomness into the claims
-                    jti: Some(&jti),
-                    aud: Audience::single(client.id.as_str()),
-                    sub: None,
-                    typ: JwtTokenType::Refresh,
-                    azp: &client.id,
-                    scope: None,
-                    did: did.as_deref(),
-                    cnf: dpop_fingerprint
-                        .as_ref()
-                        .map(|jkt| JktClaim { jkt: &jkt.0 }),
-                },
-                uid: &user.id,
-                // Only Optional for backwards compatibility with older Rauthy versions and tokens.
-                // Could be changed with v1.0.0 maybe.
-                auth_time: Some(auth_time.get()),
-                resource,
-            };
+/// Builds the access token for a user after all validation has been successful
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub async fn build_access_token(
+    user: Option<&User>,
+    client: &Client,
+    dpop_fingerprint: Option<DpopFingerprint>,
+    lifetime: i64,
+    scope: Option<TokenScopes>,
+    scope_customs: Option<ScopeCustomsSelection<'_>>,
+    webhook_claims: Option<&WebhookClaims>,
+    sid: Option<SessionId>,
+    resource: Option<&str>,
+    device_code_flow: DeviceCodeFlow,
+) -> Result<(AccessTokenJti, String), ErrorResponse> {
+    let did = match device_code_flow {
+        DeviceCodeFlow::Yes(did) => Some(did),
+        DeviceCodeFlow::No => None,
+    };
+    let scope = scope
+        .map(|s| Cow::from(s.0))
+        .unwrap_or_else(|| Cow::from(client.default_scopes.replace(',', " ")));
+
+    let mapped = match user {
+        Some(u) => map_claims(
+            ClaimTarget::AccessToken,
+            &client.get_claims_emission()?,
+            u.roles_iter(),
+            u.groups_iter(),
+            &scope,
+            scope_customs,
+            webhook_claims,
+            None,
+        )?,
+        None => MappedClaims::default(),
+    };
+
+    let user_id = user.map(|u| u.id.as_str());
+    let now = Utc::now().timestamp();
+    let exp = now + lifetime;
+
+    let issued_token =
+        IssuedToken::create(user_id, did.as_deref(), sid.map(|sid| sid.0), exp).await?;
+
+    let claims = access_token_claims(
+        user,
+        client,
+        dpop_fingerprint.as_ref(),
+        now,
+        exp,
+        scope,
+        &mapped,
+        Some(&issued_token.jti),
+        did.as_deref(),
+        resource,
+    )?;
+
+    if let Some(user) = user
+        && let Some(count) = mapped.groups_exceeded
+    {
+        send_groups_overflow_event(client, user, count).await;
+    }
 
-            let kp = JwkKeyPair::find_latest(JwkKeyPairAlg::default()).await?;
-            JwtToken::build(&kp, &claims)?
-        };
+    let key_pair_alg = JwkKeyPairAlg::from_str(&client.access_token_alg)?;
+    let kp = JwkKeyPair::find_latest(key_pair_alg).await?;
+    let token = JwtToken::build(&kp, &claims)?;
 
-        // only save the last 50 characters for validation
-        let validation_string = String::from(&token).split_off(token.len() - 49);
+    Ok((AccessTokenJti(issued_token.jti), token))
+}
 
-        if let Some(device_id) = did {
-            RefreshTokenDevice::create(
-                validation_string,
-                device_id,
-                user.id.clone(),
-                nbf,
-                exp,
-                scope.map(|s| s.0),
-                Some(jti.0),
-            )
-            .await?;
-        } else {
-            RefreshToken::create(
-                validation_string,
-                user.id.clone(),
-                nbf,
-                exp,
-                scope.map(|s| s.0),
-                is_mfa,
-                sid.map(|s| s.0),
-                Some(jti.0),
-            )
-            .await?;
+/// Builds the unsigned claims of an access token.
+#[allow(clippy::too_many_arguments)]
+pub fn access_token_claims<'a>(
+    user: Option<&'a User>,
+    client: &'a Client,
+    dpop_fingerprint: Option<&'a DpopFingerprint>,
+    now: i64,
+    exp: i64,
+    scope: Cow<'a, str>,
+    mapped: &'a MappedClaims,
+    jti: Option<&'a str>,
+    did: Option<&'a str>,
+    resource: Option<&'a str>,
+) -> Result<JwtAccessClaims<'a>, ErrorResponse> {
+    let email = if scope.contains("email") {
+        user.as_ref().map(|u| u.email.as_str())
+    } else {
+        None
+    };
+    let email_verified = if scope.contains("email") {
+        user.as_ref().map(|u| u.email_verified)
+    } else {
+        None
+    };
+
+    let sub = if let Some(user) = user {
+        Some(user.id.as_str())
+    } else if RauthyConfig::get().vars.access.client_credentials_map_sub {
+        Some(client.id.as_str())
+    } else {
+        None
+    };
+
+    // RFC 8707: the access token audience is the client itself, plus any always-on
+    // `default_aud` entries, plus the granted `resource` (de-duplicated).
+    let mut auds: Vec<Cow<'_, str>> = Vec::with_capacity(1);
+    auds.push(Cow::Borrowed(client.id.as_str()));
+    for a in client.default_aud_iter() {
+        if !auds.iter().any(|x| x.as_ref() == a) {
+            auds.push(Cow::Borrowed(a));
         }
-
-        Ok(token)
     }
-
-    pub async fn for_client_credentials(
-        client: &Client,
-        dpop_fingerprint: Option<DpopFingerprint>,
-        resource: Option<&str>,
-    ) -> Result<Self, ErrorResponse> {
-        let token_type = if dpop_fingerprint.is_some() {
-            JwtTokenType::DPoP
-        } else {
-            JwtTokenType::Bearer
-        };
-        let (_jti, access_token) = Self::build_access_token(
-            None,
-            client,
-            dpop_fingerprint,
-            client.access_token_lifetime as i64,
-            None,
-            None,
-            None,
-            resource,
-            DeviceCodeFlow::No,
-        )
-        .await?;
-
-        Ok(Self {
-            access_token,
-            token_type,
-            id_token: None,
-            expires_in: client.access_token_lifetime,
-            refresh_token: None,
-        })
+    if let Some(resource) = resource
+        && !auds.iter().any(|x| x.as_ref() == resource)
+    {
+        auds.push(Cow::Borrowed(resource));
+    }
+    let aud = Audience::from_values(auds);
+
+    let mut claims = JwtAccessClaims {
+        common: JwtCommonClaims {
+            iat: now,
+            nbf: now,
+            exp,
+            iss: &RauthyConfig::get().issuer,
+            jti,
+            aud,
+            sub,
+            typ: JwtTokenType::Bearer,
+            azp: &client.id,
+            scope: Some(scope),
+            did,
+            cnf: dpop_fingerprint.map(|jkt| JktClaim { jkt: &jkt.0 }),
+        },
+        allowed_origins: None,
+        email,
+        email_verified,
+        roles: mapped
+            .roles
+            .as_ref()
+            .map(|r| r.iter().map(String::as_str).collect()),
+        groups: mapped
+            .groups
+            .as_ref()
+            .map(|g| g.iter().map(String::as_str).collect()),
+        custom: mapped.custom.clone(),
+        custom_flattened: mapped.custom_flattened.clone(),
+    };
+
+    // `client_credentials` tokens have no user (`user.is_none()`), so they
+    // carry the client's admin-defined custom claims. Routed to the token root
+    // (flattened) or nested under `custom` by the client's `claims_at_root`
+    // flag, mirroring the per-scope routing above without the scope checks.
+    // Only the admin API / UI can set these; the dynamic client registration
+    // path never populates `client.claims`.
+    if user.is_none()
+        && let Some(client_claims) = &client.claims
+    {
+        let value: serde_json::Value = serde_json::from_slice(client_claims)?;
+        if let serde_json::Value::Object(map) = value {
+            if client.claims_at_root {
+                let flattened = map.into_iter().collect::<HashMap<_, _>>();
+                // Fail issuance rather than emit a token that shadows a reserved claim.
+                validate_no_reserved_collision(&flattened)?;
+                claims
+                    .custom_flattened
+                    .get_or_insert_with(HashMap::new)
+                    .extend(flattened);
+            } else {
+                claims.custom.get_or_insert_with(HashMap::new).extend(map);
+            }
+        }
     }
 
-    // too many arguments is not an issue - params cannot be mistaken because of typed wrappers
-    #[allow(clippy::too_many_arguments)]
-    pub async fn from_user(
-        user: &User,
-        client: &Client,
-        auth_time: AuthTime,
-        dpop_fingerprint: Option<DpopFingerprint>,
-        nonce: Option<TokenNonce>,
-        scopes: Option<TokenScopes>,
-        sid: Option<SessionId>,
-        resource: Option<String>,
-        auth_code_flow: AuthCodeFlow,
-        device_code_flow: DeviceCodeFlow,
-    ) -> Result<Self, ErrorResponse> {
-        let scopes = scopes.map(|s| s.0);
-        let scope = if let Some(s) = &scopes {
-            s.clone()
-        } else {
-            client.default_scopes.clone().replace(',', " ")
-        };
+    Ok(claims)
+}
 
-        // check for any non-custom scopes and prepare data
-        let cust = Scope::extract_custom(&scope);
+/// Builds the id token for a user after all validation has been successful
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub async fn build_id_token(
+    user: &User,
+    client: &Client,
+    auth_time: AuthTime,
+    dpop_fingerprint: Option<DpopFingerprint>,
+    at_hash: AtHash,
+    lifetime: i64,
+    nonce: Option<TokenNonce>,
+    scope: &str,
+    scope_customs: Option<ScopeCustomsSelection<'_>>,
+    webhook_claims: Option<&WebhookClaims>,
+    sid: Option<SessionId>,
+    auth_code_flow: AuthCodeFlow,
+) -> Result<String, ErrorResponse> {
+    let user_roles = user.get_roles();
+    let user_groups = user.get_groups();
+    let mapped = map_claims(
+        ClaimTarget::IdToken,
+        &client.get_claims_emission()?,
+        user_roles.iter().map(String::as_str),
+        user_groups.iter().map(String::as_str),
+        scope,
+        scope_customs,
+        webhook_claims,
+        None,
+    )?;
+    let groups_exceeded = mapped.groups_exceeded;
+
+    let user_values = UserValues::find(&user.id).await?;
+    let claims = id_token_claims(
+        user,
+        user_values.as_ref(),
+        client,
+        &auth_time,
+        dpop_fingerprint.as_ref(),
+        &at_hash.0,
+        Utc::now().timestamp(),
+        lifetime,
+        nonce.as_ref(),
+        scope,
+        sid.as_ref(),
+        auth_code_flow,
+        mapped,
+    );
+
+    if let Some(count) = groups_exceeded {
+        send_groups_overflow_event(client, user, count).await;
+    }
 
-        let scps;
-        let attrs;
-        let (customs_access, customs_id) = if !cust.is_empty() {
-            scps = Some(Scope::find_all().await?);
+    let key_pair_alg = JwkKeyPairAlg::from_str(&client.id_token_alg)?;
+    let kp = JwkKeyPair::find_latest(key_pair_alg).await?;
+    JwtToken::build(&kp, &claims)
+}
 
-            let mut customs_access = Vec::with_capacity(cust.len());
-            let mut customs_id = Vec::with_capacity(cust.len());
+/// Builds the unsigned claims of an id token.
+#[allow(clippy::too_many_arguments)]
+pub fn id_token_claims<'a>(
+    user: &'a User,
+    user_values: Option<&'a UserValues>,
+    client: &'a Client,
+    auth_time: &AuthTime,
+    dpop_fingerprint: Option<&'a DpopFingerprint>,
+    at_hash: &'a str,
+    now: i64,
+    lifetime: i64,
+    nonce: Option<&'a TokenNonce>,
+    scope: &'a str,
+    sid: Option<&'a SessionId>,
+    auth_code_flow: AuthCodeFlow,
+    mapped: MappedClaims,
+) -> JwtIdClaims<'a> {
+    let config = RauthyConfig::get();
+
+    let amr = if user.has_webauthn_enabled() && auth_code_flow == AuthCodeFlow::Yes {
+        JwtAmrValue::Mfa.as_str()
+    } else {
+        JwtAmrValue::Pwd.as_str()
+    };
+    // Solid-OIDC ephemeral clients additionally carry the `solid` audience.
+    let aud = if client.is_ephemeral() && config.vars.ephemeral_clients.enable_solid_aud {
+        Audience::Multiple(vec![
+            Cow::Borrowed(client.id.as_str()),
+            Cow::Borrowed("solid"),
+        ])
+    } else {
+        Audience::single(client.id.as_str())
+    };
+
+    let webid = (config.vars.ephemeral_clients.enable_web_id && scope.contains("webid"))
+        .then(|| Cow::from(WebId::resolve_webid_uri(&user.id)));
+
+    let mut claims = JwtIdClaims {
+        common: JwtCommonClaims {
+            iat: now,
+            nbf: now,
+            exp: now + lifetime,
+            iss: &config.issuer,
+            jti: None,
+            aud,
+            sub: Some(user.id.as_str()),
+            typ: JwtTokenType::Id,
+            azp: &client.id,
+            scope: Some(Cow::Borrowed(scope)),
+            did: None,
+            cnf: dpop_fingerprint.map(|jkt| JktClaim { jkt: &jkt.0 }),
+        },
+        amr: vec![amr],
+        auth_time: auth_time.get(),
+        at_hash,
+        sid: sid.map(|sid| sid.0.as_str()),
+        email: None,
+        email_verified: None,
+        preferred_username: None,
+        given_name: None,
+        family_name: None,
+        address: None,
+        birthdate: None,
+        picture: None,
+        locale: None,
+        nonce: nonce.map(|n| n.0.as_str()),
+        phone_number: None,
+        phone_number_verified: None,
+        roles: mapped.roles,
+        groups: mapped.groups,
+        custom: mapped.custom,
+        custom_flattened: mapped.custom_flattened,
+        webid,
+        zoneinfo: None,
+    };
+
+    if scope.contains("email") {
+        claims.email = Some(user.email.as_str());
+        claims.email_verified = Some(user.email_verified);
+    }
+    if scope.contains("profile") {
+        claims.given_name = Some(user.given_name.as_str());
+        claims.family_name = user.family_name.as_deref();
+        claims.locale = Some(user.language.as_str());
+
+        if let Some(uv) = user_values {
+            if let Some(username) = &uv.preferred_username {
+                claims.preferred_username = Some(username);
+            }
 
-            for s in scps.as_ref().unwrap() {
-                if cust.contains(s.name.as_str()) {
-                    if s.attr_include_access.is_some() {
-                        customs_access.push(s);
-                    }
-                    if s.attr_include_id.is_some() {
-                        customs_id.push(s);
-                    }
-                }
+            if let Some(birthdate) = &uv.birthdate {
+                claims.birthdate = Some(birthdate.as_str());
             }
 
-            // if there was any custom mapping, we need the additional user attributes
-            attrs = if !customs_access.is_empty() || !customs_id.is_empty() {
-                let attrs = UserAttrValueEntity::find_for_user_with_defaults(&user.id).await?;
-                let mut res = HashMap::with_capacity(attrs.len());
-                attrs.iter().for_each(|a| {
-                    res.insert(a.key.clone(), a.value.clone());
-                });
-                Some(res)
-            } else {
-                None
-            };
+            if let Some(zone) = &uv.tz {
+                claims.zoneinfo = Some(zone)
+            }
+        }
 
-            // prepare the result
-            let access = if customs_access.is_empty() {
-                None
-            } else {
-                Some((customs_access, &attrs))
-            };
-            let id = if customs_id.is_empty() {
-                None
-            } else {
-                Some((customs_id, &attrs))
-            };
+        if config.vars.user_values.preferred_username.email_fallback
+            && claims.preferred_username.is_none()
+        {
+            claims.preferred_username = Some(user.email.as_str());
+        }
 
-            (access, id)
-        } else {
-            (None, None)
-        };
+        claims.picture = user.picture_uri().map(Cow::from);
+    }
+    if scope.contains("address")
+        && let Some(values) = user_values
+    {
+        claims.address = rauthy_jwt::claims::build_address_claim(user, values);
+    }
+    if scope.contains("phone")
+        && let Some(values) = user_values
+        && let Some(phone) = &values.phone
+    {
+        claims.phone_number = Some(phone.as_str());
+        // Note: Rauthy does not have a mechanism to send SMS and therefore verify a number.
+        claims.phone_number_verified = Some(false);
+    }
 
-        // set the correct lifetime
-        let lifetime = if let Some(ts) = user.user_expires {
-            let now = Utc::now().timestamp();
-            let diff = ts - now;
-            if diff < 1 {
-                return Err(ErrorResponse::new(
-                    ErrorResponseType::Forbidden,
-                    "User has expired",
-                ));
-            }
+    claims
+}
 
-            let client_lt = client.access_token_lifetime.unsigned_abs() as i64;
-            if client_lt < diff { client_lt } else { diff }
-        } else {
-            client.access_token_lifetime.unsigned_abs() as i64
+/// Builds the refresh token for a user after all validation has been successful
+#[allow(clippy::too_many_arguments)]
+pub async fn build_refresh_token(
+    user: &User,
+    dpop_fingerprint: Option<DpopFingerprint>,
+    client: &Client,
+    auth_time: AuthTime,
+    access_token_lifetime: i64,
+    scope: Option<TokenScopes>,
+    is_mfa: bool,
+    device_code_flow: DeviceCodeFlow,
+    sid: Option<SessionId>,
+    resource: Option<&str>,
+    jti: AccessTokenJti,
+    rt_fingerprint: Option<RefreshTokenFingerprint>,
+) -> Result<String, ErrorResponse> {
+    let did = if let DeviceCodeFlow::Yes(device_id) = device_code_flow {
+        Some(device_id)
+    } else {
+        None
+    };
+
+    let now = Utc::now().timestamp();
+    let nbf = if RauthyConfig::get().vars.access.disable_refresh_token_nbf {
+        now
+    } else {
+        // allow 60 second early usage
+        now + access_token_lifetime - 60
+    };
+    let exp = if did.is_some() {
+        nbf + 3600 * RauthyConfig::get().vars.device_grant.refresh_token_lifetime as i64
+    } else {
+        nbf + 3600 * RauthyConfig::get().vars.lifetimes.refresh_token_lifetime as i64
+    };
+
+    let token = {
+        let jti = secure_random_alnum(8);
+
+        let claims = rauthy_api_types::claims::JwtRefreshClaims {
+            common: JwtCommonClaims {
+                iat: now,
+                nbf,
+                exp,
+                iss: &RauthyConfig::get().issuer,
+                // jti is not really used for any validation, it just exists
+                // to bring a bit more randomness into the claims
+                jti: Some(&jti),
+                aud: Audience::single(client.id.as_str()),
+                sub: None,
+                typ: JwtTokenType::Refresh,
+                azp: &client.id,
+                scope: None,
+                did: did.as_deref(),
+                cnf: dpop_fingerprint
+                    .as_ref()
+                    .map(|jkt| JktClaim { jkt: &jkt.0 }),
+            },
+            uid: &user.id,
+            // Only Optional for backwards compatibility with older Rauthy versions and tokens.
+            // Could be changed with v1.0.0 maybe.
+            auth_time: Some(auth_time.get()),
+            resource,
         };
 
-        let token_type = if dpop_fingerprint.is_some() {
-            JwtTokenType::DPoP
-        } else {
-            JwtTokenType::Bearer
-        };
-        let (jti, access_token) = Self::build_access_token(
-            Some(user),
-            client,
-            dpop_fingerprint.clone(),
-            lifetime,
-            Some(TokenScopes(scope.clone())),
-            customs_access,
-            sid.clone(),
-            resource.as_deref(),
-            device_code_flow.clone(),
+        let kp = JwkKeyPair::find_latest(JwkKeyPairAlg::default()).await?;
+        JwtToken::build(&kp, &claims)?
+    };
+
+    // only save the last 50 characters for validation
+    let validation_string = String::from(&token).split_off(token.len() - 49);
+
+    if let Some(device_id) = did {
+        RefreshTokenDevice::create(
+            &validation_string,
+            device_id,
+            user.id.clone(),
+            nbf,
+            exp,
+            scope.map(|s| s.0),
+            Some(jti.0),
         )
         .await?;
-
-        let at_hash = AtHash::build(
-            access_token.as_bytes(),
-            AtHashAlg::try_from(client.access_token_alg.as_str())?,
-        );
-        let id_token = Self::build_id_token(
-            user,
-            client,
-            auth_time.clone(),
-            dpop_fingerprint.clone(),
-            at_hash,
-            lifetime,
-            nonce,
-            &scope,
-            customs_id,
-            sid.clone(),
-            auth_code_flow,
+    } else {
+        RefreshToken::create(
+            &validation_string,
+            user.id.clone(),
+            nbf,
+            exp,
+            scope.map(|s| s.0),
+            is_mfa,
+            sid.map(|s| s.0),
+            Some(jti.0),
+            rt_fingerprint.map(|fp| fp.0),
+            client.id.clone(),
+            auth_time.get(),
         )
         .await?;
-        let refresh_token = if client.allow_refresh_token() {
-            Some(
-                Self::build_refresh_token(
-                    user,
-                    dpop_fingerprint,
-                    client,
-                    auth_time,
-                    lifetime,
-                    scopes.map(TokenScopes),
-                    user.has_webauthn_enabled(),
-                    device_code_flow,
-                    sid,
-                    resource.as_deref(),
-                    jti,
-                )
-                .await?,
+    }
+
+    Ok(token)
+}
+
+pub async fn for_client_credentials(
+    client: &Client,
+    dpop_fingerprint: Option<DpopFingerprint>,
+    resource: Option<&str>,
+) -> Result<TokenSet, ErrorResponse> {
+    let token_type = if dpop_fingerprint.is_some() {
+        JwtTokenType::DPoP
+    } else {
+        JwtTokenType::Bearer
+    };
+    let (_jti, access_token) = build_access_token(
+        None,
+        client,
+        dpop_fingerprint,
+        client.access_token_lifetime as i64,
+        None,
+        None,
+        None,
+        None,
+        resource,
+        DeviceCodeFlow::No,
+    )
+    .await?;
+
+    Ok(TokenSet::new(
+        access_token,
+        token_type,
+        client.access_token_lifetime,
+    ))
+}
+
+// too many arguments is not an issue - params cannot be mistaken because of typed wrappers
+#[allow(clippy::too_many_arguments)]
+pub async fn from_user(
+    user: &User,
+    client: &Client,
+    auth_time: AuthTime,
+    dpop_fingerprint: Option<DpopFingerprint>,
+    rt_fingerprint: Option<RefreshTokenFingerprint>,
+    nonce: Option<TokenNonce>,
+    scopes: Option<TokenScopes>,
+    sid: Option<SessionId>,
+    resource: Option<String>,
+    auth_code_flow: AuthCodeFlow,
+    device_code_flow: DeviceCodeFlow,
+) -> Result<TokenSet, ErrorResponse> {
+    let scopes = scopes.map(|s| s.0);
+    let scope = if let Some(s) = &scopes {
+        s.clone()
+    } else {
+        client.default_scopes.clone().replace(',', " ")
+    };
+
+    let customs = ScopeCustoms::load(&user.id, &scope).await?;
+
+    // set the correct lifetime
+    let lifetime = if let Some(ts) = user.user_expires {
+        let now = Utc::now().timestamp();
+        let diff = ts - now;
+        if diff < 1 {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Forbidden,
+                "User has expired",
+            ));
+        }
+
+        let client_lt = client.access_token_lifetime.unsigned_abs() as i64;
+        if client_lt < diff { client_lt } else { diff }
+    } else {
+        client.access_token_lifetime.unsigned_abs() as i64
+    };
+
+    // The webhook is called only once and its result is shared between all tokens.
+    // Its own timeout bounds the additional latency for the token request.
+    let webhook_claims = match ClientClaimsWebhook::find_opt(&client.id).await? {
+        Some(webhook) => webhook.fetch(user, &scope).await?,
+        None => None,
+    };
+
+    let token_type = if dpop_fingerprint.is_some() {
+        JwtTokenType::DPoP
+    } else {
+        JwtTokenType::Bearer
+    };
+    let (jti, access_token) = build_access_token(
+        Some(user),
+        client,
+        dpop_fingerprint.clone(),
+        lifetime,
+        Some(TokenScopes(scope.clone())),
+        customs.access(),
+        webhook_claims.as_ref(),
+        sid.clone(),
+        resource.as_deref(),
+        device_code_flow.clone(),
+    )
+    .await?;
+
+    let at_hash = AtHash::build(
+        access_token.as_bytes(),
+        AtHashAlg::try_from(client.access_token_alg.as_str())?,
+    );
+    let id_token = build_id_token(
+        user,
+        client,
+        auth_time.clone(),
+        dpop_fingerprint.clone(),
+        at_hash,
+        lifetime,
+        nonce,
+        &scope,
+        customs.id(),
+        webhook_claims.as_ref(),
+        sid.clone(),
+        auth_code_flow,
+    )
+    .await?;
+    let refresh_token = if client.allow_refresh_token() {
+        Some(
+            build_refresh_token(
+                user,
+                dpop_fingerprint,
+                client,
+                auth_time,
+                lifetime,
+                scopes.map(TokenScopes),
+                user.has_webauthn_enabled(),
+                device_code_flow,
+                sid,
+                resource.as_deref(),
+                jti,
+                rt_fingerprint,
             )
-        } else {
-            None
-        };
+            .await?,
+        )
+    } else {
+        None
+    };
+
+    let mut ts = TokenSet::new(access_token, token_type, client.access_token_lifetime);
+    ts.id_token = Some(id_token);
+    ts.refresh_token = refresh_token;
+    Ok(ts)
+}
 
-        Ok(Self {
-            access_token,
-            token_type,
-            id_token: Some(id_token),
-            expires_in: client.access_token_lifetime,
-            refresh_token,
-        })
+/// Emits the `TokenGroupsOverflow` event. Failing to do so never fails token issuance.
+async fn send_groups_overflow_event(client: &Client, user: &User, groups: usize) {
+    if let Err(err) = Event::token_groups_overflow(&client.id, &user.id, groups)
+        .send()
+        .await
+    {
+        error!(?err, "Cannot send TokenGroupsOverflow event");
     }
 }
 
@@ -752,4 +1005,163 @@ mod tests {
         let sha512 = AtHash::build(ref_token, AtHashAlg::Sha512);
         assert_eq!(&sha512.0, "p2LHG4H-8pYDc0hyVOo3iIHvZJUqe9tbj3jESOuXbkY");
     }
+
+    fn scope(name: &str, access: Option<&str>, id: Option<&str>, at_root: bool) -> Scope {
+        Scope {
+            id: name.to_string(),
+            name: name.to_string(),
+            attr_include_access: access.map(String::from),
+            attr_include_id: id.map(String::from),
+            claims_at_root: at_root,
+        }
+    }
+
+    fn trace(target: ClaimTarget, claim: &str, source: ClaimSource) -> ClaimTrace {
+        ClaimTrace {
+            target,
+            claim: claim.to_string(),
+            source,
+        }
+    }
+
+    #[test]
+    fn test_map_claims_scope_attributes() {
+        let tenant = scope("tenant", Some("tenant_id"), Some("tenant_id,plan"), false);
+        let org = scope("org", Some("org_id"), None, true);
+        let attrs = Some(HashMap::from([
+            ("tenant_id".to_string(), br#""t1""#.to_vec()),
+            ("plan".to_string(), br#""pro""#.to_vec()),
+            ("org_id".to_string(), br#""o1""#.to_vec()),
+        ]));
+
+        let mut tr = Vec::new();
+        let mapped = map_claims(
+            ClaimTarget::AccessToken,
+            &ClientClaimsEmission::default(),
+            "admin".split(','),
+            "ops".split(','),
+            "openid tenant org",
+            Some((vec![&tenant, &org], &attrs)),
+            None,
+            Some(&mut tr),
+        )
+        .unwrap();
+        assert_eq!(mapped.roles, Some(vec!["admin".to_string()]));
+        // `groups` has not been requested
+        assert_eq!(mapped.groups, None);
+        assert_eq!(
+            mapped.custom,
+            Some(HashMap::from([("tenant_id".to_string(), "t1".into())]))
+        );
+        assert_eq!(
+            mapped.custom_flattened,
+            Some(HashMap::from([("org_id".to_string(), "o1".into())]))
+        );
+        assert_eq!(
+            tr,
+            vec![
+                trace(ClaimTarget::AccessToken, "roles", ClaimSource::Roles),
+                trace(
+                    ClaimTarget::AccessToken,
+                    "custom.tenant_id",
+                    ClaimSource::ScopeAttribute {
+                        scope: "tenant".to_string(),
+                        attribute: "tenant_id".to_string(),
+                    }
+                ),
+                trace(
+                    ClaimTarget::AccessToken,
+                    "org_id",
+                    ClaimSource::ScopeAttribute {
+                        scope: "org".to_string(),
+                        attribute: "org_id".to_string(),
+                    }
+                ),
+            ]
+        );
+
+        // the userinfo never contains scope attributes
+        let mapped = map_claims(
+            ClaimTarget::Userinfo,
+            &ClientClaimsEmission::default(),
+            "admin".split(','),
+            "ops".split(','),
+            "openid tenant org",
+            Some((vec![&tenant, &org], &attrs)),
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(mapped.custom, None);
+        assert_eq!(mapped.custom_flattened, None);
+
+        // a root level attribute must never shadow a reserved claim
+        let attrs = Some(HashMap::from([("sub".to_string(), br#""evil""#.to_vec())]));
+        let evil = scope("evil", None, Some("sub"), true);
+        let res = map_claims(
+            ClaimTarget::IdToken,
+            &ClientClaimsEmission::default(),
+            "admin".split(','),
+            "ops".split(','),
+            "openid evil",
+            Some((vec![&evil], &attrs)),
+            None,
+            None,
+        );
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_map_claims_emission_webhook() {
+        let emission = ClientClaimsEmission {
+            roles_claim: Some("app_roles".to_string()),
+            roles_in_groups: true,
+            groups_max: Some(2),
+            groups_overflow: true,
+            ..Default::default()
+        };
+        let webhook = WebhookClaims {
+            roles: Some(vec!["billing".to_string()]),
+            groups: None,
+            claims: HashMap::from([("tenant".to_string(), "t1".into())]),
+        };
+
+        let mut tr = Vec::new();
+        let mapped = map_claims(
+            ClaimTarget::IdToken,
+            &emission,
+            "admin".split(','),
+            "ops".split(','),
+            "openid groups",
+            None,
+            Some(&webhook),
+            Some(&mut tr),
+        )
+        .unwrap();
+        assert_eq!(mapped.roles, None);
+        // `ops`, `admin` and `billing` exceed the `groups_max`
+        assert_eq!(mapped.groups, None);
+        assert_eq!(mapped.groups_exceeded, Some(3));
+        assert_eq!(
+            mapped.custom_flattened,
+            Some(HashMap::from([
+                ("app_roles".to_string(), vec!["admin", "billing"].into()),
+                (CLAIM_GROUPS_OVERFLOW.to_string(), true.into()),
+                ("tenant".to_string(), "t1".into()),
+            ]))
+        );
+        assert_eq!(
+            tr,
+            vec![
+                trace(ClaimTarget::IdToken, "app_roles", ClaimSource::Roles),
+                trace(ClaimTarget::IdToken, "app_roles", ClaimSource::Webhook),
+                trace(
+                    ClaimTarget::IdToken,
+                    CLAIM_GROUPS_OVERFLOW,
+                    ClaimSource::GroupsOverflow
+                ),
+                trace(ClaimTarget::IdToken, "tenant", ClaimSource::Webhook),
+            ]
+        );
+    }
 }